@@ -0,0 +1,87 @@
+#!/usr/bin/env python3
+"""
+Maintenance result tests for the FSDB Python bindings
+
+Checks that OPTIMIZE, Z-ORDER and VACUUM return populated records.
+
+Running (after generating bindings, see bindings/python/README.md):
+  python3 -m unittest discover bindings/python/tests
+"""
+
+import json
+import shutil
+import tempfile
+import unittest
+from pathlib import Path
+
+from fsdb import DatabaseOps, Field, Schema
+
+
+def create_db(path, batches=3):
+    """Create a database with one small file per inserted batch"""
+    schema = Schema(
+        fields=[
+            Field(name="id", data_type="Int32", nullable=False),
+            Field(name="name", data_type="String", nullable=False),
+        ]
+    )
+    db = DatabaseOps.create(str(path), schema)
+    for batch in range(batches):
+        rows = [
+            {"id": batch * 10 + i, "name": f"user_{batch * 10 + i}"}
+            for i in range(10)
+        ]
+        db.insert_json(json.dumps(rows))
+    return db
+
+
+class MaintenanceResultTest(unittest.TestCase):
+    def setUp(self):
+        self.temp_dir = Path(tempfile.mkdtemp())
+
+    def tearDown(self):
+        shutil.rmtree(self.temp_dir, ignore_errors=True)
+
+    def test_optimize_metrics(self):
+        db = create_db(self.temp_dir / "optimize_db")
+
+        metrics = db.optimize()
+
+        self.assertEqual(metrics.num_files_removed, 3)
+        self.assertEqual(metrics.num_files_added, 1)
+        self.assertGreaterEqual(metrics.total_considered_files, 3)
+        self.assertEqual(metrics.partitions_optimized, 1)
+        self.assertGreater(metrics.bytes_added, 0)
+        self.assertGreater(metrics.bytes_removed, 0)
+
+    def test_zorder_metrics(self):
+        db = create_db(self.temp_dir / "zorder_db")
+
+        metrics = db.zorder(["id", "name"])
+
+        self.assertEqual(metrics.num_files_removed, 3)
+        self.assertGreater(metrics.num_files_added, 0)
+        self.assertGreater(metrics.bytes_added, 0)
+        self.assertGreater(metrics.bytes_removed, 0)
+
+    def test_vacuum_report(self):
+        db = create_db(self.temp_dir / "vacuum_db")
+        db.optimize()
+
+        # The three compacted files are no longer referenced
+        preview = db.vacuum_dry_run(0)
+        report = db.vacuum(0)
+
+        self.assertFalse(report.dry_run)
+        self.assertEqual(report.files_deleted, len(preview))
+        self.assertEqual(report.files_deleted, 3)
+        self.assertGreater(report.bytes_reclaimed, 0)
+
+        # Nothing left to reclaim on a second run
+        report = db.vacuum(0)
+        self.assertEqual(report.files_deleted, 0)
+        self.assertEqual(report.bytes_reclaimed, 0)
+
+
+if __name__ == "__main__":
+    unittest.main()
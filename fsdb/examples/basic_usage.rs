@@ -52,8 +52,8 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         ],
     )?;
 
-    let txn_id = db.insert(batch).await?;
-    println!("   Inserted 5 rows in transaction {}", txn_id);
+    let commit = db.insert(batch).await?;
+    println!("   Inserted 5 rows in version {}", commit.version);
 
     // 4. Query all data
     println!("\n4. Querying all data...");
@@ -132,8 +132,8 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         ],
     )?;
 
-    let txn_id2 = db.insert(batch2).await?;
-    println!("   Inserted 2 more rows in transaction {}", txn_id2);
+    let commit2 = db.insert(batch2).await?;
+    println!("   Inserted 2 more rows in version {}", commit2.version);
 
     // 8. Final count
     println!("\n8. Final row count...");
@@ -126,8 +126,11 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             .unwrap();
 
             match rt.block_on(db_writer.insert(batch)) {
-                Ok(txn_id) => {
-                    println!("   [Writer] Write {} completed (txn {})", write_num, txn_id);
+                Ok(commit) => {
+                    println!(
+                        "   [Writer] Write {} completed (version {})",
+                        write_num, commit.version
+                    );
                 }
                 Err(e) => {
                     eprintln!("   [Writer] Write {} failed: {}", write_num, e);
@@ -288,8 +288,11 @@ def main():
 
         print("\n1. OPTIMIZE (compact files)")
         try:
-            db.optimize()
-            print("   ✓ Optimize completed")
+            result = db.optimize()
+            print(
+                f"   ✓ Optimize completed: {result.num_files_removed} -> "
+                f"{result.num_files_added} files"
+            )
         except FsdbError as e:
             print(f"   ✗ Optimize failed: {e}")
 
@@ -306,8 +309,11 @@ def main():
 
         print("\n3. Z-ORDER (multi-dimensional clustering)")
         try:
-            db.zorder(["age", "salary"])
-            print("   ✓ Z-ORDER completed on columns: age, salary")
+            result = db.zorder(["age", "salary"])
+            print(
+                f"   ✓ Z-ORDER completed on columns: age, salary "
+                f"({result.num_files_added} files written)"
+            )
         except FsdbError as e:
             print(f"   ✗ Z-ORDER failed: {e}")
 
@@ -1,9 +1,17 @@
 //! RecordBatch buffering for reducing transaction overhead
 //!
 //! Accumulates RecordBatches and flushes them in larger transactions to Delta Lake.
+//!
+//! For a partitioned table, rows are grouped by the partition they belong
+//! to. A flush writes every partition's rows together, so each partition gets
+//! one file per flush however the rows arrived. A partition whose share of
+//! the buffer reaches `max_partition_rows` is flushed on its own, without
+//! waiting for the others.
 
-use arrow::array::RecordBatch;
+use arrow::array::{RecordBatch, UInt32Array};
 use arrow::datatypes::SchemaRef;
+use arrow::util::display::{ArrayFormatter, FormatOptions};
+use std::collections::BTreeMap;
 use std::sync::Arc;
 use tokio::sync::Mutex;
 use tracing::{debug, info};
@@ -13,22 +21,44 @@ use tracing::{debug, info};
 pub struct BatchBufferConfig {
     /// Maximum number of rows before auto-flush
     pub max_rows: usize,
+
+    /// Rows one partition may buffer before it is flushed on its own
+    /// (None waits for `max_rows`)
+    pub max_partition_rows: Option<usize>,
 }
 
 impl Default for BatchBufferConfig {
     fn default() -> Self {
-        Self { max_rows: 1000 }
+        Self {
+            max_rows: 1000,
+            max_partition_rows: None,
+        }
     }
 }
 
+/// Partition values of buffered rows; empty for an unpartitioned table
+type PartitionKey = Vec<Option<String>>;
+
 /// Batch buffer state
+#[derive(Default)]
 struct BufferState {
-    batches: Vec<RecordBatch>,
+    partitions: BTreeMap<PartitionKey, Vec<RecordBatch>>,
+}
+
+impl BufferState {
+    fn rows(batches: &[RecordBatch]) -> usize {
+        batches.iter().map(|b| b.num_rows()).sum()
+    }
+
+    fn total_rows(&self) -> usize {
+        self.partitions.values().map(|b| Self::rows(b)).sum()
+    }
 }
 
 /// RecordBatch buffer for batching small writes
 pub struct BatchBuffer {
     config: BatchBufferConfig,
+    partition_columns: Vec<String>,
     state: Arc<Mutex<BufferState>>,
 }
 
@@ -43,45 +73,159 @@ impl BatchBuffer {
         debug!("Creating batch buffer with max_rows: {}", config.max_rows);
         Self {
             config,
-            state: Arc::new(Mutex::new(BufferState {
-                batches: Vec::new(),
-            })),
+            partition_columns: Vec::new(),
+            state: Arc::new(Mutex::new(BufferState::default())),
         }
     }
 
+    /// Group buffered rows by the values of `columns`
+    pub fn with_partition_columns(mut self, columns: Vec<String>) -> Self {
+        self.partition_columns = columns;
+        self
+    }
+
+    /// Columns buffered rows are grouped by
+    pub fn partition_columns(&self) -> &[String] {
+        &self.partition_columns
+    }
+
     /// Add a batch to the buffer, returns true if auto-flush should occur
+    ///
+    /// Once it does, `take_flushable` returns the rows to flush.
     pub async fn push(&self, batch: RecordBatch) -> bool {
+        let groups = self.split_by_partition(batch);
         let mut state = self.state.lock().await;
-        state.batches.push(batch);
+        for (key, batch) in groups {
+            state.partitions.entry(key).or_default().push(batch);
+        }
 
-        let total_rows: usize = state.batches.iter().map(|b| b.num_rows()).sum();
+        let total_rows = state.total_rows();
         debug!(
-            "Buffer now has {} batches with {} total rows",
-            state.batches.len(),
+            "Buffer now has {} partitions with {} total rows",
+            state.partitions.len(),
             total_rows
         );
 
-        total_rows >= self.config.max_rows
+        total_rows >= self.config.max_rows || !self.full_partitions(&state).is_empty()
     }
 
     /// Take all batches from buffer and return them
+    ///
+    /// Batches come grouped by partition.
     pub async fn take_all(&self) -> Vec<RecordBatch> {
         let mut state = self.state.lock().await;
-        std::mem::take(&mut state.batches)
+        std::mem::take(&mut state.partitions)
+            .into_values()
+            .flatten()
+            .collect()
+    }
+
+    /// Copies of the buffered batches, left in the buffer
+    pub async fn snapshot(&self) -> Vec<RecordBatch> {
+        let state = self.state.lock().await;
+        state.partitions.values().flatten().cloned().collect()
+    }
+
+    /// Take the batches due to be flushed
+    ///
+    /// Past `max_rows` that's the whole buffer, every partition included, so
+    /// no partition's rows are left behind to become a smaller file later.
+    /// Otherwise it's the partitions that reached `max_partition_rows`.
+    pub async fn take_flushable(&self) -> Vec<RecordBatch> {
+        let mut state = self.state.lock().await;
+        if state.total_rows() >= self.config.max_rows {
+            return std::mem::take(&mut state.partitions)
+                .into_values()
+                .flatten()
+                .collect();
+        }
+        let full = self.full_partitions(&state);
+        full.into_iter()
+            .filter_map(|key| state.partitions.remove(&key))
+            .flatten()
+            .collect()
     }
 
     /// Check if buffer is empty
     pub async fn is_empty(&self) -> bool {
         let state = self.state.lock().await;
-        state.batches.is_empty()
+        state.partitions.is_empty()
     }
 
     /// Get current buffer stats (batch count, total rows)
     pub async fn stats(&self) -> (usize, usize) {
         let state = self.state.lock().await;
-        let batch_count = state.batches.len();
-        let total_rows = state.batches.iter().map(|b| b.num_rows()).sum();
-        (batch_count, total_rows)
+        let batch_count = state.partitions.values().map(Vec::len).sum();
+        (batch_count, state.total_rows())
+    }
+
+    /// Partitions holding at least `max_partition_rows` rows
+    fn full_partitions(&self, state: &BufferState) -> Vec<PartitionKey> {
+        let Some(limit) = self.config.max_partition_rows else {
+            return Vec::new();
+        };
+        state
+            .partitions
+            .iter()
+            .filter(|(_, batches)| BufferState::rows(batches) >= limit)
+            .map(|(key, _)| key.clone())
+            .collect()
+    }
+
+    /// Split `batch` into one batch per partition
+    ///
+    /// A batch missing a partition column is kept whole, under the key it
+    /// would have if the column were null.
+    fn split_by_partition(&self, batch: RecordBatch) -> Vec<(PartitionKey, RecordBatch)> {
+        if self.partition_columns.is_empty() {
+            return vec![(Vec::new(), batch)];
+        }
+        let options = FormatOptions::default();
+        let formatters: Vec<Option<_>> = self
+            .partition_columns
+            .iter()
+            .map(|name| {
+                batch
+                    .column_by_name(name)
+                    .and_then(|column| ArrayFormatter::try_new(column.as_ref(), &options).ok())
+            })
+            .collect();
+        if formatters.iter().any(Option::is_none) {
+            return vec![(vec![None; self.partition_columns.len()], batch)];
+        }
+
+        let mut rows: BTreeMap<PartitionKey, Vec<u32>> = BTreeMap::new();
+        for row in 0..batch.num_rows() {
+            let key = self
+                .partition_columns
+                .iter()
+                .zip(&formatters)
+                .map(|(name, formatter)| {
+                    let column = batch.column_by_name(name).expect("checked above");
+                    (!column.is_null(row)).then(|| {
+                        formatter
+                            .as_ref()
+                            .expect("checked above")
+                            .value(row)
+                            .to_string()
+                    })
+                })
+                .collect();
+            rows.entry(key).or_default().push(row as u32);
+        }
+
+        if rows.len() == 1 {
+            let key = rows.into_keys().next().expect("one partition");
+            return vec![(key, batch)];
+        }
+        rows.into_iter()
+            .map(|(key, indices)| {
+                let indices = UInt32Array::from(indices);
+                let part = arrow::compute::take_record_batch(&batch, &indices)
+                    .expect("indices are in bounds");
+                (key, part)
+            })
+            .collect()
     }
 
     /// Concatenate all batches into a single batch
@@ -155,7 +299,10 @@ mod tests {
     #[tokio::test]
     async fn test_batch_buffer_auto_flush_threshold() {
         let schema = create_test_schema();
-        let config = BatchBufferConfig { max_rows: 3 };
+        let config = BatchBufferConfig {
+            max_rows: 3,
+            ..Default::default()
+        };
         let buffer = BatchBuffer::with_config(config);
 
         // Add 2 batches (2 rows)
@@ -195,4 +342,52 @@ mod tests {
         assert_eq!(result.num_rows(), 3);
         assert_eq!(result.num_columns(), 2);
     }
+
+    fn create_regional_batch(schema: SchemaRef, ids: &[i32], regions: &[&str]) -> RecordBatch {
+        RecordBatch::try_new(
+            schema,
+            vec![
+                Arc::new(Int32Array::from(ids.to_vec())) as ArrayRef,
+                Arc::new(StringArray::from(regions.to_vec())) as ArrayRef,
+            ],
+        )
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_rows_grouped_by_partition() {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("id", DataType::Int32, false),
+            Field::new("region", DataType::Utf8, false),
+        ]));
+        let buffer = BatchBuffer::with_config(BatchBufferConfig {
+            max_rows: 100,
+            max_partition_rows: Some(3),
+        })
+        .with_partition_columns(vec!["region".to_string()]);
+
+        let mixed = create_regional_batch(schema.clone(), &[1, 2, 3], &["us", "eu", "us"]);
+        assert!(!buffer.push(mixed).await);
+        let eu = create_regional_batch(schema.clone(), &[4], &["eu"]);
+        assert!(!buffer.push(eu).await);
+        assert_eq!(buffer.stats().await, (3, 4));
+
+        // The third "us" row fills that partition, which flushes alone
+        let us = create_regional_batch(schema.clone(), &[5], &["us"]);
+        assert!(buffer.push(us).await);
+        let flushed = buffer.take_flushable().await;
+        let flushed_rows: usize = flushed.iter().map(|b| b.num_rows()).sum();
+        assert_eq!(flushed_rows, 3);
+        for batch in &flushed {
+            let regions = batch
+                .column(1)
+                .as_any()
+                .downcast_ref::<StringArray>()
+                .unwrap();
+            assert!(regions.iter().all(|r| r == Some("us")));
+        }
+
+        // The "eu" rows wait for the next flush
+        assert_eq!(buffer.stats().await.1, 2);
+    }
 }
@@ -1,13 +1,19 @@
 //! FSDB CLI - Mount database as POSIX filesystem via NFS server
 //!
 //! Usage:
-//!   fsdb mount <DB_PATH> <MOUNT_POINT> [--port PORT]
+//!   fsdb mount <DB_PATH> <MOUNT_POINT> [--port PORT] [--nfs4-port PORT] [--http-port PORT]
+//!   fsdb serve <DB_PATH> [--bind ADDR] [--port PORT] [--flight-port PORT]
 //!   fsdb unmount <MOUNT_POINT>
 //!   fsdb status <MOUNT_POINT>
+//!   fsdb export <DB_PATH> [--format csv|ndjson|parquet] [--output FILE]
 
 use clap::{Parser, Subcommand};
+use fsdb::export::{ExportFormat, ExportOptions};
+use fsdb::flight::{FlightSqlConfig, FlightSqlServer};
 use fsdb::nfs::NfsServer;
+use fsdb::rest::{RestConfig, RestServer};
 use fsdb::{error::Result, DatabaseOps};
+use std::net::{IpAddr, SocketAddr};
 use std::path::PathBuf;
 use std::sync::Arc;
 use tokio::signal;
@@ -37,6 +43,33 @@ enum Commands {
         /// NFS server port (default: 12049)
         #[arg(long, short = 'p', default_value = "12049")]
         port: u16,
+
+        /// Also serve NFSv4.0/4.1 on this port, and mount with it
+        #[arg(long)]
+        nfs4_port: Option<u16>,
+
+        /// Also serve the database over HTTP on this port
+        #[arg(long)]
+        http_port: Option<u16>,
+    },
+
+    /// Serve a database over HTTP (JSON REST API)
+    Serve {
+        /// Path to the database directory
+        #[arg(value_name = "DB_PATH")]
+        db_path: PathBuf,
+
+        /// Address to listen on (default: 127.0.0.1)
+        #[arg(long, default_value = "127.0.0.1")]
+        bind: IpAddr,
+
+        /// HTTP server port (default: 8080)
+        #[arg(long, short = 'p', default_value = "8080")]
+        port: u16,
+
+        /// Also serve Arrow Flight SQL on this port
+        #[arg(long)]
+        flight_port: Option<u16>,
     },
 
     /// Unmount a mounted database
@@ -53,6 +86,29 @@ enum Commands {
         mount_point: PathBuf,
     },
 
+    /// Export a table to stdout or a file
+    Export {
+        /// Path to the database directory
+        #[arg(value_name = "DB_PATH")]
+        db_path: PathBuf,
+
+        /// Output format: csv, ndjson or parquet (default: csv)
+        #[arg(long, short = 'f', default_value = "csv")]
+        format: ExportFormat,
+
+        /// Columns to export, comma separated (default: all)
+        #[arg(long, value_delimiter = ',')]
+        columns: Option<Vec<String>>,
+
+        /// SQL predicate rows must match
+        #[arg(long)]
+        filter: Option<String>,
+
+        /// Output file (default: stdout)
+        #[arg(long, short = 'o')]
+        output: Option<PathBuf>,
+    },
+
     /// Test S3/MinIO backend
     S3Test {
         /// S3 URI (e.g., s3://bucket/path)
@@ -84,6 +140,8 @@ async fn main() -> Result<()> {
             db_path,
             mount_point,
             port,
+            nfs4_port,
+            http_port,
         } => {
             // Open database
             eprintln!("Opening database: {}", db_path.display());
@@ -93,6 +151,34 @@ async fn main() -> Result<()> {
             // Start NFS server
             eprintln!("Starting NFS server on port {}", port);
             let server = NfsServer::new(db.clone(), port).await?;
+            let server = match nfs4_port {
+                Some(nfs4_port) => {
+                    eprintln!("Starting NFSv4 server on port {}", nfs4_port);
+                    server.with_nfs4(nfs4_port).await?
+                }
+                None => server,
+            };
+
+            // NFSv4 needs no separate mount protocol and does its own locking
+            #[cfg(target_os = "macos")]
+            let mount_options = match nfs4_port {
+                Some(nfs4_port) => format!("vers=4,tcp,port={}", nfs4_port),
+                None => format!("nolocks,vers=3,tcp,port={},mountport={}", port, port),
+            };
+            #[cfg(target_os = "linux")]
+            let mount_options = match nfs4_port {
+                Some(nfs4_port) => format!("vers=4.1,tcp,port={}", nfs4_port),
+                None => format!("nolocks,vers=3,tcp,port={},mountport={}", port, port),
+            };
+
+            // Start the HTTP server alongside it; it stops when dropped on exit
+            let _http_server = match http_port {
+                Some(http_port) => {
+                    eprintln!("Starting HTTP server on port {}", http_port);
+                    Some(RestServer::new(db.clone(), http_port).await?)
+                }
+                None => None,
+            };
 
             // Wait for server to be fully ready
             eprintln!("Waiting for NFS server to be ready...");
@@ -110,10 +196,7 @@ async fn main() -> Result<()> {
             let mount_status = std::process::Command::new("sudo")
                 .arg("mount_nfs")
                 .arg("-o")
-                .arg(format!(
-                    "nolocks,vers=3,tcp,port={},mountport={}",
-                    port, port
-                ))
+                .arg(&mount_options)
                 .arg("localhost:/")
                 .arg(mount_point.as_os_str())
                 .status();
@@ -124,10 +207,7 @@ async fn main() -> Result<()> {
                 .arg("-t")
                 .arg("nfs")
                 .arg("-o")
-                .arg(format!(
-                    "nolocks,vers=3,tcp,port={},mountport={}",
-                    port, port
-                ))
+                .arg(&mount_options)
                 .arg("localhost:/")
                 .arg(mount_point.as_os_str())
                 .status();
@@ -176,9 +256,17 @@ async fn main() -> Result<()> {
                         port
                     );
                     #[cfg(target_os = "macos")]
-                    eprintln!("  sudo mount_nfs -o nolocks,vers=3,tcp,port={},mountport={} localhost:/fsdb {}", port, port, mount_point.display());
+                    eprintln!(
+                        "  sudo mount_nfs -o {} localhost:/fsdb {}",
+                        mount_options,
+                        mount_point.display()
+                    );
                     #[cfg(target_os = "linux")]
-                    eprintln!("  sudo mount -t nfs -o nolocks,vers=3,tcp,port={},mountport={} localhost:/fsdb {}", port, port, mount_point.display());
+                    eprintln!(
+                        "  sudo mount -t nfs -o {} localhost:/fsdb {}",
+                        mount_options,
+                        mount_point.display()
+                    );
                     eprintln!();
                     eprintln!("Press Ctrl+C to shutdown server");
 
@@ -196,9 +284,17 @@ async fn main() -> Result<()> {
                         port
                     );
                     #[cfg(target_os = "macos")]
-                    eprintln!("  sudo mount_nfs -o nolocks,vers=3,tcp,port={},mountport={} localhost:/fsdb {}", port, port, mount_point.display());
+                    eprintln!(
+                        "  sudo mount_nfs -o {} localhost:/fsdb {}",
+                        mount_options,
+                        mount_point.display()
+                    );
                     #[cfg(target_os = "linux")]
-                    eprintln!("  sudo mount -t nfs -o nolocks,vers=3,tcp,port={},mountport={} localhost:/fsdb {}", port, port, mount_point.display());
+                    eprintln!(
+                        "  sudo mount -t nfs -o {} localhost:/fsdb {}",
+                        mount_options,
+                        mount_point.display()
+                    );
                     eprintln!();
                     eprintln!("Press Ctrl+C to shutdown server");
 
@@ -211,6 +307,51 @@ async fn main() -> Result<()> {
             }
         }
 
+        Commands::Serve {
+            db_path,
+            bind,
+            port,
+            flight_port,
+        } => {
+            // System access; with authentication enabled, requests log in as their user
+            eprintln!("Opening database: {}", db_path.display());
+            let db = Arc::new(DatabaseOps::open_with_credentials(&db_path, None).await?);
+
+            let config = RestConfig {
+                addr: SocketAddr::new(bind, port),
+                ..RestConfig::default()
+            };
+            let server = RestServer::with_config(db.clone(), config).await?;
+            eprintln!("Serving HTTP on http://{}", server.local_addr());
+            eprintln!("  curl http://{}/api/tables", server.local_addr());
+
+            let flight_server = match flight_port {
+                Some(flight_port) => {
+                    let config = FlightSqlConfig {
+                        addr: SocketAddr::new(bind, flight_port),
+                        ..FlightSqlConfig::default()
+                    };
+                    let flight_server = FlightSqlServer::with_config(db, config).await?;
+                    eprintln!(
+                        "Serving Flight SQL on grpc://{}",
+                        flight_server.local_addr()
+                    );
+                    Some(flight_server)
+                }
+                None => None,
+            };
+            eprintln!();
+            eprintln!("Press Ctrl+C to shutdown");
+
+            signal::ctrl_c().await?;
+            eprintln!("\nShutting down...");
+            server.shutdown().await?;
+            if let Some(flight_server) = flight_server {
+                flight_server.shutdown().await?;
+            }
+            Ok(())
+        }
+
         Commands::Unmount { mount_point } => {
             #[cfg(any(target_os = "macos", target_os = "linux"))]
             let status = std::process::Command::new("sudo")
@@ -249,6 +390,30 @@ async fn main() -> Result<()> {
             std::process::exit(1);
         }
 
+        Commands::Export {
+            db_path,
+            format,
+            columns,
+            filter,
+            output,
+        } => {
+            let db = DatabaseOps::open(&db_path).await?;
+            let options = ExportOptions {
+                columns,
+                filter,
+                ..ExportOptions::default()
+            };
+            let report = match output {
+                Some(path) => {
+                    let mut file = tokio::fs::File::create(&path).await?;
+                    db.export(&mut file, format, options).await?
+                }
+                None => db.export(&mut tokio::io::stdout(), format, options).await?,
+            };
+            eprintln!("Exported {} rows", report.rows);
+            Ok(())
+        }
+
         Commands::S3Test {
             s3_path,
             endpoint,
@@ -299,8 +464,8 @@ async fn main() -> Result<()> {
                 ],
             )?;
 
-            let txn_id = db.insert(batch).await?;
-            eprintln!("      ✓ Inserted 3 rows (transaction {})", txn_id);
+            let commit = db.insert(batch).await?;
+            eprintln!("      ✓ Inserted 3 rows (version {})", commit.version);
             eprintln!();
 
             // Query data
@@ -0,0 +1,530 @@
+//! Streaming bulk load of CSV and Parquet files
+//!
+//! Rows are read from disk in chunks, coerced to the table schema and staged
+//! in a `BatchBuffer` that commits to Delta Lake every `commit_rows` rows.
+//! `DatabaseOps` restores the pre-load version if the load fails.
+
+use crate::batch_buffer::{BatchBuffer, BatchBufferConfig};
+use crate::database_ops::DatabaseOps;
+use crate::nfs::coercion::{coerce_records, header_positions, CoercionMode, CoercionPolicy};
+use crate::storage::spill::SpillReservation;
+use crate::{Error, Result};
+use arrow::array::{new_null_array, RecordBatch};
+use arrow::compute::{can_cast_types, cast_with_options, CastOptions};
+use arrow::datatypes::{Schema, SchemaRef};
+use arrow::util::display::{ArrayFormatter, FormatOptions};
+use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tracing::{debug, info, warn};
+
+/// Callback invoked as a load makes progress
+pub type ProgressCallback = Arc<dyn Fn(&LoadProgress) + Send + Sync>;
+
+/// Snapshot of a running load, passed to the progress callback
+#[derive(Debug, Clone, Default)]
+pub struct LoadProgress {
+    /// Rows read from the source so far
+    pub rows_read: u64,
+
+    /// Rows committed to the table so far
+    pub rows_loaded: u64,
+
+    /// Rows written to the reject file so far
+    pub rows_rejected: u64,
+
+    /// Delta commits made so far
+    pub commits: u64,
+
+    /// Total rows in the source, when known up front (Parquet only)
+    pub total_rows: Option<u64>,
+}
+
+/// Summary of a completed load
+#[derive(Debug, Clone, Default)]
+pub struct LoadReport {
+    /// Rows committed to the table
+    pub rows_loaded: u64,
+
+    /// Rows written to the reject file
+    pub rows_rejected: u64,
+
+    /// Delta commits made
+    pub commits: u64,
+
+    /// Reject file, if any rows were rejected
+    pub reject_file: Option<PathBuf>,
+}
+
+/// Options for `DatabaseOps::load_from_csv` and `load_from_parquet_with_options`
+#[derive(Clone)]
+pub struct LoadOptions {
+    /// CSV source has a header row (columns are then matched by name)
+    pub has_header: bool,
+
+    /// CSV field delimiter
+    pub delimiter: u8,
+
+    /// How source values are coerced to the table's column types
+    pub coercion: CoercionPolicy,
+
+    /// Rows read from the source per chunk
+    pub read_batch_rows: usize,
+
+    /// Rows staged before each Delta commit
+    pub commit_rows: usize,
+
+    /// Write bad rows to a reject file instead of failing the load
+    pub continue_on_error: bool,
+
+    /// Reject file path; defaults to `<source file name>.rejects.csv` in the
+    /// database's spill directory
+    pub reject_path: Option<PathBuf>,
+
+    /// Called after every commit and once when the load finishes
+    pub progress: Option<ProgressCallback>,
+}
+
+impl Default for LoadOptions {
+    fn default() -> Self {
+        Self {
+            has_header: true,
+            delimiter: b',',
+            coercion: CoercionPolicy::default(),
+            read_batch_rows: 8192,
+            commit_rows: 100_000,
+            continue_on_error: false,
+            reject_path: None,
+            progress: None,
+        }
+    }
+}
+
+impl std::fmt::Debug for LoadOptions {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("LoadOptions")
+            .field("has_header", &self.has_header)
+            .field("delimiter", &(self.delimiter as char))
+            .field("coercion", &self.coercion)
+            .field("read_batch_rows", &self.read_batch_rows)
+            .field("commit_rows", &self.commit_rows)
+            .field("continue_on_error", &self.continue_on_error)
+            .field("reject_path", &self.reject_path)
+            .field("progress", &self.progress.is_some())
+            .finish()
+    }
+}
+
+impl LoadOptions {
+    /// Use a custom coercion policy
+    pub fn with_coercion(mut self, policy: CoercionPolicy) -> Self {
+        self.coercion = policy;
+        self
+    }
+
+    /// Commit every `rows` rows
+    pub fn with_commit_rows(mut self, rows: usize) -> Self {
+        self.commit_rows = rows;
+        self
+    }
+
+    /// Collect bad rows into `reject_path` instead of failing the load
+    pub fn with_continue_on_error(mut self, reject_path: impl Into<PathBuf>) -> Self {
+        self.continue_on_error = true;
+        self.reject_path = Some(reject_path.into());
+        self
+    }
+
+    /// Report progress through `callback`
+    pub fn with_progress(
+        mut self,
+        callback: impl Fn(&LoadProgress) + Send + Sync + 'static,
+    ) -> Self {
+        self.progress = Some(Arc::new(callback));
+        self
+    }
+}
+
+/// Stream a CSV file into the table
+pub(crate) async fn load_csv(
+    db: &DatabaseOps,
+    path: &Path,
+    options: &LoadOptions,
+) -> Result<LoadReport> {
+    let schema = db.schema();
+    let mut reader = csv::ReaderBuilder::new()
+        .has_headers(options.has_header)
+        .delimiter(options.delimiter)
+        .flexible(true)
+        .from_reader(crate::storage::compression::open(path)?);
+
+    // Validate the source columns before anything is committed
+    let (positions, header) = if options.has_header {
+        let headers = reader
+            .headers()
+            .map_err(|e| csv_error(e, "Invalid CSV header"))?
+            .clone();
+        let positions = header_positions(&headers, &schema)?;
+        for (field, position) in schema.fields().iter().zip(&positions) {
+            if position.is_none() && !field.is_nullable() {
+                return Err(Error::InvalidOperation(format!(
+                    "Non-nullable column '{}' is missing from {}",
+                    field.name(),
+                    path.display()
+                )));
+            }
+        }
+        (positions, headers.iter().map(|h| h.to_string()).collect())
+    } else {
+        let positions = (0..schema.fields().len()).map(Some).collect();
+        let header = schema.fields().iter().map(|f| f.name().clone()).collect();
+        (positions, header)
+    };
+
+    let chunk_rows = chunk_rows(options);
+    let mut loader = Loader::new(db, schema, path, options, header);
+    let mut chunk = Vec::with_capacity(chunk_rows);
+    let mut record = csv::StringRecord::new();
+    loop {
+        let more = reader
+            .read_record(&mut record)
+            .map_err(|e| csv_error(e, "Invalid CSV"))?;
+        if more {
+            chunk.push(record.clone());
+        }
+        if chunk.len() >= chunk_rows || (!more && !chunk.is_empty()) {
+            loader.load_csv_chunk(&chunk, &positions).await?;
+            chunk.clear();
+        }
+        if !more {
+            break;
+        }
+    }
+
+    loader.finish().await
+}
+
+/// Stream a Parquet file into the table
+pub(crate) async fn load_parquet(
+    db: &DatabaseOps,
+    path: &Path,
+    options: &LoadOptions,
+) -> Result<LoadReport> {
+    let schema = db.schema();
+    let builder = ParquetRecordBatchReaderBuilder::try_new(File::open(path)?)?;
+    validate_schema(builder.schema(), &schema, path)?;
+
+    let header = builder
+        .schema()
+        .fields()
+        .iter()
+        .map(|f| f.name().clone())
+        .collect();
+    let total_rows = builder.metadata().file_metadata().num_rows().max(0) as u64;
+    let reader = builder.with_batch_size(chunk_rows(options)).build()?;
+
+    let mut loader = Loader::new(db, schema, path, options, header);
+    loader.progress.total_rows = Some(total_rows);
+    for batch in reader {
+        loader.load_arrow_batch(&batch?).await?;
+    }
+
+    loader.finish().await
+}
+
+/// Error for a failed CSV read, which for a compressed source may be the
+/// decompressor's
+fn csv_error(e: csv::Error, context: &str) -> Error {
+    if let csv::ErrorKind::Io(io) = e.kind() {
+        if let Some(err) = crate::storage::compression::decompression_error(io) {
+            return err;
+        }
+    }
+    Error::InvalidOperation(format!("{}: {}", context, e))
+}
+
+/// Rows per read, capped so each commit stays close to `commit_rows`
+fn chunk_rows(options: &LoadOptions) -> usize {
+    options.read_batch_rows.min(options.commit_rows).max(1)
+}
+
+/// Check that every source column fits the table and no required column is missing
+fn validate_schema(source: &Schema, target: &SchemaRef, path: &Path) -> Result<()> {
+    for field in source.fields() {
+        let Ok(target_field) = target.field_with_name(field.name()) else {
+            return Err(Error::InvalidOperation(format!(
+                "Column '{}' in {} is not in the table schema",
+                field.name(),
+                path.display()
+            )));
+        };
+        if !can_cast_types(field.data_type(), target_field.data_type()) {
+            return Err(Error::InvalidOperation(format!(
+                "Column '{}' of type {} cannot be loaded as {}",
+                field.name(),
+                field.data_type(),
+                target_field.data_type()
+            )));
+        }
+    }
+    for field in target.fields() {
+        if !field.is_nullable() && source.field_with_name(field.name()).is_err() {
+            return Err(Error::InvalidOperation(format!(
+                "Non-nullable column '{}' is missing from {}",
+                field.name(),
+                path.display()
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// Cast a source batch to the table schema, filling absent columns with NULL
+fn conform_batch(
+    batch: &RecordBatch,
+    schema: &SchemaRef,
+    policy: &CoercionPolicy,
+) -> Result<RecordBatch> {
+    let options = CastOptions {
+        safe: policy.mode == CoercionMode::Lenient,
+        ..Default::default()
+    };
+    let columns = schema
+        .fields()
+        .iter()
+        .map(|field| match batch.column_by_name(field.name()) {
+            Some(column) => Ok(cast_with_options(column, field.data_type(), &options)?),
+            None => Ok(new_null_array(field.data_type(), batch.num_rows())),
+        })
+        .collect::<Result<Vec<_>>>()?;
+    Ok(RecordBatch::try_new(schema.clone(), columns)?)
+}
+
+/// Per-load state: the staging buffer, progress counters and reject file
+struct Loader<'a> {
+    db: &'a DatabaseOps,
+    schema: SchemaRef,
+    options: &'a LoadOptions,
+    buffer: BatchBuffer,
+    progress: LoadProgress,
+    reject_path: PathBuf,
+    reject_header: Vec<String>,
+    rejects: Option<csv::Writer<File>>,
+    /// Spill space taken by the reject file while the load runs
+    reject_space: Option<SpillReservation>,
+}
+
+impl<'a> Loader<'a> {
+    fn new(
+        db: &'a DatabaseOps,
+        schema: SchemaRef,
+        source: &Path,
+        options: &'a LoadOptions,
+        reject_header: Vec<String>,
+    ) -> Self {
+        let reject_path = options.reject_path.clone().unwrap_or_else(|| {
+            let mut name = source.file_name().unwrap_or_default().to_os_string();
+            name.push(".rejects.csv");
+            db.spill().temp_dir().join(name)
+        });
+        Self {
+            db,
+            schema,
+            options,
+            buffer: BatchBuffer::with_config(BatchBufferConfig {
+                max_rows: options.commit_rows.max(1),
+                ..Default::default()
+            }),
+            progress: LoadProgress::default(),
+            reject_path,
+            reject_header,
+            rejects: None,
+            reject_space: None,
+        }
+    }
+
+    /// Coerce a chunk of CSV records, falling back to row-by-row on error
+    async fn load_csv_chunk(
+        &mut self,
+        records: &[csv::StringRecord],
+        positions: &[Option<usize>],
+    ) -> Result<()> {
+        let first_row = self.progress.rows_read;
+        self.progress.rows_read += records.len() as u64;
+
+        match coerce_records(records, positions, &self.schema, &self.options.coercion) {
+            Ok(batch) => self.stage(batch).await,
+            Err(e) if !self.options.continue_on_error => Err(offset_row(e, first_row)),
+            Err(_) => {
+                let mut good = Vec::with_capacity(records.len());
+                for record in records {
+                    match coerce_records(
+                        std::slice::from_ref(record),
+                        positions,
+                        &self.schema,
+                        &self.options.coercion,
+                    ) {
+                        Ok(_) => good.push(record.clone()),
+                        Err(e) => {
+                            self.reject(record.iter().map(|c| c.to_string()).collect(), &e)?
+                        }
+                    }
+                }
+                let batch = coerce_records(&good, positions, &self.schema, &self.options.coercion)?;
+                self.stage(batch).await
+            }
+        }
+    }
+
+    /// Conform an Arrow batch, falling back to row-by-row on error
+    async fn load_arrow_batch(&mut self, batch: &RecordBatch) -> Result<()> {
+        self.progress.rows_read += batch.num_rows() as u64;
+
+        match conform_batch(batch, &self.schema, &self.options.coercion) {
+            Ok(conformed) => self.stage(conformed).await,
+            Err(e) if !self.options.continue_on_error => Err(e),
+            Err(_) => {
+                let mut good = Vec::new();
+                for row in 0..batch.num_rows() {
+                    let slice = batch.slice(row, 1);
+                    match conform_batch(&slice, &self.schema, &self.options.coercion) {
+                        Ok(conformed) => good.push(conformed),
+                        Err(e) => self.reject(format_row(&slice)?, &e)?,
+                    }
+                }
+                if good.is_empty() {
+                    return Ok(());
+                }
+                let batch = BatchBuffer::concatenate_batches(&self.schema, good)?;
+                self.stage(batch).await
+            }
+        }
+    }
+
+    /// Stage a conformed batch, committing once the buffer is full
+    async fn stage(&mut self, batch: RecordBatch) -> Result<()> {
+        if batch.num_rows() == 0 {
+            return Ok(());
+        }
+        if self.buffer.push(batch).await {
+            self.commit().await?;
+        }
+        Ok(())
+    }
+
+    /// Commit everything staged so far in one Delta transaction
+    async fn commit(&mut self) -> Result<()> {
+        let batches = self.buffer.take_all().await;
+        if batches.is_empty() {
+            return Ok(());
+        }
+        let batch = BatchBuffer::concatenate_batches(&self.schema, batches)?;
+        let rows = batch.num_rows() as u64;
+        self.db.insert(batch).await?;
+
+        self.progress.rows_loaded += rows;
+        self.progress.commits += 1;
+        debug!(
+            "Bulk load committed {} rows ({} total)",
+            rows, self.progress.rows_loaded
+        );
+        self.report_progress();
+        Ok(())
+    }
+
+    /// Append a rejected row and its error to the reject file
+    fn reject(&mut self, mut fields: Vec<String>, error: &Error) -> Result<()> {
+        let reject_error =
+            |e: csv::Error| Error::InvalidOperation(format!("Failed to write reject file: {}", e));
+
+        fields.push(error.to_string());
+        // Roughly the record's size in the file, so the spill cap stops a runaway reject file
+        let record_bytes = fields.iter().map(|f| f.len() as u64 + 1).sum::<u64>();
+        match &mut self.reject_space {
+            Some(space) => space.grow(record_bytes)?,
+            None => self.reject_space = Some(self.db.spill().reserve(record_bytes)?),
+        }
+
+        if self.rejects.is_none() {
+            if let Some(dir) = self.reject_path.parent() {
+                std::fs::create_dir_all(dir)?;
+            }
+            let mut writer = csv::Writer::from_path(&self.reject_path).map_err(reject_error)?;
+            let mut header = self.reject_header.clone();
+            header.push("error".to_string());
+            writer.write_record(&header).map_err(reject_error)?;
+            self.rejects = Some(writer);
+        }
+
+        let writer = self.rejects.as_mut().unwrap();
+        writer.write_record(&fields).map_err(reject_error)?;
+        self.progress.rows_rejected += 1;
+        Ok(())
+    }
+
+    fn report_progress(&self) {
+        if let Some(callback) = &self.options.progress {
+            callback(&self.progress);
+        }
+    }
+
+    /// Commit the remainder and close the reject file
+    async fn finish(mut self) -> Result<LoadReport> {
+        self.commit().await?;
+
+        let reject_file = match self.rejects.take() {
+            Some(mut writer) => {
+                writer.flush()?;
+                warn!(
+                    "Bulk load rejected {} rows, see {}",
+                    self.progress.rows_rejected,
+                    self.reject_path.display()
+                );
+                Some(self.reject_path.clone())
+            }
+            None => None,
+        };
+        self.report_progress();
+
+        info!(
+            "Bulk load finished: {} rows in {} commits",
+            self.progress.rows_loaded, self.progress.commits
+        );
+        Ok(LoadReport {
+            rows_loaded: self.progress.rows_loaded,
+            rows_rejected: self.progress.rows_rejected,
+            commits: self.progress.commits,
+            reject_file,
+        })
+    }
+}
+
+/// Make a chunk-relative coercion row number relative to the whole file
+fn offset_row(error: Error, offset: u64) -> Error {
+    match error {
+        Error::TypeCoercion {
+            row,
+            column,
+            message,
+        } => Error::TypeCoercion {
+            row: row + offset as usize,
+            column,
+            message,
+        },
+        other => other,
+    }
+}
+
+/// Render a single-row batch as text for the reject file
+fn format_row(row: &RecordBatch) -> Result<Vec<String>> {
+    let options = FormatOptions::default();
+    row.columns()
+        .iter()
+        .map(|column| -> Result<String> {
+            Ok(ArrayFormatter::try_new(column.as_ref(), &options)?
+                .value(0)
+                .to_string())
+        })
+        .collect()
+}
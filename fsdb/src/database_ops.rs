@@ -2,20 +2,24 @@
 //!
 //! Delta Lake native implementation using deltalake-rs
 
-use crate::metadata::{BackupMetadata, BackupVerificationReport};
-use crate::query::QueryExecutor;
+use crate::metadata::{BackupMetadata, BackupVerificationReport, SnapshotManifest};
+use crate::query::{
+    Collation, CursorConfig, CursorId, CursorInfo, IdentifierCase, QueryExecutor, ResultSet,
+};
 // Removed: extract_predicates, is_value_less_than, is_value_greater_than - moved to query::pruning module
 use crate::delta_lake::stats::{get_column_statistics_from_delta, ColumnStats};
-use crate::storage::parquet::ParquetReader;
+use crate::delta_lake::CommitResult;
+use crate::security::CredentialStore;
+use crate::storage::parquet::{MissingColumn, ParquetReader};
 use crate::{Error, Result};
 use arrow::array::{Array, RecordBatch};
 use arrow::datatypes::{Schema, SchemaRef};
 use std::collections::HashMap;
 use std::path::{Path, PathBuf};
-use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 use std::sync::Arc;
 use std::time::Instant;
-use tracing::info;
+use tracing::{info, instrument, warn};
 
 /// Query pruning statistics
 #[derive(Debug, Clone)]
@@ -33,6 +37,9 @@ pub struct DataSkippingStats {
     pub files_skipped: usize,
     pub bytes_scanned: u64,
     pub bytes_skipped: u64,
+    /// Fraction of rows the last query's predicates were estimated to keep,
+    /// when the table has histograms enabled
+    pub estimated_selectivity: Option<f64>,
 }
 
 /// Real-time database metrics for monitoring and observability
@@ -58,8 +65,61 @@ pub struct HealthStatus {
     pub total_size_bytes: u64,
 }
 
+/// Size of a table, from the Delta log rather than its data
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct TableSummary {
+    /// Committed rows; rows still in the write buffer aren't counted
+    pub num_rows: u64,
+    pub num_files: usize,
+    /// Size of the live data files
+    pub total_bytes: u64,
+    /// `None` for a table with no commits yet
+    pub latest_version: Option<u64>,
+    /// Files with no row count in the log, whose Parquet footers were read
+    /// to count their rows
+    pub files_without_stats: usize,
+}
+
+/// Result of a `_returning` mutation
+///
+/// The affected rows are not materialized: page through them with
+/// `DatabaseOps::fetch` and close the cursor when done.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Returning {
+    /// Number of rows deleted or updated
+    pub count: usize,
+    /// Cursor over the affected rows
+    pub cursor: CursorId,
+    /// Table version after the statement, unchanged if no rows matched
+    pub version: i64,
+}
+
+/// What `DatabaseOps::update_if_with` does when its predicate matches
+/// more than one row
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MultipleMatches {
+    /// Compare and update every matching row, all or none
+    #[default]
+    ApplyAll,
+    /// Fail with `Error::InvalidOperation`, updating nothing
+    Error,
+}
+
+/// Attempts `update_if` makes before giving up on conflicting writers
+pub const CONDITIONAL_UPDATE_ATTEMPTS: u32 = 5;
+
 // Re-export Transaction from its own module
-pub use crate::transaction::Transaction;
+pub use crate::transaction::{Transaction, TransactionInfo};
+
+/// What the background maintenance task carries from one check to the next
+struct MaintenanceState {
+    /// Latest table version seen, and when it was first seen
+    seen_version: Option<u64>,
+    seen_at: Instant,
+
+    /// When the task started or last vacuumed
+    vacuumed_at: std::time::SystemTime,
+}
 
 /// Internal metrics tracker with atomic counters for thread-safe updates
 struct MetricsTracker {
@@ -79,7 +139,7 @@ pub struct DatabaseOps {
 
     /// S3 configuration (if using S3 backend)
     s3_url: Option<String>,
-    s3_storage_options: Option<std::collections::HashMap<String, String>>,
+    s3_config: Option<crate::storage::s3::S3Config>,
 
     /// Query executor
     #[allow(dead_code)]
@@ -97,8 +157,8 @@ pub struct DatabaseOps {
     /// Authentication context (None = auth disabled)
     auth_context: Option<Arc<crate::security::AuthContext>>,
 
-    /// User store (for authentication)
-    user_store: Option<Arc<tokio::sync::Mutex<crate::security::UserStore>>>,
+    /// Where users are looked up and saved (for authentication)
+    credential_store: Option<Arc<crate::security::CachedCredentialStore>>,
 
     /// Audit logger
     audit_logger: Option<Arc<crate::security::AuditLogger>>,
@@ -108,6 +168,88 @@ pub struct DatabaseOps {
 
     /// Batch buffer for reducing transaction overhead
     batch_buffer: Arc<crate::batch_buffer::BatchBuffer>,
+
+    /// Server-side cursors for paginated queries
+    cursors: Arc<crate::query::CursorRegistry>,
+
+    /// Number of recent versions VACUUM keeps readable for time travel
+    vacuum_keep_versions: usize,
+
+    /// Shortest retention VACUUM accepts without `force`
+    vacuum_min_retention: std::time::Duration,
+
+    /// How OPTIMIZE, VACUUM and Z-ORDER runs on the table are serialized
+    maintenance_lock: crate::delta_lake::MaintenanceLockConfig,
+
+    /// How long commits stay in `_delta_log` once checkpointed
+    log_retention: crate::delta_lake::LogRetentionConfig,
+
+    /// How unquoted identifiers in SQL are matched to tables and columns
+    pub(crate) identifier_case: IdentifierCase,
+
+    /// How strings are compared, ordered and grouped in queries
+    collation: Collation,
+
+    /// Resource locks taken by explicit transactions
+    pub(crate) lock_manager: Arc<crate::lock_manager::LockManager>,
+
+    /// Retries for transient storage errors on table reads and Delta commits
+    retry_policy: crate::storage::retry::RetryPolicy,
+
+    /// What to mask in SQL written to logs and audit details
+    log_redaction: crate::security::LogRedaction,
+
+    /// Small-file compaction after writes (None = disabled)
+    auto_compaction: Option<crate::delta_lake::AutoCompaction>,
+
+    /// Set while an automatic compaction runs, so concurrent writes don't start another
+    auto_compacting: AtomicBool,
+
+    /// Result of the last automatic compaction
+    last_auto_compaction: tokio::sync::Mutex<Option<crate::delta_lake::OptimizeMetrics>>,
+
+    /// Background maintenance task, while one is started
+    maintenance_scheduler:
+        tokio::sync::Mutex<Option<crate::delta_lake::scheduler::SchedulerHandle>>,
+
+    /// What the background maintenance task has done
+    maintenance_status: Arc<std::sync::Mutex<crate::delta_lake::MaintenanceStatus>>,
+
+    /// Explicit transactions begun and not yet committed, rolled back,
+    /// aborted or dropped, by ID
+    pub(crate) open_transactions:
+        std::sync::Mutex<HashMap<u64, Arc<crate::transaction::TransactionEntry>>>,
+
+    /// Scratch directory and cap shared by everything that spills to disk
+    spill: Arc<crate::storage::spill::SpillManager>,
+
+    /// Memory pool size for each query, in bytes (None = unlimited)
+    query_memory_limit: Option<usize>,
+
+    /// Data files a query reads at once (None = one per CPU)
+    scan_concurrency: Option<usize>,
+
+    /// Template for the names of files inserts write (None = delta-rs names)
+    file_naming: Option<crate::delta_lake::FileNaming>,
+
+    /// Which statistics inserts record in the Delta log (None = all)
+    stats_config: Option<crate::delta_lake::StatsConfig>,
+
+    /// Master keys for encrypted columns (None = none can be read or written)
+    column_keys: Option<Arc<crate::security::ColumnKeys>>,
+
+    /// Size inserts split their files to and compaction merges toward, in
+    /// bytes (None = one file per insert, and OPTIMIZE's default)
+    target_file_size: Option<u64>,
+
+    /// What the recovery pass at open cleaned up
+    recovery_report: crate::delta_lake::RecoveryReport,
+
+    /// How long idempotency keys are remembered
+    idempotency_window: std::time::Duration,
+
+    /// Largest rows and values writes may contain
+    size_limits: crate::storage::limits::SizeLimits,
 }
 
 impl MetricsTracker {
@@ -146,6 +288,18 @@ impl DatabaseOps {
     pub async fn create_with_delta_native<P: AsRef<Path>>(
         path: P,
         schema: SchemaRef,
+    ) -> Result<Self> {
+        Self::create_partitioned(path, schema, &[]).await
+    }
+
+    /// Create a new database partitioned by `partition_columns`
+    ///
+    /// Rows with the same values in the partition columns are stored together,
+    /// under `column=value/` directories, in the order the columns are given.
+    pub async fn create_partitioned<P: AsRef<Path>>(
+        path: P,
+        schema: SchemaRef,
+        partition_columns: &[&str],
     ) -> Result<Self> {
         let base_path = path.as_ref().to_path_buf();
         info!(
@@ -153,6 +307,26 @@ impl DatabaseOps {
             base_path.display()
         );
 
+        for column in partition_columns {
+            if schema.field_with_name(column).is_err() {
+                return Err(Error::InvalidOperation(format!(
+                    "Unknown partition column: {}",
+                    column
+                )));
+            }
+        }
+        if partition_columns.len() == schema.fields().len() {
+            return Err(Error::InvalidOperation(
+                "At least one column must not be a partition column".to_string(),
+            ));
+        }
+
+        // An s3:// path connects with the settings in the environment
+        if let Some(s3_path) = base_path.to_str().filter(|p| p.starts_with("s3://")) {
+            let config = crate::storage::s3::S3Config::from_env();
+            return Self::create_s3(s3_path, schema, partition_columns, config).await;
+        }
+
         // Create base directory
         std::fs::create_dir_all(&base_path)?;
 
@@ -163,15 +337,11 @@ impl DatabaseOps {
             use url::Url;
 
             // Convert Arrow schema to Delta Lake schema
-            let mut delta_fields = Vec::new();
-            for field in schema.fields() {
-                let delta_type = Self::arrow_to_delta_type(field.data_type())?;
-                delta_fields.push(StructField::new(
-                    field.name().clone(),
-                    delta_type,
-                    field.is_nullable(),
-                ));
-            }
+            let delta_fields: Vec<StructField> = schema
+                .fields()
+                .iter()
+                .map(|field| Self::arrow_to_delta_field(field))
+                .collect::<Result<_>>()?;
             let delta_schema = StructType::try_new(delta_fields)
                 .map_err(|e| Error::Other(format!("Failed to create Delta schema: {}", e)))?;
 
@@ -186,6 +356,7 @@ impl DatabaseOps {
             let table = ops
                 .create()
                 .with_columns(delta_schema.fields().cloned())
+                .with_partition_columns(partition_columns.iter().copied())
                 .await
                 .map_err(Error::DeltaTable)?;
 
@@ -194,30 +365,79 @@ impl DatabaseOps {
 
         let query_executor = Arc::new(QueryExecutor::new());
         let metrics = Arc::new(MetricsTracker::new());
-        let batch_buffer = Arc::new(crate::batch_buffer::BatchBuffer::new(schema.clone()));
+        let batch_buffer = Arc::new(
+            crate::batch_buffer::BatchBuffer::new(schema.clone())
+                .with_partition_columns(partition_columns.iter().map(|c| c.to_string()).collect()),
+        );
 
         Ok(Self {
             base_path,
             s3_url: None,
-            s3_storage_options: None,
+            s3_config: None,
             query_executor,
             schema,
             metrics,
             data_skipping_stats: Arc::new(tokio::sync::Mutex::new(DataSkippingStats::default())),
             auth_context: None,
-            user_store: None,
+            credential_store: None,
             audit_logger: None,
             role_manager: None,
             batch_buffer,
+            cursors: Arc::new(crate::query::CursorRegistry::new()),
+            vacuum_keep_versions: 0,
+            vacuum_min_retention: std::time::Duration::ZERO,
+            maintenance_lock: Default::default(),
+            log_retention: crate::delta_lake::LogRetentionConfig::default(),
+            identifier_case: IdentifierCase::default(),
+            collation: Collation::default(),
+            lock_manager: Arc::new(crate::lock_manager::LockManager::default()),
+            retry_policy: crate::storage::retry::RetryPolicy::default(),
+            log_redaction: crate::security::LogRedaction::default(),
+            auto_compaction: None,
+            auto_compacting: AtomicBool::new(false),
+            last_auto_compaction: tokio::sync::Mutex::new(None),
+            maintenance_scheduler: tokio::sync::Mutex::new(None),
+            maintenance_status: Default::default(),
+            open_transactions: std::sync::Mutex::new(HashMap::new()),
+            spill: Arc::default(),
+            query_memory_limit: None,
+            scan_concurrency: None,
+            file_naming: None,
+            stats_config: None,
+            column_keys: None,
+            target_file_size: None,
+            recovery_report: Default::default(),
+            idempotency_window: crate::metadata::DEFAULT_IDEMPOTENCY_WINDOW,
+            size_limits: crate::storage::limits::SizeLimits::default(),
         })
     }
 
     /// Open an existing Delta Lake table with FSDB
     /// Allows FSDB to read and write to existing Delta Lake tables
+    ///
+    /// Writes a crash interrupted are cleaned up first, with the default
+    /// `RecoveryConfig`.
     pub async fn open_delta_native<P: AsRef<Path>>(path: P) -> Result<Self> {
+        Self::open_with_recovery(path, crate::delta_lake::RecoveryConfig::default()).await
+    }
+
+    /// Open an existing table after cleaning up interrupted writes per
+    /// `recovery`, such as to also remove orphaned data files
+    ///
+    /// What was cleaned up is available from `recovery_report`.
+    pub async fn open_with_recovery<P: AsRef<Path>>(
+        path: P,
+        recovery: crate::delta_lake::RecoveryConfig,
+    ) -> Result<Self> {
         let base_path = path.as_ref().to_path_buf();
+        if let Some(s3_path) = base_path.to_str().filter(|p| p.starts_with("s3://")) {
+            let config = crate::storage::s3::S3Config::from_env();
+            return Self::open_with_s3_config(s3_path, config).await;
+        }
         info!("Opening Delta Lake table at: {}", base_path.display());
 
+        let recovery_report = crate::delta_lake::recover(&base_path, &recovery)?;
+
         // Open Delta Lake table
         let (_delta_table, schema) = {
             use deltalake::open_table;
@@ -226,48 +446,66 @@ impl DatabaseOps {
             let table_url = Url::from_directory_path(&base_path)
                 .map_err(|_| Error::Other("Invalid path for Delta table".to_string()))?;
 
+            // Tables written by other engines may need features FSDB can't read
+            crate::delta_lake::read_table_config(&base_path)?.check_readable()?;
+
             let table = open_table(table_url).await.map_err(Error::DeltaTable)?;
 
             // Convert Delta schema to Arrow schema
             let snapshot = table.snapshot().map_err(Error::DeltaTable)?;
 
-            let delta_schema = snapshot.schema();
-
-            // Convert Delta StructType to Arrow Schema
-            let arrow_fields: Result<Vec<_>> = delta_schema
-                .fields()
-                .map(|field| {
-                    let arrow_type = Self::delta_to_arrow_type(field.data_type())?;
-                    Ok(arrow::datatypes::Field::new(
-                        field.name(),
-                        arrow_type,
-                        field.is_nullable(),
-                    ))
-                })
-                .collect();
-
-            let arrow_schema = Arc::new(arrow::datatypes::Schema::new(arrow_fields?));
+            let arrow_schema = Self::delta_to_arrow_schema(&snapshot.schema())?;
 
             (table, arrow_schema)
         };
 
         let query_executor = Arc::new(QueryExecutor::new());
         let metrics = Arc::new(MetricsTracker::new());
-        let batch_buffer = Arc::new(crate::batch_buffer::BatchBuffer::new(schema.clone()));
+        let batch_buffer = Arc::new(
+            crate::batch_buffer::BatchBuffer::new(schema.clone()).with_partition_columns(
+                crate::delta_lake::read_table_config(&base_path)?.partition_columns,
+            ),
+        );
 
         Ok(Self {
             base_path,
             s3_url: None,
-            s3_storage_options: None,
+            s3_config: None,
             query_executor,
             schema,
             metrics,
             data_skipping_stats: Arc::new(tokio::sync::Mutex::new(DataSkippingStats::default())),
             auth_context: None,
-            user_store: None,
+            credential_store: None,
             audit_logger: None,
             role_manager: None,
             batch_buffer,
+            cursors: Arc::new(crate::query::CursorRegistry::new()),
+            vacuum_keep_versions: 0,
+            vacuum_min_retention: std::time::Duration::ZERO,
+            maintenance_lock: Default::default(),
+            log_retention: crate::delta_lake::LogRetentionConfig::default(),
+            identifier_case: IdentifierCase::default(),
+            collation: Collation::default(),
+            lock_manager: Arc::new(crate::lock_manager::LockManager::default()),
+            retry_policy: crate::storage::retry::RetryPolicy::default(),
+            log_redaction: crate::security::LogRedaction::default(),
+            auto_compaction: None,
+            auto_compacting: AtomicBool::new(false),
+            last_auto_compaction: tokio::sync::Mutex::new(None),
+            maintenance_scheduler: tokio::sync::Mutex::new(None),
+            maintenance_status: Default::default(),
+            open_transactions: std::sync::Mutex::new(HashMap::new()),
+            spill: Arc::default(),
+            query_memory_limit: None,
+            scan_concurrency: None,
+            file_naming: None,
+            stats_config: None,
+            column_keys: None,
+            target_file_size: None,
+            recovery_report,
+            idempotency_window: crate::metadata::DEFAULT_IDEMPOTENCY_WINDOW,
+            size_limits: crate::storage::limits::SizeLimits::default(),
         })
     }
 
@@ -280,38 +518,56 @@ impl DatabaseOps {
         access_key: &str,
         secret_key: &str,
     ) -> Result<Self> {
-        use crate::storage::s3::{create_delta_storage_options, get_s3_cache_path, parse_s3_url};
+        let config = crate::storage::s3::minio_config(endpoint, access_key, secret_key);
+        Self::create_with_s3_config(s3_path, schema, config).await
+    }
+
+    /// Create a database at `s3_path`, `s3://bucket/prefix`, connecting as `config` says
+    ///
+    /// `config` carries the region, endpoint, credentials and multipart
+    /// upload settings; see `storage::s3::S3Config`.
+    pub async fn create_with_s3_config(
+        s3_path: &str,
+        schema: SchemaRef,
+        config: crate::storage::s3::S3Config,
+    ) -> Result<Self> {
+        Self::create_s3(s3_path, schema, &[], config).await
+    }
+
+    async fn create_s3(
+        s3_path: &str,
+        schema: SchemaRef,
+        partition_columns: &[&str],
+        config: crate::storage::s3::S3Config,
+    ) -> Result<Self> {
+        use crate::storage::s3::{get_s3_cache_path, parse_s3_url};
         use deltalake::kernel::{StructField, StructType};
         use deltalake::DeltaOps;
 
         info!("Creating Delta Lake database on S3 at: {}", s3_path);
 
-        // Configure S3 storage options using helper
-        let storage_options = create_delta_storage_options(endpoint, access_key, secret_key);
-
         // Convert Arrow schema to Delta Lake schema
-        let mut delta_fields = Vec::new();
-        for field in schema.fields() {
-            let delta_type = Self::arrow_to_delta_type(field.data_type())?;
-            delta_fields.push(StructField::new(
-                field.name().clone(),
-                delta_type,
-                field.is_nullable(),
-            ));
-        }
+        let delta_fields: Vec<StructField> = schema
+            .fields()
+            .iter()
+            .map(|field| Self::arrow_to_delta_field(field))
+            .collect::<Result<_>>()?;
         let delta_schema = StructType::try_new(delta_fields)
             .map_err(|e| Error::Other(format!("Failed to create Delta schema: {}", e)))?;
 
         // Create Delta table on S3
         let s3_url = parse_s3_url(s3_path)?;
-        let ops =
-            DeltaOps::try_from_uri_with_storage_options(s3_url.clone(), storage_options.clone())
-                .await
-                .map_err(Error::DeltaTable)?;
+        let ops = DeltaOps(
+            config
+                .table_builder(s3_url)?
+                .build()
+                .map_err(Error::DeltaTable)?,
+        );
 
         let _table = ops
             .create()
             .with_columns(delta_schema.fields().cloned())
+            .with_partition_columns(partition_columns.iter().copied())
             .await
             .map_err(Error::DeltaTable)?;
 
@@ -323,21 +579,50 @@ impl DatabaseOps {
 
         let query_executor = Arc::new(QueryExecutor::new());
         let metrics = Arc::new(MetricsTracker::new());
-        let batch_buffer = Arc::new(crate::batch_buffer::BatchBuffer::new(schema.clone()));
+        let batch_buffer = Arc::new(
+            crate::batch_buffer::BatchBuffer::new(schema.clone())
+                .with_partition_columns(partition_columns.iter().map(|c| c.to_string()).collect()),
+        );
 
         Ok(Self {
             base_path,
             s3_url: Some(s3_path.to_string()),
-            s3_storage_options: Some(storage_options),
+            s3_config: Some(config),
             query_executor,
             schema,
             metrics,
             data_skipping_stats: Arc::new(tokio::sync::Mutex::new(DataSkippingStats::default())),
             auth_context: None,
-            user_store: None,
+            credential_store: None,
             audit_logger: None,
             role_manager: None,
             batch_buffer,
+            cursors: Arc::new(crate::query::CursorRegistry::new()),
+            vacuum_keep_versions: 0,
+            vacuum_min_retention: std::time::Duration::ZERO,
+            maintenance_lock: Default::default(),
+            log_retention: crate::delta_lake::LogRetentionConfig::default(),
+            identifier_case: IdentifierCase::default(),
+            collation: Collation::default(),
+            lock_manager: Arc::new(crate::lock_manager::LockManager::default()),
+            retry_policy: crate::storage::retry::RetryPolicy::default(),
+            log_redaction: crate::security::LogRedaction::default(),
+            auto_compaction: None,
+            auto_compacting: AtomicBool::new(false),
+            last_auto_compaction: tokio::sync::Mutex::new(None),
+            maintenance_scheduler: tokio::sync::Mutex::new(None),
+            maintenance_status: Default::default(),
+            open_transactions: std::sync::Mutex::new(HashMap::new()),
+            spill: Arc::default(),
+            query_memory_limit: None,
+            scan_concurrency: None,
+            file_naming: None,
+            stats_config: None,
+            column_keys: None,
+            target_file_size: None,
+            recovery_report: Default::default(),
+            idempotency_window: crate::metadata::DEFAULT_IDEMPOTENCY_WINDOW,
+            size_limits: crate::storage::limits::SizeLimits::default(),
         })
     }
 
@@ -348,38 +633,27 @@ impl DatabaseOps {
         access_key: &str,
         secret_key: &str,
     ) -> Result<Self> {
-        use crate::storage::s3::{create_delta_storage_options, get_s3_cache_path, parse_s3_url};
-        use deltalake::open_table_with_storage_options;
+        let config = crate::storage::s3::minio_config(endpoint, access_key, secret_key);
+        Self::open_with_s3_config(s3_path, config).await
+    }
+
+    /// Open the database at `s3_path`, `s3://bucket/prefix`, connecting as `config` says
+    pub async fn open_with_s3_config(
+        s3_path: &str,
+        config: crate::storage::s3::S3Config,
+    ) -> Result<Self> {
+        use crate::storage::s3::{get_s3_cache_path, parse_s3_url};
 
         info!("Opening Delta Lake database from S3 at: {}", s3_path);
 
-        // Configure S3 storage options using helper
-        let storage_options = create_delta_storage_options(endpoint, access_key, secret_key);
-
         // Open Delta table from S3
         let s3_url = parse_s3_url(s3_path)?;
-        let table = open_table_with_storage_options(s3_url, storage_options.clone())
-            .await
-            .map_err(Error::DeltaTable)?;
+        let table = config.open_table(s3_url).await?;
 
         // Get schema from Delta table
         let snapshot = table.snapshot().map_err(Error::DeltaTable)?;
-        let delta_schema = snapshot.schema();
-
-        // Convert to Arrow schema
-        let arrow_fields: Result<Vec<_>> = delta_schema
-            .fields()
-            .map(|field| {
-                let arrow_type = Self::delta_to_arrow_type(field.data_type())?;
-                Ok(arrow::datatypes::Field::new(
-                    field.name(),
-                    arrow_type,
-                    field.is_nullable(),
-                ))
-            })
-            .collect();
-
-        let schema = Arc::new(arrow::datatypes::Schema::new(arrow_fields?));
+        let schema = Self::delta_to_arrow_schema(&snapshot.schema())?;
+        let partition_columns = snapshot.metadata().partition_columns().clone();
 
         info!(
             "Opened Delta Lake from S3 with {} fields",
@@ -392,24 +666,109 @@ impl DatabaseOps {
 
         let query_executor = Arc::new(QueryExecutor::new());
         let metrics = Arc::new(MetricsTracker::new());
-        let batch_buffer = Arc::new(crate::batch_buffer::BatchBuffer::new(schema.clone()));
+        let batch_buffer = Arc::new(
+            crate::batch_buffer::BatchBuffer::new(schema.clone())
+                .with_partition_columns(partition_columns),
+        );
 
         Ok(Self {
             base_path,
             s3_url: Some(s3_path.to_string()),
-            s3_storage_options: Some(storage_options),
+            s3_config: Some(config),
             query_executor,
             schema,
             metrics,
             data_skipping_stats: Arc::new(tokio::sync::Mutex::new(DataSkippingStats::default())),
             auth_context: None,
-            user_store: None,
+            credential_store: None,
             audit_logger: None,
             role_manager: None,
             batch_buffer,
+            cursors: Arc::new(crate::query::CursorRegistry::new()),
+            vacuum_keep_versions: 0,
+            vacuum_min_retention: std::time::Duration::ZERO,
+            maintenance_lock: Default::default(),
+            log_retention: crate::delta_lake::LogRetentionConfig::default(),
+            identifier_case: IdentifierCase::default(),
+            collation: Collation::default(),
+            lock_manager: Arc::new(crate::lock_manager::LockManager::default()),
+            retry_policy: crate::storage::retry::RetryPolicy::default(),
+            log_redaction: crate::security::LogRedaction::default(),
+            auto_compaction: None,
+            auto_compacting: AtomicBool::new(false),
+            last_auto_compaction: tokio::sync::Mutex::new(None),
+            maintenance_scheduler: tokio::sync::Mutex::new(None),
+            maintenance_status: Default::default(),
+            open_transactions: std::sync::Mutex::new(HashMap::new()),
+            spill: Arc::default(),
+            query_memory_limit: None,
+            scan_concurrency: None,
+            file_naming: None,
+            stats_config: None,
+            column_keys: None,
+            target_file_size: None,
+            recovery_report: Default::default(),
+            idempotency_window: crate::metadata::DEFAULT_IDEMPOTENCY_WINDOW,
+            size_limits: crate::storage::limits::SizeLimits::default(),
+        })
+    }
+
+    /// Helper: Convert an Arrow field to a Delta Lake field, keeping its
+    /// column default and generating expression
+    fn arrow_to_delta_field(
+        field: &arrow::datatypes::Field,
+    ) -> Result<deltalake::kernel::StructField> {
+        use crate::metadata::defaults::{default_expr, DEFAULT_METADATA_KEY};
+        use crate::metadata::generated::{generated_expr, GENERATED_METADATA_KEY};
+
+        let delta_field = deltalake::kernel::StructField::new(
+            field.name().clone(),
+            Self::arrow_to_delta_type(field.data_type())?,
+            field.is_nullable(),
+        );
+        let metadata: Vec<(&str, String)> = [
+            (DEFAULT_METADATA_KEY, default_expr(field)),
+            (GENERATED_METADATA_KEY, generated_expr(field)),
+        ]
+        .into_iter()
+        .filter_map(|(key, expr)| expr.map(|expr| (key, expr.to_string())))
+        .collect();
+        Ok(if metadata.is_empty() {
+            delta_field
+        } else {
+            delta_field.with_metadata(metadata)
         })
     }
 
+    /// Helper: Convert a Delta Lake schema to an Arrow schema
+    fn delta_to_arrow_schema(schema: &deltalake::kernel::StructType) -> Result<SchemaRef> {
+        let fields: Result<Vec<_>> = schema.fields().map(Self::delta_to_arrow_field).collect();
+        Ok(Arc::new(arrow::datatypes::Schema::new(fields?)))
+    }
+
+    /// Helper: Convert a Delta Lake field to an Arrow field, keeping its
+    /// column default and generating expression
+    fn delta_to_arrow_field(
+        field: &deltalake::kernel::StructField,
+    ) -> Result<arrow::datatypes::Field> {
+        use crate::metadata::defaults::{with_default, DEFAULT_METADATA_KEY};
+        use crate::metadata::generated::{with_generated, GENERATED_METADATA_KEY};
+        use deltalake::kernel::MetadataValue;
+
+        let mut arrow_field = arrow::datatypes::Field::new(
+            field.name(),
+            Self::delta_to_arrow_type(field.data_type())?,
+            field.is_nullable(),
+        );
+        if let Some(MetadataValue::String(expr)) = field.metadata().get(DEFAULT_METADATA_KEY) {
+            arrow_field = with_default(arrow_field, expr.clone());
+        }
+        if let Some(MetadataValue::String(expr)) = field.metadata().get(GENERATED_METADATA_KEY) {
+            arrow_field = with_generated(arrow_field, expr.clone());
+        }
+        Ok(arrow_field)
+    }
+
     /// Helper: Convert Arrow DataType to Delta Lake DataType
     fn arrow_to_delta_type(
         arrow_type: &arrow::datatypes::DataType,
@@ -457,6 +816,10 @@ impl DatabaseOps {
                 deltalake::kernel::PrimitiveType::String => Ok(ArrowDataType::Utf8),
                 deltalake::kernel::PrimitiveType::Binary => Ok(ArrowDataType::Binary),
                 deltalake::kernel::PrimitiveType::Date => Ok(ArrowDataType::Date32),
+                deltalake::kernel::PrimitiveType::Timestamp => Ok(ArrowDataType::Timestamp(
+                    TimeUnit::Microsecond,
+                    Some("UTC".into()),
+                )),
                 deltalake::kernel::PrimitiveType::TimestampNtz => {
                     Ok(ArrowDataType::Timestamp(TimeUnit::Microsecond, None))
                 }
@@ -488,7 +851,9 @@ impl DatabaseOps {
             let user_store = crate::security::UserStore::new();
             user_store.save(&users_path)?;
 
-            db.user_store = Some(Arc::new(tokio::sync::Mutex::new(user_store)));
+            db.credential_store = Some(Arc::new(crate::security::CachedCredentialStore::new(
+                Arc::new(crate::security::FileCredentialStore::new(users_path)),
+            )));
             db.audit_logger = Some(Arc::new(crate::security::AuditLogger::new(audit_path)?));
             db.role_manager = Some(Arc::new(crate::security::RoleManager::new()));
             db.auth_context = Some(Arc::new(crate::security::AuthContext::system()));
@@ -512,32 +877,115 @@ impl DatabaseOps {
         let users_path = base_path.join("_metadata").join("users.json");
 
         // Check if authentication is enabled
-        let auth_enabled = users_path.exists();
+        if !users_path.exists() {
+            return Self::open(&base_path).await;
+        }
+        let store = crate::security::FileCredentialStore::new(users_path);
+        Self::open_with_credential_store(&base_path, Arc::new(store), credentials).await
+    }
 
+    /// Open database with authentication against users and roles in `store`
+    ///
+    /// Authentication is enabled whether or not the table was created with
+    /// it. Roles `store` lists are added to the default roles. Users are
+    /// cached once looked up; see `invalidate_cached_user`.
+    pub async fn open_with_credential_store<P: AsRef<Path>>(
+        path: P,
+        store: Arc<dyn crate::security::CredentialStore>,
+        credentials: crate::security::Credentials,
+    ) -> Result<Self> {
+        let base_path = path.as_ref().to_path_buf();
         let mut db = Self::open(&base_path).await?;
+        let store = crate::security::CachedCredentialStore::new(store);
 
-        if auth_enabled {
-            let user_store = crate::security::UserStore::load(&users_path)?;
+        // None credentials = system access (for administrative operations)
+        let auth_ctx = if let Some((username, password)) = credentials {
+            crate::security::credential_store::authenticate(&store, username, password)?
+        } else {
+            // System access with admin privileges
+            crate::security::AuthContext::system()
+        };
 
-            // None credentials = system access (for administrative operations)
-            let auth_ctx = if let Some((username, password)) = credentials {
-                user_store.authenticate(username, password)?
-            } else {
-                // System access with admin privileges
-                crate::security::AuthContext::system()
-            };
+        let mut role_manager = crate::security::RoleManager::new();
+        for role in store.list_roles()? {
+            role_manager.add_role(role);
+        }
 
-            db.auth_context = Some(Arc::new(auth_ctx));
+        db.auth_context = Some(Arc::new(auth_ctx));
 
-            let audit_path = base_path.join("_metadata").join("audit.json");
-            db.user_store = Some(Arc::new(tokio::sync::Mutex::new(user_store)));
-            db.audit_logger = Some(Arc::new(crate::security::AuditLogger::new(audit_path)?));
-            db.role_manager = Some(Arc::new(crate::security::RoleManager::new()));
+        let audit_path = base_path.join("_metadata").join("audit.json");
+        db.credential_store = Some(Arc::new(store));
+        db.audit_logger = Some(Arc::new(crate::security::AuditLogger::new(audit_path)?));
+        db.role_manager = Some(Arc::new(role_manager));
+
+        Ok(db)
+    }
+
+    /// Whether users must authenticate, with a credential store to check them against
+    pub fn auth_enabled(&self) -> bool {
+        self.credential_store.is_some()
+    }
+
+    /// Handle on the same table acting as `username`, if `password` is theirs
+    ///
+    /// For servers answering many users from one handle, such as
+    /// `rest::RestServer`. The new handle shares this one's credential store,
+    /// roles, audit log, keys and settings, size limits included. Fails with
+    /// `Error::InvalidOperation` if authentication isn't enabled. Only
+    /// available for local tables.
+    pub async fn login(&self, username: &str, password: &str) -> Result<DatabaseOps> {
+        let store = self
+            .credential_store
+            .as_ref()
+            .ok_or_else(|| Error::InvalidOperation("Authentication is not enabled".to_string()))?;
+        if self.s3_url.is_some() {
+            return Err(Error::InvalidOperation(
+                "Logging in is only supported for local tables".to_string(),
+            ));
         }
+        let auth_ctx = match crate::security::credential_store::authenticate(
+            store.as_ref(),
+            username,
+            password,
+        ) {
+            Ok(auth_ctx) => auth_ctx,
+            Err(e) => {
+                self.audit_log("LOGIN", &format!("username={}: {}", username, e), false)
+                    .await;
+                return Err(e);
+            }
+        };
 
+        let mut db = self.share_session(Self::open(&self.base_path).await?);
+        db.auth_context = Some(Arc::new(auth_ctx));
+        db.audit_log("LOGIN", &format!("username={}", username), true)
+            .await;
         Ok(db)
     }
 
+    /// `username`'s account as the credential store holds it now
+    ///
+    /// For servers keeping handles from `login`, to end them once the
+    /// user's password or roles change or the user is removed.
+    pub(crate) fn stored_user(&self, username: &str) -> Result<Option<crate::security::User>> {
+        use crate::security::CredentialStore;
+
+        match &self.credential_store {
+            Some(store) => store.load_user(username),
+            None => Ok(None),
+        }
+    }
+
+    /// Look `username` up in the credential store again on next use
+    ///
+    /// For changes made to the store other than through this handle, such as
+    /// a role granted in the external system.
+    pub fn invalidate_cached_user(&self, username: &str) {
+        if let Some(store) = &self.credential_store {
+            store.invalidate(username);
+        }
+    }
+
     /// Create a user (requires admin role)
     pub async fn create_user(&self, username: &str, password: &str, roles: &[&str]) -> Result<()> {
         self.check_permission(&crate::security::Permission::Admin)?;
@@ -549,11 +997,8 @@ impl DatabaseOps {
                 roles.iter().map(|s| s.to_string()).collect(),
             )?;
 
-            if let Some(user_store) = &self.user_store {
-                let mut store = user_store.lock().await;
-                store.add_user(user)?;
-                let users_path = self.base_path.join("_metadata").join("users.json");
-                store.save(users_path)?;
+            if let Some(store) = &self.credential_store {
+                store.add_user(&user)?;
             }
 
             Ok(())
@@ -586,25 +1031,174 @@ impl DatabaseOps {
     pub async fn revoke_role_from_user(&self, username: &str, role: &str) -> Result<()> {
         self.check_permission(&crate::security::Permission::Admin)?;
 
-        if let Some(user_store) = &self.user_store {
-            let mut store = user_store.lock().await;
-            store.revoke_role(username, role)?;
-            let users_path = self.base_path.join("_metadata").join("users.json");
-            store.save(users_path)?;
+        if let Some(store) = &self.credential_store {
+            store.update_user(username, |user| user.roles.retain(|r| r != role))?;
         }
 
         Ok(())
     }
 
+    /// Set or clear a user's own storage quota (requires admin role)
+    ///
+    /// A user's own quota takes precedence over their roles'; `None` goes
+    /// back to the roles'. Handles open as the user see the change once
+    /// reopened.
+    pub async fn set_user_quota(
+        &self,
+        username: &str,
+        quota: Option<crate::security::Quota>,
+    ) -> Result<()> {
+        self.check_permission(&crate::security::Permission::Admin)?;
+
+        let result = async {
+            let store = self.credential_store.as_ref().ok_or_else(|| {
+                Error::InvalidOperation("Quotas need authentication enabled".to_string())
+            })?;
+            store.update_user(username, |user| user.quota = quota)
+        }
+        .await;
+
+        let details = format!("username={}, quota={:?}", username, quota);
+        match &result {
+            Ok(_) => self.audit_log("SET_QUOTA", &details, true).await,
+            Err(e) => {
+                self.audit_log("SET_QUOTA", &format!("{}: {}", details, e), false)
+                    .await
+            }
+        }
+        result
+    }
+
+    /// A user's stored bytes and rows against their quota
+    ///
+    /// Users can see their own; anyone else's needs admin permission.
+    pub async fn quota_status(&self, username: &str) -> Result<crate::security::QuotaStatus> {
+        let auth_ctx = self.auth_context.as_ref().ok_or_else(|| {
+            Error::InvalidOperation("Quotas need authentication enabled".to_string())
+        })?;
+        let roles = if auth_ctx.username == username {
+            auth_ctx.roles.clone()
+        } else {
+            self.check_permission(&crate::security::Permission::Admin)?;
+            let store = self.credential_store.as_ref().ok_or_else(|| {
+                Error::InvalidOperation("Quotas need authentication enabled".to_string())
+            })?;
+            store
+                .load_user(username)?
+                .ok_or_else(|| Error::Other(format!("User not found: {}", username)))?
+                .roles
+        };
+        match self.quota_guard_for(username, &roles).await {
+            Some(guard) => guard.status(),
+            None => Err(Error::InvalidOperation(
+                "Quotas need authentication enabled".to_string(),
+            )),
+        }
+    }
+
+    /// Quota `username` writes under, with `roles`; None with authentication disabled
+    async fn quota_guard_for(
+        &self,
+        username: &str,
+        roles: &[String],
+    ) -> Option<crate::security::QuotaGuard> {
+        let store = self.credential_store.as_ref()?;
+        let own = match store.load_user(username) {
+            Ok(user) => user.and_then(|user| user.quota),
+            Err(e) => {
+                warn!("Failed to look up {}'s own quota: {}", username, e);
+                None
+            }
+        };
+        let limit = own.unwrap_or_else(|| {
+            self.role_manager
+                .as_ref()
+                .map(|role_manager| role_manager.quota_for(roles))
+                .unwrap_or_default()
+        });
+        Some(crate::security::QuotaGuard::new(
+            &self.base_path,
+            username,
+            limit,
+        ))
+    }
+
+    /// The authenticated user's quota, with the version a write starting now
+    /// begins from, to charge it with afterwards
+    ///
+    /// None with authentication disabled. Only local tables are metered.
+    async fn quota_meter(&self) -> Option<(crate::security::QuotaGuard, Option<u64>)> {
+        let auth_ctx = self.auth_context.as_ref()?;
+        if self.s3_url.is_some() {
+            return None;
+        }
+        let guard = self
+            .quota_guard_for(&auth_ctx.username, &auth_ctx.roles)
+            .await?;
+        match crate::delta_lake::latest_version(&self.base_path) {
+            Ok(version) => Some((guard, version)),
+            Err(e) => {
+                warn!("Not metering write, failed to read table version: {}", e);
+                None
+            }
+        }
+    }
+
+    /// Charge what the commits up to `version` changed to the user of `meter`
+    ///
+    /// The write has already committed, so a failure is only logged.
+    fn charge_quota(
+        &self,
+        meter: Option<(crate::security::QuotaGuard, Option<u64>)>,
+        version: i64,
+    ) {
+        let Some((guard, before)) = meter else {
+            return;
+        };
+        if before == Some(version as u64) {
+            return;
+        }
+        if let Err(e) = guard.charge(before, version as u64) {
+            warn!("Failed to record quota usage at version {}: {}", version, e);
+        }
+    }
+
     /// Get audit log
+    ///
+    /// Unredacted details are only included for users with admin permission.
     pub async fn get_audit_log(&self) -> Result<Vec<crate::security::AuditEntry>> {
         if let Some(logger) = &self.audit_logger {
-            Ok(logger.get_entries().await)
+            let mut entries = logger.get_entries().await;
+            if self
+                .check_permission(&crate::security::Permission::Admin)
+                .is_err()
+            {
+                for entry in &mut entries {
+                    entry.full_details = None;
+                }
+            }
+            Ok(entries)
         } else {
             Ok(vec![])
         }
     }
 
+    /// Stream audit entries logged through this handle from now on
+    ///
+    /// None without audit logging. As with `get_audit_log`, unredacted
+    /// details are only included for users with admin permission.
+    pub fn subscribe_audit_log(&self) -> Option<crate::security::AuditSubscription> {
+        let subscription = self.audit_logger.as_ref()?.subscribe();
+        if self
+            .check_permission(&crate::security::Permission::Admin)
+            .is_err()
+        {
+            Some(subscription.without_full_details())
+        } else {
+            Some(subscription)
+        }
+    }
+
     /// Check if current user has permission
     fn check_permission(&self, permission: &crate::security::Permission) -> Result<()> {
         // If auth is disabled (no role_manager), allow all operations
@@ -616,79 +1210,416 @@ impl DatabaseOps {
         if let Some(role_manager) = &self.role_manager {
             if let Some(auth_ctx) = &self.auth_context {
                 if !role_manager.has_permission(&auth_ctx.roles, permission) {
-                    return Err(Error::Other(format!("Permission denied: {:?}", permission)));
+                    return Err(Error::PermissionDenied(format!("{:?}", permission)));
                 }
                 return Ok(());
             } else {
                 // Auth enabled but no auth context means user must authenticate
-                return Err(Error::Other("Authentication required".to_string()));
+                return Err(Error::AuthenticationRequired(
+                    "no user is logged in".to_string(),
+                ));
             }
         }
         Ok(())
     }
 
-    /// Get the base path of the database
-    pub fn base_path(&self) -> &std::path::Path {
-        &self.base_path
+    /// Check if the authenticated user can read `table`
+    ///
+    /// Always true with authentication disabled.
+    pub fn can_read_table(&self, table: &str) -> bool {
+        match &self.auth_context {
+            Some(auth_ctx) => self.can_read_table_as(auth_ctx, table),
+            None => self.role_manager.is_none(),
+        }
     }
 
-    /// Log audit entry
-    async fn audit_log(&self, operation: &str, details: &str, success: bool) {
-        if let Some(logger) = &self.audit_logger {
-            if let Some(auth_ctx) = &self.auth_context {
-                let _ = logger
-                    .log(
-                        auth_ctx.username.clone(),
-                        operation.to_string(),
-                        details.to_string(),
-                        success,
-                    )
-                    .await;
-            }
-        }
+    /// Check if `auth_ctx`, rather than the authenticated user, can read `table`
+    pub fn can_read_table_as(&self, auth_ctx: &crate::security::AuthContext, table: &str) -> bool {
+        self.role_manager
+            .as_ref()
+            .is_none_or(|role_manager| role_manager.can_read_table(&auth_ctx.roles, table))
     }
 
-    /// Get the database schema
-    pub fn schema(&self) -> SchemaRef {
-        self.schema.clone()
+    /// Check if `auth_ctx`, rather than the authenticated user, has `permission`
+    ///
+    /// Always true with authentication disabled.
+    pub fn has_permission_as(
+        &self,
+        auth_ctx: &crate::security::AuthContext,
+        permission: &crate::security::Permission,
+    ) -> bool {
+        self.role_manager
+            .as_ref()
+            .is_none_or(|role_manager| role_manager.has_permission(&auth_ctx.roles, permission))
     }
 
-    /// Insert a RecordBatch into the database
-    pub async fn insert(&self, batch: RecordBatch) -> Result<u64> {
-        info!("Inserting {} rows", batch.num_rows());
+    /// Check if the authenticated user has `permission`, without failing
+    pub fn has_permission(&self, permission: &crate::security::Permission) -> bool {
+        self.check_permission(permission).is_ok()
+    }
 
-        // Check write permission
-        self.check_permission(&crate::security::Permission::Write)?;
+    /// Columns of this table the authenticated user reads masked
+    ///
+    /// None with authentication disabled.
+    pub fn column_masks(&self) -> crate::security::ColumnMasks {
+        self.masks_of(&self.table_name())
+    }
 
-        // Track metrics on completion (success or error)
-        let num_rows = batch.num_rows();
-        let result = self.insert_delta_native(batch).await;
-        match &result {
-            Ok(_) => {
-                self.metrics.total_inserts.fetch_add(1, Ordering::Relaxed);
-                self.metrics
-                    .total_transactions
-                    .fetch_add(1, Ordering::Relaxed);
-                self.audit_log("INSERT", &format!("{} rows", num_rows), true)
-                    .await;
-            }
-            Err(e) => {
-                self.metrics.total_errors.fetch_add(1, Ordering::Relaxed);
-                self.audit_log("INSERT", &format!("failed: {}", e), false)
-                    .await;
+    /// Columns of `table` the authenticated user reads masked
+    fn masks_of(&self, table: &str) -> crate::security::ColumnMasks {
+        match (&self.role_manager, &self.auth_context) {
+            (Some(role_manager), Some(auth_ctx)) => {
+                role_manager.column_masks(&auth_ctx.roles, table)
             }
+            _ => crate::security::ColumnMasks::default(),
         }
-        result
     }
 
-    /// Insert data via write buffer (batches multiple small writes for performance)
-    ///
-    /// This method buffers writes and flushes automatically when thresholds are reached.
-    /// Use this for many small appends to reduce transaction overhead.
-    ///
-    /// The buffer accumulates batches and flushes when:
-    /// - Buffer exceeds max_rows threshold (default: 1000)
+    /// Columns of this table `auth_ctx`, rather than the authenticated user,
+    /// reads masked
+    pub fn column_masks_as(
+        &self,
+        auth_ctx: &crate::security::AuthContext,
+    ) -> crate::security::ColumnMasks {
+        self.role_manager
+            .as_ref()
+            .map(|role_manager| role_manager.column_masks(&auth_ctx.roles, &self.table_name()))
+            .unwrap_or_default()
+    }
+
+    /// `provider`, a scan of `table`, as the authenticated user reads it
+    fn mask_table(
+        &self,
+        ctx: &datafusion::prelude::SessionContext,
+        table: &str,
+        provider: Arc<dyn datafusion::catalog::TableProvider>,
+    ) -> Result<Arc<dyn datafusion::catalog::TableProvider>> {
+        let masks = self.masks_of(table);
+        if masks.is_empty() {
+            return Ok(provider);
+        }
+        Ok(masks.apply(ctx.read_table(provider)?)?.into_view())
+    }
+
+    /// Mask rows read from this table for the authenticated user
+    fn mask_batches(&self, batches: Vec<RecordBatch>) -> Result<Vec<RecordBatch>> {
+        let masks = self.column_masks();
+        if masks.is_empty() {
+            return Ok(batches);
+        }
+        batches
+            .iter()
+            .map(|batch| masks.mask_batch(batch))
+            .collect()
+    }
+
+    /// Which writes the table accepts, whoever makes them
+    ///
+    /// Read from the local Delta log; S3 tables report `ReadWrite` and leave
+    /// the check to delta-rs at commit time.
+    pub fn write_mode(&self) -> crate::delta_lake::WriteMode {
+        use crate::delta_lake::WriteMode;
+
+        if self.s3_url.is_some() {
+            return WriteMode::ReadWrite;
+        }
+        crate::delta_lake::read_table_config(&self.base_path)
+            .map(|config| config.write_mode())
+            .unwrap_or(WriteMode::ReadOnly)
+    }
+
+    /// Whether the table asks for deletion vectors, from
+    /// `delta.enableDeletionVectors`
+    ///
+    /// FSDB only deletes and updates by rewriting files (copy-on-write), so
+    /// while this is true its deletes and updates fail with
+    /// `Error::UnsupportedDeltaFeature`. Read from the local Delta log like
+    /// `write_mode`; S3 tables report false.
+    pub fn deletion_vectors_enabled(&self) -> bool {
+        if self.s3_url.is_some() {
+            return false;
+        }
+        crate::delta_lake::read_table_config(&self.base_path)
+            .map(|config| config.deletion_vectors_enabled())
+            .unwrap_or(false)
+    }
+
+    /// Clear `delta.enableDeletionVectors` in the table's metadata
+    ///
+    /// For tables another engine set to use deletion vectors: from then on
+    /// every writer rewrites files, and FSDB's deletes and updates are
+    /// accepted again. Deletion vectors already committed are left alone.
+    pub async fn disable_deletion_vectors(&self) -> Result<()> {
+        use deltalake::DeltaOps;
+
+        info!("Disabling deletion vectors");
+
+        // Check admin permission
+        self.check_permission(&crate::security::Permission::Admin)?;
+        self.check_writable()?;
+
+        self.retry_policy
+            .run("Disable deletion vectors", || async {
+                let table = self.open_delta_table().await?;
+                DeltaOps(table)
+                    .set_tbl_properties()
+                    .with_properties(HashMap::from([(
+                        "delta.enableDeletionVectors".to_string(),
+                        "false".to_string(),
+                    )]))
+                    .await
+                    .map_err(Error::DeltaTable)
+            })
+            .await?;
+        self.audit_log("SET TBLPROPERTIES", "deletion vectors false", true)
+            .await;
+        Ok(())
+    }
+
+    /// Column inserts keep data files clustered by, from the
+    /// `fsdb.clusteringKey` table property
+    ///
+    /// Read from the local Delta log; S3 tables have none.
+    pub fn clustering_key(&self) -> Option<String> {
+        if self.s3_url.is_some() {
+            return None;
+        }
+        crate::delta_lake::read_table_config(&self.base_path)
+            .ok()?
+            .configuration
+            .get(crate::delta_lake::clustering::CLUSTERING_KEY_PROPERTY)
+            .filter(|key| !key.is_empty())
+            .cloned()
+    }
+
+    /// Store `column` as the table's clustering key, or clear it with `None`
+    ///
+    /// From then on each insert is sorted by the key and split so every new
+    /// file's key range stays within the range of an existing file, or
+    /// between ranges, which keeps range filters on the key skipping files
+    /// without a Z-ORDER. Files already written are left as they are.
+    /// Fails with `Error::InvalidOperation` if the table has no such column
+    /// or its type can't be ordered.
+    pub async fn set_clustering_key(&self, column: Option<&str>) -> Result<()> {
+        use deltalake::DeltaOps;
+
+        info!("Setting clustering key to {:?}", column);
+
+        // Check admin permission
+        self.check_permission(&crate::security::Permission::Admin)?;
+        self.check_writable()?;
+
+        if let Some(column) = column {
+            let schema = self.table_schema().await?;
+            let field = schema
+                .field_with_name(column)
+                .map_err(|_| Error::InvalidOperation(format!("No such column: {}", column)))?;
+            if !crate::delta_lake::clustering::supports_type(field.data_type()) {
+                return Err(Error::InvalidOperation(format!(
+                    "Can't cluster by {}: {} values can't be ordered",
+                    column,
+                    field.data_type()
+                )));
+            }
+        }
+        self.retry_policy
+            .run("Set clustering key", || async {
+                let table = self.open_delta_table().await?;
+                DeltaOps(table)
+                    .set_tbl_properties()
+                    .with_properties(HashMap::from([(
+                        crate::delta_lake::clustering::CLUSTERING_KEY_PROPERTY.to_string(),
+                        column.unwrap_or_default().to_string(),
+                    )]))
+                    .await
+                    .map_err(Error::DeltaTable)
+            })
+            .await?;
+        self.audit_log(
+            "SET TBLPROPERTIES",
+            &format!("clustering key {:?}", column),
+            true,
+        )
+        .await;
+        Ok(())
+    }
+
+    /// Check permissions against `role_manager` instead of the default roles
+    /// and any the credential store lists
+    ///
+    /// Only takes effect with authentication enabled.
+    pub fn with_role_manager(mut self, role_manager: crate::security::RoleManager) -> Self {
+        if self.role_manager.is_some() {
+            self.role_manager = Some(Arc::new(role_manager));
+        }
+        self
+    }
+
+    /// Get the base path of the database
+    pub fn base_path(&self) -> &std::path::Path {
+        &self.base_path
+    }
+
+    /// What recovery cleaned up when the table was opened; clean for
+    /// tables this handle created
+    pub fn recovery_report(&self) -> &crate::delta_lake::RecoveryReport {
+        &self.recovery_report
+    }
+
+    /// The table as named in role grants: its catalog name, or `data` for the
+    /// database's own table
+    fn table_name(&self) -> String {
+        use crate::metadata::catalog;
+
+        let parent = self.base_path.parent();
+        match self.base_path.file_name() {
+            Some(name)
+                if self.s3_url.is_none()
+                    && parent.and_then(|p| p.file_name())
+                        == Some(std::ffi::OsStr::new(catalog::TABLES_DIR)) =>
+            {
+                name.to_string_lossy().into_owned()
+            }
+            _ => "data".to_string(),
+        }
+    }
+
+    /// The table as named in tracing spans: its S3 URL or local path
+    pub(crate) fn table_label(&self) -> String {
+        match &self.s3_url {
+            Some(url) => url.clone(),
+            None => self.base_path.display().to_string(),
+        }
+    }
+
+    /// Log audit entry
+    async fn audit_log(&self, operation: &str, details: &str, success: bool) {
+        if let Some(logger) = &self.audit_logger {
+            if let Some(auth_ctx) = &self.auth_context {
+                let redacted = self.log_redaction.redact(details);
+                let mut entry = crate::security::AuditEntry::new(
+                    auth_ctx.username.clone(),
+                    operation.to_string(),
+                    redacted.clone(),
+                    success,
+                );
+                if redacted != details {
+                    entry = entry.with_full_details(details.to_string());
+                }
+                let _ = logger.log_entry(entry).await;
+            }
+        }
+    }
+
+    /// Get the database schema
+    pub fn schema(&self) -> SchemaRef {
+        self.schema.clone()
+    }
+
+    /// Schema of the table's latest version
+    ///
+    /// Unlike `schema()`, which is fixed when the table is opened, this
+    /// includes columns added by later writes.
+    pub async fn table_schema(&self) -> Result<SchemaRef> {
+        let table = self.get_delta_table().await?;
+        let snapshot = table.snapshot().map_err(Error::DeltaTable)?;
+        Self::delta_to_arrow_schema(&snapshot.schema())
+    }
+
+    /// Check whether rows following `input` could be inserted, without
+    /// writing anything
+    ///
+    /// Compares `input` with the table's latest schema column by column and
+    /// reports type mismatches, NOT NULL columns left out, and columns the
+    /// table lacks, which an insert's schema evolution would add. Lossy
+    /// conversions such as Float64 into Int32 are warnings. To check a batch,
+    /// pass its `schema()`.
+    pub async fn check_insert_compatibility(
+        &self,
+        input: &Schema,
+    ) -> Result<crate::metadata::CompatibilityReport> {
+        // Check read permission
+        self.check_permission(&crate::security::Permission::Read)?;
+
+        let table = self.table_schema().await?;
+        Ok(crate::metadata::check_compatibility(&table, input))
+    }
+
+    /// Insert a RecordBatch into the database
+    ///
+    /// Returns the version the insert committed. An empty batch commits
+    /// nothing and returns the current version.
+    pub async fn insert(&self, batch: RecordBatch) -> Result<CommitResult> {
+        info!("Inserting {} rows", batch.num_rows());
+
+        // Check write permission
+        self.check_permission(&crate::security::Permission::Write)?;
+
+        // Track metrics on completion (success or error)
+        let num_rows = batch.num_rows();
+        let meter = self.quota_meter().await;
+        let quota = meter.as_ref().map(|(guard, _)| guard);
+        let result = match self.complete_batch(batch).await {
+            Ok(batch) => {
+                let result = match quota.map(|quota| quota.check(0, num_rows as u64)) {
+                    Some(Err(e)) => Err(e),
+                    _ => self.insert_delta_native(batch.clone(), quota).await,
+                };
+                if result.is_ok() {
+                    self.merge_into_histograms(&batch);
+                }
+                result
+            }
+            Err(e) => Err(e),
+        };
+        match &result {
+            Ok(commit) => {
+                self.charge_quota(meter, commit.version);
+                self.metrics.total_inserts.fetch_add(1, Ordering::Relaxed);
+                self.metrics
+                    .total_transactions
+                    .fetch_add(1, Ordering::Relaxed);
+                self.audit_log(
+                    "INSERT",
+                    &format!("{} rows (version {})", num_rows, commit.version),
+                    true,
+                )
+                .await;
+                self.maybe_auto_compact().await;
+                self.maybe_checkpoint().await;
+                self.maybe_refresh_views().await;
+            }
+            Err(e) => {
+                self.metrics.total_errors.fetch_add(1, Ordering::Relaxed);
+                self.audit_log("INSERT", &format!("failed: {}", e), false)
+                    .await;
+            }
+        }
+        result
+    }
+
+    /// Fill in defaults, then generated columns, which may depend on them,
+    /// check the rows against the size limits and encrypt encrypted columns
+    async fn complete_batch(&self, batch: RecordBatch) -> Result<RecordBatch> {
+        let batch = crate::metadata::apply_defaults(batch, &self.schema).await?;
+        let batch = crate::metadata::apply_generated(batch, &self.schema).await?;
+        self.size_limits.check_batch(&batch)?;
+        self.column_encryption()?
+            .encrypt_batch(batch, self.column_keys.as_deref())
+    }
+
+    /// Insert data via write buffer (batches multiple small writes for performance)
+    ///
+    /// This method buffers writes and flushes automatically when thresholds are reached.
+    /// Use this for many small appends to reduce transaction overhead.
+    ///
+    /// The buffer accumulates batches and flushes when:
+    /// - Buffer exceeds max_rows threshold (default: 1000)
+    /// - One partition's rows exceed max_partition_rows (only that partition is flushed)
     /// - flush_write_buffer() is called explicitly
+    ///
+    /// Rows are grouped by partition, so a flush writes one file per partition.
+    /// Until then they're visible to queries on this `DatabaseOps` only.
     pub async fn insert_buffered(&self, batch: RecordBatch) -> Result<()> {
         let num_rows = batch.num_rows();
         info!("Buffering {} rows for insertion", num_rows);
@@ -696,6 +1627,9 @@ impl DatabaseOps {
         // Check write permission
         self.check_permission(&crate::security::Permission::Write)?;
 
+        // Fill defaults now so they reflect when the rows were inserted, not flushed
+        let batch = self.complete_batch(batch).await?;
+
         // Add batch to buffer and check if we should auto-flush
         let should_flush = self.batch_buffer.push(batch).await;
 
@@ -706,8 +1640,8 @@ impl DatabaseOps {
                 total_rows, batch_count
             );
 
-            // Take all batches and flush
-            let batches_to_flush = self.batch_buffer.take_all().await;
+            // Take the full buffer, or just the partitions that filled up
+            let batches_to_flush = self.batch_buffer.take_flushable().await;
             self.flush_batches(batches_to_flush).await?;
         }
 
@@ -716,8 +1650,9 @@ impl DatabaseOps {
 
     /// Flush the write buffer immediately
     ///
-    /// Forces any buffered writes to be committed to Delta Lake.
-    /// Call this before reading data to ensure consistency, or at shutdown.
+    /// Forces any buffered writes to be committed to Delta Lake. Queries on
+    /// this `DatabaseOps` already see buffered rows; call this so other
+    /// sessions do, or at shutdown.
     pub async fn flush_write_buffer(&self) -> Result<()> {
         if self.batch_buffer.is_empty().await {
             info!("No buffered data to flush");
@@ -739,6 +1674,26 @@ impl DatabaseOps {
         Ok(())
     }
 
+    /// Configure when `insert_buffered` flushes
+    ///
+    /// This replaces the write buffer, so call it before buffering any rows.
+    pub fn with_write_buffer_config(
+        mut self,
+        config: crate::batch_buffer::BatchBufferConfig,
+    ) -> Self {
+        let partition_columns = self.batch_buffer.partition_columns().to_vec();
+        self.batch_buffer = Arc::new(
+            crate::batch_buffer::BatchBuffer::with_config(config)
+                .with_partition_columns(partition_columns),
+        );
+        self
+    }
+
+    /// Number of rows waiting in the write buffer
+    pub async fn buffered_rows(&self) -> usize {
+        self.batch_buffer.stats().await.1
+    }
+
     /// Internal: Flush multiple batches efficiently
     /// Concatenates batches if possible and inserts in a single Delta Lake transaction
     async fn flush_batches(&self, batches: Vec<RecordBatch>) -> Result<()> {
@@ -762,99 +1717,184 @@ impl DatabaseOps {
         self.insert(concatenated).await.map(|_| ())
     }
 
-    /// Get the underlying Delta Lake table for advanced operations
+    /// Bulk load a CSV file into the table
     ///
-    /// Exposes the DeltaTable for version checking, history inspection, etc.
-    pub async fn get_delta_table(&self) -> Result<deltalake::DeltaTable> {
-        use crate::storage::s3::parse_s3_url;
-        use deltalake::{open_table, open_table_with_storage_options};
-        use url::Url;
+    /// Rows are coerced with `options.coercion` and committed every
+    /// `options.commit_rows` rows. If the load fails, the table is restored to
+    /// the version it had before the load started, so a failed load leaves no
+    /// partial data. With `continue_on_error`, rows that fail coercion are
+    /// written to a reject file instead of failing the load.
+    ///
+    /// gzip and zstd files are decompressed as they're read, recognised by
+    /// their content whatever they're named; a truncated or corrupt stream
+    /// fails the load with `Error::Decompression`.
+    pub async fn load_from_csv(
+        &self,
+        path: impl AsRef<Path>,
+        options: crate::bulk_load::LoadOptions,
+    ) -> Result<crate::bulk_load::LoadReport> {
+        let path = path.as_ref();
+        info!("Bulk loading CSV from {}", path.display());
 
-        let table = if let (Some(s3_url), Some(storage_options)) =
-            (&self.s3_url, &self.s3_storage_options)
-        {
-            // S3 backend
-            let s3_url_parsed = parse_s3_url(s3_url)?;
-            open_table_with_storage_options(s3_url_parsed, storage_options.clone())
-                .await
-                .map_err(Error::DeltaTable)?
-        } else {
-            // Local backend
-            let table_url = Url::from_directory_path(&self.base_path)
-                .map_err(|_| Error::Other("Invalid path for Delta table".to_string()))?;
-            open_table(table_url).await.map_err(Error::DeltaTable)?
-        };
+        // Check write permission
+        self.check_permission(&crate::security::Permission::Write)?;
 
-        Ok(table)
+        let start_version = self.get_delta_table().await?.version();
+        let result = crate::bulk_load::load_csv(self, path, &options).await;
+        self.finish_load("LOAD_CSV", path, start_version, result)
+            .await
     }
 
-    /// Insert data using Delta Lake native format
-    async fn insert_delta_native(&self, batch: RecordBatch) -> Result<u64> {
-        use crate::storage::s3::parse_s3_url;
-        use deltalake::operations::write::SchemaMode;
-        use deltalake::protocol::SaveMode;
-        use deltalake::{open_table, open_table_with_storage_options, DeltaOps};
-        use url::Url;
+    /// Export rows of the table to `writer` as CSV, NDJSON or Parquet
+    ///
+    /// Rows are read and encoded a batch at a time, and each batch is written
+    /// before the next is read, so a slow writer slows the export down rather
+    /// than the table being buffered in memory. `options` chooses the columns
+    /// and, with an SQL predicate, the rows; rows still in the write buffer
+    /// are included. The writer is flushed but not closed.
+    pub async fn export<W>(
+        &self,
+        writer: &mut W,
+        format: crate::export::ExportFormat,
+        options: crate::export::ExportOptions,
+    ) -> Result<crate::export::ExportReport>
+    where
+        W: tokio::io::AsyncWrite + Unpin + ?Sized,
+    {
+        info!("Exporting table as {:?}", format);
 
-        info!("Inserting {} rows to Delta Lake", batch.num_rows());
+        // Check read permission
+        self.check_permission(&crate::security::Permission::Read)?;
 
-        // Open the Delta Lake table (S3 or local)
-        let table = if let (Some(s3_url), Some(storage_options)) =
-            (&self.s3_url, &self.s3_storage_options)
-        {
-            // S3 backend
-            let s3_url_parsed = parse_s3_url(s3_url)?;
-            open_table_with_storage_options(s3_url_parsed, storage_options.clone())
-                .await
-                .map_err(Error::DeltaTable)?
-        } else {
-            // Local backend
-            let table_url = Url::from_directory_path(&self.base_path)
-                .map_err(|_| Error::Other("Invalid path for Delta table".to_string()))?;
-            open_table(table_url).await.map_err(Error::DeltaTable)?
-        };
+        match crate::export::export(self, writer, format, &options).await {
+            Ok(report) => {
+                self.audit_log(
+                    "EXPORT",
+                    &format!("{:?}: {} rows, {} bytes", format, report.rows, report.bytes),
+                    true,
+                )
+                .await;
+                Ok(report)
+            }
+            Err(e) => {
+                self.metrics.total_errors.fetch_add(1, Ordering::Relaxed);
+                self.audit_log("EXPORT", &format!("{:?}: {}", format, e), false)
+                    .await;
+                Err(e)
+            }
+        }
+    }
 
-        // Write the batch using DeltaOps with schema merging enabled for evolution
-        let row_count = batch.num_rows() as u64;
+    /// Bulk load a Parquet file into the table with default options
+    pub async fn load_from_parquet(
+        &self,
+        path: impl AsRef<Path>,
+    ) -> Result<crate::bulk_load::LoadReport> {
+        self.load_from_parquet_with_options(path, crate::bulk_load::LoadOptions::default())
+            .await
+    }
 
-        DeltaOps(table)
-            .write(vec![batch])
-            .with_save_mode(SaveMode::Append)
-            .with_schema_mode(SchemaMode::Merge)
+    /// Bulk load a Parquet file into the table
+    ///
+    /// Source columns are matched by name and cast to the table's types; the
+    /// rollback and reject behaviour matches `load_from_csv`.
+    pub async fn load_from_parquet_with_options(
+        &self,
+        path: impl AsRef<Path>,
+        options: crate::bulk_load::LoadOptions,
+    ) -> Result<crate::bulk_load::LoadReport> {
+        let path = path.as_ref();
+        info!("Bulk loading Parquet from {}", path.display());
+
+        // Check write permission
+        self.check_permission(&crate::security::Permission::Write)?;
+
+        let start_version = self.get_delta_table().await?.version();
+        let result = crate::bulk_load::load_parquet(self, path, &options).await;
+        self.finish_load("LOAD_PARQUET", path, start_version, result)
             .await
-            .map_err(Error::DeltaTable)?;
+    }
 
-        info!("Successfully wrote {} rows to Delta Lake", row_count);
+    /// Internal: audit a bulk load and roll it back if it failed
+    async fn finish_load(
+        &self,
+        operation: &str,
+        path: &Path,
+        start_version: Option<i64>,
+        result: Result<crate::bulk_load::LoadReport>,
+    ) -> Result<crate::bulk_load::LoadReport> {
+        match &result {
+            Ok(report) => {
+                self.audit_log(
+                    operation,
+                    &format!(
+                        "{}: {} rows loaded, {} rejected",
+                        path.display(),
+                        report.rows_loaded,
+                        report.rows_rejected
+                    ),
+                    true,
+                )
+                .await;
+            }
+            Err(e) => {
+                self.metrics.total_errors.fetch_add(1, Ordering::Relaxed);
+                if let Some(version) = start_version {
+                    // The load error is what the caller needs to see
+                    if let Err(rollback_err) = self.restore_version(version).await {
+                        tracing::error!(
+                            "Failed to roll back load of {}: {}",
+                            path.display(),
+                            rollback_err
+                        );
+                    }
+                }
+                self.audit_log(operation, &format!("{}: {}", path.display(), e), false)
+                    .await;
+            }
+        }
+        result
+    }
 
-        // Return a synthetic transaction ID (Delta Lake uses versions, not transaction IDs)
-        // We can use the current timestamp as a pseudo txn_id
-        let txn_id = std::time::SystemTime::now()
-            .duration_since(std::time::UNIX_EPOCH)
-            .unwrap()
-            .as_millis() as u64;
+    /// Internal: undo commits made after `version` by restoring it
+    ///
+    /// Restoring adds a new commit, so any other writes made since `version`
+    /// are undone as well.
+    async fn restore_version(&self, version: i64) -> Result<()> {
+        use deltalake::DeltaOps;
+
+        let table = self.get_delta_table().await?;
+        if table.version() == Some(version) {
+            return Ok(());
+        }
 
-        Ok(txn_id)
+        info!("Restoring table to version {}", version);
+        DeltaOps(table)
+            .restore()
+            .with_version_to_restore(version)
+            .await
+            .map_err(Error::DeltaTable)?;
+        Ok(())
     }
 
-    /// Query Delta Lake natively using DataFusion
-    async fn query_delta_native(&self, sql: &str) -> Result<Vec<RecordBatch>> {
+    /// Get the underlying Delta Lake table for advanced operations
+    ///
+    /// Exposes the DeltaTable for version checking, history inspection, etc.
+    pub async fn get_delta_table(&self) -> Result<deltalake::DeltaTable> {
+        self.retry_policy
+            .run("Open Delta table", || self.open_delta_table())
+            .await
+    }
+
+    /// Internal: open the Delta table once, without retrying
+    async fn open_delta_table(&self) -> Result<deltalake::DeltaTable> {
         use crate::storage::s3::parse_s3_url;
-        use deltalake::{
-            datafusion::prelude::SessionContext, open_table, open_table_with_storage_options,
-        };
+        use deltalake::open_table;
         use url::Url;
 
-        info!("Querying Delta Lake with SQL: {}", sql);
-
-        // Open the Delta Lake table (S3 or local)
-        let table = if let (Some(s3_url), Some(storage_options)) =
-            (&self.s3_url, &self.s3_storage_options)
-        {
+        let table = if let (Some(s3_url), Some(s3_config)) = (&self.s3_url, &self.s3_config) {
             // S3 backend
-            let s3_url_parsed = parse_s3_url(s3_url)?;
-            open_table_with_storage_options(s3_url_parsed, storage_options.clone())
-                .await
-                .map_err(Error::DeltaTable)?
+            s3_config.open_table(parse_s3_url(s3_url)?).await?
         } else {
             // Local backend
             let table_url = Url::from_directory_path(&self.base_path)
@@ -862,35 +1902,414 @@ impl DatabaseOps {
             open_table(table_url).await.map_err(Error::DeltaTable)?
         };
 
-        // Create DataFusion context and register the table
-        let ctx = SessionContext::new();
-        ctx.register_table("data", Arc::new(table))
-            .map_err(|e| Error::InvalidOperation(e.to_string()))?;
-
-        // Execute the SQL query
-        let df = ctx
-            .sql(sql)
-            .await
-            .map_err(|e| Error::InvalidOperation(e.to_string()))?;
+        Ok(table)
+    }
 
-        // Collect results
-        let batches = df
-            .collect()
+    /// Fail with `Error::UnsupportedDeltaFeature` if committing needs a Delta
+    /// feature FSDB doesn't implement, e.g. column mapping on a Spark table
+    ///
+    /// Only local logs are read directly; on S3 delta-rs's own protocol check applies.
+    fn check_writable(&self) -> Result<()> {
+        if self.s3_url.is_some() {
+            return Ok(());
+        }
+        crate::delta_lake::read_table_config(&self.base_path)?.check_writable()
+    }
+
+    /// Fail if the table asks for deletion vectors
+    ///
+    /// Deletes and updates always rewrite files, so such a table is refused
+    /// rather than rewritten against its owner's choice.
+    fn check_deletion_vectors(&self) -> Result<()> {
+        if self.deletion_vectors_enabled() {
+            return Err(Error::UnsupportedDeltaFeature(
+                "deletionVectors".to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Insert data using Delta Lake native format
+    ///
+    /// With a byte limit in `quota`, the files are checked against it before
+    /// they're committed, where `insert_files` can write them.
+    #[instrument(
+        name = "commit",
+        skip_all,
+        fields(
+            table = %self.table_label(),
+            operation = "INSERT",
+            version = tracing::field::Empty,
+            rows = tracing::field::Empty,
+        )
+    )]
+    async fn insert_delta_native(
+        &self,
+        batch: RecordBatch,
+        quota: Option<&crate::security::QuotaGuard>,
+    ) -> Result<CommitResult> {
+        use deltalake::operations::write::SchemaMode;
+        use deltalake::protocol::SaveMode;
+        use deltalake::DeltaOps;
+
+        info!("Inserting {} rows to Delta Lake", batch.num_rows());
+        self.check_writable()?;
+
+        // Write the batch using DeltaOps with schema merging enabled for evolution.
+        // A failed commit leaves only unreferenced files, so the whole
+        // open-write-commit sequence is retried.
+        let row_count = batch.num_rows() as u64;
+        if row_count == 0 {
+            info!("No rows to insert");
+            let table = self.get_delta_table().await?;
+            return CommitResult::at(&table, "INSERT", 0).await;
+        }
+
+        // Clustered tables get one file per key range
+        let batches = match self.clustering_key() {
+            Some(key) => {
+                let files = crate::delta_lake::get_file_statistics(&self.base_path)?;
+                crate::delta_lake::clustering::cluster_batch(&batch, &key, &files)?
+            }
+            None => vec![batch],
+        };
+        // and writes over the target file size one file per target-sized slice
+        let batches = match self.target_file_size {
+            Some(target) => batches
+                .iter()
+                .map(|batch| crate::delta_lake::split_to_target_size(batch, target))
+                .collect::<Result<Vec<_>>>()?
+                .concat(),
+            None => batches,
+        };
+
+        let limits_bytes = quota.is_some_and(|quota| quota.limit().max_bytes.is_some());
+        if self.file_naming.is_some()
+            || self.stats_config.is_some()
+            || batches.len() > 1
+            || limits_bytes
+        {
+            if let Some(table) = self
+                .insert_files(&batches, self.file_naming.as_ref(), quota)
+                .await?
+            {
+                info!("Successfully wrote {} rows to Delta Lake", row_count);
+                return CommitResult::at(&table, "INSERT", row_count).await;
+            }
+        }
+
+        let table = self
+            .retry_policy
+            .run("Delta write", || async {
+                let table = self.open_delta_table().await?;
+                let mut write = DeltaOps(table)
+                    .write(batches.clone())
+                    .with_save_mode(SaveMode::Append)
+                    .with_schema_mode(SchemaMode::Merge)
+                    .with_commit_properties(crate::delta_lake::commit::commit_properties());
+                if let Some(target) = self.target_file_size {
+                    write = write.with_target_file_size(target as usize);
+                }
+                write.await.map_err(Error::DeltaTable)
+            })
+            .await?;
+
+        info!("Successfully wrote {} rows to Delta Lake", row_count);
+        CommitResult::at(&table, "INSERT", row_count).await
+    }
+
+    /// Append each of `batches` in files of its own, named by `naming` if
+    /// given, or `None` to leave the write to delta-rs
+    ///
+    /// The files' statistics are trimmed to `stats_config`, if set. Files
+    /// that would take `quota`'s user past their limit are deleted rather
+    /// than committed.
+    ///
+    /// Only local tables whose columns match the batches' exactly are written
+    /// here; anything that needs schema evolution goes through delta-rs.
+    async fn insert_files(
+        &self,
+        batches: &[RecordBatch],
+        naming: Option<&crate::delta_lake::FileNaming>,
+        quota: Option<&crate::security::QuotaGuard>,
+    ) -> Result<Option<deltalake::DeltaTable>> {
+        use deltalake::kernel::transaction::CommitBuilder;
+        use deltalake::kernel::Action;
+        use deltalake::protocol::{DeltaOperation, SaveMode};
+        use deltalake::writer::{DeltaWriter, RecordBatchWriter};
+
+        if self.s3_url.is_some() {
+            return Ok(None);
+        }
+
+        self.retry_policy
+            .run("Delta write", || async {
+                let table = self.open_delta_table().await?;
+                let mut writer = RecordBatchWriter::for_table(&table).map_err(Error::DeltaTable)?;
+                let target = writer.arrow_schema();
+                let mut adds = Vec::new();
+                for batch in batches {
+                    if target.fields().len() != batch.num_columns() {
+                        return Ok(None);
+                    }
+                    let mut columns = Vec::with_capacity(target.fields().len());
+                    for field in target.fields() {
+                        let Some(column) = batch.column_by_name(field.name()) else {
+                            return Ok(None);
+                        };
+                        columns.push(arrow::compute::cast(column, field.data_type())?);
+                    }
+                    writer
+                        .write(RecordBatch::try_new(target.clone(), columns)?)
+                        .await
+                        .map_err(Error::DeltaTable)?;
+                    // Flushing after each batch keeps it in files of its own
+                    adds.extend(writer.flush().await.map_err(Error::DeltaTable)?);
+                }
+                if let Some(naming) = naming {
+                    let version = table.version().map_or(0, |v| v + 1);
+                    naming.rename(&self.base_path, &mut adds, version)?;
+                }
+                if let Some(stats_config) = &self.stats_config {
+                    stats_config.apply(&mut adds)?;
+                }
+                if let Some(quota) = quota {
+                    let bytes = adds.iter().map(|add| add.size as u64).sum();
+                    let rows = batches.iter().map(|b| b.num_rows() as u64).sum();
+                    if let Err(e) = quota.check(bytes, rows) {
+                        for add in &adds {
+                            let path = crate::metadata::snapshot::percent_decode(&add.path)?;
+                            let _ = std::fs::remove_file(self.base_path.join(path));
+                        }
+                        return Err(e);
+                    }
+                }
+
+                let snapshot = table.snapshot().map_err(Error::DeltaTable)?;
+                let partition_by = snapshot.metadata().partition_columns().clone();
+                CommitBuilder::default()
+                    .with_app_metadata(crate::delta_lake::commit::app_metadata())
+                    .with_actions(adds.into_iter().map(Action::Add).collect())
+                    .build(
+                        Some(snapshot),
+                        table.log_store(),
+                        DeltaOperation::Write {
+                            mode: SaveMode::Append,
+                            partition_by: (!partition_by.is_empty()).then_some(partition_by),
+                            predicate: None,
+                        },
+                    )
+                    .await
+                    .map_err(Error::DeltaTable)?;
+                Ok(Some(self.open_delta_table().await?))
+            })
             .await
+    }
+
+    /// Replace every row of the table with `batches`, in one commit
+    ///
+    /// Batches are cast to the table's schema by position.
+    pub(crate) async fn replace_contents(&self, batches: Vec<RecordBatch>) -> Result<CommitResult> {
+        use deltalake::protocol::SaveMode;
+        use deltalake::DeltaOps;
+
+        self.check_writable()?;
+        let mut batches = batches
+            .iter()
+            .map(|batch| crate::query::materialized::conform(batch, &self.schema))
+            .collect::<Result<Vec<_>>>()?;
+        if batches.is_empty() {
+            batches.push(RecordBatch::new_empty(self.schema.clone()));
+        }
+        let row_count = batches.iter().map(|b| b.num_rows() as u64).sum();
+
+        let table = self
+            .retry_policy
+            .run("Delta write", || async {
+                let table = self.open_delta_table().await?;
+                DeltaOps(table)
+                    .write(batches.clone())
+                    .with_save_mode(SaveMode::Overwrite)
+                    .with_commit_properties(crate::delta_lake::commit::commit_properties())
+                    .await
+                    .map_err(Error::DeltaTable)
+            })
+            .await?;
+        CommitResult::at(&table, "WRITE", row_count).await
+    }
+
+    /// Query Delta Lake natively using DataFusion
+    ///
+    /// Reads committed rows only, without the write buffer, and compares
+    /// strings byte by byte.
+    async fn query_delta_native(&self, sql: &str) -> Result<Vec<RecordBatch>> {
+        Ok(self
+            .query_delta_native_with_schema(
+                sql,
+                &[],
+                self.query_memory_limit,
+                false,
+                false,
+                Collation::Binary,
+            )
+            .await?
+            .1)
+    }
+
+    /// Query Delta Lake natively, also returning the result's schema
+    ///
+    /// The schema comes from the plan, so it's there even with no rows.
+    /// With `read_buffered`, rows in the write buffer are read as well,
+    /// unless `snapshots` pins `data` to an earlier version. With `masked`,
+    /// `data` is read with the user's column masks, as queries are; writes
+    /// planning their own queries read it in the clear.
+    #[instrument(
+        name = "query",
+        skip_all,
+        fields(table = %self.table_label(), version = tracing::field::Empty)
+    )]
+    async fn query_delta_native_with_schema(
+        &self,
+        sql: &str,
+        snapshots: &[crate::query::SnapshotRef],
+        memory_limit: Option<usize>,
+        read_buffered: bool,
+        masked: bool,
+        collation: Collation,
+    ) -> Result<(SchemaRef, Vec<RecordBatch>)> {
+        info!(
+            "Querying Delta Lake with SQL: {}",
+            self.log_redaction.redact(sql)
+        );
+
+        // Open the Delta Lake table (S3 or local), at a past version if pinned
+        let pinned = snapshots.iter().find(|s| s.name == "data");
+        let table = match pinned {
+            Some(snapshot) => self.snapshot_table(snapshot).await?,
+            None => self.get_delta_table().await?,
+        };
+        tracing::Span::current().record("version", table.version().unwrap_or(0));
+
+        // Create DataFusion context and register the table
+        let ctx = self.session_context_with_memory_limit(memory_limit);
+        let mut provider = if read_buffered && pinned.is_none() {
+            self.with_buffered_rows(&ctx, table).await?
+        } else {
+            Arc::new(table)
+        };
+        if masked {
+            provider = self.mask_table(&ctx, &self.table_name(), provider)?;
+        }
+        ctx.register_table("data", provider)
             .map_err(|e| Error::InvalidOperation(e.to_string()))?;
+        self.register_catalog(&ctx).await?;
+        self.register_snapshots(&ctx, snapshots).await?;
+
+        // Execute the SQL query
+        let df = crate::query::identifiers::plan_sql_with_collation(
+            &ctx,
+            sql,
+            self.identifier_case,
+            collation,
+        )
+        .await?;
+        let schema = Arc::new(df.schema().as_arrow().clone());
+
+        // Collect results
+        let batches = df
+            .collect()
+            .await
+            .map_err(|e| self.execution_error(e, memory_limit))?;
 
         info!("Query returned {} batches", batches.len());
-        Ok(batches)
+        Ok((schema, batches))
+    }
+
+    /// Run `sql` and stream its result batch by batch
+    ///
+    /// Like `query`, rows in the write buffer are read as well, and time
+    /// travel, named tables, encryption and column masks all apply. Batches
+    /// are produced as the stream is polled, under the query memory limit,
+    /// so a result larger than memory can be processed without
+    /// materializing it; `QueryStream::next_rows` reads it in pages instead.
+    /// Errors while planning are returned here, errors while running from
+    /// the stream.
+    pub async fn query_stream(&self, sql: &str) -> Result<crate::query::QueryStream> {
+        let result = self.stream_sql(sql).await;
+        match &result {
+            Ok(_) => {
+                self.metrics.total_queries.fetch_add(1, Ordering::Relaxed);
+                self.audit_log("SELECT_STREAM", sql, true).await;
+            }
+            Err(e) => {
+                self.metrics.total_errors.fetch_add(1, Ordering::Relaxed);
+                self.audit_log("SELECT_STREAM", &format!("{}: {}", sql, e), false)
+                    .await;
+            }
+        }
+        result.map(crate::query::QueryStream::new)
+    }
+
+    /// Plan `sql` and start streaming its result, without auditing it
+    ///
+    /// Used by the NFS views and exports, which read the table on the
+    /// user's behalf many times over.
+    pub(crate) async fn stream_sql(
+        &self,
+        sql: &str,
+    ) -> Result<datafusion::physical_plan::SendableRecordBatchStream> {
+        info!("Streaming query: {}", self.log_redaction.redact(sql));
+        self.check_permission(&crate::security::Permission::Read)?;
+        let (sql, snapshots) = crate::query::time_travel::rewrite_time_travel(sql)?;
+        let sql = self.encrypt_sql(&sql)?;
+
+        let table = self.get_delta_table().await?;
+        let ctx = self.session_context_with_memory_limit(self.query_memory_limit);
+        let provider = self.with_buffered_rows(&ctx, table).await?;
+        let provider = self.mask_table(&ctx, &self.table_name(), provider)?;
+        ctx.register_table("data", provider)
+            .map_err(|e| Error::InvalidOperation(e.to_string()))?;
+        self.register_catalog(&ctx).await?;
+        self.register_snapshots(&ctx, &snapshots).await?;
+        let df = crate::query::identifiers::plan_sql_with_collation(
+            &ctx,
+            &sql,
+            self.identifier_case,
+            self.collation,
+        )
+        .await?;
+        let stream = df
+            .execute_stream()
+            .await
+            .map_err(|e| self.execution_error(e, self.query_memory_limit))?;
+        self.decrypt_stream(stream)
     }
 
     /// Delete rows from Delta Lake using native DELETE operation
-    async fn delete_delta_native(&self, where_clause: &str) -> Result<usize> {
-        use deltalake::{open_table, DeltaOps};
-        use url::Url;
+    #[instrument(
+        name = "commit",
+        skip_all,
+        fields(
+            table = %self.table_label(),
+            operation = "DELETE",
+            version = tracing::field::Empty,
+            rows = tracing::field::Empty,
+        )
+    )]
+    async fn delete_delta_native(&self, where_clause: &str) -> Result<CommitResult> {
+        use deltalake::DeltaOps;
 
-        info!("Deleting from Delta Lake where: {}", where_clause);
+        info!(
+            "Deleting from Delta Lake where: {}",
+            self.log_redaction.redact(where_clause)
+        );
+        self.check_writable()?;
+        self.check_deletion_vectors()?;
+        let encrypted = self.encrypt_predicate(where_clause)?;
+        let where_clause = encrypted.as_str();
 
-        // First count how many rows will be deleted
+        // Count matching rows first so a predicate that matches nothing
+        // returns without committing an empty Delta version
         let count_query = format!("SELECT COUNT(*) as count FROM data WHERE {}", where_clause);
         let count_results = self.query_delta_native(&count_query).await?;
         let deleted_count = if !count_results.is_empty() {
@@ -906,27 +2325,188 @@ impl DatabaseOps {
 
         if deleted_count == 0 {
             info!("No rows match deletion criteria");
-            return Ok(0);
+            let table = self.get_delta_table().await?;
+            return CommitResult::at(&table, "DELETE", 0).await;
         }
 
-        // Open the Delta Lake table
-        let table_url = Url::from_directory_path(&self.base_path)
-            .map_err(|_| Error::Other("Invalid path for Delta table".to_string()))?;
-
-        let table = open_table(table_url).await.map_err(Error::DeltaTable)?;
+        // Files the predicate covers entirely are dropped without being read
+        if let Some(result) = self.delete_by_file(where_clause).await? {
+            return Ok(result);
+        }
 
-        // Execute DELETE operation
-        DeltaOps(table)
-            .delete()
-            .with_predicate(where_clause)
-            .await
-            .map_err(Error::DeltaTable)?;
+        // Execute DELETE operation. The metrics are authoritative: rows may have
+        // changed between the COUNT above and the commit.
+        let (table, metrics) = self
+            .retry_policy
+            .run("Delta delete", || async {
+                let table = self.open_delta_table().await?;
+                DeltaOps(table)
+                    .delete()
+                    .with_predicate(where_clause)
+                    .with_commit_properties(crate::delta_lake::commit::commit_properties())
+                    .await
+                    .map_err(Error::DeltaTable)
+            })
+            .await?;
+        let deleted_count = metrics.num_deleted_rows;
 
         info!(
             "Successfully deleted {} rows from Delta Lake",
             deleted_count
         );
-        Ok(deleted_count)
+        CommitResult::at(&table, "DELETE", deleted_count as u64).await
+    }
+
+    /// Delete by classifying files on their statistics, or `None` to leave
+    /// the delete to delta-rs
+    ///
+    /// Files the predicate can't match are untouched, files it matches
+    /// entirely are removed from the log without being read, and the rest are
+    /// rewritten without the matching rows, all in one commit. This only
+    /// applies when the predicate is a conjunction `parse_conjunction`
+    /// understands, the table is local, unpartitioned and without column
+    /// mapping or a change data feed, and at least one file is matched
+    /// entirely; otherwise delta-rs, which reads every candidate file and
+    /// writes the feed's change data, is no worse. FSDB tables don't
+    /// support deletion vectors, so partially matched files are rewritten.
+    async fn delete_by_file(&self, where_clause: &str) -> Result<Option<CommitResult>> {
+        use crate::delta_lake::{file_match, get_file_statistics, parse_conjunction, FileMatch};
+        use deltalake::kernel::transaction::CommitBuilder;
+        use deltalake::kernel::{Action, Remove};
+        use deltalake::protocol::DeltaOperation;
+        use deltalake::writer::{DeltaWriter, RecordBatchWriter};
+
+        if self.s3_url.is_some() {
+            return Ok(None);
+        }
+        let Some(predicates) = parse_conjunction(where_clause) else {
+            return Ok(None);
+        };
+        let config = crate::delta_lake::read_table_config(&self.base_path)?;
+        if !config.partition_columns.is_empty()
+            || config.column_mapping_mode() != "none"
+            || config.is_change_data_feed_enabled()
+        {
+            return Ok(None);
+        }
+
+        let result = self
+            .retry_policy
+            .run("Delta delete", || async {
+                let table = self.open_delta_table().await?;
+                let files = get_file_statistics(&self.base_path)?;
+                let (matched, partial): (Vec<_>, Vec<_>) = files
+                    .into_iter()
+                    .map(|file| (file_match(&file, &predicates), file))
+                    .filter(|(class, _)| *class != FileMatch::None)
+                    .partition(|(class, _)| *class == FileMatch::All);
+                if matched.is_empty() {
+                    return Ok(None);
+                }
+                info!(
+                    "Dropping {} whole files, rewriting {}",
+                    matched.len(),
+                    partial.len()
+                );
+
+                let now = chrono::Utc::now().timestamp_millis();
+                let remove = |file: &crate::delta_lake::FileStats| {
+                    Action::Remove(Remove {
+                        path: file.path.clone(),
+                        deletion_timestamp: Some(now),
+                        data_change: true,
+                        size: Some(file.size_bytes as i64),
+                        ..Default::default()
+                    })
+                };
+
+                let mut deleted = matched.iter().map(|(_, f)| f.num_records).sum::<u64>();
+                let mut actions: Vec<Action> = matched.iter().map(|(_, f)| remove(f)).collect();
+
+                let mut writer = RecordBatchWriter::for_table(&table).map_err(Error::DeltaTable)?;
+                let target = writer.arrow_schema();
+                for (_, file) in &partial {
+                    let (kept, removed) = self.filter_file(&file.path, where_clause).await?;
+                    if removed == 0 {
+                        continue;
+                    }
+                    for batch in kept {
+                        let columns = target
+                            .fields()
+                            .iter()
+                            .map(|field| {
+                                let column =
+                                    batch.column_by_name(field.name()).ok_or_else(|| {
+                                        Error::SchemaMismatch(format!(
+                                            "{} has no column {}",
+                                            file.path,
+                                            field.name()
+                                        ))
+                                    })?;
+                                Ok(arrow::compute::cast(column, field.data_type())?)
+                            })
+                            .collect::<Result<Vec<_>>>()?;
+                        writer
+                            .write(RecordBatch::try_new(target.clone(), columns)?)
+                            .await
+                            .map_err(Error::DeltaTable)?;
+                    }
+                    deleted += removed;
+                    actions.push(remove(file));
+                }
+                let adds = writer.flush().await.map_err(Error::DeltaTable)?;
+                actions.extend(adds.into_iter().map(Action::Add));
+
+                let snapshot = table.snapshot().map_err(Error::DeltaTable)?;
+                CommitBuilder::default()
+                    .with_app_metadata(crate::delta_lake::commit::app_metadata())
+                    .with_actions(actions)
+                    .build(
+                        Some(snapshot),
+                        table.log_store(),
+                        DeltaOperation::Delete {
+                            predicate: Some(where_clause.to_string()),
+                        },
+                    )
+                    .await
+                    .map_err(Error::DeltaTable)?;
+                Ok(Some(deleted))
+            })
+            .await?;
+
+        let Some(deleted) = result else {
+            return Ok(None);
+        };
+        info!("Successfully deleted {} rows from Delta Lake", deleted);
+        let table = self.get_delta_table().await?;
+        Ok(Some(CommitResult::at(&table, "DELETE", deleted).await?))
+    }
+
+    /// Read one data file and drop the rows matching `where_clause`,
+    /// returning the remaining rows and how many were dropped
+    async fn filter_file(&self, path: &str, where_clause: &str) -> Result<(Vec<RecordBatch>, u64)> {
+        let batches = self.read_file(path).await?;
+        let total: usize = batches.iter().map(|b| b.num_rows()).sum();
+
+        let ctx = self.session_context();
+        let schema = batches[0].schema();
+        let table = datafusion::datasource::MemTable::try_new(schema, vec![batches])
+            .map_err(|e| Error::InvalidOperation(e.to_string()))?;
+        ctx.register_table("data", Arc::new(table))
+            .map_err(|e| Error::InvalidOperation(e.to_string()))?;
+
+        // A row whose predicate is NULL doesn't match, so it's kept
+        let sql = format!(
+            "SELECT * FROM data WHERE NOT COALESCE(({}), false)",
+            where_clause
+        );
+        let kept = crate::query::identifiers::plan_sql(&ctx, &sql, self.identifier_case)
+            .await?
+            .collect()
+            .await
+            .map_err(|e| Error::InvalidOperation(e.to_string()))?;
+        let remaining: usize = kept.iter().map(|b| b.num_rows()).sum();
+        Ok((kept, (total - remaining) as u64))
     }
 
     /// Compute a unified schema from all batches to handle schema evolution
@@ -1021,110 +2601,207 @@ impl DatabaseOps {
         Ok(unified_batch)
     }
 
+    /// `table` with the rows waiting in this session's write buffer added
+    ///
+    /// This gives a session read-your-writes: queries on this `DatabaseOps`
+    /// see rows from `insert_buffered` before they're flushed, while other
+    /// sessions see them once they're committed. Deletes, updates and merges
+    /// flush the buffer before they run, so they apply to buffered rows too.
+    async fn with_buffered_rows(
+        &self,
+        ctx: &datafusion::prelude::SessionContext,
+        table: deltalake::DeltaTable,
+    ) -> Result<Arc<dyn datafusion::catalog::TableProvider>> {
+        let buffered = self.batch_buffer.snapshot().await;
+        if buffered.is_empty() {
+            return Ok(Arc::new(table));
+        }
+
+        // Buffered batches may predate or follow schema evolution
+        let unified_schema = self.compute_unified_schema(&buffered)?;
+        let buffered = buffered
+            .iter()
+            .map(|batch| self.unify_batch_schema(batch, &unified_schema))
+            .collect::<Result<Vec<_>>>()?;
+        let buffered = datafusion::datasource::MemTable::try_new(unified_schema, vec![buffered])?;
+
+        // Columns are matched by name; ones missing on either side are NULL
+        let overlay = ctx
+            .read_table(Arc::new(table))?
+            .union_by_name(ctx.read_table(Arc::new(buffered))?)?;
+        Ok(overlay.into_view())
+    }
+
     /// Query the database using SQL
     pub async fn query(&self, sql: &str) -> Result<Vec<RecordBatch>> {
-        info!("Executing query: {}", sql);
+        Ok(self
+            .query_with_schema(sql, None, self.query_memory_limit, self.collation)
+            .await?
+            .1)
+    }
+
+    /// Check `sql` against the table without running it, returning its output schema
+    ///
+    /// The statement is parsed and planned against the current schema,
+    /// including columns added by schema evolution that older files lack, so
+    /// unknown tables and columns are caught. Only the Delta log is read.
+    /// The first problem is returned as `Error::InvalidSql`, with its line
+    /// and column when known.
+    pub async fn validate(&self, sql: &str) -> Result<SchemaRef> {
+        info!("Validating query: {}", self.log_redaction.redact(sql));
 
         // Check read permission
         self.check_permission(&crate::security::Permission::Read)?;
 
-        // Track query metrics with latency
-        let start = Instant::now();
-        let result = self.query_inner(sql).await;
-        let latency_ms = start.elapsed().as_secs_f64() * 1000.0;
+        let (sql, snapshots) = crate::query::time_travel::rewrite_time_travel(sql)?;
+        let table = self.get_delta_table().await?;
+        let ctx = self.session_context();
+        ctx.register_table("data", Arc::new(table))
+            .map_err(|e| Error::InvalidOperation(e.to_string()))?;
+        self.register_catalog(&ctx).await?;
+        self.register_snapshots(&ctx, &snapshots).await?;
+        crate::query::validate_sql(&ctx, &sql, self.identifier_case).await
+    }
 
-        match &result {
-            Ok(_) => {
-                self.metrics.total_queries.fetch_add(1, Ordering::Relaxed);
-                let mut latencies = self.metrics.query_latencies.lock().await;
-                latencies.push(latency_ms);
-                // Keep only last 1000 latencies to prevent unbounded growth
-                let len = latencies.len();
-                if len > 1000 {
-                    latencies.drain(0..len - 1000);
-                }
-                self.audit_log("SELECT", sql, true).await;
-            }
-            Err(e) => {
-                self.metrics.total_errors.fetch_add(1, Ordering::Relaxed);
-                self.audit_log("SELECT", &format!("{}: {}", sql, e), false)
-                    .await;
-            }
-        }
+    /// Describe how `sql` would run, without running it (`EXPLAIN`)
+    ///
+    /// The plan is DataFusion's physical plan, and the scan section shows
+    /// which files data skipping leaves to read.
+    pub async fn explain(&self, sql: &str) -> Result<crate::query::QueryPlan> {
+        info!("Explaining query: {}", self.log_redaction.redact(sql));
+
+        // Check read permission
+        self.check_permission(&crate::security::Permission::Read)?;
+
+        let result = self.explain_inner(sql, false).await;
+        self.audit_explain("EXPLAIN", sql, &result).await;
         result
     }
 
-    /// Internal query method - delegates to Delta Lake native query with data skipping
-    async fn query_inner(&self, sql: &str) -> Result<Vec<RecordBatch>> {
-        // Extract predicates from SQL query
-        let predicates = crate::delta_lake::data_skipping::extract_predicates(sql);
+    /// Run `sql` and report how it ran (`EXPLAIN ANALYZE`)
+    ///
+    /// The result rows are discarded. The plan is annotated with each
+    /// operator's rows and timings, and the runtime section has the totals:
+    /// rows scanned and returned, files opened and peak memory. A query that
+    /// fails while running still returns the metrics collected so far, with
+    /// the failure in `RuntimeStats::error`; one that fails to plan returns
+    /// the error.
+    pub async fn explain_analyze(&self, sql: &str) -> Result<crate::query::QueryPlan> {
+        info!("Analyzing query: {}", self.log_redaction.redact(sql));
 
-        // Get file-level statistics from Delta Lake transaction log
-        let file_stats = crate::delta_lake::data_skipping::get_file_statistics(&self.base_path)?;
+        // Check read permission
+        self.check_permission(&crate::security::Permission::Read)?;
 
-        // Evaluate which files can be skipped
-        let mut files_to_read = Vec::new();
-        let mut files_to_skip = Vec::new();
+        let result = self.explain_inner(sql, true).await;
+        self.audit_explain("EXPLAIN ANALYZE", sql, &result).await;
+        result
+    }
 
-        for file_stat in &file_stats {
-            let mut can_skip = false;
-
-            // Check if file can be skipped based on predicates
-            for (column, operator, value) in &predicates {
-                if crate::delta_lake::data_skipping::can_skip_file(
-                    file_stat, column, operator, value,
-                ) {
-                    can_skip = true;
-                    break;
-                }
-            }
+    async fn explain_inner(&self, sql: &str, analyze: bool) -> Result<crate::query::QueryPlan> {
+        let predicates = self.skipping_predicates(sql);
+        let scan = self.scan_stats(&predicates, self.collation)?;
 
-            if can_skip {
-                files_to_skip.push(file_stat.clone());
-            } else {
-                files_to_read.push(file_stat.clone());
-            }
+        let ctx = self.session_context();
+        let table = self.with_buffered_rows(&ctx, self.get_delta_table().await?);
+        ctx.register_table("data", table.await?)
+            .map_err(|e| Error::InvalidOperation(e.to_string()))?;
+        self.register_catalog(&ctx).await?;
+        let df = crate::query::identifiers::plan_sql_with_collation(
+            &ctx,
+            sql,
+            self.identifier_case,
+            self.collation,
+        )
+        .await?;
+        let plan = df
+            .create_physical_plan()
+            .await
+            .map_err(|e| self.execution_error(e, self.query_memory_limit))?;
+
+        if !analyze {
+            return Ok(crate::query::QueryPlan {
+                plan: crate::query::explain::describe(&plan),
+                scan,
+                runtime: None,
+            });
         }
+        let (annotated, runtime) = crate::query::explain::analyze(plan, ctx.task_ctx()).await;
+        Ok(crate::query::QueryPlan {
+            plan: annotated,
+            scan,
+            runtime: Some(runtime),
+        })
+    }
 
-        // Update data skipping statistics
-        {
-            let mut stats = self.data_skipping_stats.lock().await;
-            stats.total_files = file_stats.len();
-            stats.files_read = files_to_read.len();
-            stats.files_skipped = files_to_skip.len();
-            stats.bytes_scanned = files_to_read.iter().map(|f| f.size_bytes).sum();
-            stats.bytes_skipped = files_to_skip.iter().map(|f| f.size_bytes).sum();
+    async fn audit_explain(
+        &self,
+        operation: &str,
+        sql: &str,
+        result: &Result<crate::query::QueryPlan>,
+    ) {
+        let failure = match result {
+            Ok(plan) => plan
+                .runtime
+                .as_ref()
+                .and_then(|r| r.error.as_ref())
+                .cloned(),
+            Err(e) => Some(e.to_string()),
+        };
+        match failure {
+            None => self.audit_log(operation, sql, true).await,
+            Some(e) => {
+                self.audit_log(operation, &format!("{}: {}", sql, e), false)
+                    .await
+            }
         }
+    }
 
-        info!(
-            "Data skipping: {} files total, {} to read, {} skipped",
-            file_stats.len(),
-            files_to_read.len(),
-            files_to_skip.len()
-        );
-
-        // Execute query (Delta Lake + DataFusion will also do its own pruning)
-        self.query_delta_native(sql).await
+    /// Query the database using SQL within a memory limit of `max_bytes`
+    ///
+    /// Overrides the limit set with `with_query_memory_limit` for this query
+    /// only. Aggregations and sorts spill to the spill directory once they
+    /// run out of memory; operators that can't spill, such as the build side
+    /// of a hash join, fail with `Error::QueryMemoryExceeded`.
+    pub async fn query_with_memory_limit(
+        &self,
+        sql: &str,
+        max_bytes: usize,
+    ) -> Result<Vec<RecordBatch>> {
+        Ok(self
+            .query_with_schema(sql, None, Some(max_bytes), self.collation)
+            .await?
+            .1)
     }
 
-    /// Query the database at a specific Delta Lake version (time travel)
+    /// Combine `sql` on this table with `other_sql` on `other`'s table
     ///
-    /// This allows querying historical data without affecting the current state.
-    /// Version numbers start at 0 (first commit).
-    pub async fn query_version(&self, sql: &str, version: i64) -> Result<Vec<RecordBatch>> {
+    /// Each query sees its own table as `data`. Columns are matched by
+    /// position, take the names of this query's columns, and are cast to a
+    /// common type (see `crate::query::union`); mismatched branches fail with
+    /// `Error::UnionMismatch`. The union runs under this database's query
+    /// memory limit, so deduplication spills rather than growing unbounded.
+    pub async fn query_union(
+        &self,
+        sql: &str,
+        other: &DatabaseOps,
+        other_sql: &str,
+        mode: crate::query::UnionMode,
+    ) -> Result<Vec<RecordBatch>> {
         info!(
-            "Executing time travel query at version {}: {}",
-            version, sql
+            "Executing union: {} / {}",
+            self.log_redaction.redact(sql),
+            other.log_redaction.redact(other_sql)
         );
 
-        // Check read permission
+        // Check read permission on both tables
         self.check_permission(&crate::security::Permission::Read)?;
+        other.check_permission(&crate::security::Permission::Read)?;
 
-        // Track query metrics with latency
         let start = Instant::now();
-        let result = self.query_version_inner(sql, version).await;
+        let result = self.query_union_inner(sql, other, other_sql, mode).await;
         let latency_ms = start.elapsed().as_secs_f64() * 1000.0;
 
+        let description = format!("{} UNION {:?} {}", sql, mode, other_sql);
         match &result {
             Ok(_) => {
                 self.metrics.total_queries.fetch_add(1, Ordering::Relaxed);
@@ -1134,666 +2811,4351 @@ impl DatabaseOps {
                 if len > 1000 {
                     latencies.drain(0..len - 1000);
                 }
-                self.audit_log("SELECT_VERSION", &format!("v{}: {}", version, sql), true)
-                    .await;
+                self.audit_log("SELECT", &description, true).await;
             }
             Err(e) => {
                 self.metrics.total_errors.fetch_add(1, Ordering::Relaxed);
-                self.audit_log(
-                    "SELECT_VERSION",
-                    &format!("v{}: {}: {}", version, sql, e),
-                    false,
-                )
-                .await;
+                self.audit_log("SELECT", &format!("{}: {}", description, e), false)
+                    .await;
             }
         }
         result
     }
 
-    /// Internal time travel query by version
-    async fn query_version_inner(&self, sql: &str, version: i64) -> Result<Vec<RecordBatch>> {
-        use deltalake::datafusion::prelude::SessionContext;
-        use tracing::error;
-        use url::Url;
-
-        info!(
-            "Querying Delta Lake with SQL at version {}: {}",
-            version, sql
-        );
+    async fn query_union_inner(
+        &self,
+        sql: &str,
+        other: &DatabaseOps,
+        other_sql: &str,
+        mode: crate::query::UnionMode,
+    ) -> Result<Vec<RecordBatch>> {
+        let memory_limit = self.query_memory_limit;
 
-        // Open table at specific version
-        let table = if let Some(ref s3_url) = self.s3_url {
-            if let Some(ref storage_options) = self.s3_storage_options {
-                let url = Url::parse(s3_url)
-                    .map_err(|e| Error::Other(format!("Invalid S3 URL: {}", e)))?;
-
-                let builder = deltalake::DeltaTableBuilder::from_uri(url)
-                    .map_err(|e| {
-                        error!("Failed to create Delta table builder from S3 URL: {}", e);
-                        Error::DeltaTable(e)
-                    })?
-                    .with_storage_options(storage_options.clone())
-                    .with_version(version);
-
-                builder.load().await.map_err(|e| {
-                    error!(
-                        "Failed to load Delta table at version {} from S3: {}",
-                        version, e
-                    );
-                    Error::DeltaTable(e)
-                })?
-            } else {
-                return Err(Error::Other(
-                    "S3 storage options not configured".to_string(),
-                ));
+        let ctx = self.session_context_with_memory_limit(memory_limit);
+        let table = self.with_buffered_rows(&ctx, self.get_delta_table().await?);
+        let table = self.mask_table(&ctx, &self.table_name(), table.await?)?;
+        ctx.register_table("data", table)
+            .map_err(|e| Error::InvalidOperation(e.to_string()))?;
+        self.register_catalog(&ctx).await?;
+        let left = crate::query::identifiers::plan_sql(&ctx, sql, self.identifier_case).await?;
+
+        // The other table is planned in its own context, under the same name
+        let other_ctx = other.session_context();
+        let other_table = other.with_buffered_rows(&other_ctx, other.get_delta_table().await?);
+        let other_table = other.mask_table(&other_ctx, &other.table_name(), other_table.await?)?;
+        other_ctx
+            .register_table("data", other_table)
+            .map_err(|e| Error::InvalidOperation(e.to_string()))?;
+        other.register_catalog(&other_ctx).await?;
+        let right =
+            crate::query::identifiers::plan_sql(&other_ctx, other_sql, other.identifier_case)
+                .await?;
+
+        // The union runs in this table's context, with its memory pool
+        crate::query::union::union(left, right, mode)?
+            .collect()
+            .await
+            .map_err(|e| self.execution_error(e, memory_limit))
+    }
+
+    /// Query the database using SQL, returning rows of typed values
+    ///
+    /// Unlike record batches the result is easy to walk row by row, and unlike
+    /// `query_csv` nothing goes through text: decimals keep their scale and
+    /// timestamps their time zone.
+    pub async fn query_rows(&self, sql: &str) -> Result<ResultSet> {
+        self.query_rows_with_collation(sql, self.collation).await
+    }
+
+    /// Like `query_rows`, comparing strings under `collation` instead of the
+    /// table's collation set with `with_collation`
+    pub async fn query_rows_with_collation(
+        &self,
+        sql: &str,
+        collation: Collation,
+    ) -> Result<ResultSet> {
+        let (schema, batches) = self
+            .query_with_schema(sql, None, self.query_memory_limit, collation)
+            .await?;
+        ResultSet::from_batches(schema, &batches)
+    }
+
+    /// Query the database using SQL, returning the result as CSV text with a header
+    pub async fn query_csv(&self, sql: &str) -> Result<String> {
+        let (schema, batches) = self
+            .query_with_schema(sql, None, self.query_memory_limit, self.collation)
+            .await?;
+        let mut buffer = Vec::new();
+        {
+            let mut writer = arrow::csv::Writer::new(&mut buffer);
+            if batches.is_empty() {
+                writer.write(&RecordBatch::new_empty(schema))?;
             }
-        } else {
-            let table_path = self
-                .base_path
-                .to_str()
-                .ok_or_else(|| Error::Other("Invalid path".to_string()))?;
-
-            let url = Url::from_file_path(table_path)
-                .map_err(|_| Error::Other(format!("Invalid file path: {}", table_path)))?;
-
-            deltalake::DeltaTableBuilder::from_uri(url)
-                .map_err(|e| {
-                    error!("Failed to create Delta table builder from path: {}", e);
-                    Error::DeltaTable(e)
-                })?
-                .with_version(version)
-                .load()
-                .await
-                .map_err(|e| {
-                    error!("Failed to load Delta table at version {}: {}", version, e);
-                    Error::DeltaTable(e)
-                })?
-        };
+            for batch in &batches {
+                writer.write(batch)?;
+            }
+        }
+        String::from_utf8(buffer).map_err(|e| Error::Other(e.to_string()))
+    }
 
-        // Create DataFusion context and register table
-        let ctx = SessionContext::new();
-        ctx.register_table("data", Arc::new(table))?;
+    /// Run several queries against one snapshot of the table
+    ///
+    /// The table is loaded once and every query reads that version, so the
+    /// results are consistent with each other even if the table is written
+    /// meanwhile. Each query has its own result: one that fails doesn't stop
+    /// the rest. The batch as a whole fails only if the table can't be read
+    /// at all.
+    pub async fn query_batch(&self, queries: Vec<String>) -> Result<Vec<Result<ResultSet>>> {
+        let queries = queries.into_iter().map(|sql| (sql, Vec::new())).collect();
+        self.query_batch_with_params(queries).await
+    }
 
-        // Execute query
-        let df = ctx.sql(sql).await?;
-        let batches = df.collect().await?;
+    /// Like `query_batch`, with values bound to each query's `$1`, `$2`, ...
+    /// placeholders
+    pub async fn query_batch_with_params(
+        &self,
+        queries: Vec<(String, Vec<crate::query::Value>)>,
+    ) -> Result<Vec<Result<ResultSet>>> {
+        if queries.is_empty() {
+            return Ok(Vec::new());
+        }
+        info!("Executing batch of {} queries", queries.len());
 
-        info!(
-            "Time travel query at version {} returned {} batches",
-            version,
-            batches.len()
-        );
-        Ok(batches)
+        // Check read permission
+        self.check_permission(&crate::security::Permission::Read)?;
+
+        // One snapshot and context shared by the whole batch
+        let memory_limit = self.query_memory_limit;
+        let ctx = self.session_context_with_memory_limit(memory_limit);
+        let table = self.with_buffered_rows(&ctx, self.get_delta_table().await?);
+        let table = self.mask_table(&ctx, &self.table_name(), table.await?)?;
+        ctx.register_table("data", table)
+            .map_err(|e| Error::InvalidOperation(e.to_string()))?;
+        self.register_catalog(&ctx).await?;
+
+        let mut results = Vec::with_capacity(queries.len());
+        for (sql, params) in queries {
+            info!(
+                "Executing batched query: {}",
+                self.log_redaction.redact(&sql)
+            );
+            let start = Instant::now();
+            let result = self
+                .run_batched_query(&ctx, &sql, params, memory_limit)
+                .await;
+            let latency_ms = start.elapsed().as_secs_f64() * 1000.0;
+
+            match &result {
+                Ok(_) => {
+                    self.metrics.total_queries.fetch_add(1, Ordering::Relaxed);
+                    let mut latencies = self.metrics.query_latencies.lock().await;
+                    latencies.push(latency_ms);
+                    let len = latencies.len();
+                    if len > 1000 {
+                        latencies.drain(0..len - 1000);
+                    }
+                    self.audit_log("SELECT", &sql, true).await;
+                }
+                Err(e) => {
+                    self.metrics.total_errors.fetch_add(1, Ordering::Relaxed);
+                    self.audit_log("SELECT", &format!("{}: {}", sql, e), false)
+                        .await;
+                }
+            }
+            results.push(result);
+        }
+        Ok(results)
     }
 
-    /// Query the database at a specific timestamp (time travel)
+    /// Plan and run one query of a batch in the batch's context
+    async fn run_batched_query(
+        &self,
+        ctx: &datafusion::prelude::SessionContext,
+        sql: &str,
+        params: Vec<crate::query::Value>,
+        memory_limit: Option<usize>,
+    ) -> Result<ResultSet> {
+        let mut df = crate::query::identifiers::plan_sql_with_collation(
+            ctx,
+            sql,
+            self.identifier_case,
+            self.collation,
+        )
+        .await?;
+        if !params.is_empty() {
+            let params: Vec<_> = params.iter().map(crate::query::Value::to_scalar).collect();
+            df = df.with_param_values(params)?;
+        }
+        let schema = Arc::new(df.schema().as_arrow().clone());
+        let batches = df
+            .collect()
+            .await
+            .map_err(|e| self.execution_error(e, memory_limit))?;
+        ResultSet::from_batches(schema, &batches)
+    }
+
+    /// Query entry point behind `query`, `query_rows` and `query_csv`
     ///
-    /// Timestamp is Unix epoch milliseconds. Delta Lake will find the version
-    /// that was active at or before the given timestamp.
-    pub async fn query_timestamp(&self, sql: &str, timestamp_ms: i64) -> Result<Vec<RecordBatch>> {
-        info!(
-            "Executing time travel query at timestamp {}: {}",
-            timestamp_ms, sql
-        );
+    /// With `at_version`, `data` is read as committed at that version.
+    async fn query_with_schema(
+        &self,
+        sql: &str,
+        at_version: Option<i64>,
+        memory_limit: Option<usize>,
+        collation: Collation,
+    ) -> Result<(SchemaRef, Vec<RecordBatch>)> {
+        use crate::query::{AsOf, SnapshotRef};
+
+        info!("Executing query: {}", self.log_redaction.redact(sql));
 
         // Check read permission
         self.check_permission(&crate::security::Permission::Read)?;
 
         // Track query metrics with latency
         let start = Instant::now();
-        let result = self.query_timestamp_inner(sql, timestamp_ms).await;
+        let result = async {
+            let (sql, mut snapshots) = crate::query::time_travel::rewrite_time_travel(sql)?;
+            if let Some(version) = at_version {
+                snapshots.push(SnapshotRef {
+                    table: "data".to_string(),
+                    name: "data".to_string(),
+                    as_of: AsOf::Version(version),
+                });
+            }
+            let sql = self.encrypt_sql(&sql)?;
+            let (schema, batches) = self
+                .query_inner(&sql, &snapshots, memory_limit, collation)
+                .await?;
+            Ok((schema, self.decrypt_batches(batches)?))
+        }
+        .await;
         let latency_ms = start.elapsed().as_secs_f64() * 1000.0;
+        let (operation, details) = match at_version {
+            Some(version) => ("SELECT_VERSION", format!("v{}: {}", version, sql)),
+            None => ("SELECT", sql.to_string()),
+        };
 
         match &result {
             Ok(_) => {
                 self.metrics.total_queries.fetch_add(1, Ordering::Relaxed);
                 let mut latencies = self.metrics.query_latencies.lock().await;
                 latencies.push(latency_ms);
+                // Keep only last 1000 latencies to prevent unbounded growth
                 let len = latencies.len();
                 if len > 1000 {
                     latencies.drain(0..len - 1000);
                 }
-                self.audit_log(
-                    "SELECT_TIMESTAMP",
-                    &format!("@{}: {}", timestamp_ms, sql),
-                    true,
-                )
-                .await;
+                self.audit_log(operation, &details, true).await;
             }
             Err(e) => {
                 self.metrics.total_errors.fetch_add(1, Ordering::Relaxed);
-                self.audit_log(
-                    "SELECT_TIMESTAMP",
-                    &format!("@{}: {}: {}", timestamp_ms, sql, e),
-                    false,
-                )
-                .await;
+                self.audit_log(operation, &format!("{}: {}", details, e), false)
+                    .await;
             }
         }
         result
     }
 
-    /// Internal time travel query by timestamp
-    async fn query_timestamp_inner(
-        &self,
-        sql: &str,
-        timestamp_ms: i64,
-    ) -> Result<Vec<RecordBatch>> {
-        use chrono::{DateTime, Utc};
-        use deltalake::datafusion::prelude::SessionContext;
-        use tracing::error;
-        use url::Url;
-
-        info!(
-            "Querying Delta Lake with SQL at timestamp {}: {}",
-            timestamp_ms, sql
-        );
+    /// Comparisons in `sql` that file statistics can be checked against
+    ///
+    /// Those on columns of a cast view the query reads are left out, since
+    /// the statistics are of the stored values, not the cast ones.
+    fn skipping_predicates(&self, sql: &str) -> Vec<(String, String, serde_json::Value)> {
+        let mut predicates = crate::delta_lake::data_skipping::extract_predicates(sql);
+        let views = match self.load_cast_views() {
+            Ok(views) => views,
+            Err(e) => {
+                warn!("Failed to load cast views: {}", e);
+                return predicates;
+            }
+        };
+        let cast = views.cast_columns_in(sql);
+        predicates.retain(|(column, _, _)| !cast.iter().any(|c| c.eq_ignore_ascii_case(column)));
+        predicates
+    }
 
-        // Convert milliseconds to DateTime<Utc>
-        let timestamp_secs = timestamp_ms / 1000;
-        let timestamp_nanos = ((timestamp_ms % 1000) * 1_000_000) as u32;
-        let datetime = DateTime::<Utc>::from_timestamp(timestamp_secs, timestamp_nanos)
-            .ok_or_else(|| Error::Other(format!("Invalid timestamp: {}", timestamp_ms)))?;
+    /// Which of the table's files `predicates` leave to read, from the
+    /// file-level statistics in the Delta log
+    fn scan_stats(
+        &self,
+        predicates: &[(String, String, serde_json::Value)],
+        collation: Collation,
+    ) -> Result<crate::query::ScanStats> {
+        let file_stats = crate::delta_lake::data_skipping::get_file_statistics(&self.base_path)?;
 
-        // Open table at specific timestamp
-        let table = if let Some(ref s3_url) = self.s3_url {
-            if let Some(ref storage_options) = self.s3_storage_options {
-                let url = Url::parse(s3_url)
-                    .map_err(|e| Error::Other(format!("Invalid S3 URL: {}", e)))?;
-
-                let builder = deltalake::DeltaTableBuilder::from_uri(url)
-                    .map_err(|e| {
-                        error!("Failed to create Delta table builder from S3 URL: {}", e);
-                        Error::DeltaTable(e)
-                    })?
-                    .with_storage_options(storage_options.clone())
-                    .with_datestring(datetime.to_rfc3339())
-                    .map_err(|e| {
-                        error!("Failed to set datestring for Delta table: {}", e);
-                        Error::DeltaTable(e)
-                    })?;
-
-                builder.load().await.map_err(|e| {
-                    error!(
-                        "Failed to load Delta table at timestamp {} from S3: {}",
-                        timestamp_ms, e
-                    );
-                    Error::DeltaTable(e)
-                })?
+        let mut scan = crate::query::ScanStats {
+            total_files: file_stats.len(),
+            ..Default::default()
+        };
+        for file_stat in &file_stats {
+            // A file is skipped if any predicate rules it out
+            let can_skip = predicates.iter().any(|(column, operator, value)| {
+                crate::delta_lake::data_skipping::can_skip_file_with_collation(
+                    file_stat, column, operator, value, collation,
+                )
+            });
+            if can_skip {
+                scan.files_skipped += 1;
+                scan.bytes_skipped += file_stat.size_bytes;
             } else {
-                return Err(Error::Other(
-                    "S3 storage options not configured".to_string(),
-                ));
+                scan.files_to_read += 1;
+                scan.bytes_to_read += file_stat.size_bytes;
             }
-        } else {
-            let table_path = self
-                .base_path
-                .to_str()
-                .ok_or_else(|| Error::Other("Invalid path".to_string()))?;
-
-            let url = Url::from_file_path(table_path)
-                .map_err(|_| Error::Other(format!("Invalid file path: {}", table_path)))?;
-
-            deltalake::DeltaTableBuilder::from_uri(url)
-                .map_err(|e| {
-                    error!("Failed to create Delta table builder from path: {}", e);
-                    Error::DeltaTable(e)
-                })?
-                .with_datestring(datetime.to_rfc3339())
-                .map_err(|e| {
-                    error!("Failed to set datestring for Delta table: {}", e);
-                    Error::DeltaTable(e)
-                })?
-                .load()
-                .await
-                .map_err(|e| {
-                    error!(
-                        "Failed to load Delta table at timestamp {}: {}",
-                        timestamp_ms, e
-                    );
-                    Error::DeltaTable(e)
-                })?
-        };
+        }
+        Ok(scan)
+    }
 
-        // Create DataFusion context and register table
-        let ctx = SessionContext::new();
-        ctx.register_table("data", Arc::new(table))?;
+    /// Internal query method - delegates to Delta Lake native query with data skipping
+    ///
+    /// `snapshots` are the tables the query reads at earlier versions, see
+    /// `query::time_travel`.
+    async fn query_inner(
+        &self,
+        sql: &str,
+        snapshots: &[crate::query::SnapshotRef],
+        memory_limit: Option<usize>,
+        collation: Collation,
+    ) -> Result<(SchemaRef, Vec<RecordBatch>)> {
+        if snapshots.iter().any(|s| s.name == "data") {
+            // File statistics are of the current version, not the one read
+            return self
+                .query_delta_native_with_schema(
+                    sql,
+                    snapshots,
+                    memory_limit,
+                    false,
+                    true,
+                    collation,
+                )
+                .await;
+        }
 
-        // Execute query
-        let df = ctx.sql(sql).await?;
-        let batches = df.collect().await?;
+        // Extract predicates from SQL query
+        let predicates = self.skipping_predicates(sql);
+
+        // Evaluate which files can be skipped
+        let scan = self.scan_stats(&predicates, collation)?;
+
+        // Update data skipping statistics
+        {
+            let mut stats = self.data_skipping_stats.lock().await;
+            stats.total_files = scan.total_files;
+            stats.files_read = scan.files_to_read;
+            stats.files_skipped = scan.files_skipped;
+            stats.bytes_scanned = scan.bytes_to_read;
+            stats.bytes_skipped = scan.bytes_skipped;
+        }
 
         info!(
-            "Time travel query at timestamp {} returned {} batches",
-            timestamp_ms,
-            batches.len()
+            "Data skipping: {} files total, {} to read, {} skipped",
+            scan.total_files, scan.files_to_read, scan.files_skipped
         );
-        Ok(batches)
-    }
-
-    /// Query a specific Parquet file (for individual file views)
-    pub async fn query_file(&self, file_path: &str) -> Result<Vec<RecordBatch>> {
-        info!("Querying specific file: {}", file_path);
 
-        let full_path = self.base_path.join(file_path);
-        if !full_path.exists() {
-            return Err(Error::RecordNotFound(format!(
-                "File not found: {}",
-                file_path
-            )));
+        // Estimate how much of the table survives the predicates
+        let estimate = match self.load_histograms() {
+            Ok(Some(histograms)) => self.estimate_with(&histograms, &predicates).ok(),
+            _ => None,
+        };
+        if let Some(selectivity) = estimate {
+            info!("Estimated selectivity: {:.4}", selectivity);
         }
+        self.data_skipping_stats.lock().await.estimated_selectivity = estimate;
 
-        let reader = ParquetReader::new();
-        let batch = reader.read_batch(&full_path)?;
-
-        Ok(vec![batch])
+        // Execute query (Delta Lake + DataFusion will also do its own pruning)
+        self.query_delta_native_with_schema(sql, snapshots, memory_limit, true, true, collation)
+            .await
     }
 
-    /// Delete a specific file from Delta Lake
-    /// Note: Delta Lake handles deletions natively through its transaction log
-    pub async fn delete(&self, _file_path: &str) -> Result<u64> {
-        // Track delete metrics
-        self.metrics.total_deletes.fetch_add(1, Ordering::Relaxed);
-        self.metrics
-            .total_transactions
-            .fetch_add(1, Ordering::Relaxed);
+    /// The table's history, newest version first, like Delta's `DESCRIBE HISTORY`
+    ///
+    /// Lists at most `limit` versions with their operation, commit time,
+    /// parameters and commit metadata, read from `_delta_log`. The user comes
+    /// from the commit if another engine recorded one, otherwise from the
+    /// audit entry of the write that reported the version. Versions only kept
+    /// in a checkpoint are listed with nothing but their number. Only
+    /// available for local tables.
+    pub async fn history(&self, limit: usize) -> Result<Vec<crate::delta_lake::CommitInfo>> {
+        info!("Reading history (limit {})", limit);
 
-        // Delta Lake doesn't support individual file deletion - use delete_rows_where instead
-        Err(Error::Other(
-            "Use delete_rows_where() for row-level deletion in Delta Lake".to_string(),
-        ))
+        // Check read permission
+        self.check_permission(&crate::security::Permission::Read)?;
+
+        if self.s3_url.is_some() {
+            return Err(Error::InvalidOperation(
+                "History is only supported for local tables".to_string(),
+            ));
+        }
+        let mut history = crate::delta_lake::history::read_history(&self.base_path, limit)?;
+
+        if let Some(logger) = &self.audit_logger {
+            // Writes that changed nothing also report a version, but didn't commit it
+            let entries: Vec<_> = logger
+                .get_entries()
+                .await
+                .into_iter()
+                .filter(|e| {
+                    e.success
+                        && matches!(e.operation.as_str(), "INSERT" | "UPDATE" | "DELETE")
+                        && !e.details.starts_with("0 rows")
+                        && !e.details.contains("(0 rows")
+                })
+                .collect();
+            for commit in history.iter_mut().filter(|c| c.user.is_none()) {
+                let closed = format!("version {})", commit.version);
+                let listed = format!("version {},", commit.version);
+                commit.user = entries
+                    .iter()
+                    .find(|e| e.details.contains(&closed) || e.details.contains(&listed))
+                    .map(|e| e.user.clone());
+            }
+        }
+        Ok(history)
     }
 
-    /// Delete rows matching a SQL WHERE clause (row-level deletion with deletion vectors)
-    /// This is efficient as it doesn't rewrite Parquet files - just marks rows as deleted
-    pub async fn delete_rows_where(&self, where_clause: &str) -> Result<usize> {
-        info!("Deleting rows where: {}", where_clause);
+    /// Follow the table's changes from `from_version` on, like `tail -f`
+    ///
+    /// The follower yields one `TableChange` per committed version, in order,
+    /// and waits for new commits until `cancel` is cancelled. If a version it
+    /// hasn't reached yet has been cleaned out of the log or vacuumed, it
+    /// yields `Error::VersionGone` so the consumer can re-bootstrap from the
+    /// current table. Rows are decrypted and masked as for `read_changes`,
+    /// by the user's permissions when the follower was made. Only available
+    /// for local tables.
+    pub fn follow_changes(
+        &self,
+        from_version: u64,
+        cancel: crate::delta_lake::changes::CancellationToken,
+    ) -> Result<crate::delta_lake::ChangeFollower> {
+        info!("Following changes from version {}", from_version);
 
-        // Check write permission
-        self.check_permission(&crate::security::Permission::Write)?;
+        // Check read permission
+        self.check_permission(&crate::security::Permission::Read)?;
 
-        let result = self.delete_rows_where_inner(where_clause).await;
-        match &result {
-            Ok(count) => {
-                self.metrics.total_deletes.fetch_add(1, Ordering::Relaxed);
-                self.audit_log(
-                    "DELETE",
-                    &format!("WHERE {} ({} rows)", where_clause, count),
-                    true,
-                )
-                .await;
-            }
-            Err(e) => {
-                self.metrics.total_errors.fetch_add(1, Ordering::Relaxed);
-                self.audit_log("DELETE", &format!("WHERE {}: {}", where_clause, e), false)
-                    .await;
-            }
+        if self.s3_url.is_some() {
+            return Err(Error::InvalidOperation(
+                "Following changes is only supported for local tables".to_string(),
+            ));
         }
-        result
+        let encryption = self.column_encryption()?;
+        let keys = self.column_keys.clone();
+        let authorized = self.may_decrypt();
+        let masks = self.column_masks();
+        Ok(crate::delta_lake::ChangeFollower::new(
+            self.base_path.clone(),
+            self.schema.clone(),
+            from_version,
+            cancel,
+        )
+        .with_transform(Arc::new(move |batches| {
+            batches
+                .into_iter()
+                .map(|batch| {
+                    masks.mask_batch(&encryption.decrypt_batch(
+                        batch,
+                        keys.as_deref(),
+                        authorized,
+                    )?)
+                })
+                .collect()
+        })))
     }
 
-    /// Internal delete method - delegates to Delta Lake native delete
-    async fn delete_rows_where_inner(&self, where_clause: &str) -> Result<usize> {
-        self.delete_delta_native(where_clause).await
+    /// Whether the change data feed is enabled, from `delta.enableChangeDataFeed`
+    ///
+    /// Read from the local Delta log; S3 tables report false.
+    pub fn change_data_feed_enabled(&self) -> bool {
+        if self.s3_url.is_some() {
+            return false;
+        }
+        crate::delta_lake::read_table_config(&self.base_path)
+            .map(|config| config.is_change_data_feed_enabled())
+            .unwrap_or(false)
     }
 
-    /// Create a MERGE operation builder
+    /// Turn the change data feed on or off, in the table's metadata for this
+    /// and other writers
     ///
-    /// MERGE allows INSERT, UPDATE, and DELETE operations in a single atomic transaction.
+    /// While it's on, deletes, updates and merges record the rows they
+    /// changed, so `read_changes` reports updated rows with their old and
+    /// new values rather than whole rewritten files. Only available for
+    /// local tables.
+    pub async fn set_change_data_feed(&self, enabled: bool) -> Result<()> {
+        use deltalake::DeltaOps;
+
+        info!("Setting change data feed to {}", enabled);
+
+        // Check admin permission
+        self.check_permission(&crate::security::Permission::Admin)?;
+        self.check_writable()?;
+
+        if self.s3_url.is_some() {
+            return Err(Error::InvalidOperation(
+                "The change data feed is only supported for local tables".to_string(),
+            ));
+        }
+        self.retry_policy
+            .run("Set change data feed", || async {
+                let table = self.open_delta_table().await?;
+                DeltaOps(table)
+                    .set_tbl_properties()
+                    .with_properties(HashMap::from([(
+                        crate::delta_lake::change_feed::ENABLE_PROPERTY.to_string(),
+                        enabled.to_string(),
+                    )]))
+                    .await
+                    .map_err(Error::DeltaTable)
+            })
+            .await?;
+        self.audit_log(
+            "SET TBLPROPERTIES",
+            &format!("change data feed {}", enabled),
+            true,
+        )
+        .await;
+        Ok(())
+    }
+
+    /// Rows changed by versions `from_version` through `to_version`, inclusive
     ///
-    /// # Example
-    /// ```no_run
-    /// use arrow::array::{Int32Array, StringArray};
-    /// use arrow::datatypes::{DataType, Field, Schema};
-    /// use arrow::record_batch::RecordBatch;
-    /// use std::sync::Arc;
-    /// use fsdb::DatabaseOps;
+    /// Each row has the table's columns followed by `_change_type`
+    /// (`insert`, `delete`, `update_preimage` or `update_postimage`),
+    /// `_commit_version` and `_commit_timestamp`, see
+    /// `delta_lake::change_feed`. The change data feed has to be enabled;
+    /// versions from before it was have no row-level change data, so their
+    /// updates read as deletes and inserts of the files they rewrote. Fails
+    /// with `Error::VersionGone` if log cleanup or VACUUM removed one of the
+    /// versions. Only available for local tables.
+    pub async fn read_changes(
+        &self,
+        from_version: u64,
+        to_version: u64,
+    ) -> Result<Vec<RecordBatch>> {
+        info!(
+            "Reading changes from version {} to {}",
+            from_version, to_version
+        );
+
+        // Check read permission
+        self.check_permission(&crate::security::Permission::Read)?;
+
+        let result = async {
+            if self.s3_url.is_some() {
+                return Err(Error::InvalidOperation(
+                    "The change data feed is only supported for local tables".to_string(),
+                ));
+            }
+            if !self.change_data_feed_enabled() {
+                return Err(Error::InvalidOperation(format!(
+                    "The change data feed is not enabled: set {} with set_change_data_feed",
+                    crate::delta_lake::change_feed::ENABLE_PROPERTY
+                )));
+            }
+            let latest = self.get_delta_table().await?.version().unwrap_or(0) as u64;
+            if from_version > to_version || to_version > latest {
+                return Err(Error::InvalidOperation(format!(
+                    "Invalid version range {}..={}: the latest version is {}",
+                    from_version, to_version, latest
+                )));
+            }
+            let schema = self.table_schema().await?;
+            let rows = crate::delta_lake::change_feed::read_change_feed(
+                &self.base_path,
+                &schema,
+                from_version,
+                to_version,
+            )?;
+            self.mask_batches(self.decrypt_batches(rows)?)
+        }
+        .await;
+        let details = format!("versions {}..={}", from_version, to_version);
+        match &result {
+            Ok(_) => self.audit_log("READ CHANGES", &details, true).await,
+            Err(e) => {
+                self.audit_log("READ CHANGES", &format!("{}: {}", details, e), false)
+                    .await
+            }
+        }
+        result
+    }
+
+    /// Create a materialized view of `sql` at `path`, as a Delta table of its own
+    ///
+    /// `sql` queries this table as `data`, and its columns need names Delta
+    /// accepts, so name aggregates with `AS`. Views of SUM and COUNT grouped by
+    /// columns or expressions are maintained incrementally from the rows each
+    /// commit changed; anything else, such as `COUNT(DISTINCT)` or median, is
+    /// recomputed on every refresh, and the returned definition's
+    /// `full_refresh_reason` says why. Views are refreshed after each insert,
+    /// delete and update through this `DatabaseOps`; other writes are caught
+    /// up by the next refresh. Only available for local tables.
+    pub async fn create_materialized_view(
+        &self,
+        path: impl AsRef<Path>,
+        sql: &str,
+    ) -> Result<crate::query::ViewDefinition> {
+        let path = path.as_ref();
+        info!(
+            "Creating materialized view at {}: {}",
+            path.display(),
+            self.log_redaction.redact(sql)
+        );
+
+        // Check read permission
+        self.check_permission(&crate::security::Permission::Read)?;
+
+        let result = self.create_materialized_view_inner(path, sql).await;
+        let details = match &result {
+            Ok(view) => format!(
+                "{}: {} (version {})",
+                path.display(),
+                sql,
+                view.base_version
+            ),
+            Err(e) => format!("{}: {}: {}", path.display(), sql, e),
+        };
+        self.audit_log("CREATE MATERIALIZED VIEW", &details, result.is_ok())
+            .await;
+        result
+    }
+
+    async fn create_materialized_view_inner(
+        &self,
+        path: &Path,
+        sql: &str,
+    ) -> Result<crate::query::ViewDefinition> {
+        use crate::query::materialized::{plan_incremental, register_view, view_schema};
+
+        if self.s3_url.is_some() {
+            return Err(Error::InvalidOperation(
+                "Materialized views are only supported for local tables".to_string(),
+            ));
+        }
+        if path.join("_delta_log").exists() {
+            return Err(Error::DatabaseAlreadyExists(path.display().to_string()));
+        }
+
+        let plan = plan_incremental(sql);
+        let view_sql = match &plan {
+            Ok(plan) => {
+                let schema = view_schema(&self.validate(sql).await?)?;
+                let names: Vec<String> = schema.fields().iter().map(|f| f.name().clone()).collect();
+                plan.aggregate_sql(&names, false)
+            }
+            Err(_) => sql.to_string(),
+        };
+        let schema = view_schema(&self.validate(&view_sql).await?)?;
+        let view = DatabaseOps::create(path, schema).await?;
+
+        let mut definition = crate::query::ViewDefinition {
+            sql: sql.to_string(),
+            base_path: std::path::absolute(&self.base_path)?,
+            base_version: 0,
+            view_version: -1,
+            full_refresh_reason: plan.err(),
+        };
+        self.recompute_view(path, &view, &mut definition).await?;
+        register_view(&self.base_path, path)?;
+        Ok(definition)
+    }
+
+    /// Bring the materialized view at `path` up to this table's latest version
+    ///
+    /// Incrementally maintained views apply just the rows committed since
+    /// their last refresh. They're recomputed instead when those changes can
+    /// no longer be read, because log cleanup or VACUUM removed them, or when
+    /// the view table was written to outside a refresh; the result says why.
+    pub async fn refresh_view(&self, path: impl AsRef<Path>) -> Result<crate::query::ViewRefresh> {
+        self.refresh_view_logged(path.as_ref(), false).await
+    }
+
+    /// Recompute the materialized view at `path` from the whole table
+    pub async fn refresh_view_full(
+        &self,
+        path: impl AsRef<Path>,
+    ) -> Result<crate::query::ViewRefresh> {
+        self.refresh_view_logged(path.as_ref(), true).await
+    }
+
+    async fn refresh_view_logged(
+        &self,
+        path: &Path,
+        full: bool,
+    ) -> Result<crate::query::ViewRefresh> {
+        info!("Refreshing materialized view at {}", path.display());
+
+        // Check read permission
+        self.check_permission(&crate::security::Permission::Read)?;
+
+        let result = self.refresh_view_inner(path, full).await;
+        let details = match &result {
+            Ok(refresh) => format!(
+                "{} (version {}, {})",
+                path.display(),
+                refresh.base_version,
+                match &refresh.full_refresh_reason {
+                    None => format!("{} changed rows applied", refresh.rows_applied),
+                    Some(reason) => format!("recomputed: {}", reason),
+                }
+            ),
+            Err(e) => format!("{}: {}", path.display(), e),
+        };
+        self.audit_log("REFRESH MATERIALIZED VIEW", &details, result.is_ok())
+            .await;
+        result
+    }
+
+    async fn refresh_view_inner(
+        &self,
+        path: &Path,
+        full: bool,
+    ) -> Result<crate::query::ViewRefresh> {
+        use crate::query::materialized::{plan_incremental, refresh_lock};
+
+        let lock = refresh_lock(path);
+        let _refreshing = lock.lock().await;
+
+        let mut definition = crate::query::ViewDefinition::load(path)?;
+        if std::path::absolute(&self.base_path)? != definition.base_path {
+            return Err(Error::InvalidOperation(format!(
+                "{} is a view of {}",
+                path.display(),
+                definition.base_path.display()
+            )));
+        }
+        let view = DatabaseOps::open(path).await?;
+
+        let plan = plan_incremental(&definition.sql);
+        let view_version = view.get_delta_table().await?.version();
+        let reason = if full {
+            Some("full refresh requested".to_string())
+        } else if let Err(reason) = &plan {
+            Some(reason.clone())
+        } else if view_version != Some(definition.view_version) {
+            Some("the view table was written to outside a refresh".to_string())
+        } else {
+            None
+        };
+
+        let reason = match (reason, plan) {
+            (None, Ok(plan)) => {
+                match self
+                    .apply_view_changes(path, &view, &mut definition, &plan)
+                    .await?
+                {
+                    Some(rows_applied) => {
+                        return Ok(crate::query::ViewRefresh {
+                            base_version: definition.base_version,
+                            rows_applied,
+                            full_refresh_reason: None,
+                        })
+                    }
+                    None => format!(
+                        "changes after version {} are no longer in the log",
+                        definition.base_version
+                    ),
+                }
+            }
+            (Some(reason), _) | (None, Err(reason)) => reason,
+        };
+        self.recompute_view(path, &view, &mut definition).await?;
+        Ok(crate::query::ViewRefresh {
+            base_version: definition.base_version,
+            rows_applied: 0,
+            full_refresh_reason: Some(reason),
+        })
+    }
+
+    /// Recompute a view from the whole table, at its latest version
+    async fn recompute_view(
+        &self,
+        path: &Path,
+        view: &DatabaseOps,
+        definition: &mut crate::query::ViewDefinition,
+    ) -> Result<()> {
+        use crate::query::materialized::{plan_incremental, visible_columns};
+
+        let version = self.get_delta_table().await?.version().unwrap_or(0);
+        let sql = match plan_incremental(&definition.sql) {
+            Ok(plan) => plan.aggregate_sql(&visible_columns(&view.schema()), false),
+            Err(_) => definition.sql.clone(),
+        };
+        let batches = self.query_version_inner(&sql, version).await?;
+        let commit = view.replace_contents(batches).await?;
+
+        definition.base_version = version as u64;
+        definition.view_version = commit.version;
+        definition.save(path)
+    }
+
+    /// Apply the rows committed since a view's last refresh to its aggregates
+    ///
+    /// Returns the number of changed rows, or None if some of the changes
+    /// are gone from the log and the view has to be recomputed.
+    async fn apply_view_changes(
+        &self,
+        path: &Path,
+        view: &DatabaseOps,
+        definition: &mut crate::query::ViewDefinition,
+        plan: &crate::query::materialized::IncrementalPlan,
+    ) -> Result<Option<usize>> {
+        use crate::query::materialized::{
+            change_schema, signed_batch, visible_columns, DELTA_TABLE, VIEW_TABLE,
+        };
+        use datafusion::datasource::MemTable;
+
+        let latest = self.get_delta_table().await?.version().unwrap_or(0) as u64;
+        let schema = self.table_schema().await?;
+        let mut changes = Vec::new();
+        for version in definition.base_version + 1..=latest {
+            let change = match crate::delta_lake::read_change(&self.base_path, &schema, version) {
+                Ok(Some(change)) => change,
+                Ok(None) => break,
+                Err(Error::VersionGone(_)) => return Ok(None),
+                Err(e) => return Err(e),
+            };
+            for batch in &change.added {
+                changes.push(signed_batch(batch, &schema, 1)?);
+            }
+            for batch in &change.removed {
+                changes.push(signed_batch(batch, &schema, -1)?);
+            }
+        }
+        let rows_applied = changes.iter().map(RecordBatch::num_rows).sum();
+
+        if rows_applied > 0 {
+            // Aggregate the changes the way the view aggregates the table
+            let names = visible_columns(&view.schema());
+            let ctx = self.session_context();
+            ctx.register_table(
+                "data",
+                Arc::new(MemTable::try_new(change_schema(&schema), vec![changes])?),
+            )?;
+            let delta = crate::query::identifiers::plan_sql(
+                &ctx,
+                &plan.aggregate_sql(&names, true),
+                self.identifier_case,
+            )
+            .await?;
+            let delta_schema = Arc::new(delta.schema().as_arrow().clone());
+            let delta = delta.collect().await?;
+
+            // And add them to what the view holds
+            let current = view.query("SELECT * FROM data").await?;
+            let ctx = self.session_context();
+            ctx.register_table(
+                VIEW_TABLE,
+                Arc::new(MemTable::try_new(view.schema(), vec![current])?),
+            )?;
+            ctx.register_table(
+                DELTA_TABLE,
+                Arc::new(MemTable::try_new(delta_schema, vec![delta])?),
+            )?;
+            let merged = crate::query::identifiers::plan_sql(
+                &ctx,
+                &plan.merge_sql(&names),
+                self.identifier_case,
+            )
+            .await?
+            .collect()
+            .await?;
+            definition.view_version = view.replace_contents(merged).await?.version;
+        }
+
+        definition.base_version = latest.max(definition.base_version);
+        definition.save(path)?;
+        Ok(Some(rows_applied))
+    }
+
+    /// Refresh this table's materialized views after a commit
+    ///
+    /// The commit has already happened, so a failed refresh is only logged;
+    /// the next one catches the view up.
+    async fn maybe_refresh_views(&self) {
+        if self.s3_url.is_some() {
+            return;
+        }
+        let views = match crate::query::materialized::registered_views(&self.base_path) {
+            Ok(views) => views,
+            Err(e) => {
+                warn!("Failed to list materialized views: {}", e);
+                return;
+            }
+        };
+        for view in views {
+            if let Err(e) = self.refresh_view_inner(&view, false).await {
+                warn!(
+                    "Refreshing materialized view {} failed: {}",
+                    view.display(),
+                    e
+                );
+            }
+        }
+    }
+
+    /// Query the database at a specific Delta Lake version (time travel)
+    ///
+    /// This allows querying historical data without affecting the current state.
+    /// Version numbers start at 0 (first commit).
+    pub async fn query_version(&self, sql: &str, version: i64) -> Result<Vec<RecordBatch>> {
+        info!(
+            "Executing time travel query at version {}: {}",
+            version,
+            self.log_redaction.redact(sql)
+        );
+
+        // Check read permission
+        self.check_permission(&crate::security::Permission::Read)?;
+
+        // Track query metrics with latency
+        let start = Instant::now();
+        let result = self.query_version_inner(sql, version).await;
+        let latency_ms = start.elapsed().as_secs_f64() * 1000.0;
+
+        match &result {
+            Ok(_) => {
+                self.metrics.total_queries.fetch_add(1, Ordering::Relaxed);
+                let mut latencies = self.metrics.query_latencies.lock().await;
+                latencies.push(latency_ms);
+                let len = latencies.len();
+                if len > 1000 {
+                    latencies.drain(0..len - 1000);
+                }
+                self.audit_log("SELECT_VERSION", &format!("v{}: {}", version, sql), true)
+                    .await;
+            }
+            Err(e) => {
+                self.metrics.total_errors.fetch_add(1, Ordering::Relaxed);
+                self.audit_log(
+                    "SELECT_VERSION",
+                    &format!("v{}: {}: {}", version, sql, e),
+                    false,
+                )
+                .await;
+            }
+        }
+        result
+    }
+
+    /// Internal time travel query by version
+    async fn query_version_inner(&self, sql: &str, version: i64) -> Result<Vec<RecordBatch>> {
+        info!(
+            "Querying Delta Lake with SQL at version {}: {}",
+            version,
+            self.log_redaction.redact(sql)
+        );
+
+        // Open table at specific version
+        let table = self
+            .table_as_of(crate::query::AsOf::Version(version))
+            .await?;
+
+        // Create DataFusion context and register table
+        let ctx = self.session_context();
+        let table = self.mask_table(&ctx, &self.table_name(), Arc::new(table))?;
+        ctx.register_table("data", table)?;
+
+        // Execute query
+        let df = crate::query::identifiers::plan_sql_with_collation(
+            &ctx,
+            sql,
+            self.identifier_case,
+            self.collation,
+        )
+        .await?;
+        let batches = df.collect().await?;
+
+        info!(
+            "Time travel query at version {} returned {} batches",
+            version,
+            batches.len()
+        );
+        Ok(batches)
+    }
+
+    /// Query the database as it was at `version`, like `query`
+    ///
+    /// The statement runs exactly as `query` would run it, named tables,
+    /// cast views and column encryption included, except that `data` is
+    /// read as committed at `version`. Named tables are read at their
+    /// latest version unless the SQL says otherwise with `VERSION AS OF` or
+    /// `TIMESTAMP AS OF`, see `query::time_travel`.
+    pub async fn query_at_version(&self, sql: &str, version: i64) -> Result<Vec<RecordBatch>> {
+        Ok(self
+            .query_with_schema(sql, Some(version), self.query_memory_limit, self.collation)
+            .await?
+            .1)
+    }
+
+    /// Open the main table as of `as_of`
+    async fn table_as_of(&self, as_of: crate::query::AsOf) -> Result<deltalake::DeltaTable> {
+        let builder = if let Some(ref s3_url) = self.s3_url {
+            let s3_config = self
+                .s3_config
+                .as_ref()
+                .ok_or_else(|| Error::Other("S3 connection not configured".to_string()))?;
+            s3_config.table_builder(crate::storage::s3::parse_s3_url(s3_url)?)?
+        } else {
+            let url = url::Url::from_directory_path(&self.base_path)
+                .map_err(|_| Error::Other("Invalid path for Delta table".to_string()))?;
+            deltalake::DeltaTableBuilder::from_uri(url).map_err(Error::DeltaTable)?
+        };
+        load_as_of(builder, as_of).await
+    }
+
+    /// Open the table a time travel query reads, checking the user may read it
+    async fn snapshot_table(
+        &self,
+        snapshot: &crate::query::SnapshotRef,
+    ) -> Result<deltalake::DeltaTable> {
+        use crate::metadata::catalog;
+
+        if snapshot.table == "data" {
+            return self.table_as_of(snapshot.as_of).await;
+        }
+        catalog::check_table_name(&snapshot.table)?;
+        if !self.can_read_table(&snapshot.table) {
+            return Err(Error::PermissionDenied(format!("table {}", snapshot.table)));
+        }
+        if self.s3_url.is_some() || !catalog::exists(&self.base_path, &snapshot.table) {
+            return Err(Error::InvalidOperation(format!(
+                "No table named {}",
+                snapshot.table
+            )));
+        }
+        let path = catalog::table_path(&self.base_path, &snapshot.table);
+        let url = url::Url::from_directory_path(&path)
+            .map_err(|_| Error::Other("Invalid path for Delta table".to_string()))?;
+        let builder = deltalake::DeltaTableBuilder::from_uri(url).map_err(Error::DeltaTable)?;
+        load_as_of(builder, snapshot.as_of).await
+    }
+
+    /// Register the snapshots a time travel query reads in `ctx`, under the
+    /// names the rewritten SQL gives them
+    ///
+    /// A snapshot of `data` named `data` replaces the current table, so it's
+    /// left to the caller to register in its place.
+    async fn register_snapshots(
+        &self,
+        ctx: &datafusion::prelude::SessionContext,
+        snapshots: &[crate::query::SnapshotRef],
+    ) -> Result<()> {
+        for snapshot in snapshots.iter().filter(|s| s.name != "data") {
+            let table = self.snapshot_table(snapshot).await?;
+            let table = match snapshot.table.as_str() {
+                "data" => self.mask_table(ctx, &self.table_name(), Arc::new(table))?,
+                name => self.mask_table(ctx, name, Arc::new(table))?,
+            };
+            // Registered as written: the name isn't an identifier to parse
+            ctx.register_table(
+                datafusion::common::TableReference::bare(snapshot.name.as_str()),
+                table,
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Replace the cursor registry with one using the given configuration
+    ///
+    /// Any cursors already open on this handle are dropped.
+    pub fn with_cursor_config(mut self, config: CursorConfig) -> Self {
+        self.cursors = Arc::new(crate::query::CursorRegistry::with_config(config));
+        self
+    }
+
+    /// Set how unquoted identifiers in SQL are matched to tables and columns
+    ///
+    /// Case-insensitive by default: `Customer`, `customer` and `CUSTOMER` all
+    /// name the same column, and a reference that matches columns differing
+    /// only by case fails with `Error::AmbiguousIdentifier`. Quoted identifiers
+    /// always match exactly.
+    pub fn with_identifier_case(mut self, case: IdentifierCase) -> Self {
+        self.identifier_case = case;
+        self
+    }
+
+    /// Set how strings are compared, ordered and grouped in this table's queries
+    ///
+    /// Binary by default. Under `Collation::CaseInsensitive`, `WHERE`
+    /// comparisons, `ORDER BY` and `GROUP BY` on strings ignore case (see
+    /// `crate::query::collation`), and data skipping only rules out files
+    /// the collation can't match. `query_rows_with_collation` overrides it
+    /// for one query. Writes such as `delete` and `update` always compare
+    /// strings byte by byte.
+    pub fn with_collation(mut self, collation: Collation) -> Self {
+        self.collation = collation;
+        self
+    }
+
+    /// Open a server-side cursor over the results of `sql`
+    ///
+    /// The cursor is pinned to the Delta version current at open time, so pages
+    /// fetched later see a stable snapshot even if the table is modified.
+    pub async fn open_cursor(&self, sql: &str) -> Result<CursorId> {
+        info!("Opening cursor: {}", self.log_redaction.redact(sql));
+
+        // Check read permission
+        self.check_permission(&crate::security::Permission::Read)?;
+
+        let result = self.open_cursor_inner(sql).await;
+        match &result {
+            Ok(id) => {
+                self.metrics.total_queries.fetch_add(1, Ordering::Relaxed);
+                self.audit_log("OPEN_CURSOR", &format!("cursor {}: {}", id, sql), true)
+                    .await;
+            }
+            Err(e) => {
+                self.metrics.total_errors.fetch_add(1, Ordering::Relaxed);
+                self.audit_log("OPEN_CURSOR", &format!("{}: {}", sql, e), false)
+                    .await;
+            }
+        }
+        result
+    }
+
+    /// Internal: plan the query against the current snapshot and register its stream
+    async fn open_cursor_inner(&self, sql: &str) -> Result<CursorId> {
+        let table = self.get_delta_table().await?;
+        self.register_snapshot_cursor(&table, sql).await
+    }
+
+    /// Internal: register a cursor streaming `sql` over `table`
+    ///
+    /// The loaded table is a snapshot, so the stream stays on its version.
+    async fn register_snapshot_cursor(
+        &self,
+        table: &deltalake::DeltaTable,
+        sql: &str,
+    ) -> Result<CursorId> {
+        let version = table.version().unwrap_or(0);
+        let sql = self.encrypt_sql(sql)?;
+
+        let ctx = self.session_context();
+        let provider = self.mask_table(&ctx, &self.table_name(), Arc::new(table.clone()))?;
+        ctx.register_table("data", provider)?;
+        let stream = crate::query::identifiers::plan_sql_with_collation(
+            &ctx,
+            &sql,
+            self.identifier_case,
+            self.collation,
+        )
+        .await?
+        .execute_stream()
+        .await?;
+
+        self.cursors
+            .register(version, self.decrypt_stream(stream)?)
+            .await
+    }
+
+    /// Fetch up to `n` rows from a cursor
+    ///
+    /// Returns the rows and whether more remain. Fails with
+    /// `Error::CursorExpired` once the cursor has been idle past its TTL.
+    pub async fn fetch(&self, cursor: CursorId, n: usize) -> Result<(Vec<RecordBatch>, bool)> {
+        // Check read permission
+        self.check_permission(&crate::security::Permission::Read)?;
+
+        let result = self.cursors.fetch(cursor, n).await;
+        if result.is_err() {
+            self.metrics.total_errors.fetch_add(1, Ordering::Relaxed);
+        }
+        result
+    }
+
+    /// Close a cursor early, releasing its snapshot
+    pub async fn close_cursor(&self, cursor: CursorId) -> bool {
+        self.cursors.close(cursor).await
+    }
+
+    /// Cursors open on this handle, for finding ones that pin old versions
+    pub async fn list_cursors(&self) -> Result<Vec<CursorInfo>> {
+        // Check admin permission
+        self.check_permission(&crate::security::Permission::Admin)?;
+
+        Ok(self.cursors.list().await)
+    }
+
+    /// Cancel another caller's cursor, unpinning its version so vacuum can
+    /// remove the files it was reading
+    ///
+    /// Returns false if the cursor was already closed or expired. The
+    /// owner's next fetch fails with `Error::Cancelled`.
+    pub async fn cancel_cursor(&self, cursor: CursorId) -> Result<bool> {
+        // Check admin permission
+        self.check_permission(&crate::security::Permission::Admin)?;
+
+        let cancelled = self.cursors.cancel(cursor).await;
+        if cancelled {
+            self.audit_log("CANCEL_CURSOR", &format!("cursor {}", cursor), true)
+                .await;
+        }
+        Ok(cancelled)
+    }
+
+    /// Query the database at a specific timestamp (time travel)
+    ///
+    /// Timestamp is Unix epoch milliseconds. Delta Lake will find the version
+    /// that was active at or before the given timestamp.
+    pub async fn query_timestamp(&self, sql: &str, timestamp_ms: i64) -> Result<Vec<RecordBatch>> {
+        info!(
+            "Executing time travel query at timestamp {}: {}",
+            timestamp_ms,
+            self.log_redaction.redact(sql)
+        );
+
+        // Check read permission
+        self.check_permission(&crate::security::Permission::Read)?;
+
+        // Track query metrics with latency
+        let start = Instant::now();
+        let result = self.query_timestamp_inner(sql, timestamp_ms).await;
+        let latency_ms = start.elapsed().as_secs_f64() * 1000.0;
+
+        match &result {
+            Ok(_) => {
+                self.metrics.total_queries.fetch_add(1, Ordering::Relaxed);
+                let mut latencies = self.metrics.query_latencies.lock().await;
+                latencies.push(latency_ms);
+                let len = latencies.len();
+                if len > 1000 {
+                    latencies.drain(0..len - 1000);
+                }
+                self.audit_log(
+                    "SELECT_TIMESTAMP",
+                    &format!("@{}: {}", timestamp_ms, sql),
+                    true,
+                )
+                .await;
+            }
+            Err(e) => {
+                self.metrics.total_errors.fetch_add(1, Ordering::Relaxed);
+                self.audit_log(
+                    "SELECT_TIMESTAMP",
+                    &format!("@{}: {}: {}", timestamp_ms, sql, e),
+                    false,
+                )
+                .await;
+            }
+        }
+        result
+    }
+
+    /// Internal time travel query by timestamp
+    async fn query_timestamp_inner(
+        &self,
+        sql: &str,
+        timestamp_ms: i64,
+    ) -> Result<Vec<RecordBatch>> {
+        use chrono::{DateTime, Utc};
+
+        info!(
+            "Querying Delta Lake with SQL at timestamp {}: {}",
+            timestamp_ms,
+            self.log_redaction.redact(sql)
+        );
+
+        // Convert milliseconds to DateTime<Utc>
+        let timestamp_secs = timestamp_ms / 1000;
+        let timestamp_nanos = ((timestamp_ms % 1000) * 1_000_000) as u32;
+        let datetime = DateTime::<Utc>::from_timestamp(timestamp_secs, timestamp_nanos)
+            .ok_or_else(|| Error::Other(format!("Invalid timestamp: {}", timestamp_ms)))?;
+
+        // Open table at specific timestamp
+        let table = self
+            .table_as_of(crate::query::AsOf::Timestamp(datetime))
+            .await?;
+
+        // Create DataFusion context and register table
+        let ctx = self.session_context();
+        let table = self.mask_table(&ctx, &self.table_name(), Arc::new(table))?;
+        ctx.register_table("data", table)?;
+
+        // Execute query
+        let df = crate::query::identifiers::plan_sql_with_collation(
+            &ctx,
+            sql,
+            self.identifier_case,
+            self.collation,
+        )
+        .await?;
+        let batches = df.collect().await?;
+
+        info!(
+            "Time travel query at timestamp {} returned {} batches",
+            timestamp_ms,
+            batches.len()
+        );
+        Ok(batches)
+    }
+
+    /// Query a specific Parquet file (for individual file views)
+    ///
+    /// Masked columns read as the user's roles mask them.
+    pub async fn query_file(&self, file_path: &str) -> Result<Vec<RecordBatch>> {
+        self.mask_batches(self.read_file(file_path).await?)
+    }
+
+    /// Read a specific Parquet file as stored
+    async fn read_file(&self, file_path: &str) -> Result<Vec<RecordBatch>> {
+        info!("Querying specific file: {}", file_path);
+
+        let full_path = self.base_path.join(file_path);
+        if !full_path.exists() {
+            return Err(Error::RecordNotFound(format!(
+                "File not found: {}",
+                file_path
+            )));
+        }
+
+        let reader = ParquetReader::new();
+        let batch = reader.read_batch(&full_path)?;
+
+        Ok(vec![batch])
+    }
+
+    /// Query some columns of a specific Parquet file
+    ///
+    /// Only the named columns are decoded, in the order given, with the
+    /// table's current types. Columns added after the file was written read
+    /// as nulls; a column the table doesn't have is an error.
+    pub async fn query_file_columns(
+        &self,
+        file_path: &str,
+        columns: &[String],
+    ) -> Result<Vec<RecordBatch>> {
+        info!("Querying {} columns of file: {}", columns.len(), file_path);
+
+        let full_path = self.base_path.join(file_path);
+        if !full_path.exists() {
+            return Err(Error::RecordNotFound(format!(
+                "File not found: {}",
+                file_path
+            )));
+        }
+
+        let schema = self.table_schema().await?;
+        let fields = columns
+            .iter()
+            .map(|name| {
+                schema
+                    .field_with_name(name)
+                    .cloned()
+                    .map_err(|_| Error::InvalidOperation(format!("No such column: {}", name)))
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let reader = ParquetReader::new();
+        let batch = reader.read_projected(&full_path, &Schema::new(fields), MissingColumn::Null)?;
+
+        self.mask_batches(vec![batch])
+    }
+
+    /// Delete a specific file from Delta Lake
+    /// Note: Delta Lake handles deletions natively through its transaction log
+    pub async fn delete(&self, _file_path: &str) -> Result<u64> {
+        // Track delete metrics
+        self.metrics.total_deletes.fetch_add(1, Ordering::Relaxed);
+        self.metrics
+            .total_transactions
+            .fetch_add(1, Ordering::Relaxed);
+
+        // Delta Lake doesn't support individual file deletion - use delete_rows_where instead
+        Err(Error::Other(
+            "Use delete_rows_where() for row-level deletion in Delta Lake".to_string(),
+        ))
+    }
+
+    /// Delete rows matching a SQL WHERE clause
+    ///
+    /// Files whose statistics show every row matches are dropped without
+    /// being read, and files no row can match are left untouched; only the
+    /// files in between are rewritten.
+    ///
+    /// Returns the version committed and the number of rows deleted, which
+    /// are also recorded in the audit log. A predicate matching no rows
+    /// affects 0 rows and returns the current version without committing.
+    pub async fn delete_rows_where(&self, where_clause: &str) -> Result<CommitResult> {
+        info!(
+            "Deleting rows where: {}",
+            self.log_redaction.redact(where_clause)
+        );
+
+        // Check write permission
+        self.check_permission(&crate::security::Permission::Write)?;
+
+        // Buffered rows are committed first, so they're affected too
+        self.flush_write_buffer().await?;
+
+        let meter = self.quota_meter().await;
+        let result = self.delete_rows_where_inner(where_clause).await;
+        match &result {
+            Ok(commit) => {
+                self.charge_quota(meter, commit.version);
+                self.metrics.total_deletes.fetch_add(1, Ordering::Relaxed);
+                self.audit_log(
+                    "DELETE",
+                    &format!(
+                        "WHERE {} ({} rows, version {})",
+                        where_clause, commit.rows_affected, commit.version
+                    ),
+                    true,
+                )
+                .await;
+                self.maybe_refresh_views().await;
+            }
+            Err(e) => {
+                self.metrics.total_errors.fetch_add(1, Ordering::Relaxed);
+                self.audit_log("DELETE", &format!("WHERE {}: {}", where_clause, e), false)
+                    .await;
+            }
+        }
+        result
+    }
+
+    /// Internal delete method - delegates to Delta Lake native delete
+    async fn delete_rows_where_inner(&self, where_clause: &str) -> Result<CommitResult> {
+        self.delete_delta_native(where_clause).await
+    }
+
+    /// Delete rows matching a SQL WHERE clause and return them (`DELETE ... RETURNING`)
+    ///
+    /// The cursor yields the deleted rows as they were before the delete. It
+    /// reads the snapshot the delete was applied to, so it matches the deleted
+    /// set exactly even if other writers commit in between.
+    pub async fn delete_rows_where_returning(&self, where_clause: &str) -> Result<Returning> {
+        info!(
+            "Deleting rows where: {} (returning)",
+            self.log_redaction.redact(where_clause)
+        );
+
+        // Check write permission, plus read for the returned rows
+        self.check_permission(&crate::security::Permission::Write)?;
+        self.check_permission(&crate::security::Permission::Read)?;
+
+        // Buffered rows are committed first, so they're affected too
+        self.flush_write_buffer().await?;
+
+        let meter = self.quota_meter().await;
+        let result = self.delete_rows_where_returning_inner(where_clause).await;
+        match &result {
+            Ok(returning) => {
+                self.charge_quota(meter, returning.version);
+                self.metrics.total_deletes.fetch_add(1, Ordering::Relaxed);
+                self.audit_log(
+                    "DELETE",
+                    &format!(
+                        "WHERE {} ({} rows, version {}, returning cursor {})",
+                        where_clause, returning.count, returning.version, returning.cursor
+                    ),
+                    true,
+                )
+                .await;
+            }
+            Err(e) => {
+                self.metrics.total_errors.fetch_add(1, Ordering::Relaxed);
+                self.audit_log("DELETE", &format!("WHERE {}: {}", where_clause, e), false)
+                    .await;
+            }
+        }
+        result
+    }
+
+    /// Internal: open a cursor over the matching rows, then delete them from the same snapshot
+    #[instrument(
+        name = "commit",
+        skip_all,
+        fields(
+            table = %self.table_label(),
+            operation = "DELETE",
+            version = tracing::field::Empty,
+            rows = tracing::field::Empty,
+        )
+    )]
+    async fn delete_rows_where_returning_inner(&self, where_clause: &str) -> Result<Returning> {
+        use deltalake::DeltaOps;
+
+        self.check_writable()?;
+        self.check_deletion_vectors()?;
+        let table = self.get_delta_table().await?;
+        let count = self.count_matching(&table, where_clause).await?;
+        let sql = format!("SELECT * FROM data WHERE {}", where_clause);
+        let cursor = self.register_snapshot_cursor(&table, &sql).await?;
+        if count == 0 {
+            return Ok(Returning {
+                count,
+                cursor,
+                version: table.version().unwrap_or(0),
+            });
+        }
+
+        let deleted = self
+            .retry_policy
+            .run("Delta delete", || async {
+                DeltaOps(table.clone())
+                    .delete()
+                    .with_predicate(where_clause)
+                    .with_commit_properties(crate::delta_lake::commit::commit_properties())
+                    .await
+                    .map_err(Error::DeltaTable)
+            })
+            .await;
+        match deleted {
+            Ok((deleted, metrics)) => Ok(Returning {
+                count: metrics.num_deleted_rows,
+                cursor,
+                version: deleted.version().unwrap_or(0),
+            }),
+            Err(e) => {
+                self.cursors.close(cursor).await;
+                Err(e)
+            }
+        }
+    }
+
+    /// Update rows matching a SQL WHERE clause
+    ///
+    /// `assignments` pairs a column name with a SQL expression evaluated
+    /// against the old row, e.g. `("value", "value + 1")`. Returns the version
+    /// committed and the number of rows updated; a predicate matching nothing
+    /// commits no new version and returns the current one.
+    pub async fn update_rows(
+        &self,
+        assignments: &[(&str, &str)],
+        where_clause: &str,
+    ) -> Result<CommitResult> {
+        let set = Self::format_assignments(assignments);
+        info!(
+            "Updating rows SET {} WHERE {}",
+            self.log_redaction.redact(&set),
+            self.log_redaction.redact(where_clause)
+        );
+
+        // Check write permission
+        self.check_permission(&crate::security::Permission::Write)?;
+
+        // Buffered rows are committed first, so they're affected too
+        self.flush_write_buffer().await?;
+
+        let meter = self.quota_meter().await;
+        let result = self.update_rows_inner(assignments, where_clause).await;
+        match &result {
+            Ok(commit) => {
+                self.charge_quota(meter, commit.version);
+                self.metrics
+                    .total_transactions
+                    .fetch_add(1, Ordering::Relaxed);
+                self.audit_log(
+                    "UPDATE",
+                    &format!(
+                        "SET {} WHERE {} ({} rows, version {})",
+                        set, where_clause, commit.rows_affected, commit.version
+                    ),
+                    true,
+                )
+                .await;
+                self.maybe_refresh_views().await;
+            }
+            Err(e) => {
+                self.metrics.total_errors.fetch_add(1, Ordering::Relaxed);
+                self.audit_log(
+                    "UPDATE",
+                    &format!("SET {} WHERE {}: {}", set, where_clause, e),
+                    false,
+                )
+                .await;
+            }
+        }
+        result
+    }
+
+    /// Internal update method - Delta Lake native UPDATE
+    #[instrument(
+        name = "commit",
+        skip_all,
+        fields(
+            table = %self.table_label(),
+            operation = "UPDATE",
+            version = tracing::field::Empty,
+            rows = tracing::field::Empty,
+        )
+    )]
+    async fn update_rows_inner(
+        &self,
+        assignments: &[(&str, &str)],
+        where_clause: &str,
+    ) -> Result<CommitResult> {
+        let table = self.get_delta_table().await?;
+        self.check_assignments(&table, assignments)?;
+        let generated =
+            crate::metadata::generated::generated_assignments(&self.schema, assignments)?;
+        let assignments = &Self::with_generated_assignments(assignments, &generated);
+        let encrypted = self.encrypt_assignments(assignments)?;
+        let assignments = &Self::borrow_assignments(&encrypted);
+        let where_clause = &self.encrypt_predicate(where_clause)?;
+        if self.count_matching(&table, where_clause).await? == 0 {
+            info!("No rows match update criteria");
+            return CommitResult::at(&table, "UPDATE", 0).await;
+        }
+        let (table, count) = self
+            .update_delta_native(&table, assignments, where_clause)
+            .await?;
+        CommitResult::at(&table, "UPDATE", count as u64).await
+    }
+
+    /// Update rows matching `where_clause` only if they all still satisfy
+    /// `expected`, returning whether the update was applied
+    ///
+    /// A compare-and-set for optimistic clients: having read a row,
+    /// `update_if(&[("balance", "balance - 30")], "id = 7", "balance = 100")`
+    /// applies only if nobody has changed the balance since. Nothing is
+    /// applied if no row matches, or if any matching row fails `expected`.
+    ///
+    /// The check and the update run against one snapshot, and the update's
+    /// commit is refused if a concurrent commit rewrote the files it read,
+    /// such as another update of the same rows. The check then runs again
+    /// against the new snapshot, up to `CONDITIONAL_UPDATE_ATTEMPTS` times
+    /// before failing with `Error::TransactionConflict`.
+    pub async fn update_if(
+        &self,
+        assignments: &[(&str, &str)],
+        where_clause: &str,
+        expected: &str,
+    ) -> Result<bool> {
+        self.update_if_with(
+            assignments,
+            where_clause,
+            expected,
+            MultipleMatches::ApplyAll,
+        )
+        .await
+    }
+
+    /// Like `update_if`, with `multiple` deciding what a predicate matching
+    /// more than one row does
+    pub async fn update_if_with(
+        &self,
+        assignments: &[(&str, &str)],
+        where_clause: &str,
+        expected: &str,
+        multiple: MultipleMatches,
+    ) -> Result<bool> {
+        let set = Self::format_assignments(assignments);
+        info!(
+            "Updating rows SET {} WHERE {} IF {}",
+            self.log_redaction.redact(&set),
+            self.log_redaction.redact(where_clause),
+            self.log_redaction.redact(expected)
+        );
+
+        // Check write permission, plus read since the outcome reveals the rows
+        self.check_permission(&crate::security::Permission::Write)?;
+        self.check_permission(&crate::security::Permission::Read)?;
+
+        // Buffered rows are committed first, so they're compared too
+        self.flush_write_buffer().await?;
+
+        let meter = self.quota_meter().await;
+        let result = self
+            .update_if_inner(assignments, where_clause, expected, multiple)
+            .await;
+        let details = format!("SET {} WHERE {} IF {}", set, where_clause, expected);
+        match &result {
+            Ok(Some(commit)) => {
+                self.charge_quota(meter, commit.version);
+                self.metrics
+                    .total_transactions
+                    .fetch_add(1, Ordering::Relaxed);
+                self.audit_log(
+                    "UPDATE IF",
+                    &format!(
+                        "{} ({} rows, version {})",
+                        details, commit.rows_affected, commit.version
+                    ),
+                    true,
+                )
+                .await;
+                self.maybe_refresh_views().await;
+            }
+            Ok(None) => {
+                self.audit_log("UPDATE IF", &format!("{} (not applied)", details), true)
+                    .await;
+            }
+            Err(e) => {
+                self.metrics.total_errors.fetch_add(1, Ordering::Relaxed);
+                self.audit_log("UPDATE IF", &format!("{}: {}", details, e), false)
+                    .await;
+            }
+        }
+        Ok(result?.is_some())
+    }
+
+    /// Internal: compare and update, from a fresh snapshot after each
+    /// conflict; `None` if the comparison failed
+    async fn update_if_inner(
+        &self,
+        assignments: &[(&str, &str)],
+        where_clause: &str,
+        expected: &str,
+        multiple: MultipleMatches,
+    ) -> Result<Option<CommitResult>> {
+        let generated =
+            crate::metadata::generated::generated_assignments(&self.schema, assignments)?;
+        let assignments = &Self::with_generated_assignments(assignments, &generated);
+        let encrypted = self.encrypt_assignments(assignments)?;
+        let assignments = &Self::borrow_assignments(&encrypted);
+        let where_clause = &self.encrypt_predicate(where_clause)?;
+        let expected = &self.encrypt_predicate(expected)?;
+        let guarded = format!("({}) AND ({})", where_clause, expected);
+
+        let mut attempt = 1;
+        loop {
+            let table = self.get_delta_table().await?;
+            self.check_assignments(&table, assignments)?;
+            let matched = self.count_matching(&table, where_clause).await?;
+            if matched == 0 {
+                info!("No rows match the conditional update");
+                return Ok(None);
+            }
+            if matched > 1 && multiple == MultipleMatches::Error {
+                return Err(Error::InvalidOperation(format!(
+                    "Conditional update matches {} rows, expected one: {}",
+                    matched, where_clause
+                )));
+            }
+            // NULL comparisons count as failing, since the guard won't update them
+            if self.count_matching(&table, &guarded).await? != matched {
+                info!("Expected condition no longer holds; not updating");
+                return Ok(None);
+            }
+
+            match self
+                .update_delta_native(&table, assignments, &guarded)
+                .await
+            {
+                Ok((table, count)) => {
+                    return Ok(Some(
+                        CommitResult::at(&table, "UPDATE", count as u64).await?,
+                    ))
+                }
+                Err(e) if crate::storage::retry::is_commit_conflict(&e) => {
+                    if attempt >= CONDITIONAL_UPDATE_ATTEMPTS {
+                        return Err(Error::TransactionConflict(format!(
+                            "conditional update gave up after {} conflicting commits: {}",
+                            attempt, e
+                        )));
+                    }
+                    warn!(
+                        "Conditional update lost to a concurrent commit (attempt {}): {}",
+                        attempt, e
+                    );
+                    attempt += 1;
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    /// Update rows matching a SQL WHERE clause and return them (`UPDATE ... RETURNING`)
+    ///
+    /// The cursor yields the updated rows with their new values. Like
+    /// `delete_rows_where_returning`, it is computed from the snapshot the
+    /// update was applied to.
+    pub async fn update_rows_returning(
+        &self,
+        assignments: &[(&str, &str)],
+        where_clause: &str,
+    ) -> Result<Returning> {
+        let set = Self::format_assignments(assignments);
+        info!(
+            "Updating rows SET {} WHERE {} (returning)",
+            self.log_redaction.redact(&set),
+            self.log_redaction.redact(where_clause)
+        );
+
+        // Check write permission, plus read for the returned rows
+        self.check_permission(&crate::security::Permission::Write)?;
+        self.check_permission(&crate::security::Permission::Read)?;
+
+        // Buffered rows are committed first, so they're affected too
+        self.flush_write_buffer().await?;
+
+        let meter = self.quota_meter().await;
+        let result = self
+            .update_rows_returning_inner(assignments, where_clause)
+            .await;
+        match &result {
+            Ok(returning) => {
+                self.charge_quota(meter, returning.version);
+                self.metrics
+                    .total_transactions
+                    .fetch_add(1, Ordering::Relaxed);
+                self.audit_log(
+                    "UPDATE",
+                    &format!(
+                        "SET {} WHERE {} ({} rows, version {}, returning cursor {})",
+                        set, where_clause, returning.count, returning.version, returning.cursor
+                    ),
+                    true,
+                )
+                .await;
+            }
+            Err(e) => {
+                self.metrics.total_errors.fetch_add(1, Ordering::Relaxed);
+                self.audit_log(
+                    "UPDATE",
+                    &format!("SET {} WHERE {}: {}", set, where_clause, e),
+                    false,
+                )
+                .await;
+            }
+        }
+        result
+    }
+
+    /// Internal: open a cursor projecting the new values, then update the same snapshot
+    async fn update_rows_returning_inner(
+        &self,
+        assignments: &[(&str, &str)],
+        where_clause: &str,
+    ) -> Result<Returning> {
+        use datafusion::datasource::TableProvider;
+        use datafusion::prelude::{cast, ident};
+
+        let table = self.get_delta_table().await?;
+        self.check_assignments(&table, assignments)?;
+        let generated =
+            crate::metadata::generated::generated_assignments(&self.schema, assignments)?;
+        let assignments = &Self::with_generated_assignments(assignments, &generated);
+        let encrypted = self.encrypt_assignments(assignments)?;
+        let assignments = &Self::borrow_assignments(&encrypted);
+        let where_clause = &self.encrypt_predicate(where_clause)?;
+        let count = self.count_matching(&table, where_clause).await?;
+
+        // Evaluate each assignment against the old row, cast to the column
+        // type as the update will store it
+        let set_exprs: Vec<String> = assignments
+            .iter()
+            .enumerate()
+            .map(|(i, (_, expr))| format!("{} AS \"__fsdb_set_{}\"", expr, i))
+            .collect();
+        let sql = format!(
+            "SELECT {}, * FROM data WHERE {}",
+            set_exprs.join(", "),
+            where_clause
+        );
+        let ctx = self.session_context();
+        ctx.register_table("data", Arc::new(table.clone()))?;
+        let df = crate::query::identifiers::plan_sql(&ctx, &sql, self.identifier_case).await?;
+        let projection = TableProvider::schema(&table)
+            .fields()
+            .iter()
+            .map(
+                |field| match assignments.iter().position(|(c, _)| *c == field.name()) {
+                    Some(i) => cast(
+                        ident(format!("__fsdb_set_{}", i)),
+                        field.data_type().clone(),
+                    )
+                    .alias(field.name()),
+                    None => ident(field.name()),
+                },
+            )
+            .collect::<Vec<_>>();
+        // Returned as the user reads the table: masked, then decrypted
+        let returned =
+            self.mask_table(&ctx, &self.table_name(), df.select(projection)?.into_view())?;
+        let stream = ctx.read_table(returned)?.execute_stream().await?;
+        let cursor = self
+            .cursors
+            .register(table.version().unwrap_or(0), self.decrypt_stream(stream)?)
+            .await?;
+        if count == 0 {
+            return Ok(Returning {
+                count,
+                cursor,
+                version: table.version().unwrap_or(0),
+            });
+        }
+
+        match self
+            .update_delta_native(&table, assignments, where_clause)
+            .await
+        {
+            Ok((updated, count)) => Ok(Returning {
+                count,
+                cursor,
+                version: updated.version().unwrap_or(0),
+            }),
+            Err(e) => {
+                self.cursors.close(cursor).await;
+                Err(e)
+            }
+        }
+    }
+
+    /// Internal: run a Delta UPDATE against `table`'s snapshot
+    ///
+    /// Returns the updated table and the number of rows updated.
+    async fn update_delta_native(
+        &self,
+        table: &deltalake::DeltaTable,
+        assignments: &[(&str, &str)],
+        where_clause: &str,
+    ) -> Result<(deltalake::DeltaTable, usize)> {
+        use deltalake::DeltaOps;
+
+        self.check_writable()?;
+        self.check_deletion_vectors()?;
+        let (updated, metrics) = self
+            .retry_policy
+            .run("Delta update", || async {
+                let mut builder = DeltaOps(table.clone())
+                    .update()
+                    .with_predicate(where_clause)
+                    .with_commit_properties(crate::delta_lake::commit::commit_properties());
+                for (column, expr) in assignments {
+                    builder = builder.with_update(*column, *expr);
+                }
+                builder.await.map_err(Error::DeltaTable)
+            })
+            .await?;
+
+        info!(
+            "Successfully updated {} rows in Delta Lake",
+            metrics.num_updated_rows
+        );
+        Ok((updated, metrics.num_updated_rows))
+    }
+
+    /// Internal: reject empty or unknown assignment targets before touching the table
+    fn check_assignments(
+        &self,
+        table: &deltalake::DeltaTable,
+        assignments: &[(&str, &str)],
+    ) -> Result<()> {
+        if assignments.is_empty() {
+            return Err(Error::InvalidOperation(
+                "UPDATE requires at least one assignment".to_string(),
+            ));
+        }
+        let schema = datafusion::datasource::TableProvider::schema(table);
+        for (column, _) in assignments {
+            if schema.field_with_name(column).is_err() {
+                return Err(Error::InvalidOperation(format!(
+                    "UPDATE of unknown column: {}",
+                    column
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    /// Internal: number of rows in `table`'s snapshot matching `where_clause`
+    async fn count_matching(
+        &self,
+        table: &deltalake::DeltaTable,
+        where_clause: &str,
+    ) -> Result<usize> {
+        let ctx = self.session_context();
+        ctx.register_table("data", Arc::new(table.clone()))?;
+        let sql = format!("SELECT COUNT(*) FROM data WHERE {}", where_clause);
+        let batches = crate::query::identifiers::plan_sql(&ctx, &sql, self.identifier_case)
+            .await?
+            .collect()
+            .await?;
+        Ok(batches
+            .first()
+            .and_then(|batch| {
+                batch
+                    .column(0)
+                    .as_any()
+                    .downcast_ref::<arrow::array::Int64Array>()
+                    .map(|counts| counts.value(0) as usize)
+            })
+            .unwrap_or(0))
+    }
+
+    /// Internal: `assignments` followed by those recomputing generated columns
+    fn with_generated_assignments<'a>(
+        assignments: &[(&'a str, &'a str)],
+        generated: &'a [(String, String)],
+    ) -> Vec<(&'a str, &'a str)> {
+        assignments
+            .iter()
+            .copied()
+            .chain(
+                generated
+                    .iter()
+                    .map(|(column, expr)| (column.as_str(), expr.as_str())),
+            )
+            .collect()
+    }
+
+    /// `assignments` with values for encrypted columns encrypted
+    fn encrypt_assignments(&self, assignments: &[(&str, &str)]) -> Result<Vec<(String, String)>> {
+        let encryption = self.column_encryption()?;
+        assignments
+            .iter()
+            .map(|(column, expr)| {
+                let expr =
+                    encryption.encrypt_assignment(column, expr, self.column_keys.as_deref())?;
+                Ok((column.to_string(), expr))
+            })
+            .collect()
+    }
+
+    fn borrow_assignments(assignments: &[(String, String)]) -> Vec<(&str, &str)> {
+        assignments
+            .iter()
+            .map(|(column, expr)| (column.as_str(), expr.as_str()))
+            .collect()
+    }
+
+    fn format_assignments(assignments: &[(&str, &str)]) -> String {
+        assignments
+            .iter()
+            .map(|(column, expr)| format!("{} = {}", column, expr))
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
+    /// Create a MERGE operation builder
+    ///
+    /// MERGE allows INSERT, UPDATE, and DELETE operations in a single atomic transaction.
+    ///
+    /// # Example
+    /// ```no_run
+    /// use arrow::array::{Int32Array, StringArray};
+    /// use arrow::datatypes::{DataType, Field, Schema};
+    /// use arrow::record_batch::RecordBatch;
+    /// use std::sync::Arc;
+    /// use fsdb::DatabaseOps;
+    ///
+    /// # async fn example(db: &DatabaseOps) -> Result<(), Box<dyn std::error::Error>> {
+    /// // Create source data with changes
+    /// let schema = Arc::new(Schema::new(vec![
+    ///     Field::new("id", DataType::Int32, false),
+    ///     Field::new("name", DataType::Utf8, false),
+    ///     Field::new("_op", DataType::Utf8, false),  // Operation type
+    /// ]));
+    ///
+    /// let source = RecordBatch::try_new(
+    ///     schema,
+    ///     vec![
+    ///         Arc::new(Int32Array::from(vec![1, 2, 3])),
+    ///         Arc::new(StringArray::from(vec!["Alice", "Bob", "Charlie"])),
+    ///         Arc::new(StringArray::from(vec!["UPDATE", "DELETE", "INSERT"])),
+    ///     ],
+    /// )?;
+    ///
+    /// // Execute MERGE
+    /// let metrics = db.merge()
+    ///     .await?
+    ///     .with_source(source, "source")
+    ///     .on("target.id = source.id")
+    ///     .when_matched_update()
+    ///         .condition("source._op = 'UPDATE'")
+    ///         .set_all()
+    ///     .when_matched_delete()
+    ///         .condition("source._op = 'DELETE'")
+    ///         .then()
+    ///     .when_not_matched_insert()
+    ///         .condition("source._op = 'INSERT'")
+    ///         .values_all()
+    ///     .execute()
+    ///     .await?;
+    ///
+    /// println!("MERGE complete: {} inserted, {} updated, {} deleted",
+    ///          metrics.rows_inserted, metrics.rows_updated, metrics.rows_deleted);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn merge(&self) -> Result<crate::delta_lake::merge::MergeBuilder> {
+        info!("Creating MERGE operation");
+
+        // Check write permission
+        self.check_permission(&crate::security::Permission::Write)?;
+
+        self.check_writable()?;
+
+        // Buffered rows are committed first, so they're affected too
+        self.flush_write_buffer().await?;
+
+        // Open the Delta Lake table
+        use crate::storage::s3::parse_s3_url;
+        use deltalake::open_table;
+        use url::Url;
+
+        let table = if let (Some(s3_url), Some(s3_config)) = (&self.s3_url, &self.s3_config) {
+            // S3 backend
+            s3_config.open_table(parse_s3_url(s3_url)?).await?
+        } else {
+            // Local backend
+            let table_url = Url::from_directory_path(&self.base_path)
+                .map_err(|_| Error::Other("Invalid path for Delta table".to_string()))?;
+            open_table(table_url).await.map_err(Error::DeltaTable)?
+        };
+
+        let mut builder = crate::delta_lake::merge::MergeBuilder::new(table)
+            .with_table_schema(self.schema())
+            .with_log_redaction(self.log_redaction)
+            .with_size_limits(self.size_limits.clone())
+            .with_column_encryption(self.column_encryption()?, self.column_keys.clone());
+        if let Some((guard, _)) = self.quota_meter().await {
+            builder = builder.with_quota(guard);
+        }
+        Ok(builder)
+    }
+
+    /// Compact database files using Delta Lake OPTIMIZE
+    /// - Merges small Parquet files into larger ones
+    /// - Removes deleted records (Delta Lake deletion vectors)
+    /// - Bin-packs files to target size
+    pub async fn optimize(&self) -> Result<crate::delta_lake::OptimizeMetrics> {
+        self.optimize_with_control(&crate::delta_lake::MaintenanceControl::default())
+            .await
+    }
+
+    /// OPTIMIZE that reports progress and can be cancelled through `control`
+    ///
+    /// Partitions are compacted in separate commits, and cancellation takes
+    /// effect between them: partitions already compacted stay compacted, and
+    /// the metrics returned cover them with `cancelled` set.
+    pub async fn optimize_with_control(
+        &self,
+        control: &crate::delta_lake::MaintenanceControl,
+    ) -> Result<crate::delta_lake::OptimizeMetrics> {
+        info!("Running Delta Lake OPTIMIZE operation");
+
+        // Check write permission
+        self.check_permission(&crate::security::Permission::Write)?;
+
+        let result = self
+            .optimize_inner(None, None, control, self.maintenance_lock.on_conflict)
+            .await;
+        match &result {
+            Ok(metrics) => {
+                info!(
+                    "OPTIMIZE {}: {} files added, {} files removed",
+                    if metrics.cancelled {
+                        "cancelled"
+                    } else {
+                        "completed"
+                    },
+                    metrics.num_files_added,
+                    metrics.num_files_removed
+                );
+                self.audit_log(
+                    "OPTIMIZE",
+                    &format!(
+                        "compacted {} -> {} files{}",
+                        metrics.num_files_removed,
+                        metrics.num_files_added,
+                        if metrics.cancelled {
+                            " (cancelled)"
+                        } else {
+                            ""
+                        }
+                    ),
+                    true,
+                )
+                .await;
+            }
+            Err(e) => {
+                self.metrics.total_errors.fetch_add(1, Ordering::Relaxed);
+                self.audit_log("OPTIMIZE", &format!("failed: {}", e), false)
+                    .await;
+            }
+        }
+        result
+    }
+
+    /// Optimize with a filter predicate (only compact matching partitions)
+    pub async fn optimize_with_filter(
+        &self,
+        filter: &str,
+    ) -> Result<crate::delta_lake::OptimizeMetrics> {
+        info!(
+            "Running Delta Lake OPTIMIZE with filter: {}",
+            self.log_redaction.redact(filter)
+        );
+
+        // Check write permission
+        self.check_permission(&crate::security::Permission::Write)?;
+
+        let result = self
+            .optimize_inner(
+                Some(filter),
+                None,
+                &Default::default(),
+                self.maintenance_lock.on_conflict,
+            )
+            .await;
+        match &result {
+            Ok(metrics) => {
+                info!(
+                    "OPTIMIZE with filter completed: {} files added, {} files removed",
+                    metrics.num_files_added, metrics.num_files_removed
+                );
+                self.audit_log(
+                    "OPTIMIZE",
+                    &format!(
+                        "filter='{}': {} -> {} files",
+                        filter, metrics.num_files_removed, metrics.num_files_added
+                    ),
+                    true,
+                )
+                .await;
+            }
+            Err(e) => {
+                self.metrics.total_errors.fetch_add(1, Ordering::Relaxed);
+                self.audit_log("OPTIMIZE", &format!("filter='{}': {}", filter, e), false)
+                    .await;
+            }
+        }
+        result
+    }
+
+    /// Optimize with target file size
+    pub async fn optimize_with_target_size(
+        &self,
+        target_size_bytes: u64,
+    ) -> Result<crate::delta_lake::OptimizeMetrics> {
+        info!(
+            "Running Delta Lake OPTIMIZE with target size: {} bytes",
+            target_size_bytes
+        );
+
+        // Check write permission
+        self.check_permission(&crate::security::Permission::Write)?;
+
+        let result = self
+            .optimize_inner(
+                None,
+                Some(target_size_bytes),
+                &Default::default(),
+                self.maintenance_lock.on_conflict,
+            )
+            .await;
+        match &result {
+            Ok(metrics) => {
+                info!(
+                    "OPTIMIZE with target size completed: {} files added, {} files removed",
+                    metrics.num_files_added, metrics.num_files_removed
+                );
+                self.audit_log(
+                    "OPTIMIZE",
+                    &format!(
+                        "target_size={}: {} -> {} files",
+                        target_size_bytes, metrics.num_files_removed, metrics.num_files_added
+                    ),
+                    true,
+                )
+                .await;
+            }
+            Err(e) => {
+                self.metrics.total_errors.fetch_add(1, Ordering::Relaxed);
+                self.audit_log(
+                    "OPTIMIZE",
+                    &format!("target_size={}: {}", target_size_bytes, e),
+                    false,
+                )
+                .await;
+            }
+        }
+        result
+    }
+
+    /// Internal optimize implementation
+    #[instrument(
+        name = "optimize",
+        skip_all,
+        fields(table = %self.table_label(), version = tracing::field::Empty)
+    )]
+    async fn optimize_inner(
+        &self,
+        filter: Option<&str>,
+        target_size: Option<u64>,
+        control: &crate::delta_lake::MaintenanceControl,
+        on_conflict: crate::delta_lake::MaintenanceConflict,
+    ) -> Result<crate::delta_lake::OptimizeMetrics> {
+        self.check_writable()?;
+        let _guard = self.lock_maintenance("OPTIMIZE", on_conflict).await?;
+        let metrics = crate::delta_lake::optimize_table(
+            &self.base_path,
+            self.s3_url.as_deref(),
+            self.s3_config.as_ref(),
+            filter,
+            target_size.or(self.target_file_size),
+            control,
+        )
+        .await?;
+        if self.s3_url.is_none() {
+            if let Ok(Some(version)) = crate::delta_lake::latest_version(&self.base_path) {
+                tracing::Span::current().record("version", version);
+            }
+        }
+
+        // Compaction is the scan that rebuilds histograms exactly
+        if let Ok(Some(histograms)) = self.load_histograms() {
+            let columns: Vec<&str> = histograms.columns.keys().map(String::as_str).collect();
+            match self
+                .build_histograms(&columns, histograms.num_buckets)
+                .await
+            {
+                Ok(rebuilt) => {
+                    if let Err(e) = rebuilt.save(&self.base_path) {
+                        warn!("Failed to save rebuilt histograms: {}", e);
+                    }
+                }
+                Err(e) => warn!("Failed to rebuild histograms after OPTIMIZE: {}", e),
+            }
+        }
+
+        Ok(metrics)
+    }
+
+    /// Compact small files if there are more than the auto-compaction limit
+    ///
+    /// Runs after each successful insert. Compaction is put off while cursors
+    /// or explicit transactions are open, since they read the files it would
+    /// replace; the next insert after they close picks it up. The insert has
+    /// already committed, so a failed compaction is only logged.
+    async fn maybe_auto_compact(&self) {
+        let Some(config) = &self.auto_compaction else {
+            return;
+        };
+        // The small-file count comes from the local log
+        if self.s3_url.is_some() {
+            return;
+        }
+
+        let small_files =
+            match crate::delta_lake::count_small_files(&self.base_path, config.small_file_bytes) {
+                Ok(n) => n,
+                Err(e) => {
+                    warn!("Failed to count small files: {}", e);
+                    return;
+                }
+            };
+        if small_files <= config.max_small_files {
+            return;
+        }
+
+        if !self.open_transactions.lock().unwrap().is_empty()
+            || !self.cursors.pinned_versions().await.is_empty()
+        {
+            info!(
+                "Deferring auto-compaction of {} small files while cursors or transactions are open",
+                small_files
+            );
+            return;
+        }
+        if self.auto_compacting.swap(true, Ordering::SeqCst) {
+            return;
+        }
+
+        info!(
+            "Auto-compacting {} small files (limit {})",
+            small_files, config.max_small_files
+        );
+        let result = self
+            .optimize_inner(
+                None,
+                config.target_size,
+                &Default::default(),
+                crate::delta_lake::MaintenanceConflict::Fail,
+            )
+            .await;
+        self.auto_compacting.store(false, Ordering::SeqCst);
+        match result {
+            Err(Error::MaintenanceInProgress(holder)) => {
+                info!("Skipping auto-compaction: {}", holder);
+            }
+            Ok(metrics) => {
+                self.audit_log(
+                    "AUTO_OPTIMIZE",
+                    &format!(
+                        "{} small files: {} -> {} files",
+                        small_files, metrics.files_before, metrics.files_after
+                    ),
+                    true,
+                )
+                .await;
+                *self.last_auto_compaction.lock().await = Some(metrics);
+            }
+            Err(e) => {
+                warn!("Auto-compaction failed: {}", e);
+                self.metrics.total_errors.fetch_add(1, Ordering::Relaxed);
+                self.audit_log("AUTO_OPTIMIZE", &format!("failed: {}", e), false)
+                    .await;
+            }
+        }
+    }
+
+    /// Metrics of the last automatic compaction, if one has run
+    pub async fn last_auto_compaction(&self) -> Option<crate::delta_lake::OptimizeMetrics> {
+        self.last_auto_compaction.lock().await.clone()
+    }
+
+    /// Start compacting and vacuuming the table in the background
+    ///
+    /// A task checks the table every `policy.poll_interval`. Once no commit
+    /// has landed for `policy.quiet_period` and nothing is buffered, it runs
+    /// OPTIMIZE when there are more than `policy.max_small_files` small files
+    /// to merge or the oldest is older than `policy.max_small_file_age`, and
+    /// VACUUM every `policy.vacuum_interval`. Compaction waits while cursors
+    /// or transactions are open, and a run that finds another maintenance
+    /// run holding the lock is retried on the next check. The task holds no
+    /// reference to the database: it ends with `stop_maintenance` or when the
+    /// last handle is dropped. Local tables only.
+    pub async fn start_maintenance(
+        self: &Arc<Self>,
+        policy: crate::delta_lake::MaintenancePolicy,
+    ) -> Result<()> {
+        // Check write permission
+        self.check_permission(&crate::security::Permission::Write)?;
+        self.check_writable()?;
+
+        if self.s3_url.is_some() {
+            return Err(Error::InvalidOperation(
+                "Background maintenance is only available for local tables".to_string(),
+            ));
+        }
+        if policy.poll_interval.is_zero() {
+            return Err(Error::InvalidOperation(
+                "Maintenance poll interval must be greater than zero".to_string(),
+            ));
+        }
+
+        let mut scheduler = self.maintenance_scheduler.lock().await;
+        if scheduler
+            .as_ref()
+            .is_some_and(|handle| !handle.is_finished())
+        {
+            return Err(Error::InvalidOperation(
+                "Maintenance scheduler is already running".to_string(),
+            ));
+        }
+
+        info!("Starting maintenance scheduler: {:?}", policy);
+        // Commits already in the log have settled
+        let state = Arc::new(tokio::sync::Mutex::new(MaintenanceState {
+            seen_version: crate::delta_lake::latest_version(&self.base_path)?,
+            seen_at: Instant::now()
+                .checked_sub(policy.quiet_period)
+                .unwrap_or_else(Instant::now),
+            vacuumed_at: std::time::SystemTime::now(),
+        }));
+        *self.maintenance_status.lock().unwrap() = crate::delta_lake::MaintenanceStatus {
+            running: true,
+            ..Default::default()
+        };
+
+        let db = Arc::downgrade(self);
+        let poll_interval = policy.poll_interval;
+        *scheduler = Some(crate::delta_lake::scheduler::SchedulerHandle::spawn(
+            poll_interval,
+            move || {
+                let db = db.clone();
+                let policy = policy.clone();
+                let state = state.clone();
+                async move {
+                    let Some(db) = db.upgrade() else {
+                        return false;
+                    };
+                    db.maintenance_pass(&policy, &mut *state.lock().await).await;
+                    true
+                }
+            },
+        ));
+        self.audit_log("START_MAINTENANCE", "", true).await;
+        Ok(())
+    }
+
+    /// Stop the background maintenance task, waiting for a run in progress
     ///
-    /// # async fn example(db: &DatabaseOps) -> Result<(), Box<dyn std::error::Error>> {
-    /// // Create source data with changes
-    /// let schema = Arc::new(Schema::new(vec![
-    ///     Field::new("id", DataType::Int32, false),
-    ///     Field::new("name", DataType::Utf8, false),
-    ///     Field::new("_op", DataType::Utf8, false),  // Operation type
-    /// ]));
+    /// Does nothing if none is running.
+    pub async fn stop_maintenance(&self) {
+        let Some(handle) = self.maintenance_scheduler.lock().await.take() else {
+            return;
+        };
+        handle.stop().await;
+        self.maintenance_status.lock().unwrap().running = false;
+        self.audit_log("STOP_MAINTENANCE", "", true).await;
+    }
+
+    /// What the background maintenance task has done since it was started
+    pub async fn maintenance_status(&self) -> crate::delta_lake::MaintenanceStatus {
+        let running = self
+            .maintenance_scheduler
+            .lock()
+            .await
+            .as_ref()
+            .is_some_and(|handle| !handle.is_finished());
+        crate::delta_lake::MaintenanceStatus {
+            running,
+            ..self.maintenance_status.lock().unwrap().clone()
+        }
+    }
+
+    /// One check of the background maintenance task
+    ///
+    /// The runs have already been decided on, so failures are only logged
+    /// and recorded in the status.
+    async fn maintenance_pass(
+        &self,
+        policy: &crate::delta_lake::MaintenancePolicy,
+        state: &mut MaintenanceState,
+    ) {
+        {
+            let mut status = self.maintenance_status.lock().unwrap();
+            status.checks += 1;
+            status.last_check = Some(std::time::SystemTime::now());
+        }
+
+        let version = match crate::delta_lake::latest_version(&self.base_path) {
+            Ok(version) => version,
+            Err(e) => {
+                warn!("Maintenance check failed to read the table version: {}", e);
+                return;
+            }
+        };
+        if version != state.seen_version {
+            state.seen_version = version;
+            state.seen_at = Instant::now();
+        }
+        if state.seen_at.elapsed() < policy.quiet_period || self.buffered_rows().await > 0 {
+            return;
+        }
+
+        let (small_files, oldest) = match crate::delta_lake::scheduler::small_files(
+            &self.base_path,
+            policy.small_file_bytes,
+        ) {
+            Ok(small_files) => small_files,
+            Err(e) => {
+                warn!("Failed to count small files: {}", e);
+                return;
+            }
+        };
+        let now = chrono::Utc::now().timestamp_millis();
+        if crate::delta_lake::scheduler::optimize_due(policy, small_files, oldest, now) {
+            let open_transactions = !self.open_transactions.lock().unwrap().is_empty();
+            if open_transactions || !self.cursors.pinned_versions().await.is_empty() {
+                info!(
+                    "Deferring scheduled compaction of {} small files while cursors or transactions are open",
+                    small_files
+                );
+            } else if !self.auto_compacting.swap(true, Ordering::SeqCst) {
+                info!("Scheduled compaction of {} small files", small_files);
+                let result = self
+                    .optimize_inner(
+                        None,
+                        policy.target_size,
+                        &Default::default(),
+                        crate::delta_lake::MaintenanceConflict::Fail,
+                    )
+                    .await;
+                self.auto_compacting.store(false, Ordering::SeqCst);
+                match result {
+                    Err(Error::MaintenanceInProgress(holder)) => {
+                        info!("Skipping scheduled compaction: {}", holder);
+                    }
+                    Ok(metrics) => {
+                        self.audit_log(
+                            "AUTO_OPTIMIZE",
+                            &format!(
+                                "scheduled, {} small files: {} -> {} files",
+                                small_files, metrics.files_before, metrics.files_after
+                            ),
+                            true,
+                        )
+                        .await;
+                        let mut status = self.maintenance_status.lock().unwrap();
+                        status.optimize_runs += 1;
+                        status.last_optimize = Some(metrics);
+                        status.last_error = None;
+                    }
+                    Err(e) => {
+                        warn!("Scheduled compaction failed: {}", e);
+                        self.metrics.total_errors.fetch_add(1, Ordering::Relaxed);
+                        self.audit_log("AUTO_OPTIMIZE", &format!("failed: {}", e), false)
+                            .await;
+                        self.maintenance_status.lock().unwrap().last_error = Some(e.to_string());
+                    }
+                }
+            }
+        }
+
+        if crate::delta_lake::scheduler::vacuum_due(policy, state.vacuumed_at) {
+            let result = self
+                .vacuum_inner(
+                    std::time::Duration::from_secs(
+                        policy.vacuum_retention_hours.saturating_mul(3600),
+                    ),
+                    false,
+                    false,
+                    &Default::default(),
+                    crate::delta_lake::MaintenanceConflict::Fail,
+                )
+                .await;
+            match result {
+                Err(Error::MaintenanceInProgress(holder)) => {
+                    info!("Skipping scheduled VACUUM: {}", holder);
+                }
+                Ok(report) => {
+                    state.vacuumed_at = std::time::SystemTime::now();
+                    self.audit_log(
+                        "AUTO_VACUUM",
+                        &format!(
+                            "retention={}h: {} files deleted",
+                            policy.vacuum_retention_hours, report.files_deleted
+                        ),
+                        true,
+                    )
+                    .await;
+                    let mut status = self.maintenance_status.lock().unwrap();
+                    status.vacuum_runs += 1;
+                    status.last_vacuum = Some(report);
+                    status.last_vacuum_at = Some(state.vacuumed_at);
+                    status.last_error = None;
+                }
+                Err(e) => {
+                    // Retried at the next interval rather than every check
+                    state.vacuumed_at = std::time::SystemTime::now();
+                    warn!("Scheduled VACUUM failed: {}", e);
+                    self.metrics.total_errors.fetch_add(1, Ordering::Relaxed);
+                    self.audit_log("AUTO_VACUUM", &format!("failed: {}", e), false)
+                        .await;
+                    self.maintenance_status.lock().unwrap().last_error = Some(e.to_string());
+                }
+            }
+        }
+    }
+
+    /// Legacy compact method - delegates to optimize()
+    pub async fn compact(&self) -> Result<()> {
+        self.optimize().await.map(|_| ())
+    }
+
+    /// Legacy compact with custom options - delegates to optimize_with_target_size
+    pub async fn compact_with_options(&self, min_file_size: u64) -> Result<()> {
+        // Use min_file_size as target size
+        self.optimize_with_target_size(min_file_size)
+            .await
+            .map(|_| ())
+    }
+
+    /// VACUUM: Physically delete old Parquet files no longer referenced by Delta Lake
+    ///
+    /// - `retention_hours`: Minimum age in hours for files to be eligible for deletion
+    /// - Files newer than retention period are kept (for time travel safety)
+    /// - Default retention is usually 168 hours (7 days) in production
+    /// - Use retention=0 only for testing or when time travel is not needed
+    ///
+    /// Returns the number of files deleted, for local tables the bytes freed,
+    /// and the oldest version still readable. Fails with
+    /// `Error::InvalidOperation` below the minimum retention set with
+    /// `with_vacuum_min_retention`; see `vacuum_with_retention` to override it.
+    pub async fn vacuum(&self, retention_hours: u64) -> Result<crate::delta_lake::VacuumReport> {
+        self.vacuum_with_control(
+            retention_hours,
+            &crate::delta_lake::MaintenanceControl::default(),
+        )
+        .await
+    }
+
+    /// VACUUM that reports progress and can be cancelled through `control`
+    ///
+    /// Cancellation takes effect between file deletions. Files already
+    /// deleted stay deleted; the rest are picked up by the next VACUUM.
+    pub async fn vacuum_with_control(
+        &self,
+        retention_hours: u64,
+        control: &crate::delta_lake::MaintenanceControl,
+    ) -> Result<crate::delta_lake::VacuumReport> {
+        self.vacuum_run(
+            std::time::Duration::from_secs(retention_hours.saturating_mul(3600)),
+            false,
+            control,
+        )
+        .await
+    }
+
+    /// VACUUM keeping files removed within `retention`
+    ///
+    /// With `force`, a retention below the minimum set with
+    /// `with_vacuum_min_retention` is accepted, at the cost of time travel to
+    /// the versions whose files it deletes; that takes admin permission.
+    pub async fn vacuum_with_retention(
+        &self,
+        retention: std::time::Duration,
+        force: bool,
+    ) -> Result<crate::delta_lake::VacuumReport> {
+        self.vacuum_run(
+            retention,
+            force,
+            &crate::delta_lake::MaintenanceControl::default(),
+        )
+        .await
+    }
+
+    async fn vacuum_run(
+        &self,
+        retention: std::time::Duration,
+        force: bool,
+        control: &crate::delta_lake::MaintenanceControl,
+    ) -> Result<crate::delta_lake::VacuumReport> {
+        let retention_label = crate::delta_lake::operations::format_retention(retention);
+        info!(
+            "Running Delta Lake VACUUM with {} retention",
+            retention_label
+        );
+
+        // Check write permission
+        self.check_permission(&crate::security::Permission::Write)?;
+
+        // Check admin permission to go below the minimum
+        let forced = force && retention < self.vacuum_min_retention;
+        if forced {
+            self.check_permission(&crate::security::Permission::Admin)?;
+        }
+
+        let result = self
+            .vacuum_inner(
+                retention,
+                force,
+                false,
+                control,
+                self.maintenance_lock.on_conflict,
+            )
+            .await;
+        match &result {
+            Ok(report) => {
+                info!(
+                    "VACUUM {}: {} files deleted, {} bytes reclaimed",
+                    if report.cancelled {
+                        "cancelled"
+                    } else {
+                        "completed"
+                    },
+                    report.files_deleted,
+                    report.bytes_reclaimed
+                );
+                self.audit_log(
+                    "VACUUM",
+                    &format!(
+                        "retention={}{}: {} files deleted{}",
+                        retention_label,
+                        if forced { " (forced)" } else { "" },
+                        report.files_deleted,
+                        if report.cancelled { " (cancelled)" } else { "" }
+                    ),
+                    true,
+                )
+                .await;
+            }
+            Err(e) => {
+                self.metrics.total_errors.fetch_add(1, Ordering::Relaxed);
+                self.audit_log(
+                    "VACUUM",
+                    &format!("retention={}: {}", retention_label, e),
+                    false,
+                )
+                .await;
+            }
+        }
+        result
+    }
+
+    /// VACUUM dry run: Preview what files would be deleted without actually deleting
+    /// Returns list of file paths that would be deleted
+    pub async fn vacuum_dry_run(&self, retention_hours: u64) -> Result<Vec<String>> {
+        info!(
+            "Running Delta Lake VACUUM dry run with {} hour retention",
+            retention_hours
+        );
+
+        // Check read permission for dry run
+        self.check_permission(&crate::security::Permission::Read)?;
+
+        self.vacuum_inner_dry_run(retention_hours).await
+    }
+
+    /// Keep the `n` most recent versions readable across VACUUM
+    ///
+    /// Files referenced by those versions survive VACUUM regardless of the
+    /// retention period passed in. The default of 0 protects only the latest.
+    pub fn with_vacuum_keep_versions(mut self, n: usize) -> Self {
+        self.vacuum_keep_versions = n;
+        self
+    }
+
+    /// Refuse VACUUM retentions shorter than `retention`
+    ///
+    /// Protects time travel to versions younger than `retention`: a VACUUM
+    /// or dry run asking for less fails with `Error::InvalidOperation`
+    /// unless forced through `vacuum_with_retention`. Sessions from `login`
+    /// and handles from `table` keep the minimum. The default of zero
+    /// accepts any retention.
+    pub fn with_vacuum_min_retention(mut self, retention: std::time::Duration) -> Self {
+        self.vacuum_min_retention = retention;
+        self
+    }
+
+    /// Set how OPTIMIZE, VACUUM and Z-ORDER runs wait for each other
+    ///
+    /// By default a run waits for one already in progress on the table, and
+    /// only runs in this process are seen. Automatic compaction never waits:
+    /// it is skipped while another run holds the lock.
+    pub fn with_maintenance_lock(
+        mut self,
+        config: crate::delta_lake::MaintenanceLockConfig,
+    ) -> Self {
+        self.maintenance_lock = config;
+        self
+    }
+
+    /// Take the table's maintenance lock for `operation`
+    async fn lock_maintenance(
+        &self,
+        operation: &str,
+        on_conflict: crate::delta_lake::MaintenanceConflict,
+    ) -> Result<crate::delta_lake::MaintenanceGuard> {
+        let config = crate::delta_lake::MaintenanceLockConfig {
+            on_conflict,
+            ..self.maintenance_lock.clone()
+        };
+        crate::delta_lake::maintenance_lock::acquire(
+            &self.base_path,
+            operation,
+            &config,
+            self.s3_url.is_none(),
+        )
+        .await
+    }
+
+    /// Keep commits in `_delta_log` for `config.retention` once checkpointed
+    ///
+    /// Applies to the cleanup that follows each checkpoint, whether written by
+    /// `checkpoint` or after `config.checkpoint_interval` commits. Separate
+    /// from VACUUM's retention, which only covers data files.
+    pub fn with_log_retention(mut self, config: crate::delta_lake::LogRetentionConfig) -> Self {
+        self.log_retention = config;
+        self
+    }
+
+    /// Retry transient storage errors according to `policy`
+    ///
+    /// Applies to opening the table, queries, and Delta write and delete
+    /// commits. Use `RetryPolicy::none()` to fail on the first error.
+    pub fn with_retry_policy(mut self, policy: crate::storage::retry::RetryPolicy) -> Self {
+        self.retry_policy = policy;
+        self
+    }
+
+    /// Mask literals (and optionally hash identifiers) in SQL that is logged
+    ///
+    /// Audit entries are redacted the same way; the original text is kept
+    /// alongside them and only returned to admins by `get_audit_log`.
+    pub fn with_log_redaction(mut self, policy: crate::security::LogRedaction) -> Self {
+        self.log_redaction = policy;
+        self
+    }
+
+    /// Compact small files automatically once there are too many of them
+    ///
+    /// After each insert the files below `config.small_file_bytes` are
+    /// counted, and OPTIMIZE bin-packs them once more than
+    /// `config.max_small_files` could be merged. Disabled by default, and
+    /// only available for local tables.
+    pub fn with_auto_compaction(mut self, config: crate::delta_lake::AutoCompaction) -> Self {
+        self.auto_compaction = Some(config);
+        self
+    }
+
+    /// Put scratch data in `config.temp_dir` and cap its total size
+    ///
+    /// Covers query operators that spill to disk, bulk load reject files
+    /// without an explicit path, and the NFS disk cache. Operations that need
+    /// more scratch space than the cap leaves fail with
+    /// `Error::SpillLimitExceeded`; the NFS cache evicts instead.
+    pub fn with_spill_config(mut self, config: crate::storage::spill::SpillConfig) -> Self {
+        self.spill = Arc::new(crate::storage::spill::SpillManager::new(config));
+        self
+    }
+
+    /// Scratch space accounting shared by everything that spills to disk
+    pub fn spill(&self) -> &Arc<crate::storage::spill::SpillManager> {
+        &self.spill
+    }
+
+    /// Limit the memory each query may use to `max_bytes`
+    ///
+    /// Hash join build sides, aggregation state and sort buffers draw from
+    /// one pool per query. Operators that can spill go to the spill directory
+    /// when the pool runs dry; the others fail the query with
+    /// `Error::QueryMemoryExceeded`, leaving other queries unaffected.
+    /// `query_with_memory_limit` overrides it per call. Unlimited by default.
+    pub fn with_query_memory_limit(mut self, max_bytes: usize) -> Self {
+        self.query_memory_limit = Some(max_bytes);
+        self
+    }
+
+    /// Read at most `files` data files at once in each query
+    ///
+    /// A query's files are split into that many groups which are read and
+    /// decoded concurrently, one file at a time per group, so a many-file
+    /// scan isn't serial but doesn't flood the storage backend with requests
+    /// either. Cursors prefetch from the same number of groups, each a batch
+    /// ahead at most. `ORDER BY` results are merged back into order; others
+    /// come in whatever order the groups produce them. An error reading one
+    /// file cancels the other groups. Unset, DataFusion only splits scans of
+    /// files over 10 MB, one group per CPU.
+    pub fn with_scan_concurrency(mut self, files: usize) -> Self {
+        self.scan_concurrency = Some(files.max(1));
+        self
+    }
+
+    /// Data files a query reads at once, at most
+    pub fn scan_concurrency(&self) -> usize {
+        self.scan_concurrency.unwrap_or_else(|| {
+            std::thread::available_parallelism()
+                .map(|n| n.get())
+                .unwrap_or(1)
+        })
+    }
+
+    /// Name the Parquet files inserts write after `naming`'s template
+    ///
+    /// Applies to inserts, including buffered flushes, on local tables when
+    /// the rows carry exactly the table's columns. Inserts that evolve the
+    /// schema, and files written by deletes, updates, merges and compaction,
+    /// keep delta-rs's names.
+    pub fn with_file_naming(mut self, naming: crate::delta_lake::FileNaming) -> Self {
+        self.file_naming = Some(naming);
+        self
+    }
+
+    /// Choose per column which statistics inserts record in the Delta log
+    ///
+    /// Applies to the same inserts as `with_file_naming`. Columns whose
+    /// statistics are dropped are read in full by every filtered query, so
+    /// keep them on the columns queries filter by.
+    pub fn with_stats_config(mut self, config: crate::delta_lake::StatsConfig) -> Self {
+        self.stats_config = Some(config);
+        self
+    }
+
+    /// Use `keys` to encrypt and decrypt the table's encrypted columns
+    ///
+    /// Keys stay in memory; see `security::column_encryption`. Without the
+    /// key of an encrypted column, rows can't be inserted and equality
+    /// queries on it fail, and it reads as ciphertext or its mask.
+    pub fn with_column_keys(mut self, keys: crate::security::ColumnKeys) -> Self {
+        self.column_keys = Some(Arc::new(keys));
+        self
+    }
+
+    /// Aim for data files of `bytes` each
+    ///
+    /// Inserts and batch-buffer flushes larger than that are cut into row
+    /// slices written to files of about `bytes` each, sized from a sample of
+    /// the data as it encodes (see `delta_lake::file_size`). A row larger
+    /// than `bytes` still goes in one file. OPTIMIZE and auto-compaction
+    /// without a size of their own bin-pack small files up to `bytes`.
+    pub fn with_target_file_size_bytes(mut self, bytes: u64) -> Self {
+        self.target_file_size = Some(bytes.max(1));
+        self
+    }
+
+    /// Data file size writes and compaction aim for, if set
+    pub fn target_file_size_bytes(&self) -> Option<u64> {
+        self.target_file_size
+    }
+
+    /// Remember idempotency keys for `window` (24 hours by default)
+    ///
+    /// A key reused after its window is treated as new.
+    pub fn with_idempotency_window(mut self, window: std::time::Duration) -> Self {
+        self.idempotency_window = window;
+        self
+    }
+
+    /// Reject writes with rows or values larger than `limits` allow
+    ///
+    /// Inserts fail with `Error::RowTooLarge` or `Error::FieldTooLarge`
+    /// before anything is committed, as do CSV writes through the mount,
+    /// which are checked before they're parsed. Unlimited by default.
+    pub fn with_size_limits(mut self, limits: crate::storage::limits::SizeLimits) -> Self {
+        self.size_limits = limits;
+        self
+    }
+
+    /// Size limits set with `with_size_limits`
+    pub fn size_limits(&self) -> &crate::storage::limits::SizeLimits {
+        &self.size_limits
+    }
+
+    /// Insert `batch` unless a write with `key` was already committed
+    ///
+    /// A retry with the same key returns the original `CommitResult`
+    /// without inserting again. See `crate::metadata::idempotency` for how
+    /// a crash between the commit and recording the key is handled.
+    pub async fn insert_idempotent(&self, batch: RecordBatch, key: &str) -> Result<CommitResult> {
+        self.idempotent(key, "INSERT", self.insert(batch)).await
+    }
+
+    /// Delete rows matching `where_clause` unless a write with `key` was already committed
+    pub async fn delete_rows_where_idempotent(
+        &self,
+        where_clause: &str,
+        key: &str,
+    ) -> Result<CommitResult> {
+        self.idempotent(key, "DELETE", self.delete_rows_where(where_clause))
+            .await
+    }
+
+    /// Update rows matching `where_clause` unless a write with `key` was already committed
+    pub async fn update_rows_idempotent(
+        &self,
+        assignments: &[(&str, &str)],
+        where_clause: &str,
+        key: &str,
+    ) -> Result<CommitResult> {
+        self.idempotent(key, "UPDATE", self.update_rows(assignments, where_clause))
+            .await
+    }
+
+    /// Run `write` once per idempotency key, recording its result in the ledger
+    async fn idempotent(
+        &self,
+        key: &str,
+        operation: &str,
+        write: impl std::future::Future<Output = Result<CommitResult>>,
+    ) -> Result<CommitResult> {
+        use crate::metadata::IdempotencyLedger;
+
+        // A recorded result is only handed to callers who could have written it
+        self.check_permission(&crate::security::Permission::Write)?;
+
+        // Other handles on the table, such as sessions and NFS exports, share
+        // the ledger, so they take the same lock
+        let lock = IdempotencyLedger::lock(&self.base_path);
+        let _guard = lock.lock().await;
+        let ledger = IdempotencyLedger::load(&self.base_path)?;
+        let now = chrono::Utc::now().timestamp_millis();
+        if let Some(result) = ledger.lookup(key, operation, self.idempotency_window, now)? {
+            info!(
+                "Idempotency key {} already committed version {}, not applying {} again",
+                key, result.version, operation
+            );
+            return Ok(result);
+        }
+
+        let result = write.await?;
+
+        // Failed writes aren't recorded, so they can be retried with the same
+        // key. Read again, to keep what other processes recorded meanwhile.
+        let mut ledger = IdempotencyLedger::load(&self.base_path).unwrap_or(ledger);
+        ledger.record(
+            key,
+            result.clone(),
+            self.idempotency_window,
+            chrono::Utc::now().timestamp_millis(),
+        );
+        if let Err(e) = ledger.save(&self.base_path) {
+            // The write is committed; failing now would only invite a duplicate retry
+            warn!("Failed to record idempotency key {}: {}", key, e);
+        }
+        Ok(result)
+    }
+
+    /// Run `write` with `metadata` recorded in the `commitInfo` of every
+    /// commit it makes
+    ///
+    /// Use it for lineage, such as the pipeline or source system behind a
+    /// write; `history` returns the metadata with each version. Fails with
+    /// `Error::CommitMetadataTooLarge` without writing if the keys and values
+    /// add up to more than `delta_lake::commit::MAX_COMMIT_METADATA_BYTES`.
+    pub async fn with_commit_metadata<T>(
+        &self,
+        metadata: HashMap<String, String>,
+        write: impl std::future::Future<Output = Result<T>>,
+    ) -> Result<T> {
+        crate::delta_lake::commit::with_metadata(metadata, write).await
+    }
+
+    /// Insert `batch`, recording `metadata` in the commit
+    pub async fn insert_with_metadata(
+        &self,
+        batch: RecordBatch,
+        metadata: HashMap<String, String>,
+    ) -> Result<CommitResult> {
+        self.with_commit_metadata(metadata, self.insert(batch))
+            .await
+    }
+
+    /// Delete rows matching `where_clause`, recording `metadata` in the commit
+    pub async fn delete_rows_where_with_metadata(
+        &self,
+        where_clause: &str,
+        metadata: HashMap<String, String>,
+    ) -> Result<CommitResult> {
+        self.with_commit_metadata(metadata, self.delete_rows_where(where_clause))
+            .await
+    }
+
+    /// Update rows matching `where_clause`, recording `metadata` in the commit
+    pub async fn update_rows_with_metadata(
+        &self,
+        assignments: &[(&str, &str)],
+        where_clause: &str,
+        metadata: HashMap<String, String>,
+    ) -> Result<CommitResult> {
+        self.with_commit_metadata(metadata, self.update_rows(assignments, where_clause))
+            .await
+    }
+
+    /// Report DataFusion running out of disk or memory as a typed error
     ///
-    /// let source = RecordBatch::try_new(
-    ///     schema,
-    ///     vec![
-    ///         Arc::new(Int32Array::from(vec![1, 2, 3])),
-    ///         Arc::new(StringArray::from(vec!["Alice", "Bob", "Charlie"])),
-    ///         Arc::new(StringArray::from(vec!["UPDATE", "DELETE", "INSERT"])),
-    ///     ],
-    /// )?;
+    /// Exhausting its share of the spill cap is `SpillLimitExceeded`, and
+    /// exhausting the query's memory pool is `QueryMemoryExceeded`.
+    fn execution_error(
+        &self,
+        e: datafusion::error::DataFusionError,
+        memory_limit: Option<usize>,
+    ) -> Error {
+        use datafusion::error::DataFusionError;
+
+        let DataFusionError::ResourcesExhausted(msg) = e.find_root() else {
+            return Error::InvalidOperation(e.to_string());
+        };
+        if msg.contains("disk") {
+            if let Some(limit) = self.spill.config().max_spill_bytes {
+                return Error::SpillLimitExceeded {
+                    requested: 0,
+                    used: self.spill.used(),
+                    limit,
+                };
+            }
+        } else if let Some(limit) = memory_limit {
+            return Error::QueryMemoryExceeded {
+                limit: limit as u64,
+                detail: msg.clone(),
+            };
+        }
+        Error::InvalidOperation(e.to_string())
+    }
+
+    /// DataFusion context for this table's SQL, spilling under the spill config
+    pub(crate) fn session_context(&self) -> datafusion::prelude::SessionContext {
+        self.session_context_with_memory_limit(self.query_memory_limit)
+    }
+
+    /// Like `session_context`, with a memory pool of `memory_limit` bytes
+    fn session_context_with_memory_limit(
+        &self,
+        memory_limit: Option<usize>,
+    ) -> datafusion::prelude::SessionContext {
+        crate::query::identifiers::session_context_with_spill(
+            self.identifier_case,
+            &self.spill,
+            memory_limit,
+            self.scan_concurrency,
+        )
+    }
+
+    /// Configure deadlock detection for transaction locks
     ///
-    /// // Execute MERGE
-    /// let metrics = db.merge()
-    ///     .await?
-    ///     .with_source(source, "source")
-    ///     .on("target.id = source.id")
-    ///     .when_matched_update()
-    ///         .condition("source._op = 'UPDATE'")
-    ///         .set_all()
-    ///     .when_matched_delete()
-    ///         .condition("source._op = 'DELETE'")
-    ///         .then()
-    ///     .when_not_matched_insert()
-    ///         .condition("source._op = 'INSERT'")
-    ///         .values_all()
-    ///     .execute()
-    ///     .await?;
+    /// Locks already held under the previous configuration are dropped, so
+    /// call this before starting transactions.
+    pub fn with_lock_manager_config(
+        mut self,
+        config: crate::lock_manager::LockManagerConfig,
+    ) -> Self {
+        self.lock_manager = Arc::new(crate::lock_manager::LockManager::new(config));
+        self
+    }
+
+    /// Write a checkpoint of the latest version, then clean up the log
     ///
-    /// println!("MERGE complete: {} inserted, {} updated, {} deleted",
-    ///          metrics.rows_inserted, metrics.rows_updated, metrics.rows_deleted);
-    /// # Ok(())
-    /// # }
-    /// ```
-    pub async fn merge(&self) -> Result<crate::delta_lake::merge::MergeBuilder> {
-        info!("Creating MERGE operation");
+    /// Log files from before the checkpoint are deleted once every version
+    /// committed within the log retention period, every version pinned by an
+    /// open cursor and the VACUUM keep-N window can be rebuilt without them.
+    /// Only available for local tables.
+    pub async fn checkpoint(&self) -> Result<crate::delta_lake::LogCleanupReport> {
+        info!("Writing checkpoint");
 
         // Check write permission
         self.check_permission(&crate::security::Permission::Write)?;
 
-        // Open the Delta Lake table
-        use crate::storage::s3::parse_s3_url;
-        use deltalake::{open_table, open_table_with_storage_options};
-        use url::Url;
+        let result = self.checkpoint_inner().await;
+        match &result {
+            Ok(report) => {
+                self.audit_log(
+                    "CHECKPOINT",
+                    &format!(
+                        "version {:?}: {} log files deleted",
+                        report.checkpoint_version, report.files_deleted
+                    ),
+                    true,
+                )
+                .await;
+            }
+            Err(e) => {
+                self.metrics.total_errors.fetch_add(1, Ordering::Relaxed);
+                self.audit_log("CHECKPOINT", &e.to_string(), false).await;
+            }
+        }
+        result
+    }
 
-        let table = if let (Some(s3_url), Some(storage_options)) =
-            (&self.s3_url, &self.s3_storage_options)
-        {
-            // S3 backend
-            let s3_url_parsed = parse_s3_url(s3_url)?;
-            open_table_with_storage_options(s3_url_parsed, storage_options.clone())
-                .await
-                .map_err(Error::DeltaTable)?
+    async fn checkpoint_inner(&self) -> Result<crate::delta_lake::LogCleanupReport> {
+        if self.s3_url.is_some() {
+            return Err(Error::InvalidOperation(
+                "Checkpoints are only supported for local tables".to_string(),
+            ));
+        }
+
+        let table = self.get_delta_table().await?;
+        deltalake::checkpoints::create_checkpoint(&table, None)
+            .await
+            .map_err(Error::DeltaTable)?;
+
+        let protection = self.vacuum_protection().await;
+        crate::delta_lake::cleanup_log(
+            &self.base_path,
+            &self.log_retention,
+            &protection,
+            std::time::SystemTime::now(),
+        )
+    }
+
+    /// Checkpoint once `checkpoint_interval` commits follow the last checkpoint
+    ///
+    /// Runs after each successful insert. The insert has already committed,
+    /// so a failed checkpoint is only logged.
+    async fn maybe_checkpoint(&self) {
+        let Some(interval) = self.log_retention.checkpoint_interval else {
+            return;
+        };
+        if self.s3_url.is_some() {
+            return;
+        }
+        match crate::delta_lake::commits_since_checkpoint(&self.base_path) {
+            Ok(commits) if commits >= interval.max(1) => {
+                if let Err(e) = self.checkpoint_inner().await {
+                    warn!("Automatic checkpoint failed: {}", e);
+                }
+            }
+            Ok(_) => {}
+            Err(e) => warn!("Failed to count commits since checkpoint: {}", e),
+        }
+    }
+
+    /// Versions VACUUM must not break: open cursors plus the keep-N window
+    ///
+    /// Expired cursors are excluded, so an abandoned cursor only holds files
+    /// back until its idle TTL runs out.
+    async fn vacuum_protection(&self) -> crate::delta_lake::VacuumProtection {
+        crate::delta_lake::VacuumProtection {
+            pinned_versions: self.cursors.pinned_versions().await,
+            keep_versions: self.vacuum_keep_versions,
+        }
+    }
+
+    /// `retention` checked against the table's minimum
+    fn vacuum_retention(
+        &self,
+        retention: std::time::Duration,
+        force: bool,
+    ) -> crate::delta_lake::VacuumRetention {
+        crate::delta_lake::VacuumRetention {
+            retention,
+            min_retention: self.vacuum_min_retention,
+            force,
+        }
+    }
+
+    /// Internal VACUUM implementation
+    async fn vacuum_inner(
+        &self,
+        retention: std::time::Duration,
+        force: bool,
+        dry_run: bool,
+        control: &crate::delta_lake::MaintenanceControl,
+        on_conflict: crate::delta_lake::MaintenanceConflict,
+    ) -> Result<crate::delta_lake::VacuumReport> {
+        let _guard = if dry_run {
+            None
         } else {
-            // Local backend
-            let table_url = Url::from_directory_path(&self.base_path)
-                .map_err(|_| Error::Other("Invalid path for Delta table".to_string()))?;
-            open_table(table_url).await.map_err(Error::DeltaTable)?
+            Some(self.lock_maintenance("VACUUM", on_conflict).await?)
         };
+        let protection = self.vacuum_protection().await;
+        crate::delta_lake::vacuum_table(
+            &self.base_path,
+            self.s3_url.as_deref(),
+            self.s3_config.as_ref(),
+            &self.vacuum_retention(retention, force),
+            dry_run,
+            &protection,
+            control,
+        )
+        .await
+    }
+
+    /// Internal dry run implementation
+    async fn vacuum_inner_dry_run(&self, retention_hours: u64) -> Result<Vec<String>> {
+        let protection = self.vacuum_protection().await;
+        crate::delta_lake::vacuum_dry_run(
+            &self.base_path,
+            self.s3_url.as_deref(),
+            self.s3_config.as_ref(),
+            &self.vacuum_retention(
+                std::time::Duration::from_secs(retention_hours.saturating_mul(3600)),
+                false,
+            ),
+            &protection,
+        )
+        .await
+    }
+
+    /// Z-ORDER: Multi-dimensional clustering for query performance
+    ///
+    /// Z-ORDER organizes data using a space-filling Z-curve that preserves multi-dimensional locality.
+    /// This improves query performance for filters on the specified columns.
+    ///
+    /// - `columns`: Columns to cluster by (typically 2-4 columns for best results)
+    /// - Creates new clustered files and marks old files as removed (like OPTIMIZE)
+    /// - VACUUM is needed to physically delete the old files
+    ///
+    /// Example: `db.zorder(&["date", "category"]).await?`
+    pub async fn zorder(&self, columns: &[&str]) -> Result<crate::delta_lake::OptimizeMetrics> {
+        info!("Running Delta Lake Z-ORDER on columns: {:?}", columns);
+
+        // Check write permission
+        self.check_permission(&crate::security::Permission::Write)?;
+
+        if columns.is_empty() {
+            return Err(Error::InvalidOperation(
+                "Z-ORDER requires at least one column".to_string(),
+            ));
+        }
+
+        let result = self.zorder_inner(columns).await;
+        match &result {
+            Ok(metrics) => {
+                info!(
+                    "Z-ORDER completed: {} files added, {} files removed",
+                    metrics.num_files_added, metrics.num_files_removed
+                );
+                self.audit_log(
+                    "ZORDER",
+                    &format!(
+                        "columns={:?}: {} -> {} files",
+                        columns, metrics.num_files_removed, metrics.num_files_added
+                    ),
+                    true,
+                )
+                .await;
+            }
+            Err(e) => {
+                self.metrics.total_errors.fetch_add(1, Ordering::Relaxed);
+                self.audit_log("ZORDER", &format!("columns={:?}: {}", columns, e), false)
+                    .await;
+            }
+        }
+        result
+    }
+
+    /// Internal Z-ORDER implementation
+    async fn zorder_inner(&self, columns: &[&str]) -> Result<crate::delta_lake::OptimizeMetrics> {
+        self.check_writable()?;
+        let _guard = self
+            .lock_maintenance("Z-ORDER", self.maintenance_lock.on_conflict)
+            .await?;
+        crate::delta_lake::zorder_table(
+            &self.base_path,
+            self.s3_url.as_deref(),
+            self.s3_config.as_ref(),
+            columns,
+        )
+        .await
+    }
+
+    /// Z-ORDER only the files whose ranges on `columns` overlap
+    ///
+    /// Files whose min/max boxes on the columns overlap another file's on
+    /// every column are read, sorted along the Z-curve and rewritten in one
+    /// commit; the rest are left alone, as are files an earlier run over the
+    /// same columns wrote together. The rewritten files are read into memory
+    /// together. Partitioned tables, tables with column mapping and S3
+    /// tables get a full `zorder` instead.
+    pub async fn zorder_incremental(
+        &self,
+        columns: &[&str],
+    ) -> Result<crate::delta_lake::OptimizeMetrics> {
+        info!("Running incremental Z-ORDER on columns: {:?}", columns);
+
+        // Check write permission
+        self.check_permission(&crate::security::Permission::Write)?;
+
+        if columns.is_empty() {
+            return Err(Error::InvalidOperation(
+                "Z-ORDER requires at least one column".to_string(),
+            ));
+        }
+
+        let result = self.zorder_incremental_inner(columns).await;
+        match &result {
+            Ok(metrics) => {
+                info!(
+                    "Incremental Z-ORDER completed: {} files added, {} files removed",
+                    metrics.num_files_added, metrics.num_files_removed
+                );
+                self.audit_log(
+                    "ZORDER",
+                    &format!(
+                        "columns={:?} incremental: {} -> {} files",
+                        columns, metrics.num_files_removed, metrics.num_files_added
+                    ),
+                    true,
+                )
+                .await;
+            }
+            Err(e) => {
+                self.metrics.total_errors.fetch_add(1, Ordering::Relaxed);
+                self.audit_log("ZORDER", &format!("columns={:?}: {}", columns, e), false)
+                    .await;
+            }
+        }
+        result
+    }
+
+    async fn zorder_incremental_inner(
+        &self,
+        columns: &[&str],
+    ) -> Result<crate::delta_lake::OptimizeMetrics> {
+        if self.s3_url.is_some() {
+            return self.zorder_inner(columns).await;
+        }
+        self.check_writable()?;
+        let guard = self
+            .lock_maintenance("Z-ORDER", self.maintenance_lock.on_conflict)
+            .await?;
+        match crate::delta_lake::zorder_incremental(&self.base_path, columns, self.target_file_size)
+            .await?
+        {
+            Some(metrics) => Ok(metrics),
+            None => {
+                drop(guard);
+                self.zorder_inner(columns).await
+            }
+        }
+    }
+
+    /// Columns OPTIMIZE keeps the table Z-ordered by, from the
+    /// `fsdb.zorderColumns` table property
+    ///
+    /// Read from the local Delta log; S3 tables have none.
+    pub fn zorder_columns(&self) -> Vec<String> {
+        if self.s3_url.is_some() {
+            return Vec::new();
+        }
+        crate::delta_lake::zorder::zorder_columns(&self.base_path).unwrap_or_default()
+    }
+
+    /// Store `columns` as the table's Z-ORDER clustering columns, or clear
+    /// them with an empty slice
+    ///
+    /// From then on each OPTIMIZE, after compacting, runs
+    /// `zorder_incremental` over the columns, so the table stays clustered
+    /// as it's written. Fails with `Error::InvalidOperation` if the table
+    /// has no such column or its type can't be ordered.
+    pub async fn set_zorder_columns(&self, columns: &[&str]) -> Result<()> {
+        use deltalake::DeltaOps;
+
+        info!("Setting Z-ORDER columns to {:?}", columns);
+
+        // Check admin permission
+        self.check_permission(&crate::security::Permission::Admin)?;
+        self.check_writable()?;
+
+        let schema = self.table_schema().await?;
+        for column in columns {
+            if column.is_empty() || column.contains(',') {
+                return Err(Error::InvalidOperation(format!(
+                    "Invalid Z-ORDER column name: {:?}",
+                    column
+                )));
+            }
+            let field = schema
+                .field_with_name(column)
+                .map_err(|_| Error::InvalidOperation(format!("No such column: {}", column)))?;
+            if !crate::delta_lake::clustering::supports_type(field.data_type()) {
+                return Err(Error::InvalidOperation(format!(
+                    "Can't Z-ORDER by {}: {} values can't be ordered",
+                    column,
+                    field.data_type()
+                )));
+            }
+        }
+        self.retry_policy
+            .run("Set Z-ORDER columns", || async {
+                let table = self.open_delta_table().await?;
+                DeltaOps(table)
+                    .set_tbl_properties()
+                    .with_properties(HashMap::from([(
+                        crate::delta_lake::ZORDER_COLUMNS_PROPERTY.to_string(),
+                        columns.join(","),
+                    )]))
+                    .await
+                    .map_err(Error::DeltaTable)
+            })
+            .await?;
+        self.audit_log(
+            "SET TBLPROPERTIES",
+            &format!("Z-ORDER columns {:?}", columns),
+            true,
+        )
+        .await;
+        Ok(())
+    }
+
+    /// Get column statistics from Delta Lake transaction log
+    ///
+    /// Columns the user reads masked are left out, since their bounds would
+    /// give their values away.
+    pub async fn get_column_statistics(&self) -> Result<HashMap<String, ColumnStats>> {
+        // Check read permission
+        self.check_permission(&crate::security::Permission::Read)?;
+
+        let masks = self.column_masks();
+        let mut stats = get_column_statistics_from_delta(&self.base_path)?;
+        stats.retain(|column, _| masks.mask_of(column).is_none());
+        Ok(stats)
+    }
+
+    /// Columns the table is partitioned by, in partitioning order
+    pub fn partition_columns(&self) -> Result<Vec<String>> {
+        Ok(crate::delta_lake::read_table_config(&self.base_path)?.partition_columns)
+    }
+
+    /// Every partition that currently holds data, sorted
+    pub fn partitions(&self) -> Result<Vec<crate::delta_lake::PartitionValues>> {
+        crate::delta_lake::list_partitions(&self.base_path)
+    }
+
+    /// Insert a RecordBatch into one partition
+    ///
+    /// `partition` gives a value for every partition column, `None` for null.
+    /// The batch may leave the partition columns out; rows that include them
+    /// must agree with `partition`, otherwise nothing is inserted.
+    pub async fn insert_into_partition(
+        &self,
+        partition: &[(&str, Option<&str>)],
+        batch: RecordBatch,
+    ) -> Result<CommitResult> {
+        // Check write permission
+        self.check_permission(&crate::security::Permission::Write)?;
+
+        let columns = self.partition_columns()?;
+        let named: Vec<&str> = partition.iter().map(|(column, _)| *column).collect();
+        if named != columns {
+            return Err(Error::InvalidOperation(format!(
+                "Table is partitioned by ({}), got ({})",
+                columns.join(", "),
+                named.join(", ")
+            )));
+        }
 
-        Ok(crate::delta_lake::merge::MergeBuilder::new(table))
+        let partition: crate::delta_lake::PartitionValues = partition
+            .iter()
+            .map(|(column, value)| (column.to_string(), value.map(str::to_string)))
+            .collect();
+        let batch =
+            crate::delta_lake::partitions::conform_to_partition(batch, &self.schema, &partition)?;
+        self.insert(batch).await
     }
 
-    /// Compact database files using Delta Lake OPTIMIZE
-    /// - Merges small Parquet files into larger ones
-    /// - Removes deleted records (Delta Lake deletion vectors)
-    /// - Bin-packs files to target size
-    pub async fn optimize(&self) -> Result<()> {
-        info!("Running Delta Lake OPTIMIZE operation");
+    /// Keep equi-depth histograms of `columns` for selectivity estimation
+    ///
+    /// The histograms are built from the current data now, rebuilt by every
+    /// OPTIMIZE and approximately updated by inserts. Only integer and
+    /// floating-point columns are supported. Replaces any histograms enabled
+    /// before.
+    pub async fn enable_histograms(&self, columns: &[&str]) -> Result<()> {
+        info!("Enabling histograms on columns {:?}", columns);
 
         // Check write permission
         self.check_permission(&crate::security::Permission::Write)?;
 
-        let result = self.optimize_inner(None, None).await;
+        let result = self.enable_histograms_inner(columns).await;
+        let details = columns.join(", ");
         match &result {
-            Ok(metrics) => {
-                info!(
-                    "OPTIMIZE completed: {} files added, {} files removed",
-                    metrics.num_files_added, metrics.num_files_removed
-                );
-                self.audit_log(
-                    "OPTIMIZE",
-                    &format!(
-                        "compacted {} -> {} files",
-                        metrics.num_files_removed, metrics.num_files_added
-                    ),
-                    true,
-                )
-                .await;
-            }
+            Ok(()) => self.audit_log("ENABLE_HISTOGRAMS", &details, true).await,
             Err(e) => {
                 self.metrics.total_errors.fetch_add(1, Ordering::Relaxed);
-                self.audit_log("OPTIMIZE", &format!("failed: {}", e), false)
+                self.audit_log("ENABLE_HISTOGRAMS", &format!("{}: {}", details, e), false)
                     .await;
             }
         }
-        result.map(|_| ())
+        result
     }
 
-    /// Optimize with a filter predicate (only compact matching partitions)
-    pub async fn optimize_with_filter(&self, filter: &str) -> Result<()> {
-        info!("Running Delta Lake OPTIMIZE with filter: {}", filter);
+    async fn enable_histograms_inner(&self, columns: &[&str]) -> Result<()> {
+        if self.s3_url.is_some() {
+            return Err(Error::InvalidOperation(
+                "Histograms are only supported for local tables".to_string(),
+            ));
+        }
+        let mut names = Vec::with_capacity(columns.len());
+        for column in columns {
+            let field = self
+                .schema
+                .fields()
+                .iter()
+                .find(|f| f.name() == column)
+                .or_else(|| {
+                    self.schema
+                        .fields()
+                        .iter()
+                        .find(|f| f.name().eq_ignore_ascii_case(column))
+                })
+                .ok_or_else(|| {
+                    Error::InvalidOperation(format!("Histogram on unknown column: {}", column))
+                })?;
+            if !crate::delta_lake::histogram::is_histogram_type(field.data_type()) {
+                return Err(Error::InvalidOperation(format!(
+                    "Histograms need a numeric column, '{}' is {}",
+                    field.name(),
+                    field.data_type()
+                )));
+            }
+            names.push(field.name().as_str());
+        }
+        self.build_histograms(&names, crate::delta_lake::histogram::DEFAULT_BUCKETS)
+            .await?
+            .save(&self.base_path)
+    }
+
+    /// Stop keeping histograms; estimates fall back to min/max statistics
+    pub async fn disable_histograms(&self) -> Result<()> {
+        info!("Disabling histograms");
 
         // Check write permission
         self.check_permission(&crate::security::Permission::Write)?;
 
-        let result = self.optimize_inner(Some(filter), None).await;
-        match &result {
-            Ok(metrics) => {
-                info!(
-                    "OPTIMIZE with filter completed: {} files added, {} files removed",
-                    metrics.num_files_added, metrics.num_files_removed
-                );
-                self.audit_log(
-                    "OPTIMIZE",
-                    &format!(
-                        "filter='{}': {} -> {} files",
-                        filter, metrics.num_files_removed, metrics.num_files_added
-                    ),
-                    true,
-                )
-                .await;
+        let path = crate::delta_lake::TableHistograms::path(&self.base_path);
+        if path.exists() {
+            std::fs::remove_file(path)?;
+        }
+        self.audit_log("DISABLE_HISTOGRAMS", "", true).await;
+        Ok(())
+    }
+
+    /// The table's histograms, if enabled
+    ///
+    /// Histograms of columns the user reads masked are left out.
+    pub async fn get_histograms(&self) -> Result<Option<crate::delta_lake::TableHistograms>> {
+        self.check_permission(&crate::security::Permission::Read)?;
+
+        let masks = self.column_masks();
+        Ok(self.load_histograms()?.map(|mut histograms| {
+            histograms
+                .columns
+                .retain(|column, _| masks.mask_of(column).is_none());
+            histograms
+        }))
+    }
+
+    /// Estimate the fraction of rows that `predicate` keeps
+    ///
+    /// `predicate` is a WHERE clause, with or without the `WHERE`, whose
+    /// `AND`ed comparisons of a column with a literal are estimated
+    /// independently and multiplied. Columns with a histogram use it; other
+    /// numeric columns assume their values are spread evenly between the
+    /// min and max in the file statistics. Terms that can't be estimated,
+    /// and terms on columns the user reads masked, count as keeping every
+    /// row.
+    pub async fn estimate_selectivity(&self, predicate: &str) -> Result<f64> {
+        self.check_permission(&crate::security::Permission::Read)?;
+
+        let sql = if predicate.to_uppercase().contains("WHERE") {
+            predicate.to_string()
+        } else {
+            format!("SELECT * FROM data WHERE {}", predicate)
+        };
+        let predicates = crate::delta_lake::data_skipping::extract_predicates(&sql);
+        let histograms = self.load_histograms()?.unwrap_or_default();
+        self.estimate_with(&histograms, &predicates)
+    }
+
+    fn estimate_with(
+        &self,
+        histograms: &crate::delta_lake::TableHistograms,
+        predicates: &[(String, String, serde_json::Value)],
+    ) -> Result<f64> {
+        use crate::delta_lake::EquiDepthHistogram;
+
+        // Terms on one column are combined; different columns are assumed
+        // independent. Masked columns are skipped: queries compare their
+        // masked values, and an estimate from the real ones would leak them.
+        let masks = self.column_masks();
+        let mut by_column: std::collections::BTreeMap<&str, Vec<(&str, f64)>> = Default::default();
+        for (column, operator, value) in predicates {
+            if masks.mask_of(column).is_some() {
+                continue;
             }
-            Err(e) => {
-                self.metrics.total_errors.fetch_add(1, Ordering::Relaxed);
-                self.audit_log("OPTIMIZE", &format!("filter='{}': {}", filter, e), false)
-                    .await;
+            let value = match value {
+                serde_json::Value::Null => 0.0,
+                value => match value.as_f64() {
+                    Some(value) => value,
+                    None => continue,
+                },
+            };
+            by_column
+                .entry(column.as_str())
+                .or_default()
+                .push((operator.as_str(), value));
+        }
+
+        let mut fallback: Option<(HashMap<String, ColumnStats>, u64)> = None;
+        let mut selectivity = 1.0;
+        for (column, terms) in by_column {
+            if let Some(histogram) = histograms.column(column) {
+                selectivity *= histogram.conjunction_selectivity(&terms);
+                continue;
+            }
+
+            if fallback.is_none() {
+                let stats = get_column_statistics_from_delta(&self.base_path)?;
+                let rows = crate::delta_lake::get_file_statistics(&self.base_path)?
+                    .iter()
+                    .map(|f| f.num_records)
+                    .sum();
+                fallback = Some((stats, rows));
+            }
+            let Some((stats, rows)) = &fallback else {
+                continue;
+            };
+            let Some(stats) = stats.get(column) else {
+                continue;
+            };
+            if let (Some(min), Some(max)) = (stats.min_value.as_f64(), stats.max_value.as_f64()) {
+                let non_null = rows.saturating_sub(stats.null_count);
+                selectivity *= EquiDepthHistogram::uniform(min, max, non_null, stats.null_count)
+                    .conjunction_selectivity(&terms);
             }
         }
-        result.map(|_| ())
+        Ok(selectivity)
     }
 
-    /// Optimize with target file size
-    pub async fn optimize_with_target_size(&self, target_size_bytes: u64) -> Result<()> {
-        info!(
-            "Running Delta Lake OPTIMIZE with target size: {} bytes",
-            target_size_bytes
-        );
+    fn load_histograms(&self) -> Result<Option<crate::delta_lake::TableHistograms>> {
+        if self.s3_url.is_some() {
+            return Ok(None);
+        }
+        crate::delta_lake::TableHistograms::load(&self.base_path)
+    }
+
+    /// Build histograms of `columns` by scanning the table
+    async fn build_histograms(
+        &self,
+        columns: &[&str],
+        num_buckets: usize,
+    ) -> Result<crate::delta_lake::TableHistograms> {
+        let projection: Vec<String> = columns
+            .iter()
+            .map(|c| format!("\"{}\"", c.replace('"', "\"\"")))
+            .collect();
+        let batches = self
+            .query_delta_native(&format!("SELECT {} FROM data", projection.join(", ")))
+            .await?;
+
+        let mut histograms = crate::delta_lake::TableHistograms {
+            num_buckets,
+            columns: Default::default(),
+        };
+        for (i, column) in columns.iter().enumerate() {
+            let arrays: Vec<&dyn Array> = batches.iter().map(|b| b.column(i).as_ref()).collect();
+            let histogram = if arrays.is_empty() {
+                crate::delta_lake::EquiDepthHistogram::default()
+            } else {
+                let array = arrow::compute::concat(&arrays)?;
+                crate::delta_lake::EquiDepthHistogram::from_array(&array, num_buckets)?
+                    .unwrap_or_default()
+            };
+            histograms.columns.insert(column.to_string(), histogram);
+        }
+        Ok(histograms)
+    }
+
+    /// Fold the histograms of newly inserted rows into the stored ones
+    ///
+    /// Failing here doesn't fail the insert; the next OPTIMIZE rebuilds them.
+    fn merge_into_histograms(&self, batch: &RecordBatch) {
+        let result = (|| -> Result<()> {
+            let Some(mut histograms) = self.load_histograms()? else {
+                return Ok(());
+            };
+            let num_buckets = histograms.num_buckets;
+            for (column, histogram) in histograms.columns.iter_mut() {
+                let Some(array) = batch.column_by_name(column) else {
+                    continue;
+                };
+                if let Some(new_rows) =
+                    crate::delta_lake::EquiDepthHistogram::from_array(array, num_buckets)?
+                {
+                    *histogram = histogram.merge(&new_rows, num_buckets);
+                }
+            }
+            histograms.save(&self.base_path)
+        })();
+        if let Err(e) = result {
+            warn!("Failed to update histograms after insert: {}", e);
+        }
+    }
+
+    /// Define the cast view `name`, replacing any view of that name
+    ///
+    /// Queries can then read the table through it, as `SELECT ... FROM
+    /// name`, with its columns cast as `view` says; see `query::cast_view`.
+    /// Each cast must name a column of the table and convert between types
+    /// Arrow can cast. Only available for local tables.
+    pub async fn create_cast_view(&self, name: &str, view: crate::query::CastView) -> Result<()> {
+        info!("Creating cast view {}", name);
 
         // Check write permission
         self.check_permission(&crate::security::Permission::Write)?;
 
-        let result = self.optimize_inner(None, Some(target_size_bytes)).await;
-        match &result {
-            Ok(metrics) => {
-                info!(
-                    "OPTIMIZE with target size completed: {} files added, {} files removed",
-                    metrics.num_files_added, metrics.num_files_removed
-                );
-                self.audit_log(
-                    "OPTIMIZE",
-                    &format!(
-                        "target_size={}: {} -> {} files",
-                        target_size_bytes, metrics.num_files_removed, metrics.num_files_added
-                    ),
-                    true,
-                )
-                .await;
-            }
-            Err(e) => {
-                self.metrics.total_errors.fetch_add(1, Ordering::Relaxed);
-                self.audit_log(
-                    "OPTIMIZE",
-                    &format!("target_size={}: {}", target_size_bytes, e),
-                    false,
-                )
-                .await;
+        let result = self.create_cast_view_inner(name, view).await;
+        let details = match &result {
+            Ok(()) => name.to_string(),
+            Err(e) => format!("{}: {}", name, e),
+        };
+        self.audit_log("CREATE CAST VIEW", &details, result.is_ok())
+            .await;
+        result
+    }
+
+    async fn create_cast_view_inner(&self, name: &str, view: crate::query::CastView) -> Result<()> {
+        if self.s3_url.is_some() {
+            return Err(Error::InvalidOperation(
+                "Cast views are only supported for local tables".to_string(),
+            ));
+        }
+        crate::query::cast_view::check_view_name(name)?;
+        if crate::metadata::catalog::exists(&self.base_path, name) {
+            return Err(Error::InvalidOperation(format!(
+                "There's already a table named {}",
+                name
+            )));
+        }
+        view.validate(&self.table_schema().await?)?;
+
+        let mut views = self.load_cast_views()?;
+        views.views.insert(name.to_string(), view);
+        views.save(&self.base_path)
+    }
+
+    /// Remove the cast view `name`, returning whether there was one
+    pub async fn drop_cast_view(&self, name: &str) -> Result<bool> {
+        info!("Dropping cast view {}", name);
+
+        // Check write permission
+        self.check_permission(&crate::security::Permission::Write)?;
+
+        let mut views = self.load_cast_views()?;
+        let dropped = views.views.remove(name).is_some();
+        if dropped {
+            views.save(&self.base_path)?;
+        }
+        self.audit_log("DROP CAST VIEW", name, true).await;
+        Ok(dropped)
+    }
+
+    /// The cast view `name`
+    pub fn cast_view(&self, name: &str) -> Result<crate::query::CastView> {
+        self.load_cast_views()?
+            .views
+            .remove(name)
+            .ok_or_else(|| Error::InvalidOperation(format!("No cast view named {}", name)))
+    }
+
+    /// Names of the table's cast views, sorted
+    pub fn cast_view_names(&self) -> Result<Vec<String>> {
+        Ok(self.load_cast_views()?.views.into_keys().collect())
+    }
+
+    fn load_cast_views(&self) -> Result<crate::query::cast_view::CastViews> {
+        if self.s3_url.is_some() {
+            return Ok(Default::default());
+        }
+        crate::query::cast_view::CastViews::load(&self.base_path)
+    }
+
+    /// Register each cast view in `ctx`, over the `data` table registered there
+    async fn register_cast_views(&self, ctx: &datafusion::prelude::SessionContext) -> Result<()> {
+        for (name, view) in self.load_cast_views()?.views {
+            let table = ctx.table("data").await?;
+            ctx.register_table(name.as_str(), view.apply(table)?.into_view())?;
+        }
+        Ok(())
+    }
+
+    /// Register the named tables the user can read, then each cast view, in `ctx`
+    ///
+    /// `data` must already be registered there. Named tables are read as
+    /// committed, and with the user's column masks: rows still in a table's
+    /// write buffer are only visible through the handle that buffered them.
+    async fn register_catalog(&self, ctx: &datafusion::prelude::SessionContext) -> Result<()> {
+        for name in self.table_names()? {
+            if !self.can_read_table(&name) {
+                continue;
             }
+            let table = self.open_named_table(&name).await?;
+            let table = self.mask_table(ctx, &name, Arc::new(table))?;
+            ctx.register_table(name.as_str(), table)?;
         }
-        result.map(|_| ())
+        self.register_cast_views(ctx).await
     }
 
-    /// Internal optimize implementation
-    async fn optimize_inner(
+    /// Create the table `name` with `schema`, returning a handle on it
+    ///
+    /// The table is stored under `_tables/<name>/` as a Delta table of its
+    /// own, see `metadata::catalog`, and queries through any handle on the
+    /// database can read it by name next to `data`. Requires admin
+    /// permission. Only available for local databases.
+    pub async fn create_table(&self, name: &str, schema: SchemaRef) -> Result<DatabaseOps> {
+        self.create_table_partitioned(name, schema, &[]).await
+    }
+
+    /// Like `create_table`, with the table partitioned by `partition_columns`
+    pub async fn create_table_partitioned(
         &self,
-        filter: Option<&str>,
-        target_size: Option<u64>,
-    ) -> Result<crate::delta_lake::OptimizeMetrics> {
-        crate::delta_lake::optimize_table(
-            &self.base_path,
-            self.s3_url.as_deref(),
-            self.s3_storage_options.as_ref(),
-            filter,
-            target_size,
-        )
-        .await
+        name: &str,
+        schema: SchemaRef,
+        partition_columns: &[&str],
+    ) -> Result<DatabaseOps> {
+        info!("Creating table {}", name);
+
+        // Check admin permission
+        self.check_permission(&crate::security::Permission::Admin)?;
+
+        let result = self
+            .create_table_inner(name, schema, partition_columns)
+            .await;
+        let details = match &result {
+            Ok(_) => name.to_string(),
+            Err(e) => format!("{}: {}", name, e),
+        };
+        self.audit_log("CREATE TABLE", &details, result.is_ok())
+            .await;
+        result
     }
 
-    /// Legacy compact method - delegates to optimize()
-    pub async fn compact(&self) -> Result<()> {
-        self.optimize().await
+    async fn create_table_inner(
+        &self,
+        name: &str,
+        schema: SchemaRef,
+        partition_columns: &[&str],
+    ) -> Result<DatabaseOps> {
+        use crate::metadata::catalog;
+
+        if self.s3_url.is_some() {
+            return Err(Error::InvalidOperation(
+                "Named tables are only supported for local databases".to_string(),
+            ));
+        }
+        catalog::check_table_name(name)?;
+        if catalog::exists(&self.base_path, name) {
+            return Err(Error::InvalidOperation(format!(
+                "Table {} already exists",
+                name
+            )));
+        }
+        if self.load_cast_views()?.views.contains_key(name) {
+            return Err(Error::InvalidOperation(format!(
+                "There's already a cast view named {}",
+                name
+            )));
+        }
+
+        let path = catalog::table_path(&self.base_path, name);
+        let db = Self::create_partitioned(&path, schema, partition_columns).await?;
+        Ok(self.share_session(db))
     }
 
-    /// Legacy compact with custom options - delegates to optimize_with_target_size
-    pub async fn compact_with_options(&self, min_file_size: u64) -> Result<()> {
-        // Use min_file_size as target size
-        self.optimize_with_target_size(min_file_size).await
+    /// Handle on the table `name`
+    ///
+    /// The handle acts as the same user, with the same roles and audit log,
+    /// and any write through it goes to that table alone. Users whose roles
+    /// read only some tables get handles on just those.
+    pub async fn table(&self, name: &str) -> Result<DatabaseOps> {
+        use crate::metadata::catalog;
+
+        catalog::check_table_name(name)?;
+        if self
+            .check_permission(&crate::security::Permission::Read)
+            .is_ok()
+            && !self.can_read_table(name)
+        {
+            return Err(Error::PermissionDenied(format!("table {}", name)));
+        }
+        if self.s3_url.is_some() || !catalog::exists(&self.base_path, name) {
+            return Err(Error::InvalidOperation(format!("No table named {}", name)));
+        }
+        let db = Self::open(catalog::table_path(&self.base_path, name)).await?;
+        Ok(self.share_session(db))
     }
 
-    /// VACUUM: Physically delete old Parquet files no longer referenced by Delta Lake
+    /// Drop the table `name` and all its data, returning whether there was one
     ///
-    /// - `retention_hours`: Minimum age in hours for files to be eligible for deletion
-    /// - Files newer than retention period are kept (for time travel safety)
-    /// - Default retention is usually 168 hours (7 days) in production
-    /// - Use retention=0 only for testing or when time travel is not needed
-    pub async fn vacuum(&self, retention_hours: u64) -> Result<()> {
-        info!(
-            "Running Delta Lake VACUUM with {} hour retention",
-            retention_hours
+    /// Open handles on the table fail from then on. Requires admin permission.
+    pub async fn drop_table(&self, name: &str) -> Result<bool> {
+        use crate::metadata::catalog;
+
+        info!("Dropping table {}", name);
+
+        // Check admin permission
+        self.check_permission(&crate::security::Permission::Admin)?;
+
+        let result: Result<bool> = async {
+            catalog::check_table_name(name)?;
+            if self.s3_url.is_some() || !catalog::exists(&self.base_path, name) {
+                return Ok(false);
+            }
+            std::fs::remove_dir_all(catalog::table_path(&self.base_path, name))?;
+            Ok(true)
+        }
+        .await;
+        let details = match &result {
+            Ok(_) => name.to_string(),
+            Err(e) => format!("{}: {}", name, e),
+        };
+        self.audit_log("DROP TABLE", &details, result.is_ok()).await;
+        result
+    }
+
+    /// Names of the named tables the user can read, sorted
+    ///
+    /// The main table, `data`, isn't included.
+    pub fn list_tables(&self) -> Result<Vec<String>> {
+        // Check read permission
+        self.check_permission(&crate::security::Permission::Read)?;
+
+        Ok(self
+            .table_names()?
+            .into_iter()
+            .filter(|name| self.can_read_table(name))
+            .collect())
+    }
+
+    /// Names of all the named tables, none for S3 databases
+    fn table_names(&self) -> Result<Vec<String>> {
+        if self.s3_url.is_some() {
+            return Ok(Vec::new());
+        }
+        crate::metadata::catalog::list(&self.base_path)
+    }
+
+    /// Open the Delta table of the named table `name`
+    async fn open_named_table(&self, name: &str) -> Result<deltalake::DeltaTable> {
+        let path = crate::metadata::catalog::table_path(&self.base_path, name);
+        let table_url = url::Url::from_directory_path(&path)
+            .map_err(|_| Error::Other("Invalid path for Delta table".to_string()))?;
+        self.retry_policy
+            .run("Open Delta table", || async {
+                deltalake::open_table(table_url.clone())
+                    .await
+                    .map_err(Error::DeltaTable)
+            })
+            .await
+    }
+
+    /// `db`, a named table's handle, acting as this handle's user, with its
+    /// keys and settings
+    ///
+    /// Every `with_*` setting is carried over, so limits and safety checks
+    /// configured on this handle hold on the tables and sessions it opens.
+    /// Cursors aren't: the handle gets a registry of its own, configured
+    /// like this one's, so one user can't fetch from another's cursor.
+    fn share_session(&self, mut db: DatabaseOps) -> DatabaseOps {
+        db.auth_context = self.auth_context.clone();
+        db.credential_store = self.credential_store.clone();
+        db.audit_logger = self.audit_logger.clone();
+        db.role_manager = self.role_manager.clone();
+        db.column_keys = self.column_keys.clone();
+        db.log_redaction = self.log_redaction;
+        db.retry_policy = self.retry_policy.clone();
+        db.identifier_case = self.identifier_case;
+        db.collation = self.collation;
+        db.spill = self.spill.clone();
+        db.query_memory_limit = self.query_memory_limit;
+        db.scan_concurrency = self.scan_concurrency;
+        db.cursors = Arc::new(crate::query::CursorRegistry::with_config(
+            self.cursors.config().clone(),
+        ));
+        db.vacuum_keep_versions = self.vacuum_keep_versions;
+        db.vacuum_min_retention = self.vacuum_min_retention;
+        db.maintenance_lock = self.maintenance_lock.clone();
+        db.log_retention = self.log_retention.clone();
+        db.auto_compaction = self.auto_compaction.clone();
+        db.file_naming = self.file_naming.clone();
+        db.stats_config = self.stats_config.clone();
+        db.target_file_size = self.target_file_size;
+        db.idempotency_window = self.idempotency_window;
+        db.size_limits = self.size_limits.clone();
+        db
+    }
+
+    /// Encrypt `column` from now on, as `encryption` describes
+    ///
+    /// The column must hold strings, not partition the table and have no
+    /// values yet, since values already written would stay in the clear.
+    /// No generated column may be computed from it either, as its values
+    /// would be derived from the plaintext and stored unencrypted.
+    /// Requires admin permission. See `security::column_encryption`.
+    pub async fn encrypt_column(
+        &self,
+        column: &str,
+        encryption: crate::security::EncryptedColumn,
+    ) -> Result<()> {
+        info!("Encrypting column {}", column);
+
+        // Check admin permission
+        self.check_permission(&crate::security::Permission::Admin)?;
+
+        let result = self.encrypt_column_inner(column, encryption).await;
+        let details = match &result {
+            Ok(()) => column.to_string(),
+            Err(e) => format!("{}: {}", column, e),
+        };
+        self.audit_log("ENCRYPT COLUMN", &details, result.is_ok())
+            .await;
+        result
+    }
+
+    async fn encrypt_column_inner(
+        &self,
+        column: &str,
+        encryption: crate::security::EncryptedColumn,
+    ) -> Result<()> {
+        use arrow::datatypes::DataType;
+
+        if self.s3_url.is_some() {
+            return Err(Error::InvalidOperation(
+                "Column encryption is only supported for local tables".to_string(),
+            ));
+        }
+        let schema = self.table_schema().await?;
+        let field = schema.field_with_name(column).map_err(|_| {
+            Error::InvalidOperation(format!("Can't encrypt unknown column: {}", column))
+        })?;
+        if !matches!(
+            field.data_type(),
+            DataType::Utf8 | DataType::LargeUtf8 | DataType::Utf8View
+        ) {
+            return Err(Error::InvalidOperation(format!(
+                "Only string columns can be encrypted, '{}' is {}",
+                column,
+                field.data_type()
+            )));
+        }
+        if self.partition_columns()?.iter().any(|c| c == column) {
+            return Err(Error::InvalidOperation(format!(
+                "Partition column '{}' can't be encrypted: its values are in file paths",
+                column
+            )));
+        }
+        let derived = crate::metadata::generated::generated_from(&schema, column)?;
+        if !derived.is_empty() {
+            return Err(Error::InvalidOperation(format!(
+                "Column '{}' can't be encrypted: {} generated from it would be stored in the clear",
+                column,
+                derived.join(", ")
+            )));
+        }
+
+        self.flush_write_buffer().await?;
+        let count_query = format!(
+            "SELECT COUNT(\"{}\") AS n FROM data",
+            column.replace('"', "\"\"")
         );
+        let counts = self.query_delta_native(&count_query).await?;
+        let written = counts.first().is_some_and(|batch| {
+            batch
+                .column(0)
+                .as_any()
+                .downcast_ref::<arrow::array::Int64Array>()
+                .is_some_and(|n| n.value(0) > 0)
+        });
+        if written {
+            return Err(Error::InvalidOperation(format!(
+                "Column '{}' already has values, which would stay unencrypted",
+                column
+            )));
+        }
 
-        // Check write permission
-        self.check_permission(&crate::security::Permission::Write)?;
+        let mut columns = self.column_encryption()?;
+        columns.columns.insert(column.to_string(), encryption);
+        columns.save(&self.base_path)
+    }
 
-        let result = self.vacuum_inner(retention_hours, false).await;
-        match &result {
-            Ok(deleted_count) => {
-                info!("VACUUM completed: {} files deleted", deleted_count);
-                self.audit_log(
-                    "VACUUM",
-                    &format!(
-                        "retention={}h: {} files deleted",
-                        retention_hours, deleted_count
-                    ),
-                    true,
-                )
-                .await;
-            }
-            Err(e) => {
-                self.metrics.total_errors.fetch_add(1, Ordering::Relaxed);
-                self.audit_log(
-                    "VACUUM",
-                    &format!("retention={}h: {}", retention_hours, e),
-                    false,
-                )
-                .await;
-            }
+    /// The table's encrypted columns
+    pub fn encrypted_columns(&self) -> Result<crate::security::ColumnEncryption> {
+        self.column_encryption()
+    }
+
+    fn column_encryption(&self) -> Result<crate::security::ColumnEncryption> {
+        if self.s3_url.is_some() {
+            return Ok(Default::default());
         }
-        result.map(|_| ())
+        crate::security::ColumnEncryption::load(&self.base_path)
     }
 
-    /// VACUUM dry run: Preview what files would be deleted without actually deleting
-    /// Returns list of file paths that would be deleted
-    pub async fn vacuum_dry_run(&self, retention_hours: u64) -> Result<Vec<String>> {
-        info!(
-            "Running Delta Lake VACUUM dry run with {} hour retention",
-            retention_hours
-        );
+    /// Whether the user may read encrypted columns in the clear
+    fn may_decrypt(&self) -> bool {
+        self.check_permission(&crate::security::Permission::Decrypt)
+            .is_ok()
+    }
 
-        // Check read permission for dry run
-        self.check_permission(&crate::security::Permission::Read)?;
+    /// `sql` with literals compared to encrypted columns encrypted
+    fn encrypt_sql(&self, sql: &str) -> Result<String> {
+        self.column_encryption()?
+            .encrypt_sql(sql, self.column_keys.as_deref())
+    }
 
-        self.vacuum_inner_dry_run(retention_hours).await
+    /// `predicate` with literals compared to encrypted columns encrypted
+    fn encrypt_predicate(&self, predicate: &str) -> Result<String> {
+        self.column_encryption()?
+            .encrypt_predicate(predicate, self.column_keys.as_deref())
     }
 
-    /// Internal VACUUM implementation
-    async fn vacuum_inner(&self, retention_hours: u64, dry_run: bool) -> Result<usize> {
-        crate::delta_lake::vacuum_table(
-            &self.base_path,
-            self.s3_url.as_deref(),
-            self.s3_storage_options.as_ref(),
-            retention_hours,
-            dry_run,
-        )
-        .await
+    /// Decrypt or mask the encrypted columns of query results
+    fn decrypt_batches(&self, batches: Vec<RecordBatch>) -> Result<Vec<RecordBatch>> {
+        let encryption = self.column_encryption()?;
+        let authorized = self.may_decrypt();
+        batches
+            .into_iter()
+            .map(|batch| encryption.decrypt_batch(batch, self.column_keys.as_deref(), authorized))
+            .collect()
     }
 
-    /// Internal dry run implementation
-    async fn vacuum_inner_dry_run(&self, retention_hours: u64) -> Result<Vec<String>> {
-        crate::delta_lake::vacuum_dry_run(
-            &self.base_path,
-            self.s3_url.as_deref(),
-            self.s3_storage_options.as_ref(),
-            retention_hours,
-        )
-        .await
+    /// Decrypt or mask the encrypted columns of batches as `stream` yields them
+    fn decrypt_stream(
+        &self,
+        stream: datafusion::physical_plan::SendableRecordBatchStream,
+    ) -> Result<datafusion::physical_plan::SendableRecordBatchStream> {
+        use datafusion::error::DataFusionError;
+        use datafusion::physical_plan::stream::RecordBatchStreamAdapter;
+        use futures::StreamExt;
+
+        let encryption = self.column_encryption()?;
+        if encryption.is_empty() {
+            return Ok(stream);
+        }
+        let keys = self.column_keys.clone();
+        let authorized = self.may_decrypt();
+        let schema = stream.schema();
+        let decrypted = stream.map(move |batch| {
+            encryption
+                .decrypt_batch(batch?, keys.as_deref(), authorized)
+                .map_err(|e| DataFusionError::External(Box::new(e)))
+        });
+        Ok(Box::pin(RecordBatchStreamAdapter::new(schema, decrypted)))
     }
 
-    /// Z-ORDER: Multi-dimensional clustering for query performance
-    ///
-    /// Z-ORDER organizes data using a space-filling Z-curve that preserves multi-dimensional locality.
-    /// This improves query performance for filters on the specified columns.
-    ///
-    /// - `columns`: Columns to cluster by (typically 2-4 columns for best results)
-    /// - Creates new clustered files and marks old files as removed (like OPTIMIZE)
-    /// - VACUUM is needed to physically delete the old files
+    /// Estimate the value at fraction `p` of `column`'s non-null values
     ///
-    /// Example: `db.zorder(&["date", "category"]).await?`
-    pub async fn zorder(&self, columns: &[&str]) -> Result<()> {
-        info!("Running Delta Lake Z-ORDER on columns: {:?}", columns);
+    /// The estimate merges a t-digest of the column kept for each data file
+    /// (see `delta_lake::sketches`), so only files without one are read:
+    /// every file the first time a column is asked about, then just files
+    /// written since. It's the estimate `approx_percentile` gives in SQL, and
+    /// None if the column has no non-null values. Partition columns, columns
+    /// the user reads masked and tables on S3 are answered by scanning with
+    /// `approx_percentile`.
+    pub async fn approx_percentile(&self, column: &str, p: f64) -> Result<Option<f64>> {
+        use arrow::array::AsArray;
+        use arrow::datatypes::{DataType, Field, Float64Type};
 
-        // Check write permission
-        self.check_permission(&crate::security::Permission::Write)?;
+        // Check read permission
+        self.check_permission(&crate::security::Permission::Read)?;
+        crate::query::percentile::check_fraction(p)?;
 
-        if columns.is_empty() {
-            return Err(Error::InvalidOperation(
-                "Z-ORDER requires at least one column".to_string(),
-            ));
+        let schema = self.table_schema().await?;
+        let field = schema
+            .fields()
+            .iter()
+            .find(|f| f.name() == column)
+            .or_else(|| {
+                schema
+                    .fields()
+                    .iter()
+                    .find(|f| f.name().eq_ignore_ascii_case(column))
+            })
+            .ok_or_else(|| {
+                Error::InvalidOperation(format!("Percentile of unknown column: {}", column))
+            })?;
+        if !field.data_type().is_numeric() {
+            return Err(Error::InvalidOperation(format!(
+                "Percentiles need a numeric column, '{}' is {}",
+                field.name(),
+                field.data_type()
+            )));
         }
-
-        let result = self.zorder_inner(columns).await;
-        match &result {
-            Ok(metrics) => {
-                info!(
-                    "Z-ORDER completed: {} files added, {} files removed",
-                    metrics.num_files_added, metrics.num_files_removed
-                );
-                self.audit_log(
-                    "ZORDER",
-                    &format!(
-                        "columns={:?}: {} -> {} files",
-                        columns, metrics.num_files_removed, metrics.num_files_added
-                    ),
-                    true,
-                )
-                .await;
-            }
-            Err(e) => {
-                self.metrics.total_errors.fetch_add(1, Ordering::Relaxed);
-                self.audit_log("ZORDER", &format!("columns={:?}: {}", columns, e), false)
-                    .await;
-            }
+        let name = field.name().as_str();
+
+        // The sketches hold unmasked values, so a masked column is answered
+        // over the values the user reads
+        let masked = self.column_masks().mask_of(name).is_some();
+        if masked || self.s3_url.is_some() || self.partition_columns()?.iter().any(|c| c == name) {
+            let sql = format!(
+                "SELECT approx_percentile(\"{}\", {}) FROM data",
+                name.replace('"', "\"\""),
+                p
+            );
+            let batches = if masked {
+                self.query(&sql).await?
+            } else {
+                self.query_delta_native(&sql).await?
+            };
+            return Ok(batches.iter().find(|b| b.num_rows() > 0).and_then(|b| {
+                let values = b.column(0).as_primitive::<Float64Type>();
+                values.is_valid(0).then(|| values.value(0))
+            }));
         }
-        result.map(|_| ())
-    }
 
-    /// Internal Z-ORDER implementation
-    async fn zorder_inner(&self, columns: &[&str]) -> Result<crate::delta_lake::OptimizeMetrics> {
-        crate::delta_lake::zorder_table(
-            &self.base_path,
-            self.s3_url.as_deref(),
-            self.s3_storage_options.as_ref(),
-            columns,
-        )
-        .await
-    }
+        let files = crate::delta_lake::get_file_statistics(&self.base_path)?;
+        let live: Vec<&str> = files.iter().map(|f| f.path.as_str()).collect();
+        let mut sketches = crate::delta_lake::FileSketches::load(&self.base_path)?;
+        let known = sketches.columns.get(name).map_or(0, |d| d.len());
+        let unsketched = sketches.retain_live(name, &live);
+        let digests = sketches.columns.entry(name.to_string()).or_default();
+        let changed = !unsketched.is_empty() || digests.len() != known;
 
-    /// Get column statistics from Delta Lake transaction log
-    pub async fn get_column_statistics(&self) -> Result<HashMap<String, ColumnStats>> {
-        get_column_statistics_from_delta(&self.base_path)
+        let projection = Schema::new(vec![Field::new(name, DataType::Float64, true)]);
+        let reader = ParquetReader::new();
+        for path in unsketched {
+            let file = self
+                .base_path
+                .join(crate::metadata::snapshot::percent_decode(path)?);
+            let batch = reader.read_projected(&file, &projection, MissingColumn::Null)?;
+            let values = batch.column(0).as_primitive::<Float64Type>();
+            digests.insert(
+                path.to_string(),
+                crate::query::percentile::TDigest::from_values(values.iter()),
+            );
+        }
+
+        // The sketches are a cache; failing to keep them doesn't fail the read
+        if changed {
+            if let Err(e) = sketches.save(&self.base_path) {
+                warn!("Failed to save percentile sketches: {}", e);
+            }
+        }
+        Ok(sketches.merged(name).quantile(p))
     }
 
     /// Legacy method - delegates to main method
@@ -1851,6 +7213,42 @@ impl DatabaseOps {
         *stats = DataSkippingStats::default();
     }
 
+    /// Row count, file count and size of the table, without scanning it
+    ///
+    /// Everything comes from the Delta log's per-file sizes and row counts.
+    /// Files written without statistics have their row counts read from
+    /// their Parquet footers instead, which is exact but reads those files;
+    /// `files_without_stats` says how many. Only available for local tables.
+    pub async fn table_summary(&self) -> Result<TableSummary> {
+        self.check_permission(&crate::security::Permission::Read)?;
+
+        if self.s3_url.is_some() {
+            return Err(Error::InvalidOperation(
+                "Table summaries are only supported for local tables".to_string(),
+            ));
+        }
+        let log = crate::delta_lake::read_delta_log(&self.base_path)?;
+
+        let mut summary = TableSummary {
+            num_files: log.files.len(),
+            latest_version: log.version,
+            ..Default::default()
+        };
+        for file in &log.files {
+            summary.total_bytes += file.size;
+            if file.recorded_num_records().is_none() {
+                summary.files_without_stats += 1;
+            }
+            summary.num_rows += file.num_records(&self.base_path)?;
+        }
+
+        info!(
+            "Table summary: {} rows in {} files ({} bytes), {} without stats",
+            summary.num_rows, summary.num_files, summary.total_bytes, summary.files_without_stats
+        );
+        Ok(summary)
+    }
+
     /// Get database health status
     pub async fn health_check(&self) -> HealthStatus {
         // For Delta Lake, check _delta_log directory
@@ -1870,6 +7268,240 @@ impl DatabaseOps {
         }
     }
 
+    /// Check every file the Delta log references against the log, changing
+    /// nothing
+    ///
+    /// Reports missing files, size mismatches, broken deletion vectors,
+    /// statistics at odds with their files and a checkpoint that disagrees
+    /// with its commits; see `delta_lake::integrity`. Only available for
+    /// local tables.
+    pub async fn check_integrity(&self) -> Result<crate::delta_lake::IntegrityReport> {
+        info!("Checking table integrity");
+
+        // Check read permission
+        self.check_permission(&crate::security::Permission::Read)?;
+
+        let result = self.check_integrity_inner(false).await;
+        self.audit_integrity("FSCK", &result).await;
+        result
+    }
+
+    /// `check_integrity`, then recompute and commit statistics at odds with
+    /// their files
+    ///
+    /// Nothing else is repaired: missing files and the like are reported as
+    /// by `check_integrity`. Files with deletion vectors, and tables with
+    /// column mapping, keep their statistics.
+    pub async fn repair_integrity(&self) -> Result<crate::delta_lake::IntegrityReport> {
+        info!("Checking and repairing table integrity");
+
+        // Check write permission
+        self.check_permission(&crate::security::Permission::Write)?;
+
+        let result = self.check_integrity_inner(true).await;
+        self.audit_integrity("FSCK REPAIR", &result).await;
+        result
+    }
+
+    async fn audit_integrity(
+        &self,
+        operation: &str,
+        result: &Result<crate::delta_lake::IntegrityReport>,
+    ) {
+        match result {
+            Ok(report) => {
+                let details = format!(
+                    "{} files, {} issues, {} repaired",
+                    report.files_checked,
+                    report.issues.len(),
+                    report.stats_repaired.len()
+                );
+                self.audit_log(operation, &details, true).await
+            }
+            Err(e) => self.audit_log(operation, &e.to_string(), false).await,
+        }
+    }
+
+    async fn check_integrity_inner(
+        &self,
+        repair: bool,
+    ) -> Result<crate::delta_lake::IntegrityReport> {
+        use crate::delta_lake::IntegrityIssue;
+
+        if self.s3_url.is_some() {
+            return Err(Error::InvalidOperation(
+                "Integrity checks are only supported for local tables".to_string(),
+            ));
+        }
+        let orphan_lease = crate::delta_lake::RecoveryConfig::default().orphan_lease;
+        let mut report = crate::delta_lake::integrity::check(&self.base_path, orphan_lease)?;
+        for issue in &report.issues {
+            warn!("Integrity check: {}", issue);
+        }
+        if !repair {
+            return Ok(report);
+        }
+
+        let stale: Vec<&str> = report
+            .issues
+            .iter()
+            .filter_map(|issue| match issue {
+                IntegrityIssue::InconsistentStats { path, .. } => Some(path.as_str()),
+                _ => None,
+            })
+            .collect();
+        if stale.is_empty() {
+            return Ok(report);
+        }
+        let config = crate::delta_lake::read_table_config(&self.base_path)?;
+        if config.column_mapping_mode() != "none" {
+            warn!("Not repairing statistics of a table with column mapping");
+            return Ok(report);
+        }
+        self.check_writable()?;
+        let repaired = self.recommit_stats(&stale).await?;
+        report.issues.retain(|issue| {
+            !matches!(issue, IntegrityIssue::InconsistentStats { path, .. } if repaired.contains(path))
+        });
+        report.stats_repaired = repaired;
+        Ok(report)
+    }
+
+    /// Commit the live files of `paths` again with statistics recomputed
+    /// from their rows, returning those recommitted
+    async fn recommit_stats(&self, paths: &[&str]) -> Result<Vec<String>> {
+        use deltalake::kernel::transaction::CommitBuilder;
+        use deltalake::kernel::{Action, Add};
+        use deltalake::protocol::DeltaOperation;
+
+        let log = crate::delta_lake::read_delta_log(&self.base_path)?;
+        let now = chrono::Utc::now().timestamp_millis();
+        let mut adds = Vec::new();
+        for file in &log.files {
+            if !paths.contains(&file.path.as_str()) || file.deletion_vector.is_some() {
+                continue;
+            }
+            adds.push(Add {
+                path: file.path.clone(),
+                size: file.size as i64,
+                partition_values: file.partition_values.clone().into_iter().collect(),
+                modification_time: now,
+                data_change: false,
+                stats: Some(crate::delta_lake::integrity::recompute_stats(
+                    &self.base_path,
+                    &file.path,
+                )?),
+                ..Default::default()
+            });
+        }
+        if adds.is_empty() {
+            return Ok(Vec::new());
+        }
+        if let Some(stats_config) = &self.stats_config {
+            stats_config.apply(&mut adds)?;
+        }
+        let repaired: Vec<String> = adds.iter().map(|add| add.path.clone()).collect();
+
+        let table = self.get_delta_table().await?;
+        let snapshot = table.snapshot().map_err(Error::DeltaTable)?;
+        CommitBuilder::default()
+            .with_app_metadata(crate::delta_lake::commit::app_metadata())
+            .with_actions(adds.into_iter().map(Action::Add).collect())
+            .build(
+                Some(snapshot),
+                table.log_store(),
+                DeltaOperation::FileSystemCheck {},
+            )
+            .await
+            .map_err(Error::DeltaTable)?;
+        info!("Recomputed statistics of {} files", repaired.len());
+        Ok(repaired)
+    }
+
+    /// Probe the storage backend, Delta log, local disk and write buffer
+    ///
+    /// Cheap enough for a load balancer to poll every few seconds. Losing the
+    /// storage backend or the Delta log makes the database `Unhealthy`; a
+    /// local disk that rejects writes or a stalled write buffer only makes it
+    /// `Degraded`, since reads still work.
+    pub async fn health(&self) -> crate::health::HealthReport {
+        use crate::health::{self, HealthReport, HealthState, S3Probe};
+
+        let start = Instant::now();
+        let mut components = Vec::with_capacity(4);
+
+        if let (Some(s3_url), Some(s3_config)) = (&self.s3_url, &self.s3_config) {
+            match S3Probe::new(s3_url, s3_config) {
+                Ok(s3) => {
+                    components.push(
+                        health::probe("storage", HealthState::Unhealthy, s3.check_storage()).await,
+                    );
+                    components.push(
+                        health::probe("delta_log", HealthState::Unhealthy, s3.check_delta_log())
+                            .await,
+                    );
+                }
+                Err(e) => {
+                    for name in ["storage", "delta_log"] {
+                        components.push(
+                            health::probe(name, HealthState::Unhealthy, async {
+                                Err(Error::Other(e.to_string()))
+                            })
+                            .await,
+                        );
+                    }
+                }
+            }
+        } else {
+            components.push(
+                health::probe(
+                    "storage",
+                    HealthState::Unhealthy,
+                    health::check_local_storage(&self.base_path),
+                )
+                .await,
+            );
+            components.push(
+                health::probe(
+                    "delta_log",
+                    HealthState::Unhealthy,
+                    health::check_local_delta_log(&self.base_path),
+                )
+                .await,
+            );
+        }
+
+        components.push(
+            health::probe(
+                "local_disk",
+                HealthState::Degraded,
+                health::check_local_disk(&self.base_path),
+            )
+            .await,
+        );
+        components.push(
+            health::probe("write_buffer", HealthState::Degraded, async {
+                self.batch_buffer.stats().await;
+                Ok(())
+            })
+            .await,
+        );
+
+        let report = HealthReport::from_components(components, start.elapsed());
+        if report.status != HealthState::Healthy {
+            warn!(
+                "Health check {}: {:?}",
+                report.status,
+                report
+                    .components
+                    .iter()
+                    .filter_map(|c| c.detail.as_ref().map(|d| format!("{}: {}", c.name, d)))
+                    .collect::<Vec<_>>()
+            );
+        }
+        report
+    }
+
     /// Reset metrics counters (except uptime)
     pub async fn reset_metrics(&self) {
         self.metrics.reset();
@@ -2052,6 +7684,61 @@ impl DatabaseOps {
         crate::metadata::backup::verify_backup(backup_path)
     }
 
+    /// Export one version of the table, data files and log, to a portable archive
+    ///
+    /// Fails if a file the version references is gone, e.g. removed by a
+    /// concurrent vacuum. Requires admin role: the archive holds every
+    /// column as stored, past any masks.
+    pub async fn export_snapshot<P: AsRef<Path>>(
+        &self,
+        version: i64,
+        out_path: P,
+    ) -> Result<SnapshotManifest> {
+        // Check admin permission
+        self.check_permission(&crate::security::Permission::Admin)?;
+
+        info!(
+            "Exporting version {} to snapshot {}",
+            version,
+            out_path.as_ref().display()
+        );
+        if self.s3_url.is_some() {
+            return Err(Error::InvalidOperation(
+                "Snapshots are only supported for local tables".to_string(),
+            ));
+        }
+        let version = u64::try_from(version)
+            .map_err(|_| Error::InvalidOperation(format!("Invalid version {}", version)))?;
+
+        let base_path = self.base_path.clone();
+        let out_path = out_path.as_ref().to_path_buf();
+        tokio::task::spawn_blocking(move || {
+            crate::metadata::snapshot::export_snapshot(&base_path, version, &out_path)
+        })
+        .await
+        .map_err(|e| Error::Other(format!("Snapshot export task failed: {}", e)))?
+    }
+
+    /// Restore a snapshot archive into a new table at `dest` and open it
+    pub async fn import_snapshot<P: AsRef<Path>>(archive_path: P, dest: P) -> Result<Self> {
+        let archive_path = archive_path.as_ref().to_path_buf();
+        let dest = dest.as_ref().to_path_buf();
+        info!(
+            "Importing snapshot {} into {}",
+            archive_path.display(),
+            dest.display()
+        );
+
+        let target = dest.clone();
+        tokio::task::spawn_blocking(move || {
+            crate::metadata::snapshot::import_snapshot(&archive_path, &target)
+        })
+        .await
+        .map_err(|e| Error::Other(format!("Snapshot import task failed: {}", e)))??;
+
+        Self::open(dest).await
+    }
+
     /// Restore database to a specific transaction (point-in-time recovery)
     /// target_txn_id is a timestamp in milliseconds
     pub async fn restore_to_transaction<P: AsRef<Path>>(
@@ -2151,6 +7838,20 @@ impl DatabaseOps {
 // - is_value_less_than/is_value_greater_than -> query::pruning
 // - compute_column_statistics -> delta_lake::stats
 
+/// Load the table `builder` points at, as of `as_of`
+async fn load_as_of(
+    builder: deltalake::DeltaTableBuilder,
+    as_of: crate::query::AsOf,
+) -> Result<deltalake::DeltaTable> {
+    let builder = match as_of {
+        crate::query::AsOf::Version(version) => builder.with_version(version),
+        crate::query::AsOf::Timestamp(timestamp) => builder
+            .with_datestring(timestamp.to_rfc3339())
+            .map_err(Error::DeltaTable)?,
+    };
+    builder.load().await.map_err(Error::DeltaTable)
+}
+
 impl DatabaseOps {
     /// Begin an explicit transaction with user-controlled commit/rollback
     ///
@@ -2178,6 +7879,51 @@ impl DatabaseOps {
 
         Ok(Transaction::new(Arc::clone(self), txn_id, snapshot_version))
     }
+
+    /// Explicit transactions open on this handle, by ID
+    pub fn list_transactions(&self) -> Result<Vec<TransactionInfo>> {
+        // Check admin permission
+        self.check_permission(&crate::security::Permission::Admin)?;
+
+        let open = self.open_transactions.lock().unwrap();
+        let mut transactions: Vec<_> = open
+            .iter()
+            .map(|(id, entry)| TransactionInfo::new(*id, entry, self.lock_manager.locks_held(*id)))
+            .collect();
+        transactions.sort_by_key(|txn| txn.id);
+        Ok(transactions)
+    }
+
+    /// Abort another caller's transaction, releasing its locks so waiting
+    /// transactions can proceed
+    ///
+    /// Its buffered writes are never committed, and the owner's next
+    /// operation fails with `Error::Cancelled`. Returns false if the
+    /// transaction had already committed, rolled back or been dropped.
+    pub async fn abort_transaction(&self, txn_id: u64) -> Result<bool> {
+        // Check admin permission
+        self.check_permission(&crate::security::Permission::Admin)?;
+
+        let entry = self.open_transactions.lock().unwrap().get(&txn_id).cloned();
+        let Some(entry) = entry else {
+            return Ok(false);
+        };
+        // Loses to a commit or rollback that got there first
+        if !entry.cancel().await {
+            return Ok(false);
+        }
+        self.lock_manager.cancel(txn_id);
+        self.open_transactions.lock().unwrap().remove(&txn_id);
+
+        warn!("Aborted transaction {}", txn_id);
+        self.audit_log(
+            "ABORT_TRANSACTION",
+            &format!("transaction {}", txn_id),
+            true,
+        )
+        .await;
+        Ok(true)
+    }
 }
 
 #[cfg(test)]
@@ -2219,8 +7965,10 @@ mod tests {
             .unwrap();
         let batch = create_test_batch(schema, 1);
 
-        let txn_id = db.insert(batch).await.unwrap();
-        assert!(txn_id > 0, "Transaction ID should be positive");
+        let commit = db.insert(batch).await.unwrap();
+        assert_eq!(commit.version, 1, "Insert should commit the first version");
+        assert_eq!(commit.rows_affected, 3);
+        assert_eq!(commit.operation, "INSERT");
 
         // Verify Parquet file exists (Delta Lake stores files in root directory)
         let mut parquet_count = 0;
@@ -2360,7 +8108,7 @@ mod tests {
             .unwrap();
         let batch = create_test_batch(schema, 1);
 
-        let _txn_id = db.insert(batch).await.unwrap();
+        db.insert(batch).await.unwrap();
 
         // Verify Delta Lake transaction log exists (Delta Lake native mode)
         let delta_log_dir = temp_dir.path().join("_delta_log");
@@ -0,0 +1,276 @@
+//! Delta change data feed
+//!
+//! With `delta.enableChangeDataFeed` set, deletes, updates and merges write
+//! the rows they changed to change data files under `_change_data/`, listed
+//! in the commit as `cdc` actions, each row tagged with its `_change_type`:
+//! `insert`, `delete`, and `update_preimage` / `update_postimage` for the
+//! old and new values of an updated row. Commits without change data files,
+//! such as appends, are read from the files they added and removed instead:
+//! added rows are inserts and removed rows deletes.
+//!
+//! The feed is the table's columns followed by `_change_type`,
+//! `_commit_version` and `_commit_timestamp`, as Delta readers on Spark
+//! return it. Versions committed before the feed was enabled have no change
+//! data files, so an update there reads as a delete and an insert of every
+//! row in the files it rewrote.
+
+use super::changes::{read_change, TableChange};
+use crate::{Error, Result};
+use arrow::array::{
+    new_null_array, ArrayRef, Int64Array, RecordBatch, StringArray, TimestampMillisecondArray,
+};
+use arrow::datatypes::{DataType, Field, Schema, SchemaRef, TimeUnit};
+use std::path::Path;
+use std::sync::Arc;
+
+/// Table property that turns the feed on
+pub const ENABLE_PROPERTY: &str = "delta.enableChangeDataFeed";
+
+/// Column holding each change row's `ChangeType`
+pub const CHANGE_TYPE_COLUMN: &str = "_change_type";
+
+/// Column holding the version that made each change
+pub const COMMIT_VERSION_COLUMN: &str = "_commit_version";
+
+/// Column holding the commit time of that version
+pub const COMMIT_TIMESTAMP_COLUMN: &str = "_commit_timestamp";
+
+/// What happened to a row
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChangeType {
+    Insert,
+    /// The row as it was before an update
+    UpdatePreimage,
+    /// The row as an update left it
+    UpdatePostimage,
+    Delete,
+}
+
+impl ChangeType {
+    /// The `_change_type` value, e.g. `update_preimage`
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ChangeType::Insert => "insert",
+            ChangeType::UpdatePreimage => "update_preimage",
+            ChangeType::UpdatePostimage => "update_postimage",
+            ChangeType::Delete => "delete",
+        }
+    }
+
+    /// The change type a `_change_type` value names
+    pub fn parse(value: &str) -> Option<Self> {
+        [
+            ChangeType::Insert,
+            ChangeType::UpdatePreimage,
+            ChangeType::UpdatePostimage,
+            ChangeType::Delete,
+        ]
+        .into_iter()
+        .find(|t| t.as_str() == value)
+    }
+}
+
+/// Schema of the feed of a table with `schema`
+pub fn change_feed_schema(schema: &SchemaRef) -> SchemaRef {
+    let mut fields: Vec<Field> = schema.fields().iter().map(|f| f.as_ref().clone()).collect();
+    fields.push(Field::new(CHANGE_TYPE_COLUMN, DataType::Utf8, false));
+    fields.push(Field::new(COMMIT_VERSION_COLUMN, DataType::Int64, false));
+    fields.push(Field::new(
+        COMMIT_TIMESTAMP_COLUMN,
+        DataType::Timestamp(TimeUnit::Millisecond, Some("UTC".into())),
+        true,
+    ));
+    Arc::new(Schema::new(fields))
+}
+
+/// Change rows of versions `from_version` through `to_version` of the table
+/// at `base_path`
+///
+/// Stops early at a version that hasn't been committed. Fails with
+/// `Error::VersionGone` if log cleanup or VACUUM removed one of the versions.
+pub fn read_change_feed(
+    base_path: &Path,
+    schema: &SchemaRef,
+    from_version: u64,
+    to_version: u64,
+) -> Result<Vec<RecordBatch>> {
+    let mut rows = Vec::new();
+    for version in from_version..=to_version {
+        let Some(change) = read_change(base_path, schema, version)? else {
+            break;
+        };
+        rows.extend(change_rows(&change, schema)?);
+    }
+    Ok(rows)
+}
+
+/// Change rows of one version, from its change data files if it has any
+pub fn change_rows(change: &TableChange, schema: &SchemaRef) -> Result<Vec<RecordBatch>> {
+    let mut rows = Vec::new();
+    if change.change_data.is_empty() {
+        for batch in &change.removed {
+            rows.push(feed_batch(batch, schema, Some(ChangeType::Delete), change)?);
+        }
+        for batch in &change.added {
+            rows.push(feed_batch(batch, schema, Some(ChangeType::Insert), change)?);
+        }
+    } else {
+        for batch in &change.change_data {
+            rows.push(feed_batch(batch, schema, None, change)?);
+        }
+    }
+    rows.retain(|batch| batch.num_rows() > 0);
+    Ok(rows)
+}
+
+/// `batch` as feed rows, its columns matched to `schema` by name
+///
+/// The change type is `change_type`, or else the batch's own
+/// `_change_type` column. Files written before a column was added don't
+/// have it, so it reads as NULL.
+fn feed_batch(
+    batch: &RecordBatch,
+    schema: &SchemaRef,
+    change_type: Option<ChangeType>,
+    change: &TableChange,
+) -> Result<RecordBatch> {
+    let num_rows = batch.num_rows();
+    let mut columns: Vec<ArrayRef> = schema
+        .fields()
+        .iter()
+        .map(|field| match batch.column_by_name(field.name()) {
+            Some(column) if column.data_type() == field.data_type() => Ok(column.clone()),
+            Some(column) => Ok(arrow::compute::cast(column, field.data_type())?),
+            None => Ok(new_null_array(field.data_type(), num_rows)),
+        })
+        .collect::<Result<_>>()?;
+
+    let change_types: ArrayRef = match change_type {
+        Some(change_type) => Arc::new(StringArray::from(vec![change_type.as_str(); num_rows])),
+        None => {
+            let column = batch.column_by_name(CHANGE_TYPE_COLUMN).ok_or_else(|| {
+                Error::Other(format!(
+                    "Change data in version {} has no {} column",
+                    change.version, CHANGE_TYPE_COLUMN
+                ))
+            })?;
+            arrow::compute::cast(column, &DataType::Utf8)?
+        }
+    };
+    columns.push(change_types);
+    columns.push(Arc::new(Int64Array::from(vec![
+        change.version as i64;
+        num_rows
+    ])));
+    columns.push(Arc::new(
+        TimestampMillisecondArray::from(vec![change.timestamp; num_rows]).with_timezone("UTC"),
+    ));
+    Ok(RecordBatch::try_new(change_feed_schema(schema), columns)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow::array::{AsArray, Int32Array};
+    use arrow::datatypes::Int32Type;
+
+    fn schema() -> SchemaRef {
+        Arc::new(Schema::new(vec![Field::new("id", DataType::Int32, false)]))
+    }
+
+    fn ids(values: Vec<i32>) -> RecordBatch {
+        RecordBatch::try_new(schema(), vec![Arc::new(Int32Array::from(values))]).unwrap()
+    }
+
+    fn change(added: Vec<RecordBatch>, removed: Vec<RecordBatch>) -> TableChange {
+        TableChange {
+            version: 4,
+            timestamp: Some(1_700_000_000_000),
+            operation: Some("WRITE".to_string()),
+            added,
+            removed,
+            change_data: Vec::new(),
+        }
+    }
+
+    fn change_types(rows: &[RecordBatch]) -> Vec<(i32, String)> {
+        rows.iter()
+            .flat_map(|batch| {
+                let ids = batch.column(0).as_primitive::<Int32Type>().clone();
+                let types = batch.column(1).as_string::<i32>().clone();
+                (0..batch.num_rows())
+                    .map(move |i| (ids.value(i), types.value(i).to_string()))
+                    .collect::<Vec<_>>()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_changes_inferred_from_files() {
+        let rows = change_rows(
+            &change(vec![ids(vec![3])], vec![ids(vec![1, 2])]),
+            &schema(),
+        )
+        .unwrap();
+        assert_eq!(
+            change_types(&rows),
+            vec![
+                (1, "delete".to_string()),
+                (2, "delete".to_string()),
+                (3, "insert".to_string())
+            ]
+        );
+        assert_eq!(rows[0].schema(), change_feed_schema(&schema()));
+        assert_eq!(
+            rows[0]
+                .column_by_name(COMMIT_VERSION_COLUMN)
+                .unwrap()
+                .as_primitive::<arrow::datatypes::Int64Type>()
+                .value(0),
+            4
+        );
+    }
+
+    #[test]
+    fn test_change_data_files_take_precedence() {
+        let cdc_schema = Arc::new(Schema::new(vec![
+            Field::new("id", DataType::Int32, false),
+            Field::new(CHANGE_TYPE_COLUMN, DataType::Utf8, false),
+        ]));
+        let cdc = RecordBatch::try_new(
+            cdc_schema,
+            vec![
+                Arc::new(Int32Array::from(vec![1, 1])),
+                Arc::new(StringArray::from(vec![
+                    "update_preimage",
+                    "update_postimage",
+                ])),
+            ],
+        )
+        .unwrap();
+        let mut update = change(vec![ids(vec![1, 2])], vec![ids(vec![1, 2])]);
+        update.change_data.push(cdc);
+
+        let rows = change_rows(&update, &schema()).unwrap();
+        assert_eq!(
+            change_types(&rows),
+            vec![
+                (1, "update_preimage".to_string()),
+                (1, "update_postimage".to_string())
+            ]
+        );
+    }
+
+    #[test]
+    fn test_change_type_names() {
+        for change_type in [
+            ChangeType::Insert,
+            ChangeType::UpdatePreimage,
+            ChangeType::UpdatePostimage,
+            ChangeType::Delete,
+        ] {
+            assert_eq!(ChangeType::parse(change_type.as_str()), Some(change_type));
+        }
+        assert_eq!(ChangeType::parse("upsert"), None);
+    }
+}
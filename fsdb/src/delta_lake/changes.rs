@@ -0,0 +1,341 @@
+//! Change data read from the Delta log
+//!
+//! Every commit lists the data files it added and removed, so the rows a
+//! version changed can be read back from those files. Changes are file
+//! granular: a DELETE or UPDATE that rewrites a file removes all of the old
+//! file's rows and adds the surviving ones, so rows it didn't touch show up on
+//! both sides. Files rewritten without changing data, by OPTIMIZE or Z-ORDER,
+//! are left out. With the change data feed enabled, commits also carry
+//! row-level change data, see `change_feed`.
+
+use super::partitions::{conform_to_partition, PartitionValues};
+use crate::{Error, Result};
+use arrow::array::RecordBatch;
+use arrow::datatypes::SchemaRef;
+use futures::Stream;
+use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+use serde_json::Value;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+use url::Url;
+
+pub use tokio_util::sync::CancellationToken;
+
+/// How often a follower checks the log for the next version by default
+pub const DEFAULT_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Rows added and removed by one committed version
+#[derive(Debug, Clone)]
+pub struct TableChange {
+    pub version: u64,
+    /// Commit time in milliseconds since the epoch
+    pub timestamp: Option<i64>,
+    /// Operation recorded with the commit, such as `WRITE` or `DELETE`
+    pub operation: Option<String>,
+    pub added: Vec<RecordBatch>,
+    pub removed: Vec<RecordBatch>,
+    /// Rows of the commit's change data files, each with its `_change_type`
+    ///
+    /// Written by deletes, updates and merges while the change data feed is
+    /// enabled; empty for other commits.
+    pub change_data: Vec<RecordBatch>,
+}
+
+impl TableChange {
+    pub fn num_added(&self) -> usize {
+        self.added.iter().map(RecordBatch::num_rows).sum()
+    }
+
+    pub fn num_removed(&self) -> usize {
+        self.removed.iter().map(RecordBatch::num_rows).sum()
+    }
+}
+
+/// Changes made by `version`, or `None` if it hasn't been committed yet
+///
+/// Partition columns, which the data files leave out, are filled in from the
+/// file actions. Fails with `Error::VersionGone` if log cleanup has removed
+/// the commit or VACUUM one of its data files.
+pub fn read_change(
+    base_path: &Path,
+    schema: &SchemaRef,
+    version: u64,
+) -> Result<Option<TableChange>> {
+    let log_dir = base_path.join("_delta_log");
+    let text = match std::fs::read_to_string(log_dir.join(format!("{:020}.json", version))) {
+        Ok(text) => text,
+        Err(e) if e.kind() == ErrorKind::NotFound => {
+            return if log_reaches(&log_dir, version)? {
+                Err(Error::VersionGone(version))
+            } else {
+                Ok(None)
+            };
+        }
+        Err(e) => return Err(e.into()),
+    };
+
+    let mut change = TableChange {
+        version,
+        timestamp: None,
+        operation: None,
+        added: Vec::new(),
+        removed: Vec::new(),
+        change_data: Vec::new(),
+    };
+    for line in text.lines().filter(|line| !line.trim().is_empty()) {
+        let action: Value = serde_json::from_str(line)?;
+        if let Some(info) = action.get("commitInfo") {
+            change.timestamp = info["timestamp"].as_i64();
+            change.operation = info["operation"].as_str().map(str::to_string);
+        }
+        if let Some(add) = action.get("add").filter(|a| changes_data(a)) {
+            change
+                .added
+                .extend(read_data_file(base_path, schema, version, add)?);
+        }
+        if let Some(remove) = action.get("remove").filter(|a| changes_data(a)) {
+            change
+                .removed
+                .extend(read_data_file(base_path, schema, version, remove)?);
+        }
+        if let Some(cdc) = action.get("cdc") {
+            change
+                .change_data
+                .extend(read_data_file(base_path, schema, version, cdc)?);
+        }
+    }
+    Ok(Some(change))
+}
+
+/// Follows a table's log, yielding each version as it is committed
+///
+/// Like `tail -f`: once caught up, it checks for the next commit every poll
+/// interval until its cancellation token is cancelled. After an error, such
+/// as `Error::VersionGone` when the follower fell behind log cleanup, it
+/// yields nothing more; the consumer should re-read the table and follow from
+/// its current version.
+pub struct ChangeFollower {
+    base_path: PathBuf,
+    schema: SchemaRef,
+    /// `None` once the follower has failed
+    next_version: Option<u64>,
+    poll_interval: Duration,
+    cancel: CancellationToken,
+    /// Applied to each change's batches before they're yielded
+    transform: Option<BatchTransform>,
+}
+
+/// Rewrites the batches of a change, such as decrypting or masking columns
+pub type BatchTransform = Arc<dyn Fn(Vec<RecordBatch>) -> Result<Vec<RecordBatch>> + Send + Sync>;
+
+impl ChangeFollower {
+    pub fn new(
+        base_path: PathBuf,
+        schema: SchemaRef,
+        from_version: u64,
+        cancel: CancellationToken,
+    ) -> Self {
+        Self {
+            base_path,
+            schema,
+            next_version: Some(from_version),
+            poll_interval: DEFAULT_POLL_INTERVAL,
+            cancel,
+            transform: None,
+        }
+    }
+
+    /// Check for new commits every `interval` instead of every 100ms
+    pub fn with_poll_interval(mut self, interval: Duration) -> Self {
+        self.poll_interval = interval;
+        self
+    }
+
+    /// Pass the added, removed and change data batches of every change
+    /// through `transform` before yielding it
+    pub fn with_transform(mut self, transform: BatchTransform) -> Self {
+        self.transform = Some(transform);
+        self
+    }
+
+    /// Version the next change will be for
+    pub fn next_version(&self) -> Option<u64> {
+        self.next_version
+    }
+
+    /// Wait for the next version to be committed and return its changes
+    ///
+    /// Returns `None` once cancelled, or after an error has been returned.
+    pub async fn next_change(&mut self) -> Option<Result<TableChange>> {
+        let version = self.next_version?;
+        loop {
+            if self.cancel.is_cancelled() {
+                return None;
+            }
+            match read_change(&self.base_path, &self.schema, version) {
+                Ok(Some(change)) => {
+                    let change = match self.transformed(change) {
+                        Ok(change) => change,
+                        Err(e) => {
+                            self.next_version = None;
+                            return Some(Err(e));
+                        }
+                    };
+                    self.next_version = Some(version + 1);
+                    return Some(Ok(change));
+                }
+                Ok(None) => {}
+                Err(e) => {
+                    self.next_version = None;
+                    return Some(Err(e));
+                }
+            }
+            tokio::select! {
+                _ = self.cancel.cancelled() => return None,
+                _ = tokio::time::sleep(self.poll_interval) => {}
+            }
+        }
+    }
+
+    /// `change` with its batches passed through the transform, if there is one
+    fn transformed(&self, mut change: TableChange) -> Result<TableChange> {
+        if let Some(transform) = &self.transform {
+            change.added = transform(change.added)?;
+            change.removed = transform(change.removed)?;
+            change.change_data = transform(change.change_data)?;
+        }
+        Ok(change)
+    }
+
+    /// The follower as a stream of changes
+    pub fn into_stream(self) -> impl Stream<Item = Result<TableChange>> + Send + 'static {
+        futures::stream::unfold(self, |mut follower| async move {
+            let change = follower.next_change().await?;
+            Some((change, follower))
+        })
+    }
+}
+
+/// Whether an add or remove action changes rows, rather than rearranging them
+fn changes_data(action: &Value) -> bool {
+    action["dataChange"].as_bool() != Some(false)
+}
+
+/// Whether the log holds `version` or anything newer, such as a checkpoint
+fn log_reaches(log_dir: &Path, version: u64) -> Result<bool> {
+    if !log_dir.exists() {
+        return Ok(false);
+    }
+    for entry in std::fs::read_dir(log_dir)? {
+        let name = entry?.file_name();
+        let logged = name
+            .to_str()
+            .and_then(|name| name.get(..20))
+            .and_then(|v| v.parse::<u64>().ok());
+        if logged.is_some_and(|logged| logged >= version) {
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
+
+/// Rows of the data file an add, remove or cdc action refers to
+fn read_data_file(
+    base_path: &Path,
+    schema: &SchemaRef,
+    version: u64,
+    action: &Value,
+) -> Result<Vec<RecordBatch>> {
+    let path = action["path"].as_str().ok_or_else(|| {
+        Error::Other(format!("File action without a path in version {}", version))
+    })?;
+    // Paths in the log are URL-encoded and relative to the table, or absolute URLs
+    let path = std::path::absolute(base_path)
+        .ok()
+        .and_then(|base_path| Url::from_directory_path(base_path).ok())
+        .and_then(|table| table.join(path).ok())
+        .and_then(|url| url.to_file_path().ok())
+        .ok_or_else(|| Error::Other(format!("Invalid data file path: {}", path)))?;
+    let file = match std::fs::File::open(&path) {
+        Ok(file) => file,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Err(Error::VersionGone(version)),
+        Err(e) => return Err(e.into()),
+    };
+
+    let partition: PartitionValues = action["partitionValues"]
+        .as_object()
+        .into_iter()
+        .flatten()
+        .map(|(column, value)| (column.clone(), value.as_str().map(str::to_string)))
+        .collect();
+    ParquetRecordBatchReaderBuilder::try_new(file)?
+        .build()?
+        .map(|batch| conform_to_partition(batch?, schema, &partition))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow::datatypes::{DataType, Field, Schema};
+    use std::sync::Arc;
+    use tempfile::TempDir;
+
+    fn schema() -> SchemaRef {
+        Arc::new(Schema::new(vec![Field::new("id", DataType::Int32, false)]))
+    }
+
+    fn write_commit(log_dir: &Path, version: u64, actions: &str) {
+        std::fs::create_dir_all(log_dir).unwrap();
+        std::fs::write(log_dir.join(format!("{:020}.json", version)), actions).unwrap();
+    }
+
+    #[test]
+    fn test_missing_version_is_pending_or_gone() {
+        let temp_dir = TempDir::new().unwrap();
+        let log_dir = temp_dir.path().join("_delta_log");
+        write_commit(
+            &log_dir,
+            0,
+            r#"{"commitInfo":{"timestamp":1700000000000,"operation":"CREATE TABLE"}}"#,
+        );
+
+        let change = read_change(temp_dir.path(), &schema(), 0).unwrap().unwrap();
+        assert_eq!(change.operation.as_deref(), Some("CREATE TABLE"));
+        assert_eq!(change.timestamp, Some(1_700_000_000_000));
+        assert_eq!(change.num_added(), 0);
+
+        // Not committed yet
+        assert!(read_change(temp_dir.path(), &schema(), 1)
+            .unwrap()
+            .is_none());
+
+        // Cleaned out of the log: a later version exists but this one doesn't
+        std::fs::write(log_dir.join(format!("{:020}.checkpoint.parquet", 2)), b"").unwrap();
+        let err = read_change(temp_dir.path(), &schema(), 1).unwrap_err();
+        assert!(matches!(err, Error::VersionGone(1)), "{}", err);
+    }
+
+    #[test]
+    fn test_vacuumed_data_file_is_gone() {
+        let temp_dir = TempDir::new().unwrap();
+        write_commit(
+            &temp_dir.path().join("_delta_log"),
+            3,
+            r#"{"add":{"path":"part-0.parquet","size":10,"partitionValues":{},"dataChange":true}}"#,
+        );
+
+        let err = read_change(temp_dir.path(), &schema(), 3).unwrap_err();
+        assert!(matches!(err, Error::VersionGone(3)), "{}", err);
+    }
+
+    #[test]
+    fn test_rearranged_files_are_not_changes() {
+        assert!(changes_data(&serde_json::json!({"path": "a.parquet"})));
+        assert!(!changes_data(
+            &serde_json::json!({"path": "a.parquet", "dataChange": false})
+        ));
+    }
+}
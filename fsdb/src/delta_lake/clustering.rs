@@ -0,0 +1,258 @@
+//! Clustered inserts
+//!
+//! A table with a clustering key, stored in the `fsdb.clusteringKey` table
+//! property, keeps the key's values in each data file within a narrow range,
+//! so range filters on the key skip most files. Each insert is sorted by the
+//! key, then cut where the key crosses the range of an existing file: rows
+//! inside a file's range go to a new file within that range, and rows between
+//! ranges to a file of their own. Out-of-order rows are sorted into place
+//! within the insert, so a write buffer flush clusters everything it holds.
+//!
+//! Overlapping file ranges are merged first, and null keys sort before every
+//! range. The ranges come from the Delta log's statistics; files without
+//! statistics for the key don't constrain where rows go.
+
+use crate::delta_lake::FileStats;
+use crate::Result;
+use arrow::array::{Array, ArrayRef, RecordBatch, StringArray};
+use arrow::compute::SortOptions;
+use arrow::datatypes::DataType;
+use arrow::row::{RowConverter, Rows, SortField};
+use std::sync::Arc;
+use tracing::debug;
+
+/// Table property naming the clustering key column
+pub const CLUSTERING_KEY_PROPERTY: &str = "fsdb.clusteringKey";
+
+/// Whether values of `data_type` can be ordered for clustering
+pub fn supports_type(data_type: &DataType) -> bool {
+    RowConverter::supports_fields(&[SortField::new(data_type.clone())])
+}
+
+/// Sort `batch` by `key` and cut it into batches that each stay within the
+/// range of one existing file, or within a gap between ranges
+///
+/// A batch without the key column is returned as it is.
+pub fn cluster_batch(
+    batch: &RecordBatch,
+    key: &str,
+    files: &[FileStats],
+) -> Result<Vec<RecordBatch>> {
+    let Some(column) = batch.column_by_name(key) else {
+        return Ok(vec![batch.clone()]);
+    };
+    if batch.num_rows() == 0 || !supports_type(column.data_type()) {
+        return Ok(vec![batch.clone()]);
+    }
+
+    let options = SortOptions {
+        descending: false,
+        nulls_first: true,
+    };
+    let indices = arrow::compute::sort_to_indices(column, Some(options), None)?;
+    let sorted = arrow::compute::take_record_batch(batch, &indices)?;
+
+    let converter = RowConverter::new(vec![SortField::new_with_options(
+        column.data_type().clone(),
+        options,
+    )])?;
+    let keys = converter.convert_columns(&[sorted.column_by_name(key).unwrap().clone()])?;
+    let Some((mins, maxs)) = file_ranges(files, key, column.data_type(), &converter)? else {
+        return Ok(vec![sorted]);
+    };
+    let ranges = merge_ranges(&mins, &maxs);
+
+    // Ranges and rows are both in key order, so one pass assigns each row
+    // its segment: 2i is the gap before range i, 2i + 1 range i itself
+    let mut segments = Vec::new();
+    let mut start = 0;
+    let mut current = None;
+    let mut range = 0;
+    for row in 0..sorted.num_rows() {
+        let value = keys.row(row);
+        while range < ranges.len() && value > maxs.row(ranges[range].1) {
+            range += 1;
+        }
+        let segment = match ranges.get(range) {
+            Some(&(min, _)) if value >= mins.row(min) => 2 * range + 1,
+            _ => 2 * range,
+        };
+        if current.is_some_and(|current| current != segment) {
+            segments.push(sorted.slice(start, row - start));
+            start = row;
+        }
+        current = Some(segment);
+    }
+    segments.push(sorted.slice(start, sorted.num_rows() - start));
+
+    debug!(
+        "Clustered {} rows on {} into {} files against {} ranges",
+        sorted.num_rows(),
+        key,
+        segments.len(),
+        ranges.len()
+    );
+    Ok(segments)
+}
+
+/// Minimum and maximum of `key` in each file with statistics for it, as
+/// rows of `converter`, or `None` if no file has any
+fn file_ranges(
+    files: &[FileStats],
+    key: &str,
+    data_type: &DataType,
+    converter: &RowConverter,
+) -> Result<Option<(Rows, Rows)>> {
+    let text = |value: &serde_json::Value| match value {
+        serde_json::Value::String(s) => Some(s.clone()),
+        serde_json::Value::Number(n) => Some(n.to_string()),
+        serde_json::Value::Bool(b) => Some(b.to_string()),
+        _ => None,
+    };
+    let bounds: Vec<(String, String)> = files
+        .iter()
+        .filter_map(|file| {
+            let min = text(file.min_values.get(key)?)?;
+            let max = text(file.max_values.get(key)?)?;
+            Some((min, max))
+        })
+        .collect();
+    if bounds.is_empty() {
+        return Ok(None);
+    }
+
+    // Statistics are JSON; cast them back to the key's type
+    let to_array = |values: Vec<&str>| -> Result<ArrayRef> {
+        let strings: ArrayRef = Arc::new(StringArray::from(values));
+        Ok(arrow::compute::cast(&strings, data_type)?)
+    };
+    let mins = to_array(bounds.iter().map(|(min, _)| min.as_str()).collect())?;
+    let maxs = to_array(bounds.iter().map(|(_, max)| max.as_str()).collect())?;
+    // A bound that didn't convert would pose as a null; drop its file
+    let valid: Vec<u32> = (0..mins.len())
+        .filter(|&i| mins.is_valid(i) && maxs.is_valid(i))
+        .map(|i| i as u32)
+        .collect();
+    if valid.is_empty() {
+        return Ok(None);
+    }
+    let valid = arrow::array::UInt32Array::from(valid);
+    let mins = arrow::compute::take(&mins, &valid, None)?;
+    let maxs = arrow::compute::take(&maxs, &valid, None)?;
+
+    Ok(Some((
+        converter.convert_columns(&[mins])?,
+        converter.convert_columns(&[maxs])?,
+    )))
+}
+
+/// Indices of (min, max) of the disjoint ranges the file ranges cover, in
+/// key order
+fn merge_ranges(mins: &Rows, maxs: &Rows) -> Vec<(usize, usize)> {
+    let mut order: Vec<usize> = (0..mins.num_rows()).collect();
+    order.sort_by(|&a, &b| mins.row(a).cmp(&mins.row(b)));
+
+    let mut merged: Vec<(usize, usize)> = Vec::new();
+    for file in order {
+        match merged.last_mut() {
+            Some((_, max)) if mins.row(file) <= maxs.row(*max) => {
+                if maxs.row(file) > maxs.row(*max) {
+                    *max = file;
+                }
+            }
+            _ => merged.push((file, file)),
+        }
+    }
+    merged
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow::array::Int64Array;
+    use arrow::datatypes::{Field, Schema};
+    use std::collections::HashMap;
+
+    fn batch(keys: Vec<Option<i64>>) -> RecordBatch {
+        let schema = Arc::new(Schema::new(vec![Field::new("ts", DataType::Int64, true)]));
+        RecordBatch::try_new(schema, vec![Arc::new(Int64Array::from(keys))]).unwrap()
+    }
+
+    fn file(min: i64, max: i64) -> FileStats {
+        FileStats {
+            path: format!("{}-{}.parquet", min, max),
+            size_bytes: 0,
+            min_values: HashMap::from([("ts".to_string(), serde_json::json!(min))]),
+            max_values: HashMap::from([("ts".to_string(), serde_json::json!(max))]),
+            null_counts: HashMap::new(),
+            num_records: 1,
+            has_stats: true,
+        }
+    }
+
+    fn keys(batches: &[RecordBatch]) -> Vec<Vec<Option<i64>>> {
+        batches
+            .iter()
+            .map(|b| {
+                b.column(0)
+                    .as_any()
+                    .downcast_ref::<Int64Array>()
+                    .unwrap()
+                    .iter()
+                    .collect()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_sorted_without_existing_files() {
+        let clustered = cluster_batch(&batch(vec![Some(3), None, Some(1)]), "ts", &[]).unwrap();
+        assert_eq!(keys(&clustered), vec![vec![None, Some(1), Some(3)]]);
+    }
+
+    #[test]
+    fn test_split_at_file_ranges() {
+        // Ranges 10-20 and 15-30 merge into 10-30; 50-60 stays apart
+        let files = [file(10, 20), file(50, 60), file(15, 30)];
+        let rows = batch(vec![
+            Some(55),
+            Some(5),
+            Some(12),
+            Some(40),
+            Some(30),
+            None,
+            Some(70),
+            Some(50),
+        ]);
+        let clustered = cluster_batch(&rows, "ts", &files).unwrap();
+        assert_eq!(
+            keys(&clustered),
+            vec![
+                vec![None, Some(5)],
+                vec![Some(12), Some(30)],
+                vec![Some(40)],
+                vec![Some(50), Some(55)],
+                vec![Some(70)],
+            ]
+        );
+    }
+
+    #[test]
+    fn test_string_bounds_cast_to_key_type() {
+        let schema = Arc::new(Schema::new(vec![Field::new(
+            "day",
+            DataType::Date32,
+            false,
+        )]));
+        let days = arrow::array::Date32Array::from(vec![19723, 19800, 19700]);
+        let rows = RecordBatch::try_new(schema, vec![Arc::new(days)]).unwrap();
+        let mut stats = file(0, 0);
+        stats.min_values = HashMap::from([("day".to_string(), serde_json::json!("2024-01-01"))]);
+        stats.max_values = HashMap::from([("day".to_string(), serde_json::json!("2024-01-31"))]);
+
+        // 2024-01-01 is day 19723
+        let clustered = cluster_batch(&rows, "day", &[stats]).unwrap();
+        let sizes: Vec<usize> = clustered.iter().map(|b| b.num_rows()).collect();
+        assert_eq!(sizes, vec![1, 1, 1]);
+    }
+}
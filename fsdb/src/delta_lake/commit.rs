@@ -0,0 +1,111 @@
+//! What a write committed to the table, and metadata attached to commits
+//!
+//! Writes run inside `with_metadata` record its key-value pairs in their
+//! commits' `commitInfo`, under `fsdb.commitMetadata`, where `history`
+//! reads them back. The metadata follows the write through the task, so
+//! every commit site picks it up without it being passed down explicitly.
+
+use crate::{Error, Result};
+use deltalake::kernel::transaction::CommitProperties;
+use deltalake::DeltaTable;
+use std::collections::HashMap;
+
+/// Key of the metadata object in `commitInfo`
+pub const COMMIT_METADATA_KEY: &str = "fsdb.commitMetadata";
+
+/// Most bytes of keys and values one commit's metadata may hold
+///
+/// Every commit is read back on log replay, so metadata belongs in the log
+/// only as small identifiers, not payloads.
+pub const MAX_COMMIT_METADATA_BYTES: usize = 64 * 1024;
+
+tokio::task_local! {
+    static COMMIT_METADATA: HashMap<String, String>;
+}
+
+/// Run `write` with `metadata` attached to the commits it makes
+///
+/// Fails with `Error::CommitMetadataTooLarge` before running anything if
+/// the keys and values add up to more than `MAX_COMMIT_METADATA_BYTES`.
+pub(crate) async fn with_metadata<T>(
+    metadata: HashMap<String, String>,
+    write: impl std::future::Future<Output = Result<T>>,
+) -> Result<T> {
+    check_metadata_size(&metadata)?;
+    COMMIT_METADATA.scope(metadata, write).await
+}
+
+/// Refuse metadata over `MAX_COMMIT_METADATA_BYTES`
+pub(crate) fn check_metadata_size(metadata: &HashMap<String, String>) -> Result<()> {
+    let bytes = metadata.iter().map(|(k, v)| k.len() + v.len()).sum();
+    if bytes > MAX_COMMIT_METADATA_BYTES {
+        return Err(Error::CommitMetadataTooLarge {
+            bytes,
+            limit: MAX_COMMIT_METADATA_BYTES,
+        });
+    }
+    Ok(())
+}
+
+/// Extra `commitInfo` fields for a commit made now
+pub(crate) fn app_metadata() -> HashMap<String, serde_json::Value> {
+    COMMIT_METADATA
+        .try_with(|metadata| {
+            if metadata.is_empty() {
+                return HashMap::new();
+            }
+            HashMap::from([(COMMIT_METADATA_KEY.to_string(), serde_json::json!(metadata))])
+        })
+        .unwrap_or_default()
+}
+
+/// Commit properties for a delta-rs operation committing now
+pub(crate) fn commit_properties() -> CommitProperties {
+    CommitProperties::default().with_metadata(app_metadata())
+}
+
+/// Outcome of a write: the version it produced and what it did
+///
+/// A write that changes nothing commits no new version; it reports the
+/// version the table was already at, with no rows affected.
+#[derive(Debug, Clone, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct CommitResult {
+    /// Table version after the write
+    pub version: i64,
+    /// Commit time of that version in milliseconds since the epoch
+    pub timestamp: Option<i64>,
+    /// Rows inserted, updated or deleted
+    pub rows_affected: u64,
+    /// Operation that made the write, as recorded in the audit log
+    pub operation: String,
+}
+
+impl CommitResult {
+    /// Describe the version `table` is at, as left by `operation`
+    ///
+    /// The version and row count are also recorded on the current tracing
+    /// span, for the commit spans that declare them.
+    pub(crate) async fn at(
+        table: &DeltaTable,
+        operation: &str,
+        rows_affected: u64,
+    ) -> Result<Self> {
+        let timestamp = table
+            .history(Some(1))
+            .await
+            .map_err(Error::DeltaTable)?
+            .into_iter()
+            .next()
+            .and_then(|commit| commit.timestamp);
+        let version = table.version().unwrap_or(0);
+        tracing::Span::current()
+            .record("version", version)
+            .record("rows", rows_affected);
+        Ok(Self {
+            version,
+            timestamp,
+            rows_affected,
+            operation: operation.to_string(),
+        })
+    }
+}
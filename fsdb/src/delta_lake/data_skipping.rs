@@ -3,7 +3,21 @@
 //! This module implements data skipping by reading per-file statistics from Delta Lake
 //! transaction logs and evaluating query predicates against them to skip files that
 //! cannot contain matching data.
+//!
+//! # NULL policy
+//!
+//! FSDB follows SQL three-valued logic everywhere:
+//! - A comparison involving `NULL` (`col > 5` on a null row, `col = NULL`) is
+//!   unknown, so the row does not match a `WHERE` clause
+//! - `GROUP BY` puts all `NULL` keys into a single group
+//! - Only `IS NULL` / `IS NOT NULL` match on nullness
+//!
+//! Min/max statistics exclude nulls, so pruning uses the per-file null counts:
+//! `IS NULL` skips files with no nulls, `IS NOT NULL` skips files that are all
+//! nulls, and comparisons skip files where the column is entirely null. A file
+//! without a null count for the column is never skipped on nullness.
 
+use crate::query::Collation;
 use crate::Result;
 use std::collections::HashMap;
 use std::path::Path;
@@ -18,153 +32,119 @@ pub struct FileStats {
     pub max_values: HashMap<String, serde_json::Value>,
     pub null_counts: HashMap<String, u64>,
     pub num_records: u64,
+    /// Whether the log recorded statistics for the file at all
+    pub has_stats: bool,
 }
 
-/// Extract per-file statistics from Delta Lake transaction log
-///
-/// Reads all add actions from the transaction log and extracts min/max statistics
-/// for each Parquet file.
-pub fn get_file_statistics(base_path: &Path) -> Result<Vec<FileStats>> {
-    let delta_log_path = base_path.join("_delta_log");
-
-    if !delta_log_path.exists() {
-        return Ok(Vec::new());
-    }
-
-    // Read all Delta Lake commit files
-    let mut commit_files = Vec::new();
-    for entry in std::fs::read_dir(&delta_log_path)? {
-        let entry = entry?;
-        let path = entry.path();
-        if path.is_file() && path.extension().and_then(|s| s.to_str()) == Some("json") {
-            let filename = path.file_name().unwrap().to_str().unwrap();
-            // Skip checkpoint files for now
-            if filename.contains(".checkpoint.") {
-                continue;
-            }
-            if let Some(version_str) = filename.strip_suffix(".json") {
-                if let Ok(version) = version_str.parse::<u64>() {
-                    commit_files.push((version, path.clone()));
-                }
-            }
+impl From<super::log_replay::AddFile> for FileStats {
+    fn from(file: super::log_replay::AddFile) -> Self {
+        let stats = file.stats.unwrap_or_default();
+        let values = |key: &str| -> HashMap<String, serde_json::Value> {
+            stats[key]
+                .as_object()
+                .map(|obj| obj.clone().into_iter().collect())
+                .unwrap_or_default()
+        };
+        FileStats {
+            min_values: values("minValues"),
+            max_values: values("maxValues"),
+            null_counts: values("nullCount")
+                .into_iter()
+                .filter_map(|(col, count)| count.as_u64().map(|c| (col, c)))
+                .collect(),
+            num_records: stats["numRecords"].as_u64().unwrap_or(0),
+            has_stats: stats.is_object(),
+            path: file.path,
+            size_bytes: file.size,
         }
     }
+}
 
-    // Sort by version
-    commit_files.sort_by_key(|(v, _)| *v);
-
-    // Track currently active files (path -> FileStats)
-    // Need to handle remove actions too
-    let mut active_files: HashMap<String, FileStats> = HashMap::new();
-
-    // Read stats from each commit file (JSONL format)
-    for (_version, commit_path) in commit_files {
-        let content = std::fs::read_to_string(&commit_path)?;
-
-        // Parse each line as JSON
-        for line in content.lines() {
-            if line.trim().is_empty() {
-                continue;
-            }
-
-            if let Ok(json) = serde_json::from_str::<serde_json::Value>(line) {
-                // Look for "add" actions which contain file stats
-                if let Some(add) = json.get("add") {
-                    let path = add
-                        .get("path")
-                        .and_then(|p| p.as_str())
-                        .unwrap_or("")
-                        .to_string();
-
-                    let size_bytes = add.get("size").and_then(|s| s.as_u64()).unwrap_or(0);
-
-                    let num_records = add.get("numRecords").and_then(|n| n.as_u64()).unwrap_or(0);
-
-                    let mut min_values = HashMap::new();
-                    let mut max_values = HashMap::new();
-                    let mut null_counts = HashMap::new();
-
-                    // Parse stats JSON (might be a string or object)
-                    if let Some(stats) = add.get("stats") {
-                        let stats_obj = if let Some(stats_str) = stats.as_str() {
-                            // Stats is a JSON string, parse it
-                            serde_json::from_str::<serde_json::Value>(stats_str).ok()
-                        } else {
-                            // Stats is already an object
-                            Some(stats.clone())
-                        };
-
-                        if let Some(stats_json) = stats_obj {
-                            // Extract minValues
-                            if let Some(min_vals) = stats_json.get("minValues") {
-                                if let Some(min_obj) = min_vals.as_object() {
-                                    for (col_name, val) in min_obj {
-                                        min_values.insert(col_name.clone(), val.clone());
-                                    }
-                                }
-                            }
-
-                            // Extract maxValues
-                            if let Some(max_vals) = stats_json.get("maxValues") {
-                                if let Some(max_obj) = max_vals.as_object() {
-                                    for (col_name, val) in max_obj {
-                                        max_values.insert(col_name.clone(), val.clone());
-                                    }
-                                }
-                            }
-
-                            // Extract nullCount
-                            if let Some(null_cnts) = stats_json.get("nullCount") {
-                                if let Some(null_obj) = null_cnts.as_object() {
-                                    for (col_name, val) in null_obj {
-                                        if let Some(count) = val.as_u64() {
-                                            null_counts.insert(col_name.clone(), count);
-                                        }
-                                    }
-                                }
-                            }
-                        }
-                    }
-
-                    let file_stats = FileStats {
-                        path: path.clone(),
-                        size_bytes,
-                        min_values,
-                        max_values,
-                        null_counts,
-                        num_records,
-                    };
-
-                    active_files.insert(path, file_stats);
-                }
+/// Extract per-file statistics from Delta Lake transaction log
+///
+/// Replays the log (checkpoints included) and extracts min/max statistics
+/// for each live Parquet file, under logical column names.
+pub fn get_file_statistics(base_path: &Path) -> Result<Vec<FileStats>> {
+    let log = super::log_replay::read_delta_log(base_path)?;
 
-                // Look for "remove" actions to handle deleted files
-                if let Some(remove) = json.get("remove") {
-                    if let Some(path) = remove.get("path").and_then(|p| p.as_str()) {
-                        active_files.remove(path);
-                    }
-                }
-            }
-        }
-    }
+    let files: Vec<FileStats> = log.files.into_iter().map(FileStats::from).collect();
 
-    debug!(
-        "Extracted statistics for {} active files",
-        active_files.len()
-    );
-    Ok(active_files.into_values().collect())
+    debug!("Extracted statistics for {} active files", files.len());
+    Ok(files)
 }
 
 /// Evaluate if a file can be skipped based on a predicate
 ///
 /// Returns true if the file can definitely be skipped (doesn't contain matching data).
 /// Returns false if the file might contain matching data (needs to be read).
+///
+/// `operator` is a comparison (`>`, `>=`, `<`, `<=`, `=`) or `IS NULL` /
+/// `IS NOT NULL`, for which `value` is ignored.
 pub fn can_skip_file(
     file_stats: &FileStats,
     column: &str,
     operator: &str,
     value: &serde_json::Value,
 ) -> bool {
+    can_skip_file_with_collation(file_stats, column, operator, value, Collation::Binary)
+}
+
+/// Like `can_skip_file`, for a query comparing strings under `collation`
+///
+/// Min/max statistics are binary, and under `Collation::CaseInsensitive` the
+/// values matching a string can sort anywhere in binary order (`'B' < 'a' <
+/// 'b'`), so string ranges never skip a file. An equality with an ASCII
+/// string skips files whose maximum sorts below the string in upper case,
+/// the lowest value that can equal it ignoring case. Nothing bounds the
+/// matches from above: some non-ASCII characters, like the Kelvin sign, lower
+/// to ASCII letters.
+pub fn can_skip_file_with_collation(
+    file_stats: &FileStats,
+    column: &str,
+    operator: &str,
+    value: &serde_json::Value,
+    collation: Collation,
+) -> bool {
+    let folded = match (collation, value) {
+        (Collation::CaseInsensitive, serde_json::Value::String(s)) => s,
+        _ => return skip_binary(file_stats, column, operator, value),
+    };
+    match operator {
+        "=" | "==" if folded.is_ascii() => {
+            let lowest = serde_json::Value::String(folded.to_ascii_uppercase());
+            skip_binary(file_stats, column, ">=", &lowest)
+        }
+        // Only a column that's entirely null rules the file out
+        ">" | ">=" | "<" | "<=" | "=" | "==" => {
+            skip_binary(file_stats, column, "IS NOT NULL", value)
+        }
+        _ => skip_binary(file_stats, column, operator, value),
+    }
+}
+
+/// `can_skip_file` under byte-wise string comparison
+fn skip_binary(
+    file_stats: &FileStats,
+    column: &str,
+    operator: &str,
+    value: &serde_json::Value,
+) -> bool {
+    // numRecords defaults to 0 when missing, so an unknown row count never proves anything
+    let null_count = file_stats.null_counts.get(column).copied();
+    let all_null = file_stats.num_records > 0 && null_count == Some(file_stats.num_records);
+
+    match operator {
+        "IS NULL" => return null_count == Some(0),
+        "IS NOT NULL" => return all_null,
+        ">" | ">=" | "<" | "<=" | "=" | "==" => {
+            // Comparisons with NULL are unknown and never match
+            if value.is_null() || all_null {
+                return true;
+            }
+        }
+        _ => return false,
+    }
+
     // Get min/max for the column
     let min_val = file_stats.min_values.get(column);
     let max_val = file_stats.max_values.get(column);
@@ -246,6 +226,12 @@ fn is_value_greater_than(a: &serde_json::Value, b: &serde_json::Value) -> bool {
 /// - age > 55
 /// - value >= 210 AND value <= 290
 /// - category >= 'W'
+/// - score IS NULL / score IS NOT NULL
+///
+/// Only a bare column compared with a lone literal is extracted. Terms with
+/// arithmetic or functions on either side, such as `price * quantity > 100`
+/// or `lower(name) = 'acme'`, say nothing the statistics can check, so they
+/// are left out and the files they'd filter are read and evaluated row by row.
 pub fn extract_predicates(sql: &str) -> Vec<(String, String, serde_json::Value)> {
     let mut predicates = Vec::new();
 
@@ -260,32 +246,40 @@ pub fn extract_predicates(sql: &str) -> Vec<(String, String, serde_json::Value)>
         for part in parts {
             let part = part.trim();
 
+            // Nullness tests: column IS [NOT] NULL
+            let tokens: Vec<String> = part
+                .split_whitespace()
+                .take(4)
+                .map(|t| t.to_uppercase())
+                .collect();
+            if tokens.len() >= 3 && tokens[1] == "IS" {
+                let (operator, words) =
+                    match (tokens[2].as_str(), tokens.get(3).map(String::as_str)) {
+                        ("NULL", _) => ("IS NULL", 3),
+                        ("NOT", Some("NULL")) => ("IS NOT NULL", 4),
+                        _ => continue,
+                    };
+                let column = part.split_whitespace().next().unwrap_or_default();
+                if is_column_name(column) && ends_term(skip_words(part, words)) {
+                    predicates.push((
+                        column.to_string(),
+                        operator.to_string(),
+                        serde_json::Value::Null,
+                    ));
+                }
+                continue;
+            }
+
             // Try to match: column operator value
             // Support: >, >=, <, <=, =
             for op in &[">=", "<=", ">", "<", "="] {
                 if let Some(op_idx) = part.find(op) {
                     let column = part[..op_idx].trim().to_string();
-                    let value_str = part[op_idx + op.len()..].trim();
-
-                    // Remove ORDER BY, LIMIT, etc.
-                    let value_str = value_str.split_whitespace().next().unwrap_or(value_str);
-
-                    // Parse value (int, float, or string)
-                    let value = if value_str.starts_with('\'') || value_str.starts_with('"') {
-                        // String value
-                        let cleaned = value_str.trim_matches('\'').trim_matches('"');
-                        serde_json::Value::String(cleaned.to_string())
-                    } else if let Ok(i) = value_str.parse::<i64>() {
-                        // Integer value
-                        serde_json::json!(i)
-                    } else if let Ok(f) = value_str.parse::<f64>() {
-                        // Float value
-                        serde_json::json!(f)
-                    } else {
-                        continue;
-                    };
-
-                    predicates.push((column, op.to_string(), value));
+                    if let Some(value) = parse_literal(part[op_idx + op.len()..].trim()) {
+                        if is_column_name(&column) {
+                            predicates.push((column, op.to_string(), value));
+                        }
+                    }
                     break; // Found operator, don't try others
                 }
             }
@@ -296,6 +290,238 @@ pub fn extract_predicates(sql: &str) -> Vec<(String, String, serde_json::Value)>
     predicates
 }
 
+/// Whether `s` is a bare column name rather than an expression
+fn is_column_name(s: &str) -> bool {
+    !s.is_empty()
+        && s.chars().all(|c| c.is_alphanumeric() || c == '_')
+        && !s.starts_with(|c: char| c.is_ascii_digit())
+}
+
+/// Parse the right-hand side of a comparison if it's a single literal
+///
+/// The literal may be followed by the rest of the statement (`ORDER BY`,
+/// `LIMIT`, ...) but not by more of an expression, as in `x > 100 - 50`.
+fn parse_literal(text: &str) -> Option<serde_json::Value> {
+    let (value, rest) = match text.chars().next()? {
+        quote @ ('\'' | '"') => {
+            // String value
+            let end = text[1..].find(quote)? + 1;
+            (
+                serde_json::Value::String(text[1..end].to_string()),
+                &text[end + 1..],
+            )
+        }
+        _ => {
+            let end = text.find(char::is_whitespace).unwrap_or(text.len());
+            let (token, rest) = text.split_at(end);
+            let token = token.trim_end_matches(';');
+            let value = if let Ok(i) = token.parse::<i64>() {
+                // Integer value
+                serde_json::json!(i)
+            } else {
+                // Float value
+                serde_json::json!(token.parse::<f64>().ok().filter(|f| f.is_finite())?)
+            };
+            (value, rest)
+        }
+    };
+
+    ends_term(rest).then_some(value)
+}
+
+/// Whether `rest` ends the term before it: nothing but the rest of the
+/// statement (`ORDER BY`, `LIMIT`, ...) follows, rather than more of an
+/// expression or an `OR`
+fn ends_term(rest: &str) -> bool {
+    let next = rest.split_whitespace().next().unwrap_or_default();
+    next.is_empty()
+        || next == ";"
+        || ["ORDER", "GROUP", "HAVING", "LIMIT", "OFFSET"]
+            .iter()
+            .any(|keyword| next.eq_ignore_ascii_case(keyword))
+}
+
+/// `text` after its first `words` whitespace-separated words
+fn skip_words(text: &str, words: usize) -> &str {
+    let mut rest = text;
+    for _ in 0..words {
+        rest = rest.trim_start();
+        rest = &rest[rest.find(char::is_whitespace).unwrap_or(rest.len())..];
+    }
+    rest
+}
+
+/// How much of a file a predicate matches, judged from its statistics
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FileMatch {
+    /// No row can match: the file is left alone
+    None,
+    /// Some rows may match: the file has to be read
+    Partial,
+    /// Every row matches: the file can be dropped without reading it
+    All,
+}
+
+/// Classify `file_stats` against a conjunction of predicates
+///
+/// `All` is only claimed when the statistics prove it: the row count is
+/// known, and for each comparison the column has no nulls and its whole
+/// numeric min/max range satisfies the predicate. String statistics may be
+/// truncated, so they never prove a full match.
+pub fn file_match(
+    file_stats: &FileStats,
+    predicates: &[(String, String, serde_json::Value)],
+) -> FileMatch {
+    if predicates
+        .iter()
+        .any(|(column, op, value)| can_skip_file(file_stats, column, op, value))
+    {
+        return FileMatch::None;
+    }
+    if file_stats.has_stats
+        && file_stats.num_records > 0
+        && !predicates.is_empty()
+        && predicates
+            .iter()
+            .all(|(column, op, value)| matches_all_rows(file_stats, column, op, value))
+    {
+        return FileMatch::All;
+    }
+    FileMatch::Partial
+}
+
+/// Whether every row of the file satisfies `column operator value`
+fn matches_all_rows(
+    file_stats: &FileStats,
+    column: &str,
+    operator: &str,
+    value: &serde_json::Value,
+) -> bool {
+    let null_count = file_stats.null_counts.get(column).copied();
+    match operator {
+        "IS NULL" => return null_count == Some(file_stats.num_records),
+        "IS NOT NULL" => return null_count == Some(0),
+        _ if null_count != Some(0) => return false,
+        _ => {}
+    }
+
+    let (Some(min), Some(max)) = (
+        file_stats.min_values.get(column),
+        file_stats.max_values.get(column),
+    ) else {
+        return false;
+    };
+    let (Some(min), Some(max)) = (compare_numbers(min, value), compare_numbers(max, value)) else {
+        return false;
+    };
+    use std::cmp::Ordering::*;
+    match operator {
+        ">" => min == Greater,
+        ">=" => min != Less,
+        "<" => max == Less,
+        "<=" => max != Greater,
+        "=" | "==" => min == Equal && max == Equal,
+        _ => false,
+    }
+}
+
+/// Order two JSON numbers, or `None` if either isn't one
+fn compare_numbers(a: &serde_json::Value, b: &serde_json::Value) -> Option<std::cmp::Ordering> {
+    let (a, b) = (a.as_number()?, b.as_number()?);
+    match (a.as_i64(), b.as_i64()) {
+        (Some(a), Some(b)) => Some(a.cmp(&b)),
+        _ => a.as_f64()?.partial_cmp(&b.as_f64()?),
+    }
+}
+
+/// Parse a `WHERE` clause that is a plain conjunction of simple predicates
+///
+/// Unlike `extract_predicates`, which only needs predicates that are implied
+/// by the query, this returns `None` unless the predicates are exactly
+/// equivalent to the clause: every `AND`-separated term must be a column
+/// compared with a number (`>`, `>=`, `<`, `<=`, `=`) or `IS [NOT] NULL`.
+/// `OR`, parentheses and any other expression are rejected, and so are
+/// string literals, whose statistics may be truncated or formatted
+/// differently from the SQL literal.
+pub fn parse_conjunction(where_clause: &str) -> Option<Vec<(String, String, serde_json::Value)>> {
+    if where_clause.contains(['(', ')', '"', '\'']) {
+        return None;
+    }
+    let mut terms = vec![Vec::new()];
+    for token in where_clause.split_whitespace() {
+        match token.to_uppercase().as_str() {
+            "AND" => terms.push(Vec::new()),
+            "OR" | "NOT" | "BETWEEN" | "IN" | "LIKE" => {
+                // NOT is only allowed inside IS NOT NULL
+                let last = terms.last()?;
+                if token.eq_ignore_ascii_case("NOT") && last.len() == 2 && last[1] == "IS" {
+                    terms.last_mut()?.push("NOT".to_string());
+                } else {
+                    return None;
+                }
+            }
+            upper => {
+                let is_keyword = matches!(upper, "IS" | "NULL");
+                terms.last_mut()?.push(if is_keyword {
+                    upper.to_string()
+                } else {
+                    token.to_string()
+                });
+            }
+        }
+    }
+
+    terms
+        .into_iter()
+        .map(|tokens| parse_term(&tokens.join(" ")))
+        .collect()
+}
+
+/// Parse one term of a conjunction
+fn parse_term(term: &str) -> Option<(String, String, serde_json::Value)> {
+    if let Some(column) = term.strip_suffix(" IS NOT NULL") {
+        return is_column_name(column).then(|| {
+            (
+                column.to_string(),
+                "IS NOT NULL".to_string(),
+                serde_json::Value::Null,
+            )
+        });
+    }
+    if let Some(column) = term.strip_suffix(" IS NULL") {
+        return is_column_name(column).then(|| {
+            (
+                column.to_string(),
+                "IS NULL".to_string(),
+                serde_json::Value::Null,
+            )
+        });
+    }
+
+    let op_idx = term.find(['<', '>', '=', '!'])?;
+    let op_len = term[op_idx..]
+        .chars()
+        .take_while(|c| matches!(c, '<' | '>' | '=' | '!'))
+        .count();
+    let op = &term[op_idx..op_idx + op_len];
+    if !matches!(op, ">" | ">=" | "<" | "<=" | "=") {
+        return None;
+    }
+    let column = term[..op_idx].trim();
+    let literal = term[op_idx + op_len..].trim();
+    if !is_column_name(column) {
+        return None;
+    }
+
+    let value = if let Ok(i) = literal.parse::<i64>() {
+        serde_json::json!(i)
+    } else {
+        let f = literal.parse::<f64>().ok().filter(|f| f.is_finite())?;
+        serde_json::json!(f)
+    };
+    Some((column.to_string(), op.to_string(), value))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -326,4 +552,234 @@ mod tests {
         assert_eq!(predicates[0].1, ">=");
         assert_eq!(predicates[0].2, serde_json::Value::String("W".to_string()));
     }
+
+    #[test]
+    fn test_null_predicate_extraction() {
+        let sql = "SELECT * FROM data WHERE score IS NULL AND name is not null ORDER BY id";
+        let predicates = extract_predicates(sql);
+        assert_eq!(predicates.len(), 2);
+        assert_eq!(predicates[0].0, "score");
+        assert_eq!(predicates[0].1, "IS NULL");
+        assert_eq!(predicates[1].0, "name");
+        assert_eq!(predicates[1].1, "IS NOT NULL");
+    }
+
+    #[test]
+    fn test_expressions_are_not_extracted() {
+        // Nothing the statistics can check, so every candidate file is read
+        for sql in [
+            "SELECT * FROM data WHERE price * quantity > 100",
+            "SELECT * FROM data WHERE lower(name) = 'acme'",
+            "SELECT * FROM data WHERE abs(score) IS NULL",
+            "SELECT * FROM data WHERE score > 100 - 50",
+            "SELECT * FROM data WHERE score > 5 OR score < 2",
+            "SELECT * FROM data WHERE payload->>'status' = 'active'",
+            "SELECT * FROM data WHERE payload ->> 'n' >= 5",
+            "SELECT * FROM data WHERE payload->'user'->>'name' IS NULL",
+            "SELECT * FROM data WHERE score IS NULL OR id = 1",
+            "SELECT * FROM data WHERE score IS NOT NULL OR id = 1",
+            "SELECT * FROM data WHERE score IS NULL OR score > 5",
+            "SELECT * FROM data WHERE json_extract_text(payload, 'status') = 'active'",
+        ] {
+            assert!(extract_predicates(sql).is_empty(), "{}", sql);
+        }
+
+        // Plain terms next to expressions still are
+        let predicates = extract_predicates(
+            "SELECT * FROM data WHERE upper(name) = 'ACME' AND id >= 10 ORDER BY id LIMIT 5",
+        );
+        assert_eq!(
+            predicates,
+            vec![("id".to_string(), ">=".to_string(), serde_json::json!(10))]
+        );
+        assert_eq!(
+            extract_predicates("SELECT * FROM data WHERE city = 'New York';")[0].2,
+            serde_json::json!("New York")
+        );
+    }
+
+    fn file_with_nulls(null_count: Option<u64>, num_records: u64) -> FileStats {
+        let mut stats = FileStats {
+            path: "part-0.parquet".to_string(),
+            size_bytes: 100,
+            min_values: HashMap::new(),
+            max_values: HashMap::new(),
+            null_counts: HashMap::new(),
+            num_records,
+            has_stats: true,
+        };
+        if let Some(count) = null_count {
+            stats.null_counts.insert("score".to_string(), count);
+        }
+        // Min/max only exist when at least one value is non-null
+        if null_count != Some(num_records) {
+            stats
+                .min_values
+                .insert("score".to_string(), serde_json::json!(10));
+            stats
+                .max_values
+                .insert("score".to_string(), serde_json::json!(20));
+        }
+        stats
+    }
+
+    #[test]
+    fn test_null_pruning() {
+        let null = serde_json::Value::Null;
+        let no_nulls = file_with_nulls(Some(0), 10);
+        let some_nulls = file_with_nulls(Some(3), 10);
+        let all_nulls = file_with_nulls(Some(10), 10);
+        let unknown = file_with_nulls(None, 10);
+
+        assert!(can_skip_file(&no_nulls, "score", "IS NULL", &null));
+        assert!(!can_skip_file(&some_nulls, "score", "IS NULL", &null));
+        assert!(!can_skip_file(&all_nulls, "score", "IS NULL", &null));
+        assert!(!can_skip_file(&unknown, "score", "IS NULL", &null));
+
+        assert!(!can_skip_file(&no_nulls, "score", "IS NOT NULL", &null));
+        assert!(!can_skip_file(&some_nulls, "score", "IS NOT NULL", &null));
+        assert!(can_skip_file(&all_nulls, "score", "IS NOT NULL", &null));
+        assert!(!can_skip_file(&unknown, "score", "IS NOT NULL", &null));
+    }
+
+    #[test]
+    fn test_comparison_never_matches_null() {
+        let value = serde_json::json!(15);
+        let all_nulls = file_with_nulls(Some(10), 10);
+        let some_nulls = file_with_nulls(Some(3), 10);
+
+        // A column holding only nulls cannot satisfy any comparison
+        assert!(can_skip_file(&all_nulls, "score", "=", &value));
+        assert!(can_skip_file(&all_nulls, "score", ">", &value));
+        assert!(!can_skip_file(&some_nulls, "score", "=", &value));
+
+        // col = NULL is unknown for every row
+        assert!(can_skip_file(
+            &some_nulls,
+            "score",
+            "=",
+            &serde_json::Value::Null
+        ));
+
+        // Missing row count proves nothing
+        let unknown_rows = file_with_nulls(Some(0), 0);
+        assert!(!can_skip_file(
+            &unknown_rows,
+            "score",
+            "IS NOT NULL",
+            &value
+        ));
+    }
+
+    fn numeric_file(min: i64, max: i64, nulls: u64) -> FileStats {
+        FileStats {
+            path: "part-0.parquet".to_string(),
+            size_bytes: 100,
+            min_values: HashMap::from([("id".to_string(), serde_json::json!(min))]),
+            max_values: HashMap::from([("id".to_string(), serde_json::json!(max))]),
+            null_counts: HashMap::from([("id".to_string(), nulls)]),
+            num_records: 10,
+            has_stats: true,
+        }
+    }
+
+    fn string_file(min: &str, max: &str) -> FileStats {
+        FileStats {
+            path: "part-0.parquet".to_string(),
+            size_bytes: 100,
+            min_values: HashMap::from([("name".to_string(), serde_json::json!(min))]),
+            max_values: HashMap::from([("name".to_string(), serde_json::json!(max))]),
+            null_counts: HashMap::from([("name".to_string(), 0)]),
+            num_records: 10,
+            has_stats: true,
+        }
+    }
+
+    #[test]
+    fn test_case_insensitive_skipping() {
+        let ci = |file: &FileStats, op: &str, value: &str| {
+            let value = serde_json::json!(value);
+            can_skip_file_with_collation(file, "name", op, &value, Collation::CaseInsensitive)
+        };
+        let upper = string_file("ALICE", "CAROL");
+        let lower = string_file("alice", "carol");
+
+        // Binary skipping would wrongly rule these out
+        let alice = serde_json::json!("alice");
+        assert!(can_skip_file(&upper, "name", "=", &alice));
+        assert!(!ci(&upper, "=", "alice"));
+        assert!(!ci(&lower, "=", "ALICE"));
+        assert!(!ci(&lower, "=", "Zed"));
+        assert!(!ci(&upper, ">", "dave"));
+        assert!(!ci(&lower, "<", "Bob"));
+
+        // Every case variant of "zed" sorts at or after "ZED"
+        assert!(ci(&string_file("ABE", "YVES"), "=", "zed"));
+        assert!(!ci(&string_file("ABE", "ZED"), "=", "zed"));
+
+        // Non-ASCII values prove nothing
+        assert!(!ci(&string_file("ABE", "BOB"), "=", "émile"));
+
+        // Numbers are unaffected
+        let ids = numeric_file(0, 9, 0);
+        let value = serde_json::json!(20);
+        assert!(can_skip_file_with_collation(
+            &ids,
+            "id",
+            ">",
+            &value,
+            Collation::CaseInsensitive
+        ));
+    }
+
+    #[test]
+    fn test_file_match_classification() {
+        let preds = parse_conjunction("id >= 10 AND id < 20").unwrap();
+        assert_eq!(file_match(&numeric_file(0, 9, 0), &preds), FileMatch::None);
+        assert_eq!(file_match(&numeric_file(10, 19, 0), &preds), FileMatch::All);
+        assert_eq!(
+            file_match(&numeric_file(15, 25, 0), &preds),
+            FileMatch::Partial
+        );
+
+        // Nulls never satisfy a comparison, so they block a full match
+        assert_eq!(
+            file_match(&numeric_file(10, 19, 1), &preds),
+            FileMatch::Partial
+        );
+
+        // Without statistics nothing is proven
+        let mut no_stats = numeric_file(10, 19, 0);
+        no_stats.has_stats = false;
+        assert_eq!(file_match(&no_stats, &preds), FileMatch::Partial);
+    }
+
+    #[test]
+    fn test_parse_conjunction() {
+        assert_eq!(
+            parse_conjunction("id < 5 and name IS NOT NULL and score>=1.5").unwrap(),
+            vec![
+                ("id".to_string(), "<".to_string(), serde_json::json!(5)),
+                (
+                    "name".to_string(),
+                    "IS NOT NULL".to_string(),
+                    serde_json::Value::Null
+                ),
+                (
+                    "score".to_string(),
+                    ">=".to_string(),
+                    serde_json::json!(1.5)
+                ),
+            ]
+        );
+
+        // Anything that isn't exactly a conjunction of simple terms
+        assert!(parse_conjunction("id < 5 OR id > 10").is_none());
+        assert!(parse_conjunction("(id < 5)").is_none());
+        assert!(parse_conjunction("id != 5").is_none());
+        assert!(parse_conjunction("id + 1 < 5").is_none());
+        assert!(parse_conjunction("NOT id < 5").is_none());
+        assert!(parse_conjunction("id < other").is_none());
+        assert!(parse_conjunction("name = 'a AND b'").is_none());
+    }
 }
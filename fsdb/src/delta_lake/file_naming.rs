@@ -0,0 +1,266 @@
+//! Names for the data files inserts write
+//!
+//! delta-rs names each Parquet file `part-<n>-<uuid>-c000.<codec>.parquet`,
+//! which says nothing about what the file holds. A `FileNaming` template
+//! renames the files an insert writes before they're committed, so the Delta
+//! log, the table directory and the NFS listing all carry the new name.
+//!
+//! Templates are made of letters, digits, `-`, `_` and `.` plus these
+//! placeholders:
+//!
+//! - `{version}`: the table version the write commits, zero-padded to 8 digits
+//! - `{timestamp}`: UTC write time, as `20240101T120000123Z`
+//! - `{partition}`: the file's partition values, as `region-us_day-1`
+//!   (empty for unpartitioned tables)
+//! - `{file}`: the file's index within the write, zero-padded to 4 digits
+//!
+//! `.parquet` is appended. A name that's already taken, by a file on disk or
+//! another file of the same write, gets `-1`, `-2`, ... before the extension.
+
+use crate::{Error, Result};
+use deltalake::kernel::Add;
+use std::collections::HashSet;
+use std::path::Path;
+
+const PLACEHOLDERS: [&str; 4] = ["version", "timestamp", "partition", "file"];
+
+/// Template for the names of the data files inserts write
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileNaming {
+    template: String,
+}
+
+impl FileNaming {
+    /// Check `template` and build a naming from it
+    ///
+    /// Fails with `Error::InvalidOperation` on unknown placeholders,
+    /// characters other than letters, digits, `-`, `_` and `.`, or a leading
+    /// `_` or `.`, which Delta readers and VACUUM treat as hidden.
+    pub fn new(template: impl Into<String>) -> Result<Self> {
+        let template = template.into();
+        let invalid = |reason: String| {
+            Error::InvalidOperation(format!(
+                "Invalid file name template '{}': {}",
+                template, reason
+            ))
+        };
+        if template.is_empty() {
+            return Err(invalid("it's empty".to_string()));
+        }
+        if template.starts_with(['_', '.']) {
+            return Err(invalid(
+                "names starting with '_' or '.' are hidden".to_string(),
+            ));
+        }
+        let mut rest = template.as_str();
+        while let Some(open) = rest.find('{') {
+            check_literal(&rest[..open]).map_err(&invalid)?;
+            let close = rest[open..]
+                .find('}')
+                .ok_or_else(|| invalid("unclosed '{'".to_string()))?;
+            let name = &rest[open + 1..open + close];
+            if !PLACEHOLDERS.contains(&name) {
+                return Err(invalid(format!(
+                    "unknown placeholder {{{}}}, expected one of {}",
+                    name,
+                    PLACEHOLDERS.map(|p| format!("{{{}}}", p)).join(", ")
+                )));
+            }
+            rest = &rest[open + close + 1..];
+        }
+        check_literal(rest).map_err(&invalid)?;
+        Ok(Self { template })
+    }
+
+    /// The template, as given
+    pub fn template(&self) -> &str {
+        &self.template
+    }
+
+    /// File name, without extension or disambiguator, for one file
+    fn render(&self, version: i64, timestamp: &str, partition: &str, file: usize) -> String {
+        let name = self
+            .template
+            .replace("{version}", &format!("{:08}", version))
+            .replace("{timestamp}", timestamp)
+            .replace("{partition}", partition)
+            .replace("{file}", &format!("{:04}", file));
+        // Only a leading {partition} can leave these
+        match name.chars().next() {
+            None => "part".to_string(),
+            Some('_' | '.') => format!("part{}", name),
+            Some(_) => name,
+        }
+    }
+
+    /// Rename the files `adds` describe, written under `base_path` and not
+    /// yet committed, and point the actions at the new names
+    ///
+    /// Each file keeps its partition directory. Renames use a hard link, so
+    /// a name another writer takes concurrently is never overwritten.
+    pub(crate) fn rename(&self, base_path: &Path, adds: &mut [Add], version: i64) -> Result<()> {
+        let timestamp = chrono::Utc::now().format("%Y%m%dT%H%M%S%3fZ").to_string();
+        let mut taken = HashSet::new();
+        for (index, add) in adds.iter_mut().enumerate() {
+            let (dir, old_name) = match add.path.rsplit_once('/') {
+                Some((dir, name)) => (Some(dir), name),
+                None => (None, add.path.as_str()),
+            };
+            let disk_dir = match dir {
+                Some(dir) => base_path.join(crate::metadata::snapshot::percent_decode(dir)?),
+                None => base_path.to_path_buf(),
+            };
+            let old_path = disk_dir.join(crate::metadata::snapshot::percent_decode(old_name)?);
+
+            let mut partition: Vec<_> = add.partition_values.iter().collect();
+            partition.sort();
+            let partition = partition
+                .into_iter()
+                .map(|(column, value)| {
+                    format!(
+                        "{}-{}",
+                        sanitize(column),
+                        value.as_deref().map_or("null".to_string(), sanitize)
+                    )
+                })
+                .collect::<Vec<_>>()
+                .join("_");
+            let stem = self.render(version, &timestamp, &partition, index);
+
+            let mut attempt = 0;
+            let name = loop {
+                let name = match attempt {
+                    0 => format!("{}.parquet", stem),
+                    n => format!("{}-{}.parquet", stem, n),
+                };
+                attempt += 1;
+                if !taken.insert(disk_dir.join(&name)) {
+                    continue;
+                }
+                match std::fs::hard_link(&old_path, disk_dir.join(&name)) {
+                    Ok(()) => break name,
+                    Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => continue,
+                    Err(e) => return Err(e.into()),
+                }
+            };
+            std::fs::remove_file(&old_path)?;
+            add.path = match dir {
+                Some(dir) => format!("{}/{}", dir, name),
+                None => name,
+            };
+        }
+        Ok(())
+    }
+}
+
+/// Fail on characters that aren't allowed outside placeholders
+fn check_literal(text: &str) -> std::result::Result<(), String> {
+    match text.chars().find(|c| !allowed(*c)) {
+        Some(c) => Err(format!("'{}' isn't allowed in file names", c)),
+        None => Ok(()),
+    }
+}
+
+fn allowed(c: char) -> bool {
+    c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.')
+}
+
+/// A partition column or value with disallowed characters replaced by `_`
+fn sanitize(text: &str) -> String {
+    text.chars()
+        .map(|c| if allowed(c) { c } else { '_' })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use tempfile::TempDir;
+
+    fn add(path: &str, partition: &[(&str, Option<&str>)]) -> Add {
+        Add {
+            path: path.to_string(),
+            partition_values: partition
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.map(str::to_string)))
+                .collect::<HashMap<_, _>>(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_template_validation() {
+        assert!(FileNaming::new("orders-{version}-{file}").is_ok());
+        assert!(FileNaming::new("{partition}_{timestamp}").is_ok());
+        for bad in ["", "_hidden", ".hidden", "a/b", "x{seq}", "x{file", "a b"] {
+            assert!(FileNaming::new(bad).is_err(), "{}", bad);
+        }
+        let err = FileNaming::new("x{seq}").unwrap_err().to_string();
+        assert!(err.contains("{version}"), "{}", err);
+    }
+
+    #[test]
+    fn test_rename_follows_template() {
+        let dir = TempDir::new().unwrap();
+        std::fs::create_dir(dir.path().join("region=us east")).unwrap();
+        std::fs::write(dir.path().join("part-a.parquet"), b"a").unwrap();
+        std::fs::write(dir.path().join("region=us east/part-b.parquet"), b"b").unwrap();
+
+        let naming = FileNaming::new("orders-{version}-{partition}-{file}").unwrap();
+        let mut adds = vec![
+            add("part-a.parquet", &[]),
+            add(
+                "region=us%20east/part-b.parquet",
+                &[("region", Some("us east"))],
+            ),
+        ];
+        naming.rename(dir.path(), &mut adds, 12).unwrap();
+
+        assert_eq!(adds[0].path, "orders-00000012--0000.parquet");
+        assert_eq!(
+            adds[1].path,
+            "region=us%20east/orders-00000012-region-us_east-0001.parquet"
+        );
+        assert_eq!(
+            std::fs::read(dir.path().join("orders-00000012--0000.parquet")).unwrap(),
+            b"a"
+        );
+        assert!(dir
+            .path()
+            .join("region=us east/orders-00000012-region-us_east-0001.parquet")
+            .exists());
+        assert!(!dir.path().join("part-a.parquet").exists());
+    }
+
+    #[test]
+    fn test_colliding_names_are_disambiguated() {
+        let dir = TempDir::new().unwrap();
+        std::fs::write(dir.path().join("daily.parquet"), b"old").unwrap();
+        for name in ["a", "b", "c"] {
+            std::fs::write(dir.path().join(format!("{}.parquet", name)), name).unwrap();
+        }
+
+        let naming = FileNaming::new("daily").unwrap();
+        let mut adds = vec![
+            add("a.parquet", &[]),
+            add("b.parquet", &[]),
+            add("c.parquet", &[]),
+        ];
+        naming.rename(dir.path(), &mut adds, 1).unwrap();
+
+        let paths: Vec<_> = adds.iter().map(|a| a.path.as_str()).collect();
+        assert_eq!(
+            paths,
+            vec!["daily-1.parquet", "daily-2.parquet", "daily-3.parquet"]
+        );
+        assert_eq!(
+            std::fs::read(dir.path().join("daily.parquet")).unwrap(),
+            b"old"
+        );
+        assert_eq!(
+            std::fs::read(dir.path().join("daily-3.parquet")).unwrap(),
+            b"c"
+        );
+    }
+}
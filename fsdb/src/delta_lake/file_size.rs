@@ -0,0 +1,122 @@
+//! Splitting writes into files of a target size
+//!
+//! With a target file size set, an insert or a buffer flush is cut into row
+//! slices that should each encode to about the target, and each slice is
+//! written to a file of its own. A slice's size is predicted from a sample of
+//! the batch encoded the way delta-rs writes data files (Parquet, Snappy), so
+//! it reflects the data's real compression rather than its size in memory.
+//! Every slice holds at least one row: a row larger than the target is
+//! written alone, in a file over the target.
+
+use crate::Result;
+use arrow::array::RecordBatch;
+use parquet::arrow::ArrowWriter;
+use parquet::basic::Compression;
+use parquet::file::properties::WriterProperties;
+use tracing::debug;
+
+/// Rows encoded to estimate the bytes each row takes in a file
+const SAMPLE_ROWS: usize = 8192;
+
+/// Split `batch` into slices of about `target_bytes` encoded each
+///
+/// A batch expected to fit in one file is returned whole.
+pub fn split_to_target_size(batch: &RecordBatch, target_bytes: u64) -> Result<Vec<RecordBatch>> {
+    let rows = batch.num_rows();
+    if rows == 0 {
+        return Ok(vec![batch.clone()]);
+    }
+
+    let bytes_per_row = encoded_bytes_per_row(batch)?;
+    let rows_per_file = ((target_bytes as f64 / bytes_per_row) as usize).clamp(1, rows);
+    if rows_per_file == rows {
+        return Ok(vec![batch.clone()]);
+    }
+
+    debug!(
+        "Splitting {} rows into files of {} rows (~{:.1} bytes per row)",
+        rows, rows_per_file, bytes_per_row
+    );
+    Ok((0..rows)
+        .step_by(rows_per_file)
+        .map(|offset| batch.slice(offset, rows_per_file.min(rows - offset)))
+        .collect())
+}
+
+/// Average bytes per row of a sample of `batch` written as a Parquet file
+///
+/// The sample is spread across the batch, and the file's footer is counted
+/// against its rows like a data file's would be.
+fn encoded_bytes_per_row(batch: &RecordBatch) -> Result<f64> {
+    let rows = batch.num_rows();
+    let sample = if rows <= SAMPLE_ROWS {
+        batch.clone()
+    } else {
+        let stride = rows / SAMPLE_ROWS;
+        let indices = arrow::array::UInt64Array::from_iter_values(
+            (0..SAMPLE_ROWS).map(|i| (i * stride) as u64),
+        );
+        arrow::compute::take_record_batch(batch, &indices)?
+    };
+
+    let properties = WriterProperties::builder()
+        .set_compression(Compression::SNAPPY)
+        .build();
+    let mut buffer = Vec::new();
+    let mut writer = ArrowWriter::try_new(&mut buffer, sample.schema(), Some(properties))?;
+    writer.write(&sample)?;
+    writer.close()?;
+    Ok(buffer.len() as f64 / sample.num_rows() as f64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow::array::{ArrayRef, Int64Array, StringArray};
+    use arrow::datatypes::{DataType, Field, Schema};
+    use std::sync::Arc;
+
+    fn batch(rows: usize, value_len: usize) -> RecordBatch {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("id", DataType::Int64, false),
+            Field::new("value", DataType::Utf8, false),
+        ]));
+        RecordBatch::try_new(
+            schema,
+            vec![
+                Arc::new(Int64Array::from_iter_values(0..rows as i64)) as ArrayRef,
+                Arc::new(StringArray::from_iter_values((0..rows).map(|i| {
+                    // Scrambled letters, so values don't compress away
+                    (0..value_len)
+                        .map(|j| (b'a' + (((i * 7919 + j) * 2654435761) >> 7) as u8 % 26) as char)
+                        .collect::<String>()
+                }))) as ArrayRef,
+            ],
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_split_keeps_every_row_in_order() {
+        let batch = batch(10_000, 32);
+        let slices = split_to_target_size(&batch, 16 * 1024).unwrap();
+        assert!(slices.len() > 1);
+        assert_eq!(slices.iter().map(|s| s.num_rows()).sum::<usize>(), 10_000);
+        let rejoined = arrow::compute::concat_batches(&batch.schema(), &slices).unwrap();
+        assert_eq!(rejoined, batch);
+    }
+
+    #[test]
+    fn test_small_batch_and_oversized_rows() {
+        let small = batch(10, 8);
+        assert_eq!(split_to_target_size(&small, 1024 * 1024).unwrap().len(), 1);
+
+        // Rows larger than the target still get one file each
+        let wide = batch(3, 4096);
+        let slices = split_to_target_size(&wide, 1024).unwrap();
+        assert_eq!(
+            slices.iter().map(|s| s.num_rows()).collect::<Vec<_>>(),
+            vec![1, 1, 1]
+        );
+    }
+}
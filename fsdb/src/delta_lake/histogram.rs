@@ -0,0 +1,443 @@
+//! Equi-depth histograms for selectivity estimation
+//!
+//! Per-file min/max statistics say where a column's values lie but not how
+//! they are spread, so a range predicate on skewed data is badly misjudged:
+//! with most values in `[0, 10]` and a long tail to 10,000, `value < 100`
+//! looks like 1% of the table under a uniform assumption when it is closer
+//! to 90%. An equi-depth histogram splits the sorted values into buckets
+//! holding roughly the same number of rows, so dense ranges get narrow
+//! buckets and a value frequent enough to fill a bucket gets one of its own.
+//!
+//! Histograms are kept for the numeric columns a table enables them on, in
+//! `_metadata/histograms.json`. OPTIMIZE rebuilds them from the table; an
+//! insert merges a histogram of the new rows into the stored one, which is
+//! approximate but keeps estimates usable between rebuilds. Deletes and
+//! updates are only reflected at the next rebuild.
+
+use crate::{Error, Result};
+use arrow::array::{Array, ArrayRef, Float64Array};
+use arrow::datatypes::DataType;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+/// Buckets per histogram unless the table asks for a different number
+pub const DEFAULT_BUCKETS: usize = 32;
+
+/// One bucket: `count` values in `[lower, upper]`, `distinct` of them different
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct HistogramBucket {
+    pub lower: f64,
+    pub upper: f64,
+    pub count: u64,
+    pub distinct: u64,
+}
+
+impl HistogramBucket {
+    /// Whether the bucket holds a single value
+    fn is_point(&self) -> bool {
+        self.lower == self.upper
+    }
+
+    /// Rows in this bucket equal to `value`, assuming distinct values share the rows evenly
+    fn rows_equal(&self, value: f64) -> f64 {
+        if value < self.lower || value > self.upper {
+            0.0
+        } else if self.is_point() {
+            self.count as f64
+        } else {
+            self.count as f64 / self.distinct.max(1) as f64
+        }
+    }
+
+    /// Rows in this bucket below `value`, interpolating linearly inside it
+    fn rows_below(&self, value: f64) -> f64 {
+        if value <= self.lower {
+            0.0
+        } else if value > self.upper {
+            self.count as f64
+        } else {
+            // value is inside (lower, upper], so the bucket isn't a point
+            let fraction = (value - self.lower) / (self.upper - self.lower);
+            (self.count as f64 * fraction).min(self.count as f64 - self.rows_equal(value))
+        }
+    }
+}
+
+/// Equi-depth histogram of one column
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct EquiDepthHistogram {
+    /// Buckets in ascending order, not overlapping
+    pub buckets: Vec<HistogramBucket>,
+    pub null_count: u64,
+}
+
+impl EquiDepthHistogram {
+    /// Build a histogram with at most `num_buckets` buckets from column values
+    ///
+    /// Equal values always land in the same bucket, so a bucket can end up
+    /// holding more than its share when one value is that frequent.
+    pub fn build(values: impl IntoIterator<Item = Option<f64>>, num_buckets: usize) -> Self {
+        let mut null_count = 0;
+        let mut sorted: Vec<f64> = values
+            .into_iter()
+            .filter_map(|v| {
+                if v.is_none_or(f64::is_nan) {
+                    null_count += 1;
+                }
+                v.filter(|v| !v.is_nan())
+            })
+            .collect();
+        sorted.sort_by(f64::total_cmp);
+
+        let mut buckets = Vec::new();
+        let mut start = 0;
+        while start < sorted.len() {
+            // Re-balance on what's left, so a large bucket doesn't starve the last ones
+            let remaining_buckets = num_buckets.max(1).saturating_sub(buckets.len()).max(1);
+            let target = (sorted.len() - start).div_ceil(remaining_buckets);
+            let mut end = (start + target).min(sorted.len());
+            while end < sorted.len() && sorted[end] == sorted[end - 1] {
+                end += 1;
+            }
+
+            let values = &sorted[start..end];
+            let distinct = 1 + values.windows(2).filter(|w| w[0] != w[1]).count() as u64;
+            buckets.push(HistogramBucket {
+                lower: values[0],
+                upper: values[values.len() - 1],
+                count: values.len() as u64,
+                distinct,
+            });
+            start = end;
+        }
+
+        Self {
+            buckets,
+            null_count,
+        }
+    }
+
+    /// Build a histogram from an Arrow column, if its type is numeric
+    pub fn from_array(array: &ArrayRef, num_buckets: usize) -> Result<Option<Self>> {
+        if !is_histogram_type(array.data_type()) {
+            return Ok(None);
+        }
+        let values = arrow::compute::cast(array, &DataType::Float64)?;
+        let values = values
+            .as_any()
+            .downcast_ref::<Float64Array>()
+            .ok_or_else(|| Error::Other("Cast to Float64 returned another type".to_string()))?;
+        Ok(Some(Self::build(values.iter(), num_buckets)))
+    }
+
+    /// A single bucket spreading `count` rows evenly over `[min, max]`
+    ///
+    /// This is all that min/max statistics can say about a column, used where
+    /// no histogram has been built.
+    pub fn uniform(min: f64, max: f64, count: u64, null_count: u64) -> Self {
+        let (lower, upper) = (min.min(max), min.max(max));
+        let mut distinct = count;
+        if lower.fract() == 0.0 && upper.fract() == 0.0 {
+            distinct = distinct.min((upper - lower) as u64 + 1);
+        }
+        Self {
+            buckets: vec![HistogramBucket {
+                lower,
+                upper,
+                count,
+                distinct: distinct.max(1),
+            }],
+            null_count,
+        }
+    }
+
+    /// Number of rows described, nulls included
+    pub fn row_count(&self) -> u64 {
+        self.non_null_count() + self.null_count
+    }
+
+    fn non_null_count(&self) -> u64 {
+        self.buckets.iter().map(|b| b.count).sum()
+    }
+
+    /// Estimated fraction of rows for which `column <operator> value` holds
+    ///
+    /// `operator` is a comparison (`<`, `<=`, `>`, `>=`, `=`, `!=`) or
+    /// `IS NULL` / `IS NOT NULL`. Comparisons never match nulls. An unknown
+    /// operator is assumed to keep every row.
+    pub fn selectivity(&self, operator: &str, value: f64) -> f64 {
+        let total = self.row_count();
+        if total == 0 {
+            return 0.0;
+        }
+        let non_null = self.non_null_count() as f64;
+        let below: f64 = self.buckets.iter().map(|b| b.rows_below(value)).sum();
+        let equal: f64 = self.buckets.iter().map(|b| b.rows_equal(value)).sum();
+
+        let rows = match operator {
+            "<" => below,
+            "<=" => below + equal,
+            ">" => non_null - below - equal,
+            ">=" => non_null - below,
+            "=" | "==" => equal,
+            "!=" | "<>" => non_null - equal,
+            "IS NULL" => self.null_count as f64,
+            "IS NOT NULL" => non_null,
+            _ => total as f64,
+        };
+        (rows / total as f64).clamp(0.0, 1.0)
+    }
+
+    /// Estimated fraction of rows matching every `(operator, value)` term
+    ///
+    /// Terms on one column aren't independent, so they aren't multiplied: a
+    /// lower and an upper bound overlap on all non-null rows, which makes
+    /// `P(lower AND upper) = P(lower) + P(upper) - P(not null)`, and any
+    /// other term can only narrow that down to its own selectivity.
+    pub fn conjunction_selectivity(&self, terms: &[(&str, f64)]) -> f64 {
+        let tightest = |operators: &[&str]| {
+            terms
+                .iter()
+                .filter(|(operator, _)| operators.contains(operator))
+                .map(|(operator, value)| self.selectivity(operator, *value))
+                .reduce(f64::min)
+        };
+        let non_null = self.selectivity("IS NOT NULL", 0.0);
+        let range = match (tightest(&[">", ">="]), tightest(&["<", "<="])) {
+            (Some(lower), Some(upper)) => (lower + upper - non_null).max(0.0),
+            (Some(bound), None) | (None, Some(bound)) => bound,
+            (None, None) => 1.0,
+        };
+        terms
+            .iter()
+            .filter(|(operator, _)| !matches!(*operator, ">" | ">=" | "<" | "<="))
+            .map(|(operator, value)| self.selectivity(operator, *value))
+            .fold(range, f64::min)
+    }
+
+    /// Combine with a histogram of other rows of the same column
+    ///
+    /// Buckets of both are interleaved by value and regrouped into at most
+    /// `num_buckets` buckets of roughly equal depth. The result is an
+    /// approximation: the distinct counts of overlapping buckets are added,
+    /// and values are assumed spread evenly inside each original bucket.
+    pub fn merge(&self, other: &Self, num_buckets: usize) -> Self {
+        let mut parts: Vec<HistogramBucket> =
+            self.buckets.iter().chain(&other.buckets).copied().collect();
+        parts.sort_by(|a, b| {
+            a.lower
+                .total_cmp(&b.lower)
+                .then(a.upper.total_cmp(&b.upper))
+        });
+
+        // The same single value on both sides is one bucket
+        let mut combined: Vec<HistogramBucket> = Vec::with_capacity(parts.len());
+        for part in parts {
+            match combined.last_mut() {
+                Some(last) if last.is_point() && part.is_point() && last.lower == part.lower => {
+                    last.count += part.count;
+                }
+                _ => combined.push(part),
+            }
+        }
+
+        let total: u64 = combined.iter().map(|b| b.count).sum();
+        let target = total.div_ceil(num_buckets.max(1) as u64).max(1);
+        let mut buckets: Vec<HistogramBucket> = Vec::new();
+        let mut open: Option<HistogramBucket> = None;
+        for part in combined {
+            open = Some(match open {
+                // Overlapping buckets can't be told apart any more, so they always combine
+                Some(current) if part.lower <= current.upper => combine(current, part),
+                Some(current) if current.count + part.count <= target => combine(current, part),
+                Some(current) => {
+                    buckets.push(current);
+                    part
+                }
+                None => part,
+            });
+        }
+        buckets.extend(open);
+
+        Self {
+            buckets,
+            null_count: self.null_count + other.null_count,
+        }
+    }
+}
+
+fn combine(a: HistogramBucket, b: HistogramBucket) -> HistogramBucket {
+    HistogramBucket {
+        lower: a.lower.min(b.lower),
+        upper: a.upper.max(b.upper),
+        count: a.count + b.count,
+        distinct: a.distinct + b.distinct,
+    }
+}
+
+/// Whether histograms can be kept for a column of this type
+pub fn is_histogram_type(data_type: &DataType) -> bool {
+    data_type.is_integer() || data_type.is_floating()
+}
+
+/// Histograms kept for a table
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct TableHistograms {
+    /// Buckets per histogram
+    pub num_buckets: usize,
+    /// Histogram of each enabled column
+    pub columns: BTreeMap<String, EquiDepthHistogram>,
+}
+
+impl TableHistograms {
+    /// Where a table's histograms are stored
+    pub fn path(base_path: &Path) -> PathBuf {
+        base_path.join("_metadata").join("histograms.json")
+    }
+
+    /// Load a table's histograms, if it has any enabled
+    pub fn load(base_path: &Path) -> Result<Option<Self>> {
+        let path = Self::path(base_path);
+        if !path.exists() {
+            return Ok(None);
+        }
+        let json = std::fs::read_to_string(&path)?;
+        Ok(Some(serde_json::from_str(&json)?))
+    }
+
+    /// Save atomically, replacing any previous histograms
+    pub fn save(&self, base_path: &Path) -> Result<()> {
+        let path = Self::path(base_path);
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let tmp_path = path.with_extension("tmp");
+        std::fs::write(&tmp_path, serde_json::to_string(self)?)?;
+        std::fs::rename(tmp_path, path)?;
+        Ok(())
+    }
+
+    /// Histogram of `column`, matched case-insensitively if there's no exact match
+    pub fn column(&self, column: &str) -> Option<&EquiDepthHistogram> {
+        self.columns.get(column).or_else(|| {
+            self.columns
+                .iter()
+                .find(|(name, _)| name.eq_ignore_ascii_case(column))
+                .map(|(_, histogram)| histogram)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 1,000 rows: 900 spread over 0..10, 100 spread over 10..10,000
+    fn skewed() -> Vec<Option<f64>> {
+        let head = (0..900).map(|i| Some((i % 10) as f64));
+        let tail = (0..100).map(|i| Some(10.0 + i as f64 * 99.9));
+        head.chain(tail).collect()
+    }
+
+    fn actual(values: &[Option<f64>], predicate: impl Fn(f64) -> bool) -> f64 {
+        let matching = values.iter().flatten().filter(|v| predicate(**v)).count();
+        matching as f64 / values.len() as f64
+    }
+
+    #[test]
+    fn test_buckets_have_equal_depth() {
+        let values: Vec<Option<f64>> = (0..1000).map(|i| Some(i as f64)).collect();
+        let histogram = EquiDepthHistogram::build(values, 10);
+
+        assert_eq!(histogram.buckets.len(), 10);
+        assert!(histogram.buckets.iter().all(|b| b.count == 100));
+        assert_eq!(histogram.buckets[0].lower, 0.0);
+        assert_eq!(histogram.buckets[9].upper, 999.0);
+    }
+
+    #[test]
+    fn test_skewed_range_estimates() {
+        let values = skewed();
+        let histogram = EquiDepthHistogram::build(values.clone(), 16);
+        let uniform = EquiDepthHistogram::uniform(0.0, 9900.0, 1000, 0);
+
+        let estimate = histogram.selectivity("<", 100.0);
+        let expected = actual(&values, |v| v < 100.0);
+        assert!(
+            (estimate - expected).abs() < 0.02,
+            "{} vs {}",
+            estimate,
+            expected
+        );
+        // Min/max alone are off by nearly the whole table
+        assert!(uniform.selectivity("<", 100.0) < 0.02);
+
+        let estimate = histogram.selectivity("=", 3.0);
+        let expected = actual(&values, |v| v == 3.0);
+        assert!(
+            (estimate - expected).abs() < 0.02,
+            "{} vs {}",
+            estimate,
+            expected
+        );
+
+        let estimate = histogram.selectivity(">=", 5000.0);
+        let expected = actual(&values, |v| v >= 5000.0);
+        assert!(
+            (estimate - expected).abs() < 0.02,
+            "{} vs {}",
+            estimate,
+            expected
+        );
+
+        let estimate = histogram.conjunction_selectivity(&[(">", 5.0), ("<=", 8.0)]);
+        let expected = actual(&values, |v| v > 5.0 && v <= 8.0);
+        assert!(
+            (estimate - expected).abs() < 0.02,
+            "{} vs {}",
+            estimate,
+            expected
+        );
+    }
+
+    #[test]
+    fn test_nulls_never_match_comparisons() {
+        let values = vec![Some(1.0), None, Some(2.0), None];
+        let histogram = EquiDepthHistogram::build(values, 4);
+
+        assert_eq!(histogram.row_count(), 4);
+        assert_eq!(histogram.selectivity("IS NULL", 0.0), 0.5);
+        assert_eq!(histogram.selectivity(">=", 0.0), 0.5);
+        assert_eq!(histogram.selectivity("!=", 1.0), 0.25);
+    }
+
+    #[test]
+    fn test_merge_approximates_combined_build() {
+        let values = skewed();
+        let (first, second) = values.split_at(600);
+        let merged = EquiDepthHistogram::build(first.to_vec(), 16)
+            .merge(&EquiDepthHistogram::build(second.to_vec(), 16), 16);
+
+        assert_eq!(merged.row_count(), 1000);
+        assert!(merged.buckets.len() <= 16);
+        assert!(merged.buckets.windows(2).all(|w| w[0].upper < w[1].lower));
+        for (operator, value, predicate) in [
+            (
+                "<",
+                100.0,
+                Box::new(|v: f64| v < 100.0) as Box<dyn Fn(f64) -> bool>,
+            ),
+            (">", 2000.0, Box::new(|v: f64| v > 2000.0)),
+        ] {
+            let estimate = merged.selectivity(operator, value);
+            let expected = actual(&values, predicate);
+            assert!(
+                (estimate - expected).abs() < 0.05,
+                "{} vs {}",
+                estimate,
+                expected
+            );
+        }
+    }
+}
@@ -0,0 +1,169 @@
+//! Table history read from the Delta log
+//!
+//! The counterpart of Delta's `DESCRIBE HISTORY`: one entry per version,
+//! newest first, with what each commit's `commitInfo` recorded. Versions
+//! whose commit files were cleaned up after a checkpoint still get an entry,
+//! so numbering has no gaps, but nothing is known about them beyond that.
+
+use super::log_replay::LogListing;
+use crate::Result;
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::path::Path;
+
+/// One version in a table's history
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CommitInfo {
+    pub version: i64,
+    /// Commit time in milliseconds since the epoch
+    pub timestamp: Option<i64>,
+    /// Operation such as `WRITE`, `DELETE`, `MERGE` or `OPTIMIZE`
+    pub operation: Option<String>,
+    /// Who made the commit, from the commit or the audit log
+    pub user: Option<String>,
+    /// Operation parameters, with non-string values as JSON text
+    pub operation_parameters: BTreeMap<String, String>,
+    /// Key-value metadata the writer attached, see
+    /// `DatabaseOps::with_commit_metadata`
+    pub metadata: BTreeMap<String, String>,
+    /// The commit file is gone and the version survives only in a checkpoint
+    pub checkpointed: bool,
+}
+
+impl CommitInfo {
+    /// Fill in what a commit's `commitInfo` action recorded
+    fn apply(&mut self, info: &Value) {
+        self.timestamp = info["timestamp"].as_i64();
+        self.operation = info["operation"].as_str().map(str::to_string);
+        self.user = info["userName"]
+            .as_str()
+            .or_else(|| info["userId"].as_str())
+            .map(str::to_string);
+        if let Some(parameters) = info["operationParameters"].as_object() {
+            self.operation_parameters = parameters
+                .iter()
+                .map(|(key, value)| {
+                    let value = match value {
+                        Value::String(s) => s.clone(),
+                        other => other.to_string(),
+                    };
+                    (key.clone(), value)
+                })
+                .collect();
+        }
+        if let Some(metadata) = info[super::commit::COMMIT_METADATA_KEY].as_object() {
+            self.metadata = metadata
+                .iter()
+                .filter_map(|(key, value)| Some((key.clone(), value.as_str()?.to_string())))
+                .collect();
+        }
+    }
+}
+
+/// History of the table at `base_path`, newest version first
+///
+/// Returns at most `limit` versions.
+pub fn read_history(base_path: &Path, limit: usize) -> Result<Vec<CommitInfo>> {
+    let log_dir = base_path.join("_delta_log");
+    if !log_dir.exists() {
+        return Ok(Vec::new());
+    }
+    let listing = LogListing::read(&log_dir, None)?;
+    let checkpoint_version = listing.checkpoint.as_ref().map(|(version, _)| *version);
+    let latest = listing
+        .commits
+        .keys()
+        .next_back()
+        .copied()
+        .max(checkpoint_version);
+    let Some(latest) = latest else {
+        return Ok(Vec::new());
+    };
+
+    let mut history = Vec::new();
+    for version in (0..=latest).rev().take(limit) {
+        let mut commit = CommitInfo {
+            version: version as i64,
+            ..CommitInfo::default()
+        };
+        match listing.commits.get(&version) {
+            Some(path) => {
+                let content = std::fs::read_to_string(path)?;
+                for line in content.lines().filter(|l| l.contains("\"commitInfo\"")) {
+                    let action: Value = serde_json::from_str(line)?;
+                    commit.apply(&action["commitInfo"]);
+                }
+            }
+            // Older than the checkpoint, its commit file cleaned up
+            None if checkpoint_version.is_some_and(|v| version <= v) => {
+                commit.checkpointed = true;
+            }
+            // A gap past the checkpoint: the log is incomplete from here back
+            None => break,
+        }
+        history.push(commit);
+    }
+    Ok(history)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn write_log_file(dir: &Path, name: String, content: &str) {
+        let log_dir = dir.join("_delta_log");
+        std::fs::create_dir_all(&log_dir).unwrap();
+        std::fs::write(log_dir.join(name), content).unwrap();
+    }
+
+    #[test]
+    fn test_history_reads_commit_info() {
+        let dir = TempDir::new().unwrap();
+        write_log_file(
+            dir.path(),
+            format!("{:020}.json", 0),
+            r#"{"commitInfo":{"timestamp":1000,"operation":"CREATE TABLE"}}"#,
+        );
+        write_log_file(
+            dir.path(),
+            format!("{:020}.json", 1),
+            concat!(
+                r#"{"add":{"path":"a.parquet","size":1}}"#,
+                "\n",
+                r#"{"commitInfo":{"timestamp":2000,"operation":"WRITE","userName":"spark","#,
+                r#""operationParameters":{"mode":"Append","partitionBy":[]}}}"#,
+            ),
+        );
+
+        let history = read_history(dir.path(), 10).unwrap();
+        assert_eq!(history.len(), 2);
+        assert_eq!(history[0].version, 1);
+        assert_eq!(history[0].operation.as_deref(), Some("WRITE"));
+        assert_eq!(history[0].user.as_deref(), Some("spark"));
+        assert_eq!(history[0].operation_parameters["mode"], "Append");
+        assert_eq!(history[0].operation_parameters["partitionBy"], "[]");
+        assert_eq!(history[1].timestamp, Some(1000));
+
+        assert_eq!(read_history(dir.path(), 1).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_checkpointed_versions_still_listed() {
+        let dir = TempDir::new().unwrap();
+        // Versions 0 and 1 were cleaned up after the checkpoint at 1
+        write_log_file(dir.path(), format!("{:020}.checkpoint.parquet", 1), "");
+        write_log_file(
+            dir.path(),
+            format!("{:020}.json", 2),
+            r#"{"commitInfo":{"timestamp":3000,"operation":"DELETE"}}"#,
+        );
+
+        let history = read_history(dir.path(), 10).unwrap();
+        let versions: Vec<i64> = history.iter().map(|c| c.version).collect();
+        assert_eq!(versions, vec![2, 1, 0]);
+        assert!(!history[0].checkpointed);
+        assert!(history[1].checkpointed && history[2].checkpointed);
+        assert_eq!(history[1].operation, None);
+    }
+}
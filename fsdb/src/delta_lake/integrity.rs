@@ -0,0 +1,483 @@
+//! Consistency checks of a table's files against its Delta log
+//!
+//! `DatabaseOps::check_integrity` replays the log and checks, changing
+//! nothing:
+//!
+//! - Every live file exists and has the size its add action records
+//! - Deletion vectors kept in files point inside a file that exists, and
+//!   delete no more rows than their data file holds
+//! - Each file's statistics agree with the file and with themselves:
+//!   `numRecords` matches the Parquet footer, no column has more nulls than
+//!   rows, and no minimum is above its maximum
+//! - The newest checkpoint lists the same live files as replaying the
+//!   commits it stands in for, as long as those commits are still in the log
+//!
+//! A write in progress never shows up as a missing file, since data files
+//! are written before the commit that references them. What can is a file
+//! removed by a commit made while the check runs, such as an OPTIMIZE
+//! followed by VACUUM, so missing files are checked again against a fresh
+//! replay and only reported if they're still live. Data files no commit
+//! references are listed apart from the issues, and only once older than
+//! the orphan lease: younger ones may belong to a write yet to commit.
+//!
+//! `DatabaseOps::repair_integrity` also makes the one fix that can't lose
+//! data: statistics at odds with their file are recomputed from it and
+//! committed again. Missing files, size mismatches and broken deletion
+//! vectors are only reported, since dropping them from the log would drop
+//! their rows with them.
+
+use super::log_replay::{read_commits_at, read_delta_log, read_delta_log_at, AddFile, LogListing};
+use super::recovery::{data_files, is_older_than, referenced_files};
+use crate::metadata::snapshot::percent_decode;
+use crate::Result;
+use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+use serde_json::Value;
+use std::collections::{BTreeMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tracing::{debug, warn};
+
+/// Characters of the Z85 encoding, in value order
+const Z85: &[u8] =
+    b"0123456789abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ.-:+=^!/*?&<>()[]{}@%$#";
+
+/// A discrepancy between the log and the files it describes
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum IntegrityIssue {
+    /// A live file that doesn't exist
+    MissingFile { path: String },
+    /// A live file whose size isn't the one recorded
+    SizeMismatch {
+        path: String,
+        recorded: u64,
+        actual: u64,
+    },
+    /// A live file whose Parquet footer can't be read
+    UnreadableFile { path: String, detail: String },
+    /// A deletion vector that's missing or doesn't fit its file
+    BrokenDeletionVector { path: String, detail: String },
+    /// Statistics at odds with the file or with themselves
+    InconsistentStats { path: String, detail: String },
+    /// A checkpoint that doesn't match the commits it stands in for
+    CheckpointMismatch { version: u64, detail: String },
+}
+
+impl IntegrityIssue {
+    /// Data file the issue is about, as in the log
+    pub fn path(&self) -> Option<&str> {
+        match self {
+            IntegrityIssue::MissingFile { path }
+            | IntegrityIssue::SizeMismatch { path, .. }
+            | IntegrityIssue::UnreadableFile { path, .. }
+            | IntegrityIssue::BrokenDeletionVector { path, .. }
+            | IntegrityIssue::InconsistentStats { path, .. } => Some(path),
+            IntegrityIssue::CheckpointMismatch { .. } => None,
+        }
+    }
+}
+
+impl std::fmt::Display for IntegrityIssue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            IntegrityIssue::MissingFile { path } => write!(f, "{}: missing", path),
+            IntegrityIssue::SizeMismatch {
+                path,
+                recorded,
+                actual,
+            } => write!(
+                f,
+                "{}: {} bytes, but the log records {}",
+                path, actual, recorded
+            ),
+            IntegrityIssue::UnreadableFile { path, detail } => {
+                write!(f, "{}: unreadable: {}", path, detail)
+            }
+            IntegrityIssue::BrokenDeletionVector { path, detail } => {
+                write!(f, "{}: deletion vector {}", path, detail)
+            }
+            IntegrityIssue::InconsistentStats { path, detail } => {
+                write!(f, "{}: statistics {}", path, detail)
+            }
+            IntegrityIssue::CheckpointMismatch { version, detail } => {
+                write!(f, "checkpoint {}: {}", version, detail)
+            }
+        }
+    }
+}
+
+/// Result of `DatabaseOps::check_integrity` or `repair_integrity`
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct IntegrityReport {
+    /// Version checked, `None` for a table without commits
+    pub version: Option<u64>,
+    /// Live files checked
+    pub files_checked: usize,
+    /// Discrepancies found, less any repaired
+    pub issues: Vec<IntegrityIssue>,
+    /// Data files no commit references, older than the orphan lease, sorted
+    pub unreferenced_files: Vec<String>,
+    /// Files whose statistics were recomputed and committed again
+    pub stats_repaired: Vec<String>,
+}
+
+impl IntegrityReport {
+    /// True if the log and the files agree
+    pub fn is_consistent(&self) -> bool {
+        self.issues.is_empty()
+    }
+}
+
+/// Check the table at `base_path` against its log
+///
+/// Unreferenced data files are listed once older than `orphan_lease`.
+pub fn check(base_path: &Path, orphan_lease: Duration) -> Result<IntegrityReport> {
+    let log = read_delta_log(base_path)?;
+    let mut report = IntegrityReport {
+        version: log.version,
+        files_checked: log.files.len(),
+        ..Default::default()
+    };
+
+    let mut missing = Vec::new();
+    for file in &log.files {
+        let full_path = base_path.join(percent_decode(&file.path)?);
+        let actual = match std::fs::metadata(&full_path) {
+            Ok(metadata) => metadata.len(),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                missing.push(file.path.clone());
+                continue;
+            }
+            Err(e) => return Err(e.into()),
+        };
+        if actual != file.size {
+            report.issues.push(IntegrityIssue::SizeMismatch {
+                path: file.path.clone(),
+                recorded: file.size,
+                actual,
+            });
+            continue;
+        }
+
+        let rows = match footer_rows(&full_path) {
+            Ok(rows) => rows,
+            Err(e) => {
+                report.issues.push(IntegrityIssue::UnreadableFile {
+                    path: file.path.clone(),
+                    detail: e.to_string(),
+                });
+                continue;
+            }
+        };
+        if let Some(detail) = check_stats(file, rows) {
+            report.issues.push(IntegrityIssue::InconsistentStats {
+                path: file.path.clone(),
+                detail,
+            });
+        }
+        if let Some(detail) = file
+            .deletion_vector
+            .as_ref()
+            .and_then(|dv| check_deletion_vector(base_path, dv, rows))
+        {
+            report.issues.push(IntegrityIssue::BrokenDeletionVector {
+                path: file.path.clone(),
+                detail,
+            });
+        }
+    }
+
+    if !missing.is_empty() {
+        // A commit made during the check may have removed them
+        let live: HashSet<String> = read_delta_log(base_path)?
+            .files
+            .into_iter()
+            .map(|file| file.path)
+            .collect();
+        for path in missing {
+            if live.contains(&path) && !base_path.join(percent_decode(&path)?).exists() {
+                warn!("Live file {} is missing", path);
+                report.issues.push(IntegrityIssue::MissingFile { path });
+            } else {
+                debug!("{} was removed while the table was checked", path);
+            }
+        }
+    }
+
+    if let Some(issue) = check_checkpoint(base_path)? {
+        report.issues.push(issue);
+    }
+    report.unreferenced_files = unreferenced_files(base_path, orphan_lease)?;
+    Ok(report)
+}
+
+/// Statistics for the data file `path`, in the log's `stats` form,
+/// recomputed from its rows
+///
+/// Only columns of the types `compute_column_statistics` covers get bounds;
+/// others keep their null counts alone.
+pub fn recompute_stats(base_path: &Path, path: &str) -> Result<String> {
+    let file = std::fs::File::open(base_path.join(percent_decode(path)?))?;
+    let builder = ParquetRecordBatchReaderBuilder::try_new(file)?;
+    let schema = builder.schema().clone();
+    let reader = builder.build()?;
+    let batches = reader.collect::<std::result::Result<Vec<_>, _>>()?;
+    let batch = arrow::compute::concat_batches(&schema, &batches)?;
+    let (min_values, max_values, null_counts) = super::compute_column_statistics(&batch)?;
+    let sorted = |values: std::collections::HashMap<String, Value>| -> BTreeMap<String, Value> {
+        values.into_iter().collect()
+    };
+    let null_counts: BTreeMap<String, u64> = null_counts.into_iter().collect();
+    Ok(serde_json::json!({
+        "numRecords": batch.num_rows(),
+        "minValues": sorted(min_values),
+        "maxValues": sorted(max_values),
+        "nullCount": null_counts,
+    })
+    .to_string())
+}
+
+/// Row count in a Parquet file's footer
+fn footer_rows(path: &Path) -> Result<u64> {
+    let file = std::fs::File::open(path)?;
+    let footer = parquet::file::metadata::ParquetMetaDataReader::new().parse_and_finish(&file)?;
+    Ok(footer.file_metadata().num_rows() as u64)
+}
+
+/// What's wrong with `file`'s statistics, given that it holds `rows` rows
+fn check_stats(file: &AddFile, rows: u64) -> Option<String> {
+    let stats = file.stats.as_ref()?;
+    if let Some(recorded) = stats["numRecords"].as_u64() {
+        if recorded != rows {
+            return Some(format!(
+                "record {} rows, but the file holds {}",
+                recorded, rows
+            ));
+        }
+    }
+    for (column, nulls) in stats["nullCount"].as_object().into_iter().flatten() {
+        if nulls.as_u64().is_some_and(|nulls| nulls > rows) {
+            return Some(format!(
+                "record {} nulls in {}, more than the file's {} rows",
+                nulls, column, rows
+            ));
+        }
+    }
+    for (column, min) in stats["minValues"].as_object().into_iter().flatten() {
+        let max = &stats["maxValues"][column.as_str()];
+        let above = match (min, max) {
+            (Value::String(min), Value::String(max)) => min > max,
+            _ => crate::query::pruning::is_value_greater_than(min, max),
+        };
+        if above {
+            return Some(format!(
+                "record a minimum of {} above its maximum, {} > {}",
+                column, min, max
+            ));
+        }
+    }
+    None
+}
+
+/// What's wrong with the deletion vector `dv` of a file of `rows` rows
+fn check_deletion_vector(base_path: &Path, dv: &Value, rows: u64) -> Option<String> {
+    if let Some(cardinality) = dv["cardinality"].as_u64() {
+        if cardinality > rows {
+            return Some(format!(
+                "deletes {} rows of a file of {}",
+                cardinality, rows
+            ));
+        }
+    }
+    let location = dv["pathOrInlineDv"].as_str().unwrap_or_default();
+    let path = match dv["storageType"].as_str() {
+        // Inline in the log, so nothing to find
+        Some("i") => return None,
+        Some("u") => match deletion_vector_path(base_path, location) {
+            Some(path) => path,
+            None => return Some(format!("has an invalid location '{}'", location)),
+        },
+        // Elsewhere than the local filesystem can't be checked from here
+        Some("p") => PathBuf::from(location.strip_prefix("file://")?),
+        other => return Some(format!("has an unknown storage type {:?}", other)),
+    };
+    let Ok(metadata) = std::fs::metadata(&path) else {
+        return Some(format!("file {} is missing", path.display()));
+    };
+    // The bitmap sits between a 4-byte size and a 4-byte checksum
+    let offset = dv["offset"].as_u64().unwrap_or(1);
+    let size = dv["sizeInBytes"].as_u64().unwrap_or(0);
+    let end = offset + 4 + size + 4;
+    if end > metadata.len() {
+        return Some(format!(
+            "ends at byte {} of {}, past its {} bytes",
+            end,
+            path.display(),
+            metadata.len()
+        ));
+    }
+    None
+}
+
+/// File of a deletion vector stored relative to the table: a directory
+/// prefix, then the file's UUID in 20 characters of Z85
+fn deletion_vector_path(base_path: &Path, location: &str) -> Option<PathBuf> {
+    let split = location.len().checked_sub(20)?;
+    let (prefix, encoded) = (location.get(..split)?, location.get(split..)?);
+    let uuid = uuid::Uuid::from_slice(&z85_decode(encoded)?).ok()?;
+    Some(
+        base_path
+            .join(prefix)
+            .join(format!("deletion_vector_{}.bin", uuid)),
+    )
+}
+
+/// Decode Z85 text, five characters to four bytes
+fn z85_decode(text: &str) -> Option<Vec<u8>> {
+    if text.len() % 5 != 0 {
+        return None;
+    }
+    let mut bytes = Vec::with_capacity(text.len() / 5 * 4);
+    for chunk in text.as_bytes().chunks(5) {
+        let mut value: u64 = 0;
+        for c in chunk {
+            value = value * 85 + Z85.iter().position(|z| z == c)? as u64;
+        }
+        bytes.extend_from_slice(&u32::try_from(value).ok()?.to_be_bytes());
+    }
+    Some(bytes)
+}
+
+/// A discrepancy between the newest checkpoint and the commits before it
+fn check_checkpoint(base_path: &Path) -> Result<Option<IntegrityIssue>> {
+    let log_dir = base_path.join("_delta_log");
+    if !log_dir.is_dir() {
+        return Ok(None);
+    }
+    let Some((version, _)) = LogListing::read(&log_dir, None)?.checkpoint else {
+        return Ok(None);
+    };
+    let Some(from_commits) = read_commits_at(base_path, version)? else {
+        debug!("Commits before checkpoint {} were cleaned up", version);
+        return Ok(None);
+    };
+    let from_checkpoint = match read_delta_log_at(base_path, version) {
+        Ok(log) => log,
+        Err(e) => {
+            return Ok(Some(IntegrityIssue::CheckpointMismatch {
+                version,
+                detail: format!("unreadable: {}", e),
+            }))
+        }
+    };
+
+    let sizes = |files: Vec<AddFile>| -> BTreeMap<String, u64> {
+        files.into_iter().map(|f| (f.path, f.size)).collect()
+    };
+    let checkpointed = sizes(from_checkpoint.files);
+    let committed = sizes(from_commits.files);
+    let only_checkpointed = checkpointed
+        .keys()
+        .filter(|path| !committed.contains_key(*path))
+        .count();
+    let only_committed = committed
+        .keys()
+        .filter(|path| !checkpointed.contains_key(*path))
+        .count();
+    let resized = checkpointed
+        .iter()
+        .filter(|(path, size)| committed.get(*path).is_some_and(|s| s != *size))
+        .count();
+    if only_checkpointed + only_committed + resized == 0 {
+        return Ok(None);
+    }
+    Ok(Some(IntegrityIssue::CheckpointMismatch {
+        version,
+        detail: format!(
+            "{} live files only in the checkpoint, {} only in the commits, {} with other sizes",
+            only_checkpointed, only_committed, resized
+        ),
+    }))
+}
+
+/// Data files no commit references, older than `lease`
+fn unreferenced_files(base_path: &Path, lease: Duration) -> Result<Vec<String>> {
+    let referenced = referenced_files(base_path)?;
+    let mut files = Vec::new();
+    data_files(base_path, base_path, &mut files)?;
+    let mut unreferenced: Vec<String> = files
+        .into_iter()
+        .filter(|(relative, path)| !referenced.contains(relative) && is_older_than(path, lease))
+        .map(|(relative, _)| relative)
+        .collect();
+    unreferenced.sort();
+    Ok(unreferenced)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn add_file(stats: Value) -> AddFile {
+        AddFile {
+            path: "part-0.parquet".to_string(),
+            size: 100,
+            modification_time: 0,
+            stats: Some(stats),
+            partition_values: BTreeMap::new(),
+            deletion_vector: None,
+            tags: BTreeMap::new(),
+        }
+    }
+
+    #[test]
+    fn test_z85_and_deletion_vector_paths() {
+        // The example from the Z85 specification
+        assert_eq!(
+            z85_decode("HelloWorld"),
+            Some(vec![0x86, 0x4F, 0xD2, 0x6F, 0xB5, 0x59, 0xF7, 0x5B])
+        );
+        assert_eq!(z85_decode("Hell"), None);
+        assert_eq!(z85_decode("~~~~~"), None);
+
+        let base = Path::new("/table");
+        let path = deletion_vector_path(base, "ab00000000000000000000").unwrap();
+        assert_eq!(
+            path,
+            base.join("ab")
+                .join("deletion_vector_00000000-0000-0000-0000-000000000000.bin")
+        );
+        assert_eq!(deletion_vector_path(base, "short"), None);
+    }
+
+    #[test]
+    fn test_stats_checks() {
+        let consistent = json!({
+            "numRecords": 3,
+            "minValues": {"id": 1, "name": "a"},
+            "maxValues": {"id": 3, "name": "c"},
+            "nullCount": {"id": 0, "name": 1},
+        });
+        assert_eq!(check_stats(&add_file(consistent), 3), None);
+
+        let stale = json!({"numRecords": 2});
+        assert!(check_stats(&add_file(stale), 3).is_some());
+        let nulls = json!({"numRecords": 3, "nullCount": {"name": 4}});
+        assert!(check_stats(&add_file(nulls), 3).is_some());
+        let bounds = json!({"minValues": {"name": "z"}, "maxValues": {"name": "a"}});
+        assert!(check_stats(&add_file(bounds), 3).is_some());
+    }
+
+    #[test]
+    fn test_deletion_vector_checks() {
+        let base = Path::new("/nonexistent");
+        let inline = json!({"storageType": "i", "pathOrInlineDv": "wi5b=000010000siXQKl0rr91000f55c8Xg0@@D72lkbi5=-{L", "cardinality": 2});
+        assert_eq!(check_deletion_vector(base, &inline, 10), None);
+        assert!(check_deletion_vector(base, &inline, 1).is_some());
+
+        let missing = json!({"storageType": "u", "pathOrInlineDv": "ab00000000000000000000", "offset": 1, "sizeInBytes": 34, "cardinality": 2});
+        assert!(check_deletion_vector(base, &missing, 10)
+            .unwrap()
+            .contains("missing"));
+    }
+}
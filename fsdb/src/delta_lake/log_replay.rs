@@ -0,0 +1,840 @@
+//! Delta transaction log replay
+//!
+//! Statistics, data skipping and compatibility checks read `_delta_log`
+//! directly instead of going through a loaded `DeltaTable`. Besides FSDB's own
+//! tables this has to cope with logs written by Spark and Databricks:
+//!
+//! - Checkpoints, single- or multi-part, stand in for every commit before
+//!   them, and log retention may already have deleted those commits
+//! - With column mapping (`name` or `id` mode), statistics are keyed by
+//!   physical column names (`col-<uuid>`) that only the schema maps back to
+//!   logical names
+//! - Statistics live in the `stats` JSON string of each add action (or in
+//!   `stats_parsed` in checkpoints), including `numRecords`
+//!
+//! V2 checkpoints and unknown reader features fail with
+//! `Error::UnsupportedDeltaFeature` rather than returning partial results.
+
+use crate::{Error, Result};
+use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+use parquet::arrow::ProjectionMask;
+use serde_json::{Map, Value};
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+use tracing::debug;
+
+/// Reader features FSDB understands
+const READER_FEATURES: &[&str] = &["columnMapping", "timestampNtz", "vacuumProtocolCheck"];
+
+/// Writer features FSDB's commits honour
+const WRITER_FEATURES: &[&str] = &[
+    "appendOnly",
+    "invariants",
+    "checkConstraints",
+    "changeDataFeed",
+    "generatedColumns",
+    "timestampNtz",
+];
+
+/// Reader and writer requirements of a table
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Protocol {
+    pub min_reader_version: i64,
+    pub min_writer_version: i64,
+    /// Only set from reader version 3
+    pub reader_features: Vec<String>,
+    /// Only set from writer version 7
+    pub writer_features: Vec<String>,
+}
+
+/// Which writes a table accepts
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WriteMode {
+    /// Rows can be inserted, updated and deleted
+    ReadWrite,
+    /// `delta.appendOnly` is set: rows can be inserted but not changed or removed
+    AppendOnly,
+    /// Committing needs a Delta feature FSDB doesn't implement
+    ReadOnly,
+}
+
+/// Protocol and metadata of a table at its latest version
+#[derive(Debug, Clone, Default)]
+pub struct TableConfig {
+    pub protocol: Protocol,
+    /// `metaData.configuration`, e.g. `delta.columnMapping.mode`
+    pub configuration: HashMap<String, String>,
+    /// `metaData.schemaString`
+    pub schema_string: Option<String>,
+    /// `metaData.partitionColumns`, in partitioning order
+    pub partition_columns: Vec<String>,
+}
+
+impl TableConfig {
+    /// Column mapping mode: `none`, `name` or `id`
+    pub fn column_mapping_mode(&self) -> &str {
+        self.configuration
+            .get("delta.columnMapping.mode")
+            .map(String::as_str)
+            .unwrap_or("none")
+    }
+
+    /// Fail if reading the table needs a feature FSDB doesn't understand
+    pub fn check_readable(&self) -> Result<()> {
+        if self.protocol.min_reader_version > 3 {
+            return Err(Error::UnsupportedDeltaFeature(format!(
+                "reader version {}",
+                self.protocol.min_reader_version
+            )));
+        }
+        unsupported(&self.protocol.reader_features, READER_FEATURES)
+    }
+
+    /// Fail if committing to the table needs a feature FSDB doesn't implement
+    ///
+    /// Column-mapped tables are readable but not writable: new Parquet files
+    /// would carry logical column names that other engines can't resolve.
+    pub fn check_writable(&self) -> Result<()> {
+        self.check_readable()?;
+        match self.protocol.min_writer_version {
+            v if v > 7 => Err(Error::UnsupportedDeltaFeature(format!(
+                "writer version {}",
+                v
+            ))),
+            7 => unsupported(&self.protocol.writer_features, WRITER_FEATURES),
+            _ if self.column_mapping_mode() != "none" => {
+                Err(Error::UnsupportedDeltaFeature("columnMapping".to_string()))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Whether `delta.appendOnly` forbids updating and deleting rows
+    pub fn is_append_only(&self) -> bool {
+        self.configuration
+            .get("delta.appendOnly")
+            .is_some_and(|v| v.eq_ignore_ascii_case("true"))
+    }
+
+    /// Whether `delta.enableChangeDataFeed` has writers record changed rows
+    pub fn is_change_data_feed_enabled(&self) -> bool {
+        self.configuration
+            .get(super::change_feed::ENABLE_PROPERTY)
+            .is_some_and(|v| v.eq_ignore_ascii_case("true"))
+    }
+
+    /// Whether `delta.enableDeletionVectors` asks deletes to mark rows in
+    /// deletion vectors rather than rewrite files
+    pub fn deletion_vectors_enabled(&self) -> bool {
+        self.configuration
+            .get("delta.enableDeletionVectors")
+            .is_some_and(|v| v.eq_ignore_ascii_case("true"))
+    }
+
+    /// Which writes the table accepts
+    pub fn write_mode(&self) -> WriteMode {
+        if self.check_writable().is_err() {
+            WriteMode::ReadOnly
+        } else if self.is_append_only() {
+            WriteMode::AppendOnly
+        } else {
+            WriteMode::ReadWrite
+        }
+    }
+
+    /// Physical column path -> logical column path, empty without column mapping
+    ///
+    /// Nested fields are joined with `.` on both sides.
+    pub fn logical_names(&self) -> HashMap<String, String> {
+        let mut names = HashMap::new();
+        if self.column_mapping_mode() == "none" {
+            return names;
+        }
+        if let Some(schema) = self
+            .schema_string
+            .as_deref()
+            .and_then(|s| serde_json::from_str::<Value>(s).ok())
+        {
+            collect_names(&schema["fields"], "", "", &mut names);
+        }
+        names
+    }
+
+    fn apply(&mut self, action: &Value) {
+        if let Some(protocol) = action.get("protocol") {
+            let features = |key: &str| -> Vec<String> {
+                protocol[key]
+                    .as_array()
+                    .into_iter()
+                    .flatten()
+                    .filter_map(|f| f.as_str().map(str::to_string))
+                    .collect()
+            };
+            self.protocol = Protocol {
+                min_reader_version: protocol["minReaderVersion"].as_i64().unwrap_or(1),
+                min_writer_version: protocol["minWriterVersion"].as_i64().unwrap_or(2),
+                reader_features: features("readerFeatures"),
+                writer_features: features("writerFeatures"),
+            };
+        }
+        if let Some(metadata) = action.get("metaData") {
+            self.configuration = metadata["configuration"]
+                .as_object()
+                .into_iter()
+                .flatten()
+                .filter_map(|(k, v)| v.as_str().map(|v| (k.clone(), v.to_string())))
+                .collect();
+            self.schema_string = metadata["schemaString"].as_str().map(str::to_string);
+            self.partition_columns = metadata["partitionColumns"]
+                .as_array()
+                .into_iter()
+                .flatten()
+                .filter_map(|c| c.as_str().map(str::to_string))
+                .collect();
+        }
+    }
+}
+
+fn unsupported(features: &[String], supported: &[&str]) -> Result<()> {
+    match features.iter().find(|f| !supported.contains(&f.as_str())) {
+        Some(feature) => Err(Error::UnsupportedDeltaFeature(feature.clone())),
+        None => Ok(()),
+    }
+}
+
+fn collect_names(
+    fields: &Value,
+    physical_prefix: &str,
+    logical_prefix: &str,
+    names: &mut HashMap<String, String>,
+) {
+    for field in fields.as_array().into_iter().flatten() {
+        let Some(name) = field["name"].as_str() else {
+            continue;
+        };
+        let physical = field["metadata"]["delta.columnMapping.physicalName"]
+            .as_str()
+            .unwrap_or(name);
+        let physical = join_path(physical_prefix, physical);
+        let logical = join_path(logical_prefix, name);
+        collect_names(&field["type"]["fields"], &physical, &logical, names);
+        names.insert(physical, logical);
+    }
+}
+
+fn join_path(prefix: &str, name: &str) -> String {
+    if prefix.is_empty() {
+        name.to_string()
+    } else {
+        format!("{}.{}", prefix, name)
+    }
+}
+
+/// A data file live at the replayed version
+#[derive(Debug, Clone)]
+pub struct AddFile {
+    /// Relative to the table root, URL-encoded as in the log
+    pub path: String,
+    pub size: u64,
+    /// `modificationTime`, in milliseconds since the epoch
+    pub modification_time: i64,
+    /// `numRecords`, `minValues`, `maxValues` and `nullCount`, with nested
+    /// columns flattened to dotted logical names
+    pub stats: Option<Value>,
+    /// Partition column -> value as written in the log, `None` for null
+    pub partition_values: BTreeMap<String, Option<String>>,
+    /// The add action's `deletionVector` descriptor, if it has one
+    pub deletion_vector: Option<Value>,
+    /// The add action's `tags`, such as the Z-ORDER run that wrote the file
+    pub tags: BTreeMap<String, String>,
+}
+
+impl AddFile {
+    /// `numRecords` from the file's statistics, or its Parquet footer if the
+    /// log has none
+    pub fn num_records(&self, base_path: &Path) -> Result<u64> {
+        if let Some(rows) = self.recorded_num_records() {
+            return Ok(rows);
+        }
+        let path = base_path.join(crate::metadata::snapshot::percent_decode(&self.path)?);
+        let file = std::fs::File::open(path)?;
+        let footer =
+            parquet::file::metadata::ParquetMetaDataReader::new().parse_and_finish(&file)?;
+        Ok(footer.file_metadata().num_rows() as u64)
+    }
+
+    /// `numRecords` as recorded in the log
+    pub fn recorded_num_records(&self) -> Option<u64> {
+        self.stats
+            .as_ref()
+            .and_then(|stats| stats["numRecords"].as_u64())
+    }
+}
+
+/// Result of replaying a table's log
+#[derive(Debug, Clone, Default)]
+pub struct DeltaLog {
+    /// Version replayed to, `None` for a missing or empty log
+    pub version: Option<u64>,
+    pub config: TableConfig,
+    pub files: Vec<AddFile>,
+}
+
+/// Replay the log under `base_path` into the latest protocol, metadata and live files
+pub fn read_delta_log(base_path: &Path) -> Result<DeltaLog> {
+    replay(base_path, true, None, true)
+}
+
+/// Replay the log up to and including `version`
+///
+/// Fails if `version` can't be reconstructed: it doesn't exist yet, or the
+/// commits leading to it were cleaned up without a checkpoint covering them.
+pub fn read_delta_log_at(base_path: &Path, version: u64) -> Result<DeltaLog> {
+    let log = replay(base_path, true, Some(version), true)?;
+    if log.version != Some(version) {
+        return Err(Error::InvalidOperation(format!(
+            "Version {} is not available in the Delta log",
+            version
+        )));
+    }
+    Ok(log)
+}
+
+/// Log files a reader needs to reconstruct `version`
+///
+/// The newest complete checkpoint at or before `version`, if any, followed by
+/// every commit up to `version` still in the log.
+pub fn log_files_at(base_path: &Path, version: u64) -> Result<Vec<PathBuf>> {
+    let listing = LogListing::read(&base_path.join("_delta_log"), Some(version))?;
+    let mut files = listing
+        .checkpoint
+        .map(|(_, parts)| parts)
+        .unwrap_or_default();
+    files.extend(listing.commits.into_values());
+    Ok(files)
+}
+
+/// Replay only protocol and metadata, skipping file actions
+pub fn read_table_config(base_path: &Path) -> Result<TableConfig> {
+    Ok(replay(base_path, false, None, true)?.config)
+}
+
+/// Replay the commits up to and including `version`, ignoring checkpoints
+///
+/// `None` if any of those commits has been cleaned up, so that only a
+/// checkpoint can reconstruct `version`.
+pub fn read_commits_at(base_path: &Path, version: u64) -> Result<Option<DeltaLog>> {
+    let log = replay(base_path, true, Some(version), false)?;
+    Ok((log.version == Some(version)).then_some(log))
+}
+
+/// Newest version in the log under `base_path`, without replaying it
+pub fn latest_version(base_path: &Path) -> Result<Option<u64>> {
+    let listing = LogListing::read(&base_path.join("_delta_log"), None)?;
+    Ok(listing
+        .commits
+        .keys()
+        .next_back()
+        .copied()
+        .max(listing.checkpoint.map(|(version, _)| version)))
+}
+
+/// Commits and the newest usable checkpoint in `_delta_log`
+#[derive(Default)]
+pub(crate) struct LogListing {
+    pub(crate) commits: BTreeMap<u64, PathBuf>,
+    /// Version and part files, in part order
+    pub(crate) checkpoint: Option<(u64, Vec<PathBuf>)>,
+    /// Newest UUID-named (V2) checkpoint, which FSDB can't read
+    v2_checkpoint: Option<u64>,
+}
+
+impl LogListing {
+    /// List `log_dir`, ignoring anything newer than `until`
+    pub(crate) fn read(log_dir: &Path, until: Option<u64>) -> Result<Self> {
+        let mut listing = Self::default();
+        // (version, part count) -> part number -> file
+        let mut checkpoints: BTreeMap<(u64, u32), BTreeMap<u32, PathBuf>> = BTreeMap::new();
+
+        for entry in std::fs::read_dir(log_dir)? {
+            let path = entry?.path();
+            let Some(name) = path.file_name().and_then(|n| n.to_str()) else {
+                continue;
+            };
+            let parts: Vec<&str> = name.split('.').collect();
+            let Some(version) = parts
+                .first()
+                .filter(|v| v.len() == 20)
+                .and_then(|v| v.parse::<u64>().ok())
+                .filter(|v| until.is_none_or(|until| *v <= until))
+            else {
+                continue;
+            };
+            match parts[1..] {
+                ["json"] => {
+                    listing.commits.insert(version, path);
+                }
+                ["checkpoint", "parquet"] => {
+                    checkpoints.entry((version, 1)).or_default().insert(1, path);
+                }
+                ["checkpoint", part, total, "parquet"] => {
+                    if let (Ok(part), Ok(total)) = (part.parse::<u32>(), total.parse::<u32>()) {
+                        checkpoints
+                            .entry((version, total))
+                            .or_default()
+                            .insert(part, path);
+                    }
+                }
+                ["checkpoint", _, "json" | "parquet"] => {
+                    listing.v2_checkpoint = listing.v2_checkpoint.max(Some(version));
+                }
+                // .crc files, log compactions and anything else
+                _ => {}
+            }
+        }
+
+        // A checkpoint still being written has missing parts and is skipped
+        listing.checkpoint = checkpoints
+            .into_iter()
+            .rev()
+            .find(|((_, total), parts)| parts.len() == *total as usize)
+            .map(|((version, _), parts)| (version, parts.into_values().collect()));
+        Ok(listing)
+    }
+}
+
+fn replay(
+    base_path: &Path,
+    with_files: bool,
+    until: Option<u64>,
+    from_checkpoint: bool,
+) -> Result<DeltaLog> {
+    let log_dir = base_path.join("_delta_log");
+    let mut log = DeltaLog::default();
+    if !log_dir.exists() {
+        return Ok(log);
+    }
+
+    let mut listing = LogListing::read(&log_dir, until)?;
+    if !from_checkpoint {
+        listing.checkpoint = None;
+        listing.v2_checkpoint = None;
+    }
+    let checkpoint_version = listing.checkpoint.as_ref().map(|(version, _)| *version);
+    if listing.v2_checkpoint > checkpoint_version {
+        return Err(Error::UnsupportedDeltaFeature("v2Checkpoint".to_string()));
+    }
+
+    // Path -> add action; insertion order is irrelevant since removes are by path
+    let mut adds: HashMap<String, Value> = HashMap::new();
+    let mut apply = |action: &Value, config: &mut TableConfig| {
+        config.apply(action);
+        if !with_files {
+            return;
+        }
+        if let Some(path) = action["add"]["path"].as_str() {
+            adds.insert(path.to_string(), action["add"].clone());
+        }
+        if let Some(path) = action["remove"]["path"].as_str() {
+            adds.remove(path);
+        }
+    };
+
+    if let Some((version, parts)) = &listing.checkpoint {
+        debug!(
+            "Replaying from checkpoint {} ({} parts)",
+            version,
+            parts.len()
+        );
+        for part in parts {
+            for action in read_checkpoint_part(part, with_files)? {
+                apply(&action, &mut log.config);
+            }
+        }
+        log.version = Some(*version);
+    }
+
+    let first_commit = checkpoint_version.map_or(0, |v| v + 1);
+    for (version, path) in listing.commits.range(first_commit..) {
+        // A gap means the commits in between were cleaned up past any checkpoint
+        if log.version.map_or(*version != 0, |v| *version != v + 1) {
+            break;
+        }
+        let content = std::fs::read_to_string(path)?;
+        for line in content.lines().filter(|l| !l.trim().is_empty()) {
+            // Commits are mostly file actions, which a config-only replay can skip unparsed
+            if !with_files && !line.contains("\"protocol\"") && !line.contains("\"metaData\"") {
+                continue;
+            }
+            let action: Value = serde_json::from_str(line)?;
+            apply(&action, &mut log.config);
+        }
+        log.version = Some(*version);
+    }
+
+    let names = log.config.logical_names();
+    log.files = adds
+        .into_iter()
+        .map(|(path, add)| AddFile {
+            size: add["size"].as_u64().unwrap_or(0),
+            modification_time: add["modificationTime"].as_i64().unwrap_or(0),
+            stats: normalize_stats(&add, &names),
+            partition_values: partition_values(&add["partitionValues"], &names),
+            deletion_vector: add["deletionVector"]
+                .is_object()
+                .then(|| add["deletionVector"].clone()),
+            tags: add["tags"]
+                .as_object()
+                .map(|tags| {
+                    tags.iter()
+                        .filter_map(|(key, value)| Some((key.clone(), value.as_str()?.to_string())))
+                        .collect()
+                })
+                .unwrap_or_default(),
+            path,
+        })
+        .collect();
+    Ok(log)
+}
+
+/// Read one checkpoint part as JSON actions, the same shape commits use
+fn read_checkpoint_part(path: &Path, with_files: bool) -> Result<Vec<Value>> {
+    let wanted: &[&str] = if with_files {
+        &["add", "remove", "metaData", "protocol"]
+    } else {
+        &["metaData", "protocol"]
+    };
+
+    let builder = ParquetRecordBatchReaderBuilder::try_new(std::fs::File::open(path)?)?;
+    let roots: Vec<usize> = builder
+        .parquet_schema()
+        .root_schema()
+        .get_fields()
+        .iter()
+        .enumerate()
+        .filter(|(_, field)| wanted.contains(&field.name()))
+        .map(|(i, _)| i)
+        .collect();
+    let mask = ProjectionMask::roots(builder.parquet_schema(), roots);
+    let reader = builder.with_projection(mask).build()?;
+
+    // Null columns are left out, so each row becomes e.g. {"add": {...}}
+    let mut writer = arrow::json::LineDelimitedWriter::new(Vec::new());
+    for batch in reader {
+        writer.write(&batch?)?;
+    }
+    writer.finish()?;
+    let json = writer.into_inner();
+
+    let mut actions = Vec::new();
+    for line in json.split(|b| *b == b'\n').filter(|l| !l.is_empty()) {
+        actions.push(serde_json::from_slice(line)?);
+    }
+    Ok(actions)
+}
+
+/// Read an add action's partition values under logical column names
+///
+/// Commits hold them as an object; checkpoints, read back through Arrow,
+/// may hold them as a list of key/value entries instead.
+fn partition_values(
+    values: &Value,
+    names: &HashMap<String, String>,
+) -> BTreeMap<String, Option<String>> {
+    let entries: Vec<(&str, &Value)> = match values {
+        Value::Object(map) => map.iter().map(|(k, v)| (k.as_str(), v)).collect(),
+        Value::Array(entries) => entries
+            .iter()
+            .filter_map(|e| Some((e["key"].as_str()?, &e["value"])))
+            .collect(),
+        _ => Vec::new(),
+    };
+    entries
+        .into_iter()
+        .map(|(column, value)| {
+            let column = names.get(column).cloned().unwrap_or(column.to_string());
+            (column, value.as_str().map(str::to_string))
+        })
+        .collect()
+}
+
+/// Parse an add action's statistics and rename columns to logical names
+fn normalize_stats(add: &Value, names: &HashMap<String, String>) -> Option<Value> {
+    let stats = match &add["stats"] {
+        Value::String(s) => serde_json::from_str(s).ok()?,
+        Value::Object(_) => add["stats"].clone(),
+        _ => match &add["stats_parsed"] {
+            Value::Object(_) => add["stats_parsed"].clone(),
+            _ => return None,
+        },
+    };
+
+    let mut normalized = Map::new();
+    if let Some(num_records) = stats.get("numRecords") {
+        normalized.insert("numRecords".to_string(), num_records.clone());
+    }
+    for key in ["minValues", "maxValues", "nullCount"] {
+        if let Some(values) = stats[key].as_object() {
+            let mut flat = Map::new();
+            flatten_stats("", values, names, &mut flat);
+            normalized.insert(key.to_string(), Value::Object(flat));
+        }
+    }
+    Some(Value::Object(normalized))
+}
+
+fn flatten_stats(
+    prefix: &str,
+    values: &Map<String, Value>,
+    names: &HashMap<String, String>,
+    out: &mut Map<String, Value>,
+) {
+    for (name, value) in values {
+        let path = join_path(prefix, name);
+        match value {
+            Value::Object(nested) => flatten_stats(&path, nested, names, out),
+            _ => {
+                let logical = names.get(&path).cloned().unwrap_or(path);
+                out.insert(logical, value.clone());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use tempfile::TempDir;
+
+    fn write_commit(dir: &Path, version: u64, actions: &[Value]) {
+        let log_dir = dir.join("_delta_log");
+        std::fs::create_dir_all(&log_dir).unwrap();
+        let lines: Vec<String> = actions.iter().map(Value::to_string).collect();
+        std::fs::write(
+            log_dir.join(format!("{:020}.json", version)),
+            lines.join("\n"),
+        )
+        .unwrap();
+    }
+
+    fn column_mapped_metadata() -> Value {
+        let schema = json!({"type": "struct", "fields": [
+            {"name": "id", "type": "long", "nullable": true, "metadata": {
+                "delta.columnMapping.id": 1,
+                "delta.columnMapping.physicalName": "col-1"}},
+            {"name": "address", "type": {"type": "struct", "fields": [
+                {"name": "city", "type": "string", "nullable": true, "metadata": {
+                    "delta.columnMapping.id": 3,
+                    "delta.columnMapping.physicalName": "col-3"}}
+            ]}, "nullable": true, "metadata": {
+                "delta.columnMapping.id": 2,
+                "delta.columnMapping.physicalName": "col-2"}}
+        ]});
+        json!({"metaData": {
+            "id": "t",
+            "format": {"provider": "parquet", "options": {}},
+            "schemaString": schema.to_string(),
+            "partitionColumns": [],
+            "configuration": {"delta.columnMapping.mode": "name"}
+        }})
+    }
+
+    #[test]
+    fn test_missing_log_is_empty() {
+        let dir = TempDir::new().unwrap();
+        let log = read_delta_log(dir.path()).unwrap();
+        assert_eq!(log.version, None);
+        assert!(log.files.is_empty());
+    }
+
+    #[test]
+    fn test_column_mapped_stats_use_logical_names() {
+        let dir = TempDir::new().unwrap();
+        let stats = json!({
+            "numRecords": 2,
+            "minValues": {"col-1": 1, "col-2": {"col-3": "Berlin"}},
+            "maxValues": {"col-1": 9, "col-2": {"col-3": "Paris"}},
+            "nullCount": {"col-1": 0, "col-2": {"col-3": 1}}
+        });
+        write_commit(
+            dir.path(),
+            0,
+            &[
+                json!({"protocol": {"minReaderVersion": 2, "minWriterVersion": 5}}),
+                column_mapped_metadata(),
+                json!({"add": {"path": "a.parquet", "size": 10, "stats": stats.to_string()}}),
+            ],
+        );
+
+        let log = read_delta_log(dir.path()).unwrap();
+        assert_eq!(log.version, Some(0));
+        assert_eq!(log.config.column_mapping_mode(), "name");
+        let stats = log.files[0].stats.as_ref().unwrap();
+        assert_eq!(stats["numRecords"], 2);
+        assert_eq!(stats["minValues"]["id"], 1);
+        assert_eq!(stats["maxValues"]["address.city"], "Paris");
+        assert_eq!(stats["nullCount"]["address.city"], 1);
+    }
+
+    #[test]
+    fn test_removed_files_are_dropped() {
+        let dir = TempDir::new().unwrap();
+        write_commit(
+            dir.path(),
+            0,
+            &[
+                json!({"add": {"path": "a.parquet", "size": 1}}),
+                json!({"add": {"path": "b.parquet", "size": 2}}),
+            ],
+        );
+        write_commit(
+            dir.path(),
+            1,
+            &[json!({"remove": {"path": "a.parquet", "dataChange": true}})],
+        );
+
+        let log = read_delta_log(dir.path()).unwrap();
+        assert_eq!(log.version, Some(1));
+        assert_eq!(log.files.len(), 1);
+        assert_eq!(log.files[0].path, "b.parquet");
+        assert!(log.files[0].stats.is_none());
+    }
+
+    #[test]
+    fn test_partition_values_replayed() {
+        let dir = TempDir::new().unwrap();
+        write_commit(
+            dir.path(),
+            0,
+            &[
+                json!({"metaData": {"partitionColumns": ["region", "day"]}}),
+                json!({"add": {"path": "region=eu/day=1/a.parquet", "size": 1,
+                    "partitionValues": {"region": "eu", "day": "1"}}}),
+                json!({"add": {"path": "region=__HIVE_DEFAULT_PARTITION__/day=2/b.parquet",
+                    "size": 1, "partitionValues": {"region": null, "day": "2"}}}),
+            ],
+        );
+
+        let log = read_delta_log(dir.path()).unwrap();
+        assert_eq!(log.config.partition_columns, vec!["region", "day"]);
+        let mut files = log.files;
+        files.sort_by(|a, b| a.path.cmp(&b.path));
+        assert_eq!(files[0].partition_values["region"], None);
+        assert_eq!(files[1].partition_values["region"].as_deref(), Some("eu"));
+        assert_eq!(files[1].partition_values["day"].as_deref(), Some("1"));
+    }
+
+    #[test]
+    fn test_replay_up_to_version() {
+        let dir = TempDir::new().unwrap();
+        write_commit(
+            dir.path(),
+            0,
+            &[json!({"add": {"path": "a.parquet", "size": 1}})],
+        );
+        write_commit(
+            dir.path(),
+            1,
+            &[json!({"remove": {"path": "a.parquet", "dataChange": true}})],
+        );
+
+        let log = read_delta_log_at(dir.path(), 0).unwrap();
+        assert_eq!(log.version, Some(0));
+        assert_eq!(log.files[0].path, "a.parquet");
+        assert_eq!(log_files_at(dir.path(), 0).unwrap().len(), 1);
+        assert!(read_delta_log_at(dir.path(), 2).is_err());
+
+        // Without commit 0 and no checkpoint, no version can be rebuilt
+        std::fs::remove_file(
+            dir.path()
+                .join("_delta_log")
+                .join(format!("{:020}.json", 0)),
+        )
+        .unwrap();
+        assert!(read_delta_log_at(dir.path(), 1).is_err());
+    }
+
+    #[test]
+    fn test_writability() {
+        let dir = TempDir::new().unwrap();
+        write_commit(
+            dir.path(),
+            0,
+            &[
+                json!({"protocol": {"minReaderVersion": 2, "minWriterVersion": 5}}),
+                column_mapped_metadata(),
+            ],
+        );
+        let config = read_table_config(dir.path()).unwrap();
+        assert!(config.check_readable().is_ok());
+        assert!(matches!(
+            config.check_writable(),
+            Err(Error::UnsupportedDeltaFeature(f)) if f == "columnMapping"
+        ));
+        assert_eq!(config.write_mode(), WriteMode::ReadOnly);
+
+        write_commit(
+            dir.path(),
+            1,
+            &[json!({"protocol": {
+                "minReaderVersion": 3,
+                "minWriterVersion": 7,
+                "readerFeatures": ["timestampNtz"],
+                "writerFeatures": ["timestampNtz", "deletionVectors"]
+            }})],
+        );
+        let config = read_table_config(dir.path()).unwrap();
+        assert!(config.check_readable().is_ok());
+        assert!(matches!(
+            config.check_writable(),
+            Err(Error::UnsupportedDeltaFeature(f)) if f == "deletionVectors"
+        ));
+    }
+
+    #[test]
+    fn test_append_only_write_mode() {
+        let dir = TempDir::new().unwrap();
+        write_commit(
+            dir.path(),
+            0,
+            &[
+                json!({"protocol": {"minReaderVersion": 1, "minWriterVersion": 2}}),
+                json!({"metaData": {
+                    "id": "t",
+                    "partitionColumns": [],
+                    "configuration": {"delta.appendOnly": "true"}
+                }}),
+            ],
+        );
+        let config = read_table_config(dir.path()).unwrap();
+        assert!(config.is_append_only());
+        assert_eq!(config.write_mode(), WriteMode::AppendOnly);
+
+        write_commit(
+            dir.path(),
+            1,
+            &[json!({"metaData": {"id": "t", "partitionColumns": [], "configuration": {}}})],
+        );
+        let config = read_table_config(dir.path()).unwrap();
+        assert_eq!(config.write_mode(), WriteMode::ReadWrite);
+    }
+
+    #[test]
+    fn test_v2_checkpoint_is_unsupported() {
+        let dir = TempDir::new().unwrap();
+        write_commit(dir.path(), 0, &[json!({"add": {"path": "a.parquet"}})]);
+        std::fs::write(
+            dir.path()
+                .join("_delta_log")
+                .join("00000000000000000000.checkpoint.80a083e8-7026-4e79-81be-64bd76c43a11.json"),
+            "{}",
+        )
+        .unwrap();
+
+        assert!(matches!(
+            read_delta_log(dir.path()),
+            Err(Error::UnsupportedDeltaFeature(f)) if f == "v2Checkpoint"
+        ));
+    }
+}
@@ -0,0 +1,259 @@
+//! Delta log retention and cleanup
+//!
+//! Every commit adds a JSON file to `_delta_log`, and checkpoints don't
+//! remove them. Once a checkpoint covers a commit, the commit is only needed
+//! to time travel to versions before that checkpoint. Cleanup deletes the log
+//! files older than the newest checkpoint from which every retained version
+//! can still be rebuilt: versions committed within the retention period, the
+//! latest version, and versions protected by open cursors or the keep-N
+//! window. `_last_checkpoint` is never touched.
+
+use super::log_replay::LogListing;
+use super::operations::VacuumProtection;
+use crate::Result;
+use std::path::Path;
+use std::time::{Duration, SystemTime};
+use tracing::{debug, info};
+
+/// How long commits stay in `_delta_log`, independent of VACUUM's retention
+#[derive(Debug, Clone)]
+pub struct LogRetentionConfig {
+    /// Versions committed less than this long ago stay reconstructable
+    pub retention: Duration,
+
+    /// Write a checkpoint, and clean up, once this many commits follow the
+    /// last checkpoint (None = only when `DatabaseOps::checkpoint` is called)
+    pub checkpoint_interval: Option<u64>,
+}
+
+impl Default for LogRetentionConfig {
+    fn default() -> Self {
+        Self {
+            retention: Duration::from_secs(30 * 24 * 60 * 60), // Delta's default of 30 days
+            checkpoint_interval: None,
+        }
+    }
+}
+
+/// Result of a log cleanup
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LogCleanupReport {
+    /// Checkpoint the remaining log starts from, if any
+    pub checkpoint_version: Option<u64>,
+
+    /// Commit, checkpoint and checksum files deleted
+    pub files_deleted: usize,
+}
+
+/// Commits in the log after its newest checkpoint
+pub fn commits_since_checkpoint(base_path: &Path) -> Result<u64> {
+    let listing = LogListing::read(&base_path.join("_delta_log"), None)?;
+    let checkpoint = listing.checkpoint.map(|(version, _)| version);
+    Ok(listing
+        .commits
+        .keys()
+        .filter(|version| checkpoint.is_none_or(|c| **version > c))
+        .count() as u64)
+}
+
+/// Delete log files no retained version needs
+///
+/// `now` is passed in so retention can be tested without waiting.
+pub fn cleanup_log(
+    base_path: &Path,
+    config: &LogRetentionConfig,
+    protection: &VacuumProtection,
+    now: SystemTime,
+) -> Result<LogCleanupReport> {
+    let log_dir = base_path.join("_delta_log");
+    let listing = LogListing::read(&log_dir, None)?;
+    let latest = listing
+        .commits
+        .keys()
+        .next_back()
+        .copied()
+        .max(listing.checkpoint.as_ref().map(|(version, _)| *version));
+    let Some(latest) = latest else {
+        return Ok(LogCleanupReport::default());
+    };
+
+    // The oldest version someone may still read
+    let cutoff = now
+        .checked_sub(config.retention)
+        .unwrap_or(SystemTime::UNIX_EPOCH);
+    let mut oldest = listing
+        .commits
+        .iter()
+        .find(|(_, path)| {
+            std::fs::metadata(path)
+                .and_then(|m| m.modified())
+                .is_ok_and(|modified| modified >= cutoff)
+        })
+        .map_or(latest, |(version, _)| *version);
+    if let Some(pinned) = protection.pinned_versions.iter().copied().min() {
+        oldest = oldest.min(pinned.max(0) as u64);
+    }
+    if protection.keep_versions > 1 {
+        oldest = oldest.min((latest + 1).saturating_sub(protection.keep_versions as u64));
+    }
+
+    // Rebuilding `oldest` starts from the newest checkpoint at or before it
+    let Some((checkpoint, _)) = LogListing::read(&log_dir, Some(oldest))?.checkpoint else {
+        debug!(
+            "No checkpoint at or before version {}; keeping the log",
+            oldest
+        );
+        return Ok(LogCleanupReport::default());
+    };
+
+    let mut files_deleted = 0;
+    for entry in std::fs::read_dir(&log_dir)? {
+        let path = entry?.path();
+        let Some(version) = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .and_then(log_file_version)
+        else {
+            continue;
+        };
+        if version < checkpoint {
+            std::fs::remove_file(&path)?;
+            files_deleted += 1;
+        }
+    }
+
+    info!(
+        "Log cleanup: {} files before checkpoint {} deleted (oldest retained version {})",
+        files_deleted, checkpoint, oldest
+    );
+    Ok(LogCleanupReport {
+        checkpoint_version: Some(checkpoint),
+        files_deleted,
+    })
+}
+
+/// Version of a commit, checkpoint or checksum file, named `{version:020}.*`
+fn log_file_version(name: &str) -> Option<u64> {
+    let (version, rest) = name.split_once('.')?;
+    let is_log_file = rest == "json"
+        || rest == "crc"
+        || (rest.starts_with("checkpoint.") && rest.ends_with(".parquet"));
+    if version.len() != 20 || !is_log_file {
+        return None;
+    }
+    version.parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn write_log(dir: &Path, names: &[&str]) {
+        let log_dir = dir.join("_delta_log");
+        std::fs::create_dir_all(&log_dir).unwrap();
+        for name in names {
+            std::fs::write(log_dir.join(name), b"").unwrap();
+        }
+    }
+
+    fn remaining(dir: &Path) -> Vec<String> {
+        let mut names: Vec<String> = std::fs::read_dir(dir.join("_delta_log"))
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+            .collect();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn test_log_file_version() {
+        assert_eq!(log_file_version("00000000000000000007.json"), Some(7));
+        assert_eq!(log_file_version("00000000000000000007.crc"), Some(7));
+        assert_eq!(
+            log_file_version("00000000000000000007.checkpoint.parquet"),
+            Some(7)
+        );
+        assert_eq!(
+            log_file_version("00000000000000000007.checkpoint.0000000001.0000000002.parquet"),
+            Some(7)
+        );
+        assert_eq!(log_file_version("_last_checkpoint"), None);
+        assert_eq!(log_file_version("00000000000000000007.json.tmp"), None);
+    }
+
+    #[test]
+    fn test_keeps_log_needed_by_protected_version() {
+        let dir = TempDir::new().unwrap();
+        write_log(
+            dir.path(),
+            &[
+                "00000000000000000000.json",
+                "00000000000000000001.json",
+                "00000000000000000002.json",
+                "00000000000000000002.checkpoint.parquet",
+                "00000000000000000003.json",
+                "00000000000000000004.json",
+                "00000000000000000004.checkpoint.parquet",
+                "00000000000000000005.json",
+                "_last_checkpoint",
+            ],
+        );
+        let config = LogRetentionConfig {
+            retention: Duration::ZERO,
+            checkpoint_interval: None,
+        };
+        let later = SystemTime::now() + Duration::from_secs(60);
+
+        // A cursor on version 3 needs checkpoint 2 and commit 3
+        let protection = VacuumProtection {
+            pinned_versions: vec![3],
+            keep_versions: 0,
+        };
+        let report = cleanup_log(dir.path(), &config, &protection, later).unwrap();
+        assert_eq!(report.checkpoint_version, Some(2));
+        assert_eq!(report.files_deleted, 2);
+        assert_eq!(
+            remaining(dir.path())[0],
+            "00000000000000000002.checkpoint.parquet"
+        );
+
+        // Once nothing is protected, everything before the newest checkpoint goes
+        let report = cleanup_log(dir.path(), &config, &VacuumProtection::default(), later).unwrap();
+        assert_eq!(report.files_deleted, 3);
+        assert_eq!(
+            remaining(dir.path()),
+            vec![
+                "00000000000000000004.checkpoint.parquet",
+                "00000000000000000004.json",
+                "00000000000000000005.json",
+                "_last_checkpoint",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_recent_commits_are_retained() {
+        let dir = TempDir::new().unwrap();
+        write_log(
+            dir.path(),
+            &[
+                "00000000000000000000.json",
+                "00000000000000000001.json",
+                "00000000000000000001.checkpoint.parquet",
+                "00000000000000000002.json",
+            ],
+        );
+
+        // Every commit is younger than the retention period
+        let report = cleanup_log(
+            dir.path(),
+            &LogRetentionConfig::default(),
+            &VacuumProtection::default(),
+            SystemTime::now(),
+        )
+        .unwrap();
+        assert_eq!(report.files_deleted, 0);
+        assert_eq!(remaining(dir.path()).len(), 4);
+    }
+}
@@ -0,0 +1,296 @@
+//! Table-level lock serializing OPTIMIZE, VACUUM and Z-ORDER
+//!
+//! Maintenance runs rewrite and delete the same files, so two of them at once
+//! on one table conflict on the Delta log at best. Every run takes the
+//! table's lock first. Within a process the lock is keyed by the table path,
+//! so separate `DatabaseOps` on one table share it. With `lock_file` set, a
+//! run also creates `_metadata/maintenance.lock`, which other processes
+//! sharing the table see.
+//!
+//! A run that finds the lock held waits for it or fails with
+//! `Error::MaintenanceInProgress`, per `MaintenanceConflict`. The lock is
+//! released when its `MaintenanceGuard` is dropped, so a run that errors or
+//! panics releases it too. A lock file left by a process that died is taken
+//! over once it's older than `stale_after`.
+
+use crate::{Error, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, LazyLock, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::sync::OwnedMutexGuard;
+use tracing::{debug, warn};
+
+/// In-process lock of each table, by path
+static TABLE_LOCKS: LazyLock<Mutex<HashMap<PathBuf, Arc<tokio::sync::Mutex<()>>>>> =
+    LazyLock::new(Default::default);
+
+/// How often a waiting run checks another process's lock file
+const LOCK_FILE_POLL: Duration = Duration::from_millis(100);
+
+/// What a maintenance run does when another holds the table's lock
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MaintenanceConflict {
+    /// Wait for the other run to finish
+    #[default]
+    Wait,
+    /// Fail with `Error::MaintenanceInProgress`
+    Fail,
+}
+
+/// How maintenance runs on a table are serialized
+#[derive(Debug, Clone)]
+pub struct MaintenanceLockConfig {
+    pub on_conflict: MaintenanceConflict,
+
+    /// Also hold `_metadata/maintenance.lock`, for processes sharing the
+    /// table. Only for local tables.
+    pub lock_file: bool,
+
+    /// Age after which a lock file is assumed to be left by a process that
+    /// died, and taken over
+    pub stale_after: Duration,
+}
+
+impl Default for MaintenanceLockConfig {
+    fn default() -> Self {
+        Self {
+            on_conflict: MaintenanceConflict::Wait,
+            lock_file: false,
+            stale_after: Duration::from_secs(60 * 60),
+        }
+    }
+}
+
+/// Contents of a lock file, naming its holder
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct LockFileOwner {
+    operation: String,
+    pid: u32,
+    acquired_ms: u64,
+}
+
+/// Held for the length of a maintenance run; dropping it releases the lock
+#[derive(Debug)]
+pub struct MaintenanceGuard {
+    _local: OwnedMutexGuard<()>,
+    lock_file: Option<PathBuf>,
+}
+
+impl Drop for MaintenanceGuard {
+    fn drop(&mut self) {
+        if let Some(path) = &self.lock_file {
+            if let Err(e) = std::fs::remove_file(path) {
+                warn!(
+                    "Failed to remove maintenance lock {}: {}",
+                    path.display(),
+                    e
+                );
+            }
+        }
+    }
+}
+
+/// Where a table's lock file is
+pub fn lock_file_path(base_path: &Path) -> PathBuf {
+    base_path.join("_metadata").join("maintenance.lock")
+}
+
+/// Take the maintenance lock of the table at `base_path` for `operation`
+///
+/// `use_lock_file` is false for tables whose path isn't a local directory,
+/// which then only get the in-process lock.
+pub async fn acquire(
+    base_path: &Path,
+    operation: &str,
+    config: &MaintenanceLockConfig,
+    use_lock_file: bool,
+) -> Result<MaintenanceGuard> {
+    let table_lock = TABLE_LOCKS
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .entry(base_path.to_path_buf())
+        .or_default()
+        .clone();
+
+    let local = match config.on_conflict {
+        MaintenanceConflict::Wait => table_lock.lock_owned().await,
+        MaintenanceConflict::Fail => table_lock.try_lock_owned().map_err(|_| {
+            Error::MaintenanceInProgress(format!(
+                "another run holds the lock of {}",
+                base_path.display()
+            ))
+        })?,
+    };
+
+    let lock_file = if config.lock_file && use_lock_file {
+        Some(acquire_lock_file(base_path, operation, config).await?)
+    } else {
+        None
+    };
+
+    debug!(
+        "{} holds maintenance lock of {}",
+        operation,
+        base_path.display()
+    );
+    Ok(MaintenanceGuard {
+        _local: local,
+        lock_file,
+    })
+}
+
+async fn acquire_lock_file(
+    base_path: &Path,
+    operation: &str,
+    config: &MaintenanceLockConfig,
+) -> Result<PathBuf> {
+    let path = lock_file_path(base_path);
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let owner = LockFileOwner {
+        operation: operation.to_string(),
+        pid: std::process::id(),
+        acquired_ms: now_ms(),
+    };
+
+    loop {
+        match std::fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&path)
+        {
+            Ok(mut file) => {
+                file.write_all(serde_json::to_string(&owner)?.as_bytes())?;
+                return Ok(path);
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => {}
+            Err(e) => return Err(e.into()),
+        }
+
+        let holder = std::fs::read_to_string(&path)
+            .ok()
+            .and_then(|json| serde_json::from_str::<LockFileOwner>(&json).ok());
+        // A file that doesn't parse, such as one still being written, ages
+        // by its modification time
+        let age = match &holder {
+            Some(h) => Some(Duration::from_millis(
+                now_ms().saturating_sub(h.acquired_ms),
+            )),
+            None => std::fs::metadata(&path)
+                .and_then(|m| m.modified())
+                .ok()
+                .and_then(|modified| modified.elapsed().ok()),
+        };
+        match age {
+            Some(age) if age > config.stale_after => {
+                warn!(
+                    "Taking over maintenance lock {} held for {:?}",
+                    path.display(),
+                    age
+                );
+                let _ = std::fs::remove_file(&path);
+                continue;
+            }
+            // Removed between the attempt and the read
+            None if !path.exists() => continue,
+            _ => {}
+        }
+
+        if config.on_conflict == MaintenanceConflict::Fail {
+            return Err(Error::MaintenanceInProgress(match holder {
+                Some(h) => format!("{} by process {}", h.operation, h.pid),
+                None => format!("lock file {} exists", path.display()),
+            }));
+        }
+        tokio::time::sleep(LOCK_FILE_POLL).await;
+    }
+}
+
+fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn failing(lock_file: bool) -> MaintenanceLockConfig {
+        MaintenanceLockConfig {
+            on_conflict: MaintenanceConflict::Fail,
+            lock_file,
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_second_run_fails_until_released() {
+        let temp_dir = TempDir::new().unwrap();
+        let guard = acquire(temp_dir.path(), "OPTIMIZE", &failing(false), true)
+            .await
+            .unwrap();
+        assert!(matches!(
+            acquire(temp_dir.path(), "VACUUM", &failing(false), true).await,
+            Err(Error::MaintenanceInProgress(_))
+        ));
+        drop(guard);
+        acquire(temp_dir.path(), "VACUUM", &failing(false), true)
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_lock_file_is_held_and_removed() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = lock_file_path(temp_dir.path());
+        let guard = acquire(temp_dir.path(), "OPTIMIZE", &failing(true), true)
+            .await
+            .unwrap();
+        assert!(path.exists());
+        drop(guard);
+        assert!(!path.exists());
+
+        // Another process's lock file
+        std::fs::write(
+            &path,
+            r#"{"operation":"VACUUM","pid":1,"acquired_ms":9999999999999}"#,
+        )
+        .unwrap();
+        let err = acquire(temp_dir.path(), "OPTIMIZE", &failing(true), true)
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("VACUUM by process 1"), "{}", err);
+
+        // Left by a process that died long ago
+        std::fs::write(&path, r#"{"operation":"VACUUM","pid":1,"acquired_ms":0}"#).unwrap();
+        acquire(temp_dir.path(), "OPTIMIZE", &failing(true), true)
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_guard_released_on_panic() {
+        let temp_dir = TempDir::new().unwrap();
+        let base = temp_dir.path().to_path_buf();
+        let task_base = base.clone();
+        let result = tokio::spawn(async move {
+            let _guard = acquire(&task_base, "OPTIMIZE", &failing(true), true)
+                .await
+                .unwrap();
+            panic!("maintenance failed");
+        })
+        .await;
+        assert!(result.is_err());
+        assert!(!lock_file_path(&base).exists());
+        acquire(&base, "OPTIMIZE", &failing(true), true)
+            .await
+            .unwrap();
+    }
+}
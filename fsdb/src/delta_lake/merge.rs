@@ -6,14 +6,20 @@
 //! Note: delta-rs 0.29.4 doesn't have native MERGE support, so we implement it
 //! using a combination of DataFusion queries and Delta Lake write/delete operations.
 
+use super::commit::CommitResult;
+use crate::security::{ColumnEncryption, ColumnKeys, LogRedaction, QuotaGuard};
+use crate::storage::limits::SizeLimits;
 use crate::{Error, Result};
+use arrow::array::{Array, ArrayRef, UInt64Array};
+use arrow::datatypes::{DataType, Field, Schema, SchemaRef};
 use arrow::record_batch::RecordBatch;
 use datafusion::prelude::*;
 use deltalake::operations::write::SchemaMode;
 use deltalake::protocol::SaveMode;
 use deltalake::{DeltaOps, DeltaTable};
+use std::collections::HashMap;
 use std::sync::Arc;
-use tracing::{debug, info};
+use tracing::{debug, info, warn};
 
 /// MERGE operation builder
 ///
@@ -45,10 +51,46 @@ use tracing::{debug, info};
 pub struct MergeBuilder {
     target: DeltaTable,
     source: Option<(RecordBatch, String)>, // (data, alias)
+    /// Target schema, for column defaults and generated columns
+    table_schema: Option<SchemaRef>,
+    /// Applied to SQL the merge logs
+    log_redaction: LogRedaction,
     join_condition: Option<String>,
-    matched_updates: Vec<MatchedUpdateClause>,
-    matched_deletes: Vec<MatchedDeleteClause>,
+    /// WHEN MATCHED clauses, in the order given
+    matched: Vec<MatchedClause>,
     not_matched_inserts: Vec<NotMatchedInsertClause>,
+    /// WHEN NOT MATCHED BY SOURCE clauses, in the order given
+    not_matched_by_source: Vec<MatchedClause>,
+    /// Recorded in the `commitInfo` of the merge's commits
+    commit_metadata: HashMap<String, String>,
+    /// Checked before the merge commits and charged after
+    quota: Option<QuotaGuard>,
+    /// Add columns the clauses write that the target lacks
+    schema_evolution: bool,
+    /// Checked against every row the merge writes
+    size_limits: SizeLimits,
+    /// Encrypted columns of the target, and the keys to write them with
+    column_encryption: Option<(ColumnEncryption, Option<Arc<ColumnKeys>>)>,
+}
+
+/// WHEN MATCHED, or WHEN NOT MATCHED BY SOURCE, clause
+///
+/// Clauses of a kind apply in order: a row takes the first whose condition
+/// it meets. Clauses for target rows no source row matches can only refer
+/// to target columns.
+#[derive(Debug, Clone)]
+pub enum MatchedClause {
+    Update(MatchedUpdateClause),
+    Delete(MatchedDeleteClause),
+}
+
+impl MatchedClause {
+    pub fn condition(&self) -> Option<&String> {
+        match self {
+            MatchedClause::Update(clause) => clause.condition.as_ref(),
+            MatchedClause::Delete(clause) => clause.condition.as_ref(),
+        }
+    }
 }
 
 /// Clause for WHEN MATCHED UPDATE
@@ -77,10 +119,17 @@ impl MergeBuilder {
         Self {
             target,
             source: None,
+            table_schema: None,
+            log_redaction: LogRedaction::default(),
             join_condition: None,
-            matched_updates: Vec::new(),
-            matched_deletes: Vec::new(),
+            matched: Vec::new(),
             not_matched_inserts: Vec::new(),
+            not_matched_by_source: Vec::new(),
+            commit_metadata: HashMap::new(),
+            quota: None,
+            schema_evolution: false,
+            size_limits: SizeLimits::default(),
+            column_encryption: None,
         }
     }
 
@@ -90,9 +139,84 @@ impl MergeBuilder {
         self
     }
 
+    /// Fill column defaults from `schema` into rows inserted by WHEN NOT
+    /// MATCHED INSERT, and compute its generated columns for inserted and
+    /// updated rows
+    pub fn with_table_schema(mut self, schema: SchemaRef) -> Self {
+        self.table_schema = Some(schema);
+        self
+    }
+
+    /// Redact SQL the merge writes to debug logs
+    pub fn with_log_redaction(mut self, policy: LogRedaction) -> Self {
+        self.log_redaction = policy;
+        self
+    }
+
+    /// Record `metadata` in the merge's commits, see
+    /// `DatabaseOps::with_commit_metadata`
+    pub fn with_commit_metadata(mut self, metadata: HashMap<String, String>) -> Self {
+        self.commit_metadata = metadata;
+        self
+    }
+
+    /// Meter the merge against `quota`
+    ///
+    /// The rows the merge adds are checked before anything is committed; its
+    /// bytes are only known once written, so they're charged afterwards and
+    /// can take the user past a byte limit.
+    pub fn with_quota(mut self, quota: QuotaGuard) -> Self {
+        self.quota = Some(quota);
+        self
+    }
+
+    /// Reject the merge if a row it inserts or updates is over `limits`,
+    /// before anything is written
+    pub fn with_size_limits(mut self, limits: SizeLimits) -> Self {
+        self.size_limits = limits;
+        self
+    }
+
+    /// Write the target's encrypted columns encrypted with `keys`
+    ///
+    /// The source's values for encrypted columns are encrypted before the
+    /// merge runs, and string literals compared with them in conditions as
+    /// by `ColumnEncryption::encrypt_predicate`. A clause can only set an
+    /// encrypted column to a string literal, NULL, or the source's or
+    /// target's value of the same column. Matching on an encrypted column
+    /// needs deterministic encryption.
+    pub fn with_column_encryption(
+        mut self,
+        encryption: ColumnEncryption,
+        keys: Option<Arc<ColumnKeys>>,
+    ) -> Self {
+        self.column_encryption = (!encryption.is_empty()).then_some((encryption, keys));
+        self
+    }
+
+    /// Add columns the merge writes that the target doesn't have yet
+    ///
+    /// Without it, a clause writing a column missing from the target, such
+    /// as `values_all` with a source holding a new column, fails the merge
+    /// before anything is written. With it, the new columns are added to the
+    /// target's schema as nullable columns in the merge's commit, and rows
+    /// written before it, or by clauses not setting them, read them as NULL.
+    pub fn with_schema_evolution(mut self, enabled: bool) -> Self {
+        self.schema_evolution = enabled;
+        self
+    }
+
     /// Set the join condition (e.g., "target.id = source.id")
+    ///
+    /// Calling `on` again adds to the condition with `AND`, so a composite key
+    /// can be given a column at a time. See [`MergeBuilder::execute`] for how
+    /// keys match.
     pub fn on(mut self, condition: impl Into<String>) -> Self {
-        self.join_condition = Some(condition.into());
+        let condition = condition.into();
+        self.join_condition = Some(match self.join_condition.take() {
+            Some(existing) => format!("{} AND {}", existing, condition),
+            None => condition,
+        });
         self
     }
 
@@ -122,6 +246,25 @@ impl MergeBuilder {
         }
     }
 
+    /// Add a WHEN NOT MATCHED BY SOURCE UPDATE clause, for target rows no
+    /// source row matches
+    pub fn when_not_matched_by_source_update(self) -> NotMatchedBySourceUpdateBuilder {
+        NotMatchedBySourceUpdateBuilder {
+            merge_builder: self,
+            condition: None,
+            updates: Vec::new(),
+        }
+    }
+
+    /// Add a WHEN NOT MATCHED BY SOURCE DELETE clause, for target rows no
+    /// source row matches
+    pub fn when_not_matched_by_source_delete(self) -> NotMatchedBySourceDeleteBuilder {
+        NotMatchedBySourceDeleteBuilder {
+            merge_builder: self,
+            condition: None,
+        }
+    }
+
     /// Execute the MERGE operation
     ///
     /// This performs the MERGE in multiple steps within a single Delta Lake transaction:
@@ -129,13 +272,87 @@ impl MergeBuilder {
     /// 2. Apply DELETE operations for matched rows (if any)
     /// 3. Apply UPDATE operations for matched rows (implemented as INSERT of new values)
     /// 4. Apply INSERT operations for unmatched rows
-    pub async fn execute(self) -> Result<MergeMetrics> {
+    ///
+    /// # Merge keys
+    ///
+    /// A join condition of `AND`ed equalities between target and source
+    /// columns, such as `target.a = source.a AND target.b = source.b`, is a
+    /// merge key: its columns must exist in both tables, and matched target
+    /// rows are replaced by key. As in SQL, a null in any key column matches
+    /// nothing, so a source row with a null key can only be inserted, and a
+    /// target row with one is never updated or deleted. Other conditions
+    /// identify matched target rows by the target's first integer column.
+    ///
+    /// With a WHEN MATCHED clause, several source rows matching the same
+    /// target row fail the merge with `Error::AmbiguousMerge` before anything
+    /// is written.
+    ///
+    /// # Clause order
+    ///
+    /// Like SQL MERGE, the WHEN MATCHED clauses are tried in the order they
+    /// were added, and a matched row is updated or deleted by the first whose
+    /// condition it meets; a condition that's NULL isn't met. The same goes
+    /// for WHEN NOT MATCHED INSERT and WHEN NOT MATCHED BY SOURCE clauses.
+    /// Clauses after one without a condition are never reached. Updates keep
+    /// the target's values of columns they don't set.
+    ///
+    /// WHEN NOT MATCHED BY SOURCE clauses identify target rows by the merge
+    /// key, so target rows with a null key are left alone.
+    pub async fn execute(mut self) -> Result<MergeMetrics> {
+        let metadata = std::mem::take(&mut self.commit_metadata);
+        super::commit::with_metadata(metadata, self.encrypted()?.run()).await
+    }
+
+    /// The merge with its source, conditions and values encrypted for the
+    /// target's encrypted columns, see `with_column_encryption`
+    fn encrypted(mut self) -> Result<Self> {
+        let Some((encryption, keys)) = self.column_encryption.take() else {
+            return Ok(self);
+        };
+        let keys = keys.as_deref();
+        if let Some((source, alias)) = self.source.take() {
+            self.source = Some((encryption.encrypt_batch(source, keys)?, alias));
+        }
+        let encrypt_condition = |condition: &mut Option<String>| -> Result<()> {
+            if let Some(predicate) = condition {
+                *predicate = encryption.encrypt_predicate(predicate, keys)?;
+            }
+            Ok(())
+        };
+        let encrypt_values = |values: &mut Vec<(String, String)>| -> Result<()> {
+            for (column, expr) in values.iter_mut() {
+                *expr = encryption.encrypt_assignment(column, expr, keys)?;
+            }
+            Ok(())
+        };
+        encrypt_condition(&mut self.join_condition)?;
+        for clause in self
+            .matched
+            .iter_mut()
+            .chain(self.not_matched_by_source.iter_mut())
+        {
+            match clause {
+                MatchedClause::Update(update) => {
+                    encrypt_condition(&mut update.condition)?;
+                    encrypt_values(&mut update.updates)?;
+                }
+                MatchedClause::Delete(delete) => encrypt_condition(&mut delete.condition)?,
+            }
+        }
+        for insert in &mut self.not_matched_inserts {
+            encrypt_condition(&mut insert.condition)?;
+            encrypt_values(&mut insert.values)?;
+        }
+        Ok(self)
+    }
+
+    async fn run(self) -> Result<MergeMetrics> {
         info!("Executing MERGE operation");
         info!(
-            "Clauses: {} updates, {} deletes, {} inserts",
-            self.matched_updates.len(),
-            self.matched_deletes.len(),
-            self.not_matched_inserts.len()
+            "Clauses: {} matched, {} not matched, {} not matched by source",
+            self.matched.len(),
+            self.not_matched_inserts.len(),
+            self.not_matched_by_source.len()
         );
 
         // Validate inputs
@@ -156,89 +373,174 @@ impl MergeBuilder {
         ctx.register_table("target", Arc::new(self.target.clone()))
             .map_err(|e| Error::Other(format!("Failed to register target table: {}", e)))?;
 
-        // Register source data
-        let source_schema = source_data.schema();
-        let source_table = datafusion::datasource::MemTable::try_new(
-            source_schema.clone(),
-            vec![vec![(*source_data).clone()]],
-        )
-        .map_err(|e| Error::Other(format!("Failed to create source table: {}", e)))?;
+        // Register source data, numbering its rows to tell apart source rows
+        // matching the same target row
+        let numbered = numbered_source(source_data)?;
+        let source_table =
+            datafusion::datasource::MemTable::try_new(numbered.schema(), vec![vec![numbered]])
+                .map_err(|e| Error::Other(format!("Failed to create source table: {}", e)))?;
 
         ctx.register_table(source_alias, Arc::new(source_table))
             .map_err(|e| Error::Other(format!("Failed to register source table: {}", e)))?;
 
-        let mut metrics = MergeMetrics::default();
+        let target_schema = ctx
+            .table_provider("target")
+            .await
+            .map_err(|e| Error::Other(format!("Failed to read target schema: {}", e)))?
+            .schema();
+        let new_columns = self.new_columns(&target_schema, &source_data.schema());
+        if !new_columns.is_empty() && !self.schema_evolution {
+            return Err(Error::InvalidOperation(format!(
+                "MERGE writes columns not in the target table: {} (see with_schema_evolution)",
+                new_columns
+                    .iter()
+                    .map(|f| f.name().as_str())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            )));
+        }
 
-        // PHASE 1: Collect all IDs to delete and all batches to insert
-        let mut all_ids_to_delete: Vec<i64> = Vec::new();
-        let mut all_batches_to_insert: Vec<RecordBatch> = Vec::new();
+        let merge_key = parse_merge_keys(join_condition, source_alias);
+        if let Some(keys) = &merge_key {
+            validate_merge_keys(keys, &target_schema, &source_data.schema(), source_alias)?;
+        }
 
-        // Process WHEN MATCHED DELETE clauses
-        for delete_clause in &self.matched_deletes {
-            let (ids, count) = self
-                .collect_matched_delete_ids(&ctx, source_alias, join_condition, delete_clause)
+        // Target columns identifying the rows matched clauses replace
+        let row_key = if self.matched.is_empty() && self.not_matched_by_source.is_empty() {
+            Vec::new()
+        } else {
+            match &merge_key {
+                Some(keys) => keys.iter().map(|(target, _)| target.clone()).collect(),
+                None => vec![first_integer_column(&target_schema)?],
+            }
+        };
+        if !self.matched.is_empty() {
+            self.check_unambiguous(&ctx, source_alias, join_condition, &row_key)
                 .await?;
-            all_ids_to_delete.extend(ids);
-            metrics.rows_deleted += count;
-            debug!("DELETE clause matched {} rows", count);
         }
 
-        // Process WHEN MATCHED UPDATE clauses (collect IDs to delete + batches to insert)
-        for update_clause in &self.matched_updates {
-            let (ids, batches, count) = self
-                .collect_matched_update_data(
-                    &ctx,
-                    source_alias,
-                    join_condition,
-                    update_clause,
-                    source_data,
-                )
-                .await?;
-            all_ids_to_delete.extend(ids);
-            all_batches_to_insert.extend(batches);
-            metrics.rows_updated += count;
-            debug!("UPDATE clause matched {} rows", count);
+        let mut metrics = MergeMetrics::default();
+
+        // PHASE 1: Collect all keys to delete and all batches to insert
+        let mut all_keys_to_delete: Vec<Vec<String>> = Vec::new();
+        let mut all_batches_to_insert: Vec<RecordBatch> = Vec::new();
+
+        // Process WHEN MATCHED and WHEN NOT MATCHED BY SOURCE clauses: an
+        // update deletes the old rows and inserts the new ones
+        let matched = format!("target INNER JOIN {} ON {}", source_alias, join_condition);
+        let not_matched_by_source = format!(
+            "target LEFT ANTI JOIN {} ON {}",
+            source_alias, join_condition
+        );
+        for (from, clauses) in [
+            (&matched, &self.matched),
+            (&not_matched_by_source, &self.not_matched_by_source),
+        ] {
+            let conditions: Vec<Option<&String>> =
+                clauses.iter().map(MatchedClause::condition).collect();
+            for (i, clause) in clauses.iter().enumerate() {
+                if !is_reachable(&conditions, i) {
+                    warn!(
+                        "MERGE clause {} follows one without a condition and is skipped",
+                        i + 1
+                    );
+                    continue;
+                }
+                let condition = ordered_condition(&conditions, i);
+                match clause {
+                    MatchedClause::Delete(_) => {
+                        let (keys, count) = self
+                            .collect_delete_keys(&ctx, from, &row_key, condition.as_deref())
+                            .await?;
+                        all_keys_to_delete.extend(keys);
+                        metrics.rows_deleted += count;
+                        debug!("DELETE clause matched {} rows", count);
+                    }
+                    MatchedClause::Update(update_clause) => {
+                        let updates = if update_clause.updates.is_empty() {
+                            source_columns(source_data, source_alias)
+                        } else {
+                            update_clause.updates.clone()
+                        };
+                        let (keys, batches, count) = self
+                            .collect_update_data(
+                                &ctx,
+                                from,
+                                &row_key,
+                                &target_schema,
+                                &updates,
+                                condition.as_deref(),
+                            )
+                            .await?;
+                        all_keys_to_delete.extend(keys);
+                        for batch in batches {
+                            all_batches_to_insert.push(self.fill_generated(batch).await?);
+                        }
+                        metrics.rows_updated += count;
+                        debug!("UPDATE clause matched {} rows", count);
+                    }
+                }
+            }
         }
 
         // Process WHEN NOT MATCHED INSERT clauses
-        for insert_clause in &self.not_matched_inserts {
+        let conditions: Vec<Option<&String>> = self
+            .not_matched_inserts
+            .iter()
+            .map(|clause| clause.condition.as_ref())
+            .collect();
+        for (i, insert_clause) in self.not_matched_inserts.iter().enumerate() {
+            if !is_reachable(&conditions, i) {
+                warn!(
+                    "MERGE clause {} follows one without a condition and is skipped",
+                    i + 1
+                );
+                continue;
+            }
             let (batches, count) = self
                 .collect_not_matched_insert_data(
                     &ctx,
                     source_alias,
                     join_condition,
                     insert_clause,
+                    ordered_condition(&conditions, i).as_deref(),
                     source_data,
                 )
                 .await?;
-            all_batches_to_insert.extend(batches);
+            for batch in batches {
+                all_batches_to_insert.push(self.fill_defaults(batch).await?);
+            }
             metrics.rows_inserted += count;
             debug!("INSERT clause matched {} rows", count);
         }
 
+        if let Some(quota) = &self.quota {
+            let added = metrics.rows_inserted.saturating_sub(metrics.rows_deleted);
+            quota.check(0, added as u64)?;
+        }
+        for batch in &all_batches_to_insert {
+            self.size_limits.check_batch(batch)?;
+        }
+
         // PHASE 2: Execute batched operations
         // First: DELETE all rows that need to be deleted (from DELETE and UPDATE clauses)
         let mut table_ref = self.target.clone();
-        if !all_ids_to_delete.is_empty() {
+        if !all_keys_to_delete.is_empty() {
             debug!(
                 "Executing batched DELETE for {} total rows",
-                all_ids_to_delete.len()
+                all_keys_to_delete.len()
             );
-            // Deduplicate IDs
-            all_ids_to_delete.sort_unstable();
-            all_ids_to_delete.dedup();
+            // Deduplicate keys
+            all_keys_to_delete.sort_unstable();
+            all_keys_to_delete.dedup();
 
-            let id_list = all_ids_to_delete
-                .iter()
-                .map(|id| id.to_string())
-                .collect::<Vec<_>>()
-                .join(", ");
-            let delete_predicate = format!("id IN ({})", id_list);
+            let delete_predicate = key_predicate(&row_key, &all_keys_to_delete);
 
             // DELETE returns the updated table - use it for subsequent operations
             let (updated_table, _metrics) = DeltaOps(table_ref)
                 .delete()
                 .with_predicate(delete_predicate)
+                .with_commit_properties(super::commit::commit_properties())
                 .await
                 .map_err(Error::DeltaTable)?;
             table_ref = updated_table;
@@ -254,56 +556,184 @@ impl MergeBuilder {
                 "Executing batched INSERT for {} batches in single operation",
                 all_batches_to_insert.len()
             );
+            if !new_columns.is_empty() {
+                info!(
+                    "MERGE adds {} column(s) to the target schema",
+                    new_columns.len()
+                );
+            }
+            // Updated rows come in the target's column order, inserted ones
+            // in the source's: give them all one schema
+            let schema = evolved_schema(&target_schema, &new_columns, &all_batches_to_insert);
+            all_batches_to_insert = all_batches_to_insert
+                .iter()
+                .map(|batch| conform(batch, &schema))
+                .collect::<Result<Vec<_>>>()?;
 
-            DeltaOps(table_ref)
+            table_ref = DeltaOps(table_ref)
                 .write(all_batches_to_insert)
                 .with_save_mode(SaveMode::Append)
                 .with_schema_mode(SchemaMode::Merge)
+                .with_commit_properties(super::commit::commit_properties())
                 .await
                 .map_err(Error::DeltaTable)?;
 
             debug!("Batched INSERT completed successfully");
         }
 
+        metrics.commit =
+            CommitResult::at(&table_ref, "MERGE", metrics.total_rows_affected() as u64).await?;
+        if let (Some(quota), Some(version)) = (&self.quota, table_ref.version()) {
+            let before = self.target.version().map(|v| v as u64);
+            if before != Some(version as u64) {
+                if let Err(e) = quota.charge(before, version as u64) {
+                    warn!("Failed to record quota usage of MERGE: {}", e);
+                }
+            }
+        }
         info!(
-            "MERGE completed: inserted={}, updated={}, deleted={}",
-            metrics.rows_inserted, metrics.rows_updated, metrics.rows_deleted
+            "MERGE completed: inserted={}, updated={}, deleted={} (version {})",
+            metrics.rows_inserted,
+            metrics.rows_updated,
+            metrics.rows_deleted,
+            metrics.commit.version
         );
 
         Ok(metrics)
     }
 
-    /// Collect IDs for WHEN MATCHED DELETE clause (doesn't execute DELETE)
-    async fn collect_matched_delete_ids(
+    /// Columns the update and insert clauses write that `target_schema`
+    /// lacks, typed as in `source_schema`
+    ///
+    /// Columns named by `set` or `value` but not in the source are typed
+    /// `Null` here, and take the type of the rows written.
+    fn new_columns(&self, target_schema: &Schema, source_schema: &Schema) -> Vec<Field> {
+        let all_columns = || {
+            source_schema
+                .fields()
+                .iter()
+                .filter(|f| f.name() != "_op")
+                .map(|f| f.name().clone())
+                .collect::<Vec<_>>()
+        };
+        let written = self
+            .matched
+            .iter()
+            .chain(&self.not_matched_by_source)
+            .filter_map(|clause| match clause {
+                MatchedClause::Update(clause) => Some(&clause.updates),
+                MatchedClause::Delete(_) => None,
+            })
+            .chain(self.not_matched_inserts.iter().map(|clause| &clause.values))
+            .flat_map(|columns| {
+                if columns.is_empty() {
+                    all_columns()
+                } else {
+                    columns.iter().map(|(column, _)| column.clone()).collect()
+                }
+            });
+
+        let mut new_columns: Vec<Field> = Vec::new();
+        for column in written {
+            let exists = |name: &str| name.eq_ignore_ascii_case(&column);
+            if target_schema.fields().iter().any(|f| exists(f.name()))
+                || new_columns.iter().any(|f| exists(f.name()))
+            {
+                continue;
+            }
+            let data_type = source_schema
+                .field_with_name(&column)
+                .map(|f| f.data_type().clone())
+                .unwrap_or(DataType::Null);
+            new_columns.push(Field::new(column, data_type, true));
+        }
+        new_columns
+    }
+
+    /// Fail with `Error::AmbiguousMerge` if more than one source row matches
+    /// the same target row, identified by `row_key`
+    async fn check_unambiguous(
         &self,
         ctx: &SessionContext,
         source_alias: &str,
         join_condition: &str,
-        clause: &MatchedDeleteClause,
-    ) -> Result<(Vec<i64>, usize)> {
-        debug!("Collecting MATCHED DELETE IDs");
+        row_key: &[String],
+    ) -> Result<()> {
+        let source_rows = format!("count(DISTINCT {}.{})", source_alias, SOURCE_ROW_COLUMN);
+        let sql = format!(
+            "SELECT {}, {} AS source_rows FROM target INNER JOIN {} ON {} GROUP BY {} HAVING {} > 1 LIMIT 1",
+            key_select_list(row_key),
+            source_rows,
+            source_alias,
+            join_condition,
+            row_key
+                .iter()
+                .map(|column| format!("target.{}", quote(column)))
+                .collect::<Vec<_>>()
+                .join(", "),
+            source_rows
+        );
+        debug!("Ambiguity SQL: {}", self.log_redaction.redact(&sql));
+
+        let batches = ctx
+            .sql(&sql)
+            .await
+            .map_err(|e| Error::Other(format!("Failed to check MERGE source keys: {}", e)))?
+            .collect()
+            .await
+            .map_err(|e| Error::Other(format!("Failed to check MERGE source keys: {}", e)))?;
+        let Some(batch) = batches.iter().find(|b| b.num_rows() > 0) else {
+            return Ok(());
+        };
+
+        let key = row_key
+            .iter()
+            .enumerate()
+            .map(|(i, column)| {
+                let value = sql_literal(batch.column(i), 0)?.unwrap_or_default();
+                Ok(format!("{} = {}", column, value))
+            })
+            .collect::<Result<Vec<_>>>()?
+            .join(", ");
+        let matches = arrow::compute::cast(batch.column(row_key.len()), &DataType::UInt64)?;
+        let matches = matches
+            .as_any()
+            .downcast_ref::<UInt64Array>()
+            .map(|counts| counts.value(0) as usize)
+            .unwrap_or_default();
+        Err(Error::AmbiguousMerge { key, matches })
+    }
+
+    /// Collect keys of the rows of `from` meeting `condition` that a
+    /// DELETE clause removes (doesn't execute DELETE)
+    async fn collect_delete_keys(
+        &self,
+        ctx: &SessionContext,
+        from: &str,
+        row_key: &[String],
+        condition: Option<&str>,
+    ) -> Result<(Vec<Vec<String>>, usize)> {
+        debug!("Collecting DELETE keys");
 
         // Build SQL to find matching rows to delete
-        let mut sql = format!(
-            "SELECT target.* FROM target INNER JOIN {} ON {}",
-            source_alias, join_condition
-        );
+        let mut sql = format!("SELECT {} FROM {}", key_select_list(row_key), from);
 
-        if let Some(condition) = &clause.condition {
+        if let Some(condition) = condition {
             sql.push_str(&format!(" WHERE {}", condition));
         }
 
-        debug!("DELETE SQL: {}", sql);
+        debug!("DELETE SQL: {}", self.log_redaction.redact(&sql));
 
         // Execute query to get matching rows
         let df = ctx
             .sql(&sql)
             .await
-            .map_err(|e| Error::Other(format!("Failed to execute MATCHED DELETE query: {}", e)))?;
+            .map_err(|e| Error::Other(format!("Failed to execute MERGE DELETE query: {}", e)))?;
 
-        let batches = df.collect().await.map_err(|e| {
-            Error::Other(format!("Failed to collect MATCHED DELETE results: {}", e))
-        })?;
+        let batches = df
+            .collect()
+            .await
+            .map_err(|e| Error::Other(format!("Failed to collect MERGE DELETE results: {}", e)))?;
 
         if batches.is_empty() {
             return Ok((Vec::new(), 0));
@@ -311,62 +741,96 @@ impl MergeBuilder {
 
         let row_count: usize = batches.iter().map(|b| b.num_rows()).sum();
 
-        // Extract IDs from matching rows
-        let ids = self.extract_ids_from_batches(&batches)?;
+        // Extract keys from matching rows
+        let keys = extract_keys(&batches, 0)?;
 
-        debug!("Collected {} IDs for deletion", ids.len());
-        Ok((ids, row_count))
+        debug!("Collected {} keys for deletion", keys.len());
+        Ok((keys, row_count))
     }
 
-    /// Collect data for WHEN MATCHED UPDATE clause (returns IDs to delete + batches to insert)
-    async fn collect_matched_update_data(
+    /// Collect data for an UPDATE clause over the rows of `from` meeting
+    /// `condition` (returns keys to delete + batches to insert)
+    ///
+    /// Target columns `updates` doesn't set keep their values.
+    async fn collect_update_data(
         &self,
         ctx: &SessionContext,
-        source_alias: &str,
-        join_condition: &str,
-        clause: &MatchedUpdateClause,
-        source_data: &RecordBatch,
-    ) -> Result<(Vec<i64>, Vec<RecordBatch>, usize)> {
-        debug!("Executing MATCHED UPDATE clause");
+        from: &str,
+        row_key: &[String],
+        target_schema: &Schema,
+        updates: &[(String, String)],
+        condition: Option<&str>,
+    ) -> Result<(Vec<Vec<String>>, Vec<RecordBatch>, usize)> {
+        debug!("Executing UPDATE clause");
 
         // Build SQL to find matching rows with updated values
-        let update_columns = if clause.updates.is_empty() {
-            // Update all columns (exclude _op column if present)
-            source_data
-                .schema()
-                .fields()
+        let update_of = |column: &str| {
+            updates
                 .iter()
-                .filter(|f| f.name() != "_op")
-                .map(|f| (f.name().clone(), format!("{}.{}", source_alias, f.name())))
-                .collect()
-        } else {
-            clause.updates.clone()
+                .find(|(set, _)| set.eq_ignore_ascii_case(column))
+                .map(|(_, expr)| expr.clone())
         };
-
-        let select_list = update_columns
+        // Generated columns are left out, to be recomputed from the new row
+        let generated = |column: &str| {
+            self.table_schema
+                .as_ref()
+                .and_then(|schema| schema.field_with_name(column).ok())
+                .and_then(|field| crate::metadata::generated_expr(field))
+        };
+        if let Some((column, expr)) = updates
             .iter()
-            .map(|(col, expr)| format!("{} as {}", expr, col))
-            .collect::<Vec<_>>()
-            .join(", ");
+            .find_map(|(column, _)| generated(column).map(|expr| (column, expr)))
+        {
+            return Err(Error::InvalidOperation(format!(
+                "Column '{}' is generated as {} and can't be updated",
+                column, expr
+            )));
+        }
+        let mut select_list: Vec<String> = target_schema
+            .fields()
+            .iter()
+            .filter(|field| generated(field.name()).is_none())
+            .map(|field| {
+                let value = update_of(field.name())
+                    .unwrap_or_else(|| format!("target.{}", quote(field.name())));
+                format!("{} as {}", value, quote(field.name()))
+            })
+            .collect();
+        select_list.extend(
+            updates
+                .iter()
+                .filter(|(column, _)| {
+                    !target_schema
+                        .fields()
+                        .iter()
+                        .any(|f| f.name().eq_ignore_ascii_case(column))
+                })
+                .map(|(column, expr)| format!("{} as {}", expr, column)),
+        );
 
         let mut sql = format!(
-            "SELECT {} FROM target INNER JOIN {} ON {}",
-            select_list, source_alias, join_condition
+            "SELECT {}, {} FROM {}",
+            select_list.join(", "),
+            key_select_list(row_key),
+            from
         );
 
-        if let Some(condition) = &clause.condition {
+        if let Some(condition) = condition {
             sql.push_str(&format!(" WHERE {}", condition));
         }
 
+        debug!("UPDATE SQL: {}", self.log_redaction.redact(&sql));
+
         // Execute query
         let df = ctx
             .sql(&sql)
             .await
-            .map_err(|e| Error::Other(format!("Failed to execute MATCHED UPDATE query: {}", e)))?;
+            .map_err(|e| Error::Other(format!("Failed to execute MERGE UPDATE query: {}", e)))?;
 
-        let batches = df.collect().await.map_err(|e| {
-            Error::Other(format!("Failed to collect MATCHED UPDATE results: {}", e))
-        })?;
+        let batches = df
+            .collect()
+            .await
+            .map_err(|e| Error::Other(format!("Failed to collect MERGE UPDATE results: {}", e)))?;
 
         if batches.is_empty() {
             return Ok((Vec::new(), Vec::new(), 0));
@@ -374,14 +838,19 @@ impl MergeBuilder {
 
         let row_count: usize = batches.iter().map(|b| b.num_rows()).sum();
 
-        // Extract IDs to delete
-        let ids = self.extract_ids_from_batches(&batches)?;
+        // Extract keys to delete, leaving the updated rows
+        let keys = extract_keys(&batches, select_list.len())?;
+        let updated_columns: Vec<usize> = (0..select_list.len()).collect();
+        let batches = batches
+            .iter()
+            .map(|batch| batch.project(&updated_columns))
+            .collect::<std::result::Result<Vec<_>, _>>()?;
 
         debug!(
-            "Collected {} rows for update (IDs to delete + batches to insert)",
+            "Collected {} rows for update (keys to delete + batches to insert)",
             row_count
         );
-        Ok((ids, batches, row_count))
+        Ok((keys, batches, row_count))
     }
 
     /// Collect data for WHEN NOT MATCHED INSERT clause (returns batches to insert)
@@ -391,20 +860,14 @@ impl MergeBuilder {
         source_alias: &str,
         join_condition: &str,
         clause: &NotMatchedInsertClause,
+        condition: Option<&str>,
         source_data: &RecordBatch,
     ) -> Result<(Vec<RecordBatch>, usize)> {
         debug!("Executing NOT MATCHED INSERT clause");
 
         // Build SQL to find non-matching rows (LEFT ANTI JOIN)
         let insert_columns = if clause.values.is_empty() {
-            // Insert all columns (exclude _op column if present)
-            source_data
-                .schema()
-                .fields()
-                .iter()
-                .filter(|f| f.name() != "_op")
-                .map(|f| (f.name().clone(), format!("{}.{}", source_alias, f.name())))
-                .collect()
+            source_columns(source_data, source_alias)
         } else {
             clause.values.clone()
         };
@@ -420,7 +883,7 @@ impl MergeBuilder {
             select_list, source_alias, join_condition
         );
 
-        if let Some(condition) = &clause.condition {
+        if let Some(condition) = condition {
             sql.push_str(&format!(" WHERE {}", condition));
         }
 
@@ -446,61 +909,287 @@ impl MergeBuilder {
         Ok((batches, row_count))
     }
 
-    /// Extract IDs from batches (assumes first Int32/Int64 column is ID)
-    fn extract_ids_from_batches(&self, batches: &[RecordBatch]) -> Result<Vec<i64>> {
-        use arrow::array::*;
-        use arrow::datatypes::DataType;
+    /// Fill column defaults into rows about to be inserted, then generated
+    /// columns, which may depend on them
+    async fn fill_defaults(&self, batch: RecordBatch) -> Result<RecordBatch> {
+        let Some(schema) = &self.table_schema else {
+            return Ok(batch);
+        };
+        let filled = crate::metadata::apply_defaults(batch, schema).await?;
+        self.fill_generated(filled).await
+    }
+
+    /// Compute generated columns into rows about to be written
+    ///
+    /// Fields stay nullable and metadata-free like the other batches of the
+    /// write, so all of them share one schema.
+    async fn fill_generated(&self, batch: RecordBatch) -> Result<RecordBatch> {
+        let Some(schema) = &self.table_schema else {
+            return Ok(batch);
+        };
+        let filled = crate::metadata::apply_generated(batch, schema).await?;
+        let fields: Vec<Field> = filled
+            .schema()
+            .fields()
+            .iter()
+            .map(|f| Field::new(f.name(), f.data_type().clone(), true))
+            .collect();
+        Ok(RecordBatch::try_new(
+            Arc::new(Schema::new(fields)),
+            filled.columns().to_vec(),
+        )?)
+    }
+}
+
+/// Every column of the source but `_op`, set from the source
+fn source_columns(source: &RecordBatch, source_alias: &str) -> Vec<(String, String)> {
+    source
+        .schema()
+        .fields()
+        .iter()
+        .filter(|f| f.name() != "_op")
+        .map(|f| (f.name().clone(), format!("{}.{}", source_alias, f.name())))
+        .collect()
+}
+
+/// Whether any row can reach the `i`th of ordered clauses: none before it
+/// lacks a condition
+fn is_reachable(conditions: &[Option<&String>], i: usize) -> bool {
+    conditions[..i].iter().all(Option::is_some)
+}
 
-        let mut ids = Vec::new();
+/// Condition of the rows the `i`th of ordered clauses applies to: its own,
+/// and none of the earlier clauses', None for every row
+///
+/// An earlier condition that's NULL for a row isn't met by it.
+fn ordered_condition(conditions: &[Option<&String>], i: usize) -> Option<String> {
+    let mut terms: Vec<String> = conditions[..i]
+        .iter()
+        .flatten()
+        .map(|condition| format!("NOT COALESCE(({}), false)", condition))
+        .collect();
+    terms.extend(conditions[i].map(|condition| format!("({})", condition)));
+    (!terms.is_empty()).then(|| terms.join(" AND "))
+}
 
-        for batch in batches {
-            let schema = batch.schema();
+/// `target` with `new_columns` appended, every field nullable
+///
+/// New columns set from expressions take their type from the first of
+/// `batches` holding them, and are left out if none does.
+fn evolved_schema(target: &Schema, new_columns: &[Field], batches: &[RecordBatch]) -> SchemaRef {
+    let typed = |field: &Field| match field.data_type() {
+        DataType::Null => batches
+            .iter()
+            .find_map(|batch| {
+                let schema = batch.schema();
+                schema
+                    .fields()
+                    .iter()
+                    .find(|f| f.name().eq_ignore_ascii_case(field.name()))
+                    .map(|f| f.data_type().clone())
+            })
+            .map(|data_type| Field::new(field.name(), data_type, true))
+            .unwrap_or_else(|| field.clone()),
+        _ => field.clone(),
+    };
+    let fields: Vec<Field> = target
+        .fields()
+        .iter()
+        .map(|f| Field::new(f.name(), f.data_type().clone(), true))
+        .chain(new_columns.iter().map(typed))
+        .filter(|f| f.data_type() != &DataType::Null)
+        .collect();
+    Arc::new(Schema::new(fields))
+}
 
-            // Find first integer column
-            let id_column = schema
+/// `batch` with the columns of `schema`, in its order, NULL where `batch`
+/// lacks them
+fn conform(batch: &RecordBatch, schema: &SchemaRef) -> Result<RecordBatch> {
+    let columns = schema
+        .fields()
+        .iter()
+        .map(|field| {
+            let position = batch
+                .schema()
                 .fields()
                 .iter()
-                .find(|f| matches!(f.data_type(), DataType::Int32 | DataType::Int64))
-                .ok_or_else(|| Error::Other("No integer column found for ID".to_string()))?;
-
-            let id_array = batch
-                .column_by_name(id_column.name())
-                .ok_or_else(|| Error::Other("ID column not found in batch".to_string()))?;
-
-            match id_array.data_type() {
-                DataType::Int32 => {
-                    let int_array =
-                        id_array
-                            .as_any()
-                            .downcast_ref::<Int32Array>()
-                            .ok_or_else(|| {
-                                Error::Other("Failed to downcast to Int32Array".to_string())
-                            })?;
-                    for i in 0..int_array.len() {
-                        if !int_array.is_null(i) {
-                            ids.push(int_array.value(i) as i64);
-                        }
-                    }
-                }
-                DataType::Int64 => {
-                    let int_array =
-                        id_array
-                            .as_any()
-                            .downcast_ref::<Int64Array>()
-                            .ok_or_else(|| {
-                                Error::Other("Failed to downcast to Int64Array".to_string())
-                            })?;
-                    for i in 0..int_array.len() {
-                        if !int_array.is_null(i) {
-                            ids.push(int_array.value(i));
-                        }
-                    }
+                .position(|f| f.name().eq_ignore_ascii_case(field.name()));
+            Ok(match position {
+                Some(i) => arrow::compute::cast(batch.column(i), field.data_type())?,
+                None => arrow::array::new_null_array(field.data_type(), batch.num_rows()),
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
+    Ok(RecordBatch::try_new(schema.clone(), columns)?)
+}
+
+/// Column numbering the source rows in the registered source table
+const SOURCE_ROW_COLUMN: &str = "__fsdb_merge_source_row";
+
+/// Prefix of the row key columns selected alongside matched rows
+const KEY_COLUMN_PREFIX: &str = "__fsdb_merge_key_";
+
+/// `source` with `SOURCE_ROW_COLUMN` appended
+fn numbered_source(source: &RecordBatch) -> Result<RecordBatch> {
+    let mut fields: Vec<Field> = source
+        .schema()
+        .fields()
+        .iter()
+        .map(|f| f.as_ref().clone())
+        .collect();
+    fields.push(Field::new(SOURCE_ROW_COLUMN, DataType::UInt64, false));
+    let mut columns = source.columns().to_vec();
+    columns.push(Arc::new(UInt64Array::from_iter_values(
+        0..source.num_rows() as u64,
+    )));
+    Ok(RecordBatch::try_new(
+        Arc::new(Schema::new(fields)),
+        columns,
+    )?)
+}
+
+/// The (target, source) column pairs of a join condition that's only
+/// `AND`ed equalities between target and source columns
+///
+/// None for any other condition. Unquoted identifiers are lowercased, as
+/// DataFusion does.
+fn parse_merge_keys(condition: &str, source_alias: &str) -> Option<Vec<(String, String)>> {
+    let tokens: Vec<&str> = condition.split_whitespace().collect();
+    let source_alias = source_alias.to_lowercase();
+    let mut keys = Vec::new();
+    for conjunct in tokens.split(|token| token.eq_ignore_ascii_case("AND")) {
+        let equality = conjunct.concat();
+        let (left, right) = equality.split_once('=')?;
+        let (left, right) = (qualified_column(left)?, qualified_column(right)?);
+        let (target, source) = if left.0 == "target" && right.0 == source_alias {
+            (left.1, right.1)
+        } else if right.0 == "target" && left.0 == source_alias {
+            (right.1, left.1)
+        } else {
+            return None;
+        };
+        keys.push((target, source));
+    }
+    (!keys.is_empty()).then_some(keys)
+}
+
+/// `table.column`, lowercased, if `text` is one
+fn qualified_column(text: &str) -> Option<(String, String)> {
+    let is_identifier = |name: &str| {
+        !name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+    };
+    let (table, column) = text.split_once('.')?;
+    (is_identifier(table) && is_identifier(column))
+        .then(|| (table.to_lowercase(), column.to_lowercase()))
+}
+
+/// Fail if a merge key column is missing from the target or source table
+fn validate_merge_keys(
+    keys: &[(String, String)],
+    target_schema: &Schema,
+    source_schema: &Schema,
+    source_alias: &str,
+) -> Result<()> {
+    for (target_column, source_column) in keys {
+        if target_schema.field_with_name(target_column).is_err() {
+            return Err(Error::InvalidOperation(format!(
+                "MERGE key column '{}' not found in target table",
+                target_column
+            )));
+        }
+        if source_schema.field_with_name(source_column).is_err() {
+            return Err(Error::InvalidOperation(format!(
+                "MERGE key column '{}' not found in source '{}'",
+                source_column, source_alias
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// First Int32/Int64 column of the target, taken as its row ID
+fn first_integer_column(schema: &Schema) -> Result<String> {
+    schema
+        .fields()
+        .iter()
+        .find(|f| matches!(f.data_type(), DataType::Int32 | DataType::Int64))
+        .map(|f| f.name().clone())
+        .ok_or_else(|| Error::Other("No integer column found for ID".to_string()))
+}
+
+fn quote(name: &str) -> String {
+    format!("\"{}\"", name.replace('"', "\"\""))
+}
+
+/// Select list of the target's `row_key` columns, as `KEY_COLUMN_PREFIX` columns
+fn key_select_list(row_key: &[String]) -> String {
+    row_key
+        .iter()
+        .enumerate()
+        .map(|(i, column)| format!("target.{} AS {}{}", quote(column), KEY_COLUMN_PREFIX, i))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Keys in the columns from `first_column` on, as SQL literals
+///
+/// Rows with a null in the key can't have matched, and are skipped.
+fn extract_keys(batches: &[RecordBatch], first_column: usize) -> Result<Vec<Vec<String>>> {
+    let mut keys = Vec::new();
+    for batch in batches {
+        let key_columns = &batch.columns()[first_column..];
+        'rows: for row in 0..batch.num_rows() {
+            let mut key = Vec::with_capacity(key_columns.len());
+            for column in key_columns {
+                match sql_literal(column, row)? {
+                    Some(value) => key.push(value),
+                    None => continue 'rows,
                 }
-                _ => return Err(Error::Other("Unsupported ID column type".to_string())),
             }
+            keys.push(key);
         }
+    }
+    Ok(keys)
+}
 
-        Ok(ids)
+/// Delete predicate matching the target rows with any of `keys`
+fn key_predicate(row_key: &[String], keys: &[Vec<String>]) -> String {
+    if let [column] = row_key {
+        let values = keys.iter().map(|key| key[0].as_str()).collect::<Vec<_>>();
+        return format!("{} IN ({})", quote(column), values.join(", "));
+    }
+    keys.iter()
+        .map(|key| {
+            let equalities = row_key
+                .iter()
+                .zip(key)
+                .map(|(column, value)| format!("{} = {}", quote(column), value))
+                .collect::<Vec<_>>();
+            format!("({})", equalities.join(" AND "))
+        })
+        .collect::<Vec<_>>()
+        .join(" OR ")
+}
+
+/// Value of `array` at `row` as a SQL literal, None if null
+fn sql_literal(array: &ArrayRef, row: usize) -> Result<Option<String>> {
+    if array.is_null(row) {
+        return Ok(None);
+    }
+    let text = arrow::util::display::array_value_to_string(array, row)?;
+    match array.data_type() {
+        DataType::Boolean => Ok(Some(text)),
+        data_type if data_type.is_numeric() => Ok(Some(text)),
+        DataType::Utf8
+        | DataType::LargeUtf8
+        | DataType::Utf8View
+        | DataType::Date32
+        | DataType::Date64
+        | DataType::Timestamp(_, _) => Ok(Some(format!("'{}'", text.replace('\'', "''")))),
+        other => Err(Error::InvalidOperation(format!(
+            "MERGE key columns of type {} aren't supported",
+            other
+        ))),
     }
 }
 
@@ -533,7 +1222,7 @@ impl MatchedUpdateBuilder {
         };
 
         let mut builder = self.merge_builder;
-        builder.matched_updates.push(clause);
+        builder.matched.push(MatchedClause::Update(clause));
         builder
     }
 
@@ -545,7 +1234,7 @@ impl MatchedUpdateBuilder {
         };
 
         let mut builder = self.merge_builder;
-        builder.matched_updates.push(clause);
+        builder.matched.push(MatchedClause::Update(clause));
         builder
     }
 }
@@ -570,7 +1259,7 @@ impl MatchedDeleteBuilder {
         };
 
         let mut builder = self.merge_builder;
-        builder.matched_deletes.push(clause);
+        builder.matched.push(MatchedClause::Delete(clause));
         builder
     }
 }
@@ -620,12 +1309,76 @@ impl NotMatchedInsertBuilder {
     }
 }
 
+/// Builder for WHEN NOT MATCHED BY SOURCE UPDATE clause
+pub struct NotMatchedBySourceUpdateBuilder {
+    merge_builder: MergeBuilder,
+    condition: Option<String>,
+    updates: Vec<(String, String)>,
+}
+
+impl NotMatchedBySourceUpdateBuilder {
+    /// Add a condition, on target columns, for this UPDATE clause
+    pub fn condition(mut self, condition: impl Into<String>) -> Self {
+        self.condition = Some(condition.into());
+        self
+    }
+
+    /// Set a column to an expression of target columns
+    pub fn set(mut self, column: impl Into<String>, expression: impl Into<String>) -> Self {
+        self.updates.push((column.into(), expression.into()));
+        self
+    }
+
+    /// Finish this clause and return to main builder
+    pub fn then(self) -> MergeBuilder {
+        let clause = MatchedUpdateClause {
+            condition: self.condition,
+            updates: self.updates,
+        };
+
+        let mut builder = self.merge_builder;
+        builder
+            .not_matched_by_source
+            .push(MatchedClause::Update(clause));
+        builder
+    }
+}
+
+/// Builder for WHEN NOT MATCHED BY SOURCE DELETE clause
+pub struct NotMatchedBySourceDeleteBuilder {
+    merge_builder: MergeBuilder,
+    condition: Option<String>,
+}
+
+impl NotMatchedBySourceDeleteBuilder {
+    /// Add a condition, on target columns, for this DELETE clause
+    pub fn condition(mut self, condition: impl Into<String>) -> Self {
+        self.condition = Some(condition.into());
+        self
+    }
+
+    /// Finish this clause and return to main builder
+    pub fn then(self) -> MergeBuilder {
+        let clause = MatchedDeleteClause {
+            condition: self.condition,
+        };
+
+        let mut builder = self.merge_builder;
+        builder
+            .not_matched_by_source
+            .push(MatchedClause::Delete(clause));
+        builder
+    }
+}
+
 /// Metrics from MERGE operation
 #[derive(Debug, Default, Clone)]
 pub struct MergeMetrics {
     pub rows_inserted: usize,
     pub rows_updated: usize,
     pub rows_deleted: usize,
+    /// Version the MERGE left the table at; unchanged if nothing matched
+    pub commit: CommitResult,
 }
 
 impl MergeMetrics {
@@ -633,3 +1386,64 @@ impl MergeMetrics {
         self.rows_inserted + self.rows_updated + self.rows_deleted
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_merge_keys() {
+        let keys = |condition| parse_merge_keys(condition, "src");
+        assert_eq!(
+            keys("target.a = src.a AND src.B=target.b"),
+            Some(vec![
+                ("a".to_string(), "a".to_string()),
+                ("b".to_string(), "b".to_string())
+            ])
+        );
+        assert_eq!(
+            keys("target.id = src.user_id"),
+            Some(vec![("id".to_string(), "user_id".to_string())])
+        );
+
+        // Anything but equalities between the two tables isn't a key
+        assert_eq!(keys("target.a = src.a AND src._op = 'UPDATE'"), None);
+        assert_eq!(keys("target.a >= src.a"), None);
+        assert_eq!(keys("target.a = src.a OR target.b = src.b"), None);
+        assert_eq!(keys("target.a = other.a"), None);
+    }
+
+    #[test]
+    fn test_ordered_condition() {
+        let (a, b) = ("s.op = 'd'".to_string(), "s.v > 1".to_string());
+        let conditions = vec![Some(&a), Some(&b), None, Some(&a)];
+        assert_eq!(ordered_condition(&conditions, 0).unwrap(), "(s.op = 'd')");
+        assert_eq!(
+            ordered_condition(&conditions, 1).unwrap(),
+            "NOT COALESCE((s.op = 'd'), false) AND (s.v > 1)"
+        );
+        assert_eq!(
+            ordered_condition(&conditions, 2).unwrap(),
+            "NOT COALESCE((s.op = 'd'), false) AND NOT COALESCE((s.v > 1), false)"
+        );
+        assert_eq!(ordered_condition(&[None], 0), None);
+        assert!(is_reachable(&conditions, 2));
+        assert!(!is_reachable(&conditions, 3));
+    }
+
+    #[test]
+    fn test_key_predicate() {
+        let keys = vec![
+            vec!["1".to_string(), "'x'".to_string()],
+            vec!["2".to_string(), "'it''s'".to_string()],
+        ];
+        assert_eq!(
+            key_predicate(&["a".to_string(), "b".to_string()], &keys),
+            r#"("a" = 1 AND "b" = 'x') OR ("a" = 2 AND "b" = 'it''s')"#
+        );
+        assert_eq!(
+            key_predicate(&["id".to_string()], &keys[..1]),
+            r#""id" IN (1)"#
+        );
+    }
+}
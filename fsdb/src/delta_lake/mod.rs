@@ -2,14 +2,62 @@
 //!
 //! Native Delta Lake format support with column statistics and operations.
 
+pub mod change_feed;
+pub mod changes;
+pub mod clustering;
+pub mod commit;
 pub mod data_skipping;
+pub mod file_naming;
+pub mod file_size;
+pub mod histogram;
+pub mod history;
+pub mod integrity;
+pub mod log_replay;
+pub mod log_retention;
+pub mod maintenance_lock;
 pub mod merge;
 pub mod operations;
+pub mod partitions;
+pub mod recovery;
+pub mod scheduler;
+pub mod sketches;
 pub mod stats;
+pub mod stats_config;
+pub mod zorder;
 
-pub use data_skipping::{can_skip_file, extract_predicates, get_file_statistics, FileStats};
+pub use change_feed::ChangeType;
+pub use changes::{read_change, ChangeFollower, TableChange};
+pub use commit::CommitResult;
+pub use data_skipping::{
+    can_skip_file, can_skip_file_with_collation, extract_predicates, file_match,
+    get_file_statistics, parse_conjunction, FileMatch, FileStats,
+};
+pub use file_naming::FileNaming;
+pub use file_size::split_to_target_size;
+pub use histogram::{EquiDepthHistogram, HistogramBucket, TableHistograms};
+pub use history::{read_history, CommitInfo};
+pub use integrity::{IntegrityIssue, IntegrityReport};
+pub use log_replay::{
+    latest_version, log_files_at, read_commits_at, read_delta_log, read_delta_log_at,
+    read_table_config, DeltaLog, TableConfig, WriteMode,
+};
+pub use log_retention::{
+    cleanup_log, commits_since_checkpoint, LogCleanupReport, LogRetentionConfig,
+};
+pub use maintenance_lock::{MaintenanceConflict, MaintenanceGuard, MaintenanceLockConfig};
 pub use merge::{
-    MatchedDeleteClause, MatchedUpdateClause, MergeBuilder, MergeMetrics, NotMatchedInsertClause,
+    MatchedClause, MatchedDeleteClause, MatchedUpdateClause, MergeBuilder, MergeMetrics,
+    NotMatchedInsertClause,
+};
+pub use operations::{
+    count_small_files, optimize_table, vacuum_dry_run, vacuum_table, zorder_table, AutoCompaction,
+    MaintenanceControl, MaintenanceProgress, MaintenanceProgressCallback, OptimizeMetrics,
+    VacuumProtection, VacuumReport, VacuumRetention,
 };
-pub use operations::{optimize_table, vacuum_dry_run, vacuum_table, zorder_table, OptimizeMetrics};
+pub use partitions::{list_partitions, PartitionValues};
+pub use recovery::{recover, RecoveryConfig, RecoveryReport};
+pub use scheduler::{MaintenancePolicy, MaintenanceStatus};
+pub use sketches::FileSketches;
 pub use stats::{compute_column_statistics, get_column_statistics_from_delta, ColumnStats};
+pub use stats_config::{ColumnStatsConfig, StatsConfig};
+pub use zorder::{zorder_incremental, ZORDER_COLUMNS_PROPERTY};
@@ -4,9 +4,11 @@
 //! that improve performance and manage storage.
 
 use crate::{Error, Result};
-use deltalake::{open_table, open_table_with_storage_options, DeltaOps};
+use deltalake::{open_table, DeltaOps, DeltaTable};
 use std::collections::HashMap;
 use std::path::Path;
+use std::sync::Arc;
+use tokio_util::sync::CancellationToken;
 use tracing::info;
 use url::Url;
 
@@ -18,39 +20,350 @@ pub struct OptimizeMetrics {
     pub total_files_skipped: u64,
     pub total_considered_files: u64,
     pub preserve_insertion_order: bool,
+    pub partitions_optimized: u64,
+    pub bytes_added: u64,
+    pub bytes_removed: u64,
+    /// Live files in the optimized partitions before the run
+    pub files_before: u64,
+    /// Live files in the optimized partitions after the run
+    pub files_after: u64,
+    /// True if the run was cancelled; the metrics cover what was committed
+    pub cancelled: bool,
+}
+
+impl From<&deltalake::operations::optimize::Metrics> for OptimizeMetrics {
+    fn from(metrics: &deltalake::operations::optimize::Metrics) -> Self {
+        // Every live file of the optimized partitions is considered, rewritten or not
+        let files_before = metrics.total_considered_files as u64;
+        Self {
+            num_files_added: metrics.num_files_added,
+            num_files_removed: metrics.num_files_removed,
+            total_files_skipped: metrics.total_files_skipped as u64,
+            total_considered_files: metrics.total_considered_files as u64,
+            preserve_insertion_order: metrics.preserve_insertion_order,
+            partitions_optimized: metrics.partitions_optimized,
+            bytes_added: metrics.files_added.total_size.max(0) as u64,
+            bytes_removed: metrics.files_removed.total_size.max(0) as u64,
+            files_before,
+            files_after: (files_before + metrics.num_files_added)
+                .saturating_sub(metrics.num_files_removed),
+            cancelled: false,
+        }
+    }
+}
+
+impl OptimizeMetrics {
+    /// Add the metrics of another run over different partitions
+    fn accumulate(&mut self, other: &OptimizeMetrics) {
+        self.num_files_added += other.num_files_added;
+        self.num_files_removed += other.num_files_removed;
+        self.total_files_skipped += other.total_files_skipped;
+        self.total_considered_files += other.total_considered_files;
+        self.partitions_optimized += other.partitions_optimized;
+        self.bytes_added += other.bytes_added;
+        self.bytes_removed += other.bytes_removed;
+        self.files_before += other.files_before;
+        self.files_after += other.files_after;
+    }
+
+    /// Add the metrics of a later run over the same files
+    pub(crate) fn follow(&mut self, later: &OptimizeMetrics) {
+        self.num_files_added += later.num_files_added;
+        self.num_files_removed += later.num_files_removed;
+        self.bytes_added += later.bytes_added;
+        self.bytes_removed += later.bytes_removed;
+        self.files_after =
+            (self.files_after + later.num_files_added).saturating_sub(later.num_files_removed);
+    }
+}
+
+/// Callback invoked as an OPTIMIZE or VACUUM makes progress
+pub type MaintenanceProgressCallback = Arc<dyn Fn(&MaintenanceProgress) + Send + Sync>;
+
+/// Snapshot of a running OPTIMIZE or VACUUM, passed to the progress callback
+#[derive(Debug, Clone, Default)]
+pub struct MaintenanceProgress {
+    /// Files compacted or deleted so far
+    pub files_processed: u64,
+
+    /// Files the run will process in total, when known up front
+    pub files_total: Option<u64>,
+
+    /// Bytes freed so far: deleted files for VACUUM, and for OPTIMIZE how
+    /// much smaller the new files are than the ones they replace
+    pub bytes_reclaimed: u64,
+}
+
+/// Cancellation and progress reporting for OPTIMIZE and VACUUM
+///
+/// Cancellation is checked between units of work that each leave the table
+/// consistent: between partitions for OPTIMIZE, each committed on its own,
+/// and between files for VACUUM. Work done before the cancellation stays done,
+/// and the result reports it with `cancelled` set.
+#[derive(Clone, Default)]
+pub struct MaintenanceControl {
+    /// Cancel to stop at the next safe point
+    pub cancel: CancellationToken,
+
+    /// Called after each unit of work
+    pub progress: Option<MaintenanceProgressCallback>,
+}
+
+impl std::fmt::Debug for MaintenanceControl {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("MaintenanceControl")
+            .field("cancelled", &self.cancel.is_cancelled())
+            .field("progress", &self.progress.is_some())
+            .finish()
+    }
+}
+
+impl MaintenanceControl {
+    /// Stop at the next safe point once `cancel` is cancelled
+    pub fn with_cancellation(mut self, cancel: CancellationToken) -> Self {
+        self.cancel = cancel;
+        self
+    }
+
+    /// Report progress through `callback`
+    pub fn with_progress(
+        mut self,
+        callback: impl Fn(&MaintenanceProgress) + Send + Sync + 'static,
+    ) -> Self {
+        self.progress = Some(Arc::new(callback));
+        self
+    }
+
+    fn report(&self, progress: &MaintenanceProgress) {
+        if let Some(callback) = &self.progress {
+            callback(progress);
+        }
+    }
+}
+
+/// When to compact small files automatically after a write
+#[derive(Debug, Clone)]
+pub struct AutoCompaction {
+    /// Files smaller than this many bytes count as small
+    pub small_file_bytes: u64,
+
+    /// Compact once more than this many small files could be merged
+    pub max_small_files: usize,
+
+    /// Size OPTIMIZE bin-packs files up to (None uses the target file size,
+    /// or OPTIMIZE's default of 100 MiB)
+    pub target_size: Option<u64>,
+}
+
+impl Default for AutoCompaction {
+    fn default() -> Self {
+        Self {
+            small_file_bytes: 16 * 1024 * 1024,
+            max_small_files: 100,
+            target_size: None,
+        }
+    }
+}
+
+/// Number of small files OPTIMIZE could merge, from the local Delta log
+///
+/// OPTIMIZE only bin-packs files within a partition, so a partition's only
+/// small file isn't counted: it has nothing to be merged with, and counting
+/// it would make a table with many small partitions compact on every write.
+pub fn count_small_files(base_path: &Path, small_file_bytes: u64) -> Result<usize> {
+    let log = super::read_delta_log(base_path)?;
+    let mut per_partition: HashMap<_, usize> = HashMap::new();
+    for file in log.files.iter().filter(|f| f.size < small_file_bytes) {
+        *per_partition.entry(&file.partition_values).or_default() += 1;
+    }
+    Ok(per_partition.into_values().filter(|n| *n > 1).sum())
+}
+
+/// Result of a VACUUM run
+#[derive(Debug, Clone, Default)]
+pub struct VacuumReport {
+    /// Files deleted, or that would be deleted in a dry run
+    pub files_deleted: u64,
+
+    /// Bytes freed by the deleted files (measured for local tables only)
+    pub bytes_reclaimed: u64,
+
+    /// True if nothing was actually deleted
+    pub dry_run: bool,
+
+    /// True if the run was cancelled before deleting every candidate
+    pub cancelled: bool,
+
+    /// Oldest version still readable for time travel once the files are
+    /// gone (None for an empty table)
+    ///
+    /// Conservative: an older version can stay readable if the commits
+    /// after it removed no files.
+    pub oldest_retained_version: Option<i64>,
+}
+
+/// How long VACUUM keeps removed files, and the shortest it may be told to
+#[derive(Debug, Clone, Default)]
+pub struct VacuumRetention {
+    /// Files removed from the table more recently than this are kept
+    pub retention: std::time::Duration,
+
+    /// Shortest `retention` accepted without `force`, so that time travel
+    /// to recent versions keeps working
+    pub min_retention: std::time::Duration,
+
+    /// Run with a `retention` below `min_retention`
+    pub force: bool,
+}
+
+impl VacuumRetention {
+    /// Fail with `Error::InvalidOperation` if `retention` is below
+    /// `min_retention` and not forced
+    pub fn check(&self) -> Result<()> {
+        if self.retention < self.min_retention && !self.force {
+            return Err(Error::InvalidOperation(format!(
+                "VACUUM retention of {} is below the minimum of {}; \
+                 files older versions need for time travel would be deleted (use force to override)",
+                format_retention(self.retention),
+                format_retention(self.min_retention)
+            )));
+        }
+        Ok(())
+    }
+
+    /// `retention` for delta-rs
+    fn to_chrono(&self) -> Result<chrono::Duration> {
+        chrono::Duration::from_std(self.retention).map_err(|_| {
+            Error::Other(format!(
+                "Invalid retention: {}",
+                format_retention(self.retention)
+            ))
+        })
+    }
+}
+
+/// A retention as whole hours where it is one, seconds otherwise
+pub fn format_retention(retention: std::time::Duration) -> String {
+    let secs = retention.as_secs();
+    if secs % 3600 == 0 {
+        format!("{}h", secs / 3600)
+    } else {
+        format!("{}s", secs)
+    }
+}
+
+/// Oldest version whose files VACUUM with `retention` leaves in place
+///
+/// A file is deleted once the commit that removed it is older than the
+/// retention, so the newest such commit is the oldest version whose files
+/// are all kept; with none, every version in the log is.
+async fn oldest_retained_version(
+    table: &DeltaTable,
+    retention: chrono::Duration,
+) -> Result<Option<i64>> {
+    let Some(latest) = table.version() else {
+        return Ok(None);
+    };
+    let cutoff = chrono::Utc::now().timestamp_millis() - retention.num_milliseconds();
+    // Newest first, one commit per version
+    let history = table.history(None).await.map_err(Error::DeltaTable)?;
+    let mut oldest = latest;
+    for (age, commit) in history.into_iter().enumerate() {
+        let version = latest - age as i64;
+        if commit.timestamp.is_some_and(|timestamp| timestamp < cutoff) {
+            return Ok(Some(version));
+        }
+        oldest = version;
+    }
+    Ok(Some(oldest.max(0)))
+}
+
+/// Versions that VACUUM must leave readable
+///
+/// Files referenced by these versions are kept even when their tombstones are
+/// older than the requested retention period. Log cleanup keeps the log
+/// files needed to rebuild them the same way.
+#[derive(Debug, Clone, Default)]
+pub struct VacuumProtection {
+    /// Versions pinned by open cursors
+    pub pinned_versions: Vec<i64>,
+
+    /// Number of most recent versions to keep readable (0 or 1 keeps only the latest)
+    pub keep_versions: usize,
+}
+
+/// Widen the retention period so no protected version loses its files
+///
+/// A file that a protected version reads can only have been tombstoned by a
+/// later commit, so keeping every tombstone newer than the oldest protected
+/// commit keeps that version readable.
+async fn protected_retention(
+    table: &DeltaTable,
+    retention: chrono::Duration,
+    protection: &VacuumProtection,
+) -> Result<chrono::Duration> {
+    let Some(latest) = table.version() else {
+        return Ok(retention);
+    };
+
+    let mut oldest = protection.pinned_versions.iter().copied().min();
+    if protection.keep_versions > 1 {
+        let kept = (latest - protection.keep_versions as i64 + 1).max(0);
+        oldest = Some(oldest.map_or(kept, |v| v.min(kept)));
+    }
+
+    // The latest version's files are never vacuumed
+    let Some(oldest) = oldest.filter(|v| *v < latest) else {
+        return Ok(retention);
+    };
+
+    let limit = (latest - oldest.max(0) + 1) as usize;
+    let oldest_commit = table
+        .history(Some(limit))
+        .await
+        .map_err(Error::DeltaTable)?
+        .into_iter()
+        .filter_map(|commit| commit.timestamp)
+        .min();
+    let Some(oldest_commit) = oldest_commit else {
+        return Ok(retention);
+    };
+
+    // One second of slack for clock differences between writers
+    let needed = chrono::Duration::milliseconds(
+        chrono::Utc::now().timestamp_millis() - oldest_commit + 1000,
+    );
+    if needed > retention {
+        info!(
+            "VACUUM retention extended to {}s to protect version {}",
+            needed.num_seconds(),
+            oldest
+        );
+        Ok(needed)
+    } else {
+        Ok(retention)
+    }
 }
 
 /// Execute OPTIMIZE operation on a Delta Lake table
+///
+/// Local partitioned tables are optimized one partition at a time, each in
+/// its own commit, so that `control` can stop between partitions. Other
+/// tables are a single unit of work.
+///
+/// Local tables with clustering columns in the `fsdb.zorderColumns`
+/// property then get an incremental Z-ORDER, which rewrites the files
+/// whose ranges on those columns overlap; see `zorder::zorder_incremental`.
 pub async fn optimize_table(
     base_path: &Path,
     s3_url: Option<&str>,
-    s3_storage_options: Option<&HashMap<String, String>>,
+    s3_config: Option<&crate::storage::s3::S3Config>,
     filter: Option<&str>,
     target_size: Option<u64>,
+    control: &MaintenanceControl,
 ) -> Result<OptimizeMetrics> {
     use crate::storage::s3::parse_s3_url;
-
-    // Open the Delta Lake table (S3 or local)
-    let table = if let (Some(s3_url), Some(storage_options)) = (s3_url, s3_storage_options) {
-        // S3 backend
-        let s3_url_parsed = parse_s3_url(s3_url)?;
-        open_table_with_storage_options(s3_url_parsed, storage_options.clone())
-            .await
-            .map_err(Error::DeltaTable)?
-    } else {
-        // Local backend
-        let table_url = Url::from_directory_path(base_path)
-            .map_err(|_| Error::Other("Invalid path for Delta table".to_string()))?;
-        open_table(table_url).await.map_err(Error::DeltaTable)?
-    };
-
-    // Build optimize operation
-    let mut optimize_builder = DeltaOps(table).optimize();
-
-    // Add target file size if provided
-    if let Some(size) = target_size {
-        optimize_builder = optimize_builder.with_target_size(size);
-    }
+    use deltalake::PartitionFilter;
 
     // Note: Filter support requires PartitionFilter construction
     // which is partition-column based, not arbitrary SQL predicates
@@ -60,39 +373,136 @@ pub async fn optimize_table(
         ));
     }
 
-    // Execute optimize - returns (DeltaTable, Metrics)
-    let (_table, delta_metrics) = optimize_builder.await.map_err(Error::DeltaTable)?;
+    // Partitions to optimize separately; a partition with a null value can't
+    // be selected by a filter, so such tables run as one unit
+    let (units, files_total) = if s3_url.is_none() {
+        let log = super::read_delta_log(base_path)?;
+        let mut partitions: Vec<_> = log.files.iter().map(|f| &f.partition_values).collect();
+        partitions.sort();
+        partitions.dedup();
+        let units = if !partitions.is_empty()
+            && partitions
+                .iter()
+                .all(|p| !p.is_empty() && p.values().all(Option::is_some))
+        {
+            partitions
+                .into_iter()
+                .map(|values| {
+                    values
+                        .iter()
+                        .map(|(column, value)| {
+                            PartitionFilter::try_from((
+                                column.as_str(),
+                                "=",
+                                value.as_deref().unwrap_or_default(),
+                            ))
+                            .map_err(Error::DeltaTable)
+                        })
+                        .collect::<Result<Vec<_>>>()
+                })
+                .collect::<Result<Vec<_>>>()?
+        } else {
+            vec![Vec::new()]
+        };
+        (units, Some(log.files.len() as u64))
+    } else {
+        (vec![Vec::new()], None)
+    };
 
-    // Extract metrics from the result
-    let metrics = OptimizeMetrics {
-        num_files_added: delta_metrics.num_files_added,
-        num_files_removed: delta_metrics.num_files_removed,
-        total_files_skipped: delta_metrics.total_files_skipped as u64,
-        total_considered_files: delta_metrics.total_considered_files as u64,
-        preserve_insertion_order: delta_metrics.preserve_insertion_order,
+    let mut metrics: Option<OptimizeMetrics> = None;
+    let mut progress = MaintenanceProgress {
+        files_total,
+        ..Default::default()
     };
+    for filters in &units {
+        if control.cancel.is_cancelled() {
+            info!("OPTIMIZE cancelled");
+            let mut metrics = metrics.unwrap_or_else(|| {
+                OptimizeMetrics::from(&deltalake::operations::optimize::Metrics::default())
+            });
+            metrics.cancelled = true;
+            return Ok(metrics);
+        }
+
+        // Open the Delta Lake table (S3 or local)
+        let table = if let (Some(s3_url), Some(s3_config)) = (s3_url, s3_config) {
+            // S3 backend
+            s3_config.open_table(parse_s3_url(s3_url)?).await?
+        } else {
+            // Local backend
+            let table_url = Url::from_directory_path(base_path)
+                .map_err(|_| Error::Other("Invalid path for Delta table".to_string()))?;
+            open_table(table_url).await.map_err(Error::DeltaTable)?
+        };
+
+        // Build optimize operation
+        let mut optimize_builder = DeltaOps(table).optimize().with_filters(filters);
+
+        // Add target file size if provided
+        if let Some(size) = target_size {
+            optimize_builder = optimize_builder.with_target_size(size);
+        }
+
+        // Execute optimize - returns (DeltaTable, Metrics)
+        let (_table, delta_metrics) = optimize_builder.await.map_err(Error::DeltaTable)?;
+
+        // Extract metrics from the result
+        let unit = OptimizeMetrics::from(&delta_metrics);
+        progress.files_processed += unit.total_considered_files;
+        progress.bytes_reclaimed += unit.bytes_removed.saturating_sub(unit.bytes_added);
+        control.report(&progress);
+        match &mut metrics {
+            Some(metrics) => metrics.accumulate(&unit),
+            None => metrics = Some(unit),
+        }
+    }
+
+    let mut metrics = metrics.expect("OPTIMIZE runs at least one unit");
+
+    if s3_url.is_none() && !control.cancel.is_cancelled() {
+        let columns = super::zorder::zorder_columns(base_path)?;
+        if !columns.is_empty() {
+            let columns: Vec<&str> = columns.iter().map(String::as_str).collect();
+            match super::zorder::zorder_incremental(base_path, &columns, target_size).await? {
+                Some(zorder) => metrics.follow(&zorder),
+                None => {
+                    info!("Incremental Z-ORDER doesn't apply to this table, running a full one");
+                    let zorder = zorder_table(base_path, None, None, &columns).await?;
+                    metrics.follow(&zorder);
+                }
+            }
+        }
+    }
 
     Ok(metrics)
 }
 
 /// Execute VACUUM operation on a Delta Lake table
+///
+/// The files to delete are listed by a delta-rs dry run and then deleted one
+/// at a time, so that `control` can stop between files. VACUUM never
+/// touches the log, and every listed file is already unreferenced, so
+/// stopping part way leaves the table as it was.
+///
+/// Fails with `Error::InvalidOperation` if the retention is below its
+/// minimum and not forced.
 pub async fn vacuum_table(
     base_path: &Path,
     s3_url: Option<&str>,
-    s3_storage_options: Option<&HashMap<String, String>>,
-    retention_hours: u64,
+    s3_config: Option<&crate::storage::s3::S3Config>,
+    retention: &VacuumRetention,
     dry_run: bool,
-) -> Result<usize> {
+    protection: &VacuumProtection,
+    control: &MaintenanceControl,
+) -> Result<VacuumReport> {
     use crate::storage::s3::parse_s3_url;
-    use chrono::Duration as ChronoDuration;
+
+    retention.check()?;
 
     // Open the Delta Lake table (S3 or local)
-    let table = if let (Some(s3_url), Some(storage_options)) = (s3_url, s3_storage_options) {
+    let table = if let (Some(s3_url), Some(s3_config)) = (s3_url, s3_config) {
         // S3 backend
-        let s3_url_parsed = parse_s3_url(s3_url)?;
-        open_table_with_storage_options(s3_url_parsed, storage_options.clone())
-            .await
-            .map_err(Error::DeltaTable)?
+        s3_config.open_table(parse_s3_url(s3_url)?).await?
     } else {
         // Local backend
         let table_url = Url::from_directory_path(base_path)
@@ -100,66 +510,98 @@ pub async fn vacuum_table(
         open_table(table_url).await.map_err(Error::DeltaTable)?
     };
 
-    // Build VACUUM operation
-    let mut vacuum_builder = DeltaOps(table).vacuum();
+    let retention_duration =
+        protected_retention(&table, retention.to_chrono()?, protection).await?;
 
-    // Set retention period (Delta Lake uses chrono::Duration in hours)
-    let retention_duration = ChronoDuration::try_hours(retention_hours as i64)
-        .ok_or_else(|| Error::Other(format!("Invalid retention hours: {}", retention_hours)))?;
-    vacuum_builder = vacuum_builder.with_retention_period(retention_duration);
+    // Build VACUUM operation
+    let mut vacuum_builder = DeltaOps(table.clone())
+        .vacuum()
+        .with_retention_period(retention_duration)
+        .with_dry_run(true);
 
-    // If retention is less than 168 hours (7 days), we need to disable the safety check
-    // This is useful for testing but should be used carefully in production
-    if retention_hours < 168 {
+    // delta-rs refuses retentions under 7 days; `VacuumRetention::check`
+    // has already applied the table's own minimum
+    if retention.retention < std::time::Duration::from_secs(168 * 3600) {
         vacuum_builder = vacuum_builder.with_enforce_retention_duration(false);
     }
 
-    // Set dry run mode
-    if dry_run {
-        vacuum_builder = vacuum_builder.with_dry_run(true);
-    }
-
-    // Execute VACUUM
-    let (table_result, metrics) = vacuum_builder.await.map_err(Error::DeltaTable)?;
+    let (_, candidates) = vacuum_builder.await.map_err(Error::DeltaTable)?;
+
+    // Files must be sized before they are deleted, which is only possible
+    // for local tables; S3 reports zero bytes reclaimed
+    let file_size = |file: &str| -> u64 {
+        if s3_url.is_none() {
+            std::fs::metadata(base_path.join(file))
+                .map(|metadata| metadata.len())
+                .unwrap_or(0)
+        } else {
+            0
+        }
+    };
 
-    // For local tables, we can verify files were deleted
-    let deleted_count = if !dry_run && s3_url.is_none() {
-        // Count how many files were actually deleted from disk
-        // This is just for verification - Delta Lake handles the actual deletion
-        metrics.files_deleted.len()
-    } else {
-        metrics.files_deleted.len()
+    let mut report = VacuumReport {
+        dry_run,
+        oldest_retained_version: oldest_retained_version(&table, retention_duration).await?,
+        ..Default::default()
     };
+    if dry_run {
+        report.files_deleted = candidates.files_deleted.len() as u64;
+        report.bytes_reclaimed = candidates.files_deleted.iter().map(|f| file_size(f)).sum();
+    } else {
+        let store = table.object_store();
+        let mut progress = MaintenanceProgress {
+            files_total: Some(candidates.files_deleted.len() as u64),
+            ..Default::default()
+        };
+        for file in &candidates.files_deleted {
+            if control.cancel.is_cancelled() {
+                info!("VACUUM cancelled");
+                report.cancelled = true;
+                break;
+            }
+            let size = file_size(file);
+            let location = object_store::path::Path::parse(file)
+                .map_err(|e| Error::Other(format!("Invalid path {}: {}", file, e)))?;
+            match store.delete(&location).await {
+                Ok(()) | Err(object_store::Error::NotFound { .. }) => {}
+                Err(e) => return Err(e.into()),
+            }
+            report.files_deleted += 1;
+            report.bytes_reclaimed += size;
+
+            progress.files_processed = report.files_deleted;
+            progress.bytes_reclaimed = report.bytes_reclaimed;
+            control.report(&progress);
+        }
+    }
 
     info!(
-        "VACUUM {} deleted {} files",
+        "VACUUM {} deleted {} files ({} bytes), oldest retained version {:?}",
         if dry_run { "dry run" } else { "operation" },
-        deleted_count
+        report.files_deleted,
+        report.bytes_reclaimed,
+        report.oldest_retained_version
     );
 
-    // Update table reference (not used but part of the API)
-    drop(table_result);
-
-    Ok(deleted_count)
+    Ok(report)
 }
 
 /// Execute VACUUM dry run to preview what would be deleted
 pub async fn vacuum_dry_run(
     base_path: &Path,
     s3_url: Option<&str>,
-    s3_storage_options: Option<&HashMap<String, String>>,
-    retention_hours: u64,
+    s3_config: Option<&crate::storage::s3::S3Config>,
+    retention: &VacuumRetention,
+    protection: &VacuumProtection,
 ) -> Result<Vec<String>> {
     use crate::storage::s3::parse_s3_url;
-    use chrono::Duration as ChronoDuration;
+
+    retention.check()?;
 
     // Open the Delta Lake table (S3 or local)
-    let table = if let (Some(s3_url), Some(storage_options)) = (s3_url, s3_storage_options) {
+    let table = if let (Some(s3_url), Some(s3_config)) = (s3_url, s3_config) {
         // S3 backend
-        let s3_url_parsed = parse_s3_url(s3_url)?;
-        open_table_with_storage_options(s3_url_parsed, storage_options.clone())
-            .await
-            .map_err(Error::DeltaTable)?
+        s3_config.open_table(parse_s3_url(s3_url)?).await?
     } else {
         // Local backend
         let table_url = Url::from_directory_path(base_path)
@@ -168,15 +610,15 @@ pub async fn vacuum_dry_run(
     };
 
     // Build VACUUM operation with dry run
-    let retention_duration = ChronoDuration::try_hours(retention_hours as i64)
-        .ok_or_else(|| Error::Other(format!("Invalid retention hours: {}", retention_hours)))?;
+    let retention_duration =
+        protected_retention(&table, retention.to_chrono()?, protection).await?;
 
     let mut vacuum_builder = DeltaOps(table)
         .vacuum()
         .with_retention_period(retention_duration);
 
     // If retention is less than 168 hours (7 days), disable the safety check
-    if retention_hours < 168 {
+    if retention.retention < std::time::Duration::from_secs(168 * 3600) {
         vacuum_builder = vacuum_builder.with_enforce_retention_duration(false);
     }
 
@@ -204,19 +646,16 @@ pub async fn vacuum_dry_run(
 pub async fn zorder_table(
     base_path: &Path,
     s3_url: Option<&str>,
-    s3_storage_options: Option<&HashMap<String, String>>,
+    s3_config: Option<&crate::storage::s3::S3Config>,
     columns: &[&str],
 ) -> Result<OptimizeMetrics> {
     use crate::storage::s3::parse_s3_url;
     use deltalake::operations::optimize::OptimizeType;
 
     // Open the Delta Lake table (S3 or local)
-    let table = if let (Some(s3_url), Some(storage_options)) = (s3_url, s3_storage_options) {
+    let table = if let (Some(s3_url), Some(s3_config)) = (s3_url, s3_config) {
         // S3 backend
-        let s3_url_parsed = parse_s3_url(s3_url)?;
-        open_table_with_storage_options(s3_url_parsed, storage_options.clone())
-            .await
-            .map_err(Error::DeltaTable)?
+        s3_config.open_table(parse_s3_url(s3_url)?).await?
     } else {
         // Local backend
         let table_url = Url::from_directory_path(base_path)
@@ -235,13 +674,7 @@ pub async fn zorder_table(
     let (_table, delta_metrics) = optimize_builder.await.map_err(Error::DeltaTable)?;
 
     // Extract metrics
-    let metrics = OptimizeMetrics {
-        num_files_added: delta_metrics.num_files_added,
-        num_files_removed: delta_metrics.num_files_removed,
-        total_files_skipped: delta_metrics.total_files_skipped as u64,
-        total_considered_files: delta_metrics.total_considered_files as u64,
-        preserve_insertion_order: delta_metrics.preserve_insertion_order,
-    };
+    let metrics = OptimizeMetrics::from(&delta_metrics);
 
     Ok(metrics)
 }
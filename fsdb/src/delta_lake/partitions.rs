@@ -0,0 +1,296 @@
+//! Partitioned tables
+//!
+//! A partitioned table keeps the rows of each combination of partition
+//! column values in its own directory, `region=eu/day=1/`. The values
+//! themselves are recorded per file in the log rather than in the Parquet
+//! data, so listing a table's partitions is a log replay.
+
+use super::log_replay::read_delta_log;
+use crate::{Error, Result};
+use arrow::array::{new_null_array, Array, ArrayRef, RecordBatch, StringArray};
+use arrow::datatypes::{DataType, Field, Schema, SchemaRef};
+use std::collections::BTreeSet;
+use std::path::Path;
+use std::sync::Arc;
+
+/// Directory name used for a null partition value, as Hive and Spark do
+pub const NULL_PARTITION: &str = "__HIVE_DEFAULT_PARTITION__";
+
+/// One partition: each partition column with its value, in partitioning order
+///
+/// `None` is a null value.
+pub type PartitionValues = Vec<(String, Option<String>)>;
+
+/// Every partition that has live files, sorted
+pub fn list_partitions(base_path: &Path) -> Result<Vec<PartitionValues>> {
+    let log = read_delta_log(base_path)?;
+    let columns = &log.config.partition_columns;
+    if columns.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let partitions: BTreeSet<PartitionValues> = log
+        .files
+        .iter()
+        .map(|file| {
+            columns
+                .iter()
+                .map(|c| (c.clone(), file.partition_values.get(c).cloned().flatten()))
+                .collect()
+        })
+        .collect();
+    Ok(partitions.into_iter().collect())
+}
+
+/// `column=value` directory name of one partition level
+pub fn dir_name(column: &str, value: Option<&str>) -> String {
+    format!("{}={}", column, value.unwrap_or(NULL_PARTITION))
+}
+
+/// Split a `column=value` directory name
+pub fn parse_dir_name(name: &str) -> Option<(String, Option<String>)> {
+    let (column, value) = name.split_once('=')?;
+    if column.is_empty() {
+        return None;
+    }
+    let value = (value != NULL_PARTITION).then(|| value.to_string());
+    Some((column.to_string(), value))
+}
+
+/// SQL predicate selecting the rows of one partition
+pub fn partition_filter(partition: &[(String, Option<String>)]) -> String {
+    if partition.is_empty() {
+        return "TRUE".to_string();
+    }
+    partition
+        .iter()
+        .map(|(column, value)| {
+            let column = format!("\"{}\"", column.replace('"', "\"\""));
+            match value {
+                Some(value) => format!("{} = '{}'", column, value.replace('\'', "''")),
+                None => format!("{} IS NULL", column),
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(" AND ")
+}
+
+/// Check `batch` against the partition it's written to and fill in the partition columns
+///
+/// Partition columns the batch leaves out take the partition's value. A row
+/// whose own value contradicts the partition is rejected, since writing it
+/// would put it in a different partition than the one asked for. Columns are
+/// returned in schema order, followed by any the schema doesn't know.
+pub fn conform_to_partition(
+    batch: RecordBatch,
+    schema: &SchemaRef,
+    partition: &[(String, Option<String>)],
+) -> Result<RecordBatch> {
+    let num_rows = batch.num_rows();
+    let mut filled: Vec<(String, ArrayRef)> = Vec::new();
+
+    for (column, value) in partition {
+        let field = schema.field_with_name(column).map_err(|_| {
+            Error::InvalidOperation(format!("Unknown partition column: {}", column))
+        })?;
+        let expected = typed_value(field, value.as_deref())?;
+
+        let Some(written) = batch.column_by_name(column) else {
+            let array = match value {
+                Some(value) => {
+                    let values: ArrayRef =
+                        Arc::new(StringArray::from(vec![value.as_str(); num_rows]));
+                    arrow::compute::cast(&values, field.data_type())?
+                }
+                None => new_null_array(field.data_type(), num_rows),
+            };
+            filled.push((column.clone(), array));
+            continue;
+        };
+
+        // Compare as text after normalising through the column type, so `01` matches `1`
+        let written = arrow::compute::cast(written, field.data_type())?;
+        let written = arrow::compute::cast(&written, &DataType::Utf8)?;
+        let written = written
+            .as_any()
+            .downcast_ref::<StringArray>()
+            .ok_or_else(|| Error::Other("Cast to Utf8 returned another type".to_string()))?;
+        for row in 0..num_rows {
+            let actual = (!written.is_null(row)).then(|| written.value(row));
+            if actual != expected.as_deref() {
+                return Err(Error::InvalidOperation(format!(
+                    "Row {} has {} = {}, but it is written to partition {}",
+                    row + 1,
+                    column,
+                    actual.unwrap_or("NULL"),
+                    dir_name(column, value.as_deref())
+                )));
+            }
+        }
+    }
+
+    let batch_schema = batch.schema();
+    let mut fields = Vec::new();
+    let mut columns = Vec::new();
+    for field in schema.fields() {
+        if let Ok(i) = batch_schema.index_of(field.name()) {
+            fields.push(batch_schema.field(i).clone());
+            columns.push(batch.column(i).clone());
+        } else if let Some((_, array)) = filled.iter().find(|(c, _)| c == field.name()) {
+            fields.push(field.as_ref().clone());
+            columns.push(array.clone());
+        }
+    }
+    for (i, field) in batch_schema.fields().iter().enumerate() {
+        if schema.field_with_name(field.name()).is_err() {
+            fields.push(field.as_ref().clone());
+            columns.push(batch.column(i).clone());
+        }
+    }
+    Ok(RecordBatch::try_new(
+        Arc::new(Schema::new(fields)),
+        columns,
+    )?)
+}
+
+/// Check that each value is valid for its partition column
+pub fn check_values(schema: &SchemaRef, partition: &[(String, Option<String>)]) -> Result<()> {
+    for (column, value) in partition {
+        let field = schema.field_with_name(column).map_err(|_| {
+            Error::InvalidOperation(format!("Unknown partition column: {}", column))
+        })?;
+        typed_value(field, value.as_deref())?;
+    }
+    Ok(())
+}
+
+/// A partition value normalised through the column's type, as text
+///
+/// Fails if the value isn't valid for the column, such as `abc` for an
+/// integer column, or is null for a NOT NULL column.
+fn typed_value(field: &Field, value: Option<&str>) -> Result<Option<String>> {
+    let Some(value) = value else {
+        if !field.is_nullable() {
+            return Err(Error::InvalidOperation(format!(
+                "Partition column '{}' is NOT NULL",
+                field.name()
+            )));
+        }
+        return Ok(None);
+    };
+    let text: ArrayRef = Arc::new(StringArray::from(vec![value]));
+    let typed = arrow::compute::cast(&text, field.data_type())
+        .and_then(|typed| arrow::compute::cast(&typed, &DataType::Utf8))
+        .ok()
+        .filter(|typed| !typed.is_null(0))
+        .ok_or_else(|| {
+            Error::InvalidOperation(format!(
+                "Partition value '{}' is not a valid {} for column '{}'",
+                value,
+                field.data_type(),
+                field.name()
+            ))
+        })?;
+    let typed = typed
+        .as_any()
+        .downcast_ref::<StringArray>()
+        .ok_or_else(|| Error::Other("Cast to Utf8 returned another type".to_string()))?;
+    Ok(Some(typed.value(0).to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow::array::Int32Array;
+
+    fn schema() -> SchemaRef {
+        Arc::new(Schema::new(vec![
+            Field::new("id", DataType::Int32, false),
+            Field::new("region", DataType::Utf8, true),
+            Field::new("year", DataType::Int32, false),
+        ]))
+    }
+
+    fn partition(region: Option<&str>, year: &str) -> PartitionValues {
+        vec![
+            ("region".to_string(), region.map(str::to_string)),
+            ("year".to_string(), Some(year.to_string())),
+        ]
+    }
+
+    #[test]
+    fn test_dir_names_round_trip() {
+        assert_eq!(dir_name("region", Some("eu")), "region=eu");
+        assert_eq!(
+            parse_dir_name(&dir_name("region", None)),
+            Some(("region".to_string(), None))
+        );
+        assert_eq!(
+            parse_dir_name("year=2024"),
+            Some(("year".to_string(), Some("2024".to_string())))
+        );
+        assert_eq!(parse_dir_name("data.csv"), None);
+        assert_eq!(parse_dir_name("=x"), None);
+    }
+
+    #[test]
+    fn test_partition_filter() {
+        assert_eq!(
+            partition_filter(&partition(Some("o'hare"), "2024")),
+            "\"region\" = 'o''hare' AND \"year\" = '2024'"
+        );
+        assert_eq!(
+            partition_filter(&partition(None, "1")),
+            "\"region\" IS NULL AND \"year\" = '1'"
+        );
+    }
+
+    #[test]
+    fn test_missing_partition_columns_are_filled() {
+        let batch = RecordBatch::try_new(
+            Arc::new(Schema::new(vec![Field::new("id", DataType::Int32, false)])),
+            vec![Arc::new(Int32Array::from(vec![1, 2]))],
+        )
+        .unwrap();
+
+        let batch = conform_to_partition(batch, &schema(), &partition(Some("eu"), "2024")).unwrap();
+        assert_eq!(batch.schema().field(1).name(), "region");
+        let years = batch
+            .column(2)
+            .as_any()
+            .downcast_ref::<Int32Array>()
+            .unwrap();
+        assert_eq!(years.values(), &[2024, 2024]);
+    }
+
+    #[test]
+    fn test_contradicting_row_is_rejected() {
+        let batch = RecordBatch::try_new(
+            schema(),
+            vec![
+                Arc::new(Int32Array::from(vec![1, 2])),
+                Arc::new(StringArray::from(vec![Some("eu"), Some("us")])),
+                Arc::new(Int32Array::from(vec![2024, 2024])),
+            ],
+        )
+        .unwrap();
+
+        let err = conform_to_partition(batch.clone(), &schema(), &partition(Some("eu"), "2024"))
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("Row 2 has region = us"), "{}", err);
+        assert!(err.contains("region=eu"), "{}", err);
+
+        // Values are compared by type, and invalid ones rejected up front
+        let eu_only = batch.slice(0, 1);
+        assert!(
+            conform_to_partition(eu_only.clone(), &schema(), &partition(Some("eu"), "02024"))
+                .is_ok()
+        );
+        assert!(
+            conform_to_partition(eu_only.clone(), &schema(), &partition(Some("eu"), "2025"))
+                .is_err()
+        );
+        assert!(conform_to_partition(eu_only, &schema(), &partition(Some("eu"), "abc")).is_err());
+    }
+}
@@ -0,0 +1,376 @@
+//! Recovery from writes interrupted by a crash
+//!
+//! A process that dies while writing can leave behind:
+//!
+//! - Temporary commit files (`_delta_log/_commit_<uuid>.json.tmp`), which
+//!   delta-rs renames into place only once they're complete
+//! - A commit file cut short, by a writer on a filesystem without atomic
+//!   renames, which stops the table from opening
+//! - Data files written for a commit that never happened, which no version
+//!   references and VACUUM never sees
+//!
+//! `recover` removes the first two, and with `remove_orphans` set, the last.
+//! Incomplete commits are only ever the newest versions, so they're set
+//! aside newest first until the latest commit parses; they're moved to
+//! `_metadata/recovered/` rather than deleted. A torn commit older than a
+//! valid one is left alone and reported, since removing it would break
+//! the history after it.
+//!
+//! A writer that is still working looks just like one that died, so only
+//! files untouched for longer than a lease are treated as dead: a commit
+//! takes milliseconds to write, but a large insert can spend minutes on its
+//! data files, hence the longer orphan lease. Files referenced by any commit
+//! still in the log, live or removed, are never deleted.
+
+use super::log_replay::{read_delta_log, LogListing};
+use crate::metadata::snapshot::percent_decode;
+use crate::Result;
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tracing::{info, warn};
+
+/// How interrupted writes are cleaned up when a table is opened
+#[derive(Debug, Clone)]
+pub struct RecoveryConfig {
+    /// Age after which an incomplete commit or temporary commit file is
+    /// assumed to belong to a writer that died
+    pub commit_lease: Duration,
+
+    /// Also delete data files no commit references. Off by default: a
+    /// writer outliving `orphan_lease` would lose its files.
+    pub remove_orphans: bool,
+
+    /// Age after which an unreferenced data file is assumed orphaned
+    pub orphan_lease: Duration,
+}
+
+impl Default for RecoveryConfig {
+    fn default() -> Self {
+        Self {
+            commit_lease: Duration::from_secs(60),
+            remove_orphans: false,
+            orphan_lease: Duration::from_secs(60 * 60),
+        }
+    }
+}
+
+/// What a recovery pass cleaned up
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RecoveryReport {
+    /// Versions whose incomplete commit files were set aside, newest first
+    pub discarded_versions: Vec<u64>,
+    /// Temporary commit files removed
+    pub temp_files_removed: usize,
+    /// Incomplete commits left in place because later versions follow them
+    pub damaged_versions: Vec<u64>,
+    /// Unreferenced data files deleted, relative to the table root
+    pub orphans_removed: Vec<String>,
+    /// Size of the deleted data files
+    pub orphan_bytes: u64,
+}
+
+impl RecoveryReport {
+    /// True if there was nothing to clean up
+    pub fn is_clean(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+/// Clean up after writes to the table at `base_path` that were interrupted
+pub fn recover(base_path: &Path, config: &RecoveryConfig) -> Result<RecoveryReport> {
+    let mut report = RecoveryReport::default();
+    let log_dir = base_path.join("_delta_log");
+    if !log_dir.is_dir() {
+        return Ok(report);
+    }
+
+    report.temp_files_removed = remove_temp_commits(&log_dir, config.commit_lease)?;
+
+    let listing = LogListing::read(&log_dir, None)?;
+    let mut trailing = true;
+    for (version, path) in listing.commits.iter().rev() {
+        if is_complete(path)? {
+            trailing = false;
+            continue;
+        }
+        if !trailing {
+            warn!("Commit {} is damaged but later versions follow it", version);
+            report.damaged_versions.push(*version);
+            continue;
+        }
+        if !is_older_than(path, config.commit_lease) {
+            // Probably still being written
+            trailing = false;
+            continue;
+        }
+        set_aside(base_path, path)?;
+        warn!("Set aside incomplete commit {}", version);
+        report.discarded_versions.push(*version);
+    }
+
+    // Without a readable log there is no telling which files are referenced
+    let readable = report.damaged_versions.is_empty()
+        && listing.commits.len() > report.discarded_versions.len();
+    if config.remove_orphans && readable {
+        remove_orphans(base_path, config.orphan_lease, &mut report)?;
+    }
+
+    if !report.is_clean() {
+        info!("Recovered {}: {:?}", base_path.display(), report);
+    }
+    Ok(report)
+}
+
+/// Remove temporary commit files older than `lease`
+fn remove_temp_commits(log_dir: &Path, lease: Duration) -> Result<usize> {
+    let mut removed = 0;
+    for entry in std::fs::read_dir(log_dir)? {
+        let path = entry?.path();
+        let is_temp = path
+            .file_name()
+            .and_then(|n| n.to_str())
+            .is_some_and(|n| n.starts_with("_commit_") && n.ends_with(".tmp"));
+        if is_temp && is_older_than(&path, lease) {
+            std::fs::remove_file(&path)?;
+            removed += 1;
+        }
+    }
+    Ok(removed)
+}
+
+/// Whether a commit file holds at least one action and every line parses
+fn is_complete(path: &Path) -> Result<bool> {
+    let content = std::fs::read(path)?;
+    let Ok(content) = String::from_utf8(content) else {
+        return Ok(false);
+    };
+    let mut actions = 0;
+    for line in content.lines().filter(|line| !line.trim().is_empty()) {
+        match serde_json::from_str::<serde_json::Value>(line) {
+            Ok(action) if action.is_object() => actions += 1,
+            _ => return Ok(false),
+        }
+    }
+    Ok(actions > 0)
+}
+
+pub(super) fn is_older_than(path: &Path, lease: Duration) -> bool {
+    std::fs::metadata(path)
+        .and_then(|m| m.modified())
+        .ok()
+        .and_then(|modified| modified.elapsed().ok())
+        .is_some_and(|age| age > lease)
+}
+
+/// Move a commit file to `_metadata/recovered/`
+fn set_aside(base_path: &Path, path: &Path) -> Result<()> {
+    let dir = base_path.join("_metadata").join("recovered");
+    std::fs::create_dir_all(&dir)?;
+    let name = path.file_name().unwrap_or_default();
+    std::fs::rename(path, dir.join(name))?;
+    Ok(())
+}
+
+/// Delete data files that no commit in the log references
+fn remove_orphans(base_path: &Path, lease: Duration, report: &mut RecoveryReport) -> Result<()> {
+    let referenced = referenced_files(base_path)?;
+    let mut files = Vec::new();
+    data_files(base_path, base_path, &mut files)?;
+
+    for (relative, path) in files {
+        if referenced.contains(&relative) || !is_older_than(&path, lease) {
+            continue;
+        }
+        let size = std::fs::metadata(&path)?.len();
+        std::fs::remove_file(&path)?;
+        warn!("Removed orphaned data file {}", relative);
+        report.orphan_bytes += size;
+        report.orphans_removed.push(relative);
+    }
+    report.orphans_removed.sort();
+    Ok(())
+}
+
+/// Decoded paths of the live files and of every file a commit still in the
+/// log adds, removes or writes change data to
+pub(super) fn referenced_files(base_path: &Path) -> Result<HashSet<String>> {
+    let mut referenced = HashSet::new();
+    for file in read_delta_log(base_path)?.files {
+        referenced.insert(percent_decode(&file.path)?);
+    }
+
+    let listing = LogListing::read(&base_path.join("_delta_log"), None)?;
+    for path in listing.commits.values() {
+        for line in std::fs::read_to_string(path)?.lines() {
+            let Ok(action) = serde_json::from_str::<serde_json::Value>(line) else {
+                continue;
+            };
+            for kind in ["add", "remove", "cdc"] {
+                if let Some(path) = action[kind]["path"].as_str() {
+                    referenced.insert(percent_decode(path)?);
+                }
+            }
+        }
+    }
+    Ok(referenced)
+}
+
+/// Parquet files under `dir`, skipping `_` and `.` directories such as the
+/// log and metadata, as (path relative to `base_path`, full path)
+pub(super) fn data_files(
+    base_path: &Path,
+    dir: &Path,
+    files: &mut Vec<(String, PathBuf)>,
+) -> Result<()> {
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        let name = path
+            .file_name()
+            .and_then(|n| n.to_str())
+            .unwrap_or_default();
+        if path.is_dir() {
+            if !name.starts_with(['_', '.']) {
+                data_files(base_path, &path, files)?;
+            }
+        } else if name.ends_with(".parquet") {
+            let relative = path
+                .strip_prefix(base_path)
+                .unwrap_or(&path)
+                .to_string_lossy()
+                .replace(std::path::MAIN_SEPARATOR, "/");
+            files.push((relative, path));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::{json, Value};
+    use tempfile::TempDir;
+
+    fn immediate() -> RecoveryConfig {
+        RecoveryConfig {
+            commit_lease: Duration::ZERO,
+            remove_orphans: true,
+            orphan_lease: Duration::ZERO,
+        }
+    }
+
+    fn write_commit(dir: &Path, version: u64, content: &str) {
+        let path = dir.join("_delta_log").join(format!("{:020}.json", version));
+        std::fs::write(path, content).unwrap();
+    }
+
+    fn add(path: &str) -> String {
+        json!({"add": {
+            "path": path,
+            "size": 4,
+            "partitionValues": {},
+            "modificationTime": 0,
+            "dataChange": true
+        }})
+        .to_string()
+    }
+
+    fn table() -> TempDir {
+        let dir = TempDir::new().unwrap();
+        std::fs::create_dir_all(dir.path().join("_delta_log")).unwrap();
+        let actions: Vec<String> = [
+            json!({"protocol": {"minReaderVersion": 1, "minWriterVersion": 2}}),
+            json!({"metaData": {
+                "id": "t",
+                "format": {"provider": "parquet", "options": {}},
+                "schemaString": json!({"type": "struct", "fields": []}).to_string(),
+                "partitionColumns": [],
+                "configuration": {}
+            }}),
+        ]
+        .iter()
+        .map(Value::to_string)
+        .chain([add("a.parquet")])
+        .collect();
+        write_commit(dir.path(), 0, &actions.join("\n"));
+        dir
+    }
+
+    fn commit_path(dir: &Path, subdir: &str, version: u64) -> PathBuf {
+        dir.join(subdir).join(format!("{:020}.json", version))
+    }
+
+    #[test]
+    fn test_torn_commits_set_aside_newest_first() {
+        let dir = table();
+        write_commit(dir.path(), 1, &add("b%20c.parquet"));
+        write_commit(dir.path(), 2, r#"{"add":{"path":"d.parq"#);
+        write_commit(dir.path(), 3, "");
+        std::fs::write(dir.path().join("_delta_log/_commit_x.json.tmp"), "{").unwrap();
+
+        let config = RecoveryConfig {
+            remove_orphans: false,
+            ..immediate()
+        };
+        let report = recover(dir.path(), &config).unwrap();
+        assert_eq!(report.discarded_versions, vec![3, 2]);
+        assert_eq!(report.temp_files_removed, 1);
+        assert!(report.damaged_versions.is_empty());
+        assert!(commit_path(dir.path(), "_metadata/recovered", 2).exists());
+        assert!(commit_path(dir.path(), "_delta_log", 1).exists());
+
+        assert!(recover(dir.path(), &immediate()).unwrap().is_clean());
+    }
+
+    #[test]
+    fn test_recent_and_inner_commits_left_alone() {
+        let dir = table();
+        write_commit(dir.path(), 1, "{");
+        write_commit(dir.path(), 2, &add("b.parquet"));
+        write_commit(dir.path(), 3, "{");
+
+        // Version 3 may still be being written
+        let report = recover(dir.path(), &RecoveryConfig::default()).unwrap();
+        assert!(report.discarded_versions.is_empty());
+        assert_eq!(report.damaged_versions, vec![1]);
+        assert!(commit_path(dir.path(), "_delta_log", 3).exists());
+
+        let report = recover(dir.path(), &immediate()).unwrap();
+        assert_eq!(report.discarded_versions, vec![3]);
+        assert_eq!(report.damaged_versions, vec![1]);
+        assert!(commit_path(dir.path(), "_delta_log", 1).exists());
+    }
+
+    #[test]
+    fn test_only_unreferenced_files_removed() {
+        let dir = table();
+        write_commit(dir.path(), 1, &add("part%3D1/b.parquet"));
+        let remove = json!({"remove": {"path": "a.parquet", "dataChange": true}});
+        write_commit(dir.path(), 2, &remove.to_string());
+        std::fs::create_dir_all(dir.path().join("part=1")).unwrap();
+        std::fs::create_dir_all(dir.path().join("_metadata")).unwrap();
+        for file in [
+            "a.parquet",
+            "orphan.parquet",
+            "part=1/b.parquet",
+            "part=1/orphan.parquet",
+            "_metadata/x.parquet",
+        ] {
+            std::fs::write(dir.path().join(file), "data").unwrap();
+        }
+
+        let report = recover(dir.path(), &RecoveryConfig::default()).unwrap();
+        assert!(report.is_clean(), "orphan removal is off by default");
+
+        let report = recover(dir.path(), &immediate()).unwrap();
+        assert_eq!(
+            report.orphans_removed,
+            vec!["orphan.parquet", "part=1/orphan.parquet"]
+        );
+        assert_eq!(report.orphan_bytes, 8);
+        // Removed by a commit but not yet vacuumed
+        assert!(dir.path().join("a.parquet").exists());
+        assert!(dir.path().join("part=1/b.parquet").exists());
+        assert!(dir.path().join("_metadata/x.parquet").exists());
+    }
+}
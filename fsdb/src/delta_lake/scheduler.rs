@@ -0,0 +1,234 @@
+//! Background maintenance scheduling
+//!
+//! `DatabaseOps::start_maintenance` runs a task that polls the table and,
+//! once its commits have settled, decides from a `MaintenancePolicy` whether
+//! OPTIMIZE or VACUUM is due. This module holds the policy, the status the
+//! task reports, the decisions themselves and the handle that stops the
+//! task; the runs go through `DatabaseOps` like any other.
+
+use super::{OptimizeMetrics, VacuumReport};
+use crate::error::Result;
+use std::path::Path;
+use std::time::{Duration, SystemTime};
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+use tracing::info;
+
+/// When the maintenance scheduler compacts and vacuums a table
+#[derive(Debug, Clone)]
+pub struct MaintenancePolicy {
+    /// How often the table is checked
+    pub poll_interval: Duration,
+
+    /// The table must go this long without a commit before maintenance runs
+    pub quiet_period: Duration,
+
+    /// Files smaller than this many bytes count as small
+    pub small_file_bytes: u64,
+
+    /// Compact once more than this many small files could be merged
+    pub max_small_files: usize,
+
+    /// Also compact once a small file that could be merged is this old,
+    /// however few there are (None = only the count matters)
+    pub max_small_file_age: Option<Duration>,
+
+    /// Size OPTIMIZE bin-packs files up to (None uses the target file size,
+    /// or OPTIMIZE's default of 100 MiB)
+    pub target_size: Option<u64>,
+
+    /// VACUUM this often, counted from the start of the scheduler or the
+    /// last VACUUM (None = never)
+    pub vacuum_interval: Option<Duration>,
+
+    /// Retention VACUUM runs with, in hours
+    pub vacuum_retention_hours: u64,
+}
+
+impl Default for MaintenancePolicy {
+    fn default() -> Self {
+        Self {
+            poll_interval: Duration::from_secs(30),
+            quiet_period: Duration::from_secs(10),
+            small_file_bytes: 16 * 1024 * 1024,
+            max_small_files: 100,
+            max_small_file_age: Some(Duration::from_secs(60 * 60)),
+            target_size: None,
+            vacuum_interval: Some(Duration::from_secs(24 * 60 * 60)),
+            vacuum_retention_hours: 168,
+        }
+    }
+}
+
+/// What the maintenance scheduler has done since it was started
+#[derive(Debug, Clone, Default)]
+pub struct MaintenanceStatus {
+    /// True while the scheduler task is running
+    pub running: bool,
+
+    /// Times the table has been checked
+    pub checks: u64,
+
+    /// When the table was last checked
+    pub last_check: Option<SystemTime>,
+
+    /// OPTIMIZE runs the scheduler started
+    pub optimize_runs: u64,
+
+    /// Metrics of the last of them
+    pub last_optimize: Option<OptimizeMetrics>,
+
+    /// VACUUM runs the scheduler started
+    pub vacuum_runs: u64,
+
+    /// Report of the last of them
+    pub last_vacuum: Option<VacuumReport>,
+
+    /// When the last VACUUM finished
+    pub last_vacuum_at: Option<SystemTime>,
+
+    /// Error of the last failed run, cleared by the next successful one
+    pub last_error: Option<String>,
+}
+
+/// Small files OPTIMIZE could merge: how many, and the oldest one's
+/// modification time in milliseconds
+///
+/// Counted as by `count_small_files`: a partition's only small file has
+/// nothing to be merged with and is left out.
+pub fn small_files(base_path: &Path, small_file_bytes: u64) -> Result<(usize, Option<i64>)> {
+    let log = super::read_delta_log(base_path)?;
+    let mut per_partition: std::collections::HashMap<_, (usize, i64)> = Default::default();
+    for file in log.files.iter().filter(|f| f.size < small_file_bytes) {
+        let entry = per_partition
+            .entry(&file.partition_values)
+            .or_insert((0, i64::MAX));
+        entry.0 += 1;
+        entry.1 = entry.1.min(file.modification_time);
+    }
+    Ok(per_partition.into_values().filter(|(n, _)| *n > 1).fold(
+        (0, None),
+        |(count, oldest), (n, modified)| {
+            (
+                count + n,
+                Some(oldest.map_or(modified, |o: i64| o.min(modified))),
+            )
+        },
+    ))
+}
+
+/// Whether `policy` calls for OPTIMIZE, given the mergeable small files and
+/// the oldest one's modification time, at `now` (both in milliseconds)
+pub fn optimize_due(
+    policy: &MaintenancePolicy,
+    small_files: usize,
+    oldest_small_file: Option<i64>,
+    now: i64,
+) -> bool {
+    if small_files > policy.max_small_files {
+        return true;
+    }
+    match (policy.max_small_file_age, oldest_small_file) {
+        (Some(max_age), Some(modified)) if small_files > 0 => {
+            now - modified >= max_age.as_millis() as i64
+        }
+        _ => false,
+    }
+}
+
+/// Whether `policy` calls for VACUUM, `since` the scheduler started or last
+/// vacuumed
+pub fn vacuum_due(policy: &MaintenancePolicy, since: SystemTime) -> bool {
+    policy
+        .vacuum_interval
+        .is_some_and(|interval| since.elapsed().unwrap_or_default() >= interval)
+}
+
+/// Handle to a running maintenance scheduler
+pub struct SchedulerHandle {
+    shutdown_tx: mpsc::Sender<()>,
+    task: JoinHandle<()>,
+}
+
+impl SchedulerHandle {
+    /// Run `pass` every `poll_interval` until the handle is stopped or
+    /// `pass` returns false
+    pub fn spawn<F, Fut>(poll_interval: Duration, mut pass: F) -> Self
+    where
+        F: FnMut() -> Fut + Send + 'static,
+        Fut: std::future::Future<Output = bool> + Send,
+    {
+        let (shutdown_tx, mut shutdown_rx) = mpsc::channel(1);
+        let task = tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(poll_interval);
+            ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+            loop {
+                tokio::select! {
+                    _ = ticker.tick() => {
+                        if !pass().await {
+                            break;
+                        }
+                    }
+                    _ = shutdown_rx.recv() => {
+                        info!("Maintenance scheduler stopped");
+                        break;
+                    }
+                }
+            }
+        });
+        Self { shutdown_tx, task }
+    }
+
+    /// True once the task has exited
+    pub fn is_finished(&self) -> bool {
+        self.task.is_finished()
+    }
+
+    /// Stop the scheduler and wait for the current pass to finish
+    pub async fn stop(self) {
+        self.shutdown_tx.send(()).await.ok();
+        self.task.await.ok();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_optimize_due() {
+        let policy = MaintenancePolicy {
+            max_small_files: 10,
+            max_small_file_age: Some(Duration::from_secs(60)),
+            ..Default::default()
+        };
+        let now = 1_000_000;
+        assert!(optimize_due(&policy, 11, Some(now), now));
+        assert!(!optimize_due(&policy, 10, Some(now - 59_000), now));
+        assert!(optimize_due(&policy, 2, Some(now - 60_000), now));
+        assert!(!optimize_due(&policy, 0, None, now));
+
+        let by_count = MaintenancePolicy {
+            max_small_file_age: None,
+            ..policy
+        };
+        assert!(!optimize_due(&by_count, 2, Some(0), now));
+    }
+
+    #[test]
+    fn test_vacuum_due() {
+        let policy = MaintenancePolicy {
+            vacuum_interval: Some(Duration::from_secs(60)),
+            ..Default::default()
+        };
+        let now = SystemTime::now();
+        assert!(!vacuum_due(&policy, now));
+        assert!(vacuum_due(&policy, now - Duration::from_secs(61)));
+
+        let never = MaintenancePolicy {
+            vacuum_interval: None,
+            ..policy
+        };
+        assert!(!vacuum_due(&never, now - Duration::from_secs(3600)));
+    }
+}
@@ -0,0 +1,92 @@
+//! Per-file percentile sketches
+//!
+//! `DatabaseOps::approx_percentile` keeps a t-digest of a column for each
+//! data file, built the first time the column is asked about and stored in
+//! `_metadata/sketches.json`. Data files never change once written, so a
+//! file's digest stays valid for as long as the file is live: an insert only
+//! adds digests for its new files, and digests of files removed by a delete,
+//! update or OPTIMIZE are dropped the next time the column's sketches are
+//! brought up to date. Percentiles are answered by merging the live files'
+//! digests, reading no rows but those of files without one.
+
+use crate::query::percentile::TDigest;
+use crate::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+/// Digests kept for a table
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct FileSketches {
+    /// Digest of each data file, by file path as in the Delta log, per column
+    pub columns: BTreeMap<String, BTreeMap<String, TDigest>>,
+}
+
+impl FileSketches {
+    /// Where a table's sketches are stored
+    pub fn path(base_path: &Path) -> PathBuf {
+        base_path.join("_metadata").join("sketches.json")
+    }
+
+    /// Load a table's sketches, empty if it has none
+    pub fn load(base_path: &Path) -> Result<Self> {
+        let path = Self::path(base_path);
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let json = std::fs::read_to_string(&path)?;
+        Ok(serde_json::from_str(&json)?)
+    }
+
+    /// Save atomically, replacing any previous sketches
+    pub fn save(&self, base_path: &Path) -> Result<()> {
+        let path = Self::path(base_path);
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let tmp_path = path.with_extension("tmp");
+        std::fs::write(&tmp_path, serde_json::to_string(self)?)?;
+        std::fs::rename(tmp_path, path)?;
+        Ok(())
+    }
+
+    /// Drop `column`'s digests of files not in `live`, and list live files
+    /// without one
+    pub fn retain_live<'a>(&mut self, column: &str, live: &[&'a str]) -> Vec<&'a str> {
+        let digests = self.columns.entry(column.to_string()).or_default();
+        digests.retain(|path, _| live.contains(&path.as_str()));
+        live.iter()
+            .copied()
+            .filter(|path| !digests.contains_key(*path))
+            .collect()
+    }
+
+    /// Merge of `column`'s digests
+    pub fn merged(&self, column: &str) -> TDigest {
+        self.columns
+            .get(column)
+            .map(|digests| TDigest::merge(digests.values()))
+            .unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_retain_live_files() {
+        let mut sketches = FileSketches::default();
+        assert_eq!(sketches.retain_live("x", &["a", "b"]), vec!["a", "b"]);
+
+        let digests = sketches.columns.get_mut("x").unwrap();
+        digests.insert("a".to_string(), TDigest::from_values([Some(1.0)]));
+        digests.insert("b".to_string(), TDigest::from_values([Some(3.0)]));
+        assert_eq!(sketches.merged("x").quantile(0.5), Some(2.0));
+
+        // b was compacted into c
+        assert_eq!(sketches.retain_live("x", &["a", "c"]), vec!["c"]);
+        assert_eq!(sketches.columns["x"].len(), 1);
+        assert_eq!(sketches.merged("y").quantile(0.5), None);
+    }
+}
@@ -98,88 +98,51 @@ pub fn compute_column_statistics(batch: &RecordBatch) -> Result<ColumnStatsMaps>
 }
 
 /// Get column statistics from Delta Lake transaction log
+///
+/// Aggregates the per-file statistics of the live files, under logical column names.
 pub fn get_column_statistics_from_delta(
     base_path: &std::path::Path,
 ) -> Result<HashMap<String, ColumnStats>> {
     use crate::query::pruning::{is_value_greater_than, is_value_less_than};
 
-    let delta_log_path = base_path.join("_delta_log");
+    let log = super::log_replay::read_delta_log(base_path)?;
     let mut column_stats: HashMap<String, ColumnStats> = HashMap::new();
 
-    // Read all Delta Lake commit files
-    let mut commit_files = Vec::new();
-    for entry in std::fs::read_dir(&delta_log_path)? {
-        let entry = entry?;
-        let path = entry.path();
-        if path.is_file() && path.extension().and_then(|s| s.to_str()) == Some("json") {
-            let filename = path.file_name().unwrap().to_str().unwrap();
-            if let Some(version_str) = filename.strip_suffix(".json") {
-                if let Ok(version) = version_str.parse::<u64>() {
-                    commit_files.push((version, path.clone()));
-                }
-            }
-        }
-    }
-
-    // Sort by version
-    commit_files.sort_by_key(|(v, _)| *v);
-
-    // Read stats from each commit file (JSONL format)
-    for (_version, commit_path) in commit_files {
-        let content = std::fs::read_to_string(&commit_path)?;
-
-        // Parse each line as JSON
-        for line in content.lines() {
-            if let Ok(json) = serde_json::from_str::<serde_json::Value>(line) {
-                // Look for "add" actions which contain file stats
-                if let Some(add) = json.get("add") {
-                    if let Some(stats_str) = add.get("stats").and_then(|s| s.as_str()) {
-                        // Parse the stats JSON string
-                        if let Ok(stats_json) = serde_json::from_str::<serde_json::Value>(stats_str)
-                        {
-                            // Extract minValues, maxValues, nullCount
-                            if let (Some(min_vals), Some(max_vals), Some(null_counts)) = (
-                                stats_json.get("minValues"),
-                                stats_json.get("maxValues"),
-                                stats_json.get("nullCount"),
-                            ) {
-                                // Process each column
-                                if let Some(min_obj) = min_vals.as_object() {
-                                    for (col_name, min_val) in min_obj {
-                                        if let Some(max_val) = max_vals.get(col_name) {
-                                            let null_count = null_counts
-                                                .get(col_name)
-                                                .and_then(|v| v.as_u64())
-                                                .unwrap_or(0);
-
-                                            // Update global min/max for this column
-                                            column_stats
-                                                .entry(col_name.clone())
-                                                .and_modify(|stats| {
-                                                    if is_value_less_than(min_val, &stats.min_value)
-                                                    {
-                                                        stats.min_value = min_val.clone();
-                                                    }
-                                                    if is_value_greater_than(
-                                                        max_val,
-                                                        &stats.max_value,
-                                                    ) {
-                                                        stats.max_value = max_val.clone();
-                                                    }
-                                                    stats.null_count += null_count;
-                                                })
-                                                .or_insert(ColumnStats {
-                                                    min_value: min_val.clone(),
-                                                    max_value: max_val.clone(),
-                                                    null_count,
-                                                });
-                                        }
-                                    }
-                                }
-                            }
+    for stats_json in log.files.iter().filter_map(|file| file.stats.as_ref()) {
+        // Extract minValues, maxValues, nullCount
+        let (Some(min_obj), Some(max_vals), Some(null_counts)) = (
+            stats_json["minValues"].as_object(),
+            stats_json.get("maxValues"),
+            stats_json.get("nullCount"),
+        ) else {
+            continue;
+        };
+
+        // Process each column
+        for (col_name, min_val) in min_obj {
+            if let Some(max_val) = max_vals.get(col_name) {
+                let null_count = null_counts
+                    .get(col_name)
+                    .and_then(|v| v.as_u64())
+                    .unwrap_or(0);
+
+                // Update global min/max for this column
+                column_stats
+                    .entry(col_name.clone())
+                    .and_modify(|stats| {
+                        if is_value_less_than(min_val, &stats.min_value) {
+                            stats.min_value = min_val.clone();
                         }
-                    }
-                }
+                        if is_value_greater_than(max_val, &stats.max_value) {
+                            stats.max_value = max_val.clone();
+                        }
+                        stats.null_count += null_count;
+                    })
+                    .or_insert(ColumnStats {
+                        min_value: min_val.clone(),
+                        max_value: max_val.clone(),
+                        null_count,
+                    });
             }
         }
     }
@@ -0,0 +1,205 @@
+//! Per-column control of the statistics inserts record in the Delta log
+//!
+//! delta-rs records the minimum, maximum and null count of every column in
+//! each file's `add` action. For wide text columns those bounds can be most
+//! of the log's size while rarely letting a query skip a file. A
+//! `StatsConfig` trims the statistics of the files an insert writes before
+//! they're committed: per column, min/max and the null count can be dropped,
+//! and string bounds cut to a prefix.
+//!
+//! A truncated minimum is still a lower bound. A truncated maximum gets
+//! U+10FFFF appended, which sorts after every string with that prefix, so it
+//! stays an upper bound. A column without min/max or null count in the log is
+//! never used to skip files, so trimming can make queries read more files but
+//! never return fewer rows.
+
+use crate::Result;
+use deltalake::kernel::Add;
+use std::collections::HashMap;
+
+/// Appended to a truncated maximum to keep it an upper bound
+const MAX_SUFFIX: char = '\u{10FFFF}';
+
+/// Which statistics the Delta log keeps for one column
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ColumnStatsConfig {
+    /// Keep the column's minimum and maximum
+    pub min_max: bool,
+
+    /// Keep the column's null count
+    pub null_count: bool,
+
+    /// Cut string minimums and maximums to this many characters
+    pub max_string_length: Option<usize>,
+}
+
+impl Default for ColumnStatsConfig {
+    fn default() -> Self {
+        Self::all()
+    }
+}
+
+impl ColumnStatsConfig {
+    /// Every statistic, with strings kept whole
+    pub fn all() -> Self {
+        Self {
+            min_max: true,
+            null_count: true,
+            max_string_length: None,
+        }
+    }
+
+    /// No statistics at all
+    pub fn none() -> Self {
+        Self {
+            min_max: false,
+            null_count: false,
+            max_string_length: None,
+        }
+    }
+
+    /// Cut string bounds to `chars` characters
+    pub fn with_max_string_length(mut self, chars: usize) -> Self {
+        self.max_string_length = Some(chars);
+        self
+    }
+}
+
+/// Which statistics inserts record in the Delta log, per column
+///
+/// Columns not listed get `default`, which keeps everything unless changed.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct StatsConfig {
+    pub default: ColumnStatsConfig,
+    pub columns: HashMap<String, ColumnStatsConfig>,
+}
+
+impl StatsConfig {
+    /// Start from `default` for every column
+    pub fn new(default: ColumnStatsConfig) -> Self {
+        Self {
+            default,
+            columns: HashMap::new(),
+        }
+    }
+
+    /// Set the statistics kept for `name`
+    pub fn column(mut self, name: &str, config: ColumnStatsConfig) -> Self {
+        self.columns.insert(name.to_string(), config);
+        self
+    }
+
+    /// Statistics kept for `name`
+    pub fn for_column(&self, name: &str) -> &ColumnStatsConfig {
+        self.columns.get(name).unwrap_or(&self.default)
+    }
+
+    /// Trim the statistics of `adds`, not yet committed, to the configuration
+    pub(crate) fn apply(&self, adds: &mut [Add]) -> Result<()> {
+        for add in adds {
+            let Some(json) = &add.stats else {
+                continue;
+            };
+            let mut stats: serde_json::Value = serde_json::from_str(json)?;
+            self.trim(&mut stats);
+            add.stats = Some(serde_json::to_string(&stats)?);
+        }
+        Ok(())
+    }
+
+    fn trim(&self, stats: &mut serde_json::Value) {
+        for (key, is_max) in [("minValues", false), ("maxValues", true)] {
+            if let Some(values) = stats.get_mut(key).and_then(|v| v.as_object_mut()) {
+                values.retain(|column, _| self.for_column(column).min_max);
+                for (column, value) in values.iter_mut() {
+                    if let Some(chars) = self.for_column(column).max_string_length {
+                        truncate_strings(value, chars, is_max);
+                    }
+                }
+            }
+        }
+        if let Some(counts) = stats.get_mut("nullCount").and_then(|v| v.as_object_mut()) {
+            counts.retain(|column, _| self.for_column(column).null_count);
+        }
+    }
+}
+
+/// Cut the strings in `value`, a column's bound or a struct of them, to
+/// `chars` characters
+fn truncate_strings(value: &mut serde_json::Value, chars: usize, is_max: bool) {
+    match value {
+        serde_json::Value::String(s) => {
+            if let Some((end, _)) = s.char_indices().nth(chars) {
+                s.truncate(end);
+                if is_max {
+                    s.push(MAX_SUFFIX);
+                }
+            }
+        }
+        serde_json::Value::Object(fields) => {
+            for field in fields.values_mut() {
+                truncate_strings(field, chars, is_max);
+            }
+        }
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn trimmed(config: &StatsConfig, stats: serde_json::Value) -> serde_json::Value {
+        let mut stats = stats;
+        config.trim(&mut stats);
+        stats
+    }
+
+    #[test]
+    fn test_columns_dropped_per_config() {
+        let config = StatsConfig::default()
+            .column("body", ColumnStatsConfig::none())
+            .column(
+                "kind",
+                ColumnStatsConfig {
+                    null_count: false,
+                    ..ColumnStatsConfig::all()
+                },
+            );
+        let stats = json!({
+            "numRecords": 2,
+            "minValues": {"id": 1, "kind": "a", "body": "x"},
+            "maxValues": {"id": 2, "kind": "b", "body": "y"},
+            "nullCount": {"id": 0, "kind": 0, "body": 1},
+        });
+        assert_eq!(
+            trimmed(&config, stats),
+            json!({
+                "numRecords": 2,
+                "minValues": {"id": 1, "kind": "a"},
+                "maxValues": {"id": 2, "kind": "b"},
+                "nullCount": {"id": 0, "body": 1},
+            })
+        );
+    }
+
+    #[test]
+    fn test_string_bounds_truncated() {
+        let config = StatsConfig::new(ColumnStatsConfig::all().with_max_string_length(3));
+        let stats = json!({
+            "minValues": {"name": "ábcdef", "short": "ab", "n": 12345},
+            "maxValues": {"name": "ábd", "s": {"inner": "zzzz"}},
+        });
+        let stats = trimmed(&config, stats);
+        assert_eq!(
+            stats["minValues"],
+            json!({"name": "ábc", "short": "ab", "n": 12345})
+        );
+        assert_eq!(stats["maxValues"]["name"], json!("ábd"));
+        let max = stats["maxValues"]["s"]["inner"].as_str().unwrap();
+        assert_eq!(max, "zzz\u{10FFFF}");
+        // Still an upper bound of the original
+        assert!(max > "zzzz");
+    }
+}
@@ -0,0 +1,425 @@
+//! Incremental Z-ORDER
+//!
+//! `zorder_table` rewrites every file of the table along a Z-curve over the
+//! clustering columns. Once a table is clustered most of that work is
+//! wasted: later writes add a few files whose ranges cut across the
+//! clustered ones. `zorder_incremental` rewrites only the files whose
+//! min/max boxes overlap another file's on every clustering column, and
+//! leaves files that already stand apart as they are.
+//!
+//! The files of one run are cut from a single Z-curve, so neighbouring files
+//! can still overlap a little. Each carries its run in the `fsdb.zorder`
+//! tag of its add action, and overlaps between files of the same run over
+//! the same columns don't count, so a clustered table stays put until new
+//! files land among it.
+//!
+//! A table's clustering columns can be stored in the `fsdb.zorderColumns`
+//! table property, after which OPTIMIZE follows compaction with an
+//! incremental Z-ORDER.
+
+use super::{FileStats, OptimizeMetrics};
+use crate::{Error, Result};
+use arrow::array::{new_null_array, Array, ArrayRef, RecordBatch, StringArray, UInt32Array};
+use arrow::datatypes::{DataType, SchemaRef};
+use arrow::row::{OwnedRow, RowConverter, SortField};
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::Arc;
+use tracing::info;
+
+/// Table property listing the clustering columns, comma-separated
+pub const ZORDER_COLUMNS_PROPERTY: &str = "fsdb.zorderColumns";
+
+/// Add action tag naming the Z-ORDER run that wrote a file
+pub const ZORDER_TAG: &str = "fsdb.zorder";
+
+/// Target size of rewritten files when none is given, as for delta-rs OPTIMIZE
+const DEFAULT_TARGET_SIZE: u64 = 100 * 1024 * 1024;
+
+/// Bytes of each column's order-preserving encoding that go into the curve
+const KEY_BYTES: usize = 16;
+
+/// Columns in a `fsdb.zorderColumns` value
+pub fn parse_columns(value: &str) -> Vec<String> {
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|column| !column.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+/// Clustering columns stored for the local table at `base_path`, empty if
+/// none are
+pub fn zorder_columns(base_path: &Path) -> Result<Vec<String>> {
+    Ok(super::read_table_config(base_path)?
+        .configuration
+        .get(ZORDER_COLUMNS_PROPERTY)
+        .map(|value| parse_columns(value))
+        .unwrap_or_default())
+}
+
+/// Z-ORDER only the files of the local table at `base_path` whose boxes on
+/// `columns` overlap, in one commit
+///
+/// The overlapping files are read into memory together, sorted along the
+/// curve and written back in files of about `target_size` bytes. Returns
+/// `None` for tables this can't rewrite, partitioned ones, ones with column
+/// mapping or deletion vectors, which `zorder_table` handles. Fails with
+/// `Error::InvalidOperation` if a column doesn't exist or can't be ordered.
+pub async fn zorder_incremental(
+    base_path: &Path,
+    columns: &[&str],
+    target_size: Option<u64>,
+) -> Result<Option<OptimizeMetrics>> {
+    use deltalake::kernel::transaction::CommitBuilder;
+    use deltalake::kernel::{Action, Remove};
+    use deltalake::protocol::DeltaOperation;
+    use deltalake::writer::{DeltaWriter, RecordBatchWriter};
+
+    let log = super::read_delta_log(base_path)?;
+    if !log.config.partition_columns.is_empty()
+        || log.config.column_mapping_mode() != "none"
+        || log.files.iter().any(|file| file.deletion_vector.is_some())
+    {
+        return Ok(None);
+    }
+
+    let table_url = url::Url::from_directory_path(base_path)
+        .map_err(|_| Error::Other("Invalid path for Delta table".to_string()))?;
+    let table = deltalake::open_table(table_url)
+        .await
+        .map_err(Error::DeltaTable)?;
+    let mut writer = RecordBatchWriter::for_table(&table).map_err(Error::DeltaTable)?;
+    let schema = writer.arrow_schema();
+    let mut fields = Vec::with_capacity(columns.len());
+    for column in columns {
+        let field = schema
+            .field_with_name(column)
+            .map_err(|_| Error::InvalidOperation(format!("No such column: {}", column)))?;
+        if !super::clustering::supports_type(field.data_type()) {
+            return Err(Error::InvalidOperation(format!(
+                "Can't Z-ORDER by {}: {} values can't be ordered",
+                column,
+                field.data_type()
+            )));
+        }
+        fields.push((column.to_string(), field.data_type().clone()));
+    }
+
+    // Files of a run over other columns were clustered for those columns
+    let columns_label = columns.join(",");
+    let runs: Vec<Option<&str>> =
+        log.files
+            .iter()
+            .map(|file| {
+                file.tags.get(ZORDER_TAG).map(String::as_str).filter(|run| {
+                    run.split_once('@').map(|(c, _)| c) == Some(columns_label.as_str())
+                })
+            })
+            .collect();
+    let files: Vec<FileStats> = log.files.iter().cloned().map(FileStats::from).collect();
+    let mut bounds = vec![Vec::with_capacity(fields.len()); files.len()];
+    for (column, data_type) in &fields {
+        for (file, bound) in column_bounds(&files, column, data_type)?
+            .into_iter()
+            .enumerate()
+        {
+            bounds[file].push(bound);
+        }
+    }
+
+    let selected = overlapping(&bounds, &runs);
+    let mut metrics = OptimizeMetrics {
+        num_files_added: 0,
+        num_files_removed: 0,
+        total_files_skipped: (files.len() - selected.len()) as u64,
+        total_considered_files: files.len() as u64,
+        preserve_insertion_order: false,
+        partitions_optimized: 0,
+        bytes_added: 0,
+        bytes_removed: 0,
+        files_before: files.len() as u64,
+        files_after: files.len() as u64,
+        cancelled: false,
+    };
+    if selected.is_empty() {
+        info!("Z-ORDER: no overlapping files among {}", files.len());
+        return Ok(Some(metrics));
+    }
+    info!(
+        "Z-ORDER rewriting {} of {} files by {}",
+        selected.len(),
+        files.len(),
+        columns_label
+    );
+
+    let reader = crate::storage::parquet::ParquetReader::new();
+    let mut batches = Vec::with_capacity(selected.len());
+    for &file in &selected {
+        let path = crate::metadata::snapshot::percent_decode(&files[file].path)?;
+        batches.push(conform(&reader.read_batch(base_path.join(path))?, &schema)?);
+    }
+    let rows = arrow::compute::concat_batches(&schema, &batches)?;
+    drop(batches);
+    let sorted = sort_by_zorder(&rows, columns)?;
+    drop(rows);
+
+    let target_size = target_size.unwrap_or(DEFAULT_TARGET_SIZE);
+    let mut adds = Vec::new();
+    for slice in super::split_to_target_size(&sorted, target_size)? {
+        writer.write(slice).await.map_err(Error::DeltaTable)?;
+        // Flushing after each slice keeps it in a file of its own
+        adds.extend(writer.flush().await.map_err(Error::DeltaTable)?);
+    }
+
+    let now = chrono::Utc::now().timestamp_millis();
+    let run = format!("{}@{}", columns_label, now);
+    let mut actions: Vec<Action> = selected
+        .iter()
+        .map(|&file| {
+            Action::Remove(Remove {
+                path: files[file].path.clone(),
+                deletion_timestamp: Some(now),
+                data_change: false,
+                size: Some(files[file].size_bytes as i64),
+                ..Default::default()
+            })
+        })
+        .collect();
+    metrics.num_files_removed = selected.len() as u64;
+    metrics.bytes_removed = selected.iter().map(|&f| files[f].size_bytes).sum();
+    metrics.num_files_added = adds.len() as u64;
+    metrics.bytes_added = adds.iter().map(|add| add.size.max(0) as u64).sum();
+    metrics.files_after =
+        metrics.files_before + metrics.num_files_added - metrics.num_files_removed;
+    metrics.partitions_optimized = 1;
+    for mut add in adds {
+        add.data_change = false;
+        add.tags = Some(HashMap::from([(ZORDER_TAG.to_string(), Some(run.clone()))]));
+        actions.push(Action::Add(add));
+    }
+
+    let snapshot = table.snapshot().map_err(Error::DeltaTable)?;
+    CommitBuilder::default()
+        .with_app_metadata(super::commit::app_metadata())
+        .with_actions(actions)
+        .build(
+            Some(snapshot),
+            table.log_store(),
+            DeltaOperation::Optimize {
+                predicate: None,
+                target_size: target_size as i64,
+            },
+        )
+        .await
+        .map_err(Error::DeltaTable)?;
+    Ok(Some(metrics))
+}
+
+/// Minimum and maximum of `column` in each of `files`, as rows of the
+/// column's order-preserving encoding, or `None` for a file without
+/// statistics for it
+fn column_bounds(
+    files: &[FileStats],
+    column: &str,
+    data_type: &DataType,
+) -> Result<Vec<Option<(OwnedRow, OwnedRow)>>> {
+    let text = |value: Option<&serde_json::Value>| match value? {
+        serde_json::Value::String(s) => Some(s.clone()),
+        serde_json::Value::Number(n) => Some(n.to_string()),
+        serde_json::Value::Bool(b) => Some(b.to_string()),
+        _ => None,
+    };
+    // Statistics are JSON; cast them back to the column's type
+    let to_array = |values: Vec<Option<String>>| -> Result<ArrayRef> {
+        let strings: ArrayRef = Arc::new(StringArray::from(values));
+        Ok(arrow::compute::cast(&strings, data_type)?)
+    };
+    let mins = to_array(
+        files
+            .iter()
+            .map(|file| text(file.min_values.get(column)))
+            .collect(),
+    )?;
+    let maxs = to_array(
+        files
+            .iter()
+            .map(|file| text(file.max_values.get(column)))
+            .collect(),
+    )?;
+
+    let converter = RowConverter::new(vec![SortField::new(data_type.clone())])?;
+    let min_rows = converter.convert_columns(&[mins.clone()])?;
+    let max_rows = converter.convert_columns(&[maxs.clone()])?;
+    Ok((0..files.len())
+        .map(|i| {
+            (mins.is_valid(i) && maxs.is_valid(i))
+                .then(|| (min_rows.row(i).owned(), max_rows.row(i).owned()))
+        })
+        .collect())
+}
+
+/// Indices of the files whose boxes overlap another file's on every column
+///
+/// `bounds` holds each file's (min, max) per column, `None` where unknown,
+/// which overlaps anything. Files of the same run in `runs` don't count as
+/// overlapping each other.
+fn overlapping<T: Ord>(bounds: &[Vec<Option<(T, T)>>], runs: &[Option<&str>]) -> Vec<usize> {
+    let intersects = |a: &[Option<(T, T)>], b: &[Option<(T, T)>]| {
+        a.iter().zip(b).all(|pair| match pair {
+            (Some((a_min, a_max)), Some((b_min, b_max))) => a_min <= b_max && b_min <= a_max,
+            _ => true,
+        })
+    };
+    let mut selected = vec![false; bounds.len()];
+    for i in 0..bounds.len() {
+        for j in i + 1..bounds.len() {
+            if runs[i].is_some() && runs[i] == runs[j] {
+                continue;
+            }
+            if intersects(&bounds[i], &bounds[j]) {
+                selected[i] = true;
+                selected[j] = true;
+            }
+        }
+    }
+    (0..bounds.len()).filter(|&i| selected[i]).collect()
+}
+
+/// `batch` with the columns of `schema`, cast to its types, NULL where
+/// `batch` lacks them
+fn conform(batch: &RecordBatch, schema: &SchemaRef) -> Result<RecordBatch> {
+    let columns = schema
+        .fields()
+        .iter()
+        .map(|field| match batch.column_by_name(field.name()) {
+            Some(column) => Ok(arrow::compute::cast(column, field.data_type())?),
+            None => Ok(new_null_array(field.data_type(), batch.num_rows())),
+        })
+        .collect::<Result<Vec<_>>>()?;
+    Ok(RecordBatch::try_new(schema.clone(), columns)?)
+}
+
+/// `batch` sorted along the Z-curve over `columns`
+pub fn sort_by_zorder(batch: &RecordBatch, columns: &[&str]) -> Result<RecordBatch> {
+    let keys = zorder_keys(batch, columns)?;
+    let mut order: Vec<u32> = (0..batch.num_rows() as u32).collect();
+    order.sort_by(|&a, &b| keys[a as usize].cmp(&keys[b as usize]));
+    Ok(arrow::compute::take_record_batch(
+        batch,
+        &UInt32Array::from(order),
+    )?)
+}
+
+/// Position of each row of `batch` on the Z-curve over `columns`
+///
+/// Each column's values are encoded in arrow's order-preserving row format,
+/// the first `KEY_BYTES` bytes taken as a big-endian integer, and the
+/// integers' bits interleaved from the most significant down.
+fn zorder_keys(batch: &RecordBatch, columns: &[&str]) -> Result<Vec<Vec<u8>>> {
+    let mut values: Vec<Vec<u128>> = Vec::with_capacity(columns.len());
+    for column in columns {
+        let array = batch
+            .column_by_name(column)
+            .ok_or_else(|| Error::InvalidOperation(format!("No such column: {}", column)))?;
+        let converter = RowConverter::new(vec![SortField::new(array.data_type().clone())])?;
+        let rows = converter.convert_columns(&[array.clone()])?;
+        values.push(
+            (0..rows.num_rows())
+                .map(|i| {
+                    let mut prefix = [0u8; KEY_BYTES];
+                    let bytes = rows.row(i);
+                    let bytes = bytes.as_ref();
+                    let len = bytes.len().min(KEY_BYTES);
+                    prefix[..len].copy_from_slice(&bytes[..len]);
+                    u128::from_be_bytes(prefix)
+                })
+                .collect(),
+        );
+    }
+
+    Ok((0..batch.num_rows())
+        .map(|row| {
+            let mut key = vec![0u8; KEY_BYTES * columns.len()];
+            let mut bit = 0;
+            for shift in (0..128).rev() {
+                for column in &values {
+                    if column[row] >> shift & 1 == 1 {
+                        key[bit / 8] |= 0x80 >> (bit % 8);
+                    }
+                    bit += 1;
+                }
+            }
+            key
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow::array::Int64Array;
+    use arrow::datatypes::{Field, Schema};
+
+    #[test]
+    fn test_parse_columns() {
+        assert_eq!(parse_columns("x, y,,z "), vec!["x", "y", "z"]);
+        assert!(parse_columns("").is_empty());
+    }
+
+    #[test]
+    fn test_overlapping_boxes() {
+        let boxes = vec![
+            // Apart from each other on x
+            vec![Some((0, 10)), Some((0, 10))],
+            vec![Some((20, 30)), Some((0, 10))],
+            // Overlaps the first on x but not on y
+            vec![Some((5, 15)), Some((50, 60))],
+            // Overlaps the second on both
+            vec![Some((25, 40)), Some((5, 8))],
+        ];
+        assert_eq!(overlapping(&boxes, &[None; 4]), vec![1, 3]);
+
+        // Files of one run don't count against each other
+        let runs = [None, Some("x,y@1"), None, Some("x,y@1")];
+        assert!(overlapping(&boxes, &runs).is_empty());
+
+        // Unknown bounds overlap everything
+        let mut unknown = boxes.clone();
+        unknown[2][1] = None;
+        assert_eq!(overlapping(&unknown, &[None; 4]), vec![0, 1, 2, 3]);
+    }
+
+    #[test]
+    fn test_sort_by_zorder_interleaves_columns() {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("x", DataType::Int64, false),
+            Field::new("y", DataType::Int64, false),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema,
+            vec![
+                Arc::new(Int64Array::from(vec![1, 0, 1, 0])),
+                Arc::new(Int64Array::from(vec![1, 1, 0, 0])),
+            ],
+        )
+        .unwrap();
+
+        let sorted = sort_by_zorder(&batch, &["x", "y"]).unwrap();
+        let pairs: Vec<(i64, i64)> = (0..4)
+            .map(|i| {
+                let value = |c: usize| {
+                    sorted
+                        .column(c)
+                        .as_any()
+                        .downcast_ref::<Int64Array>()
+                        .unwrap()
+                        .value(i)
+                };
+                (value(0), value(1))
+            })
+            .collect();
+        assert_eq!(pairs, vec![(0, 0), (0, 1), (1, 0), (1, 1)]);
+    }
+}
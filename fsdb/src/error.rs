@@ -39,6 +39,118 @@ pub enum Error {
     #[error("Bincode error: {0}")]
     Bincode(String),
 
+    #[error("Type coercion failed at row {row}, column '{column}': {message}")]
+    TypeCoercion {
+        row: usize,
+        column: String,
+        message: String,
+    },
+
+    #[error("Row {row} is {bytes} bytes, over the limit of {limit}")]
+    RowTooLarge {
+        row: usize,
+        bytes: usize,
+        limit: usize,
+    },
+
+    #[error("Value in row {row}, column '{column}' is {bytes} bytes, over the limit of {limit}")]
+    FieldTooLarge {
+        row: usize,
+        column: String,
+        bytes: usize,
+        limit: usize,
+    },
+
+    #[error("CSV doesn't match the table schema: {0}")]
+    SchemaMismatch(String),
+
+    #[error("Cursor {0} has expired or does not exist")]
+    CursorExpired(u64),
+
+    #[error("Ambiguous identifier '{identifier}': matches {}", .candidates.join(", "))]
+    AmbiguousIdentifier {
+        identifier: String,
+        candidates: Vec<String>,
+    },
+
+    /// An unqualified column exists in more than one table or alias of a query
+    #[error("Column '{column}' is ambiguous: qualify it with a table name or alias")]
+    AmbiguousColumn { column: String },
+
+    /// The branches of a union have different column counts or incompatible column types
+    #[error("Union mismatch: {0}")]
+    UnionMismatch(String),
+
+    /// `position` is the 1-based line and column of the error, when known
+    #[error("Invalid SQL{}: {message}", at_position(.position))]
+    InvalidSql {
+        message: String,
+        position: Option<(u64, u64)>,
+    },
+
+    #[error("Deadlock detected: transaction {victim} aborted to break cycle {cycle:?}")]
+    DeadlockDetected { victim: u64, cycle: Vec<u64> },
+
+    #[error(
+        "Spill limit of {limit} bytes exceeded: {used} bytes in use, {requested} more requested"
+    )]
+    SpillLimitExceeded {
+        requested: u64,
+        used: u64,
+        limit: u64,
+    },
+
+    #[error("Query exceeded its memory limit of {limit} bytes: {detail}")]
+    QueryMemoryExceeded { limit: u64, detail: String },
+
+    #[error("Version {0} is no longer in the Delta log or its data files were vacuumed")]
+    VersionGone(u64),
+
+    #[error("Unsupported Delta feature: {0}")]
+    UnsupportedDeltaFeature(String),
+
+    #[error("Decompression failed: {0}")]
+    Decompression(String),
+
+    #[error("Commit metadata is {bytes} bytes, over the limit of {limit}")]
+    CommitMetadataTooLarge { bytes: usize, limit: usize },
+
+    /// Another OPTIMIZE, VACUUM or Z-ORDER holds the table's maintenance lock
+    #[error("Maintenance already in progress: {0}")]
+    MaintenanceInProgress(String),
+
+    /// The cursor or transaction was cancelled by another caller
+    #[error("Cancelled: {0}")]
+    Cancelled(String),
+
+    /// A write would take a user past their quota
+    #[error(
+        "Quota exceeded for '{username}': {used} {resource} stored, {requested} more, limit {limit}"
+    )]
+    QuotaExceeded {
+        username: String,
+        resource: String,
+        used: u64,
+        requested: u64,
+        limit: u64,
+    },
+
+    /// More than one source row of a MERGE matched the same target row
+    #[error("MERGE is ambiguous: {matches} source rows match the target row with {key}")]
+    AmbiguousMerge { key: String, matches: usize },
+
+    /// The user's roles don't grant a permission, or access to a table
+    #[error("Permission denied: {0}")]
+    PermissionDenied(String),
+
+    /// Authentication is enabled and no valid credentials were given
+    #[error("Authentication required: {0}")]
+    AuthenticationRequired(String),
+
+    /// A value couldn't be encrypted or decrypted, or its column key is missing
+    #[error("Encryption error: {0}")]
+    Encryption(String),
+
     #[error("Delta Lake error: {0}")]
     DeltaTable(#[from] deltalake::DeltaTableError),
 
@@ -46,6 +158,12 @@ pub enum Error {
     Other(String),
 }
 
+fn at_position(position: &Option<(u64, u64)>) -> String {
+    position
+        .map(|(line, column)| format!(" at line {}, column {}", line, column))
+        .unwrap_or_default()
+}
+
 impl From<bincode::error::EncodeError> for Error {
     fn from(err: bincode::error::EncodeError) -> Self {
         Error::Bincode(err.to_string())
@@ -60,6 +178,17 @@ impl From<bincode::error::DecodeError> for Error {
 
 impl From<datafusion::error::DataFusionError> for Error {
     fn from(err: datafusion::error::DataFusionError) -> Self {
+        use datafusion::common::SchemaError;
+        use datafusion::error::DataFusionError;
+
+        if let DataFusionError::SchemaError(schema_err, _) = err.find_root() {
+            let schema_err: &SchemaError = schema_err;
+            if let SchemaError::AmbiguousReference { field } = schema_err {
+                return Error::AmbiguousColumn {
+                    column: field.name.clone(),
+                };
+            }
+        }
         Error::InvalidOperation(err.to_string())
     }
 }
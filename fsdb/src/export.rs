@@ -0,0 +1,260 @@
+//! Streaming export of a table to CSV, NDJSON or Parquet
+//!
+//! Rows are read a batch at a time, encoded in the chosen format and written
+//! to the caller's writer before the next batch is read. A slow consumer, such
+//! as a pipe or socket, holds the export back instead of letting encoded data
+//! pile up, so memory stays bounded by one batch (one row group for Parquet)
+//! however large the table.
+
+use crate::database_ops::DatabaseOps;
+use crate::nfs::coercion::{write_csv, write_csv_rows, CoercionPolicy};
+use crate::{Error, Result};
+use arrow::array::RecordBatch;
+use arrow::json::LineDelimitedWriter;
+use futures::TryStreamExt;
+use parquet::arrow::ArrowWriter;
+use parquet::basic::Compression;
+use parquet::file::properties::WriterProperties;
+use std::str::FromStr;
+use tokio::io::{AsyncWrite, AsyncWriteExt};
+use tracing::debug;
+
+/// Encoding of exported rows
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    /// CSV with a header row, written the way the table's CSV view writes it
+    Csv,
+    /// One JSON object per line
+    NdJson,
+    /// A single Parquet file
+    Parquet,
+}
+
+impl FromStr for ExportFormat {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "csv" => Ok(Self::Csv),
+            "ndjson" | "jsonl" => Ok(Self::NdJson),
+            "parquet" => Ok(Self::Parquet),
+            _ => Err(Error::InvalidOperation(format!(
+                "Unknown export format '{}': use csv, ndjson or parquet",
+                s
+            ))),
+        }
+    }
+}
+
+/// Summary of a completed export
+#[derive(Debug, Clone, Default)]
+pub struct ExportReport {
+    /// Rows written
+    pub rows: u64,
+
+    /// Batches read from the table
+    pub batches: u64,
+
+    /// Bytes written to the writer
+    pub bytes: u64,
+}
+
+/// Options for `DatabaseOps::export`
+#[derive(Debug, Clone)]
+pub struct ExportOptions {
+    /// Columns exported, in this order; all of them by default
+    pub columns: Option<Vec<String>>,
+
+    /// SQL predicate rows must match, e.g. `region = 'eu' AND amount > 10`
+    pub filter: Option<String>,
+
+    /// How CSV cells are spelled (NULL, booleans, binary columns)
+    pub coercion: CoercionPolicy,
+
+    /// Rows per Parquet row group, which is buffered before it's written
+    pub row_group_rows: usize,
+}
+
+impl Default for ExportOptions {
+    fn default() -> Self {
+        Self {
+            columns: None,
+            filter: None,
+            coercion: CoercionPolicy::default(),
+            row_group_rows: 65_536,
+        }
+    }
+}
+
+impl ExportOptions {
+    /// Export only `columns`, in this order
+    pub fn with_columns<S: Into<String>>(mut self, columns: impl IntoIterator<Item = S>) -> Self {
+        self.columns = Some(columns.into_iter().map(Into::into).collect());
+        self
+    }
+
+    /// Export only the rows matching `predicate`
+    pub fn with_filter(mut self, predicate: impl Into<String>) -> Self {
+        self.filter = Some(predicate.into());
+        self
+    }
+
+    /// Use a custom coercion policy for CSV
+    pub fn with_coercion(mut self, policy: CoercionPolicy) -> Self {
+        self.coercion = policy;
+        self
+    }
+
+    /// Write a Parquet row group every `rows` rows
+    pub fn with_row_group_rows(mut self, rows: usize) -> Self {
+        self.row_group_rows = rows;
+        self
+    }
+
+    /// The query reading the exported rows
+    fn sql(&self) -> String {
+        let select = match self.columns {
+            Some(ref columns) => columns
+                .iter()
+                .map(|c| format!("\"{}\"", c.replace('"', "\"\"")))
+                .collect::<Vec<_>>()
+                .join(", "),
+            None => "*".to_string(),
+        };
+        match self.filter {
+            Some(ref filter) => format!("SELECT {} FROM data WHERE {}", select, filter),
+            None => format!("SELECT {} FROM data", select),
+        }
+    }
+}
+
+/// Encodes batches into chunks of output
+enum Encoder {
+    Csv(CoercionPolicy),
+    NdJson,
+    Parquet(ArrowWriter<Vec<u8>>),
+}
+
+impl Encoder {
+    /// Bytes for `batch`, possibly none while a row group fills up
+    fn encode(&mut self, batch: &RecordBatch) -> Result<Vec<u8>> {
+        match self {
+            Self::Csv(policy) => write_csv_rows(batch, policy),
+            Self::NdJson => {
+                let mut writer = LineDelimitedWriter::new(Vec::new());
+                writer.write(batch)?;
+                writer.finish()?;
+                Ok(writer.into_inner())
+            }
+            Self::Parquet(writer) => {
+                writer.write(batch)?;
+                // Row groups are written to the buffer as they fill; the
+                // writer tracks its offset itself, so the buffer can be drained
+                Ok(std::mem::take(writer.inner_mut()))
+            }
+        }
+    }
+
+    /// Bytes ending the output: Parquet's last row group and footer
+    fn finish(self) -> Result<Vec<u8>> {
+        match self {
+            Self::Csv(_) | Self::NdJson => Ok(Vec::new()),
+            Self::Parquet(writer) => Ok(writer.into_inner()?),
+        }
+    }
+}
+
+/// Export the rows of `db` chosen by `options` to `writer` as `format`
+pub(crate) async fn export<W>(
+    db: &DatabaseOps,
+    writer: &mut W,
+    format: ExportFormat,
+    options: &ExportOptions,
+) -> Result<ExportReport>
+where
+    W: AsyncWrite + Unpin + ?Sized,
+{
+    if options.row_group_rows == 0 {
+        return Err(Error::InvalidOperation(
+            "Parquet row groups need at least one row".to_string(),
+        ));
+    }
+
+    let mut batches = db.stream_sql(&options.sql()).await?;
+    let schema = batches.schema();
+    let mut encoder = match format {
+        ExportFormat::Csv => Encoder::Csv(options.coercion.clone()),
+        ExportFormat::NdJson => Encoder::NdJson,
+        ExportFormat::Parquet => {
+            let properties = WriterProperties::builder()
+                .set_compression(Compression::SNAPPY)
+                .set_max_row_group_size(options.row_group_rows)
+                .build();
+            Encoder::Parquet(ArrowWriter::try_new(
+                Vec::new(),
+                schema.clone(),
+                Some(properties),
+            )?)
+        }
+    };
+
+    let mut report = ExportReport::default();
+    if format == ExportFormat::Csv {
+        // The header comes from an empty batch, so it's there with no rows
+        let header = write_csv(&RecordBatch::new_empty(schema), &options.coercion)?;
+        writer.write_all(&header).await?;
+        report.bytes += header.len() as u64;
+    }
+
+    // The next batch isn't read until the writer has taken this one
+    while let Some(batch) = batches.try_next().await? {
+        report.batches += 1;
+        report.rows += batch.num_rows() as u64;
+        let chunk = encoder.encode(&batch)?;
+        if !chunk.is_empty() {
+            writer.write_all(&chunk).await?;
+            report.bytes += chunk.len() as u64;
+        }
+    }
+    let tail = encoder.finish()?;
+    writer.write_all(&tail).await?;
+    report.bytes += tail.len() as u64;
+    writer.flush().await?;
+
+    debug!(
+        "Exported {} rows in {} batches ({} bytes)",
+        report.rows, report.batches, report.bytes
+    );
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_export_sql() {
+        assert_eq!(ExportOptions::default().sql(), "SELECT * FROM data");
+        let options = ExportOptions::default()
+            .with_columns(["id", "say \"hi\""])
+            .with_filter("id > 1");
+        assert_eq!(
+            options.sql(),
+            "SELECT \"id\", \"say \"\"hi\"\"\" FROM data WHERE id > 1"
+        );
+    }
+
+    #[test]
+    fn test_format_names() {
+        assert_eq!("CSV".parse::<ExportFormat>().unwrap(), ExportFormat::Csv);
+        assert_eq!(
+            "jsonl".parse::<ExportFormat>().unwrap(),
+            ExportFormat::NdJson
+        );
+        assert_eq!(
+            "parquet".parse::<ExportFormat>().unwrap(),
+            ExportFormat::Parquet
+        );
+        assert!("xlsx".parse::<ExportFormat>().is_err());
+    }
+}
@@ -0,0 +1,119 @@
+//! Arrow Flight SQL server
+//!
+//! BI tools and the Flight SQL drivers for Python, Java (JDBC) and Go query
+//! FSDB over the standard Flight SQL protocol, getting results as Arrow
+//! record batches with no conversion to text. Queries run through the same
+//! `DatabaseOps::query` as everything else, against `data` and the named
+//! tables.
+//!
+//! Supported: statement queries, prepared statements without parameters,
+//! and listing tables (`GetTables`, `GetCatalogs`, `GetDbSchemas`). With
+//! authentication enabled, clients log in with the handshake, sending Basic
+//! credentials and getting a bearer token for the rest of the connection;
+//! see `service`. Basic credentials on each call work too.
+
+pub mod service;
+
+use crate::database_ops::DatabaseOps;
+use crate::error::{Error, Result};
+use crate::flight::service::FsdbFlightSqlService;
+use arrow_flight::flight_service_server::FlightServiceServer;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tracing::{error, info};
+
+/// Where the Flight SQL server listens and how long tokens last
+#[derive(Debug, Clone)]
+pub struct FlightSqlConfig {
+    /// Address to bind; port 0 picks a free port, see `FlightSqlServer::local_addr`
+    pub addr: SocketAddr,
+
+    /// How long a handshake's token stays valid while unused
+    pub token_ttl: Duration,
+}
+
+impl Default for FlightSqlConfig {
+    fn default() -> Self {
+        Self {
+            addr: SocketAddr::from(([127, 0, 0, 1], 32010)),
+            token_ttl: Duration::from_secs(3600),
+        }
+    }
+}
+
+/// Flight SQL server answering queries against a database
+///
+/// Stops when `shutdown` is called or the server is dropped.
+pub struct FlightSqlServer {
+    local_addr: SocketAddr,
+    shutdown_tx: Option<mpsc::Sender<()>>,
+}
+
+impl FlightSqlServer {
+    /// Create and start a server on `127.0.0.1:port`
+    pub async fn new(db: Arc<DatabaseOps>, port: u16) -> Result<Self> {
+        let config = FlightSqlConfig {
+            addr: SocketAddr::from(([127, 0, 0, 1], port)),
+            ..FlightSqlConfig::default()
+        };
+        Self::with_config(db, config).await
+    }
+
+    /// Create and start a server as `config` describes
+    pub async fn with_config(db: Arc<DatabaseOps>, config: FlightSqlConfig) -> Result<Self> {
+        info!("Starting FSDB Flight SQL server on {}", config.addr);
+
+        let listener = tokio::net::TcpListener::bind(config.addr)
+            .await
+            .map_err(|e| {
+                Error::InvalidOperation(format!(
+                    "Failed to bind Flight SQL server to {}: {}",
+                    config.addr, e
+                ))
+            })?;
+        let local_addr = listener.local_addr()?;
+        let service = FsdbFlightSqlService::new(db, config.token_ttl);
+
+        let (shutdown_tx, mut shutdown_rx) = mpsc::channel::<()>(1);
+        tokio::spawn(async move {
+            let shutdown = async move {
+                // A dropped server closes the channel, which stops it too
+                shutdown_rx.recv().await;
+                info!("Flight SQL server received shutdown signal");
+            };
+            let result = tonic::transport::Server::builder()
+                .add_service(FlightServiceServer::new(service))
+                .serve_with_incoming_shutdown(
+                    tokio_stream::wrappers::TcpListenerStream::new(listener),
+                    shutdown,
+                )
+                .await;
+            if let Err(e) = result {
+                error!("Flight SQL server error: {:?}", e);
+            }
+            info!("Flight SQL server stopped");
+        });
+
+        info!("Flight SQL server listening on {}", local_addr);
+        Ok(Self {
+            local_addr,
+            shutdown_tx: Some(shutdown_tx),
+        })
+    }
+
+    /// Address the server is listening on
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+
+    /// Shutdown the Flight SQL server, letting calls in flight finish
+    pub async fn shutdown(mut self) -> Result<()> {
+        info!("Shutting down Flight SQL server");
+        if let Some(tx) = self.shutdown_tx.take() {
+            tx.send(()).await.ok();
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,391 @@
+//! The Flight SQL service: authentication, queries and table listings
+//!
+//! A handshake with Basic credentials logs the user in through
+//! `rest::sessions::Sessions`, the same as the HTTP API, and returns a
+//! bearer token naming the user's handle, in the `authorization` header and
+//! as the payload. Later calls send the token, or Basic credentials again.
+//! Tokens last for the configured TTL after their last use.
+//!
+//! Tickets and prepared statement handles carry the SQL itself, so they
+//! hold no server state and any connection of the same user can redeem them.
+
+use crate::database_ops::DatabaseOps;
+use crate::error::Error;
+use crate::rest::sessions::Sessions;
+use arrow::datatypes::{Schema, SchemaRef};
+use arrow::error::ArrowError;
+use arrow::ipc::writer::IpcWriteOptions;
+use arrow::record_batch::RecordBatch;
+use arrow_flight::encode::FlightDataEncoderBuilder;
+use arrow_flight::error::FlightError;
+use arrow_flight::flight_service_server::FlightService;
+use arrow_flight::sql::server::FlightSqlService;
+use arrow_flight::sql::{
+    ActionClosePreparedStatementRequest, ActionCreatePreparedStatementRequest,
+    ActionCreatePreparedStatementResult, CommandGetCatalogs, CommandGetDbSchemas, CommandGetTables,
+    CommandPreparedStatementQuery, CommandStatementQuery, ProstMessageExt, SqlInfo,
+    TicketStatementQuery,
+};
+use arrow_flight::{
+    Action, FlightData, FlightDescriptor, FlightEndpoint, FlightInfo, HandshakeRequest,
+    HandshakeResponse, IpcMessage, SchemaAsIpc, Ticket,
+};
+use futures::{Stream, TryStreamExt};
+use moka::future::Cache;
+use prost::Message;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::time::Duration;
+use tonic::{Request, Response, Status, Streaming};
+
+/// Catalog the tables are listed under, DataFusion's default
+pub const CATALOG: &str = "datafusion";
+
+/// Schema the tables are listed under, DataFusion's default
+pub const DB_SCHEMA: &str = "public";
+
+/// Most tokens kept at once
+const MAX_TOKENS: u64 = 10_000;
+
+type FlightDataStream = Pin<Box<dyn Stream<Item = Result<FlightData, Status>> + Send + 'static>>;
+
+/// Flight SQL over a database, answering as each caller's user
+pub struct FsdbFlightSqlService {
+    sessions: Sessions,
+    tokens: Cache<String, Arc<DatabaseOps>>,
+}
+
+impl FsdbFlightSqlService {
+    /// Service over `db`, its tokens expiring after `token_ttl` unused
+    pub fn new(db: Arc<DatabaseOps>, token_ttl: Duration) -> Self {
+        Self {
+            sessions: Sessions::new(db, token_ttl),
+            tokens: Cache::builder()
+                .max_capacity(MAX_TOKENS)
+                .time_to_idle(token_ttl)
+                .build(),
+        }
+    }
+
+    /// Handle of the user making `request`, from its token or credentials
+    async fn session<T>(&self, request: &Request<T>) -> Result<Arc<DatabaseOps>, Status> {
+        if !self.sessions.db().auth_enabled() {
+            return Ok(self.sessions.db().clone());
+        }
+        let authorization = request
+            .metadata()
+            .get("authorization")
+            .and_then(|value| value.to_str().ok());
+        if let Some(token) = authorization.and_then(|value| value.strip_prefix("Bearer ")) {
+            return self
+                .tokens
+                .get(token.trim())
+                .await
+                .ok_or_else(|| Status::unauthenticated("Unknown or expired token"));
+        }
+        self.sessions
+            .authenticate(authorization)
+            .await
+            .map_err(status)
+    }
+
+    /// Run `sql` as `db`'s user, streaming the result as Flight data
+    async fn run(&self, db: &DatabaseOps, sql: &str) -> Result<FlightDataStream, Status> {
+        let batches = db.query(sql).await.map_err(status)?;
+        let schema = match batches.first() {
+            Some(batch) => batch.schema(),
+            None => db.validate(sql).await.map_err(status)?,
+        };
+        Ok(batch_stream(schema, batches))
+    }
+
+    /// `data` and the named tables `db`'s user can read, with their schemas
+    /// if `include_schema`
+    async fn tables(
+        &self,
+        db: &DatabaseOps,
+        include_schema: bool,
+    ) -> Result<Vec<(String, SchemaRef)>, Status> {
+        let mut tables = vec![("data".to_string(), db.schema())];
+        for name in db.list_tables().map_err(status)? {
+            let schema = if include_schema {
+                db.table(&name).await.map_err(status)?.schema()
+            } else {
+                Arc::new(Schema::empty())
+            };
+            tables.push((name, schema));
+        }
+        Ok(tables)
+    }
+}
+
+#[tonic::async_trait]
+impl FlightSqlService for FsdbFlightSqlService {
+    type FlightService = FsdbFlightSqlService;
+
+    async fn do_handshake(
+        &self,
+        request: Request<Streaming<HandshakeRequest>>,
+    ) -> Result<
+        Response<Pin<Box<dyn Stream<Item = Result<HandshakeResponse, Status>> + Send>>>,
+        Status,
+    > {
+        let db = self.session(&request).await?;
+        let token = if self.sessions.db().auth_enabled() {
+            let token = uuid::Uuid::new_v4().to_string();
+            self.tokens.insert(token.clone(), db).await;
+            Some(token)
+        } else {
+            None
+        };
+
+        let handshake = HandshakeResponse {
+            protocol_version: 0,
+            payload: token.clone().unwrap_or_default().into(),
+        };
+        let stream: Pin<Box<dyn Stream<Item = Result<HandshakeResponse, Status>> + Send>> =
+            Box::pin(futures::stream::iter(vec![Ok(handshake)]));
+        let mut response = Response::new(stream);
+        if let Some(token) = token {
+            let header = format!("Bearer {}", token)
+                .parse()
+                .map_err(|_| Status::internal("Token isn't a valid header value"))?;
+            response.metadata_mut().insert("authorization", header);
+        }
+        Ok(response)
+    }
+
+    async fn get_flight_info_statement(
+        &self,
+        query: CommandStatementQuery,
+        request: Request<FlightDescriptor>,
+    ) -> Result<Response<FlightInfo>, Status> {
+        let db = self.session(&request).await?;
+        let schema = db.validate(&query.query).await.map_err(status)?;
+        let ticket = TicketStatementQuery {
+            statement_handle: query.query.into(),
+        };
+        flight_info(ticket, &schema, request.into_inner())
+    }
+
+    async fn do_get_statement(
+        &self,
+        ticket: TicketStatementQuery,
+        request: Request<Ticket>,
+    ) -> Result<Response<<Self as FlightService>::DoGetStream>, Status> {
+        let db = self.session(&request).await?;
+        let sql = statement_sql(&ticket.statement_handle)?;
+        Ok(Response::new(self.run(&db, &sql).await?))
+    }
+
+    async fn do_action_create_prepared_statement(
+        &self,
+        query: ActionCreatePreparedStatementRequest,
+        request: Request<Action>,
+    ) -> Result<ActionCreatePreparedStatementResult, Status> {
+        let db = self.session(&request).await?;
+        let schema = db.validate(&query.query).await.map_err(status)?;
+        let IpcMessage(dataset_schema) = SchemaAsIpc::new(&schema, &IpcWriteOptions::default())
+            .try_into()
+            .map_err(arrow_status)?;
+        Ok(ActionCreatePreparedStatementResult {
+            prepared_statement_handle: query.query.into(),
+            dataset_schema,
+            parameter_schema: Default::default(),
+        })
+    }
+
+    async fn do_action_close_prepared_statement(
+        &self,
+        _query: ActionClosePreparedStatementRequest,
+        _request: Request<Action>,
+    ) -> Result<(), Status> {
+        // Handles hold their SQL, so there's nothing to release
+        Ok(())
+    }
+
+    async fn get_flight_info_prepared_statement(
+        &self,
+        cmd: CommandPreparedStatementQuery,
+        request: Request<FlightDescriptor>,
+    ) -> Result<Response<FlightInfo>, Status> {
+        let db = self.session(&request).await?;
+        let sql = statement_sql(&cmd.prepared_statement_handle)?;
+        let schema = db.validate(&sql).await.map_err(status)?;
+        flight_info(cmd, &schema, request.into_inner())
+    }
+
+    async fn do_get_prepared_statement(
+        &self,
+        query: CommandPreparedStatementQuery,
+        request: Request<Ticket>,
+    ) -> Result<Response<<Self as FlightService>::DoGetStream>, Status> {
+        let db = self.session(&request).await?;
+        let sql = statement_sql(&query.prepared_statement_handle)?;
+        Ok(Response::new(self.run(&db, &sql).await?))
+    }
+
+    async fn get_flight_info_catalogs(
+        &self,
+        query: CommandGetCatalogs,
+        request: Request<FlightDescriptor>,
+    ) -> Result<Response<FlightInfo>, Status> {
+        self.session(&request).await?;
+        let schema = query.clone().into_builder().schema();
+        flight_info(query, &schema, request.into_inner())
+    }
+
+    async fn do_get_catalogs(
+        &self,
+        query: CommandGetCatalogs,
+        request: Request<Ticket>,
+    ) -> Result<Response<<Self as FlightService>::DoGetStream>, Status> {
+        self.session(&request).await?;
+        let mut builder = query.into_builder();
+        builder.append(CATALOG);
+        let batch = builder.build().map_err(arrow_status)?;
+        Ok(Response::new(batch_stream(batch.schema(), vec![batch])))
+    }
+
+    async fn get_flight_info_schemas(
+        &self,
+        query: CommandGetDbSchemas,
+        request: Request<FlightDescriptor>,
+    ) -> Result<Response<FlightInfo>, Status> {
+        self.session(&request).await?;
+        let schema = query.clone().into_builder().schema();
+        flight_info(query, &schema, request.into_inner())
+    }
+
+    async fn do_get_schemas(
+        &self,
+        query: CommandGetDbSchemas,
+        request: Request<Ticket>,
+    ) -> Result<Response<<Self as FlightService>::DoGetStream>, Status> {
+        self.session(&request).await?;
+        let mut builder = query.into_builder();
+        builder.append(CATALOG, DB_SCHEMA);
+        let batch = builder.build().map_err(arrow_status)?;
+        Ok(Response::new(batch_stream(batch.schema(), vec![batch])))
+    }
+
+    async fn get_flight_info_tables(
+        &self,
+        query: CommandGetTables,
+        request: Request<FlightDescriptor>,
+    ) -> Result<Response<FlightInfo>, Status> {
+        self.session(&request).await?;
+        let schema = query.clone().into_builder().schema();
+        flight_info(query, &schema, request.into_inner())
+    }
+
+    async fn do_get_tables(
+        &self,
+        query: CommandGetTables,
+        request: Request<Ticket>,
+    ) -> Result<Response<<Self as FlightService>::DoGetStream>, Status> {
+        let db = self.session(&request).await?;
+        let tables = self.tables(&db, query.include_schema).await?;
+        let mut builder = query.into_builder();
+        for (name, schema) in tables {
+            builder
+                .append(CATALOG, DB_SCHEMA, name, "TABLE", &schema)
+                .map_err(arrow_status)?;
+        }
+        let batch = builder.build().map_err(arrow_status)?;
+        Ok(Response::new(batch_stream(batch.schema(), vec![batch])))
+    }
+
+    async fn register_sql_info(&self, _id: i32, _result: &SqlInfo) {}
+}
+
+/// Flight info with one endpoint, whose ticket is `command`
+fn flight_info(
+    command: impl ProstMessageExt,
+    schema: &Schema,
+    descriptor: FlightDescriptor,
+) -> Result<Response<FlightInfo>, Status> {
+    let ticket = Ticket::new(command.as_any().encode_to_vec());
+    let info = FlightInfo::new()
+        .try_with_schema(schema)
+        .map_err(arrow_status)?
+        .with_endpoint(FlightEndpoint::new().with_ticket(ticket))
+        .with_descriptor(descriptor);
+    Ok(Response::new(info))
+}
+
+fn batch_stream(schema: SchemaRef, batches: Vec<RecordBatch>) -> FlightDataStream {
+    let batches = futures::stream::iter(batches.into_iter().map(Ok::<_, FlightError>));
+    Box::pin(
+        FlightDataEncoderBuilder::new()
+            .with_schema(schema)
+            .build(batches)
+            .map_err(Status::from),
+    )
+}
+
+/// The SQL a statement ticket or prepared statement handle carries
+fn statement_sql(handle: &[u8]) -> Result<String, Status> {
+    String::from_utf8(handle.to_vec())
+        .map_err(|_| Status::invalid_argument("Statement handle isn't UTF-8 SQL"))
+}
+
+/// gRPC status for `err`, classified as the HTTP API classifies it
+pub fn status(err: Error) -> Status {
+    use axum::http::StatusCode;
+
+    let message = err.to_string();
+    match crate::rest::routes::status_of(&err) {
+        StatusCode::UNAUTHORIZED => Status::unauthenticated(message),
+        StatusCode::FORBIDDEN => Status::permission_denied(message),
+        StatusCode::BAD_REQUEST => Status::invalid_argument(message),
+        StatusCode::NOT_FOUND => Status::not_found(message),
+        StatusCode::CONFLICT => Status::aborted(message),
+        _ => Status::internal(message),
+    }
+}
+
+fn arrow_status(err: ArrowError) -> Status {
+    Status::internal(err.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tonic::Code;
+
+    #[test]
+    fn test_status_codes() {
+        for (err, code) in [
+            (
+                Error::AuthenticationRequired("invalid credentials".to_string()),
+                Code::Unauthenticated,
+            ),
+            (
+                Error::PermissionDenied("Read".to_string()),
+                Code::PermissionDenied,
+            ),
+            (
+                Error::InvalidOperation("bad".to_string()),
+                Code::InvalidArgument,
+            ),
+            // Only the typed variant is a permission error
+            (
+                Error::Other("Permission denied: Read".to_string()),
+                Code::Internal,
+            ),
+            (Error::Other("broken".to_string()), Code::Internal),
+        ] {
+            assert_eq!(status(err).code(), code);
+        }
+    }
+
+    #[test]
+    fn test_statement_sql() {
+        assert_eq!(statement_sql(b"SELECT 1").unwrap(), "SELECT 1");
+        assert_eq!(
+            statement_sql(&[0xff, 0xfe]).unwrap_err().code(),
+            Code::InvalidArgument
+        );
+    }
+}
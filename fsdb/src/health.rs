@@ -0,0 +1,292 @@
+//! Lightweight health probes for running FSDB as a service
+//!
+//! `DatabaseOps::health` runs a handful of cheap checks, each bounded by a
+//! timeout, so it can be polled every few seconds by a load balancer. Every
+//! component reports its own state and latency; the overall state is the
+//! worst of them.
+
+use crate::{Error, Result};
+use object_store::path::Path as ObjectPath;
+use object_store::ObjectStore;
+use std::future::Future;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// Longest a single probe may take before it is considered failed
+const PROBE_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Probes slower than this report `Degraded`
+const SLOW_PROBE: Duration = Duration::from_millis(500);
+
+/// Name of the scratch file used to check the local disk is writable
+const WRITE_PROBE_FILE: &str = ".fsdb_health_probe";
+
+/// Health of a component, ordered from best to worst
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum HealthState {
+    /// Working normally
+    Healthy,
+
+    /// Usable, but slow or with a non-critical failure (e.g. cache disk full)
+    Degraded,
+
+    /// Not usable; should be taken out of rotation
+    Unhealthy,
+}
+
+impl std::fmt::Display for HealthState {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            HealthState::Healthy => write!(f, "healthy"),
+            HealthState::Degraded => write!(f, "degraded"),
+            HealthState::Unhealthy => write!(f, "unhealthy"),
+        }
+    }
+}
+
+/// Result of probing one component
+#[derive(Debug, Clone)]
+pub struct ComponentHealth {
+    /// Component name: "storage", "delta_log", "local_disk" or "write_buffer"
+    pub name: &'static str,
+    pub state: HealthState,
+    pub latency: Duration,
+    /// Reason for a state other than `Healthy`
+    pub detail: Option<String>,
+}
+
+/// Result of `DatabaseOps::health`
+#[derive(Debug, Clone)]
+pub struct HealthReport {
+    /// Worst state across all components
+    pub status: HealthState,
+    pub components: Vec<ComponentHealth>,
+    /// Total time spent probing
+    pub latency: Duration,
+}
+
+impl HealthReport {
+    /// Build a report from component results
+    pub fn from_components(components: Vec<ComponentHealth>, latency: Duration) -> Self {
+        let status = components
+            .iter()
+            .map(|c| c.state)
+            .max()
+            .unwrap_or(HealthState::Healthy);
+        Self {
+            status,
+            components,
+            latency,
+        }
+    }
+
+    /// Look up a component by name
+    pub fn component(&self, name: &str) -> Option<&ComponentHealth> {
+        self.components.iter().find(|c| c.name == name)
+    }
+
+    /// True unless the database is `Unhealthy`
+    pub fn is_ready(&self) -> bool {
+        self.status != HealthState::Unhealthy
+    }
+}
+
+/// Run `probe` with a timeout, reporting `on_failure` if it errors or times out
+pub(crate) async fn probe<F>(
+    name: &'static str,
+    on_failure: HealthState,
+    probe: F,
+) -> ComponentHealth
+where
+    F: Future<Output = Result<()>>,
+{
+    let start = Instant::now();
+    let result = tokio::time::timeout(PROBE_TIMEOUT, probe).await;
+    let latency = start.elapsed();
+
+    let (state, detail) = match result {
+        Ok(Ok(())) if latency > SLOW_PROBE => (
+            HealthState::Degraded,
+            Some(format!("slow response ({} ms)", latency.as_millis())),
+        ),
+        Ok(Ok(())) => (HealthState::Healthy, None),
+        Ok(Err(e)) => (on_failure, Some(e.to_string())),
+        Err(_) => (
+            on_failure,
+            Some(format!("timed out after {} ms", PROBE_TIMEOUT.as_millis())),
+        ),
+    };
+    ComponentHealth {
+        name,
+        state,
+        latency,
+        detail,
+    }
+}
+
+/// Check that a local table directory exists
+pub(crate) async fn check_local_storage(base_path: &Path) -> Result<()> {
+    let metadata = std::fs::metadata(base_path)?;
+    if !metadata.is_dir() {
+        return Err(Error::Other(format!(
+            "{} is not a directory",
+            base_path.display()
+        )));
+    }
+    Ok(())
+}
+
+/// Check that the latest commit in a local Delta log can be read
+pub(crate) async fn check_local_delta_log(base_path: &Path) -> Result<()> {
+    let log_dir = base_path.join("_delta_log");
+    let latest = std::fs::read_dir(&log_dir)?
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| commit_version(&entry.file_name().to_string_lossy()))
+        .max()
+        .ok_or_else(|| Error::Other("Delta log has no commits".to_string()))?;
+
+    let commit = std::fs::read(log_dir.join(format!("{:020}.json", latest)))?;
+    validate_commit(&commit)
+}
+
+/// Check that the local disk accepts writes
+///
+/// For S3 tables this is the query cache, so a failure only degrades service.
+pub(crate) async fn check_local_disk(base_path: &Path) -> Result<()> {
+    let probe_path = base_path.join(WRITE_PROBE_FILE);
+    std::fs::write(&probe_path, b"ok")?;
+    std::fs::remove_file(&probe_path)?;
+    Ok(())
+}
+
+/// Connection to the bucket holding an S3 table
+pub(crate) struct S3Probe {
+    store: Arc<dyn ObjectStore>,
+    prefix: ObjectPath,
+}
+
+impl S3Probe {
+    /// Build a probe from the table URL and the settings it's reached with
+    pub(crate) fn new(s3_url: &str, config: &crate::storage::s3::S3Config) -> Result<Self> {
+        use crate::storage::s3::{parse_s3_uri, parse_s3_url};
+
+        let (_, prefix) = parse_s3_uri(s3_url)?;
+        let store = config.object_store(&parse_s3_url(s3_url)?)?;
+        Ok(Self {
+            store,
+            prefix: ObjectPath::from(prefix.trim_end_matches('/')),
+        })
+    }
+
+    /// Check the bucket answers a listing of the table prefix
+    pub(crate) async fn check_storage(&self) -> Result<()> {
+        self.store.list_with_delimiter(Some(&self.prefix)).await?;
+        Ok(())
+    }
+
+    /// Check that the latest commit in the Delta log can be read
+    pub(crate) async fn check_delta_log(&self) -> Result<()> {
+        let log_prefix = self.prefix.child("_delta_log");
+        let listing = self.store.list_with_delimiter(Some(&log_prefix)).await?;
+        let latest = listing
+            .objects
+            .iter()
+            .filter_map(|object| {
+                let name = object.location.filename()?;
+                commit_version(name).map(|version| (version, &object.location))
+            })
+            .max_by_key(|(version, _)| *version)
+            .map(|(_, location)| location.clone())
+            .ok_or_else(|| Error::Other("Delta log has no commits".to_string()))?;
+
+        let commit = self.store.get(&latest).await?.bytes().await?;
+        validate_commit(&commit)
+    }
+}
+
+/// Parse the version from a commit file name such as `00000000000000000003.json`
+fn commit_version(file_name: &str) -> Option<i64> {
+    file_name.strip_suffix(".json")?.parse().ok()
+}
+
+/// Check every line of a commit is a JSON action
+fn validate_commit(commit: &[u8]) -> Result<()> {
+    for line in commit.split(|b| *b == b'\n') {
+        if line.iter().all(u8::is_ascii_whitespace) {
+            continue;
+        }
+        serde_json::from_slice::<serde_json::Value>(line)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_overall_state_is_worst_component() {
+        let component = |name, state| ComponentHealth {
+            name,
+            state,
+            latency: Duration::ZERO,
+            detail: None,
+        };
+        let report = HealthReport::from_components(
+            vec![
+                component("storage", HealthState::Healthy),
+                component("local_disk", HealthState::Degraded),
+            ],
+            Duration::ZERO,
+        );
+        assert_eq!(report.status, HealthState::Degraded);
+        assert!(report.is_ready());
+
+        let report = HealthReport::from_components(
+            vec![
+                component("storage", HealthState::Unhealthy),
+                component("local_disk", HealthState::Degraded),
+            ],
+            Duration::ZERO,
+        );
+        assert_eq!(report.status, HealthState::Unhealthy);
+        assert!(!report.is_ready());
+    }
+
+    #[test]
+    fn test_commit_version() {
+        assert_eq!(commit_version("00000000000000000012.json"), Some(12));
+        assert_eq!(
+            commit_version("00000000000000000010.checkpoint.parquet"),
+            None
+        );
+        assert_eq!(commit_version("_last_checkpoint"), None);
+    }
+
+    #[tokio::test]
+    async fn test_probe_failure_state() {
+        let health = probe("local_disk", HealthState::Degraded, async {
+            Err(Error::Other("disk full".to_string()))
+        })
+        .await;
+        assert_eq!(health.state, HealthState::Degraded);
+        assert_eq!(health.detail.as_deref(), Some("disk full"));
+    }
+
+    #[tokio::test]
+    async fn test_corrupt_commit_fails() {
+        let dir = tempfile::tempdir().unwrap();
+        let log_dir = dir.path().join("_delta_log");
+        std::fs::create_dir_all(&log_dir).unwrap();
+        std::fs::write(
+            log_dir.join("00000000000000000000.json"),
+            "{\"commitInfo\":{}}\n",
+        )
+        .unwrap();
+        assert!(check_local_delta_log(dir.path()).await.is_ok());
+
+        std::fs::write(log_dir.join("00000000000000000001.json"), "{not json").unwrap();
+        assert!(check_local_delta_log(dir.path()).await.is_err());
+    }
+}
@@ -3,8 +3,12 @@
 
 // Core modules
 pub mod batch_buffer;
+pub mod bulk_load;
 pub mod delta_lake;
 pub mod error;
+pub mod export;
+pub mod health;
+pub mod lock_manager;
 pub mod metadata;
 pub mod query;
 pub mod security;
@@ -17,13 +21,20 @@ pub mod database_ops;
 // POSIX interface (NFS server)
 pub mod nfs;
 
+// HTTP interface (JSON over REST)
+pub mod rest;
+
+// Arrow Flight SQL interface
+pub mod flight;
+
 // Python bindings (UniFFI)
 pub mod python;
 
 // Public API
 pub use database_ops::DatabaseOps;
+pub use delta_lake::CommitResult;
 pub use error::{Error, Result};
-pub use transaction::Transaction;
+pub use transaction::{Transaction, TransactionInfo};
 
 // Generate UniFFI scaffolding (0.29+ uses proc-macros)
 uniffi::setup_scaffolding!();
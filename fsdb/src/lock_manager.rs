@@ -0,0 +1,395 @@
+//! Lock manager for explicit transactions
+//!
+//! Transactions lock named resources (e.g. `"table:data"` or `"row:data:42"`)
+//! in shared or exclusive mode. Waiting is cheap: the wait-for graph is only
+//! built once a wait exceeds `detection_threshold`, and then re-checked every
+//! threshold interval while the wait continues. When a cycle is found one
+//! transaction in it is chosen by the `VictimPolicy`, its locks are released,
+//! and its pending acquisition fails with `Error::DeadlockDetected`.
+//! A transaction aborted from outside with `cancel` has its locks released
+//! the same way, and its acquisitions fail with `Error::Cancelled`.
+
+use crate::{Error, Result};
+use std::collections::{HashMap, HashSet};
+use std::sync::Mutex;
+use std::time::Duration;
+use tokio::sync::Notify;
+use tracing::{debug, warn};
+
+/// Lock mode
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LockMode {
+    /// Compatible with other shared locks
+    Shared,
+    /// Compatible with nothing
+    Exclusive,
+}
+
+impl LockMode {
+    fn compatible(self, other: LockMode) -> bool {
+        self == LockMode::Shared && other == LockMode::Shared
+    }
+}
+
+/// Which transaction in a deadlock cycle is aborted
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum VictimPolicy {
+    /// Highest transaction ID, i.e. the one that started last and has done the least work
+    #[default]
+    Youngest,
+    /// Lowest transaction ID
+    Oldest,
+    /// The transaction holding the fewest locks (ties go to the youngest)
+    FewestLocks,
+}
+
+/// Configuration for the lock manager
+#[derive(Debug, Clone)]
+pub struct LockManagerConfig {
+    /// How long a lock wait may last before deadlock detection runs
+    pub detection_threshold: Duration,
+    /// How the victim of a deadlock is chosen
+    pub victim_policy: VictimPolicy,
+}
+
+impl Default for LockManagerConfig {
+    fn default() -> Self {
+        Self {
+            detection_threshold: Duration::from_millis(100),
+            victim_policy: VictimPolicy::default(),
+        }
+    }
+}
+
+#[derive(Default)]
+struct LockTable {
+    /// Resource -> transactions holding it
+    holders: HashMap<String, Vec<(u64, LockMode)>>,
+    /// Transaction -> the resource it is waiting for
+    waiting: HashMap<u64, (String, LockMode)>,
+    /// Victims whose locks were released, until they call `release_all`
+    aborted: HashMap<u64, Vec<u64>>,
+    /// Cancelled transactions, until they call `release_all`
+    cancelled: HashSet<u64>,
+}
+
+impl LockTable {
+    fn can_grant(&self, txn_id: u64, resource: &str, mode: LockMode) -> bool {
+        self.holders.get(resource).is_none_or(|holders| {
+            holders
+                .iter()
+                .all(|(holder, held)| *holder == txn_id || held.compatible(mode))
+        })
+    }
+
+    fn grant(&mut self, txn_id: u64, resource: &str, mode: LockMode) {
+        let holders = self.holders.entry(resource.to_string()).or_default();
+        match holders.iter_mut().find(|(holder, _)| *holder == txn_id) {
+            // Re-acquiring upgrades shared to exclusive, never the reverse
+            Some((_, held)) => {
+                if mode == LockMode::Exclusive {
+                    *held = LockMode::Exclusive;
+                }
+            }
+            None => holders.push((txn_id, mode)),
+        }
+    }
+
+    fn release(&mut self, txn_id: u64) {
+        self.holders.retain(|_, holders| {
+            holders.retain(|(holder, _)| *holder != txn_id);
+            !holders.is_empty()
+        });
+        self.waiting.remove(&txn_id);
+    }
+
+    fn locks_held(&self, txn_id: u64) -> usize {
+        self.holders
+            .values()
+            .filter(|holders| holders.iter().any(|(holder, _)| *holder == txn_id))
+            .count()
+    }
+
+    /// Wait-for edges: waiter -> holders blocking it
+    fn wait_for_graph(&self) -> HashMap<u64, Vec<u64>> {
+        let mut graph = HashMap::new();
+        for (waiter, (resource, mode)) in &self.waiting {
+            let blockers: Vec<u64> = self
+                .holders
+                .get(resource)
+                .into_iter()
+                .flatten()
+                .filter(|(holder, held)| holder != waiter && !held.compatible(*mode))
+                .map(|(holder, _)| *holder)
+                .collect();
+            graph.insert(*waiter, blockers);
+        }
+        graph
+    }
+}
+
+/// Find a cycle through `start` in the wait-for graph
+fn find_cycle(graph: &HashMap<u64, Vec<u64>>, start: u64) -> Option<Vec<u64>> {
+    fn visit(
+        graph: &HashMap<u64, Vec<u64>>,
+        start: u64,
+        node: u64,
+        path: &mut Vec<u64>,
+        seen: &mut HashSet<u64>,
+    ) -> bool {
+        for &next in graph.get(&node).into_iter().flatten() {
+            if next == start {
+                return true;
+            }
+            if seen.insert(next) {
+                path.push(next);
+                if visit(graph, start, next, path, seen) {
+                    return true;
+                }
+                path.pop();
+            }
+        }
+        false
+    }
+
+    let mut path = vec![start];
+    let mut seen = HashSet::from([start]);
+    visit(graph, start, start, &mut path, &mut seen).then_some(path)
+}
+
+/// Grants resource locks to transactions and breaks deadlocks between them
+pub struct LockManager {
+    config: LockManagerConfig,
+    /// Synchronous so that dropping a `Transaction` can release its locks
+    table: Mutex<LockTable>,
+    released: Notify,
+}
+
+impl Default for LockManager {
+    fn default() -> Self {
+        Self::new(LockManagerConfig::default())
+    }
+}
+
+impl LockManager {
+    pub fn new(config: LockManagerConfig) -> Self {
+        Self {
+            config,
+            table: Mutex::new(LockTable::default()),
+            released: Notify::new(),
+        }
+    }
+
+    pub fn config(&self) -> &LockManagerConfig {
+        &self.config
+    }
+
+    /// Acquire `resource` for `txn_id`, waiting while it is held incompatibly
+    ///
+    /// Fails with `Error::DeadlockDetected` if the transaction was chosen as
+    /// the victim of a deadlock. Its locks have then already been released.
+    pub async fn acquire(&self, txn_id: u64, resource: &str, mode: LockMode) -> Result<()> {
+        // Wake-ups from unrelated releases must not postpone detection
+        let mut detect_at = tokio::time::Instant::now() + self.config.detection_threshold;
+        loop {
+            let released = self.released.notified();
+            tokio::pin!(released);
+            {
+                let mut table = self.table.lock().unwrap();
+                if table.cancelled.contains(&txn_id) {
+                    table.waiting.remove(&txn_id);
+                    return Err(Error::Cancelled(format!("transaction {}", txn_id)));
+                }
+                if let Some(cycle) = table.aborted.get(&txn_id) {
+                    return Err(Error::DeadlockDetected {
+                        victim: txn_id,
+                        cycle: cycle.clone(),
+                    });
+                }
+                if table.can_grant(txn_id, resource, mode) {
+                    table.waiting.remove(&txn_id);
+                    table.grant(txn_id, resource, mode);
+                    return Ok(());
+                }
+                table.waiting.insert(txn_id, (resource.to_string(), mode));
+                // Register before unlocking so a release in between is not missed
+                released.as_mut().enable();
+            }
+
+            if tokio::time::timeout_at(detect_at, released).await.is_err() {
+                self.detect_deadlock(txn_id)?;
+                detect_at = tokio::time::Instant::now() + self.config.detection_threshold;
+            }
+        }
+    }
+
+    /// Release every lock held by `txn_id` and forget it was a victim
+    pub fn release_all(&self, txn_id: u64) {
+        let mut table = self.table.lock().unwrap();
+        table.release(txn_id);
+        table.aborted.remove(&txn_id);
+        table.cancelled.remove(&txn_id);
+        drop(table);
+        self.released.notify_waiters();
+    }
+
+    /// Release every lock held by `txn_id` on behalf of someone else
+    ///
+    /// A wait in progress and every later acquisition by the transaction
+    /// fail with `Error::Cancelled`, until it calls `release_all`.
+    pub fn cancel(&self, txn_id: u64) {
+        let mut table = self.table.lock().unwrap();
+        table.release(txn_id);
+        table.cancelled.insert(txn_id);
+        drop(table);
+        self.released.notify_waiters();
+    }
+
+    /// Number of resources `txn_id` holds a lock on
+    pub fn locks_held(&self, txn_id: u64) -> usize {
+        self.table.lock().unwrap().locks_held(txn_id)
+    }
+
+    /// Break a cycle through `txn_id`, if there is one
+    fn detect_deadlock(&self, txn_id: u64) -> Result<()> {
+        let mut table = self.table.lock().unwrap();
+        let Some(cycle) = find_cycle(&table.wait_for_graph(), txn_id) else {
+            debug!("Transaction {} still waiting, no deadlock", txn_id);
+            return Ok(());
+        };
+
+        let victim = match self.config.victim_policy {
+            VictimPolicy::Youngest => cycle.iter().copied().max(),
+            VictimPolicy::Oldest => cycle.iter().copied().min(),
+            VictimPolicy::FewestLocks => cycle
+                .iter()
+                .copied()
+                .min_by_key(|txn| (table.locks_held(*txn), std::cmp::Reverse(*txn))),
+        }
+        .expect("cycle is never empty");
+
+        warn!(
+            "Deadlock detected between transactions {:?}, aborting {}",
+            cycle, victim
+        );
+        table.release(victim);
+        table.aborted.insert(victim, cycle.clone());
+        drop(table);
+        self.released.notify_waiters();
+
+        if victim == txn_id {
+            Err(Error::DeadlockDetected { victim, cycle })
+        } else {
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    fn manager(victim_policy: VictimPolicy) -> Arc<LockManager> {
+        Arc::new(LockManager::new(LockManagerConfig {
+            detection_threshold: Duration::from_millis(20),
+            victim_policy,
+        }))
+    }
+
+    #[test]
+    fn test_find_cycle() {
+        let graph = HashMap::from([(1, vec![2]), (2, vec![3]), (3, vec![1]), (4, vec![1])]);
+        assert_eq!(find_cycle(&graph, 1), Some(vec![1, 2, 3]));
+        // 4 waits on the cycle but is not part of it
+        assert_eq!(find_cycle(&graph, 4), None);
+    }
+
+    #[tokio::test]
+    async fn test_shared_locks_are_compatible() {
+        let locks = manager(VictimPolicy::Youngest);
+        locks
+            .acquire(1, "table:data", LockMode::Shared)
+            .await
+            .unwrap();
+        locks
+            .acquire(2, "table:data", LockMode::Shared)
+            .await
+            .unwrap();
+        assert_eq!(locks.locks_held(1), 1);
+        assert_eq!(locks.locks_held(2), 1);
+    }
+
+    #[tokio::test]
+    async fn test_waiter_proceeds_after_release() {
+        let locks = manager(VictimPolicy::Youngest);
+        locks.acquire(1, "a", LockMode::Exclusive).await.unwrap();
+
+        let waiter = {
+            let locks = locks.clone();
+            tokio::spawn(async move { locks.acquire(2, "a", LockMode::Exclusive).await })
+        };
+        tokio::time::sleep(Duration::from_millis(60)).await;
+        assert!(
+            !waiter.is_finished(),
+            "no deadlock, so the waiter keeps waiting"
+        );
+
+        locks.release_all(1);
+        waiter.await.unwrap().unwrap();
+        assert_eq!(locks.locks_held(2), 1);
+    }
+
+    #[tokio::test]
+    async fn test_cancel_releases_and_fails_waits() {
+        let locks = manager(VictimPolicy::Youngest);
+        locks.acquire(1, "a", LockMode::Exclusive).await.unwrap();
+        locks.acquire(2, "b", LockMode::Exclusive).await.unwrap();
+
+        let waiter = {
+            let locks = locks.clone();
+            tokio::spawn(async move { locks.acquire(2, "a", LockMode::Exclusive).await })
+        };
+        tokio::time::sleep(Duration::from_millis(30)).await;
+        locks.cancel(2);
+        assert!(matches!(waiter.await.unwrap(), Err(Error::Cancelled(_))));
+        assert_eq!(locks.locks_held(2), 0);
+        locks.acquire(1, "b", LockMode::Exclusive).await.unwrap();
+        assert!(matches!(
+            locks.acquire(2, "c", LockMode::Shared).await,
+            Err(Error::Cancelled(_))
+        ));
+
+        locks.release_all(2);
+        locks.acquire(2, "c", LockMode::Shared).await.unwrap();
+    }
+
+    async fn deadlock_victim(policy: VictimPolicy, extra_locks_for_2: usize) -> u64 {
+        let locks = manager(policy);
+        locks.acquire(1, "a", LockMode::Exclusive).await.unwrap();
+        locks.acquire(2, "b", LockMode::Exclusive).await.unwrap();
+        for i in 0..extra_locks_for_2 {
+            locks
+                .acquire(2, &format!("extra:{}", i), LockMode::Exclusive)
+                .await
+                .unwrap();
+        }
+
+        let (first, second) = tokio::join!(
+            locks.acquire(1, "b", LockMode::Exclusive),
+            locks.acquire(2, "a", LockMode::Exclusive)
+        );
+        match (first, second) {
+            (Err(Error::DeadlockDetected { victim, .. }), Ok(())) => victim,
+            (Ok(()), Err(Error::DeadlockDetected { victim, .. })) => victim,
+            other => panic!("expected exactly one victim, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_victim_policies() {
+        assert_eq!(deadlock_victim(VictimPolicy::Youngest, 0).await, 2);
+        assert_eq!(deadlock_victim(VictimPolicy::Oldest, 0).await, 1);
+        assert_eq!(deadlock_victim(VictimPolicy::FewestLocks, 2).await, 1);
+    }
+}
@@ -0,0 +1,100 @@
+//! Named tables stored alongside the main table
+//!
+//! A database's main table, `data`, lives at its base path. Further tables
+//! made with `DatabaseOps::create_table` live under `_tables/<name>/`, each a
+//! Delta table of its own with its own log, files and metadata. The leading
+//! underscore keeps them out of the main table's file listings, so VACUUM and
+//! orphan cleanup of `data` never touch their files.
+//!
+//! There's no separate catalog file: a table exists when its directory has a
+//! `_delta_log`, so a table copied in by hand, or by another engine, shows up
+//! like one made through FSDB.
+
+use crate::{Error, Result};
+use std::path::{Path, PathBuf};
+
+/// Directory under the base path holding the named tables
+pub const TABLES_DIR: &str = "_tables";
+
+/// Where the table `name` of the database at `base_path` is stored
+pub fn table_path(base_path: &Path, name: &str) -> PathBuf {
+    base_path.join(TABLES_DIR).join(name)
+}
+
+/// True if the database at `base_path` has a table `name`
+pub fn exists(base_path: &Path, name: &str) -> bool {
+    table_path(base_path, name).join("_delta_log").is_dir()
+}
+
+/// Names of the tables of the database at `base_path`, sorted
+pub fn list(base_path: &Path) -> Result<Vec<String>> {
+    let dir = base_path.join(TABLES_DIR);
+    if !dir.exists() {
+        return Ok(Vec::new());
+    }
+    let mut names = Vec::new();
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        let Some(name) = path.file_name().and_then(|n| n.to_str()) else {
+            continue;
+        };
+        if check_table_name(name).is_ok() && path.join("_delta_log").is_dir() {
+            names.push(name.to_string());
+        }
+    }
+    names.sort();
+    Ok(names)
+}
+
+/// Fail unless `name` can name a table
+///
+/// Names are plain lowercase identifiers, other than `data`, so they can be
+/// used unquoted in SQL and as directory names.
+pub fn check_table_name(name: &str) -> Result<()> {
+    let mut chars = name.chars();
+    let valid = chars
+        .next()
+        .is_some_and(|c| c.is_ascii_lowercase() || c == '_')
+        && chars.all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_');
+    if !valid || name == "data" {
+        return Err(Error::InvalidOperation(format!(
+            "Invalid table name '{}': use a lowercase identifier other than data",
+            name
+        )));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_table_names() {
+        for name in ["orders", "line_items", "_staging", "t2"] {
+            assert!(check_table_name(name).is_ok(), "{}", name);
+        }
+        for name in ["", "data", "Orders", "2t", "a-b", "a.b", "../x", "a b"] {
+            assert!(check_table_name(name).is_err(), "{}", name);
+        }
+    }
+
+    #[test]
+    fn test_list_only_tables() {
+        let temp_dir = TempDir::new().unwrap();
+        let base = temp_dir.path();
+        assert!(list(base).unwrap().is_empty());
+
+        for name in ["orders", "customers"] {
+            std::fs::create_dir_all(table_path(base, name).join("_delta_log")).unwrap();
+        }
+        // A directory without a log, and one with a name no table can have
+        std::fs::create_dir_all(table_path(base, "partial")).unwrap();
+        std::fs::create_dir_all(table_path(base, "Bad").join("_delta_log")).unwrap();
+
+        assert_eq!(list(base).unwrap(), vec!["customers", "orders"]);
+        assert!(exists(base, "orders"));
+        assert!(!exists(base, "partial"));
+    }
+}
@@ -0,0 +1,336 @@
+//! Dry-run schema compatibility checks for inserts
+//!
+//! `check_compatibility` compares the schema of rows about to be inserted
+//! with the table's, column by column, without writing anything. Each
+//! difference is a `ColumnIssue`: errors would fail the insert, warnings
+//! would let it through with values possibly changed or rejected row by row.
+//!
+//! Inserts evolve the schema, so a column the table doesn't have is added
+//! rather than refused. The report says which errors that resolves, so a
+//! pipeline can tell "needs evolution" apart from "will fail".
+
+use crate::metadata::{default_expr, generated_expr};
+use arrow::datatypes::{DataType, Schema};
+
+/// How serious a `ColumnIssue` is
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    /// The insert goes through, but values may change or some rows fail
+    Warning,
+    /// The insert fails unless schema evolution resolves it
+    Error,
+}
+
+/// One difference between the input and the table
+#[derive(Debug, Clone, PartialEq)]
+pub enum ColumnIssue {
+    /// Values convert to the table's type, but some may lose precision or
+    /// range, such as Float64 into Int32
+    Lossy {
+        column: String,
+        input: DataType,
+        table: DataType,
+    },
+    /// Values can't be converted to the table's type
+    TypeMismatch {
+        column: String,
+        input: DataType,
+        table: DataType,
+    },
+    /// A NOT NULL column with no default or generating expression is absent
+    MissingRequired { column: String },
+    /// The input may hold nulls for a NOT NULL column without a default
+    NullableIntoRequired { column: String },
+    /// A column the table doesn't have
+    Extra { column: String, data_type: DataType },
+}
+
+impl ColumnIssue {
+    /// Column the issue is about
+    pub fn column(&self) -> &str {
+        match self {
+            ColumnIssue::Lossy { column, .. }
+            | ColumnIssue::TypeMismatch { column, .. }
+            | ColumnIssue::MissingRequired { column }
+            | ColumnIssue::NullableIntoRequired { column }
+            | ColumnIssue::Extra { column, .. } => column,
+        }
+    }
+
+    pub fn severity(&self) -> Severity {
+        match self {
+            ColumnIssue::Lossy { .. } | ColumnIssue::NullableIntoRequired { .. } => {
+                Severity::Warning
+            }
+            ColumnIssue::TypeMismatch { .. }
+            | ColumnIssue::MissingRequired { .. }
+            | ColumnIssue::Extra { .. } => Severity::Error,
+        }
+    }
+
+    /// Whether schema evolution on insert makes the issue go away
+    ///
+    /// Only extra columns are resolved: evolution adds columns, but never
+    /// changes a column's type or makes it nullable.
+    pub fn resolved_by_evolution(&self) -> bool {
+        matches!(self, ColumnIssue::Extra { .. })
+    }
+}
+
+/// Outcome of `check_compatibility`
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CompatibilityReport {
+    /// Differences, in table column order, then extra columns in input order
+    pub issues: Vec<ColumnIssue>,
+}
+
+impl CompatibilityReport {
+    /// Whether the input matches the table with no errors, warnings aside
+    pub fn is_compatible(&self) -> bool {
+        self.errors().next().is_none()
+    }
+
+    /// Whether an insert would succeed with schema evolution, which inserts
+    /// always apply
+    pub fn is_compatible_with_evolution(&self) -> bool {
+        self.errors().all(ColumnIssue::resolved_by_evolution)
+    }
+
+    pub fn errors(&self) -> impl Iterator<Item = &ColumnIssue> {
+        self.issues
+            .iter()
+            .filter(|issue| issue.severity() == Severity::Error)
+    }
+
+    pub fn warnings(&self) -> impl Iterator<Item = &ColumnIssue> {
+        self.issues
+            .iter()
+            .filter(|issue| issue.severity() == Severity::Warning)
+    }
+}
+
+/// Compare rows following `input` with a table following `table`
+///
+/// Columns are matched by name. A column the input leaves out is fine if
+/// it's nullable or has a default or generating expression to fill it.
+pub fn check_compatibility(table: &Schema, input: &Schema) -> CompatibilityReport {
+    let mut issues = Vec::new();
+
+    for field in table.fields() {
+        let filled = default_expr(field).is_some() || generated_expr(field).is_some();
+        let Ok(supplied) = input.field_with_name(field.name()) else {
+            if !field.is_nullable() && !filled {
+                issues.push(ColumnIssue::MissingRequired {
+                    column: field.name().clone(),
+                });
+            }
+            continue;
+        };
+
+        let column = field.name().clone();
+        let (from, to) = (supplied.data_type(), field.data_type());
+        if from != to && from != &DataType::Null {
+            if !arrow::compute::can_cast_types(from, to) || !is_same_kind(from, to) {
+                issues.push(ColumnIssue::TypeMismatch {
+                    column: column.clone(),
+                    input: from.clone(),
+                    table: to.clone(),
+                });
+                continue;
+            }
+            if !is_lossless(from, to) {
+                issues.push(ColumnIssue::Lossy {
+                    column: column.clone(),
+                    input: from.clone(),
+                    table: to.clone(),
+                });
+            }
+        }
+        if supplied.is_nullable() && !field.is_nullable() && !filled {
+            issues.push(ColumnIssue::NullableIntoRequired { column });
+        }
+    }
+
+    for field in input.fields() {
+        if table.field_with_name(field.name()).is_err() {
+            issues.push(ColumnIssue::Extra {
+                column: field.name().clone(),
+                data_type: field.data_type().clone(),
+            });
+        }
+    }
+
+    CompatibilityReport { issues }
+}
+
+/// Whether `from` and `to` hold the same kind of value: numbers, times,
+/// text, bytes or booleans
+///
+/// Arrow will cast text to numbers and numbers to text, but whether that
+/// works depends on each value, so it's treated as a mismatch.
+fn is_same_kind(from: &DataType, to: &DataType) -> bool {
+    let kind = |t: &DataType| {
+        if t.is_numeric() {
+            Some("number")
+        } else if t.is_temporal() {
+            Some("time")
+        } else if matches!(t, DataType::Utf8 | DataType::LargeUtf8 | DataType::Utf8View) {
+            Some("text")
+        } else if matches!(
+            t,
+            DataType::Binary | DataType::LargeBinary | DataType::BinaryView
+        ) {
+            Some("bytes")
+        } else if t == &DataType::Boolean {
+            Some("boolean")
+        } else {
+            None
+        }
+    };
+    match (kind(from), kind(to)) {
+        (Some(a), Some(b)) => a == b,
+        // Nested and other types convert only between layouts of the same kind
+        _ => std::mem::discriminant(from) == std::mem::discriminant(to),
+    }
+}
+
+/// Whether every value of `from` is represented exactly in `to`
+fn is_lossless(from: &DataType, to: &DataType) -> bool {
+    use DataType::*;
+
+    match (from, to) {
+        (Int8, Int16 | Int32 | Int64 | Float32 | Float64)
+        | (Int16, Int32 | Int64 | Float32 | Float64)
+        | (Int32, Int64 | Float64)
+        | (UInt8, UInt16 | UInt32 | UInt64 | Int16 | Int32 | Int64 | Float32 | Float64)
+        | (UInt16, UInt32 | UInt64 | Int32 | Int64 | Float32 | Float64)
+        | (UInt32, UInt64 | Int64 | Float64)
+        | (Float16, Float32 | Float64)
+        | (Float32, Float64)
+        | (Utf8 | LargeUtf8 | Utf8View, Utf8 | LargeUtf8 | Utf8View)
+        | (Binary | LargeBinary | BinaryView, Binary | LargeBinary | BinaryView)
+        | (Date32, Date64) => true,
+        (Decimal128(p1, s1), Decimal128(p2, s2)) => {
+            // Enough digits on both sides of the point
+            s2 >= s1 && *p2 as i16 - *s2 as i16 >= *p1 as i16 - *s1 as i16
+        }
+        (Timestamp(unit1, tz1), Timestamp(unit2, tz2)) => {
+            tz1 == tz2 && unit_rank(unit2) >= unit_rank(unit1)
+        }
+        _ => false,
+    }
+}
+
+fn unit_rank(unit: &arrow::datatypes::TimeUnit) -> u8 {
+    use arrow::datatypes::TimeUnit;
+    match unit {
+        TimeUnit::Second => 0,
+        TimeUnit::Millisecond => 1,
+        TimeUnit::Microsecond => 2,
+        TimeUnit::Nanosecond => 3,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow::datatypes::Field;
+    use std::sync::Arc;
+
+    fn table() -> Schema {
+        Schema::new(vec![
+            Field::new("id", DataType::Int64, false),
+            Field::new("name", DataType::Utf8, true),
+            Field::new("score", DataType::Int32, true),
+        ])
+    }
+
+    #[test]
+    fn test_identical_schema_is_compatible() {
+        let report = check_compatibility(&table(), &table());
+        assert!(report.issues.is_empty());
+        assert!(report.is_compatible());
+    }
+
+    #[test]
+    fn test_widening_and_missing_nullable_are_fine() {
+        let input = Schema::new(vec![
+            Field::new("id", DataType::Int32, false),
+            Field::new("name", DataType::LargeUtf8, true),
+        ]);
+        let report = check_compatibility(&table(), &input);
+        assert!(report.issues.is_empty(), "{:?}", report.issues);
+    }
+
+    #[test]
+    fn test_lossy_conversion_is_a_warning() {
+        let input = Schema::new(vec![
+            Field::new("id", DataType::Int64, false),
+            Field::new("score", DataType::Float64, true),
+        ]);
+        let report = check_compatibility(&table(), &input);
+        assert_eq!(
+            report.issues,
+            vec![ColumnIssue::Lossy {
+                column: "score".to_string(),
+                input: DataType::Float64,
+                table: DataType::Int32,
+            }]
+        );
+        assert!(report.is_compatible());
+        assert_eq!(report.warnings().count(), 1);
+    }
+
+    #[test]
+    fn test_incompatible_input() {
+        let input = Schema::new(vec![
+            Field::new("name", DataType::Utf8, true),
+            Field::new("score", DataType::Utf8, true),
+            Field::new("email", DataType::Utf8, true),
+        ]);
+        let report = check_compatibility(&table(), &input);
+        assert_eq!(
+            report.issues,
+            vec![
+                ColumnIssue::MissingRequired {
+                    column: "id".to_string()
+                },
+                ColumnIssue::TypeMismatch {
+                    column: "score".to_string(),
+                    input: DataType::Utf8,
+                    table: DataType::Int32,
+                },
+                ColumnIssue::Extra {
+                    column: "email".to_string(),
+                    data_type: DataType::Utf8,
+                },
+            ]
+        );
+        assert!(!report.is_compatible());
+        assert!(!report.is_compatible_with_evolution());
+    }
+
+    #[test]
+    fn test_extra_column_resolved_by_evolution() {
+        let mut fields = table().fields().to_vec();
+        fields.push(Arc::new(Field::new("email", DataType::Utf8, true)));
+        let report = check_compatibility(&table(), &Schema::new(fields));
+        assert!(!report.is_compatible());
+        assert!(report.is_compatible_with_evolution());
+    }
+
+    #[test]
+    fn test_defaults_fill_required_columns() {
+        let table = Schema::new(vec![
+            Field::new("id", DataType::Int64, false),
+            crate::metadata::with_default(Field::new("status", DataType::Utf8, false), "'new'"),
+        ]);
+        let input = Schema::new(vec![Field::new("id", DataType::Int64, true)]);
+        assert_eq!(
+            check_compatibility(&table, &input).issues,
+            vec![ColumnIssue::NullableIntoRequired {
+                column: "id".to_string()
+            }]
+        );
+    }
+}
@@ -0,0 +1,210 @@
+//! Per-column default values
+//!
+//! A default is a SQL expression stored under `fsdb.default` in the column's
+//! field metadata, in both the Arrow schema and the Delta schema it is
+//! persisted as. It can be a literal (`0`, `'unknown'`) or an expression
+//! evaluated at insert time (`now()`), once per inserted batch.
+//!
+//! Defaults fill columns a batch leaves out. A null the caller supplied
+//! explicitly is kept, except in a NOT NULL column: there the default
+//! replaces it, since a default is what makes omitting a NOT NULL column legal.
+
+use crate::{Error, Result};
+use arrow::array::{new_null_array, Array, ArrayRef, RecordBatch};
+use arrow::datatypes::{Field, Schema, SchemaRef};
+use datafusion::prelude::SessionContext;
+use datafusion::scalar::ScalarValue;
+use std::sync::Arc;
+
+/// Field metadata key holding a column's default expression
+pub const DEFAULT_METADATA_KEY: &str = "fsdb.default";
+
+/// Give `field` a default expression
+pub fn with_default(field: Field, expr: impl Into<String>) -> Field {
+    let mut metadata = field.metadata().clone();
+    metadata.insert(DEFAULT_METADATA_KEY.to_string(), expr.into());
+    field.with_metadata(metadata)
+}
+
+/// Default expression of `field`, if it has one
+pub fn default_expr(field: &Field) -> Option<&str> {
+    field
+        .metadata()
+        .get(DEFAULT_METADATA_KEY)
+        .map(String::as_str)
+}
+
+/// Conform `batch` to `schema`, filling in defaults
+///
+/// Columns are put in schema order; columns the schema doesn't know are kept
+/// at the end so schema evolution still sees them. Without any defaults in
+/// the schema the batch is returned unchanged.
+pub async fn apply_defaults(batch: RecordBatch, schema: &SchemaRef) -> Result<RecordBatch> {
+    if !schema.fields().iter().any(|f| default_expr(f).is_some()) {
+        return Ok(batch);
+    }
+
+    let num_rows = batch.num_rows();
+    let mut fields = Vec::with_capacity(schema.fields().len());
+    let mut columns = Vec::with_capacity(schema.fields().len());
+
+    for field in schema.fields() {
+        let column = match (batch.column_by_name(field.name()), default_expr(field)) {
+            (Some(column), Some(expr)) if !field.is_nullable() && column.null_count() > 0 => {
+                let column = arrow::compute::cast(column, field.data_type())?;
+                let default = evaluate_default(field, expr, num_rows).await?;
+                let present = arrow::compute::is_not_null(&column)?;
+                arrow::compute::kernels::zip::zip(&present, &column, &default)?
+            }
+            (Some(column), _) => arrow::compute::cast(column, field.data_type())?,
+            (None, Some(expr)) => evaluate_default(field, expr, num_rows).await?,
+            (None, None) if field.is_nullable() => new_null_array(field.data_type(), num_rows),
+            (None, None) => {
+                return Err(Error::InvalidOperation(format!(
+                    "Column '{}' is NOT NULL and has no default",
+                    field.name()
+                )))
+            }
+        };
+        fields.push(field.clone());
+        columns.push(column);
+    }
+
+    let batch_schema = batch.schema();
+    for (i, field) in batch_schema.fields().iter().enumerate() {
+        if schema.field_with_name(field.name()).is_err() {
+            fields.push(field.clone());
+            columns.push(batch.column(i).clone());
+        }
+    }
+
+    Ok(RecordBatch::try_new(
+        Arc::new(Schema::new(fields)),
+        columns,
+    )?)
+}
+
+/// Evaluate `field`'s default once and repeat it `num_rows` times
+async fn evaluate_default(field: &Field, expr: &str, num_rows: usize) -> Result<ArrayRef> {
+    let invalid = |e: String| {
+        Error::InvalidOperation(format!(
+            "Invalid default for column '{}' ({}): {}",
+            field.name(),
+            expr,
+            e
+        ))
+    };
+
+    let batches = SessionContext::new()
+        .sql(&format!("SELECT {}", expr))
+        .await
+        .map_err(|e| invalid(e.to_string()))?
+        .collect()
+        .await
+        .map_err(|e| invalid(e.to_string()))?;
+    let value = batches
+        .iter()
+        .find(|b| b.num_rows() > 0)
+        .map(|b| b.column(0).clone())
+        .ok_or_else(|| invalid("produced no value".to_string()))?;
+
+    let value = arrow::compute::cast(&value, field.data_type())?;
+    ScalarValue::try_from_array(&value, 0)
+        .and_then(|scalar| scalar.to_array_of_size(num_rows))
+        .map_err(|e| invalid(e.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow::array::{Int32Array, StringArray};
+    use arrow::datatypes::DataType;
+
+    fn schema() -> SchemaRef {
+        Arc::new(Schema::new(vec![
+            Field::new("id", DataType::Int32, false),
+            with_default(Field::new("status", DataType::Utf8, true), "'new'"),
+            with_default(Field::new("priority", DataType::Int32, false), "3"),
+        ]))
+    }
+
+    #[tokio::test]
+    async fn test_missing_columns_get_defaults() {
+        let ids = Arc::new(Schema::new(vec![Field::new("id", DataType::Int32, false)]));
+        let batch =
+            RecordBatch::try_new(ids, vec![Arc::new(Int32Array::from(vec![1, 2]))]).unwrap();
+
+        let filled = apply_defaults(batch, &schema()).await.unwrap();
+
+        assert_eq!(filled.num_columns(), 3);
+        let status = filled
+            .column(1)
+            .as_any()
+            .downcast_ref::<StringArray>()
+            .unwrap();
+        assert_eq!(status.value(0), "new");
+        let priority = filled
+            .column(2)
+            .as_any()
+            .downcast_ref::<Int32Array>()
+            .unwrap();
+        assert_eq!(priority.values(), &[3, 3]);
+    }
+
+    #[tokio::test]
+    async fn test_explicit_null_wins_unless_not_null() {
+        let batch = RecordBatch::try_new(
+            Arc::new(Schema::new(vec![
+                Field::new("id", DataType::Int32, false),
+                Field::new("status", DataType::Utf8, true),
+                Field::new("priority", DataType::Int32, true),
+            ])),
+            vec![
+                Arc::new(Int32Array::from(vec![1, 2])),
+                Arc::new(StringArray::from(vec![None, Some("done")])),
+                Arc::new(Int32Array::from(vec![None, Some(1)])),
+            ],
+        )
+        .unwrap();
+
+        let filled = apply_defaults(batch, &schema()).await.unwrap();
+
+        // status is nullable, so the explicit null stays
+        assert!(filled.column(1).is_null(0));
+        let priority = filled
+            .column(2)
+            .as_any()
+            .downcast_ref::<Int32Array>()
+            .unwrap();
+        assert_eq!(priority.values(), &[3, 1]);
+    }
+
+    #[tokio::test]
+    async fn test_not_null_without_default_is_rejected() {
+        let batch = RecordBatch::try_new(
+            Arc::new(Schema::new(vec![Field::new(
+                "status",
+                DataType::Utf8,
+                true,
+            )])),
+            vec![Arc::new(StringArray::from(vec!["a"]))],
+        )
+        .unwrap();
+
+        let err = apply_defaults(batch, &schema()).await.unwrap_err();
+        assert!(err.to_string().contains("'id'"), "{}", err);
+    }
+
+    #[tokio::test]
+    async fn test_schema_without_defaults_is_untouched() {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("id", DataType::Int32, false),
+            Field::new("name", DataType::Utf8, true),
+        ]));
+        let ids = Arc::new(Schema::new(vec![Field::new("id", DataType::Int32, false)]));
+        let batch = RecordBatch::try_new(ids, vec![Arc::new(Int32Array::from(vec![1]))]).unwrap();
+
+        let result = apply_defaults(batch.clone(), &schema).await.unwrap();
+        assert_eq!(result, batch);
+    }
+}
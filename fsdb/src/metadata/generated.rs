@@ -0,0 +1,378 @@
+//! Generated columns
+//!
+//! A generated column's value is a SQL expression over other columns of the
+//! same row, e.g. `first || ' ' || last`. The expression is stored under
+//! `fsdb.generated` in the column's field metadata, like a column default,
+//! and the value is computed on every insert and update, so it is stored in
+//! the data files and gets statistics like any other column.
+//!
+//! Writers can't choose the value. An insert may include the column only if
+//! every value equals the computed one, and an update can't assign it.
+//!
+//! A generated column may reference another generated column. They are
+//! computed in dependency order, ties broken by schema order; a cycle is an
+//! error.
+
+use crate::{Error, Result};
+use arrow::array::{Array, ArrayRef, RecordBatch};
+use arrow::datatypes::{Field, Schema, SchemaRef};
+use datafusion::common::tree_node::{Transformed, TreeNode};
+use datafusion::common::DFSchema;
+use datafusion::logical_expr::Expr;
+use datafusion::prelude::{SessionConfig, SessionContext};
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+
+/// Field metadata key holding a generated column's expression
+pub const GENERATED_METADATA_KEY: &str = "fsdb.generated";
+
+/// Make `field` a column generated by `expr`
+pub fn with_generated(field: Field, expr: impl Into<String>) -> Field {
+    let mut metadata = field.metadata().clone();
+    metadata.insert(GENERATED_METADATA_KEY.to_string(), expr.into());
+    field.with_metadata(metadata)
+}
+
+/// Generating expression of `field`, if it is a generated column
+pub fn generated_expr(field: &Field) -> Option<&str> {
+    field
+        .metadata()
+        .get(GENERATED_METADATA_KEY)
+        .map(String::as_str)
+}
+
+/// A generated column with its parsed expression
+struct Generated<'a> {
+    field: &'a Field,
+    sql: &'a str,
+    expr: Expr,
+}
+
+impl Generated<'_> {
+    fn invalid(&self, e: impl std::fmt::Display) -> Error {
+        Error::InvalidOperation(format!(
+            "Invalid generated column '{}' ({}): {}",
+            self.field.name(),
+            self.sql,
+            e
+        ))
+    }
+}
+
+/// Parse `schema`'s generated columns and order them so each comes after
+/// the generated columns it references
+fn generated_columns<'a>(ctx: &SessionContext, schema: &'a Schema) -> Result<Vec<Generated<'a>>> {
+    let df_schema =
+        DFSchema::try_from(schema.clone()).map_err(|e| Error::InvalidOperation(e.to_string()))?;
+    let mut pending = Vec::new();
+    for field in schema.fields() {
+        let Some(sql) = generated_expr(field) else {
+            continue;
+        };
+        let expr = ctx
+            .parse_sql_expr(sql, &df_schema)
+            .map_err(|e| Error::InvalidOperation(e.to_string()))?;
+        pending.push(Generated {
+            field: field.as_ref(),
+            sql,
+            expr,
+        });
+    }
+
+    let generated: HashSet<String> = pending.iter().map(|g| g.field.name().clone()).collect();
+    let mut ordered: Vec<Generated> = Vec::with_capacity(pending.len());
+    let mut done: HashSet<String> = HashSet::new();
+    while !pending.is_empty() {
+        let ready = pending.iter().position(|g| {
+            g.expr
+                .column_refs()
+                .iter()
+                .all(|c| !generated.contains(&c.name) || done.contains(&c.name))
+        });
+        let Some(ready) = ready else {
+            let names: Vec<&str> = pending.iter().map(|g| g.field.name().as_str()).collect();
+            return Err(Error::InvalidOperation(format!(
+                "Generated columns reference each other in a cycle: {}",
+                names.join(", ")
+            )));
+        };
+        let next = pending.remove(ready);
+        done.insert(next.field.name().clone());
+        ordered.push(next);
+    }
+    Ok(ordered)
+}
+
+/// Compute `schema`'s generated columns for `batch`
+///
+/// Columns are put in schema order, with generated columns filled in;
+/// columns the schema doesn't know are kept at the end. Fails if `batch`
+/// supplies a generated column with any value other than the computed one.
+/// Without generated columns in the schema the batch is returned unchanged.
+pub async fn apply_generated(batch: RecordBatch, schema: &SchemaRef) -> Result<RecordBatch> {
+    if !schema.fields().iter().any(|f| generated_expr(f).is_some()) {
+        return Ok(batch);
+    }
+    // One partition keeps computed values in row order
+    let ctx = SessionContext::new_with_config(SessionConfig::new().with_target_partitions(1));
+    let generated = generated_columns(&ctx, schema)?;
+
+    // Evaluate against the supplied columns plus the generated ones so far
+    let mut fields: Vec<Arc<Field>> = Vec::new();
+    let mut columns: Vec<ArrayRef> = Vec::new();
+    let batch_schema = batch.schema();
+    for (i, field) in batch_schema.fields().iter().enumerate() {
+        if !generated.iter().any(|g| g.field.name() == field.name()) {
+            fields.push(field.clone());
+            columns.push(batch.column(i).clone());
+        }
+    }
+    let mut computed: HashMap<String, ArrayRef> = HashMap::new();
+    for g in &generated {
+        let input = RecordBatch::try_new_with_options(
+            Arc::new(Schema::new(fields.clone())),
+            columns.clone(),
+            &arrow::array::RecordBatchOptions::new().with_row_count(Some(batch.num_rows())),
+        )?;
+        let df = ctx.read_batch(input).map_err(|e| g.invalid(e))?;
+        let expr = df.parse_sql_expr(g.sql).map_err(|e| g.invalid(e))?;
+        let result = df
+            .select(vec![expr.alias(g.field.name())])
+            .map_err(|e| g.invalid(e))?
+            .collect()
+            .await
+            .map_err(|e| g.invalid(e))?;
+        let values: Vec<ArrayRef> = result.iter().map(|b| b.column(0).clone()).collect();
+        let value = match values.as_slice() {
+            [] => arrow::array::new_empty_array(g.field.data_type()),
+            _ => {
+                let refs: Vec<&dyn Array> = values.iter().map(|v| v.as_ref()).collect();
+                arrow::compute::concat(&refs)?
+            }
+        };
+        let value = arrow::compute::cast(&value, g.field.data_type())?;
+
+        if let Some(supplied) = batch.column_by_name(g.field.name()) {
+            let supplied = arrow::compute::cast(supplied, g.field.data_type())?;
+            let same = arrow::compute::kernels::cmp::not_distinct(&supplied, &value)?;
+            if same.false_count() > 0 {
+                return Err(Error::InvalidOperation(format!(
+                    "Column '{}' is generated as {} and can't be set to a different value",
+                    g.field.name(),
+                    g.sql
+                )));
+            }
+        }
+
+        fields.push(Arc::new(g.field.clone()));
+        columns.push(value.clone());
+        computed.insert(g.field.name().clone(), value);
+    }
+
+    // Schema order, then unknown columns
+    let mut out_fields = Vec::with_capacity(fields.len());
+    let mut out_columns = Vec::with_capacity(fields.len());
+    for field in schema.fields() {
+        if let Some(value) = computed.get(field.name()) {
+            out_fields.push(field.clone());
+            out_columns.push(value.clone());
+        } else if let Some(column) = batch.column_by_name(field.name()) {
+            out_fields.push(field.clone());
+            out_columns.push(arrow::compute::cast(column, field.data_type())?);
+        }
+    }
+    for (i, field) in batch_schema.fields().iter().enumerate() {
+        if schema.field_with_name(field.name()).is_err() {
+            out_fields.push(field.clone());
+            out_columns.push(batch.column(i).clone());
+        }
+    }
+
+    Ok(RecordBatch::try_new_with_options(
+        Arc::new(Schema::new(out_fields)),
+        out_columns,
+        &arrow::array::RecordBatchOptions::new().with_row_count(Some(batch.num_rows())),
+    )?)
+}
+
+/// Names of `schema`'s generated columns whose expressions reference `column`
+///
+/// Columns generated from those aren't included.
+pub fn generated_from(schema: &Schema, column: &str) -> Result<Vec<String>> {
+    let ctx = SessionContext::new();
+    Ok(generated_columns(&ctx, schema)?
+        .into_iter()
+        .filter(|g| g.expr.column_refs().iter().any(|c| c.name == column))
+        .map(|g| g.field.name().clone())
+        .collect())
+}
+
+/// Assignments that keep generated columns current under an UPDATE
+///
+/// For each generated column that depends, directly or through other
+/// generated columns, on an assigned column, returns an assignment of its
+/// expression with the assigned expressions substituted in, since UPDATE
+/// evaluates every assignment against the old row. Fails if a generated
+/// column is assigned directly.
+pub fn generated_assignments(
+    schema: &SchemaRef,
+    assignments: &[(&str, &str)],
+) -> Result<Vec<(String, String)>> {
+    if !schema.fields().iter().any(|f| generated_expr(f).is_some()) {
+        return Ok(Vec::new());
+    }
+    for (column, _) in assignments {
+        if let Some(expr) = schema
+            .field_with_name(column)
+            .ok()
+            .and_then(|f| generated_expr(f))
+        {
+            return Err(Error::InvalidOperation(format!(
+                "Column '{}' is generated as {} and can't be updated",
+                column, expr
+            )));
+        }
+    }
+
+    let ctx = SessionContext::new();
+    let df_schema = DFSchema::try_from(schema.as_ref().clone())
+        .map_err(|e| Error::InvalidOperation(e.to_string()))?;
+    let mut replacements: HashMap<String, Expr> = HashMap::new();
+    for (column, sql) in assignments {
+        let expr = ctx
+            .parse_sql_expr(sql, &df_schema)
+            .map_err(|e| Error::InvalidOperation(e.to_string()))?;
+        replacements.insert(column.to_string(), expr);
+    }
+
+    let mut extra = Vec::new();
+    for g in generated_columns(&ctx, schema)? {
+        let substituted = g
+            .expr
+            .clone()
+            .transform(|e| match &e {
+                Expr::Column(c) => match replacements.get(&c.name) {
+                    Some(replacement) => Ok(Transformed::yes(replacement.clone())),
+                    None => Ok(Transformed::no(e)),
+                },
+                _ => Ok(Transformed::no(e)),
+            })
+            .map_err(|e| g.invalid(e))?;
+        if !substituted.transformed {
+            continue;
+        }
+        let sql = datafusion::sql::unparser::expr_to_sql(&substituted.data)
+            .map_err(|e| g.invalid(e))?
+            .to_string();
+        // Columns generated later see this column's new value
+        replacements.insert(g.field.name().clone(), substituted.data);
+        extra.push((g.field.name().clone(), sql));
+    }
+    Ok(extra)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow::array::StringArray;
+    use arrow::datatypes::DataType;
+
+    fn schema() -> SchemaRef {
+        Arc::new(Schema::new(vec![
+            Field::new("first", DataType::Utf8, true),
+            // Declared before the column it depends on
+            with_generated(
+                Field::new("greeting", DataType::Utf8, true),
+                "'hi ' || full_name",
+            ),
+            Field::new("last", DataType::Utf8, true),
+            with_generated(
+                Field::new("full_name", DataType::Utf8, true),
+                "first || ' ' || last",
+            ),
+        ]))
+    }
+
+    fn names(first: &str, last: &str) -> RecordBatch {
+        RecordBatch::try_new(
+            Arc::new(Schema::new(vec![
+                Field::new("first", DataType::Utf8, true),
+                Field::new("last", DataType::Utf8, true),
+            ])),
+            vec![
+                Arc::new(StringArray::from(vec![first])),
+                Arc::new(StringArray::from(vec![last])),
+            ],
+        )
+        .unwrap()
+    }
+
+    fn string(batch: &RecordBatch, column: &str) -> String {
+        batch
+            .column_by_name(column)
+            .unwrap()
+            .as_any()
+            .downcast_ref::<StringArray>()
+            .unwrap()
+            .value(0)
+            .to_string()
+    }
+
+    #[tokio::test]
+    async fn test_generated_columns_computed_in_dependency_order() {
+        let batch = apply_generated(names("Ada", "Lovelace"), &schema())
+            .await
+            .unwrap();
+
+        assert_eq!(batch.schema().field(1).name(), "greeting");
+        assert_eq!(string(&batch, "full_name"), "Ada Lovelace");
+        assert_eq!(string(&batch, "greeting"), "hi Ada Lovelace");
+    }
+
+    #[tokio::test]
+    async fn test_supplied_value_must_match() {
+        let with_full_name = |value: &str| {
+            let batch = names("Ada", "Lovelace");
+            let mut fields = batch.schema().fields().to_vec();
+            fields.push(Arc::new(Field::new("full_name", DataType::Utf8, true)));
+            let mut columns = batch.columns().to_vec();
+            columns.push(Arc::new(StringArray::from(vec![value])));
+            RecordBatch::try_new(Arc::new(Schema::new(fields)), columns).unwrap()
+        };
+
+        assert!(apply_generated(with_full_name("Ada Lovelace"), &schema())
+            .await
+            .is_ok());
+        let err = apply_generated(with_full_name("Grace Hopper"), &schema())
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("full_name"), "{}", err);
+    }
+
+    #[tokio::test]
+    async fn test_cycle_is_rejected() {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("id", DataType::Int32, false),
+            with_generated(Field::new("a", DataType::Int32, true), "b + 1"),
+            with_generated(Field::new("b", DataType::Int32, true), "a + 1"),
+        ]));
+        let batch = RecordBatch::try_new(
+            Arc::new(Schema::new(vec![Field::new("id", DataType::Int32, false)])),
+            vec![Arc::new(arrow::array::Int32Array::from(vec![1]))],
+        )
+        .unwrap();
+
+        let err = apply_generated(batch, &schema).await.unwrap_err();
+        assert!(err.to_string().contains("cycle"), "{}", err);
+    }
+
+    #[test]
+    fn test_update_assignments_follow_dependencies() {
+        let extra = generated_assignments(&schema(), &[("last", "'Byron'")]).unwrap();
+        let columns: Vec<&str> = extra.iter().map(|(c, _)| c.as_str()).collect();
+        assert_eq!(columns, vec!["full_name", "greeting"]);
+        assert!(extra[1].1.contains("Byron"), "{}", extra[1].1);
+
+        assert!(generated_assignments(&schema(), &[("full_name", "'x'")]).is_err());
+    }
+}
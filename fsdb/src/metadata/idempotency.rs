@@ -0,0 +1,172 @@
+//! Idempotency ledger for retried writes
+//!
+//! A write made with an idempotency key records its `CommitResult` under that
+//! key in `_metadata/idempotency.json`. Repeating the write with the same key
+//! returns the recorded result instead of applying it again. Entries older
+//! than the ledger's window are dropped whenever it's saved, so a key can be
+//! reused once it has expired.
+//!
+//! The key is recorded after the Delta commit, not in it. If the process dies
+//! between the two, the write is committed but the key isn't, and a retry
+//! applies it again: delivery is at-least-once across crashes and
+//! exactly-once otherwise.
+
+use crate::delta_lake::CommitResult;
+use crate::{Error, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, LazyLock};
+use std::time::Duration;
+
+/// How long keys are remembered unless configured otherwise
+pub const DEFAULT_IDEMPOTENCY_WINDOW: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// A write recorded under an idempotency key
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LedgerEntry {
+    pub result: CommitResult,
+    /// Milliseconds since the Unix epoch
+    pub recorded_at: i64,
+}
+
+/// Keys of recent writes and what they committed
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct IdempotencyLedger {
+    pub entries: BTreeMap<String, LedgerEntry>,
+}
+
+impl IdempotencyLedger {
+    /// Where a table's ledger is stored
+    pub fn path(base_path: &Path) -> PathBuf {
+        base_path.join("_metadata").join("idempotency.json")
+    }
+
+    /// Held from looking up a key of the table at `base_path` until its
+    /// write is recorded, by every handle on the table in this process
+    pub fn lock(base_path: &Path) -> Arc<tokio::sync::Mutex<()>> {
+        static LOCKS: LazyLock<std::sync::Mutex<HashMap<PathBuf, Arc<tokio::sync::Mutex<()>>>>> =
+            LazyLock::new(Default::default);
+        let key = std::fs::canonicalize(base_path).unwrap_or_else(|_| base_path.to_path_buf());
+        LOCKS
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .entry(key)
+            .or_default()
+            .clone()
+    }
+
+    /// Load a table's ledger, empty if nothing was recorded yet
+    pub fn load(base_path: &Path) -> Result<Self> {
+        let path = Self::path(base_path);
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let json = std::fs::read_to_string(&path)?;
+        Ok(serde_json::from_str(&json)?)
+    }
+
+    /// Save atomically, replacing the previous ledger
+    ///
+    /// The temporary file is named uniquely, so concurrent saves never
+    /// write into each other's.
+    pub fn save(&self, base_path: &Path) -> Result<()> {
+        let path = Self::path(base_path);
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let tmp_path = path.with_extension(format!("{}.tmp", uuid::Uuid::new_v4()));
+        std::fs::write(&tmp_path, serde_json::to_string(self)?)?;
+        std::fs::rename(tmp_path, path)?;
+        Ok(())
+    }
+
+    /// Result recorded under `key` for `operation`, if it hasn't expired
+    ///
+    /// Fails if the key was used for a different kind of write, which is
+    /// almost certainly a key collision in the caller.
+    pub fn lookup(
+        &self,
+        key: &str,
+        operation: &str,
+        window: Duration,
+        now: i64,
+    ) -> Result<Option<CommitResult>> {
+        match self.entries.get(key) {
+            Some(entry) if !Self::expired(entry, window, now) => {
+                if entry.result.operation != operation {
+                    return Err(Error::InvalidOperation(format!(
+                        "Idempotency key {} was used for {}, not {}",
+                        key, entry.result.operation, operation
+                    )));
+                }
+                Ok(Some(entry.result.clone()))
+            }
+            _ => Ok(None),
+        }
+    }
+
+    /// Record `result` under `key`, dropping expired entries
+    pub fn record(&mut self, key: &str, result: CommitResult, window: Duration, now: i64) {
+        self.entries
+            .retain(|_, entry| !Self::expired(entry, window, now));
+        self.entries.insert(
+            key.to_string(),
+            LedgerEntry {
+                result,
+                recorded_at: now,
+            },
+        );
+    }
+
+    fn expired(entry: &LedgerEntry, window: Duration, now: i64) -> bool {
+        now.saturating_sub(entry.recorded_at) > window.as_millis() as i64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn commit(version: i64, operation: &str) -> CommitResult {
+        CommitResult {
+            version,
+            timestamp: Some(1_000),
+            rows_affected: 1,
+            operation: operation.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_keys_expire_after_window() {
+        let window = Duration::from_secs(60);
+        let mut ledger = IdempotencyLedger::default();
+        ledger.record("a", commit(1, "INSERT"), window, 0);
+
+        assert_eq!(
+            ledger.lookup("a", "INSERT", window, 30_000).unwrap(),
+            Some(commit(1, "INSERT"))
+        );
+        assert_eq!(ledger.lookup("a", "INSERT", window, 61_000).unwrap(), None);
+        assert!(ledger.lookup("a", "DELETE", window, 30_000).is_err());
+
+        // Recording prunes what has expired
+        ledger.record("b", commit(2, "DELETE"), window, 120_000);
+        assert_eq!(ledger.entries.len(), 1);
+    }
+
+    #[test]
+    fn test_ledger_round_trips() {
+        let temp_dir = TempDir::new().unwrap();
+        assert!(IdempotencyLedger::load(temp_dir.path())
+            .unwrap()
+            .entries
+            .is_empty());
+
+        let mut ledger = IdempotencyLedger::default();
+        ledger.record("a", commit(3, "UPDATE"), DEFAULT_IDEMPOTENCY_WINDOW, 0);
+        ledger.save(temp_dir.path()).unwrap();
+        assert_eq!(IdempotencyLedger::load(temp_dir.path()).unwrap(), ledger);
+    }
+}
@@ -0,0 +1,35 @@
+//! JSON columns
+//!
+//! A JSON column holds JSON documents as text: it is a string column marked
+//! `fsdb.type = json` in its field metadata, like a column default, so the
+//! Parquet files and the Delta schema see a plain string. Queries reach into
+//! documents with `->`, `->>`, `json_extract` and `json_extract_text`; see
+//! `query::json`.
+//!
+//! Documents aren't validated on insert. A cell that isn't valid JSON reads
+//! as NULL from every path, rather than failing the query.
+
+use arrow::datatypes::{DataType, Field};
+
+/// Field metadata key holding a column's FSDB type, when it has one
+pub const TYPE_METADATA_KEY: &str = "fsdb.type";
+
+/// `TYPE_METADATA_KEY` value of JSON columns
+pub const JSON_TYPE: &str = "json";
+
+/// A JSON column named `name`
+pub fn json_field(name: impl Into<String>, nullable: bool) -> Field {
+    with_json(Field::new(name, DataType::Utf8, nullable))
+}
+
+/// Mark the string column `field` as holding JSON
+pub fn with_json(field: Field) -> Field {
+    let mut metadata = field.metadata().clone();
+    metadata.insert(TYPE_METADATA_KEY.to_string(), JSON_TYPE.to_string());
+    field.with_metadata(metadata)
+}
+
+/// Whether `field` is a JSON column
+pub fn is_json(field: &Field) -> bool {
+    field.metadata().get(TYPE_METADATA_KEY).map(String::as_str) == Some(JSON_TYPE)
+}
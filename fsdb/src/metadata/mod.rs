@@ -1,7 +1,20 @@
 //! Metadata management for Delta Lake native databases
 
 pub mod backup;
+pub mod catalog;
+pub mod compatibility;
+pub mod defaults;
+pub mod generated;
+pub mod idempotency;
+pub mod json;
 pub mod schema;
+pub mod snapshot;
 
 pub use backup::{BackupMetadata, BackupVerificationReport};
+pub use compatibility::{check_compatibility, ColumnIssue, CompatibilityReport, Severity};
+pub use defaults::{apply_defaults, default_expr, with_default};
+pub use generated::{apply_generated, generated_expr, with_generated};
+pub use idempotency::{IdempotencyLedger, LedgerEntry, DEFAULT_IDEMPOTENCY_WINDOW};
+pub use json::{is_json, json_field, with_json};
 pub use schema::{DataTypeRepr, Schema, SchemaField, SchemaManager, SchemaVersion};
+pub use snapshot::{SnapshotFile, SnapshotManifest};
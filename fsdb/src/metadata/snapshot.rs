@@ -0,0 +1,445 @@
+//! Portable table snapshots
+//!
+//! A snapshot is a plain ustar archive holding every file a reader needs for
+//! one table version: the live data files, the checkpoint and commits leading
+//! up to it, and a `fsdb_snapshot.json` manifest written last with the size
+//! and MD5 of each file. Any `tar` can unpack it; `import_snapshot` also
+//! checks every file against the manifest.
+
+use crate::delta_lake::log_replay::{log_files_at, read_delta_log_at};
+use crate::{Error, Result};
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::path::{Component, Path, PathBuf};
+use tracing::{debug, info};
+
+/// Archive entry holding the manifest
+pub const MANIFEST_NAME: &str = "fsdb_snapshot.json";
+
+const FORMAT_VERSION: u32 = 1;
+const BLOCK: usize = 512;
+
+/// Contents of a snapshot archive
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct SnapshotManifest {
+    pub format_version: u32,
+    /// Table version the snapshot captures
+    pub version: u64,
+    /// Milliseconds since the Unix epoch
+    pub created_at: u64,
+    pub files: Vec<SnapshotFile>,
+}
+
+/// One file in a snapshot, relative to the table root
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct SnapshotFile {
+    pub path: String,
+    pub size: u64,
+    pub md5: String,
+}
+
+/// Write `version` of the table at `base_path` to the archive `out_path`
+///
+/// The version is pinned by replaying the log up to it, so commits made while
+/// the export runs don't leak in. A referenced file that has disappeared,
+/// typically because vacuum removed it, fails the export rather than
+/// producing an archive that can't be restored.
+pub fn export_snapshot(
+    base_path: &Path,
+    version: u64,
+    out_path: &Path,
+) -> Result<SnapshotManifest> {
+    let log = read_delta_log_at(base_path, version)?;
+
+    let mut data_files: Vec<(String, u64)> = log
+        .files
+        .iter()
+        .map(|file| Ok((percent_decode(&file.path)?, file.size)))
+        .collect::<Result<_>>()?;
+    data_files.sort();
+    let expected_sizes: HashMap<String, u64> = data_files.iter().cloned().collect();
+    let mut paths: Vec<String> = data_files.into_iter().map(|(path, _)| path).collect();
+    for log_file in log_files_at(base_path, version)? {
+        let name = log_file
+            .file_name()
+            .and_then(|n| n.to_str())
+            .ok_or_else(|| Error::Other(format!("Invalid log file {}", log_file.display())))?;
+        paths.push(format!("_delta_log/{}", name));
+    }
+
+    // Write next to the target and rename, so a failed export leaves nothing behind
+    let mut partial_name = out_path.as_os_str().to_owned();
+    partial_name.push(".partial");
+    let partial_path = PathBuf::from(partial_name);
+
+    let result = write_archive(base_path, version, &paths, &expected_sizes, &partial_path);
+    match result {
+        Ok(manifest) => {
+            std::fs::rename(&partial_path, out_path)?;
+            info!(
+                "Exported version {} ({} files) to {}",
+                version,
+                manifest.files.len(),
+                out_path.display()
+            );
+            Ok(manifest)
+        }
+        Err(e) => {
+            let _ = std::fs::remove_file(&partial_path);
+            Err(e)
+        }
+    }
+}
+
+fn write_archive(
+    base_path: &Path,
+    version: u64,
+    paths: &[String],
+    expected_sizes: &HashMap<String, u64>,
+    archive_path: &Path,
+) -> Result<SnapshotManifest> {
+    let mut out = BufWriter::new(File::create(archive_path)?);
+    let mut files = Vec::with_capacity(paths.len());
+
+    for path in paths {
+        let mut file = File::open(base_path.join(path)).map_err(|e| {
+            if e.kind() == std::io::ErrorKind::NotFound {
+                Error::InvalidOperation(format!(
+                    "Version {} references {}, which no longer exists (removed by vacuum?)",
+                    version, path
+                ))
+            } else {
+                Error::Io(e)
+            }
+        })?;
+        let size = file.metadata()?.len();
+        if let Some(expected) = expected_sizes.get(path) {
+            if *expected != size {
+                return Err(Error::InvalidOperation(format!(
+                    "{} is {} bytes but version {} recorded {}",
+                    path, size, version, expected
+                )));
+            }
+        }
+
+        debug!("Archiving {} ({} bytes)", path, size);
+        write_header(&mut out, path, size)?;
+        let md5 = copy_hashed(&mut file, &mut out, size)?;
+        write_padding(&mut out, size)?;
+        files.push(SnapshotFile {
+            path: path.clone(),
+            size,
+            md5,
+        });
+    }
+
+    let manifest = SnapshotManifest {
+        format_version: FORMAT_VERSION,
+        version,
+        created_at: std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis() as u64,
+        files,
+    };
+    let manifest_json = serde_json::to_vec_pretty(&manifest)?;
+    write_header(&mut out, MANIFEST_NAME, manifest_json.len() as u64)?;
+    out.write_all(&manifest_json)?;
+    write_padding(&mut out, manifest_json.len() as u64)?;
+
+    // End-of-archive marker
+    out.write_all(&[0u8; BLOCK * 2])?;
+    out.into_inner()
+        .map_err(|e| Error::Io(e.into_error()))?
+        .sync_all()?;
+    Ok(manifest)
+}
+
+/// Unpack the archive at `archive_path` into the new table directory `dest`
+///
+/// Every file is checked against the manifest; on any mismatch `dest` is
+/// removed again.
+pub fn import_snapshot(archive_path: &Path, dest: &Path) -> Result<SnapshotManifest> {
+    if dest.exists() && std::fs::read_dir(dest)?.next().is_some() {
+        return Err(Error::DatabaseAlreadyExists(dest.display().to_string()));
+    }
+    std::fs::create_dir_all(dest)?;
+
+    match unpack_archive(archive_path, dest) {
+        Ok(manifest) => {
+            info!(
+                "Imported version {} ({} files) into {}",
+                manifest.version,
+                manifest.files.len(),
+                dest.display()
+            );
+            Ok(manifest)
+        }
+        Err(e) => {
+            let _ = std::fs::remove_dir_all(dest);
+            Err(e)
+        }
+    }
+}
+
+fn unpack_archive(archive_path: &Path, dest: &Path) -> Result<SnapshotManifest> {
+    let mut input = BufReader::new(File::open(archive_path)?);
+    let mut unpacked: HashMap<String, (u64, String)> = HashMap::new();
+    let mut manifest: Option<SnapshotManifest> = None;
+
+    while let Some((path, size)) = read_header(&mut input)? {
+        if path == MANIFEST_NAME {
+            let mut json = vec![0u8; size as usize];
+            input.read_exact(&mut json)?;
+            manifest = Some(serde_json::from_slice(&json)?);
+        } else {
+            let target = dest.join(safe_relative_path(&path)?);
+            if let Some(parent) = target.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            let mut out = File::create(&target)?;
+            let md5 = copy_hashed(&mut input, &mut out, size)?;
+            out.sync_all()?;
+            unpacked.insert(path, (size, md5));
+        }
+        skip_padding(&mut input, size)?;
+    }
+
+    let manifest = manifest.ok_or_else(|| {
+        Error::InvalidOperation(format!("{} is missing from the archive", MANIFEST_NAME))
+    })?;
+    if manifest.format_version > FORMAT_VERSION {
+        return Err(Error::InvalidOperation(format!(
+            "Snapshot format version {} is newer than the supported version {}",
+            manifest.format_version, FORMAT_VERSION
+        )));
+    }
+    for file in &manifest.files {
+        match unpacked.remove(&file.path) {
+            Some((size, md5)) if size == file.size && md5 == file.md5 => {}
+            Some(_) => {
+                return Err(Error::InvalidOperation(format!(
+                    "Checksum mismatch for {}",
+                    file.path
+                )))
+            }
+            None => {
+                return Err(Error::InvalidOperation(format!(
+                    "{} is missing from the archive",
+                    file.path
+                )))
+            }
+        }
+    }
+    if let Some(extra) = unpacked.keys().next() {
+        return Err(Error::InvalidOperation(format!(
+            "{} is in the archive but not in its manifest",
+            extra
+        )));
+    }
+    Ok(manifest)
+}
+
+/// Copy exactly `size` bytes, returning their MD5 as hex
+fn copy_hashed(input: &mut impl Read, out: &mut impl Write, size: u64) -> Result<String> {
+    let mut context = md5::Context::new();
+    let mut buffer = vec![0u8; 64 * 1024];
+    let mut remaining = size;
+    while remaining > 0 {
+        let chunk = remaining.min(buffer.len() as u64) as usize;
+        input.read_exact(&mut buffer[..chunk])?;
+        context.consume(&buffer[..chunk]);
+        out.write_all(&buffer[..chunk])?;
+        remaining -= chunk as u64;
+    }
+    Ok(format!("{:x}", context.finalize()))
+}
+
+/// Decode a percent-encoded path from the Delta log
+pub(crate) fn percent_decode(path: &str) -> Result<String> {
+    let bytes = path.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' {
+            let byte = path
+                .get(i + 1..i + 3)
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+                .ok_or_else(|| Error::Other(format!("Invalid escape in path {}", path)))?;
+            decoded.push(byte);
+            i += 3;
+        } else {
+            decoded.push(bytes[i]);
+            i += 1;
+        }
+    }
+    String::from_utf8(decoded).map_err(|_| Error::Other(format!("Invalid path {}", path)))
+}
+
+/// Reject entries that would land outside the destination
+fn safe_relative_path(path: &str) -> Result<&Path> {
+    let relative = Path::new(path);
+    if relative
+        .components()
+        .all(|component| matches!(component, Component::Normal(_)))
+    {
+        Ok(relative)
+    } else {
+        Err(Error::InvalidOperation(format!(
+            "Unsafe path in archive: {}",
+            path
+        )))
+    }
+}
+
+fn write_header(out: &mut impl Write, path: &str, size: u64) -> Result<()> {
+    let mut header = [0u8; BLOCK];
+
+    // Names over 100 bytes go into the 155-byte prefix, split at a '/'
+    let (prefix, name) = if path.len() <= 100 {
+        ("", path)
+    } else {
+        path.char_indices()
+            .filter(|(i, c)| *c == '/' && *i <= 155 && path.len() - i - 1 <= 100)
+            .map(|(i, _)| (&path[..i], &path[i + 1..]))
+            .next()
+            .ok_or_else(|| Error::InvalidOperation(format!("Path too long to archive: {}", path)))?
+    };
+
+    header[..name.len()].copy_from_slice(name.as_bytes());
+    write_octal(&mut header[100..108], 0o644);
+    write_octal(&mut header[108..116], 0);
+    write_octal(&mut header[116..124], 0);
+    write_octal(&mut header[124..136], size);
+    write_octal(&mut header[136..148], 0);
+    header[156] = b'0';
+    header[257..263].copy_from_slice(b"ustar\0");
+    header[263..265].copy_from_slice(b"00");
+    header[345..345 + prefix.len()].copy_from_slice(prefix.as_bytes());
+
+    // The checksum is computed with its own field set to spaces
+    header[148..156].fill(b' ');
+    let checksum: u32 = header.iter().map(|b| *b as u32).sum();
+    write_octal(&mut header[148..155], checksum as u64);
+
+    out.write_all(&header)?;
+    Ok(())
+}
+
+/// Next regular file's path and size, `None` at the end of the archive
+fn read_header(input: &mut impl Read) -> Result<Option<(String, u64)>> {
+    loop {
+        let mut header = [0u8; BLOCK];
+        input.read_exact(&mut header)?;
+        if header.iter().all(|b| *b == 0) {
+            return Ok(None);
+        }
+
+        let stored = parse_octal(&header[148..156])?;
+        let mut unsigned = header;
+        unsigned[148..156].fill(b' ');
+        if unsigned.iter().map(|b| *b as u64).sum::<u64>() != stored {
+            return Err(Error::InvalidOperation(
+                "Corrupt archive: bad header checksum".to_string(),
+            ));
+        }
+
+        let size = parse_octal(&header[124..136])?;
+        // Directories, links and extended headers carry nothing to restore
+        if !matches!(header[156], b'0' | 0) {
+            let mut skipped = std::io::sink();
+            copy_hashed(input, &mut skipped, size)?;
+            skip_padding(input, size)?;
+            continue;
+        }
+
+        let name = c_string(&header[..100])?;
+        let prefix = c_string(&header[345..500])?;
+        let path = if prefix.is_empty() {
+            name
+        } else {
+            format!("{}/{}", prefix, name)
+        };
+        return Ok(Some((path, size)));
+    }
+}
+
+fn write_padding(out: &mut impl Write, size: u64) -> Result<()> {
+    let padding = (BLOCK - (size as usize % BLOCK)) % BLOCK;
+    out.write_all(&[0u8; BLOCK][..padding])?;
+    Ok(())
+}
+
+fn skip_padding(input: &mut impl Read, size: u64) -> Result<()> {
+    let padding = (BLOCK - (size as usize % BLOCK)) % BLOCK;
+    input.read_exact(&mut [0u8; BLOCK][..padding])?;
+    Ok(())
+}
+
+fn write_octal(field: &mut [u8], value: u64) {
+    let digits = format!("{:0width$o}", value, width = field.len() - 1);
+    field[..digits.len()].copy_from_slice(digits.as_bytes());
+    field[digits.len()] = 0;
+}
+
+fn parse_octal(field: &[u8]) -> Result<u64> {
+    let text = std::str::from_utf8(field)
+        .map_err(|_| Error::InvalidOperation("Corrupt archive: bad number".to_string()))?;
+    let text = text.trim_matches(|c: char| c == '\0' || c == ' ');
+    if text.is_empty() {
+        return Ok(0);
+    }
+    u64::from_str_radix(text, 8)
+        .map_err(|_| Error::InvalidOperation("Corrupt archive: bad number".to_string()))
+}
+
+fn c_string(field: &[u8]) -> Result<String> {
+    let end = field.iter().position(|b| *b == 0).unwrap_or(field.len());
+    String::from_utf8(field[..end].to_vec())
+        .map_err(|_| Error::InvalidOperation("Corrupt archive: bad name".to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_header_round_trip() {
+        let long_path = format!("{}/{}", "p".repeat(120), "f".repeat(90));
+        for path in ["_delta_log/00000000000000000000.json", long_path.as_str()] {
+            let mut archive = Vec::new();
+            write_header(&mut archive, path, 1234).unwrap();
+            assert_eq!(archive.len(), BLOCK);
+
+            let parsed = read_header(&mut archive.as_slice()).unwrap();
+            assert_eq!(parsed, Some((path.to_string(), 1234)));
+        }
+    }
+
+    #[test]
+    fn test_corrupt_header_is_rejected() {
+        let mut archive = Vec::new();
+        write_header(&mut archive, "part-0.parquet", 10).unwrap();
+        archive[0] = b'q';
+
+        let err = read_header(&mut archive.as_slice()).unwrap_err();
+        assert!(err.to_string().contains("checksum"), "{}", err);
+    }
+
+    #[test]
+    fn test_unsafe_paths_are_rejected() {
+        assert!(safe_relative_path("_delta_log/00000000000000000000.json").is_ok());
+        assert!(safe_relative_path("../outside.parquet").is_err());
+        assert!(safe_relative_path("/etc/passwd").is_err());
+    }
+
+    #[test]
+    fn test_percent_decode() {
+        assert_eq!(
+            percent_decode("region=us%20east/part-0.parquet").unwrap(),
+            "region=us east/part-0.parquet"
+        );
+        assert!(percent_decode("bad%zz").is_err());
+    }
+}
@@ -0,0 +1,116 @@
+//! Table visibility for authenticated mounts
+//!
+//! A mount is authenticated as the database's user, or as a mount-time
+//! identity given to the filesystem. Tables that user's roles can't read are
+//! left out of directory listings, and anything under them is refused.
+//!
+//! The ACCESS procedure reports what the rest of the mount would allow, so
+//! clients see a table as read-only before a write fails.
+
+use crate::delta_lake::WriteMode;
+use nfsserve::nfs::{
+    nfsstat3, ACCESS3_DELETE, ACCESS3_EXTEND, ACCESS3_LOOKUP, ACCESS3_MODIFY, ACCESS3_READ,
+};
+
+/// What a lookup of a table the mount can't read returns
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ForbiddenLookup {
+    /// `NFS3ERR_ACCES`, telling permission apart from absence
+    #[default]
+    Denied,
+    /// `NFS3ERR_NOENT`, not revealing that the table exists
+    NotFound,
+}
+
+impl ForbiddenLookup {
+    /// NFS status to return
+    pub fn status(self) -> nfsstat3 {
+        match self {
+            Self::Denied => nfsstat3::NFS3ERR_ACCES,
+            Self::NotFound => nfsstat3::NFS3ERR_NOENT,
+        }
+    }
+}
+
+/// What the mount's user may do, as far as ACCESS is concerned
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Grants {
+    /// The user has `Permission::Write`
+    pub write: bool,
+    /// The user has `Permission::Delete`
+    pub delete: bool,
+}
+
+/// ACCESS3 bits for the table's directory and CSV files
+///
+/// Appending needs write permission, rewriting rows also needs the table to
+/// accept updates, and removing entries needs delete permission on a table
+/// that accepts deletes.
+pub fn table_access(grants: Grants, mode: WriteMode) -> u32 {
+    let mut bits = ACCESS3_READ | ACCESS3_LOOKUP;
+    if grants.write && mode != WriteMode::ReadOnly {
+        bits |= ACCESS3_EXTEND;
+        if mode == WriteMode::ReadWrite {
+            bits |= ACCESS3_MODIFY;
+        }
+    }
+    if grants.delete && mode == WriteMode::ReadWrite {
+        bits |= ACCESS3_DELETE;
+    }
+    bits
+}
+
+/// ACCESS3 bits for entries outside the table: the root and created scratch files
+pub fn scratch_access(grants: Grants) -> u32 {
+    let mut bits = ACCESS3_READ | ACCESS3_LOOKUP;
+    if grants.write {
+        bits |= ACCESS3_MODIFY | ACCESS3_EXTEND | ACCESS3_DELETE;
+    }
+    bits
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WRITER: Grants = Grants {
+        write: true,
+        delete: true,
+    };
+
+    #[test]
+    fn test_table_access_follows_write_mode() {
+        let all = ACCESS3_READ | ACCESS3_LOOKUP | ACCESS3_MODIFY | ACCESS3_EXTEND | ACCESS3_DELETE;
+        assert_eq!(table_access(WRITER, WriteMode::ReadWrite), all);
+        assert_eq!(
+            table_access(WRITER, WriteMode::AppendOnly),
+            ACCESS3_READ | ACCESS3_LOOKUP | ACCESS3_EXTEND
+        );
+        assert_eq!(
+            table_access(WRITER, WriteMode::ReadOnly),
+            ACCESS3_READ | ACCESS3_LOOKUP
+        );
+    }
+
+    #[test]
+    fn test_table_access_follows_grants() {
+        let reader = Grants {
+            write: false,
+            delete: false,
+        };
+        assert_eq!(
+            table_access(reader, WriteMode::ReadWrite),
+            ACCESS3_READ | ACCESS3_LOOKUP
+        );
+        assert_eq!(scratch_access(reader), ACCESS3_READ | ACCESS3_LOOKUP);
+
+        let appender = Grants {
+            write: true,
+            delete: false,
+        };
+        assert_eq!(
+            table_access(appender, WriteMode::ReadWrite) & ACCESS3_DELETE,
+            0
+        );
+    }
+}
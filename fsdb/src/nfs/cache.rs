@@ -4,9 +4,19 @@
 // - Memory cache (moka): Hot cache for frequently accessed data (microsecond access)
 // - Disk cache (sled): Warm cache for persistent storage (millisecond access)
 // - Database: Cold storage (10-100ms access)
+//
+// Disk entries can be stored zstd-compressed. What counts against the disk
+// budget is what sled stores, so compressed entries count at their
+// compressed size.
+//
+// Values too large to hold in memory, like data.csv of a big table, are
+// stored as chunked entries: a `ChunkSpool` writes them to the disk tier a
+// chunk at a time as they're generated, with an index of chunk offsets, and
+// `read_chunked` reads back a byte range from the chunks that cover it.
 
 use crate::error::Result;
 use moka::future::Cache;
+use serde::{Deserialize, Serialize};
 use sled::Db;
 use std::path::Path;
 use std::sync::Arc;
@@ -27,8 +37,14 @@ pub struct NfsCache {
 
     /// Maximum disk cache size in bytes
     max_disk_size: u64,
+
+    /// zstd level for disk entries, if they're compressed
+    disk_compression: Option<i32>,
 }
 
+/// Magic number every zstd frame starts with
+const ZSTD_MAGIC: [u8; 4] = [0x28, 0xb5, 0x2f, 0xfd];
+
 impl NfsCache {
     /// Create a new NFS cache with default settings
     pub async fn new(disk_path: impl AsRef<Path>) -> Result<Self> {
@@ -51,6 +67,7 @@ impl NfsCache {
             memory,
             disk,
             max_disk_size: config.max_disk_size,
+            disk_compression: config.disk_compression,
         })
     }
 
@@ -67,7 +84,7 @@ impl NfsCache {
             crate::error::Error::InvalidOperation(format!("Disk cache read error: {}", e))
         })? {
             tracing::debug!("Cache HIT (disk): {}", key);
-            let bytes = value.to_vec();
+            let bytes = Self::decode_disk_value(&value)?;
 
             // Promote to memory cache
             self.memory
@@ -89,8 +106,9 @@ impl NfsCache {
             .await;
 
         // Insert into disk cache
+        let stored = self.encode_disk_value(&value)?;
         self.disk
-            .insert(key.as_bytes(), value.as_slice())
+            .insert(key.as_bytes(), stored.as_slice())
             .map_err(|e| {
                 crate::error::Error::InvalidOperation(format!("Disk cache write error: {}", e))
             })?;
@@ -106,6 +124,97 @@ impl NfsCache {
         Ok(())
     }
 
+    /// Get `key`'s value if `insert_versioned` stored it at `version`
+    ///
+    /// An entry stored at another version, or without one, is a miss, so a
+    /// cache shared by several exports of a database never serves content
+    /// from before another export's write.
+    pub async fn get_versioned(&self, key: &str, version: &str) -> Result<Option<Vec<u8>>> {
+        match self.get(&Self::version_key(key)).await? {
+            Some(stored) if stored == version.as_bytes() => self.get(key).await,
+            _ => Ok(None),
+        }
+    }
+
+    /// Insert `value`, generated at `version`, for `get_versioned`
+    pub async fn insert_versioned(&self, key: String, value: Vec<u8>, version: &str) -> Result<()> {
+        let version_key = Self::version_key(&key);
+        self.insert(key, value).await?;
+        self.insert(version_key, version.as_bytes().to_vec()).await
+    }
+
+    fn version_key(key: &str) -> String {
+        format!("{}#version", key)
+    }
+
+    /// Read `count` bytes at `offset` of the chunked entry `key`
+    ///
+    /// Returns the bytes and the entry's total length, or None unless a
+    /// `ChunkSpool` finished the entry at `version`. Only the chunks covering
+    /// the range are read.
+    pub async fn read_chunked(
+        &self,
+        key: &str,
+        version: Option<&str>,
+        offset: u64,
+        count: u64,
+    ) -> Result<Option<(Vec<u8>, u64)>> {
+        let Some(index) = self.chunk_index(key, version).await? else {
+            return Ok(None);
+        };
+        let total = index.len();
+        let end = offset.saturating_add(count).min(total);
+
+        let mut data = Vec::new();
+        let mut position = offset;
+        let mut chunk = index.ends.partition_point(|&chunk_end| chunk_end <= offset);
+        while position < end {
+            let chunk_start = chunk.checked_sub(1).map_or(0, |i| index.ends[i]);
+            let chunk_end = index.ends[chunk];
+            let stored = self.disk.get(Self::chunk_key(key, chunk)).map_err(|e| {
+                crate::error::Error::InvalidOperation(format!("Disk cache read error: {}", e))
+            })?;
+            let Some(stored) = stored else {
+                // Evicted since the entry was written
+                return Ok(None);
+            };
+            let bytes = Self::decode_disk_value(&stored)?;
+            if bytes.len() as u64 != chunk_end - chunk_start {
+                return Ok(None);
+            }
+            let from = (position - chunk_start) as usize;
+            let to = (end.min(chunk_end) - chunk_start) as usize;
+            data.extend_from_slice(&bytes[from..to]);
+            position = end.min(chunk_end);
+            chunk += 1;
+        }
+        Ok(Some((data, total)))
+    }
+
+    /// Total length of the chunked entry `key`, if finished at `version`
+    pub async fn chunked_len(&self, key: &str, version: Option<&str>) -> Result<Option<u64>> {
+        Ok(self
+            .chunk_index(key, version)
+            .await?
+            .map(|index| index.len()))
+    }
+
+    async fn chunk_index(&self, key: &str, version: Option<&str>) -> Result<Option<ChunkIndex>> {
+        let Some(index) = self.get(&Self::chunk_index_key(key)).await? else {
+            return Ok(None);
+        };
+        let index: ChunkIndex = serde_json::from_slice(&index)?;
+        Ok((index.version.as_deref() == version).then_some(index))
+    }
+
+    fn chunk_index_key(key: &str) -> String {
+        format!("{}#chunks", key)
+    }
+
+    fn chunk_key(key: &str, chunk: usize) -> String {
+        format!("{}#chunk:{}", key, chunk)
+    }
+
     /// Remove entry from both caches
     pub async fn remove(&self, key: &str) -> Result<()> {
         self.memory.invalidate(key).await;
@@ -141,8 +250,45 @@ impl NfsCache {
         })
     }
 
+    /// Maximum disk cache size in bytes
+    pub fn max_disk_size(&self) -> u64 {
+        self.max_disk_size
+    }
+
+    /// Form of `value` stored in the disk tier
+    ///
+    /// Entries are compressed when compression is on. A value that happens to
+    /// start with the zstd magic number is compressed regardless, so reading
+    /// it back can't mistake it for a compressed entry.
+    fn encode_disk_value(&self, value: &[u8]) -> Result<Vec<u8>> {
+        let level = match self.disk_compression {
+            Some(level) => level,
+            None if value.starts_with(&ZSTD_MAGIC) => zstd::DEFAULT_COMPRESSION_LEVEL,
+            None => return Ok(value.to_vec()),
+        };
+        zstd::encode_all(value, level).map_err(|e| {
+            crate::error::Error::InvalidOperation(format!("Disk cache compression error: {}", e))
+        })
+    }
+
+    /// Value read from the disk tier, decompressed if it was stored compressed
+    ///
+    /// Compressed entries are recognised by their magic number rather than by
+    /// the current setting, so a cache reopened with compression switched on
+    /// or off still reads what it stored before.
+    fn decode_disk_value(stored: &[u8]) -> Result<Vec<u8>> {
+        if !stored.starts_with(&ZSTD_MAGIC) {
+            return Ok(stored.to_vec());
+        }
+        zstd::decode_all(stored).map_err(|e| {
+            crate::error::Error::InvalidOperation(format!("Disk cache decompression error: {}", e))
+        })
+    }
+
     /// Evict old entries from disk cache if size exceeds limit
-    async fn maybe_evict_disk(&self) -> Result<()> {
+    ///
+    /// Returns whether it did.
+    async fn maybe_evict_disk(&self) -> Result<bool> {
         let size = self.disk_size_bytes()?;
 
         if size > self.max_disk_size {
@@ -154,10 +300,90 @@ impl NfsCache {
             self.disk.clear().map_err(|e| {
                 crate::error::Error::InvalidOperation(format!("Failed to clear disk cache: {}", e))
             })?;
+            return Ok(true);
         }
 
+        Ok(false)
+    }
+}
+
+/// Where each chunk of a chunked entry ends, and the version it was made at
+#[derive(Debug, Serialize, Deserialize)]
+struct ChunkIndex {
+    version: Option<String>,
+    ends: Vec<u64>,
+}
+
+impl ChunkIndex {
+    fn len(&self) -> u64 {
+        self.ends.last().copied().unwrap_or(0)
+    }
+}
+
+/// Writes a chunked entry to the disk tier as its chunks are produced
+///
+/// The entry is readable with `NfsCache::read_chunked` once `finish` writes
+/// its index. Until then any earlier entry under the key reads as a miss, so
+/// a spool abandoned partway leaves nothing readable behind.
+pub struct ChunkSpool {
+    cache: Arc<NfsCache>,
+    key: String,
+    version: Option<String>,
+    ends: Vec<u64>,
+}
+
+impl ChunkSpool {
+    /// Start spooling the entry `key`, generated at `version`
+    pub async fn new(
+        cache: Arc<NfsCache>,
+        key: impl Into<String>,
+        version: Option<String>,
+    ) -> Result<Self> {
+        let key = key.into();
+        cache.remove(&NfsCache::chunk_index_key(&key)).await?;
+        Ok(Self {
+            cache,
+            key,
+            version,
+            ends: Vec::new(),
+        })
+    }
+
+    /// Write the next chunk
+    pub fn push(&mut self, chunk: &[u8]) -> Result<()> {
+        let key = NfsCache::chunk_key(&self.key, self.ends.len());
+        let stored = self.cache.encode_disk_value(chunk)?;
+        self.cache
+            .disk
+            .insert(key.as_bytes(), stored.as_slice())
+            .map_err(|e| {
+                crate::error::Error::InvalidOperation(format!("Disk cache write error: {}", e))
+            })?;
+        let end = self.ends.last().copied().unwrap_or(0) + chunk.len() as u64;
+        self.ends.push(end);
         Ok(())
     }
+
+    /// Write the index, making the entry readable, and return its length
+    ///
+    /// If the chunks pushed the disk tier over its budget it's cleared as
+    /// usual, and the entry isn't written.
+    pub async fn finish(self) -> Result<u64> {
+        let index = ChunkIndex {
+            version: self.version,
+            ends: self.ends,
+        };
+        let len = index.len();
+        self.cache.disk.flush().map_err(|e| {
+            crate::error::Error::InvalidOperation(format!("Disk cache flush error: {}", e))
+        })?;
+        if self.cache.maybe_evict_disk().await? {
+            return Ok(len);
+        }
+        let key = NfsCache::chunk_index_key(&self.key);
+        self.cache.insert(key, serde_json::to_vec(&index)?).await?;
+        Ok(len)
+    }
 }
 
 /// Configuration for NFS cache
@@ -174,6 +400,11 @@ pub struct CacheConfig {
 
     /// Maximum disk cache size in bytes
     pub max_disk_size: u64,
+
+    /// zstd level to compress disk entries with, or None to store them as-is
+    ///
+    /// Memory entries are never compressed.
+    pub disk_compression: Option<i32>,
 }
 
 impl Default for CacheConfig {
@@ -183,6 +414,7 @@ impl Default for CacheConfig {
             memory_ttl: Duration::from_secs(300),         // 5 minutes
             memory_idle_timeout: Duration::from_secs(60), // 1 minute
             max_disk_size: 1_073_741_824,                 // 1 GB
+            disk_compression: None,
         }
     }
 }
@@ -206,6 +438,65 @@ mod tests {
         assert_eq!(cache.disk_entry_count().unwrap(), 0);
     }
 
+    #[tokio::test]
+    async fn test_versioned_entries_miss_other_versions() {
+        let (cache, _temp) = create_test_cache().await;
+
+        cache
+            .insert_versioned("csv:data".to_string(), b"id\n1\n".to_vec(), "3:0")
+            .await
+            .unwrap();
+        assert_eq!(
+            cache.get_versioned("csv:data", "3:0").await.unwrap(),
+            Some(b"id\n1\n".to_vec())
+        );
+        assert_eq!(cache.get_versioned("csv:data", "4:0").await.unwrap(), None);
+
+        // A plain insert carries no version
+        cache
+            .insert("csv:other".to_string(), b"x".to_vec())
+            .await
+            .unwrap();
+        assert_eq!(cache.get_versioned("csv:other", "3:0").await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn test_chunked_entries() {
+        let (cache, _temp) = create_test_cache().await;
+        let cache = Arc::new(cache);
+        let version = Some("3:0".to_string());
+
+        let mut spool = ChunkSpool::new(cache.clone(), "csv:data", version.clone())
+            .await
+            .unwrap();
+        for chunk in [&b"id,name\n"[..], b"1,a\n2,b\n", b"3,c\n"] {
+            spool.push(chunk).unwrap();
+        }
+        // Nothing is readable until the index is written
+        let read = cache.read_chunked("csv:data", Some("3:0"), 0, 4).await;
+        assert_eq!(read.unwrap(), None);
+        assert_eq!(spool.finish().await.unwrap(), 20);
+
+        // Ranges spanning chunks, at the end, and past it
+        let read = cache.read_chunked("csv:data", Some("3:0"), 6, 6).await;
+        assert_eq!(read.unwrap(), Some((b"e\n1,a\n".to_vec(), 20)));
+        let read = cache.read_chunked("csv:data", Some("3:0"), 18, 10).await;
+        assert_eq!(read.unwrap(), Some((b"c\n".to_vec(), 20)));
+        let read = cache.read_chunked("csv:data", Some("3:0"), 25, 5).await;
+        assert_eq!(read.unwrap(), Some((Vec::new(), 20)));
+        let len = cache.chunked_len("csv:data", Some("3:0")).await;
+        assert_eq!(len.unwrap(), Some(20));
+
+        // Other versions miss, and a new spool hides the finished entry
+        let read = cache.read_chunked("csv:data", Some("4:0"), 0, 4).await;
+        assert_eq!(read.unwrap(), None);
+        ChunkSpool::new(cache.clone(), "csv:data", Some("4:0".to_string()))
+            .await
+            .unwrap();
+        let read = cache.read_chunked("csv:data", Some("3:0"), 0, 4).await;
+        assert_eq!(read.unwrap(), None);
+    }
+
     #[tokio::test]
     async fn test_cache_insert_and_get() {
         let (cache, _temp) = create_test_cache().await;
@@ -356,6 +647,7 @@ mod tests {
             memory_ttl: Duration::from_secs(1),
             memory_idle_timeout: Duration::from_millis(500),
             max_disk_size: 1_000_000, // 1MB
+            disk_compression: None,
         };
 
         let cache = NfsCache::with_config(temp_dir.path().join("cache"), config)
@@ -371,6 +663,76 @@ mod tests {
         assert_eq!(cached, Some(b"value".to_vec()));
     }
 
+    /// CSV text that repeats every thousand rows, so it compresses very well
+    fn csv_value(rows: usize) -> Vec<u8> {
+        let mut csv = String::from("id,name,value\n");
+        for i in 0..rows {
+            let id = i % 1000;
+            csv.push_str(&format!("{},user_{},{}\n", id, id % 100, id * 10));
+        }
+        csv.into_bytes()
+    }
+
+    async fn create_compressed_cache(temp: &TempDir, max_disk_size: u64) -> NfsCache {
+        let config = CacheConfig {
+            max_disk_size,
+            disk_compression: Some(3),
+            ..CacheConfig::default()
+        };
+        NfsCache::with_config(temp.path().join("cache"), config)
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_disk_compression_round_trip() {
+        let temp_dir = TempDir::new().unwrap();
+        let cache = create_compressed_cache(&temp_dir, 1_073_741_824).await;
+        let value = csv_value(10_000);
+
+        cache
+            .insert("csv:data".to_string(), value.clone())
+            .await
+            .unwrap();
+
+        // The disk tier holds a zstd frame much smaller than the CSV
+        let stored = cache.disk.get("csv:data").unwrap().unwrap();
+        assert!(stored.starts_with(&ZSTD_MAGIC));
+        assert!(stored.len() < value.len() / 4, "{} bytes", stored.len());
+
+        // Memory keeps the plain bytes; reads from disk decompress
+        assert_eq!(cache.get("csv:data").await.unwrap(), Some(value.clone()));
+        cache.memory.invalidate("csv:data").await;
+        assert_eq!(cache.get("csv:data").await.unwrap(), Some(value.clone()));
+
+        // Reopened without compression, earlier entries still read back
+        drop(cache);
+        let cache = NfsCache::new(temp_dir.path().join("cache")).await.unwrap();
+        assert_eq!(cache.get("csv:data").await.unwrap(), Some(value));
+
+        // A plain value that looks like a zstd frame isn't misread
+        let lookalike = [&ZSTD_MAGIC[..], b"not really zstd"].concat();
+        cache
+            .insert("odd".to_string(), lookalike.clone())
+            .await
+            .unwrap();
+        cache.memory.invalidate("odd").await;
+        assert_eq!(cache.get("odd").await.unwrap(), Some(lookalike));
+    }
+
+    #[tokio::test]
+    async fn test_compressed_size_counts_against_disk_budget() {
+        let temp_dir = TempDir::new().unwrap();
+        let cache = create_compressed_cache(&temp_dir, 1_000_000).await;
+
+        // Several times the budget uncompressed, well under it compressed
+        let value = csv_value(200_000);
+        assert!(value.len() as u64 > 3 * cache.max_disk_size());
+        cache.insert("csv:data".to_string(), value).await.unwrap();
+
+        assert_eq!(cache.disk_entry_count().unwrap(), 1, "entry was evicted");
+    }
+
     #[tokio::test]
     async fn test_concurrent_access() {
         let (cache, _temp) = create_test_cache().await;
@@ -0,0 +1,327 @@
+//! Background cache warming for hot tables
+//!
+//! A cold read of `data.csv` regenerates the whole CSV from Delta Lake. The
+//! warmer polls the version of each configured table and, once a new commit
+//! has settled, regenerates the CSV into `NfsCache` so the next client read is
+//! a hit. Tables still being written, warmed too recently, or whose CSV would
+//! crowd out the rest of the cache are left alone.
+
+use crate::database_ops::DatabaseOps;
+use crate::error::Result;
+use crate::nfs::cache::NfsCache;
+use crate::nfs::file_views::{csv_version, CsvFileView};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+use tracing::{debug, info, warn};
+
+/// Configuration for the cache warmer
+#[derive(Debug, Clone)]
+pub struct WarmerConfig {
+    /// How often table versions are checked
+    pub poll_interval: Duration,
+
+    /// Minimum time between two warms of the same table
+    pub min_interval: Duration,
+
+    /// A table committed to more recently than this is considered actively
+    /// written, and warming waits until it settles
+    pub quiet_period: Duration,
+
+    /// Largest CSV the warmer will cache (default: a quarter of the cache's disk budget)
+    pub max_entry_bytes: Option<u64>,
+}
+
+impl Default for WarmerConfig {
+    fn default() -> Self {
+        Self {
+            poll_interval: Duration::from_secs(1),
+            min_interval: Duration::from_secs(10),
+            quiet_period: Duration::from_secs(2),
+            max_entry_bytes: None,
+        }
+    }
+}
+
+/// What a warming pass did for one table
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WarmOutcome {
+    /// CSV regenerated and cached (size in bytes)
+    Warmed(u64),
+
+    /// Cache already holds the current version
+    Unchanged,
+
+    /// Table committed to within the quiet period or has buffered writes
+    ActiveWrites,
+
+    /// Warmed within `min_interval`
+    RateLimited,
+
+    /// CSV (or the table's Parquet data) exceeds the size budget
+    TooLarge(u64),
+}
+
+/// Per-table warming state
+struct HotTable {
+    name: String,
+    db: Arc<DatabaseOps>,
+    /// Version currently held in the cache
+    warmed_version: Option<i64>,
+    /// Latest version observed and when it was first seen
+    seen_version: Option<i64>,
+    seen_at: Instant,
+    last_warm: Option<Instant>,
+}
+
+/// Regenerates cached CSV for hot tables after commits
+pub struct CacheWarmer {
+    cache: Arc<NfsCache>,
+    config: WarmerConfig,
+    tables: Vec<HotTable>,
+}
+
+impl CacheWarmer {
+    /// Create a warmer with no tables
+    pub fn new(cache: Arc<NfsCache>, config: WarmerConfig) -> Self {
+        Self {
+            cache,
+            config,
+            tables: Vec::new(),
+        }
+    }
+
+    /// Keep `name` warm; its CSV is cached under `csv:<name>`
+    pub fn with_table(mut self, name: impl Into<String>, db: Arc<DatabaseOps>) -> Self {
+        self.tables.push(HotTable {
+            name: name.into(),
+            db,
+            warmed_version: None,
+            seen_version: None,
+            seen_at: Instant::now(),
+            last_warm: None,
+        });
+        self
+    }
+
+    /// Cache key holding the CSV of a table
+    pub fn cache_key(name: &str) -> String {
+        format!("csv:{}", name)
+    }
+
+    /// Largest CSV that will be cached
+    fn budget(&self) -> u64 {
+        self.config
+            .max_entry_bytes
+            .unwrap_or(self.cache.max_disk_size() / 4)
+    }
+
+    /// Check every table once, warming those with a settled new commit
+    pub async fn warm_once(&mut self) -> Vec<(String, WarmOutcome)> {
+        let budget = self.budget();
+        let mut outcomes = Vec::with_capacity(self.tables.len());
+        for table in &mut self.tables {
+            let outcome = match warm_table(&self.cache, &self.config, budget, table).await {
+                Ok(outcome) => outcome,
+                Err(e) => {
+                    warn!("Cache warming failed for {}: {}", table.name, e);
+                    continue;
+                }
+            };
+            debug!("Cache warming {}: {:?}", table.name, outcome);
+            outcomes.push((table.name.clone(), outcome));
+        }
+        outcomes
+    }
+
+    /// Run the warmer in a background task until the handle is stopped
+    pub fn spawn(mut self) -> WarmerHandle {
+        let (shutdown_tx, mut shutdown_rx) = mpsc::channel(1);
+        let poll_interval = self.config.poll_interval;
+
+        let task = tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(poll_interval);
+            ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+            loop {
+                tokio::select! {
+                    _ = ticker.tick() => {
+                        self.warm_once().await;
+                    }
+                    _ = shutdown_rx.recv() => {
+                        info!("Cache warmer stopped");
+                        break;
+                    }
+                }
+            }
+        });
+
+        WarmerHandle { shutdown_tx, task }
+    }
+}
+
+/// Handle to a running cache warmer
+pub struct WarmerHandle {
+    shutdown_tx: mpsc::Sender<()>,
+    task: JoinHandle<()>,
+}
+
+impl WarmerHandle {
+    /// Stop the warmer and wait for the current pass to finish
+    pub async fn stop(self) {
+        self.shutdown_tx.send(()).await.ok();
+        self.task.await.ok();
+    }
+}
+
+async fn warm_table(
+    cache: &NfsCache,
+    config: &WarmerConfig,
+    budget: u64,
+    table: &mut HotTable,
+) -> Result<WarmOutcome> {
+    let version = table.db.get_delta_table().await?.version();
+    if version != table.seen_version {
+        table.seen_version = version;
+        table.seen_at = Instant::now();
+    }
+
+    if version == table.warmed_version {
+        return Ok(WarmOutcome::Unchanged);
+    }
+    if table.seen_at.elapsed() < config.quiet_period || table.db.buffered_rows().await > 0 {
+        return Ok(WarmOutcome::ActiveWrites);
+    }
+    if let Some(last_warm) = table.last_warm {
+        if last_warm.elapsed() < config.min_interval {
+            return Ok(WarmOutcome::RateLimited);
+        }
+    }
+
+    let key = CacheWarmer::cache_key(&table.name);
+
+    // CSV is larger than the Parquet it comes from, so skip generating it at all
+    // when the Parquet data alone is over budget
+    let parquet_bytes: u64 =
+        crate::delta_lake::data_skipping::get_file_statistics(table.db.base_path())
+            .map(|files| files.iter().map(|f| f.size_bytes).sum())
+            .unwrap_or(0);
+    if parquet_bytes > budget {
+        return too_large(cache, &key, table, version, parquet_bytes).await;
+    }
+
+    let csv_version = csv_version(&table.db).await;
+    let csv = CsvFileView::new(table.db.clone()).generate_csv().await?;
+    let size = csv.len() as u64;
+    if size > budget {
+        return too_large(cache, &key, table, version, size).await;
+    }
+
+    match csv_version {
+        Some(csv_version) => cache.insert_versioned(key, csv, &csv_version).await?,
+        None => cache.insert(key, csv).await?,
+    }
+    table.warmed_version = version;
+    table.last_warm = Some(Instant::now());
+    info!(
+        "Warmed cache for {} at version {:?} ({} bytes)",
+        table.name, version, size
+    );
+    Ok(WarmOutcome::Warmed(size))
+}
+
+/// Drop the now-stale entry and stop retrying until the next commit
+async fn too_large(
+    cache: &NfsCache,
+    key: &str,
+    table: &mut HotTable,
+    version: Option<i64>,
+    size: u64,
+) -> Result<WarmOutcome> {
+    info!(
+        "Not warming {}: {} bytes exceeds cache budget",
+        table.name, size
+    );
+    cache.remove(key).await?;
+    table.warmed_version = version;
+    Ok(WarmOutcome::TooLarge(size))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow::array::{ArrayRef, Int32Array, RecordBatch};
+    use arrow::datatypes::{DataType, Field, Schema};
+    use tempfile::TempDir;
+
+    async fn setup() -> (Arc<DatabaseOps>, Arc<NfsCache>, TempDir) {
+        let temp_dir = TempDir::new().unwrap();
+        let schema = Arc::new(Schema::new(vec![Field::new("id", DataType::Int32, false)]));
+        let db = DatabaseOps::create(temp_dir.path().join("db"), schema.clone())
+            .await
+            .unwrap();
+        let batch = RecordBatch::try_new(
+            schema,
+            vec![Arc::new(Int32Array::from(vec![1, 2, 3])) as ArrayRef],
+        )
+        .unwrap();
+        db.insert(batch).await.unwrap();
+        let cache = NfsCache::new(temp_dir.path().join("cache")).await.unwrap();
+        (Arc::new(db), Arc::new(cache), temp_dir)
+    }
+
+    fn config() -> WarmerConfig {
+        WarmerConfig {
+            poll_interval: Duration::from_millis(10),
+            min_interval: Duration::ZERO,
+            quiet_period: Duration::ZERO,
+            max_entry_bytes: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_warms_then_unchanged() {
+        let (db, cache, _temp) = setup().await;
+        let mut warmer = CacheWarmer::new(cache.clone(), config()).with_table("data", db);
+
+        let outcomes = warmer.warm_once().await;
+        assert!(matches!(outcomes[0].1, WarmOutcome::Warmed(_)));
+        let cached = cache.get("csv:data").await.unwrap().unwrap();
+        assert!(String::from_utf8(cached).unwrap().starts_with("id\n"));
+
+        assert_eq!(warmer.warm_once().await[0].1, WarmOutcome::Unchanged);
+    }
+
+    #[tokio::test]
+    async fn test_waits_for_quiet_period() {
+        let (db, cache, _temp) = setup().await;
+        let config = WarmerConfig {
+            quiet_period: Duration::from_secs(60),
+            ..config()
+        };
+        let mut warmer = CacheWarmer::new(cache.clone(), config).with_table("data", db);
+
+        assert_eq!(warmer.warm_once().await[0].1, WarmOutcome::ActiveWrites);
+        assert!(cache.get("csv:data").await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_skips_tables_over_budget() {
+        let (db, cache, _temp) = setup().await;
+        cache
+            .insert("csv:data".to_string(), b"stale".to_vec())
+            .await
+            .unwrap();
+        let config = WarmerConfig {
+            max_entry_bytes: Some(4),
+            ..config()
+        };
+        let mut warmer = CacheWarmer::new(cache.clone(), config).with_table("data", db);
+
+        assert!(matches!(
+            warmer.warm_once().await[0].1,
+            WarmOutcome::TooLarge(_)
+        ));
+        assert!(cache.get("csv:data").await.unwrap().is_none());
+    }
+}
@@ -0,0 +1,1218 @@
+//! Type coercion for CSV text written through the NFS mount
+//!
+//! Cells arrive as text; this module maps them back onto the table's typed
+//! schema according to a `CoercionPolicy`. Binary columns have no natural
+//! text form, so they are written and read back as base64 or hex.
+//!
+//! NULL is spelled by the policy's `NullRepresentation`. A quoted field is
+//! always a value, so `""` is the empty string and `"\N"` the text `\N`;
+//! `write_csv` quotes text that would otherwise read back as NULL.
+
+use crate::error::{Error, Result};
+use arrow::array::{
+    Array, ArrayRef, AsArray, BooleanBuilder, GenericBinaryBuilder, GenericStringArray,
+    OffsetSizeTrait, PrimitiveBuilder, RecordBatch, StringArray,
+};
+use arrow::datatypes::{
+    ArrowPrimitiveType, DataType, Date32Type, Field, Float32Type, Float64Type, Int16Type,
+    Int32Type, Int64Type, Int8Type, SchemaRef, TimeUnit, TimestampMicrosecondType,
+    TimestampMillisecondType, TimestampNanosecondType, TimestampSecondType, UInt16Type, UInt32Type,
+    UInt64Type, UInt8Type,
+};
+use base64::Engine;
+use chrono::{DateTime, NaiveDate, NaiveDateTime, Utc};
+use std::str::FromStr;
+use std::sync::Arc;
+
+/// How unparseable cells are handled
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CoercionMode {
+    /// Reject the whole write with `Error::TypeCoercion`
+    #[default]
+    Strict,
+    /// Store NULL for the offending cell (nullable columns only)
+    Lenient,
+}
+
+/// Text form of binary cells in the CSV view
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BinaryEncoding {
+    /// Standard base64 with padding
+    #[default]
+    Base64,
+    /// Lowercase hex, two digits per byte (uppercase accepted on write)
+    Hex,
+}
+
+impl BinaryEncoding {
+    /// Render bytes as text
+    pub fn encode(self, bytes: &[u8]) -> String {
+        match self {
+            Self::Base64 => base64::engine::general_purpose::STANDARD.encode(bytes),
+            Self::Hex => bytes.iter().map(|b| format!("{:02x}", b)).collect(),
+        }
+    }
+
+    /// Parse text rendered by `encode`, `None` if it isn't valid
+    pub fn decode(self, text: &str) -> Option<Vec<u8>> {
+        let text = text.trim();
+        match self {
+            Self::Base64 => base64::engine::general_purpose::STANDARD.decode(text).ok(),
+            Self::Hex => {
+                if !text.len().is_multiple_of(2) || !text.bytes().all(|b| b.is_ascii_hexdigit()) {
+                    return None;
+                }
+                (0..text.len())
+                    .step_by(2)
+                    .map(|i| u8::from_str_radix(&text[i..i + 2], 16).ok())
+                    .collect()
+            }
+        }
+    }
+}
+
+/// How NULL is spelled in CSV text
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum NullRepresentation {
+    /// An unquoted empty field is NULL, and `""` the empty string
+    #[default]
+    Empty,
+
+    /// An unquoted field equal to the token (such as `\N`) is NULL, and an
+    /// empty field the empty string. The token mustn't contain commas,
+    /// quotes or line breaks.
+    Token(String),
+}
+
+impl NullRepresentation {
+    /// Text NULL is written as
+    pub fn token(&self) -> &str {
+        match self {
+            Self::Empty => "",
+            Self::Token(token) => token,
+        }
+    }
+}
+
+/// Marks a field that was quoted in the CSV text, see `mark_quoted_fields`
+const QUOTED: char = '\u{0}';
+
+/// Configuration for coercing CSV text into typed columns
+#[derive(Debug, Clone)]
+pub struct CoercionPolicy {
+    /// Strict or lenient handling of unparseable cells
+    pub mode: CoercionMode,
+
+    /// Decimal separator used in numeric cells
+    pub decimal_separator: char,
+
+    /// Optional digit-group separator stripped from numeric cells
+    pub thousands_separator: Option<char>,
+
+    /// Case-insensitive spellings accepted as `true`
+    pub true_values: Vec<String>,
+
+    /// Case-insensitive spellings accepted as `false`
+    pub false_values: Vec<String>,
+
+    /// Text form of binary cells, both when rendered and when written
+    pub binary_encoding: BinaryEncoding,
+
+    /// Add columns a rewritten file's header introduces, instead of rejecting it
+    pub add_new_columns: bool,
+
+    /// How NULL is spelled, both when rendered and when written
+    pub null: NullRepresentation,
+}
+
+impl Default for CoercionPolicy {
+    fn default() -> Self {
+        Self {
+            mode: CoercionMode::Strict,
+            decimal_separator: '.',    // C locale
+            thousands_separator: None, // No digit grouping
+            true_values: ["true", "t", "yes", "y", "1"]
+                .iter()
+                .map(|s| s.to_string())
+                .collect(),
+            false_values: ["false", "f", "no", "n", "0"]
+                .iter()
+                .map(|s| s.to_string())
+                .collect(),
+            binary_encoding: BinaryEncoding::Base64,
+            add_new_columns: false,
+            null: NullRepresentation::Empty,
+        }
+    }
+}
+
+impl CoercionPolicy {
+    /// Strict policy (the default)
+    pub fn strict() -> Self {
+        Self::default()
+    }
+
+    /// Lenient policy: unparseable cells become NULL
+    pub fn lenient() -> Self {
+        Self {
+            mode: CoercionMode::Lenient,
+            ..Self::default()
+        }
+    }
+
+    /// Use locale-specific number separators (e.g. `,` decimal and `.` grouping)
+    pub fn with_number_format(mut self, decimal: char, thousands: Option<char>) -> Self {
+        self.decimal_separator = decimal;
+        self.thousands_separator = thousands;
+        self
+    }
+
+    /// Evolve the schema when a rewritten file's header adds columns
+    ///
+    /// New columns are added as nullable strings.
+    pub fn with_schema_evolution(mut self, enabled: bool) -> Self {
+        self.add_new_columns = enabled;
+        self
+    }
+
+    /// Render and read binary cells as `encoding` instead of base64
+    pub fn with_binary_encoding(mut self, encoding: BinaryEncoding) -> Self {
+        self.binary_encoding = encoding;
+        self
+    }
+
+    /// Spell NULL as `null` instead of an unquoted empty field
+    pub fn with_null_representation(mut self, null: NullRepresentation) -> Self {
+        self.null = null;
+        self
+    }
+
+    /// Value of a parsed cell, or None for NULL
+    ///
+    /// Only text and binary columns have an empty value; in other columns an
+    /// empty cell is always NULL.
+    fn cell_value(&self, cell: &str, has_empty_value: bool) -> Option<String> {
+        let (text, quoted) = match cell.strip_prefix(QUOTED) {
+            Some(text) => (text, true),
+            None => (cell, false),
+        };
+        let is_null = if text.trim().is_empty() {
+            !has_empty_value || (!quoted && self.null == NullRepresentation::Empty)
+        } else {
+            !quoted && text.trim() == self.null.token()
+        };
+        (!is_null).then(|| text.to_string())
+    }
+
+    /// Whether a value written unquoted would read back as NULL
+    fn reads_as_null(&self, value: &str) -> bool {
+        match &self.null {
+            NullRepresentation::Empty => value.trim().is_empty(),
+            NullRepresentation::Token(token) => value.trim() == token,
+        }
+    }
+
+    /// Normalize a numeric cell to the C locale so `FromStr` can parse it
+    fn normalize_number(&self, text: &str) -> String {
+        text.trim()
+            .chars()
+            .filter(|c| Some(*c) != self.thousands_separator)
+            .map(|c| if c == self.decimal_separator { '.' } else { c })
+            .collect()
+    }
+
+    fn parse_number<N: FromStr>(&self, text: &str) -> Option<N> {
+        self.normalize_number(text).parse::<N>().ok()
+    }
+
+    fn parse_bool(&self, text: &str) -> Option<bool> {
+        let text = text.trim();
+        if self
+            .true_values
+            .iter()
+            .any(|v| v.eq_ignore_ascii_case(text))
+        {
+            Some(true)
+        } else if self
+            .false_values
+            .iter()
+            .any(|v| v.eq_ignore_ascii_case(text))
+        {
+            Some(false)
+        } else {
+            None
+        }
+    }
+}
+
+/// Parse an ISO-8601 timestamp; values without an offset are taken as UTC
+pub fn parse_timestamp(text: &str) -> Option<DateTime<Utc>> {
+    let text = text.trim();
+
+    if let Ok(dt) = DateTime::parse_from_rfc3339(text) {
+        return Some(dt.with_timezone(&Utc));
+    }
+    for fmt in ["%Y-%m-%d %H:%M:%S%.f%:z", "%Y-%m-%d %H:%M:%S%.f%#z"] {
+        if let Ok(dt) = DateTime::parse_from_str(text, fmt) {
+            return Some(dt.with_timezone(&Utc));
+        }
+    }
+    for fmt in ["%Y-%m-%dT%H:%M:%S%.f", "%Y-%m-%d %H:%M:%S%.f"] {
+        if let Ok(naive) = NaiveDateTime::parse_from_str(text, fmt) {
+            return Some(naive.and_utc());
+        }
+    }
+    NaiveDate::parse_from_str(text, "%Y-%m-%d")
+        .ok()
+        .and_then(|d| d.and_hms_opt(0, 0, 0))
+        .map(|naive| naive.and_utc())
+}
+
+/// Parse CSV text into a batch matching `schema`
+///
+/// With a header, columns are matched by name and schema columns absent from
+/// the header are filled with NULL. Without one, columns are positional and
+/// rows are checked with `check_row_widths`.
+pub fn coerce_csv(
+    csv_text: &str,
+    schema: &SchemaRef,
+    policy: &CoercionPolicy,
+    has_header: bool,
+) -> Result<RecordBatch> {
+    let csv_text = mark_quoted_fields(csv_text);
+    let mut reader = csv::ReaderBuilder::new()
+        .has_headers(has_header)
+        .flexible(true)
+        .from_reader(csv_text.as_bytes());
+
+    // Map each schema field to its position in the CSV record
+    let positions = if has_header {
+        let headers: csv::StringRecord = reader
+            .headers()
+            .map_err(|e| Error::InvalidOperation(format!("Invalid CSV header: {}", e)))?
+            .iter()
+            .map(|name| name.strip_prefix(QUOTED).unwrap_or(name))
+            .collect();
+        header_positions(&headers, schema)?
+    } else {
+        (0..schema.fields().len()).map(Some).collect()
+    };
+
+    let records = reader
+        .records()
+        .collect::<std::result::Result<Vec<_>, _>>()
+        .map_err(|e| Error::InvalidOperation(format!("Invalid CSV: {}", e)))?;
+    if !has_header {
+        check_row_widths(&records, schema)?;
+    }
+
+    coerce_records(&records, &positions, schema, policy)
+}
+
+/// Prefix the content of every quoted field with `QUOTED`
+///
+/// The CSV parser drops the quotes, which would make `""` and an empty field
+/// the same; the marker survives parsing and `CoercionPolicy::cell_value`
+/// strips it.
+fn mark_quoted_fields(csv_text: &str) -> String {
+    let mut marked = String::with_capacity(csv_text.len());
+    let mut chars = csv_text.chars().peekable();
+    let (mut field_start, mut in_quotes) = (true, false);
+    while let Some(c) = chars.next() {
+        marked.push(c);
+        if in_quotes {
+            if c == '"' {
+                if chars.peek() == Some(&'"') {
+                    marked.push(chars.next().unwrap());
+                } else {
+                    in_quotes = false;
+                }
+            }
+            continue;
+        }
+        match c {
+            '"' if field_start => {
+                marked.push(QUOTED);
+                in_quotes = true;
+                field_start = false;
+            }
+            ',' | '\n' | '\r' => field_start = true,
+            _ => field_start = false,
+        }
+    }
+    marked
+}
+
+/// Fail with `Error::SchemaMismatch` if headerless rows don't fit `schema`
+///
+/// A row with more fields than there are columns would lose data. A shorter
+/// row is allowed when the columns it leaves out can be NULL or have a default.
+pub fn check_row_widths(records: &[csv::StringRecord], schema: &SchemaRef) -> Result<()> {
+    let columns = schema.fields().len();
+    for (i, record) in records.iter().enumerate() {
+        if record.iter().all(|c| c.trim().is_empty()) {
+            continue;
+        }
+        if record.len() > columns {
+            return Err(Error::SchemaMismatch(format!(
+                "row {} has {} fields but the table has {} columns",
+                i + 1,
+                record.len(),
+                columns
+            )));
+        }
+        let required = schema.fields()[record.len()..]
+            .iter()
+            .find(|f| !f.is_nullable() && crate::metadata::default_expr(f).is_none());
+        if let Some(field) = required {
+            return Err(Error::SchemaMismatch(format!(
+                "row {} has {} fields and leaves out required column '{}'",
+                i + 1,
+                record.len(),
+                field.name()
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// Header line of `csv_text`
+pub fn csv_header(csv_text: &str) -> Result<csv::StringRecord> {
+    csv::ReaderBuilder::new()
+        .flexible(true)
+        .from_reader(csv_text.as_bytes())
+        .headers()
+        .cloned()
+        .map_err(|e| Error::InvalidOperation(format!("Invalid CSV: {}", e)))
+}
+
+/// Schema to parse a file written over the whole table with
+///
+/// Columns are matched by name, so they may come in any order, but all of
+/// them must be there: leaving one out would overwrite its values with NULL.
+/// Columns the table doesn't have are rejected unless the policy adds new
+/// columns, in which case they're appended as nullable strings.
+pub fn overwrite_schema(
+    headers: &csv::StringRecord,
+    schema: &SchemaRef,
+    policy: &CoercionPolicy,
+) -> Result<SchemaRef> {
+    let names: Vec<&str> = headers.iter().map(str::trim).collect();
+    let missing: Vec<&str> = schema
+        .fields()
+        .iter()
+        .map(|f| f.name().as_str())
+        .filter(|name| !names.contains(name))
+        .collect();
+    let unknown: Vec<&str> = names
+        .iter()
+        .copied()
+        .filter(|name| schema.field_with_name(name).is_err())
+        .collect();
+
+    if !missing.is_empty() {
+        return Err(Error::SchemaMismatch(format!(
+            "missing columns {}",
+            missing.join(", ")
+        )));
+    }
+    if unknown.is_empty() {
+        return Ok(schema.clone());
+    }
+    if !policy.add_new_columns {
+        return Err(Error::SchemaMismatch(format!(
+            "unknown columns {}",
+            unknown.join(", ")
+        )));
+    }
+    let mut fields: Vec<Field> = schema.fields().iter().map(|f| f.as_ref().clone()).collect();
+    fields.extend(
+        unknown
+            .iter()
+            .map(|name| Field::new(*name, DataType::Utf8, true)),
+    );
+    Ok(Arc::new(arrow::datatypes::Schema::new_with_metadata(
+        fields,
+        schema.metadata().clone(),
+    )))
+}
+
+/// Schema for a new table holding `csv_text`, which must start with a header
+///
+/// Every column is nullable and takes the first of Int64, Float64, Boolean
+/// and Timestamp that all of its values parse as under `policy`. Columns with
+/// no values, or values of mixed types, are strings.
+pub fn infer_schema(csv_text: &str, policy: &CoercionPolicy) -> Result<SchemaRef> {
+    let csv_text = mark_quoted_fields(csv_text);
+    let mut reader = csv::ReaderBuilder::new()
+        .flexible(true)
+        .from_reader(csv_text.as_bytes());
+    let names: Vec<String> = reader
+        .headers()
+        .map_err(|e| Error::InvalidOperation(format!("Invalid CSV header: {}", e)))?
+        .iter()
+        .map(|name| name.strip_prefix(QUOTED).unwrap_or(name).trim().to_string())
+        .collect();
+    if names.iter().any(String::is_empty) {
+        return Err(Error::SchemaMismatch(
+            "every column needs a name in the header".to_string(),
+        ));
+    }
+    if let Some(name) = names
+        .iter()
+        .enumerate()
+        .find_map(|(i, name)| names[..i].contains(name).then_some(name))
+    {
+        return Err(Error::SchemaMismatch(format!(
+            "column '{}' appears twice in the header",
+            name
+        )));
+    }
+
+    let timestamp = DataType::Timestamp(TimeUnit::Microsecond, None);
+    let mut candidates = vec![
+        vec![
+            DataType::Int64,
+            DataType::Float64,
+            DataType::Boolean,
+            timestamp
+        ];
+        names.len()
+    ];
+    let mut has_values = vec![false; names.len()];
+    for record in reader.records() {
+        let record = record.map_err(|e| Error::InvalidOperation(format!("Invalid CSV: {}", e)))?;
+        for (i, cell) in record.iter().enumerate().take(names.len()) {
+            let Some(value) = policy.cell_value(cell, false) else {
+                continue;
+            };
+            has_values[i] = true;
+            candidates[i].retain(|data_type| match data_type {
+                DataType::Int64 => policy.parse_number::<i64>(&value).is_some(),
+                DataType::Float64 => policy.parse_number::<f64>(&value).is_some(),
+                DataType::Boolean => policy.parse_bool(&value).is_some(),
+                _ => parse_timestamp(&value).is_some(),
+            });
+        }
+    }
+
+    let fields: Vec<Field> = names
+        .iter()
+        .zip(candidates)
+        .zip(has_values)
+        .map(|((name, candidates), has_values)| {
+            let data_type = match candidates.into_iter().next() {
+                Some(data_type) if has_values => data_type,
+                _ => DataType::Utf8,
+            };
+            Field::new(name, data_type, true)
+        })
+        .collect();
+    Ok(Arc::new(arrow::datatypes::Schema::new(fields)))
+}
+
+/// Map each schema field to its column in a CSV header
+///
+/// Header columns that are not in the schema are rejected; schema fields
+/// missing from the header map to `None` and load as NULL.
+pub fn header_positions(
+    headers: &csv::StringRecord,
+    schema: &SchemaRef,
+) -> Result<Vec<Option<usize>>> {
+    for name in headers.iter() {
+        if schema.field_with_name(name.trim()).is_err() {
+            return Err(Error::SchemaMismatch(format!(
+                "unknown column '{}'",
+                name.trim()
+            )));
+        }
+    }
+    Ok(schema
+        .fields()
+        .iter()
+        .map(|f| headers.iter().position(|h| h.trim() == f.name()))
+        .collect())
+}
+
+/// Coerce parsed CSV records into a batch, using `positions` from `header_positions`
+///
+/// Blank records are skipped. Row numbers in errors are 1-based within `records`.
+pub fn coerce_records(
+    records: &[csv::StringRecord],
+    positions: &[Option<usize>],
+    schema: &SchemaRef,
+    policy: &CoercionPolicy,
+) -> Result<RecordBatch> {
+    let mut cells: Vec<Vec<Option<String>>> = vec![Vec::new(); schema.fields().len()];
+    let has_empty_value: Vec<bool> = schema
+        .fields()
+        .iter()
+        .map(|f| has_empty_value(f.data_type()))
+        .collect();
+    for record in records {
+        if record.iter().all(|c| c.trim().is_empty()) {
+            continue;
+        }
+        for (col, pos) in positions.iter().enumerate() {
+            let cell = pos
+                .and_then(|p| record.get(p))
+                .and_then(|c| policy.cell_value(c, has_empty_value[col]));
+            cells[col].push(cell);
+        }
+    }
+
+    let columns = schema
+        .fields()
+        .iter()
+        .zip(cells.iter())
+        .map(|(field, column)| coerce_column(field, column, policy))
+        .collect::<Result<Vec<_>>>()?;
+
+    Ok(RecordBatch::try_new(schema.clone(), columns)?)
+}
+
+fn has_empty_value(data_type: &DataType) -> bool {
+    matches!(
+        data_type,
+        DataType::Utf8 | DataType::LargeUtf8 | DataType::Binary | DataType::LargeBinary
+    )
+}
+
+/// Coerce one column of text cells into an Arrow array of the field's type
+fn coerce_column(
+    field: &Field,
+    cells: &[Option<String>],
+    policy: &CoercionPolicy,
+) -> Result<ArrayRef> {
+    match field.data_type() {
+        DataType::Int8 => {
+            build_primitive::<Int8Type>(field, cells, policy, |s| policy.parse_number(s))
+        }
+        DataType::Int16 => {
+            build_primitive::<Int16Type>(field, cells, policy, |s| policy.parse_number(s))
+        }
+        DataType::Int32 => {
+            build_primitive::<Int32Type>(field, cells, policy, |s| policy.parse_number(s))
+        }
+        DataType::Int64 => {
+            build_primitive::<Int64Type>(field, cells, policy, |s| policy.parse_number(s))
+        }
+        DataType::UInt8 => {
+            build_primitive::<UInt8Type>(field, cells, policy, |s| policy.parse_number(s))
+        }
+        DataType::UInt16 => {
+            build_primitive::<UInt16Type>(field, cells, policy, |s| policy.parse_number(s))
+        }
+        DataType::UInt32 => {
+            build_primitive::<UInt32Type>(field, cells, policy, |s| policy.parse_number(s))
+        }
+        DataType::UInt64 => {
+            build_primitive::<UInt64Type>(field, cells, policy, |s| policy.parse_number(s))
+        }
+        DataType::Float32 => {
+            build_primitive::<Float32Type>(field, cells, policy, |s| policy.parse_number(s))
+        }
+        DataType::Float64 => {
+            build_primitive::<Float64Type>(field, cells, policy, |s| policy.parse_number(s))
+        }
+        DataType::Date32 => build_primitive::<Date32Type>(field, cells, policy, |s| {
+            parse_timestamp(s).map(|dt| dt.timestamp().div_euclid(86_400) as i32)
+        }),
+        DataType::Timestamp(TimeUnit::Second, _) => {
+            build_primitive::<TimestampSecondType>(field, cells, policy, |s| {
+                parse_timestamp(s).map(|dt| dt.timestamp())
+            })
+        }
+        DataType::Timestamp(TimeUnit::Millisecond, _) => {
+            build_primitive::<TimestampMillisecondType>(field, cells, policy, |s| {
+                parse_timestamp(s).map(|dt| dt.timestamp_millis())
+            })
+        }
+        DataType::Timestamp(TimeUnit::Microsecond, _) => {
+            build_primitive::<TimestampMicrosecondType>(field, cells, policy, |s| {
+                parse_timestamp(s).map(|dt| dt.timestamp_micros())
+            })
+        }
+        DataType::Timestamp(TimeUnit::Nanosecond, _) => {
+            build_primitive::<TimestampNanosecondType>(field, cells, policy, |s| {
+                parse_timestamp(s).and_then(|dt| dt.timestamp_nanos_opt())
+            })
+        }
+        DataType::Boolean => {
+            let mut builder = BooleanBuilder::with_capacity(cells.len());
+            for (row, cell) in cells.iter().enumerate() {
+                match cell.as_deref().map(|s| (s, policy.parse_bool(s))) {
+                    Some((_, Some(v))) => builder.append_value(v),
+                    Some((text, None)) => {
+                        reject_cell(field, row, text, policy)?;
+                        builder.append_null();
+                    }
+                    None => {
+                        check_null(field, row)?;
+                        builder.append_null();
+                    }
+                }
+            }
+            Ok(Arc::new(builder.finish()))
+        }
+        DataType::Binary => build_binary::<i32>(field, cells, policy),
+        DataType::LargeBinary => build_binary::<i64>(field, cells, policy),
+        DataType::Utf8 => {
+            for (row, cell) in cells.iter().enumerate() {
+                if cell.is_none() {
+                    check_null(field, row)?;
+                }
+            }
+            Ok(Arc::new(StringArray::from(cells.to_vec())))
+        }
+        other => {
+            // Fall back to Arrow's string cast; a cell that was present but
+            // casts to NULL is the one that failed to parse
+            let strings = StringArray::from(cells.to_vec());
+            let options = arrow::compute::CastOptions {
+                safe: true,
+                ..Default::default()
+            };
+            let array = arrow::compute::cast_with_options(&strings, other, &options)?;
+            for (row, cell) in cells.iter().enumerate() {
+                match cell {
+                    None => check_null(field, row)?,
+                    Some(text) if array.is_null(row) => reject_cell(field, row, text, policy)?,
+                    Some(_) => {}
+                }
+            }
+            Ok(array)
+        }
+    }
+}
+
+/// Build a primitive array, parsing each present cell with `parse`
+fn build_primitive<T: ArrowPrimitiveType>(
+    field: &Field,
+    cells: &[Option<String>],
+    policy: &CoercionPolicy,
+    parse: impl Fn(&str) -> Option<T::Native>,
+) -> Result<ArrayRef> {
+    let mut builder =
+        PrimitiveBuilder::<T>::with_capacity(cells.len()).with_data_type(field.data_type().clone());
+    for (row, cell) in cells.iter().enumerate() {
+        match cell {
+            Some(text) => match parse(text) {
+                Some(value) => builder.append_value(value),
+                None => {
+                    reject_cell(field, row, text, policy)?;
+                    builder.append_null();
+                }
+            },
+            None => {
+                check_null(field, row)?;
+                builder.append_null();
+            }
+        }
+    }
+    Ok(Arc::new(builder.finish()))
+}
+
+/// Build a binary array, decoding each present cell with the policy's encoding
+fn build_binary<O: OffsetSizeTrait>(
+    field: &Field,
+    cells: &[Option<String>],
+    policy: &CoercionPolicy,
+) -> Result<ArrayRef> {
+    let mut builder = GenericBinaryBuilder::<O>::new();
+    for (row, cell) in cells.iter().enumerate() {
+        match cell {
+            Some(text) => match policy.binary_encoding.decode(text) {
+                Some(bytes) => builder.append_value(bytes),
+                None => {
+                    reject_cell(field, row, text, policy)?;
+                    builder.append_null();
+                }
+            },
+            None => {
+                check_null(field, row)?;
+                builder.append_null();
+            }
+        }
+    }
+    Ok(Arc::new(builder.finish()))
+}
+
+/// Replace binary columns with their text form, for writing out as CSV
+///
+/// Other columns are left as they are.
+pub fn encode_binary_columns(batch: &RecordBatch, encoding: BinaryEncoding) -> Result<RecordBatch> {
+    let schema = batch.schema();
+    if !schema
+        .fields()
+        .iter()
+        .any(|f| matches!(f.data_type(), DataType::Binary | DataType::LargeBinary))
+    {
+        return Ok(batch.clone());
+    }
+
+    let mut fields = Vec::with_capacity(schema.fields().len());
+    let mut columns = Vec::with_capacity(schema.fields().len());
+    for (field, column) in schema.fields().iter().zip(batch.columns()) {
+        let encoded: Option<StringArray> = match field.data_type() {
+            DataType::Binary => Some(
+                column
+                    .as_binary::<i32>()
+                    .iter()
+                    .map(|v| v.map(|bytes| encoding.encode(bytes)))
+                    .collect(),
+            ),
+            DataType::LargeBinary => Some(
+                column
+                    .as_binary::<i64>()
+                    .iter()
+                    .map(|v| v.map(|bytes| encoding.encode(bytes)))
+                    .collect(),
+            ),
+            _ => None,
+        };
+        match encoded {
+            Some(encoded) => {
+                fields.push(Field::new(
+                    field.name(),
+                    DataType::Utf8,
+                    field.is_nullable(),
+                ));
+                columns.push(Arc::new(encoded) as ArrayRef);
+            }
+            None => {
+                fields.push(field.as_ref().clone());
+                columns.push(column.clone());
+            }
+        }
+    }
+    let schema = arrow::datatypes::Schema::new_with_metadata(fields, schema.metadata().clone());
+    Ok(RecordBatch::try_new(Arc::new(schema), columns)?)
+}
+
+/// Write `batch` as CSV that `coerce_csv` reads back unchanged
+///
+/// Binary columns are encoded, NULL is written as the policy's null
+/// representation, and values that would read back as NULL are quoted.
+pub fn write_csv(batch: &RecordBatch, policy: &CoercionPolicy) -> Result<Vec<u8>> {
+    write_csv_with_header(batch, policy, true)
+}
+
+/// Like `write_csv`, without the header line
+///
+/// CSV written batch by batch this way, after a header from `write_csv` of
+/// an empty batch, is byte for byte the CSV of all the batches at once.
+pub fn write_csv_rows(batch: &RecordBatch, policy: &CoercionPolicy) -> Result<Vec<u8>> {
+    write_csv_with_header(batch, policy, false)
+}
+
+fn write_csv_with_header(
+    batch: &RecordBatch,
+    policy: &CoercionPolicy,
+    header: bool,
+) -> Result<Vec<u8>> {
+    let batch = encode_binary_columns(batch, policy.binary_encoding)?;
+    let (batch, marked) = mark_null_like_values(&batch, policy)?;
+
+    let mut buffer = Vec::new();
+    {
+        let mut writer = arrow::csv::WriterBuilder::new()
+            .with_header(header)
+            .with_null(policy.null.token().to_string())
+            .build(&mut buffer);
+        writer.write(&batch)?;
+    }
+    if !marked {
+        return Ok(buffer);
+    }
+    // The marked values have no characters the writer quotes, so the markers
+    // around them are the only ones in the output
+    let text = String::from_utf8(buffer)
+        .map_err(|e| Error::InvalidOperation(format!("CSV is not UTF-8: {}", e)))?;
+    Ok(text.replace(QUOTED, "\"").into_bytes())
+}
+
+/// Surround text values that `reads_as_null` with `QUOTED`
+///
+/// Returns whether any were. Nothing is marked if a value already contains
+/// the marker character.
+fn mark_null_like_values(
+    batch: &RecordBatch,
+    policy: &CoercionPolicy,
+) -> Result<(RecordBatch, bool)> {
+    let mut columns = Vec::with_capacity(batch.num_columns());
+    let mut marked = false;
+    for column in batch.columns() {
+        let quoted = match column.data_type() {
+            DataType::Utf8 => mark_strings::<i32>(column, policy),
+            DataType::LargeUtf8 => mark_strings::<i64>(column, policy),
+            _ => Ok(None),
+        };
+        match quoted {
+            Ok(Some(quoted)) => {
+                columns.push(quoted);
+                marked = true;
+            }
+            Ok(None) => columns.push(column.clone()),
+            Err(()) => return Ok((batch.clone(), false)),
+        }
+    }
+    Ok((RecordBatch::try_new(batch.schema(), columns)?, marked))
+}
+
+/// A string column with its NULL-like values marked, None if it has none,
+/// or Err if a value contains the marker
+fn mark_strings<O: OffsetSizeTrait>(
+    column: &ArrayRef,
+    policy: &CoercionPolicy,
+) -> std::result::Result<Option<ArrayRef>, ()> {
+    let strings = column.as_string::<O>();
+    if strings.iter().flatten().any(|v| v.contains(QUOTED)) {
+        return Err(());
+    }
+    if !strings.iter().flatten().any(|v| policy.reads_as_null(v)) {
+        return Ok(None);
+    }
+    let quoted: GenericStringArray<O> = strings
+        .iter()
+        .map(|v| {
+            v.map(|v| {
+                if policy.reads_as_null(v) {
+                    format!("{}{}{}", QUOTED, v, QUOTED)
+                } else {
+                    v.to_string()
+                }
+            })
+        })
+        .collect();
+    Ok(Some(Arc::new(quoted)))
+}
+
+/// Fail on an unparseable cell, unless lenient mode can null it out
+fn reject_cell(field: &Field, row: usize, text: &str, policy: &CoercionPolicy) -> Result<()> {
+    if policy.mode == CoercionMode::Lenient && field.is_nullable() {
+        return Ok(());
+    }
+    Err(Error::TypeCoercion {
+        row: row + 1,
+        column: field.name().to_string(),
+        message: format!("cannot parse '{}' as {}", text, field.data_type()),
+    })
+}
+
+/// Missing values are only allowed in nullable columns
+fn check_null(field: &Field, row: usize) -> Result<()> {
+    if field.is_nullable() {
+        return Ok(());
+    }
+    Err(Error::TypeCoercion {
+        row: row + 1,
+        column: field.name().to_string(),
+        message: "missing value for non-nullable column".to_string(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow::array::{
+        BinaryArray, BooleanArray, Float64Array, Int32Array, TimestampMicrosecondArray,
+    };
+    use arrow::datatypes::Schema;
+
+    fn test_schema() -> SchemaRef {
+        Arc::new(Schema::new(vec![
+            Field::new("id", DataType::Int32, false),
+            Field::new("score", DataType::Float64, true),
+            Field::new("active", DataType::Boolean, true),
+            Field::new(
+                "seen_at",
+                DataType::Timestamp(TimeUnit::Microsecond, None),
+                true,
+            ),
+        ]))
+    }
+
+    #[test]
+    fn test_coerce_typed_columns() {
+        let csv = "id,score,active,seen_at\n\
+                   1,1.5,true,2024-01-02T03:04:05Z\n\
+                   2,,no,2024-01-02 03:04:05\n";
+        let batch = coerce_csv(csv, &test_schema(), &CoercionPolicy::strict(), true).unwrap();
+
+        assert_eq!(batch.num_rows(), 2);
+        let ids = batch
+            .column(0)
+            .as_any()
+            .downcast_ref::<Int32Array>()
+            .unwrap();
+        assert_eq!(ids.values(), &[1, 2]);
+        let scores = batch
+            .column(1)
+            .as_any()
+            .downcast_ref::<Float64Array>()
+            .unwrap();
+        assert_eq!(scores.value(0), 1.5);
+        assert!(scores.is_null(1));
+        let active = batch
+            .column(2)
+            .as_any()
+            .downcast_ref::<BooleanArray>()
+            .unwrap();
+        assert!(active.value(0));
+        assert!(!active.value(1));
+        let seen = batch
+            .column(3)
+            .as_any()
+            .downcast_ref::<TimestampMicrosecondArray>()
+            .unwrap();
+        // With and without an offset resolve to the same UTC instant
+        assert_eq!(seen.value(0), seen.value(1));
+    }
+
+    #[test]
+    fn test_timestamp_offset_converted_to_utc() {
+        let a = parse_timestamp("2024-01-02T05:04:05+02:00").unwrap();
+        let b = parse_timestamp("2024-01-02T03:04:05").unwrap();
+        assert_eq!(a, b);
+        assert!(parse_timestamp("yesterday").is_none());
+    }
+
+    #[test]
+    fn test_strict_rejects_unparseable_cell() {
+        let csv = "1,1.5,true,\n2,abc,false,\n";
+        let err = coerce_csv(csv, &test_schema(), &CoercionPolicy::strict(), false).unwrap_err();
+
+        match err {
+            Error::TypeCoercion { row, column, .. } => {
+                assert_eq!(row, 2);
+                assert_eq!(column, "score");
+            }
+            other => panic!("expected TypeCoercion, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_lenient_nulls_unparseable_cell() {
+        let csv = "1,1.5,maybe,not-a-date\n2,abc,false,\n";
+        let batch = coerce_csv(csv, &test_schema(), &CoercionPolicy::lenient(), false).unwrap();
+
+        assert_eq!(batch.num_rows(), 2);
+        assert!(batch.column(1).is_null(1));
+        assert!(batch.column(2).is_null(0));
+        assert!(batch.column(3).is_null(0));
+    }
+
+    #[test]
+    fn test_lenient_still_rejects_non_nullable() {
+        let csv = "x,1.5,true,\n";
+        let err = coerce_csv(csv, &test_schema(), &CoercionPolicy::lenient(), false).unwrap_err();
+        assert!(matches!(err, Error::TypeCoercion { row: 1, .. }));
+    }
+
+    #[test]
+    fn test_locale_number_format() {
+        let policy = CoercionPolicy::strict().with_number_format(',', Some('.'));
+        let csv = "1,\"1.234,5\",true,\n";
+        let batch = coerce_csv(csv, &test_schema(), &policy, false).unwrap();
+
+        let scores = batch
+            .column(1)
+            .as_any()
+            .downcast_ref::<Float64Array>()
+            .unwrap();
+        assert_eq!(scores.value(0), 1234.5);
+    }
+
+    #[test]
+    fn test_headerless_row_widths() {
+        let schema = test_schema();
+        // Trailing nullable columns can be left out
+        assert!(coerce_csv("1,1.5\n", &schema, &CoercionPolicy::strict(), false).is_ok());
+
+        let err = coerce_csv(
+            "1,1.5,true,,extra\n",
+            &schema,
+            &CoercionPolicy::strict(),
+            false,
+        )
+        .unwrap_err();
+        assert!(matches!(err, Error::SchemaMismatch(_)), "{}", err);
+    }
+
+    #[test]
+    fn test_overwrite_header_matched_by_name() {
+        let schema = test_schema();
+        let header = |names: &[&str]| csv::StringRecord::from(names.to_vec());
+        let strict = CoercionPolicy::strict();
+
+        let reordered = header(&["seen_at", "active", "score", "id"]);
+        assert_eq!(
+            overwrite_schema(&reordered, &schema, &strict).unwrap(),
+            schema
+        );
+
+        let missing = header(&["id", "score"]);
+        assert!(matches!(
+            overwrite_schema(&missing, &schema, &strict),
+            Err(Error::SchemaMismatch(msg)) if msg.contains("active")
+        ));
+
+        let added = header(&["id", "score", "active", "seen_at", "note"]);
+        assert!(matches!(
+            overwrite_schema(&added, &schema, &strict),
+            Err(Error::SchemaMismatch(msg)) if msg.contains("note")
+        ));
+        let evolved =
+            overwrite_schema(&added, &schema, &strict.with_schema_evolution(true)).unwrap();
+        assert_eq!(evolved.fields().len(), 5);
+        assert_eq!(evolved.field(4).data_type(), &DataType::Utf8);
+        assert!(evolved.field(4).is_nullable());
+    }
+
+    #[test]
+    fn test_binary_cells_round_trip() {
+        let schema: SchemaRef = Arc::new(Schema::new(vec![
+            Field::new("id", DataType::Int32, false),
+            Field::new("payload", DataType::Binary, true),
+        ]));
+        let bytes = [0u8, 1, 0xfe, 0xff];
+
+        for encoding in [BinaryEncoding::Base64, BinaryEncoding::Hex] {
+            let policy = CoercionPolicy::strict().with_binary_encoding(encoding);
+            let csv = format!("1,{}\n2,\n", encoding.encode(&bytes));
+            let batch = coerce_csv(&csv, &schema, &policy, false).unwrap();
+
+            let payload = batch.column(1).as_binary::<i32>();
+            assert_eq!(payload.value(0), &bytes);
+            assert!(payload.is_null(1));
+
+            let encoded = encode_binary_columns(&batch, encoding).unwrap();
+            assert_eq!(encoded.schema().field(1).data_type(), &DataType::Utf8);
+            assert_eq!(
+                encoded.column(1).as_string::<i32>().value(0),
+                encoding.encode(&bytes)
+            );
+        }
+        assert_eq!(BinaryEncoding::Base64.encode(&bytes), "AAH+/w==");
+        assert_eq!(BinaryEncoding::Hex.encode(&bytes), "0001feff");
+        assert_eq!(BinaryEncoding::Hex.decode("0001FEFF"), Some(bytes.to_vec()));
+    }
+
+    #[test]
+    fn test_strict_rejects_bad_binary_cell() {
+        let schema: SchemaRef = Arc::new(Schema::new(vec![Field::new(
+            "payload",
+            DataType::Binary,
+            true,
+        )]));
+        let hex = CoercionPolicy::strict().with_binary_encoding(BinaryEncoding::Hex);
+        let err = coerce_csv("abc\n", &schema, &hex, false).unwrap_err();
+        assert!(matches!(err, Error::TypeCoercion { row: 1, .. }), "{}", err);
+        assert!(coerce_csv("not base64!\n", &schema, &CoercionPolicy::strict(), false).is_err());
+    }
+
+    #[test]
+    fn test_quoted_empty_field_is_empty_string() {
+        let schema: SchemaRef = Arc::new(Schema::new(vec![
+            Field::new("id", DataType::Int32, true),
+            Field::new("name", DataType::Utf8, true),
+        ]));
+        let csv = "id,name\n1,\"\"\n2,\n\"\",\"a,\"\"b\"\"\"\n";
+        let batch = coerce_csv(csv, &schema, &CoercionPolicy::strict(), true).unwrap();
+        let names = batch.column(1).as_string::<i32>();
+        assert_eq!(names.value(0), "");
+        assert!(names.is_null(1));
+        assert_eq!(names.value(2), "a,\"b\"");
+        // Numbers have no empty value, quoted or not
+        assert!(batch.column(0).is_null(2));
+
+        // With a token, empty fields are empty strings and only the token is NULL
+        let token = CoercionPolicy::strict()
+            .with_null_representation(NullRepresentation::Token("\\N".to_string()));
+        let csv = "id,name\n1,\n\\N,\\N\n3,\"\\N\"\n";
+        let batch = coerce_csv(csv, &schema, &token, true).unwrap();
+        let names = batch.column(1).as_string::<i32>();
+        assert_eq!(names.value(0), "");
+        assert!(batch.column(0).is_null(1));
+        assert!(names.is_null(1));
+        assert_eq!(names.value(2), "\\N");
+    }
+
+    #[test]
+    fn test_infer_schema_from_values() {
+        let csv = "id,price,active,at,name,empty\n\
+                   1,\"1,5\",yes,2024-01-01,alice,\n\
+                   2,3,no,2024-01-02 10:00:00,42,\n\
+                   3,,,,,\n";
+        let policy = CoercionPolicy::strict().with_number_format(',', None);
+        let schema = infer_schema(csv, &policy).unwrap();
+        let types: Vec<&DataType> = schema.fields().iter().map(|f| f.data_type()).collect();
+        assert_eq!(
+            types,
+            vec![
+                &DataType::Int64,
+                &DataType::Float64,
+                &DataType::Boolean,
+                &DataType::Timestamp(TimeUnit::Microsecond, None),
+                &DataType::Utf8,
+                &DataType::Utf8,
+            ]
+        );
+        assert!(schema.fields().iter().all(|f| f.is_nullable()));
+
+        // The inferred schema loads the same text back
+        let batch = coerce_csv(csv, &schema, &policy, true).unwrap();
+        assert_eq!(batch.num_rows(), 3);
+
+        assert!(matches!(
+            infer_schema("id,id\n1,2\n", &policy),
+            Err(Error::SchemaMismatch(_))
+        ));
+        assert!(matches!(
+            infer_schema("id,\n1,2\n", &policy),
+            Err(Error::SchemaMismatch(_))
+        ));
+    }
+
+    #[test]
+    fn test_empty_strings_and_nulls_round_trip() {
+        let schema: SchemaRef = Arc::new(Schema::new(vec![
+            Field::new("id", DataType::Int32, false),
+            Field::new("name", DataType::Utf8, true),
+            Field::new("note", DataType::Utf8, true),
+            Field::new("payload", DataType::Binary, true),
+            Field::new("score", DataType::Float64, true),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![
+                Arc::new(Int32Array::from(vec![1, 2, 3])) as ArrayRef,
+                Arc::new(StringArray::from(vec![Some(""), None, Some("\\N")])),
+                Arc::new(StringArray::from(vec![None, Some(" "), Some("x")])),
+                Arc::new(BinaryArray::from_opt_vec(vec![
+                    Some(b"".as_ref()),
+                    None,
+                    Some(b"\x01".as_ref()),
+                ])),
+                Arc::new(Float64Array::from(vec![None, Some(1.5), None])),
+            ],
+        )
+        .unwrap();
+
+        for null in [
+            NullRepresentation::Empty,
+            NullRepresentation::Token("\\N".to_string()),
+        ] {
+            let policy = CoercionPolicy::strict().with_null_representation(null);
+            let csv = String::from_utf8(write_csv(&batch, &policy).unwrap()).unwrap();
+            let read = coerce_csv(&csv, &schema, &policy, true).unwrap();
+            assert_eq!(read, batch, "{}", csv);
+        }
+
+        let token = CoercionPolicy::strict()
+            .with_null_representation(NullRepresentation::Token("\\N".to_string()));
+        let csv = String::from_utf8(write_csv(&batch, &token).unwrap()).unwrap();
+        assert_eq!(csv.lines().nth(3), Some("3,\"\\N\",x,AQ==,\\N"), "{}", csv);
+    }
+}
@@ -0,0 +1,235 @@
+//! Conflict handling for concurrent overwrites of data.csv
+//!
+//! An overwrite is applied as a diff against the table's current rows, so a
+//! client that read data.csv before someone else changed the table silently
+//! undoes those changes. The server remembers the table version and content
+//! each client last read, its base, and a `ConflictPolicy` decides what to do
+//! when an overwrite arrives from a client whose base is out of date.
+
+use crate::error::{Error, Result};
+use std::collections::HashMap;
+use tokio::sync::Mutex;
+
+/// What to do with an overwrite based on an out-of-date read
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ConflictPolicy {
+    /// Apply it as written, undoing changes made since the read
+    #[default]
+    LastWriterWins,
+    /// Fail it with `Error::TransactionConflict`
+    Reject,
+    /// Merge the writer's changes to its base onto the current rows,
+    /// failing only if both sides changed the same row
+    Merge,
+}
+
+/// data.csv as a client last read it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReadBase {
+    /// Table version the content was generated from
+    pub version: Option<i64>,
+    pub content: Vec<u8>,
+}
+
+/// Each client's base, shared by all filesystem handles of a server
+#[derive(Debug, Default)]
+pub struct ReadBases {
+    clients: Mutex<HashMap<u64, ReadBase>>,
+}
+
+impl ReadBases {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Remember what `client` has just read, or just written
+    pub async fn record(&self, client: u64, base: ReadBase) {
+        self.clients.lock().await.insert(client, base);
+    }
+
+    /// `client`'s base, if it has read data.csv since the server started
+    pub async fn get(&self, client: u64) -> Option<ReadBase> {
+        self.clients.lock().await.get(&client).cloned()
+    }
+}
+
+/// CSV rows keyed by their ID column, in file order
+struct Rows {
+    header: Vec<String>,
+    order: Vec<String>,
+    rows: HashMap<String, Vec<String>>,
+}
+
+impl Rows {
+    fn parse(csv_text: &str, id_column: &str) -> Result<Self> {
+        let mut reader = csv::ReaderBuilder::new()
+            .flexible(true)
+            .from_reader(csv_text.as_bytes());
+        let header: Vec<String> = reader
+            .headers()
+            .map_err(|e| Error::InvalidOperation(format!("Invalid CSV header: {}", e)))?
+            .iter()
+            .map(|name| name.trim().to_string())
+            .collect();
+        let id = header
+            .iter()
+            .position(|name| name == id_column)
+            .ok_or_else(|| {
+                Error::InvalidOperation(format!("ID column '{}' not found in CSV", id_column))
+            })?;
+
+        let mut order = Vec::new();
+        let mut rows = HashMap::new();
+        for record in reader.records() {
+            let record =
+                record.map_err(|e| Error::InvalidOperation(format!("Invalid CSV: {}", e)))?;
+            let fields: Vec<String> = record.iter().map(|f| f.trim().to_string()).collect();
+            if fields.iter().all(String::is_empty) {
+                continue;
+            }
+            let key = fields.get(id).cloned().unwrap_or_default();
+            if rows.insert(key.clone(), fields).is_none() {
+                order.push(key);
+            }
+        }
+        Ok(Self {
+            header,
+            order,
+            rows,
+        })
+    }
+
+    fn get(&self, key: &str) -> Option<&Vec<String>> {
+        self.rows.get(key)
+    }
+}
+
+/// Three-way merge of an overwrite of data.csv
+///
+/// `written` is the writer's edit of `base`; `current` is the table now. Rows
+/// are matched on `id_column`. Every row the writer changed, added or removed
+/// relative to `base` is applied to `current`, and everything else in
+/// `current` is kept. Fails with `Error::TransactionConflict` if a row the
+/// writer touched was also changed by someone else.
+pub fn merge_csv(base: &str, current: &str, written: &str, id_column: &str) -> Result<String> {
+    let base = Rows::parse(base, id_column)?;
+    let current = Rows::parse(current, id_column)?;
+    let written = Rows::parse(written, id_column)?;
+    if written.header != current.header || base.header != current.header {
+        return Err(Error::TransactionConflict(
+            "Columns of data.csv changed since it was read".to_string(),
+        ));
+    }
+    let conflict = |key: &str, what: &str| {
+        Err(Error::TransactionConflict(format!(
+            "Row {} = {} {} since data.csv was read",
+            id_column, key, what
+        )))
+    };
+
+    let mut merged: Vec<&Vec<String>> = Vec::new();
+    for key in &current.order {
+        let now = &current.rows[key];
+        match (base.get(key), written.get(key)) {
+            (Some(was), Some(new)) if new == was => merged.push(now),
+            (Some(was), Some(new)) => {
+                if now != was && now != new {
+                    return conflict(key, "was also changed");
+                }
+                merged.push(new);
+            }
+            (Some(was), None) => {
+                if now != was {
+                    return conflict(key, "was deleted here but changed");
+                }
+            }
+            (None, Some(new)) => {
+                if now != new {
+                    return conflict(key, "was inserted by both writers");
+                }
+                merged.push(now);
+            }
+            // Inserted by someone else
+            (None, None) => merged.push(now),
+        }
+    }
+    for key in &written.order {
+        if current.get(key).is_some() {
+            continue;
+        }
+        let new = &written.rows[key];
+        match base.get(key) {
+            None => merged.push(new),
+            // Deleted by someone else; fine unless the writer changed it
+            Some(was) if was == new => {}
+            Some(_) => return conflict(key, "was changed here but deleted"),
+        }
+    }
+
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    writer
+        .write_record(&current.header)
+        .and_then(|_| merged.iter().try_for_each(|row| writer.write_record(*row)))
+        .map_err(|e| Error::Other(format!("Failed to write merged CSV: {}", e)))?;
+    let bytes = writer
+        .into_inner()
+        .map_err(|e| Error::Other(format!("Failed to write merged CSV: {}", e)))?;
+    String::from_utf8(bytes).map_err(|e| Error::Other(e.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BASE: &str = "id,name\n1,Alice\n2,Bob\n3,Carol\n";
+
+    #[test]
+    fn test_disjoint_changes_merge() {
+        // Someone else updated Alice and added Dave; the writer updated Bob and deleted Carol
+        let current = "id,name\n1,ALICE\n2,Bob\n3,Carol\n4,Dave\n";
+        let written = "id,name\n1,Alice\n2,BOB\n5,Erin\n";
+
+        let merged = merge_csv(BASE, current, written, "id").unwrap();
+        assert_eq!(merged, "id,name\n1,ALICE\n2,BOB\n4,Dave\n5,Erin\n");
+    }
+
+    #[test]
+    fn test_same_row_changed_twice_conflicts() {
+        let current = "id,name\n1,ALICE\n2,Bob\n3,Carol\n";
+
+        let err =
+            merge_csv(BASE, current, "id,name\n1,Alicia\n2,Bob\n3,Carol\n", "id").unwrap_err();
+        assert!(matches!(err, Error::TransactionConflict(_)), "{}", err);
+        assert!(err.to_string().contains("id = 1"), "{}", err);
+
+        // Deleting a row someone else changed is a conflict too
+        let err = merge_csv(BASE, current, "id,name\n2,Bob\n3,Carol\n", "id").unwrap_err();
+        assert!(matches!(err, Error::TransactionConflict(_)), "{}", err);
+
+        // Making the same change on both sides is not
+        let merged = merge_csv(BASE, current, current, "id").unwrap();
+        assert_eq!(merged, current);
+    }
+
+    #[test]
+    fn test_row_deleted_by_someone_else() {
+        let current = "id,name\n1,Alice\n2,Bob\n";
+
+        let merged = merge_csv(BASE, current, BASE, "id").unwrap();
+        assert_eq!(merged, current);
+        assert!(merge_csv(BASE, current, "id,name\n3,Caroline\n", "id").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_bases_are_per_client() {
+        let bases = ReadBases::new();
+        let base = ReadBase {
+            version: Some(1),
+            content: BASE.as_bytes().to_vec(),
+        };
+        bases.record(1, base.clone()).await;
+
+        assert_eq!(bases.get(1).await, Some(base));
+        assert_eq!(bases.get(2).await, None);
+    }
+}
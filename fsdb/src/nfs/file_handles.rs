@@ -0,0 +1,208 @@
+//! Stable NFS file handles
+//!
+//! nfsserve's default handles carry the server's start time, so every handle
+//! a client holds goes stale when the server restarts. FSDB's handles are
+//! instead the export's identity, a hash of the table path and the export's
+//! root file ID, followed by the file ID. Fixed entries always have the same
+//! ID, and Parquet files get theirs from a mapping persisted in
+//! `_metadata/nfs_file_ids.json`, so a handle keeps resolving across
+//! restarts as long as its object exists.
+//!
+//! A handle for another export, or for an object that is gone, is
+//! `NFS3ERR_STALE`; one that isn't FSDB's format at all is
+//! `NFS3ERR_BADHANDLE`. Directories and files made by clients live only in
+//! memory, so their handles go stale on restart along with them.
+
+use crate::Result;
+use nfsserve::nfs::{fileid3, nfs_fh3, nfsstat3};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+/// Held while a table's mapping is read, extended and saved, so exports in
+/// one process don't hand out the same ID twice
+static MAPPING_LOCK: Mutex<()> = Mutex::new(());
+
+/// Identity of an export, the first half of each of its handles
+pub fn export_tag(base_path: &Path, root: fileid3) -> u64 {
+    let digest = md5::compute(format!("{}\0{}", base_path.display(), root));
+    u64::from_le_bytes(digest.0[..8].try_into().expect("8 bytes"))
+}
+
+/// Handle for `id` in the export tagged `tag`
+pub fn encode(tag: u64, id: fileid3) -> nfs_fh3 {
+    let mut data = Vec::with_capacity(16);
+    data.extend_from_slice(&tag.to_le_bytes());
+    data.extend_from_slice(&id.to_le_bytes());
+    nfs_fh3 { data }
+}
+
+/// File ID a handle refers to, if it's one of this export's
+pub fn decode(tag: u64, handle: &nfs_fh3) -> std::result::Result<fileid3, nfsstat3> {
+    if handle.data.len() != 16 {
+        return Err(nfsstat3::NFS3ERR_BADHANDLE);
+    }
+    let (handle_tag, id) = handle.data.split_at(8);
+    if u64::from_le_bytes(handle_tag.try_into().expect("8 bytes")) != tag {
+        return Err(nfsstat3::NFS3ERR_STALE);
+    }
+    Ok(u64::from_le_bytes(id.try_into().expect("8 bytes")))
+}
+
+/// Persisted offsets of Parquet files into the Parquet file ID range
+///
+/// Offsets rather than IDs are stored so exports with different ID bases
+/// share one mapping. Offsets of removed files aren't handed out again until
+/// the range runs out, so an old handle for a removed file is stale rather
+/// than naming whichever file got its ID next.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ParquetFileIds {
+    pub offsets: BTreeMap<String, u64>,
+    /// Lowest offset never assigned
+    pub next: u64,
+}
+
+impl ParquetFileIds {
+    /// Where a table's mapping is stored
+    pub fn path(base_path: &Path) -> PathBuf {
+        base_path.join("_metadata").join("nfs_file_ids.json")
+    }
+
+    /// Load a table's mapping, empty if none was saved yet
+    pub fn load(base_path: &Path) -> Result<Self> {
+        let path = Self::path(base_path);
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let json = std::fs::read_to_string(&path)?;
+        Ok(serde_json::from_str(&json)?)
+    }
+
+    /// Save atomically, replacing the previous mapping
+    pub fn save(&self, base_path: &Path) -> Result<()> {
+        let path = Self::path(base_path);
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let tmp_path = path.with_extension("tmp");
+        std::fs::write(&tmp_path, serde_json::to_string(self)?)?;
+        std::fs::rename(tmp_path, path)?;
+        Ok(())
+    }
+
+    /// Give each of `files` an offset below `capacity`, keeping the ones
+    /// they already have and forgetting files no longer present
+    ///
+    /// Returns whether anything changed. Files left over once every offset
+    /// is taken get none.
+    pub fn assign(&mut self, files: &[String], capacity: u64) -> bool {
+        let present: HashSet<&String> = files.iter().collect();
+        let before = self.offsets.len();
+        self.offsets.retain(|name, _| present.contains(name));
+        let mut changed = self.offsets.len() != before;
+
+        let mut missing: Vec<&String> = files
+            .iter()
+            .filter(|name| !self.offsets.contains_key(*name))
+            .collect();
+        missing.sort();
+        for name in missing {
+            let offset = if self.next < capacity {
+                self.next += 1;
+                self.next - 1
+            } else {
+                // Range used up: fall back to offsets freed by removed files
+                let used: HashSet<u64> = self.offsets.values().copied().collect();
+                match (0..capacity).find(|offset| !used.contains(offset)) {
+                    Some(offset) => offset,
+                    None => break,
+                }
+            };
+            self.offsets.insert(name.clone(), offset);
+            changed = true;
+        }
+        changed
+    }
+
+    /// Load the table's mapping, extend it to `files` and save it if it
+    /// changed
+    ///
+    /// A mapping that can't be saved, as on a read-only table directory, is
+    /// still returned; IDs are then only stable while the server runs.
+    pub fn refresh(base_path: &Path, files: &[String], capacity: u64) -> Self {
+        let _guard = MAPPING_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        let mut ids = Self::load(base_path).unwrap_or_else(|e| {
+            tracing::warn!("NFS: Ignoring unreadable file ID mapping: {}", e);
+            Self::default()
+        });
+        if ids.assign(files, capacity) {
+            if let Err(e) = ids.save(base_path) {
+                tracing::warn!("NFS: Failed to save file ID mapping: {}", e);
+            }
+        }
+        ids
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn names(names: &[&str]) -> Vec<String> {
+        names.iter().map(|n| n.to_string()).collect()
+    }
+
+    #[test]
+    fn test_handle_round_trip() {
+        let tag = export_tag(Path::new("/tables/orders"), 1);
+        let handle = encode(tag, 1234);
+        assert!(matches!(decode(tag, &handle), Ok(1234)));
+
+        // Another export's handle, and garbage
+        let other = export_tag(Path::new("/tables/orders"), 1 + (1 << 32));
+        assert_ne!(tag, other);
+        assert!(matches!(
+            decode(other, &handle),
+            Err(nfsstat3::NFS3ERR_STALE)
+        ));
+        assert!(matches!(
+            decode(tag, &nfs_fh3 { data: vec![1, 2] }),
+            Err(nfsstat3::NFS3ERR_BADHANDLE)
+        ));
+    }
+
+    #[test]
+    fn test_offsets_survive_changes() {
+        let mut ids = ParquetFileIds::default();
+        assert!(ids.assign(&names(&["b.parquet", "a.parquet"]), 10));
+        assert_eq!(ids.offsets["a.parquet"], 0);
+        assert_eq!(ids.offsets["b.parquet"], 1);
+        assert!(!ids.assign(&names(&["a.parquet", "b.parquet"]), 10));
+
+        // A removed file's offset isn't reused while the range lasts
+        assert!(ids.assign(&names(&["b.parquet", "c.parquet"]), 10));
+        assert_eq!(ids.offsets.get("a.parquet"), None);
+        assert_eq!(ids.offsets["b.parquet"], 1);
+        assert_eq!(ids.offsets["c.parquet"], 2);
+    }
+
+    #[test]
+    fn test_exhausted_range_reuses_freed_offsets() {
+        let mut ids = ParquetFileIds::default();
+        ids.assign(&names(&["a", "b"]), 2);
+        ids.assign(&names(&["b", "c", "d"]), 2);
+        assert_eq!(ids.offsets["c"], 0);
+        assert_eq!(ids.offsets.get("d"), None);
+    }
+
+    #[test]
+    fn test_mapping_persists() {
+        let temp_dir = TempDir::new().unwrap();
+        let first = ParquetFileIds::refresh(temp_dir.path(), &names(&["x", "y"]), 100);
+        let reloaded = ParquetFileIds::refresh(temp_dir.path(), &names(&["y", "x"]), 100);
+        assert_eq!(first, reloaded);
+        assert_eq!(ParquetFileIds::load(temp_dir.path()).unwrap(), first);
+    }
+}
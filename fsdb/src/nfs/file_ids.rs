@@ -0,0 +1,223 @@
+//! NFS file ID allocation
+//!
+//! Every object the NFS server exposes needs a stable `fileid3`. The fixed
+//! entries (root, `/data`, `data.csv`, `.stats.json`, `/tables`) get single IDs and everything
+//! else is allocated from a range. The ranges are configurable so FSDB can be composed
+//! into a larger NFS namespace without colliding with IDs owned by others.
+
+use crate::{Error, Result};
+use nfsserve::nfs::fileid3;
+use std::ops::Range;
+
+/// File ID assignment for an `FsdbFilesystem`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileIdLayout {
+    /// Root directory
+    pub root: fileid3,
+
+    /// `/data` directory
+    pub data_dir: fileid3,
+
+    /// `/data/data.csv`
+    pub data_csv: fileid3,
+
+    /// `/data/.stats.json`
+    pub stats_json: fileid3,
+
+    /// `/tables` directory
+    pub tables_dir: fileid3,
+
+    /// Parquet files of the Delta table listed under `/data`
+    pub parquet_files: Range<fileid3>,
+
+    /// Directories created by clients with MKDIR
+    pub created_dirs: Range<fileid3>,
+
+    /// Files created by clients with CREATE
+    pub created_files: Range<fileid3>,
+
+    /// Named tables' directories under `/tables` and the files in them
+    pub tables: Range<fileid3>,
+
+    /// Partition directories of a partitioned table and their `data.csv` files
+    pub partitions: Range<fileid3>,
+}
+
+impl Default for FileIdLayout {
+    fn default() -> Self {
+        Self {
+            root: 1,
+            data_dir: 2,
+            data_csv: 3,
+            stats_json: 4,
+            tables_dir: 5,
+            parquet_files: 100..1000,
+            created_dirs: 1000..2000,
+            created_files: 2000..(1 << 47),
+            tables: (1 << 47)..(1 << 48),
+            partitions: (1 << 48)..fileid3::MAX,
+        }
+    }
+}
+
+impl FileIdLayout {
+    /// Layout with every ID shifted up by `base`, for embedding behind other exports
+    pub fn with_base(base: fileid3) -> Result<Self> {
+        let default = Self::default();
+        let shift = |id: fileid3| {
+            id.checked_add(base).ok_or_else(|| {
+                Error::InvalidOperation(format!("File ID base {} overflows fileid3", base))
+            })
+        };
+        Ok(Self {
+            root: shift(default.root)?,
+            data_dir: shift(default.data_dir)?,
+            data_csv: shift(default.data_csv)?,
+            stats_json: shift(default.stats_json)?,
+            tables_dir: shift(default.tables_dir)?,
+            parquet_files: shift(default.parquet_files.start)?..shift(default.parquet_files.end)?,
+            created_dirs: shift(default.created_dirs.start)?..shift(default.created_dirs.end)?,
+            created_files: shift(default.created_files.start)?..shift(default.created_files.end)?,
+            tables: shift(default.tables.start)?..shift(default.tables.end)?,
+            // The open-ended range keeps running to the top
+            partitions: shift(default.partitions.start)?..fileid3::MAX,
+        })
+    }
+
+    /// Check that no two entries share an ID
+    ///
+    /// ID 0 is reserved because some NFS clients treat it as "no file".
+    pub fn validate(&self) -> Result<()> {
+        let entries = [
+            ("root", self.root..self.root.saturating_add(1)),
+            ("data_dir", self.data_dir..self.data_dir.saturating_add(1)),
+            ("data_csv", self.data_csv..self.data_csv.saturating_add(1)),
+            (
+                "stats_json",
+                self.stats_json..self.stats_json.saturating_add(1),
+            ),
+            (
+                "tables_dir",
+                self.tables_dir..self.tables_dir.saturating_add(1),
+            ),
+            ("parquet_files", self.parquet_files.clone()),
+            ("created_dirs", self.created_dirs.clone()),
+            ("created_files", self.created_files.clone()),
+            ("tables", self.tables.clone()),
+            ("partitions", self.partitions.clone()),
+        ];
+
+        for (name, range) in &entries {
+            if range.is_empty() {
+                return Err(Error::InvalidOperation(format!(
+                    "File ID range {} is empty ({:?})",
+                    name, range
+                )));
+            }
+            if range.start == 0 {
+                return Err(Error::InvalidOperation(format!(
+                    "File ID range {} includes reserved ID 0",
+                    name
+                )));
+            }
+        }
+
+        for (i, (name, range)) in entries.iter().enumerate() {
+            for (other_name, other) in &entries[i + 1..] {
+                if range.start < other.end && other.start < range.end {
+                    return Err(Error::InvalidOperation(format!(
+                        "File ID ranges overlap: {} {:?} and {} {:?}",
+                        name, range, other_name, other
+                    )));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// True if `id` belongs to the Parquet file range
+    pub fn is_parquet_file(&self, id: fileid3) -> bool {
+        self.parquet_files.contains(&id)
+    }
+
+    /// True if `id` belongs to the created directory range
+    pub fn is_created_dir(&self, id: fileid3) -> bool {
+        self.created_dirs.contains(&id)
+    }
+
+    /// True if `id` belongs to the created file range
+    pub fn is_created_file(&self, id: fileid3) -> bool {
+        self.created_files.contains(&id)
+    }
+
+    /// True if `id` belongs to the named table range
+    pub fn is_table(&self, id: fileid3) -> bool {
+        self.tables.contains(&id)
+    }
+
+    /// True if `id` belongs to the partition range
+    pub fn is_partition(&self, id: fileid3) -> bool {
+        self.partitions.contains(&id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_layout_is_valid() {
+        let layout = FileIdLayout::default();
+        assert!(layout.validate().is_ok());
+        assert!(layout.is_parquet_file(100));
+        assert!(!layout.is_parquet_file(1000));
+        assert!(layout.is_created_dir(1000));
+        assert!(layout.is_created_file(2000));
+        assert!(!layout.is_created_file(1 << 47));
+        assert!(layout.is_table(1 << 47));
+        assert!(!layout.is_table(1 << 48));
+        assert!(layout.is_partition(1 << 48));
+    }
+
+    #[test]
+    fn test_shifted_layout() {
+        let layout = FileIdLayout::with_base(1 << 32).unwrap();
+        assert!(layout.validate().is_ok());
+        assert_eq!(layout.root, (1 << 32) + 1);
+        assert_eq!(layout.parquet_files.start, (1 << 32) + 100);
+        assert!(FileIdLayout::with_base(fileid3::MAX).is_err());
+    }
+
+    #[test]
+    fn test_overlap_detected() {
+        let layout = FileIdLayout {
+            created_dirs: 500..1500,
+            ..Default::default()
+        };
+        let err = layout.validate().unwrap_err().to_string();
+        assert!(err.contains("parquet_files"), "{}", err);
+        assert!(err.contains("created_dirs"), "{}", err);
+
+        // A fixed ID inside a range is a collision too
+        let layout = FileIdLayout {
+            data_csv: 150,
+            ..Default::default()
+        };
+        assert!(layout.validate().is_err());
+    }
+
+    #[test]
+    fn test_empty_and_zero_rejected() {
+        let layout = FileIdLayout {
+            parquet_files: 100..100,
+            ..Default::default()
+        };
+        assert!(layout.validate().is_err());
+
+        let layout = FileIdLayout {
+            root: 0,
+            ..Default::default()
+        };
+        assert!(layout.validate().is_err());
+    }
+}
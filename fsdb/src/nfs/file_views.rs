@@ -1,12 +1,34 @@
 use crate::database_ops::DatabaseOps;
+use crate::delta_lake::PartitionValues;
 use crate::error::Result;
+use crate::nfs::cache::ChunkSpool;
+use crate::nfs::coercion::{
+    coerce_csv, csv_header, overwrite_schema, write_csv, write_csv_rows, CoercionPolicy,
+};
+use crate::security::{AuthContext, ColumnMasks};
 use arrow::array::RecordBatch;
-use arrow::csv::{ReaderBuilder as CsvReaderBuilder, Writer as CsvWriter};
+use arrow::csv::Writer as CsvWriter;
+use arrow::datatypes::Schema;
 use arrow::json::{ArrayWriter as JsonArrayWriter, LineDelimitedWriter as JsonLinesWriter};
+use bytes::Bytes;
+use futures::stream::{BoxStream, Stream, StreamExt, TryStreamExt};
 use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
 use tracing::{debug, error, info, warn};
 
+/// Version data.csv content is cached at: the table's version and the rows
+/// in the database's write buffer, which reads include
+///
+/// None when the table's log can't be listed locally, such as on S3.
+pub async fn csv_version(db: &DatabaseOps) -> Option<String> {
+    let version = crate::delta_lake::latest_version(db.base_path()).ok()?;
+    Some(format!(
+        "{}:{}",
+        version.map_or(-1, |v| v as i64),
+        db.buffered_rows().await
+    ))
+}
+
 /// CSV file view that generates CSV content from database query results
 /// With lazy loading: content is generated on-demand, not cached internally.
 /// Caching is handled by the NFS cache layer for better performance.
@@ -14,6 +36,16 @@ pub struct CsvFileView {
     db: Arc<DatabaseOps>,
     /// Optional: specific file to query (if None, query all data)
     file_path: Option<String>,
+    /// How written CSV text is coerced into the typed schema
+    coercion: CoercionPolicy,
+    /// Optional: partition to restrict reads and writes to
+    partition: Option<PartitionValues>,
+    /// Optional: columns to show, in order (if None, all columns)
+    columns: Option<Vec<String>>,
+    /// Optional: cast view to read rows through (if None, stored types)
+    cast_view: Option<String>,
+    /// Optional: user the rows are read for (if None, the database's user)
+    reader: Option<Arc<AuthContext>>,
 }
 
 impl CsvFileView {
@@ -23,6 +55,11 @@ impl CsvFileView {
         CsvFileView {
             db,
             file_path: None,
+            coercion: CoercionPolicy::default(),
+            partition: None,
+            columns: None,
+            cast_view: None,
+            reader: None,
         }
     }
 
@@ -35,68 +72,183 @@ impl CsvFileView {
         CsvFileView {
             db,
             file_path: Some(file_path),
+            coercion: CoercionPolicy::default(),
+            partition: None,
+            columns: None,
+            cast_view: None,
+            reader: None,
         }
     }
 
-    /// Generate CSV content from database query results (lazy loading - no internal cache)
-    /// Content is generated fresh on each call. Caching is handled by NFS cache layer.
-    pub async fn generate_csv(&self) -> Result<Vec<u8>> {
-        debug!("Generating CSV content from database (lazy loading)");
+    /// Create a new CSV file view for the rows of one partition
+    ///
+    /// Writes to it always append, into that partition.
+    pub fn new_for_partition(db: Arc<DatabaseOps>, partition: PartitionValues) -> Self {
+        debug!(
+            "Creating CSV file view for partition: {:?} (lazy loading)",
+            partition
+        );
+        CsvFileView {
+            db,
+            file_path: None,
+            coercion: CoercionPolicy::default(),
+            partition: Some(partition),
+            columns: None,
+            cast_view: None,
+            reader: None,
+        }
+    }
 
-        // Query data - either all data or specific file
-        let batches = if let Some(ref file_path) = self.file_path {
-            // Query specific Parquet file
-            debug!("Querying specific file: {}", file_path);
-            self.db.query_file(file_path).await?
-        } else {
-            // Query all data from the database
-            self.db.query("SELECT * FROM data").await?
-        };
+    /// Set the coercion policy applied by `apply_write`
+    pub fn with_coercion(mut self, policy: CoercionPolicy) -> Self {
+        self.coercion = policy;
+        self
+    }
 
-        if batches.is_empty() {
-            debug!("No data in database, returning empty CSV with headers only");
-            // If no data, create empty CSV with schema headers
-            let schema = self.db.schema();
-            let mut buffer = Vec::new();
-            {
-                let mut writer = CsvWriter::new(&mut buffer);
-
-                // Write just the headers by creating an empty batch
-                let empty_batch = RecordBatch::new_empty(schema);
-                writer.write(&empty_batch)?;
-            }
+    /// Show only `columns`, in that order
+    ///
+    /// Only those columns are read from the Parquet files. The view is then
+    /// read-only, since its rows can't be written back whole.
+    pub fn with_columns(mut self, columns: Vec<String>) -> Self {
+        self.columns = Some(columns);
+        self
+    }
 
-            return Ok(buffer);
-        }
+    /// Read rows through the cast view `name`, showing its types
+    ///
+    /// The view is then read-only, like the cast view itself.
+    pub fn with_cast_view(mut self, name: impl Into<String>) -> Self {
+        self.cast_view = Some(name.into());
+        self
+    }
 
-        // Rebuild each batch with canonical schema to handle metadata differences
-        let schema = self.db.schema();
-        let mut casted_batches = Vec::new();
-        for batch in &batches {
-            // Extract columns in schema order
-            let mut columns = Vec::new();
-            for field in schema.fields() {
-                let col = batch.column_by_name(field.name()).ok_or_else(|| {
-                    crate::error::Error::InvalidOperation(format!(
-                        "Missing column: {}",
-                        field.name()
-                    ))
-                })?;
-                columns.push(col.clone());
-            }
-            // Create new batch with canonical schema
-            let casted = RecordBatch::try_new(schema.clone(), columns)?;
-            casted_batches.push(casted);
+    /// Read rows for `reader` rather than the database's user
+    ///
+    /// Columns masked for `reader` are masked on top of those masked for the
+    /// database's user. Masked views only accept appends: an overwrite would
+    /// write the masked values back over the real ones.
+    pub fn with_reader(mut self, reader: Option<Arc<AuthContext>>) -> Self {
+        self.reader = reader;
+        self
+    }
+
+    /// Columns masked for the reader, beyond the database user's
+    fn reader_masks(&self) -> ColumnMasks {
+        self.reader
+            .as_ref()
+            .map(|reader| self.db.column_masks_as(reader))
+            .unwrap_or_default()
+    }
+
+    /// Whether the view shows any column masked
+    pub fn is_masked(&self) -> bool {
+        !self.reader_masks().is_empty() || !self.db.column_masks().is_empty()
+    }
+
+    /// Schema of the CSV: the table's, or its cast view's, narrowed to the
+    /// view's columns
+    async fn view_schema(&self) -> Result<Arc<Schema>> {
+        let mut schema = self.db.table_schema().await?;
+        if let Some(ref name) = self.cast_view {
+            schema = self.db.cast_view(name)?.schema(&schema)?;
         }
+        schema = self.db.column_masks().schema(&schema);
+        schema = self.reader_masks().schema(&schema);
+        let Some(ref columns) = self.columns else {
+            return Ok(schema);
+        };
+        let indices = columns
+            .iter()
+            .map(|name| {
+                schema.index_of(name).map_err(|_| {
+                    crate::error::Error::InvalidOperation(format!("No such column: {}", name))
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Ok(Arc::new(schema.project(&indices)?))
+    }
+
+    /// Stream the CSV content in chunks: the header, then one chunk per batch
+    ///
+    /// Rows are read and rendered a batch at a time as the stream is polled,
+    /// so memory stays bounded however large the table. Concatenated, the
+    /// chunks are exactly the content `generate_csv` returns. A view of one
+    /// Parquet file reads the file whole first.
+    pub async fn stream(&self) -> Result<impl Stream<Item = Result<Bytes>> + Send + 'static> {
+        let schema = self.view_schema().await?;
+        let select = match self.columns {
+            Some(_) => schema
+                .fields()
+                .iter()
+                .map(|f| format!("\"{}\"", f.name().replace('"', "\"\"")))
+                .collect::<Vec<_>>()
+                .join(", "),
+            None => "*".to_string(),
+        };
+
+        // Query data - either all data or specific file
+        let batches: BoxStream<'static, Result<RecordBatch>> =
+            if let Some(ref file_path) = self.file_path {
+                // Query specific Parquet file
+                debug!("Querying specific file: {}", file_path);
+                let batches = match self.columns {
+                    Some(_) => {
+                        let names: Vec<String> =
+                            schema.fields().iter().map(|f| f.name().clone()).collect();
+                        self.db.query_file_columns(file_path, &names).await?
+                    }
+                    None => self.db.query_file(file_path).await?,
+                };
+                let batches = match self.cast_view {
+                    Some(ref name) => {
+                        let view = self.db.cast_view(name)?;
+                        batches
+                            .iter()
+                            .map(|batch| view.cast_batch(batch))
+                            .collect::<Result<Vec<_>>>()?
+                    }
+                    None => batches,
+                };
+                futures::stream::iter(batches.into_iter().map(Ok)).boxed()
+            } else {
+                let table = self.cast_view.as_deref().unwrap_or("data");
+                let sql = match self.partition {
+                    Some(ref partition) => {
+                        let filter = crate::delta_lake::partitions::partition_filter(partition);
+                        format!("SELECT {} FROM {} WHERE {}", select, table, filter)
+                    }
+                    None => format!("SELECT {} FROM {}", select, table),
+                };
+                self.db
+                    .stream_sql(&sql)
+                    .await?
+                    .map_err(crate::error::Error::from)
+                    .boxed()
+            };
 
-        // Concatenate all casted batches
-        let unified_batch = arrow::compute::concat_batches(&schema, &casted_batches)?;
+        // The header comes from an empty batch, so it's there with no rows
+        let header = write_csv(&RecordBatch::new_empty(schema.clone()), &self.coercion)?;
+        let coercion = self.coercion.clone();
+        let masks = self.reader_masks();
+        let rows = batches
+            .map(move |batch| -> Result<Bytes> {
+                // NULL is spelled the way writes read it back
+                let batch = conform_batch(&masks.mask_batch(&batch?)?, &schema)?;
+                Ok(Bytes::from(write_csv_rows(&batch, &coercion)?))
+            })
+            .try_filter(|chunk| futures::future::ready(!chunk.is_empty()));
+        Ok(futures::stream::once(futures::future::ready(Ok(Bytes::from(header)))).chain(rows))
+    }
+
+    /// Generate CSV content from database query results (lazy loading - no internal cache)
+    /// Content is generated fresh on each call. Caching is handled by NFS cache layer.
+    pub async fn generate_csv(&self) -> Result<Vec<u8>> {
+        debug!("Generating CSV content from database (lazy loading)");
 
-        // Generate CSV from unified batch
         let mut buffer = Vec::new();
-        {
-            let mut writer = CsvWriter::new(&mut buffer);
-            writer.write(&unified_batch)?;
+        let mut chunks = std::pin::pin!(self.stream().await?);
+        while let Some(chunk) = chunks.try_next().await? {
+            buffer.extend_from_slice(&chunk);
         }
 
         debug!("Generated CSV content: {} bytes", buffer.len());
@@ -104,9 +256,10 @@ impl CsvFileView {
     }
 
     /// Get the size of the CSV content (generates on-demand)
+    ///
+    /// The content is streamed and counted, not held.
     pub async fn size(&self) -> Result<u64> {
-        let content = self.generate_csv().await?;
-        Ok(content.len() as u64)
+        CsvStreamReader::new(self.stream().await?).finish().await
     }
 
     /// Get the full CSV content (for NFS cache layer)
@@ -115,23 +268,19 @@ impl CsvFileView {
     }
 
     /// Read a portion of the CSV content at the given offset (lazy loading)
-    /// Content is generated fresh on each read. NFS cache layer handles caching.
+    /// Content is generated fresh on each read, up to the end of the range.
+    /// NFS cache layer handles caching.
     pub async fn read(&self, offset: u64, size: u32) -> Result<Vec<u8>> {
-        let content = self.generate_csv().await?;
-        let offset = offset as usize;
-        let size = size as usize;
-
-        if offset >= content.len() {
-            return Ok(Vec::new());
-        }
-
-        let end = std::cmp::min(offset + size, content.len());
-        Ok(content[offset..end].to_vec())
+        let mut reader = CsvStreamReader::new(self.stream().await?);
+        Ok(reader.read_at(offset, size as u64).await?.0)
     }
 
     /// Apply write operation: handles both appends and overwrites with row deletion
     /// Detects if rows were deleted by comparing old vs new CSV content
     ///
+    /// Written cells are coerced to the table schema using the view's
+    /// `CoercionPolicy`; in strict mode a bad cell fails with `Error::TypeCoercion`.
+    ///
     /// cached_current_csv: Optional pre-fetched CSV content from cache (performance optimization)
     pub async fn apply_write(
         &self,
@@ -141,6 +290,26 @@ impl CsvFileView {
         let _start_time = std::time::Instant::now();
         info!("Applying write: {} bytes", data.len());
 
+        if self.columns.is_some() {
+            return Err(crate::error::Error::InvalidOperation(
+                "Column subset views are read-only".to_string(),
+            ));
+        }
+        if self.cast_view.is_some() {
+            return Err(crate::error::Error::InvalidOperation(
+                "Cast views are read-only".to_string(),
+            ));
+        }
+
+        if let Some(ref partition) = self.partition {
+            return self.handle_partition_append(data, partition).await;
+        }
+        if Self::is_overwrite(data) && self.is_masked() {
+            return Err(crate::error::Error::PermissionDenied(
+                "masked columns can't be overwritten".to_string(),
+            ));
+        }
+
         let new_csv_str = String::from_utf8_lossy(data);
         debug!(
             "Write data preview: {}",
@@ -172,11 +341,7 @@ impl CsvFileView {
         );
 
         // Check if this looks like an overwrite (has header and potentially fewer rows)
-        let new_has_header = new_csv_str
-            .lines()
-            .next()
-            .map(|l| l.contains(',') && !l.chars().next().unwrap_or('0').is_numeric())
-            .unwrap_or(false);
+        let new_has_header = Self::is_overwrite(data);
 
         if new_has_header && data.len() < current_csv_bytes.len() {
             // Potential deletion detected - compare CSVs
@@ -197,72 +362,148 @@ impl CsvFileView {
         }
     }
 
-    /// Handle CSV append (original logic)
-    async fn handle_csv_append(&self, data: &[u8]) -> Result<()> {
-        let csv_str = String::from_utf8_lossy(data);
+    /// Whether written data replaces the file rather than appending to it
+    ///
+    /// An overwrite starts with a header line; appended rows don't.
+    pub(crate) fn is_overwrite(data: &[u8]) -> bool {
+        String::from_utf8_lossy(data)
+            .lines()
+            .next()
+            .map(|l| l.contains(',') && !l.chars().next().unwrap_or('0').is_numeric())
+            .unwrap_or(false)
+    }
 
-        // Parse CSV - we need to add headers to make it valid CSV
+    /// Column that identifies rows when an overwrite is diffed
+    ///
+    /// The first Int32/Int64 column, or the first column if there is none.
+    pub(crate) fn id_column(&self) -> String {
         let schema = self.db.schema();
-        let header_line = schema
+        schema
             .fields()
             .iter()
-            .map(|f| f.name().as_str())
-            .collect::<Vec<_>>()
-            .join(",");
-
-        // Combine header + new data
-        let full_csv = format!("{}\n{}", header_line, csv_str.trim());
-
-        // Parse CSV using Arrow CSV reader
-        let cursor = std::io::Cursor::new(full_csv.as_bytes());
-        let csv_reader = CsvReaderBuilder::new(schema.clone())
-            .with_header(true)
-            .build(cursor)?;
-
-        // Read all batches
-        let mut all_rows = Vec::new();
-        for batch_result in csv_reader {
-            let batch = batch_result?;
-            info!("Parsed batch with {} rows", batch.num_rows());
-            all_rows.push(batch);
-        }
+            .find(|f| {
+                matches!(
+                    f.data_type(),
+                    arrow::datatypes::DataType::Int32 | arrow::datatypes::DataType::Int64
+                )
+            })
+            .map(|f| f.name().clone())
+            .unwrap_or_else(|| schema.field(0).name().clone())
+    }
+
+    /// Handle CSV append (original logic)
+    async fn handle_csv_append(&self, data: &[u8]) -> Result<()> {
+        let csv_str = String::from_utf8_lossy(data);
 
-        if all_rows.is_empty() {
+        // Headerless rows are positional against the table schema
+        let schema = self.db.table_schema().await?;
+        self.db.size_limits().check_csv(
+            csv_str.trim(),
+            &schema,
+            false,
+            self.coercion.binary_encoding,
+        )?;
+        let batch = coerce_csv(csv_str.trim(), &schema, &self.coercion, false)?;
+        info!("Parsed batch with {} rows", batch.num_rows());
+
+        if batch.num_rows() == 0 {
             error!("No rows parsed from CSV write");
             return Ok(()); // Empty write is OK
         }
 
-        // Insert all rows into database
-        for batch in all_rows {
-            if batch.num_rows() > 0 {
-                info!("Inserting {} rows into database", batch.num_rows());
-                self.db.insert(batch).await?;
-            }
-        }
+        info!("Inserting {} rows into database", batch.num_rows());
+        self.db.insert(batch).await?;
 
         info!("Append committed");
         Ok(())
     }
 
+    /// Append rows written to a partition's data.csv
+    ///
+    /// The rows may leave out the partition columns, in which case they're
+    /// taken from the partition. A header is recognised when every name in the
+    /// first line is a column; headerless rows are positional, against either
+    /// all columns or just the ones that aren't partition columns.
+    async fn handle_partition_append(
+        &self,
+        data: &[u8],
+        partition: &PartitionValues,
+    ) -> Result<()> {
+        let csv_str = String::from_utf8_lossy(data);
+        let csv_text = csv_str.trim();
+        let schema = self.db.table_schema().await?;
+        let is_partition_column = |name: &str| partition.iter().any(|(c, _)| c == name);
+
+        let first = csv::ReaderBuilder::new()
+            .has_headers(false)
+            .flexible(true)
+            .from_reader(csv_text.as_bytes())
+            .records()
+            .next()
+            .transpose()
+            .map_err(|e| crate::error::Error::InvalidOperation(format!("Invalid CSV: {}", e)))?
+            .unwrap_or_default();
+        let has_header = !first.is_empty()
+            && first
+                .iter()
+                .all(|name| schema.field_with_name(name.trim()).is_ok());
+
+        // Partition columns the rows leave out mustn't be parsed as NULL
+        let parse_schema = if has_header {
+            let fields: Vec<_> = schema
+                .fields()
+                .iter()
+                .filter(|f| {
+                    !is_partition_column(f.name().as_str())
+                        || first.iter().any(|n| n.trim() == f.name())
+                })
+                .cloned()
+                .collect();
+            Arc::new(Schema::new(fields))
+        } else if first.len() == schema.fields().len() - partition.len() {
+            let fields: Vec<_> = schema
+                .fields()
+                .iter()
+                .filter(|f| !is_partition_column(f.name().as_str()))
+                .cloned()
+                .collect();
+            Arc::new(Schema::new(fields))
+        } else {
+            schema.clone()
+        };
+
+        self.db.size_limits().check_csv(
+            csv_text,
+            &parse_schema,
+            has_header,
+            self.coercion.binary_encoding,
+        )?;
+        let batch = coerce_csv(csv_text, &parse_schema, &self.coercion, has_header)?;
+        if batch.num_rows() == 0 {
+            return Ok(());
+        }
+
+        let partition: Vec<(&str, Option<&str>)> = partition
+            .iter()
+            .map(|(column, value)| (column.as_str(), value.as_deref()))
+            .collect();
+        info!(
+            "Inserting {} rows into partition {:?}",
+            batch.num_rows(),
+            partition
+        );
+        self.db.insert_into_partition(&partition, batch).await?;
+        Ok(())
+    }
+
     /// Handle CSV overwrite with deletion detection
     async fn handle_csv_overwrite(&self, old_csv: &str, new_csv: &str) -> Result<()> {
         let overwrite_start = std::time::Instant::now();
         info!("Processing CSV overwrite with UPDATE/DELETE/INSERT detection (MERGE)");
 
-        let schema = self.db.schema();
-
         // Find ID column (first Int32/Int64 field, or first field if none)
-        let id_column_name = schema
-            .fields()
-            .iter()
-            .find(|f| {
-                matches!(
-                    f.data_type(),
-                    arrow::datatypes::DataType::Int32 | arrow::datatypes::DataType::Int64
-                )
-            })
-            .map(|f| f.name().as_str())
-            .unwrap_or_else(|| schema.field(0).name().as_str());
+        let id_column = self.id_column();
+        let id_column_name = id_column.as_str();
 
         info!(
             "Using '{}' as ID column for row identification",
@@ -271,8 +512,22 @@ impl CsvFileView {
 
         // Parse both CSVs into RecordBatches
         let parse_start = std::time::Instant::now();
-        let old_batch = self.parse_csv_to_batch(old_csv)?;
-        let new_batch = self.parse_csv_to_batch(new_csv)?;
+        let schema = self.db.table_schema().await?;
+        // Old content is our own output, so it always parses with the default
+        // policy, apart from how binary columns and NULL were rendered
+        let old_policy = CoercionPolicy::default()
+            .with_binary_encoding(self.coercion.binary_encoding)
+            .with_null_representation(self.coercion.null.clone());
+        let old_batch = coerce_csv(old_csv, &schema, &old_policy, true)?;
+        // The new header must name every column, in any order
+        let new_schema = overwrite_schema(&csv_header(new_csv)?, &schema, &self.coercion)?;
+        self.db.size_limits().check_csv(
+            new_csv,
+            &new_schema,
+            true,
+            self.coercion.binary_encoding,
+        )?;
+        let new_batch = coerce_csv(new_csv, &new_schema, &self.coercion, true)?;
         let parse_duration = parse_start.elapsed();
         debug!("CSV parsing took: {:?}", parse_duration);
 
@@ -371,8 +626,8 @@ impl CsvFileView {
         if !delete_ids.is_empty() {
             let delete_where = format!("{} IN ({})", id_column_name, delete_ids.join(", "));
             debug!("Executing DELETE: {}", delete_where);
-            let deleted_count = self.db.delete_rows_where(&delete_where).await?;
-            info!("Deleted {} rows", deleted_count);
+            let deleted = self.db.delete_rows_where(&delete_where).await?;
+            info!("Deleted {} rows", deleted.rows_affected);
         }
 
         let merge_duration = merge_start.elapsed();
@@ -434,38 +689,6 @@ impl CsvFileView {
         Ok(ids)
     }
 
-    /// Parse CSV string into a RecordBatch
-    fn parse_csv_to_batch(&self, csv_content: &str) -> Result<RecordBatch> {
-        use std::io::Cursor;
-
-        let cursor = Cursor::new(csv_content.as_bytes());
-        let csv_reader = CsvReaderBuilder::new(self.db.schema())
-            .with_header(true)
-            .build(cursor)?;
-
-        // Read all batches and concatenate
-        let mut batches = Vec::new();
-        for batch_result in csv_reader {
-            let batch = batch_result?;
-            if batch.num_rows() > 0 {
-                batches.push(batch);
-            }
-        }
-
-        if batches.is_empty() {
-            // Return empty batch with schema
-            return Ok(RecordBatch::new_empty(self.db.schema()));
-        }
-
-        // Concatenate batches if multiple
-        if batches.len() == 1 {
-            Ok(batches.into_iter().next().unwrap())
-        } else {
-            arrow::compute::concat_batches(&self.db.schema(), &batches)
-                .map_err(crate::error::Error::Arrow)
-        }
-    }
-
     /// Build a HashMap of ID -> Row (as string representation) for comparison
     fn build_id_to_row_map(
         &self,
@@ -546,6 +769,17 @@ impl CsvFileView {
                             array.value(row_idx).to_string()
                         }
                     }
+                    DataType::Binary | DataType::LargeBinary => {
+                        if col.is_null(row_idx) {
+                            "NULL".to_string()
+                        } else {
+                            let bytes = match col.data_type() {
+                                DataType::Binary => col.as_binary::<i32>().value(row_idx),
+                                _ => col.as_binary::<i64>().value(row_idx),
+                            };
+                            crate::nfs::coercion::BinaryEncoding::Hex.encode(bytes)
+                        }
+                    }
                     _ => "UNSUPPORTED".to_string(),
                 };
 
@@ -664,6 +898,138 @@ impl CsvFileView {
     }
 }
 
+/// Rebuild `batch` with the view's schema, to handle metadata differences
+///
+/// Columns are taken in schema order. Files written before a column was
+/// added don't have it, so it reads as NULL.
+fn conform_batch(batch: &RecordBatch, schema: &Arc<Schema>) -> Result<RecordBatch> {
+    let mut columns = Vec::new();
+    for field in schema.fields() {
+        let col = match batch.column_by_name(field.name()) {
+            Some(col) => col.clone(),
+            None if field.is_nullable() => {
+                arrow::array::new_null_array(field.data_type(), batch.num_rows())
+            }
+            None => {
+                return Err(crate::error::Error::InvalidOperation(format!(
+                    "Missing column: {}",
+                    field.name()
+                )))
+            }
+        };
+        columns.push(col);
+    }
+    Ok(RecordBatch::try_new(schema.clone(), columns)?)
+}
+
+/// Reads byte ranges of a CSV stream, keeping its place between reads
+///
+/// The reader holds the chunk the last read ended in. A read starting in
+/// that chunk or after it continues the stream from there, so a file read
+/// front to back is generated once; a read starting before it needs a new
+/// reader. With a `ChunkSpool`, every chunk is also written to the cache's
+/// disk tier as it's produced, and the entry is finished when the stream
+/// ends.
+pub struct CsvStreamReader {
+    chunks: BoxStream<'static, Result<Bytes>>,
+    /// Offset of `current`'s first byte
+    position: u64,
+    current: Bytes,
+    done: bool,
+    spool: Option<ChunkSpool>,
+}
+
+impl CsvStreamReader {
+    pub fn new(chunks: impl Stream<Item = Result<Bytes>> + Send + 'static) -> Self {
+        Self {
+            chunks: chunks.boxed(),
+            position: 0,
+            current: Bytes::new(),
+            done: false,
+            spool: None,
+        }
+    }
+
+    /// Also write the stream's chunks into the cache through `spool`
+    ///
+    /// Only useful on a new reader, since chunks already read are skipped.
+    pub fn with_spool(mut self, spool: ChunkSpool) -> Self {
+        self.spool = Some(spool);
+        self
+    }
+
+    /// Lowest offset this reader can still read from
+    pub fn position(&self) -> u64 {
+        self.position
+    }
+
+    /// Read up to `count` bytes at `offset`, and whether they reach the end
+    ///
+    /// Fails if `offset` is before `position()`.
+    pub async fn read_at(&mut self, offset: u64, count: u64) -> Result<(Vec<u8>, bool)> {
+        if offset < self.position {
+            return Err(crate::error::Error::InvalidOperation(format!(
+                "CSV stream is past offset {} (at {})",
+                offset, self.position
+            )));
+        }
+        let end = offset.saturating_add(count);
+        let mut data = Vec::new();
+        let mut cursor = offset;
+        loop {
+            let current_end = self.position + self.current.len() as u64;
+            if cursor < current_end && cursor < end {
+                let from = (cursor - self.position) as usize;
+                let to = (end.min(current_end) - self.position) as usize;
+                data.extend_from_slice(&self.current[from..to]);
+                cursor = self.position + to as u64;
+            }
+            // Done once the range is read and known not to end the content
+            if cursor == end && cursor < current_end {
+                return Ok((data, false));
+            }
+            match self.next_chunk().await? {
+                Some(chunk) => {
+                    self.position = current_end;
+                    self.current = chunk;
+                }
+                None => return Ok((data, true)),
+            }
+        }
+    }
+
+    /// Read the rest of the stream, returning the content's total length
+    pub async fn finish(&mut self) -> Result<u64> {
+        while let Some(chunk) = self.next_chunk().await? {
+            self.position += self.current.len() as u64;
+            self.current = chunk;
+        }
+        Ok(self.position + self.current.len() as u64)
+    }
+
+    async fn next_chunk(&mut self) -> Result<Option<Bytes>> {
+        if self.done {
+            return Ok(None);
+        }
+        let Some(chunk) = self.chunks.try_next().await? else {
+            self.done = true;
+            if let Some(spool) = self.spool.take() {
+                if let Err(e) = spool.finish().await {
+                    warn!("Failed to cache CSV: {}", e);
+                }
+            }
+            return Ok(None);
+        };
+        if let Some(spool) = &mut self.spool {
+            if let Err(e) = spool.push(&chunk) {
+                warn!("Failed to cache CSV, reading on without: {}", e);
+                self.spool = None;
+            }
+        }
+        Ok(Some(chunk))
+    }
+}
+
 /// JSON file view that generates JSON or JSON Lines content from database query results
 pub struct JsonFileView {
     db: Arc<DatabaseOps>,
@@ -1004,10 +1370,106 @@ impl StatsFile {
     }
 }
 
+/// Virtual `/data/.stats.json` with each live Parquet file's statistics
+///
+/// Rendered from the Delta log on first read and kept until the table moves
+/// to another version. A file committed without statistics is still listed,
+/// with nulls where its numbers would be. So are masked columns, whose
+/// statistics would give their values away.
+pub struct FileStatsFile {
+    db: Arc<DatabaseOps>,
+    /// Content and the version it was rendered at
+    rendered: tokio::sync::Mutex<Option<(Option<u64>, Arc<Vec<u8>>)>>,
+}
+
+impl FileStatsFile {
+    pub fn new(db: Arc<DatabaseOps>) -> Self {
+        FileStatsFile {
+            db,
+            rendered: tokio::sync::Mutex::new(None),
+        }
+    }
+
+    /// JSON for the table's current version, read with `masks`
+    ///
+    /// Only the rendering without masks is kept.
+    pub async fn content(&self, masks: &ColumnMasks) -> Result<Arc<Vec<u8>>> {
+        let version = crate::delta_lake::latest_version(self.db.base_path())?;
+        if !masks.is_empty() {
+            return self.render(version, masks).await;
+        }
+        let mut rendered = self.rendered.lock().await;
+        if let Some((at, content)) = rendered.as_ref() {
+            if *at == version {
+                return Ok(content.clone());
+            }
+        }
+        let content = self.render(version, masks).await?;
+        *rendered = Some((version, content.clone()));
+        Ok(content)
+    }
+
+    async fn render(&self, version: Option<u64>, masks: &ColumnMasks) -> Result<Arc<Vec<u8>>> {
+        let schema = self.db.table_schema().await?;
+        let mut files = crate::delta_lake::get_file_statistics(self.db.base_path())?;
+        files.sort_by(|a, b| a.path.cmp(&b.path));
+        let stats = serde_json::json!({
+            "version": version,
+            "files": files
+                .iter()
+                .map(|file| file_stats_json(file, &schema, masks))
+                .collect::<Vec<_>>(),
+        });
+        let content = Arc::new(serde_json::to_vec_pretty(&stats)?);
+        debug!(
+            "Rendered .stats.json for version {:?}: {} bytes",
+            version,
+            content.len()
+        );
+        Ok(content)
+    }
+}
+
+/// One file's entry in `.stats.json`, with every column of `schema`, those
+/// in `masks` without statistics
+fn file_stats_json(
+    file: &crate::delta_lake::FileStats,
+    schema: &Schema,
+    masks: &ColumnMasks,
+) -> serde_json::Value {
+    let columns: serde_json::Map<String, serde_json::Value> = schema
+        .fields()
+        .iter()
+        .map(|field| {
+            let name = field.name();
+            if masks.mask_of(name).is_some() {
+                let column = serde_json::json!({
+                    "min": null,
+                    "max": null,
+                    "null_count": null,
+                });
+                return (name.clone(), column);
+            }
+            let column = serde_json::json!({
+                "min": file.min_values.get(name),
+                "max": file.max_values.get(name),
+                "null_count": file.null_counts.get(name),
+            });
+            (name.clone(), column)
+        })
+        .collect();
+    serde_json::json!({
+        "path": file.path,
+        "size_bytes": file.size_bytes,
+        "num_records": file.has_stats.then_some(file.num_records),
+        "columns": columns,
+    })
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use arrow::array::{Int32Array, StringArray};
+    use arrow::array::{Array, AsArray, Int32Array, StringArray};
     use arrow::datatypes::{DataType, Field, Schema};
     use std::sync::Arc;
     use tempfile::TempDir;
@@ -1043,4 +1505,189 @@ mod tests {
         assert!(csv_str.contains("1,Alice"));
         assert!(csv_str.contains("2,Bob"));
     }
+
+    #[tokio::test]
+    async fn test_csv_view_column_subset() {
+        let temp_dir = TempDir::new().unwrap();
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("id", DataType::Int32, false),
+            Field::new("name", DataType::Utf8, false),
+        ]));
+        let db = DatabaseOps::create(temp_dir.path().join("test_db"), schema.clone())
+            .await
+            .unwrap();
+        let batch = RecordBatch::try_new(
+            schema,
+            vec![
+                Arc::new(Int32Array::from(vec![1, 2])),
+                Arc::new(StringArray::from(vec!["Alice", "Bob"])),
+            ],
+        )
+        .unwrap();
+        db.insert(batch).await.unwrap();
+        let db = Arc::new(db);
+
+        let view = CsvFileView::new(db.clone()).with_columns(vec!["name".to_string()]);
+        let content = view.generate_csv().await.unwrap();
+        assert_eq!(String::from_utf8_lossy(&content), "name\nAlice\nBob\n");
+        assert!(matches!(
+            view.apply_write(b"3,Carol\n", None).await,
+            Err(crate::error::Error::InvalidOperation(_))
+        ));
+
+        // A single file's view reads just the listed columns, in that order
+        let file = std::fs::read_dir(db.base_path())
+            .unwrap()
+            .filter_map(|e| e.ok())
+            .map(|e| e.file_name().to_string_lossy().to_string())
+            .find(|name| name.ends_with(".parquet"))
+            .unwrap();
+        let view = CsvFileView::new_for_file(db.clone(), file)
+            .with_columns(vec!["name".to_string(), "id".to_string()]);
+        let content = view.generate_csv().await.unwrap();
+        assert_eq!(
+            String::from_utf8_lossy(&content),
+            "name,id\nAlice,1\nBob,2\n"
+        );
+
+        // A column the table doesn't have
+        let view = CsvFileView::new(db).with_columns(vec!["email".to_string()]);
+        assert!(view.generate_csv().await.is_err());
+    }
+
+    async fn create_typed_db(temp_dir: &TempDir) -> Arc<DatabaseOps> {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("id", DataType::Int32, false),
+            Field::new("score", DataType::Float64, true),
+        ]));
+        let db = DatabaseOps::create(temp_dir.path().join("test_db"), schema)
+            .await
+            .unwrap();
+        Arc::new(db)
+    }
+
+    #[tokio::test]
+    async fn test_apply_write_strict_rejects_bad_cell() {
+        let temp_dir = TempDir::new().unwrap();
+        let db = create_typed_db(&temp_dir).await;
+
+        let view = CsvFileView::new(db.clone()).with_coercion(CoercionPolicy::strict());
+        let err = view.apply_write(b"1,2.5\n2,abc\n", None).await.unwrap_err();
+
+        match err {
+            crate::error::Error::TypeCoercion { row, column, .. } => {
+                assert_eq!(row, 2);
+                assert_eq!(column, "score");
+            }
+            other => panic!("expected TypeCoercion, got {:?}", other),
+        }
+
+        // Nothing was written
+        let csv = String::from_utf8(view.generate_csv().await.unwrap()).unwrap();
+        assert_eq!(csv.lines().count(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_apply_write_lenient_nulls_bad_cell() {
+        let temp_dir = TempDir::new().unwrap();
+        let db = create_typed_db(&temp_dir).await;
+
+        let view = CsvFileView::new(db.clone()).with_coercion(CoercionPolicy::lenient());
+        view.apply_write(b"1,2.5\n2,abc\n", None).await.unwrap();
+
+        let batches = db
+            .query("SELECT score FROM data ORDER BY id")
+            .await
+            .unwrap();
+        let batch = arrow::compute::concat_batches(&batches[0].schema(), &batches).unwrap();
+        assert_eq!(batch.num_rows(), 2);
+        assert!(!batch.column(0).is_null(0));
+        assert!(batch.column(0).is_null(1));
+    }
+
+    #[tokio::test]
+    async fn test_csv_round_trip_keeps_empty_strings_apart_from_nulls() {
+        use crate::nfs::coercion::NullRepresentation;
+
+        let cases = [
+            (
+                NullRepresentation::Empty,
+                "id,name,note\n1,\"\",\n2,,\"\"\n",
+            ),
+            (
+                NullRepresentation::Token("\\N".to_string()),
+                "id,name,note\n1,,\\N\n2,\\N,\n",
+            ),
+        ];
+        for (null, expected) in cases {
+            let temp_dir = TempDir::new().unwrap();
+            let schema = Arc::new(Schema::new(vec![
+                Field::new("id", DataType::Int32, false),
+                Field::new("name", DataType::Utf8, true),
+                Field::new("note", DataType::Utf8, true),
+            ]));
+            let db = DatabaseOps::create(temp_dir.path().join("test_db"), schema.clone())
+                .await
+                .unwrap();
+            let batch = RecordBatch::try_new(
+                schema,
+                vec![
+                    Arc::new(Int32Array::from(vec![1, 2])),
+                    Arc::new(StringArray::from(vec![Some(""), None])),
+                    Arc::new(StringArray::from(vec![None, Some("")])),
+                ],
+            )
+            .unwrap();
+            db.insert(batch).await.unwrap();
+            let db = Arc::new(db);
+            let view = CsvFileView::new(db.clone())
+                .with_coercion(CoercionPolicy::strict().with_null_representation(null));
+
+            let csv = String::from_utf8(view.generate_csv().await.unwrap()).unwrap();
+            assert_eq!(csv, expected);
+
+            // Row 3 copies row 1; rows 1 and 2 read back unchanged
+            let row = csv.lines().nth(1).unwrap().replacen('1', "3", 1);
+            let rewritten = format!("{}{}\n", csv, row);
+            view.apply_write(rewritten.as_bytes(), Some(csv.into_bytes()))
+                .await
+                .unwrap();
+
+            let batches = db
+                .query("SELECT name, note FROM data ORDER BY id")
+                .await
+                .unwrap();
+            let batch = arrow::compute::concat_batches(&batches[0].schema(), &batches).unwrap();
+            let (names, notes) = (batch.column(0).as_string::<i32>(), batch.column(1));
+            assert_eq!(batch.num_rows(), 3);
+            assert_eq!(names.value(0), "");
+            assert!(notes.is_null(0));
+            assert!(names.is_null(1));
+            assert_eq!(notes.as_string::<i32>().value(1), "");
+            assert_eq!(names.value(2), "");
+            assert!(notes.is_null(2));
+        }
+    }
+
+    #[test]
+    fn test_file_without_stats_lists_nulls() {
+        let schema = Schema::new(vec![Field::new("id", DataType::Int32, false)]);
+        let file = crate::delta_lake::FileStats {
+            path: "part-0.parquet".to_string(),
+            size_bytes: 100,
+            min_values: HashMap::new(),
+            max_values: HashMap::new(),
+            null_counts: HashMap::new(),
+            num_records: 0,
+            has_stats: false,
+        };
+
+        let json = file_stats_json(&file, &schema);
+        assert_eq!(json["path"], "part-0.parquet");
+        assert!(json["num_records"].is_null());
+        assert_eq!(
+            json["columns"]["id"],
+            serde_json::json!({"min": null, "max": null, "null_count": null})
+        );
+    }
 }
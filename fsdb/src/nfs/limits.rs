@@ -0,0 +1,103 @@
+//! Filesystem limits reported through FSINFO and PATHCONF
+//!
+//! Clients size their reads and writes, and decide what they can ask of the
+//! mount, from these rather than assuming defaults:
+//!
+//! - Transfers: reads and writes of any size are served, so the preferred and
+//!   maximum sizes are both the filesystem's transfer size, 1 MiB unless set
+//!   with `FsdbFilesystem::with_transfer_size`
+//! - File size: the Delta log records file sizes as signed 64-bit integers,
+//!   which bounds Parquet files, and data.csv is rendered from them
+//! - Names: at most `NAME_MAX` bytes; longer ones are refused with
+//!   `NFS3ERR_NAMETOOLONG` rather than truncated
+//! - No hard links or symlinks, and SETATTR keeps a file's own timestamps
+//!
+//! A read-only mount reports the same limits; what it refuses is reported by
+//! ACCESS and the `ReadOnly` capability instead.
+
+use nfsserve::nfs::nfsstat3;
+
+/// Longest file or directory name, in bytes
+pub const NAME_MAX: u32 = 255;
+
+/// Largest file the mount can hold, in bytes
+pub const MAX_FILE_SIZE: u64 = i64::MAX as u64;
+
+/// Transfer size used unless the filesystem sets one
+pub const DEFAULT_TRANSFER_SIZE: u32 = 1024 * 1024;
+
+/// Multiple transfers should be sized in, and smallest transfer size
+pub const TRANSFER_MULTIPLE: u32 = 4096;
+
+/// FSINFO property bits (RFC 1813, section 3.3.19)
+pub const FSF3_LINK: u32 = 0x0001;
+pub const FSF3_SYMLINK: u32 = 0x0002;
+pub const FSF3_HOMOGENEOUS: u32 = 0x0008;
+pub const FSF3_CANSETTIME: u32 = 0x0010;
+
+/// FSINFO properties: PATHCONF holds everywhere, nothing more
+pub const FSINFO_PROPERTIES: u32 = FSF3_HOMOGENEOUS;
+
+/// What PATHCONF reports for any file on the mount
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PathConf {
+    /// Most hard links to a file
+    pub link_max: u32,
+    /// Longest name, in bytes
+    pub name_max: u32,
+    /// Whether longer names are refused rather than truncated
+    pub no_trunc: bool,
+    /// Whether only the superuser can change a file's owner
+    pub chown_restricted: bool,
+    /// Whether names are compared without case
+    pub case_insensitive: bool,
+    /// Whether names keep the case they were created with
+    pub case_preserving: bool,
+}
+
+impl Default for PathConf {
+    fn default() -> Self {
+        Self {
+            link_max: 1,
+            name_max: NAME_MAX,
+            no_trunc: true,
+            chown_restricted: true,
+            case_insensitive: false,
+            case_preserving: true,
+        }
+    }
+}
+
+/// Round a requested transfer size to a multiple of `TRANSFER_MULTIPLE`
+pub fn transfer_size(bytes: u32) -> u32 {
+    (bytes / TRANSFER_MULTIPLE).max(1) * TRANSFER_MULTIPLE
+}
+
+/// Refuse `name` if it's longer than `NAME_MAX`
+pub fn check_name(name: &[u8]) -> Result<(), nfsstat3> {
+    if name.len() > NAME_MAX as usize {
+        return Err(nfsstat3::NFS3ERR_NAMETOOLONG);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_transfer_size_rounding() {
+        assert_eq!(transfer_size(DEFAULT_TRANSFER_SIZE), DEFAULT_TRANSFER_SIZE);
+        assert_eq!(transfer_size(65_000), 61_440);
+        assert_eq!(transfer_size(0), TRANSFER_MULTIPLE);
+    }
+
+    #[test]
+    fn test_name_length() {
+        assert!(check_name(&[b'a'; 255]).is_ok());
+        assert!(matches!(
+            check_name(&[b'a'; 256]),
+            Err(nfsstat3::NFS3ERR_NAMETOOLONG)
+        ));
+    }
+}
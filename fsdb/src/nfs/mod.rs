@@ -1,21 +1,40 @@
 //! NFS server implementation for POSIX interface
-//! This module provides a pure Rust NFSv3 server that exposes FSDB as a filesystem
+//! This module provides a pure Rust NFSv3 server that exposes FSDB as a filesystem,
+//! and optionally an NFSv4.0/4.1 server of the same filesystem (see `v4`)
 //! No external drivers required - OS uses built-in NFS client
 
+pub mod access;
 pub mod attr_cache;
 pub mod cache;
+pub mod cache_warmer;
+pub mod coercion;
+pub mod conflict;
+pub mod file_handles;
+pub mod file_ids;
 pub mod file_views;
+pub mod limits;
 pub mod mmap_cache;
+pub mod partition_dirs;
+pub mod pending_writes;
 pub mod server;
+pub mod table_dirs;
+pub mod v4;
 pub mod write_buffer;
 
 use crate::database_ops::DatabaseOps;
 use crate::error::{Error, Result};
-use crate::nfs::cache::NfsCache;
+use crate::nfs::cache::{CacheConfig, NfsCache};
+use crate::nfs::cache_warmer::{CacheWarmer, WarmerConfig, WarmerHandle};
+use crate::nfs::file_ids::FileIdLayout;
+use crate::nfs::partition_dirs::PartitionDirs;
+use crate::nfs::pending_writes::PendingWrites;
 use crate::nfs::server::FsdbFilesystem;
+use crate::nfs::table_dirs::TableDirs;
+use crate::nfs::v4::Nfs4Listener;
 
 use nfsserve::tcp::{NFSTcp, NFSTcpListener};
 use std::collections::HashMap;
+use std::net::SocketAddr;
 use std::sync::Arc;
 use tokio::sync::mpsc;
 use tracing::{error, info};
@@ -36,28 +55,59 @@ pub struct NfsServer {
     ready: bool,
     /// Cache for NFS operations (shared with filesystem)
     cache: Arc<NfsCache>,
+    /// File IDs used by the exported filesystem
+    layout: FileIdLayout,
+    /// Background cache warmer, if enabled
+    warmer: Option<WarmerHandle>,
+    /// Write chunks awaiting assembly, shared with the filesystem
+    pending_writes: Arc<PendingWrites>,
+    /// Partition directory IDs, shared with the filesystem
+    partition_dirs: Arc<PartitionDirs>,
+    /// Named table IDs, shared with the filesystem
+    table_dirs: Arc<TableDirs>,
+    /// NFSv4 listener's address and shutdown signal, if enabled
+    nfs4: Option<(SocketAddr, mpsc::Sender<()>)>,
 }
 
 impl NfsServer {
     /// Create and start a new NFS server
     pub async fn new(db: Arc<DatabaseOps>, port: u16) -> Result<Self> {
+        Self::with_file_id_layout(db, port, FileIdLayout::default()).await
+    }
+
+    /// Create and start a new NFS server using a custom file ID layout
+    ///
+    /// Fails before binding the port if the layout's ID ranges overlap.
+    pub async fn with_file_id_layout(
+        db: Arc<DatabaseOps>,
+        port: u16,
+        layout: FileIdLayout,
+    ) -> Result<Self> {
+        layout.validate()?;
+
         info!(
             "Starting FSDB NFS server on port {} with caching enabled",
             port
         );
 
         // Create NFS cache (two-tier: moka in-memory + sled on-disk)
-        // Use a unique temporary directory for the cache (includes timestamp for uniqueness)
-        let cache_dir = std::env::temp_dir().join(format!(
-            "fsdb_nfs_cache_{}_{}",
-            port,
-            std::time::SystemTime::now()
-                .duration_since(std::time::UNIX_EPOCH)
-                .unwrap()
-                .as_nanos()
-        ));
-        let cache = Arc::new(NfsCache::new(&cache_dir).await?);
-        let fs = FsdbFilesystem::with_cache(db.clone(), cache.clone());
+        // The disk tier lives in a fresh directory under the spill directory,
+        // and is kept within the spill cap by eviction
+        let spill = db.spill();
+        let cache_dir = spill.create_dir(&format!("fsdb_nfs_cache_{}", port))?;
+        let mut cache_config = CacheConfig::default();
+        if let Some(limit) = spill.config().max_spill_bytes {
+            cache_config.max_disk_size = cache_config.max_disk_size.min(limit);
+        }
+        let cache = Arc::new(NfsCache::with_config(&cache_dir, cache_config).await?);
+        let pending_writes = Arc::new(PendingWrites::new());
+        let partition_dirs = Arc::new(PartitionDirs::new());
+        let table_dirs = Arc::new(TableDirs::new());
+        let fs = FsdbFilesystem::with_cache(db.clone(), cache.clone())
+            .with_file_id_layout(layout.clone())?
+            .with_pending_writes(pending_writes.clone())
+            .with_partition_dirs(partition_dirs.clone())
+            .with_table_dirs(table_dirs.clone());
 
         // Start the server in a background task
         let (shutdown_tx, mut shutdown_rx) = mpsc::channel(1);
@@ -109,6 +159,12 @@ impl NfsServer {
                     shutdown_tx: Some(shutdown_tx),
                     ready: true,
                     cache,
+                    layout,
+                    warmer: None,
+                    pending_writes,
+                    partition_dirs,
+                    table_dirs,
+                    nfs4: None,
                 })
             }
             Ok(None) => Err(Error::InvalidOperation(
@@ -120,6 +176,54 @@ impl NfsServer {
         }
     }
 
+    /// Keep data.csv warm in the cache after each commit
+    ///
+    /// Opt-in: without this, the CSV is regenerated on the first read after a change.
+    pub fn with_cache_warming(mut self, config: WarmerConfig) -> Self {
+        let warmer =
+            CacheWarmer::new(self.cache.clone(), config).with_table("data", self.db.clone());
+        self.warmer = Some(warmer.spawn());
+        self
+    }
+
+    /// Also serve the filesystem over NFSv4.0/4.1 on `port`
+    ///
+    /// Both versions share the cache, pending writes and file handles, so
+    /// clients of either see each other's changes. Port 0 picks a free port;
+    /// see `nfs4_addr`.
+    pub async fn with_nfs4(mut self, port: u16) -> Result<Self> {
+        let listener =
+            Nfs4Listener::bind(&format!("127.0.0.1:{}", port), self.filesystem()?).await?;
+        let addr = listener.local_addr()?;
+        let (shutdown_tx, mut shutdown_rx) = mpsc::channel(1);
+
+        tokio::spawn(async move {
+            tokio::select! {
+                result = listener.handle_forever() => {
+                    if let Err(e) = result {
+                        error!("NFSv4 server error: {:?}", e);
+                    }
+                }
+                _ = shutdown_rx.recv() => {
+                    info!("NFSv4 server received shutdown signal");
+                }
+            }
+        });
+
+        self.nfs4 = Some((addr, shutdown_tx));
+        Ok(self)
+    }
+
+    /// Address the NFSv4 server listens on, if enabled
+    pub fn nfs4_addr(&self) -> Option<SocketAddr> {
+        self.nfs4.as_ref().map(|(addr, _)| *addr)
+    }
+
+    /// Content cache shared with the filesystem
+    pub fn cache(&self) -> &Arc<NfsCache> {
+        &self.cache
+    }
+
     /// Check if server is ready
     pub async fn is_ready(&self) -> bool {
         self.ready
@@ -128,9 +232,15 @@ impl NfsServer {
     /// Shutdown the NFS server
     pub async fn shutdown(mut self) -> Result<()> {
         info!("Shutting down NFS server");
+        if let Some(warmer) = self.warmer.take() {
+            warmer.stop().await;
+        }
         if let Some(tx) = self.shutdown_tx.take() {
             tx.send(()).await.ok();
         }
+        if let Some((_, tx)) = self.nfs4.take() {
+            tx.send(()).await.ok();
+        }
         Ok(())
     }
 
@@ -150,15 +260,15 @@ impl NfsServer {
         }
 
         // Create temporary filesystem to query
-        let fs = FsdbFilesystem::new(self.db.clone());
+        let fs = self.filesystem()?;
         use nfsserve::vfs::NFSFileSystem;
 
         let dirid = if path == "/" {
-            1
+            self.layout.root
         } else if path == "/data" {
-            2
+            self.layout.data_dir
         } else {
-            return Err(Error::InvalidOperation(format!("Unknown path: {}", path)));
+            self.resolve(&fs, path).await?
         };
 
         let result = fs
@@ -243,11 +353,11 @@ impl NfsServer {
         }
 
         // Handle regular NFS files - use cached filesystem
-        let fs = FsdbFilesystem::with_cache(self.db.clone(), self.cache.clone());
+        let fs = self.filesystem()?;
         use nfsserve::vfs::NFSFileSystem;
 
         let fileid = if path == "/data/data.csv" {
-            3
+            self.layout.data_csv
         } else if path.starts_with("/data/") && path.ends_with(".parquet") {
             // Handle individual Parquet files
             fs.refresh_parquet_files().await.map_err(|e| {
@@ -277,7 +387,7 @@ impl NfsServer {
                 }
             }
         } else {
-            return Err(Error::InvalidOperation(format!("Unknown file: {}", path)));
+            self.resolve(&fs, path).await?
         };
 
         let (data, _eof) = fs
@@ -288,8 +398,11 @@ impl NfsServer {
         Ok(data)
     }
 
-    /// Write to file (for testing)
-    pub async fn write_file(&self, path: &str, _offset: u64, data: &[u8]) -> Result<()> {
+    /// Write to file at `offset` (for testing)
+    ///
+    /// Like an NFS WRITE, a chunk that ends mid-row stays pending until the
+    /// rest of the row arrives or `commit_file` is called.
+    pub async fn write_file(&self, path: &str, offset: u64, data: &[u8]) -> Result<()> {
         use crate::nfs::file_views::QueryFile;
 
         // Handle special .query file
@@ -307,30 +420,120 @@ impl NfsServer {
         }
 
         // Handle regular files - use cached filesystem
-        let fs = FsdbFilesystem::with_cache(self.db.clone(), self.cache.clone());
+        let fs = self.filesystem()?;
         use nfsserve::vfs::NFSFileSystem;
 
         let fileid = if path == "/data/data.csv" {
-            3
+            self.layout.data_csv
         } else {
-            return Err(Error::InvalidOperation(format!("Unknown file: {}", path)));
+            self.resolve(&fs, path).await?
         };
 
-        fs.write(fileid, 0, data)
+        fs.write(fileid, offset, data)
             .await
             .map_err(|e| Error::InvalidOperation(format!("write failed: {:?}", e)))?;
 
         Ok(())
     }
 
+    /// Commit pending writes to a file, as an NFS COMMIT would (for testing)
+    pub async fn commit_file(&self, path: &str) -> Result<()> {
+        let fs = self.filesystem()?;
+        let fileid = if path == "/data/data.csv" {
+            self.layout.data_csv
+        } else {
+            self.resolve(&fs, path).await?
+        };
+
+        fs.commit(fileid)
+            .await
+            .map_err(|e| Error::InvalidOperation(format!("commit failed: {:?}", e)))?;
+        Ok(())
+    }
+
+    /// Create a directory (for testing)
+    ///
+    /// Under `/data` of a partitioned table, `column=value` directories start
+    /// new partitions.
+    pub async fn mkdir(&self, path: &str) -> Result<()> {
+        let fs = self.filesystem()?;
+        use nfsserve::vfs::NFSFileSystem;
+
+        let (parent, name) = path
+            .rsplit_once('/')
+            .ok_or_else(|| Error::InvalidOperation(format!("Unknown path: {}", path)))?;
+        let dirid = if parent.is_empty() {
+            self.layout.root
+        } else {
+            self.resolve(&fs, parent).await?
+        };
+
+        fs.mkdir(dirid, &name.as_bytes().into())
+            .await
+            .map_err(|e| Error::InvalidOperation(format!("mkdir failed: {:?}", e)))?;
+        Ok(())
+    }
+
+    /// Create a file (for testing)
+    ///
+    /// In a directory under `/tables`, a `*.csv` file is a copy to load into
+    /// that table.
+    pub async fn create_file(&self, path: &str) -> Result<()> {
+        let fs = self.filesystem()?;
+        use nfsserve::vfs::NFSFileSystem;
+
+        let (parent, name) = path
+            .rsplit_once('/')
+            .ok_or_else(|| Error::InvalidOperation(format!("Unknown path: {}", path)))?;
+        let dirid = if parent.is_empty() {
+            self.layout.root
+        } else {
+            self.resolve(&fs, parent).await?
+        };
+
+        fs.create(dirid, &name.as_bytes().into(), Default::default())
+            .await
+            .map_err(|e| Error::InvalidOperation(format!("create failed: {:?}", e)))?;
+        Ok(())
+    }
+
+    /// Filesystem handle sharing this server's cache, pending writes and
+    /// partition and table IDs
+    fn filesystem(&self) -> Result<FsdbFilesystem> {
+        Ok(
+            FsdbFilesystem::with_cache(self.db.clone(), self.cache.clone())
+                .with_file_id_layout(self.layout.clone())?
+                .with_pending_writes(self.pending_writes.clone())
+                .with_partition_dirs(self.partition_dirs.clone())
+                .with_table_dirs(self.table_dirs.clone()),
+        )
+    }
+
+    /// File ID of `path`, looked up one component at a time from the root
+    async fn resolve(&self, fs: &FsdbFilesystem, path: &str) -> Result<nfsserve::nfs::fileid3> {
+        use nfsserve::vfs::NFSFileSystem;
+
+        let mut id = self.layout.root;
+        for name in path.split('/').filter(|name| !name.is_empty()) {
+            id = fs
+                .lookup(id, &name.as_bytes().into())
+                .await
+                .map_err(|_| Error::InvalidOperation(format!("Unknown path: {}", path)))?;
+        }
+        Ok(id)
+    }
+
     /// Remove file (for testing) - truncates table if data.csv is deleted
-    pub async fn remove_file(&self, path: &str) -> Result<()> {
+    ///
+    /// Returns the number of rows deleted.
+    pub async fn remove_file(&self, path: &str) -> Result<usize> {
         // Only support deletion of data.csv (truncate table operation)
         if path == "/data/data.csv" {
             info!("Removing {} - truncating table", path);
             // Delete all rows from the table using deletion vectors
-            self.db.delete_rows_where("1=1").await?;
-            Ok(())
+            let deleted = self.db.delete_rows_where("1=1").await?.rows_affected as usize;
+            info!("Truncated table: {} rows deleted", deleted);
+            Ok(deleted)
         } else {
             Err(Error::InvalidOperation(format!(
                 "File deletion not supported for: {}",
@@ -341,16 +544,16 @@ impl NfsServer {
 
     /// Get file attributes (for testing)
     pub async fn getattr(&self, path: &str) -> Result<FileAttributes> {
-        let fs = FsdbFilesystem::new(self.db.clone());
+        let fs = FsdbFilesystem::new(self.db.clone()).with_file_id_layout(self.layout.clone())?;
         use nfsserve::nfs::ftype3;
         use nfsserve::vfs::NFSFileSystem;
 
         let fileid = if path == "/" {
-            1
+            self.layout.root
         } else if path == "/data" {
-            2
+            self.layout.data_dir
         } else if path == "/data/data.csv" {
-            3
+            self.layout.data_csv
         } else if path.starts_with("/data/") && path.ends_with(".parquet") {
             // Handle individual Parquet files
             // First, populate the Parquet files cache
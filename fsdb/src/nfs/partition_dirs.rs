@@ -0,0 +1,153 @@
+//! Partition directories under `/data`
+//!
+//! A partitioned table is exported as one directory level per partition
+//! column, `/data/region=eu/year=2024/`, with a `data.csv` in each complete
+//! partition holding just that partition's rows. The directories are derived
+//! from the table's partitions plus any a client has made with MKDIR and not
+//! written to yet; this keeps their file IDs stable across handles.
+
+use crate::delta_lake::PartitionValues;
+use nfsserve::nfs::fileid3;
+use std::collections::{BTreeSet, HashMap};
+use std::ops::Range;
+use tokio::sync::Mutex;
+
+/// An object inside the partition tree
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum PartitionEntry {
+    /// Directory for the partition columns given so far
+    Dir(PartitionValues),
+    /// `data.csv` of a complete partition
+    Csv(PartitionValues),
+}
+
+impl PartitionEntry {
+    /// Partition values the entry is under
+    pub fn values(&self) -> &PartitionValues {
+        match self {
+            PartitionEntry::Dir(values) | PartitionEntry::Csv(values) => values,
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct Inner {
+    ids: HashMap<PartitionEntry, fileid3>,
+    entries: HashMap<fileid3, PartitionEntry>,
+    next_id: Option<fileid3>,
+    /// Directories made by clients, which may not have any rows yet
+    made: BTreeSet<PartitionValues>,
+}
+
+/// File IDs of partition directories, shared by all filesystem handles of a server
+#[derive(Debug, Default)]
+pub struct PartitionDirs {
+    inner: Mutex<Inner>,
+}
+
+impl PartitionDirs {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// File ID of `entry`, allocating one from `range` the first time
+    ///
+    /// Returns `None` once `range` is exhausted.
+    pub async fn id(&self, entry: &PartitionEntry, range: &Range<fileid3>) -> Option<fileid3> {
+        let mut inner = self.inner.lock().await;
+        if let Some(&id) = inner.ids.get(entry) {
+            return Some(id);
+        }
+        let id = inner.next_id.unwrap_or(range.start);
+        if !range.contains(&id) {
+            return None;
+        }
+        inner.next_id = Some(id + 1);
+        inner.ids.insert(entry.clone(), id);
+        inner.entries.insert(id, entry.clone());
+        Some(id)
+    }
+
+    /// Entry a file ID was allocated to
+    pub async fn entry(&self, id: fileid3) -> Option<PartitionEntry> {
+        self.inner.lock().await.entries.get(&id).cloned()
+    }
+
+    /// Record a directory made by a client
+    pub async fn make(&self, values: PartitionValues) {
+        self.inner.lock().await.made.insert(values);
+    }
+
+    /// Directories made by clients
+    pub async fn made(&self) -> Vec<PartitionValues> {
+        self.inner.lock().await.made.iter().cloned().collect()
+    }
+}
+
+/// Distinct directories one level below `parent`, sorted
+///
+/// `partitions` may hold complete partitions or directory prefixes.
+pub fn children(partitions: &[PartitionValues], parent: &PartitionValues) -> Vec<PartitionValues> {
+    let depth = parent.len() + 1;
+    partitions
+        .iter()
+        .filter(|values| values.len() >= depth && values.starts_with(parent))
+        .map(|values| values[..depth].to_vec())
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn values(pairs: &[(&str, &str)]) -> PartitionValues {
+        pairs
+            .iter()
+            .map(|(c, v)| (c.to_string(), Some(v.to_string())))
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_ids_are_stable_and_bounded() {
+        let dirs = PartitionDirs::new();
+        let eu = PartitionEntry::Dir(values(&[("region", "eu")]));
+        let csv = PartitionEntry::Csv(values(&[("region", "eu")]));
+
+        let id = dirs.id(&eu, &(10..12)).await.unwrap();
+        assert_eq!(dirs.id(&eu, &(10..12)).await, Some(id));
+        assert_eq!(dirs.id(&csv, &(10..12)).await, Some(id + 1));
+        assert_eq!(dirs.entry(id + 1).await, Some(csv));
+
+        let us = PartitionEntry::Dir(values(&[("region", "us")]));
+        assert_eq!(dirs.id(&us, &(10..12)).await, None);
+    }
+
+    #[test]
+    fn test_children() {
+        let partitions = vec![
+            values(&[("region", "eu"), ("year", "2023")]),
+            values(&[("region", "eu"), ("year", "2024")]),
+            values(&[("region", "us"), ("year", "2024")]),
+            values(&[("region", "apac")]),
+        ];
+
+        assert_eq!(
+            children(&partitions, &Vec::new()),
+            vec![
+                values(&[("region", "apac")]),
+                values(&[("region", "eu")]),
+                values(&[("region", "us")]),
+            ]
+        );
+        assert_eq!(
+            children(&partitions, &values(&[("region", "eu")])),
+            vec![
+                values(&[("region", "eu"), ("year", "2023")]),
+                values(&[("region", "eu"), ("year", "2024")]),
+            ]
+        );
+        assert!(children(&partitions, &values(&[("region", "apac")])).is_empty());
+    }
+}
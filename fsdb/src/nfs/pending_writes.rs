@@ -0,0 +1,204 @@
+//! Per-file accumulator for NFS WRITE chunks
+//!
+//! NFS clients split a large write into several WRITE calls at increasing
+//! offsets, and a chunk boundary usually falls in the middle of a CSV row.
+//! Chunks are assembled here by offset until they form one contiguous write
+//! ending on a row boundary, or until the client sends COMMIT, and only then
+//! handed to the CSV view as a single write.
+
+use crate::storage::compression::Compression;
+use nfsserve::nfs::fileid3;
+use std::collections::HashMap;
+use tokio::sync::Mutex;
+
+/// Bytes written to one file that haven't been applied yet
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PendingWrite {
+    /// File offset of `data[0]`
+    offset: u64,
+    data: Vec<u8>,
+    /// Written ranges as absolute `[start, end)` offsets, sorted and merged
+    ranges: Vec<(u64, u64)>,
+}
+
+impl PendingWrite {
+    /// File offset the assembled write starts at
+    pub fn offset(&self) -> u64 {
+        self.offset
+    }
+
+    /// Assembled bytes, zero-filled where nothing has been written yet
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    /// File offset just past the last written byte
+    pub fn end(&self) -> u64 {
+        self.offset + self.data.len() as u64
+    }
+
+    /// Whether every byte between `offset` and `end` has been written
+    pub fn is_contiguous(&self) -> bool {
+        self.ranges.len() == 1
+    }
+
+    /// Whether the write is complete enough to apply: no holes, and the last
+    /// row is terminated
+    ///
+    /// Compressed content only decompresses whole, and a newline byte in it
+    /// means nothing, so it waits for COMMIT.
+    pub fn is_complete(&self) -> bool {
+        self.is_contiguous()
+            && self.data.last() == Some(&b'\n')
+            && Compression::detect(&self.data) == Compression::None
+    }
+
+    fn add(&mut self, offset: u64, data: &[u8]) {
+        if data.is_empty() {
+            return;
+        }
+        if self.ranges.is_empty() {
+            self.offset = offset;
+        } else if offset < self.offset {
+            // A chunk arrived before the one that started the assembly
+            let shift = (self.offset - offset) as usize;
+            self.data.splice(0..0, std::iter::repeat_n(0, shift));
+            self.offset = offset;
+        }
+
+        let start = (offset - self.offset) as usize;
+        let end = start + data.len();
+        if self.data.len() < end {
+            self.data.resize(end, 0);
+        }
+        self.data[start..end].copy_from_slice(data);
+
+        self.ranges.push((offset, offset + data.len() as u64));
+        self.ranges.sort_unstable();
+        let mut merged: Vec<(u64, u64)> = Vec::with_capacity(self.ranges.len());
+        for (start, end) in self.ranges.drain(..) {
+            match merged.last_mut() {
+                Some(last) if start <= last.1 => last.1 = last.1.max(end),
+                _ => merged.push((start, end)),
+            }
+        }
+        self.ranges = merged;
+    }
+}
+
+/// Pending writes for every file, shared by all filesystem handles of a server
+#[derive(Debug, Default)]
+pub struct PendingWrites {
+    files: Mutex<HashMap<fileid3, PendingWrite>>,
+}
+
+impl PendingWrites {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a chunk to `id`'s pending write
+    ///
+    /// Returns the assembled write, removing it, once `ready` accepts it;
+    /// otherwise the chunk stays pending and `None` is returned.
+    pub async fn add(
+        &self,
+        id: fileid3,
+        offset: u64,
+        data: &[u8],
+        ready: impl FnOnce(&PendingWrite) -> bool,
+    ) -> Option<PendingWrite> {
+        let mut files = self.files.lock().await;
+        let pending = files.entry(id).or_default();
+        pending.add(offset, data);
+        if ready(pending) {
+            files.remove(&id)
+        } else {
+            None
+        }
+    }
+
+    /// Remove and return whatever is pending for `id`
+    pub async fn take(&self, id: fileid3) -> Option<PendingWrite> {
+        self.files.lock().await.remove(&id)
+    }
+
+    /// End offset of `id`'s pending write, if it has one
+    pub async fn end(&self, id: fileid3) -> Option<u64> {
+        self.files.lock().await.get(&id).map(PendingWrite::end)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_chunks_assemble_until_row_boundary() {
+        let pending = PendingWrites::new();
+
+        let first = pending
+            .add(7, 0, b"id,name\n1,Al", PendingWrite::is_complete)
+            .await;
+        assert!(first.is_none());
+        assert_eq!(pending.end(7).await, Some(12));
+
+        let write = pending
+            .add(7, 12, b"ice\n2,Bob\n", PendingWrite::is_complete)
+            .await
+            .unwrap();
+        assert_eq!(write.offset(), 0);
+        assert_eq!(write.data(), b"id,name\n1,Alice\n2,Bob\n");
+        assert!(pending.take(7).await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_out_of_order_chunks_wait_for_the_hole() {
+        let pending = PendingWrites::new();
+
+        // The last chunk arrives first and ends the row, but there's a hole before it
+        assert!(pending
+            .add(7, 100, b"ice\n", |w| w.is_complete() && w.offset() == 95)
+            .await
+            .is_none());
+        assert!(pending
+            .add(7, 95, b"3,Ali", |w| w.is_complete() && w.offset() == 95)
+            .await
+            .is_some());
+    }
+
+    #[tokio::test]
+    async fn test_take_returns_partial_write() {
+        let pending = PendingWrites::new();
+        pending
+            .add(7, 0, b"3,Carol", PendingWrite::is_complete)
+            .await;
+
+        let write = pending.take(7).await.unwrap();
+        assert!(write.is_contiguous());
+        assert!(!write.is_complete());
+        assert_eq!(write.data(), b"3,Carol");
+        assert!(pending.take(7).await.is_none());
+    }
+
+    #[test]
+    fn test_compressed_write_waits_for_commit() {
+        let mut write = PendingWrite::default();
+        // A gzip stream that happens to end a chunk on a newline byte
+        write.add(0, &[0x1f, 0x8b, 0x08, 0x00, b'\n']);
+        assert!(write.is_contiguous());
+        assert!(!write.is_complete());
+    }
+
+    #[test]
+    fn test_overlapping_chunks_merge() {
+        let mut write = PendingWrite::default();
+        write.add(10, b"aaaa");
+        write.add(20, b"cc");
+        assert!(!write.is_contiguous());
+        write.add(12, b"bbbbbbbb");
+        assert!(write.is_contiguous());
+        assert_eq!(write.data(), b"aabbbbbbbbcc");
+        assert_eq!(write.end(), 22);
+    }
+}
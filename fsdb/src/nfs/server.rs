@@ -2,19 +2,43 @@
 //! Exposes database as NFSv3 filesystem with CSV file views
 
 use crate::database_ops::DatabaseOps;
+use crate::delta_lake::partitions::{check_values, dir_name, parse_dir_name};
+use crate::delta_lake::{PartitionValues, WriteMode};
+use crate::nfs::access::{scratch_access, table_access, ForbiddenLookup, Grants};
 use crate::nfs::attr_cache::AttrCache;
-use crate::nfs::cache::NfsCache;
-use crate::nfs::file_views::CsvFileView;
+use crate::nfs::cache::{ChunkSpool, NfsCache};
+use crate::nfs::coercion::{coerce_csv, infer_schema, CoercionPolicy};
+use crate::nfs::conflict::{merge_csv, ConflictPolicy, ReadBase, ReadBases};
+use crate::nfs::file_handles::{self, ParquetFileIds};
+use crate::nfs::file_ids::FileIdLayout;
+use crate::nfs::file_views::{csv_version, CsvFileView, CsvStreamReader, FileStatsFile};
+use crate::nfs::limits::{self, PathConf};
+use crate::nfs::partition_dirs::{children, PartitionDirs, PartitionEntry};
+use crate::nfs::pending_writes::{PendingWrite, PendingWrites};
+use crate::nfs::table_dirs::{TableDirs, TableEntry, Upload};
+use crate::security::AuthContext;
+use crate::storage::compression::decompress;
 
 use async_trait::async_trait;
 use nfsserve::{
-    nfs::{fattr3, fileid3, filename3, ftype3, nfsstat3, nfstime3, sattr3, specdata3},
+    nfs::{
+        fattr3, fileid3, filename3, fsinfo3, ftype3, nfs_fh3, nfsstat3, nfstime3, post_op_attr,
+        sattr3, set_size3, specdata3,
+    },
     vfs::{DirEntry, NFSFileSystem, ReadDirResult, VFSCapabilities},
 };
 use std::collections::HashMap;
+use std::future::Future;
 use std::sync::Arc;
+use std::time::Duration;
 use tokio::sync::Mutex;
-use tracing::{debug, error, info};
+use tracing::{debug, error, info, instrument, warn};
+
+/// Table exported as the root's data directory
+const TABLE_NAME: &str = "data";
+
+/// Directory of the root the named tables are exported under
+const TABLES_DIR: &str = "tables";
 
 /// Metadata for created files including stable timestamps
 #[derive(Clone, Debug)]
@@ -26,16 +50,24 @@ struct FileMetadata {
     ctime: nfstime3,
 }
 
-// File ID constants
-const ROOT_ID: fileid3 = 1;
-const DATA_DIR_ID: fileid3 = 2;
-const DATA_CSV_ID: fileid3 = 3;
-const PARQUET_FILE_ID_START: fileid3 = 100;
-const CREATED_DIR_START: fileid3 = 1000;
-const CREATED_FILE_START: fileid3 = 2000;
-
 /// FSDB NFS Filesystem
 /// Maps database operations to NFS file operations
+///
+/// One `DatabaseOps` can back several filesystems at once, each with its own
+/// cache, coercion policy, auth context and read-only flag, such as a
+/// read-only export for analysts next to a read-write one for ingestion.
+/// They share the database's write buffer, transactions and locks, so a
+/// write through one export is visible to reads through the others as soon
+/// as it's committed; cached data.csv content and attributes are checked
+/// against the table's version before they're used. Pending writes, read
+/// bases, and partition and table directories belong to one export: share
+/// them with `with_pending_writes`, `with_read_bases`, `with_partition_dirs`
+/// and `with_table_dirs` only between handles on the same export.
+///
+/// The main table is exported as `/data`. Tables made with
+/// `DatabaseOps::create_table` are exported under `/tables`, each as a
+/// directory with its own data.csv and Parquet files. Copying a CSV file into
+/// a directory made there creates a table from it.
 pub struct FsdbFilesystem {
     db: Arc<DatabaseOps>,
     /// Cache of Parquet file IDs to paths
@@ -52,34 +84,323 @@ pub struct FsdbFilesystem {
     cache: Option<Arc<NfsCache>>,
     /// Attribute cache to prevent mount disconnections during concurrent writes
     attr_cache: Arc<AttrCache>,
+    /// Type coercion applied to CSV written to data.csv
+    coercion: CoercionPolicy,
+    /// File IDs assigned to fixed entries and allocated objects
+    layout: FileIdLayout,
+    /// Written chunks not yet applied, waiting for whole rows or COMMIT
+    pending_writes: Arc<PendingWrites>,
+    /// File IDs of partition directories
+    partition_dirs: Arc<PartitionDirs>,
+    /// File IDs of named tables' directories and files
+    table_dirs: Arc<TableDirs>,
+    /// Handles on named tables opened so far
+    table_handles: Arc<Mutex<HashMap<String, Arc<DatabaseOps>>>>,
+    /// Handling of overwrites based on an out-of-date read of data.csv
+    conflict_policy: ConflictPolicy,
+    /// What each client last read of data.csv
+    read_bases: Arc<ReadBases>,
+    /// Client the bases of this handle's reads and writes are kept for
+    client: u64,
+    /// Identity the mount is authenticated as, instead of the database's user
+    auth: Option<Arc<AuthContext>>,
+    /// What lookups of a table the mount can't read return
+    forbidden_lookup: ForbiddenLookup,
+    /// Whether every mutating operation is refused
+    read_only: bool,
+    /// Preferred and largest read and write size FSINFO reports
+    transfer_size: u32,
+    /// Deadline for each LOOKUP, GETATTR, READ and READDIR
+    operation_timeout: Option<Duration>,
+    /// Content of `/data/.stats.json`, kept per table version
+    file_stats: Arc<FileStatsFile>,
+    /// Table version data.csv's cached attributes were computed at
+    csv_attr_version: Arc<Mutex<Option<String>>>,
+    /// Stream of data.csv the last read continued, with its version
+    csv_reader: Arc<Mutex<Option<(Option<String>, CsvStreamReader)>>>,
 }
 
 impl FsdbFilesystem {
     pub fn new(db: Arc<DatabaseOps>) -> Self {
+        let layout = FileIdLayout::default();
         Self {
+            file_stats: Arc::new(FileStatsFile::new(db.clone())),
             db,
             parquet_files: Arc::new(Mutex::new(HashMap::new())),
             created_dirs: Arc::new(Mutex::new(HashMap::new())),
-            next_dir_id: Arc::new(Mutex::new(CREATED_DIR_START)),
+            next_dir_id: Arc::new(Mutex::new(layout.created_dirs.start)),
             created_files: Arc::new(Mutex::new(HashMap::new())),
-            next_file_id: Arc::new(Mutex::new(CREATED_FILE_START)),
+            next_file_id: Arc::new(Mutex::new(layout.created_files.start)),
             cache: None,
             attr_cache: Arc::new(AttrCache::new()),
+            coercion: CoercionPolicy::default(),
+            layout,
+            pending_writes: Arc::new(PendingWrites::new()),
+            partition_dirs: Arc::new(PartitionDirs::new()),
+            table_dirs: Arc::new(TableDirs::new()),
+            table_handles: Arc::new(Mutex::new(HashMap::new())),
+            conflict_policy: ConflictPolicy::default(),
+            read_bases: Arc::new(ReadBases::new()),
+            client: 0,
+            auth: None,
+            forbidden_lookup: ForbiddenLookup::default(),
+            read_only: false,
+            transfer_size: limits::DEFAULT_TRANSFER_SIZE,
+            operation_timeout: None,
+            csv_attr_version: Arc::new(Mutex::new(None)),
+            csv_reader: Arc::new(Mutex::new(None)),
         }
     }
 
     /// Create a new filesystem with caching enabled
     pub fn with_cache(db: Arc<DatabaseOps>, cache: Arc<NfsCache>) -> Self {
+        let layout = FileIdLayout::default();
         Self {
+            file_stats: Arc::new(FileStatsFile::new(db.clone())),
             db,
             parquet_files: Arc::new(Mutex::new(HashMap::new())),
             created_dirs: Arc::new(Mutex::new(HashMap::new())),
-            next_dir_id: Arc::new(Mutex::new(CREATED_DIR_START)),
+            next_dir_id: Arc::new(Mutex::new(layout.created_dirs.start)),
             created_files: Arc::new(Mutex::new(HashMap::new())),
-            next_file_id: Arc::new(Mutex::new(CREATED_FILE_START)),
+            next_file_id: Arc::new(Mutex::new(layout.created_files.start)),
             cache: Some(cache),
             attr_cache: Arc::new(AttrCache::new()),
+            coercion: CoercionPolicy::default(),
+            layout,
+            pending_writes: Arc::new(PendingWrites::new()),
+            partition_dirs: Arc::new(PartitionDirs::new()),
+            table_dirs: Arc::new(TableDirs::new()),
+            table_handles: Arc::new(Mutex::new(HashMap::new())),
+            conflict_policy: ConflictPolicy::default(),
+            read_bases: Arc::new(ReadBases::new()),
+            client: 0,
+            auth: None,
+            forbidden_lookup: ForbiddenLookup::default(),
+            read_only: false,
+            transfer_size: limits::DEFAULT_TRANSFER_SIZE,
+            operation_timeout: None,
+            csv_attr_version: Arc::new(Mutex::new(None)),
+            csv_reader: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    /// Set the coercion policy used when data.csv is written
+    ///
+    /// Its binary encoding and null representation are also the ones
+    /// data.csv is rendered with.
+    pub fn with_coercion_policy(mut self, policy: CoercionPolicy) -> Self {
+        self.coercion = policy;
+        self
+    }
+
+    /// Share pending writes with other handles on the same export
+    ///
+    /// A write split across several calls may arrive through different
+    /// handles, so they all need to assemble it in the same place.
+    pub fn with_pending_writes(mut self, pending_writes: Arc<PendingWrites>) -> Self {
+        self.pending_writes = pending_writes;
+        self
+    }
+
+    /// Set how overwrites of data.csv based on an out-of-date read are handled
+    pub fn with_conflict_policy(mut self, policy: ConflictPolicy) -> Self {
+        self.conflict_policy = policy;
+        self
+    }
+
+    /// Keep read bases in `read_bases`, under `client`
+    ///
+    /// Handles on the same export share one `ReadBases`. The NFS protocol
+    /// layer doesn't tell clients apart, so all reads through one handle count
+    /// as the same client's; a handle per client gives each its own base.
+    pub fn with_read_bases(mut self, read_bases: Arc<ReadBases>, client: u64) -> Self {
+        self.read_bases = read_bases;
+        self.client = client;
+        self
+    }
+
+    /// Share partition directory IDs with other handles on the same export
+    pub fn with_partition_dirs(mut self, partition_dirs: Arc<PartitionDirs>) -> Self {
+        self.partition_dirs = partition_dirs;
+        self
+    }
+
+    /// Share named table IDs with other handles on the same export
+    pub fn with_table_dirs(mut self, table_dirs: Arc<TableDirs>) -> Self {
+        self.table_dirs = table_dirs;
+        self
+    }
+
+    /// Authenticate the mount as `auth` rather than the database's user
+    ///
+    /// Tables its roles can't read are hidden from listings and refused.
+    pub fn with_auth_context(mut self, auth: Arc<AuthContext>) -> Self {
+        self.auth = Some(auth);
+        self
+    }
+
+    /// Set what lookups of a table the mount can't read return
+    pub fn with_forbidden_lookup(mut self, forbidden_lookup: ForbiddenLookup) -> Self {
+        self.forbidden_lookup = forbidden_lookup;
+        self
+    }
+
+    /// Export the database read-only
+    ///
+    /// Every operation that would change the mount fails with NFS3ERR_ROFS
+    /// before touching the database, whatever the user's permissions or the
+    /// table's write mode.
+    pub fn with_read_only(mut self, read_only: bool) -> Self {
+        self.read_only = read_only;
+        self
+    }
+
+    /// Have clients read and write in transfers of `bytes`
+    ///
+    /// FSINFO reports it as both the preferred and the largest transfer,
+    /// rounded down to a multiple of 4 KiB. Reads and writes of any size are
+    /// still served.
+    pub fn with_transfer_size(mut self, bytes: u32) -> Self {
+        self.transfer_size = limits::transfer_size(bytes);
+        self
+    }
+
+    /// Fail LOOKUP, GETATTR, READ and READDIR that take longer than `timeout`
+    ///
+    /// The deadline is absolute: an operation still running when it passes
+    /// fails with NFS3ERR_JUKEBOX, telling the client to retry later, however
+    /// much progress it was making. The operation is cancelled, along with
+    /// the query or scan it was waiting on, so a stuck table doesn't hold
+    /// the mount's resources. Operations that change the table aren't
+    /// bounded, since a write cancelled part way through leaves the client
+    /// unable to tell whether it was applied.
+    pub fn with_operation_timeout(mut self, timeout: Duration) -> Self {
+        self.operation_timeout = Some(timeout);
+        self
+    }
+
+    /// Use a custom file ID layout
+    ///
+    /// Fails if any of the layout's IDs or ranges overlap.
+    pub fn with_file_id_layout(mut self, layout: FileIdLayout) -> crate::Result<Self> {
+        layout.validate()?;
+        self.next_dir_id = Arc::new(Mutex::new(layout.created_dirs.start));
+        self.next_file_id = Arc::new(Mutex::new(layout.created_files.start));
+        self.layout = layout;
+        Ok(self)
+    }
+
+    /// File ID layout in use
+    pub fn file_id_layout(&self) -> &FileIdLayout {
+        &self.layout
+    }
+
+    /// Run `op`, failing it with NFS3ERR_JUKEBOX past the operation timeout
+    ///
+    /// `op` is dropped at the deadline. A read of data.csv holds its stream
+    /// while it reads, so a read part way through a slow scan drops the
+    /// stream with it, and the scan stops rather than waiting for a retry.
+    async fn with_deadline<T>(
+        &self,
+        name: &str,
+        op: impl Future<Output = std::result::Result<T, nfsstat3>>,
+    ) -> std::result::Result<T, nfsstat3> {
+        let Some(timeout) = self.operation_timeout else {
+            return op.await;
+        };
+        match tokio::time::timeout(timeout, op).await {
+            Ok(result) => result,
+            Err(_) => {
+                warn!("NFS {} exceeded its {:?} deadline", name, timeout);
+                Err(nfsstat3::NFS3ERR_JUKEBOX)
+            }
+        }
+    }
+
+    /// Whether the mount's user can read the table `table`
+    fn can_read(&self, table: &str) -> bool {
+        match &self.auth {
+            Some(auth) => self.db.can_read_table_as(auth, table),
+            None => self.db.can_read_table(table),
+        }
+    }
+
+    /// Whether the mount's user has `permission`
+    fn has_permission(&self, permission: &crate::security::Permission) -> bool {
+        match &self.auth {
+            Some(auth) => self.db.has_permission_as(auth, permission),
+            None => self.db.has_permission(permission),
+        }
+    }
+
+    /// Columns of `db`'s table the mount reads masked: the database user's,
+    /// and the mount's identity's
+    fn masks(&self, db: &DatabaseOps) -> crate::security::ColumnMasks {
+        let mut masks = db.column_masks();
+        if let Some(auth) = &self.auth {
+            masks.columns.extend(db.column_masks_as(auth).columns);
+        }
+        masks
+    }
+
+    /// Whether the mount reads any column of `db`'s table masked
+    fn masked(&self, db: &DatabaseOps) -> bool {
+        !self.masks(db).is_empty()
+    }
+
+    /// Which writes `db`'s table accepts through the mount
+    ///
+    /// A table the mount reads masked is append-only, since rewriting its
+    /// rows would write the masked values back.
+    fn write_mode(&self, db: &DatabaseOps) -> WriteMode {
+        match db.write_mode() {
+            WriteMode::ReadWrite if self.masked(db) => WriteMode::AppendOnly,
+            mode => mode,
+        }
+    }
+
+    /// Cache of data.csv's content, unless the mount reads it masked
+    ///
+    /// The cache is shared with mounts that may read the table in the clear.
+    fn content_cache(&self) -> Option<&Arc<NfsCache>> {
+        self.cache.as_ref().filter(|_| !self.masked(&self.db))
+    }
+
+    /// Refuse an overwrite of a table the mount reads masked
+    fn check_overwrite(&self, db: &DatabaseOps, data: &[u8]) -> std::result::Result<(), nfsstat3> {
+        if CsvFileView::is_overwrite(data) && self.masked(db) {
+            warn!("Rejecting overwrite of a table with masked columns");
+            return Err(nfsstat3::NFS3ERR_ACCES);
+        }
+        Ok(())
+    }
+
+    /// Whether the mount's user can read the exported table
+    fn table_visible(&self) -> bool {
+        self.can_read(TABLE_NAME)
+    }
+
+    /// Refuse access to the table's directory and files if it isn't visible
+    fn check_table_access(&self, id: fileid3) -> std::result::Result<(), nfsstat3> {
+        let in_table = id == self.layout.data_dir
+            || id == self.layout.data_csv
+            || id == self.layout.stats_json
+            || self.layout.is_parquet_file(id)
+            || self.layout.is_partition(id);
+        if in_table && !self.table_visible() {
+            warn!("NFS access to table {} denied for id={}", TABLE_NAME, id);
+            return Err(nfsstat3::NFS3ERR_ACCES);
+        }
+        Ok(())
+    }
+
+    /// Refuse a mutating operation if the mount is read-only
+    fn check_writable(&self, op: &str) -> std::result::Result<(), nfsstat3> {
+        if self.read_only {
+            warn!("NFS {} refused on read-only mount", op);
+            return Err(nfsstat3::NFS3ERR_ROFS);
         }
+        Ok(())
     }
 
     /// Get current timestamp for file attributes
@@ -133,45 +454,1116 @@ impl FsdbFilesystem {
         }
     }
 
-    /// Refresh Parquet file cache (Delta Lake mode)
-    pub(crate) async fn refresh_parquet_files(&self) -> std::result::Result<(), nfsstat3> {
-        // For Delta Lake, scan the base directory for parquet files
-        // Delta Lake stores parquet files in the root table directory
-        let mut parquet_files = self.parquet_files.lock().await;
-        parquet_files.clear();
+    /// Content of `/data/.stats.json`
+    async fn file_stats_content(&self) -> std::result::Result<Arc<Vec<u8>>, nfsstat3> {
+        let masks = self.masks(&self.db);
+        self.file_stats.content(&masks).await.map_err(|e| {
+            error!("Failed to render .stats.json: {}", e);
+            nfsstat3::NFS3ERR_IO
+        })
+    }
+
+    /// Refresh Parquet file cache (Delta Lake mode)
+    ///
+    /// IDs come from the table's persisted mapping, so a file keeps its ID,
+    /// and clients their handles to it, across server restarts.
+    pub(crate) async fn refresh_parquet_files(&self) -> std::result::Result<(), nfsstat3> {
+        // For Delta Lake, scan the base directory for parquet files
+        // Delta Lake stores parquet files in the root table directory
+        let mut parquet_files = self.parquet_files.lock().await;
+        parquet_files.clear();
+
+        let base_path = self.db.base_path();
+
+        // Scan for .parquet files in the base directory
+        let entries = match std::fs::read_dir(base_path) {
+            Ok(entries) => entries,
+            Err(e) => {
+                error!("Failed to read Delta Lake directory: {}", e);
+                return Err(nfsstat3::NFS3ERR_IO);
+            }
+        };
+        let names: Vec<String> = entries
+            .flatten()
+            .map(|entry| entry.path())
+            .filter(|path| {
+                path.is_file() && path.extension().and_then(|s| s.to_str()) == Some("parquet")
+            })
+            .filter_map(|path| Some(path.file_name()?.to_string_lossy().to_string()))
+            .collect();
+
+        let range = &self.layout.parquet_files;
+        let ids = ParquetFileIds::refresh(base_path, &names, range.end - range.start);
+        for (name, offset) in ids.offsets {
+            parquet_files.insert(range.start + offset, name);
+        }
+        if parquet_files.len() < names.len() {
+            warn!(
+                "NFS: Parquet file ID range {:?} exhausted, not listing {} files",
+                range,
+                names.len() - parquet_files.len()
+            );
+        }
+
+        info!(
+            "NFS: Found {} parquet files in Delta Lake table",
+            parquet_files.len()
+        );
+        Ok(())
+    }
+
+    /// Name of the Parquet file with ID `id`
+    ///
+    /// The listing is refreshed first if the ID isn't in it, since a handle
+    /// can outlive the server that issued it. An ID no file has any more is
+    /// stale.
+    async fn parquet_file(&self, id: fileid3) -> std::result::Result<String, nfsstat3> {
+        if let Some(name) = self.parquet_files.lock().await.get(&id) {
+            return Ok(name.clone());
+        }
+        self.refresh_parquet_files().await?;
+        self.parquet_files
+            .lock()
+            .await
+            .get(&id)
+            .cloned()
+            .ok_or(nfsstat3::NFS3ERR_STALE)
+    }
+
+    /// Apply an assembled write to data.csv and refresh its cached content and attributes
+    async fn apply_csv_write(&self, data: &[u8]) -> std::result::Result<fattr3, nfsstat3> {
+        let data = decompress(data, self.db.size_limits().decompressed_limit())
+            .map_err(Self::write_status)?;
+        self.check_overwrite(&self.db, &data)?;
+        let (data, current) = self.resolve_conflict(&data).await?;
+
+        // Fetch cached content BEFORE invalidating (for performance)
+        let cached_content = if current.is_some() {
+            current
+        } else if self.cache.is_some() {
+            match self.cached_csv().await {
+                Some(content) => {
+                    info!("Using cached CSV for write diff ({} bytes)", content.len());
+                    Some(content)
+                }
+                None => {
+                    debug!("No cached CSV available for write diff");
+                    None
+                }
+            }
+        } else {
+            None
+        };
+
+        // Don't hold lock across await - create temporary view
+        let db = self.db.clone();
+        let view = CsvFileView::new(db)
+            .with_coercion(self.coercion.clone())
+            .with_reader(self.auth.clone());
+
+        view.apply_write(&data, cached_content)
+            .await
+            .map_err(Self::write_status)?;
+
+        // UPDATE content cache after write (don't invalidate!)
+        // This keeps subsequent reads fast by avoiding CSV regeneration
+        if self.content_cache().is_some() {
+            // Generate fresh CSV content after write
+            let version = csv_version(&self.db).await;
+            let fresh_csv = view.generate_csv().await.map_err(|e| {
+                error!("Failed to generate CSV for cache: {}", e);
+                nfsstat3::NFS3ERR_IO
+            })?;
+            let fresh_size = fresh_csv.len();
+
+            // Update cache with new content
+            if let Err(e) = self.cache_csv(version, fresh_csv).await {
+                error!("Failed to update cache after write: {}", e);
+                // Don't fail the write if cache update fails
+            } else {
+                info!(
+                    "Content cache UPDATED for data.csv after write ({} bytes)",
+                    fresh_size
+                );
+            }
+        }
+
+        // IMPORTANT: Update attr_cache with NEW file size after write
+        // We must return accurate file size or OS NFS clients will truncate reads!
+        let size = view.size().await.unwrap_or(0);
+        let attr = Self::file_attr(self.layout.data_csv, size);
+        self.attr_cache.set(self.layout.data_csv, attr).await;
+        info!(
+            "Write completed, attr cache updated with new size: {} bytes",
+            size
+        );
+
+        // The writer has now seen the table as it wrote it
+        self.record_read_base().await;
+        Ok(attr)
+    }
+
+    /// Check an overwrite of data.csv against the writer's read base
+    ///
+    /// Returns the data to apply, which under `ConflictPolicy::Merge` is the
+    /// merged content, along with the current content if it had to be
+    /// generated. Appends never conflict, and neither does an overwrite from a
+    /// client with no recorded base.
+    async fn resolve_conflict(
+        &self,
+        data: &[u8],
+    ) -> std::result::Result<(Vec<u8>, Option<Vec<u8>>), nfsstat3> {
+        if self.conflict_policy == ConflictPolicy::LastWriterWins
+            || !CsvFileView::is_overwrite(data)
+        {
+            return Ok((data.to_vec(), None));
+        }
+        let Some(base) = self.read_bases.get(self.client).await else {
+            return Ok((data.to_vec(), None));
+        };
+        let version = self.table_version().await?;
+        if base.version == version {
+            return Ok((data.to_vec(), None));
+        }
+
+        match self.conflict_policy {
+            ConflictPolicy::Reject => {
+                warn!(
+                    "Rejecting overwrite of data.csv read at version {:?}, table is at {:?}",
+                    base.version, version
+                );
+                Err(Self::conflict_status())
+            }
+            // Merge; last-writer-wins returned above
+            _ => {
+                let view = CsvFileView::new(self.db.clone())
+                    .with_coercion(self.coercion.clone())
+                    .with_reader(self.auth.clone());
+                let current = view.generate_csv().await.map_err(|e| {
+                    error!("Failed to generate CSV for merge: {}", e);
+                    nfsstat3::NFS3ERR_IO
+                })?;
+                let merged = merge_csv(
+                    &String::from_utf8_lossy(&base.content),
+                    &String::from_utf8_lossy(&current),
+                    &String::from_utf8_lossy(data),
+                    &view.id_column(),
+                )
+                .map_err(|e| {
+                    warn!("Failed to merge overwrite of data.csv: {}", e);
+                    match e {
+                        crate::Error::TransactionConflict(_) => Self::conflict_status(),
+                        _ => nfsstat3::NFS3ERR_IO,
+                    }
+                })?;
+                info!(
+                    "Merged overwrite of data.csv read at version {:?} onto version {:?}",
+                    base.version, version
+                );
+                Ok((merged.into_bytes(), Some(current)))
+            }
+        }
+    }
+
+    /// Status for a CSV write that `apply_write` rejected
+    ///
+    /// Content that doesn't fit the table, or a compressed write that doesn't
+    /// decompress, is the client's mistake, so it gets INVAL rather than the
+    /// IO error used for storage failures, FBIG for rows over the size
+    /// limits, or DQUOT for a write over the user's quota.
+    fn write_status(e: crate::Error) -> nfsstat3 {
+        match e {
+            crate::Error::SchemaMismatch(_)
+            | crate::Error::TypeCoercion { .. }
+            | crate::Error::Decompression(_) => {
+                warn!("Rejected CSV write: {}", e);
+                nfsstat3::NFS3ERR_INVAL
+            }
+            crate::Error::RowTooLarge { .. } | crate::Error::FieldTooLarge { .. } => {
+                warn!("Rejected CSV write: {}", e);
+                nfsstat3::NFS3ERR_FBIG
+            }
+            crate::Error::QuotaExceeded { .. } => {
+                warn!("Rejected CSV write: {}", e);
+                nfsstat3::NFS3ERR_DQUOT
+            }
+            _ => {
+                error!("Write error: {}", e);
+                nfsstat3::NFS3ERR_IO
+            }
+        }
+    }
+
+    /// Status for a write that conflicts with changes since the writer's read
+    ///
+    /// NOT_SYNC is NFSv3's error for a guarded update whose guard no longer
+    /// matches, which is what a stale read base amounts to.
+    fn conflict_status() -> nfsstat3 {
+        nfsstat3::NFS3ERR_NOT_SYNC
+    }
+
+    /// Current version of the table
+    async fn table_version(&self) -> std::result::Result<Option<i64>, nfsstat3> {
+        let table = self.db.get_delta_table().await.map_err(|e| {
+            error!("Failed to open table for its version: {}", e);
+            nfsstat3::NFS3ERR_IO
+        })?;
+        Ok(table.version())
+    }
+
+    /// Cached data.csv, if it was generated at the table's current version
+    ///
+    /// Writes through other filesystems on the same `DatabaseOps`, or
+    /// through its API, bypass this one's cache, so an entry is only used
+    /// while the table version and write buffer are what they were when it
+    /// was generated. Tables whose log can't be listed, such as on S3, use
+    /// whatever is cached.
+    async fn cached_csv(&self) -> Option<Vec<u8>> {
+        let cache = self.content_cache()?;
+        let cached = match csv_version(&self.db).await {
+            Some(version) => cache.get_versioned("csv:data", &version).await,
+            None => cache.get("csv:data").await,
+        };
+        cached.ok().flatten()
+    }
+
+    /// Cache data.csv content generated at `version`
+    async fn cache_csv(&self, version: Option<String>, content: Vec<u8>) -> crate::Result<()> {
+        let Some(cache) = self.content_cache() else {
+            return Ok(());
+        };
+        match version {
+            Some(version) => {
+                cache
+                    .insert_versioned("csv:data".to_string(), content, &version)
+                    .await
+            }
+            None => cache.insert("csv:data".to_string(), content).await,
+        }
+    }
+
+    /// Read `count` bytes of data.csv at `offset`, and whether they reach the end
+    ///
+    /// Content is streamed rather than generated whole. A read continuing
+    /// from where the last one ended picks up the same stream, so reading
+    /// the file front to back generates it once; a read from the start with
+    /// the cache enabled spools the content into the cache's disk tier as
+    /// it goes, and later reads at any offset are served from there.
+    async fn read_csv(&self, offset: u64, count: u32) -> crate::Result<(Vec<u8>, bool)> {
+        let version = csv_version(&self.db).await;
+        if let Some(cache) = self.content_cache() {
+            let cached = cache
+                .read_chunked("csv:data", version.as_deref(), offset, count as u64)
+                .await?;
+            if let Some((data, len)) = cached {
+                info!("Cache HIT for data.csv (chunked)");
+                let eof = offset + data.len() as u64 >= len;
+                return Ok((data, eof));
+            }
+        }
+
+        // The stream is out of its slot while it's read, so a read dropped
+        // at its deadline takes only its own stream with it
+        let mut reader = self.csv_reader.lock().await;
+        let reusable = reader.take().filter(|(reader_version, stream)| {
+            *reader_version == version && stream.position() <= offset
+        });
+        let (version, mut stream) = match reusable {
+            Some(reusable) => reusable,
+            None => {
+                let view = CsvFileView::new(self.db.clone())
+                    .with_coercion(self.coercion.clone())
+                    .with_reader(self.auth.clone());
+                let mut stream = CsvStreamReader::new(view.stream().await?);
+                if let (Some(cache), 0) = (self.content_cache(), offset) {
+                    let spool = ChunkSpool::new(cache.clone(), "csv:data", version.clone()).await?;
+                    stream = stream.with_spool(spool);
+                }
+                (version, stream)
+            }
+        };
+        let read = stream.read_at(offset, count as u64).await;
+        *reader = Some((version, stream));
+        read
+    }
+
+    /// Size of data.csv, counted from a stream of it unless it's cached
+    ///
+    /// With the cache enabled the content is spooled into its disk tier while
+    /// it's counted, so the reads that usually follow a stat find it there.
+    async fn csv_size(&self) -> crate::Result<u64> {
+        if let Some(content) = self.cached_csv().await {
+            return Ok(content.len() as u64);
+        }
+        let view = CsvFileView::new(self.db.clone())
+            .with_coercion(self.coercion.clone())
+            .with_reader(self.auth.clone());
+        let Some(cache) = self.content_cache() else {
+            return view.size().await;
+        };
+        let version = csv_version(&self.db).await;
+        if let Some(len) = cache.chunked_len("csv:data", version.as_deref()).await? {
+            return Ok(len);
+        }
+        let spool = ChunkSpool::new(cache.clone(), "csv:data", version).await?;
+        CsvStreamReader::new(view.stream().await?)
+            .with_spool(spool)
+            .finish()
+            .await
+    }
+
+    /// Drop data.csv's cached attributes if the table changed since they were cached
+    async fn revalidate_csv_attr(&self) {
+        let version = csv_version(&self.db).await;
+        let mut attr_version = self.csv_attr_version.lock().await;
+        if *attr_version != version {
+            self.attr_cache.invalidate(self.layout.data_csv).await;
+            *attr_version = version;
+        }
+    }
+
+    /// Record data.csv as it is now as this handle's client's read base
+    ///
+    /// Only done when a conflict policy needs it. The version is taken before
+    /// the content, so a change in between makes the base look older than it
+    /// is rather than newer.
+    async fn record_read_base(&self) {
+        if self.conflict_policy == ConflictPolicy::LastWriterWins {
+            return;
+        }
+        let Ok(version) = self.table_version().await else {
+            return;
+        };
+        let cached = self.cached_csv().await;
+        let content = match cached {
+            Some(content) => content,
+            None => match CsvFileView::new(self.db.clone())
+                .with_coercion(self.coercion.clone())
+                .with_reader(self.auth.clone())
+                .generate_csv()
+                .await
+            {
+                Ok(content) => content,
+                Err(e) => {
+                    warn!("Failed to record read base: {}", e);
+                    return;
+                }
+            },
+        };
+        self.read_bases
+            .record(self.client, ReadBase { version, content })
+            .await;
+    }
+
+    /// Columns the table is partitioned by, empty if it isn't
+    ///
+    /// A table whose log can't be read locally, such as one on S3, is exported
+    /// without partition directories.
+    fn partition_columns(&self) -> Vec<String> {
+        self.db.partition_columns().unwrap_or_else(|e| {
+            debug!("No partition directories: {}", e);
+            Vec::new()
+        })
+    }
+
+    /// The table's partitions along with directories clients have made
+    async fn known_partitions(&self) -> std::result::Result<Vec<PartitionValues>, nfsstat3> {
+        let mut partitions = self.db.partitions().map_err(|e| {
+            error!("Failed to list partitions: {}", e);
+            nfsstat3::NFS3ERR_IO
+        })?;
+        partitions.extend(self.partition_dirs.made().await);
+        Ok(partitions)
+    }
+
+    /// File ID of a partition directory or data.csv
+    async fn partition_id(&self, entry: &PartitionEntry) -> std::result::Result<fileid3, nfsstat3> {
+        self.partition_dirs
+            .id(entry, &self.layout.partitions)
+            .await
+            .ok_or_else(|| {
+                error!("Partition ID range {:?} exhausted", self.layout.partitions);
+                nfsstat3::NFS3ERR_NOSPC
+            })
+    }
+
+    /// Partition entry behind `id`, if it's in the partition range
+    ///
+    /// Partition IDs are handed out as directories are looked up, so one
+    /// that was never handed out is a handle from before a restart: stale,
+    /// and the client looks the path up again.
+    async fn partition_entry(&self, id: fileid3) -> std::result::Result<PartitionEntry, nfsstat3> {
+        self.partition_dirs
+            .entry(id)
+            .await
+            .ok_or(nfsstat3::NFS3ERR_STALE)
+    }
+
+    /// Values of the directory one level below `parent` named `name`, if it
+    /// names the next partition column
+    fn partition_child(
+        columns: &[String],
+        parent: &PartitionValues,
+        name: &str,
+    ) -> Option<PartitionValues> {
+        let (column, value) = parse_dir_name(name)?;
+        if columns.get(parent.len()) != Some(&column) {
+            return None;
+        }
+        let mut child = parent.clone();
+        child.push((column, value));
+        Some(child)
+    }
+
+    /// Look up `name` in the partition directory `parent`, which is empty for `/data`
+    async fn lookup_partition(
+        &self,
+        parent: &PartitionValues,
+        name: &str,
+    ) -> std::result::Result<Option<fileid3>, nfsstat3> {
+        let columns = self.partition_columns();
+        if columns.is_empty() {
+            return Ok(None);
+        }
+        if parent.len() == columns.len() {
+            if name == "data.csv" {
+                let id = self
+                    .partition_id(&PartitionEntry::Csv(parent.clone()))
+                    .await?;
+                return Ok(Some(id));
+            }
+            return Ok(None);
+        }
+
+        let Some(child) = Self::partition_child(&columns, parent, name) else {
+            return Ok(None);
+        };
+        if !children(&self.known_partitions().await?, parent).contains(&child) {
+            return Ok(None);
+        }
+        Ok(Some(self.partition_id(&PartitionEntry::Dir(child)).await?))
+    }
+
+    /// Entries of the partition directory `parent`, which is empty for `/data`
+    ///
+    /// A directory lists the next level of partition directories, or the
+    /// partition's data.csv once every partition column has a value.
+    async fn partition_dir_entries(
+        &self,
+        parent: &PartitionValues,
+    ) -> std::result::Result<Vec<DirEntry>, nfsstat3> {
+        let columns = self.partition_columns();
+        if columns.is_empty() {
+            return Ok(Vec::new());
+        }
+        if parent.len() == columns.len() {
+            let id = self
+                .partition_id(&PartitionEntry::Csv(parent.clone()))
+                .await?;
+            return Ok(vec![DirEntry {
+                fileid: id,
+                name: "data.csv".as_bytes().into(),
+                attr: self.partition_csv_attr(id, parent).await,
+            }]);
+        }
+
+        let mut entries = Vec::new();
+        for child in children(&self.known_partitions().await?, parent) {
+            let (column, value) = &child[child.len() - 1];
+            let id = self
+                .partition_id(&PartitionEntry::Dir(child.clone()))
+                .await?;
+            entries.push(DirEntry {
+                fileid: id,
+                name: dir_name(column, value.as_deref()).as_bytes().into(),
+                attr: Self::dir_attr(id),
+            });
+        }
+        Ok(entries)
+    }
+
+    /// Attributes of a partition's data.csv
+    async fn partition_csv_attr(&self, id: fileid3, partition: &PartitionValues) -> fattr3 {
+        let view = CsvFileView::new_for_partition(self.db.clone(), partition.clone())
+            .with_coercion(self.coercion.clone())
+            .with_reader(self.auth.clone());
+        let size = match view.size().await {
+            Ok(s) => s,
+            Err(e) => {
+                error!("Failed to get partition CSV size: {}", e);
+                0
+            }
+        };
+        Self::file_attr(id, size)
+    }
+
+    /// Apply an assembled write to a partition's data.csv
+    ///
+    /// The rows are appended to that partition. The table-wide data.csv
+    /// changes too, so its cached content and attributes are dropped.
+    async fn apply_partition_write(
+        &self,
+        id: fileid3,
+        partition: &PartitionValues,
+        data: &[u8],
+    ) -> std::result::Result<fattr3, nfsstat3> {
+        let data = decompress(data, self.db.size_limits().decompressed_limit())
+            .map_err(Self::write_status)?;
+        let view = CsvFileView::new_for_partition(self.db.clone(), partition.clone())
+            .with_coercion(self.coercion.clone())
+            .with_reader(self.auth.clone());
+        view.apply_write(&data, None)
+            .await
+            .map_err(Self::write_status)?;
+
+        if let Some(ref cache) = self.cache {
+            let _ = cache.remove("csv:data").await;
+        }
+        self.attr_cache.invalidate(self.layout.data_csv).await;
+
+        let attr = self.partition_csv_attr(id, partition).await;
+        self.attr_cache.set(id, attr).await;
+        info!(
+            "Write to partition {:?} completed, size: {} bytes",
+            partition, attr.size
+        );
+        Ok(attr)
+    }
+
+    /// Named tables the mount's user can read, sorted
+    ///
+    /// A database on S3, or whose user can't list tables, exports none.
+    fn named_tables(&self) -> Vec<String> {
+        match self.db.list_tables() {
+            Ok(names) => names
+                .into_iter()
+                .filter(|name| self.can_read(name))
+                .collect(),
+            Err(e) => {
+                debug!("No table directories: {}", e);
+                Vec::new()
+            }
+        }
+    }
+
+    /// Directories of `/tables`: the named tables the mount's user can read
+    /// and those made for tables that don't exist yet, sorted
+    async fn table_dir_names(&self) -> Vec<String> {
+        let mut names = self.named_tables();
+        names.extend(self.table_dirs.made().await);
+        names.sort();
+        names.dedup();
+        names
+    }
+
+    /// Handle on the named table `name`, or `None` if there's no such table
+    ///
+    /// Handles are opened once and kept until the table is dropped. `name`
+    /// comes from the client, so anything that can't name a table is absent
+    /// rather than a path to look for.
+    async fn named_table(
+        &self,
+        name: &str,
+    ) -> std::result::Result<Option<Arc<DatabaseOps>>, nfsstat3> {
+        use crate::metadata::catalog;
+
+        let mut handles = self.table_handles.lock().await;
+        if catalog::check_table_name(name).is_err() || !catalog::exists(self.db.base_path(), name) {
+            handles.remove(name);
+            return Ok(None);
+        }
+        if let Some(db) = handles.get(name) {
+            return Ok(Some(db.clone()));
+        }
+        let db = self.db.table(name).await.map_err(|e| {
+            error!("Failed to open table {}: {}", name, e);
+            nfsstat3::NFS3ERR_IO
+        })?;
+        let db = Arc::new(db);
+        handles.insert(name.to_string(), db.clone());
+        Ok(Some(db))
+    }
+
+    /// File ID of a named table's directory or file
+    async fn table_id(&self, entry: &TableEntry) -> std::result::Result<fileid3, nfsstat3> {
+        self.table_dirs
+            .id(entry, &self.layout.tables)
+            .await
+            .ok_or_else(|| {
+                error!("Table ID range {:?} exhausted", self.layout.tables);
+                nfsstat3::NFS3ERR_NOSPC
+            })
+    }
+
+    /// Entry behind `id`, if it's in the named table range, and its table
+    ///
+    /// Like partition IDs, table IDs are handed out as they're looked up, so
+    /// one never handed out, or of a table dropped since, is stale. A table
+    /// the mount's user can't read is refused. The table is `None` for a
+    /// directory made with MKDIR that nothing has been copied into yet.
+    async fn table_entry(
+        &self,
+        id: fileid3,
+    ) -> std::result::Result<(TableEntry, Option<Arc<DatabaseOps>>), nfsstat3> {
+        let entry = self
+            .table_dirs
+            .entry(id)
+            .await
+            .ok_or(nfsstat3::NFS3ERR_STALE)?;
+        let made = self.table_dirs.is_made(entry.table()).await;
+        if !made && !self.can_read(entry.table()) {
+            warn!("NFS access to table {} denied for id={}", entry.table(), id);
+            return Err(nfsstat3::NFS3ERR_ACCES);
+        }
+        let db = self.named_table(entry.table()).await?;
+        if db.is_none() && !made {
+            return Err(nfsstat3::NFS3ERR_STALE);
+        }
+        Ok((entry, db))
+    }
+
+    /// Look up the directory of the named table `name` in `/tables`
+    async fn lookup_table(&self, name: &str) -> std::result::Result<fileid3, nfsstat3> {
+        if self.named_table(name).await?.is_none() {
+            if !self.table_dirs.is_made(name).await {
+                return Err(nfsstat3::NFS3ERR_NOENT);
+            }
+        } else if !self.can_read(name) {
+            return Err(self.forbidden_lookup.status());
+        }
+        self.table_id(&TableEntry::Dir(name.to_string())).await
+    }
+
+    /// Names of a named table's Parquet files, sorted
+    fn table_parquet_files(db: &DatabaseOps) -> std::result::Result<Vec<String>, nfsstat3> {
+        let entries = std::fs::read_dir(db.base_path()).map_err(|e| {
+            error!("Failed to read table directory: {}", e);
+            nfsstat3::NFS3ERR_IO
+        })?;
+        let mut names: Vec<String> = entries
+            .flatten()
+            .map(|entry| entry.path())
+            .filter(|path| {
+                path.is_file() && path.extension().and_then(|s| s.to_str()) == Some("parquet")
+            })
+            .filter_map(|path| Some(path.file_name()?.to_string_lossy().to_string()))
+            .collect();
+        names.sort();
+        Ok(names)
+    }
+
+    /// Attributes of a named table's Parquet file
+    fn table_parquet_attr(
+        id: fileid3,
+        db: &DatabaseOps,
+        file: &str,
+    ) -> std::result::Result<fattr3, nfsstat3> {
+        match std::fs::metadata(db.base_path().join(file)) {
+            Ok(metadata) => Ok(Self::file_attr(id, metadata.len())),
+            Err(_) => {
+                // Removed since it was listed
+                error!("File not found: {}", file);
+                Err(nfsstat3::NFS3ERR_STALE)
+            }
+        }
+    }
+
+    /// Entries of the directory of the named table `table`
+    ///
+    /// Its data.csv comes first, then its Parquet files, then the CSV files
+    /// copied into it. A directory made for a table that doesn't exist yet
+    /// only has the copied files.
+    async fn table_dir_entries(
+        &self,
+        table: &str,
+        db: Option<&Arc<DatabaseOps>>,
+    ) -> std::result::Result<Vec<DirEntry>, nfsstat3> {
+        let mut entries = Vec::new();
+        if let Some(db) = db {
+            let csv = self.table_id(&TableEntry::Csv(table.to_string())).await?;
+            entries.push(DirEntry {
+                fileid: csv,
+                name: "data.csv".as_bytes().into(),
+                attr: self.table_csv_attr(csv, db).await,
+            });
+            entries.extend(self.table_parquet_entries(table, db).await?);
+        }
+        for (name, id) in self.table_dirs.uploads(table).await {
+            // A data.csv copied in before the table existed is now the table's
+            if db.is_some() && name == "data.csv" {
+                continue;
+            }
+            entries.push(DirEntry {
+                fileid: id,
+                name: name.as_bytes().into(),
+                attr: self.upload_attr(id).await,
+            });
+        }
+        Ok(entries)
+    }
+
+    /// Entries of a named table's Parquet files
+    async fn table_parquet_entries(
+        &self,
+        table: &str,
+        db: &Arc<DatabaseOps>,
+    ) -> std::result::Result<Vec<DirEntry>, nfsstat3> {
+        let mut entries = Vec::new();
+        for file in Self::table_parquet_files(db)? {
+            let entry = TableEntry::Parquet(table.to_string(), file.clone());
+            let id = self.table_id(&entry).await?;
+            let Ok(attr) = Self::table_parquet_attr(id, db, &file) else {
+                continue;
+            };
+            entries.push(DirEntry {
+                fileid: id,
+                name: file.as_bytes().into(),
+                attr,
+            });
+        }
+        Ok(entries)
+    }
+
+    /// Attributes of a named table's data.csv
+    async fn table_csv_attr(&self, id: fileid3, db: &Arc<DatabaseOps>) -> fattr3 {
+        let view = CsvFileView::new(db.clone())
+            .with_coercion(self.coercion.clone())
+            .with_reader(self.auth.clone());
+        let size = match view.size().await {
+            Ok(s) => s,
+            Err(e) => {
+                error!("Failed to get table CSV size: {}", e);
+                0
+            }
+        };
+        Self::file_attr(id, size)
+    }
+
+    /// Apply an assembled write to a named table's data.csv
+    ///
+    /// As with `/data/data.csv`, content starting with a header replaces the
+    /// table's rows and anything else is appended. Named tables' content
+    /// isn't cached, and overwrites aren't checked against a read base.
+    async fn apply_table_write(
+        &self,
+        id: fileid3,
+        db: &Arc<DatabaseOps>,
+        data: &[u8],
+    ) -> std::result::Result<fattr3, nfsstat3> {
+        let data =
+            decompress(data, db.size_limits().decompressed_limit()).map_err(Self::write_status)?;
+        self.check_overwrite(db, &data)?;
+        let view = CsvFileView::new(db.clone())
+            .with_coercion(self.coercion.clone())
+            .with_reader(self.auth.clone());
+        view.apply_write(&data, None)
+            .await
+            .map_err(Self::write_status)?;
+
+        let attr = self.table_csv_attr(id, db).await;
+        self.attr_cache.set(id, attr).await;
+        info!(
+            "Write to table data.csv completed, size: {} bytes",
+            attr.size
+        );
+        Ok(attr)
+    }
+
+    /// Attributes of a CSV file copied into a table's directory
+    ///
+    /// Its size is what the client has written, loaded or still pending;
+    /// reads of it return nothing, since its rows are in the table.
+    async fn upload_attr(&self, id: fileid3) -> fattr3 {
+        let applied = self.table_dirs.upload(id).await.applied;
+        let end = self.pending_writes.end(id).await.unwrap_or(0);
+        Self::file_attr(id, applied.max(end))
+    }
+
+    /// Handle a WRITE to a CSV file copied into a table's directory
+    ///
+    /// Chunks are loaded in order, once they form whole rows following what's
+    /// been loaded already.
+    async fn write_upload(
+        &self,
+        id: fileid3,
+        table: &str,
+        offset: u64,
+        data: &[u8],
+    ) -> std::result::Result<fattr3, nfsstat3> {
+        let applied = self.table_dirs.upload(id).await.applied;
+        let ready = |write: &PendingWrite| write.is_complete() && write.offset() == applied;
+        match self.pending_writes.add(id, offset, data, ready).await {
+            Some(write) => self.apply_upload(id, table, &write).await,
+            None => {
+                debug!("Write to copied CSV file pending until row boundary or COMMIT");
+                self.attr_cache.invalidate(id).await;
+                Ok(self.upload_attr(id).await)
+            }
+        }
+    }
+
+    /// Apply an assembled write to a CSV file copied into a table's directory
+    ///
+    /// A copied file must start with a header, which is kept to load the
+    /// rest of the file with. Its rows are appended to the table, matched to
+    /// the columns by name. The first rows copied into a directory made with
+    /// MKDIR create the table, with a schema inferred from them.
+    async fn apply_upload(
+        &self,
+        id: fileid3,
+        table: &str,
+        write: &PendingWrite,
+    ) -> std::result::Result<fattr3, nfsstat3> {
+        let data = decompress(write.data(), self.db.size_limits().decompressed_limit())
+            .map_err(Self::write_status)?;
+        let (header, rows) = match self.table_dirs.upload(id).await.header {
+            Some(header) => {
+                let rows = [header.as_slice(), &data].concat();
+                (header, rows)
+            }
+            None => {
+                let end = data
+                    .iter()
+                    .position(|&b| b == b'\n')
+                    .map_or(data.len(), |i| i + 1);
+                (data[..end].to_vec(), data)
+            }
+        };
+
+        let db = match self.named_table(table).await? {
+            Some(db) => Some(db),
+            // Wait for rows to infer the columns from
+            None if rows.len() == header.len() => None,
+            None => Some(self.create_upload_table(table, &rows).await?),
+        };
+        if let Some(db) = db {
+            let text = String::from_utf8_lossy(&rows);
+            let schema = db.table_schema().await.map_err(Self::write_status)?;
+            db.size_limits()
+                .check_csv(&text, &schema, true, self.coercion.binary_encoding)
+                .map_err(Self::write_status)?;
+            let batch =
+                coerce_csv(&text, &schema, &self.coercion, true).map_err(Self::write_status)?;
+            if batch.num_rows() > 0 {
+                db.insert(batch).await.map_err(Self::write_status)?;
+            }
+            if let Some(csv) = self
+                .table_dirs
+                .find(&TableEntry::Csv(table.to_string()))
+                .await
+            {
+                self.attr_cache.invalidate(csv).await;
+            }
+        }
+
+        let upload = Upload {
+            applied: write.end(),
+            header: Some(header),
+        };
+        self.table_dirs.set_upload(id, upload).await;
+        let attr = Self::file_attr(id, write.end());
+        self.attr_cache.set(id, attr).await;
+        info!(
+            "Loaded {} bytes of a CSV file copied into table {}",
+            write.end(),
+            table
+        );
+        Ok(attr)
+    }
+
+    /// Create the table `table` for the first rows copied into its directory
+    async fn create_upload_table(
+        &self,
+        table: &str,
+        rows: &[u8],
+    ) -> std::result::Result<Arc<DatabaseOps>, nfsstat3> {
+        let schema = infer_schema(&String::from_utf8_lossy(rows), &self.coercion)
+            .map_err(Self::write_status)?;
+        let db = match self.db.create_table(table, schema).await {
+            Ok(db) => Arc::new(db),
+            Err(e) => {
+                // Another copy into the directory may have created it first
+                if let Some(db) = self.named_table(table).await? {
+                    return Ok(db);
+                }
+                error!("Failed to create table {}: {}", table, e);
+                return Err(nfsstat3::NFS3ERR_IO);
+            }
+        };
+        self.table_handles
+            .lock()
+            .await
+            .insert(table.to_string(), db.clone());
+        self.table_dirs.unmake(table).await;
+        info!("Created table {} from a copied CSV file", table);
+        Ok(db)
+    }
+
+    /// Handle an NFSv3 COMMIT for `id`
+    ///
+    /// Applies whatever the client wrote to the file that is still pending,
+    /// including a trailing row without a newline, then flushes the database's
+    /// batch buffer. Returns only once the data is committed to the table. With
+    /// nothing pending this is just an attribute lookup.
+    pub async fn commit(&self, id: fileid3) -> std::result::Result<fattr3, nfsstat3> {
+        info!("NFS COMMIT: id={}", id);
+
+        let applied = match self.pending_writes.take(id).await {
+            Some(write) if id == self.layout.data_csv && !write.data().is_empty() => {
+                Some(self.apply_csv_write(write.data()).await?)
+            }
+            Some(write) if self.layout.is_partition(id) && !write.data().is_empty() => {
+                match self.partition_entry(id).await? {
+                    PartitionEntry::Csv(partition) => Some(
+                        self.apply_partition_write(id, &partition, write.data())
+                            .await?,
+                    ),
+                    PartitionEntry::Dir(_) => None,
+                }
+            }
+            Some(write) if self.layout.is_table(id) && !write.data().is_empty() => {
+                match self.table_entry(id).await? {
+                    (TableEntry::Csv(_), Some(db)) => {
+                        Some(self.apply_table_write(id, &db, write.data()).await?)
+                    }
+                    (TableEntry::Upload(table, _), _) => {
+                        Some(self.apply_upload(id, &table, &write).await?)
+                    }
+                    _ => None,
+                }
+            }
+            _ => None,
+        };
+        self.db.flush_write_buffer().await.map_err(|e| {
+            error!("Failed to flush buffered writes on COMMIT: {}", e);
+            nfsstat3::NFS3ERR_IO
+        })?;
+
+        match applied {
+            Some(attr) => Ok(attr),
+            None => self.getattr(id).await,
+        }
+    }
+
+    /// Handle an NFSv3 ACCESS for `id`, returning the subset of `requested` allowed
+    ///
+    /// Bits come from the mount user's permissions and, for a table's
+    /// directory and CSV files, that table's write mode, so an append-only
+    /// table, or one the mount reads with masked columns, is extendable but
+    /// not modifiable. Parquet files, and everything
+    /// on a read-only mount, are always read-only. A table the user can't read
+    /// is refused like any other operation on it. Users with Admin can make
+    /// directories in `/tables` and copy files into them.
+    pub async fn access(&self, id: fileid3, requested: u32) -> std::result::Result<u32, nfsstat3> {
+        use crate::security::Permission;
+
+        info!("NFS ACCESS: id={}, requested={:#x}", id, requested);
+        self.check_table_access(id)?;
+        let table = if self.layout.is_table(id) {
+            Some(self.table_entry(id).await?)
+        } else {
+            None
+        };
 
-        let base_path = self.db.base_path();
+        let has = |permission: Permission| self.has_permission(&permission);
+        let grants = Grants {
+            write: has(Permission::Write),
+            delete: has(Permission::Delete),
+        };
+        let allowed = match id {
+            _ if self.read_only => table_access(grants, WriteMode::ReadOnly),
+            id if self.layout.is_parquet_file(id) || id == self.layout.stats_json => {
+                table_access(grants, WriteMode::ReadOnly)
+            }
+            id if id == self.layout.data_dir
+                || id == self.layout.data_csv
+                || self.layout.is_partition(id) =>
+            {
+                table_access(grants, self.write_mode(&self.db))
+            }
+            id if id == self.layout.tables_dir && has(Permission::Admin) => scratch_access(grants),
+            id if id == self.layout.tables_dir => table_access(grants, WriteMode::ReadOnly),
+            id if self.layout.is_table(id) => match &table {
+                Some((TableEntry::Parquet(..), _)) | None => {
+                    table_access(grants, WriteMode::ReadOnly)
+                }
+                Some((_, Some(db))) => table_access(grants, self.write_mode(db)),
+                Some((_, None)) => scratch_access(grants),
+            },
+            _ => scratch_access(grants),
+        };
+        Ok(requested & allowed)
+    }
 
-        // Scan for .parquet files in the base directory
-        match std::fs::read_dir(base_path) {
-            Ok(entries) => {
-                let mut file_id = PARQUET_FILE_ID_START;
+    /// Handle an NFSv3 PATHCONF for `id`
+    ///
+    /// Every file has the same limits (see `nfs::limits`); `id` only has to
+    /// exist and be visible to the mount.
+    pub async fn pathconf(&self, id: fileid3) -> std::result::Result<PathConf, nfsstat3> {
+        info!("NFS PATHCONF: id={}", id);
+        self.check_table_access(id)?;
+        self.getattr(id).await?;
+        Ok(PathConf::default())
+    }
 
-                for entry in entries.flatten() {
-                    let path = entry.path();
+    /// Handle an NFSv4 LOOKUPP: the directory `id` is in
+    ///
+    /// NFSv3 clients resolve ".." from the paths they walked, but NFSv4 ones
+    /// ask the server, for handles kept across a restart too. The root has
+    /// no parent.
+    pub async fn parent(&self, id: fileid3) -> std::result::Result<fileid3, nfsstat3> {
+        info!("NFS LOOKUPP: id={}", id);
+        self.check_table_access(id)?;
 
-                    // Check if it's a parquet file
-                    if path.is_file()
-                        && path.extension().and_then(|s| s.to_str()) == Some("parquet")
-                    {
-                        if let Some(filename) = path.file_name() {
-                            let filename_str = filename.to_string_lossy().to_string();
-                            parquet_files.insert(file_id, filename_str);
-                            file_id += 1;
-                        }
+        match id {
+            id if id == self.layout.root => Err(nfsstat3::NFS3ERR_NOENT),
+            id if id == self.layout.data_dir || id == self.layout.tables_dir => {
+                Ok(self.layout.root)
+            }
+            id if id == self.layout.data_csv
+                || id == self.layout.stats_json
+                || self.layout.is_parquet_file(id) =>
+            {
+                Ok(self.layout.data_dir)
+            }
+            id if self.layout.is_created_dir(id) => {
+                let created_dirs = self.created_dirs.lock().await;
+                created_dirs
+                    .iter()
+                    .find(|(_, &dir_id)| dir_id == id)
+                    .map(|((parent, _), _)| *parent)
+                    .ok_or(nfsstat3::NFS3ERR_STALE)
+            }
+            id if self.layout.is_created_file(id) => {
+                let created_files = self.created_files.lock().await;
+                created_files
+                    .iter()
+                    .find(|(_, metadata)| metadata.file_id == id)
+                    .map(|((parent, _), _)| *parent)
+                    .ok_or(nfsstat3::NFS3ERR_STALE)
+            }
+            id if self.layout.is_partition(id) => {
+                // A directory is in the one above it, a data.csv in its own
+                let dir = match self.partition_entry(id).await? {
+                    PartitionEntry::Dir(mut values) => {
+                        values.pop();
+                        values
                     }
+                    PartitionEntry::Csv(values) => values,
+                };
+                if dir.is_empty() {
+                    return Ok(self.layout.data_dir);
                 }
-
-                info!(
-                    "NFS: Found {} parquet files in Delta Lake table",
-                    parquet_files.len()
-                );
-                Ok(())
-            }
-            Err(e) => {
-                error!("Failed to read Delta Lake directory: {}", e);
-                Err(nfsstat3::NFS3ERR_IO)
+                self.partition_id(&PartitionEntry::Dir(dir)).await
             }
+            id if self.layout.is_table(id) => match self.table_entry(id).await?.0 {
+                TableEntry::Dir(_) => Ok(self.layout.tables_dir),
+                entry => {
+                    self.table_id(&TableEntry::Dir(entry.table().to_string()))
+                        .await
+                }
+            },
+            _ => Err(nfsstat3::NFS3ERR_STALE),
         }
     }
 }
@@ -179,11 +1571,54 @@ impl FsdbFilesystem {
 #[async_trait]
 impl NFSFileSystem for FsdbFilesystem {
     fn root_dir(&self) -> fileid3 {
-        ROOT_ID
+        self.layout.root
+    }
+
+    /// Handles are the export's identity and the file ID, without nfsserve's
+    /// server generation, so they stay valid across restarts
+    fn id_to_fh(&self, id: fileid3) -> nfs_fh3 {
+        file_handles::encode(
+            file_handles::export_tag(self.db.base_path(), self.layout.root),
+            id,
+        )
+    }
+
+    fn fh_to_id(&self, handle: &nfs_fh3) -> std::result::Result<fileid3, nfsstat3> {
+        file_handles::decode(
+            file_handles::export_tag(self.db.base_path(), self.layout.root),
+            handle,
+        )
     }
 
     fn capabilities(&self) -> VFSCapabilities {
-        VFSCapabilities::ReadWrite
+        if self.read_only {
+            VFSCapabilities::ReadOnly
+        } else {
+            VFSCapabilities::ReadWrite
+        }
+    }
+
+    /// Limits from `nfs::limits`, with this filesystem's transfer size
+    async fn fsinfo(&self, root_fileid: fileid3) -> std::result::Result<fsinfo3, nfsstat3> {
+        info!("NFS FSINFO: id={}", root_fileid);
+        let attr = self.getattr(root_fileid).await?;
+        Ok(fsinfo3 {
+            obj_attributes: post_op_attr::attributes(attr),
+            rtmax: self.transfer_size,
+            rtpref: self.transfer_size,
+            rtmult: limits::TRANSFER_MULTIPLE,
+            wtmax: self.transfer_size,
+            wtpref: self.transfer_size,
+            wtmult: limits::TRANSFER_MULTIPLE,
+            dtpref: self.transfer_size,
+            maxfilesize: limits::MAX_FILE_SIZE,
+            // Timestamps are kept to the nanosecond
+            time_delta: nfstime3 {
+                seconds: 0,
+                nseconds: 1,
+            },
+            properties: limits::FSINFO_PROPERTIES,
+        })
     }
 
     async fn lookup(
@@ -191,177 +1626,252 @@ impl NFSFileSystem for FsdbFilesystem {
         dirid: fileid3,
         filename: &filename3,
     ) -> std::result::Result<fileid3, nfsstat3> {
-        let name = String::from_utf8_lossy(filename.as_ref());
-        info!("NFS LOOKUP: dir={}, filename={}", dirid, name);
-
-        match dirid {
-            ROOT_ID => {
-                if name == "data" {
-                    Ok(DATA_DIR_ID)
-                } else {
-                    // Check created directories
-                    let created_dirs = self.created_dirs.lock().await;
-                    if let Some(&dir_id) = created_dirs.get(&(ROOT_ID, name.to_string())) {
-                        return Ok(dir_id);
+        self.with_deadline("LOOKUP", async move {
+            let name = String::from_utf8_lossy(filename.as_ref());
+            info!("NFS LOOKUP: dir={}, filename={}", dirid, name);
+            self.check_table_access(dirid)?;
+
+            match dirid {
+                id if id == self.layout.root => {
+                    if name == TABLE_NAME {
+                        if !self.table_visible() {
+                            return Err(self.forbidden_lookup.status());
+                        }
+                        Ok(self.layout.data_dir)
+                    } else if name == TABLES_DIR {
+                        Ok(self.layout.tables_dir)
+                    } else {
+                        // Check created directories
+                        let created_dirs = self.created_dirs.lock().await;
+                        if let Some(&dir_id) =
+                            created_dirs.get(&(self.layout.root, name.to_string()))
+                        {
+                            return Ok(dir_id);
+                        }
+                        drop(created_dirs);
+                        // Check created files
+                        let created_files = self.created_files.lock().await;
+                        if let Some(metadata) =
+                            created_files.get(&(self.layout.root, name.to_string()))
+                        {
+                            return Ok(metadata.file_id);
+                        }
+                        Err(nfsstat3::NFS3ERR_NOENT)
                     }
-                    drop(created_dirs);
-                    // Check created files
-                    let created_files = self.created_files.lock().await;
-                    if let Some(metadata) = created_files.get(&(ROOT_ID, name.to_string())) {
-                        return Ok(metadata.file_id);
+                }
+                id if id == self.layout.data_dir => {
+                    if name == "data.csv" {
+                        Ok(self.layout.data_csv)
+                    } else if name == ".stats.json" {
+                        Ok(self.layout.stats_json)
+                    } else {
+                        // Check partition directories
+                        if let Some(id) = self.lookup_partition(&Vec::new(), &name).await? {
+                            return Ok(id);
+                        }
+                        // Check created directories
+                        let created_dirs = self.created_dirs.lock().await;
+                        if let Some(&dir_id) =
+                            created_dirs.get(&(self.layout.data_dir, name.to_string()))
+                        {
+                            return Ok(dir_id);
+                        }
+                        drop(created_dirs);
+                        // Check created files
+                        let created_files = self.created_files.lock().await;
+                        if let Some(metadata) =
+                            created_files.get(&(self.layout.data_dir, name.to_string()))
+                        {
+                            return Ok(metadata.file_id);
+                        }
+                        drop(created_files);
+
+                        // Check Parquet files
+                        self.refresh_parquet_files().await?;
+                        let parquet_files = self.parquet_files.lock().await;
+
+                        for (id, path) in parquet_files.iter() {
+                            let basename = std::path::Path::new(path)
+                                .file_name()
+                                .and_then(|n| n.to_str())
+                                .unwrap_or(path);
+                            if basename == name {
+                                return Ok(*id);
+                            }
+                        }
+
+                        Err(nfsstat3::NFS3ERR_NOENT)
                     }
-                    Err(nfsstat3::NFS3ERR_NOENT)
                 }
-            }
-            DATA_DIR_ID => {
-                if name == "data.csv" {
-                    Ok(DATA_CSV_ID)
-                } else {
-                    // Check created directories
+                id if self.layout.is_created_dir(id) => {
+                    // This is a created directory, check its children
                     let created_dirs = self.created_dirs.lock().await;
-                    if let Some(&dir_id) = created_dirs.get(&(DATA_DIR_ID, name.to_string())) {
-                        return Ok(dir_id);
-                    }
-                    drop(created_dirs);
-                    // Check created files
-                    let created_files = self.created_files.lock().await;
-                    if let Some(metadata) = created_files.get(&(DATA_DIR_ID, name.to_string())) {
-                        return Ok(metadata.file_id);
-                    }
-                    drop(created_files);
-
-                    // Check Parquet files
-                    self.refresh_parquet_files().await?;
-                    let parquet_files = self.parquet_files.lock().await;
-
-                    for (id, path) in parquet_files.iter() {
-                        let basename = std::path::Path::new(path)
-                            .file_name()
-                            .and_then(|n| n.to_str())
-                            .unwrap_or(path);
-                        if basename == name {
-                            return Ok(*id);
+                    // Check if this directory exists (it's a created directory)
+                    let dir_exists = created_dirs.values().any(|&did| did == id);
+                    if dir_exists {
+                        // Check if this directory has a child directory with the given name
+                        if let Some(&child_id) = created_dirs.get(&(id, name.to_string())) {
+                            return Ok(child_id);
+                        }
+                        drop(created_dirs);
+                        // Check if this directory has a child file with the given name
+                        let created_files = self.created_files.lock().await;
+                        if let Some(metadata) = created_files.get(&(id, name.to_string())) {
+                            return Ok(metadata.file_id);
                         }
                     }
-
                     Err(nfsstat3::NFS3ERR_NOENT)
                 }
-            }
-            id if id >= CREATED_DIR_START => {
-                // This is a created directory, check its children
-                let created_dirs = self.created_dirs.lock().await;
-                // Check if this directory exists (it's a created directory)
-                let dir_exists = created_dirs.values().any(|&did| did == id);
-                if dir_exists {
-                    // Check if this directory has a child directory with the given name
-                    if let Some(&child_id) = created_dirs.get(&(id, name.to_string())) {
-                        return Ok(child_id);
-                    }
-                    drop(created_dirs);
-                    // Check if this directory has a child file with the given name
-                    let created_files = self.created_files.lock().await;
-                    if let Some(metadata) = created_files.get(&(id, name.to_string())) {
-                        return Ok(metadata.file_id);
+                id if self.layout.is_partition(id) => match self.partition_entry(id).await? {
+                    PartitionEntry::Dir(parent) => self
+                        .lookup_partition(&parent, &name)
+                        .await?
+                        .ok_or(nfsstat3::NFS3ERR_NOENT),
+                    PartitionEntry::Csv(_) => Err(nfsstat3::NFS3ERR_NOTDIR),
+                },
+                id if id == self.layout.tables_dir => self.lookup_table(&name).await,
+                id if self.layout.is_table(id) => {
+                    let (TableEntry::Dir(table), db) = self.table_entry(id).await? else {
+                        return Err(nfsstat3::NFS3ERR_NOTDIR);
+                    };
+                    if let Some(db) = db {
+                        if name == "data.csv" {
+                            return self.table_id(&TableEntry::Csv(table)).await;
+                        }
+                        if Self::table_parquet_files(&db)?
+                            .iter()
+                            .any(|file| *file == *name)
+                        {
+                            return self
+                                .table_id(&TableEntry::Parquet(table, name.to_string()))
+                                .await;
+                        }
                     }
+                    self.table_dirs
+                        .find(&TableEntry::Upload(table, name.to_string()))
+                        .await
+                        .ok_or(nfsstat3::NFS3ERR_NOENT)
                 }
-                Err(nfsstat3::NFS3ERR_NOENT)
+                _ => Err(nfsstat3::NFS3ERR_NOTDIR),
             }
-            _ => Err(nfsstat3::NFS3ERR_NOTDIR),
-        }
+        })
+        .await
     }
 
     async fn getattr(&self, id: fileid3) -> std::result::Result<fattr3, nfsstat3> {
-        info!("NFS GETATTR: id={}", id);
+        self.with_deadline("GETATTR", async move {
+            info!("NFS GETATTR: id={}", id);
 
-        // Check attribute cache first
-        if let Some(cached_attr) = self.attr_cache.get(id).await {
-            return Ok(cached_attr);
-        }
+            // data.csv's size changes with writes through other exports too
+            if id == self.layout.data_csv {
+                self.revalidate_csv_attr().await;
+            }
 
-        // Cache miss - compute attributes
-        let attr = match id {
-            ROOT_ID => Self::dir_attr(ROOT_ID),
-            DATA_DIR_ID => Self::dir_attr(DATA_DIR_ID),
-            id if id >= CREATED_DIR_START => {
-                // Check if this is a created directory
-                let created_dirs = self.created_dirs.lock().await;
-                let exists = created_dirs.values().any(|&did| did == id);
-                drop(created_dirs);
-                if exists {
-                    Self::dir_attr(id)
-                } else {
-                    return Err(nfsstat3::NFS3ERR_NOENT);
-                }
+            // Check attribute cache first
+            if let Some(cached_attr) = self.attr_cache.get(id).await {
+                return Ok(cached_attr);
             }
-            DATA_CSV_ID => {
-                // Call size() without holding the lock across await
-                let db = self.db.clone();
-                let view = CsvFileView::new(db);
-                let size = match view.size().await {
-                    Ok(s) => s,
-                    Err(e) => {
-                        error!("Failed to get CSV size: {}", e);
-                        0
+
+            // Cache miss - compute attributes
+            let attr = match id {
+                id if id == self.layout.root => Self::dir_attr(self.layout.root),
+                id if id == self.layout.data_dir => Self::dir_attr(self.layout.data_dir),
+                id if id == self.layout.tables_dir => Self::dir_attr(self.layout.tables_dir),
+                id if self.layout.is_created_dir(id) => {
+                    // Check if this is a created directory
+                    let created_dirs = self.created_dirs.lock().await;
+                    let exists = created_dirs.values().any(|&did| did == id);
+                    drop(created_dirs);
+                    if exists {
+                        Self::dir_attr(id)
+                    } else {
+                        return Err(nfsstat3::NFS3ERR_NOENT);
                     }
-                };
-                Self::file_attr(DATA_CSV_ID, size)
-            }
-            id if (PARQUET_FILE_ID_START..CREATED_DIR_START).contains(&id) => {
-                // Parquet file (Delta Lake mode)
-                let parquet_files = self.parquet_files.lock().await;
-                if let Some(file_path) = parquet_files.get(&id) {
+                }
+                id if id == self.layout.data_csv => {
+                    let size = match self.csv_size().await {
+                        Ok(s) => s,
+                        Err(e) => {
+                            error!("Failed to get CSV size: {}", e);
+                            0
+                        }
+                    };
+                    Self::file_attr(self.layout.data_csv, size)
+                }
+                id if id == self.layout.stats_json => {
+                    let size = self.file_stats_content().await?.len() as u64;
+                    Self::file_attr(id, size)
+                }
+                id if self.layout.is_parquet_file(id) => {
+                    // Parquet file (Delta Lake mode)
+                    let file_path = self.parquet_file(id).await?;
                     // Get file size directly from filesystem
-                    let full_path = self.db.base_path().join(file_path);
+                    let full_path = self.db.base_path().join(&file_path);
                     if let Ok(metadata) = std::fs::metadata(&full_path) {
                         Self::file_attr(id, metadata.len())
                     } else {
+                        // Removed since it was listed
                         error!("File not found: {}", file_path);
-                        return Err(nfsstat3::NFS3ERR_NOENT);
+                        return Err(nfsstat3::NFS3ERR_STALE);
                     }
-                } else {
-                    return Err(nfsstat3::NFS3ERR_NOENT);
                 }
-            }
-            id if id >= CREATED_FILE_START => {
-                // Created file - use stored timestamps for stability
-                let created_files = self.created_files.lock().await;
-                if let Some(metadata) = created_files.values().find(|m| m.file_id == id) {
-                    let size = metadata.content.len() as u64;
-                    let attr = fattr3 {
-                        ftype: ftype3::NF3REG,
-                        mode: 0o644,
-                        nlink: 1,
-                        uid: 1000,
-                        gid: 1000,
-                        size,
-                        used: size,
-                        rdev: specdata3::default(),
-                        fsid: 0,
-                        fileid: id,
-                        atime: metadata.atime,
-                        mtime: metadata.mtime,
-                        ctime: metadata.ctime,
-                    };
-                    drop(created_files);
-                    attr
-                } else {
-                    return Err(nfsstat3::NFS3ERR_NOENT);
+                id if self.layout.is_created_file(id) => {
+                    // Created file - use stored timestamps for stability
+                    let created_files = self.created_files.lock().await;
+                    if let Some(metadata) = created_files.values().find(|m| m.file_id == id) {
+                        let size = metadata.content.len() as u64;
+                        let attr = fattr3 {
+                            ftype: ftype3::NF3REG,
+                            mode: 0o644,
+                            nlink: 1,
+                            uid: 1000,
+                            gid: 1000,
+                            size,
+                            used: size,
+                            rdev: specdata3::default(),
+                            fsid: 0,
+                            fileid: id,
+                            atime: metadata.atime,
+                            mtime: metadata.mtime,
+                            ctime: metadata.ctime,
+                        };
+                        drop(created_files);
+                        attr
+                    } else {
+                        return Err(nfsstat3::NFS3ERR_NOENT);
+                    }
                 }
-            }
-            _ => return Err(nfsstat3::NFS3ERR_NOENT),
-        };
-
-        // Store in cache
-        self.attr_cache.set(id, attr).await;
-
-        Ok(attr)
+                id if self.layout.is_partition(id) => match self.partition_entry(id).await? {
+                    PartitionEntry::Dir(_) => Self::dir_attr(id),
+                    PartitionEntry::Csv(partition) => self.partition_csv_attr(id, &partition).await,
+                },
+                id if self.layout.is_table(id) => match self.table_entry(id).await? {
+                    (TableEntry::Dir(_), _) => Self::dir_attr(id),
+                    (TableEntry::Csv(_), Some(db)) => self.table_csv_attr(id, &db).await,
+                    (TableEntry::Parquet(_, file), Some(db)) => {
+                        Self::table_parquet_attr(id, &db, &file)?
+                    }
+                    (TableEntry::Upload(..), _) => self.upload_attr(id).await,
+                    _ => return Err(nfsstat3::NFS3ERR_STALE),
+                },
+                _ => return Err(nfsstat3::NFS3ERR_NOENT),
+            };
+
+            // Store in cache
+            self.attr_cache.set(id, attr).await;
+
+            Ok(attr)
+        })
+        .await
     }
 
     async fn setattr(&self, id: fileid3, setattr: sattr3) -> std::result::Result<fattr3, nfsstat3> {
         info!("NFS SETATTR: id={}, setattr={:?}", id, setattr);
+        self.check_writable("SETATTR")?;
+        self.check_table_access(id)?;
 
         // For created files, acknowledge setattr but preserve stable timestamps
-        if id >= CREATED_FILE_START {
+        if self.layout.is_created_file(id) {
             let created_files = self.created_files.lock().await;
             if let Some(metadata) = created_files.values().find(|m| m.file_id == id) {
                 let size = metadata.content.len() as u64;
@@ -391,24 +1901,58 @@ impl NFSFileSystem for FsdbFilesystem {
             }
         }
 
+        // A partition's data.csv is only ever appended to, so truncation is ignored
+        if self.layout.is_partition(id) {
+            if let PartitionEntry::Csv(partition) = self.partition_entry(id).await? {
+                let attr = self.partition_csv_attr(id, &partition).await;
+                self.attr_cache.set(id, attr).await;
+                return Ok(attr);
+            }
+        }
+
+        // A copied file's rows are in the table already; truncating it to
+        // nothing starts a new copy of it, loaded from the top
+        if self.layout.is_table(id) {
+            if let (TableEntry::Upload(..), _) = self.table_entry(id).await? {
+                if matches!(setattr.size, set_size3::size(0)) {
+                    self.pending_writes.take(id).await;
+                    self.table_dirs.set_upload(id, Upload::default()).await;
+                }
+                let attr = self.upload_attr(id).await;
+                self.attr_cache.set(id, attr).await;
+                return Ok(attr);
+            }
+        }
+
         // For other files (data.csv, directories), not supported
         info!("SETATTR not supported for ID {}", id);
         Err(nfsstat3::NFS3ERR_NOTSUPP)
     }
 
+    #[instrument(
+        name = "nfs.read",
+        skip_all,
+        fields(table = TABLE_NAME, fileid = id, offset = offset, count = count)
+    )]
     async fn read(
         &self,
         id: fileid3,
         offset: u64,
         count: u32,
     ) -> std::result::Result<(Vec<u8>, bool), nfsstat3> {
-        info!("NFS READ: id={}, offset={}, count={}", id, offset, count);
+        self.with_deadline("READ", async move {
+            info!("NFS READ: id={}, offset={}, count={}", id, offset, count);
+            self.check_table_access(id)?;
+
+            match id {
+                id if id == self.layout.data_csv => {
+                    // A read from the start is what a later overwrite is based on
+                    if offset == 0 {
+                        self.record_read_base().await;
+                    }
 
-        match id {
-            DATA_CSV_ID => {
-                // Try cache first if enabled
-                if let Some(ref cache) = self.cache {
-                    if let Ok(Some(cached_content)) = cache.get("csv:data").await {
+                    // Try cache first if enabled
+                    if let Some(cached_content) = self.cached_csv().await {
                         info!("Cache HIT for data.csv");
                         let end = (offset + count as u64).min(cached_content.len() as u64) as usize;
                         let start = offset.min(cached_content.len() as u64) as usize;
@@ -416,167 +1960,165 @@ impl NFSFileSystem for FsdbFilesystem {
                         let eof = end >= cached_content.len();
                         return Ok((data, eof));
                     }
-                }
 
-                // Cache miss or no cache - generate content without holding lock
-                let db = self.db.clone();
-                let view = CsvFileView::new(db);
+                    // Cache miss or no cache - stream the content, one version of
+                    // it for a sequence of reads, so a large value spanning
+                    // several reads comes back from the same content
+                    self.read_csv(offset, count).await.map_err(|e| {
+                        error!("Read error: {}", e);
+                        nfsstat3::NFS3ERR_IO
+                    })
+                }
+                id if self.layout.is_created_file(id) => {
+                    // Read created file
+                    let created_files = self.created_files.lock().await;
+                    if let Some(metadata) = created_files.values().find(|m| m.file_id == id) {
+                        let offset_usize = offset as usize;
+                        let count_usize = count as usize;
+                        let end = (offset_usize + count_usize).min(metadata.content.len());
+                        let start = offset_usize.min(metadata.content.len());
+                        let data = if start < metadata.content.len() {
+                            metadata.content[start..end].to_vec()
+                        } else {
+                            Vec::new()
+                        };
+                        let eof = end >= metadata.content.len();
+                        drop(created_files);
+                        Ok((data, eof))
+                    } else {
+                        Err(nfsstat3::NFS3ERR_NOENT)
+                    }
+                }
+                id if id == self.layout.stats_json => {
+                    let content = self.file_stats_content().await?;
+                    let start = offset.min(content.len() as u64) as usize;
+                    let end = (offset + count as u64).min(content.len() as u64) as usize;
+                    Ok((content[start..end].to_vec(), end >= content.len()))
+                }
+                id if self.layout.is_parquet_file(id) => {
+                    // Read individual Parquet file as CSV
+                    let file_path = self.parquet_file(id).await?;
+                    let cache_key = format!("csv:file:{}", file_path);
+
+                    // Try cache first if enabled
+                    if let Some(cache) = self.content_cache() {
+                        if let Ok(Some(cached_content)) = cache.get(&cache_key).await {
+                            info!("Cache HIT for {}", file_path);
+                            let end =
+                                (offset + count as u64).min(cached_content.len() as u64) as usize;
+                            let start = offset.min(cached_content.len() as u64) as usize;
+                            let data = cached_content[start..end].to_vec();
+                            let eof = end >= cached_content.len();
+                            return Ok((data, eof));
+                        }
+                    }
 
-                let data = view.read(offset, count).await.map_err(|e| {
-                    error!("Read error: {}", e);
-                    nfsstat3::NFS3ERR_IO
-                })?;
+                    // Cache miss - generate content
+                    let file_view = CsvFileView::new_for_file(self.db.clone(), file_path.clone())
+                        .with_coercion(self.coercion.clone())
+                        .with_reader(self.auth.clone());
+                    let data = file_view.read(offset, count).await.map_err(|e| {
+                        error!("Read error for Parquet file: {}", e);
+                        nfsstat3::NFS3ERR_IO
+                    })?;
 
-                // Store in cache if enabled (only on first read, offset==0)
-                if offset == 0 {
-                    if let Some(ref cache) = self.cache {
-                        if let Ok(full_content) = view.get_full_content().await {
-                            let _ = cache.insert("csv:data".to_string(), full_content).await;
+                    // Store in cache if enabled (only on first read)
+                    if offset == 0 {
+                        if let Some(cache) = self.content_cache() {
+                            if let Ok(full_content) = file_view.get_full_content().await {
+                                let _ = cache.insert(cache_key, full_content).await;
+                            }
                         }
                     }
-                }
 
-                let size = view.size().await.unwrap_or(0);
-                let eof = offset + data.len() as u64 >= size;
-                Ok((data, eof))
-            }
-            id if id >= CREATED_FILE_START => {
-                // Read created file
-                let created_files = self.created_files.lock().await;
-                if let Some(metadata) = created_files.values().find(|m| m.file_id == id) {
-                    let offset_usize = offset as usize;
-                    let count_usize = count as usize;
-                    let end = (offset_usize + count_usize).min(metadata.content.len());
-                    let start = offset_usize.min(metadata.content.len());
-                    let data = if start < metadata.content.len() {
-                        metadata.content[start..end].to_vec()
-                    } else {
-                        Vec::new()
-                    };
-                    let eof = end >= metadata.content.len();
-                    drop(created_files);
+                    let size = file_view.size().await.unwrap_or(0);
+                    let eof = offset + data.len() as u64 >= size;
                     Ok((data, eof))
-                } else {
-                    Err(nfsstat3::NFS3ERR_NOENT)
                 }
-            }
-            id if (PARQUET_FILE_ID_START..CREATED_DIR_START).contains(&id) => {
-                // Read individual Parquet file as CSV
-                let file_path = {
-                    let parquet_files = self.parquet_files.lock().await;
-                    parquet_files
-                        .get(&id)
-                        .ok_or(nfsstat3::NFS3ERR_NOENT)?
-                        .clone()
-                };
-                let cache_key = format!("csv:file:{}", file_path);
-
-                // Try cache first if enabled
-                if let Some(ref cache) = self.cache {
-                    if let Ok(Some(cached_content)) = cache.get(&cache_key).await {
-                        info!("Cache HIT for {}", file_path);
-                        let end = (offset + count as u64).min(cached_content.len() as u64) as usize;
-                        let start = offset.min(cached_content.len() as u64) as usize;
-                        let data = cached_content[start..end].to_vec();
-                        let eof = end >= cached_content.len();
-                        return Ok((data, eof));
-                    }
+                id if self.layout.is_partition(id) => {
+                    let PartitionEntry::Csv(partition) = self.partition_entry(id).await? else {
+                        return Err(nfsstat3::NFS3ERR_ISDIR);
+                    };
+                    let view = CsvFileView::new_for_partition(self.db.clone(), partition)
+                        .with_coercion(self.coercion.clone())
+                        .with_reader(self.auth.clone());
+                    let read = async {
+                        let mut reader = CsvStreamReader::new(view.stream().await?);
+                        reader.read_at(offset, count as u64).await
+                    };
+                    read.await.map_err(|e| {
+                        error!("Read error for partition CSV: {}", e);
+                        nfsstat3::NFS3ERR_IO
+                    })
                 }
-
-                // Cache miss - generate content
-                let file_view = CsvFileView::new_for_file(self.db.clone(), file_path.clone());
-                let data = file_view.read(offset, count).await.map_err(|e| {
-                    error!("Read error for Parquet file: {}", e);
-                    nfsstat3::NFS3ERR_IO
-                })?;
-
-                // Store in cache if enabled (only on first read)
-                if offset == 0 {
-                    if let Some(ref cache) = self.cache {
-                        if let Ok(full_content) = file_view.get_full_content().await {
-                            let _ = cache.insert(cache_key, full_content).await;
+                id if self.layout.is_table(id) => {
+                    let view = match self.table_entry(id).await? {
+                        (TableEntry::Dir(_), _) => return Err(nfsstat3::NFS3ERR_ISDIR),
+                        (TableEntry::Csv(_), Some(db)) => CsvFileView::new(db),
+                        (TableEntry::Parquet(_, file), Some(db)) => {
+                            CsvFileView::new_for_file(db, file)
                         }
+                        (TableEntry::Upload(..), _) => return Ok((Vec::new(), true)),
+                        _ => return Err(nfsstat3::NFS3ERR_STALE),
                     }
+                    .with_coercion(self.coercion.clone())
+                    .with_reader(self.auth.clone());
+                    let read = async {
+                        let mut reader = CsvStreamReader::new(view.stream().await?);
+                        reader.read_at(offset, count as u64).await
+                    };
+                    read.await.map_err(|e| {
+                        error!("Read error for table CSV: {}", e);
+                        nfsstat3::NFS3ERR_IO
+                    })
                 }
-
-                let size = file_view.size().await.unwrap_or(0);
-                let eof = offset + data.len() as u64 >= size;
-                Ok((data, eof))
+                _ => Err(nfsstat3::NFS3ERR_ISDIR),
             }
-            _ => Err(nfsstat3::NFS3ERR_ISDIR),
-        }
+        })
+        .await
     }
 
+    #[instrument(
+        name = "nfs.write",
+        skip_all,
+        fields(table = TABLE_NAME, fileid = id, offset = offset, count = data.len())
+    )]
     async fn write(
         &self,
         id: fileid3,
-        _offset: u64,
+        offset: u64,
         data: &[u8],
     ) -> std::result::Result<fattr3, nfsstat3> {
-        info!("NFS WRITE: id={}, data_len={}", id, data.len());
+        info!(
+            "NFS WRITE: id={}, offset={}, data_len={}",
+            id,
+            offset,
+            data.len()
+        );
+        self.check_writable("WRITE")?;
+        self.check_table_access(id)?;
 
         match id {
-            DATA_CSV_ID => {
-                // Fetch cached content BEFORE invalidating (for performance)
-                let cached_content = if let Some(ref cache) = self.cache {
-                    match cache.get("csv:data").await {
-                        Ok(Some(content)) => {
-                            info!("Using cached CSV for write diff ({} bytes)", content.len());
-                            Some(content)
-                        }
-                        _ => {
-                            debug!("No cached CSV available for write diff");
-                            None
-                        }
-                    }
-                } else {
-                    None
+            id if id == self.layout.data_csv => {
+                // Chunks are applied once they form whole rows; a new write
+                // must start at the beginning or the end of the file
+                let current_size = self.attr_cache.get(id).await.map(|attr| attr.size);
+                let ready = |write: &PendingWrite| {
+                    write.is_complete()
+                        && (write.offset() == 0
+                            || current_size.is_none_or(|size| size == write.offset()))
                 };
-
-                // Don't hold lock across await - create temporary view
-                let db = self.db.clone();
-                let view = CsvFileView::new(db);
-
-                view.apply_write(data, cached_content).await.map_err(|e| {
-                    error!("Write error: {}", e);
-                    nfsstat3::NFS3ERR_IO
-                })?;
-
-                // UPDATE content cache after write (don't invalidate!)
-                // This keeps subsequent reads fast by avoiding CSV regeneration
-                if let Some(ref cache) = self.cache {
-                    // Generate fresh CSV content after write
-                    let fresh_csv = view.generate_csv().await.map_err(|e| {
-                        error!("Failed to generate CSV for cache: {}", e);
-                        nfsstat3::NFS3ERR_IO
-                    })?;
-                    let fresh_size = fresh_csv.len();
-
-                    // Update cache with new content
-                    if let Err(e) = cache.insert("csv:data".to_string(), fresh_csv).await {
-                        error!("Failed to update cache after write: {}", e);
-                        // Don't fail the write if cache update fails
-                    } else {
-                        info!(
-                            "Content cache UPDATED for data.csv after write ({} bytes)",
-                            fresh_size
-                        );
+                match self.pending_writes.add(id, offset, data, ready).await {
+                    Some(write) => self.apply_csv_write(write.data()).await,
+                    None => {
+                        let end = self.pending_writes.end(id).await.unwrap_or(0);
+                        debug!("Write to data.csv pending until row boundary or COMMIT");
+                        let size = current_size.unwrap_or(0).max(end);
+                        Ok(Self::file_attr(id, size))
                     }
                 }
-
-                // IMPORTANT: Update attr_cache with NEW file size after write
-                // We must return accurate file size or OS NFS clients will truncate reads!
-                let size = view.size().await.unwrap_or(0);
-                let attr = Self::file_attr(DATA_CSV_ID, size);
-                self.attr_cache.set(DATA_CSV_ID, attr).await;
-                info!(
-                    "Write completed, attr cache updated with new size: {} bytes",
-                    size
-                );
-
-                Ok(attr)
             }
-            id if id >= CREATED_FILE_START => {
+            id if self.layout.is_created_file(id) => {
                 // Write to created file - preserve timestamps
                 let mut created_files = self.created_files.lock().await;
                 if let Some(metadata) = created_files.values_mut().find(|m| m.file_id == id) {
@@ -617,6 +2159,54 @@ impl NFSFileSystem for FsdbFilesystem {
                     Err(nfsstat3::NFS3ERR_NOENT)
                 }
             }
+            id if self.layout.is_partition(id) => {
+                let PartitionEntry::Csv(partition) = self.partition_entry(id).await? else {
+                    return Err(nfsstat3::NFS3ERR_ISDIR);
+                };
+                // Every write appends, so only whole rows matter
+                match self
+                    .pending_writes
+                    .add(id, offset, data, PendingWrite::is_complete)
+                    .await
+                {
+                    Some(write) => {
+                        self.apply_partition_write(id, &partition, write.data())
+                            .await
+                    }
+                    None => {
+                        debug!("Write to partition data.csv pending until row boundary or COMMIT");
+                        let end = self.pending_writes.end(id).await.unwrap_or(0);
+                        Ok(Self::file_attr(id, end))
+                    }
+                }
+            }
+            id if self.layout.is_table(id) => {
+                let db = match self.table_entry(id).await? {
+                    (TableEntry::Csv(_), Some(db)) => db,
+                    (TableEntry::Upload(table, _), _) => {
+                        return self.write_upload(id, &table, offset, data).await;
+                    }
+                    (TableEntry::Dir(_), _) => return Err(nfsstat3::NFS3ERR_ISDIR),
+                    (TableEntry::Parquet(..), _) => return Err(nfsstat3::NFS3ERR_ROFS),
+                    (TableEntry::Csv(_), None) => return Err(nfsstat3::NFS3ERR_STALE),
+                };
+                // Same rules as /data/data.csv: whole rows, from the start or the end
+                let current_size = self.attr_cache.get(id).await.map(|attr| attr.size);
+                let ready = |write: &PendingWrite| {
+                    write.is_complete()
+                        && (write.offset() == 0
+                            || current_size.is_none_or(|size| size == write.offset()))
+                };
+                match self.pending_writes.add(id, offset, data, ready).await {
+                    Some(write) => self.apply_table_write(id, &db, write.data()).await,
+                    None => {
+                        debug!("Write to table data.csv pending until row boundary or COMMIT");
+                        let end = self.pending_writes.end(id).await.unwrap_or(0);
+                        let size = current_size.unwrap_or(0).max(end);
+                        Ok(Self::file_attr(id, size))
+                    }
+                }
+            }
             _ => Err(nfsstat3::NFS3ERR_ROFS),
         }
     }
@@ -629,18 +2219,76 @@ impl NFSFileSystem for FsdbFilesystem {
     ) -> std::result::Result<(fileid3, fattr3), nfsstat3> {
         let name = String::from_utf8_lossy(filename.as_ref());
         info!("NFS CREATE: dir={}, filename={}", dirid, name);
+        self.check_writable("CREATE")?;
+        self.check_table_access(dirid)?;
+        limits::check_name(filename.as_ref())?;
+
+        // Opening a partition's data.csv for writing creates it, but it always exists
+        if self.layout.is_partition(dirid) {
+            let entry = self.partition_entry(dirid).await?;
+            let columns = self.partition_columns();
+            return match entry {
+                PartitionEntry::Dir(partition)
+                    if partition.len() == columns.len() && name == "data.csv" =>
+                {
+                    let id = self
+                        .partition_id(&PartitionEntry::Csv(partition.clone()))
+                        .await?;
+                    Ok((id, self.partition_csv_attr(id, &partition).await))
+                }
+                PartitionEntry::Dir(_) => {
+                    error!("Only data.csv can be created in a partition directory");
+                    Err(nfsstat3::NFS3ERR_ACCES)
+                }
+                PartitionEntry::Csv(_) => Err(nfsstat3::NFS3ERR_NOTDIR),
+            };
+        }
+
+        // Likewise a named table's data.csv. Other CSV files copied into a
+        // table's directory are loaded into the table, which the first one
+        // creates if the directory was made with MKDIR.
+        if self.layout.is_table(dirid) {
+            return match self.table_entry(dirid).await? {
+                (TableEntry::Dir(table), Some(db)) if name == "data.csv" => {
+                    let id = self.table_id(&TableEntry::Csv(table)).await?;
+                    Ok((id, self.table_csv_attr(id, &db).await))
+                }
+                (TableEntry::Dir(table), _) if name.ends_with(".csv") => {
+                    let id = self
+                        .table_id(&TableEntry::Upload(table, name.to_string()))
+                        .await?;
+                    Ok((id, self.upload_attr(id).await))
+                }
+                (TableEntry::Dir(_), _) => {
+                    error!("Only CSV files can be created in a table directory");
+                    Err(nfsstat3::NFS3ERR_ACCES)
+                }
+                _ => Err(nfsstat3::NFS3ERR_NOTDIR),
+            };
+        }
+        if dirid == self.layout.tables_dir {
+            error!("Files can't be created in /{}", TABLES_DIR);
+            return Err(nfsstat3::NFS3ERR_ACCES);
+        }
 
         // Only allow creating files in root, data directory, or created directories
-        if dirid != ROOT_ID && dirid != DATA_DIR_ID && dirid < CREATED_DIR_START {
+        if dirid != self.layout.root
+            && dirid != self.layout.data_dir
+            && !self.layout.is_created_dir(dirid)
+        {
             error!("create not allowed in directory {}", dirid);
             return Err(nfsstat3::NFS3ERR_NOTDIR);
         }
 
         // Don't allow creating data.csv (it's special)
-        if dirid == DATA_DIR_ID && name == "data.csv" {
+        if dirid == self.layout.data_dir && name == "data.csv" {
             error!("Cannot create data.csv - it's a special file");
             return Err(nfsstat3::NFS3ERR_EXIST);
         }
+        if dirid == self.layout.root && name == TABLES_DIR {
+            error!("Cannot create {} - it's a special directory", TABLES_DIR);
+            return Err(nfsstat3::NFS3ERR_EXIST);
+        }
 
         // Check if file already exists
         let created_files = self.created_files.lock().await;
@@ -654,6 +2302,10 @@ impl NFSFileSystem for FsdbFilesystem {
         // Allocate new file ID
         let mut next_id = self.next_file_id.lock().await;
         let new_file_id = *next_id;
+        if !self.layout.is_created_file(new_file_id) {
+            error!("File ID range {:?} exhausted", self.layout.created_files);
+            return Err(nfsstat3::NFS3ERR_NOSPC);
+        }
         *next_id += 1;
         drop(next_id);
 
@@ -704,19 +2356,92 @@ impl NFSFileSystem for FsdbFilesystem {
         _dirid: fileid3,
         _filename: &filename3,
     ) -> std::result::Result<fileid3, nfsstat3> {
+        self.check_writable("CREATE")?;
         Err(nfsstat3::NFS3ERR_NOTSUPP)
     }
 
-    async fn mkdir(
-        &self,
-        dirid: fileid3,
-        dirname: &filename3,
-    ) -> std::result::Result<(fileid3, fattr3), nfsstat3> {
-        let name = String::from_utf8_lossy(dirname.as_ref());
-        info!("NFS MKDIR: dir={}, dirname={}", dirid, name);
+    async fn mkdir(
+        &self,
+        dirid: fileid3,
+        dirname: &filename3,
+    ) -> std::result::Result<(fileid3, fattr3), nfsstat3> {
+        let name = String::from_utf8_lossy(dirname.as_ref());
+        info!("NFS MKDIR: dir={}, dirname={}", dirid, name);
+        self.check_writable("MKDIR")?;
+        self.check_table_access(dirid)?;
+        limits::check_name(dirname.as_ref())?;
+
+        // A directory named for the next partition column starts a new partition
+        let parent = if dirid == self.layout.data_dir {
+            Some(Vec::new())
+        } else if self.layout.is_partition(dirid) {
+            match self.partition_entry(dirid).await? {
+                PartitionEntry::Dir(parent) => Some(parent),
+                PartitionEntry::Csv(_) => return Err(nfsstat3::NFS3ERR_NOTDIR),
+            }
+        } else {
+            None
+        };
+        if let Some(parent) = parent {
+            let columns = self.partition_columns();
+            match Self::partition_child(&columns, &parent, &name) {
+                Some(child) => {
+                    if children(&self.known_partitions().await?, &parent).contains(&child) {
+                        return Err(nfsstat3::NFS3ERR_EXIST);
+                    }
+                    if let Err(e) = check_values(&self.db.schema(), &child) {
+                        error!("Invalid partition directory {}: {}", name, e);
+                        return Err(nfsstat3::NFS3ERR_INVAL);
+                    }
+                    self.partition_dirs.make(child.clone()).await;
+                    let id = self.partition_id(&PartitionEntry::Dir(child)).await?;
+                    let attr = Self::dir_attr(id);
+                    self.attr_cache.set(id, attr).await;
+                    info!("Created partition directory {} with ID {}", name, id);
+                    return Ok((id, attr));
+                }
+                None if !parent.is_empty() => {
+                    error!(
+                        "mkdir in a partition directory needs a {}=<value> name",
+                        columns[parent.len()]
+                    );
+                    return Err(nfsstat3::NFS3ERR_ACCES);
+                }
+                None => {}
+            }
+        }
+
+        // A directory made in /tables becomes a table once a CSV file is
+        // copied into it
+        if dirid == self.layout.tables_dir {
+            if self.named_table(&name).await?.is_some() || self.table_dirs.is_made(&name).await {
+                return Err(nfsstat3::NFS3ERR_EXIST);
+            }
+            if let Err(e) = crate::metadata::catalog::check_table_name(&name) {
+                error!("Invalid table directory {}: {}", name, e);
+                return Err(nfsstat3::NFS3ERR_INVAL);
+            }
+            if !self.has_permission(&crate::security::Permission::Admin) {
+                error!("Making table directory {} needs Admin", name);
+                return Err(nfsstat3::NFS3ERR_ACCES);
+            }
+            self.table_dirs.make(name.to_string()).await;
+            let id = self.table_id(&TableEntry::Dir(name.to_string())).await?;
+            let attr = Self::dir_attr(id);
+            self.attr_cache.set(id, attr).await;
+            info!("Created table directory {} with ID {}", name, id);
+            return Ok((id, attr));
+        }
+        if self.layout.is_table(dirid) {
+            error!("mkdir not allowed in a table directory");
+            return Err(nfsstat3::NFS3ERR_ACCES);
+        }
+        if dirid == self.layout.root && name == TABLES_DIR {
+            return Err(nfsstat3::NFS3ERR_EXIST);
+        }
 
         // Only allow creating directories in root or data directory
-        if dirid != ROOT_ID && dirid != DATA_DIR_ID {
+        if dirid != self.layout.root && dirid != self.layout.data_dir {
             error!("mkdir not allowed in directory {}", dirid);
             return Err(nfsstat3::NFS3ERR_NOTDIR);
         }
@@ -733,6 +2458,13 @@ impl NFSFileSystem for FsdbFilesystem {
         // Allocate new directory ID
         let mut next_id = self.next_dir_id.lock().await;
         let new_dir_id = *next_id;
+        if !self.layout.is_created_dir(new_dir_id) {
+            error!(
+                "Directory ID range {:?} exhausted",
+                self.layout.created_dirs
+            );
+            return Err(nfsstat3::NFS3ERR_NOSPC);
+        }
         *next_id += 1;
         drop(next_id);
 
@@ -761,14 +2493,16 @@ impl NFSFileSystem for FsdbFilesystem {
     ) -> std::result::Result<(), nfsstat3> {
         let filename_str = String::from_utf8_lossy(filename);
         info!("NFS REMOVE: dir={}, file={}", dirid, filename_str);
+        self.check_writable("REMOVE")?;
+        self.check_table_access(dirid)?;
 
         // Only support deletion of data.csv from /data directory (truncate table)
-        if dirid == DATA_DIR_ID && filename_str == "data.csv" {
+        if dirid == self.layout.data_dir && filename_str == "data.csv" {
             info!("Deleting data.csv - truncating table");
 
             // Delete all rows using deletion vectors (efficient, no rewrite)
             let db = self.db.clone();
-            db.delete_rows_where("1=1").await.map_err(|e| {
+            let deleted = db.delete_rows_where("1=1").await.map_err(|e| {
                 error!("Failed to truncate table: {}", e);
                 nfsstat3::NFS3ERR_IO
             })?;
@@ -779,7 +2513,27 @@ impl NFSFileSystem for FsdbFilesystem {
                 info!("Content cache invalidated for data.csv after deletion");
             }
 
-            info!("Table truncated successfully");
+            info!(
+                "Table truncated successfully: {} rows deleted",
+                deleted.rows_affected
+            );
+            Ok(())
+        } else if self.layout.is_table(dirid) && filename_str == "data.csv" {
+            let (TableEntry::Dir(table), db) = self.table_entry(dirid).await? else {
+                return Err(nfsstat3::NFS3ERR_NOTDIR);
+            };
+            let db = db.ok_or(nfsstat3::NFS3ERR_NOENT)?;
+            info!("Deleting data.csv of {} - truncating table", table);
+            let deleted = db.delete_rows_where("1=1").await.map_err(|e| {
+                error!("Failed to truncate table {}: {}", table, e);
+                nfsstat3::NFS3ERR_IO
+            })?;
+            let csv = self.table_id(&TableEntry::Csv(table)).await?;
+            self.attr_cache.invalidate(csv).await;
+            info!(
+                "Table truncated successfully: {} rows deleted",
+                deleted.rows_affected
+            );
             Ok(())
         } else {
             // Other file deletions not supported
@@ -801,6 +2555,10 @@ impl NFSFileSystem for FsdbFilesystem {
             "NFS RENAME: from_dir={}, from={}, to_dir={}, to={}",
             from_dirid, from_name, to_dirid, to_name
         );
+        self.check_writable("RENAME")?;
+        self.check_table_access(from_dirid)?;
+        self.check_table_access(to_dirid)?;
+        limits::check_name(to_filename.as_ref())?;
 
         // Try to rename a created file
         let created_files = self.created_files.lock().await;
@@ -818,10 +2576,7 @@ impl NFSFileSystem for FsdbFilesystem {
             let to_file_key = (to_dirid, to_name.to_string());
             if created_files.contains_key(&to_file_key) {
                 drop(created_files);
-                error!(
-                    "Target file {} already exists in dir {}",
-                    to_name, to_dirid
-                );
+                error!("Target file {} already exists in dir {}", to_name, to_dirid);
                 return Err(nfsstat3::NFS3ERR_EXIST);
             }
             drop(created_files); // Drop lock IMMEDIATELY
@@ -833,26 +2588,28 @@ impl NFSFileSystem for FsdbFilesystem {
             drop(created_files); // Drop lock IMMEDIATELY
 
             // Update attr_cache to prevent getattr failures
-            self.attr_cache.set(file_metadata.file_id, fattr3 {
-                ftype: ftype3::NF3REG,
-                mode: 0o644,
-                nlink: 1,
-                uid: 1000,
-                gid: 1000,
-                size: file_metadata.content.len() as u64,
-                used: file_metadata.content.len() as u64,
-                rdev: specdata3::default(),
-                fsid: 0,
-                fileid: file_metadata.file_id,
-                atime: file_metadata.atime,
-                mtime: file_metadata.mtime,
-                ctime: file_metadata.ctime,
-            }).await;
-
-            info!(
-                "File renamed successfully: {} -> {}",
-                from_name, to_name
-            );
+            self.attr_cache
+                .set(
+                    file_metadata.file_id,
+                    fattr3 {
+                        ftype: ftype3::NF3REG,
+                        mode: 0o644,
+                        nlink: 1,
+                        uid: 1000,
+                        gid: 1000,
+                        size: file_metadata.content.len() as u64,
+                        used: file_metadata.content.len() as u64,
+                        rdev: specdata3::default(),
+                        fsid: 0,
+                        fileid: file_metadata.file_id,
+                        atime: file_metadata.atime,
+                        mtime: file_metadata.mtime,
+                        ctime: file_metadata.ctime,
+                    },
+                )
+                .await;
+
+            info!("File renamed successfully: {} -> {}", from_name, to_name);
             return Ok(());
         }
         drop(created_files); // Drop lock IMMEDIATELY
@@ -900,10 +2657,7 @@ impl NFSFileSystem for FsdbFilesystem {
         drop(created_dirs); // Drop lock IMMEDIATELY
 
         // Cannot rename built-in files/directories (data.csv, data/, etc.)
-        error!(
-            "Cannot rename built-in file/directory: {}",
-            from_name
-        );
+        error!("Cannot rename built-in file/directory: {}", from_name);
         Err(nfsstat3::NFS3ERR_ACCES)
     }
 
@@ -913,200 +2667,268 @@ impl NFSFileSystem for FsdbFilesystem {
         start_after: fileid3,
         max_entries: usize,
     ) -> std::result::Result<ReadDirResult, nfsstat3> {
-        info!(
-            "NFS READDIR: dir={}, start_after={}, max={}",
-            dirid, start_after, max_entries
-        );
+        self.with_deadline("READDIR", async move {
+            info!(
+                "NFS READDIR: dir={}, start_after={}, max={}",
+                dirid, start_after, max_entries
+            );
+            self.check_table_access(dirid)?;
 
-        let mut entries = Vec::new();
+            let mut entries = Vec::new();
 
-        match dirid {
-            ROOT_ID => {
-                if start_after < DATA_DIR_ID {
-                    entries.push(DirEntry {
-                        fileid: DATA_DIR_ID,
-                        name: "data".as_bytes().into(),
-                        attr: Self::dir_attr(DATA_DIR_ID),
-                    });
-                }
-                // Add created directories in root
-                let created_dirs = self.created_dirs.lock().await;
-                for ((parent_id, dir_name), &dir_id) in created_dirs.iter() {
-                    if *parent_id == ROOT_ID && dir_id > start_after && entries.len() < max_entries
-                    {
+            match dirid {
+                id if id == self.layout.root => {
+                    // Only tables the mount's user can read are listed
+                    if start_after < self.layout.data_dir && self.table_visible() {
                         entries.push(DirEntry {
-                            fileid: dir_id,
-                            name: dir_name.as_bytes().into(),
-                            attr: Self::dir_attr(dir_id),
+                            fileid: self.layout.data_dir,
+                            name: TABLE_NAME.as_bytes().into(),
+                            attr: Self::dir_attr(self.layout.data_dir),
                         });
                     }
-                }
-                drop(created_dirs);
-                // Add created files in root
-                let created_files = self.created_files.lock().await;
-                for ((parent_id, file_name), metadata) in created_files.iter() {
-                    if *parent_id == ROOT_ID
-                        && metadata.file_id > start_after
+                    // Listed once there's a named table the user can read, or
+                    // a directory made for one
+                    if start_after < self.layout.tables_dir
                         && entries.len() < max_entries
+                        && !self.table_dir_names().await.is_empty()
                     {
                         entries.push(DirEntry {
-                            fileid: metadata.file_id,
-                            name: file_name.as_bytes().into(),
-                            attr: fattr3 {
-                                ftype: ftype3::NF3REG,
-                                mode: 0o644,
-                                nlink: 1,
-                                uid: 1000,
-                                gid: 1000,
-                                size: metadata.content.len() as u64,
-                                used: metadata.content.len() as u64,
-                                rdev: specdata3::default(),
-                                fsid: 0,
-                                fileid: metadata.file_id,
-                                atime: metadata.atime,
-                                mtime: metadata.mtime,
-                                ctime: metadata.ctime,
-                            },
+                            fileid: self.layout.tables_dir,
+                            name: TABLES_DIR.as_bytes().into(),
+                            attr: Self::dir_attr(self.layout.tables_dir),
                         });
                     }
-                }
-            }
-            DATA_DIR_ID => {
-                // Always include data.csv
-                if start_after < DATA_CSV_ID {
-                    let db = self.db.clone();
-                    let view = CsvFileView::new(db);
-                    let size = match view.size().await {
-                        Ok(s) => s,
-                        Err(e) => {
-                            error!("Failed to get CSV size in readdir: {}", e);
-                            0
+                    // Add created directories in root
+                    let created_dirs = self.created_dirs.lock().await;
+                    for ((parent_id, dir_name), &dir_id) in created_dirs.iter() {
+                        if *parent_id == self.layout.root
+                            && dir_id > start_after
+                            && entries.len() < max_entries
+                        {
+                            entries.push(DirEntry {
+                                fileid: dir_id,
+                                name: dir_name.as_bytes().into(),
+                                attr: Self::dir_attr(dir_id),
+                            });
                         }
-                    };
-                    entries.push(DirEntry {
-                        fileid: DATA_CSV_ID,
-                        name: "data.csv".as_bytes().into(),
-                        attr: Self::file_attr(DATA_CSV_ID, size),
-                    });
+                    }
+                    drop(created_dirs);
+                    // Add created files in root
+                    let created_files = self.created_files.lock().await;
+                    for ((parent_id, file_name), metadata) in created_files.iter() {
+                        if *parent_id == self.layout.root
+                            && metadata.file_id > start_after
+                            && entries.len() < max_entries
+                        {
+                            entries.push(DirEntry {
+                                fileid: metadata.file_id,
+                                name: file_name.as_bytes().into(),
+                                attr: fattr3 {
+                                    ftype: ftype3::NF3REG,
+                                    mode: 0o644,
+                                    nlink: 1,
+                                    uid: 1000,
+                                    gid: 1000,
+                                    size: metadata.content.len() as u64,
+                                    used: metadata.content.len() as u64,
+                                    rdev: specdata3::default(),
+                                    fsid: 0,
+                                    fileid: metadata.file_id,
+                                    atime: metadata.atime,
+                                    mtime: metadata.mtime,
+                                    ctime: metadata.ctime,
+                                },
+                            });
+                        }
+                    }
                 }
-
-                // Add Parquet files (Delta Lake mode)
-                self.refresh_parquet_files().await?;
-                let parquet_files = self.parquet_files.lock().await;
-
-                for (id, file_path) in parquet_files.iter() {
-                    if *id > start_after && entries.len() < max_entries {
-                        let basename = std::path::Path::new(file_path)
-                            .file_name()
-                            .and_then(|n| n.to_str())
-                            .unwrap_or(file_path)
-                            .as_bytes()
-                            .into();
-
-                        // Get real file size from filesystem
-                        let full_path = self.db.base_path().join(file_path);
-                        let size = std::fs::metadata(&full_path)
-                            .map(|m| m.len())
-                            .unwrap_or(1024); // Fallback to 1024 if not found
-
+                id if id == self.layout.data_dir => {
+                    // Always include data.csv
+                    if start_after < self.layout.data_csv {
+                        let db = self.db.clone();
+                        let view = CsvFileView::new(db)
+                            .with_coercion(self.coercion.clone())
+                            .with_reader(self.auth.clone());
+                        let size = match view.size().await {
+                            Ok(s) => s,
+                            Err(e) => {
+                                error!("Failed to get CSV size in readdir: {}", e);
+                                0
+                            }
+                        };
                         entries.push(DirEntry {
-                            fileid: *id,
-                            name: basename,
-                            attr: Self::file_attr(*id, size),
+                            fileid: self.layout.data_csv,
+                            name: "data.csv".as_bytes().into(),
+                            attr: Self::file_attr(self.layout.data_csv, size),
                         });
                     }
-                }
-
-                // Add created directories in /data
-                let created_dirs = self.created_dirs.lock().await;
-                for ((parent_id, dir_name), &dir_id) in created_dirs.iter() {
-                    if *parent_id == DATA_DIR_ID
-                        && dir_id > start_after
-                        && entries.len() < max_entries
-                    {
+                    if start_after < self.layout.stats_json && entries.len() < max_entries {
+                        let size = self.file_stats_content().await?.len() as u64;
                         entries.push(DirEntry {
-                            fileid: dir_id,
-                            name: dir_name.as_bytes().into(),
-                            attr: Self::dir_attr(dir_id),
+                            fileid: self.layout.stats_json,
+                            name: ".stats.json".as_bytes().into(),
+                            attr: Self::file_attr(self.layout.stats_json, size),
                         });
                     }
+
+                    // Add Parquet files (Delta Lake mode)
+                    self.refresh_parquet_files().await?;
+                    let parquet_files = self.parquet_files.lock().await;
+
+                    for (id, file_path) in parquet_files.iter() {
+                        if *id > start_after && entries.len() < max_entries {
+                            let basename = std::path::Path::new(file_path)
+                                .file_name()
+                                .and_then(|n| n.to_str())
+                                .unwrap_or(file_path)
+                                .as_bytes()
+                                .into();
+
+                            // Get real file size from filesystem
+                            let full_path = self.db.base_path().join(file_path);
+                            let size = std::fs::metadata(&full_path)
+                                .map(|m| m.len())
+                                .unwrap_or(1024); // Fallback to 1024 if not found
+
+                            entries.push(DirEntry {
+                                fileid: *id,
+                                name: basename,
+                                attr: Self::file_attr(*id, size),
+                            });
+                        }
+                    }
+
+                    // Add partition directories
+                    for entry in self.partition_dir_entries(&Vec::new()).await? {
+                        if entry.fileid > start_after && entries.len() < max_entries {
+                            entries.push(entry);
+                        }
+                    }
+
+                    // Add created directories in /data
+                    let created_dirs = self.created_dirs.lock().await;
+                    for ((parent_id, dir_name), &dir_id) in created_dirs.iter() {
+                        if *parent_id == self.layout.data_dir
+                            && dir_id > start_after
+                            && entries.len() < max_entries
+                        {
+                            entries.push(DirEntry {
+                                fileid: dir_id,
+                                name: dir_name.as_bytes().into(),
+                                attr: Self::dir_attr(dir_id),
+                            });
+                        }
+                    }
+                    drop(created_dirs);
+                    // Add created files in /data
+                    let created_files = self.created_files.lock().await;
+                    for ((parent_id, file_name), metadata) in created_files.iter() {
+                        if *parent_id == self.layout.data_dir
+                            && metadata.file_id > start_after
+                            && entries.len() < max_entries
+                        {
+                            entries.push(DirEntry {
+                                fileid: metadata.file_id,
+                                name: file_name.as_bytes().into(),
+                                attr: fattr3 {
+                                    ftype: ftype3::NF3REG,
+                                    mode: 0o644,
+                                    nlink: 1,
+                                    uid: 1000,
+                                    gid: 1000,
+                                    size: metadata.content.len() as u64,
+                                    used: metadata.content.len() as u64,
+                                    rdev: specdata3::default(),
+                                    fsid: 0,
+                                    fileid: metadata.file_id,
+                                    atime: metadata.atime,
+                                    mtime: metadata.mtime,
+                                    ctime: metadata.ctime,
+                                },
+                            });
+                        }
+                    }
                 }
-                drop(created_dirs);
-                // Add created files in /data
-                let created_files = self.created_files.lock().await;
-                for ((parent_id, file_name), metadata) in created_files.iter() {
-                    if *parent_id == DATA_DIR_ID
-                        && metadata.file_id > start_after
-                        && entries.len() < max_entries
-                    {
-                        entries.push(DirEntry {
-                            fileid: metadata.file_id,
-                            name: file_name.as_bytes().into(),
-                            attr: fattr3 {
-                                ftype: ftype3::NF3REG,
-                                mode: 0o644,
-                                nlink: 1,
-                                uid: 1000,
-                                gid: 1000,
-                                size: metadata.content.len() as u64,
-                                used: metadata.content.len() as u64,
-                                rdev: specdata3::default(),
-                                fsid: 0,
+                id if self.layout.is_created_dir(id) => {
+                    // List contents of created directory
+                    let created_dirs = self.created_dirs.lock().await;
+                    for ((parent_id, dir_name), &dir_id) in created_dirs.iter() {
+                        if *parent_id == id && dir_id > start_after && entries.len() < max_entries {
+                            entries.push(DirEntry {
+                                fileid: dir_id,
+                                name: dir_name.as_bytes().into(),
+                                attr: Self::dir_attr(dir_id),
+                            });
+                        }
+                    }
+                    drop(created_dirs);
+                    // Add created files in this directory
+                    let created_files = self.created_files.lock().await;
+                    for ((parent_id, file_name), metadata) in created_files.iter() {
+                        if *parent_id == id
+                            && metadata.file_id > start_after
+                            && entries.len() < max_entries
+                        {
+                            entries.push(DirEntry {
                                 fileid: metadata.file_id,
-                                atime: metadata.atime,
-                                mtime: metadata.mtime,
-                                ctime: metadata.ctime,
-                            },
-                        });
+                                name: file_name.as_bytes().into(),
+                                attr: fattr3 {
+                                    ftype: ftype3::NF3REG,
+                                    mode: 0o644,
+                                    nlink: 1,
+                                    uid: 1000,
+                                    gid: 1000,
+                                    size: metadata.content.len() as u64,
+                                    used: metadata.content.len() as u64,
+                                    rdev: specdata3::default(),
+                                    fsid: 0,
+                                    fileid: metadata.file_id,
+                                    atime: metadata.atime,
+                                    mtime: metadata.mtime,
+                                    ctime: metadata.ctime,
+                                },
+                            });
+                        }
                     }
                 }
-            }
-            id if id >= CREATED_DIR_START => {
-                // List contents of created directory
-                let created_dirs = self.created_dirs.lock().await;
-                for ((parent_id, dir_name), &dir_id) in created_dirs.iter() {
-                    if *parent_id == id && dir_id > start_after && entries.len() < max_entries {
-                        entries.push(DirEntry {
-                            fileid: dir_id,
-                            name: dir_name.as_bytes().into(),
-                            attr: Self::dir_attr(dir_id),
-                        });
+                id if self.layout.is_partition(id) => {
+                    let PartitionEntry::Dir(parent) = self.partition_entry(id).await? else {
+                        return Err(nfsstat3::NFS3ERR_NOTDIR);
+                    };
+                    for entry in self.partition_dir_entries(&parent).await? {
+                        if entry.fileid > start_after && entries.len() < max_entries {
+                            entries.push(entry);
+                        }
                     }
                 }
-                drop(created_dirs);
-                // Add created files in this directory
-                let created_files = self.created_files.lock().await;
-                for ((parent_id, file_name), metadata) in created_files.iter() {
-                    if *parent_id == id
-                        && metadata.file_id > start_after
-                        && entries.len() < max_entries
-                    {
-                        entries.push(DirEntry {
-                            fileid: metadata.file_id,
-                            name: file_name.as_bytes().into(),
-                            attr: fattr3 {
-                                ftype: ftype3::NF3REG,
-                                mode: 0o644,
-                                nlink: 1,
-                                uid: 1000,
-                                gid: 1000,
-                                size: metadata.content.len() as u64,
-                                used: metadata.content.len() as u64,
-                                rdev: specdata3::default(),
-                                fsid: 0,
-                                fileid: metadata.file_id,
-                                atime: metadata.atime,
-                                mtime: metadata.mtime,
-                                ctime: metadata.ctime,
-                            },
-                        });
+                id if id == self.layout.tables_dir => {
+                    for table in self.table_dir_names().await {
+                        let id = self.table_id(&TableEntry::Dir(table.clone())).await?;
+                        if id > start_after && entries.len() < max_entries {
+                            entries.push(DirEntry {
+                                fileid: id,
+                                name: table.as_bytes().into(),
+                                attr: Self::dir_attr(id),
+                            });
+                        }
+                    }
+                }
+                id if self.layout.is_table(id) => {
+                    let (TableEntry::Dir(table), db) = self.table_entry(id).await? else {
+                        return Err(nfsstat3::NFS3ERR_NOTDIR);
+                    };
+                    for entry in self.table_dir_entries(&table, db.as_ref()).await? {
+                        if entry.fileid > start_after && entries.len() < max_entries {
+                            entries.push(entry);
+                        }
                     }
                 }
+                _ => return Err(nfsstat3::NFS3ERR_NOTDIR),
             }
-            _ => return Err(nfsstat3::NFS3ERR_NOTDIR),
-        }
 
-        Ok(ReadDirResult { entries, end: true })
+            Ok(ReadDirResult { entries, end: true })
+        })
+        .await
     }
 
     async fn symlink(
@@ -1116,6 +2938,7 @@ impl NFSFileSystem for FsdbFilesystem {
         _symlink_data: &nfsserve::nfs::nfspath3,
         _attr: &sattr3,
     ) -> std::result::Result<(fileid3, fattr3), nfsstat3> {
+        self.check_writable("SYMLINK")?;
         Err(nfsstat3::NFS3ERR_NOTSUPP)
     }
 
@@ -1126,3 +2949,943 @@ impl NFSFileSystem for FsdbFilesystem {
         Err(nfsstat3::NFS3ERR_NOTSUPP)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow::array::{ArrayRef, Int32Array, RecordBatch, StringArray};
+    use arrow::datatypes::{DataType, Field, Schema};
+    use tempfile::TempDir;
+
+    /// A table with Alice and Bob
+    async fn create_db(temp_dir: &TempDir) -> Arc<DatabaseOps> {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("id", DataType::Int32, false),
+            Field::new("name", DataType::Utf8, false),
+        ]));
+        let db = DatabaseOps::create(temp_dir.path().join("db"), schema.clone())
+            .await
+            .unwrap();
+        let batch = RecordBatch::try_new(
+            schema,
+            vec![
+                Arc::new(Int32Array::from(vec![1, 2])) as ArrayRef,
+                Arc::new(StringArray::from(vec!["Alice", "Bob"])) as ArrayRef,
+            ],
+        )
+        .unwrap();
+        db.insert(batch).await.unwrap();
+        Arc::new(db)
+    }
+
+    /// Two writers on a table with Alice and Bob that both read it first
+    async fn setup(policy: ConflictPolicy) -> (FsdbFilesystem, FsdbFilesystem, TempDir) {
+        let temp_dir = TempDir::new().unwrap();
+        let db = create_db(&temp_dir).await;
+        let read_bases = Arc::new(ReadBases::new());
+        let writer = |client| {
+            FsdbFilesystem::new(db.clone())
+                .with_conflict_policy(policy)
+                .with_read_bases(read_bases.clone(), client)
+        };
+        let (first, second) = (writer(1), writer(2));
+        let data_csv = first.layout.data_csv;
+        first.read(data_csv, 0, 4096).await.unwrap();
+        second.read(data_csv, 0, 4096).await.unwrap();
+        (first, second, temp_dir)
+    }
+
+    async fn overwrite(fs: &FsdbFilesystem, csv: &str) -> std::result::Result<fattr3, nfsstat3> {
+        fs.write(fs.layout.data_csv, 0, csv.as_bytes()).await
+    }
+
+    async fn rows(fs: &FsdbFilesystem) -> Vec<String> {
+        let (content, _) = fs.read(fs.layout.data_csv, 0, 4096).await.unwrap();
+        let mut rows: Vec<String> = String::from_utf8(content)
+            .unwrap()
+            .lines()
+            .skip(1)
+            .map(str::to_string)
+            .collect();
+        rows.sort();
+        rows
+    }
+
+    #[tokio::test]
+    async fn test_last_writer_wins() {
+        let (first, second, _temp) = setup(ConflictPolicy::LastWriterWins).await;
+
+        overwrite(&first, "id,name\n1,ALICE\n2,Bob\n")
+            .await
+            .unwrap();
+        overwrite(&second, "id,name\n1,Alice\n2,BOB\n")
+            .await
+            .unwrap();
+
+        // The second writer's stale copy of Alice undid the first writer's change
+        assert_eq!(rows(&first).await, vec!["1,Alice", "2,BOB"]);
+    }
+
+    #[tokio::test]
+    async fn test_reject_stale_overwrite() {
+        let (first, second, _temp) = setup(ConflictPolicy::Reject).await;
+
+        overwrite(&first, "id,name\n1,ALICE\n2,Bob\n")
+            .await
+            .unwrap();
+        let result = overwrite(&second, "id,name\n1,Alice\n2,BOB\n").await;
+        assert!(matches!(result, Err(nfsstat3::NFS3ERR_NOT_SYNC)));
+        assert_eq!(rows(&first).await, vec!["1,ALICE", "2,Bob"]);
+
+        // Once the second writer has read the new version it can write again,
+        // and appends never conflict
+        rows(&second).await;
+        overwrite(&second, "id,name\n1,ALICE\n2,BOB\n")
+            .await
+            .unwrap();
+        first
+            .write(first.layout.data_csv, 0, b"3,Carol\n")
+            .await
+            .unwrap();
+        assert_eq!(rows(&first).await, vec!["1,ALICE", "2,BOB", "3,Carol"]);
+    }
+
+    #[tokio::test]
+    async fn test_merge_stale_overwrite() {
+        let (first, second, _temp) = setup(ConflictPolicy::Merge).await;
+
+        overwrite(&first, "id,name\n1,ALICE\n2,Bob\n")
+            .await
+            .unwrap();
+        overwrite(&second, "id,name\n1,Alice\n2,BOB\n3,Carol\n")
+            .await
+            .unwrap();
+        assert_eq!(rows(&first).await, vec!["1,ALICE", "2,BOB", "3,Carol"]);
+
+        // The first writer's base is now out of date, and it changes a row
+        // the second writer changed
+        let result = overwrite(&first, "id,name\n1,ALICE\n2,Robert\n").await;
+        assert!(matches!(result, Err(nfsstat3::NFS3ERR_NOT_SYNC)));
+        assert_eq!(rows(&second).await, vec!["1,ALICE", "2,BOB", "3,Carol"]);
+    }
+
+    /// Names listed at the root of the mount
+    async fn root_names(fs: &FsdbFilesystem) -> Vec<String> {
+        fs.readdir(fs.layout.root, 0, 100)
+            .await
+            .unwrap()
+            .entries
+            .iter()
+            .map(|entry| String::from_utf8_lossy(&entry.name).to_string())
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_mount_only_sees_readable_tables() {
+        use crate::security::{Permission, Role, RoleManager};
+
+        let temp_dir = TempDir::new().unwrap();
+        let schema = Arc::new(Schema::new(vec![Field::new("id", DataType::Int32, false)]));
+        let mut roles = RoleManager::new();
+        roles.add_role(
+            Role::new("sales".to_string(), vec![Permission::Read])
+                .with_tables(vec!["data".to_string()]),
+        );
+        roles.add_role(
+            Role::new("hr".to_string(), vec![Permission::Read])
+                .with_tables(vec!["payroll".to_string()]),
+        );
+        let db = DatabaseOps::create_with_auth(temp_dir.path().join("db"), schema, true)
+            .await
+            .unwrap()
+            .with_role_manager(roles);
+        let db = Arc::new(db);
+        let mount = |role: &str| {
+            FsdbFilesystem::new(db.clone()).with_auth_context(Arc::new(AuthContext::authenticated(
+                "user".to_string(),
+                vec![role.to_string()],
+            )))
+        };
+        let data = "data".as_bytes().into();
+
+        let sales = mount("sales");
+        assert_eq!(root_names(&sales).await, vec!["data"]);
+        let data_dir = sales.lookup(sales.layout.root, &data).await.unwrap();
+        assert!(sales.readdir(data_dir, 0, 100).await.is_ok());
+        assert!(sales.read(sales.layout.data_csv, 0, 4096).await.is_ok());
+
+        // A user whose roles only cover other tables sees nothing of this one
+        let hr = mount("hr");
+        assert!(root_names(&hr).await.is_empty());
+        assert!(matches!(
+            hr.lookup(hr.layout.root, &data).await,
+            Err(nfsstat3::NFS3ERR_ACCES)
+        ));
+        assert!(matches!(
+            hr.readdir(data_dir, 0, 100).await,
+            Err(nfsstat3::NFS3ERR_ACCES)
+        ));
+        assert!(matches!(
+            hr.read(hr.layout.data_csv, 0, 4096).await,
+            Err(nfsstat3::NFS3ERR_ACCES)
+        ));
+
+        // Or it can be told the table doesn't exist
+        let hr = mount("hr").with_forbidden_lookup(ForbiddenLookup::NotFound);
+        assert!(matches!(
+            hr.lookup(hr.layout.root, &data).await,
+            Err(nfsstat3::NFS3ERR_NOENT)
+        ));
+
+        // Without a mount identity, the database's own user (system) is used
+        let system = FsdbFilesystem::new(db.clone());
+        assert_eq!(root_names(&system).await, vec!["data"]);
+    }
+
+    #[tokio::test]
+    async fn test_mount_reads_masked_columns() {
+        use crate::security::{ColumnMask, Permission, Role, RoleManager};
+        use nfsserve::nfs::{ACCESS3_EXTEND, ACCESS3_MODIFY};
+
+        let temp_dir = TempDir::new().unwrap();
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("id", DataType::Int32, false),
+            Field::new("ssn", DataType::Utf8, false),
+        ]));
+        let mut roles = RoleManager::new();
+        roles.add_role(
+            Role::new(
+                "support".to_string(),
+                vec![Permission::Read, Permission::Write],
+            )
+            .with_masked_column("ssn", ColumnMask::Partial { visible: 4 }),
+        );
+        let db = DatabaseOps::create_with_auth(temp_dir.path().join("db"), schema.clone(), true)
+            .await
+            .unwrap()
+            .with_role_manager(roles);
+        let batch = RecordBatch::try_new(
+            schema,
+            vec![
+                Arc::new(Int32Array::from(vec![1])) as ArrayRef,
+                Arc::new(StringArray::from(vec!["123-45-6789"])) as ArrayRef,
+            ],
+        )
+        .unwrap();
+        db.insert(batch).await.unwrap();
+        let db = Arc::new(db);
+        let support = FsdbFilesystem::new(db.clone()).with_auth_context(Arc::new(
+            AuthContext::authenticated("agent".to_string(), vec!["support".to_string()]),
+        ));
+
+        assert_eq!(rows(&support).await, vec!["1,*******6789"]);
+        // The database's own user (system) reads it in the clear
+        let system = FsdbFilesystem::new(db.clone());
+        assert_eq!(rows(&system).await, vec!["1,123-45-6789"]);
+
+        // Rows can be appended, but not rewritten with the masked values
+        let data_csv = support.layout.data_csv;
+        assert_eq!(
+            support
+                .access(data_csv, ACCESS3_MODIFY | ACCESS3_EXTEND)
+                .await
+                .unwrap(),
+            ACCESS3_EXTEND
+        );
+        assert!(matches!(
+            overwrite(&support, "id,ssn\n1,*******6789\n").await,
+            Err(nfsstat3::NFS3ERR_ACCES)
+        ));
+        support
+            .write(data_csv, 0, b"2,987-65-4321\n")
+            .await
+            .unwrap();
+        assert_eq!(rows(&support).await, vec!["1,*******6789", "2,*******4321"]);
+        assert_eq!(rows(&system).await, vec!["1,123-45-6789", "2,987-65-4321"]);
+
+        // File statistics leave out the masked column's range
+        let ssn_ranges = |content: Arc<Vec<u8>>| -> Vec<(serde_json::Value, serde_json::Value)> {
+            let stats: serde_json::Value = serde_json::from_slice(&content).unwrap();
+            stats["files"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|file| {
+                    let ssn = &file["columns"]["ssn"];
+                    (ssn["min"].clone(), ssn["max"].clone())
+                })
+                .collect()
+        };
+        let masked = ssn_ranges(support.file_stats_content().await.unwrap());
+        assert_eq!(masked.len(), 2);
+        assert!(masked
+            .iter()
+            .all(|(min, max)| min.is_null() && max.is_null()));
+        let clear = ssn_ranges(system.file_stats_content().await.unwrap());
+        assert!(clear
+            .iter()
+            .all(|(min, max)| min.is_string() && max.is_string()));
+    }
+
+    /// Commit a copy of the table's metadata with `key` set, as Spark's ALTER TABLE would
+    fn set_table_property(table_path: &std::path::Path, key: &str, value: &str) {
+        let log_dir = table_path.join("_delta_log");
+        let first = std::fs::read_to_string(log_dir.join(format!("{:020}.json", 0))).unwrap();
+        let mut metadata: serde_json::Value = first
+            .lines()
+            .map(|line| serde_json::from_str::<serde_json::Value>(line).unwrap())
+            .find(|action| action.get("metaData").is_some())
+            .unwrap();
+        metadata["metaData"]["configuration"][key] = value.into();
+        let next = std::fs::read_dir(&log_dir)
+            .unwrap()
+            .filter(|e| {
+                e.as_ref()
+                    .unwrap()
+                    .path()
+                    .extension()
+                    .is_some_and(|x| x == "json")
+            })
+            .count();
+        std::fs::write(
+            log_dir.join(format!("{:020}.json", next)),
+            metadata.to_string(),
+        )
+        .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_access_reflects_table_write_mode() {
+        use nfsserve::nfs::{
+            ACCESS3_DELETE, ACCESS3_EXTEND, ACCESS3_LOOKUP, ACCESS3_MODIFY, ACCESS3_READ,
+        };
+
+        let temp_dir = TempDir::new().unwrap();
+        let db_path = temp_dir.path().join("db");
+        let schema = Arc::new(Schema::new(vec![Field::new("id", DataType::Int32, false)]));
+        let db = Arc::new(DatabaseOps::create(&db_path, schema).await.unwrap());
+        let fs = FsdbFilesystem::new(db.clone());
+        let all = ACCESS3_READ | ACCESS3_LOOKUP | ACCESS3_MODIFY | ACCESS3_EXTEND | ACCESS3_DELETE;
+
+        // A writable table grants whatever is asked for
+        assert_eq!(fs.access(fs.layout.data_csv, all).await.unwrap(), all);
+        assert_eq!(fs.access(fs.layout.data_dir, all).await.unwrap(), all);
+        assert_eq!(
+            fs.access(fs.layout.data_csv, ACCESS3_READ).await.unwrap(),
+            ACCESS3_READ
+        );
+
+        // Parquet files are never writable through the mount
+        assert_eq!(
+            fs.access(fs.layout.parquet_files.start, all).await.unwrap(),
+            ACCESS3_READ | ACCESS3_LOOKUP
+        );
+
+        // Append-only: rows can be added but not rewritten or removed
+        set_table_property(&db_path, "delta.appendOnly", "true");
+        assert_eq!(
+            fs.access(fs.layout.data_csv, all).await.unwrap(),
+            ACCESS3_READ | ACCESS3_LOOKUP | ACCESS3_EXTEND
+        );
+
+        // Needing a column-mapped writer makes the table read-only
+        set_table_property(&db_path, "delta.columnMapping.mode", "name");
+        assert_eq!(
+            fs.access(fs.layout.data_csv, all).await.unwrap(),
+            ACCESS3_READ | ACCESS3_LOOKUP
+        );
+    }
+
+    #[tokio::test]
+    async fn test_access_follows_user_permissions() {
+        use crate::security::{Permission, Role, RoleManager};
+        use nfsserve::nfs::{
+            ACCESS3_DELETE, ACCESS3_EXTEND, ACCESS3_LOOKUP, ACCESS3_MODIFY, ACCESS3_READ,
+        };
+
+        let temp_dir = TempDir::new().unwrap();
+        let schema = Arc::new(Schema::new(vec![Field::new("id", DataType::Int32, false)]));
+        let mut roles = RoleManager::new();
+        roles.add_role(Role::new("viewer".to_string(), vec![Permission::Read]));
+        roles.add_role(Role::new(
+            "appender".to_string(),
+            vec![Permission::Read, Permission::Write],
+        ));
+        roles.add_role(
+            Role::new("hr".to_string(), vec![Permission::Read])
+                .with_tables(vec!["payroll".to_string()]),
+        );
+        let db = DatabaseOps::create_with_auth(temp_dir.path().join("db"), schema, true)
+            .await
+            .unwrap()
+            .with_role_manager(roles);
+        let db = Arc::new(db);
+        let mount = |role: &str| {
+            FsdbFilesystem::new(db.clone()).with_auth_context(Arc::new(AuthContext::authenticated(
+                "user".to_string(),
+                vec![role.to_string()],
+            )))
+        };
+        let all = ACCESS3_READ | ACCESS3_LOOKUP | ACCESS3_MODIFY | ACCESS3_EXTEND | ACCESS3_DELETE;
+
+        // A user without write permission sees the table as read-only
+        let viewer = mount("viewer");
+        assert_eq!(
+            viewer.access(viewer.layout.data_csv, all).await.unwrap(),
+            ACCESS3_READ | ACCESS3_LOOKUP
+        );
+        assert_eq!(
+            viewer.access(viewer.layout.root, all).await.unwrap(),
+            ACCESS3_READ | ACCESS3_LOOKUP
+        );
+
+        // Writing without delete permission allows changing rows but not removing files
+        let appender = mount("appender");
+        assert_eq!(
+            appender
+                .access(appender.layout.data_csv, all)
+                .await
+                .unwrap(),
+            ACCESS3_READ | ACCESS3_LOOKUP | ACCESS3_MODIFY | ACCESS3_EXTEND
+        );
+
+        // A table the user can't read is refused outright
+        let hr = mount("hr");
+        assert!(matches!(
+            hr.access(hr.layout.data_csv, ACCESS3_READ).await,
+            Err(nfsstat3::NFS3ERR_ACCES)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_read_only_mount_rejects_mutations() {
+        let (fs, _, _temp) = setup(ConflictPolicy::default()).await;
+        let fs = fs.with_read_only(true);
+        let layout = fs.layout.clone();
+        let name = |s: &str| -> filename3 { s.as_bytes().into() };
+        let rofs = |result: std::result::Result<(), nfsstat3>| {
+            assert!(
+                matches!(result, Err(nfsstat3::NFS3ERR_ROFS)),
+                "{:?}",
+                result
+            );
+        };
+
+        assert!(matches!(fs.capabilities(), VFSCapabilities::ReadOnly));
+        rofs(overwrite(&fs, "id,name\n1,Alice\n").await.map(drop));
+        rofs(fs.write(layout.data_csv, 0, b"3,Carol\n").await.map(drop));
+        rofs(
+            fs.setattr(layout.data_csv, sattr3::default())
+                .await
+                .map(drop),
+        );
+        rofs(
+            fs.create(layout.data_dir, &name("new.csv"), sattr3::default())
+                .await
+                .map(drop),
+        );
+        rofs(
+            fs.create_exclusive(layout.data_dir, &name("new.csv"))
+                .await
+                .map(drop),
+        );
+        rofs(fs.mkdir(layout.data_dir, &name("scratch")).await.map(drop));
+        rofs(fs.remove(layout.data_dir, &name("data.csv")).await);
+        rofs(
+            fs.rename(
+                layout.data_dir,
+                &name("data.csv"),
+                layout.data_dir,
+                &name("old.csv"),
+            )
+            .await,
+        );
+        rofs(
+            fs.symlink(
+                layout.root,
+                &name("link"),
+                &name("data"),
+                &sattr3::default(),
+            )
+            .await
+            .map(drop),
+        );
+
+        // Nothing was changed, and nothing may be
+        assert_eq!(rows(&fs).await, vec!["1,Alice", "2,Bob"]);
+        let all = nfsserve::nfs::ACCESS3_READ
+            | nfsserve::nfs::ACCESS3_MODIFY
+            | nfsserve::nfs::ACCESS3_EXTEND
+            | nfsserve::nfs::ACCESS3_DELETE;
+        assert_eq!(
+            fs.access(layout.data_csv, all).await.unwrap(),
+            nfsserve::nfs::ACCESS3_READ
+        );
+    }
+
+    #[tokio::test]
+    async fn test_fsinfo_and_pathconf_limits() {
+        use crate::nfs::limits::{
+            FSF3_CANSETTIME, FSF3_HOMOGENEOUS, FSF3_LINK, FSF3_SYMLINK, MAX_FILE_SIZE, NAME_MAX,
+        };
+
+        let temp_dir = TempDir::new().unwrap();
+        let db = create_db(&temp_dir).await;
+        let layout = FileIdLayout::default();
+
+        let info = FsdbFilesystem::new(db.clone())
+            .fsinfo(layout.root)
+            .await
+            .unwrap();
+        assert_eq!((info.rtpref, info.wtpref), (1024 * 1024, 1024 * 1024));
+        assert_eq!(info.maxfilesize, MAX_FILE_SIZE);
+        assert_eq!(info.properties & FSF3_HOMOGENEOUS, FSF3_HOMOGENEOUS);
+        assert_eq!(
+            info.properties & (FSF3_LINK | FSF3_SYMLINK | FSF3_CANSETTIME),
+            0
+        );
+
+        // Transfer sizes follow the configured size, in 4 KiB multiples
+        let fs = FsdbFilesystem::new(db.clone()).with_transfer_size(256 * 1024 + 100);
+        let info = fs.fsinfo(layout.root).await.unwrap();
+        assert_eq!(
+            [
+                info.rtmax,
+                info.rtpref,
+                info.wtmax,
+                info.wtpref,
+                info.dtpref
+            ],
+            [256 * 1024; 5]
+        );
+        assert_eq!((info.rtmult, info.wtmult), (4096, 4096));
+
+        let conf = fs.pathconf(layout.data_csv).await.unwrap();
+        assert_eq!((conf.name_max, conf.link_max), (NAME_MAX, 1));
+        assert!(conf.no_trunc && conf.case_preserving && !conf.case_insensitive);
+
+        // Names are refused past the reported limit, not truncated
+        let name = |len: usize| -> filename3 { vec![b'a'; len].as_slice().into() };
+        assert!(matches!(
+            fs.create(layout.root, &name(NAME_MAX as usize + 1), sattr3::default())
+                .await,
+            Err(nfsstat3::NFS3ERR_NAMETOOLONG)
+        ));
+        assert!(matches!(
+            fs.mkdir(layout.root, &name(NAME_MAX as usize + 1)).await,
+            Err(nfsstat3::NFS3ERR_NAMETOOLONG)
+        ));
+        fs.create(layout.root, &name(NAME_MAX as usize), sattr3::default())
+            .await
+            .unwrap();
+
+        // A read-only mount has the same limits; ACCESS says what it refuses
+        let read_only = FsdbFilesystem::new(db).with_read_only(true);
+        let info = read_only.fsinfo(layout.root).await.unwrap();
+        assert_eq!(info.maxfilesize, MAX_FILE_SIZE);
+        assert_eq!(read_only.pathconf(layout.data_csv).await.unwrap(), conf);
+        assert!(matches!(
+            read_only.capabilities(),
+            VFSCapabilities::ReadOnly
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_parent_directories() {
+        let temp_dir = TempDir::new().unwrap();
+        let db = create_db(&temp_dir).await;
+        let layout = FileIdLayout::default();
+        let fs = FsdbFilesystem::new(db);
+        let name = |name: &str| -> filename3 { name.as_bytes().into() };
+
+        assert_eq!(fs.parent(layout.data_csv).await.unwrap(), layout.data_dir);
+        assert_eq!(fs.parent(layout.data_dir).await.unwrap(), layout.root);
+        assert!(matches!(
+            fs.parent(layout.root).await,
+            Err(nfsstat3::NFS3ERR_NOENT)
+        ));
+
+        let (dir, _) = fs.mkdir(layout.root, &name("scratch")).await.unwrap();
+        let (file, _) = fs
+            .create(dir, &name("notes.txt"), sattr3::default())
+            .await
+            .unwrap();
+        assert_eq!(fs.parent(file).await.unwrap(), dir);
+        assert_eq!(fs.parent(dir).await.unwrap(), layout.root);
+
+        // A created directory's ID that was never handed out
+        assert!(matches!(
+            fs.parent(layout.created_dirs.end - 1).await,
+            Err(nfsstat3::NFS3ERR_STALE)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_named_table_directories() {
+        let temp_dir = TempDir::new().unwrap();
+        let db = create_db(&temp_dir).await;
+        let fs = FsdbFilesystem::new(db.clone());
+        let layout = fs.layout.clone();
+        let name = |name: &str| -> filename3 { name.as_bytes().into() };
+
+        // /tables is only listed once there's a table in it
+        assert_eq!(root_names(&fs).await, vec!["data"]);
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("id", DataType::Int32, false),
+            Field::new("item", DataType::Utf8, false),
+        ]));
+        db.create_table("orders", schema).await.unwrap();
+        assert_eq!(root_names(&fs).await, vec!["data", "tables"]);
+
+        let tables = fs.lookup(layout.root, &name("tables")).await.unwrap();
+        let orders = fs.lookup(tables, &name("orders")).await.unwrap();
+        assert!(layout.is_table(orders));
+        assert!(matches!(
+            fs.lookup(tables, &name("data")).await,
+            Err(nfsstat3::NFS3ERR_NOENT)
+        ));
+        assert!(matches!(
+            fs.lookup(tables, &name("..")).await,
+            Err(nfsstat3::NFS3ERR_NOENT)
+        ));
+
+        // Its data.csv is written and read like /data/data.csv
+        let csv = fs.lookup(orders, &name("data.csv")).await.unwrap();
+        fs.write(csv, 0, b"id,item\n1,apple\n2,pear\n")
+            .await
+            .unwrap();
+        let (content, _) = fs.read(csv, 0, 4096).await.unwrap();
+        assert_eq!(content, b"id,item\n1,apple\n2,pear\n");
+        assert_eq!(fs.parent(csv).await.unwrap(), orders);
+        assert_eq!(fs.parent(orders).await.unwrap(), tables);
+
+        // The rows went to that table alone, and its Parquet files are listed next to data.csv
+        assert_eq!(rows(&fs).await, vec!["1,Alice", "2,Bob"]);
+        let entries = fs.readdir(orders, 0, 100).await.unwrap().entries;
+        assert_eq!(entries[0].name.as_ref(), b"data.csv");
+        let parquet = entries
+            .iter()
+            .find(|entry| entry.name.as_ref().ends_with(b".parquet"))
+            .unwrap();
+        assert_eq!(
+            fs.lookup(orders, &parquet.name).await.unwrap(),
+            parquet.fileid
+        );
+        let (content, _) = fs.read(parquet.fileid, 0, 4096).await.unwrap();
+        assert!(String::from_utf8(content).unwrap().contains("1,apple"));
+
+        // A dropped table's handles go stale
+        db.drop_table("orders").await.unwrap();
+        assert!(matches!(
+            fs.getattr(orders).await,
+            Err(nfsstat3::NFS3ERR_STALE)
+        ));
+        assert_eq!(root_names(&fs).await, vec!["data"]);
+    }
+
+    #[tokio::test]
+    async fn test_copied_csv_files_create_and_fill_tables() {
+        let temp_dir = TempDir::new().unwrap();
+        let db = create_db(&temp_dir).await;
+        let fs = FsdbFilesystem::new(db.clone());
+        let layout = fs.layout.clone();
+        let name = |name: &str| -> filename3 { name.as_bytes().into() };
+        let tables = fs.lookup(layout.root, &name("tables")).await.unwrap();
+
+        // A made directory is listed, and empty, until a file is copied into it
+        let (dir, _) = fs.mkdir(tables, &name("new_table")).await.unwrap();
+        assert_eq!(root_names(&fs).await, vec!["data", "tables"]);
+        assert_eq!(fs.lookup(tables, &name("new_table")).await.unwrap(), dir);
+        assert!(fs.readdir(dir, 0, 100).await.unwrap().entries.is_empty());
+        assert!(matches!(
+            fs.mkdir(tables, &name("new_table")).await,
+            Err(nfsstat3::NFS3ERR_EXIST)
+        ));
+        assert!(matches!(
+            fs.mkdir(tables, &name("New-Table")).await,
+            Err(nfsstat3::NFS3ERR_INVAL)
+        ));
+        assert!(matches!(
+            fs.create(dir, &name("notes.txt"), sattr3::default()).await,
+            Err(nfsstat3::NFS3ERR_ACCES)
+        ));
+
+        // The first whole rows create the table, with types inferred from them
+        let (file, _) = fs
+            .create(dir, &name("mydata.csv"), sattr3::default())
+            .await
+            .unwrap();
+        fs.write(file, 0, b"id,price,note\n1,2.5,first\n")
+            .await
+            .unwrap();
+        let table = db.table("new_table").await.unwrap();
+        let schema = table.table_schema().await.unwrap();
+        assert_eq!(schema.field(0).data_type(), &DataType::Int64);
+        assert_eq!(schema.field(1).data_type(), &DataType::Float64);
+        assert_eq!(schema.field(2).data_type(), &DataType::Utf8);
+
+        // Later chunks are loaded with the file's header
+        fs.write(file, 26, b"2,3.5,second\n").await.unwrap();
+        assert_eq!(fs.getattr(file).await.unwrap().size, 39);
+        let (content, eof) = fs.read(file, 0, 4096).await.unwrap();
+        assert!(content.is_empty() && eof);
+
+        // Another file's columns are matched by name
+        let (more, _) = fs
+            .create(dir, &name("more.csv"), sattr3::default())
+            .await
+            .unwrap();
+        fs.write(more, 0, b"note,id\nthird,3\n").await.unwrap();
+
+        let entries = fs.readdir(dir, 0, 100).await.unwrap().entries;
+        assert_eq!(entries[0].name.as_ref(), b"data.csv");
+        assert_eq!(fs.parent(more).await.unwrap(), dir);
+        let (content, _) = fs.read(entries[0].fileid, 0, 4096).await.unwrap();
+        let content = String::from_utf8(content).unwrap();
+        assert_eq!(content.lines().count(), 4, "{}", content);
+        assert!(content.contains("3,,third"), "{}", content);
+
+        // The main table is untouched
+        assert_eq!(rows(&fs).await, vec!["1,Alice", "2,Bob"]);
+    }
+
+    #[tokio::test]
+    async fn test_stalled_operations_time_out() {
+        let temp_dir = TempDir::new().unwrap();
+        let db = create_db(&temp_dir).await;
+        let fs = FsdbFilesystem::new(db).with_operation_timeout(Duration::from_secs(1));
+        let layout = fs.layout.clone();
+        let promptly = |started: std::time::Instant| started.elapsed() < Duration::from_secs(5);
+
+        // Each operation waits on state a stuck scan would be holding
+        let reader = fs.csv_reader.lock().await;
+        let started = std::time::Instant::now();
+        assert!(matches!(
+            fs.read(layout.data_csv, 0, 4096).await,
+            Err(nfsstat3::NFS3ERR_JUKEBOX)
+        ));
+        assert!(promptly(started));
+        drop(reader);
+
+        let attr_version = fs.csv_attr_version.lock().await;
+        let started = std::time::Instant::now();
+        assert!(matches!(
+            fs.getattr(layout.data_csv).await,
+            Err(nfsstat3::NFS3ERR_JUKEBOX)
+        ));
+        assert!(promptly(started));
+        drop(attr_version);
+
+        let created_dirs = fs.created_dirs.lock().await;
+        let started = std::time::Instant::now();
+        assert!(matches!(
+            fs.readdir(layout.root, 0, 100).await,
+            Err(nfsstat3::NFS3ERR_JUKEBOX)
+        ));
+        assert!(promptly(started));
+        drop(created_dirs);
+
+        // Nothing is left held once the stall clears
+        let (content, eof) = fs.read(layout.data_csv, 0, 4096).await.unwrap();
+        assert!(eof);
+        assert!(String::from_utf8(content).unwrap().contains("Alice"));
+        assert_eq!(
+            fs.readdir(layout.root, 0, 100).await.unwrap().entries.len(),
+            1
+        );
+
+        // Another operation timing out leaves the read stream alone
+        fs.read(layout.data_csv, 0, 4).await.unwrap();
+        let attr_version = fs.csv_attr_version.lock().await;
+        assert!(matches!(
+            fs.getattr(layout.data_csv).await,
+            Err(nfsstat3::NFS3ERR_JUKEBOX)
+        ));
+        drop(attr_version);
+        assert!(fs.csv_reader.lock().await.is_some());
+    }
+
+    #[tokio::test]
+    async fn test_write_over_quota_is_dquot() {
+        use crate::security::Quota;
+
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("db");
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("id", DataType::Int32, false),
+            Field::new("name", DataType::Utf8, false),
+        ]));
+        let admin = DatabaseOps::create_with_auth(&path, schema, true)
+            .await
+            .unwrap();
+        admin
+            .create_user("alice", "secret", &["read", "write"])
+            .await
+            .unwrap();
+        admin
+            .set_user_quota("alice", Some(Quota::unlimited().with_max_rows(1)))
+            .await
+            .unwrap();
+        let alice = DatabaseOps::open_with_credentials(&path, Some(("alice", "secret")))
+            .await
+            .unwrap();
+        let fs = FsdbFilesystem::new(Arc::new(alice));
+
+        fs.write(fs.layout.data_csv, 0, b"1,Alice\n").await.unwrap();
+        assert!(matches!(
+            fs.write(fs.layout.data_csv, 0, b"2,Bob\n").await,
+            Err(nfsstat3::NFS3ERR_DQUOT)
+        ));
+        assert_eq!(rows(&fs).await, vec!["1,Alice"]);
+    }
+
+    #[tokio::test]
+    async fn test_large_binary_value_read_in_chunks() {
+        use crate::nfs::coercion::BinaryEncoding;
+        use arrow::array::BinaryArray;
+
+        let temp_dir = TempDir::new().unwrap();
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("id", DataType::Int32, false),
+            Field::new("payload", DataType::Binary, true),
+        ]));
+        let db = DatabaseOps::create(temp_dir.path().join("db"), schema.clone())
+            .await
+            .unwrap();
+        let blob: Vec<u8> = (0..1024 * 1024).map(|i| (i % 251) as u8).collect();
+        let batch = RecordBatch::try_new(
+            schema,
+            vec![
+                Arc::new(Int32Array::from(vec![1])) as ArrayRef,
+                Arc::new(BinaryArray::from(vec![Some(blob.as_slice())])) as ArrayRef,
+            ],
+        )
+        .unwrap();
+        db.insert(batch).await.unwrap();
+        let fs = FsdbFilesystem::new(Arc::new(db));
+
+        // The encoded value is far larger than one read
+        let mut content = Vec::new();
+        loop {
+            let (chunk, eof) = fs
+                .read(fs.layout.data_csv, content.len() as u64, 64 * 1024)
+                .await
+                .unwrap();
+            content.extend(chunk);
+            if eof {
+                break;
+            }
+        }
+
+        let csv = String::from_utf8(content).unwrap();
+        let row = csv.lines().nth(1).unwrap();
+        let encoded = row.strip_prefix("1,").unwrap();
+        assert_eq!(BinaryEncoding::Base64.decode(encoded), Some(blob));
+    }
+
+    #[tokio::test]
+    async fn test_exports_sharing_a_database_see_each_others_commits() {
+        let temp_dir = TempDir::new().unwrap();
+        let db = create_db(&temp_dir).await;
+        let cache = |dir: &str| NfsCache::new(temp_dir.path().join(dir));
+        let ingest = FsdbFilesystem::with_cache(db.clone(), Arc::new(cache("rw").await.unwrap()));
+        let analysts = FsdbFilesystem::with_cache(db.clone(), Arc::new(cache("ro").await.unwrap()))
+            .with_read_only(true);
+        let data_csv = analysts.layout.data_csv;
+
+        // Both exports cache the table as it is
+        assert_eq!(rows(&analysts).await, vec!["1,Alice", "2,Bob"]);
+        assert_eq!(rows(&ingest).await, vec!["1,Alice", "2,Bob"]);
+        let size = analysts.getattr(data_csv).await.unwrap().size;
+
+        // A write through one is read through the other, attributes included
+        ingest.write(data_csv, 0, b"3,Carol\n").await.unwrap();
+        assert_eq!(rows(&analysts).await, vec!["1,Alice", "2,Bob", "3,Carol"]);
+        assert_eq!(
+            analysts.getattr(data_csv).await.unwrap().size,
+            size + "3,Carol\n".len() as u64
+        );
+
+        // So are commits through the database itself, and its buffered rows
+        db.delete_rows_where("id = 1").await.unwrap();
+        assert_eq!(rows(&ingest).await, vec!["2,Bob", "3,Carol"]);
+        let batch = RecordBatch::try_new(
+            db.schema(),
+            vec![
+                Arc::new(Int32Array::from(vec![4])) as ArrayRef,
+                Arc::new(StringArray::from(vec!["Dave"])) as ArrayRef,
+            ],
+        )
+        .unwrap();
+        db.insert_buffered(batch).await.unwrap();
+        assert_eq!(rows(&analysts).await, vec!["2,Bob", "3,Carol", "4,Dave"]);
+
+        // The read-only export still refuses writes of its own
+        assert!(matches!(
+            analysts.write(data_csv, 0, b"5,Eve\n").await,
+            Err(nfsstat3::NFS3ERR_ROFS)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_handles_survive_restart() {
+        let temp_dir = TempDir::new().unwrap();
+        let db = create_db(&temp_dir).await;
+        let batch = RecordBatch::try_new(
+            db.schema(),
+            vec![
+                Arc::new(Int32Array::from(vec![3])) as ArrayRef,
+                Arc::new(StringArray::from(vec!["Carol"])) as ArrayRef,
+            ],
+        )
+        .unwrap();
+        db.insert(batch).await.unwrap();
+
+        // Handles a client got from the first server
+        let fs = FsdbFilesystem::new(db);
+        let listing = fs.readdir(fs.layout.data_dir, 0, 100).await.unwrap();
+        let parquet: Vec<(String, nfs_fh3)> = listing
+            .entries
+            .iter()
+            .filter(|e| fs.layout.is_parquet_file(e.fileid))
+            .map(|e| {
+                (
+                    String::from_utf8_lossy(&e.name).to_string(),
+                    fs.id_to_fh(e.fileid),
+                )
+            })
+            .collect();
+        assert_eq!(parquet.len(), 2);
+        let csv_handle = fs.id_to_fh(fs.layout.data_csv);
+        drop(fs);
+
+        // A new server over the reopened table, before any LOOKUP or READDIR
+        let db = Arc::new(DatabaseOps::open(temp_dir.path().join("db")).await.unwrap());
+        let fs = FsdbFilesystem::new(db);
+        assert_eq!(fs.fh_to_id(&csv_handle).unwrap(), fs.layout.data_csv);
+        for (name, handle) in &parquet {
+            let id = fs.fh_to_id(handle).unwrap();
+            assert!(fs.getattr(id).await.is_ok());
+            let looked_up = fs
+                .lookup(fs.layout.data_dir, &name.as_bytes().into())
+                .await
+                .unwrap();
+            assert_eq!(looked_up, id);
+        }
+
+        // A file that's gone is stale, not missing
+        let (name, handle) = &parquet[0];
+        std::fs::remove_file(temp_dir.path().join("db").join(name)).unwrap();
+        let id = fs.fh_to_id(handle).unwrap();
+        assert!(matches!(fs.getattr(id).await, Err(nfsstat3::NFS3ERR_STALE)));
+
+        // As is a handle from a different export
+        let other = FsdbFilesystem::new(fs.db.clone())
+            .with_file_id_layout(FileIdLayout::with_base(1 << 32).unwrap())
+            .unwrap();
+        assert!(matches!(
+            other.fh_to_id(&csv_handle),
+            Err(nfsstat3::NFS3ERR_STALE)
+        ));
+    }
+}
@@ -0,0 +1,197 @@
+//! Directories of named tables under `/tables`
+//!
+//! The main table is exported as `/data`. Tables made with
+//! `DatabaseOps::create_table` each get a directory of their own,
+//! `/tables/<name>/`, holding its `data.csv` and Parquet files. Tables come
+//! and go while the server runs, so their file IDs are handed out from a range
+//! as they're first looked up or listed, rather than fixed in the layout.
+//!
+//! A directory made in `/tables` with MKDIR is remembered here until the
+//! first CSV file copied into it creates the table. Copied files are tracked
+//! by how much of them has been loaded into the table.
+
+use nfsserve::nfs::fileid3;
+use std::collections::{BTreeSet, HashMap};
+use std::ops::Range;
+use tokio::sync::Mutex;
+
+/// An object inside `/tables`
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum TableEntry {
+    /// Directory of a table
+    Dir(String),
+    /// `data.csv` of a table
+    Csv(String),
+    /// Parquet file of a table, by file name
+    Parquet(String, String),
+    /// CSV file copied into a table's directory, by file name
+    Upload(String, String),
+}
+
+impl TableEntry {
+    /// Name of the table the entry belongs to
+    pub fn table(&self) -> &str {
+        match self {
+            TableEntry::Dir(table)
+            | TableEntry::Csv(table)
+            | TableEntry::Parquet(table, _)
+            | TableEntry::Upload(table, _) => table,
+        }
+    }
+}
+
+/// How much of a copied CSV file has been loaded into its table
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Upload {
+    /// Bytes of the file loaded so far
+    pub applied: u64,
+    /// Header line of the file, once its first rows are loaded
+    pub header: Option<Vec<u8>>,
+}
+
+#[derive(Debug, Default)]
+struct Inner {
+    ids: HashMap<TableEntry, fileid3>,
+    entries: HashMap<fileid3, TableEntry>,
+    next_id: Option<fileid3>,
+    made: BTreeSet<String>,
+    uploads: HashMap<fileid3, Upload>,
+}
+
+/// File IDs of table directories, shared by all filesystem handles of a server
+#[derive(Debug, Default)]
+pub struct TableDirs {
+    inner: Mutex<Inner>,
+}
+
+impl TableDirs {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// File ID of `entry`, allocating one from `range` the first time
+    ///
+    /// Returns `None` once `range` is exhausted. A table dropped and made
+    /// again keeps its IDs.
+    pub async fn id(&self, entry: &TableEntry, range: &Range<fileid3>) -> Option<fileid3> {
+        let mut inner = self.inner.lock().await;
+        if let Some(&id) = inner.ids.get(entry) {
+            return Some(id);
+        }
+        let id = inner.next_id.unwrap_or(range.start);
+        if !range.contains(&id) {
+            return None;
+        }
+        inner.next_id = Some(id + 1);
+        inner.ids.insert(entry.clone(), id);
+        inner.entries.insert(id, entry.clone());
+        Some(id)
+    }
+
+    /// Entry a file ID was allocated to
+    pub async fn entry(&self, id: fileid3) -> Option<TableEntry> {
+        self.inner.lock().await.entries.get(&id).cloned()
+    }
+
+    /// File ID of `entry`, if one has been allocated
+    pub async fn find(&self, entry: &TableEntry) -> Option<fileid3> {
+        self.inner.lock().await.ids.get(entry).copied()
+    }
+
+    /// Files copied into the directory of `table`, by name
+    pub async fn uploads(&self, table: &str) -> Vec<(String, fileid3)> {
+        let inner = self.inner.lock().await;
+        let mut uploads: Vec<(String, fileid3)> = inner
+            .ids
+            .iter()
+            .filter_map(|(entry, &id)| match entry {
+                TableEntry::Upload(t, name) if t == table => Some((name.clone(), id)),
+                _ => None,
+            })
+            .collect();
+        uploads.sort();
+        uploads
+    }
+
+    /// Progress of the copied file `id`
+    pub async fn upload(&self, id: fileid3) -> Upload {
+        let inner = self.inner.lock().await;
+        inner.uploads.get(&id).cloned().unwrap_or_default()
+    }
+
+    /// Record the progress of the copied file `id`
+    pub async fn set_upload(&self, id: fileid3, upload: Upload) {
+        self.inner.lock().await.uploads.insert(id, upload);
+    }
+
+    /// Remember a directory made for the table `name`
+    pub async fn make(&self, name: String) {
+        self.inner.lock().await.made.insert(name);
+    }
+
+    /// Forget a made directory once its table exists
+    pub async fn unmake(&self, name: &str) {
+        self.inner.lock().await.made.remove(name);
+    }
+
+    /// Whether a directory was made for the table `name`
+    pub async fn is_made(&self, name: &str) -> bool {
+        self.inner.lock().await.made.contains(name)
+    }
+
+    /// Directories made for tables that don't exist yet, sorted
+    pub async fn made(&self) -> Vec<String> {
+        self.inner.lock().await.made.iter().cloned().collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_ids_are_stable_and_bounded() {
+        let dirs = TableDirs::new();
+        let orders = TableEntry::Dir("orders".to_string());
+        let csv = TableEntry::Csv("orders".to_string());
+
+        let id = dirs.id(&orders, &(10..12)).await.unwrap();
+        assert_eq!(dirs.id(&orders, &(10..12)).await, Some(id));
+        assert_eq!(dirs.id(&csv, &(10..12)).await, Some(id + 1));
+        assert_eq!(dirs.entry(id + 1).await, Some(csv));
+        assert_eq!(dirs.entry(id + 2).await, None);
+
+        let file = TableEntry::Parquet("orders".to_string(), "part-0.parquet".to_string());
+        assert_eq!(file.table(), "orders");
+        assert_eq!(dirs.id(&file, &(10..12)).await, None);
+    }
+
+    #[tokio::test]
+    async fn test_made_dirs_and_uploads() {
+        let dirs = TableDirs::new();
+        dirs.make("orders".to_string()).await;
+        assert!(dirs.is_made("orders").await);
+        assert_eq!(dirs.made().await, vec!["orders".to_string()]);
+
+        let upload = TableEntry::Upload("orders".to_string(), "mydata.csv".to_string());
+        assert_eq!(dirs.find(&upload).await, None);
+        let id = dirs.id(&upload, &(10..20)).await.unwrap();
+        assert_eq!(dirs.find(&upload).await, Some(id));
+        assert_eq!(
+            dirs.uploads("orders").await,
+            vec![("mydata.csv".to_string(), id)]
+        );
+        assert!(dirs.uploads("other").await.is_empty());
+
+        assert_eq!(dirs.upload(id).await, Upload::default());
+        let progress = Upload {
+            applied: 8,
+            header: Some(b"id\n".to_vec()),
+        };
+        dirs.set_upload(id, progress.clone()).await;
+        assert_eq!(dirs.upload(id).await, progress);
+
+        dirs.unmake("orders").await;
+        assert!(dirs.made().await.is_empty());
+    }
+}
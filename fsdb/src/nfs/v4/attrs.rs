@@ -0,0 +1,455 @@
+//! NFSv4 file attributes (fattr4) from the filesystem's NFSv3 attributes
+//!
+//! NFSv4 clients ask for attributes by bitmap and get back the subset the
+//! server supports, encoded in bit order. Everything NFSv3 reported is here,
+//! along with the filesystem-wide values FSINFO and PATHCONF gave, so a
+//! client learns them from GETATTR instead. Owners are sent as numeric
+//! strings, which Linux and macOS map straight to IDs with AUTH_SYS.
+
+use crate::nfs::limits::PathConf;
+use crate::nfs::v4::status::nfsstat4;
+use crate::nfs::v4::xdr::{XdrReader, XdrWriter};
+use nfsserve::nfs::{fattr3, ftype3, nfstime3, sattr3, set_mode3, set_size3};
+
+pub const FATTR4_SUPPORTED_ATTRS: u32 = 0;
+pub const FATTR4_TYPE: u32 = 1;
+pub const FATTR4_FH_EXPIRE_TYPE: u32 = 2;
+pub const FATTR4_CHANGE: u32 = 3;
+pub const FATTR4_SIZE: u32 = 4;
+pub const FATTR4_LINK_SUPPORT: u32 = 5;
+pub const FATTR4_SYMLINK_SUPPORT: u32 = 6;
+pub const FATTR4_NAMED_ATTR: u32 = 7;
+pub const FATTR4_FSID: u32 = 8;
+pub const FATTR4_UNIQUE_HANDLES: u32 = 9;
+pub const FATTR4_LEASE_TIME: u32 = 10;
+pub const FATTR4_RDATTR_ERROR: u32 = 11;
+pub const FATTR4_CANSETTIME: u32 = 15;
+pub const FATTR4_CASE_INSENSITIVE: u32 = 16;
+pub const FATTR4_CASE_PRESERVING: u32 = 17;
+pub const FATTR4_CHOWN_RESTRICTED: u32 = 18;
+pub const FATTR4_FILEHANDLE: u32 = 19;
+pub const FATTR4_FILEID: u32 = 20;
+pub const FATTR4_HOMOGENEOUS: u32 = 26;
+pub const FATTR4_MAXFILESIZE: u32 = 27;
+pub const FATTR4_MAXLINK: u32 = 28;
+pub const FATTR4_MAXNAME: u32 = 29;
+pub const FATTR4_MAXREAD: u32 = 30;
+pub const FATTR4_MAXWRITE: u32 = 31;
+pub const FATTR4_MODE: u32 = 33;
+pub const FATTR4_NO_TRUNC: u32 = 34;
+pub const FATTR4_NUMLINKS: u32 = 35;
+pub const FATTR4_OWNER: u32 = 36;
+pub const FATTR4_OWNER_GROUP: u32 = 37;
+pub const FATTR4_RAWDEV: u32 = 41;
+pub const FATTR4_SPACE_USED: u32 = 45;
+pub const FATTR4_TIME_ACCESS: u32 = 47;
+pub const FATTR4_TIME_ACCESS_SET: u32 = 48;
+pub const FATTR4_TIME_DELTA: u32 = 51;
+pub const FATTR4_TIME_METADATA: u32 = 52;
+pub const FATTR4_TIME_MODIFY: u32 = 53;
+pub const FATTR4_TIME_MODIFY_SET: u32 = 54;
+pub const FATTR4_MOUNTED_ON_FILEID: u32 = 55;
+pub const FATTR4_SUPPATTR_EXCLCREAT: u32 = 75;
+
+/// Attributes the server reports or accepts, in bit order
+const SUPPORTED: &[u32] = &[
+    FATTR4_SUPPORTED_ATTRS,
+    FATTR4_TYPE,
+    FATTR4_FH_EXPIRE_TYPE,
+    FATTR4_CHANGE,
+    FATTR4_SIZE,
+    FATTR4_LINK_SUPPORT,
+    FATTR4_SYMLINK_SUPPORT,
+    FATTR4_NAMED_ATTR,
+    FATTR4_FSID,
+    FATTR4_UNIQUE_HANDLES,
+    FATTR4_LEASE_TIME,
+    FATTR4_RDATTR_ERROR,
+    FATTR4_CANSETTIME,
+    FATTR4_CASE_INSENSITIVE,
+    FATTR4_CASE_PRESERVING,
+    FATTR4_CHOWN_RESTRICTED,
+    FATTR4_FILEHANDLE,
+    FATTR4_FILEID,
+    FATTR4_HOMOGENEOUS,
+    FATTR4_MAXFILESIZE,
+    FATTR4_MAXLINK,
+    FATTR4_MAXNAME,
+    FATTR4_MAXREAD,
+    FATTR4_MAXWRITE,
+    FATTR4_MODE,
+    FATTR4_NO_TRUNC,
+    FATTR4_NUMLINKS,
+    FATTR4_OWNER,
+    FATTR4_OWNER_GROUP,
+    FATTR4_RAWDEV,
+    FATTR4_SPACE_USED,
+    FATTR4_TIME_ACCESS,
+    FATTR4_TIME_ACCESS_SET,
+    FATTR4_TIME_DELTA,
+    FATTR4_TIME_METADATA,
+    FATTR4_TIME_MODIFY,
+    FATTR4_TIME_MODIFY_SET,
+    FATTR4_MOUNTED_ON_FILEID,
+    FATTR4_SUPPATTR_EXCLCREAT,
+];
+
+/// Attributes SETATTR, OPEN and CREATE accept
+const SETTABLE: &[u32] = &[
+    FATTR4_SIZE,
+    FATTR4_MODE,
+    FATTR4_TIME_ACCESS_SET,
+    FATTR4_TIME_MODIFY_SET,
+];
+
+/// Attributes that are only ever set, never read back
+const SETTABLE_ONLY: &[u32] = &[FATTR4_TIME_ACCESS_SET, FATTR4_TIME_MODIFY_SET];
+
+/// `fh_expire_type`: handles never expire on their own
+const FH4_PERSISTENT: u32 = 0;
+
+/// A set of attribute numbers, as NFSv4 sends them
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Bitmap(pub Vec<u32>);
+
+impl Bitmap {
+    pub fn from_bits(bits: &[u32]) -> Self {
+        let mut bitmap = Self::default();
+        for &bit in bits {
+            bitmap.set(bit);
+        }
+        bitmap
+    }
+
+    pub fn contains(&self, bit: u32) -> bool {
+        self.0
+            .get((bit / 32) as usize)
+            .is_some_and(|word| word & (1 << (bit % 32)) != 0)
+    }
+
+    pub fn set(&mut self, bit: u32) {
+        let word = (bit / 32) as usize;
+        if self.0.len() <= word {
+            self.0.resize(word + 1, 0);
+        }
+        self.0[word] |= 1 << (bit % 32);
+    }
+
+    /// Attribute numbers in the set, lowest first
+    pub fn bits(&self) -> impl Iterator<Item = u32> + '_ {
+        self.0.iter().enumerate().flat_map(|(word, &bits)| {
+            (0..32)
+                .filter(move |bit| bits & (1 << bit) != 0)
+                .map(move |bit| word as u32 * 32 + bit)
+        })
+    }
+
+    pub fn decode(reader: &mut XdrReader) -> Result<Self, nfsstat4> {
+        Ok(Self(reader.u32_array()?))
+    }
+
+    pub fn encode(&self, writer: &mut XdrWriter) {
+        writer.u32_array(&self.0);
+    }
+}
+
+/// Limits of the whole filesystem, reported with every object's attributes
+#[derive(Debug, Clone, Copy)]
+pub struct FsLimits {
+    pub max_read: u64,
+    pub max_write: u64,
+    pub max_file_size: u64,
+    pub lease_time: u32,
+    pub pathconf: PathConf,
+}
+
+/// What an object's attributes are encoded from
+pub struct AttrSource<'a> {
+    pub attr: &'a fattr3,
+    pub handle: &'a [u8],
+    pub limits: &'a FsLimits,
+}
+
+/// `change` attribute of an object: its ctime, which moves with every change
+pub fn change(attr: &fattr3) -> u64 {
+    (attr.ctime.seconds as u64) * 1_000_000_000 + attr.ctime.nseconds as u64
+}
+
+/// The `requested` attributes the server supports, as a fattr4
+pub fn encode(requested: &Bitmap, source: &AttrSource, writer: &mut XdrWriter) {
+    let mut returned = Bitmap::default();
+    let mut values = XdrWriter::new();
+    for &bit in SUPPORTED {
+        if !requested.contains(bit) || SETTABLE_ONLY.contains(&bit) {
+            continue;
+        }
+        returned.set(bit);
+        encode_one(bit, source, &mut values);
+    }
+    returned.encode(writer);
+    writer.opaque(&values.into_inner());
+}
+
+fn encode_one(bit: u32, source: &AttrSource, writer: &mut XdrWriter) {
+    let attr = source.attr;
+    let limits = source.limits;
+    match bit {
+        FATTR4_SUPPORTED_ATTRS => Bitmap::from_bits(SUPPORTED).encode(writer),
+        FATTR4_TYPE => {
+            writer.u32(file_type(attr.ftype));
+        }
+        FATTR4_FH_EXPIRE_TYPE => {
+            writer.u32(FH4_PERSISTENT);
+        }
+        FATTR4_CHANGE => {
+            writer.u64(change(attr));
+        }
+        FATTR4_SIZE => {
+            writer.u64(attr.size);
+        }
+        FATTR4_LINK_SUPPORT | FATTR4_SYMLINK_SUPPORT | FATTR4_NAMED_ATTR | FATTR4_CANSETTIME => {
+            writer.bool(false);
+        }
+        FATTR4_FSID => {
+            writer.u64(attr.fsid).u64(0);
+        }
+        FATTR4_UNIQUE_HANDLES | FATTR4_HOMOGENEOUS => {
+            writer.bool(true);
+        }
+        FATTR4_LEASE_TIME => {
+            writer.u32(limits.lease_time);
+        }
+        FATTR4_RDATTR_ERROR => {
+            writer.u32(nfsstat4::NFS4_OK.code());
+        }
+        FATTR4_CASE_INSENSITIVE => {
+            writer.bool(limits.pathconf.case_insensitive);
+        }
+        FATTR4_CASE_PRESERVING => {
+            writer.bool(limits.pathconf.case_preserving);
+        }
+        FATTR4_CHOWN_RESTRICTED => {
+            writer.bool(limits.pathconf.chown_restricted);
+        }
+        FATTR4_FILEHANDLE => {
+            writer.opaque(source.handle);
+        }
+        FATTR4_FILEID | FATTR4_MOUNTED_ON_FILEID => {
+            writer.u64(attr.fileid);
+        }
+        FATTR4_MAXFILESIZE => {
+            writer.u64(limits.max_file_size);
+        }
+        FATTR4_MAXLINK => {
+            writer.u32(limits.pathconf.link_max);
+        }
+        FATTR4_MAXNAME => {
+            writer.u32(limits.pathconf.name_max);
+        }
+        FATTR4_MAXREAD => {
+            writer.u64(limits.max_read);
+        }
+        FATTR4_MAXWRITE => {
+            writer.u64(limits.max_write);
+        }
+        FATTR4_MODE => {
+            writer.u32(attr.mode & 0o7777);
+        }
+        FATTR4_NO_TRUNC => {
+            writer.bool(limits.pathconf.no_trunc);
+        }
+        FATTR4_NUMLINKS => {
+            writer.u32(attr.nlink);
+        }
+        FATTR4_OWNER => {
+            writer.opaque(attr.uid.to_string().as_bytes());
+        }
+        FATTR4_OWNER_GROUP => {
+            writer.opaque(attr.gid.to_string().as_bytes());
+        }
+        FATTR4_RAWDEV => {
+            writer.u32(0).u32(0);
+        }
+        FATTR4_SPACE_USED => {
+            writer.u64(attr.used);
+        }
+        FATTR4_TIME_ACCESS => time(attr.atime, writer),
+        // Timestamps are kept to the nanosecond, as FSINFO says
+        FATTR4_TIME_DELTA => time(
+            nfstime3 {
+                seconds: 0,
+                nseconds: 1,
+            },
+            writer,
+        ),
+        FATTR4_TIME_METADATA => time(attr.ctime, writer),
+        FATTR4_TIME_MODIFY => time(attr.mtime, writer),
+        FATTR4_SUPPATTR_EXCLCREAT => Bitmap::from_bits(&[FATTR4_SIZE, FATTR4_MODE]).encode(writer),
+        _ => unreachable!("attribute {} is not in SUPPORTED", bit),
+    }
+}
+
+/// Whether every attribute in `requested` can be read, as VERIFY needs
+pub fn readable(requested: &Bitmap) -> bool {
+    requested
+        .bits()
+        .all(|bit| SUPPORTED.contains(&bit) && !SETTABLE_ONLY.contains(&bit))
+}
+
+fn time(time: nfstime3, writer: &mut XdrWriter) {
+    writer.i64(time.seconds as i64).u32(time.nseconds);
+}
+
+/// nfs_ftype4 of an NFSv3 file type; the numbers are the same
+pub fn file_type(ftype: ftype3) -> u32 {
+    match ftype {
+        ftype3::NF3REG => 1,
+        ftype3::NF3DIR => 2,
+        ftype3::NF3BLK => 3,
+        ftype3::NF3CHR => 4,
+        ftype3::NF3LNK => 5,
+        ftype3::NF3SOCK => 6,
+        ftype3::NF3FIFO => 7,
+    }
+}
+
+/// Attributes a client sets with SETATTR, or creates a file with
+///
+/// Times are accepted but not applied, as the filesystem keeps a file's
+/// own timestamps. Attributes that are only read are `NFS4ERR_INVAL`, and
+/// unknown ones `NFS4ERR_ATTRNOTSUPP`. Returns the changes with the
+/// attributes they set.
+pub fn decode_settable(reader: &mut XdrReader) -> Result<(sattr3, Bitmap), nfsstat4> {
+    let bitmap = Bitmap::decode(reader)?;
+    let values = reader.opaque()?;
+    let mut values = XdrReader::new(values);
+    let mut sattr = sattr3::default();
+    for bit in bitmap.bits() {
+        if !SETTABLE.contains(&bit) {
+            return Err(if SUPPORTED.contains(&bit) {
+                nfsstat4::NFS4ERR_INVAL
+            } else {
+                nfsstat4::NFS4ERR_ATTRNOTSUPP
+            });
+        }
+        match bit {
+            FATTR4_SIZE => sattr.size = set_size3::size(values.u64()?),
+            FATTR4_MODE => sattr.mode = set_mode3::mode(values.u32()? & 0o7777),
+            // SET_TO_CLIENT_TIME4 carries the time, SET_TO_SERVER_TIME4 nothing
+            _ => {
+                if values.u32()? == 1 {
+                    values.i64()?;
+                    values.u32()?;
+                }
+            }
+        }
+    }
+    Ok((sattr, bitmap))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use nfsserve::nfs::specdata3;
+
+    fn limits() -> FsLimits {
+        FsLimits {
+            max_read: 1 << 20,
+            max_write: 1 << 20,
+            max_file_size: i64::MAX as u64,
+            lease_time: 90,
+            pathconf: PathConf::default(),
+        }
+    }
+
+    fn file(size: u64) -> fattr3 {
+        let time = nfstime3 {
+            seconds: 100,
+            nseconds: 5,
+        };
+        fattr3 {
+            ftype: ftype3::NF3REG,
+            mode: 0o100644,
+            nlink: 1,
+            uid: 1000,
+            gid: 1000,
+            size,
+            used: size,
+            rdev: specdata3::default(),
+            fsid: 0,
+            fileid: 3,
+            atime: time,
+            mtime: time,
+            ctime: time,
+        }
+    }
+
+    #[test]
+    fn test_bitmap() {
+        let bitmap = Bitmap::from_bits(&[FATTR4_TYPE, FATTR4_SIZE, FATTR4_MODE]);
+        assert_eq!(bitmap.0, vec![0b10010, 0b10]);
+        assert!(bitmap.contains(FATTR4_MODE));
+        assert!(!bitmap.contains(FATTR4_OWNER));
+        assert!(!bitmap.contains(200));
+        assert_eq!(
+            bitmap.bits().collect::<Vec<_>>(),
+            vec![FATTR4_TYPE, FATTR4_SIZE, FATTR4_MODE]
+        );
+
+        assert!(readable(&bitmap));
+        assert!(!readable(&Bitmap::from_bits(&[FATTR4_TYPE, 12])));
+        assert!(!readable(&Bitmap::from_bits(&[FATTR4_TIME_MODIFY_SET])));
+    }
+
+    #[test]
+    fn test_encode_requested_and_supported() {
+        // ACL (12) isn't supported, so it's left out of the reply
+        let requested = Bitmap::from_bits(&[FATTR4_TYPE, FATTR4_SIZE, 12, FATTR4_OWNER]);
+        let attr = file(42);
+        let source = AttrSource {
+            attr: &attr,
+            handle: &[1; 16],
+            limits: &limits(),
+        };
+        let mut writer = XdrWriter::new();
+        encode(&requested, &source, &mut writer);
+        let bytes = writer.into_inner();
+
+        let mut reader = XdrReader::new(&bytes);
+        let returned = Bitmap::decode(&mut reader).unwrap();
+        assert_eq!(
+            returned,
+            Bitmap::from_bits(&[FATTR4_TYPE, FATTR4_SIZE, FATTR4_OWNER])
+        );
+        let mut values = XdrReader::new(reader.opaque().unwrap());
+        assert_eq!(values.u32(), Ok(1));
+        assert_eq!(values.u64(), Ok(42));
+        assert_eq!(values.opaque(), Ok(&b"1000"[..]));
+        assert!(values.remaining().is_empty());
+    }
+
+    #[test]
+    fn test_decode_settable() {
+        let mut values = XdrWriter::new();
+        values.u64(0).u32(0o600).u32(0);
+        let mut writer = XdrWriter::new();
+        Bitmap::from_bits(&[FATTR4_SIZE, FATTR4_MODE, FATTR4_TIME_MODIFY_SET]).encode(&mut writer);
+        writer.opaque(&values.into_inner());
+        let bytes = writer.into_inner();
+
+        let (sattr, set) = decode_settable(&mut XdrReader::new(&bytes)).unwrap();
+        assert!(matches!(sattr.size, set_size3::size(0)));
+        assert!(matches!(sattr.mode, set_mode3::mode(0o600)));
+        assert!(set.contains(FATTR4_TIME_MODIFY_SET));
+
+        // The file ID can't be set
+        let mut writer = XdrWriter::new();
+        Bitmap::from_bits(&[FATTR4_FILEID]).encode(&mut writer);
+        writer.opaque(&7u64.to_be_bytes());
+        let bytes = writer.into_inner();
+        assert_eq!(
+            decode_settable(&mut XdrReader::new(&bytes)).unwrap_err(),
+            nfsstat4::NFS4ERR_INVAL
+        );
+    }
+}
@@ -0,0 +1,1418 @@
+//! NFSv4 COMPOUND processing over the shared filesystem
+//!
+//! Each COMPOUND is a list of operations run in order against a current
+//! (and saved) filehandle, stopping at the first that fails. File operations
+//! go to the same `FsdbFilesystem` the NFSv3 server uses, so CSV views,
+//! partition directories, access checks, pending writes and caches behave
+//! the same whichever version a client mounts with; only the protocol and
+//! the open and lock state (see `state`) are NFSv4's own.
+//!
+//! Not supported: delegations, pNFS layouts, named attributes, hard links
+//! and symlinks, and RPCSEC_GSS. Requests are answered as the mount's
+//! identity, like NFSv3's, whatever credentials they carry.
+
+use crate::nfs::limits::{self, PathConf};
+use crate::nfs::server::FsdbFilesystem;
+use crate::nfs::v4::attrs::{self, AttrSource, Bitmap, FsLimits};
+use crate::nfs::v4::rpc::{self, AcceptStat, Call};
+use crate::nfs::v4::state::{
+    ClientId, LockError, LockType, Locker, Range, SlotUse, State, StateId, LEASE_TIME, MAX_SLOTS,
+    SHARE_BOTH, SHARE_READ, SHARE_WRITE,
+};
+use crate::nfs::v4::status::nfsstat4;
+use crate::nfs::v4::xdr::{XdrReader, XdrWriter};
+use nfsserve::nfs::{fattr3, fileid3, filename3, ftype3, nfs_fh3, nfsstat3, sattr3, set_size3};
+use nfsserve::vfs::NFSFileSystem;
+use std::sync::{Mutex, MutexGuard};
+use std::time::Instant;
+use tracing::debug;
+
+// Operation numbers (RFC 7530 section 16, RFC 8881 section 18)
+const OP_ACCESS: u32 = 3;
+const OP_CLOSE: u32 = 4;
+const OP_COMMIT: u32 = 5;
+const OP_CREATE: u32 = 6;
+const OP_DELEGPURGE: u32 = 7;
+const OP_DELEGRETURN: u32 = 8;
+const OP_GETATTR: u32 = 9;
+const OP_GETFH: u32 = 10;
+const OP_LINK: u32 = 11;
+const OP_LOCK: u32 = 12;
+const OP_LOCKT: u32 = 13;
+const OP_LOCKU: u32 = 14;
+const OP_LOOKUP: u32 = 15;
+const OP_LOOKUPP: u32 = 16;
+const OP_NVERIFY: u32 = 17;
+const OP_OPEN: u32 = 18;
+const OP_OPENATTR: u32 = 19;
+const OP_OPEN_CONFIRM: u32 = 20;
+const OP_OPEN_DOWNGRADE: u32 = 21;
+const OP_PUTFH: u32 = 22;
+const OP_PUTPUBFH: u32 = 23;
+const OP_PUTROOTFH: u32 = 24;
+const OP_READ: u32 = 25;
+const OP_READDIR: u32 = 26;
+const OP_READLINK: u32 = 27;
+const OP_REMOVE: u32 = 28;
+const OP_RENAME: u32 = 29;
+const OP_RENEW: u32 = 30;
+const OP_RESTOREFH: u32 = 31;
+const OP_SAVEFH: u32 = 32;
+const OP_SECINFO: u32 = 33;
+const OP_SETATTR: u32 = 34;
+const OP_SETCLIENTID: u32 = 35;
+const OP_SETCLIENTID_CONFIRM: u32 = 36;
+const OP_VERIFY: u32 = 37;
+const OP_WRITE: u32 = 38;
+const OP_RELEASE_LOCKOWNER: u32 = 39;
+const OP_BIND_CONN_TO_SESSION: u32 = 41;
+const OP_EXCHANGE_ID: u32 = 42;
+const OP_CREATE_SESSION: u32 = 43;
+const OP_DESTROY_SESSION: u32 = 44;
+const OP_FREE_STATEID: u32 = 45;
+const OP_SECINFO_NO_NAME: u32 = 52;
+const OP_SEQUENCE: u32 = 53;
+const OP_TEST_STATEID: u32 = 55;
+const OP_DESTROY_CLIENTID: u32 = 57;
+const OP_RECLAIM_COMPLETE: u32 = 58;
+const OP_ILLEGAL: u32 = 10044;
+
+/// Highest operation number of each minor version
+const LAST_OP: [u32; 2] = [OP_RELEASE_LOCKOWNER, OP_RECLAIM_COMPLETE];
+
+/// 4.0 operations that 4.1 replaced with sessions
+const V40_ONLY: &[u32] = &[
+    OP_OPEN_CONFIRM,
+    OP_RENEW,
+    OP_SETCLIENTID,
+    OP_SETCLIENTID_CONFIRM,
+    OP_RELEASE_LOCKOWNER,
+];
+
+/// 4.1 operations allowed without a SEQUENCE before them
+const SESSIONLESS: &[u32] = &[
+    OP_BIND_CONN_TO_SESSION,
+    OP_EXCHANGE_ID,
+    OP_CREATE_SESSION,
+    OP_DESTROY_SESSION,
+    OP_DESTROY_CLIENTID,
+];
+
+/// Most operations in one COMPOUND
+const MAX_OPS: u32 = 64;
+
+/// ACCESS4 bits, the same as NFSv3's
+const ACCESS4_READ: u32 = 0x01;
+const ACCESS4_MODIFY: u32 = 0x04;
+const ACCESS4_EXTEND: u32 = 0x08;
+const ACCESS4_ALL: u32 = 0x3f;
+
+/// nfs_ftype4 values CREATE is asked for
+const NF4DIR: u32 = 2;
+const NF4LNK: u32 = 5;
+
+/// OPEN arguments and results
+const OPEN4_CREATE: u32 = 1;
+const UNCHECKED4: u32 = 0;
+const GUARDED4: u32 = 1;
+const EXCLUSIVE4: u32 = 2;
+const EXCLUSIVE4_1: u32 = 3;
+const CLAIM_NULL: u32 = 0;
+const CLAIM_PREVIOUS: u32 = 1;
+const CLAIM_FH: u32 = 4;
+const OPEN4_RESULT_CONFIRM: u32 = 2;
+const OPEN4_RESULT_LOCKTYPE_POSIX: u32 = 4;
+const OPEN_DELEGATE_NONE: u32 = 0;
+
+/// WRITE stability
+const UNSTABLE4: u32 = 0;
+const FILE_SYNC4: u32 = 2;
+
+/// Security flavors SECINFO offers
+const AUTH_NONE: u32 = 0;
+const AUTH_SYS: u32 = 1;
+const RPCSEC_GSS: u32 = 6;
+
+/// EXCHANGE_ID flags and state protection
+const EXCHGID4_FLAG_USE_NON_PNFS: u32 = 0x0001_0000;
+const EXCHGID4_FLAG_CONFIRMED_R: u32 = 0x8000_0000;
+const SP4_NONE: u32 = 0;
+
+/// BIND_CONN_TO_SESSION: the connection serves the fore channel only
+const CDFS4_FORE: u32 = 1;
+
+/// READDIR cookies are file IDs shifted past the reserved cookies 0 to 2
+const COOKIE_OFFSET: u64 = 2;
+
+/// Entries READDIR asks the filesystem for at once
+const READDIR_BATCH: usize = 256;
+
+/// Bytes of a READDIR reply besides its entries
+const READDIR_OVERHEAD: u32 = 32;
+
+/// Largest reply cached for a session slot
+const MAX_CACHED_REPLY: u32 = 64 * 1024;
+
+/// Where a COMPOUND's operations stand
+struct Compound {
+    minor_version: u32,
+    current: Option<fileid3>,
+    saved: Option<fileid3>,
+    current_stateid: Option<StateId>,
+    saved_stateid: Option<StateId>,
+    /// Client of the session, once SEQUENCE has run (4.1)
+    client: Option<ClientId>,
+    /// Session and slot the reply is cached in (4.1)
+    slot: Option<([u8; 16], u32)>,
+}
+
+impl Compound {
+    fn current(&self) -> Result<fileid3, nfsstat4> {
+        self.current.ok_or(nfsstat4::NFS4ERR_NOFILEHANDLE)
+    }
+
+    fn set_current(&mut self, id: fileid3) {
+        self.current = Some(id);
+        self.current_stateid = None;
+    }
+
+    /// `stateid`, with 4.1's "current stateid" replaced by the one the
+    /// previous operation returned
+    fn stateid(&self, stateid: StateId) -> Result<StateId, nfsstat4> {
+        if self.minor_version >= 1 && stateid == StateId::CURRENT {
+            return self.current_stateid.ok_or(nfsstat4::NFS4ERR_BAD_STATEID);
+        }
+        Ok(stateid)
+    }
+}
+
+/// How OPEN creates a file that doesn't exist
+enum CreateHow {
+    /// UNCHECKED4 and GUARDED4; a guarded create fails if the file exists
+    Checked { guarded: bool, attrs: sattr3 },
+    /// EXCLUSIVE4 and EXCLUSIVE4_1; a retry with the same verifier succeeds
+    Exclusive { verifier: [u8; 8], attrs: sattr3 },
+}
+
+/// Negotiated limits of a session channel
+struct ChannelAttrs {
+    max_request_size: u32,
+    max_response_size: u32,
+    max_response_size_cached: u32,
+    max_operations: u32,
+    max_requests: u32,
+}
+
+impl ChannelAttrs {
+    fn decode(reader: &mut XdrReader) -> Result<Self, nfsstat4> {
+        let _header_pad_size = reader.u32()?;
+        let attrs = Self {
+            max_request_size: reader.u32()?,
+            max_response_size: reader.u32()?,
+            max_response_size_cached: reader.u32()?,
+            max_operations: reader.u32()?,
+            max_requests: reader.u32()?,
+        };
+        reader.u32_array()?;
+        Ok(attrs)
+    }
+
+    fn encode(&self, writer: &mut XdrWriter) {
+        writer
+            .u32(0)
+            .u32(self.max_request_size)
+            .u32(self.max_response_size)
+            .u32(self.max_response_size_cached)
+            .u32(self.max_operations)
+            .u32(self.max_requests)
+            .u32_array(&[]);
+    }
+}
+
+/// Answers NFSv4 calls with an `FsdbFilesystem`
+pub struct Nfs4Service {
+    fs: FsdbFilesystem,
+    state: Mutex<State>,
+    limits: FsLimits,
+    /// Changes when the server restarts, so clients resend unstable writes
+    write_verifier: [u8; 8],
+    /// Identifies this server to 4.1 clients
+    server_owner: Vec<u8>,
+}
+
+impl Nfs4Service {
+    /// Service over `fs`, with transfer sizes from its FSINFO
+    pub async fn new(fs: FsdbFilesystem) -> Self {
+        let boot = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap_or_default();
+        let (max_read, max_write, max_file_size) = match fs.fsinfo(fs.root_dir()).await {
+            Ok(info) => (info.rtmax as u64, info.wtmax as u64, info.maxfilesize),
+            Err(_) => (
+                limits::DEFAULT_TRANSFER_SIZE as u64,
+                limits::DEFAULT_TRANSFER_SIZE as u64,
+                limits::MAX_FILE_SIZE,
+            ),
+        };
+        Self {
+            fs,
+            state: Mutex::new(State::new(boot.as_secs() as u32)),
+            limits: FsLimits {
+                max_read,
+                max_write,
+                max_file_size,
+                lease_time: LEASE_TIME.as_secs() as u32,
+                pathconf: PathConf::default(),
+            },
+            write_verifier: (boot.as_nanos() as u64).to_be_bytes(),
+            server_owner: format!("fsdb-{}", boot.as_nanos()).into_bytes(),
+        }
+    }
+
+    fn state(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Reply to an RPC message, or `None` if it isn't a call
+    pub async fn call(&self, message: &[u8]) -> Option<Vec<u8>> {
+        let call = Call::parse(message)?;
+        let reply = if call.program != rpc::NFS_PROGRAM {
+            rpc::reply(&call, AcceptStat::ProgUnavail, &[])
+        } else if call.version != rpc::NFS_V4 {
+            rpc::reply(&call, AcceptStat::ProgMismatch, &[])
+        } else {
+            match call.procedure {
+                rpc::PROC_NULL => rpc::reply(&call, AcceptStat::Success, &[]),
+                rpc::PROC_COMPOUND => {
+                    let body = self.compound(call.args).await;
+                    rpc::reply(&call, AcceptStat::Success, &body)
+                }
+                _ => rpc::reply(&call, AcceptStat::ProcUnavail, &[]),
+            }
+        };
+        Some(reply)
+    }
+
+    /// Run a COMPOUND, returning its encoded result
+    pub async fn compound(&self, args: &[u8]) -> Vec<u8> {
+        self.state().expire(Instant::now());
+
+        let mut reader = XdrReader::new(args);
+        let (tag, minor_version, num_ops) = match decode_header(&mut reader) {
+            Ok(header) => header,
+            Err(status) => return compound_result(status, &[], 0, &[]),
+        };
+        if minor_version > 1 {
+            return compound_result(nfsstat4::NFS4ERR_MINOR_VERS_MISMATCH, &tag, 0, &[]);
+        }
+        if num_ops > MAX_OPS {
+            return compound_result(nfsstat4::NFS4ERR_RESOURCE, &tag, 0, &[]);
+        }
+        debug!("NFSv4.{} COMPOUND of {} operations", minor_version, num_ops);
+
+        let mut ctx = Compound {
+            minor_version,
+            current: None,
+            saved: None,
+            current_stateid: None,
+            saved_stateid: None,
+            client: None,
+            slot: None,
+        };
+        let mut results = XdrWriter::new();
+        let mut count = 0;
+        let mut status = nfsstat4::NFS4_OK;
+        for index in 0..num_ops {
+            let op = match reader.u32() {
+                Ok(op) => op,
+                Err(err) => {
+                    status = err;
+                    break;
+                }
+            };
+            let mut body = XdrWriter::new();
+            let (op, result) = if !(OP_ACCESS..=LAST_OP[minor_version as usize]).contains(&op) {
+                (OP_ILLEGAL, Err(nfsstat4::NFS4ERR_OP_ILLEGAL))
+            } else if minor_version >= 1 && index == 0 && op == OP_SEQUENCE {
+                match self.sequence(&mut reader, &mut ctx, &mut body) {
+                    Ok(Some(replay)) => return replay,
+                    Ok(None) => (op, Ok(())),
+                    Err(err) => (op, Err(err)),
+                }
+            } else if minor_version >= 1 {
+                let result = if op == OP_SEQUENCE {
+                    Err(nfsstat4::NFS4ERR_SEQUENCE_POS)
+                } else if index == 0 && !SESSIONLESS.contains(&op) {
+                    Err(nfsstat4::NFS4ERR_OP_NOT_IN_SESSION)
+                } else if index == 0 && op != OP_DESTROY_SESSION && num_ops > 1 {
+                    Err(nfsstat4::NFS4ERR_NOT_ONLY_OP)
+                } else if V40_ONLY.contains(&op) {
+                    Err(nfsstat4::NFS4ERR_NOTSUPP)
+                } else {
+                    self.op(op, &mut reader, &mut ctx, &mut body).await
+                };
+                (op, result)
+            } else {
+                (op, self.op(op, &mut reader, &mut ctx, &mut body).await)
+            };
+
+            let op_status = result.err().unwrap_or(nfsstat4::NFS4_OK);
+            results
+                .u32(op)
+                .u32(op_status.code())
+                .raw(&body.into_inner());
+            count += 1;
+            if op_status != nfsstat4::NFS4_OK {
+                debug!("NFSv4 operation {} failed: {:?}", op, op_status);
+                status = op_status;
+                break;
+            }
+        }
+
+        let reply = compound_result(status, &tag, count, &results.into_inner());
+        if let Some((session, slot)) = ctx.slot {
+            self.state().cache_reply(&session, slot, reply.clone());
+        }
+        reply
+    }
+
+    /// Run one operation, writing its result body to `body`
+    ///
+    /// Operations write their results only once nothing can fail, apart
+    /// from the few whose error results carry a body.
+    async fn op(
+        &self,
+        op: u32,
+        reader: &mut XdrReader<'_>,
+        ctx: &mut Compound,
+        body: &mut XdrWriter,
+    ) -> Result<(), nfsstat4> {
+        match op {
+            OP_ACCESS => self.access(reader, ctx, body).await,
+            OP_CLOSE => self.close(reader, ctx, body).await,
+            OP_COMMIT => self.commit(reader, ctx, body).await,
+            OP_CREATE => self.create(reader, ctx, body).await,
+            OP_DELEGRETURN => {
+                // No delegations are handed out, so none can be returned
+                StateId::decode(reader)?;
+                Err(nfsstat4::NFS4ERR_BAD_STATEID)
+            }
+            OP_GETATTR => self.getattr(reader, ctx, body).await,
+            OP_GETFH => {
+                let id = ctx.current()?;
+                body.opaque(&self.handle(id));
+                Ok(())
+            }
+            OP_LOCK => self.lock(reader, ctx, body).await,
+            OP_LOCKT => self.lock_test(reader, ctx, body).await,
+            OP_LOCKU => self.unlock(reader, ctx, body),
+            OP_LOOKUP => {
+                let name = component(reader)?;
+                let id = self.fs.lookup(ctx.current()?, &name).await?;
+                ctx.set_current(id);
+                Ok(())
+            }
+            OP_LOOKUPP => {
+                let id = ctx.current()?;
+                if !matches!(self.attr(id).await?.ftype, ftype3::NF3DIR) {
+                    return Err(nfsstat4::NFS4ERR_NOTDIR);
+                }
+                let parent = self.fs.parent(id).await?;
+                ctx.set_current(parent);
+                Ok(())
+            }
+            OP_NVERIFY | OP_VERIFY => self.verify(op, reader, ctx).await,
+            OP_OPEN => self.open(reader, ctx, body).await,
+            OP_OPEN_CONFIRM => {
+                let stateid = StateId::decode(reader)?;
+                let _seqid = reader.u32()?;
+                let stateid = self.state().confirm_open(&stateid, ctx.current()?)?;
+                stateid.encode(body);
+                Ok(())
+            }
+            OP_OPEN_DOWNGRADE => {
+                let stateid = ctx.stateid(StateId::decode(reader)?)?;
+                let _seqid = reader.u32()?;
+                let access = reader.u32()? & SHARE_BOTH;
+                let deny = reader.u32()?;
+                let stateid = self
+                    .state()
+                    .downgrade(&stateid, ctx.current()?, access, deny)?;
+                ctx.current_stateid = Some(stateid);
+                stateid.encode(body);
+                Ok(())
+            }
+            OP_PUTFH => {
+                let handle = nfs_fh3 {
+                    data: reader.opaque()?.to_vec(),
+                };
+                let id = self.fs.fh_to_id(&handle)?;
+                ctx.set_current(id);
+                Ok(())
+            }
+            OP_PUTPUBFH | OP_PUTROOTFH => {
+                ctx.set_current(self.fs.root_dir());
+                Ok(())
+            }
+            OP_READ => self.read(reader, ctx, body).await,
+            OP_READDIR => self.readdir(reader, ctx, body).await,
+            OP_READLINK => {
+                // Nothing on the mount is a symlink
+                ctx.current()?;
+                Err(nfsstat4::NFS4ERR_INVAL)
+            }
+            OP_REMOVE => {
+                let name = component(reader)?;
+                let dir = ctx.current()?;
+                let before = attrs::change(&self.attr(dir).await?);
+                self.fs.remove(dir, &name).await?;
+                let after = attrs::change(&self.attr(dir).await?);
+                change_info(body, before, after);
+                Ok(())
+            }
+            OP_RENAME => self.rename(reader, ctx, body).await,
+            OP_RENEW => {
+                let client = reader.u64()?;
+                self.state().renew(client)
+            }
+            OP_RESTOREFH => {
+                ctx.current = Some(ctx.saved.ok_or(nfsstat4::NFS4ERR_RESTOREFH)?);
+                ctx.current_stateid = ctx.saved_stateid;
+                Ok(())
+            }
+            OP_SAVEFH => {
+                ctx.saved = Some(ctx.current()?);
+                ctx.saved_stateid = ctx.current_stateid;
+                Ok(())
+            }
+            OP_SECINFO => {
+                let name = component(reader)?;
+                self.fs.lookup(ctx.current()?, &name).await?;
+                secinfo(body);
+                // 4.1 consumes the current filehandle
+                if ctx.minor_version >= 1 {
+                    ctx.current = None;
+                }
+                Ok(())
+            }
+            OP_SECINFO_NO_NAME => {
+                let _style = reader.u32()?;
+                ctx.current()?;
+                secinfo(body);
+                ctx.current = None;
+                Ok(())
+            }
+            OP_SETATTR => self.setattr(reader, ctx, body).await,
+            OP_SETCLIENTID => {
+                let verifier = reader.fixed::<8>()?;
+                let owner = reader.opaque()?;
+                // Callback program, address and ident: there are no callbacks
+                reader.u32()?;
+                reader.opaque()?;
+                reader.opaque()?;
+                reader.u32()?;
+                let (client, confirm) = self.state().set_client_id(owner, verifier);
+                body.u64(client).fixed(&confirm);
+                Ok(())
+            }
+            OP_SETCLIENTID_CONFIRM => {
+                let client = reader.u64()?;
+                let confirm = reader.fixed::<8>()?;
+                self.state().confirm_client_id(client, confirm)
+            }
+            OP_WRITE => self.write(reader, ctx, body).await,
+            OP_RELEASE_LOCKOWNER => {
+                let owner = (reader.u64()?, reader.opaque()?.to_vec());
+                self.state().release_lock_owner(&owner)
+            }
+            OP_BIND_CONN_TO_SESSION => {
+                let session = reader.fixed::<16>()?;
+                let _direction = reader.u32()?;
+                let _use_rdma = reader.bool()?;
+                if self.state().slot_count(&session) == 0 {
+                    return Err(nfsstat4::NFS4ERR_BADSESSION);
+                }
+                body.fixed(&session).u32(CDFS4_FORE).bool(false);
+                Ok(())
+            }
+            OP_EXCHANGE_ID => self.exchange_id(reader, body),
+            OP_CREATE_SESSION => self.create_session(reader, body),
+            OP_DESTROY_SESSION => {
+                let session = reader.fixed::<16>()?;
+                self.state().destroy_session(&session)
+            }
+            OP_FREE_STATEID => {
+                let stateid = ctx.stateid(StateId::decode(reader)?)?;
+                self.state().free_stateid(&stateid)
+            }
+            OP_TEST_STATEID => {
+                let len = reader.u32()?;
+                let mut stateids = Vec::new();
+                for _ in 0..len {
+                    stateids.push(StateId::decode(reader)?);
+                }
+                let state = self.state();
+                body.u32(stateids.len() as u32);
+                for stateid in &stateids {
+                    body.u32(state.test_stateid(stateid).code());
+                }
+                Ok(())
+            }
+            OP_DESTROY_CLIENTID => {
+                let client = reader.u64()?;
+                self.state().destroy_client(client)
+            }
+            OP_RECLAIM_COMPLETE => {
+                let _one_fs = reader.bool()?;
+                let client = ctx.client.ok_or(nfsstat4::NFS4ERR_OP_NOT_IN_SESSION)?;
+                self.state().reclaim_complete(client)
+            }
+            // Hard links and named attributes aren't supported, and with no
+            // delegations there's nothing to purge
+            OP_LINK | OP_OPENATTR | OP_DELEGPURGE => Err(nfsstat4::NFS4ERR_NOTSUPP),
+            _ => Err(nfsstat4::NFS4ERR_NOTSUPP),
+        }
+    }
+
+    /// Handle of `id`, the same as NFSv3's
+    fn handle(&self, id: fileid3) -> Vec<u8> {
+        self.fs.id_to_fh(id).data
+    }
+
+    async fn attr(&self, id: fileid3) -> Result<fattr3, nfsstat4> {
+        Ok(self.fs.getattr(id).await?)
+    }
+
+    /// Attributes of a regular file about to be read, written or locked
+    async fn file_attr(&self, id: fileid3) -> Result<fattr3, nfsstat4> {
+        let attr = self.attr(id).await?;
+        match attr.ftype {
+            ftype3::NF3REG => Ok(attr),
+            ftype3::NF3DIR => Err(nfsstat4::NFS4ERR_ISDIR),
+            ftype3::NF3LNK => Err(nfsstat4::NFS4ERR_SYMLINK),
+            _ => Err(nfsstat4::NFS4ERR_INVAL),
+        }
+    }
+
+    fn encode_attrs(&self, requested: &Bitmap, id: fileid3, attr: &fattr3, writer: &mut XdrWriter) {
+        let handle = self.handle(id);
+        let source = AttrSource {
+            attr,
+            handle: &handle,
+            limits: &self.limits,
+        };
+        attrs::encode(requested, &source, writer);
+    }
+
+    async fn access(
+        &self,
+        reader: &mut XdrReader<'_>,
+        ctx: &mut Compound,
+        body: &mut XdrWriter,
+    ) -> Result<(), nfsstat4> {
+        let requested = reader.u32()? & ACCESS4_ALL;
+        let allowed = self.fs.access(ctx.current()?, requested).await?;
+        body.u32(requested).u32(allowed);
+        Ok(())
+    }
+
+    /// CLOSE, applying what was written under the open like a COMMIT
+    async fn close(
+        &self,
+        reader: &mut XdrReader<'_>,
+        ctx: &mut Compound,
+        body: &mut XdrWriter,
+    ) -> Result<(), nfsstat4> {
+        let _seqid = reader.u32()?;
+        let stateid = ctx.stateid(StateId::decode(reader)?)?;
+        let id = ctx.current()?;
+        let wrote = self.state().close(&stateid, id)?;
+        if wrote {
+            self.fs.commit(id).await?;
+        }
+        let closed = StateId {
+            seqid: stateid.seqid.wrapping_add(1),
+            other: stateid.other,
+        };
+        ctx.current_stateid = Some(closed);
+        closed.encode(body);
+        Ok(())
+    }
+
+    async fn commit(
+        &self,
+        reader: &mut XdrReader<'_>,
+        ctx: &mut Compound,
+        body: &mut XdrWriter,
+    ) -> Result<(), nfsstat4> {
+        let _offset = reader.u64()?;
+        let _count = reader.u32()?;
+        self.fs.commit(ctx.current()?).await?;
+        body.fixed(&self.write_verifier);
+        Ok(())
+    }
+
+    /// CREATE, which makes directories; everything else is made with OPEN
+    async fn create(
+        &self,
+        reader: &mut XdrReader<'_>,
+        ctx: &mut Compound,
+        body: &mut XdrWriter,
+    ) -> Result<(), nfsstat4> {
+        match reader.u32()? {
+            NF4DIR => {}
+            NF4LNK => return Err(nfsstat4::NFS4ERR_NOTSUPP),
+            _ => return Err(nfsstat4::NFS4ERR_BADTYPE),
+        }
+        let name = component(reader)?;
+        attrs::decode_settable(reader)?;
+        let dir = ctx.current()?;
+        let before = attrs::change(&self.attr(dir).await?);
+        let (id, _) = self.fs.mkdir(dir, &name).await?;
+        let after = attrs::change(&self.attr(dir).await?);
+        ctx.set_current(id);
+        change_info(body, before, after);
+        // Directories get the filesystem's own mode
+        Bitmap::default().encode(body);
+        Ok(())
+    }
+
+    async fn getattr(
+        &self,
+        reader: &mut XdrReader<'_>,
+        ctx: &mut Compound,
+        body: &mut XdrWriter,
+    ) -> Result<(), nfsstat4> {
+        let requested = Bitmap::decode(reader)?;
+        let id = ctx.current()?;
+        let attr = self.attr(id).await?;
+        self.encode_attrs(&requested, id, &attr, body);
+        Ok(())
+    }
+
+    async fn lock(
+        &self,
+        reader: &mut XdrReader<'_>,
+        ctx: &mut Compound,
+        body: &mut XdrWriter,
+    ) -> Result<(), nfsstat4> {
+        let kind = LockType::from_code(reader.u32()?)?;
+        let reclaim = reader.bool()?;
+        let offset = reader.u64()?;
+        let length = reader.u64()?;
+        let locker = if reader.bool()? {
+            let _open_seqid = reader.u32()?;
+            let open = ctx.stateid(StateId::decode(reader)?)?;
+            let _lock_seqid = reader.u32()?;
+            let owner = (reader.u64()?, reader.opaque()?.to_vec());
+            Locker::New { open, owner }
+        } else {
+            let lock = ctx.stateid(StateId::decode(reader)?)?;
+            let _lock_seqid = reader.u32()?;
+            Locker::Existing { lock }
+        };
+        if reclaim {
+            return Err(nfsstat4::NFS4ERR_NO_GRACE);
+        }
+        let id = ctx.current()?;
+        self.file_attr(id).await?;
+        let range = Range::new(offset, length)?;
+
+        let result = self.state().lock(locker, id, kind, range);
+        match result {
+            Ok(stateid) => {
+                ctx.current_stateid = Some(stateid);
+                stateid.encode(body);
+                Ok(())
+            }
+            Err(LockError::Denied(denied)) => {
+                denied.encode(body);
+                Err(nfsstat4::NFS4ERR_DENIED)
+            }
+            Err(LockError::Status(status)) => Err(status),
+        }
+    }
+
+    async fn lock_test(
+        &self,
+        reader: &mut XdrReader<'_>,
+        ctx: &mut Compound,
+        body: &mut XdrWriter,
+    ) -> Result<(), nfsstat4> {
+        let kind = LockType::from_code(reader.u32()?)?;
+        let offset = reader.u64()?;
+        let length = reader.u64()?;
+        let owner = (reader.u64()?, reader.opaque()?.to_vec());
+        let id = ctx.current()?;
+        self.file_attr(id).await?;
+        let range = Range::new(offset, length)?;
+
+        let mut state = self.state();
+        if ctx.minor_version == 0 {
+            state.renew(owner.0)?;
+        }
+        match state.test_lock(&owner, id, kind, &range) {
+            Some(denied) => {
+                denied.encode(body);
+                Err(nfsstat4::NFS4ERR_DENIED)
+            }
+            None => Ok(()),
+        }
+    }
+
+    fn unlock(
+        &self,
+        reader: &mut XdrReader<'_>,
+        ctx: &mut Compound,
+        body: &mut XdrWriter,
+    ) -> Result<(), nfsstat4> {
+        LockType::from_code(reader.u32()?)?;
+        let _seqid = reader.u32()?;
+        let stateid = ctx.stateid(StateId::decode(reader)?)?;
+        let offset = reader.u64()?;
+        let length = reader.u64()?;
+        let range = Range::new(offset, length)?;
+        let stateid = self.state().unlock(&stateid, ctx.current()?, range)?;
+        ctx.current_stateid = Some(stateid);
+        stateid.encode(body);
+        Ok(())
+    }
+
+    /// VERIFY and NVERIFY: compare attributes with the client's
+    async fn verify(
+        &self,
+        op: u32,
+        reader: &mut XdrReader<'_>,
+        ctx: &mut Compound,
+    ) -> Result<(), nfsstat4> {
+        let requested = Bitmap::decode(reader)?;
+        let values = reader.opaque()?;
+        if !attrs::readable(&requested) {
+            return Err(nfsstat4::NFS4ERR_ATTRNOTSUPP);
+        }
+        let id = ctx.current()?;
+        let attr = self.attr(id).await?;
+        let mut ours = XdrWriter::new();
+        self.encode_attrs(&requested, id, &attr, &mut ours);
+        let mut theirs = XdrWriter::new();
+        requested.encode(&mut theirs);
+        theirs.opaque(values);
+
+        let same = ours.into_inner() == theirs.into_inner();
+        match (op, same) {
+            (OP_VERIFY, false) => Err(nfsstat4::NFS4ERR_NOT_SAME),
+            (OP_NVERIFY, true) => Err(nfsstat4::NFS4ERR_SAME),
+            _ => Ok(()),
+        }
+    }
+
+    async fn open(
+        &self,
+        reader: &mut XdrReader<'_>,
+        ctx: &mut Compound,
+        body: &mut XdrWriter,
+    ) -> Result<(), nfsstat4> {
+        let _seqid = reader.u32()?;
+        // 4.1 puts delegation wishes above the access bits
+        let access = reader.u32()? & 0xff;
+        let deny = reader.u32()?;
+        let owner = (reader.u64()?, reader.opaque()?.to_vec());
+        let create = if reader.u32()? == OPEN4_CREATE {
+            Some(match reader.u32()? {
+                mode @ (UNCHECKED4 | GUARDED4) => CreateHow::Checked {
+                    guarded: mode == GUARDED4,
+                    attrs: attrs::decode_settable(reader)?.0,
+                },
+                EXCLUSIVE4 => CreateHow::Exclusive {
+                    verifier: reader.fixed()?,
+                    attrs: sattr3::default(),
+                },
+                EXCLUSIVE4_1 if ctx.minor_version >= 1 => CreateHow::Exclusive {
+                    verifier: reader.fixed()?,
+                    attrs: attrs::decode_settable(reader)?.0,
+                },
+                _ => return Err(nfsstat4::NFS4ERR_INVAL),
+            })
+        } else {
+            None
+        };
+
+        let current = ctx.current()?;
+        let (id, before, after) = match reader.u32()? {
+            CLAIM_NULL => {
+                let name = component(reader)?;
+                let dir_attr = self.attr(current).await?;
+                if !matches!(dir_attr.ftype, ftype3::NF3DIR) {
+                    return Err(nfsstat4::NFS4ERR_NOTDIR);
+                }
+                let before = attrs::change(&dir_attr);
+                let (id, created) = self.open_name(current, &name, create).await?;
+                let after = if created {
+                    attrs::change(&self.attr(current).await?)
+                } else {
+                    before
+                };
+                (id, before, after)
+            }
+            CLAIM_FH if ctx.minor_version >= 1 => {
+                if create.is_some() {
+                    return Err(nfsstat4::NFS4ERR_INVAL);
+                }
+                (current, 0, 0)
+            }
+            // Nothing is kept across restarts to reclaim
+            CLAIM_PREVIOUS => return Err(nfsstat4::NFS4ERR_NO_GRACE),
+            _ => return Err(nfsstat4::NFS4ERR_NOTSUPP),
+        };
+        self.file_attr(id).await?;
+
+        let mut wanted = 0;
+        if access & SHARE_READ != 0 {
+            wanted |= ACCESS4_READ;
+        }
+        if access & SHARE_WRITE != 0 {
+            wanted |= ACCESS4_MODIFY | ACCESS4_EXTEND;
+        }
+        let allowed = self.fs.access(id, wanted).await?;
+        if access & SHARE_READ != 0 && allowed & ACCESS4_READ == 0
+            || access & SHARE_WRITE != 0 && allowed & (ACCESS4_MODIFY | ACCESS4_EXTEND) == 0
+        {
+            return Err(nfsstat4::NFS4ERR_ACCESS);
+        }
+
+        let (stateid, confirm) = self
+            .state()
+            .open(owner, id, access, deny, ctx.minor_version)?;
+        ctx.set_current(id);
+        ctx.current_stateid = Some(stateid);
+
+        stateid.encode(body);
+        change_info(body, before, after);
+        let mut flags = OPEN4_RESULT_LOCKTYPE_POSIX;
+        if confirm {
+            flags |= OPEN4_RESULT_CONFIRM;
+        }
+        body.u32(flags);
+        // Created files get the filesystem's own mode
+        Bitmap::default().encode(body);
+        body.u32(OPEN_DELEGATE_NONE);
+        Ok(())
+    }
+
+    /// File `name` in `dir` to open, created as `create` says if it's
+    /// missing; returns whether it was created
+    async fn open_name(
+        &self,
+        dir: fileid3,
+        name: &filename3,
+        create: Option<CreateHow>,
+    ) -> Result<(fileid3, bool), nfsstat4> {
+        let existing = match self.fs.lookup(dir, name).await {
+            Ok(id) => Some(id),
+            Err(nfsstat3::NFS3ERR_NOENT) => None,
+            Err(status) => return Err(status.into()),
+        };
+        match (existing, create) {
+            (None, None) => Err(nfsstat4::NFS4ERR_NOENT),
+            (Some(id), None) => Ok((id, false)),
+            (Some(_), Some(CreateHow::Checked { guarded: true, .. })) => {
+                Err(nfsstat4::NFS4ERR_EXIST)
+            }
+            (Some(id), Some(CreateHow::Checked { attrs: sattr, .. })) => {
+                // Opening with O_TRUNC sets the size
+                if let set_size3::size(size) = sattr.size {
+                    let truncate = sattr3 {
+                        size: set_size3::size(size),
+                        ..sattr3::default()
+                    };
+                    self.fs.setattr(id, truncate).await?;
+                }
+                Ok((id, false))
+            }
+            (Some(id), Some(CreateHow::Exclusive { verifier, .. })) => {
+                if self.state().exclusive_verifier(id) == Some(verifier) {
+                    Ok((id, false))
+                } else {
+                    Err(nfsstat4::NFS4ERR_EXIST)
+                }
+            }
+            (None, Some(how)) => {
+                let (sattr, verifier) = match how {
+                    CreateHow::Checked { attrs, .. } => (attrs, None),
+                    CreateHow::Exclusive { verifier, attrs } => (attrs, Some(verifier)),
+                };
+                let (id, _) = self.fs.create(dir, name, sattr).await?;
+                if let Some(verifier) = verifier {
+                    self.state().set_exclusive_verifier(id, verifier);
+                }
+                Ok((id, true))
+            }
+        }
+    }
+
+    async fn read(
+        &self,
+        reader: &mut XdrReader<'_>,
+        ctx: &mut Compound,
+        body: &mut XdrWriter,
+    ) -> Result<(), nfsstat4> {
+        let stateid = ctx.stateid(StateId::decode(reader)?)?;
+        let offset = reader.u64()?;
+        let count = reader.u32()?;
+        let id = ctx.current()?;
+        self.file_attr(id).await?;
+        self.state().check_io(&stateid, id, false)?;
+
+        let count = count.min(self.limits.max_read as u32);
+        let (data, eof) = self.fs.read(id, offset, count).await?;
+        body.bool(eof).opaque(&data);
+        Ok(())
+    }
+
+    async fn readdir(
+        &self,
+        reader: &mut XdrReader<'_>,
+        ctx: &mut Compound,
+        body: &mut XdrWriter,
+    ) -> Result<(), nfsstat4> {
+        let cookie = reader.u64()?;
+        let _verifier = reader.fixed::<8>()?;
+        let _dircount = reader.u32()?;
+        let maxcount = reader.u32()?;
+        let requested = Bitmap::decode(reader)?;
+        let dir = ctx.current()?;
+        if !matches!(self.attr(dir).await?.ftype, ftype3::NF3DIR) {
+            return Err(nfsstat4::NFS4ERR_NOTDIR);
+        }
+        let start_after = match cookie {
+            0 => 0,
+            1 | 2 => return Err(nfsstat4::NFS4ERR_BAD_COOKIE),
+            cookie => cookie - COOKIE_OFFSET,
+        };
+
+        let result = self.fs.readdir(dir, start_after, READDIR_BATCH).await?;
+        let budget = maxcount.saturating_sub(READDIR_OVERHEAD) as usize;
+        let mut entries = XdrWriter::new();
+        let mut truncated = false;
+        for entry in &result.entries {
+            let name: &[u8] = entry.name.as_ref();
+            if name == b"." || name == b".." {
+                continue;
+            }
+            let Some(cookie) = entry.fileid.checked_add(COOKIE_OFFSET) else {
+                return Err(nfsstat4::NFS4ERR_SERVERFAULT);
+            };
+            let mut encoded = XdrWriter::new();
+            encoded.bool(true).u64(cookie).opaque(name);
+            self.encode_attrs(&requested, entry.fileid, &entry.attr, &mut encoded);
+            if entries.len() + encoded.len() > budget {
+                truncated = true;
+                break;
+            }
+            entries.raw(&encoded.into_inner());
+        }
+        if truncated && entries.is_empty() {
+            return Err(nfsstat4::NFS4ERR_TOOSMALL);
+        }
+
+        // Cookies are file IDs, which stay valid, so the verifier is unused
+        body.fixed(&[0; 8])
+            .raw(&entries.into_inner())
+            .bool(false)
+            .bool(!truncated && result.end);
+        Ok(())
+    }
+
+    /// RENAME from the saved directory to the current one
+    async fn rename(
+        &self,
+        reader: &mut XdrReader<'_>,
+        ctx: &mut Compound,
+        body: &mut XdrWriter,
+    ) -> Result<(), nfsstat4> {
+        let old_name = component(reader)?;
+        let new_name = component(reader)?;
+        let from = ctx.saved.ok_or(nfsstat4::NFS4ERR_NOFILEHANDLE)?;
+        let to = ctx.current()?;
+        let from_before = attrs::change(&self.attr(from).await?);
+        let to_before = attrs::change(&self.attr(to).await?);
+        self.fs.rename(from, &old_name, to, &new_name).await?;
+        let from_after = attrs::change(&self.attr(from).await?);
+        let to_after = attrs::change(&self.attr(to).await?);
+        change_info(body, from_before, from_after);
+        change_info(body, to_before, to_after);
+        Ok(())
+    }
+
+    /// SETATTR, whose result lists the attributes set even when it fails
+    async fn setattr(
+        &self,
+        reader: &mut XdrReader<'_>,
+        ctx: &mut Compound,
+        body: &mut XdrWriter,
+    ) -> Result<(), nfsstat4> {
+        let result = async {
+            let stateid = ctx.stateid(StateId::decode(reader)?)?;
+            let (sattr, set) = attrs::decode_settable(reader)?;
+            let id = ctx.current()?;
+            if matches!(sattr.size, set_size3::size(_)) {
+                self.file_attr(id).await?;
+                self.state().check_io(&stateid, id, true)?;
+            }
+            self.fs.setattr(id, sattr).await?;
+            Ok::<_, nfsstat4>(set)
+        }
+        .await;
+        match result {
+            Ok(set) => {
+                set.encode(body);
+                Ok(())
+            }
+            Err(status) => {
+                Bitmap::default().encode(body);
+                Err(status)
+            }
+        }
+    }
+
+    /// WRITE; stable writes are committed before the reply
+    async fn write(
+        &self,
+        reader: &mut XdrReader<'_>,
+        ctx: &mut Compound,
+        body: &mut XdrWriter,
+    ) -> Result<(), nfsstat4> {
+        let stateid = ctx.stateid(StateId::decode(reader)?)?;
+        let offset = reader.u64()?;
+        let stable = reader.u32()?;
+        let data = reader.opaque()?;
+        let id = ctx.current()?;
+        self.file_attr(id).await?;
+        self.state().check_io(&stateid, id, true)?;
+
+        self.fs.write(id, offset, data).await?;
+        let committed = if stable == UNSTABLE4 {
+            UNSTABLE4
+        } else {
+            self.fs.commit(id).await?;
+            FILE_SYNC4
+        };
+        body.u32(data.len() as u32)
+            .u32(committed)
+            .fixed(&self.write_verifier);
+        Ok(())
+    }
+
+    fn exchange_id(&self, reader: &mut XdrReader, body: &mut XdrWriter) -> Result<(), nfsstat4> {
+        let verifier = reader.fixed::<8>()?;
+        let owner = reader.opaque()?;
+        let _flags = reader.u32()?;
+        if reader.u32()? != SP4_NONE {
+            return Err(nfsstat4::NFS4ERR_NOTSUPP);
+        }
+        // The client's implementation ID: domain, name and date
+        for _ in 0..reader.u32()? {
+            reader.opaque()?;
+            reader.opaque()?;
+            reader.i64()?;
+            reader.u32()?;
+        }
+
+        let (client, sequence, confirmed) = self.state().exchange_id(owner, verifier);
+        let mut flags = EXCHGID4_FLAG_USE_NON_PNFS;
+        if confirmed {
+            flags |= EXCHGID4_FLAG_CONFIRMED_R;
+        }
+        body.u64(client)
+            .u32(sequence)
+            .u32(flags)
+            .u32(SP4_NONE)
+            // Server owner (minor and major ID) and scope
+            .u64(0)
+            .opaque(&self.server_owner)
+            .opaque(&self.server_owner)
+            // No implementation ID
+            .u32(0);
+        Ok(())
+    }
+
+    fn create_session(&self, reader: &mut XdrReader, body: &mut XdrWriter) -> Result<(), nfsstat4> {
+        let client = reader.u64()?;
+        let sequence = reader.u32()?;
+        let _flags = reader.u32()?;
+        let fore = ChannelAttrs::decode(reader)?;
+        let back = ChannelAttrs::decode(reader)?;
+        let _callback_program = reader.u32()?;
+        for _ in 0..reader.u32()? {
+            match reader.u32()? {
+                AUTH_NONE => {}
+                AUTH_SYS => {
+                    reader.u32()?;
+                    reader.opaque()?;
+                    reader.u32()?;
+                    reader.u32()?;
+                    reader.u32_array()?;
+                }
+                RPCSEC_GSS => {
+                    reader.u32()?;
+                    reader.opaque()?;
+                    reader.opaque()?;
+                }
+                _ => return Err(nfsstat4::NFS4ERR_INVAL),
+            }
+        }
+
+        let slots = fore.max_requests.clamp(1, MAX_SLOTS);
+        let session = self.state().create_session(client, sequence, slots)?;
+        let max_message = rpc::MAX_RECORD_SIZE as u32;
+        let fore = ChannelAttrs {
+            max_request_size: fore.max_request_size.min(max_message),
+            max_response_size: fore.max_response_size.min(max_message),
+            max_response_size_cached: fore.max_response_size_cached.min(MAX_CACHED_REPLY),
+            max_operations: fore.max_operations.min(MAX_OPS),
+            max_requests: slots,
+        };
+        // No flags: the session isn't persistent and has no back channel
+        body.fixed(&session).u32(sequence).u32(0);
+        fore.encode(body);
+        back.encode(body);
+        Ok(())
+    }
+
+    /// SEQUENCE, returning the cached reply of a retransmitted request
+    fn sequence(
+        &self,
+        reader: &mut XdrReader,
+        ctx: &mut Compound,
+        body: &mut XdrWriter,
+    ) -> Result<Option<Vec<u8>>, nfsstat4> {
+        let session = reader.fixed::<16>()?;
+        let seqid = reader.u32()?;
+        let slot = reader.u32()?;
+        let _highest_slot = reader.u32()?;
+        let _cache_this = reader.bool()?;
+
+        let mut state = self.state();
+        let (client, slot_use) = state.sequence(&session, slot, seqid)?;
+        if let SlotUse::Replay(reply) = slot_use {
+            return Ok(Some(reply));
+        }
+        ctx.client = Some(client);
+        ctx.slot = Some((session, slot));
+        let highest = state.slot_count(&session).saturating_sub(1);
+        body.fixed(&session)
+            .u32(seqid)
+            .u32(slot)
+            .u32(highest)
+            .u32(highest)
+            .u32(0);
+        Ok(None)
+    }
+}
+
+/// Tag, minor version and operation count of a COMPOUND
+fn decode_header(reader: &mut XdrReader) -> Result<(Vec<u8>, u32, u32), nfsstat4> {
+    Ok((reader.opaque()?.to_vec(), reader.u32()?, reader.u32()?))
+}
+
+/// A COMPOUND's result: its status, tag and operation results
+fn compound_result(status: nfsstat4, tag: &[u8], count: u32, results: &[u8]) -> Vec<u8> {
+    let mut writer = XdrWriter::new();
+    writer
+        .u32(status.code())
+        .opaque(tag)
+        .u32(count)
+        .raw(results);
+    writer.into_inner()
+}
+
+/// A name in a directory: UTF-8, not empty, "." or "..", nor too long
+fn component(reader: &mut XdrReader) -> Result<filename3, nfsstat4> {
+    let name = reader.opaque()?;
+    if name.is_empty() || std::str::from_utf8(name).is_err() {
+        return Err(nfsstat4::NFS4ERR_INVAL);
+    }
+    if name == b"." || name == b".." {
+        return Err(nfsstat4::NFS4ERR_BADNAME);
+    }
+    limits::check_name(name)?;
+    Ok(name.into())
+}
+
+/// change_info4 of a directory: not atomic, as other clients may change
+/// it in between
+fn change_info(body: &mut XdrWriter, before: u64, after: u64) {
+    body.bool(false).u64(before).u64(after);
+}
+
+/// Security flavors, AUTH_SYS preferred
+fn secinfo(body: &mut XdrWriter) {
+    body.u32(2).u32(AUTH_SYS).u32(AUTH_NONE);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database_ops::DatabaseOps;
+    use arrow::array::{ArrayRef, Int32Array, RecordBatch, StringArray};
+    use arrow::datatypes::{DataType, Field, Schema};
+    use std::sync::Arc;
+    use tempfile::TempDir;
+
+    async fn service(temp_dir: &TempDir) -> Nfs4Service {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("id", DataType::Int32, false),
+            Field::new("name", DataType::Utf8, false),
+        ]));
+        let db = DatabaseOps::create(temp_dir.path().join("db"), schema.clone())
+            .await
+            .unwrap();
+        let batch = RecordBatch::try_new(
+            schema,
+            vec![
+                Arc::new(Int32Array::from(vec![1, 2])) as ArrayRef,
+                Arc::new(StringArray::from(vec!["Alice", "Bob"])) as ArrayRef,
+            ],
+        )
+        .unwrap();
+        db.insert(batch).await.unwrap();
+        Nfs4Service::new(FsdbFilesystem::new(Arc::new(db))).await
+    }
+
+    /// COMPOUND arguments of a 4.0 request
+    fn compound(ops: impl FnOnce(&mut XdrWriter), num_ops: u32) -> Vec<u8> {
+        let mut writer = XdrWriter::new();
+        writer.opaque(b"test").u32(0).u32(num_ops);
+        ops(&mut writer);
+        writer.into_inner()
+    }
+
+    /// Status of a COMPOUND result, and a reader at its operation results
+    fn statuses(reply: &[u8]) -> (u32, XdrReader<'_>) {
+        let mut reader = XdrReader::new(reply);
+        let status = reader.u32().unwrap();
+        reader.opaque().unwrap();
+        reader.u32().unwrap();
+        (status, reader)
+    }
+
+    #[tokio::test]
+    async fn test_lookup_and_read_data_csv() {
+        let temp_dir = TempDir::new().unwrap();
+        let service = service(&temp_dir).await;
+
+        let args = compound(
+            |w| {
+                w.u32(OP_PUTROOTFH);
+                w.u32(OP_LOOKUP).opaque(b"data");
+                w.u32(OP_LOOKUP).opaque(b"data.csv");
+                w.u32(OP_GETATTR);
+                Bitmap::from_bits(&[attrs::FATTR4_TYPE]).encode(w);
+                w.u32(OP_READ);
+                StateId::ANONYMOUS.encode(w);
+                w.u64(0).u32(4096);
+            },
+            5,
+        );
+        let reply = service.compound(&args).await;
+        let (status, mut reader) = statuses(&reply);
+        assert_eq!(status, 0);
+        for op in [OP_PUTROOTFH, OP_LOOKUP, OP_LOOKUP] {
+            assert_eq!((reader.u32(), reader.u32()), (Ok(op), Ok(0)));
+        }
+        assert_eq!((reader.u32(), reader.u32()), (Ok(OP_GETATTR), Ok(0)));
+        Bitmap::decode(&mut reader).unwrap();
+        let mut values = XdrReader::new(reader.opaque().unwrap());
+        assert_eq!(values.u32(), Ok(1), "a regular file");
+        assert_eq!((reader.u32(), reader.u32()), (Ok(OP_READ), Ok(0)));
+        assert_eq!(reader.bool(), Ok(true));
+        let csv = String::from_utf8(reader.opaque().unwrap().to_vec()).unwrap();
+        assert!(csv.contains("Alice") && csv.contains("Bob"), "{}", csv);
+    }
+
+    #[tokio::test]
+    async fn test_processing_stops_at_first_error() {
+        let temp_dir = TempDir::new().unwrap();
+        let service = service(&temp_dir).await;
+
+        // GETFH without a current filehandle, then an operation never run
+        let args = compound(
+            |w| {
+                w.u32(OP_GETFH);
+                w.u32(OP_PUTROOTFH);
+            },
+            2,
+        );
+        let reply = service.compound(&args).await;
+        let (status, mut reader) = statuses(&reply);
+        assert_eq!(status, nfsstat4::NFS4ERR_NOFILEHANDLE.code());
+        assert_eq!(reader.u32(), Ok(OP_GETFH));
+        assert_eq!(reader.u32(), Ok(nfsstat4::NFS4ERR_NOFILEHANDLE.code()));
+        assert!(reader.remaining().is_empty());
+
+        // A 4.1 operation in a 4.0 COMPOUND
+        let args = compound(
+            |w| {
+                w.u32(OP_SEQUENCE);
+            },
+            1,
+        );
+        let reply = service.compound(&args).await;
+        let (status, mut reader) = statuses(&reply);
+        assert_eq!(status, nfsstat4::NFS4ERR_OP_ILLEGAL.code());
+        assert_eq!(reader.u32(), Ok(OP_ILLEGAL));
+
+        // A 4.1 COMPOUND must start with SEQUENCE
+        let mut writer = XdrWriter::new();
+        writer.opaque(b"").u32(1).u32(1).u32(OP_PUTROOTFH);
+        let reply = service.compound(&writer.into_inner()).await;
+        assert_eq!(
+            statuses(&reply).0,
+            nfsstat4::NFS4ERR_OP_NOT_IN_SESSION.code()
+        );
+
+        let mut writer = XdrWriter::new();
+        writer.opaque(b"").u32(2).u32(0);
+        let reply = service.compound(&writer.into_inner()).await;
+        assert_eq!(
+            statuses(&reply).0,
+            nfsstat4::NFS4ERR_MINOR_VERS_MISMATCH.code()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_lookupp_and_readdir() {
+        let temp_dir = TempDir::new().unwrap();
+        let service = service(&temp_dir).await;
+
+        let args = compound(
+            |w| {
+                w.u32(OP_PUTROOTFH);
+                w.u32(OP_LOOKUP).opaque(b"data");
+                w.u32(OP_LOOKUPP);
+                w.u32(OP_READDIR).u64(0).fixed(&[0; 8]).u32(4096).u32(8192);
+                Bitmap::from_bits(&[attrs::FATTR4_FILEID]).encode(w);
+            },
+            4,
+        );
+        let reply = service.compound(&args).await;
+        let (status, mut reader) = statuses(&reply);
+        assert_eq!(status, 0);
+        for _ in 0..3 {
+            reader.u32().unwrap();
+            reader.u32().unwrap();
+        }
+        assert_eq!((reader.u32(), reader.u32()), (Ok(OP_READDIR), Ok(0)));
+        reader.fixed::<8>().unwrap();
+        let mut names = Vec::new();
+        while reader.bool().unwrap() {
+            let cookie = reader.u64().unwrap();
+            assert!(cookie > 2, "cookies 0 to 2 are reserved");
+            names.push(String::from_utf8(reader.opaque().unwrap().to_vec()).unwrap());
+            Bitmap::decode(&mut reader).unwrap();
+            reader.opaque().unwrap();
+        }
+        assert_eq!(names, vec!["data"]);
+        assert_eq!(reader.bool(), Ok(true), "end of directory");
+    }
+}
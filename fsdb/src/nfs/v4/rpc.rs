@@ -0,0 +1,216 @@
+//! ONC RPC (RFC 5531) framing for the NFSv4 server
+//!
+//! NFSv4 runs on a single TCP port with no MOUNT or portmapper protocol.
+//! Each message is sent as record-marked fragments; a call names the
+//! program, version and procedure, and carries credentials the server
+//! doesn't use, as the mount's identity is the filesystem's.
+
+use crate::nfs::v4::xdr::{XdrReader, XdrWriter};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+/// ONC RPC program number of NFS
+pub const NFS_PROGRAM: u32 = 100003;
+
+/// The only NFS version this listener serves
+pub const NFS_V4: u32 = 4;
+
+/// Procedures of NFSv4
+pub const PROC_NULL: u32 = 0;
+pub const PROC_COMPOUND: u32 = 1;
+
+/// Largest record accepted, well above the largest WRITE a client sends
+pub const MAX_RECORD_SIZE: usize = 8 * 1024 * 1024;
+
+const CALL: u32 = 0;
+const REPLY: u32 = 1;
+const RPC_VERSION: u32 = 2;
+const MSG_ACCEPTED: u32 = 0;
+const MSG_DENIED: u32 = 1;
+const RPC_MISMATCH: u32 = 0;
+const AUTH_NONE: u32 = 0;
+const LAST_FRAGMENT: u32 = 0x8000_0000;
+
+/// How an accepted call was answered
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AcceptStat {
+    Success = 0,
+    ProgUnavail = 1,
+    ProgMismatch = 2,
+    ProcUnavail = 3,
+    GarbageArgs = 4,
+}
+
+/// Header of an RPC call, and where its arguments start
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Call<'a> {
+    pub xid: u32,
+    pub rpc_version: u32,
+    pub program: u32,
+    pub version: u32,
+    pub procedure: u32,
+    pub args: &'a [u8],
+}
+
+impl<'a> Call<'a> {
+    /// Parse a call message; `None` if it isn't one
+    pub fn parse(message: &'a [u8]) -> Option<Self> {
+        let mut reader = XdrReader::new(message);
+        let xid = reader.u32().ok()?;
+        if reader.u32().ok()? != CALL {
+            return None;
+        }
+        let rpc_version = reader.u32().ok()?;
+        let program = reader.u32().ok()?;
+        let version = reader.u32().ok()?;
+        let procedure = reader.u32().ok()?;
+        // Credential and verifier: flavor and body of each
+        for _ in 0..2 {
+            reader.u32().ok()?;
+            reader.opaque().ok()?;
+        }
+        Some(Self {
+            xid,
+            rpc_version,
+            program,
+            version,
+            procedure,
+            args: reader.remaining(),
+        })
+    }
+}
+
+/// Reply to `call`: accepted with `stat`, followed by `body`
+///
+/// A program mismatch reports version 4 as the only one served, and a call
+/// of another RPC version is denied.
+pub fn reply(call: &Call, stat: AcceptStat, body: &[u8]) -> Vec<u8> {
+    let mut writer = XdrWriter::new();
+    writer.u32(call.xid).u32(REPLY);
+    if call.rpc_version != RPC_VERSION {
+        writer
+            .u32(MSG_DENIED)
+            .u32(RPC_MISMATCH)
+            .u32(RPC_VERSION)
+            .u32(RPC_VERSION);
+        return writer.into_inner();
+    }
+    writer
+        .u32(MSG_ACCEPTED)
+        .u32(AUTH_NONE)
+        .opaque(&[])
+        .u32(stat as u32);
+    match stat {
+        AcceptStat::Success => {
+            writer.raw(body);
+        }
+        AcceptStat::ProgMismatch => {
+            writer.u32(NFS_V4).u32(NFS_V4);
+        }
+        _ => {}
+    }
+    writer.into_inner()
+}
+
+/// Read one record, joining its fragments; `None` at end of stream
+pub async fn read_record<R: AsyncRead + Unpin>(reader: &mut R) -> std::io::Result<Option<Vec<u8>>> {
+    let mut record = Vec::new();
+    loop {
+        let mut header = [0u8; 4];
+        match reader.read_exact(&mut header).await {
+            Ok(_) => {}
+            Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof && record.is_empty() => {
+                return Ok(None);
+            }
+            Err(e) => return Err(e),
+        }
+        let header = u32::from_be_bytes(header);
+        let len = (header & !LAST_FRAGMENT) as usize;
+        if record.len() + len > MAX_RECORD_SIZE {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!("RPC record larger than {} bytes", MAX_RECORD_SIZE),
+            ));
+        }
+        let start = record.len();
+        record.resize(start + len, 0);
+        reader.read_exact(&mut record[start..]).await?;
+        if header & LAST_FRAGMENT != 0 {
+            return Ok(Some(record));
+        }
+    }
+}
+
+/// Write `message` as a single-fragment record
+pub async fn write_record<W: AsyncWrite + Unpin>(
+    writer: &mut W,
+    message: &[u8],
+) -> std::io::Result<()> {
+    let header = LAST_FRAGMENT | message.len() as u32;
+    let mut record = Vec::with_capacity(4 + message.len());
+    record.extend_from_slice(&header.to_be_bytes());
+    record.extend_from_slice(message);
+    writer.write_all(&record).await?;
+    writer.flush().await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn call_message(version: u32, procedure: u32, args: &[u8]) -> Vec<u8> {
+        let mut writer = XdrWriter::new();
+        writer
+            .u32(42)
+            .u32(CALL)
+            .u32(RPC_VERSION)
+            .u32(NFS_PROGRAM)
+            .u32(version)
+            .u32(procedure)
+            // AUTH_SYS credential, AUTH_NONE verifier
+            .u32(1)
+            .opaque(&[0; 20])
+            .u32(AUTH_NONE)
+            .opaque(&[])
+            .raw(args);
+        writer.into_inner()
+    }
+
+    #[test]
+    fn test_parse_call() {
+        let message = call_message(NFS_V4, PROC_COMPOUND, &[0, 0, 0, 9]);
+        let call = Call::parse(&message).unwrap();
+        assert_eq!(call.xid, 42);
+        assert_eq!(call.program, NFS_PROGRAM);
+        assert_eq!(call.version, NFS_V4);
+        assert_eq!(call.procedure, PROC_COMPOUND);
+        assert_eq!(call.args, &[0, 0, 0, 9]);
+
+        assert!(Call::parse(&message[..20]).is_none());
+    }
+
+    #[test]
+    fn test_version_mismatch_reply() {
+        let message = call_message(3, PROC_NULL, &[]);
+        let call = Call::parse(&message).unwrap();
+        let reply = reply(&call, AcceptStat::ProgMismatch, &[]);
+        let mut reader = XdrReader::new(&reply);
+        let words: Vec<u32> = (0..8).map(|_| reader.u32().unwrap()).collect();
+        // xid, REPLY, MSG_ACCEPTED, AUTH_NONE verifier, PROG_MISMATCH, 4..4
+        assert_eq!(words, vec![42, REPLY, MSG_ACCEPTED, AUTH_NONE, 0, 2, 4, 4]);
+    }
+
+    #[tokio::test]
+    async fn test_fragmented_record() {
+        let mut stream = Vec::new();
+        stream.extend_from_slice(&3u32.to_be_bytes());
+        stream.extend_from_slice(b"abc");
+        stream.extend_from_slice(&(LAST_FRAGMENT | 2).to_be_bytes());
+        stream.extend_from_slice(b"de");
+        let mut reader = &stream[..];
+        assert_eq!(
+            read_record(&mut reader).await.unwrap(),
+            Some(b"abcde".to_vec())
+        );
+        assert_eq!(read_record(&mut reader).await.unwrap(), None);
+    }
+}
@@ -0,0 +1,1158 @@
+//! Client, session, open and lock state of the NFSv4 server
+//!
+//! NFSv4 is stateful where NFSv3 wasn't:
+//!
+//! - Clients register once (SETCLIENTID in 4.0, EXCHANGE_ID and
+//!   CREATE_SESSION in 4.1) and keep a lease by renewing it; a client that
+//!   stays silent for two lease periods loses its opens and locks.
+//! - OPEN returns a stateid for the open owner and file, carrying the share
+//!   reservation: the access wanted and the access denied to others. READ
+//!   and WRITE name the stateid they're done under.
+//! - Byte-range locks belong to lock owners, each with its own stateid,
+//!   and are checked against the other owners' locks on the file. They're
+//!   advisory, as with POSIX `fcntl` locks: reads and writes aren't refused
+//!   because of them.
+//! - 4.1 sessions give each request a slot and sequence ID, so a
+//!   retransmitted request gets the reply it got the first time.
+//!
+//! State lives in memory and is gone after a restart. There's no grace
+//! period: reclaims are refused with `NFS4ERR_NO_GRACE` and clients open
+//! their files again.
+
+use crate::nfs::v4::status::nfsstat4;
+use crate::nfs::v4::xdr::{XdrReader, XdrWriter};
+use nfsserve::nfs::fileid3;
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+/// How long a client's lease lasts without renewal
+pub const LEASE_TIME: Duration = Duration::from_secs(90);
+
+/// Slots of each session's fore channel
+pub const MAX_SLOTS: u32 = 64;
+
+/// OPEN4_SHARE_ACCESS and OPEN4_SHARE_DENY bits
+pub const SHARE_READ: u32 = 1;
+pub const SHARE_WRITE: u32 = 2;
+pub const SHARE_BOTH: u32 = 3;
+
+pub type ClientId = u64;
+
+/// Open or lock owner: the client and the client's name for the owner
+pub type Owner = (ClientId, Vec<u8>);
+
+/// Identifies an open or a lock owner's locks on a file
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct StateId {
+    pub seqid: u32,
+    pub other: [u8; 12],
+}
+
+impl StateId {
+    /// For I/O without an open, such as from a client that doesn't track opens
+    pub const ANONYMOUS: StateId = StateId {
+        seqid: 0,
+        other: [0; 12],
+    };
+
+    /// For reads that bypass share reservations
+    pub const BYPASS: StateId = StateId {
+        seqid: u32::MAX,
+        other: [0xff; 12],
+    };
+
+    /// NFSv4.1: the stateid the previous operation of the COMPOUND returned
+    pub const CURRENT: StateId = StateId {
+        seqid: 1,
+        other: [0; 12],
+    };
+
+    pub fn is_special(&self) -> bool {
+        *self == Self::ANONYMOUS || *self == Self::BYPASS
+    }
+
+    pub fn decode(reader: &mut XdrReader) -> Result<Self, nfsstat4> {
+        Ok(Self {
+            seqid: reader.u32()?,
+            other: reader.fixed()?,
+        })
+    }
+
+    pub fn encode(&self, writer: &mut XdrWriter) {
+        writer.u32(self.seqid).fixed(&self.other);
+    }
+}
+
+/// Kind of a byte-range lock
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LockType {
+    Read,
+    Write,
+}
+
+impl LockType {
+    /// From nfs_lock_type4; the blocking variants are treated like the others,
+    /// and the client polls
+    pub fn from_code(code: u32) -> Result<Self, nfsstat4> {
+        match code {
+            1 | 3 => Ok(Self::Read),
+            2 | 4 => Ok(Self::Write),
+            _ => Err(nfsstat4::NFS4ERR_INVAL),
+        }
+    }
+
+    pub fn code(self) -> u32 {
+        match self {
+            Self::Read => 1,
+            Self::Write => 2,
+        }
+    }
+}
+
+/// Bytes `offset..end` of a file, `end` being `u64::MAX` for "to the end"
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Range {
+    pub offset: u64,
+    pub end: u64,
+}
+
+impl Range {
+    /// Range of `length` bytes from `offset`, all ones meaning "to the end"
+    pub fn new(offset: u64, length: u64) -> Result<Self, nfsstat4> {
+        if length == 0 {
+            return Err(nfsstat4::NFS4ERR_INVAL);
+        }
+        let end = if length == u64::MAX {
+            u64::MAX
+        } else {
+            offset.checked_add(length).ok_or(nfsstat4::NFS4ERR_INVAL)?
+        };
+        Ok(Self { offset, end })
+    }
+
+    pub fn length(&self) -> u64 {
+        if self.end == u64::MAX {
+            u64::MAX
+        } else {
+            self.end - self.offset
+        }
+    }
+
+    fn overlaps(&self, other: &Range) -> bool {
+        self.offset < other.end && other.offset < self.end
+    }
+
+    /// What's left of this range after removing `other`
+    fn minus(&self, other: &Range) -> Vec<Range> {
+        if !self.overlaps(other) {
+            return vec![*self];
+        }
+        let mut left = Vec::new();
+        if self.offset < other.offset {
+            left.push(Range {
+                offset: self.offset,
+                end: other.offset,
+            });
+        }
+        if other.end < self.end {
+            left.push(Range {
+                offset: other.end,
+                end: self.end,
+            });
+        }
+        left
+    }
+}
+
+/// A lock held by another owner, returned when a lock is refused
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Denied {
+    pub range: Range,
+    pub kind: LockType,
+    pub owner: Owner,
+}
+
+impl Denied {
+    pub fn encode(&self, writer: &mut XdrWriter) {
+        writer
+            .u64(self.range.offset)
+            .u64(self.range.length())
+            .u32(self.kind.code())
+            .u64(self.owner.0)
+            .opaque(&self.owner.1);
+    }
+}
+
+/// Why a LOCK failed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LockError {
+    Status(nfsstat4),
+    Denied(Denied),
+}
+
+impl From<nfsstat4> for LockError {
+    fn from(status: nfsstat4) -> Self {
+        Self::Status(status)
+    }
+}
+
+/// Who a LOCK is for: a lock owner's first lock on the file, under an
+/// open, or a later one under its lock stateid
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Locker {
+    New { open: StateId, owner: Owner },
+    Existing { lock: StateId },
+}
+
+/// What SEQUENCE found in the slot
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SlotUse {
+    /// A new request; its reply is cached once the COMPOUND finishes
+    New,
+    /// A retransmission, answered with the earlier reply
+    Replay(Vec<u8>),
+}
+
+#[derive(Debug)]
+struct Client {
+    owner: Vec<u8>,
+    verifier: [u8; 8],
+    /// SETCLIENTID_CONFIRM's verifier (4.0)
+    confirm: [u8; 8],
+    confirmed: bool,
+    renewed: Instant,
+    /// Sequence ID the next CREATE_SESSION must carry (4.1)
+    create_session_seq: u32,
+    /// Session the last CREATE_SESSION made, for its retransmission
+    last_session: Option<[u8; 16]>,
+    reclaim_complete: bool,
+}
+
+#[derive(Debug)]
+struct Open {
+    file: fileid3,
+    owner: Owner,
+    access: u32,
+    deny: u32,
+    seqid: u32,
+}
+
+#[derive(Debug)]
+struct Lock {
+    file: fileid3,
+    owner: Owner,
+    /// `other` of the open the owner's first lock was taken under
+    open: [u8; 12],
+    seqid: u32,
+    ranges: Vec<(Range, LockType)>,
+}
+
+#[derive(Debug)]
+struct Slot {
+    seqid: u32,
+    reply: Option<Vec<u8>>,
+}
+
+#[derive(Debug)]
+struct Session {
+    client: ClientId,
+    slots: Vec<Slot>,
+}
+
+/// Everything the server remembers about its clients
+#[derive(Debug)]
+pub struct State {
+    /// Identifies this server instance in client and state IDs, so ones
+    /// from before a restart are recognized as stale
+    boot: u32,
+    next_id: u64,
+    clients: HashMap<ClientId, Client>,
+    opens: HashMap<[u8; 12], Open>,
+    locks: HashMap<[u8; 12], Lock>,
+    /// Open owners that have confirmed their first open (4.0)
+    confirmed_owners: Vec<Owner>,
+    sessions: HashMap<[u8; 16], Session>,
+    /// Verifiers of files made by exclusive OPENs, to recognize retries
+    exclusive: HashMap<fileid3, [u8; 8]>,
+}
+
+impl State {
+    pub fn new(boot: u32) -> Self {
+        Self {
+            boot,
+            next_id: 1,
+            clients: HashMap::new(),
+            opens: HashMap::new(),
+            locks: HashMap::new(),
+            confirmed_owners: Vec::new(),
+            sessions: HashMap::new(),
+            exclusive: HashMap::new(),
+        }
+    }
+
+    fn next_client_id(&mut self) -> ClientId {
+        let id = ((self.boot as u64) << 32) | (self.next_id & 0xffff_ffff);
+        self.next_id += 1;
+        id
+    }
+
+    fn next_other(&mut self) -> [u8; 12] {
+        let mut other = [0u8; 12];
+        other[..4].copy_from_slice(&self.boot.to_be_bytes());
+        other[4..].copy_from_slice(&self.next_id.to_be_bytes());
+        self.next_id += 1;
+        other
+    }
+
+    fn verifier(&mut self) -> [u8; 8] {
+        let id = self.next_id;
+        self.next_id += 1;
+        id.to_be_bytes()
+    }
+
+    /// Drop clients whose lease ran out twice over, with all their state
+    pub fn expire(&mut self, now: Instant) {
+        let expired: Vec<ClientId> = self
+            .clients
+            .iter()
+            .filter(|(_, client)| now.duration_since(client.renewed) > LEASE_TIME * 2)
+            .map(|(&id, _)| id)
+            .collect();
+        for id in expired {
+            self.remove_client(id);
+        }
+    }
+
+    fn remove_client(&mut self, id: ClientId) {
+        self.clients.remove(&id);
+        self.opens.retain(|_, open| open.owner.0 != id);
+        self.locks.retain(|_, lock| lock.owner.0 != id);
+        self.confirmed_owners.retain(|owner| owner.0 != id);
+        self.sessions.retain(|_, session| session.client != id);
+    }
+
+    /// Drop other clients with the same owner, replaced by `id`
+    fn replace_clients(&mut self, id: ClientId) {
+        let Some(owner) = self.clients.get(&id).map(|client| client.owner.clone()) else {
+            return;
+        };
+        let replaced: Vec<ClientId> = self
+            .clients
+            .iter()
+            .filter(|(&other, client)| other != id && client.owner == owner)
+            .map(|(&other, _)| other)
+            .collect();
+        for other in replaced {
+            self.remove_client(other);
+        }
+    }
+
+    /// Check `id` is a known client, and renew its lease
+    pub fn renew(&mut self, id: ClientId) -> Result<(), nfsstat4> {
+        if (id >> 32) as u32 != self.boot {
+            return Err(nfsstat4::NFS4ERR_STALE_CLIENTID);
+        }
+        let client = self.clients.get_mut(&id).ok_or(nfsstat4::NFS4ERR_EXPIRED)?;
+        client.renewed = Instant::now();
+        Ok(())
+    }
+
+    /// SETCLIENTID: register the client `owner`, returning its ID and the
+    /// verifier to confirm it with
+    ///
+    /// A client that sets its ID again with the same boot verifier keeps
+    /// its ID; a new verifier means it rebooted, and it gets a new one
+    /// whose confirmation drops the old one's state.
+    pub fn set_client_id(&mut self, owner: &[u8], verifier: [u8; 8]) -> (ClientId, [u8; 8]) {
+        let confirm = self.verifier();
+        let existing = self
+            .clients
+            .iter()
+            .find(|(_, client)| client.owner == owner && client.verifier == verifier)
+            .map(|(&id, _)| id);
+        let id = existing.unwrap_or_else(|| self.next_client_id());
+        let client = self.clients.entry(id).or_insert_with(|| Client {
+            owner: owner.to_vec(),
+            verifier,
+            confirm,
+            confirmed: false,
+            renewed: Instant::now(),
+            create_session_seq: 1,
+            last_session: None,
+            reclaim_complete: false,
+        });
+        client.confirm = confirm;
+        client.renewed = Instant::now();
+        (id, confirm)
+    }
+
+    /// SETCLIENTID_CONFIRM
+    pub fn confirm_client_id(&mut self, id: ClientId, confirm: [u8; 8]) -> Result<(), nfsstat4> {
+        match self.clients.get_mut(&id) {
+            Some(client) if client.confirm == confirm => {
+                client.confirmed = true;
+                client.renewed = Instant::now();
+            }
+            _ => return Err(nfsstat4::NFS4ERR_STALE_CLIENTID),
+        }
+        self.replace_clients(id);
+        Ok(())
+    }
+
+    /// EXCHANGE_ID: the client `owner`'s ID, the sequence ID its
+    /// CREATE_SESSION must carry, and whether it's confirmed already
+    pub fn exchange_id(&mut self, owner: &[u8], verifier: [u8; 8]) -> (ClientId, u32, bool) {
+        let existing = self
+            .clients
+            .iter()
+            .find(|(_, client)| client.owner == owner && client.verifier == verifier)
+            .map(|(&id, _)| id);
+        if let Some(id) = existing {
+            let client = self.clients.get_mut(&id).expect("client just found");
+            client.renewed = Instant::now();
+            return (id, client.create_session_seq, client.confirmed);
+        }
+        let id = self.next_client_id();
+        self.clients.insert(
+            id,
+            Client {
+                owner: owner.to_vec(),
+                verifier,
+                confirm: [0; 8],
+                confirmed: false,
+                renewed: Instant::now(),
+                create_session_seq: 1,
+                last_session: None,
+                reclaim_complete: false,
+            },
+        );
+        (id, 1, false)
+    }
+
+    /// CREATE_SESSION: a session for `id` with `slots` slots, confirming
+    /// the client
+    pub fn create_session(
+        &mut self,
+        id: ClientId,
+        sequence: u32,
+        slots: u32,
+    ) -> Result<[u8; 16], nfsstat4> {
+        if (id >> 32) as u32 != self.boot {
+            return Err(nfsstat4::NFS4ERR_STALE_CLIENTID);
+        }
+        let client = self
+            .clients
+            .get_mut(&id)
+            .ok_or(nfsstat4::NFS4ERR_STALE_CLIENTID)?;
+        if sequence.wrapping_add(1) == client.create_session_seq {
+            // A retransmission of the last CREATE_SESSION
+            return client.last_session.ok_or(nfsstat4::NFS4ERR_SEQ_MISORDERED);
+        }
+        if sequence != client.create_session_seq {
+            return Err(nfsstat4::NFS4ERR_SEQ_MISORDERED);
+        }
+        client.create_session_seq = client.create_session_seq.wrapping_add(1);
+        client.confirmed = true;
+        client.renewed = Instant::now();
+
+        let mut session_id = [0u8; 16];
+        session_id[..8].copy_from_slice(&id.to_be_bytes());
+        session_id[8..].copy_from_slice(&self.verifier());
+        self.clients
+            .get_mut(&id)
+            .expect("client checked")
+            .last_session = Some(session_id);
+        let slots = (0..slots.clamp(1, MAX_SLOTS))
+            .map(|_| Slot {
+                seqid: 0,
+                reply: None,
+            })
+            .collect();
+        self.sessions
+            .insert(session_id, Session { client: id, slots });
+        self.replace_clients(id);
+        Ok(session_id)
+    }
+
+    /// SEQUENCE: check the request's slot and sequence ID, returning the
+    /// session's client and whether this is a new request
+    pub fn sequence(
+        &mut self,
+        session_id: &[u8; 16],
+        slot: u32,
+        seqid: u32,
+    ) -> Result<(ClientId, SlotUse), nfsstat4> {
+        let session = self
+            .sessions
+            .get_mut(session_id)
+            .ok_or(nfsstat4::NFS4ERR_BADSESSION)?;
+        let client = session.client;
+        let slot = session
+            .slots
+            .get_mut(slot as usize)
+            .ok_or(nfsstat4::NFS4ERR_BADSLOT)?;
+        let slot_use = if seqid == slot.seqid {
+            match &slot.reply {
+                Some(reply) => SlotUse::Replay(reply.clone()),
+                None => return Err(nfsstat4::NFS4ERR_SEQ_MISORDERED),
+            }
+        } else if seqid == slot.seqid.wrapping_add(1) {
+            slot.seqid = seqid;
+            slot.reply = None;
+            SlotUse::New
+        } else {
+            return Err(nfsstat4::NFS4ERR_SEQ_MISORDERED);
+        };
+        self.renew(client)?;
+        Ok((client, slot_use))
+    }
+
+    /// Keep the reply to the request in `slot`, for its retransmissions
+    pub fn cache_reply(&mut self, session_id: &[u8; 16], slot: u32, reply: Vec<u8>) {
+        if let Some(slot) = self
+            .sessions
+            .get_mut(session_id)
+            .and_then(|session| session.slots.get_mut(slot as usize))
+        {
+            slot.reply = Some(reply);
+        }
+    }
+
+    /// Number of slots of a session, for SEQUENCE's reply
+    pub fn slot_count(&self, session_id: &[u8; 16]) -> u32 {
+        self.sessions
+            .get(session_id)
+            .map_or(0, |session| session.slots.len() as u32)
+    }
+
+    pub fn destroy_session(&mut self, session_id: &[u8; 16]) -> Result<(), nfsstat4> {
+        self.sessions
+            .remove(session_id)
+            .map(|_| ())
+            .ok_or(nfsstat4::NFS4ERR_BADSESSION)
+    }
+
+    /// DESTROY_CLIENTID, refused while the client has sessions
+    pub fn destroy_client(&mut self, id: ClientId) -> Result<(), nfsstat4> {
+        if !self.clients.contains_key(&id) {
+            return Err(nfsstat4::NFS4ERR_STALE_CLIENTID);
+        }
+        if self.sessions.values().any(|session| session.client == id) {
+            return Err(nfsstat4::NFS4ERR_CLIENTID_BUSY);
+        }
+        self.remove_client(id);
+        Ok(())
+    }
+
+    /// RECLAIM_COMPLETE, allowed once per client
+    pub fn reclaim_complete(&mut self, id: ClientId) -> Result<(), nfsstat4> {
+        let client = self
+            .clients
+            .get_mut(&id)
+            .ok_or(nfsstat4::NFS4ERR_STALE_CLIENTID)?;
+        if client.reclaim_complete {
+            return Err(nfsstat4::NFS4ERR_COMPLETE_ALREADY);
+        }
+        client.reclaim_complete = true;
+        Ok(())
+    }
+
+    /// Verifier a file was made with by an exclusive OPEN
+    pub fn exclusive_verifier(&self, file: fileid3) -> Option<[u8; 8]> {
+        self.exclusive.get(&file).copied()
+    }
+
+    pub fn set_exclusive_verifier(&mut self, file: fileid3, verifier: [u8; 8]) {
+        self.exclusive.insert(file, verifier);
+    }
+
+    /// Check a stateid's sequence ID against the state's; zero means the
+    /// current one
+    fn check_seqid(held: u32, given: u32) -> Result<(), nfsstat4> {
+        if given == 0 || given == held {
+            Ok(())
+        } else if given < held {
+            Err(nfsstat4::NFS4ERR_OLD_STATEID)
+        } else {
+            Err(nfsstat4::NFS4ERR_BAD_STATEID)
+        }
+    }
+
+    fn check_boot(&self, stateid: &StateId) -> Result<(), nfsstat4> {
+        if stateid.other[..4] != self.boot.to_be_bytes() {
+            return Err(nfsstat4::NFS4ERR_STALE_STATEID);
+        }
+        Ok(())
+    }
+
+    /// OPEN: open `file` for `owner` with a share reservation
+    ///
+    /// Another owner's open that denies access this open wants, or wants
+    /// access this open denies, is `NFS4ERR_SHARE_DENIED`. An owner opening
+    /// a file again gets its open upgraded to the union of both. Returns the
+    /// stateid and whether the owner still has to confirm it (4.0).
+    pub fn open(
+        &mut self,
+        owner: Owner,
+        file: fileid3,
+        access: u32,
+        deny: u32,
+        minor_version: u32,
+    ) -> Result<(StateId, bool), nfsstat4> {
+        if access & SHARE_BOTH == 0 || access & !SHARE_BOTH != 0 || deny & !SHARE_BOTH != 0 {
+            return Err(nfsstat4::NFS4ERR_INVAL);
+        }
+        self.renew(owner.0)?;
+        let conflict = self.opens.values().any(|open| {
+            open.file == file
+                && open.owner != owner
+                && (open.deny & access != 0 || deny & open.access != 0)
+        });
+        if conflict {
+            return Err(nfsstat4::NFS4ERR_SHARE_DENIED);
+        }
+
+        let existing = self
+            .opens
+            .iter_mut()
+            .find(|(_, open)| open.file == file && open.owner == owner);
+        let stateid = match existing {
+            Some((other, open)) => {
+                open.access |= access;
+                open.deny |= deny;
+                open.seqid += 1;
+                StateId {
+                    seqid: open.seqid,
+                    other: *other,
+                }
+            }
+            None => {
+                let other = self.next_other();
+                self.opens.insert(
+                    other,
+                    Open {
+                        file,
+                        owner: owner.clone(),
+                        access,
+                        deny,
+                        seqid: 1,
+                    },
+                );
+                StateId { seqid: 1, other }
+            }
+        };
+        let confirm = minor_version == 0 && !self.confirmed_owners.contains(&owner);
+        Ok((stateid, confirm))
+    }
+
+    fn open_mut(&mut self, stateid: &StateId) -> Result<&mut Open, nfsstat4> {
+        self.check_boot(stateid)?;
+        let open = self
+            .opens
+            .get_mut(&stateid.other)
+            .ok_or(nfsstat4::NFS4ERR_BAD_STATEID)?;
+        Self::check_seqid(open.seqid, stateid.seqid)?;
+        Ok(open)
+    }
+
+    /// OPEN_CONFIRM: confirm the owner's first open (4.0)
+    pub fn confirm_open(&mut self, stateid: &StateId, file: fileid3) -> Result<StateId, nfsstat4> {
+        let open = self.open_mut(stateid)?;
+        if open.file != file {
+            return Err(nfsstat4::NFS4ERR_BAD_STATEID);
+        }
+        open.seqid += 1;
+        let (seqid, owner) = (open.seqid, open.owner.clone());
+        if !self.confirmed_owners.contains(&owner) {
+            self.confirmed_owners.push(owner);
+        }
+        Ok(StateId {
+            seqid,
+            other: stateid.other,
+        })
+    }
+
+    /// OPEN_DOWNGRADE: narrow an open to `access` and `deny`, which must be
+    /// within what it has
+    pub fn downgrade(
+        &mut self,
+        stateid: &StateId,
+        file: fileid3,
+        access: u32,
+        deny: u32,
+    ) -> Result<StateId, nfsstat4> {
+        let open = self.open_mut(stateid)?;
+        if open.file != file {
+            return Err(nfsstat4::NFS4ERR_BAD_STATEID);
+        }
+        if access & SHARE_BOTH == 0 || access & !open.access != 0 || deny & !open.deny != 0 {
+            return Err(nfsstat4::NFS4ERR_INVAL);
+        }
+        open.access = access;
+        open.deny = deny;
+        open.seqid += 1;
+        Ok(StateId {
+            seqid: open.seqid,
+            other: stateid.other,
+        })
+    }
+
+    /// CLOSE: release an open, refused while its owners' locks are held
+    ///
+    /// Returns whether the open had write access.
+    pub fn close(&mut self, stateid: &StateId, file: fileid3) -> Result<bool, nfsstat4> {
+        let open = self.open_mut(stateid)?;
+        if open.file != file {
+            return Err(nfsstat4::NFS4ERR_BAD_STATEID);
+        }
+        let wrote = open.access & SHARE_WRITE != 0;
+        let held = self
+            .locks
+            .values()
+            .any(|lock| lock.open == stateid.other && !lock.ranges.is_empty());
+        if held {
+            return Err(nfsstat4::NFS4ERR_LOCKS_HELD);
+        }
+        self.opens.remove(&stateid.other);
+        self.locks.retain(|_, lock| lock.open != stateid.other);
+        Ok(wrote)
+    }
+
+    /// Check a READ or WRITE of `file` may be done under `stateid`
+    ///
+    /// Special stateids are always accepted. Writing needs an open with
+    /// write access; reading is allowed under any open of the file.
+    pub fn check_io(
+        &mut self,
+        stateid: &StateId,
+        file: fileid3,
+        write: bool,
+    ) -> Result<(), nfsstat4> {
+        if stateid.is_special() {
+            return Ok(());
+        }
+        self.check_boot(stateid)?;
+        let open_other = match self.locks.get(&stateid.other) {
+            Some(lock) => {
+                if lock.file != file {
+                    return Err(nfsstat4::NFS4ERR_BAD_STATEID);
+                }
+                Self::check_seqid(lock.seqid, stateid.seqid)?;
+                lock.open
+            }
+            None => {
+                let open = self
+                    .opens
+                    .get(&stateid.other)
+                    .ok_or(nfsstat4::NFS4ERR_BAD_STATEID)?;
+                Self::check_seqid(open.seqid, stateid.seqid)?;
+                stateid.other
+            }
+        };
+        let open = self
+            .opens
+            .get(&open_other)
+            .ok_or(nfsstat4::NFS4ERR_BAD_STATEID)?;
+        if open.file != file {
+            return Err(nfsstat4::NFS4ERR_BAD_STATEID);
+        }
+        if write && open.access & SHARE_WRITE == 0 {
+            return Err(nfsstat4::NFS4ERR_OPENMODE);
+        }
+        let client = open.owner.0;
+        self.renew(client)
+    }
+
+    /// A lock of another owner on `file` that conflicts with `kind` over `range`
+    fn conflict(
+        &self,
+        file: fileid3,
+        owner: &Owner,
+        kind: LockType,
+        range: &Range,
+    ) -> Option<Denied> {
+        self.locks
+            .values()
+            .filter(|lock| lock.file == file && lock.owner != *owner)
+            .flat_map(|lock| {
+                lock.ranges
+                    .iter()
+                    .map(move |(held, held_kind)| (lock, held, held_kind))
+            })
+            .find(|(_, held, held_kind)| {
+                held.overlaps(range) && (kind == LockType::Write || **held_kind == LockType::Write)
+            })
+            .map(|(lock, held, held_kind)| Denied {
+                range: *held,
+                kind: *held_kind,
+                owner: lock.owner.clone(),
+            })
+    }
+
+    /// LOCK: lock `range` of `file`
+    ///
+    /// The owner's own locks over the range are replaced, as with POSIX
+    /// locks, so a read lock can be upgraded to a write lock and back.
+    pub fn lock(
+        &mut self,
+        locker: Locker,
+        file: fileid3,
+        kind: LockType,
+        range: Range,
+    ) -> Result<StateId, LockError> {
+        let (other, owner) = match locker {
+            Locker::New { open, owner } => {
+                let open_state = self.open_mut(&open)?;
+                if open_state.file != file {
+                    return Err(nfsstat4::NFS4ERR_BAD_STATEID.into());
+                }
+                if open_state.owner.0 != owner.0 {
+                    return Err(nfsstat4::NFS4ERR_INVAL.into());
+                }
+                let existing = self
+                    .locks
+                    .iter()
+                    .find(|(_, lock)| lock.file == file && lock.owner == owner)
+                    .map(|(&other, _)| other);
+                let other = match existing {
+                    Some(other) => other,
+                    None => {
+                        let other = self.next_other();
+                        self.locks.insert(
+                            other,
+                            Lock {
+                                file,
+                                owner: owner.clone(),
+                                open: open.other,
+                                seqid: 0,
+                                ranges: Vec::new(),
+                            },
+                        );
+                        other
+                    }
+                };
+                (other, owner)
+            }
+            Locker::Existing { lock } => {
+                self.check_boot(&lock)?;
+                let state = self
+                    .locks
+                    .get(&lock.other)
+                    .ok_or(nfsstat4::NFS4ERR_BAD_STATEID)?;
+                if state.file != file {
+                    return Err(nfsstat4::NFS4ERR_BAD_STATEID.into());
+                }
+                Self::check_seqid(state.seqid, lock.seqid)?;
+                (lock.other, state.owner.clone())
+            }
+        };
+        self.renew(owner.0)?;
+
+        let open_other = self.locks[&other].open;
+        let access = self.opens.get(&open_other).map_or(0, |open| open.access);
+        let needed = match kind {
+            LockType::Read => SHARE_READ,
+            LockType::Write => SHARE_WRITE,
+        };
+        if access & needed == 0 {
+            return Err(nfsstat4::NFS4ERR_OPENMODE.into());
+        }
+        if let Some(denied) = self.conflict(file, &owner, kind, &range) {
+            return Err(LockError::Denied(denied));
+        }
+
+        let lock = self.locks.get_mut(&other).expect("lock state just found");
+        lock.ranges = std::mem::take(&mut lock.ranges)
+            .into_iter()
+            .flat_map(|(held, held_kind)| {
+                held.minus(&range)
+                    .into_iter()
+                    .map(move |left| (left, held_kind))
+            })
+            .collect();
+        lock.ranges.push((range, kind));
+        lock.seqid += 1;
+        Ok(StateId {
+            seqid: lock.seqid,
+            other,
+        })
+    }
+
+    /// LOCKT: the lock that would refuse `owner` a lock, if any
+    pub fn test_lock(
+        &self,
+        owner: &Owner,
+        file: fileid3,
+        kind: LockType,
+        range: &Range,
+    ) -> Option<Denied> {
+        self.conflict(file, owner, kind, range)
+    }
+
+    /// LOCKU: unlock `range`, which may cover only parts of held locks
+    pub fn unlock(
+        &mut self,
+        stateid: &StateId,
+        file: fileid3,
+        range: Range,
+    ) -> Result<StateId, nfsstat4> {
+        self.check_boot(stateid)?;
+        let lock = self
+            .locks
+            .get_mut(&stateid.other)
+            .ok_or(nfsstat4::NFS4ERR_BAD_STATEID)?;
+        if lock.file != file {
+            return Err(nfsstat4::NFS4ERR_BAD_STATEID);
+        }
+        Self::check_seqid(lock.seqid, stateid.seqid)?;
+        lock.ranges = std::mem::take(&mut lock.ranges)
+            .into_iter()
+            .flat_map(|(held, kind)| held.minus(&range).into_iter().map(move |left| (left, kind)))
+            .collect();
+        lock.seqid += 1;
+        Ok(StateId {
+            seqid: lock.seqid,
+            other: stateid.other,
+        })
+    }
+
+    /// RELEASE_LOCKOWNER: forget a lock owner that holds no locks
+    pub fn release_lock_owner(&mut self, owner: &Owner) -> Result<(), nfsstat4> {
+        let held = self
+            .locks
+            .values()
+            .any(|lock| lock.owner == *owner && !lock.ranges.is_empty());
+        if held {
+            return Err(nfsstat4::NFS4ERR_LOCKS_HELD);
+        }
+        self.locks.retain(|_, lock| lock.owner != *owner);
+        Ok(())
+    }
+
+    /// TEST_STATEID: whether a stateid is still usable
+    pub fn test_stateid(&self, stateid: &StateId) -> nfsstat4 {
+        if let Err(status) = self.check_boot(stateid) {
+            return status;
+        }
+        let held = self
+            .opens
+            .get(&stateid.other)
+            .map(|open| open.seqid)
+            .or_else(|| self.locks.get(&stateid.other).map(|lock| lock.seqid));
+        match held {
+            Some(held) => match Self::check_seqid(held, stateid.seqid) {
+                Ok(()) => nfsstat4::NFS4_OK,
+                Err(status) => status,
+            },
+            None => nfsstat4::NFS4ERR_BAD_STATEID,
+        }
+    }
+
+    /// FREE_STATEID: drop a lock stateid holding no locks
+    pub fn free_stateid(&mut self, stateid: &StateId) -> Result<(), nfsstat4> {
+        self.check_boot(stateid)?;
+        if self.opens.contains_key(&stateid.other) {
+            return Err(nfsstat4::NFS4ERR_LOCKS_HELD);
+        }
+        match self.locks.get(&stateid.other) {
+            Some(lock) if !lock.ranges.is_empty() => Err(nfsstat4::NFS4ERR_LOCKS_HELD),
+            Some(_) => {
+                self.locks.remove(&stateid.other);
+                Ok(())
+            }
+            None => Err(nfsstat4::NFS4ERR_BAD_STATEID),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A confirmed 4.0 client
+    fn client(state: &mut State, name: &[u8]) -> ClientId {
+        let (id, confirm) = state.set_client_id(name, [1; 8]);
+        state.confirm_client_id(id, confirm).unwrap();
+        id
+    }
+
+    fn owner(client: ClientId, name: &[u8]) -> Owner {
+        (client, name.to_vec())
+    }
+
+    #[test]
+    fn test_share_reservations() {
+        let mut state = State::new(7);
+        let a = client(&mut state, b"a");
+        let b = client(&mut state, b"b");
+
+        let (reader, confirm) = state
+            .open(owner(a, b"r"), 10, SHARE_READ, SHARE_WRITE, 0)
+            .unwrap();
+        assert!(confirm);
+        // Writing is denied to others, reading isn't
+        assert_eq!(
+            state.open(owner(b, b"w"), 10, SHARE_WRITE, 0, 0),
+            Err(nfsstat4::NFS4ERR_SHARE_DENIED)
+        );
+        state.open(owner(b, b"r"), 10, SHARE_READ, 0, 0).unwrap();
+        assert!(state.open(owner(b, b"w"), 11, SHARE_BOTH, 0, 0).is_ok());
+
+        // Writing under a read-only open
+        assert_eq!(
+            state.check_io(&reader, 10, true),
+            Err(nfsstat4::NFS4ERR_OPENMODE)
+        );
+        assert_eq!(state.check_io(&reader, 10, false), Ok(()));
+        assert_eq!(
+            state.check_io(&reader, 11, false),
+            Err(nfsstat4::NFS4ERR_BAD_STATEID)
+        );
+        assert_eq!(state.check_io(&StateId::ANONYMOUS, 10, true), Ok(()));
+
+        let confirmed = state.confirm_open(&reader, 10).unwrap();
+        assert_eq!(
+            state.check_io(&reader, 10, false),
+            Err(nfsstat4::NFS4ERR_OLD_STATEID)
+        );
+        assert!(!state.close(&confirmed, 10).unwrap());
+        assert!(state.open(owner(b, b"w2"), 10, SHARE_WRITE, 0, 0).is_ok());
+    }
+
+    #[test]
+    fn test_byte_range_locks() {
+        let mut state = State::new(7);
+        let a = client(&mut state, b"a");
+        let b = client(&mut state, b"b");
+        let (open_a, _) = state.open(owner(a, b"o"), 10, SHARE_BOTH, 0, 0).unwrap();
+        let (open_b, _) = state.open(owner(b, b"o"), 10, SHARE_READ, 0, 0).unwrap();
+
+        let lock_a = state
+            .lock(
+                Locker::New {
+                    open: open_a,
+                    owner: owner(a, b"l"),
+                },
+                10,
+                LockType::Write,
+                Range::new(0, 100).unwrap(),
+            )
+            .unwrap();
+
+        // Overlapping locks of another owner are refused, others aren't
+        let denied = state.lock(
+            Locker::New {
+                open: open_b,
+                owner: owner(b, b"l"),
+            },
+            10,
+            LockType::Read,
+            Range::new(50, 10).unwrap(),
+        );
+        match denied {
+            Err(LockError::Denied(denied)) => {
+                assert_eq!(denied.range, Range::new(0, 100).unwrap());
+                assert_eq!(denied.owner, owner(a, b"l"));
+            }
+            other => panic!("expected a denial, got {:?}", other),
+        }
+        let lock_b = state
+            .lock(
+                Locker::New {
+                    open: open_b,
+                    owner: owner(b, b"l"),
+                },
+                10,
+                LockType::Read,
+                Range::new(100, u64::MAX).unwrap(),
+            )
+            .unwrap();
+        // A write lock needs write access
+        assert_eq!(
+            state.lock(
+                Locker::Existing { lock: lock_b },
+                10,
+                LockType::Write,
+                Range::new(200, 1).unwrap()
+            ),
+            Err(LockError::Status(nfsstat4::NFS4ERR_OPENMODE))
+        );
+
+        // Unlocking the middle leaves both ends locked
+        let lock_a = state
+            .unlock(&lock_a, 10, Range::new(40, 20).unwrap())
+            .unwrap();
+        let probe = |state: &State, offset| {
+            state.test_lock(
+                &owner(b, b"l"),
+                10,
+                LockType::Read,
+                &Range::new(offset, 1).unwrap(),
+            )
+        };
+        assert!(probe(&state, 10).is_some());
+        assert!(probe(&state, 45).is_none());
+        assert!(probe(&state, 70).is_some());
+
+        assert_eq!(state.close(&open_a, 10), Err(nfsstat4::NFS4ERR_LOCKS_HELD));
+        state
+            .unlock(&lock_a, 10, Range::new(0, u64::MAX).unwrap())
+            .unwrap();
+        assert!(state.close(&open_a, 10).unwrap());
+        assert!(probe(&state, 10).is_none());
+    }
+
+    #[test]
+    fn test_sessions_replay() {
+        let mut state = State::new(7);
+        let (id, sequence, confirmed) = state.exchange_id(b"linux", [2; 8]);
+        assert!(!confirmed);
+        let session = state.create_session(id, sequence, 8).unwrap();
+        // A retransmitted CREATE_SESSION gets the same session
+        assert_eq!(state.create_session(id, sequence, 8), Ok(session));
+        assert_eq!(
+            state.exchange_id(b"linux", [2; 8]),
+            (id, sequence + 1, true)
+        );
+
+        assert_eq!(state.sequence(&session, 0, 1), Ok((id, SlotUse::New)));
+        state.cache_reply(&session, 0, b"reply".to_vec());
+        assert_eq!(
+            state.sequence(&session, 0, 1),
+            Ok((id, SlotUse::Replay(b"reply".to_vec())))
+        );
+        assert_eq!(
+            state.sequence(&session, 0, 3),
+            Err(nfsstat4::NFS4ERR_SEQ_MISORDERED)
+        );
+        assert_eq!(
+            state.sequence(&session, 8, 1),
+            Err(nfsstat4::NFS4ERR_BADSLOT)
+        );
+
+        assert_eq!(
+            state.destroy_client(id),
+            Err(nfsstat4::NFS4ERR_CLIENTID_BUSY)
+        );
+        state.destroy_session(&session).unwrap();
+        state.destroy_client(id).unwrap();
+        assert_eq!(
+            state.sequence(&session, 0, 2),
+            Err(nfsstat4::NFS4ERR_BADSESSION)
+        );
+    }
+
+    #[test]
+    fn test_ids_from_before_a_restart_are_stale() {
+        let mut before = State::new(1);
+        let id = client(&mut before, b"a");
+        let (open, _) = before.open(owner(id, b"o"), 10, SHARE_READ, 0, 0).unwrap();
+
+        let mut after = State::new(2);
+        assert_eq!(after.renew(id), Err(nfsstat4::NFS4ERR_STALE_CLIENTID));
+        assert_eq!(
+            after.check_io(&open, 10, false),
+            Err(nfsstat4::NFS4ERR_STALE_STATEID)
+        );
+    }
+}
@@ -0,0 +1,136 @@
+//! NFSv4 status codes (RFC 7530 section 13, RFC 8881 section 15)
+//!
+//! Only the codes the server returns are listed. Errors from the shared
+//! filesystem are NFSv3 statuses, which mostly keep their numbers in NFSv4.
+
+use nfsserve::nfs::nfsstat3;
+
+#[allow(non_camel_case_types)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum nfsstat4 {
+    NFS4_OK = 0,
+    NFS4ERR_PERM = 1,
+    NFS4ERR_NOENT = 2,
+    NFS4ERR_IO = 5,
+    NFS4ERR_NXIO = 6,
+    NFS4ERR_ACCESS = 13,
+    NFS4ERR_EXIST = 17,
+    NFS4ERR_XDEV = 18,
+    NFS4ERR_NOTDIR = 20,
+    NFS4ERR_ISDIR = 21,
+    NFS4ERR_INVAL = 22,
+    NFS4ERR_FBIG = 27,
+    NFS4ERR_NOSPC = 28,
+    NFS4ERR_ROFS = 30,
+    NFS4ERR_MLINK = 31,
+    NFS4ERR_NAMETOOLONG = 63,
+    NFS4ERR_NOTEMPTY = 66,
+    NFS4ERR_DQUOT = 69,
+    NFS4ERR_STALE = 70,
+    NFS4ERR_BADHANDLE = 10001,
+    NFS4ERR_BAD_COOKIE = 10003,
+    NFS4ERR_NOTSUPP = 10004,
+    NFS4ERR_TOOSMALL = 10005,
+    NFS4ERR_SERVERFAULT = 10006,
+    NFS4ERR_BADTYPE = 10007,
+    NFS4ERR_DELAY = 10008,
+    NFS4ERR_SAME = 10009,
+    NFS4ERR_DENIED = 10010,
+    NFS4ERR_EXPIRED = 10011,
+    NFS4ERR_SHARE_DENIED = 10015,
+    NFS4ERR_CLID_INUSE = 10017,
+    NFS4ERR_RESOURCE = 10018,
+    NFS4ERR_NOFILEHANDLE = 10020,
+    NFS4ERR_MINOR_VERS_MISMATCH = 10021,
+    NFS4ERR_STALE_CLIENTID = 10022,
+    NFS4ERR_STALE_STATEID = 10023,
+    NFS4ERR_OLD_STATEID = 10024,
+    NFS4ERR_BAD_STATEID = 10025,
+    NFS4ERR_BAD_SEQID = 10026,
+    NFS4ERR_NOT_SAME = 10027,
+    NFS4ERR_LOCK_RANGE = 10028,
+    NFS4ERR_SYMLINK = 10029,
+    NFS4ERR_RESTOREFH = 10030,
+    NFS4ERR_ATTRNOTSUPP = 10032,
+    NFS4ERR_NO_GRACE = 10033,
+    NFS4ERR_BADXDR = 10036,
+    NFS4ERR_LOCKS_HELD = 10037,
+    NFS4ERR_OPENMODE = 10038,
+    NFS4ERR_BADOWNER = 10039,
+    NFS4ERR_BADCHAR = 10040,
+    NFS4ERR_BADNAME = 10041,
+    NFS4ERR_BAD_RANGE = 10042,
+    NFS4ERR_OP_ILLEGAL = 10044,
+    NFS4ERR_BADSESSION = 10052,
+    NFS4ERR_BADSLOT = 10053,
+    NFS4ERR_COMPLETE_ALREADY = 10054,
+    NFS4ERR_SEQ_MISORDERED = 10063,
+    NFS4ERR_SEQUENCE_POS = 10064,
+    NFS4ERR_TOO_MANY_OPS = 10070,
+    NFS4ERR_OP_NOT_IN_SESSION = 10071,
+    NFS4ERR_CLIENTID_BUSY = 10074,
+    NFS4ERR_NOT_ONLY_OP = 10081,
+}
+
+impl nfsstat4 {
+    pub fn code(self) -> u32 {
+        self as u32
+    }
+}
+
+impl From<nfsstat3> for nfsstat4 {
+    fn from(status: nfsstat3) -> Self {
+        match status {
+            nfsstat3::NFS3_OK => nfsstat4::NFS4_OK,
+            nfsstat3::NFS3ERR_PERM => nfsstat4::NFS4ERR_PERM,
+            nfsstat3::NFS3ERR_NOENT => nfsstat4::NFS4ERR_NOENT,
+            nfsstat3::NFS3ERR_IO => nfsstat4::NFS4ERR_IO,
+            nfsstat3::NFS3ERR_NXIO => nfsstat4::NFS4ERR_NXIO,
+            nfsstat3::NFS3ERR_ACCES => nfsstat4::NFS4ERR_ACCESS,
+            nfsstat3::NFS3ERR_EXIST => nfsstat4::NFS4ERR_EXIST,
+            nfsstat3::NFS3ERR_XDEV => nfsstat4::NFS4ERR_XDEV,
+            nfsstat3::NFS3ERR_NOTDIR => nfsstat4::NFS4ERR_NOTDIR,
+            nfsstat3::NFS3ERR_ISDIR => nfsstat4::NFS4ERR_ISDIR,
+            nfsstat3::NFS3ERR_INVAL => nfsstat4::NFS4ERR_INVAL,
+            nfsstat3::NFS3ERR_FBIG => nfsstat4::NFS4ERR_FBIG,
+            nfsstat3::NFS3ERR_NOSPC => nfsstat4::NFS4ERR_NOSPC,
+            nfsstat3::NFS3ERR_ROFS => nfsstat4::NFS4ERR_ROFS,
+            nfsstat3::NFS3ERR_MLINK => nfsstat4::NFS4ERR_MLINK,
+            nfsstat3::NFS3ERR_NAMETOOLONG => nfsstat4::NFS4ERR_NAMETOOLONG,
+            nfsstat3::NFS3ERR_NOTEMPTY => nfsstat4::NFS4ERR_NOTEMPTY,
+            nfsstat3::NFS3ERR_DQUOT => nfsstat4::NFS4ERR_DQUOT,
+            nfsstat3::NFS3ERR_STALE => nfsstat4::NFS4ERR_STALE,
+            nfsstat3::NFS3ERR_BADHANDLE => nfsstat4::NFS4ERR_BADHANDLE,
+            nfsstat3::NFS3ERR_BAD_COOKIE => nfsstat4::NFS4ERR_BAD_COOKIE,
+            nfsstat3::NFS3ERR_NOTSUPP => nfsstat4::NFS4ERR_NOTSUPP,
+            nfsstat3::NFS3ERR_TOOSMALL => nfsstat4::NFS4ERR_TOOSMALL,
+            nfsstat3::NFS3ERR_BADTYPE => nfsstat4::NFS4ERR_BADTYPE,
+            // Timeouts, "try again later" in NFSv3
+            nfsstat3::NFS3ERR_JUKEBOX => nfsstat4::NFS4ERR_DELAY,
+            _ => nfsstat4::NFS4ERR_SERVERFAULT,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_v3_statuses_keep_their_meaning() {
+        for (v3, v4, code) in [
+            (nfsstat3::NFS3ERR_NOENT, nfsstat4::NFS4ERR_NOENT, 2),
+            (nfsstat3::NFS3ERR_ACCES, nfsstat4::NFS4ERR_ACCESS, 13),
+            (nfsstat3::NFS3ERR_STALE, nfsstat4::NFS4ERR_STALE, 70),
+            (nfsstat3::NFS3ERR_JUKEBOX, nfsstat4::NFS4ERR_DELAY, 10008),
+            (
+                nfsstat3::NFS3ERR_NOT_SYNC,
+                nfsstat4::NFS4ERR_SERVERFAULT,
+                10006,
+            ),
+        ] {
+            assert_eq!(nfsstat4::from(v3), v4);
+            assert_eq!(v4.code(), code);
+        }
+    }
+}
@@ -0,0 +1,203 @@
+//! XDR encoding (RFC 4506) of NFSv4 arguments and results
+//!
+//! nfsserve's XDR types are NFSv3's, so the NFSv4 layer reads and writes its
+//! messages with this small reader and writer instead. Everything is
+//! big-endian and padded to four bytes.
+
+use crate::nfs::v4::status::nfsstat4;
+
+/// Reads XDR values from a message, failing with `NFS4ERR_BADXDR` when it
+/// runs out
+#[derive(Debug, Clone)]
+pub struct XdrReader<'a> {
+    buf: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> XdrReader<'a> {
+    pub fn new(buf: &'a [u8]) -> Self {
+        Self { buf, pos: 0 }
+    }
+
+    /// Bytes not read yet
+    pub fn remaining(&self) -> &'a [u8] {
+        &self.buf[self.pos..]
+    }
+
+    fn take(&mut self, len: usize) -> Result<&'a [u8], nfsstat4> {
+        let end = self
+            .pos
+            .checked_add(len)
+            .filter(|&end| end <= self.buf.len())
+            .ok_or(nfsstat4::NFS4ERR_BADXDR)?;
+        let bytes = &self.buf[self.pos..end];
+        self.pos = end;
+        Ok(bytes)
+    }
+
+    pub fn u32(&mut self) -> Result<u32, nfsstat4> {
+        let bytes = self.take(4)?;
+        Ok(u32::from_be_bytes(bytes.try_into().expect("4 bytes")))
+    }
+
+    pub fn u64(&mut self) -> Result<u64, nfsstat4> {
+        let bytes = self.take(8)?;
+        Ok(u64::from_be_bytes(bytes.try_into().expect("8 bytes")))
+    }
+
+    pub fn i64(&mut self) -> Result<i64, nfsstat4> {
+        Ok(self.u64()? as i64)
+    }
+
+    pub fn bool(&mut self) -> Result<bool, nfsstat4> {
+        match self.u32()? {
+            0 => Ok(false),
+            1 => Ok(true),
+            _ => Err(nfsstat4::NFS4ERR_BADXDR),
+        }
+    }
+
+    /// Fixed-length opaque data of `N` bytes
+    pub fn fixed<const N: usize>(&mut self) -> Result<[u8; N], nfsstat4> {
+        let bytes = self.take(N)?;
+        self.take(padding(N))?;
+        Ok(bytes.try_into().expect("N bytes"))
+    }
+
+    /// Variable-length opaque data or string
+    pub fn opaque(&mut self) -> Result<&'a [u8], nfsstat4> {
+        let len = self.u32()? as usize;
+        let bytes = self.take(len)?;
+        self.take(padding(len))?;
+        Ok(bytes)
+    }
+
+    /// Variable-length array of `u32`, such as a bitmap
+    pub fn u32_array(&mut self) -> Result<Vec<u32>, nfsstat4> {
+        let len = self.u32()? as usize;
+        if len > self.remaining().len() / 4 {
+            return Err(nfsstat4::NFS4ERR_BADXDR);
+        }
+        (0..len).map(|_| self.u32()).collect()
+    }
+}
+
+/// Writes XDR values into a message
+#[derive(Debug, Clone, Default)]
+pub struct XdrWriter {
+    buf: Vec<u8>,
+}
+
+impl XdrWriter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Bytes written so far
+    pub fn len(&self) -> usize {
+        self.buf.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.buf.is_empty()
+    }
+
+    pub fn into_inner(self) -> Vec<u8> {
+        self.buf
+    }
+
+    pub fn u32(&mut self, value: u32) -> &mut Self {
+        self.buf.extend_from_slice(&value.to_be_bytes());
+        self
+    }
+
+    pub fn u64(&mut self, value: u64) -> &mut Self {
+        self.buf.extend_from_slice(&value.to_be_bytes());
+        self
+    }
+
+    pub fn i64(&mut self, value: i64) -> &mut Self {
+        self.u64(value as u64)
+    }
+
+    pub fn bool(&mut self, value: bool) -> &mut Self {
+        self.u32(value as u32)
+    }
+
+    /// Fixed-length opaque data, its length known to both sides
+    pub fn fixed(&mut self, bytes: &[u8]) -> &mut Self {
+        self.buf.extend_from_slice(bytes);
+        self.buf.resize(self.buf.len() + padding(bytes.len()), 0);
+        self
+    }
+
+    /// Variable-length opaque data or string
+    pub fn opaque(&mut self, bytes: &[u8]) -> &mut Self {
+        self.u32(bytes.len() as u32);
+        self.fixed(bytes)
+    }
+
+    /// Variable-length array of `u32`, such as a bitmap
+    pub fn u32_array(&mut self, values: &[u32]) -> &mut Self {
+        self.u32(values.len() as u32);
+        for &value in values {
+            self.u32(value);
+        }
+        self
+    }
+
+    /// Append already encoded values
+    pub fn raw(&mut self, bytes: &[u8]) -> &mut Self {
+        self.buf.extend_from_slice(bytes);
+        self
+    }
+}
+
+/// Zero bytes after `len` bytes of opaque data
+fn padding(len: usize) -> usize {
+    (4 - len % 4) % 4
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        let mut writer = XdrWriter::new();
+        writer
+            .u32(7)
+            .u64(u64::MAX - 1)
+            .i64(-5)
+            .bool(true)
+            .opaque(b"data.csv")
+            .opaque(b"abcde")
+            .fixed(&[1, 2, 3, 4, 5, 6, 7, 8])
+            .u32_array(&[0x1f, 0x100]);
+        let bytes = writer.into_inner();
+        // "abcde" is padded to eight bytes
+        assert_eq!(bytes.len(), 4 + 8 + 8 + 4 + 12 + 12 + 8 + 12);
+
+        let mut reader = XdrReader::new(&bytes);
+        assert_eq!(reader.u32(), Ok(7));
+        assert_eq!(reader.u64(), Ok(u64::MAX - 1));
+        assert_eq!(reader.i64(), Ok(-5));
+        assert_eq!(reader.bool(), Ok(true));
+        assert_eq!(reader.opaque(), Ok(&b"data.csv"[..]));
+        assert_eq!(reader.opaque(), Ok(&b"abcde"[..]));
+        assert_eq!(reader.fixed::<8>(), Ok([1, 2, 3, 4, 5, 6, 7, 8]));
+        assert_eq!(reader.u32_array(), Ok(vec![0x1f, 0x100]));
+        assert!(reader.remaining().is_empty());
+    }
+
+    #[test]
+    fn test_truncated_input() {
+        let mut reader = XdrReader::new(&[0, 0, 0, 9, b'a']);
+        assert_eq!(reader.opaque(), Err(nfsstat4::NFS4ERR_BADXDR));
+        let mut reader = XdrReader::new(&[0, 0, 0, 2]);
+        assert_eq!(reader.bool(), Err(nfsstat4::NFS4ERR_BADXDR));
+        // An array longer than the message can hold isn't allocated
+        let mut reader = XdrReader::new(&[0xff, 0xff, 0xff, 0xff]);
+        assert_eq!(reader.u32_array(), Err(nfsstat4::NFS4ERR_BADXDR));
+    }
+}
@@ -56,6 +56,75 @@ pub enum FsdbError {
     #[error("Bincode error: {message}")]
     BincodeError { message: String },
 
+    #[error("Type coercion error: {message}")]
+    TypeCoercionError { message: String },
+
+    #[error("Schema mismatch: {message}")]
+    SchemaMismatch { message: String },
+
+    #[error("Row too large: {message}")]
+    RowTooLarge { message: String },
+
+    #[error("Field too large: {message}")]
+    FieldTooLarge { message: String },
+
+    #[error("Cursor expired: {message}")]
+    CursorExpired { message: String },
+
+    #[error("Ambiguous identifier: {message}")]
+    AmbiguousIdentifier { message: String },
+
+    #[error("Ambiguous column: {message}")]
+    AmbiguousColumn { message: String },
+
+    #[error("Union mismatch: {message}")]
+    UnionMismatch { message: String },
+
+    #[error("Invalid SQL: {message}")]
+    InvalidSql { message: String },
+
+    #[error("Deadlock detected: {message}")]
+    DeadlockDetected { message: String },
+
+    #[error("Spill limit exceeded: {message}")]
+    SpillLimitExceeded { message: String },
+
+    #[error("Query memory exceeded: {message}")]
+    QueryMemoryExceeded { message: String },
+
+    #[error("Version gone: {message}")]
+    VersionGone { message: String },
+
+    #[error("Unsupported Delta feature: {message}")]
+    UnsupportedDeltaFeature { message: String },
+
+    #[error("Decompression failed: {message}")]
+    Decompression { message: String },
+
+    #[error("Commit metadata too large: {message}")]
+    CommitMetadataTooLarge { message: String },
+
+    #[error("Maintenance already in progress: {message}")]
+    MaintenanceInProgress { message: String },
+
+    #[error("Cancelled: {message}")]
+    Cancelled { message: String },
+
+    #[error("Quota exceeded: {message}")]
+    QuotaExceeded { message: String },
+
+    #[error("Ambiguous merge: {message}")]
+    AmbiguousMerge { message: String },
+
+    #[error("Permission denied: {message}")]
+    PermissionDenied { message: String },
+
+    #[error("Authentication required: {message}")]
+    AuthenticationRequired { message: String },
+
+    #[error("Encryption error: {message}")]
+    Encryption { message: String },
+
     #[error("Delta Lake error: {message}")]
     DeltaLakeError { message: String },
 
@@ -90,6 +159,99 @@ impl From<CoreError> for FsdbError {
             CoreError::TransactionConflict(msg) => FsdbError::TransactionConflict { message: msg },
             CoreError::Wal(msg) => FsdbError::WalError { message: msg },
             CoreError::Bincode(msg) => FsdbError::BincodeError { message: msg },
+            CoreError::TypeCoercion {
+                row,
+                column,
+                message,
+            } => FsdbError::TypeCoercionError {
+                message: format!("row {}, column '{}': {}", row, column, message),
+            },
+            CoreError::SchemaMismatch(msg) => FsdbError::SchemaMismatch { message: msg },
+            CoreError::RowTooLarge { row, bytes, limit } => FsdbError::RowTooLarge {
+                message: format!("row {} is {} bytes, limit {}", row, bytes, limit),
+            },
+            CoreError::FieldTooLarge {
+                row,
+                column,
+                bytes,
+                limit,
+            } => FsdbError::FieldTooLarge {
+                message: format!(
+                    "row {}, column '{}' is {} bytes, limit {}",
+                    row, column, bytes, limit
+                ),
+            },
+            CoreError::CursorExpired(id) => FsdbError::CursorExpired {
+                message: format!("cursor {}", id),
+            },
+            CoreError::AmbiguousIdentifier {
+                identifier,
+                candidates,
+            } => FsdbError::AmbiguousIdentifier {
+                message: format!("'{}' matches {}", identifier, candidates.join(", ")),
+            },
+            CoreError::AmbiguousColumn { column } => FsdbError::AmbiguousColumn { message: column },
+            CoreError::UnionMismatch(message) => FsdbError::UnionMismatch { message },
+            CoreError::InvalidSql { message, position } => FsdbError::InvalidSql {
+                message: match position {
+                    Some((line, column)) => {
+                        format!("line {}, column {}: {}", line, column, message)
+                    }
+                    None => message,
+                },
+            },
+            CoreError::DeadlockDetected { victim, cycle } => FsdbError::DeadlockDetected {
+                message: format!("transaction {} aborted to break cycle {:?}", victim, cycle),
+            },
+            CoreError::SpillLimitExceeded {
+                requested,
+                used,
+                limit,
+            } => FsdbError::SpillLimitExceeded {
+                message: format!(
+                    "{} bytes requested with {} of {} in use",
+                    requested, used, limit
+                ),
+            },
+            CoreError::QueryMemoryExceeded { limit, detail } => FsdbError::QueryMemoryExceeded {
+                message: format!("limit of {} bytes: {}", limit, detail),
+            },
+            CoreError::VersionGone(version) => FsdbError::VersionGone {
+                message: format!("version {}", version),
+            },
+            CoreError::UnsupportedDeltaFeature(feature) => {
+                FsdbError::UnsupportedDeltaFeature { message: feature }
+            }
+            CoreError::Decompression(message) => FsdbError::Decompression { message },
+            CoreError::CommitMetadataTooLarge { bytes, limit } => {
+                FsdbError::CommitMetadataTooLarge {
+                    message: format!("{} bytes, over the limit of {}", bytes, limit),
+                }
+            }
+            CoreError::MaintenanceInProgress(message) => {
+                FsdbError::MaintenanceInProgress { message }
+            }
+            CoreError::Cancelled(message) => FsdbError::Cancelled { message },
+            CoreError::QuotaExceeded {
+                username,
+                resource,
+                used,
+                requested,
+                limit,
+            } => FsdbError::QuotaExceeded {
+                message: format!(
+                    "user '{}' has {} {} of {}, {} more requested",
+                    username, used, resource, limit, requested
+                ),
+            },
+            CoreError::AmbiguousMerge { key, matches } => FsdbError::AmbiguousMerge {
+                message: format!("{} source rows match the target row with {}", matches, key),
+            },
+            CoreError::PermissionDenied(message) => FsdbError::PermissionDenied { message },
+            CoreError::AuthenticationRequired(message) => {
+                FsdbError::AuthenticationRequired { message }
+            }
+            CoreError::Encryption(message) => FsdbError::Encryption { message },
             CoreError::DeltaTable(e) => FsdbError::DeltaLakeError {
                 message: e.to_string(),
             },
@@ -139,6 +301,7 @@ impl Schema {
                     "Timestamp" => {
                         DataType::Timestamp(arrow::datatypes::TimeUnit::Microsecond, None)
                     }
+                    "Json" => return Ok(crate::metadata::json_field(&f.name, f.nullable)),
                     _ => {
                         return Err(FsdbError::InvalidOperation {
                             message: format!("Unsupported data type: {}", f.data_type),
@@ -158,6 +321,7 @@ impl Schema {
             .iter()
             .map(|f| {
                 let data_type = match f.data_type() {
+                    _ if crate::metadata::is_json(f) => "Json",
                     DataType::Int8 => "Int8",
                     DataType::Int16 => "Int16",
                     DataType::Int32 => "Int32",
@@ -195,6 +359,20 @@ pub struct Row {
     pub values: HashMap<String, String>,
 }
 
+/// One page of rows fetched from a cursor or query stream
+#[derive(Debug, Clone, uniffi::Record)]
+pub struct CursorPage {
+    pub rows: Vec<Row>,
+    pub has_more: bool,
+}
+
+/// Outcome of one query in a batch: its rows, or why it failed
+#[derive(Debug, Clone, uniffi::Record)]
+pub struct BatchQueryResult {
+    pub rows: Vec<Row>,
+    pub error: Option<String>,
+}
+
 /// Database metrics for monitoring
 #[derive(Debug, Clone, uniffi::Record)]
 pub struct DatabaseMetrics {
@@ -228,6 +406,58 @@ pub struct DataSkippingStats {
     pub bytes_skipped: u64,
 }
 
+/// Result of OPTIMIZE or Z-ORDER
+#[derive(Debug, Clone, uniffi::Record)]
+pub struct OptimizeMetrics {
+    pub num_files_added: u64,
+    pub num_files_removed: u64,
+    pub total_files_skipped: u64,
+    pub total_considered_files: u64,
+    pub partitions_optimized: u64,
+    pub bytes_added: u64,
+    pub bytes_removed: u64,
+    pub files_before: u64,
+    pub files_after: u64,
+    pub preserve_insertion_order: bool,
+}
+
+impl From<crate::delta_lake::OptimizeMetrics> for OptimizeMetrics {
+    fn from(metrics: crate::delta_lake::OptimizeMetrics) -> Self {
+        Self {
+            num_files_added: metrics.num_files_added,
+            num_files_removed: metrics.num_files_removed,
+            total_files_skipped: metrics.total_files_skipped,
+            total_considered_files: metrics.total_considered_files,
+            partitions_optimized: metrics.partitions_optimized,
+            bytes_added: metrics.bytes_added,
+            bytes_removed: metrics.bytes_removed,
+            files_before: metrics.files_before,
+            files_after: metrics.files_after,
+            preserve_insertion_order: metrics.preserve_insertion_order,
+        }
+    }
+}
+
+/// Result of VACUUM (bytes_reclaimed is 0 for S3 tables)
+#[derive(Debug, Clone, uniffi::Record)]
+pub struct VacuumReport {
+    pub files_deleted: u64,
+    pub bytes_reclaimed: u64,
+    pub dry_run: bool,
+    pub oldest_retained_version: Option<i64>,
+}
+
+impl From<crate::delta_lake::VacuumReport> for VacuumReport {
+    fn from(report: crate::delta_lake::VacuumReport) -> Self {
+        Self {
+            files_deleted: report.files_deleted,
+            bytes_reclaimed: report.bytes_reclaimed,
+            dry_run: report.dry_run,
+            oldest_retained_version: report.oldest_retained_version,
+        }
+    }
+}
+
 /// S3 configuration
 #[derive(Debug, Clone, uniffi::Record)]
 pub struct S3Config {
@@ -237,6 +467,17 @@ pub struct S3Config {
     pub region: String,
 }
 
+impl S3Config {
+    fn to_core(&self) -> crate::storage::s3::S3Config {
+        crate::storage::s3::minio_config(
+            &self.endpoint,
+            &self.access_key_id,
+            &self.secret_access_key,
+        )
+        .with_region(&self.region)
+    }
+}
+
 /// Main DatabaseOps wrapper for Python
 #[derive(uniffi::Object)]
 pub struct DatabaseOps {
@@ -299,12 +540,10 @@ impl DatabaseOps {
                 message: e.to_string(),
             })?,
         );
-        let db = runtime.block_on(CoreDatabaseOps::create_with_s3(
+        let db = runtime.block_on(CoreDatabaseOps::create_with_s3_config(
             &s3_path,
             arrow_schema,
-            &s3_config.endpoint,
-            &s3_config.access_key_id,
-            &s3_config.secret_access_key,
+            s3_config.to_core(),
         ))?;
         Ok(Arc::new(Self {
             inner: Arc::new(db),
@@ -361,11 +600,9 @@ impl DatabaseOps {
                 message: e.to_string(),
             })?,
         );
-        let db = runtime.block_on(CoreDatabaseOps::open_with_s3(
+        let db = runtime.block_on(CoreDatabaseOps::open_with_s3_config(
             &s3_path,
-            &s3_config.endpoint,
-            &s3_config.access_key_id,
-            &s3_config.secret_access_key,
+            s3_config.to_core(),
         ))?;
         Ok(Arc::new(Self {
             inner: Arc::new(db),
@@ -429,7 +666,8 @@ impl DatabaseOps {
     /// Flush any buffered writes to Delta Lake immediately
     ///
     /// Forces all buffered data from insert_buffered_json() to be committed.
-    /// Call this before reading data to ensure consistency, or at shutdown.
+    /// Queries on this database already see buffered rows; call this so
+    /// other processes do, or at shutdown.
     pub fn flush_write_buffer(&self) -> Result<(), FsdbError> {
         self.runtime.block_on(self.inner.flush_write_buffer())?;
         Ok(())
@@ -441,6 +679,12 @@ impl DatabaseOps {
         Ok(self.record_batches_to_rows(result))
     }
 
+    /// Check SQL against the table without running it, returning the result's schema
+    pub fn validate(&self, sql: String) -> Result<Schema, FsdbError> {
+        let schema = self.runtime.block_on(self.inner.validate(&sql))?;
+        Ok(Schema::from_arrow_schema(&schema))
+    }
+
     /// Query data and return as JSON string
     pub fn query_json(&self, sql: String) -> Result<String, FsdbError> {
         let result = self.runtime.block_on(self.inner.query(&sql))?;
@@ -450,12 +694,71 @@ impl DatabaseOps {
         })
     }
 
+    /// Run several queries against one snapshot of the table
+    ///
+    /// A query that fails has its error in its result; the others still run.
+    pub fn query_batch(&self, queries: Vec<String>) -> Result<Vec<BatchQueryResult>, FsdbError> {
+        let results = self.runtime.block_on(self.inner.query_batch(queries))?;
+        Ok(results
+            .into_iter()
+            .map(|result| match result {
+                Ok(result_set) => BatchQueryResult {
+                    rows: self.result_set_to_rows(&result_set),
+                    error: None,
+                },
+                Err(e) => BatchQueryResult {
+                    rows: Vec::new(),
+                    error: Some(e.to_string()),
+                },
+            })
+            .collect())
+    }
+
+    /// Open a server-side cursor pinned to the current table version
+    pub fn open_cursor(&self, sql: String) -> Result<u64, FsdbError> {
+        let cursor_id = self.runtime.block_on(self.inner.open_cursor(&sql))?;
+        Ok(cursor_id)
+    }
+
+    /// Fetch up to `n` rows from a cursor
+    pub fn fetch(&self, cursor_id: u64, n: u64) -> Result<CursorPage, FsdbError> {
+        let (batches, has_more) = self
+            .runtime
+            .block_on(self.inner.fetch(cursor_id, n as usize))?;
+        Ok(CursorPage {
+            rows: self.record_batches_to_rows(batches),
+            has_more,
+        })
+    }
+
+    /// Run a query and read its result a page at a time from the returned stream
+    ///
+    /// Rows are produced as pages are read, so results larger than memory
+    /// can be processed. Unlike a cursor, the stream doesn't expire.
+    pub fn query_stream(&self, sql: String) -> Result<Arc<QueryStream>, FsdbError> {
+        let stream = self.runtime.block_on(self.inner.query_stream(&sql))?;
+        Ok(Arc::new(QueryStream {
+            inner: tokio::sync::Mutex::new(stream),
+            runtime: self.runtime.clone(),
+        }))
+    }
+
+    /// Close a cursor before it expires
+    pub fn close_cursor(&self, cursor_id: u64) -> bool {
+        self.runtime.block_on(self.inner.close_cursor(cursor_id))
+    }
+
+    /// Cancel another caller's cursor; its next fetch fails as cancelled
+    pub fn cancel_cursor(&self, cursor_id: u64) -> Result<bool, FsdbError> {
+        Ok(self.runtime.block_on(self.inner.cancel_cursor(cursor_id))?)
+    }
+
     /// Delete rows matching a WHERE clause
     pub fn delete_rows_where(&self, predicate: String) -> Result<u64, FsdbError> {
-        let count = self
+        let commit = self
             .runtime
             .block_on(self.inner.delete_rows_where(&predicate))?;
-        Ok(count as u64)
+        Ok(commit.rows_affected)
     }
 
     /// Execute MERGE (UPSERT) operation from JSON data
@@ -465,7 +768,8 @@ impl DatabaseOps {
     /// - "UPDATE": Update existing rows  
     /// - "DELETE": Delete existing rows
     ///
-    /// The join is performed on the specified join_column (typically "id").
+    /// The join is performed on the specified join_column (typically "id"), or
+    /// on comma-separated columns forming a composite key.
     ///
     /// Returns a JSON string with merge metrics: rows_inserted, rows_updated, rows_deleted
     pub fn merge_json(&self, json_data: String, join_column: String) -> Result<String, FsdbError> {
@@ -494,7 +798,11 @@ impl DatabaseOps {
 
             merge_builder
                 .with_source(batch, "source")
-                .on(format!("target.{} = source.{}", join_column, join_column))
+                .on(join_column
+                    .split(',')
+                    .map(|column| format!("target.{0} = source.{0}", column.trim()))
+                    .collect::<Vec<_>>()
+                    .join(" AND "))
                 .when_matched_update()
                 .condition("source._op = 'UPDATE'")
                 .set_all()
@@ -513,7 +821,8 @@ impl DatabaseOps {
             "rows_inserted": metrics.rows_inserted,
             "rows_updated": metrics.rows_updated,
             "rows_deleted": metrics.rows_deleted,
-            "total_affected": metrics.total_rows_affected()
+            "total_affected": metrics.total_rows_affected(),
+            "version": metrics.commit.version
         });
 
         serde_json::to_string_pretty(&result).map_err(|e| FsdbError::SerializationError {
@@ -542,6 +851,30 @@ impl DatabaseOps {
         })
     }
 
+    /// Query data at a specific version, as `query` would
+    pub fn query_at_version(&self, sql: String, version: i64) -> Result<Vec<Row>, FsdbError> {
+        let result = self
+            .runtime
+            .block_on(self.inner.query_at_version(&sql, version))?;
+        Ok(self.record_batches_to_rows(result))
+    }
+
+    /// Turn the change data feed on or off
+    pub fn set_change_data_feed(&self, enabled: bool) -> Result<(), FsdbError> {
+        Ok(self
+            .runtime
+            .block_on(self.inner.set_change_data_feed(enabled))?)
+    }
+
+    /// Rows changed by versions `from_version` through `to_version`, with
+    /// `_change_type`, `_commit_version` and `_commit_timestamp`
+    pub fn read_changes(&self, from_version: u64, to_version: u64) -> Result<Vec<Row>, FsdbError> {
+        let result = self
+            .runtime
+            .block_on(self.inner.read_changes(from_version, to_version))?;
+        Ok(self.record_batches_to_rows(result))
+    }
+
     /// Query data at a specific timestamp (milliseconds since epoch)
     pub fn query_timestamp(&self, sql: String, timestamp_ms: i64) -> Result<Vec<Row>, FsdbError> {
         let result = self
@@ -568,29 +901,48 @@ impl DatabaseOps {
     // Delta Lake operations
 
     /// Run OPTIMIZE to compact files
-    pub fn optimize(&self) -> Result<(), FsdbError> {
-        self.runtime.block_on(self.inner.optimize())?;
-        Ok(())
+    pub fn optimize(&self) -> Result<OptimizeMetrics, FsdbError> {
+        let metrics = self.runtime.block_on(self.inner.optimize())?;
+        Ok(metrics.into())
     }
 
     /// Run OPTIMIZE with a target file size
-    pub fn optimize_with_target_size(&self, target_size_bytes: u64) -> Result<(), FsdbError> {
-        self.runtime
+    pub fn optimize_with_target_size(
+        &self,
+        target_size_bytes: u64,
+    ) -> Result<OptimizeMetrics, FsdbError> {
+        let metrics = self
+            .runtime
             .block_on(self.inner.optimize_with_target_size(target_size_bytes))?;
-        Ok(())
+        Ok(metrics.into())
     }
 
     /// Run OPTIMIZE with a filter
-    pub fn optimize_with_filter(&self, filter: String) -> Result<(), FsdbError> {
-        self.runtime
+    pub fn optimize_with_filter(&self, filter: String) -> Result<OptimizeMetrics, FsdbError> {
+        let metrics = self
+            .runtime
             .block_on(self.inner.optimize_with_filter(&filter))?;
-        Ok(())
+        Ok(metrics.into())
     }
 
     /// Run VACUUM to remove old files
-    pub fn vacuum(&self, retention_hours: u64) -> Result<(), FsdbError> {
-        self.runtime.block_on(self.inner.vacuum(retention_hours))?;
-        Ok(())
+    pub fn vacuum(&self, retention_hours: u64) -> Result<VacuumReport, FsdbError> {
+        let report = self.runtime.block_on(self.inner.vacuum(retention_hours))?;
+        Ok(report.into())
+    }
+
+    /// Run VACUUM keeping files removed within `retention_secs`, below the
+    /// minimum retention if `force` is set
+    pub fn vacuum_with_retention(
+        &self,
+        retention_secs: u64,
+        force: bool,
+    ) -> Result<VacuumReport, FsdbError> {
+        let report = self.runtime.block_on(
+            self.inner
+                .vacuum_with_retention(std::time::Duration::from_secs(retention_secs), force),
+        )?;
+        Ok(report.into())
     }
 
     /// Run VACUUM dry run to see what would be deleted
@@ -602,9 +954,31 @@ impl DatabaseOps {
     }
 
     /// Run Z-ORDER on specified columns
-    pub fn zorder(&self, columns: Vec<String>) -> Result<(), FsdbError> {
+    pub fn zorder(&self, columns: Vec<String>) -> Result<OptimizeMetrics, FsdbError> {
+        let column_refs: Vec<&str> = columns.iter().map(|s| s.as_str()).collect();
+        let metrics = self.runtime.block_on(self.inner.zorder(&column_refs))?;
+        Ok(metrics.into())
+    }
+
+    /// Run Z-ORDER on specified columns, rewriting only overlapping files
+    pub fn zorder_incremental(&self, columns: Vec<String>) -> Result<OptimizeMetrics, FsdbError> {
+        let column_refs: Vec<&str> = columns.iter().map(|s| s.as_str()).collect();
+        let metrics = self
+            .runtime
+            .block_on(self.inner.zorder_incremental(&column_refs))?;
+        Ok(metrics.into())
+    }
+
+    /// Columns OPTIMIZE keeps the table Z-ordered by
+    pub fn zorder_columns(&self) -> Vec<String> {
+        self.inner.zorder_columns()
+    }
+
+    /// Store the columns OPTIMIZE keeps the table Z-ordered by
+    pub fn set_zorder_columns(&self, columns: Vec<String>) -> Result<(), FsdbError> {
         let column_refs: Vec<&str> = columns.iter().map(|s| s.as_str()).collect();
-        self.runtime.block_on(self.inner.zorder(&column_refs))?;
+        self.runtime
+            .block_on(self.inner.set_zorder_columns(&column_refs))?;
         Ok(())
     }
 
@@ -623,6 +997,65 @@ impl DatabaseOps {
         Ok(())
     }
 
+    // Tables
+
+    /// Create the table `name` next to `data`, returning a handle on it
+    pub fn create_table(&self, name: String, schema: Schema) -> Result<Arc<Self>, FsdbError> {
+        let arrow_schema = schema.to_arrow_schema()?;
+        let db = self
+            .runtime
+            .block_on(self.inner.create_table(&name, arrow_schema))?;
+        Ok(Arc::new(Self {
+            inner: Arc::new(db),
+            runtime: self.runtime.clone(),
+        }))
+    }
+
+    /// Handle on the table `name`
+    pub fn table(&self, name: String) -> Result<Arc<Self>, FsdbError> {
+        let db = self.runtime.block_on(self.inner.table(&name))?;
+        Ok(Arc::new(Self {
+            inner: Arc::new(db),
+            runtime: self.runtime.clone(),
+        }))
+    }
+
+    /// Drop the table `name`, returning whether there was one
+    pub fn drop_table(&self, name: String) -> Result<bool, FsdbError> {
+        Ok(self.runtime.block_on(self.inner.drop_table(&name))?)
+    }
+
+    /// Names of the tables next to `data`
+    pub fn list_tables(&self) -> Result<Vec<String>, FsdbError> {
+        Ok(self.inner.list_tables()?)
+    }
+
+    // Export
+
+    /// Export the table to a file as "csv", "ndjson" or "parquet", returning the rows written
+    ///
+    /// Rows are streamed to the file a batch at a time. `columns` and `filter`
+    /// (an SQL predicate) narrow what's exported.
+    pub fn export_to_file(
+        &self,
+        path: String,
+        format: String,
+        columns: Option<Vec<String>>,
+        filter: Option<String>,
+    ) -> Result<u64, FsdbError> {
+        let format: crate::export::ExportFormat = format.parse()?;
+        let options = crate::export::ExportOptions {
+            columns,
+            filter,
+            ..Default::default()
+        };
+        let report = self.runtime.block_on(async {
+            let mut file = tokio::fs::File::create(&path).await?;
+            self.inner.export(&mut file, format, options).await
+        })?;
+        Ok(report.rows)
+    }
+
     // Backup and restore
 
     /// Create a full backup
@@ -778,24 +1211,23 @@ impl DatabaseOps {
 
     /// Convert RecordBatches to Rows
     fn record_batches_to_rows(&self, batches: Vec<arrow::array::RecordBatch>) -> Vec<Row> {
-        let mut rows = Vec::new();
-        for batch in batches {
-            for row_idx in 0..batch.num_rows() {
-                let mut values = HashMap::new();
-                for (col_idx, field) in batch.schema().fields().iter().enumerate() {
-                    let col = batch.column(col_idx);
-                    let value = format!("{:?}", col.slice(row_idx, 1));
-                    // Clean up the format (remove array wrapper)
-                    let clean_value = value
-                        .trim_start_matches('[')
-                        .trim_end_matches(']')
-                        .to_string();
-                    values.insert(field.name().clone(), clean_value);
-                }
-                rows.push(Row { values });
-            }
-        }
-        rows
+        record_batches_to_rows(batches)
+    }
+
+    /// Convert a typed result set to Rows
+    fn result_set_to_rows(&self, result_set: &crate::query::ResultSet) -> Vec<Row> {
+        let columns = result_set.columns();
+        result_set
+            .rows()
+            .iter()
+            .map(|row| Row {
+                values: columns
+                    .iter()
+                    .zip(row)
+                    .map(|(column, value)| (column.to_string(), value.to_string()))
+                    .collect(),
+            })
+            .collect()
     }
 
     /// Convert RecordBatches to JSON
@@ -831,6 +1263,53 @@ impl DatabaseOps {
     }
 }
 
+/// Convert RecordBatches to Rows
+fn record_batches_to_rows(batches: Vec<arrow::array::RecordBatch>) -> Vec<Row> {
+    let mut rows = Vec::new();
+    for batch in batches {
+        for row_idx in 0..batch.num_rows() {
+            let mut values = HashMap::new();
+            for (col_idx, field) in batch.schema().fields().iter().enumerate() {
+                // Typed conversion, so decimals keep their scale and timestamps their zone
+                let value = crate::query::Value::from_array(batch.column(col_idx), row_idx)
+                    .map(|value| value.to_string())
+                    .unwrap_or_default();
+                values.insert(field.name().clone(), value);
+            }
+            rows.push(Row { values });
+        }
+    }
+    rows
+}
+
+/// Result of a streaming query, read a page at a time
+#[derive(uniffi::Object)]
+pub struct QueryStream {
+    inner: tokio::sync::Mutex<crate::query::QueryStream>,
+    runtime: Arc<tokio::runtime::Runtime>,
+}
+
+#[uniffi::export]
+impl QueryStream {
+    /// Schema of the result, known before any row is read
+    pub fn schema(&self) -> Schema {
+        let inner = self.runtime.block_on(self.inner.lock());
+        Schema::from_arrow_schema(&inner.schema())
+    }
+
+    /// Read up to n rows; has_more is false once the result is drained
+    pub fn next_page(&self, n: u64) -> Result<CursorPage, FsdbError> {
+        let (batches, has_more) = self.runtime.block_on(async {
+            let mut inner = self.inner.lock().await;
+            inner.next_rows(n as usize).await
+        })?;
+        Ok(CursorPage {
+            rows: record_batches_to_rows(batches),
+            has_more,
+        })
+    }
+}
+
 /// NFS Server for exposing database as POSIX filesystem
 #[derive(uniffi::Object)]
 pub struct NfsServer {
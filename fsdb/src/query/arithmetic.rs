@@ -0,0 +1,99 @@
+//! SQL semantics for arithmetic DataFusion handles differently
+//!
+//! DataFusion fails the whole query when an integer is divided by zero, and
+//! gives infinity or NaN for floats. FSDB follows SQL instead: `x / 0` and
+//! `x % 0` are NULL for the rows where the divisor is zero, and the rest of
+//! the query goes on. The rewrite wraps each divisor in `NULLIF(divisor, 0)`
+//! before planning, except literals that are already non-zero.
+
+use datafusion::sql::sqlparser::ast::{
+    visit_expressions_mut, BinaryOperator, Expr, Value, ValueWithSpan,
+};
+use datafusion::sql::sqlparser::dialect::GenericDialect;
+use datafusion::sql::sqlparser::parser::Parser;
+use std::ops::ControlFlow;
+use tracing::debug;
+
+/// Rewrite `sql` so division and modulo by zero give NULL
+///
+/// SQL that doesn't parse is returned as-is for DataFusion to report.
+pub fn null_on_division_by_zero(sql: &str) -> String {
+    let dialect = GenericDialect {};
+    let Ok(mut statements) = Parser::parse_sql(&dialect, sql) else {
+        debug!("Skipping division rewrite: SQL did not parse");
+        return sql.to_string();
+    };
+
+    let mut rewritten = 0;
+    for statement in &mut statements {
+        let _ = visit_expressions_mut(statement, |expr| {
+            if let Expr::BinaryOp {
+                op: BinaryOperator::Divide | BinaryOperator::Modulo,
+                right,
+                ..
+            } = expr
+            {
+                if !is_nonzero_literal(right) {
+                    let guarded = format!("NULLIF({}, 0)", right);
+                    if let Ok(guarded) = Parser::new(&dialect)
+                        .try_with_sql(&guarded)
+                        .and_then(|mut parser| parser.parse_expr())
+                    {
+                        **right = guarded;
+                        rewritten += 1;
+                    }
+                }
+            }
+            ControlFlow::<()>::Continue(())
+        });
+    }
+    if rewritten == 0 {
+        return sql.to_string();
+    }
+
+    debug!("Guarded {} divisor(s) against zero", rewritten);
+    statements
+        .iter()
+        .map(|s| s.to_string())
+        .collect::<Vec<_>>()
+        .join("; ")
+}
+
+fn is_nonzero_literal(expr: &Expr) -> bool {
+    match expr {
+        Expr::Value(ValueWithSpan {
+            value: Value::Number(n, _),
+            ..
+        }) => n.parse::<f64>().is_ok_and(|n| n != 0.0),
+        Expr::Nested(inner) => is_nonzero_literal(inner),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_divisors_are_guarded() {
+        assert_eq!(
+            null_on_division_by_zero("SELECT a / b, a % (b - 1) FROM data"),
+            "SELECT a / NULLIF(b, 0), a % NULLIF((b - 1), 0) FROM data"
+        );
+        assert_eq!(
+            null_on_division_by_zero("SELECT * FROM data WHERE price / qty > 2"),
+            "SELECT * FROM data WHERE price / NULLIF(qty, 0) > 2"
+        );
+    }
+
+    #[test]
+    fn test_nonzero_literals_and_other_sql_left_alone() {
+        let sql = "SELECT price / 2, price * quantity FROM data";
+        assert_eq!(null_on_division_by_zero(sql), sql);
+        assert_eq!(
+            null_on_division_by_zero("SELECT a / 0 FROM data"),
+            "SELECT a / NULLIF(0, 0) FROM data"
+        );
+        assert_eq!(null_on_division_by_zero("not sql"), "not sql");
+    }
+}
@@ -0,0 +1,308 @@
+//! Schema-on-read casting views
+//!
+//! A cast view presents the table with some columns read as another type,
+//! such as a string column of timestamps read as `Timestamp`, without
+//! rewriting any data. Views are named and stored with the table in
+//! `_metadata/cast_views.json`, and every query can select from them next to
+//! `data`: `SELECT * FROM events_typed WHERE ts > '2024-06-01'`. A view has
+//! every column of the table, in order, with the cast ones replaced by
+//! `CAST` (strict) or `TRY_CAST` (lenient) of the stored value as part of the
+//! scan's projection. In lenient mode a value that doesn't convert reads as
+//! NULL; in strict mode it fails the query.
+//!
+//! Filters on columns a view leaves alone are pushed down to the table and
+//! skip files by their statistics as usual. Filters on cast columns never
+//! skip files: a file's minimum and maximum of the stored values say nothing
+//! reliable about the cast ones (`'10' < '9'` as strings), so every file is
+//! read and the filter applied to each cast row.
+
+use crate::{Error, Result};
+use arrow::array::RecordBatch;
+use arrow::compute::CastOptions;
+use arrow::datatypes::{DataType, Field, Schema, SchemaRef};
+use datafusion::dataframe::DataFrame;
+use datafusion::logical_expr::{cast, ident, try_cast};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// What a cast view does with a value that doesn't convert
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum CastMode {
+    /// Read it as NULL
+    #[default]
+    Lenient,
+    /// Fail the query
+    Strict,
+}
+
+/// A column read as another type
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ColumnCast {
+    /// Stored column
+    pub column: String,
+    /// Type it's read as, in Arrow's notation, e.g. `Timestamp(Microsecond, None)`
+    pub data_type: String,
+}
+
+impl ColumnCast {
+    /// Type the column is read as
+    pub fn data_type(&self) -> Result<DataType> {
+        self.data_type.parse().map_err(|e| {
+            Error::InvalidOperation(format!(
+                "Cast of '{}' to unknown type {}: {}",
+                self.column, self.data_type, e
+            ))
+        })
+    }
+}
+
+/// A view of the table with columns cast on read
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CastView {
+    /// Columns cast, in the order they were added
+    pub casts: Vec<ColumnCast>,
+    /// Handling of values that don't convert
+    pub mode: CastMode,
+}
+
+impl CastView {
+    /// A view casting nothing yet, with `mode`
+    pub fn new(mode: CastMode) -> Self {
+        Self {
+            casts: Vec::new(),
+            mode,
+        }
+    }
+
+    /// Read `column` as `data_type`
+    pub fn with_cast(mut self, column: impl Into<String>, data_type: DataType) -> Self {
+        self.casts.push(ColumnCast {
+            column: column.into(),
+            data_type: data_type.to_string(),
+        });
+        self
+    }
+
+    /// Type `column` is read as, if the view casts it
+    pub fn cast_of(&self, column: &str) -> Result<Option<DataType>> {
+        self.casts
+            .iter()
+            .find(|c| c.column == column)
+            .map(ColumnCast::data_type)
+            .transpose()
+    }
+
+    /// Check every cast names a column of `table` and can convert its type
+    pub fn validate(&self, table: &Schema) -> Result<()> {
+        for column_cast in &self.casts {
+            let field = table.field_with_name(&column_cast.column).map_err(|_| {
+                Error::InvalidOperation(format!("Cast of unknown column: {}", column_cast.column))
+            })?;
+            let to = column_cast.data_type()?;
+            if !arrow::compute::can_cast_types(field.data_type(), &to) {
+                return Err(Error::InvalidOperation(format!(
+                    "Column '{}' can't be cast from {} to {}",
+                    field.name(),
+                    field.data_type(),
+                    to
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    /// The view's schema over a table of schema `table`
+    ///
+    /// Cast columns are nullable in lenient mode, since values that don't
+    /// convert read as NULL.
+    pub fn schema(&self, table: &Schema) -> Result<SchemaRef> {
+        let fields = table
+            .fields()
+            .iter()
+            .map(|field| {
+                Ok(match self.cast_of(field.name())? {
+                    Some(to) => Arc::new(Field::new(
+                        field.name(),
+                        to,
+                        field.is_nullable() || self.mode == CastMode::Lenient,
+                    )),
+                    None => field.clone(),
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Ok(Arc::new(Schema::new(fields)))
+    }
+
+    /// Project `table`, a scan of the table, through the view
+    pub fn apply(&self, table: DataFrame) -> Result<DataFrame> {
+        let names: Vec<String> = table
+            .schema()
+            .fields()
+            .iter()
+            .map(|f| f.name().clone())
+            .collect();
+        let mut projection = Vec::with_capacity(names.len());
+        for name in &names {
+            projection.push(match self.cast_of(name)? {
+                Some(to) if self.mode == CastMode::Strict => cast(ident(name), to).alias(name),
+                Some(to) => try_cast(ident(name), to).alias(name),
+                None => ident(name),
+            });
+        }
+        Ok(table.select(projection)?)
+    }
+
+    /// Cast the columns of `batch`, rows read from the table, for the view
+    pub fn cast_batch(&self, batch: &RecordBatch) -> Result<RecordBatch> {
+        let schema = self.schema(&batch.schema())?;
+        let options = CastOptions {
+            safe: self.mode == CastMode::Lenient,
+            ..Default::default()
+        };
+        let columns = schema
+            .fields()
+            .iter()
+            .zip(batch.columns())
+            .map(|(field, column)| {
+                Ok(if field.data_type() == column.data_type() {
+                    column.clone()
+                } else {
+                    arrow::compute::cast_with_options(column, field.data_type(), &options)?
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Ok(RecordBatch::try_new(schema, columns)?)
+    }
+}
+
+/// Cast views defined on a table, by name
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CastViews {
+    pub views: BTreeMap<String, CastView>,
+}
+
+impl CastViews {
+    /// Where a table's cast views are stored
+    pub fn path(base_path: &Path) -> PathBuf {
+        base_path.join("_metadata").join("cast_views.json")
+    }
+
+    /// Load a table's cast views, none if it has no file of them
+    pub fn load(base_path: &Path) -> Result<Self> {
+        let path = Self::path(base_path);
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let json = std::fs::read_to_string(&path)?;
+        Ok(serde_json::from_str(&json)?)
+    }
+
+    /// Save atomically, replacing the previous views
+    pub fn save(&self, base_path: &Path) -> Result<()> {
+        let path = Self::path(base_path);
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let tmp_path = path.with_extension("tmp");
+        std::fs::write(&tmp_path, serde_json::to_string_pretty(self)?)?;
+        std::fs::rename(tmp_path, path)?;
+        Ok(())
+    }
+
+    /// Columns cast by the views `sql` reads from
+    ///
+    /// Views are found by name anywhere in the statement, so a mention in a
+    /// string literal counts too; that only costs file skipping.
+    pub fn cast_columns_in(&self, sql: &str) -> Vec<&str> {
+        let words: Vec<String> = sql
+            .split(|c: char| !(c.is_alphanumeric() || c == '_'))
+            .map(str::to_lowercase)
+            .collect();
+        self.views
+            .iter()
+            .filter(|(name, _)| words.contains(&name.to_lowercase()))
+            .flat_map(|(_, view)| view.casts.iter().map(|c| c.column.as_str()))
+            .collect()
+    }
+}
+
+/// Fail unless `name` can name a cast view
+///
+/// Names are plain identifiers, other than `data`, so they can be used
+/// unquoted in SQL.
+pub fn check_view_name(name: &str) -> Result<()> {
+    let mut chars = name.chars();
+    let valid = chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_');
+    if !valid || name.eq_ignore_ascii_case("data") || name != name.to_lowercase() {
+        return Err(Error::InvalidOperation(format!(
+            "Invalid cast view name '{}': use a lowercase identifier other than data",
+            name
+        )));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow::array::{Array, ArrayRef, AsArray, Int32Array, StringArray};
+    use arrow::datatypes::{Float64Type, TimeUnit};
+
+    fn table() -> Schema {
+        Schema::new(vec![
+            Field::new("id", DataType::Int32, false),
+            Field::new("amount", DataType::Utf8, false),
+        ])
+    }
+
+    #[test]
+    fn test_schema_and_batch_casts() {
+        let view = CastView::new(CastMode::Lenient).with_cast("amount", DataType::Float64);
+        view.validate(&table()).unwrap();
+        let schema = view.schema(&table()).unwrap();
+        assert_eq!(schema.field(0), table().field(0));
+        assert_eq!(schema.field(1).data_type(), &DataType::Float64);
+        assert!(schema.field(1).is_nullable());
+
+        let batch = RecordBatch::try_new(
+            Arc::new(table()),
+            vec![
+                Arc::new(Int32Array::from(vec![1, 2])) as ArrayRef,
+                Arc::new(StringArray::from(vec!["12.5", "n/a"])) as ArrayRef,
+            ],
+        )
+        .unwrap();
+        let cast = view.cast_batch(&batch).unwrap();
+        let amounts = cast.column(1).as_primitive::<Float64Type>();
+        assert_eq!(amounts.value(0), 12.5);
+        assert!(amounts.is_null(1));
+
+        let strict = CastView {
+            mode: CastMode::Strict,
+            ..view
+        };
+        assert!(strict.cast_batch(&batch).is_err());
+    }
+
+    #[test]
+    fn test_invalid_definitions() {
+        let timestamp = DataType::Timestamp(TimeUnit::Microsecond, None);
+        let unknown = CastView::new(CastMode::Lenient).with_cast("missing", timestamp.clone());
+        assert!(unknown.validate(&table()).is_err());
+        let impossible = CastView::new(CastMode::Lenient).with_cast(
+            "id",
+            DataType::Struct(vec![Field::new("x", timestamp, true)].into()),
+        );
+        assert!(impossible.validate(&table()).is_err());
+
+        assert!(check_view_name("events_typed").is_ok());
+        for name in ["data", "DATA", "Typed", "1view", "has space", ""] {
+            assert!(check_view_name(name).is_err(), "{}", name);
+        }
+    }
+}
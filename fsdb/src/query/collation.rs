@@ -0,0 +1,445 @@
+//! String collation for SQL queries
+//!
+//! DataFusion compares strings byte by byte, so `'Alice' = 'alice'` is false
+//! and `'Zebra'` sorts before `'apple'`. Under `Collation::CaseInsensitive`
+//! the query is rewritten before planning so string comparisons ignore case:
+//!
+//! - `=`, `<>`, `<`, `<=`, `>`, `>=`, `BETWEEN` and `IN` compare
+//!   `LOWER(...)` of both sides when either side is a string column or
+//!   literal, and `LIKE` becomes `ILIKE`
+//! - `ORDER BY` a string column sorts by `LOWER(col)`, then by `col` so
+//!   values differing only by case keep a stable order
+//! - `GROUP BY` a string column groups by `LOWER(col)`, and the grouped
+//!   column reads back in lower case
+//!
+//! String columns are recognized from the registered tables' schemas, so
+//! comparisons on expressions or aliases are only collated when the other
+//! side is a string literal. `SELECT DISTINCT` and `ORDER BY` of a `DISTINCT`
+//! query stay binary, since DataFusion requires their sort keys in the
+//! select list.
+//!
+//! File statistics are binary, so data skipping has to agree with the
+//! collation too; see `delta_lake::data_skipping::can_skip_file_with_collation`.
+
+use arrow::datatypes::DataType;
+use datafusion::prelude::SessionContext;
+use datafusion::sql::sqlparser::ast::{
+    BinaryOperator, Expr, GroupByExpr, Ident, ObjectNamePart, OrderByExpr, OrderByKind, Query,
+    SelectItem, SetExpr, Value, ValueWithSpan, VisitMut, VisitorMut,
+};
+use datafusion::sql::sqlparser::dialect::GenericDialect;
+use datafusion::sql::sqlparser::parser::Parser;
+use std::collections::HashSet;
+use std::ops::ControlFlow;
+use tracing::debug;
+
+/// How string values are compared, ordered and grouped in queries
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Collation {
+    /// Byte-wise comparison, as DataFusion does natively
+    #[default]
+    Binary,
+
+    /// Strings equal after `LOWER` compare equal, and sort by their lower case
+    CaseInsensitive,
+}
+
+/// Aggregate functions whose arguments aren't grouped values
+const AGGREGATES: &[&str] = &[
+    "count",
+    "sum",
+    "min",
+    "max",
+    "avg",
+    "mean",
+    "median",
+    "first_value",
+    "last_value",
+    "array_agg",
+    "string_agg",
+    "bool_and",
+    "bool_or",
+    "stddev",
+    "stddev_pop",
+    "stddev_samp",
+    "var",
+    "var_pop",
+    "var_samp",
+    "approx_distinct",
+    "approx_median",
+    "approx_percentile_cont",
+];
+
+/// Names of the string columns of every table registered in `ctx`, lower-cased
+pub async fn string_columns(ctx: &SessionContext) -> crate::Result<HashSet<String>> {
+    let options = ctx.copied_config().options().catalog.clone();
+    let mut columns = HashSet::new();
+    let schema = ctx
+        .catalog(&options.default_catalog)
+        .and_then(|catalog| catalog.schema(&options.default_schema));
+    if let Some(schema) = schema {
+        for name in schema.table_names() {
+            if let Some(table) = schema.table(&name).await? {
+                columns.extend(
+                    table
+                        .schema()
+                        .fields()
+                        .iter()
+                        .filter(|f| {
+                            matches!(
+                                f.data_type(),
+                                DataType::Utf8 | DataType::LargeUtf8 | DataType::Utf8View
+                            )
+                        })
+                        .map(|f| f.name().to_lowercase()),
+                );
+            }
+        }
+    }
+    Ok(columns)
+}
+
+/// Rewrite `sql` so string comparisons follow `collation`
+///
+/// `string_columns` are the lower-cased names of the columns holding
+/// strings. SQL that doesn't parse is returned as-is for DataFusion to report.
+pub fn apply_collation(
+    sql: &str,
+    collation: Collation,
+    string_columns: &HashSet<String>,
+) -> String {
+    if collation == Collation::Binary {
+        return sql.to_string();
+    }
+    let Ok(mut statements) = Parser::parse_sql(&GenericDialect {}, sql) else {
+        debug!("Skipping collation rewrite: SQL did not parse");
+        return sql.to_string();
+    };
+
+    let mut rewriter = CaseFolder {
+        string_columns,
+        rewritten: 0,
+    };
+    for statement in &mut statements {
+        let _ = statement.visit(&mut rewriter);
+    }
+    if rewriter.rewritten == 0 {
+        return sql.to_string();
+    }
+
+    debug!("Collated {} string comparison(s)", rewriter.rewritten);
+    statements
+        .iter()
+        .map(|s| s.to_string())
+        .collect::<Vec<_>>()
+        .join("; ")
+}
+
+/// Folds case in comparisons, then in each query's grouping and ordering
+struct CaseFolder<'a> {
+    string_columns: &'a HashSet<String>,
+    rewritten: usize,
+}
+
+impl CaseFolder<'_> {
+    fn is_string(&self, expr: &Expr) -> bool {
+        match expr {
+            Expr::Value(ValueWithSpan {
+                value: Value::SingleQuotedString(_) | Value::EscapedStringLiteral(_),
+                ..
+            }) => true,
+            Expr::Identifier(ident) => self.is_string_column(ident),
+            Expr::CompoundIdentifier(idents) => idents
+                .last()
+                .is_some_and(|ident| self.is_string_column(ident)),
+            Expr::Nested(inner) => self.is_string(inner),
+            _ => false,
+        }
+    }
+
+    fn is_string_column(&self, ident: &Ident) -> bool {
+        self.string_columns.contains(&ident.value.to_lowercase())
+    }
+
+    fn fold_group_by(&mut self, query: &mut Query) -> HashSet<String> {
+        let SetExpr::Select(select) = query.body.as_mut() else {
+            return HashSet::new();
+        };
+        let GroupByExpr::Expressions(exprs, _) = &mut select.group_by else {
+            return HashSet::new();
+        };
+
+        let mut keys = HashSet::new();
+        for expr in exprs.iter_mut() {
+            if let Some(name) = column_name(expr).filter(|_| self.is_string(expr)) {
+                keys.insert(name);
+                *expr = lower(expr);
+                self.rewritten += 1;
+            }
+        }
+        if keys.is_empty() {
+            return keys;
+        }
+
+        // Grouped columns outside aggregates now have to read the folded key
+        let mut folder = GroupKeyFolder {
+            keys: &keys,
+            in_aggregate: 0,
+            in_subquery: 0,
+        };
+        for item in &mut select.projection {
+            match item {
+                SelectItem::UnnamedExpr(expr)
+                    if column_name(expr).is_some_and(|n| keys.contains(&n)) =>
+                {
+                    let alias = match expr {
+                        Expr::CompoundIdentifier(idents) => idents.last().cloned(),
+                        Expr::Identifier(ident) => Some(ident.clone()),
+                        _ => None,
+                    };
+                    if let Some(alias) = alias {
+                        *item = SelectItem::ExprWithAlias {
+                            expr: lower(expr),
+                            alias,
+                        };
+                    }
+                }
+                SelectItem::UnnamedExpr(expr) | SelectItem::ExprWithAlias { expr, .. } => {
+                    let _ = expr.visit(&mut folder);
+                }
+                _ => {}
+            }
+        }
+        if let Some(having) = &mut select.having {
+            let _ = having.visit(&mut folder);
+        }
+        keys
+    }
+
+    fn fold_order_by(&mut self, query: &mut Query, group_keys: &HashSet<String>) {
+        if let SetExpr::Select(select) = query.body.as_ref() {
+            if select.distinct.is_some() {
+                return;
+            }
+        }
+        let Some(order_by) = &mut query.order_by else {
+            return;
+        };
+        let OrderByKind::Expressions(exprs) = &mut order_by.kind else {
+            return;
+        };
+
+        let mut folded = Vec::with_capacity(exprs.len());
+        for order in exprs.drain(..) {
+            if !self.is_string(&order.expr) {
+                folded.push(order);
+                continue;
+            }
+            self.rewritten += 1;
+            let grouped = column_name(&order.expr).is_some_and(|name| group_keys.contains(&name));
+            folded.push(OrderByExpr {
+                expr: lower(&order.expr),
+                ..order.clone()
+            });
+            // Grouped columns are already folded, so there's nothing to break ties on
+            if !grouped {
+                folded.push(order);
+            }
+        }
+        *exprs = folded;
+    }
+}
+
+impl VisitorMut for CaseFolder<'_> {
+    type Break = ();
+
+    fn post_visit_expr(&mut self, expr: &mut Expr) -> ControlFlow<Self::Break> {
+        match expr {
+            Expr::BinaryOp {
+                left,
+                op:
+                    BinaryOperator::Eq
+                    | BinaryOperator::NotEq
+                    | BinaryOperator::Lt
+                    | BinaryOperator::LtEq
+                    | BinaryOperator::Gt
+                    | BinaryOperator::GtEq,
+                right,
+            } if self.is_string(left) || self.is_string(right) => {
+                **left = lower(left);
+                **right = lower(right);
+                self.rewritten += 1;
+            }
+            Expr::Between {
+                expr: inner,
+                low,
+                high,
+                ..
+            } if self.is_string(inner) || self.is_string(low) || self.is_string(high) => {
+                **inner = lower(inner);
+                **low = lower(low);
+                **high = lower(high);
+                self.rewritten += 1;
+            }
+            Expr::InList {
+                expr: inner, list, ..
+            } if self.is_string(inner) || list.iter().any(|e| self.is_string(e)) => {
+                **inner = lower(inner);
+                for item in list.iter_mut() {
+                    *item = lower(item);
+                }
+                self.rewritten += 1;
+            }
+            Expr::Like {
+                negated,
+                any,
+                expr: inner,
+                pattern,
+                escape_char,
+            } => {
+                *expr = Expr::ILike {
+                    negated: *negated,
+                    any: *any,
+                    expr: inner.clone(),
+                    pattern: pattern.clone(),
+                    escape_char: escape_char.clone(),
+                };
+                self.rewritten += 1;
+            }
+            _ => {}
+        }
+        ControlFlow::Continue(())
+    }
+
+    fn post_visit_query(&mut self, query: &mut Query) -> ControlFlow<Self::Break> {
+        let group_keys = self.fold_group_by(query);
+        self.fold_order_by(query, &group_keys);
+        ControlFlow::Continue(())
+    }
+}
+
+/// Wraps references to folded group keys in `LOWER`, outside aggregates
+struct GroupKeyFolder<'a> {
+    keys: &'a HashSet<String>,
+    in_aggregate: usize,
+    in_subquery: usize,
+}
+
+impl VisitorMut for GroupKeyFolder<'_> {
+    type Break = ();
+
+    fn pre_visit_query(&mut self, _query: &mut Query) -> ControlFlow<Self::Break> {
+        self.in_subquery += 1;
+        ControlFlow::Continue(())
+    }
+
+    fn post_visit_query(&mut self, _query: &mut Query) -> ControlFlow<Self::Break> {
+        self.in_subquery -= 1;
+        ControlFlow::Continue(())
+    }
+
+    fn pre_visit_expr(&mut self, expr: &mut Expr) -> ControlFlow<Self::Break> {
+        if is_aggregate(expr) {
+            self.in_aggregate += 1;
+        }
+        ControlFlow::Continue(())
+    }
+
+    fn post_visit_expr(&mut self, expr: &mut Expr) -> ControlFlow<Self::Break> {
+        if is_aggregate(expr) {
+            self.in_aggregate -= 1;
+        } else if self.in_aggregate == 0
+            && self.in_subquery == 0
+            && column_name(expr).is_some_and(|name| self.keys.contains(&name))
+        {
+            *expr = lower(expr);
+        }
+        ControlFlow::Continue(())
+    }
+}
+
+fn is_aggregate(expr: &Expr) -> bool {
+    match expr {
+        Expr::Function(function) => match function.name.0.last() {
+            Some(ObjectNamePart::Identifier(ident)) => {
+                AGGREGATES.contains(&ident.value.to_lowercase().as_str())
+            }
+            _ => false,
+        },
+        _ => false,
+    }
+}
+
+/// The lower-cased name of a column reference
+fn column_name(expr: &Expr) -> Option<String> {
+    match expr {
+        Expr::Identifier(ident) => Some(ident.value.to_lowercase()),
+        Expr::CompoundIdentifier(idents) => idents.last().map(|i| i.value.to_lowercase()),
+        _ => None,
+    }
+}
+
+/// `LOWER(expr)`, or `expr` itself if the call doesn't parse
+fn lower(expr: &Expr) -> Expr {
+    Parser::new(&GenericDialect {})
+        .try_with_sql(&format!("LOWER({})", expr))
+        .and_then(|mut parser| parser.parse_expr())
+        .unwrap_or_else(|_| expr.clone())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn collate(sql: &str) -> String {
+        let columns = HashSet::from(["name".to_string(), "city".to_string()]);
+        apply_collation(sql, Collation::CaseInsensitive, &columns)
+    }
+
+    #[test]
+    fn test_comparisons_folded() {
+        assert_eq!(
+            collate("SELECT id FROM data WHERE name = 'Alice' AND id > 3"),
+            "SELECT id FROM data WHERE LOWER(name) = LOWER('Alice') AND id > 3"
+        );
+        assert_eq!(
+            collate("SELECT id FROM data WHERE city IN ('Oslo', 'bergen') OR name LIKE 'a%'"),
+            "SELECT id FROM data WHERE LOWER(city) IN (LOWER('Oslo'), LOWER('bergen')) \
+             OR name ILIKE 'a%'"
+        );
+        assert_eq!(
+            collate("SELECT id FROM data WHERE upper(code) >= 'ab'"),
+            "SELECT id FROM data WHERE LOWER(upper(code)) >= LOWER('ab')"
+        );
+    }
+
+    #[test]
+    fn test_order_and_group_folded() {
+        assert_eq!(
+            collate("SELECT name FROM data ORDER BY name DESC, id"),
+            "SELECT name FROM data ORDER BY LOWER(name) DESC, name DESC, id"
+        );
+        assert_eq!(
+            collate("SELECT city, count(*) FROM data GROUP BY city ORDER BY city"),
+            "SELECT LOWER(city) AS city, count(*) FROM data GROUP BY LOWER(city) \
+             ORDER BY LOWER(city)"
+        );
+        assert_eq!(
+            collate("SELECT city, max(name) AS top FROM data GROUP BY city"),
+            "SELECT LOWER(city) AS city, max(name) AS top FROM data GROUP BY LOWER(city)"
+        );
+    }
+
+    #[test]
+    fn test_binary_and_non_strings_left_alone() {
+        let columns = HashSet::from(["name".to_string()]);
+        let sql = "SELECT * FROM data WHERE name = 'Alice' ORDER BY name";
+        assert_eq!(apply_collation(sql, Collation::Binary, &columns), sql);
+
+        let sql = "SELECT id FROM data WHERE id = 3 GROUP BY id ORDER BY id";
+        assert_eq!(collate(sql), sql);
+        let sql = "SELECT DISTINCT name FROM data ORDER BY name";
+        assert_eq!(collate(sql), sql);
+        assert_eq!(collate("not sql"), "not sql");
+    }
+}
@@ -0,0 +1,388 @@
+//! Server-side query cursors for stateless clients
+//!
+//! A cursor keeps a lazily evaluated result stream pinned to the Delta version
+//! that was current when it was opened, so clients can page through results
+//! across separate calls. Cursors that sit idle past the TTL expire.
+//!
+//! Open cursors can be listed and cancelled from outside, which unpins their
+//! version at once. The owner's next fetch then fails with `Error::Cancelled`.
+
+use super::stream::QueryStream;
+use crate::{Error, Result};
+use arrow::array::RecordBatch;
+use datafusion::physical_plan::SendableRecordBatchStream;
+use std::collections::{HashMap, HashSet};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::Mutex;
+use tracing::{debug, info};
+
+/// Identifier handed out by `DatabaseOps::open_cursor`
+pub type CursorId = u64;
+
+/// Configuration for the cursor registry
+#[derive(Debug, Clone)]
+pub struct CursorConfig {
+    /// Idle time after which a cursor expires
+    pub idle_timeout: Duration,
+
+    /// Maximum number of cursors open at once
+    pub max_open_cursors: usize,
+}
+
+impl Default for CursorConfig {
+    fn default() -> Self {
+        Self {
+            idle_timeout: Duration::from_secs(300), // 5 minutes
+            max_open_cursors: 1024,
+        }
+    }
+}
+
+/// An open cursor, as listed by `CursorRegistry::list`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CursorInfo {
+    pub id: CursorId,
+    /// Delta version the cursor is pinned to
+    pub version: i64,
+    /// Time since the cursor was opened
+    pub age: Duration,
+    /// Time since the last open or fetch
+    pub idle: Duration,
+    /// Rows returned so far
+    pub rows_fetched: u64,
+    /// All rows have been read from the query
+    pub exhausted: bool,
+}
+
+/// State of a single open cursor
+struct CursorState {
+    /// Delta version the cursor reads from
+    version: i64,
+    /// Remaining query output
+    stream: QueryStream,
+    /// Last open or fetch, used for TTL expiry
+    last_access: Instant,
+    opened: Instant,
+    rows_fetched: u64,
+}
+
+impl CursorState {
+    fn is_expired(&self, idle_timeout: Duration) -> bool {
+        self.last_access.elapsed() >= idle_timeout
+    }
+}
+
+/// Registry of open cursors with inactivity expiry
+pub struct CursorRegistry {
+    config: CursorConfig,
+    cursors: Mutex<HashMap<CursorId, Arc<Mutex<CursorState>>>>,
+    /// Cancelled cursors whose owner hasn't been told yet
+    cancelled: Mutex<HashSet<CursorId>>,
+    next_id: AtomicU64,
+}
+
+impl Default for CursorRegistry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl CursorRegistry {
+    /// Create a registry with default configuration
+    pub fn new() -> Self {
+        Self::with_config(CursorConfig::default())
+    }
+
+    /// Create a registry with custom configuration
+    pub fn with_config(config: CursorConfig) -> Self {
+        Self {
+            config,
+            cursors: Mutex::new(HashMap::new()),
+            cancelled: Mutex::new(HashSet::new()),
+            next_id: AtomicU64::new(1),
+        }
+    }
+
+    /// Configuration the registry was created with
+    pub fn config(&self) -> &CursorConfig {
+        &self.config
+    }
+
+    /// Register a result stream pinned to `version` and return its cursor ID
+    pub async fn register(
+        &self,
+        version: i64,
+        stream: SendableRecordBatchStream,
+    ) -> Result<CursorId> {
+        self.evict_expired().await;
+
+        let mut cursors = self.cursors.lock().await;
+        if cursors.len() >= self.config.max_open_cursors {
+            return Err(Error::InvalidOperation(format!(
+                "Too many open cursors (limit {})",
+                self.config.max_open_cursors
+            )));
+        }
+
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        cursors.insert(
+            id,
+            Arc::new(Mutex::new(CursorState {
+                version,
+                stream: QueryStream::new(stream),
+                last_access: Instant::now(),
+                opened: Instant::now(),
+                rows_fetched: 0,
+            })),
+        );
+
+        info!("Opened cursor {} at version {}", id, version);
+        Ok(id)
+    }
+
+    /// Fetch up to `n` rows from a cursor
+    ///
+    /// Returns the rows and whether more remain. Unknown, closed or idle
+    /// cursors fail with `Error::CursorExpired`, and the first fetch after
+    /// a cancel with `Error::Cancelled`.
+    pub async fn fetch(&self, id: CursorId, n: usize) -> Result<(Vec<RecordBatch>, bool)> {
+        if n == 0 {
+            return Err(Error::InvalidOperation(
+                "Fetch size must be greater than zero".to_string(),
+            ));
+        }
+
+        let cursor = {
+            let cursors = self.cursors.lock().await;
+            cursors.get(&id).cloned()
+        };
+        let Some(cursor) = cursor else {
+            if self.cancelled.lock().await.remove(&id) {
+                return Err(Error::Cancelled(format!("cursor {}", id)));
+            }
+            return Err(Error::CursorExpired(id));
+        };
+
+        let mut state = cursor.lock().await;
+        if state.is_expired(self.config.idle_timeout) {
+            drop(state);
+            self.cursors.lock().await.remove(&id);
+            debug!("Cursor {} expired", id);
+            return Err(Error::CursorExpired(id));
+        }
+        state.last_access = Instant::now();
+
+        let (rows, has_more) = state.stream.next_rows(n).await?;
+        let fetched: usize = rows.iter().map(|b| b.num_rows()).sum();
+        state.rows_fetched += fetched as u64;
+
+        debug!(
+            "Cursor {} fetched {} rows (has_more: {})",
+            id, fetched, has_more
+        );
+        Ok((rows, has_more))
+    }
+
+    /// Close a cursor, returning true if it was open
+    pub async fn close(&self, id: CursorId) -> bool {
+        self.cancelled.lock().await.remove(&id);
+        self.cursors.lock().await.remove(&id).is_some()
+    }
+
+    /// Cancel a cursor on behalf of someone other than its owner, returning
+    /// true if it was open
+    ///
+    /// The cursor's stream is dropped and its version unpinned right away;
+    /// a fetch already in progress finishes first. The owner's next fetch
+    /// fails with `Error::Cancelled`.
+    pub async fn cancel(&self, id: CursorId) -> bool {
+        let removed = self.cursors.lock().await.remove(&id);
+        let Some(cursor) = removed else {
+            return false;
+        };
+        self.cancelled.lock().await.insert(id);
+        info!(
+            "Cancelled cursor {} at version {}",
+            id,
+            cursor.lock().await.version
+        );
+        true
+    }
+
+    /// Open cursors, by ID
+    pub async fn list(&self) -> Vec<CursorInfo> {
+        let cursors = self.cursors.lock().await;
+        let mut open = Vec::with_capacity(cursors.len());
+        for (id, cursor) in cursors.iter() {
+            let state = cursor.lock().await;
+            open.push(CursorInfo {
+                id: *id,
+                version: state.version,
+                age: state.opened.elapsed(),
+                idle: state.last_access.elapsed(),
+                rows_fetched: state.rows_fetched,
+                exhausted: state.stream.is_exhausted(),
+            });
+        }
+        open.sort_by_key(|info| info.id);
+        open
+    }
+
+    /// Drop cursors idle past the TTL, returning how many were removed
+    pub async fn evict_expired(&self) -> usize {
+        let mut cursors = self.cursors.lock().await;
+        let mut expired = Vec::new();
+        for (id, cursor) in cursors.iter() {
+            // A cursor that is locked is mid-fetch and therefore not idle
+            if let Ok(state) = cursor.try_lock() {
+                if state.is_expired(self.config.idle_timeout) {
+                    expired.push(*id);
+                }
+            }
+        }
+        for id in &expired {
+            cursors.remove(id);
+        }
+        if !expired.is_empty() {
+            debug!("Evicted {} expired cursors", expired.len());
+        }
+        expired.len()
+    }
+
+    /// Delta versions pinned by cursors that have not expired
+    pub async fn pinned_versions(&self) -> Vec<i64> {
+        let cursors = self.cursors.lock().await;
+        let mut versions = Vec::new();
+        for cursor in cursors.values() {
+            let state = cursor.lock().await;
+            if !state.is_expired(self.config.idle_timeout) {
+                versions.push(state.version);
+            }
+        }
+        versions.sort_unstable();
+        versions.dedup();
+        versions
+    }
+
+    /// Number of open cursors
+    pub async fn len(&self) -> usize {
+        self.cursors.lock().await.len()
+    }
+
+    /// True if no cursors are open
+    pub async fn is_empty(&self) -> bool {
+        self.cursors.lock().await.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow::array::Int32Array;
+    use arrow::datatypes::{DataType, Field, Schema};
+    use datafusion::physical_plan::stream::RecordBatchStreamAdapter;
+
+    fn test_stream(batch_sizes: &[i32]) -> SendableRecordBatchStream {
+        let schema = Arc::new(Schema::new(vec![Field::new("id", DataType::Int32, false)]));
+        let mut start = 0;
+        let batches: Vec<_> = batch_sizes
+            .iter()
+            .map(|size| {
+                let ids: Vec<i32> = (start..start + size).collect();
+                start += size;
+                Ok(
+                    RecordBatch::try_new(schema.clone(), vec![Arc::new(Int32Array::from(ids))])
+                        .unwrap(),
+                )
+            })
+            .collect();
+        Box::pin(RecordBatchStreamAdapter::new(
+            schema,
+            futures::stream::iter(batches),
+        ))
+    }
+
+    fn row_count(batches: &[RecordBatch]) -> usize {
+        batches.iter().map(|b| b.num_rows()).sum()
+    }
+
+    #[tokio::test]
+    async fn test_fetch_in_pages() {
+        let registry = CursorRegistry::new();
+        let id = registry.register(0, test_stream(&[4, 3])).await.unwrap();
+
+        let (page, has_more) = registry.fetch(id, 3).await.unwrap();
+        assert_eq!(row_count(&page), 3);
+        assert!(has_more);
+
+        // Page spans the batch boundary
+        let (page, has_more) = registry.fetch(id, 3).await.unwrap();
+        assert_eq!(row_count(&page), 3);
+        assert!(has_more);
+        let first = page[0]
+            .column(0)
+            .as_any()
+            .downcast_ref::<Int32Array>()
+            .unwrap();
+        assert_eq!(first.value(0), 3);
+
+        let (page, has_more) = registry.fetch(id, 3).await.unwrap();
+        assert_eq!(row_count(&page), 1);
+        assert!(!has_more);
+    }
+
+    #[tokio::test]
+    async fn test_fetch_after_expiry() {
+        let registry = CursorRegistry::with_config(CursorConfig {
+            idle_timeout: Duration::from_millis(50),
+            ..Default::default()
+        });
+        let id = registry.register(0, test_stream(&[5])).await.unwrap();
+
+        registry.fetch(id, 2).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        let err = registry.fetch(id, 2).await.unwrap_err();
+        assert!(matches!(err, Error::CursorExpired(expired) if expired == id));
+        assert!(registry.is_empty().await);
+    }
+
+    #[tokio::test]
+    async fn test_cancel_reports_once() {
+        let registry = CursorRegistry::new();
+        let id = registry.register(3, test_stream(&[4])).await.unwrap();
+        registry.fetch(id, 1).await.unwrap();
+
+        let open = registry.list().await;
+        assert_eq!(open.len(), 1);
+        assert_eq!((open[0].id, open[0].version), (id, 3));
+        assert_eq!(open[0].rows_fetched, 1);
+
+        assert!(registry.cancel(id).await);
+        assert!(!registry.cancel(id).await);
+        assert!(registry.pinned_versions().await.is_empty());
+        assert!(matches!(
+            registry.fetch(id, 1).await,
+            Err(Error::Cancelled(_))
+        ));
+        // Only the first fetch after the cancel reports it
+        assert!(matches!(
+            registry.fetch(id, 1).await,
+            Err(Error::CursorExpired(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_closed_cursor_is_expired() {
+        let registry = CursorRegistry::new();
+        let id = registry.register(0, test_stream(&[1])).await.unwrap();
+
+        assert!(registry.close(id).await);
+        assert!(matches!(
+            registry.fetch(id, 1).await,
+            Err(Error::CursorExpired(_))
+        ));
+    }
+}
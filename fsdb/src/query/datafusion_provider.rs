@@ -1,76 +1,115 @@
 //! DataFusion TableProvider implementation
 //!
-//! Provides SQL query capabilities over Parquet data files with MVCC visibility
+//! Provides SQL query capabilities over Parquet data files with MVCC visibility.
+//! Scans of Parquet files decode only the columns a query projects.
 
+use crate::storage::parquet::{MissingColumn, ParquetReader};
 use crate::Result;
 use arrow::array::RecordBatch;
 use arrow::datatypes::SchemaRef;
 use datafusion::catalog::Session;
 use datafusion::datasource::{MemTable, TableProvider};
-use datafusion::error::Result as DataFusionResult;
+use datafusion::error::{DataFusionError, Result as DataFusionResult};
 use datafusion::logical_expr::{Expr, TableType};
 use datafusion::physical_plan::ExecutionPlan;
 use std::any::Any;
 use std::sync::Arc;
+use tracing::debug;
 
 /// TableProvider for DataFusion integration with MVCC
 #[derive(Debug)]
 pub struct FsdbTableProvider {
-    inner: Arc<MemTable>,
+    schema: SchemaRef,
+    source: Source,
+}
+
+/// Where a provider's rows come from
+#[derive(Debug)]
+enum Source {
+    Memory(Arc<MemTable>),
+    /// Read at scan time, decoding only the columns the query uses
+    ParquetFiles {
+        paths: Vec<String>,
+        missing: MissingColumn,
+    },
 }
 
 impl FsdbTableProvider {
     /// Create a new table provider with schema and data
     pub fn new(schema: SchemaRef, data: Vec<RecordBatch>) -> Result<Self> {
         // Use DataFusion's MemTable which provides TableProvider implementation
-        let mem_table = MemTable::try_new(schema, vec![data])?;
+        let mem_table = MemTable::try_new(schema.clone(), vec![data])?;
         Ok(Self {
-            inner: Arc::new(mem_table),
+            schema,
+            source: Source::Memory(Arc::new(mem_table)),
         })
     }
 
     /// Create a table provider from Parquet files with MVCC filtering
+    ///
+    /// Files are read when the table is scanned, and only the columns the
+    /// query references are decoded. A column a file doesn't have reads as
+    /// nulls; see `with_missing_columns`.
     pub fn from_parquet_files(file_paths: &[String], schema: SchemaRef) -> Result<Self> {
-        use crate::storage::parquet::ParquetReader;
-        use tracing::debug;
-
         debug!(
-            "Loading {} parquet files into DataFusion table provider",
+            "Creating DataFusion table provider over {} parquet files",
             file_paths.len()
         );
+        Ok(Self {
+            schema,
+            source: Source::ParquetFiles {
+                paths: file_paths.to_vec(),
+                missing: MissingColumn::default(),
+            },
+        })
+    }
 
+    /// Set what scans of Parquet files do with columns a file doesn't have
+    pub fn with_missing_columns(mut self, policy: MissingColumn) -> Self {
+        if let Source::ParquetFiles { missing, .. } = &mut self.source {
+            *missing = policy;
+        }
+        self
+    }
+
+    /// Read the projected columns of every file into one batch
+    fn read_files(
+        &self,
+        paths: &[String],
+        missing: MissingColumn,
+        projected: &SchemaRef,
+    ) -> Result<Vec<RecordBatch>> {
         let reader = ParquetReader::new();
         let mut all_batches = Vec::new();
 
         // Read each parquet file
-        for file_path in file_paths {
+        for file_path in paths {
             debug!("Reading parquet file: {}", file_path);
-            let batch = reader.read_batch(file_path)?;
+            let batch = reader.read_projected(file_path, projected, missing)?;
             if batch.num_rows() > 0 {
                 all_batches.push(batch);
             }
         }
 
-        // If no data, create empty batch with schema
+        // If no data, the table is empty
         if all_batches.is_empty() {
             debug!("No data in parquet files, creating empty table");
-            return Self::new(schema, vec![]);
+            return Ok(vec![]);
         }
 
         // Concatenate all batches into one
         let combined_batch = if all_batches.len() == 1 {
             all_batches.into_iter().next().unwrap()
         } else {
-            arrow::compute::concat_batches(&schema, &all_batches)?
+            arrow::compute::concat_batches(projected, &all_batches)?
         };
 
         debug!(
-            "Loaded {} total rows from parquet files",
-            combined_batch.num_rows()
+            "Loaded {} total rows, {} columns from parquet files",
+            combined_batch.num_rows(),
+            combined_batch.num_columns()
         );
-
-        // Create table provider with combined data
-        Self::new(schema, vec![combined_batch])
+        Ok(vec![combined_batch])
     }
 }
 
@@ -81,7 +120,7 @@ impl TableProvider for FsdbTableProvider {
     }
 
     fn schema(&self) -> SchemaRef {
-        self.inner.schema()
+        self.schema.clone()
     }
 
     fn table_type(&self) -> TableType {
@@ -95,8 +134,22 @@ impl TableProvider for FsdbTableProvider {
         filters: &[Expr],
         limit: Option<usize>,
     ) -> DataFusionResult<Arc<dyn ExecutionPlan>> {
-        // Delegate to the inner MemTable
-        self.inner.scan(state, projection, filters, limit).await
+        match &self.source {
+            // Delegate to the inner MemTable
+            Source::Memory(table) => table.scan(state, projection, filters, limit).await,
+            Source::ParquetFiles { paths, missing } => {
+                let projected = match projection {
+                    Some(indices) => Arc::new(self.schema.project(indices)?),
+                    None => self.schema.clone(),
+                };
+                let batches = self
+                    .read_files(paths, *missing, &projected)
+                    .map_err(|e| DataFusionError::External(Box::new(e)))?;
+                MemTable::try_new(projected, vec![batches])?
+                    .scan(state, None, filters, limit)
+                    .await
+            }
+        }
     }
 }
 
@@ -515,4 +568,82 @@ mod tests {
             .unwrap();
         assert_eq!(count_col.value(0), 5);
     }
+
+    #[tokio::test]
+    async fn test_from_parquet_files_projects_columns() {
+        use crate::storage::parquet::ParquetWriter;
+        use tempfile::TempDir;
+
+        let temp_dir = TempDir::new().unwrap();
+
+        // Written before `score` was added to the table
+        let old_schema = Arc::new(ArrowSchema::new(vec![
+            Field::new("id", DataType::Int32, false),
+            Field::new("name", DataType::Utf8, false),
+        ]));
+        let batch = RecordBatch::try_new(
+            old_schema,
+            vec![
+                Arc::new(Int32Array::from(vec![1, 2])) as ArrayRef,
+                Arc::new(StringArray::from(vec!["Alice", "Bob"])) as ArrayRef,
+            ],
+        )
+        .unwrap();
+        let file = temp_dir.path().join("data_001.parquet");
+        ParquetWriter::new().write_batch(&file, &batch).unwrap();
+        let file_paths = vec![file.to_str().unwrap().to_string()];
+
+        let schema = Arc::new(ArrowSchema::new(vec![
+            Field::new("id", DataType::Int32, false),
+            Field::new("name", DataType::Utf8, false),
+            Field::new("score", DataType::Float64, true),
+        ]));
+        let provider = FsdbTableProvider::from_parquet_files(&file_paths, schema.clone()).unwrap();
+        let ctx = SessionContext::new();
+        ctx.register_table("parquet_data", Arc::new(provider))
+            .unwrap();
+
+        // Only `score` is read, and reads as nulls
+        let results = ctx
+            .sql("SELECT score FROM parquet_data")
+            .await
+            .unwrap()
+            .collect()
+            .await
+            .unwrap();
+        assert_eq!(results[0].num_columns(), 1);
+        assert_eq!(results[0].num_rows(), 2);
+        assert_eq!(results[0].column(0).null_count(), 2);
+
+        // No columns at all still counts rows
+        let results = ctx
+            .sql("SELECT COUNT(*) FROM parquet_data")
+            .await
+            .unwrap()
+            .collect()
+            .await
+            .unwrap();
+        let count_col = results[0]
+            .column(0)
+            .as_any()
+            .downcast_ref::<arrow::array::Int64Array>()
+            .unwrap();
+        assert_eq!(count_col.value(0), 2);
+
+        // Unless missing columns are errors
+        let provider = FsdbTableProvider::from_parquet_files(&file_paths, schema)
+            .unwrap()
+            .with_missing_columns(MissingColumn::Error);
+        let ctx = SessionContext::new();
+        ctx.register_table("parquet_data", Arc::new(provider))
+            .unwrap();
+        assert!(ctx
+            .sql("SELECT score FROM parquet_data")
+            .await
+            .unwrap()
+            .collect()
+            .await
+            .is_err());
+        assert!(ctx.sql("SELECT name FROM parquet_data").await.is_ok());
+    }
 }
@@ -2,7 +2,9 @@
 //!
 //! Provides SQL query execution over Parquet data files with MVCC transaction support
 
+use crate::query::identifiers::{plan_sql, session_context, IdentifierCase};
 use crate::query::FsdbTableProvider;
+use crate::security::LogRedaction;
 use crate::Result;
 use arrow::array::RecordBatch;
 use arrow::datatypes::SchemaRef;
@@ -14,6 +16,8 @@ use tracing::{debug, info};
 pub struct QueryExecutor {
     ctx: SessionContext,
     registration_lock: tokio::sync::Mutex<()>,
+    identifier_case: IdentifierCase,
+    log_redaction: LogRedaction,
 }
 
 impl Default for QueryExecutor {
@@ -25,12 +29,25 @@ impl Default for QueryExecutor {
 impl QueryExecutor {
     /// Create a new query executor with DataFusion SessionContext
     pub fn new() -> Self {
+        Self::with_identifier_case(IdentifierCase::default())
+    }
+
+    /// Create a query executor with the given identifier case policy
+    pub fn with_identifier_case(identifier_case: IdentifierCase) -> Self {
         Self {
-            ctx: SessionContext::new(),
+            ctx: session_context(identifier_case),
             registration_lock: tokio::sync::Mutex::new(()),
+            identifier_case,
+            log_redaction: LogRedaction::default(),
         }
     }
 
+    /// Redact the SQL this executor logs
+    pub fn with_log_redaction(mut self, policy: LogRedaction) -> Self {
+        self.log_redaction = policy;
+        self
+    }
+
     /// Register a table with the executor (thread-safe)
     pub async fn register_table(
         &self,
@@ -59,9 +76,9 @@ impl QueryExecutor {
 
     /// Execute SQL query and return results as RecordBatches
     pub async fn execute_sql(&self, sql: &str) -> Result<Vec<RecordBatch>> {
-        info!("Executing SQL: {}", sql);
+        info!("Executing SQL: {}", self.log_redaction.redact(sql));
 
-        let df = self.ctx.sql(sql).await?;
+        let df = plan_sql(&self.ctx, sql, self.identifier_case).await?;
         let results = df.collect().await?;
 
         debug!("Query returned {} batches", results.len());
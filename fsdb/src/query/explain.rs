@@ -0,0 +1,198 @@
+//! EXPLAIN and EXPLAIN ANALYZE
+//!
+//! `QueryPlan` describes how a query runs: DataFusion's physical plan and
+//! the files data skipping leaves to read. EXPLAIN ANALYZE also executes the
+//! query and adds what actually happened, per operator and in total. A query
+//! that fails while running still reports the metrics collected up to the
+//! failure, along with the error.
+
+use datafusion::datasource::physical_plan::FileScanConfig;
+use datafusion::datasource::source::DataSourceExec;
+use datafusion::execution::TaskContext;
+use datafusion::physical_plan::metrics::MetricValue;
+use datafusion::physical_plan::{execute_stream, DisplayableExecutionPlan, ExecutionPlan};
+use futures::StreamExt;
+use std::fmt;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// How a query is planned to run, and with EXPLAIN ANALYZE how it ran
+#[derive(Debug, Clone)]
+pub struct QueryPlan {
+    /// Physical plan, one operator per line, indented by depth; after
+    /// EXPLAIN ANALYZE each operator carries its metrics
+    pub plan: String,
+
+    /// Files the query's predicates select from the Delta log
+    pub scan: ScanStats,
+
+    /// What running the query reported (EXPLAIN ANALYZE only)
+    pub runtime: Option<RuntimeStats>,
+}
+
+/// Data skipping for a query, decided from the Delta log's file statistics
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ScanStats {
+    pub total_files: usize,
+    pub files_to_read: usize,
+    pub files_skipped: usize,
+    pub bytes_to_read: u64,
+    pub bytes_skipped: u64,
+}
+
+/// Metrics from executing a query
+#[derive(Debug, Clone, Default)]
+pub struct RuntimeStats {
+    /// Wall time from the start of execution until it finished or failed
+    pub elapsed: Duration,
+
+    /// Rows the query returned
+    pub output_rows: usize,
+
+    /// Rows produced by the scans at the leaves of the plan
+    pub rows_scanned: usize,
+
+    /// Data files the scans opened, after DataFusion's own pruning
+    pub files_opened: usize,
+
+    /// Sum of the operators' peak memory; an upper bound, as operators
+    /// don't all peak at once
+    pub peak_memory_bytes: usize,
+
+    /// Every operator, in plan order
+    pub operators: Vec<OperatorStats>,
+
+    /// Why execution stopped early, if it did
+    pub error: Option<String>,
+}
+
+/// Metrics of one operator, summed over its partitions
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OperatorStats {
+    /// 0 for the root
+    pub depth: usize,
+    pub name: String,
+    pub output_rows: Option<usize>,
+    /// CPU time spent in the operator, excluding its inputs
+    pub elapsed_compute: Option<Duration>,
+    pub peak_memory_bytes: Option<usize>,
+}
+
+/// The static description of `plan`
+pub fn describe(plan: &Arc<dyn ExecutionPlan>) -> String {
+    DisplayableExecutionPlan::new(plan.as_ref())
+        .indent(true)
+        .to_string()
+}
+
+/// Execute `plan`, discarding its output, and collect its metrics
+///
+/// Returns the plan annotated with metrics, and the totals.
+pub async fn analyze(
+    plan: Arc<dyn ExecutionPlan>,
+    task_ctx: Arc<TaskContext>,
+) -> (String, RuntimeStats) {
+    let mut runtime = RuntimeStats::default();
+    let start = Instant::now();
+    match execute_stream(plan.clone(), task_ctx) {
+        Ok(mut stream) => {
+            while let Some(batch) = stream.next().await {
+                match batch {
+                    Ok(batch) => runtime.output_rows += batch.num_rows(),
+                    Err(e) => {
+                        runtime.error = Some(e.to_string());
+                        break;
+                    }
+                }
+            }
+        }
+        Err(e) => runtime.error = Some(e.to_string()),
+    }
+    runtime.elapsed = start.elapsed();
+
+    collect_operators(&plan, 0, &mut runtime);
+    let annotated = DisplayableExecutionPlan::with_metrics(plan.as_ref())
+        .indent(true)
+        .to_string();
+    (annotated, runtime)
+}
+
+fn collect_operators(plan: &Arc<dyn ExecutionPlan>, depth: usize, runtime: &mut RuntimeStats) {
+    let metrics = plan.metrics().map(|m| m.aggregate_by_name());
+    let mut operator = OperatorStats {
+        depth,
+        name: plan.name().to_string(),
+        output_rows: None,
+        elapsed_compute: None,
+        peak_memory_bytes: None,
+    };
+    let mut files_pruned = 0;
+    if let Some(metrics) = &metrics {
+        operator.output_rows = metrics.output_rows();
+        operator.elapsed_compute = metrics
+            .elapsed_compute()
+            .map(|ns| Duration::from_nanos(ns as u64));
+        for metric in metrics.iter() {
+            match metric.value() {
+                MetricValue::Gauge { name, gauge } if name == "peak_mem_used" => {
+                    operator.peak_memory_bytes = Some(gauge.value());
+                }
+                MetricValue::Count { name, count } if name == "files_ranges_pruned_statistics" => {
+                    files_pruned += count.value();
+                }
+                _ => {}
+            }
+        }
+    }
+    runtime.peak_memory_bytes += operator.peak_memory_bytes.unwrap_or(0);
+
+    if plan.children().is_empty() {
+        runtime.rows_scanned += operator.output_rows.unwrap_or(0);
+        runtime.files_opened += scheduled_files(plan).saturating_sub(files_pruned);
+    }
+    runtime.operators.push(operator);
+
+    for child in plan.children() {
+        collect_operators(child, depth + 1, runtime);
+    }
+}
+
+/// Files a scan was given to read
+fn scheduled_files(plan: &Arc<dyn ExecutionPlan>) -> usize {
+    plan.as_any()
+        .downcast_ref::<DataSourceExec>()
+        .and_then(|exec| exec.data_source().as_any().downcast_ref::<FileScanConfig>())
+        .map_or(0, |config| {
+            config.file_groups.iter().map(|group| group.len()).sum()
+        })
+}
+
+impl fmt::Display for QueryPlan {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{}", self.plan.trim_end())?;
+        writeln!(
+            f,
+            "Data skipping: {} of {} files to read ({} bytes), {} skipped ({} bytes)",
+            self.scan.files_to_read,
+            self.scan.total_files,
+            self.scan.bytes_to_read,
+            self.scan.files_skipped,
+            self.scan.bytes_skipped
+        )?;
+        if let Some(runtime) = &self.runtime {
+            writeln!(
+                f,
+                "Runtime: {:?}, {} rows returned, {} rows scanned from {} files, peak memory {} bytes",
+                runtime.elapsed,
+                runtime.output_rows,
+                runtime.rows_scanned,
+                runtime.files_opened,
+                runtime.peak_memory_bytes
+            )?;
+            if let Some(error) = &runtime.error {
+                writeln!(f, "Failed: {}", error)?;
+            }
+        }
+        Ok(())
+    }
+}
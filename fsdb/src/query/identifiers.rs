@@ -0,0 +1,471 @@
+//! Identifier case-folding for SQL queries
+//!
+//! DataFusion folds unquoted identifiers to lower case, so a column stored as
+//! `Customer` cannot be reached as `Customer` or `CUSTOMER` without quoting.
+//! In case-insensitive mode the resolver rewrites unquoted table and column
+//! references to the exact registered name before planning. Quoted
+//! identifiers always keep their case and must match exactly.
+
+use crate::query::collation::Collation;
+use crate::storage::spill::SpillManager;
+use crate::{Error, Result};
+use arrow::datatypes::SchemaRef;
+use datafusion::dataframe::DataFrame;
+use datafusion::prelude::{SessionConfig, SessionContext};
+use datafusion::sql::sqlparser::ast::{
+    visit_expressions_mut, Expr, Ident, ObjectName, ObjectNamePart, Statement, TableFactor,
+    VisitMut, VisitorMut,
+};
+use datafusion::sql::sqlparser::dialect::GenericDialect;
+use datafusion::sql::sqlparser::parser::Parser;
+use std::collections::{BTreeSet, HashMap};
+use std::ops::ControlFlow;
+use std::sync::Arc;
+use tracing::{debug, warn};
+
+/// How unquoted identifiers are matched against table and column names
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum IdentifierCase {
+    /// Unquoted identifiers match regardless of case, as ANSI SQL specifies
+    #[default]
+    Insensitive,
+
+    /// Unquoted identifiers match only their exact spelling
+    Sensitive,
+}
+
+/// Create a DataFusion context whose SQL parser follows `case`
+///
+/// The JSON functions of `query::json` and the percentile aggregates of
+/// `query::percentile` are registered with it.
+pub fn session_context(case: IdentifierCase) -> SessionContext {
+    let ctx = SessionContext::new_with_config(session_config(case));
+    super::json::register_json_functions(&ctx);
+    super::percentile::register_percentile_functions(&ctx);
+    ctx
+}
+
+fn session_config(case: IdentifierCase) -> SessionConfig {
+    let mut config = SessionConfig::new();
+    if case == IdentifierCase::Sensitive {
+        config.options_mut().sql_parser.enable_ident_normalization = false;
+    }
+    config
+}
+
+/// Like `session_context`, with operators spilling into the spill directory
+///
+/// DataFusion tracks its own temp files, so it's given whatever is left of
+/// the cap when the context is created. With `memory_limit`, hash tables,
+/// sort buffers and aggregation state share a pool of that many bytes:
+/// operators that can spill do so once their share runs out, and the rest
+/// fail with `ResourcesExhausted`. With `scan_concurrency`, scans spread
+/// their files over that many groups read at once, however small the files.
+pub fn session_context_with_spill(
+    case: IdentifierCase,
+    spill: &SpillManager,
+    memory_limit: Option<usize>,
+    scan_concurrency: Option<usize>,
+) -> SessionContext {
+    use datafusion::execution::disk_manager::{DiskManagerBuilder, DiskManagerMode};
+    use datafusion::execution::memory_pool::{FairSpillPool, TrackConsumersPool};
+    use datafusion::execution::runtime_env::RuntimeEnvBuilder;
+    use std::num::NonZeroUsize;
+
+    let mut disk =
+        DiskManagerBuilder::default().with_mode(DiskManagerMode::Directories(vec![spill
+            .temp_dir()
+            .to_path_buf()]));
+    if let Some(remaining) = spill.remaining() {
+        disk = disk.with_max_temp_directory_size(remaining);
+    }
+    let mut runtime = RuntimeEnvBuilder::new().with_disk_manager_builder(disk);
+    if let Some(limit) = memory_limit {
+        // Name the biggest consumers in the error so a failed query says what grew
+        runtime = runtime.with_memory_pool(Arc::new(TrackConsumersPool::new(
+            FairSpillPool::new(limit),
+            NonZeroUsize::new(5).expect("non-zero"),
+        )));
+    }
+    let mut config = session_config(case);
+    if let Some(files) = scan_concurrency {
+        // DataFusion only splits scans of files over 10 MB by default
+        config = config
+            .with_target_partitions(files.max(1))
+            .with_repartition_file_scans(true)
+            .with_repartition_file_min_size(0);
+    }
+    let ctx = match runtime.build_arc() {
+        Ok(runtime) => SessionContext::new_with_config_rt(config, runtime),
+        Err(e) => {
+            warn!("Spill directory unavailable, using the default: {}", e);
+            SessionContext::new_with_config(config)
+        }
+    };
+    super::json::register_json_functions(&ctx);
+    super::percentile::register_percentile_functions(&ctx);
+    ctx
+}
+
+/// Resolve identifiers in `sql` against the tables registered in `ctx` and plan it
+///
+/// Division and modulo by zero are rewritten to give NULL, see
+/// `query::arithmetic`, and the JSON operators `->` and `->>` to functions,
+/// see `query::json`.
+pub async fn plan_sql(ctx: &SessionContext, sql: &str, case: IdentifierCase) -> Result<DataFrame> {
+    plan_sql_with_collation(ctx, sql, case, Collation::Binary).await
+}
+
+/// Like `plan_sql`, with strings compared under `collation`, see
+/// `query::collation`
+pub async fn plan_sql_with_collation(
+    ctx: &SessionContext,
+    sql: &str,
+    case: IdentifierCase,
+    collation: Collation,
+) -> Result<DataFrame> {
+    let sql = super::json::rewrite_json_operators(sql);
+    let resolved = IdentifierResolver::from_context(ctx, case)
+        .await?
+        .resolve(&sql)?;
+    let mut resolved = super::arithmetic::null_on_division_by_zero(&resolved);
+    if collation != Collation::Binary {
+        let columns = super::collation::string_columns(ctx).await?;
+        resolved = super::collation::apply_collation(&resolved, collation, &columns);
+    }
+    Ok(ctx.sql(&resolved).await?)
+}
+
+/// Rewrites unquoted identifiers to the exact names of known tables and columns
+#[derive(Debug, Clone, Default)]
+pub struct IdentifierResolver {
+    case: IdentifierCase,
+    tables: HashMap<String, SchemaRef>,
+}
+
+impl IdentifierResolver {
+    /// Create a resolver with no known tables
+    pub fn new(case: IdentifierCase) -> Self {
+        Self {
+            case,
+            tables: HashMap::new(),
+        }
+    }
+
+    /// Add a table the resolver can match against
+    pub fn with_table(mut self, name: impl Into<String>, schema: SchemaRef) -> Self {
+        self.tables.insert(name.into(), schema);
+        self
+    }
+
+    /// Create a resolver that knows every table in the context's default schema
+    pub async fn from_context(ctx: &SessionContext, case: IdentifierCase) -> Result<Self> {
+        let mut resolver = Self::new(case);
+        if case == IdentifierCase::Sensitive {
+            return Ok(resolver);
+        }
+
+        let options = ctx.copied_config().options().catalog.clone();
+        let schema = ctx
+            .catalog(&options.default_catalog)
+            .and_then(|catalog| catalog.schema(&options.default_schema));
+        if let Some(schema) = schema {
+            for name in schema.table_names() {
+                if let Some(table) = schema.table(&name).await? {
+                    resolver.tables.insert(name, table.schema());
+                }
+            }
+        }
+        Ok(resolver)
+    }
+
+    /// Rewrite `sql` so unquoted identifiers name tables and columns exactly
+    ///
+    /// Fails with `Error::AmbiguousIdentifier` when an unquoted identifier
+    /// matches more than one name that differs only by case. Identifiers that
+    /// match nothing, such as aliases, are left for DataFusion to resolve.
+    pub fn resolve(&self, sql: &str) -> Result<String> {
+        if self.case == IdentifierCase::Sensitive {
+            return Ok(sql.to_string());
+        }
+
+        // DataFusion-only statements are passed through and reported by the planner
+        let mut statements = match Parser::parse_sql(&GenericDialect {}, sql) {
+            Ok(statements) => statements,
+            Err(_) => {
+                debug!("Skipping identifier resolution: SQL did not parse");
+                return Ok(sql.to_string());
+            }
+        };
+        for statement in &mut statements {
+            self.resolve_statement(statement)?;
+        }
+
+        let resolved = statements
+            .iter()
+            .map(|s| s.to_string())
+            .collect::<Vec<_>>()
+            .join("; ");
+        // Counts only, since the text may hold literals
+        debug!("Resolved identifiers in {} statement(s)", statements.len());
+        Ok(resolved)
+    }
+
+    fn resolve_statement(&self, statement: &mut Statement) -> Result<()> {
+        // Tables first, since projections are visited before FROM
+        let mut scope = ScopeCollector {
+            tables: &self.tables,
+            in_scope: Vec::new(),
+            aliases: HashMap::new(),
+        };
+        if let ControlFlow::Break(e) = statement.visit(&mut scope) {
+            return Err(e);
+        }
+        let ScopeCollector {
+            mut in_scope,
+            aliases,
+            ..
+        } = scope;
+        if in_scope.is_empty() {
+            in_scope = self.tables.keys().cloned().collect();
+        }
+
+        let flow = visit_expressions_mut(statement, |expr| {
+            let result = match expr {
+                Expr::Identifier(ident) => self.resolve_column(ident, &in_scope),
+                Expr::CompoundIdentifier(parts) if parts.len() >= 2 => {
+                    let (column, qualifiers) = parts.split_last_mut().unwrap();
+                    let qualifier = qualifiers.last_mut().unwrap();
+                    self.resolve_qualifier(qualifier, &aliases, &in_scope)
+                        .and_then(|tables| self.resolve_column(column, &tables))
+                }
+                _ => Ok(()),
+            };
+            match result {
+                Ok(()) => ControlFlow::Continue(()),
+                Err(e) => ControlFlow::Break(e),
+            }
+        });
+        match flow {
+            ControlFlow::Break(e) => Err(e),
+            ControlFlow::Continue(()) => Ok(()),
+        }
+    }
+
+    /// Resolve the tables a qualified column may belong to, through aliases
+    ///
+    /// An alias shadows a table of the same name. Aliases of subqueries and
+    /// unknown tables resolve to nothing, leaving their columns as written.
+    fn resolve_qualifier(
+        &self,
+        qualifier: &mut Ident,
+        aliases: &HashMap<String, Option<String>>,
+        in_scope: &[String],
+    ) -> Result<Vec<String>> {
+        if let Some(table) = aliases.get(&fold_alias(qualifier)) {
+            return Ok(table.iter().cloned().collect());
+        }
+        match resolve_ident(qualifier, self.tables.keys().map(String::as_str))? {
+            Some(table) => Ok(vec![table]),
+            None => Ok(in_scope.to_vec()),
+        }
+    }
+
+    fn resolve_column(&self, ident: &mut Ident, tables: &[String]) -> Result<()> {
+        let columns = tables
+            .iter()
+            .filter_map(|table| self.tables.get(table))
+            .flat_map(|schema| schema.fields().iter().map(|f| f.name().as_str()));
+        resolve_ident(ident, columns).map(|_| ())
+    }
+}
+
+/// First pass: resolve table names and record which tables and aliases are in scope
+///
+/// Aliases map to the table they rename, or to None for subqueries and
+/// unknown tables, whose columns the resolver can't see.
+struct ScopeCollector<'a> {
+    tables: &'a HashMap<String, SchemaRef>,
+    in_scope: Vec<String>,
+    aliases: HashMap<String, Option<String>>,
+}
+
+impl VisitorMut for ScopeCollector<'_> {
+    type Break = Error;
+
+    fn pre_visit_table_factor(&mut self, table_factor: &mut TableFactor) -> ControlFlow<Error> {
+        match table_factor {
+            TableFactor::Table { name, alias, .. } => {
+                let table = match resolve_table_name(name, self.tables) {
+                    Ok(table) => table,
+                    Err(e) => return ControlFlow::Break(e),
+                };
+                if let Some(alias) = alias {
+                    self.aliases.insert(fold_alias(&alias.name), table.clone());
+                }
+                self.in_scope.extend(table);
+            }
+            TableFactor::Derived {
+                alias: Some(alias), ..
+            } => {
+                self.aliases.insert(fold_alias(&alias.name), None);
+            }
+            _ => {}
+        }
+        ControlFlow::Continue(())
+    }
+}
+
+/// Aliases are folded by DataFusion the same way they were declared
+fn fold_alias(alias: &Ident) -> String {
+    if alias.quote_style.is_some() {
+        alias.value.clone()
+    } else {
+        alias.value.to_lowercase()
+    }
+}
+
+fn resolve_table_name(
+    name: &mut ObjectName,
+    tables: &HashMap<String, SchemaRef>,
+) -> Result<Option<String>> {
+    // Only bare table names live in the default schema
+    if name.0.len() != 1 {
+        return Ok(None);
+    }
+    match name.0.last_mut() {
+        Some(ObjectNamePart::Identifier(ident)) => {
+            resolve_ident(ident, tables.keys().map(String::as_str))
+        }
+        _ => Ok(None),
+    }
+}
+
+/// Match `ident` against `names`, rewriting it to the exact name if unquoted
+///
+/// Returns the matched name, or `None` when nothing matches.
+fn resolve_ident<'a>(
+    ident: &mut Ident,
+    names: impl Iterator<Item = &'a str>,
+) -> Result<Option<String>> {
+    if ident.quote_style.is_some() {
+        return Ok(names.find(|name| *name == ident.value).map(str::to_string));
+    }
+
+    let candidates: BTreeSet<&str> = names
+        .filter(|name| name.eq_ignore_ascii_case(&ident.value))
+        .collect();
+    match candidates.len() {
+        0 => Ok(None),
+        1 => {
+            let exact = candidates.into_iter().next().unwrap().to_string();
+            ident.value = exact.clone();
+            ident.quote_style = Some('"');
+            Ok(Some(exact))
+        }
+        _ => Err(Error::AmbiguousIdentifier {
+            identifier: ident.value.clone(),
+            candidates: candidates.into_iter().map(str::to_string).collect(),
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow::datatypes::{DataType, Field, Schema};
+    use std::sync::Arc;
+
+    fn schema(columns: &[&str]) -> SchemaRef {
+        Arc::new(Schema::new(
+            columns
+                .iter()
+                .map(|c| Field::new(*c, DataType::Int32, true))
+                .collect::<Vec<_>>(),
+        ))
+    }
+
+    fn resolver() -> IdentifierResolver {
+        IdentifierResolver::new(IdentifierCase::Insensitive)
+            .with_table("Customer", schema(&["Id", "Region"]))
+    }
+
+    #[test]
+    fn test_unquoted_identifiers_fold() {
+        let sql = resolver()
+            .resolve("SELECT id, REGION FROM customer WHERE Customer.ID > 1 ORDER BY region")
+            .unwrap();
+        assert_eq!(
+            sql,
+            "SELECT \"Id\", \"Region\" FROM \"Customer\" WHERE \"Customer\".\"Id\" > 1 ORDER BY \"Region\""
+        );
+    }
+
+    #[test]
+    fn test_quoted_identifiers_keep_case() {
+        // Quoted names must match exactly and are never rewritten
+        let sql = resolver()
+            .resolve("SELECT \"id\", \"Region\" FROM \"Customer\"")
+            .unwrap();
+        assert_eq!(sql, "SELECT \"id\", \"Region\" FROM \"Customer\"");
+    }
+
+    #[test]
+    fn test_alias_qualifier() {
+        let sql = resolver()
+            .resolve("SELECT c.region AS r FROM customer AS c GROUP BY c.REGION")
+            .unwrap();
+        assert_eq!(
+            sql,
+            "SELECT c.\"Region\" AS r FROM \"Customer\" AS c GROUP BY c.\"Region\""
+        );
+    }
+
+    #[test]
+    fn test_alias_shadows_table() {
+        let resolver = resolver().with_table("orders", schema(&["Id", "Total"]));
+
+        // `customer` names the orders table here, so its columns are resolved
+        let sql = resolver
+            .resolve("SELECT customer.total FROM orders AS customer")
+            .unwrap();
+        assert_eq!(sql, "SELECT customer.\"Total\" FROM \"orders\" AS customer");
+
+        // A subquery alias hides the table too; its columns are left alone
+        let sql = resolver
+            .resolve("SELECT customer.region FROM (SELECT 1 AS region) AS customer")
+            .unwrap();
+        assert_eq!(
+            sql,
+            "SELECT customer.region FROM (SELECT 1 AS region) AS customer"
+        );
+    }
+
+    #[test]
+    fn test_ambiguous_column() {
+        let resolver = IdentifierResolver::new(IdentifierCase::Insensitive)
+            .with_table("data", schema(&["name", "Name"]));
+
+        match resolver.resolve("SELECT NAME FROM data") {
+            Err(Error::AmbiguousIdentifier {
+                identifier,
+                candidates,
+            }) => {
+                assert_eq!(identifier, "NAME");
+                assert_eq!(candidates, vec!["Name".to_string(), "name".to_string()]);
+            }
+            other => panic!("expected AmbiguousIdentifier, got {:?}", other),
+        }
+
+        // Quoting picks one of them explicitly
+        assert!(resolver.resolve("SELECT \"Name\" FROM data").is_ok());
+    }
+
+    #[test]
+    fn test_sensitive_mode_leaves_sql_alone() {
+        let resolver = IdentifierResolver::new(IdentifierCase::Sensitive)
+            .with_table("data", schema(&["name", "Name"]));
+        let sql = "SELECT NAME FROM data";
+        assert_eq!(resolver.resolve(sql).unwrap(), sql);
+    }
+}
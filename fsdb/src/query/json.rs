@@ -0,0 +1,280 @@
+//! JSON path extraction in SQL queries
+//!
+//! Two scalar functions read values out of JSON text, such as a JSON column
+//! (see `metadata::json`):
+//!
+//! - `json_extract(doc, path)` gives the value at `path` as JSON text, so a
+//!   string keeps its quotes and a JSON `null` reads as `'null'`
+//! - `json_extract_text(doc, path)` gives strings unquoted, other values as
+//!   JSON text, and a JSON `null` as SQL NULL
+//!
+//! A path is either a JSONPath of keys and array indexes, `$.user.tags[0]` or
+//! `$["odd key"]`, or a single key without the leading `$`. The PostgreSQL
+//! operators are rewritten to these functions before planning: `doc -> 'key'`
+//! to `json_extract`, `doc ->> 'key'` to `json_extract_text`, and an integer
+//! operand, `doc -> 0`, to the array index `$[0]`. They chain:
+//! `payload -> 'user' ->> 'name'`.
+//!
+//! A missing key or index, a malformed path, and a document that isn't valid
+//! JSON all give NULL, so `payload ->> 'status' IS NULL` holds for each.
+//! File statistics say nothing about values inside documents, so filters on
+//! paths never skip files; every candidate file is read and filtered row by
+//! row.
+
+use arrow::array::{ArrayRef, AsArray, StringArray};
+use arrow::datatypes::DataType;
+use datafusion::common::ScalarValue;
+use datafusion::logical_expr::{
+    ColumnarValue, ScalarFunctionArgs, ScalarUDF, ScalarUDFImpl, Signature, Volatility,
+};
+use datafusion::prelude::SessionContext;
+use datafusion::sql::sqlparser::ast::{
+    visit_expressions_mut, BinaryOperator, Expr, Value, ValueWithSpan,
+};
+use datafusion::sql::sqlparser::dialect::PostgreSqlDialect;
+use datafusion::sql::sqlparser::parser::Parser;
+use std::any::Any;
+use std::ops::ControlFlow;
+use std::sync::Arc;
+use tracing::debug;
+
+/// Function `->` is rewritten to
+pub const JSON_EXTRACT: &str = "json_extract";
+
+/// Function `->>` is rewritten to
+pub const JSON_EXTRACT_TEXT: &str = "json_extract_text";
+
+/// Register `json_extract` and `json_extract_text` with `ctx`
+pub fn register_json_functions(ctx: &SessionContext) {
+    for (name, text) in [(JSON_EXTRACT, false), (JSON_EXTRACT_TEXT, true)] {
+        ctx.register_udf(ScalarUDF::new_from_impl(JsonExtract {
+            name,
+            text,
+            signature: Signature::any(2, Volatility::Immutable),
+        }));
+    }
+}
+
+/// Rewrite `->` and `->>` in `sql` to the JSON functions
+///
+/// They're parsed with PostgreSQL's precedence, which binds them tighter than
+/// comparisons, so `doc ->> 'a' = 'x'` compares the extracted value. SQL that
+/// doesn't parse is returned as-is for DataFusion to report.
+pub fn rewrite_json_operators(sql: &str) -> String {
+    if !sql.contains("->") {
+        return sql.to_string();
+    }
+    let dialect = PostgreSqlDialect {};
+    let Ok(mut statements) = Parser::parse_sql(&dialect, sql) else {
+        debug!("Skipping JSON operator rewrite: SQL did not parse");
+        return sql.to_string();
+    };
+
+    let mut rewritten = 0;
+    for statement in &mut statements {
+        let _ = visit_expressions_mut(statement, |expr| {
+            let call = match expr {
+                Expr::BinaryOp { left, op, right } => {
+                    let function = match op {
+                        BinaryOperator::Arrow => JSON_EXTRACT,
+                        BinaryOperator::LongArrow => JSON_EXTRACT_TEXT,
+                        _ => return ControlFlow::<()>::Continue(()),
+                    };
+                    let path = match right.as_ref() {
+                        Expr::Value(ValueWithSpan {
+                            value: Value::Number(index, _),
+                            ..
+                        }) => format!("'$[{}]'", index),
+                        other => other.to_string(),
+                    };
+                    format!("{}({}, {})", function, left, path)
+                }
+                _ => return ControlFlow::Continue(()),
+            };
+            if let Ok(call) = Parser::new(&dialect)
+                .try_with_sql(&call)
+                .and_then(|mut parser| parser.parse_expr())
+            {
+                *expr = call;
+                rewritten += 1;
+            }
+            ControlFlow::Continue(())
+        });
+    }
+    if rewritten == 0 {
+        return sql.to_string();
+    }
+
+    debug!("Rewrote {} JSON operator(s)", rewritten);
+    statements
+        .iter()
+        .map(|s| s.to_string())
+        .collect::<Vec<_>>()
+        .join("; ")
+}
+
+/// `json_extract` or, with `text`, `json_extract_text`
+#[derive(Debug, PartialEq, Eq, Hash)]
+struct JsonExtract {
+    name: &'static str,
+    text: bool,
+    signature: Signature,
+}
+
+impl ScalarUDFImpl for JsonExtract {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn name(&self) -> &str {
+        self.name
+    }
+
+    fn signature(&self) -> &Signature {
+        &self.signature
+    }
+
+    fn return_type(&self, _arg_types: &[DataType]) -> datafusion::error::Result<DataType> {
+        Ok(DataType::Utf8)
+    }
+
+    fn invoke_with_args(
+        &self,
+        args: ScalarFunctionArgs,
+    ) -> datafusion::error::Result<ColumnarValue> {
+        let scalar = args
+            .args
+            .iter()
+            .all(|arg| matches!(arg, ColumnarValue::Scalar(_)));
+        let arrays = ColumnarValue::values_to_arrays(&args.args)?;
+
+        // Documents may be strings of any layout, or binary
+        let documents = arrow::compute::cast(&arrays[0], &DataType::Utf8)?;
+        let paths = arrow::compute::cast(&arrays[1], &DataType::Utf8)?;
+        let extracted: StringArray = documents
+            .as_string::<i32>()
+            .iter()
+            .zip(paths.as_string::<i32>().iter())
+            .map(|(document, path)| extract(document?, path?, self.text))
+            .collect();
+
+        let extracted: ArrayRef = Arc::new(extracted);
+        if scalar {
+            return Ok(ColumnarValue::Scalar(ScalarValue::try_from_array(
+                &extracted, 0,
+            )?));
+        }
+        Ok(ColumnarValue::Array(extracted))
+    }
+}
+
+/// One step of a path
+#[derive(Debug, PartialEq)]
+enum PathStep {
+    Key(String),
+    Index(usize),
+}
+
+/// Steps of `path`, None if it's malformed
+fn parse_path(path: &str) -> Option<Vec<PathStep>> {
+    let Some(mut rest) = path.strip_prefix('$') else {
+        return Some(vec![PathStep::Key(path.to_string())]);
+    };
+    let mut steps = Vec::new();
+    while !rest.is_empty() {
+        if let Some(after_dot) = rest.strip_prefix('.') {
+            let end = after_dot.find(['.', '[']).unwrap_or(after_dot.len());
+            if end == 0 {
+                return None;
+            }
+            steps.push(PathStep::Key(after_dot[..end].to_string()));
+            rest = &after_dot[end..];
+        } else if let Some(in_brackets) = rest.strip_prefix('[') {
+            let quote = in_brackets.chars().next()?;
+            if quote == '"' || quote == '\'' {
+                let end = in_brackets[1..].find(quote)? + 1;
+                steps.push(PathStep::Key(in_brackets[1..end].to_string()));
+                rest = in_brackets[end + 1..].strip_prefix(']')?;
+            } else {
+                let end = in_brackets.find(']')?;
+                steps.push(PathStep::Index(in_brackets[..end].trim().parse().ok()?));
+                rest = &in_brackets[end + 1..];
+            }
+        } else {
+            return None;
+        }
+    }
+    Some(steps)
+}
+
+/// Value at `path` in `document`, None where the module docs say NULL
+fn extract(document: &str, path: &str, text: bool) -> Option<String> {
+    let document: serde_json::Value = serde_json::from_str(document).ok()?;
+    let mut value = &document;
+    for step in parse_path(path)? {
+        value = match step {
+            PathStep::Key(key) => value.as_object()?.get(&key)?,
+            PathStep::Index(index) => value.as_array()?.get(index)?,
+        };
+    }
+    match value {
+        serde_json::Value::Null if text => None,
+        serde_json::Value::String(s) if text => Some(s.clone()),
+        other => Some(other.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DOC: &str = r#"{"status": "active", "user": {"name": "ann", "tags": ["a", "b"]},
+        "odd key": 1, "gone": null}"#;
+
+    #[test]
+    fn test_extract_paths() {
+        assert_eq!(extract(DOC, "status", true), Some("active".to_string()));
+        assert_eq!(
+            extract(DOC, "status", false),
+            Some("\"active\"".to_string())
+        );
+        assert_eq!(extract(DOC, "$.user.tags[1]", true), Some("b".to_string()));
+        assert_eq!(
+            extract(DOC, "$.user", false),
+            Some(r#"{"name":"ann","tags":["a","b"]}"#.to_string())
+        );
+        assert_eq!(extract(DOC, "$[\"odd key\"]", true), Some("1".to_string()));
+
+        // JSON null is SQL NULL only as text
+        assert_eq!(extract(DOC, "gone", false), Some("null".to_string()));
+        assert_eq!(extract(DOC, "gone", true), None);
+    }
+
+    #[test]
+    fn test_missing_and_invalid_are_null() {
+        assert_eq!(extract(DOC, "$.user.tags[5]", true), None);
+        assert_eq!(extract(DOC, "$.status.name", true), None);
+        assert_eq!(extract(DOC, "$.user..name", true), None);
+        assert_eq!(extract(DOC, "$[x]", true), None);
+        assert_eq!(extract("{not json", "status", true), None);
+        assert_eq!(extract("", "status", true), None);
+    }
+
+    #[test]
+    fn test_operators_rewritten_with_precedence() {
+        assert_eq!(
+            rewrite_json_operators(
+                "SELECT id FROM data WHERE payload ->> 'status' = 'active' AND payload -> 'user' ->> 'name' IS NULL"
+            ),
+            "SELECT id FROM data WHERE json_extract_text(payload, 'status') = 'active' \
+             AND json_extract_text(json_extract(payload, 'user'), 'name') IS NULL"
+        );
+        assert_eq!(
+            rewrite_json_operators("SELECT payload->'tags'->>0 FROM data"),
+            "SELECT json_extract_text(json_extract(payload, 'tags'), '$[0]') FROM data"
+        );
+
+        let sql = "SELECT id FROM data WHERE name = 'a->b'";
+        assert_eq!(rewrite_json_operators(sql), sql);
+    }
+}
@@ -0,0 +1,509 @@
+//! Materialized views
+//!
+//! A materialized view stores the result of a query over a table in a Delta
+//! table of its own, with its definition in `_metadata/view.json`. The table
+//! lists its views in `_metadata/materialized_views.json` so they can be
+//! refreshed after its commits.
+//!
+//! Views of the form `SELECT <grouped expressions>, <SUM and COUNT
+//! aggregates> FROM data [WHERE ...] [GROUP BY ...]` are maintained
+//! incrementally: the rows each commit added and removed, read from the Delta
+//! log, are aggregated counting +1 and -1 and added to the stored aggregates.
+//! To tell when a group empties and when a sum goes back to NULL, these views
+//! keep hidden counts in columns starting with `__fsdb_`. Any other query,
+//! such as one using `COUNT(DISTINCT)`, median, `AVG`, or `MIN` and `MAX`
+//! (which a delete can't undo), is recomputed from the whole table instead.
+
+use crate::{Error, Result};
+use arrow::array::{new_null_array, ArrayRef, Int64Array, RecordBatch};
+use arrow::datatypes::{DataType, Field, Schema, SchemaRef};
+use datafusion::sql::sqlparser::ast::{
+    Expr, FunctionArg, FunctionArgExpr, FunctionArguments, GroupByExpr, SelectItem, SetExpr,
+    Statement, TableFactor,
+};
+use datafusion::sql::sqlparser::{dialect::GenericDialect, parser::Parser};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, LazyLock};
+
+/// Prefix of the hidden columns of incrementally maintained views
+pub const HIDDEN_PREFIX: &str = "__fsdb_";
+
+/// Column of the change rows holding 1 for added rows and -1 for removed ones
+pub const SIGN_COLUMN: &str = "__fsdb_sign";
+
+/// Hidden column counting the base rows in each group
+const ROWS_COLUMN: &str = "__fsdb_rows";
+
+/// Tables the stored view and the aggregated changes are registered as when merged
+pub const VIEW_TABLE: &str = "__fsdb_view";
+pub const DELTA_TABLE: &str = "__fsdb_delta";
+
+/// A materialized view's definition, stored with it
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ViewDefinition {
+    /// The view's query, over the base table as `data`
+    pub sql: String,
+
+    /// Table the view is computed from
+    pub base_path: PathBuf,
+
+    /// Base table version the view's rows reflect
+    pub base_version: u64,
+
+    /// View table version the last refresh committed; if the view table is
+    /// at another version, something else wrote to it
+    pub view_version: i64,
+
+    /// Why every refresh recomputes the view, None if it's maintained incrementally
+    pub full_refresh_reason: Option<String>,
+}
+
+impl ViewDefinition {
+    /// Where the definition of the view at `view_path` is stored
+    pub fn path(view_path: &Path) -> PathBuf {
+        view_path.join("_metadata").join("view.json")
+    }
+
+    /// Load the definition of the view at `view_path`
+    pub fn load(view_path: &Path) -> Result<Self> {
+        let path = Self::path(view_path);
+        if !path.exists() {
+            return Err(Error::DatabaseNotFound(format!(
+                "No materialized view at {}",
+                view_path.display()
+            )));
+        }
+        let json = std::fs::read_to_string(&path)?;
+        Ok(serde_json::from_str(&json)?)
+    }
+
+    /// Save atomically, replacing the previous definition
+    pub fn save(&self, view_path: &Path) -> Result<()> {
+        write_json(&Self::path(view_path), &serde_json::to_string_pretty(self)?)
+    }
+
+    /// Whether refreshes apply just the changed rows
+    pub fn is_incremental(&self) -> bool {
+        self.full_refresh_reason.is_none()
+    }
+}
+
+/// How a refresh brought a view up to date
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ViewRefresh {
+    /// Base table version the view now reflects
+    pub base_version: u64,
+
+    /// Changed base rows applied, added and removed ones together (0 when
+    /// the view was recomputed)
+    pub rows_applied: usize,
+
+    /// Why the view was recomputed, None if the changes were applied incrementally
+    pub full_refresh_reason: Option<String>,
+}
+
+/// Where a table lists its materialized views
+fn registry_path(base_path: &Path) -> PathBuf {
+    base_path.join("_metadata").join("materialized_views.json")
+}
+
+/// Materialized views of the table at `base_path` that still exist
+pub fn registered_views(base_path: &Path) -> Result<Vec<PathBuf>> {
+    let path = registry_path(base_path);
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    let views: Vec<PathBuf> = serde_json::from_str(&std::fs::read_to_string(&path)?)?;
+    Ok(views
+        .into_iter()
+        .filter(|view| ViewDefinition::path(view).exists())
+        .collect())
+}
+
+/// Add the view at `view_path` to the views of the table at `base_path`
+pub fn register_view(base_path: &Path, view_path: &Path) -> Result<()> {
+    let mut views = registered_views(base_path)?;
+    let view_path = std::path::absolute(view_path)?;
+    if !views.contains(&view_path) {
+        views.push(view_path);
+    }
+    write_json(
+        &registry_path(base_path),
+        &serde_json::to_string_pretty(&views)?,
+    )
+}
+
+fn write_json(path: &Path, json: &str) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let tmp_path = path.with_extension("tmp");
+    std::fs::write(&tmp_path, json)?;
+    std::fs::rename(&tmp_path, path)?;
+    Ok(())
+}
+
+/// Held while the view at `view_path` is refreshed, so two refreshes in this
+/// process don't both apply the same changes
+pub fn refresh_lock(view_path: &Path) -> Arc<tokio::sync::Mutex<()>> {
+    static LOCKS: LazyLock<std::sync::Mutex<HashMap<PathBuf, Arc<tokio::sync::Mutex<()>>>>> =
+        LazyLock::new(Default::default);
+    let key = std::fs::canonicalize(view_path).unwrap_or_else(|_| view_path.to_path_buf());
+    LOCKS
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .entry(key)
+        .or_default()
+        .clone()
+}
+
+/// One column of an incrementally maintained view
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum ViewColumn {
+    /// An expression the view groups by
+    Group(String),
+    /// `COUNT(*)`
+    CountRows,
+    /// `COUNT(expr)`
+    Count(String),
+    /// `SUM(expr)`
+    Sum(String),
+}
+
+/// How an incrementally maintained view is computed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IncrementalPlan {
+    columns: Vec<ViewColumn>,
+    from: String,
+    selection: Option<String>,
+    group_by: Vec<String>,
+}
+
+/// Plan incremental maintenance of a view of `sql`
+///
+/// Fails with the reason if the view has to be recomputed on each refresh.
+pub fn plan_incremental(sql: &str) -> std::result::Result<IncrementalPlan, String> {
+    let statements = Parser::parse_sql(&GenericDialect {}, sql).map_err(|e| e.to_string())?;
+    let [Statement::Query(query)] = statements.as_slice() else {
+        return Err("the view is not a single SELECT".to_string());
+    };
+    // WITH, ORDER BY and LIMIT are part of the query but not of its body
+    if query.to_string() != query.body.to_string() {
+        return Err("the view uses WITH, ORDER BY or LIMIT".to_string());
+    }
+    let SetExpr::Select(select) = query.body.as_ref() else {
+        return Err("the view is not a plain SELECT".to_string());
+    };
+    if select.distinct.is_some()
+        || select.having.is_some()
+        || select.qualify.is_some()
+        || !select.named_window.is_empty()
+    {
+        return Err("the view uses DISTINCT, HAVING, QUALIFY or WINDOW".to_string());
+    }
+    let [from] = select.from.as_slice() else {
+        return Err("the view doesn't read a single table".to_string());
+    };
+    if !from.joins.is_empty() || !matches!(from.relation, TableFactor::Table { .. }) {
+        return Err("the view joins tables or reads a subquery".to_string());
+    }
+
+    let group_by: Vec<String> = match &select.group_by {
+        GroupByExpr::Expressions(exprs, modifiers)
+            if modifiers.is_empty()
+                && !exprs.iter().any(|e| {
+                    matches!(e, Expr::Rollup(_) | Expr::Cube(_) | Expr::GroupingSets(_))
+                }) =>
+        {
+            exprs.iter().map(ToString::to_string).collect()
+        }
+        _ => return Err("the view uses GROUP BY ALL, ROLLUP, CUBE or GROUPING SETS".to_string()),
+    };
+    let selection = select.selection.as_ref().map(ToString::to_string);
+
+    let mut columns = Vec::with_capacity(select.projection.len());
+    for item in &select.projection {
+        let expr = match item {
+            SelectItem::UnnamedExpr(expr) | SelectItem::ExprWithAlias { expr, .. } => expr,
+            _ => return Err("the view selects a wildcard".to_string()),
+        };
+        columns.push(view_column(expr, &group_by)?);
+    }
+
+    // A subquery would read the changes instead of the table
+    let mut texts: Vec<&str> = group_by.iter().map(String::as_str).collect();
+    texts.extend(selection.as_deref());
+    texts.extend(columns.iter().filter_map(|column| match column {
+        ViewColumn::Group(text) | ViewColumn::Count(text) | ViewColumn::Sum(text) => {
+            Some(text.as_str())
+        }
+        ViewColumn::CountRows => None,
+    }));
+    if texts
+        .iter()
+        .any(|text| text.to_uppercase().contains("SELECT"))
+    {
+        return Err("the view uses a subquery".to_string());
+    }
+
+    Ok(IncrementalPlan {
+        columns,
+        from: from.relation.to_string(),
+        selection,
+        group_by,
+    })
+}
+
+fn view_column(expr: &Expr, group_by: &[String]) -> std::result::Result<ViewColumn, String> {
+    let text = expr.to_string();
+    if group_by.contains(&text) {
+        return Ok(ViewColumn::Group(text));
+    }
+    let not_maintainable = || format!("{} can't be maintained incrementally", text);
+    let Expr::Function(function) = expr else {
+        return Err(not_maintainable());
+    };
+    let FunctionArguments::List(list) = &function.args else {
+        return Err(not_maintainable());
+    };
+    if list.duplicate_treatment.is_some()
+        || !list.clauses.is_empty()
+        || function.filter.is_some()
+        || function.over.is_some()
+        || !function.within_group.is_empty()
+    {
+        return Err(not_maintainable());
+    }
+    match (
+        function.name.to_string().to_lowercase().as_str(),
+        list.args.as_slice(),
+    ) {
+        ("count", [FunctionArg::Unnamed(FunctionArgExpr::Wildcard)]) => Ok(ViewColumn::CountRows),
+        ("count", [FunctionArg::Unnamed(FunctionArgExpr::Expr(arg))]) => {
+            Ok(ViewColumn::Count(arg.to_string()))
+        }
+        ("sum", [FunctionArg::Unnamed(FunctionArgExpr::Expr(arg))]) => {
+            Ok(ViewColumn::Sum(arg.to_string()))
+        }
+        _ => Err(not_maintainable()),
+    }
+}
+
+impl IncrementalPlan {
+    /// Query computing the view, hidden columns included, with its visible
+    /// columns named `names`
+    ///
+    /// Each row counts once; with `signed` the rows are changes and count
+    /// as many times as their `SIGN_COLUMN` says.
+    pub fn aggregate_sql(&self, names: &[String], signed: bool) -> String {
+        let sign = quote(SIGN_COLUMN);
+        let count = |arg: Option<&str>| match (arg, signed) {
+            (None, false) => "COUNT(*)".to_string(),
+            (None, true) => format!("SUM({})", sign),
+            (Some(arg), false) => format!("COUNT({})", arg),
+            (Some(arg), true) => {
+                format!("SUM(CASE WHEN ({}) IS NULL THEN 0 ELSE {} END)", arg, sign)
+            }
+        };
+
+        let mut items = Vec::new();
+        for (column, name) in self.columns.iter().zip(names) {
+            let expr = match column {
+                ViewColumn::Group(expr) => expr.clone(),
+                ViewColumn::CountRows => count(None),
+                ViewColumn::Count(arg) => count(Some(arg)),
+                ViewColumn::Sum(arg) if signed => format!("SUM(({}) * {})", arg, sign),
+                ViewColumn::Sum(arg) => format!("SUM({})", arg),
+            };
+            items.push(format!("{} AS {}", expr, quote(name)));
+        }
+        items.push(format!("{} AS {}", count(None), quote(ROWS_COLUMN)));
+        for (i, column) in self.columns.iter().enumerate() {
+            if let ViewColumn::Sum(arg) = column {
+                items.push(format!(
+                    "{} AS {}",
+                    count(Some(arg)),
+                    quote(&non_null_column(i))
+                ));
+            }
+        }
+
+        let mut sql = format!("SELECT {} FROM {}", items.join(", "), self.from);
+        if let Some(selection) = &self.selection {
+            sql.push_str(&format!(" WHERE {}", selection));
+        }
+        if !self.group_by.is_empty() {
+            sql.push_str(&format!(" GROUP BY {}", self.group_by.join(", ")));
+        }
+        sql
+    }
+
+    /// Query adding the aggregated changes in `DELTA_TABLE` to the view's
+    /// rows in `VIEW_TABLE`, dropping groups left without rows
+    pub fn merge_sql(&self, names: &[String]) -> String {
+        let mut items = Vec::new();
+        let mut groups = Vec::new();
+        let mut hidden = vec![quote(ROWS_COLUMN)];
+        for (i, (column, name)) in self.columns.iter().zip(names).enumerate() {
+            let name = quote(name);
+            items.push(match column {
+                ViewColumn::Group(_) => {
+                    groups.push(name.clone());
+                    name
+                }
+                ViewColumn::CountRows | ViewColumn::Count(_) => format!("SUM({0}) AS {0}", name),
+                ViewColumn::Sum(_) => {
+                    let non_null = quote(&non_null_column(i));
+                    hidden.push(non_null.clone());
+                    format!(
+                        "CASE WHEN SUM({}) > 0 THEN SUM({1}) END AS {1}",
+                        non_null, name
+                    )
+                }
+            });
+        }
+        items.extend(
+            hidden
+                .iter()
+                .map(|column| format!("SUM({0}) AS {0}", column)),
+        );
+
+        let mut sql = format!(
+            "SELECT {} FROM (SELECT * FROM {} UNION ALL SELECT * FROM {})",
+            items.join(", "),
+            VIEW_TABLE,
+            DELTA_TABLE
+        );
+        if !groups.is_empty() {
+            sql.push_str(&format!(
+                " GROUP BY {} HAVING SUM({}) > 0",
+                groups.join(", "),
+                quote(ROWS_COLUMN)
+            ));
+        }
+        sql
+    }
+}
+
+fn non_null_column(index: usize) -> String {
+    format!("{}nonnull_{}", HIDDEN_PREFIX, index)
+}
+
+fn quote(name: &str) -> String {
+    format!("\"{}\"", name.replace('"', "\"\""))
+}
+
+/// Schema of the change rows of a table with `schema`: its columns plus `SIGN_COLUMN`
+pub fn change_schema(schema: &SchemaRef) -> SchemaRef {
+    let mut fields: Vec<Field> = schema.fields().iter().map(|f| f.as_ref().clone()).collect();
+    fields.push(Field::new(SIGN_COLUMN, DataType::Int64, false));
+    Arc::new(Schema::new(fields))
+}
+
+/// `batch` as change rows with `sign`, its columns matched to `schema` by name
+///
+/// Files written before a column was added don't have it, so it reads as NULL.
+pub fn signed_batch(batch: &RecordBatch, schema: &SchemaRef, sign: i64) -> Result<RecordBatch> {
+    let mut columns: Vec<ArrayRef> = schema
+        .fields()
+        .iter()
+        .map(|field| match batch.column_by_name(field.name()) {
+            Some(column) if column.data_type() == field.data_type() => Ok(column.clone()),
+            Some(column) => Ok(arrow::compute::cast(column, field.data_type())?),
+            None => Ok(new_null_array(field.data_type(), batch.num_rows())),
+        })
+        .collect::<Result<_>>()?;
+    columns.push(Arc::new(Int64Array::from(vec![sign; batch.num_rows()])));
+    Ok(RecordBatch::try_new(change_schema(schema), columns)?)
+}
+
+/// `batch` cast, column by column, to the view table's `schema`
+pub fn conform(batch: &RecordBatch, schema: &SchemaRef) -> Result<RecordBatch> {
+    if batch.num_columns() != schema.fields().len() {
+        return Err(Error::SchemaMismatch(format!(
+            "the view's query returns {} columns but its table has {}",
+            batch.num_columns(),
+            schema.fields().len()
+        )));
+    }
+    let columns = batch
+        .columns()
+        .iter()
+        .zip(schema.fields())
+        .map(|(column, field)| Ok(arrow::compute::cast(column, field.data_type())?))
+        .collect::<Result<Vec<_>>>()?;
+    Ok(RecordBatch::try_new(schema.clone(), columns)?)
+}
+
+/// A view table's schema for a query returning `schema`
+///
+/// Every column is nullable, since the rows come from an aggregation.
+/// Column names must be valid Delta column names, so expressions have to be
+/// named with `AS`.
+pub fn view_schema(schema: &SchemaRef) -> Result<SchemaRef> {
+    let mut fields = Vec::with_capacity(schema.fields().len());
+    for field in schema.fields() {
+        if field.name().chars().any(|c| " ,;{}()\n\t=".contains(c)) {
+            return Err(Error::InvalidOperation(format!(
+                "Materialized view column '{}' needs a name without spaces or punctuation; name it with AS",
+                field.name()
+            )));
+        }
+        fields.push(field.as_ref().clone().with_nullable(true));
+    }
+    Ok(Arc::new(Schema::new(fields)))
+}
+
+/// Names of the visible columns of a view table
+pub fn visible_columns(schema: &SchemaRef) -> Vec<String> {
+    schema
+        .fields()
+        .iter()
+        .map(|f| f.name().clone())
+        .filter(|name| !name.starts_with(HIDDEN_PREFIX))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_plan_incremental() {
+        let plan = plan_incremental(
+            "SELECT region, SUM(amount) AS total, COUNT(*) AS n FROM data WHERE amount > 0 GROUP BY region",
+        )
+        .unwrap();
+        assert_eq!(
+            plan.columns,
+            vec![
+                ViewColumn::Group("region".to_string()),
+                ViewColumn::Sum("amount".to_string()),
+                ViewColumn::CountRows,
+            ]
+        );
+        let names = ["region", "total", "n"].map(String::from);
+        assert_eq!(
+            plan.aggregate_sql(&names, true),
+            "SELECT region AS \"region\", SUM((amount) * \"__fsdb_sign\") AS \"total\", \
+             SUM(\"__fsdb_sign\") AS \"n\", SUM(\"__fsdb_sign\") AS \"__fsdb_rows\", \
+             SUM(CASE WHEN (amount) IS NULL THEN 0 ELSE \"__fsdb_sign\" END) AS \"__fsdb_nonnull_1\" \
+             FROM data WHERE amount > 0 GROUP BY region"
+        );
+
+        for sql in [
+            "SELECT region, COUNT(DISTINCT customer) AS n FROM data GROUP BY region",
+            "SELECT region, median(amount) AS m FROM data GROUP BY region",
+            "SELECT region, MAX(amount) AS m FROM data GROUP BY region",
+            "SELECT region, SUM(amount) AS t FROM data GROUP BY region HAVING SUM(amount) > 1",
+            "SELECT region, SUM(amount) AS t FROM data GROUP BY region ORDER BY t LIMIT 3",
+            "SELECT region, SUM(amount) / COUNT(*) AS a FROM data GROUP BY region",
+            "SELECT region, amount FROM data",
+            "SELECT region, COUNT(*) AS n FROM data WHERE amount > (SELECT AVG(amount) FROM data) GROUP BY region",
+        ] {
+            assert!(plan_incremental(sql).is_err(), "{}", sql);
+        }
+    }
+}
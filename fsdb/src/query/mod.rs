@@ -1,8 +1,33 @@
 //! Query engine integration with DataFusion
 
+pub mod arithmetic;
+pub mod cast_view;
+pub mod collation;
+pub mod cursor;
 pub mod datafusion_provider;
 pub mod executor;
+pub mod explain;
+pub mod identifiers;
+pub mod json;
+pub mod materialized;
+pub mod percentile;
 pub mod pruning;
+pub mod result_set;
+pub mod stream;
+pub mod time_travel;
+pub mod union;
+pub mod validate;
 
+pub use cast_view::{CastMode, CastView};
+pub use collation::Collation;
+pub use cursor::{CursorConfig, CursorId, CursorInfo, CursorRegistry};
 pub use datafusion_provider::FsdbTableProvider;
 pub use executor::QueryExecutor;
+pub use explain::{OperatorStats, QueryPlan, RuntimeStats, ScanStats};
+pub use identifiers::{IdentifierCase, IdentifierResolver};
+pub use materialized::{ViewDefinition, ViewRefresh};
+pub use result_set::{ResultSet, Value};
+pub use stream::QueryStream;
+pub use time_travel::{AsOf, SnapshotRef};
+pub use union::UnionMode;
+pub use validate::validate_sql;
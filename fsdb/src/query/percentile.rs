@@ -0,0 +1,422 @@
+//! Percentile aggregates
+//!
+//! Two aggregate functions complement DataFusion's built-in `median(col)`:
+//!
+//! - `percentile(col, p)` is exact: it collects a group's values and
+//!   interpolates linearly between the two nearest ranks, as `median` does,
+//!   so `percentile(col, 0.5)` equals `median(col)`. Memory grows with the
+//!   group, so it suits small groups
+//! - `approx_percentile(col, p)` summarizes values in a t-digest, a sketch of
+//!   at most a few hundred centroids whatever the group's size. Digests of
+//!   partial groups merge, so it suits large tables; see
+//!   `DatabaseOps::approx_percentile` for the per-file digests that answer it
+//!   without scanning rows
+//!
+//! `p` is a fraction between 0 and 1. Both ignore nulls, and give NULL for a
+//! group with no non-null values.
+
+use arrow::array::{Array, ArrayRef, AsArray, Float64Array, ListArray};
+use arrow::datatypes::{DataType, Field, FieldRef, Float64Type};
+use datafusion::common::ScalarValue;
+use datafusion::error::DataFusionError;
+use datafusion::logical_expr::function::{AccumulatorArgs, StateFieldsArgs};
+use datafusion::logical_expr::utils::format_state_name;
+use datafusion::logical_expr::{
+    Accumulator, AggregateUDF, AggregateUDFImpl, Signature, Volatility,
+};
+use datafusion::prelude::SessionContext;
+use serde::{Deserialize, Serialize};
+use std::any::Any;
+use std::sync::Arc;
+
+/// Exact percentile function
+pub const PERCENTILE: &str = "percentile";
+
+/// Approximate percentile function
+pub const APPROX_PERCENTILE: &str = "approx_percentile";
+
+/// How finely a t-digest summarizes values; higher keeps more centroids
+const COMPRESSION: f64 = 100.0;
+
+/// Register `percentile` and `approx_percentile` with `ctx`
+pub fn register_percentile_functions(ctx: &SessionContext) {
+    for (name, approx) in [(PERCENTILE, false), (APPROX_PERCENTILE, true)] {
+        ctx.register_udaf(AggregateUDF::new_from_impl(Percentile {
+            name,
+            approx,
+            signature: Signature::any(2, Volatility::Immutable),
+        }));
+    }
+}
+
+/// Fail unless `p` is a fraction
+pub fn check_fraction(p: f64) -> crate::Result<()> {
+    if !(0.0..=1.0).contains(&p) {
+        return Err(crate::Error::InvalidOperation(format!(
+            "Percentile must be between 0 and 1, got {}",
+            p
+        )));
+    }
+    Ok(())
+}
+
+/// Value at fraction `p` of `values`, interpolating between ranks
+///
+/// None if there are no values.
+pub fn exact_percentile(values: &mut [f64], p: f64) -> Option<f64> {
+    if values.is_empty() {
+        return None;
+    }
+    values.sort_unstable_by(f64::total_cmp);
+    let rank = p * (values.len() - 1) as f64;
+    let (lower, upper) = (values[rank.floor() as usize], values[rank.ceil() as usize]);
+    Some(lower + (upper - lower) * rank.fract())
+}
+
+/// Merging t-digest of a set of values
+///
+/// Values are grouped into centroids, small near the ends of the
+/// distribution and larger in the middle, so extreme percentiles stay
+/// accurate. A digest of few enough values keeps each as its own centroid
+/// and gives exact percentiles.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct TDigest {
+    /// Mean and weight of each centroid, by mean
+    centroids: Vec<(f64, f64)>,
+    min: f64,
+    max: f64,
+}
+
+impl TDigest {
+    /// Digest of the non-null, non-NaN `values`
+    pub fn from_values(values: impl IntoIterator<Item = Option<f64>>) -> Self {
+        let mut points: Vec<(f64, f64)> = values
+            .into_iter()
+            .flatten()
+            .filter(|v| !v.is_nan())
+            .map(|v| (v, 1.0))
+            .collect();
+        points.sort_unstable_by(|a, b| a.0.total_cmp(&b.0));
+        Self::compress(points)
+    }
+
+    /// Digest of every value in `digests`
+    pub fn merge<'a>(digests: impl IntoIterator<Item = &'a TDigest>) -> Self {
+        let mut points = Vec::new();
+        let (mut min, mut max) = (f64::INFINITY, f64::NEG_INFINITY);
+        for digest in digests.into_iter().filter(|d| !d.is_empty()) {
+            points.extend_from_slice(&digest.centroids);
+            min = min.min(digest.min);
+            max = max.max(digest.max);
+        }
+        points.sort_unstable_by(|a, b| a.0.total_cmp(&b.0));
+        let mut merged = Self::compress(points);
+        if !merged.is_empty() {
+            merged.min = min;
+            merged.max = max;
+        }
+        merged
+    }
+
+    /// Number of values summarized
+    pub fn count(&self) -> u64 {
+        self.centroids.iter().map(|c| c.1).sum::<f64>() as u64
+    }
+
+    /// Whether no values are summarized
+    pub fn is_empty(&self) -> bool {
+        self.centroids.is_empty()
+    }
+
+    /// Estimated value at fraction `p`, None if the digest is empty
+    pub fn quantile(&self, p: f64) -> Option<f64> {
+        let (first, last) = (self.centroids.first()?, self.centroids.last()?);
+        let n: f64 = self.centroids.iter().map(|c| c.1).sum();
+        let rank = p * (n - 1.0);
+
+        // A centroid's mean sits at the rank of its middle value
+        let mut centers = Vec::with_capacity(self.centroids.len());
+        let mut cumulative = 0.0;
+        for (_, weight) in &self.centroids {
+            centers.push(cumulative + (weight - 1.0) / 2.0);
+            cumulative += weight;
+        }
+
+        // Beyond the outer centroids, interpolate toward the min and max
+        let estimate = if rank <= centers[0] {
+            if centers[0] <= 0.0 {
+                first.0
+            } else {
+                self.min + (first.0 - self.min) * rank / centers[0]
+            }
+        } else if rank >= centers[centers.len() - 1] {
+            let center = centers[centers.len() - 1];
+            if n - 1.0 <= center {
+                last.0
+            } else {
+                last.0 + (self.max - last.0) * (rank - center) / (n - 1.0 - center)
+            }
+        } else {
+            let i = centers.partition_point(|c| *c <= rank) - 1;
+            let (lower, upper) = (self.centroids[i].0, self.centroids[i + 1].0);
+            lower + (upper - lower) * (rank - centers[i]) / (centers[i + 1] - centers[i])
+        };
+        Some(estimate.clamp(self.min, self.max))
+    }
+
+    /// Group sorted `(mean, weight)` points into centroids
+    ///
+    /// A centroid grows while its weight is within the bound for its place
+    /// in the distribution, `4·n·q·(1-q) / COMPRESSION`, at least 1.
+    fn compress(points: Vec<(f64, f64)>) -> Self {
+        let (Some(min), Some(max)) = (points.first(), points.last()) else {
+            return Self::default();
+        };
+        let (min, max) = (min.0, max.0);
+        let total: f64 = points.iter().map(|p| p.1).sum();
+
+        let mut centroids: Vec<(f64, f64)> = Vec::new();
+        let mut before = 0.0;
+        for (mean, weight) in points {
+            if let Some(last) = centroids.last_mut() {
+                let combined = last.1 + weight;
+                let q = (before + combined / 2.0) / total;
+                let limit = (4.0 * total * q * (1.0 - q) / COMPRESSION).max(1.0);
+                if combined <= limit {
+                    last.0 += (mean - last.0) * weight / combined;
+                    last.1 = combined;
+                    continue;
+                }
+                before += last.1;
+            }
+            centroids.push((mean, weight));
+        }
+        Self {
+            centroids,
+            min,
+            max,
+        }
+    }
+}
+
+/// `percentile` or, with `approx`, `approx_percentile`
+#[derive(Debug, PartialEq, Eq, Hash)]
+struct Percentile {
+    name: &'static str,
+    approx: bool,
+    signature: Signature,
+}
+
+impl AggregateUDFImpl for Percentile {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn name(&self) -> &str {
+        self.name
+    }
+
+    fn signature(&self) -> &Signature {
+        &self.signature
+    }
+
+    fn return_type(&self, arg_types: &[DataType]) -> datafusion::error::Result<DataType> {
+        if !arg_types[0].is_numeric() && arg_types[0] != DataType::Null {
+            return Err(DataFusionError::Plan(format!(
+                "{} needs a numeric column, got {}",
+                self.name, arg_types[0]
+            )));
+        }
+        Ok(DataType::Float64)
+    }
+
+    fn state_fields(&self, args: StateFieldsArgs) -> datafusion::error::Result<Vec<FieldRef>> {
+        let values = if self.approx {
+            Field::new(format_state_name(args.name, "digest"), DataType::Utf8, true)
+        } else {
+            Field::new_list(
+                format_state_name(args.name, "values"),
+                Field::new_list_field(DataType::Float64, true),
+                true,
+            )
+        };
+        Ok(vec![
+            Arc::new(values),
+            Arc::new(Field::new(
+                format_state_name(args.name, "fraction"),
+                DataType::Float64,
+                true,
+            )),
+        ])
+    }
+
+    fn accumulator(
+        &self,
+        _acc_args: AccumulatorArgs,
+    ) -> datafusion::error::Result<Box<dyn Accumulator>> {
+        Ok(Box::new(PercentileAccumulator {
+            approx: self.approx,
+            values: Vec::new(),
+            digest: TDigest::default(),
+            fraction: None,
+        }))
+    }
+}
+
+/// A group's values, or its digest, and the fraction asked for
+#[derive(Debug)]
+struct PercentileAccumulator {
+    approx: bool,
+    /// Values of an exact percentile
+    values: Vec<f64>,
+    /// Digest of an approximate percentile, merged batch by batch
+    digest: TDigest,
+    fraction: Option<f64>,
+}
+
+impl PercentileAccumulator {
+    /// Take the fraction from the first non-null in `fractions`
+    fn set_fraction(&mut self, fractions: &ArrayRef) -> datafusion::error::Result<()> {
+        if self.fraction.is_some() {
+            return Ok(());
+        }
+        let fractions = arrow::compute::cast(fractions, &DataType::Float64)?;
+        if let Some(p) = fractions
+            .as_primitive::<Float64Type>()
+            .iter()
+            .flatten()
+            .next()
+        {
+            check_fraction(p).map_err(|e| DataFusionError::Execution(e.to_string()))?;
+            self.fraction = Some(p);
+        }
+        Ok(())
+    }
+}
+
+impl Accumulator for PercentileAccumulator {
+    fn update_batch(&mut self, values: &[ArrayRef]) -> datafusion::error::Result<()> {
+        self.set_fraction(&values[1])?;
+        let column = arrow::compute::cast(&values[0], &DataType::Float64)?;
+        let column = column.as_primitive::<Float64Type>();
+        if self.approx {
+            let batch = TDigest::from_values(column.iter());
+            self.digest = TDigest::merge([&self.digest, &batch]);
+        } else {
+            self.values
+                .extend(column.iter().flatten().filter(|v| !v.is_nan()));
+        }
+        Ok(())
+    }
+
+    fn merge_batch(&mut self, states: &[ArrayRef]) -> datafusion::error::Result<()> {
+        self.set_fraction(&states[1])?;
+        if self.approx {
+            let mut digests = vec![std::mem::take(&mut self.digest)];
+            for json in states[0].as_string::<i32>().iter().flatten() {
+                digests.push(
+                    serde_json::from_str(json)
+                        .map_err(|e| DataFusionError::External(Box::new(e)))?,
+                );
+            }
+            self.digest = TDigest::merge(&digests);
+        } else {
+            for values in states[0].as_list::<i32>().iter().flatten() {
+                self.values
+                    .extend(values.as_primitive::<Float64Type>().iter().flatten());
+            }
+        }
+        Ok(())
+    }
+
+    fn state(&mut self) -> datafusion::error::Result<Vec<ScalarValue>> {
+        let values = if self.approx {
+            let json = serde_json::to_string(&self.digest)
+                .map_err(|e| DataFusionError::External(Box::new(e)))?;
+            ScalarValue::Utf8(Some(json))
+        } else {
+            let values = Float64Array::from(self.values.clone());
+            ScalarValue::List(Arc::new(ListArray::new(
+                Arc::new(Field::new_list_field(DataType::Float64, true)),
+                arrow::buffer::OffsetBuffer::from_lengths([values.len()]),
+                Arc::new(values),
+                None,
+            )))
+        };
+        Ok(vec![values, ScalarValue::Float64(self.fraction)])
+    }
+
+    fn evaluate(&mut self) -> datafusion::error::Result<ScalarValue> {
+        let Some(p) = self.fraction else {
+            return Ok(ScalarValue::Float64(None));
+        };
+        let value = if self.approx {
+            self.digest.quantile(p)
+        } else {
+            exact_percentile(&mut self.values, p)
+        };
+        Ok(ScalarValue::Float64(value))
+    }
+
+    fn size(&self) -> usize {
+        std::mem::size_of_val(self)
+            + self.values.capacity() * std::mem::size_of::<f64>()
+            + self.digest.centroids.capacity() * std::mem::size_of::<(f64, f64)>()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_exact_interpolates() {
+        let mut values: Vec<f64> = (1..=100).map(f64::from).collect();
+        assert_eq!(exact_percentile(&mut values, 0.5), Some(50.5));
+        assert!((exact_percentile(&mut values, 0.95).unwrap() - 95.05).abs() < 1e-9);
+        assert_eq!(exact_percentile(&mut values, 0.0), Some(1.0));
+        assert_eq!(exact_percentile(&mut values, 1.0), Some(100.0));
+        assert_eq!(exact_percentile(&mut [], 0.5), None);
+    }
+
+    #[test]
+    fn test_small_digest_is_exact() {
+        let digest = TDigest::from_values([Some(3.0), None, Some(1.0), Some(2.0), Some(4.0)]);
+        assert_eq!(digest.count(), 4);
+        assert_eq!(digest.quantile(0.5), Some(2.5));
+        assert_eq!(digest.quantile(0.0), Some(1.0));
+        assert_eq!(digest.quantile(1.0), Some(4.0));
+        assert_eq!(TDigest::from_values([None]).quantile(0.5), None);
+    }
+
+    #[test]
+    fn test_merged_digests_stay_accurate() {
+        // Skewed values, split across digests in a scrambled order
+        let values: Vec<f64> = (0..100_000)
+            .map(|i| ((i * 7919) % 100_000) as f64)
+            .map(|v| v * v / 100_000.0)
+            .collect();
+        let digests: Vec<TDigest> = values
+            .chunks(7_000)
+            .map(|chunk| TDigest::from_values(chunk.iter().map(|v| Some(*v))))
+            .collect();
+        let merged = TDigest::merge(&digests);
+        assert_eq!(merged.count(), 100_000);
+        assert!(merged.centroids.len() < 1000);
+
+        let mut sorted = values.clone();
+        for p in [0.01, 0.25, 0.5, 0.9, 0.99] {
+            let estimate = merged.quantile(p).unwrap();
+            let low = exact_percentile(&mut sorted, (p - 0.01_f64).max(0.0)).unwrap();
+            let high = exact_percentile(&mut sorted, (p + 0.01_f64).min(1.0)).unwrap();
+            assert!(
+                (low..=high).contains(&estimate),
+                "p{}: {} not in [{}, {}]",
+                p,
+                estimate,
+                low,
+                high
+            );
+        }
+    }
+}
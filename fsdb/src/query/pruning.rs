@@ -3,7 +3,8 @@
 use tracing::debug;
 
 /// Extract simple predicates from SQL WHERE clause using DataFusion's parser
-/// Returns: Vec<(column_name, operator, value)>
+/// Returns: Vec<(column_name, operator, value)>, with a `Null` value for
+/// `IS NULL` / `IS NOT NULL`
 pub fn extract_predicates(sql: &str) -> Vec<(String, String, serde_json::Value)> {
     use datafusion::sql::sqlparser::{dialect::GenericDialect, parser::Parser};
 
@@ -11,8 +12,9 @@ pub fn extract_predicates(sql: &str) -> Vec<(String, String, serde_json::Value)>
 
     // Parse SQL using DataFusion's SQL parser
     let dialect = GenericDialect {};
+    // The SQL stays out of the log: callers may have a redaction policy for it
     let Ok(statements) = Parser::parse_sql(&dialect, sql) else {
-        debug!("Failed to parse SQL: {}", sql);
+        debug!("Failed to parse SQL, skipping predicate extraction");
         return predicates;
     };
 
@@ -39,6 +41,23 @@ fn extract_predicates_from_expr(
 ) {
     use datafusion::sql::sqlparser::ast::{BinaryOperator, Expr};
 
+    // Nullness tests carry no value; see delta_lake::data_skipping for the NULL policy
+    if let Expr::IsNull(inner) | Expr::IsNotNull(inner) = expr {
+        if let Expr::Identifier(ident) = inner.as_ref() {
+            let operator = if matches!(expr, Expr::IsNull(_)) {
+                "IS NULL"
+            } else {
+                "IS NOT NULL"
+            };
+            predicates.push((
+                ident.value.clone(),
+                operator.to_string(),
+                serde_json::Value::Null,
+            ));
+        }
+        return;
+    }
+
     if let Expr::BinaryOp { left, op, right } = expr {
         // Handle AND/OR recursively
         match op {
@@ -0,0 +1,416 @@
+//! Typed query results
+//!
+//! `DatabaseOps::query` returns Arrow record batches, which are typed but
+//! awkward to consume row by row. `ResultSet` turns them into rows of `Value`s
+//! without going through text: integers stay integers, decimals keep their
+//! exact digits and scale, and timestamps keep their unit and time zone.
+
+use crate::error::Result;
+use arrow::array::timezone::Tz;
+use arrow::array::{Array, ArrayRef, AsArray, RecordBatch};
+use arrow::datatypes::{
+    DataType, Float16Type, Float32Type, Float64Type, Int16Type, Int32Type, Int64Type, Int8Type,
+    SchemaRef, TimeUnit, TimestampMicrosecondType, TimestampMillisecondType,
+    TimestampNanosecondType, TimestampSecondType, UInt16Type, UInt32Type, UInt64Type, UInt8Type,
+};
+use chrono::TimeZone;
+use datafusion::scalar::ScalarValue;
+use std::fmt;
+
+/// One cell of a query result
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Null,
+    Bool(bool),
+    /// Any signed integer column
+    Int(i64),
+    /// Any unsigned integer column
+    UInt(u64),
+    /// Any floating-point column
+    Float(f64),
+    /// Exact decimal, `value` × 10^-`scale`
+    Decimal {
+        value: i128,
+        scale: i8,
+    },
+    String(String),
+    Bytes(Vec<u8>),
+    /// Days since 1970-01-01
+    Date(i32),
+    /// `value` `unit`s since 1970-01-01 00:00:00
+    ///
+    /// With a time zone this is an instant, UTC-based; without one it's a
+    /// wall-clock time.
+    Timestamp {
+        value: i64,
+        unit: TimeUnit,
+        timezone: Option<String>,
+    },
+}
+
+impl Value {
+    /// Read row `row` of `array`
+    ///
+    /// Types without a variant of their own, such as lists and structs, are
+    /// rendered as `Value::String` the way Arrow displays them.
+    pub fn from_array(array: &ArrayRef, row: usize) -> Result<Self> {
+        if array.is_null(row) {
+            return Ok(Value::Null);
+        }
+        let value = match array.data_type() {
+            DataType::Null => Value::Null,
+            DataType::Boolean => Value::Bool(array.as_boolean().value(row)),
+            DataType::Int8 => Value::Int(array.as_primitive::<Int8Type>().value(row).into()),
+            DataType::Int16 => Value::Int(array.as_primitive::<Int16Type>().value(row).into()),
+            DataType::Int32 => Value::Int(array.as_primitive::<Int32Type>().value(row).into()),
+            DataType::Int64 => Value::Int(array.as_primitive::<Int64Type>().value(row)),
+            DataType::UInt8 => Value::UInt(array.as_primitive::<UInt8Type>().value(row).into()),
+            DataType::UInt16 => Value::UInt(array.as_primitive::<UInt16Type>().value(row).into()),
+            DataType::UInt32 => Value::UInt(array.as_primitive::<UInt32Type>().value(row).into()),
+            DataType::UInt64 => Value::UInt(array.as_primitive::<UInt64Type>().value(row)),
+            DataType::Float16 => {
+                Value::Float(array.as_primitive::<Float16Type>().value(row).to_f64())
+            }
+            DataType::Float32 => {
+                Value::Float(array.as_primitive::<Float32Type>().value(row).into())
+            }
+            DataType::Float64 => Value::Float(array.as_primitive::<Float64Type>().value(row)),
+            DataType::Decimal128(_, scale) => Value::Decimal {
+                value: array
+                    .as_primitive::<arrow::datatypes::Decimal128Type>()
+                    .value(row),
+                scale: *scale,
+            },
+            DataType::Decimal256(_, scale) => {
+                match array
+                    .as_primitive::<arrow::datatypes::Decimal256Type>()
+                    .value(row)
+                    .to_i128()
+                {
+                    Some(value) => Value::Decimal {
+                        value,
+                        scale: *scale,
+                    },
+                    // Too wide for i128; the text keeps every digit
+                    None => Value::String(arrow::util::display::array_value_to_string(array, row)?),
+                }
+            }
+            DataType::Utf8 => Value::String(array.as_string::<i32>().value(row).to_string()),
+            DataType::LargeUtf8 => Value::String(array.as_string::<i64>().value(row).to_string()),
+            DataType::Utf8View => Value::String(array.as_string_view().value(row).to_string()),
+            DataType::Binary => Value::Bytes(array.as_binary::<i32>().value(row).to_vec()),
+            DataType::LargeBinary => Value::Bytes(array.as_binary::<i64>().value(row).to_vec()),
+            DataType::BinaryView => Value::Bytes(array.as_binary_view().value(row).to_vec()),
+            DataType::FixedSizeBinary(_) => {
+                Value::Bytes(array.as_fixed_size_binary().value(row).to_vec())
+            }
+            DataType::Date32 => Value::Date(
+                array
+                    .as_primitive::<arrow::datatypes::Date32Type>()
+                    .value(row),
+            ),
+            DataType::Date64 => {
+                let millis = array
+                    .as_primitive::<arrow::datatypes::Date64Type>()
+                    .value(row);
+                Value::Date(millis.div_euclid(86_400_000) as i32)
+            }
+            DataType::Timestamp(unit, timezone) => Value::Timestamp {
+                value: match unit {
+                    TimeUnit::Second => array.as_primitive::<TimestampSecondType>().value(row),
+                    TimeUnit::Millisecond => {
+                        array.as_primitive::<TimestampMillisecondType>().value(row)
+                    }
+                    TimeUnit::Microsecond => {
+                        array.as_primitive::<TimestampMicrosecondType>().value(row)
+                    }
+                    TimeUnit::Nanosecond => {
+                        array.as_primitive::<TimestampNanosecondType>().value(row)
+                    }
+                },
+                unit: *unit,
+                timezone: timezone.as_ref().map(|tz| tz.to_string()),
+            },
+            DataType::Dictionary(_, value_type) => {
+                let value = arrow::compute::cast(&array.slice(row, 1), value_type)?;
+                return Self::from_array(&value, 0);
+            }
+            _ => Value::String(arrow::util::display::array_value_to_string(array, row)?),
+        };
+        Ok(value)
+    }
+
+    pub fn is_null(&self) -> bool {
+        matches!(self, Value::Null)
+    }
+
+    /// The value as a DataFusion scalar, for binding to a `$n` query parameter
+    ///
+    /// Integers and floats widen to 64 bits and decimals to the widest
+    /// precision; DataFusion casts them to the column's type as it plans.
+    pub fn to_scalar(&self) -> ScalarValue {
+        match self {
+            Value::Null => ScalarValue::Null,
+            Value::Bool(v) => ScalarValue::Boolean(Some(*v)),
+            Value::Int(v) => ScalarValue::Int64(Some(*v)),
+            Value::UInt(v) => ScalarValue::UInt64(Some(*v)),
+            Value::Float(v) => ScalarValue::Float64(Some(*v)),
+            Value::Decimal { value, scale } => ScalarValue::Decimal128(Some(*value), 38, *scale),
+            Value::String(v) => ScalarValue::Utf8(Some(v.clone())),
+            Value::Bytes(v) => ScalarValue::Binary(Some(v.clone())),
+            Value::Date(days) => ScalarValue::Date32(Some(*days)),
+            Value::Timestamp {
+                value,
+                unit,
+                timezone,
+            } => {
+                let tz = timezone.as_deref().map(std::sync::Arc::from);
+                match unit {
+                    TimeUnit::Second => ScalarValue::TimestampSecond(Some(*value), tz),
+                    TimeUnit::Millisecond => ScalarValue::TimestampMillisecond(Some(*value), tz),
+                    TimeUnit::Microsecond => ScalarValue::TimestampMicrosecond(Some(*value), tz),
+                    TimeUnit::Nanosecond => ScalarValue::TimestampNanosecond(Some(*value), tz),
+                }
+            }
+        }
+    }
+}
+
+/// Text form, as it would appear in CSV
+///
+/// Null is empty, decimals print every digit of their scale, and timestamps
+/// are ISO 8601, with the offset of their time zone if they have one.
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::Null => Ok(()),
+            Value::Bool(v) => write!(f, "{}", v),
+            Value::Int(v) => write!(f, "{}", v),
+            Value::UInt(v) => write!(f, "{}", v),
+            Value::Float(v) => write!(f, "{}", v),
+            Value::Decimal { value, scale } => fmt_decimal(f, *value, *scale),
+            Value::String(v) => f.write_str(v),
+            Value::Bytes(v) => v.iter().try_for_each(|b| write!(f, "{:02x}", b)),
+            Value::Date(days) => {
+                match arrow::array::temporal_conversions::date32_to_datetime(*days) {
+                    Some(date) => write!(f, "{}", date.format("%Y-%m-%d")),
+                    None => write!(f, "{}", days),
+                }
+            }
+            Value::Timestamp {
+                value,
+                unit,
+                timezone,
+            } => fmt_timestamp(f, *value, *unit, timezone.as_deref()),
+        }
+    }
+}
+
+fn fmt_decimal(f: &mut fmt::Formatter<'_>, value: i128, scale: i8) -> fmt::Result {
+    if scale <= 0 {
+        return write!(f, "{}{}", value, "0".repeat(scale.unsigned_abs() as usize));
+    }
+    let digits = value.unsigned_abs().to_string();
+    let scale = scale as usize;
+    let digits = format!("{:0>width$}", digits, width = scale + 1);
+    let (whole, fraction) = digits.split_at(digits.len() - scale);
+    let sign = if value < 0 { "-" } else { "" };
+    write!(f, "{}{}.{}", sign, whole, fraction)
+}
+
+fn fmt_timestamp(
+    f: &mut fmt::Formatter<'_>,
+    value: i64,
+    unit: TimeUnit,
+    timezone: Option<&str>,
+) -> fmt::Result {
+    use arrow::array::temporal_conversions::{
+        timestamp_ms_to_datetime, timestamp_ns_to_datetime, timestamp_s_to_datetime,
+        timestamp_us_to_datetime,
+    };
+
+    let naive = match unit {
+        TimeUnit::Second => timestamp_s_to_datetime(value),
+        TimeUnit::Millisecond => timestamp_ms_to_datetime(value),
+        TimeUnit::Microsecond => timestamp_us_to_datetime(value),
+        TimeUnit::Nanosecond => timestamp_ns_to_datetime(value),
+    };
+    let Some(naive) = naive else {
+        return write!(f, "{}", value);
+    };
+    match timezone {
+        None => write!(f, "{}", naive.format("%Y-%m-%dT%H:%M:%S%.f")),
+        Some(timezone) => match timezone.parse::<Tz>() {
+            Ok(tz) => write!(
+                f,
+                "{}",
+                tz.from_utc_datetime(&naive)
+                    .format("%Y-%m-%dT%H:%M:%S%.f%:z")
+            ),
+            Err(_) => write!(f, "{}Z[{}]", naive.format("%Y-%m-%dT%H:%M:%S%.f"), timezone),
+        },
+    }
+}
+
+/// Rows of a query result with the schema they follow
+#[derive(Debug, Clone, PartialEq)]
+pub struct ResultSet {
+    schema: SchemaRef,
+    rows: Vec<Vec<Value>>,
+}
+
+impl ResultSet {
+    /// Convert record batches whose columns follow `schema`, by position
+    pub fn from_batches(schema: SchemaRef, batches: &[RecordBatch]) -> Result<Self> {
+        let mut rows = Vec::with_capacity(batches.iter().map(RecordBatch::num_rows).sum());
+        for batch in batches {
+            for row in 0..batch.num_rows() {
+                rows.push(
+                    batch
+                        .columns()
+                        .iter()
+                        .map(|column| Value::from_array(column, row))
+                        .collect::<Result<Vec<_>>>()?,
+                );
+            }
+        }
+        Ok(Self { schema, rows })
+    }
+
+    /// Schema of the result, known even when it has no rows
+    pub fn schema(&self) -> &SchemaRef {
+        &self.schema
+    }
+
+    /// Column names, in order
+    pub fn columns(&self) -> Vec<&str> {
+        self.schema
+            .fields()
+            .iter()
+            .map(|f| f.name().as_str())
+            .collect()
+    }
+
+    pub fn rows(&self) -> &[Vec<Value>] {
+        &self.rows
+    }
+
+    pub fn len(&self) -> usize {
+        self.rows.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rows.is_empty()
+    }
+
+    /// Value of `column` in row `row`
+    pub fn get(&self, row: usize, column: &str) -> Option<&Value> {
+        let index = self.schema.index_of(column).ok()?;
+        self.rows.get(row)?.get(index)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow::array::{
+        BinaryArray, Decimal128Array, Int32Array, StringArray, TimestampMicrosecondArray,
+    };
+    use arrow::datatypes::{Field, Schema};
+    use std::sync::Arc;
+
+    #[test]
+    fn test_values_keep_their_types() {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("id", DataType::Int32, true),
+            Field::new("price", DataType::Decimal128(20, 4), true),
+            Field::new("name", DataType::Utf8, true),
+            Field::new("blob", DataType::Binary, true),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![
+                Arc::new(Int32Array::from(vec![Some(1), None])),
+                Arc::new(
+                    Decimal128Array::from(vec![Some(12345678901234567890), Some(-5)])
+                        .with_precision_and_scale(20, 4)
+                        .unwrap(),
+                ),
+                Arc::new(StringArray::from(vec![Some("a"), None])),
+                Arc::new(BinaryArray::from(vec![Some(&[0u8, 255][..]), None])),
+            ],
+        )
+        .unwrap();
+
+        let result = ResultSet::from_batches(schema, &[batch]).unwrap();
+        assert_eq!(result.len(), 2);
+        assert_eq!(result.columns(), vec!["id", "price", "name", "blob"]);
+        assert_eq!(result.get(0, "id"), Some(&Value::Int(1)));
+        assert_eq!(result.get(1, "id"), Some(&Value::Null));
+        assert_eq!(
+            result.get(0, "price"),
+            Some(&Value::Decimal {
+                value: 12345678901234567890,
+                scale: 4
+            })
+        );
+        assert_eq!(
+            result.get(0, "price").unwrap().to_string(),
+            "1234567890123456.7890"
+        );
+        assert_eq!(result.get(1, "price").unwrap().to_string(), "-0.0005");
+        assert_eq!(result.get(0, "blob"), Some(&Value::Bytes(vec![0, 255])));
+        assert!(result.get(1, "name").unwrap().is_null());
+        assert_eq!(result.get(0, "missing"), None);
+    }
+
+    #[test]
+    fn test_timestamps_keep_time_zone() {
+        let array: ArrayRef = Arc::new(
+            TimestampMicrosecondArray::from(vec![1_700_000_000_123_456]).with_timezone("+05:30"),
+        );
+        let value = Value::from_array(&array, 0).unwrap();
+        assert_eq!(
+            value,
+            Value::Timestamp {
+                value: 1_700_000_000_123_456,
+                unit: TimeUnit::Microsecond,
+                timezone: Some("+05:30".to_string()),
+            }
+        );
+        assert_eq!(value.to_string(), "2023-11-15T03:43:20.123456+05:30");
+
+        let naive: ArrayRef = Arc::new(TimestampMicrosecondArray::from(vec![0]));
+        assert_eq!(
+            Value::from_array(&naive, 0).unwrap().to_string(),
+            "1970-01-01T00:00:00"
+        );
+    }
+
+    #[test]
+    fn test_to_scalar() {
+        assert_eq!(Value::Null.to_scalar(), ScalarValue::Null);
+        assert_eq!(Value::Int(7).to_scalar(), ScalarValue::Int64(Some(7)));
+        assert_eq!(
+            Value::String("acme".to_string()).to_scalar(),
+            ScalarValue::Utf8(Some("acme".to_string()))
+        );
+        assert_eq!(
+            Value::Decimal {
+                value: 1250,
+                scale: 2
+            }
+            .to_scalar(),
+            ScalarValue::Decimal128(Some(1250), 38, 2)
+        );
+        assert_eq!(
+            Value::Timestamp {
+                value: 5,
+                unit: TimeUnit::Millisecond,
+                timezone: Some("UTC".to_string()),
+            }
+            .to_scalar(),
+            ScalarValue::TimestampMillisecond(Some(5), Some("UTC".into()))
+        );
+    }
+}
@@ -0,0 +1,173 @@
+//! Streaming query results
+//!
+//! `DatabaseOps::query_stream` hands back a query's result as a
+//! `QueryStream` of Arrow batches, produced as the stream is polled rather
+//! than collected first, so a result larger than memory can be processed a
+//! batch at a time. Callers that can't poll a stream, such as the Python
+//! bindings, read it in pages of a set number of rows with `next_rows`.
+//!
+//! The stream reads the table as it was when the query was planned. Unlike
+//! a cursor it belongs to the caller rather than to the handle: it never
+//! expires, and dropping it stops the query.
+
+use crate::Result;
+use arrow::array::RecordBatch;
+use arrow::datatypes::SchemaRef;
+use datafusion::physical_plan::SendableRecordBatchStream;
+use futures::{Stream, StreamExt};
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+/// Result of a query, read batch by batch or page by page
+pub struct QueryStream {
+    schema: SchemaRef,
+    /// Remaining query output
+    stream: SendableRecordBatchStream,
+    /// Rows read from the stream but not yet returned
+    pending: Option<RecordBatch>,
+    /// Stream has been fully drained
+    exhausted: bool,
+}
+
+impl QueryStream {
+    pub fn new(stream: SendableRecordBatchStream) -> Self {
+        Self {
+            schema: stream.schema(),
+            stream,
+            pending: None,
+            exhausted: false,
+        }
+    }
+
+    /// Schema of the result, known before any row is read
+    pub fn schema(&self) -> SchemaRef {
+        self.schema.clone()
+    }
+
+    /// Whether every row has been returned
+    pub fn is_exhausted(&self) -> bool {
+        self.exhausted && self.pending.is_none()
+    }
+
+    /// Pull the next non-empty batch into `pending` so `has_more` is exact
+    async fn fill_pending(&mut self) -> Result<()> {
+        while self.pending.is_none() && !self.exhausted {
+            match self.stream.next().await {
+                Some(batch) => {
+                    let batch = batch?;
+                    if batch.num_rows() > 0 {
+                        self.pending = Some(batch);
+                    }
+                }
+                None => self.exhausted = true,
+            }
+        }
+        Ok(())
+    }
+
+    /// Read up to `n` rows, returning them and whether more remain
+    pub async fn next_rows(&mut self, n: usize) -> Result<(Vec<RecordBatch>, bool)> {
+        let mut rows = Vec::new();
+        let mut remaining = n;
+        while remaining > 0 {
+            self.fill_pending().await?;
+            let Some(batch) = self.pending.take() else {
+                break;
+            };
+
+            if batch.num_rows() <= remaining {
+                remaining -= batch.num_rows();
+                rows.push(batch);
+            } else {
+                rows.push(batch.slice(0, remaining));
+                self.pending = Some(batch.slice(remaining, batch.num_rows() - remaining));
+                remaining = 0;
+            }
+        }
+
+        self.fill_pending().await?;
+        Ok((rows, self.pending.is_some()))
+    }
+}
+
+impl Stream for QueryStream {
+    type Item = Result<RecordBatch>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        if let Some(batch) = self.pending.take() {
+            return Poll::Ready(Some(Ok(batch)));
+        }
+        if self.exhausted {
+            return Poll::Ready(None);
+        }
+        match self.stream.poll_next_unpin(cx) {
+            Poll::Ready(None) => {
+                self.exhausted = true;
+                Poll::Ready(None)
+            }
+            Poll::Ready(Some(batch)) => Poll::Ready(Some(batch.map_err(Into::into))),
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow::array::{ArrayRef, Int32Array};
+    use arrow::datatypes::{DataType, Field, Schema};
+    use datafusion::physical_plan::stream::RecordBatchStreamAdapter;
+    use futures::TryStreamExt;
+    use std::sync::Arc;
+
+    /// A stream of batches of `sizes` rows, numbered from 0
+    fn numbers(sizes: &[i32]) -> QueryStream {
+        let schema = Arc::new(Schema::new(vec![Field::new("n", DataType::Int32, false)]));
+        let mut start = 0;
+        let batches: Vec<_> = sizes
+            .iter()
+            .map(|&size| {
+                let column: ArrayRef = Arc::new(Int32Array::from_iter_values(start..start + size));
+                start += size;
+                Ok(RecordBatch::try_new(schema.clone(), vec![column]).unwrap())
+            })
+            .collect();
+        QueryStream::new(Box::pin(RecordBatchStreamAdapter::new(
+            schema,
+            futures::stream::iter(batches),
+        )))
+    }
+
+    fn count(batches: &[RecordBatch]) -> usize {
+        batches.iter().map(|b| b.num_rows()).sum()
+    }
+
+    #[tokio::test]
+    async fn test_pages_span_batches() {
+        let mut stream = numbers(&[3, 0, 4]);
+        assert_eq!(stream.schema().field(0).name(), "n");
+
+        let (rows, more) = stream.next_rows(5).await.unwrap();
+        assert_eq!(count(&rows), 5);
+        assert!(more);
+        let (rows, more) = stream.next_rows(5).await.unwrap();
+        assert_eq!(count(&rows), 2);
+        assert!(!more);
+        assert!(stream.is_exhausted());
+        let (rows, more) = stream.next_rows(5).await.unwrap();
+        assert!(rows.is_empty() && !more);
+    }
+
+    #[tokio::test]
+    async fn test_stream_resumes_after_a_page() {
+        let mut stream = numbers(&[3, 4]);
+        stream.next_rows(2).await.unwrap();
+
+        // The rest of the split batch comes first
+        let rest: Vec<RecordBatch> = stream.try_collect().await.unwrap();
+        assert_eq!(
+            rest.iter().map(|b| b.num_rows()).collect::<Vec<_>>(),
+            vec![1, 4]
+        );
+    }
+}
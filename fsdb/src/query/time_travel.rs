@@ -0,0 +1,329 @@
+//! Time travel in SQL: `VERSION AS OF` and `TIMESTAMP AS OF`
+//!
+//! A table reference can name an earlier snapshot, as in Delta on Spark:
+//!
+//! ```sql
+//! SELECT * FROM data VERSION AS OF 12
+//! SELECT * FROM data TIMESTAMP AS OF '2024-01-01' d JOIN orders o ON ...
+//! ```
+//!
+//! DataFusion can't parse either clause, so they're rewritten before
+//! planning. Each reference becomes a quoted name for its snapshot, such as
+//! `"data@v12"`, aliased as the table was written when it has no alias of
+//! its own, so `data.id` still resolves. The snapshots are then registered
+//! under those names alongside the current tables, which lets one query
+//! compare versions: `FROM data VERSION AS OF 1 a JOIN data b ON a.id = b.id`.
+//!
+//! Timestamps are taken as UTC unless they carry an offset, and name the
+//! latest version committed at or before them.
+
+use crate::{Error, Result};
+use chrono::{DateTime, NaiveDate, NaiveDateTime, Utc};
+use datafusion::sql::sqlparser::dialect::GenericDialect;
+use datafusion::sql::sqlparser::keywords::{Keyword, RESERVED_FOR_TABLE_ALIAS};
+use datafusion::sql::sqlparser::tokenizer::{Token, Tokenizer, Word};
+
+/// Which snapshot of a table to read
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AsOf {
+    /// The table as committed at this version
+    Version(i64),
+
+    /// The table's latest version committed at or before this time
+    Timestamp(DateTime<Utc>),
+}
+
+/// A table snapshot a rewritten query reads, and the name it reads it by
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SnapshotRef {
+    /// The table: `data` or a named table
+    pub table: String,
+
+    /// Name the query uses for the snapshot
+    pub name: String,
+
+    pub as_of: AsOf,
+}
+
+/// Rewrite the `VERSION AS OF` and `TIMESTAMP AS OF` clauses of `sql`
+///
+/// Returns the SQL with each clause replaced by its snapshot's name, and
+/// the snapshots to register, once each. SQL without either clause is
+/// returned as it is.
+pub fn rewrite_time_travel(sql: &str) -> Result<(String, Vec<SnapshotRef>)> {
+    // SQL that doesn't tokenize is left for DataFusion to report
+    let Ok(tokens) = Tokenizer::new(&GenericDialect {}, sql).tokenize() else {
+        return Ok((sql.to_string(), Vec::new()));
+    };
+
+    let mut snapshots: Vec<SnapshotRef> = Vec::new();
+    let mut rewritten = String::with_capacity(sql.len());
+    let mut i = 0;
+    while i < tokens.len() {
+        let Some((snapshot, end)) = snapshot_at(&tokens, i)? else {
+            rewritten.push_str(&tokens[i].to_string());
+            i += 1;
+            continue;
+        };
+        rewritten.push_str(&quoted(&snapshot.name));
+        if !has_alias(&tokens[end..]) {
+            rewritten.push_str(" AS ");
+            rewritten.push_str(&tokens[i].to_string());
+        }
+        if !snapshots.contains(&snapshot) {
+            snapshots.push(snapshot);
+        }
+        i = end;
+    }
+    if snapshots.is_empty() {
+        return Ok((sql.to_string(), snapshots));
+    }
+    Ok((rewritten, snapshots))
+}
+
+/// Parse a timestamp given to `TIMESTAMP AS OF`
+///
+/// Accepts RFC 3339 (`2024-01-01T12:00:00+02:00`), a date and time without
+/// an offset (`2024-01-01 12:00:00`, UTC) or just a date (midnight UTC).
+pub fn parse_timestamp(value: &str) -> Result<DateTime<Utc>> {
+    let value = value.trim();
+    if let Ok(timestamp) = DateTime::parse_from_rfc3339(value) {
+        return Ok(timestamp.with_timezone(&Utc));
+    }
+    for format in ["%Y-%m-%d %H:%M:%S%.f", "%Y-%m-%dT%H:%M:%S%.f"] {
+        if let Ok(timestamp) = NaiveDateTime::parse_from_str(value, format) {
+            return Ok(timestamp.and_utc());
+        }
+    }
+    NaiveDate::parse_from_str(value, "%Y-%m-%d")
+        .ok()
+        .and_then(|date| date.and_hms_opt(0, 0, 0))
+        .map(|timestamp| timestamp.and_utc())
+        .ok_or_else(|| invalid(format!("Invalid timestamp '{}' in TIMESTAMP AS OF", value)))
+}
+
+/// The snapshot referenced by the table name at `tokens[i]`, if it's followed
+/// by an AS OF clause, and the index just past the clause
+fn snapshot_at(tokens: &[Token], i: usize) -> Result<Option<(SnapshotRef, usize)>> {
+    let Token::Word(table) = &tokens[i] else {
+        return Ok(None);
+    };
+    if !follows_table_keyword(&tokens[..i]) {
+        return Ok(None);
+    }
+    let mut rest = Significant::new(tokens, i + 1);
+    let Some(kind) = rest.next_keyword(&["VERSION", "TIMESTAMP"]) else {
+        return Ok(None);
+    };
+    if rest.next_keyword(&["AS"]).is_none() || rest.next_keyword(&["OF"]).is_none() {
+        return Ok(None);
+    }
+
+    let table_name = if table.quote_style.is_some() {
+        table.value.clone()
+    } else {
+        table.value.to_lowercase()
+    };
+    let (as_of, name) = match (kind, rest.next_token()) {
+        ("VERSION", Some(Token::Number(n, _))) => {
+            let version: i64 = n
+                .parse()
+                .map_err(|_| invalid(format!("Invalid version '{}' in VERSION AS OF", n)))?;
+            (
+                AsOf::Version(version),
+                format!("{}@v{}", table_name, version),
+            )
+        }
+        ("TIMESTAMP", Some(Token::SingleQuotedString(s))) => {
+            let timestamp = parse_timestamp(s)?;
+            (
+                AsOf::Timestamp(timestamp),
+                format!("{}@t{}", table_name, timestamp.timestamp_millis()),
+            )
+        }
+        ("VERSION", _) => {
+            return Err(invalid(format!(
+                "VERSION AS OF on {} needs a version number",
+                table_name
+            )))
+        }
+        _ => {
+            return Err(invalid(format!(
+                "TIMESTAMP AS OF on {} needs a quoted timestamp",
+                table_name
+            )))
+        }
+    };
+    Ok(Some((
+        SnapshotRef {
+            table: table_name,
+            name,
+            as_of,
+        },
+        rest.position(),
+    )))
+}
+
+/// Whether the last significant token of `before` introduces a table reference
+fn follows_table_keyword(before: &[Token]) -> bool {
+    match before
+        .iter()
+        .rev()
+        .find(|t| !matches!(t, Token::Whitespace(_)))
+    {
+        Some(Token::Word(word)) => {
+            word.quote_style.is_none() && matches!(word.keyword, Keyword::FROM | Keyword::JOIN)
+        }
+        Some(Token::Comma) => true,
+        _ => false,
+    }
+}
+
+/// Whether the tokens after a table reference start with its alias
+fn has_alias(after: &[Token]) -> bool {
+    match after.iter().find(|t| !matches!(t, Token::Whitespace(_))) {
+        Some(Token::Word(Word {
+            quote_style: Some(_),
+            ..
+        })) => true,
+        Some(Token::Word(word)) => {
+            word.keyword == Keyword::AS || !RESERVED_FOR_TABLE_ALIAS.contains(&word.keyword)
+        }
+        _ => false,
+    }
+}
+
+fn quoted(name: &str) -> String {
+    format!("\"{}\"", name.replace('"', "\"\""))
+}
+
+fn invalid(message: String) -> Error {
+    Error::InvalidSql {
+        message,
+        position: None,
+    }
+}
+
+/// Walks tokens skipping whitespace and comments
+struct Significant<'a> {
+    tokens: &'a [Token],
+    position: usize,
+}
+
+impl<'a> Significant<'a> {
+    fn new(tokens: &'a [Token], position: usize) -> Self {
+        Self { tokens, position }
+    }
+
+    fn next_token(&mut self) -> Option<&'a Token> {
+        while let Some(token) = self.tokens.get(self.position) {
+            self.position += 1;
+            if !matches!(token, Token::Whitespace(_)) {
+                return Some(token);
+            }
+        }
+        None
+    }
+
+    /// The next token if it's one of the unquoted `keywords`, consuming it
+    fn next_keyword(&mut self, keywords: &[&'static str]) -> Option<&'static str> {
+        let start = self.position;
+        if let Some(Token::Word(word)) = self.next_token() {
+            if word.quote_style.is_none() {
+                if let Some(keyword) = keywords
+                    .iter()
+                    .copied()
+                    .find(|k| word.value.eq_ignore_ascii_case(k))
+                {
+                    return Some(keyword);
+                }
+            }
+        }
+        self.position = start;
+        None
+    }
+
+    fn position(&self) -> usize {
+        self.position
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_version_as_of() {
+        let (sql, snapshots) =
+            rewrite_time_travel("SELECT * FROM data VERSION AS OF 3 WHERE id > 1").unwrap();
+        assert_eq!(sql, "SELECT * FROM \"data@v3\" AS data WHERE id > 1");
+        assert_eq!(
+            snapshots,
+            vec![SnapshotRef {
+                table: "data".to_string(),
+                name: "data@v3".to_string(),
+                as_of: AsOf::Version(3),
+            }]
+        );
+    }
+
+    #[test]
+    fn test_aliases_are_kept() {
+        let (sql, snapshots) = rewrite_time_travel(
+            "SELECT a.id FROM data VERSION AS OF 1 a JOIN Orders timestamp as of '2024-01-01' AS o ON a.id = o.id",
+        )
+        .unwrap();
+        assert_eq!(
+            sql,
+            "SELECT a.id FROM \"data@v1\" a JOIN \"orders@t1704067200000\" AS o ON a.id = o.id"
+        );
+        assert_eq!(snapshots.len(), 2);
+        assert_eq!(snapshots[1].table, "orders");
+    }
+
+    #[test]
+    fn test_same_snapshot_registered_once() {
+        let (_, snapshots) = rewrite_time_travel(
+            "SELECT * FROM data VERSION AS OF 2 a, data VERSION AS OF 2 b, data c",
+        )
+        .unwrap();
+        assert_eq!(snapshots.len(), 1);
+    }
+
+    #[test]
+    fn test_other_sql_is_untouched() {
+        for sql in [
+            "SELECT * FROM data",
+            "SELECT CAST(x AS TIMESTAMP) AS of FROM data",
+            "SELECT 'FROM data VERSION AS OF 1' FROM data",
+            "SELECT version FROM data",
+        ] {
+            let (rewritten, snapshots) = rewrite_time_travel(sql).unwrap();
+            assert_eq!(rewritten, sql);
+            assert!(snapshots.is_empty());
+        }
+    }
+
+    #[test]
+    fn test_invalid_clauses() {
+        for sql in [
+            "SELECT * FROM data VERSION AS OF 'x'",
+            "SELECT * FROM data VERSION AS OF -1",
+            "SELECT * FROM data TIMESTAMP AS OF 5",
+            "SELECT * FROM data TIMESTAMP AS OF 'yesterday'",
+        ] {
+            assert!(rewrite_time_travel(sql).is_err(), "{}", sql);
+        }
+    }
+
+    #[test]
+    fn test_parse_timestamp() {
+        let midnight = parse_timestamp("2024-01-01").unwrap();
+        assert_eq!(midnight.to_rfc3339(), "2024-01-01T00:00:00+00:00");
+        assert_eq!(
+            parse_timestamp("2024-01-01 02:00:00").unwrap(),
+            parse_timestamp("2024-01-01T04:00:00+02:00").unwrap()
+        );
+        assert!(parse_timestamp("2024-13-01").is_err());
+    }
+}
@@ -0,0 +1,142 @@
+//! UNION and UNION ALL of two planned queries
+//!
+//! SQL `UNION` between queries on the same table is planned by DataFusion.
+//! This module combines queries planned separately, such as one on an
+//! archive table and one on the live table. Branches must have the same
+//! number of columns, and each column a common type: identical types, NULL
+//! with anything, or types DataFusion coerces for comparison, such as Int32
+//! and Float64 (Float64) or Date32 and a timestamp (the timestamp). Text and
+//! binary columns never combine with other kinds of columns. The result uses
+//! the left branch's column names.
+//!
+//! Both branches are streamed. `UNION` deduplicates with a hash aggregate,
+//! which spills under the session's memory pool like any other GROUP BY.
+
+use crate::{Error, Result};
+use arrow::datatypes::DataType;
+use datafusion::common::Column;
+use datafusion::dataframe::DataFrame;
+use datafusion::logical_expr::type_coercion::binary::comparison_coercion;
+use datafusion::prelude::{cast, Expr};
+
+/// Whether a union keeps duplicate rows
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum UnionMode {
+    /// `UNION ALL`: every row of both branches
+    All,
+
+    /// `UNION`: each distinct row once
+    #[default]
+    Distinct,
+}
+
+/// Type a column of `left` and a column of `right` are combined as
+pub fn union_type(left: &DataType, right: &DataType) -> Option<DataType> {
+    if left == right {
+        return Some(left.clone());
+    }
+    match (left, right) {
+        (DataType::Null, other) | (other, DataType::Null) => return Some(other.clone()),
+        _ => {}
+    }
+    // Numbers and dates never silently become text, or bytes
+    if is_text(left) != is_text(right) || is_binary(left) != is_binary(right) {
+        return None;
+    }
+    comparison_coercion(left, right)
+}
+
+fn is_text(data_type: &DataType) -> bool {
+    matches!(
+        data_type,
+        DataType::Utf8 | DataType::LargeUtf8 | DataType::Utf8View
+    )
+}
+
+fn is_binary(data_type: &DataType) -> bool {
+    matches!(
+        data_type,
+        DataType::Binary | DataType::LargeBinary | DataType::BinaryView
+    )
+}
+
+/// Combine `left` and `right`, casting each column to its union type
+///
+/// Fails with `Error::UnionMismatch` if the column counts differ or a column
+/// has no common type. The result runs in `left`'s session.
+pub fn union(left: DataFrame, right: DataFrame, mode: UnionMode) -> Result<DataFrame> {
+    let (left_schema, right_schema) = (left.schema().clone(), right.schema().clone());
+    if left_schema.fields().len() != right_schema.fields().len() {
+        return Err(Error::UnionMismatch(format!(
+            "left has {} columns, right has {}",
+            left_schema.fields().len(),
+            right_schema.fields().len()
+        )));
+    }
+
+    let mut left_exprs = Vec::new();
+    let mut right_exprs = Vec::new();
+    for ((left_qualifier, left_field), (right_qualifier, right_field)) in
+        left_schema.iter().zip(right_schema.iter())
+    {
+        let data_type =
+            union_type(left_field.data_type(), right_field.data_type()).ok_or_else(|| {
+                Error::UnionMismatch(format!(
+                    "column '{}' is {} on the left and {} on the right",
+                    left_field.name(),
+                    left_field.data_type(),
+                    right_field.data_type()
+                ))
+            })?;
+        let column = |qualifier: Option<&datafusion::common::TableReference>, name: &str| {
+            Expr::Column(Column::new(qualifier.cloned(), name))
+        };
+        left_exprs.push(
+            cast(column(left_qualifier, left_field.name()), data_type.clone())
+                .alias(left_field.name()),
+        );
+        right_exprs.push(
+            cast(column(right_qualifier, right_field.name()), data_type).alias(left_field.name()),
+        );
+    }
+
+    let left = left.select(left_exprs)?;
+    let right = right.select(right_exprs)?;
+    Ok(match mode {
+        UnionMode::All => left.union(right)?,
+        UnionMode::Distinct => left.union_distinct(right)?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow::datatypes::TimeUnit;
+
+    #[test]
+    fn test_union_types() {
+        assert_eq!(
+            union_type(&DataType::Int32, &DataType::Float64),
+            Some(DataType::Float64)
+        );
+        assert_eq!(
+            union_type(&DataType::Int32, &DataType::Int64),
+            Some(DataType::Int64)
+        );
+        assert_eq!(
+            union_type(&DataType::Null, &DataType::Utf8),
+            Some(DataType::Utf8)
+        );
+        assert_eq!(
+            union_type(
+                &DataType::Date32,
+                &DataType::Timestamp(TimeUnit::Microsecond, None)
+            ),
+            Some(DataType::Timestamp(TimeUnit::Microsecond, None))
+        );
+
+        assert_eq!(union_type(&DataType::Int32, &DataType::Utf8), None);
+        assert_eq!(union_type(&DataType::Binary, &DataType::Utf8), None);
+        assert_eq!(union_type(&DataType::Boolean, &DataType::Float64), None);
+    }
+}
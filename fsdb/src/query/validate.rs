@@ -0,0 +1,172 @@
+//! Parse-only SQL validation
+//!
+//! Validation parses a statement and plans it against the tables registered
+//! in a context, which checks that every table and column exists, but never
+//! executes it: even DDL and DML only get a logical plan. The first error is
+//! reported as `Error::InvalidSql` with its 1-based line and column in the
+//! SQL as written, when the parser or planner can place it.
+
+use crate::query::identifiers::{IdentifierCase, IdentifierResolver};
+use crate::{Error, Result};
+use arrow::datatypes::SchemaRef;
+use datafusion::error::DataFusionError;
+use datafusion::prelude::SessionContext;
+use datafusion::sql::parser::DFParser;
+use datafusion::sql::sqlparser::tokenizer::Span;
+use std::sync::Arc;
+
+/// Check that `sql` parses and plans against `ctx`, returning its output schema
+pub async fn validate_sql(
+    ctx: &SessionContext,
+    sql: &str,
+    case: IdentifierCase,
+) -> Result<SchemaRef> {
+    let statements = DFParser::parse_sql(sql).map_err(syntax_error)?;
+    if statements.len() != 1 {
+        return Err(Error::InvalidSql {
+            message: format!("Expected one statement, found {}", statements.len()),
+            position: None,
+        });
+    }
+
+    let json_rewritten = super::json::rewrite_json_operators(sql);
+    let resolved = IdentifierResolver::from_context(ctx, case)
+        .await?
+        .resolve(&json_rewritten)?;
+
+    // Spans let the planner say where an unknown name is
+    let mut state = ctx.state();
+    state.config_mut().options_mut().sql_parser.collect_spans = true;
+    let plan = state
+        .create_logical_plan(&resolved)
+        .await
+        .map_err(|e| planning_error(e, sql, &resolved))?;
+    Ok(Arc::new(plan.schema().as_arrow().clone()))
+}
+
+/// Parser errors end in "at Line: L, Column: C"
+fn syntax_error(e: DataFusionError) -> Error {
+    let message = match &e {
+        DataFusionError::SQL(err, _) => err.to_string(),
+        _ => e.strip_backtrace(),
+    };
+    let message = message
+        .strip_prefix("sql parser error: ")
+        .unwrap_or(&message)
+        .to_string();
+
+    if let Some(at) = message.rfind(" at Line: ") {
+        let position = message[at + " at Line: ".len()..]
+            .split_once(", Column: ")
+            .and_then(|(line, column)| Some((line.parse().ok()?, column.parse().ok()?)));
+        if position.is_some() {
+            return Error::InvalidSql {
+                message: message[..at].to_string(),
+                position,
+            };
+        }
+    }
+    Error::InvalidSql {
+        message,
+        position: None,
+    }
+}
+
+fn planning_error(e: DataFusionError, sql: &str, resolved: &str) -> Error {
+    let position = e
+        .diagnostic()
+        .and_then(|d| d.span)
+        .and_then(|span| original_position(sql, resolved, span));
+    Error::InvalidSql {
+        message: e.strip_backtrace(),
+        position,
+    }
+}
+
+/// Map `span` in the resolved SQL back to `sql`
+///
+/// Resolution may rewrite the statement on one line, so the spanned text is
+/// found in `sql` by its occurrence count, ignoring case.
+fn original_position(sql: &str, resolved: &str, span: Span) -> Option<(u64, u64)> {
+    let start = offset(resolved, span.start.line, span.start.column)?;
+    let end = offset(resolved, span.end.line, span.end.column)?;
+    if sql == resolved {
+        return Some(line_column(sql, start));
+    }
+
+    let text = resolved.get(start..end)?.to_ascii_lowercase();
+    if text.is_empty() {
+        return None;
+    }
+    let nth = occurrences(&resolved[..start].to_ascii_lowercase(), &text).len();
+    let found = *occurrences(&sql.to_ascii_lowercase(), &text).get(nth)?;
+    Some(line_column(sql, found))
+}
+
+/// Byte offsets where `needle` starts a whole word in `haystack`
+fn occurrences(haystack: &str, needle: &str) -> Vec<usize> {
+    let is_word = |c: char| c.is_alphanumeric() || c == '_';
+    haystack
+        .match_indices(needle)
+        .map(|(i, _)| i)
+        .filter(|&i| {
+            let before = haystack[..i].chars().next_back();
+            let after = haystack[i + needle.len()..].chars().next();
+            !before.is_some_and(is_word) && !after.is_some_and(is_word)
+        })
+        .collect()
+}
+
+/// Byte offset of a 1-based line and column
+fn offset(sql: &str, line: u64, column: u64) -> Option<usize> {
+    if line == 0 || column == 0 {
+        return None;
+    }
+    let mut start = 0;
+    for _ in 1..line {
+        start += sql[start..].find('\n')? + 1;
+    }
+    let rest = &sql[start..];
+    let within = rest
+        .char_indices()
+        .map(|(i, _)| i)
+        .chain(std::iter::once(rest.len()))
+        .nth(column as usize - 1)?;
+    Some(start + within)
+}
+
+/// 1-based line and column of a byte offset
+fn line_column(sql: &str, offset: usize) -> (u64, u64) {
+    let before = &sql[..offset];
+    let line = before.matches('\n').count() as u64 + 1;
+    let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+    let column = sql[line_start..offset].chars().count() as u64 + 1;
+    (line, column)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use datafusion::sql::sqlparser::tokenizer::Location;
+
+    #[test]
+    fn test_positions_round_trip() {
+        let sql = "SELECT id,\n  näme FROM data";
+        let at = offset(sql, 2, 3).unwrap();
+        assert_eq!(&sql[at..at + "näme".len()], "näme");
+        assert_eq!(line_column(sql, at), (2, 3));
+        assert_eq!(offset(sql, 3, 1), None);
+    }
+
+    #[test]
+    fn test_span_maps_back_through_rewrite() {
+        let sql = "SELECT Name, nme\nFROM data WHERE nme > 1";
+        let resolved = "SELECT \"name\", nme FROM data WHERE nme > 1";
+        // The second `nme` in the resolved text
+        let span = Span::new(Location::new(1, 36), Location::new(1, 39));
+        assert_eq!(original_position(sql, resolved, span), Some((2, 17)));
+
+        // Part of a longer word doesn't count
+        assert_eq!(occurrences("select nme_2, nme", "nme"), vec![14]);
+    }
+}
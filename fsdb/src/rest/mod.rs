@@ -0,0 +1,120 @@
+//! HTTP server exposing FSDB over JSON
+//!
+//! For clients that can't mount NFS but can reach an HTTP endpoint. The
+//! server runs alongside, or instead of, the NFS server, over the same
+//! `DatabaseOps` handle:
+//!
+//! - `GET /health`: the `DatabaseOps::health` report, 503 when unhealthy
+//! - `GET /api/tables`: `data` and the named tables the user can read
+//! - `GET /api/tables/{name}`: a table's columns, size and version
+//! - `POST /api/query` with `{"sql": "..."}`: columns and rows of the result
+//! - `POST /api/tables/{name}/rows` with an array of objects: insert them
+//!
+//! With authentication enabled, every request but `/health` needs HTTP
+//! Basic credentials, checked against the handle's credential store; see
+//! `sessions`. Errors are returned as `{"error": "..."}`.
+
+pub mod routes;
+pub mod sessions;
+
+use crate::database_ops::DatabaseOps;
+use crate::error::{Error, Result};
+use crate::rest::sessions::Sessions;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tracing::{error, info};
+
+/// Where the HTTP server listens and how long logins are remembered
+#[derive(Debug, Clone)]
+pub struct RestConfig {
+    /// Address to bind; port 0 picks a free port, see `RestServer::local_addr`
+    pub addr: SocketAddr,
+
+    /// How long a user's checked credentials are reused before being
+    /// checked again; a changed password or role through the server's
+    /// handle ends the session sooner, see `sessions`
+    pub session_ttl: Duration,
+}
+
+impl Default for RestConfig {
+    fn default() -> Self {
+        Self {
+            addr: SocketAddr::from(([127, 0, 0, 1], 8080)),
+            session_ttl: Duration::from_secs(300),
+        }
+    }
+}
+
+/// HTTP server answering JSON requests against a database
+///
+/// Stops when `shutdown` is called or the server is dropped.
+pub struct RestServer {
+    local_addr: SocketAddr,
+    shutdown_tx: Option<mpsc::Sender<()>>,
+}
+
+impl RestServer {
+    /// Create and start a server on `127.0.0.1:port`
+    pub async fn new(db: Arc<DatabaseOps>, port: u16) -> Result<Self> {
+        let config = RestConfig {
+            addr: SocketAddr::from(([127, 0, 0, 1], port)),
+            ..RestConfig::default()
+        };
+        Self::with_config(db, config).await
+    }
+
+    /// Create and start a server as `config` describes
+    pub async fn with_config(db: Arc<DatabaseOps>, config: RestConfig) -> Result<Self> {
+        info!("Starting FSDB HTTP server on {}", config.addr);
+
+        let listener = tokio::net::TcpListener::bind(config.addr)
+            .await
+            .map_err(|e| {
+                Error::InvalidOperation(format!(
+                    "Failed to bind HTTP server to {}: {}",
+                    config.addr, e
+                ))
+            })?;
+        let local_addr = listener.local_addr()?;
+        let sessions = Arc::new(Sessions::new(db, config.session_ttl));
+        let app = routes::router(sessions);
+
+        let (shutdown_tx, mut shutdown_rx) = mpsc::channel::<()>(1);
+        tokio::spawn(async move {
+            let shutdown = async move {
+                // A dropped server closes the channel, which stops it too
+                shutdown_rx.recv().await;
+                info!("HTTP server received shutdown signal");
+            };
+            if let Err(e) = axum::serve(listener, app)
+                .with_graceful_shutdown(shutdown)
+                .await
+            {
+                error!("HTTP server error: {:?}", e);
+            }
+            info!("HTTP server stopped");
+        });
+
+        info!("HTTP server listening on {}", local_addr);
+        Ok(Self {
+            local_addr,
+            shutdown_tx: Some(shutdown_tx),
+        })
+    }
+
+    /// Address the server is listening on
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+
+    /// Shutdown the HTTP server, letting requests in flight finish
+    pub async fn shutdown(mut self) -> Result<()> {
+        info!("Shutting down HTTP server");
+        if let Some(tx) = self.shutdown_tx.take() {
+            tx.send(()).await.ok();
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,351 @@
+//! Request handlers of the HTTP server, and how errors map to statuses
+
+use crate::database_ops::DatabaseOps;
+use crate::error::Error;
+use crate::query::Value;
+use crate::rest::sessions::Sessions;
+use arrow::datatypes::SchemaRef;
+use arrow::record_batch::RecordBatch;
+use axum::extract::rejection::JsonRejection;
+use axum::extract::{Path, State};
+use axum::http::{header, HeaderMap, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use serde::Deserialize;
+use serde_json::{json, Value as JsonValue};
+use std::sync::Arc;
+
+/// Routes of the HTTP server, answering as the users `sessions` resolves
+pub fn router(sessions: Arc<Sessions>) -> Router {
+    Router::new()
+        .route("/health", get(health))
+        .route("/api/tables", get(list_tables))
+        .route("/api/tables/{name}", get(describe_table))
+        .route("/api/tables/{name}/rows", post(insert_rows))
+        .route("/api/query", post(query))
+        .with_state(sessions)
+}
+
+/// Body of `POST /api/query`
+#[derive(Debug, Deserialize)]
+pub struct QueryRequest {
+    pub sql: String,
+}
+
+/// An error response: `{"error": "..."}` with a status for its kind
+#[derive(Debug)]
+pub struct ApiError {
+    pub status: StatusCode,
+    pub message: String,
+}
+
+impl ApiError {
+    fn not_found(message: String) -> Self {
+        Self {
+            status: StatusCode::NOT_FOUND,
+            message,
+        }
+    }
+}
+
+impl From<Error> for ApiError {
+    fn from(err: Error) -> Self {
+        Self {
+            status: status_of(&err),
+            message: err.to_string(),
+        }
+    }
+}
+
+impl From<JsonRejection> for ApiError {
+    fn from(rejection: JsonRejection) -> Self {
+        Self {
+            status: rejection.status(),
+            message: rejection.body_text(),
+        }
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        let body = Json(json!({ "error": self.message }));
+        if self.status == StatusCode::UNAUTHORIZED {
+            return (
+                self.status,
+                [(header::WWW_AUTHENTICATE, "Basic realm=\"fsdb\"")],
+                body,
+            )
+                .into_response();
+        }
+        (self.status, body).into_response()
+    }
+}
+
+/// HTTP status for `err`: 4xx for what the client can fix, 5xx otherwise
+pub fn status_of(err: &Error) -> StatusCode {
+    match err {
+        Error::AuthenticationRequired(_) => StatusCode::UNAUTHORIZED,
+        Error::PermissionDenied(_) | Error::QuotaExceeded { .. } => StatusCode::FORBIDDEN,
+        Error::InvalidSql { .. }
+        | Error::InvalidOperation(_)
+        | Error::TypeCoercion { .. }
+        | Error::RowTooLarge { .. }
+        | Error::FieldTooLarge { .. }
+        | Error::SchemaMismatch(_)
+        | Error::AmbiguousIdentifier { .. }
+        | Error::AmbiguousColumn { .. }
+        | Error::UnionMismatch(_)
+        | Error::AmbiguousMerge { .. }
+        | Error::Arrow(_)
+        | Error::Serialization(_) => StatusCode::BAD_REQUEST,
+        Error::TransactionConflict(_) | Error::MaintenanceInProgress(_) => StatusCode::CONFLICT,
+        _ => StatusCode::INTERNAL_SERVER_ERROR,
+    }
+}
+
+async fn health(State(sessions): State<Arc<Sessions>>) -> Response {
+    let report = sessions.db().health().await;
+    let components: Vec<JsonValue> = report
+        .components
+        .iter()
+        .map(|component| {
+            json!({
+                "name": component.name,
+                "status": component.state.to_string(),
+                "latency_ms": component.latency.as_secs_f64() * 1000.0,
+                "detail": component.detail,
+            })
+        })
+        .collect();
+    let status = if report.is_ready() {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+    let body = json!({
+        "status": report.status.to_string(),
+        "latency_ms": report.latency.as_secs_f64() * 1000.0,
+        "components": components,
+    });
+    (status, Json(body)).into_response()
+}
+
+async fn list_tables(
+    State(sessions): State<Arc<Sessions>>,
+    headers: HeaderMap,
+) -> Result<Json<JsonValue>, ApiError> {
+    let db = authenticate(&sessions, &headers).await?;
+    let mut tables = vec!["data".to_string()];
+    tables.extend(db.list_tables()?);
+    Ok(Json(json!({ "tables": tables })))
+}
+
+async fn describe_table(
+    State(sessions): State<Arc<Sessions>>,
+    headers: HeaderMap,
+    Path(name): Path<String>,
+) -> Result<Json<JsonValue>, ApiError> {
+    let db = authenticate(&sessions, &headers).await?;
+    let table = table_handle(&db, &name).await?;
+    let summary = table.table_summary().await?;
+    Ok(Json(json!({
+        "name": name,
+        "columns": columns(&table.schema()),
+        "num_rows": summary.num_rows,
+        "num_files": summary.num_files,
+        "total_bytes": summary.total_bytes,
+        "version": summary.latest_version,
+    })))
+}
+
+async fn insert_rows(
+    State(sessions): State<Arc<Sessions>>,
+    headers: HeaderMap,
+    Path(name): Path<String>,
+    payload: Result<Json<Vec<JsonValue>>, JsonRejection>,
+) -> Result<Json<JsonValue>, ApiError> {
+    let db = authenticate(&sessions, &headers).await?;
+    let Json(rows) = payload?;
+    let table = table_handle(&db, &name).await?;
+    let batch = rows_to_batch(table.schema(), &rows)?;
+    let commit = table.insert(batch).await?;
+    Ok(Json(json!({
+        "rows_inserted": commit.rows_affected,
+        "version": commit.version,
+    })))
+}
+
+async fn query(
+    State(sessions): State<Arc<Sessions>>,
+    headers: HeaderMap,
+    payload: Result<Json<QueryRequest>, JsonRejection>,
+) -> Result<Json<JsonValue>, ApiError> {
+    let db = authenticate(&sessions, &headers).await?;
+    let Json(request) = payload?;
+    let result = db.query_rows(&request.sql).await?;
+    let rows: Vec<JsonValue> = result
+        .rows()
+        .iter()
+        .map(|row| JsonValue::Array(row.iter().map(json_value).collect()))
+        .collect();
+    Ok(Json(json!({
+        "columns": columns(result.schema()),
+        "rows": rows,
+    })))
+}
+
+async fn authenticate(
+    sessions: &Sessions,
+    headers: &HeaderMap,
+) -> Result<Arc<DatabaseOps>, ApiError> {
+    let authorization = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok());
+    Ok(sessions.authenticate(authorization).await?)
+}
+
+/// Handle on `data` or the named table `name`, as `db`'s user
+///
+/// Tables the user can't read are reported as not found, so their names
+/// aren't revealed.
+async fn table_handle(db: &Arc<DatabaseOps>, name: &str) -> Result<Arc<DatabaseOps>, ApiError> {
+    if name == "data" {
+        return Ok(db.clone());
+    }
+    if !db.list_tables()?.iter().any(|table| table == name) {
+        return Err(ApiError::not_found(format!("No table named {}", name)));
+    }
+    Ok(Arc::new(db.table(name).await?))
+}
+
+fn columns(schema: &SchemaRef) -> Vec<JsonValue> {
+    schema
+        .fields()
+        .iter()
+        .map(|field| {
+            json!({
+                "name": field.name(),
+                "type": field.data_type().to_string(),
+                "nullable": field.is_nullable(),
+            })
+        })
+        .collect()
+}
+
+/// A result cell as JSON
+///
+/// Numbers and booleans stay JSON numbers and booleans. Decimals are strings
+/// so they keep their exact digits, and dates, timestamps and bytes are
+/// strings as `Value` displays them.
+fn json_value(value: &Value) -> JsonValue {
+    match value {
+        Value::Null => JsonValue::Null,
+        Value::Bool(v) => JsonValue::from(*v),
+        Value::Int(v) => JsonValue::from(*v),
+        Value::UInt(v) => JsonValue::from(*v),
+        Value::Float(v) => serde_json::Number::from_f64(*v)
+            .map_or_else(|| JsonValue::String(v.to_string()), JsonValue::Number),
+        other => JsonValue::String(other.to_string()),
+    }
+}
+
+/// Rows given as JSON objects, as a batch of the table's `schema`
+///
+/// Columns missing from an object are NULL; fields the table doesn't have
+/// are an error, as are values that don't fit their column.
+fn rows_to_batch(schema: SchemaRef, rows: &[JsonValue]) -> Result<RecordBatch, ApiError> {
+    if rows.is_empty() {
+        return Err(Error::InvalidOperation("No rows to insert".to_string()).into());
+    }
+    if let Some(row) = rows.iter().find(|row| !row.is_object()) {
+        return Err(
+            Error::InvalidOperation(format!("Rows must be JSON objects, got {}", row)).into(),
+        );
+    }
+    let mut decoder = arrow::json::ReaderBuilder::new(schema)
+        .with_batch_size(rows.len())
+        .with_strict_mode(true)
+        .build_decoder()
+        .map_err(Error::from)?;
+    decoder.serialize(rows).map_err(Error::from)?;
+    let batch = decoder
+        .flush()
+        .map_err(Error::from)?
+        .ok_or_else(|| Error::InvalidOperation("No rows to insert".to_string()))?;
+    Ok(batch)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow::array::{AsArray, Int32Array};
+    use arrow::datatypes::{DataType, Field, Int32Type, Schema};
+
+    #[test]
+    fn test_rows_to_batch() {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("id", DataType::Int32, false),
+            Field::new("name", DataType::Utf8, true),
+        ]));
+        let rows = vec![json!({"id": 1, "name": "ada"}), json!({"id": 2})];
+        let batch = rows_to_batch(schema.clone(), &rows).unwrap();
+        assert_eq!(batch.num_rows(), 2);
+        assert_eq!(
+            batch.column(0).as_primitive::<Int32Type>(),
+            &Int32Array::from(vec![1, 2])
+        );
+        assert!(batch.column(1).is_null(1));
+
+        for rows in [
+            vec![],
+            vec![json!([1, "ada"])],
+            vec![json!({"id": 1, "email": "x"})],
+            vec![json!({"id": "one"})],
+        ] {
+            let err = rows_to_batch(schema.clone(), &rows).unwrap_err();
+            assert_eq!(err.status, StatusCode::BAD_REQUEST, "{:?}", rows);
+        }
+    }
+
+    #[test]
+    fn test_status_of() {
+        for (err, status) in [
+            (
+                Error::AuthenticationRequired("no Basic credentials".to_string()),
+                StatusCode::UNAUTHORIZED,
+            ),
+            (
+                Error::PermissionDenied("Write".to_string()),
+                StatusCode::FORBIDDEN,
+            ),
+            (
+                Error::InvalidSql {
+                    message: "x".to_string(),
+                    position: None,
+                },
+                StatusCode::BAD_REQUEST,
+            ),
+            (
+                Error::Other("disk on fire".to_string()),
+                StatusCode::INTERNAL_SERVER_ERROR,
+            ),
+        ] {
+            assert_eq!(status_of(&err), status, "{}", err);
+        }
+    }
+
+    #[test]
+    fn test_json_values() {
+        assert_eq!(json_value(&Value::Int(-3)), json!(-3));
+        assert_eq!(json_value(&Value::Null), JsonValue::Null);
+        assert_eq!(
+            json_value(&Value::Decimal {
+                value: 12345,
+                scale: 2
+            }),
+            json!("123.45")
+        );
+        assert_eq!(json_value(&Value::Float(f64::NAN)), json!("NaN"));
+    }
+}
@@ -0,0 +1,133 @@
+//! Which user an HTTP request acts as
+//!
+//! The server is given one handle, usually opened with system access. With
+//! authentication disabled every request uses it. Otherwise each request's
+//! `Authorization: Basic` credentials are checked with `DatabaseOps::login`,
+//! and the user's handle is kept for the session TTL, keyed by user name and
+//! a digest of the password, so the password hash and the table aren't
+//! checked and opened again for every request.
+//!
+//! A kept handle is only reused while the user's account, as the credential
+//! store holds it, has the password hash and roles it had at login: changing
+//! either, or removing the user, through the server's handle or one sharing
+//! its credential store ends the session at the next request.
+
+use crate::database_ops::DatabaseOps;
+use crate::error::{Error, Result};
+use crate::security::User;
+use base64::Engine;
+use moka::future::Cache;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Most users whose handles are kept at once
+const MAX_SESSIONS: u64 = 1024;
+
+/// Handles of the users making requests
+pub struct Sessions {
+    db: Arc<DatabaseOps>,
+    users: Cache<(String, Vec<u8>), Session>,
+}
+
+/// A user's handle, and their account as it was at login
+#[derive(Clone)]
+struct Session {
+    db: Arc<DatabaseOps>,
+    user: User,
+}
+
+impl Session {
+    /// Whether the login still stands for the account as it is now
+    fn is_current(&self, user: &User) -> bool {
+        user.password_hash == self.user.password_hash && user.roles == self.user.roles
+    }
+}
+
+impl Sessions {
+    /// Sessions over `db`, each kept for `ttl` after login
+    pub fn new(db: Arc<DatabaseOps>, ttl: Duration) -> Self {
+        Self {
+            db,
+            users: Cache::builder()
+                .max_capacity(MAX_SESSIONS)
+                .time_to_live(ttl)
+                .build(),
+        }
+    }
+
+    /// The handle the server was started with
+    pub fn db(&self) -> &Arc<DatabaseOps> {
+        &self.db
+    }
+
+    /// Handle to answer a request with this `Authorization` header as
+    ///
+    /// Fails with `Error::AuthenticationRequired` if authentication is enabled
+    /// and the header is missing or not Basic credentials.
+    pub async fn authenticate(&self, authorization: Option<&str>) -> Result<Arc<DatabaseOps>> {
+        if !self.db.auth_enabled() {
+            return Ok(self.db.clone());
+        }
+        let (username, password) = authorization
+            .and_then(parse_basic)
+            .ok_or_else(|| Error::AuthenticationRequired("no Basic credentials".to_string()))?;
+
+        let digest = ring::digest::digest(&ring::digest::SHA256, password.as_bytes());
+        let key = (username, digest.as_ref().to_vec());
+        if let Some(session) = self.users.get(&key).await {
+            let current = self.db.stored_user(&key.0)?;
+            if current.is_some_and(|user| session.is_current(&user)) {
+                return Ok(session.db);
+            }
+            self.users.invalidate(&key).await;
+        }
+        let db = Arc::new(self.db.login(&key.0, &password).await?);
+        if let Some(user) = self.db.stored_user(&key.0)? {
+            self.users
+                .insert(
+                    key,
+                    Session {
+                        db: db.clone(),
+                        user,
+                    },
+                )
+                .await;
+        }
+        Ok(db)
+    }
+}
+
+/// User name and password of an `Authorization: Basic` header value
+pub fn parse_basic(value: &str) -> Option<(String, String)> {
+    let (scheme, encoded) = value.trim().split_once(' ')?;
+    if !scheme.eq_ignore_ascii_case("basic") {
+        return None;
+    }
+    let decoded = base64::engine::general_purpose::STANDARD
+        .decode(encoded.trim())
+        .ok()?;
+    let decoded = String::from_utf8(decoded).ok()?;
+    let (username, password) = decoded.split_once(':')?;
+    Some((username.to_string(), password.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_basic() {
+        // "alice:s3cr:et", the password keeping its colon
+        assert_eq!(
+            parse_basic("Basic YWxpY2U6czNjcjpldA=="),
+            Some(("alice".to_string(), "s3cr:et".to_string()))
+        );
+        assert_eq!(
+            parse_basic("basic  YWxpY2U6czNjcjpldA== "),
+            Some(("alice".to_string(), "s3cr:et".to_string()))
+        );
+        for value in ["Bearer YWxpY2U6eA==", "Basic !!!", "Basic YWxpY2U=", ""] {
+            assert_eq!(parse_basic(value), None, "{}", value);
+        }
+    }
+}
@@ -1,8 +1,17 @@
 //! Audit logging for security and compliance
 
 use crate::Result;
+use futures::stream::{BoxStream, Stream, StreamExt};
 use serde::{Deserialize, Serialize};
 use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use tokio::sync::broadcast;
+
+/// Entries each subscriber may fall behind by before the oldest are dropped
+pub const DEFAULT_SUBSCRIBER_CAPACITY: usize = 1024;
 
 /// Audit log entry
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -12,6 +21,9 @@ pub struct AuditEntry {
     pub operation: String,
     pub details: String,
     pub success: bool,
+    /// Unredacted `details`, set when a log redaction policy changed them
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub full_details: Option<String>,
 }
 
 impl AuditEntry {
@@ -28,8 +40,15 @@ impl AuditEntry {
             operation,
             details,
             success,
+            full_details: None,
         }
     }
+
+    /// Keep the unredacted details next to the redacted ones
+    pub fn with_full_details(mut self, full_details: String) -> Self {
+        self.full_details = Some(full_details);
+        self
+    }
 }
 
 /// Audit log (stored as append-only log)
@@ -116,17 +135,27 @@ impl AuditLog {
 pub struct AuditLogger {
     log_path: PathBuf,
     log: tokio::sync::Mutex<AuditLog>,
+    subscribers: broadcast::Sender<AuditEntry>,
+    dropped: Arc<AtomicU64>,
 }
 
 impl AuditLogger {
     /// Create a new audit logger
     pub fn new<P: AsRef<Path>>(log_path: P) -> Result<Self> {
+        Self::with_subscriber_capacity(log_path, DEFAULT_SUBSCRIBER_CAPACITY)
+    }
+
+    /// Create an audit logger whose subscribers buffer up to `capacity` entries
+    pub fn with_subscriber_capacity<P: AsRef<Path>>(log_path: P, capacity: usize) -> Result<Self> {
         let log_path = log_path.as_ref().to_path_buf();
         let log = AuditLog::load(&log_path)?;
+        let (subscribers, _) = broadcast::channel(capacity.max(1));
 
         Ok(Self {
             log_path,
             log: tokio::sync::Mutex::new(log),
+            subscribers,
+            dropped: Arc::new(AtomicU64::new(0)),
         })
     }
 
@@ -138,12 +167,20 @@ impl AuditLogger {
         details: String,
         success: bool,
     ) -> Result<()> {
-        let entry = AuditEntry::new(user, operation, details, success);
+        self.log_entry(AuditEntry::new(user, operation, details, success))
+            .await
+    }
 
+    /// Log a prepared entry
+    pub async fn log_entry(&self, entry: AuditEntry) -> Result<()> {
         let mut log = self.log.lock().await;
-        log.add_entry(entry);
+        log.add_entry(entry.clone());
         log.save(&self.log_path)?;
 
+        // Sent under the lock so subscribers see entries in log order; an
+        // error only means nobody is subscribed
+        let _ = self.subscribers.send(entry);
+
         Ok(())
     }
 
@@ -152,6 +189,89 @@ impl AuditLogger {
         let log = self.log.lock().await;
         log.entries().to_vec()
     }
+
+    /// Stream the entries logged from now on
+    ///
+    /// Each subscription has its own bounded buffer. Logging never waits for
+    /// a subscriber: one that falls behind loses its oldest entries, which
+    /// are counted by `AuditSubscription::dropped` and `dropped_entries`.
+    /// Dropping the subscription unsubscribes it.
+    pub fn subscribe(&self) -> AuditSubscription {
+        AuditSubscription::new(self.subscribers.subscribe(), self.dropped.clone())
+    }
+
+    /// Number of active subscriptions
+    pub fn subscriber_count(&self) -> usize {
+        self.subscribers.receiver_count()
+    }
+
+    /// Entries dropped by lagging subscribers, across all subscriptions
+    pub fn dropped_entries(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+}
+
+/// Stream of audit entries from `AuditLogger::subscribe`
+pub struct AuditSubscription {
+    entries: BoxStream<'static, AuditEntry>,
+    dropped: Arc<AtomicU64>,
+    include_full_details: bool,
+}
+
+impl AuditSubscription {
+    fn new(receiver: broadcast::Receiver<AuditEntry>, logger_dropped: Arc<AtomicU64>) -> Self {
+        let dropped = Arc::new(AtomicU64::new(0));
+        let state = (receiver, dropped.clone(), logger_dropped);
+        let entries = futures::stream::unfold(
+            state,
+            |(mut receiver, dropped, logger_dropped)| async move {
+                loop {
+                    match receiver.recv().await {
+                        Ok(entry) => return Some((entry, (receiver, dropped, logger_dropped))),
+                        Err(broadcast::error::RecvError::Lagged(n)) => {
+                            dropped.fetch_add(n, Ordering::Relaxed);
+                            logger_dropped.fetch_add(n, Ordering::Relaxed);
+                        }
+                        Err(broadcast::error::RecvError::Closed) => return None,
+                    }
+                }
+            },
+        )
+        .boxed();
+
+        Self {
+            entries,
+            dropped,
+            include_full_details: true,
+        }
+    }
+
+    /// Strip unredacted details from streamed entries
+    pub fn without_full_details(mut self) -> Self {
+        self.include_full_details = false;
+        self
+    }
+
+    /// Entries this subscription lost by falling behind
+    pub fn dropped(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+}
+
+impl Stream for AuditSubscription {
+    type Item = AuditEntry;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<AuditEntry>> {
+        let include_full_details = self.include_full_details;
+        self.entries.poll_next_unpin(cx).map(|entry| {
+            entry.map(|mut entry| {
+                if !include_full_details {
+                    entry.full_details = None;
+                }
+                entry
+            })
+        })
+    }
 }
 
 #[cfg(test)]
@@ -189,4 +309,63 @@ mod tests {
         assert_eq!(select_entries.len(), 1);
         assert_eq!(select_entries[0].user, "bob");
     }
+
+    fn entry(operation: &str) -> AuditEntry {
+        AuditEntry::new(
+            "alice".to_string(),
+            operation.to_string(),
+            String::new(),
+            true,
+        )
+    }
+
+    #[tokio::test]
+    async fn test_subscribers_receive_later_entries() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let logger = AuditLogger::new(temp_dir.path().join("audit.json")).unwrap();
+        logger.log_entry(entry("BEFORE")).await.unwrap();
+
+        let mut first = logger.subscribe();
+        let mut second = logger.subscribe().without_full_details();
+        logger
+            .log_entry(entry("INSERT").with_full_details("secret".to_string()))
+            .await
+            .unwrap();
+        logger.log_entry(entry("SELECT")).await.unwrap();
+
+        let received = first.next().await.unwrap();
+        assert_eq!(received.operation, "INSERT");
+        assert_eq!(received.full_details.as_deref(), Some("secret"));
+        assert_eq!(first.next().await.unwrap().operation, "SELECT");
+
+        let received = second.next().await.unwrap();
+        assert_eq!(received.operation, "INSERT");
+        assert_eq!(received.full_details, None);
+
+        // Dropping a subscription unsubscribes it
+        assert_eq!(logger.subscriber_count(), 2);
+        drop(second);
+        assert_eq!(logger.subscriber_count(), 1);
+        logger.log_entry(entry("DELETE")).await.unwrap();
+        assert_eq!(first.next().await.unwrap().operation, "DELETE");
+    }
+
+    #[tokio::test]
+    async fn test_lagging_subscriber_drops_oldest() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let logger =
+            AuditLogger::with_subscriber_capacity(temp_dir.path().join("audit.json"), 2).unwrap();
+        let mut subscription = logger.subscribe();
+
+        // Logging doesn't wait for the subscriber to catch up
+        for operation in ["A", "B", "C", "D", "E"] {
+            logger.log_entry(entry(operation)).await.unwrap();
+        }
+
+        assert_eq!(subscription.next().await.unwrap().operation, "D");
+        assert_eq!(subscription.next().await.unwrap().operation, "E");
+        assert_eq!(subscription.dropped(), 3);
+        assert_eq!(logger.dropped_entries(), 3);
+        assert_eq!(logger.get_entries().await.len(), 5);
+    }
 }
@@ -1,5 +1,6 @@
 //! Authentication module with bcrypt password hashing
 
+use super::quota::Quota;
 use crate::{Error, Result};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
@@ -48,6 +49,9 @@ pub struct User {
     pub password_hash: String,
     pub roles: Vec<String>,
     pub created_at: i64,
+    /// Storage quota overriding the user's roles' (None = from roles)
+    #[serde(default)]
+    pub quota: Option<Quota>,
 }
 
 impl User {
@@ -64,6 +68,7 @@ impl User {
                 .duration_since(std::time::UNIX_EPOCH)
                 .unwrap()
                 .as_millis() as i64,
+            quota: None,
         })
     }
 
@@ -137,6 +142,11 @@ impl UserStore {
         Ok(())
     }
 
+    /// Add a user, replacing any user with the same name
+    pub fn put_user(&mut self, user: User) {
+        self.users.insert(user.username.clone(), user);
+    }
+
     /// Get a user by username
     pub fn get_user(&self, username: &str) -> Option<&User> {
         self.users.get(username)
@@ -146,10 +156,12 @@ impl UserStore {
     pub fn authenticate(&self, username: &str, password: &str) -> Result<AuthContext> {
         let user = self
             .get_user(username)
-            .ok_or_else(|| Error::Other("Invalid credentials".to_string()))?;
+            .ok_or_else(|| Error::AuthenticationRequired("invalid credentials".to_string()))?;
 
         if !user.verify_password(password) {
-            return Err(Error::Other("Invalid credentials".to_string()));
+            return Err(Error::AuthenticationRequired(
+                "invalid credentials".to_string(),
+            ));
         }
 
         Ok(AuthContext::authenticated(
@@ -169,6 +181,17 @@ impl UserStore {
         Ok(())
     }
 
+    /// Set or clear a user's own quota
+    pub fn set_quota(&mut self, username: &str, quota: Option<Quota>) -> Result<()> {
+        let user = self
+            .users
+            .get_mut(username)
+            .ok_or_else(|| Error::Other(format!("User not found: {}", username)))?;
+
+        user.quota = quota;
+        Ok(())
+    }
+
     /// Add a role to a user
     pub fn grant_role(&mut self, username: &str, role: &str) -> Result<()> {
         let user = self
@@ -0,0 +1,716 @@
+//! Per-column encryption of PII
+//!
+//! Columns declared in `_metadata/column_encryption.json` hold AES-256-GCM
+//! ciphertext instead of their values, while the rest of the table stays in
+//! the clear. Each column is encrypted with its own key, derived with HKDF
+//! from a master key the application supplies at runtime (`ColumnKeys`) and
+//! never written to disk; the metadata only names the key. Values are
+//! encrypted as rows are inserted and decrypted in query results, for users
+//! whose roles have `Permission::Decrypt`. Others see the ciphertext, or the
+//! column's mask placeholder if it has one.
+//!
+//! Ciphertext is randomized by default, so equal values encrypt differently
+//! and nothing can be compared. A column can opt into deterministic
+//! encryption, whose nonce is an HMAC of the value: equal values then encrypt
+//! alike, and `=`, `<>` and `IN` against string literals work because the
+//! literals are encrypted before planning. The tradeoff is that anyone who
+//! can read the files sees which rows share a value, and how often each
+//! value occurs. Order isn't preserved either way, so `<`, `BETWEEN`, `LIKE`
+//! and the like on an encrypted column fail rather than silently compare
+//! ciphertext.
+//!
+//! Results are decrypted by column name, so an encrypted column selected
+//! under an alias, or through a function, reads as ciphertext.
+
+use crate::{Error, Result};
+use arrow::array::{ArrayRef, AsArray, RecordBatch, StringArray};
+use arrow::datatypes::DataType;
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use datafusion::sql::sqlparser::ast::{
+    BinaryOperator, Expr, Value, ValueWithSpan, VisitMut, VisitorMut,
+};
+use datafusion::sql::sqlparser::dialect::GenericDialect;
+use datafusion::sql::sqlparser::parser::Parser;
+use ring::aead::{Aad, LessSafeKey, Nonce, UnboundKey, AES_256_GCM, NONCE_LEN};
+use ring::hkdf::{Salt, HKDF_SHA256};
+use ring::hmac;
+use ring::rand::{SecureRandom, SystemRandom};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::ops::ControlFlow;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// Marks a stored value as ciphertext, ahead of its base64 nonce and sealed bytes
+pub const CIPHERTEXT_PREFIX: &str = "fsdbenc:v1:";
+
+/// How a column is encrypted
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EncryptedColumn {
+    /// Name of the master key in `ColumnKeys` the column's key derives from
+    pub key_id: String,
+
+    /// Equal values encrypt alike, so equality queries work
+    #[serde(default)]
+    pub deterministic: bool,
+
+    /// Shown instead of the ciphertext to users who can't decrypt
+    #[serde(default)]
+    pub mask: Option<String>,
+}
+
+impl EncryptedColumn {
+    /// Randomized encryption with the master key `key_id`
+    pub fn new(key_id: impl Into<String>) -> Self {
+        Self {
+            key_id: key_id.into(),
+            deterministic: false,
+            mask: None,
+        }
+    }
+
+    /// Encrypt deterministically, allowing equality queries
+    pub fn with_deterministic(mut self) -> Self {
+        self.deterministic = true;
+        self
+    }
+
+    /// Show `placeholder` to users who can't decrypt
+    pub fn with_mask(mut self, placeholder: impl Into<String>) -> Self {
+        self.mask = Some(placeholder.into());
+        self
+    }
+}
+
+/// Master keys for encrypted columns, by key id
+///
+/// Held in memory only; `Debug` doesn't print them.
+#[derive(Clone, Default)]
+pub struct ColumnKeys {
+    keys: HashMap<String, [u8; 32]>,
+}
+
+impl ColumnKeys {
+    /// No keys yet
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add the 256-bit master key `key_id`
+    pub fn with_key(mut self, key_id: impl Into<String>, key: [u8; 32]) -> Self {
+        self.keys.insert(key_id.into(), key);
+        self
+    }
+
+    fn get(&self, key_id: &str) -> Option<&[u8; 32]> {
+        self.keys.get(key_id)
+    }
+}
+
+impl std::fmt::Debug for ColumnKeys {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut ids: Vec<&String> = self.keys.keys().collect();
+        ids.sort();
+        f.debug_struct("ColumnKeys").field("key_ids", &ids).finish()
+    }
+}
+
+/// Encrypts and decrypts the values of one column
+pub struct ColumnCipher {
+    column: String,
+    key: LessSafeKey,
+    nonce_key: hmac::Key,
+    deterministic: bool,
+}
+
+impl ColumnCipher {
+    /// The cipher for `column`, with its key derived from `master`
+    pub fn new(column: &str, master: &[u8; 32], deterministic: bool) -> Result<Self> {
+        let prk = Salt::new(HKDF_SHA256, b"fsdb column encryption").extract(master);
+        let key: UnboundKey = prk
+            .expand(&[b"aead".as_slice(), column.as_bytes()], &AES_256_GCM)
+            .map_err(|_| Error::Encryption(format!("Can't derive a key for '{}'", column)))?
+            .into();
+        let nonce_key: hmac::Key = prk
+            .expand(&[b"nonce".as_slice(), column.as_bytes()], hmac::HMAC_SHA256)
+            .map_err(|_| Error::Encryption(format!("Can't derive a key for '{}'", column)))?
+            .into();
+        Ok(Self {
+            column: column.to_string(),
+            key: LessSafeKey::new(key),
+            nonce_key,
+            deterministic,
+        })
+    }
+
+    /// Ciphertext of `value`
+    ///
+    /// The column name is authenticated with it, so ciphertext copied into
+    /// another column doesn't decrypt.
+    pub fn encrypt(&self, value: &str) -> Result<String> {
+        let mut nonce = [0u8; NONCE_LEN];
+        if self.deterministic {
+            let tag = hmac::sign(&self.nonce_key, value.as_bytes());
+            nonce.copy_from_slice(&tag.as_ref()[..NONCE_LEN]);
+        } else {
+            SystemRandom::new()
+                .fill(&mut nonce)
+                .map_err(|_| Error::Encryption("No randomness for a nonce".to_string()))?;
+        }
+        let mut sealed = value.as_bytes().to_vec();
+        self.key
+            .seal_in_place_append_tag(
+                Nonce::assume_unique_for_key(nonce),
+                Aad::from(self.column.as_bytes()),
+                &mut sealed,
+            )
+            .map_err(|_| Error::Encryption(format!("Can't encrypt '{}'", self.column)))?;
+        let mut bytes = nonce.to_vec();
+        bytes.extend_from_slice(&sealed);
+        Ok(format!("{}{}", CIPHERTEXT_PREFIX, STANDARD.encode(bytes)))
+    }
+
+    /// Value of `ciphertext`; values without the ciphertext prefix are returned as-is
+    pub fn decrypt(&self, ciphertext: &str) -> Result<String> {
+        let Some(encoded) = ciphertext.strip_prefix(CIPHERTEXT_PREFIX) else {
+            return Ok(ciphertext.to_string());
+        };
+        let invalid = || Error::Encryption(format!("Invalid ciphertext in '{}'", self.column));
+        let mut bytes = STANDARD.decode(encoded).map_err(|_| invalid())?;
+        if bytes.len() < NONCE_LEN {
+            return Err(invalid());
+        }
+        let mut sealed = bytes.split_off(NONCE_LEN);
+        let nonce = Nonce::try_assume_unique_for_key(&bytes).map_err(|_| invalid())?;
+        let plain = self
+            .key
+            .open_in_place(nonce, Aad::from(self.column.as_bytes()), &mut sealed)
+            .map_err(|_| invalid())?;
+        String::from_utf8(plain.to_vec()).map_err(|_| invalid())
+    }
+}
+
+/// Encrypted columns of a table, by name
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ColumnEncryption {
+    pub columns: BTreeMap<String, EncryptedColumn>,
+}
+
+impl ColumnEncryption {
+    /// Where a table's encrypted columns are declared
+    pub fn path(base_path: &Path) -> PathBuf {
+        base_path.join("_metadata").join("column_encryption.json")
+    }
+
+    /// Load a table's encrypted columns, none if it has no file of them
+    pub fn load(base_path: &Path) -> Result<Self> {
+        let path = Self::path(base_path);
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let json = std::fs::read_to_string(&path)?;
+        Ok(serde_json::from_str(&json)?)
+    }
+
+    /// Save atomically, replacing the previous declarations
+    pub fn save(&self, base_path: &Path) -> Result<()> {
+        let path = Self::path(base_path);
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let tmp_path = path.with_extension("tmp");
+        std::fs::write(&tmp_path, serde_json::to_string_pretty(self)?)?;
+        std::fs::rename(tmp_path, path)?;
+        Ok(())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.columns.is_empty()
+    }
+
+    /// The declared name and encryption of `column`, matched ignoring case
+    fn find(&self, column: &str) -> Option<(&str, &EncryptedColumn)> {
+        self.columns
+            .get_key_value(column)
+            .or_else(|| {
+                self.columns
+                    .iter()
+                    .find(|(name, _)| name.eq_ignore_ascii_case(column))
+            })
+            .map(|(name, encrypted)| (name.as_str(), encrypted))
+    }
+
+    /// The cipher of `column`, failing if `keys` lacks its key
+    fn cipher(&self, column: &str, keys: Option<&ColumnKeys>) -> Result<ColumnCipher> {
+        let (name, encrypted) = self
+            .find(column)
+            .ok_or_else(|| Error::Encryption(format!("Column '{}' isn't encrypted", column)))?;
+        let master = keys.and_then(|k| k.get(&encrypted.key_id)).ok_or_else(|| {
+            Error::Encryption(format!(
+                "No key '{}' for encrypted column '{}'",
+                encrypted.key_id, name
+            ))
+        })?;
+        ColumnCipher::new(name, master, encrypted.deterministic)
+    }
+
+    /// Encrypt the encrypted columns of `batch`, rows about to be written
+    pub fn encrypt_batch(
+        &self,
+        batch: RecordBatch,
+        keys: Option<&ColumnKeys>,
+    ) -> Result<RecordBatch> {
+        self.map_batch(batch, |name| {
+            let cipher = self.cipher(name, keys)?;
+            let map: ValueMap = Box::new(move |value| cipher.encrypt(value));
+            Ok(Some(map))
+        })
+    }
+
+    /// Decrypt the encrypted columns of `batch`, query results
+    ///
+    /// Without `authorized`, or without the column's key, values are masked
+    /// if the column has a mask, and left as ciphertext otherwise.
+    pub fn decrypt_batch(
+        &self,
+        batch: RecordBatch,
+        keys: Option<&ColumnKeys>,
+        authorized: bool,
+    ) -> Result<RecordBatch> {
+        self.map_batch(batch, |name| {
+            let (_, encrypted) = self.find(name).expect("mapped columns are encrypted");
+            if authorized {
+                if let Ok(cipher) = self.cipher(name, keys) {
+                    let map: ValueMap = Box::new(move |value| cipher.decrypt(value));
+                    return Ok(Some(map));
+                }
+            }
+            Ok(encrypted.mask.clone().map(|mask| {
+                let map: ValueMap = Box::new(move |_| Ok(mask.clone()));
+                map
+            }))
+        })
+    }
+
+    /// Apply to each non-null value of every encrypted string column of
+    /// `batch` the function `map_for` gives for that column, if any
+    fn map_batch(
+        &self,
+        batch: RecordBatch,
+        map_for: impl Fn(&str) -> Result<Option<ValueMap>>,
+    ) -> Result<RecordBatch> {
+        if self.is_empty() {
+            return Ok(batch);
+        }
+        let schema = batch.schema();
+        let mut columns: Vec<ArrayRef> = batch.columns().to_vec();
+        for (i, field) in schema.fields().iter().enumerate() {
+            if !is_string(field.data_type()) || self.find(field.name()).is_none() {
+                continue;
+            }
+            let Some(map) = map_for(field.name())? else {
+                continue;
+            };
+            let strings = arrow::compute::cast(&columns[i], &DataType::Utf8)?;
+            let mapped: StringArray = strings
+                .as_string::<i32>()
+                .iter()
+                .map(|value| value.map(&map).transpose())
+                .collect::<Result<_>>()?;
+            columns[i] = arrow::compute::cast(&(Arc::new(mapped) as ArrayRef), field.data_type())?;
+        }
+        Ok(RecordBatch::try_new(schema, columns)?)
+    }
+
+    /// Rewrite `sql` so comparisons on encrypted columns compare ciphertext
+    ///
+    /// String literals compared with `=`, `<>` or `IN` to a deterministic
+    /// column are encrypted; such comparisons on a randomized column, and
+    /// range or pattern comparisons on any encrypted column, fail.
+    pub fn encrypt_sql(&self, sql: &str, keys: Option<&ColumnKeys>) -> Result<String> {
+        if self.is_empty() {
+            return Ok(sql.to_string());
+        }
+        // SQL that doesn't parse is left for DataFusion to report
+        let Ok(mut statements) = Parser::parse_sql(&GenericDialect {}, sql) else {
+            return Ok(sql.to_string());
+        };
+        let mut encryptor = LiteralEncryptor {
+            encryption: self,
+            keys,
+            rewritten: 0,
+        };
+        for statement in &mut statements {
+            if let ControlFlow::Break(e) = statement.visit(&mut encryptor) {
+                return Err(e);
+            }
+        }
+        if encryptor.rewritten == 0 {
+            return Ok(sql.to_string());
+        }
+        Ok(statements
+            .iter()
+            .map(|s| s.to_string())
+            .collect::<Vec<_>>()
+            .join("; "))
+    }
+
+    /// Like `encrypt_sql`, for a predicate such as a `WHERE` clause
+    pub fn encrypt_predicate(&self, predicate: &str, keys: Option<&ColumnKeys>) -> Result<String> {
+        if self.is_empty() {
+            return Ok(predicate.to_string());
+        }
+        let Ok(mut expr) = Parser::new(&GenericDialect {})
+            .try_with_sql(predicate)
+            .and_then(|mut parser| parser.parse_expr())
+        else {
+            return Ok(predicate.to_string());
+        };
+        let mut encryptor = LiteralEncryptor {
+            encryption: self,
+            keys,
+            rewritten: 0,
+        };
+        if let ControlFlow::Break(e) = expr.visit(&mut encryptor) {
+            return Err(e);
+        }
+        if encryptor.rewritten == 0 {
+            return Ok(predicate.to_string());
+        }
+        Ok(expr.to_string())
+    }
+
+    /// The value to `SET` `column` to in an update, encrypted if the column is
+    ///
+    /// Only string literals, NULL and the column itself, already ciphertext,
+    /// possibly qualified as in a MERGE clause's `source.ssn`, can be
+    /// assigned to an encrypted column.
+    pub fn encrypt_assignment(
+        &self,
+        column: &str,
+        value: &str,
+        keys: Option<&ColumnKeys>,
+    ) -> Result<String> {
+        if self.find(column).is_none() {
+            return Ok(value.to_string());
+        }
+        let expr = Parser::new(&GenericDialect {})
+            .try_with_sql(value)
+            .and_then(|mut parser| parser.parse_expr());
+        match expr {
+            Ok(Expr::Value(ValueWithSpan {
+                value: Value::SingleQuotedString(s),
+                ..
+            })) => Ok(format!("'{}'", self.cipher(column, keys)?.encrypt(&s)?)),
+            Ok(Expr::Value(ValueWithSpan {
+                value: Value::Null, ..
+            })) => Ok(value.to_string()),
+            Ok(Expr::Identifier(ident)) if ident.value.eq_ignore_ascii_case(column) => {
+                Ok(value.to_string())
+            }
+            Ok(Expr::CompoundIdentifier(idents))
+                if idents
+                    .last()
+                    .is_some_and(|ident| ident.value.eq_ignore_ascii_case(column)) =>
+            {
+                Ok(value.to_string())
+            }
+            _ => Err(Error::InvalidOperation(format!(
+                "Encrypted column '{}' can only be set to a string literal, NULL or itself",
+                column
+            ))),
+        }
+    }
+}
+
+type ValueMap = Box<dyn Fn(&str) -> Result<String>>;
+
+fn is_string(data_type: &DataType) -> bool {
+    matches!(
+        data_type,
+        DataType::Utf8 | DataType::LargeUtf8 | DataType::Utf8View
+    )
+}
+
+/// Encrypts literals compared with encrypted columns
+struct LiteralEncryptor<'a> {
+    encryption: &'a ColumnEncryption,
+    keys: Option<&'a ColumnKeys>,
+    rewritten: usize,
+}
+
+impl LiteralEncryptor<'_> {
+    /// The encrypted column `expr` refers to, if it's a reference to one
+    fn encrypted_column(&self, expr: &Expr) -> Option<String> {
+        let name = match expr {
+            Expr::Identifier(ident) => &ident.value,
+            Expr::CompoundIdentifier(idents) => &idents.last()?.value,
+            Expr::Nested(inner) => return self.encrypted_column(inner),
+            _ => return None,
+        };
+        self.encryption
+            .find(name)
+            .map(|(column, _)| column.to_string())
+    }
+
+    /// Encrypt `expr` for comparison with `column` if it's a string literal
+    fn encrypt_literal(&mut self, column: &str, expr: &mut Expr) -> Result<()> {
+        let Expr::Value(ValueWithSpan {
+            value: Value::SingleQuotedString(literal),
+            ..
+        }) = expr
+        else {
+            return Ok(());
+        };
+        let (_, encrypted) = self.encryption.find(column).expect("column is encrypted");
+        if !encrypted.deterministic {
+            return Err(Error::InvalidOperation(format!(
+                "Equality on encrypted column '{}' needs deterministic encryption",
+                column
+            )));
+        }
+        *literal = self
+            .encryption
+            .cipher(column, self.keys)?
+            .encrypt(literal)?;
+        self.rewritten += 1;
+        Ok(())
+    }
+
+    fn check_expr(&mut self, expr: &mut Expr) -> Result<()> {
+        match expr {
+            Expr::BinaryOp {
+                left,
+                op: BinaryOperator::Eq | BinaryOperator::NotEq,
+                right,
+            } => {
+                if let Some(column) = self.encrypted_column(left) {
+                    self.encrypt_literal(&column, right)?;
+                } else if let Some(column) = self.encrypted_column(right) {
+                    self.encrypt_literal(&column, left)?;
+                }
+            }
+            Expr::BinaryOp {
+                left,
+                op:
+                    BinaryOperator::Lt
+                    | BinaryOperator::LtEq
+                    | BinaryOperator::Gt
+                    | BinaryOperator::GtEq,
+                right,
+            } => {
+                if let Some(column) = self
+                    .encrypted_column(left)
+                    .or_else(|| self.encrypted_column(right))
+                {
+                    return Err(unsupported(&column));
+                }
+            }
+            Expr::Between { expr: inner, .. }
+            | Expr::Like { expr: inner, .. }
+            | Expr::ILike { expr: inner, .. }
+            | Expr::SimilarTo { expr: inner, .. } => {
+                if let Some(column) = self.encrypted_column(inner) {
+                    return Err(unsupported(&column));
+                }
+            }
+            Expr::InList {
+                expr: inner, list, ..
+            } => {
+                if let Some(column) = self.encrypted_column(inner) {
+                    for item in list.iter_mut() {
+                        self.encrypt_literal(&column, item)?;
+                    }
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+}
+
+impl VisitorMut for LiteralEncryptor<'_> {
+    type Break = Error;
+
+    fn post_visit_expr(&mut self, expr: &mut Expr) -> ControlFlow<Self::Break> {
+        match self.check_expr(expr) {
+            Ok(()) => ControlFlow::Continue(()),
+            Err(e) => ControlFlow::Break(e),
+        }
+    }
+}
+
+fn unsupported(column: &str) -> Error {
+    Error::InvalidOperation(format!(
+        "Range and pattern comparisons aren't supported on encrypted column '{}'",
+        column
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow::array::Array;
+    use arrow::datatypes::{Field, Schema};
+
+    fn keys() -> ColumnKeys {
+        ColumnKeys::new().with_key("pii", [7u8; 32])
+    }
+
+    fn encryption() -> ColumnEncryption {
+        let mut encryption = ColumnEncryption::default();
+        encryption.columns.insert(
+            "ssn".to_string(),
+            EncryptedColumn::new("pii").with_deterministic(),
+        );
+        encryption.columns.insert(
+            "email".to_string(),
+            EncryptedColumn::new("pii").with_mask("***"),
+        );
+        encryption
+    }
+
+    #[test]
+    fn test_cipher_round_trip() {
+        let randomized = ColumnCipher::new("email", &[7u8; 32], false).unwrap();
+        let a = randomized.encrypt("ada@example.com").unwrap();
+        let b = randomized.encrypt("ada@example.com").unwrap();
+        assert!(a.starts_with(CIPHERTEXT_PREFIX));
+        assert_ne!(a, b);
+        assert_eq!(randomized.decrypt(&a).unwrap(), "ada@example.com");
+        assert_eq!(randomized.decrypt("plain").unwrap(), "plain");
+
+        let deterministic = ColumnCipher::new("ssn", &[7u8; 32], true).unwrap();
+        assert_eq!(
+            deterministic.encrypt("123").unwrap(),
+            deterministic.encrypt("123").unwrap()
+        );
+
+        // Another column's key, or another master key, doesn't open it
+        assert!(deterministic.decrypt(&a).is_err());
+        let other = ColumnCipher::new("email", &[8u8; 32], false).unwrap();
+        assert!(other.decrypt(&a).is_err());
+    }
+
+    #[test]
+    fn test_batches() {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("ssn", DataType::Utf8, true),
+            Field::new("email", DataType::Utf8, true),
+            Field::new("name", DataType::Utf8, true),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema,
+            vec![
+                Arc::new(StringArray::from(vec![Some("123"), None])) as ArrayRef,
+                Arc::new(StringArray::from(vec![Some("a@x"), Some("b@x")])) as ArrayRef,
+                Arc::new(StringArray::from(vec![Some("ada"), Some("bob")])) as ArrayRef,
+            ],
+        )
+        .unwrap();
+        let encryption = encryption();
+        assert!(encryption.encrypt_batch(batch.clone(), None).is_err());
+
+        let stored = encryption
+            .encrypt_batch(batch.clone(), Some(&keys()))
+            .unwrap();
+        assert!(stored
+            .column(0)
+            .as_string::<i32>()
+            .value(0)
+            .starts_with(CIPHERTEXT_PREFIX));
+        assert!(stored.column(0).is_null(1));
+        assert_eq!(stored.column(2), batch.column(2));
+
+        let read = encryption
+            .decrypt_batch(stored.clone(), Some(&keys()), true)
+            .unwrap();
+        assert_eq!(read, batch);
+
+        let masked = encryption
+            .decrypt_batch(stored.clone(), None, true)
+            .unwrap();
+        assert_eq!(masked.column(0), stored.column(0));
+        assert_eq!(masked.column(1).as_string::<i32>().value(0), "***");
+        assert_eq!(masked.column(2), batch.column(2));
+    }
+
+    #[test]
+    fn test_sql_rewrite() {
+        let encryption = encryption();
+        let keys = keys();
+        let cipher = ColumnCipher::new("ssn", &[7u8; 32], true).unwrap();
+        let ct = cipher.encrypt("123").unwrap();
+
+        let sql = encryption
+            .encrypt_sql("SELECT name FROM data WHERE ssn = '123'", Some(&keys))
+            .unwrap();
+        assert!(sql.contains(&ct), "{}", sql);
+        let sql = encryption
+            .encrypt_sql(
+                "SELECT name FROM data WHERE SSN IN ('123', '456')",
+                Some(&keys),
+            )
+            .unwrap();
+        assert!(sql.contains(&ct), "{}", sql);
+        let predicate = encryption
+            .encrypt_predicate("'123' <> data.ssn", Some(&keys))
+            .unwrap();
+        assert!(predicate.contains(&ct), "{}", predicate);
+
+        let untouched = "SELECT * FROM data WHERE name = 'ada'";
+        assert_eq!(
+            encryption.encrypt_sql(untouched, Some(&keys)).unwrap(),
+            untouched
+        );
+
+        for sql in [
+            "SELECT * FROM data WHERE ssn > '100'",
+            "SELECT * FROM data WHERE ssn BETWEEN '1' AND '2'",
+            "SELECT * FROM data WHERE ssn LIKE '12%'",
+            "SELECT * FROM data WHERE email = 'a@x'",
+        ] {
+            let err = encryption.encrypt_sql(sql, Some(&keys)).unwrap_err();
+            assert!(matches!(err, Error::InvalidOperation(_)), "{}", sql);
+        }
+        assert!(encryption
+            .encrypt_sql("SELECT * FROM data WHERE ssn = '123'", None)
+            .is_err());
+    }
+
+    #[test]
+    fn test_assignments() {
+        let encryption = encryption();
+        let keys = keys();
+        let value = encryption
+            .encrypt_assignment("ssn", "'123'", Some(&keys))
+            .unwrap();
+        let cipher = ColumnCipher::new("ssn", &[7u8; 32], true).unwrap();
+        assert_eq!(value, format!("'{}'", cipher.encrypt("123").unwrap()));
+        assert_eq!(
+            encryption
+                .encrypt_assignment("ssn", "NULL", Some(&keys))
+                .unwrap(),
+            "NULL"
+        );
+        assert_eq!(
+            encryption
+                .encrypt_assignment("name", "upper(name)", None)
+                .unwrap(),
+            "upper(name)"
+        );
+        assert!(encryption
+            .encrypt_assignment("ssn", "upper(ssn)", Some(&keys))
+            .is_err());
+        assert_eq!(
+            encryption
+                .encrypt_assignment("ssn", "source.ssn", None)
+                .unwrap(),
+            "source.ssn"
+        );
+        assert!(encryption
+            .encrypt_assignment("ssn", "source.name", None)
+            .is_err());
+    }
+}
@@ -0,0 +1,238 @@
+//! Column masking for roles that may not see sensitive values
+//!
+//! A role can mask columns it reads (`Role::with_masked_column`): those
+//! columns come back as NULL, as the hex SHA-256 of the value, or with all but
+//! their last few characters replaced by `*`. Users whose roles have
+//! `Permission::Unmask` see every column in the clear.
+//!
+//! Masking happens where a query reads the table, as part of the scan's
+//! projection, so aliases, functions and filters all see the masked value:
+//! `WHERE ssn = '123-45-6789'` matches nothing for a masked user, rather than
+//! revealing which row holds the value. Hashes and partial masks apply to
+//! string columns only; masked columns of other types read as NULL, so the
+//! table keeps its schema.
+
+use crate::Result;
+use arrow::array::{new_null_array, Array, ArrayRef, AsArray, RecordBatch, StringArray};
+use arrow::datatypes::{DataType, Field, Schema, SchemaRef};
+use datafusion::common::ScalarValue;
+use datafusion::dataframe::DataFrame;
+use datafusion::functions::expr_fn::{character_length, concat, encode, repeat, right, sha256};
+use datafusion::logical_expr::{cast, ident, lit, when, Expr};
+use ring::digest::{digest, SHA256};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::Arc;
+
+/// How a masked column reads
+///
+/// Ordered from least to most revealing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum ColumnMask {
+    /// Every value reads as NULL
+    Null,
+    /// Hex SHA-256 of the value, so equal values still group and join alike
+    Hash,
+    /// Only the last `visible` characters, the rest replaced by `*`
+    Partial { visible: usize },
+}
+
+impl ColumnMask {
+    /// Mask one value of a string column
+    pub fn mask_str(&self, value: &str) -> Option<String> {
+        match self {
+            ColumnMask::Null => None,
+            ColumnMask::Hash => {
+                let hash = digest(&SHA256, value.as_bytes());
+                let mut hex = String::with_capacity(hash.as_ref().len() * 2);
+                for byte in hash.as_ref() {
+                    let _ = write!(hex, "{:02x}", byte);
+                }
+                Some(hex)
+            }
+            ColumnMask::Partial { visible } => {
+                let len = value.chars().count();
+                let hidden = if len > *visible { len - visible } else { len };
+                Some(
+                    std::iter::repeat_n('*', hidden)
+                        .chain(value.chars().skip(hidden))
+                        .collect(),
+                )
+            }
+        }
+    }
+
+    /// Masked value of `column`, of type `data_type`, in a query
+    fn expr(&self, column: &str, data_type: &DataType) -> Result<Expr> {
+        let value = ident(column);
+        let masked = match self {
+            _ if !is_string(data_type) => lit(ScalarValue::Null),
+            ColumnMask::Null => lit(ScalarValue::Null),
+            ColumnMask::Hash => encode(sha256(value), lit("hex")),
+            ColumnMask::Partial { visible } => {
+                let visible = lit(*visible as i64);
+                when(
+                    character_length(value.clone()).gt(visible.clone()),
+                    concat(vec![
+                        repeat(lit("*"), character_length(value.clone()) - visible.clone()),
+                        right(value.clone(), visible),
+                    ]),
+                )
+                .otherwise(repeat(lit("*"), character_length(value)))?
+            }
+        };
+        Ok(cast(masked, data_type.clone()).alias(column))
+    }
+}
+
+fn is_string(data_type: &DataType) -> bool {
+    matches!(
+        data_type,
+        DataType::Utf8 | DataType::LargeUtf8 | DataType::Utf8View
+    )
+}
+
+/// Columns of one table a user reads masked, by name
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ColumnMasks {
+    pub columns: BTreeMap<String, ColumnMask>,
+}
+
+impl ColumnMasks {
+    /// Whether every column reads in the clear
+    pub fn is_empty(&self) -> bool {
+        self.columns.is_empty()
+    }
+
+    /// Mask of `column`, matched exactly or else ignoring case
+    pub fn mask_of(&self, column: &str) -> Option<ColumnMask> {
+        self.columns.get(column).copied().or_else(|| {
+            self.columns
+                .iter()
+                .find(|(name, _)| name.eq_ignore_ascii_case(column))
+                .map(|(_, mask)| *mask)
+        })
+    }
+
+    /// Schema of rows read through the masks: masked columns may be NULL
+    pub fn schema(&self, table: &Schema) -> SchemaRef {
+        let fields: Vec<Field> = table
+            .fields()
+            .iter()
+            .map(|field| match self.mask_of(field.name()) {
+                Some(_) => field.as_ref().clone().with_nullable(true),
+                None => field.as_ref().clone(),
+            })
+            .collect();
+        Arc::new(Schema::new_with_metadata(fields, table.metadata().clone()))
+    }
+
+    /// Project `table`, a scan of the table, through the masks
+    pub fn apply(&self, table: DataFrame) -> Result<DataFrame> {
+        if self.is_empty() {
+            return Ok(table);
+        }
+        let projection = table
+            .schema()
+            .fields()
+            .iter()
+            .map(|field| match self.mask_of(field.name()) {
+                Some(mask) => mask.expr(field.name(), field.data_type()),
+                None => Ok(ident(field.name())),
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Ok(table.select(projection)?)
+    }
+
+    /// Mask the columns of `batch`, rows read from the table
+    pub fn mask_batch(&self, batch: &RecordBatch) -> Result<RecordBatch> {
+        if self.is_empty() {
+            return Ok(batch.clone());
+        }
+        let schema = self.schema(&batch.schema());
+        let columns = schema
+            .fields()
+            .iter()
+            .zip(batch.columns())
+            .map(|(field, column)| match self.mask_of(field.name()) {
+                Some(mask) => mask_array(mask, column),
+                None => Ok(column.clone()),
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Ok(RecordBatch::try_new(schema, columns)?)
+    }
+}
+
+fn mask_array(mask: ColumnMask, column: &ArrayRef) -> Result<ArrayRef> {
+    let data_type = column.data_type();
+    if mask == ColumnMask::Null || !is_string(data_type) {
+        return Ok(new_null_array(data_type, column.len()));
+    }
+    let strings = arrow::compute::cast(column, &DataType::Utf8)?;
+    let masked: StringArray = strings
+        .as_string::<i32>()
+        .iter()
+        .map(|value| value.and_then(|v| mask.mask_str(v)))
+        .collect();
+    Ok(arrow::compute::cast(
+        &(Arc::new(masked) as ArrayRef),
+        data_type,
+    )?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow::array::Int64Array;
+
+    #[test]
+    fn test_mask_values() {
+        assert_eq!(ColumnMask::Null.mask_str("secret"), None);
+        assert_eq!(
+            ColumnMask::Hash.mask_str("abc").unwrap(),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+        let last4 = ColumnMask::Partial { visible: 4 };
+        assert_eq!(last4.mask_str("123-45-6789").unwrap(), "*******6789");
+        assert_eq!(last4.mask_str("abc").unwrap(), "***");
+        assert_eq!(last4.mask_str("").unwrap(), "");
+        assert!(ColumnMask::Null < ColumnMask::Hash);
+        assert!(ColumnMask::Hash < last4);
+    }
+
+    #[test]
+    fn test_mask_batch() {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("name", DataType::Utf8, true),
+            Field::new("ssn", DataType::Utf8, true),
+            Field::new("salary", DataType::Int64, false),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![
+                Arc::new(StringArray::from(vec![Some("Ann"), Some("Bob")])),
+                Arc::new(StringArray::from(vec![Some("123-45-6789"), None])),
+                Arc::new(Int64Array::from(vec![100, 200])),
+            ],
+        )
+        .unwrap();
+
+        let mut masks = ColumnMasks::default();
+        masks
+            .columns
+            .insert("SSN".to_string(), ColumnMask::Partial { visible: 4 });
+        masks.columns.insert("salary".to_string(), ColumnMask::Hash);
+        let masked = masks.mask_batch(&batch).unwrap();
+
+        assert!(masked.schema().field(2).is_nullable());
+        assert_eq!(masked.column(0), batch.column(0));
+        let ssn = masked.column(1).as_string::<i32>();
+        assert_eq!(ssn.value(0), "*******6789");
+        assert!(ssn.is_null(1));
+        // Non-string columns can't hold a hash and read as NULL
+        assert_eq!(masked.column(2).null_count(), 2);
+
+        assert_eq!(ColumnMasks::default().mask_batch(&batch).unwrap(), batch);
+    }
+}
@@ -0,0 +1,221 @@
+//! Pluggable storage for users and roles
+//!
+//! Authentication, role grants and quotas read and write users through a
+//! `CredentialStore`, so accounts can live outside the table: in a SQLite
+//! database, a Delta table, or a directory service. The default,
+//! `FileCredentialStore`, keeps them in `_metadata/users.json` as before.
+//!
+//! Roles a store lists are added to the built-in `admin`, `read` and `write`
+//! roles when a table is opened with it, replacing built-in roles of the
+//! same name.
+//!
+//! A table handle looks users up through `CachedCredentialStore`, which
+//! keeps each user after the first lookup. Changes made through the handle,
+//! like revoking a role, update the cached user as they're written; changes
+//! made in the backing system directly are seen once the user is
+//! invalidated or the table reopened.
+
+use super::auth::{AuthContext, User, UserStore};
+use super::rbac::Role;
+use crate::{Error, Result};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+
+/// Backend storing user accounts and role definitions
+pub trait CredentialStore: Send + Sync {
+    /// The user named `username`, if there is one
+    fn load_user(&self, username: &str) -> Result<Option<User>>;
+
+    /// Create `user`, or replace the user with the same name
+    fn save_user(&self, user: &User) -> Result<()>;
+
+    /// Roles defined by the store, beyond the built-in ones
+    fn list_roles(&self) -> Result<Vec<Role>>;
+}
+
+/// Check `password` for `username` against `store`
+///
+/// An unknown user and a wrong password fail the same way.
+pub fn authenticate(
+    store: &dyn CredentialStore,
+    username: &str,
+    password: &str,
+) -> Result<AuthContext> {
+    match store.load_user(username)? {
+        Some(user) if user.verify_password(password) => {
+            Ok(AuthContext::authenticated(user.username, user.roles))
+        }
+        _ => Err(Error::AuthenticationRequired(
+            "invalid credentials".to_string(),
+        )),
+    }
+}
+
+/// Users in a JSON file, the `UserStore` format; no roles of its own
+#[derive(Debug, Clone)]
+pub struct FileCredentialStore {
+    path: PathBuf,
+}
+
+impl FileCredentialStore {
+    /// Store users in the file at `path`, created on the first save
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+}
+
+impl CredentialStore for FileCredentialStore {
+    fn load_user(&self, username: &str) -> Result<Option<User>> {
+        Ok(UserStore::load(&self.path)?.get_user(username).cloned())
+    }
+
+    fn save_user(&self, user: &User) -> Result<()> {
+        let mut store = UserStore::load(&self.path)?;
+        store.put_user(user.clone());
+        store.save(&self.path)
+    }
+
+    fn list_roles(&self) -> Result<Vec<Role>> {
+        Ok(Vec::new())
+    }
+}
+
+/// `CredentialStore` that remembers users it has looked up
+pub struct CachedCredentialStore {
+    inner: Arc<dyn CredentialStore>,
+    users: Mutex<HashMap<String, Option<User>>>,
+    /// Held across read-modify-write changes to a user
+    writes: Mutex<()>,
+}
+
+impl CachedCredentialStore {
+    /// Cache lookups in `inner`
+    pub fn new(inner: Arc<dyn CredentialStore>) -> Self {
+        Self {
+            inner,
+            users: Mutex::new(HashMap::new()),
+            writes: Mutex::new(()),
+        }
+    }
+
+    /// Look `username` up in the backing store again next time
+    pub fn invalidate(&self, username: &str) {
+        self.users.lock().unwrap().remove(username);
+    }
+
+    /// Look every user up in the backing store again
+    pub fn invalidate_all(&self) {
+        self.users.lock().unwrap().clear();
+    }
+
+    /// Save `user`, unless the backing store already has a user by the name
+    pub fn add_user(&self, user: &User) -> Result<()> {
+        let _writes = self.writes.lock().unwrap();
+        self.invalidate(&user.username);
+        if self.load_user(&user.username)?.is_some() {
+            return Err(Error::Other(format!(
+                "User already exists: {}",
+                user.username
+            )));
+        }
+        self.save_user(user)
+    }
+
+    /// Load `username`, change them with `update`, and save them
+    ///
+    /// The user is read fresh from the backing store, not the cache.
+    pub fn update_user(&self, username: &str, update: impl FnOnce(&mut User)) -> Result<()> {
+        let _writes = self.writes.lock().unwrap();
+        self.invalidate(username);
+        let mut user = self
+            .load_user(username)?
+            .ok_or_else(|| Error::Other(format!("User not found: {}", username)))?;
+        update(&mut user);
+        self.save_user(&user)
+    }
+}
+
+impl CredentialStore for CachedCredentialStore {
+    fn load_user(&self, username: &str) -> Result<Option<User>> {
+        if let Some(user) = self.users.lock().unwrap().get(username) {
+            return Ok(user.clone());
+        }
+        let user = self.inner.load_user(username)?;
+        self.users
+            .lock()
+            .unwrap()
+            .insert(username.to_string(), user.clone());
+        Ok(user)
+    }
+
+    fn save_user(&self, user: &User) -> Result<()> {
+        // A failed save may or may not have reached the backend
+        let result = self.inner.save_user(user);
+        let mut users = self.users.lock().unwrap();
+        match &result {
+            Ok(()) => users.insert(user.username.clone(), Some(user.clone())),
+            Err(_) => users.remove(&user.username),
+        };
+        result
+    }
+
+    fn list_roles(&self) -> Result<Vec<Role>> {
+        self.inner.list_roles()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_file_store_round_trip() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let store = FileCredentialStore::new(temp_dir.path().join("users.json"));
+        assert!(store.load_user("alice").unwrap().is_none());
+
+        let mut alice = User::new("alice".to_string(), "secret", vec!["read".to_string()]).unwrap();
+        store.save_user(&alice).unwrap();
+        alice.roles.push("write".to_string());
+        store.save_user(&alice).unwrap();
+
+        let loaded = store.load_user("alice").unwrap().unwrap();
+        assert_eq!(loaded.roles, vec!["read", "write"]);
+        assert!(authenticate(&store, "alice", "secret").is_ok());
+        assert!(authenticate(&store, "alice", "wrong").is_err());
+        assert!(authenticate(&store, "bob", "secret").is_err());
+    }
+
+    #[test]
+    fn test_cache_updates_on_save() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let path = temp_dir.path().join("users.json");
+        let cached = CachedCredentialStore::new(Arc::new(FileCredentialStore::new(&path)));
+        let alice = User::new("alice".to_string(), "secret", vec!["read".to_string()]).unwrap();
+        cached.save_user(&alice).unwrap();
+
+        cached
+            .update_user("alice", |user| user.roles = vec!["write".to_string()])
+            .unwrap();
+        assert_eq!(
+            cached.load_user("alice").unwrap().unwrap().roles,
+            vec!["write"]
+        );
+
+        // Changes behind the cache's back show after invalidating
+        let backing = FileCredentialStore::new(&path);
+        let mut changed = backing.load_user("alice").unwrap().unwrap();
+        changed.roles = vec!["admin".to_string()];
+        backing.save_user(&changed).unwrap();
+        assert_eq!(
+            cached.load_user("alice").unwrap().unwrap().roles,
+            vec!["write"]
+        );
+        cached.invalidate("alice");
+        assert_eq!(
+            cached.load_user("alice").unwrap().unwrap().roles,
+            vec!["admin"]
+        );
+    }
+}
@@ -2,14 +2,29 @@
 //!
 //! Provides enterprise-grade security features:
 //! - User authentication with bcrypt password hashing
+//! - Pluggable storage for users and roles
 //! - Role-based access control (RBAC)
 //! - Audit logging
 //! - Permission enforcement
+//! - Redaction of SQL literals in logs
+//! - Per-user storage quotas
+//! - Per-column encryption of PII, decrypted by permission
+//! - Per-role masking of sensitive columns
 
 pub mod audit;
 pub mod auth;
+pub mod column_encryption;
+pub mod column_masking;
+pub mod credential_store;
+pub mod quota;
 pub mod rbac;
+pub mod redaction;
 
-pub use audit::{AuditEntry, AuditLog, AuditLogger};
+pub use audit::{AuditEntry, AuditLog, AuditLogger, AuditSubscription};
 pub use auth::{AuthContext, Credentials, User, UserStore};
+pub use column_encryption::{ColumnEncryption, ColumnKeys, EncryptedColumn};
+pub use column_masking::{ColumnMask, ColumnMasks};
+pub use credential_store::{CachedCredentialStore, CredentialStore, FileCredentialStore};
+pub use quota::{Quota, QuotaGuard, QuotaStatus, QuotaUsage};
 pub use rbac::{Permission, Role, RoleManager};
+pub use redaction::LogRedaction;
@@ -0,0 +1,295 @@
+//! Per-user quotas on stored bytes and rows
+//!
+//! Usage is charged as users write: the change a commit makes to the table's
+//! live rows and data file bytes, read from the Delta log, is added to the
+//! usage of the user who made it. A delete shrinks the table and so gives
+//! usage back, to the user who deletes; usage never drops below zero. Usage
+//! is kept in `_metadata/quota_usage.json`, next to the user store.
+//!
+//! A user's limit is their own quota, if they have one, or else the most
+//! generous quota among their roles. Users with neither are unlimited, but
+//! their usage is still tracked.
+
+use crate::delta_lake::read_delta_log_at;
+use crate::{Error, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+/// Held while a usage file is read, changed and written back
+static USAGE_LOCK: std::sync::Mutex<()> = std::sync::Mutex::new(());
+
+/// Most a user may store, `None` for no limit
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Quota {
+    /// Bytes of Parquet data files
+    pub max_bytes: Option<u64>,
+    pub max_rows: Option<u64>,
+}
+
+impl Quota {
+    /// No limit on either
+    pub fn unlimited() -> Self {
+        Self::default()
+    }
+
+    /// Limit the bytes of data files
+    pub fn with_max_bytes(mut self, bytes: u64) -> Self {
+        self.max_bytes = Some(bytes);
+        self
+    }
+
+    /// Limit the rows
+    pub fn with_max_rows(mut self, rows: u64) -> Self {
+        self.max_rows = Some(rows);
+        self
+    }
+
+    pub fn is_unlimited(&self) -> bool {
+        self.max_bytes.is_none() && self.max_rows.is_none()
+    }
+
+    /// The higher of each limit, for a user with roles of both quotas
+    pub fn most_generous(self, other: Quota) -> Quota {
+        let higher = |a: Option<u64>, b: Option<u64>| a.zip(b).map(|(a, b)| a.max(b));
+        Quota {
+            max_bytes: higher(self.max_bytes, other.max_bytes),
+            max_rows: higher(self.max_rows, other.max_rows),
+        }
+    }
+}
+
+/// Bytes and rows charged to a user
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct QuotaUsage {
+    pub bytes: u64,
+    pub rows: u64,
+}
+
+/// Change a write made to the table's bytes and rows
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct UsageDelta {
+    pub bytes: i64,
+    pub rows: i64,
+}
+
+/// A user's usage against their limit
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QuotaStatus {
+    pub username: String,
+    pub usage: QuotaUsage,
+    pub limit: Quota,
+}
+
+impl QuotaStatus {
+    /// Bytes the user can still write, `None` without a byte limit
+    pub fn bytes_remaining(&self) -> Option<u64> {
+        self.limit
+            .max_bytes
+            .map(|max| max.saturating_sub(self.usage.bytes))
+    }
+
+    /// Rows the user can still write, `None` without a row limit
+    pub fn rows_remaining(&self) -> Option<u64> {
+        self.limit
+            .max_rows
+            .map(|max| max.saturating_sub(self.usage.rows))
+    }
+}
+
+/// Usage of every user of a table, as stored on disk
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct UsageFile {
+    users: HashMap<String, QuotaUsage>,
+}
+
+impl UsageFile {
+    fn load(path: &Path) -> Result<Self> {
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let content = std::fs::read_to_string(path)?;
+        Ok(serde_json::from_str(&content)?)
+    }
+
+    fn save(&self, path: &Path) -> Result<()> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let json = serde_json::to_string_pretty(self)?;
+
+        // Atomic write: .tmp → rename
+        let tmp_path = path.with_extension("tmp");
+        std::fs::write(&tmp_path, json)?;
+        std::fs::rename(tmp_path, path)?;
+        Ok(())
+    }
+}
+
+/// File a table's quota usage is kept in
+pub fn usage_path(base_path: &Path) -> PathBuf {
+    base_path.join("_metadata").join("quota_usage.json")
+}
+
+/// Change in the table's bytes and rows from version `from` (`None` for an
+/// empty table) to version `to`
+pub fn usage_between(base_path: &Path, from: Option<u64>, to: u64) -> Result<UsageDelta> {
+    let before = match from {
+        Some(version) => table_usage(base_path, version)?,
+        None => QuotaUsage::default(),
+    };
+    let after = table_usage(base_path, to)?;
+    Ok(UsageDelta {
+        bytes: after.bytes as i64 - before.bytes as i64,
+        rows: after.rows as i64 - before.rows as i64,
+    })
+}
+
+/// Bytes and rows of the files live at `version`
+fn table_usage(base_path: &Path, version: u64) -> Result<QuotaUsage> {
+    let log = read_delta_log_at(base_path, version)?;
+    let mut usage = QuotaUsage::default();
+    for file in &log.files {
+        usage.bytes += file.size;
+        usage.rows += file.num_records(base_path)?;
+    }
+    Ok(usage)
+}
+
+/// Checks one user's writes to a table against their quota, and charges them
+#[derive(Debug, Clone)]
+pub struct QuotaGuard {
+    base_path: PathBuf,
+    username: String,
+    limit: Quota,
+}
+
+impl QuotaGuard {
+    pub fn new(base_path: impl Into<PathBuf>, username: impl Into<String>, limit: Quota) -> Self {
+        Self {
+            base_path: base_path.into(),
+            username: username.into(),
+            limit,
+        }
+    }
+
+    pub fn limit(&self) -> Quota {
+        self.limit
+    }
+
+    /// The user's current usage and limit
+    pub fn status(&self) -> Result<QuotaStatus> {
+        let usage = UsageFile::load(&usage_path(&self.base_path))?
+            .users
+            .remove(&self.username)
+            .unwrap_or_default();
+        Ok(QuotaStatus {
+            username: self.username.clone(),
+            usage,
+            limit: self.limit,
+        })
+    }
+
+    /// Fail with `Error::QuotaExceeded` if `bytes` and `rows` more would take
+    /// the user past their limit
+    ///
+    /// Reaching the limit exactly is allowed.
+    pub fn check(&self, bytes: u64, rows: u64) -> Result<()> {
+        if self.limit.is_unlimited() {
+            return Ok(());
+        }
+        let usage = self.status()?.usage;
+        let checks = [
+            ("bytes", usage.bytes, bytes, self.limit.max_bytes),
+            ("rows", usage.rows, rows, self.limit.max_rows),
+        ];
+        for (resource, used, requested, limit) in checks {
+            match limit {
+                Some(limit) if requested > 0 && used.saturating_add(requested) > limit => {
+                    return Err(Error::QuotaExceeded {
+                        username: self.username.clone(),
+                        resource: resource.to_string(),
+                        used,
+                        requested,
+                        limit,
+                    });
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    /// Charge the user with what the commits after version `from` up to
+    /// `to` changed, returning their new usage
+    pub fn charge(&self, from: Option<u64>, to: u64) -> Result<QuotaUsage> {
+        let delta = usage_between(&self.base_path, from, to)?;
+        let path = usage_path(&self.base_path);
+
+        let _lock = USAGE_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        let mut file = UsageFile::load(&path)?;
+        let usage = file.users.entry(self.username.clone()).or_default();
+        usage.bytes = usage.bytes.saturating_add_signed(delta.bytes);
+        usage.rows = usage.rows.saturating_add_signed(delta.rows);
+        let usage = *usage;
+        file.save(&path)?;
+        Ok(usage)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_most_generous() {
+        let small = Quota::unlimited().with_max_rows(10).with_max_bytes(100);
+        let large_rows = Quota::unlimited().with_max_rows(50);
+
+        // A limit one quota doesn't set is unlimited
+        let combined = small.most_generous(large_rows);
+        assert_eq!(combined.max_rows, Some(50));
+        assert_eq!(combined.max_bytes, None);
+        assert_eq!(small.most_generous(small), small);
+    }
+
+    #[test]
+    fn test_check_against_stored_usage() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = usage_path(temp_dir.path());
+        let mut file = UsageFile::default();
+        file.users
+            .insert("alice".to_string(), QuotaUsage { bytes: 0, rows: 8 });
+        file.save(&path).unwrap();
+
+        let guard = QuotaGuard::new(
+            temp_dir.path(),
+            "alice",
+            Quota::unlimited().with_max_rows(10),
+        );
+        let status = guard.status().unwrap();
+        assert_eq!(status.usage.rows, 8);
+        assert_eq!(status.rows_remaining(), Some(2));
+        assert_eq!(status.bytes_remaining(), None);
+
+        assert!(guard.check(1_000_000, 2).is_ok());
+        match guard.check(0, 3) {
+            Err(Error::QuotaExceeded {
+                resource,
+                used,
+                requested,
+                limit,
+                ..
+            }) => assert_eq!(
+                (resource.as_str(), used, requested, limit),
+                ("rows", 8, 3, 10)
+            ),
+            other => panic!("expected QuotaExceeded, got {:?}", other),
+        }
+
+        // Other users start from nothing
+        let bob = QuotaGuard::new(temp_dir.path(), "bob", Quota::unlimited().with_max_rows(10));
+        assert!(bob.check(0, 10).is_ok());
+    }
+}
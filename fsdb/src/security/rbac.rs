@@ -1,6 +1,9 @@
 //! Role-Based Access Control (RBAC)
 
+use super::column_masking::{ColumnMask, ColumnMasks};
+use super::quota::Quota;
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 
 /// Permission types
 #[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
@@ -11,6 +14,10 @@ pub enum Permission {
     Admin,
     Backup,
     Restore,
+    /// Read encrypted columns in the clear
+    Decrypt,
+    /// Read masked columns in the clear
+    Unmask,
 }
 
 /// Role definition
@@ -18,18 +25,80 @@ pub enum Permission {
 pub struct Role {
     pub name: String,
     pub permissions: Vec<Permission>,
+    /// Tables the role's permissions apply to (None = all tables)
+    #[serde(default)]
+    pub tables: Option<Vec<String>>,
+    /// Storage quota for users with the role (None = unlimited)
+    #[serde(default)]
+    pub quota: Option<Quota>,
+    /// Columns users with the role read masked, by `column` or `table.column`
+    #[serde(default)]
+    pub masks: BTreeMap<String, ColumnMask>,
 }
 
 impl Role {
     /// Create a new role
     pub fn new(name: String, permissions: Vec<Permission>) -> Self {
-        Self { name, permissions }
+        Self {
+            name,
+            permissions,
+            tables: None,
+            quota: None,
+            masks: BTreeMap::new(),
+        }
+    }
+
+    /// Limit the role to the given tables
+    pub fn with_tables(mut self, tables: Vec<String>) -> Self {
+        self.tables = Some(tables);
+        self
+    }
+
+    /// Limit what users with the role may store
+    pub fn with_quota(mut self, quota: Quota) -> Self {
+        self.quota = Some(quota);
+        self
+    }
+
+    /// Mask `column`, of every table or of one as `table.column`
+    pub fn with_masked_column(mut self, column: impl Into<String>, mask: ColumnMask) -> Self {
+        self.masks.insert(column.into(), mask);
+        self
+    }
+
+    /// Columns of `table` the role reads masked
+    ///
+    /// A mask given for the table overrides one for every table.
+    pub fn masks_for(&self, table: &str) -> BTreeMap<String, ColumnMask> {
+        let mut masks = BTreeMap::new();
+        for (key, mask) in &self.masks {
+            if !key.contains('.') {
+                masks.insert(key.clone(), *mask);
+            }
+        }
+        for (key, mask) in &self.masks {
+            if let Some((t, column)) = key.split_once('.') {
+                if t == table {
+                    masks.insert(column.to_string(), *mask);
+                }
+            }
+        }
+        masks
     }
 
     /// Check if role has a specific permission
     pub fn has_permission(&self, permission: &Permission) -> bool {
         self.permissions.contains(permission)
     }
+
+    /// Check if role can read `table`
+    pub fn can_read_table(&self, table: &str) -> bool {
+        self.has_permission(&Permission::Read)
+            && self
+                .tables
+                .as_ref()
+                .is_none_or(|tables| tables.iter().any(|t| t == table))
+    }
 }
 
 /// Role manager
@@ -60,6 +129,8 @@ impl RoleManager {
                     Permission::Admin,
                     Permission::Backup,
                     Permission::Restore,
+                    Permission::Decrypt,
+                    Permission::Unmask,
                 ],
             ),
         );
@@ -89,6 +160,63 @@ impl RoleManager {
         })
     }
 
+    /// Check if user roles can read `table`
+    pub fn can_read_table(&self, user_roles: &[String], table: &str) -> bool {
+        user_roles.iter().any(|role_name| {
+            self.roles
+                .get(role_name)
+                .is_some_and(|role| role.can_read_table(table))
+        })
+    }
+
+    /// Columns of `table` users with `user_roles` read masked
+    ///
+    /// Only roles that can read the table count. A column is masked if every
+    /// one of them masks it, with the most revealing of their masks, and none
+    /// has `Permission::Unmask`.
+    pub fn column_masks(&self, user_roles: &[String], table: &str) -> ColumnMasks {
+        let readers: Vec<&Role> = user_roles
+            .iter()
+            .filter_map(|role_name| self.roles.get(role_name))
+            .filter(|role| role.can_read_table(table))
+            .collect();
+        if readers
+            .iter()
+            .any(|role| role.has_permission(&Permission::Unmask))
+        {
+            return ColumnMasks::default();
+        }
+        let columns = readers
+            .iter()
+            .map(|role| role.masks_for(table))
+            .reduce(|masked, other| {
+                masked
+                    .into_iter()
+                    .filter_map(|(column, mask)| {
+                        other.get(&column).map(|other| (column, mask.max(*other)))
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+        ColumnMasks { columns }
+    }
+
+    /// Most generous quota among user roles that have one
+    ///
+    /// Unlimited if none of them do.
+    pub fn quota_for(&self, user_roles: &[String]) -> Quota {
+        user_roles
+            .iter()
+            .filter_map(|role_name| self.roles.get(role_name)?.quota)
+            .reduce(Quota::most_generous)
+            .unwrap_or_default()
+    }
+
+    /// Add a role, replacing any role with the same name
+    pub fn add_role(&mut self, role: Role) {
+        self.roles.insert(role.name.clone(), role);
+    }
+
     /// Get role by name
     pub fn get_role(&self, name: &str) -> Option<&Role> {
         self.roles.get(name)
@@ -133,4 +261,90 @@ mod tests {
             &Permission::Delete
         ));
     }
+
+    #[test]
+    fn test_table_scoped_roles() {
+        let mut manager = RoleManager::new();
+        manager.add_role(
+            Role::new("sales".to_string(), vec![Permission::Read])
+                .with_tables(vec!["orders".to_string()]),
+        );
+
+        let sales = ["sales".to_string()];
+        assert!(manager.can_read_table(&sales, "orders"));
+        assert!(!manager.can_read_table(&sales, "payroll"));
+
+        // Unscoped roles read every table, and only with Read permission
+        assert!(manager.can_read_table(&["read".to_string()], "payroll"));
+        assert!(!manager.can_read_table(&["write".to_string()], "orders"));
+        assert!(!manager.can_read_table(&["unknown".to_string()], "orders"));
+    }
+
+    #[test]
+    fn test_role_quotas() {
+        let mut manager = RoleManager::new();
+        manager.add_role(
+            Role::new("tenant".to_string(), vec![Permission::Write])
+                .with_quota(Quota::unlimited().with_max_rows(100)),
+        );
+        manager.add_role(
+            Role::new("premium".to_string(), vec![Permission::Write]).with_quota(
+                Quota::unlimited()
+                    .with_max_rows(1000)
+                    .with_max_bytes(1 << 20),
+            ),
+        );
+
+        let tenant = ["tenant".to_string(), "read".to_string()];
+        assert_eq!(manager.quota_for(&tenant).max_rows, Some(100));
+
+        // Roles without a quota don't lift it; a limit one role leaves out does
+        let both = ["tenant".to_string(), "premium".to_string()];
+        assert_eq!(manager.quota_for(&both).max_rows, Some(1000));
+        assert_eq!(manager.quota_for(&both).max_bytes, None);
+        assert!(manager.quota_for(&["admin".to_string()]).is_unlimited());
+    }
+
+    #[test]
+    fn test_column_masks() {
+        let mut manager = RoleManager::new();
+        manager.add_role(
+            Role::new("support".to_string(), vec![Permission::Read])
+                .with_masked_column("ssn", ColumnMask::Null)
+                .with_masked_column("email", ColumnMask::Hash)
+                .with_masked_column("orders.card", ColumnMask::Partial { visible: 4 }),
+        );
+        manager.add_role(
+            Role::new("billing".to_string(), vec![Permission::Read])
+                .with_masked_column("ssn", ColumnMask::Hash),
+        );
+
+        let support = ["support".to_string()];
+        let masks = manager.column_masks(&support, "orders");
+        assert_eq!(masks.columns.len(), 3);
+        assert_eq!(
+            masks.mask_of("card"),
+            Some(ColumnMask::Partial { visible: 4 })
+        );
+        assert_eq!(
+            manager.column_masks(&support, "people").mask_of("card"),
+            None
+        );
+
+        // A column one role leaves alone is in the clear; otherwise the most
+        // revealing mask wins
+        let both = ["support".to_string(), "billing".to_string()];
+        let masks = manager.column_masks(&both, "people");
+        assert_eq!(masks.mask_of("email"), None);
+        assert_eq!(masks.mask_of("ssn"), Some(ColumnMask::Hash));
+
+        // Unmask, or a role without masks, reads everything in the clear
+        let admin = ["support".to_string(), "admin".to_string()];
+        assert!(manager.column_masks(&admin, "orders").is_empty());
+        let read = ["support".to_string(), "read".to_string()];
+        assert!(manager.column_masks(&read, "orders").is_empty());
+        // Roles that can't read the table don't count
+        let write = ["support".to_string(), "write".to_string()];
+        assert!(!manager.column_masks(&write, "orders").is_empty());
+    }
 }
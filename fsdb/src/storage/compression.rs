@@ -0,0 +1,226 @@
+//! Transparent decompression of gzip and zstd CSV input
+//!
+//! Bulk loads and CSV written through the mount accept compressed content.
+//! The format is recognised by its magic bytes, so a file doesn't need a
+//! `.gz` or `.zst` name to be read; a file whose name claims a format its
+//! content doesn't start with is an error rather than being read as text.
+//! A truncated or corrupt stream fails with `Error::Decompression` instead of
+//! yielding the rows decoded before the damage.
+//!
+//! Content decompressed into memory, as CSV written through the mount is,
+//! is capped: a stream that inflates past the cap fails with
+//! `Error::Decompression` once the cap is reached, so a small compressed
+//! file can't expand to fill memory.
+
+use crate::{Error, Result};
+use std::borrow::Cow;
+use std::io::{BufRead, BufReader, Read};
+use std::path::Path;
+
+/// Most bytes `decompress` produces unless given another cap
+pub const DEFAULT_MAX_DECOMPRESSED_BYTES: usize = 1024 * 1024 * 1024;
+
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+const ZSTD_MAGIC: [u8; 4] = [0x28, 0xb5, 0x2f, 0xfd];
+
+/// Compression of a CSV source
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Compression {
+    None,
+    Gzip,
+    Zstd,
+}
+
+impl Compression {
+    /// Format `head`, the first bytes of the content, starts with
+    pub fn detect(head: &[u8]) -> Self {
+        if head.starts_with(&GZIP_MAGIC) {
+            Compression::Gzip
+        } else if head.starts_with(&ZSTD_MAGIC) {
+            Compression::Zstd
+        } else {
+            Compression::None
+        }
+    }
+
+    /// Format `path`'s extension names, if any
+    pub fn from_extension(path: &Path) -> Self {
+        match path.extension().and_then(|e| e.to_str()) {
+            Some("gz" | "gzip") => Compression::Gzip,
+            Some("zst" | "zstd") => Compression::Zstd,
+            _ => Compression::None,
+        }
+    }
+}
+
+/// Read error from the decompressor, as opposed to the underlying file
+#[derive(Debug)]
+struct DecompressionFailed(String);
+
+impl std::fmt::Display for DecompressionFailed {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for DecompressionFailed {}
+
+/// Reader that tags the decoder's errors so they can be told apart later
+struct Decoding<R> {
+    inner: R,
+    format: Compression,
+}
+
+impl<R: Read> Read for Decoding<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        self.inner.read(buf).map_err(|e| {
+            std::io::Error::new(
+                e.kind(),
+                DecompressionFailed(format!("{:?} stream: {}", self.format, e)),
+            )
+        })
+    }
+}
+
+/// Open `path` for reading, decompressing it if it's gzip or zstd
+pub fn open(path: &Path) -> Result<Box<dyn Read + Send>> {
+    let mut file = BufReader::new(std::fs::File::open(path)?);
+    let format = Compression::detect(file.fill_buf()?);
+    let named = Compression::from_extension(path);
+    if named != Compression::None && named != format {
+        return Err(Error::Decompression(format!(
+            "{} is named as {:?} but its content isn't",
+            path.display(),
+            named
+        )));
+    }
+    decoder(file, format)
+}
+
+fn decoder<R: BufRead + Send + 'static>(
+    reader: R,
+    format: Compression,
+) -> Result<Box<dyn Read + Send>> {
+    Ok(match format {
+        Compression::None => Box::new(reader),
+        Compression::Gzip => Box::new(Decoding {
+            inner: flate2::bufread::MultiGzDecoder::new(reader),
+            format,
+        }),
+        Compression::Zstd => Box::new(Decoding {
+            inner: zstd::stream::read::Decoder::with_buffer(reader)
+                .map_err(|e| Error::Decompression(e.to_string()))?,
+            format,
+        }),
+    })
+}
+
+/// `data`, decompressed if it's gzip or zstd
+///
+/// Fails with `Error::Decompression` if the content decompresses to more
+/// than `max_bytes`, without decoding further.
+pub fn decompress(data: &[u8], max_bytes: usize) -> Result<Cow<'_, [u8]>> {
+    let format = Compression::detect(data);
+    if format == Compression::None {
+        return Ok(Cow::Borrowed(data));
+    }
+    let mut decompressed = Vec::new();
+    decoder(std::io::Cursor::new(data.to_vec()), format)?
+        .take(max_bytes as u64 + 1)
+        .read_to_end(&mut decompressed)
+        .map_err(|e| Error::Decompression(e.to_string()))?;
+    if decompressed.len() > max_bytes {
+        return Err(Error::Decompression(format!(
+            "{:?} content decompresses to more than {} bytes",
+            format, max_bytes
+        )));
+    }
+    Ok(Cow::Owned(decompressed))
+}
+
+/// The `Error::Decompression` a read error stands for, if it came from the
+/// decompressor
+pub fn decompression_error(error: &std::io::Error) -> Option<Error> {
+    error
+        .get_ref()
+        .and_then(|inner| inner.downcast_ref::<DecompressionFailed>())
+        .map(|failed| Error::Decompression(failed.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    const CSV: &[u8] = b"id,name\n1,alice\n2,bob\n";
+
+    fn gzip(data: &[u8]) -> Vec<u8> {
+        let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder.write_all(data).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    #[test]
+    fn test_detect() {
+        assert_eq!(Compression::detect(&gzip(CSV)), Compression::Gzip);
+        assert_eq!(
+            Compression::detect(&zstd::encode_all(CSV, 3).unwrap()),
+            Compression::Zstd
+        );
+        assert_eq!(Compression::detect(CSV), Compression::None);
+        assert_eq!(Compression::detect(b""), Compression::None);
+        assert_eq!(
+            Compression::from_extension(Path::new("data.csv.gz")),
+            Compression::Gzip
+        );
+        assert_eq!(
+            Compression::from_extension(Path::new("data.csv")),
+            Compression::None
+        );
+    }
+
+    #[test]
+    fn test_decompress() {
+        let max = DEFAULT_MAX_DECOMPRESSED_BYTES;
+        assert_eq!(&*decompress(CSV, max).unwrap(), CSV);
+        assert_eq!(&*decompress(&gzip(CSV), max).unwrap(), CSV);
+        assert_eq!(
+            &*decompress(&zstd::encode_all(CSV, 3).unwrap(), max).unwrap(),
+            CSV
+        );
+        assert_eq!(&*decompress(&gzip(CSV), CSV.len()).unwrap(), CSV);
+    }
+
+    #[test]
+    fn test_oversized_stream_is_an_error() {
+        // A few KB that inflate to 64 MiB stop at the cap
+        let bomb = vec![b'0'; 64 * 1024 * 1024];
+        for compressed in [gzip(&bomb), zstd::encode_all(bomb.as_slice(), 19).unwrap()] {
+            assert!(compressed.len() < 256 * 1024);
+            let err = decompress(&compressed, 1024 * 1024).unwrap_err();
+            assert!(
+                matches!(&err, Error::Decompression(message) if message.contains("1048576")),
+                "{:?}",
+                err
+            );
+        }
+    }
+
+    #[test]
+    fn test_truncated_stream_is_an_error() {
+        for compressed in [gzip(CSV), zstd::encode_all(CSV, 3).unwrap()] {
+            let truncated = &compressed[..compressed.len() - 4];
+            let err = decompress(truncated, DEFAULT_MAX_DECOMPRESSED_BYTES).unwrap_err();
+            assert!(matches!(err, Error::Decompression(_)), "{:?}", err);
+        }
+    }
+
+    #[test]
+    fn test_open_rejects_misnamed_file() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("data.csv.gz");
+        std::fs::write(&path, CSV).unwrap();
+        let err = open(&path).err().unwrap();
+        assert!(matches!(err, Error::Decompression(_)), "{:?}", err);
+    }
+}
@@ -0,0 +1,339 @@
+//! Row and field size limits for writes
+//!
+//! A value's size is the byte length of strings and binary values, the
+//! width of fixed-size types such as integers and timestamps, and the sum of
+//! the elements of lists, structs and maps; NULL is zero. A row's size is the
+//! sum of its values. Limits are checked before anything is committed, and
+//! CSV written through the mount is checked record by record before any of it
+//! is parsed, so one giant line is rejected without being coerced.
+
+use crate::nfs::coercion::BinaryEncoding;
+use crate::{Error, Result};
+use arrow::array::{Array, AsArray, RecordBatch};
+use arrow::datatypes::{DataType, SchemaRef};
+use std::collections::HashMap;
+
+/// Maximum sizes of written rows and values, in bytes
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SizeLimits {
+    /// Largest row (None = unlimited)
+    pub max_row_bytes: Option<usize>,
+
+    /// Largest value in any column without its own limit (None = unlimited)
+    pub max_field_bytes: Option<usize>,
+
+    /// Columns whose values may be larger or smaller than `max_field_bytes`
+    pub column_field_bytes: HashMap<String, usize>,
+
+    /// Largest compressed file written through the mount, once decompressed
+    /// (None = `DEFAULT_MAX_DECOMPRESSED_BYTES`)
+    pub max_decompressed_bytes: Option<usize>,
+}
+
+impl SizeLimits {
+    /// No limits
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Reject rows over `bytes`
+    pub fn with_max_row_bytes(mut self, bytes: usize) -> Self {
+        self.max_row_bytes = Some(bytes);
+        self
+    }
+
+    /// Reject values over `bytes`
+    pub fn with_max_field_bytes(mut self, bytes: usize) -> Self {
+        self.max_field_bytes = Some(bytes);
+        self
+    }
+
+    /// Limit `column`'s values to `bytes` instead of `max_field_bytes`
+    ///
+    /// The row limit still applies, so a column of large blobs needs a row
+    /// limit that leaves room for them.
+    pub fn with_column_limit(mut self, column: impl Into<String>, bytes: usize) -> Self {
+        self.column_field_bytes.insert(column.into(), bytes);
+        self
+    }
+
+    /// Reject compressed writes that decompress to more than `bytes`
+    pub fn with_max_decompressed_bytes(mut self, bytes: usize) -> Self {
+        self.max_decompressed_bytes = Some(bytes);
+        self
+    }
+
+    /// Most bytes a compressed write may decompress to
+    pub fn decompressed_limit(&self) -> usize {
+        self.max_decompressed_bytes
+            .unwrap_or(crate::storage::compression::DEFAULT_MAX_DECOMPRESSED_BYTES)
+    }
+
+    /// Limit on `column`'s values, if any
+    pub fn field_limit(&self, column: &str) -> Option<usize> {
+        self.column_field_bytes
+            .get(column)
+            .copied()
+            .or(self.max_field_bytes)
+    }
+
+    fn is_unlimited(&self) -> bool {
+        self.max_row_bytes.is_none()
+            && self.max_field_bytes.is_none()
+            && self.column_field_bytes.is_empty()
+    }
+
+    /// Fail with `Error::RowTooLarge` or `Error::FieldTooLarge` for the first
+    /// row of `batch` over a limit
+    ///
+    /// Row numbers are 1-based within the batch.
+    pub fn check_batch(&self, batch: &RecordBatch) -> Result<()> {
+        if self.is_unlimited() {
+            return Ok(());
+        }
+        let schema = batch.schema();
+        let limits: Vec<Option<usize>> = schema
+            .fields()
+            .iter()
+            .map(|f| self.field_limit(f.name()))
+            .collect();
+        for row in 0..batch.num_rows() {
+            let values = schema
+                .fields()
+                .iter()
+                .zip(batch.columns())
+                .zip(&limits)
+                .map(|((field, column), limit)| {
+                    (
+                        field.name().as_str(),
+                        value_bytes(column.as_ref(), row),
+                        *limit,
+                    )
+                });
+            self.check_row(row + 1, values)?;
+        }
+        Ok(())
+    }
+
+    /// Like `check_batch`, on CSV text about to be parsed against `schema`
+    ///
+    /// Cells are sized as the values they will become: blank cells are NULL
+    /// and binary cells are measured decoded. Headers are matched by name and
+    /// headerless records by position; cells for columns `schema` doesn't
+    /// have count their text. Row numbers are 1-based records after any
+    /// header, blank ones included.
+    pub fn check_csv(
+        &self,
+        csv_text: &str,
+        schema: &SchemaRef,
+        has_header: bool,
+        encoding: BinaryEncoding,
+    ) -> Result<()> {
+        if self.is_unlimited() {
+            return Ok(());
+        }
+        let mut reader = csv::ReaderBuilder::new()
+            .has_headers(has_header)
+            .flexible(true)
+            .from_reader(csv_text.as_bytes());
+        let names: Vec<String> = if has_header {
+            reader
+                .headers()
+                .map_err(|e| Error::InvalidOperation(format!("Invalid CSV header: {}", e)))?
+                .iter()
+                .map(|name| name.trim().to_string())
+                .collect()
+        } else {
+            schema.fields().iter().map(|f| f.name().clone()).collect()
+        };
+        let types: Vec<Option<&DataType>> = names
+            .iter()
+            .map(|name| schema.field_with_name(name).ok().map(|f| f.data_type()))
+            .collect();
+        let limits: Vec<Option<usize>> = names.iter().map(|name| self.field_limit(name)).collect();
+
+        let mut record = csv::StringRecord::new();
+        let mut row = 0;
+        while reader
+            .read_record(&mut record)
+            .map_err(|e| Error::InvalidOperation(format!("Invalid CSV: {}", e)))?
+        {
+            row += 1;
+            let values = record.iter().enumerate().map(|(i, cell)| {
+                (
+                    names.get(i).map_or("", String::as_str),
+                    cell_bytes(cell, types.get(i).copied().flatten(), encoding),
+                    limits.get(i).copied().flatten(),
+                )
+            });
+            self.check_row(row, values)?;
+        }
+        Ok(())
+    }
+
+    fn check_row<'a>(
+        &self,
+        row: usize,
+        values: impl Iterator<Item = (&'a str, usize, Option<usize>)>,
+    ) -> Result<()> {
+        let mut total = 0;
+        for (column, bytes, limit) in values {
+            if let Some(limit) = limit {
+                if bytes > limit {
+                    return Err(Error::FieldTooLarge {
+                        row,
+                        column: column.to_string(),
+                        bytes,
+                        limit,
+                    });
+                }
+            }
+            total += bytes;
+        }
+        match self.max_row_bytes {
+            Some(limit) if total > limit => Err(Error::RowTooLarge {
+                row,
+                bytes: total,
+                limit,
+            }),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Size of the value at `row`
+fn value_bytes(array: &dyn Array, row: usize) -> usize {
+    if array.is_null(row) {
+        return 0;
+    }
+    match array.data_type() {
+        DataType::Utf8 => array.as_string::<i32>().value(row).len(),
+        DataType::LargeUtf8 => array.as_string::<i64>().value(row).len(),
+        DataType::Utf8View => array.as_string_view().value(row).len(),
+        DataType::Binary => array.as_binary::<i32>().value(row).len(),
+        DataType::LargeBinary => array.as_binary::<i64>().value(row).len(),
+        DataType::BinaryView => array.as_binary_view().value(row).len(),
+        DataType::List(_) => elements_bytes(array.as_list::<i32>().value(row).as_ref()),
+        DataType::LargeList(_) => elements_bytes(array.as_list::<i64>().value(row).as_ref()),
+        DataType::Map(_, _) => elements_bytes(&array.as_map().value(row)),
+        DataType::Struct(_) => array
+            .as_struct()
+            .columns()
+            .iter()
+            .map(|child| value_bytes(child.as_ref(), row))
+            .sum(),
+        other => fixed_width(other),
+    }
+}
+
+fn elements_bytes(values: &dyn Array) -> usize {
+    (0..values.len()).map(|i| value_bytes(values, i)).sum()
+}
+
+fn fixed_width(data_type: &DataType) -> usize {
+    match data_type {
+        DataType::Boolean => 1,
+        DataType::FixedSizeBinary(width) => *width as usize,
+        other => other.primitive_width().unwrap_or(0),
+    }
+}
+
+/// Size of the value a CSV cell will be parsed into
+fn cell_bytes(cell: &str, data_type: Option<&DataType>, encoding: BinaryEncoding) -> usize {
+    let text = cell.trim();
+    if text.is_empty() {
+        return 0;
+    }
+    match data_type {
+        Some(DataType::Utf8 | DataType::LargeUtf8 | DataType::Utf8View) | None => cell.len(),
+        Some(DataType::Binary | DataType::LargeBinary | DataType::BinaryView) => match encoding {
+            BinaryEncoding::Base64 => {
+                let padding = text.bytes().rev().take_while(|b| *b == b'=').count();
+                (text.len() * 3 / 4).saturating_sub(padding)
+            }
+            BinaryEncoding::Hex => text.len() / 2,
+        },
+        Some(other) => match fixed_width(other) {
+            0 => cell.len(),
+            width => width,
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow::array::{BinaryArray, Int32Array, StringArray};
+    use arrow::datatypes::{Field, Schema};
+    use std::sync::Arc;
+
+    fn schema() -> SchemaRef {
+        Arc::new(Schema::new(vec![
+            Field::new("id", DataType::Int32, false),
+            Field::new("blob", DataType::Binary, true),
+        ]))
+    }
+
+    #[test]
+    fn test_csv_cells_sized_like_values() {
+        let bytes: &[u8] = b"hello world!";
+        let batch = RecordBatch::try_new(
+            schema(),
+            vec![
+                Arc::new(Int32Array::from(vec![1])),
+                Arc::new(BinaryArray::from(vec![Some(bytes)])),
+            ],
+        )
+        .unwrap();
+        let csv = format!("1,{}\n", BinaryEncoding::Base64.encode(bytes));
+
+        // 4 bytes of id and 12 of blob, whichever way they arrive
+        let limits = SizeLimits::new().with_max_row_bytes(16);
+        assert!(limits.check_batch(&batch).is_ok());
+        assert!(limits
+            .check_csv(&csv, &schema(), false, BinaryEncoding::Base64)
+            .is_ok());
+
+        let limits = SizeLimits::new().with_max_row_bytes(15);
+        assert!(matches!(
+            limits.check_batch(&batch),
+            Err(Error::RowTooLarge { bytes: 16, .. })
+        ));
+        assert!(matches!(
+            limits.check_csv(&csv, &schema(), false, BinaryEncoding::Base64),
+            Err(Error::RowTooLarge { bytes: 16, .. })
+        ));
+    }
+
+    #[test]
+    fn test_column_limit_overrides_field_limit() {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("name", DataType::Utf8, true),
+            Field::new("note", DataType::Utf8, true),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema,
+            vec![
+                Arc::new(StringArray::from(vec!["ada", "grace"])),
+                Arc::new(StringArray::from(vec![Some("a much longer note"), None])),
+            ],
+        )
+        .unwrap();
+
+        let limits = SizeLimits::new().with_max_field_bytes(4);
+        match limits.check_batch(&batch) {
+            Err(Error::FieldTooLarge { row, column, .. }) => {
+                assert_eq!((row, column.as_str()), (1, "note"));
+            }
+            other => panic!("Expected FieldTooLarge, got {:?}", other),
+        }
+
+        let limits = limits.with_column_limit("note", 100);
+        match limits.check_batch(&batch) {
+            Err(Error::FieldTooLarge { row, column, .. }) => {
+                assert_eq!((row, column.as_str()), (2, "name"));
+            }
+            other => panic!("Expected FieldTooLarge, got {:?}", other),
+        }
+    }
+}
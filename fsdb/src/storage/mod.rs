@@ -1,10 +1,14 @@
 //! Storage abstraction layer for cross-platform file I/O
 //! Supports local filesystem, S3, and other ObjectStore backends
 
+pub mod compression;
+pub mod limits;
 pub mod local;
 pub mod parquet;
 pub mod path;
+pub mod retry;
 pub mod s3;
+pub mod spill;
 
 use object_store::ObjectStore;
 use std::sync::Arc;
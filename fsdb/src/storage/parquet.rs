@@ -3,18 +3,22 @@
 //! Features:
 //! - Write Arrow RecordBatch to Parquet files
 //! - Read Parquet files back to RecordBatch
+//! - Projected reads that decode only the requested columns
 //! - Compression support (Snappy default)
 //! - Column statistics and metadata
 //! - Filename convention: `data_<timestamp>_<txn_id>.parquet`
 
-use crate::Result;
-use arrow::array::{RecordBatch, RecordBatchReader};
+use crate::{Error, Result};
+use arrow::array::{RecordBatch, RecordBatchOptions, RecordBatchReader};
+use arrow::datatypes::Schema;
 use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
-use parquet::arrow::ArrowWriter;
+use parquet::arrow::{ArrowWriter, ProjectionMask};
 use parquet::basic::{Compression, Encoding};
 use parquet::file::properties::WriterProperties;
+use parquet::file::reader::ChunkReader;
 use std::fs::File;
 use std::path::Path;
+use std::sync::Arc;
 use tracing::{debug, info};
 
 /// Generate Parquet filename with timestamp and transaction ID
@@ -152,6 +156,88 @@ impl ParquetReader {
     }
 }
 
+/// What a projected read does with a column the file doesn't have
+///
+/// Files written before a column was added to the table lack it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MissingColumn {
+    /// Read it as nulls if it's nullable, fail otherwise
+    #[default]
+    Null,
+    /// Fail with `Error::SchemaMismatch`
+    Error,
+}
+
+impl ParquetReader {
+    /// Read only `columns` of a Parquet file, in their order
+    ///
+    /// Column chunks of other columns are never read from disk. Columns are
+    /// matched by name and cast to the requested type; see `MissingColumn`
+    /// for those the file doesn't have.
+    pub fn read_projected<P: AsRef<Path>>(
+        &self,
+        path: P,
+        columns: &Schema,
+        missing: MissingColumn,
+    ) -> Result<RecordBatch> {
+        let path = path.as_ref();
+        debug!(
+            "Reading {} columns of Parquet file: {}",
+            columns.fields().len(),
+            path.display()
+        );
+        read_projected_from(File::open(path)?, columns, missing).map_err(|e| match e {
+            Error::SchemaMismatch(msg) => {
+                Error::SchemaMismatch(format!("{}: {}", path.display(), msg))
+            }
+            other => other,
+        })
+    }
+}
+
+/// `ParquetReader::read_projected` over any Parquet source
+pub fn read_projected_from<R: ChunkReader + 'static>(
+    source: R,
+    columns: &Schema,
+    missing: MissingColumn,
+) -> Result<RecordBatch> {
+    let builder = ParquetRecordBatchReaderBuilder::try_new(source)?;
+    let file_schema = builder.schema().clone();
+    let roots: Vec<usize> = columns
+        .fields()
+        .iter()
+        .filter_map(|field| file_schema.index_of(field.name()).ok())
+        .collect();
+    let mask = ProjectionMask::roots(builder.parquet_schema(), roots);
+    let reader = builder.with_projection(mask).build()?;
+    let decoded_schema = reader.schema();
+    let batches = reader.collect::<std::result::Result<Vec<_>, _>>()?;
+    let decoded = arrow::compute::concat_batches(&decoded_schema, &batches)?;
+
+    let mut arrays = Vec::with_capacity(columns.fields().len());
+    for field in columns.fields() {
+        let array = match decoded.column_by_name(field.name()) {
+            Some(array) if array.data_type() == field.data_type() => array.clone(),
+            Some(array) => arrow::compute::cast(array, field.data_type())?,
+            None if missing == MissingColumn::Null && field.is_nullable() => {
+                arrow::array::new_null_array(field.data_type(), decoded.num_rows())
+            }
+            None => {
+                return Err(Error::SchemaMismatch(format!(
+                    "file has no column {}",
+                    field.name()
+                )))
+            }
+        };
+        arrays.push(array);
+    }
+    Ok(RecordBatch::try_new_with_options(
+        Arc::new(columns.clone()),
+        arrays,
+        &RecordBatchOptions::new().with_row_count(Some(decoded.num_rows())),
+    )?)
+}
+
 impl Default for ParquetReader {
     fn default() -> Self {
         Self::new()
@@ -363,4 +449,100 @@ mod tests {
         assert_eq!(read2.num_rows(), 3);
         assert_eq!(read1.schema(), read2.schema());
     }
+
+    /// Parquet bytes that remember the offset of every read
+    struct RecordingReader {
+        bytes: bytes::Bytes,
+        starts: Arc<std::sync::Mutex<Vec<u64>>>,
+    }
+
+    impl parquet::file::reader::Length for RecordingReader {
+        fn len(&self) -> u64 {
+            self.bytes.len() as u64
+        }
+    }
+
+    impl ChunkReader for RecordingReader {
+        type T = <bytes::Bytes as ChunkReader>::T;
+
+        fn get_read(&self, start: u64) -> parquet::errors::Result<Self::T> {
+            self.starts.lock().unwrap().push(start);
+            self.bytes.get_read(start)
+        }
+
+        fn get_bytes(&self, start: u64, length: usize) -> parquet::errors::Result<bytes::Bytes> {
+            self.starts.lock().unwrap().push(start);
+            self.bytes.get_bytes(start, length)
+        }
+    }
+
+    #[test]
+    fn test_projected_read_skips_other_columns() {
+        use parquet::file::reader::{FileReader, SerializedFileReader};
+
+        let batch = create_test_batch();
+        let mut buffer = Vec::new();
+        let mut writer = ArrowWriter::try_new(&mut buffer, batch.schema(), None).unwrap();
+        writer.write(&batch).unwrap();
+        writer.close().unwrap();
+        let bytes = bytes::Bytes::from(buffer);
+
+        let starts = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let reader = RecordingReader {
+            bytes: bytes.clone(),
+            starts: starts.clone(),
+        };
+        let projection = ArrowSchema::new(vec![
+            Field::new("score", DataType::Float64, true),
+            Field::new("id", DataType::Int32, false),
+        ]);
+        let read = read_projected_from(reader, &projection, MissingColumn::Error).unwrap();
+        assert_eq!(read.num_columns(), 2);
+        assert_eq!(read.column(0), batch.column(2));
+        assert_eq!(read.column(1), batch.column(0));
+
+        // No read started inside the chunks of name or active
+        let metadata = SerializedFileReader::new(bytes).unwrap().metadata().clone();
+        let row_group = metadata.row_group(0);
+        let starts = starts.lock().unwrap();
+        for (column, projected) in [(0, true), (1, false), (2, true), (3, false)] {
+            let (offset, length) = row_group.column(column).byte_range();
+            let touched = starts
+                .iter()
+                .any(|start| (offset..offset + length).contains(start));
+            assert_eq!(touched, projected, "column {}", column);
+        }
+    }
+
+    #[test]
+    fn test_projected_read_of_missing_column() {
+        let temp_dir = TempDir::new().unwrap();
+        let file_path = temp_dir.path().join("old.parquet");
+        ParquetWriter::new()
+            .write_batch(&file_path, &create_test_batch())
+            .unwrap();
+        let reader = ParquetReader::new();
+
+        // A column added after the file was written
+        let projection = ArrowSchema::new(vec![
+            Field::new("id", DataType::Int32, false),
+            Field::new("email", DataType::Utf8, true),
+        ]);
+        let read = reader
+            .read_projected(&file_path, &projection, MissingColumn::Null)
+            .unwrap();
+        assert_eq!(read.num_rows(), 5);
+        assert_eq!(read.column(1).null_count(), 5);
+
+        assert!(matches!(
+            reader.read_projected(&file_path, &projection, MissingColumn::Error),
+            Err(Error::SchemaMismatch(_))
+        ));
+
+        // Nulls can't stand in for a required column
+        let required = ArrowSchema::new(vec![Field::new("email", DataType::Utf8, false)]);
+        assert!(reader
+            .read_projected(&file_path, &required, MissingColumn::Null)
+            .is_err());
+    }
 }
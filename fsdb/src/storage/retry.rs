@@ -0,0 +1,297 @@
+//! Retry with exponential backoff for transient storage errors
+//!
+//! Only errors `is_transient` explicitly lists are retried: dropped
+//! connections, timeouts and generic object store failures (which is how
+//! network and 5xx errors surface). Everything else, including not-found,
+//! permission, schema and commit-conflict errors, fails on the first attempt.
+
+use crate::{Error, Result};
+use std::future::Future;
+use std::hash::{BuildHasher, Hasher};
+use std::time::Duration;
+use tracing::warn;
+
+/// How transient storage errors are retried
+#[derive(Debug, Clone, PartialEq)]
+pub struct RetryPolicy {
+    /// Total attempts including the first (1 disables retries)
+    pub max_attempts: u32,
+
+    /// Delay before the first retry; doubles for each retry after that
+    pub base_delay: Duration,
+
+    /// Upper bound on any single delay
+    pub max_delay: Duration,
+
+    /// Fraction of each delay that is randomized, from 0.0 (fixed) to 1.0 (full jitter)
+    pub jitter: f64,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            base_delay: Duration::from_millis(100),
+            max_delay: Duration::from_secs(5),
+            jitter: 0.5,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Policy that never retries
+    pub fn none() -> Self {
+        Self {
+            max_attempts: 1,
+            ..Self::default()
+        }
+    }
+
+    /// Backoff before retry number `retry` (1-based), before jitter
+    pub fn backoff(&self, retry: u32) -> Duration {
+        let factor = 2u32.saturating_pow(retry.saturating_sub(1));
+        self.base_delay.saturating_mul(factor).min(self.max_delay)
+    }
+
+    /// Backoff with `jitter` of it replaced by a random amount
+    fn jittered(&self, retry: u32) -> Duration {
+        let delay = self.backoff(retry);
+        let jitter = self.jitter.clamp(0.0, 1.0);
+        if jitter == 0.0 {
+            return delay;
+        }
+        let random = std::collections::hash_map::RandomState::new()
+            .build_hasher()
+            .finish() as f64
+            / u64::MAX as f64;
+        delay.mul_f64(1.0 - jitter * random)
+    }
+
+    /// Run `op`, retrying transient failures
+    ///
+    /// `op` must be safe to repeat: each attempt starts from scratch.
+    pub async fn run<T, F, Fut>(&self, operation: &str, mut op: F) -> Result<T>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<T>>,
+    {
+        let mut attempt = 1;
+        loop {
+            match op().await {
+                Err(e) if attempt < self.max_attempts && is_transient(&e) => {
+                    let delay = self.jittered(attempt);
+                    warn!(
+                        "{} failed (attempt {}/{}), retrying in {:?}: {}",
+                        operation, attempt, self.max_attempts, delay, e
+                    );
+                    tokio::time::sleep(delay).await;
+                    attempt += 1;
+                }
+                result => return result,
+            }
+        }
+    }
+}
+
+/// True for errors worth retrying
+pub fn is_transient(err: &Error) -> bool {
+    match err {
+        Error::Io(e) => is_transient_io(e),
+        Error::ObjectStore(e) => is_transient_object_store(e),
+        Error::DeltaTable(deltalake::DeltaTableError::ObjectStore { source }) => {
+            is_transient_object_store(source)
+        }
+        Error::DeltaTable(deltalake::DeltaTableError::Io { source }) => is_transient_io(source),
+        _ => false,
+    }
+}
+
+/// Whether `err` is a Delta commit that lost to a conflicting concurrent
+/// commit, which is worth retrying only from a fresh snapshot
+pub fn is_commit_conflict(err: &Error) -> bool {
+    use deltalake::kernel::transaction::TransactionError;
+    match err {
+        Error::TransactionConflict(_) => true,
+        Error::DeltaTable(deltalake::DeltaTableError::Transaction { source }) => matches!(
+            source,
+            TransactionError::CommitConflict(_)
+                | TransactionError::VersionAlreadyExists(_)
+                | TransactionError::MaxCommitAttempts(_)
+        ),
+        _ => false,
+    }
+}
+
+fn is_transient_io(err: &std::io::Error) -> bool {
+    use std::io::ErrorKind;
+    matches!(
+        err.kind(),
+        ErrorKind::TimedOut
+            | ErrorKind::Interrupted
+            | ErrorKind::WouldBlock
+            | ErrorKind::ConnectionReset
+            | ErrorKind::ConnectionAborted
+            | ErrorKind::ConnectionRefused
+            | ErrorKind::BrokenPipe
+            | ErrorKind::UnexpectedEof
+    )
+}
+
+fn is_transient_object_store(err: &object_store::Error) -> bool {
+    // The HTTP client has already retried by the time `Generic` comes back,
+    // but a connection or 5xx failure may still clear up
+    matches!(
+        err,
+        object_store::Error::Generic { .. } | object_store::Error::JoinError { .. }
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bytes::Bytes;
+    use object_store::memory::InMemory;
+    use object_store::path::Path as ObjectPath;
+    use object_store::{ObjectStore, PutPayload};
+    use std::sync::atomic::{AtomicU32, Ordering};
+    use std::sync::Arc;
+
+    /// Store that fails the first `failures` reads before delegating
+    struct FlakyStore {
+        inner: InMemory,
+        failures: u32,
+        calls: AtomicU32,
+    }
+
+    impl FlakyStore {
+        fn new(failures: u32) -> Self {
+            Self {
+                inner: InMemory::new(),
+                failures,
+                calls: AtomicU32::new(0),
+            }
+        }
+
+        async fn get(&self, location: &ObjectPath) -> Result<Bytes> {
+            let call = self.calls.fetch_add(1, Ordering::SeqCst);
+            if call < self.failures {
+                return Err(Error::ObjectStore(object_store::Error::Generic {
+                    store: "flaky",
+                    source: "connection reset by peer".into(),
+                }));
+            }
+            Ok(self.inner.get(location).await?.bytes().await?)
+        }
+    }
+
+    fn fast_policy(max_attempts: u32) -> RetryPolicy {
+        RetryPolicy {
+            max_attempts,
+            base_delay: Duration::from_millis(1),
+            max_delay: Duration::from_millis(5),
+            jitter: 0.5,
+        }
+    }
+
+    async fn flaky_store(failures: u32) -> (Arc<FlakyStore>, ObjectPath) {
+        let store = Arc::new(FlakyStore::new(failures));
+        let path = ObjectPath::from("_delta_log/00000000000000000000.json");
+        store
+            .inner
+            .put(&path, PutPayload::from_static(b"{}"))
+            .await
+            .unwrap();
+        (store, path)
+    }
+
+    #[test]
+    fn test_backoff_is_exponential_and_capped() {
+        let policy = RetryPolicy {
+            max_attempts: 10,
+            base_delay: Duration::from_millis(100),
+            max_delay: Duration::from_secs(1),
+            jitter: 0.0,
+        };
+        assert_eq!(policy.backoff(1), Duration::from_millis(100));
+        assert_eq!(policy.backoff(2), Duration::from_millis(200));
+        assert_eq!(policy.backoff(4), Duration::from_millis(800));
+        assert_eq!(policy.backoff(5), Duration::from_secs(1));
+        assert_eq!(policy.backoff(40), Duration::from_secs(1));
+        assert_eq!(policy.jittered(2), Duration::from_millis(200));
+    }
+
+    #[test]
+    fn test_jitter_stays_within_bounds() {
+        let policy = RetryPolicy {
+            jitter: 0.5,
+            ..RetryPolicy::default()
+        };
+        for _ in 0..100 {
+            let delay = policy.jittered(1);
+            assert!(delay >= Duration::from_millis(50) && delay <= Duration::from_millis(100));
+        }
+    }
+
+    #[test]
+    fn test_classification() {
+        let io = |kind| Error::Io(std::io::Error::from(kind));
+        assert!(is_transient(&io(std::io::ErrorKind::ConnectionReset)));
+        assert!(is_transient(&io(std::io::ErrorKind::TimedOut)));
+        assert!(!is_transient(&io(std::io::ErrorKind::NotFound)));
+        assert!(!is_transient(&io(std::io::ErrorKind::PermissionDenied)));
+
+        assert!(!is_transient(&Error::ObjectStore(
+            object_store::Error::NotFound {
+                path: "x".to_string(),
+                source: "missing".into(),
+            }
+        )));
+        assert!(!is_transient(&Error::TransactionConflict(
+            "concurrent append".to_string()
+        )));
+        assert!(!is_transient(&Error::InvalidOperation(
+            "constraint violated".to_string()
+        )));
+
+        let version_taken = Error::DeltaTable(deltalake::DeltaTableError::Transaction {
+            source: deltalake::kernel::transaction::TransactionError::VersionAlreadyExists(3),
+        });
+        assert!(is_commit_conflict(&version_taken));
+        assert!(!is_transient(&version_taken));
+        assert!(!is_commit_conflict(&io(std::io::ErrorKind::TimedOut)));
+    }
+
+    #[tokio::test]
+    async fn test_succeeds_after_transient_failures() {
+        let (store, path) = flaky_store(2).await;
+
+        let bytes = fast_policy(3)
+            .run("read", || store.get(&path))
+            .await
+            .unwrap();
+
+        assert_eq!(bytes, Bytes::from_static(b"{}"));
+        assert_eq!(store.calls.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn test_gives_up_after_max_attempts() {
+        let (store, path) = flaky_store(5).await;
+
+        let result = fast_policy(3).run("read", || store.get(&path)).await;
+
+        assert!(matches!(result, Err(Error::ObjectStore(_))));
+        assert_eq!(store.calls.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn test_permanent_errors_fail_fast() {
+        let (store, _) = flaky_store(0).await;
+        let missing = ObjectPath::from("missing.json");
+
+        let result = fast_policy(5).run("read", || store.get(&missing)).await;
+
+        assert!(result.is_err());
+        assert_eq!(store.calls.load(Ordering::SeqCst), 1);
+    }
+}
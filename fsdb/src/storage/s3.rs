@@ -1,12 +1,370 @@
 //! S3/MinIO storage backend implementation for Delta Lake
+//!
+//! A table on S3 is addressed as `s3://bucket/prefix` and reached with an
+//! `S3Config`: region, endpoint for S3-compatible stores such as MinIO,
+//! credentials and the multipart upload settings. delta-rs is handed the
+//! object store built from it rather than building its own, so data files
+//! above the multipart threshold are uploaded in parts, several at once,
+//! instead of in one request. Conditional puts, which Delta commits rely on,
+//! are always sent as single requests.
 
-use crate::Result;
-use object_store::aws::AmazonS3Builder;
-use object_store::ObjectStore;
+use crate::{Error, Result};
+use async_trait::async_trait;
+use futures::stream::BoxStream;
+use object_store::aws::{AmazonS3Builder, AmazonS3ConfigKey};
+use object_store::path::Path as ObjectPath;
+use object_store::{
+    GetOptions, GetResult, ListResult, MultipartUpload, ObjectMeta, ObjectStore,
+    PutMultipartOptions, PutOptions, PutPayload, PutResult, WriteMultipart,
+};
 use std::collections::HashMap;
+use std::fmt;
 use std::sync::Arc;
 use url::Url;
 
+/// Smallest part S3 accepts, other than an upload's last
+pub const MIN_PART_SIZE: usize = 5 * 1024 * 1024;
+
+/// Objects at least this large are uploaded in parts by default
+pub const DEFAULT_MULTIPART_THRESHOLD: usize = 32 * 1024 * 1024;
+
+/// Size of each part of a multipart upload by default
+pub const DEFAULT_PART_SIZE: usize = 16 * 1024 * 1024;
+
+/// Parts of one object uploaded at once
+const MAX_CONCURRENT_PARTS: usize = 8;
+
+/// Connection settings for a table on S3 or an S3-compatible store
+#[derive(Clone, PartialEq, Eq)]
+pub struct S3Config {
+    /// AWS region of the bucket
+    pub region: String,
+
+    /// Endpoint of an S3-compatible store such as MinIO (None = AWS)
+    pub endpoint: Option<String>,
+
+    /// Static credentials (None = the provider chain: environment, web
+    /// identity, instance metadata)
+    pub access_key_id: Option<String>,
+    pub secret_access_key: Option<String>,
+
+    /// Session token of temporary credentials
+    pub session_token: Option<String>,
+
+    /// Whether plain HTTP endpoints are allowed, as local MinIO needs
+    pub allow_http: bool,
+
+    /// Whether buckets are addressed as `bucket.endpoint` rather than `endpoint/bucket`
+    pub virtual_hosted_style: bool,
+
+    /// Objects at least this many bytes are uploaded in parts
+    pub multipart_threshold: usize,
+
+    /// Bytes in each part of a multipart upload
+    pub part_size: usize,
+
+    /// Further object store options, e.g. `aws_conditional_put`
+    pub options: HashMap<String, String>,
+}
+
+impl Default for S3Config {
+    fn default() -> Self {
+        Self {
+            region: "us-east-1".to_string(),
+            endpoint: None,
+            access_key_id: None,
+            secret_access_key: None,
+            session_token: None,
+            allow_http: false,
+            virtual_hosted_style: false,
+            multipart_threshold: DEFAULT_MULTIPART_THRESHOLD,
+            part_size: DEFAULT_PART_SIZE,
+            options: HashMap::new(),
+        }
+    }
+}
+
+impl fmt::Debug for S3Config {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // Secrets stay out of logs
+        f.debug_struct("S3Config")
+            .field("region", &self.region)
+            .field("endpoint", &self.endpoint)
+            .field("access_key_id", &self.access_key_id)
+            .field("allow_http", &self.allow_http)
+            .field("virtual_hosted_style", &self.virtual_hosted_style)
+            .field("multipart_threshold", &self.multipart_threshold)
+            .field("part_size", &self.part_size)
+            .finish_non_exhaustive()
+    }
+}
+
+impl S3Config {
+    /// Settings for AWS in us-east-1, with credentials from the provider chain
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Settings from the standard `AWS_*` environment variables
+    ///
+    /// Reads `AWS_REGION` (or `AWS_DEFAULT_REGION`), `AWS_ENDPOINT_URL`,
+    /// `AWS_ACCESS_KEY_ID`, `AWS_SECRET_ACCESS_KEY`, `AWS_SESSION_TOKEN` and
+    /// `AWS_ALLOW_HTTP`. This is what an `s3://` path given to
+    /// `DatabaseOps::create` or `open` connects with.
+    pub fn from_env() -> Self {
+        let var = |name: &str| std::env::var(name).ok().filter(|v| !v.is_empty());
+        let mut config = Self::default();
+        if let Some(region) = var("AWS_REGION").or_else(|| var("AWS_DEFAULT_REGION")) {
+            config.region = region;
+        }
+        config.endpoint = var("AWS_ENDPOINT_URL");
+        config.access_key_id = var("AWS_ACCESS_KEY_ID");
+        config.secret_access_key = var("AWS_SECRET_ACCESS_KEY");
+        config.session_token = var("AWS_SESSION_TOKEN");
+        config.allow_http = var("AWS_ALLOW_HTTP").is_some_and(|v| v.eq_ignore_ascii_case("true"));
+        config
+    }
+
+    /// Use the bucket's `region`
+    pub fn with_region(mut self, region: impl Into<String>) -> Self {
+        self.region = region.into();
+        self
+    }
+
+    /// Connect to an S3-compatible store at `endpoint`, e.g. `http://localhost:9000`
+    pub fn with_endpoint(mut self, endpoint: impl Into<String>) -> Self {
+        self.endpoint = Some(endpoint.into());
+        self
+    }
+
+    /// Authenticate with a static key pair
+    pub fn with_credentials(
+        mut self,
+        access_key_id: impl Into<String>,
+        secret_access_key: impl Into<String>,
+    ) -> Self {
+        self.access_key_id = Some(access_key_id.into());
+        self.secret_access_key = Some(secret_access_key.into());
+        self
+    }
+
+    /// Add the session token of temporary credentials
+    pub fn with_session_token(mut self, token: impl Into<String>) -> Self {
+        self.session_token = Some(token.into());
+        self
+    }
+
+    /// Allow plain HTTP endpoints
+    pub fn with_allow_http(mut self, allow: bool) -> Self {
+        self.allow_http = allow;
+        self
+    }
+
+    /// Address buckets as `bucket.endpoint`
+    pub fn with_virtual_hosted_style(mut self, enabled: bool) -> Self {
+        self.virtual_hosted_style = enabled;
+        self
+    }
+
+    /// Upload objects of at least `threshold` bytes in parts of `part_size`
+    ///
+    /// S3 needs parts of at least `MIN_PART_SIZE`; smaller ones are refused
+    /// when the store is built.
+    pub fn with_multipart(mut self, threshold: usize, part_size: usize) -> Self {
+        self.multipart_threshold = threshold;
+        self.part_size = part_size;
+        self
+    }
+
+    /// Set any other object store option, such as `aws_conditional_put`
+    pub fn with_option(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.options.insert(key.into(), value.into());
+        self
+    }
+
+    /// The settings as Delta Lake storage options
+    pub fn storage_options(&self) -> HashMap<String, String> {
+        let mut storage_options = self.options.clone();
+        storage_options.insert("AWS_REGION".to_string(), self.region.clone());
+        let optional = [
+            ("AWS_ENDPOINT_URL", &self.endpoint),
+            ("AWS_ACCESS_KEY_ID", &self.access_key_id),
+            ("AWS_SECRET_ACCESS_KEY", &self.secret_access_key),
+            ("AWS_SESSION_TOKEN", &self.session_token),
+        ];
+        for (key, value) in optional {
+            if let Some(value) = value {
+                storage_options.insert(key.to_string(), value.clone());
+            }
+        }
+        if self.allow_http {
+            storage_options.insert("AWS_ALLOW_HTTP".to_string(), "true".to_string());
+        }
+        if self.virtual_hosted_style {
+            storage_options.insert(
+                "AWS_VIRTUAL_HOSTED_STYLE_REQUEST".to_string(),
+                "true".to_string(),
+            );
+        }
+        storage_options
+    }
+
+    /// Object store for the bucket of `url`, uploading large objects in parts
+    pub fn object_store(&self, url: &Url) -> Result<Arc<dyn ObjectStore>> {
+        if self.part_size < MIN_PART_SIZE {
+            return Err(Error::InvalidOperation(format!(
+                "Multipart part size {} is below S3's minimum of {} bytes",
+                self.part_size, MIN_PART_SIZE
+            )));
+        }
+        let mut builder = AmazonS3Builder::new().with_url(url.as_str());
+        for (key, value) in self.storage_options() {
+            // Options the object store doesn't know are delta-rs's, such as
+            // its locking provider, and reach it through the storage options
+            if let Ok(key) = key.to_ascii_lowercase().parse::<AmazonS3ConfigKey>() {
+                builder = builder.with_config(key, value);
+            }
+        }
+        let store = builder
+            .build()
+            .map_err(|e| Error::Other(format!("Failed to create S3 store: {}", e)))?;
+        Ok(Arc::new(MultipartPutStore::new(
+            Arc::new(store),
+            self.multipart_threshold,
+            self.part_size,
+        )))
+    }
+
+    /// Builder for the Delta table at `url`, over this config's object store
+    pub(crate) fn table_builder(&self, url: Url) -> Result<deltalake::DeltaTableBuilder> {
+        let store = self.object_store(&url)?;
+        Ok(deltalake::DeltaTableBuilder::from_uri(url.clone())
+            .map_err(Error::DeltaTable)?
+            .with_storage_options(self.storage_options())
+            .with_storage_backend(store, url))
+    }
+
+    /// Open the latest version of the Delta table at `url`
+    pub(crate) async fn open_table(&self, url: Url) -> Result<deltalake::DeltaTable> {
+        self.table_builder(url)?
+            .load()
+            .await
+            .map_err(Error::DeltaTable)
+    }
+}
+
+/// Object store that sends large unconditional puts as multipart uploads
+///
+/// delta-rs writes each data file with a single `put`; behind this store,
+/// a file of at least `threshold` bytes goes up in `part_size` parts, up
+/// to eight at a time, so no one request carries the whole file and a
+/// failed part is retried alone. Puts with a precondition, such as a
+/// commit's create-if-absent, are passed through untouched, since a
+/// multipart upload can't be conditional.
+#[derive(Debug)]
+pub struct MultipartPutStore {
+    inner: Arc<dyn ObjectStore>,
+    threshold: usize,
+    part_size: usize,
+}
+
+impl MultipartPutStore {
+    pub fn new(inner: Arc<dyn ObjectStore>, threshold: usize, part_size: usize) -> Self {
+        Self {
+            inner,
+            threshold,
+            part_size,
+        }
+    }
+}
+
+impl fmt::Display for MultipartPutStore {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "MultipartPutStore({})", self.inner)
+    }
+}
+
+#[async_trait]
+impl ObjectStore for MultipartPutStore {
+    async fn put_opts(
+        &self,
+        location: &ObjectPath,
+        payload: PutPayload,
+        opts: PutOptions,
+    ) -> object_store::Result<PutResult> {
+        if payload.content_length() < self.threshold
+            || opts.mode != object_store::PutMode::Overwrite
+        {
+            return self.inner.put_opts(location, payload, opts).await;
+        }
+
+        let multipart_opts = PutMultipartOptions {
+            tags: opts.tags,
+            attributes: opts.attributes,
+            ..Default::default()
+        };
+        let upload = self
+            .inner
+            .put_multipart_opts(location, multipart_opts)
+            .await?;
+        let mut write = WriteMultipart::new_with_chunk_size(upload, self.part_size);
+        for bytes in payload {
+            if let Err(e) = write.wait_for_capacity(MAX_CONCURRENT_PARTS).await {
+                write.abort().await.ok();
+                return Err(e);
+            }
+            write.put(bytes);
+        }
+        write.finish().await
+    }
+
+    async fn put_multipart_opts(
+        &self,
+        location: &ObjectPath,
+        opts: PutMultipartOptions,
+    ) -> object_store::Result<Box<dyn MultipartUpload>> {
+        self.inner.put_multipart_opts(location, opts).await
+    }
+
+    async fn get_opts(
+        &self,
+        location: &ObjectPath,
+        options: GetOptions,
+    ) -> object_store::Result<GetResult> {
+        self.inner.get_opts(location, options).await
+    }
+
+    async fn delete(&self, location: &ObjectPath) -> object_store::Result<()> {
+        self.inner.delete(location).await
+    }
+
+    fn list(
+        &self,
+        prefix: Option<&ObjectPath>,
+    ) -> BoxStream<'static, object_store::Result<ObjectMeta>> {
+        self.inner.list(prefix)
+    }
+
+    async fn list_with_delimiter(
+        &self,
+        prefix: Option<&ObjectPath>,
+    ) -> object_store::Result<ListResult> {
+        self.inner.list_with_delimiter(prefix).await
+    }
+
+    async fn copy(&self, from: &ObjectPath, to: &ObjectPath) -> object_store::Result<()> {
+        self.inner.copy(from, to).await
+    }
+
+    async fn copy_if_not_exists(
+        &self,
+        from: &ObjectPath,
+        to: &ObjectPath,
+    ) -> object_store::Result<()> {
+        self.inner.copy_if_not_exists(from, to).await
+    }
+}
+
 /// Parse S3 URI into bucket and prefix
 /// Format: s3://bucket/prefix
 pub fn parse_s3_uri(s3_path: &str) -> Result<(String, String)> {
@@ -56,13 +414,15 @@ pub fn create_delta_storage_options(
     access_key: &str,
     secret_key: &str,
 ) -> HashMap<String, String> {
-    let mut storage_options = HashMap::new();
-    storage_options.insert("AWS_ENDPOINT_URL".to_string(), endpoint.to_string());
-    storage_options.insert("AWS_ACCESS_KEY_ID".to_string(), access_key.to_string());
-    storage_options.insert("AWS_SECRET_ACCESS_KEY".to_string(), secret_key.to_string());
-    storage_options.insert("AWS_REGION".to_string(), "us-east-1".to_string());
-    storage_options.insert("AWS_ALLOW_HTTP".to_string(), "true".to_string());
-    storage_options
+    minio_config(endpoint, access_key, secret_key).storage_options()
+}
+
+/// Settings for a MinIO-style store: `endpoint` over HTTP or HTTPS, static keys, us-east-1
+pub fn minio_config(endpoint: &str, access_key: &str, secret_key: &str) -> S3Config {
+    S3Config::new()
+        .with_endpoint(endpoint)
+        .with_credentials(access_key, secret_key)
+        .with_allow_http(true)
 }
 
 /// Parse S3 path and validate it as a URL
@@ -99,4 +459,61 @@ mod tests {
         assert!(parse_s3_uri("http://bucket/path").is_err());
         assert!(parse_s3_uri("not-a-uri").is_err());
     }
+
+    #[test]
+    fn test_storage_options() {
+        let config = S3Config::new()
+            .with_region("eu-west-1")
+            .with_credentials("key", "secret")
+            .with_session_token("token")
+            .with_option("aws_conditional_put", "etag");
+        let options = config.storage_options();
+        assert_eq!(options["AWS_REGION"], "eu-west-1");
+        assert_eq!(options["AWS_SESSION_TOKEN"], "token");
+        assert_eq!(options["aws_conditional_put"], "etag");
+        assert!(!options.contains_key("AWS_ENDPOINT_URL"));
+        assert!(!options.contains_key("AWS_ALLOW_HTTP"));
+        assert!(!format!("{:?}", config).contains("secret"));
+
+        let options = create_delta_storage_options("http://localhost:9000", "a", "b");
+        assert_eq!(options["AWS_REGION"], "us-east-1");
+        assert_eq!(options["AWS_ALLOW_HTTP"], "true");
+    }
+
+    #[test]
+    fn test_object_store_checks_part_size() {
+        let url = Url::parse("s3://bucket/table").unwrap();
+        let config = S3Config::new().with_credentials("key", "secret");
+        assert!(config.object_store(&url).is_ok());
+        assert!(config
+            .with_multipart(MIN_PART_SIZE, MIN_PART_SIZE - 1)
+            .object_store(&url)
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn test_multipart_puts() {
+        use object_store::memory::InMemory;
+        use object_store::PutMode;
+
+        let store = MultipartPutStore::new(Arc::new(InMemory::new()), 100, 64);
+        let path = ObjectPath::from("table/part-0.parquet");
+        let data: Vec<u8> = (0..1000u32).map(|i| (i % 251) as u8).collect();
+
+        // Above the threshold: uploaded in parts, read back whole
+        store.put(&path, data.clone().into()).await.unwrap();
+        let read = store.get(&path).await.unwrap().bytes().await.unwrap();
+        assert_eq!(read.as_ref(), data.as_slice());
+
+        // A conditional put keeps its precondition, however large
+        let err = store
+            .put_opts(&path, data.clone().into(), PutMode::Create.into())
+            .await
+            .unwrap_err();
+        assert!(
+            matches!(err, object_store::Error::AlreadyExists { .. }),
+            "{}",
+            err
+        );
+    }
 }
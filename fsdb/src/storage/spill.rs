@@ -0,0 +1,195 @@
+//! Scratch space for data spilled to disk
+//!
+//! Sorts and aggregations that outgrow memory, bulk load reject files and the
+//! NFS disk cache all write outside the table. `SpillConfig` says where that
+//! goes and how much of it there may be at once, and `SpillManager` accounts
+//! for it, failing with `Error::SpillLimitExceeded` rather than letting
+//! scratch data fill the disk.
+
+use crate::{Error, Result};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+/// Where scratch files go and how large they may grow in total
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SpillConfig {
+    /// Directory scratch files and directories are created in
+    pub temp_dir: PathBuf,
+
+    /// Cap on scratch bytes in use at once, across subsystems (None = unlimited)
+    pub max_spill_bytes: Option<u64>,
+}
+
+impl Default for SpillConfig {
+    fn default() -> Self {
+        Self {
+            temp_dir: std::env::temp_dir(),
+            max_spill_bytes: None,
+        }
+    }
+}
+
+/// Accounts for scratch space in use under a `SpillConfig`
+#[derive(Debug, Default)]
+pub struct SpillManager {
+    config: SpillConfig,
+    used: AtomicU64,
+}
+
+impl SpillManager {
+    pub fn new(config: SpillConfig) -> Self {
+        Self {
+            config,
+            used: AtomicU64::new(0),
+        }
+    }
+
+    pub fn config(&self) -> &SpillConfig {
+        &self.config
+    }
+
+    pub fn temp_dir(&self) -> &Path {
+        &self.config.temp_dir
+    }
+
+    /// Scratch bytes currently reserved
+    pub fn used(&self) -> u64 {
+        self.used.load(Ordering::SeqCst)
+    }
+
+    /// Bytes that can still be reserved, `None` if there is no cap
+    pub fn remaining(&self) -> Option<u64> {
+        self.config
+            .max_spill_bytes
+            .map(|limit| limit.saturating_sub(self.used()))
+    }
+
+    /// Reserve `bytes` of scratch space, released when the reservation drops
+    pub fn reserve(self: &Arc<Self>, bytes: u64) -> Result<SpillReservation> {
+        let mut reservation = SpillReservation {
+            spill: self.clone(),
+            bytes: 0,
+        };
+        reservation.grow(bytes)?;
+        Ok(reservation)
+    }
+
+    /// Create a new, uniquely named directory under `temp_dir`
+    pub fn create_dir(&self, prefix: &str) -> Result<PathBuf> {
+        static COUNTER: AtomicU64 = AtomicU64::new(0);
+        let nanos = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_nanos();
+        let dir = self.config.temp_dir.join(format!(
+            "{}_{}_{}_{}",
+            prefix,
+            std::process::id(),
+            nanos,
+            COUNTER.fetch_add(1, Ordering::Relaxed)
+        ));
+        std::fs::create_dir_all(&dir)?;
+        Ok(dir)
+    }
+
+    fn try_add(&self, bytes: u64) -> Result<()> {
+        let limit = self.config.max_spill_bytes;
+        self.used
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |used| {
+                let total = used.checked_add(bytes)?;
+                limit.is_none_or(|limit| total <= limit).then_some(total)
+            })
+            .map(|_| ())
+            .map_err(|used| Error::SpillLimitExceeded {
+                requested: bytes,
+                used,
+                limit: limit.unwrap_or(u64::MAX),
+            })
+    }
+
+    fn sub(&self, bytes: u64) {
+        let _ = self
+            .used
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |used| {
+                Some(used.saturating_sub(bytes))
+            });
+    }
+}
+
+/// Scratch space held by one operation
+#[derive(Debug)]
+pub struct SpillReservation {
+    spill: Arc<SpillManager>,
+    bytes: u64,
+}
+
+impl SpillReservation {
+    /// Reserve `bytes` more, failing with `Error::SpillLimitExceeded` past the cap
+    pub fn grow(&mut self, bytes: u64) -> Result<()> {
+        self.spill.try_add(bytes)?;
+        self.bytes += bytes;
+        Ok(())
+    }
+
+    /// Bytes held
+    pub fn size(&self) -> u64 {
+        self.bytes
+    }
+}
+
+impl Drop for SpillReservation {
+    fn drop(&mut self) {
+        self.spill.sub(self.bytes);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reservations_respect_cap() {
+        let spill = Arc::new(SpillManager::new(SpillConfig {
+            temp_dir: std::env::temp_dir(),
+            max_spill_bytes: Some(100),
+        }));
+
+        let mut first = spill.reserve(60).unwrap();
+        let err = spill.reserve(50).unwrap_err();
+        assert!(
+            matches!(
+                err,
+                Error::SpillLimitExceeded {
+                    requested: 50,
+                    used: 60,
+                    limit: 100
+                }
+            ),
+            "{}",
+            err
+        );
+        assert!(first.grow(41).is_err());
+        first.grow(40).unwrap();
+        assert_eq!(spill.remaining(), Some(0));
+
+        drop(first);
+        assert_eq!(spill.used(), 0);
+        assert_eq!(spill.reserve(100).unwrap().size(), 100);
+    }
+
+    #[test]
+    fn test_dirs_are_created_under_temp_dir() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let spill = SpillManager::new(SpillConfig {
+            temp_dir: temp_dir.path().join("scratch"),
+            max_spill_bytes: None,
+        });
+
+        let a = spill.create_dir("cache").unwrap();
+        let b = spill.create_dir("cache").unwrap();
+        assert_ne!(a, b);
+        assert!(a.is_dir() && a.starts_with(temp_dir.path().join("scratch")));
+        assert_eq!(spill.remaining(), None);
+    }
+}
@@ -1,10 +1,19 @@
 //! Explicit transaction support for FSDB
 //!
 //! Provides user-controlled transaction boundaries with ACID guarantees.
+//!
+//! Open transactions are registered on their database, where they can be
+//! listed and aborted from outside with `DatabaseOps::abort_transaction`.
+//! An aborted transaction's locks are released at once, and its owner's
+//! next operation fails with `Error::Cancelled`.
 
+use crate::delta_lake::CommitResult;
+use crate::lock_manager::LockMode;
 use crate::{database_ops::DatabaseOps, Error, Result};
 use arrow::record_batch::RecordBatch;
+use std::collections::HashMap;
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 
 /// Transaction lifecycle state
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -12,6 +21,58 @@ enum TxnLifecycleState {
     Active,
     Committed,
     Aborted,
+    /// Aborted by `DatabaseOps::abort_transaction`
+    Cancelled,
+}
+
+impl TxnLifecycleState {
+    /// Error for an operation attempted outside the `Active` state
+    fn inactive_error(&self, txn_id: u64) -> Error {
+        match self {
+            TxnLifecycleState::Cancelled => Error::Cancelled(format!("transaction {}", txn_id)),
+            state => Error::Other(format!("Transaction is not active: {:?}", state)),
+        }
+    }
+}
+
+/// A transaction's entry in its database's registry of open transactions
+pub(crate) struct TransactionEntry {
+    started: Instant,
+    /// Shared with the handle so the transaction can be aborted from outside
+    state: tokio::sync::Mutex<TxnLifecycleState>,
+}
+
+impl TransactionEntry {
+    /// Mark an active transaction cancelled, returning false if it had
+    /// already ended
+    pub(crate) async fn cancel(&self) -> bool {
+        let mut state = self.entry.state.lock().await;
+        if *state != TxnLifecycleState::Active {
+            return false;
+        }
+        *state = TxnLifecycleState::Cancelled;
+        true
+    }
+}
+
+/// An open transaction, as listed by `DatabaseOps::list_transactions`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TransactionInfo {
+    pub id: u64,
+    /// Time since the transaction began
+    pub age: Duration,
+    /// Number of resources it holds a lock on
+    pub locks_held: usize,
+}
+
+impl TransactionInfo {
+    pub(crate) fn new(id: u64, entry: &TransactionEntry, locks_held: usize) -> Self {
+        Self {
+            id,
+            age: entry.started.elapsed(),
+            locks_held,
+        }
+    }
 }
 
 /// Explicit transaction handle for user-controlled transaction boundaries
@@ -20,7 +81,6 @@ pub struct Transaction {
     db: Arc<DatabaseOps>,
 
     /// Transaction ID
-    #[allow(dead_code)]
     txn_id: u64,
 
     /// Snapshot for this transaction (MVCC isolation)
@@ -33,35 +93,82 @@ pub struct Transaction {
     /// Uncommitted write buffer (RecordBatches to be written on commit)
     write_buffer: Arc<tokio::sync::Mutex<Vec<RecordBatch>>>,
 
-    /// Transaction lifecycle state
-    state: Arc<tokio::sync::Mutex<TxnLifecycleState>>,
+    /// Lifecycle state, registered on the database while open
+    entry: Arc<TransactionEntry>,
 }
 
 impl Transaction {
     /// Create a new transaction
     pub(crate) fn new(db: Arc<DatabaseOps>, txn_id: u64, snapshot_version: u64) -> Self {
+        let entry = Arc::new(TransactionEntry {
+            started: Instant::now(),
+            state: tokio::sync::Mutex::new(TxnLifecycleState::Active),
+        });
+        db.open_transactions
+            .lock()
+            .unwrap()
+            .insert(txn_id, entry.clone());
         Self {
             db,
             txn_id,
             snapshot_version,
             snapshot_data: Arc::new(tokio::sync::Mutex::new(None)),
             write_buffer: Arc::new(tokio::sync::Mutex::new(Vec::new())),
-            state: Arc::new(tokio::sync::Mutex::new(TxnLifecycleState::Active)),
+            entry,
         }
     }
 
+    /// Stop counting the transaction as open, which lets auto-compaction run
+    fn end(&self) {
+        self.db
+            .open_transactions
+            .lock()
+            .unwrap()
+            .remove(&self.txn_id);
+    }
+
+    /// Transaction ID, also used for deadlock victim selection
+    pub fn id(&self) -> u64 {
+        self.txn_id
+    }
+
+    /// Lock a resource (e.g. `"table:data"`) until the transaction ends
+    ///
+    /// Waits while another transaction holds it incompatibly. If waiting
+    /// would deadlock and this transaction is chosen as the victim, it is
+    /// aborted and `Error::DeadlockDetected` is returned.
+    pub async fn lock(&self, resource: &str, mode: LockMode) -> Result<()> {
+        let state = self.entry.state.lock().await;
+        if *state != TxnLifecycleState::Active {
+            return Err(state.inactive_error(self.txn_id));
+        }
+        drop(state);
+
+        let result = self
+            .db
+            .lock_manager
+            .acquire(self.txn_id, resource, mode)
+            .await;
+        if let Err(Error::DeadlockDetected { .. }) = &result {
+            *self.entry.state.lock().await = TxnLifecycleState::Aborted;
+            self.write_buffer.lock().await.clear();
+            self.end();
+        }
+        result
+    }
+
     /// Insert data within this transaction (uncommitted until commit)
     pub async fn insert(&self, batch: RecordBatch) -> Result<()> {
         // Check transaction state
-        let state = self.state.lock().await;
+        let state = self.entry.state.lock().await;
         if *state != TxnLifecycleState::Active {
-            return Err(Error::Other(format!(
-                "Transaction is not active: {:?}",
-                state
-            )));
+            return Err(state.inactive_error(self.txn_id));
         }
         drop(state);
 
+        // Defaults are filled here so the transaction's own queries see them
+        let batch = crate::metadata::apply_defaults(batch, &self.db.schema()).await?;
+
         // Add to write buffer
         let mut buffer = self.write_buffer.lock().await;
         buffer.push(batch);
@@ -74,7 +181,12 @@ impl Transaction {
     /// plus uncommitted writes from this transaction.
     pub async fn query(&self, sql: &str) -> Result<Vec<RecordBatch>> {
         use datafusion::datasource::MemTable;
-        use datafusion::prelude::*;
+
+        let state = self.entry.state.lock().await;
+        if *state == TxnLifecycleState::Cancelled {
+            return Err(state.inactive_error(self.txn_id));
+        }
+        drop(state);
 
         // Lazy-load snapshot on first query
         {
@@ -104,7 +216,7 @@ impl Transaction {
 
         // Case 1: No uncommitted writes - query snapshot only
         if !has_uncommitted {
-            let ctx = SessionContext::new();
+            let ctx = self.db.session_context();
 
             if has_committed {
                 let schema = committed_batches[0].schema();
@@ -118,12 +230,13 @@ impl Transaction {
                 ctx.register_table("data", Arc::new(empty_table))?;
             }
 
-            let df = ctx.sql(sql).await?;
+            let df =
+                crate::query::identifiers::plan_sql(&ctx, sql, self.db.identifier_case).await?;
             return Ok(df.collect().await?);
         }
 
         // Case 2: Has uncommitted writes - create union view
-        let ctx = SessionContext::new();
+        let ctx = self.db.session_context();
 
         if has_committed {
             // Compute unified schema to handle schema evolution
@@ -164,7 +277,7 @@ impl Transaction {
         }
 
         // Execute the user's query on the combined view
-        let df = ctx.sql(sql).await?;
+        let df = crate::query::identifiers::plan_sql(&ctx, sql, self.db.identifier_case).await?;
         let results = df.collect().await?;
 
         Ok(results)
@@ -173,12 +286,9 @@ impl Transaction {
     /// Delete rows matching WHERE clause within this transaction
     pub async fn delete_rows_where(&self, where_clause: &str) -> Result<()> {
         // Check transaction state
-        let state = self.state.lock().await;
+        let state = self.entry.state.lock().await;
         if *state != TxnLifecycleState::Active {
-            return Err(Error::Other(format!(
-                "Transaction is not active: {:?}",
-                state
-            )));
+            return Err(state.inactive_error(self.txn_id));
         }
         drop(state);
 
@@ -189,23 +299,41 @@ impl Transaction {
     }
 
     /// Commit the transaction, persisting all writes
-    pub async fn commit(self) -> Result<()> {
+    ///
+    /// Buffered inserts are written as one Delta version, which is returned.
+    /// A transaction with nothing buffered commits nothing and returns the
+    /// current version.
+    pub async fn commit(self) -> Result<CommitResult> {
+        self.commit_with_metadata(HashMap::new()).await
+    }
+
+    /// Commit the transaction like `commit`, recording `metadata` in the
+    /// Delta commit's `commitInfo`
+    ///
+    /// Metadata over the size limit fails with
+    /// `Error::CommitMetadataTooLarge` without writing anything, which like
+    /// any failed commit discards the transaction.
+    pub async fn commit_with_metadata(
+        self,
+        metadata: HashMap<String, String>,
+    ) -> Result<CommitResult> {
+        crate::delta_lake::commit::check_metadata_size(&metadata)?;
+
         // Check and update state
-        let mut state = self.state.lock().await;
+        let mut state = self.entry.state.lock().await;
         if *state != TxnLifecycleState::Active {
-            return Err(Error::Other(format!(
-                "Transaction is not active: {:?}",
-                state
-            )));
+            return Err(state.inactive_error(self.txn_id));
         }
         *state = TxnLifecycleState::Committed;
         drop(state);
+        self.end();
 
         // Get all batches from write buffer
         let buffer = self.write_buffer.lock().await;
         if buffer.is_empty() {
             // No writes to commit
-            return Ok(());
+            let table = self.db.get_delta_table().await?;
+            return CommitResult::at(&table, "INSERT", 0).await;
         }
 
         // Concatenate all batches into one
@@ -213,14 +341,15 @@ impl Transaction {
         let all_batches = arrow::compute::concat_batches(&schema, buffer.iter())?;
 
         // Write as single transaction
-        self.db.insert(all_batches).await?;
-
-        Ok(())
+        crate::delta_lake::commit::with_metadata(metadata, self.db.insert(all_batches)).await
     }
 
     /// Rollback the transaction, discarding all writes
     pub async fn rollback(self) -> Result<()> {
-        let mut state = self.state.lock().await;
+        let mut state = self.entry.state.lock().await;
+        if *state == TxnLifecycleState::Cancelled {
+            return Err(state.inactive_error(self.txn_id));
+        }
         if *state != TxnLifecycleState::Active {
             return Err(Error::Other(format!(
                 "Cannot rollback transaction in state: {:?}",
@@ -229,6 +358,7 @@ impl Transaction {
         }
         *state = TxnLifecycleState::Aborted;
         drop(state);
+        self.end();
 
         // Clear write buffer
         let mut buffer = self.write_buffer.lock().await;
@@ -237,3 +367,11 @@ impl Transaction {
         Ok(())
     }
 }
+
+impl Drop for Transaction {
+    fn drop(&mut self) {
+        // Commit, rollback and abandoning a transaction all end its locks
+        self.db.lock_manager.release_all(self.txn_id);
+        self.end();
+    }
+}
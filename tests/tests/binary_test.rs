@@ -0,0 +1,129 @@
+use arrow::array::{Array, AsArray, BinaryArray, Int32Array, Int64Array, RecordBatch};
+use arrow::datatypes::{DataType, Field, Schema};
+use fsdb::database_ops::DatabaseOps;
+use fsdb::nfs::coercion::{BinaryEncoding, CoercionPolicy};
+use fsdb::nfs::file_views::CsvFileView;
+use fsdb::query::Value;
+use std::sync::Arc;
+use tempfile::TempDir;
+
+/// Bytes that aren't valid UTF-8 and include a zero byte
+const PAYLOAD: &[u8] = &[0x00, 0xff, 0x10, 0x80];
+
+async fn create_db(temp_dir: &TempDir) -> Arc<DatabaseOps> {
+    let schema = Arc::new(Schema::new(vec![
+        Field::new("id", DataType::Int32, false),
+        Field::new("payload", DataType::Binary, true),
+    ]));
+    let db = DatabaseOps::create(temp_dir.path().join("db"), schema.clone())
+        .await
+        .unwrap();
+
+    let batch = RecordBatch::try_new(
+        schema,
+        vec![
+            Arc::new(Int32Array::from(vec![1, 2, 3])),
+            Arc::new(BinaryArray::from(vec![
+                Some(PAYLOAD),
+                Some(&b"hello"[..]),
+                None,
+            ])),
+        ],
+    )
+    .unwrap();
+    db.insert(batch).await.unwrap();
+    Arc::new(db)
+}
+
+#[tokio::test]
+async fn test_binary_round_trips_through_typed_api() {
+    let temp_dir = TempDir::new().unwrap();
+    let db = create_db(&temp_dir).await;
+
+    let results = db
+        .query("SELECT payload FROM data ORDER BY id")
+        .await
+        .unwrap();
+    let payload = results[0].column(0).as_binary::<i32>();
+    assert_eq!(payload.value(0), PAYLOAD);
+    assert_eq!(payload.value(1), b"hello");
+    assert!(payload.is_null(2));
+
+    // Equality compares bytes
+    let result = db
+        .query_rows("SELECT id, payload FROM data WHERE payload = X'00FF1080'")
+        .await
+        .unwrap();
+    assert_eq!(result.len(), 1);
+    assert_eq!(result.get(0, "id"), Some(&Value::Int(1)));
+    assert_eq!(
+        result.get(0, "payload"),
+        Some(&Value::Bytes(PAYLOAD.to_vec()))
+    );
+
+    let results = db
+        .query("SELECT COUNT(*) FROM data WHERE payload IS NULL")
+        .await
+        .unwrap();
+    let count = results[0]
+        .column(0)
+        .as_any()
+        .downcast_ref::<Int64Array>()
+        .unwrap()
+        .value(0);
+    assert_eq!(count, 1);
+}
+
+#[tokio::test]
+async fn test_csv_view_renders_binary_as_base64() {
+    let temp_dir = TempDir::new().unwrap();
+    let db = create_db(&temp_dir).await;
+
+    let view = CsvFileView::new(db.clone());
+    let csv = String::from_utf8(view.generate_csv().await.unwrap()).unwrap();
+    assert!(csv.contains("1,AP8QgA=="), "{}", csv);
+    assert!(csv.contains("2,aGVsbG8="), "{}", csv);
+
+    // Change one payload and write the file back
+    let edited = csv.replace("aGVsbG8=", "d29ybGQ=");
+    view.apply_write(edited.as_bytes(), None).await.unwrap();
+
+    let result = db
+        .query_rows("SELECT payload FROM data ORDER BY id")
+        .await
+        .unwrap();
+    assert_eq!(
+        result.get(0, "payload"),
+        Some(&Value::Bytes(PAYLOAD.to_vec()))
+    );
+    assert_eq!(
+        result.get(1, "payload"),
+        Some(&Value::Bytes(b"world".to_vec()))
+    );
+    assert_eq!(result.get(2, "payload"), Some(&Value::Null));
+}
+
+#[tokio::test]
+async fn test_csv_view_hex_dialect() {
+    let temp_dir = TempDir::new().unwrap();
+    let db = create_db(&temp_dir).await;
+
+    let policy = CoercionPolicy::default().with_binary_encoding(BinaryEncoding::Hex);
+    let view = CsvFileView::new(db.clone()).with_coercion(policy);
+    let csv = String::from_utf8(view.generate_csv().await.unwrap()).unwrap();
+    assert!(csv.contains("1,00ff1080"), "{}", csv);
+
+    // Appended rows are read as hex too
+    view.apply_write(b"4,DEADBEEF\n", None).await.unwrap();
+    let result = db
+        .query_rows("SELECT payload FROM data WHERE id = 4")
+        .await
+        .unwrap();
+    assert_eq!(
+        result.get(0, "payload"),
+        Some(&Value::Bytes(vec![0xde, 0xad, 0xbe, 0xef]))
+    );
+
+    // Text that isn't hex is rejected rather than stored as its UTF-8 bytes
+    assert!(view.apply_write(b"5,zz\n", None).await.is_err());
+}
@@ -0,0 +1,215 @@
+// Bulk Load Integration Tests
+// Tests streaming CSV/Parquet ingest with batched commits, rollback and rejects
+
+use arrow::array::{ArrayRef, Int32Array, Int64Array, RecordBatch, StringArray};
+use arrow::datatypes::{DataType, Field, Schema, SchemaRef};
+use fsdb::bulk_load::LoadOptions;
+use fsdb::storage::parquet::ParquetWriter;
+use fsdb::{DatabaseOps, Error};
+use std::fmt::Write as _;
+use std::path::Path;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use tempfile::TempDir;
+
+const ROWS: i32 = 5000;
+
+fn create_schema() -> SchemaRef {
+    Arc::new(Schema::new(vec![
+        Field::new("id", DataType::Int32, false),
+        Field::new("name", DataType::Utf8, false),
+        Field::new("score", DataType::Int32, true),
+    ]))
+}
+
+/// Write a CSV with `ROWS` rows; rows listed in `bad_rows` get a non-numeric id
+fn write_csv(path: &Path, bad_rows: &[i32]) {
+    let mut csv = String::from("id,name,score\n");
+    for i in 1..=ROWS {
+        if bad_rows.contains(&i) {
+            writeln!(csv, "oops,user_{},{}", i, i % 100).unwrap();
+        } else {
+            writeln!(csv, "{},user_{},{}", i, i, i % 100).unwrap();
+        }
+    }
+    std::fs::write(path, csv).unwrap();
+}
+
+async fn count_rows(db: &DatabaseOps) -> i64 {
+    let results = db.query("SELECT COUNT(*) FROM data").await.unwrap();
+    results[0]
+        .column(0)
+        .as_any()
+        .downcast_ref::<Int64Array>()
+        .unwrap()
+        .value(0)
+}
+
+#[tokio::test]
+async fn test_load_csv_in_batches_with_progress() {
+    let temp_dir = TempDir::new().unwrap();
+    let csv_path = temp_dir.path().join("users.csv");
+    write_csv(&csv_path, &[]);
+
+    let db = DatabaseOps::create(temp_dir.path().join("load_db"), create_schema())
+        .await
+        .unwrap();
+
+    let callbacks = Arc::new(AtomicU64::new(0));
+    let last_loaded = Arc::new(AtomicU64::new(0));
+    let options = {
+        let callbacks = callbacks.clone();
+        let last_loaded = last_loaded.clone();
+        LoadOptions::default()
+            .with_commit_rows(1000)
+            .with_progress(move |progress| {
+                callbacks.fetch_add(1, Ordering::Relaxed);
+                last_loaded.store(progress.rows_loaded, Ordering::Relaxed);
+            })
+    };
+
+    let report = db.load_from_csv(&csv_path, options).await.unwrap();
+
+    assert_eq!(report.rows_loaded, ROWS as u64);
+    assert_eq!(report.rows_rejected, 0);
+    assert!(report.commits >= 5, "5000 rows at 1000 per commit");
+    assert!(report.reject_file.is_none());
+    assert!(callbacks.load(Ordering::Relaxed) >= report.commits);
+    assert_eq!(last_loaded.load(Ordering::Relaxed), ROWS as u64);
+    assert_eq!(count_rows(&db).await, ROWS as i64);
+}
+
+#[tokio::test]
+async fn test_load_csv_rolls_back_on_error() {
+    let temp_dir = TempDir::new().unwrap();
+    let csv_path = temp_dir.path().join("users.csv");
+    // The bad row comes after several commits have already landed
+    write_csv(&csv_path, &[4500]);
+
+    let schema = create_schema();
+    let db = DatabaseOps::create(temp_dir.path().join("load_db"), schema.clone())
+        .await
+        .unwrap();
+    db.insert(
+        RecordBatch::try_new(
+            schema,
+            vec![
+                Arc::new(Int32Array::from(vec![0])) as ArrayRef,
+                Arc::new(StringArray::from(vec!["existing"])) as ArrayRef,
+                Arc::new(Int32Array::from(vec![Some(1)])) as ArrayRef,
+            ],
+        )
+        .unwrap(),
+    )
+    .await
+    .unwrap();
+
+    let options = LoadOptions {
+        read_batch_rows: 500,
+        ..LoadOptions::default()
+    }
+    .with_commit_rows(1000);
+
+    match db.load_from_csv(&csv_path, options).await {
+        Err(Error::TypeCoercion { row, column, .. }) => {
+            assert_eq!(row, 4500);
+            assert_eq!(column, "id");
+        }
+        other => panic!("expected TypeCoercion, got {:?}", other),
+    }
+
+    // Only the row that existed before the load remains
+    assert_eq!(count_rows(&db).await, 1);
+}
+
+#[tokio::test]
+async fn test_load_csv_continue_on_error_writes_rejects() {
+    let temp_dir = TempDir::new().unwrap();
+    let csv_path = temp_dir.path().join("users.csv");
+    let reject_path = temp_dir.path().join("rejects.csv");
+    write_csv(&csv_path, &[10, 2500, 4999]);
+
+    let db = DatabaseOps::create(temp_dir.path().join("load_db"), create_schema())
+        .await
+        .unwrap();
+
+    let report = db
+        .load_from_csv(
+            &csv_path,
+            LoadOptions::default().with_continue_on_error(&reject_path),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(report.rows_loaded, ROWS as u64 - 3);
+    assert_eq!(report.rows_rejected, 3);
+    assert_eq!(report.reject_file.as_deref(), Some(reject_path.as_path()));
+    assert_eq!(count_rows(&db).await, ROWS as i64 - 3);
+
+    let rejects = std::fs::read_to_string(&reject_path).unwrap();
+    let lines: Vec<&str> = rejects.lines().collect();
+    assert_eq!(lines[0], "id,name,score,error");
+    assert_eq!(lines.len(), 4);
+    assert!(lines[1].starts_with("oops,user_10,10,"));
+}
+
+#[tokio::test]
+async fn test_load_csv_rejects_unknown_column() {
+    let temp_dir = TempDir::new().unwrap();
+    let csv_path = temp_dir.path().join("bad_header.csv");
+    std::fs::write(&csv_path, "id,name,nickname\n1,alice,al\n").unwrap();
+
+    let db = DatabaseOps::create(temp_dir.path().join("load_db"), create_schema())
+        .await
+        .unwrap();
+
+    let result = db.load_from_csv(&csv_path, LoadOptions::default()).await;
+    assert!(matches!(result, Err(Error::InvalidOperation(_))));
+    assert_eq!(count_rows(&db).await, 0);
+}
+
+#[tokio::test]
+async fn test_load_parquet() {
+    let temp_dir = TempDir::new().unwrap();
+    let parquet_path = temp_dir.path().join("users.parquet");
+
+    // Source stores ids as Int64; they are cast to the table's Int32
+    let source_schema = Arc::new(Schema::new(vec![
+        Field::new("id", DataType::Int64, false),
+        Field::new("name", DataType::Utf8, false),
+    ]));
+    let ids: Vec<i64> = (1..=ROWS as i64).collect();
+    let names: Vec<String> = ids.iter().map(|i| format!("user_{}", i)).collect();
+    let batch = RecordBatch::try_new(
+        source_schema,
+        vec![
+            Arc::new(Int64Array::from(ids)) as ArrayRef,
+            Arc::new(StringArray::from(names)) as ArrayRef,
+        ],
+    )
+    .unwrap();
+    ParquetWriter::new()
+        .write_batch(&parquet_path, &batch)
+        .unwrap();
+
+    let db = DatabaseOps::create(temp_dir.path().join("load_db"), create_schema())
+        .await
+        .unwrap();
+
+    let report = db.load_from_parquet(&parquet_path).await.unwrap();
+    assert_eq!(report.rows_loaded, ROWS as u64);
+    assert_eq!(count_rows(&db).await, ROWS as i64);
+
+    // The missing nullable column loads as NULL
+    let results = db
+        .query("SELECT COUNT(*) FROM data WHERE score IS NULL")
+        .await
+        .unwrap();
+    let nulls = results[0]
+        .column(0)
+        .as_any()
+        .downcast_ref::<Int64Array>()
+        .unwrap()
+        .value(0);
+    assert_eq!(nulls, ROWS as i64);
+}
@@ -0,0 +1,215 @@
+// Cast View Integration Tests
+// Tests reading columns through schema-on-read cast views: lenient and strict
+// handling of values that don't convert, that filters on cast columns never
+// skip files while others still do, and the CSV view of a cast view
+
+use arrow::array::{ArrayRef, Int32Array, Int64Array, RecordBatch, StringArray};
+use arrow::datatypes::{DataType, Field, Schema, SchemaRef, TimeUnit};
+use fsdb::DatabaseOps;
+use fsdb::nfs::file_views::CsvFileView;
+use fsdb::query::{CastMode, CastView, ResultSet, Value};
+use std::path::Path;
+use std::sync::Arc;
+use tempfile::TempDir;
+
+fn create_schema() -> SchemaRef {
+    Arc::new(Schema::new(vec![
+        Field::new("id", DataType::Int64, false),
+        Field::new("ts", DataType::Utf8, false),
+        Field::new("amount", DataType::Utf8, false),
+        Field::new("qty", DataType::Int32, false),
+    ]))
+}
+
+fn batch(ids: &[i64], ts: &[&str], amounts: &[&str], qty: &[i32]) -> RecordBatch {
+    RecordBatch::try_new(
+        create_schema(),
+        vec![
+            Arc::new(Int64Array::from(ids.to_vec())) as ArrayRef,
+            Arc::new(StringArray::from(ts.to_vec())) as ArrayRef,
+            Arc::new(StringArray::from(amounts.to_vec())) as ArrayRef,
+            Arc::new(Int32Array::from(qty.to_vec())) as ArrayRef,
+        ],
+    )
+    .unwrap()
+}
+
+/// Two files; row 2 holds a timestamp and an amount that don't convert
+async fn create_db(path: &Path) -> DatabaseOps {
+    let db = DatabaseOps::create(path, create_schema()).await.unwrap();
+    db.insert(batch(
+        &[1, 2],
+        &["2024-01-05 10:00:00", "not a date"],
+        &["12.5", "n/a"],
+        &[3, 4],
+    ))
+    .await
+    .unwrap();
+    db.insert(batch(
+        &[10, 11],
+        &["2024-07-01 00:00:00", "2024-08-15 12:30:00"],
+        &["7", "100.25"],
+        &[5, 6],
+    ))
+    .await
+    .unwrap();
+    db
+}
+
+fn typed(mode: CastMode) -> CastView {
+    CastView::new(mode)
+        .with_cast("ts", DataType::Timestamp(TimeUnit::Microsecond, None))
+        .with_cast("amount", DataType::Float64)
+        .with_cast("qty", DataType::Float64)
+}
+
+fn ids(rows: &ResultSet) -> Vec<i64> {
+    (0..rows.len())
+        .map(|row| match rows.get(row, "id").unwrap() {
+            Value::Int(v) => *v,
+            other => panic!("expected an integer, got {:?}", other),
+        })
+        .collect()
+}
+
+#[tokio::test]
+async fn test_lenient_view_reads_bad_values_as_null() {
+    let temp_dir = TempDir::new().unwrap();
+    let db = create_db(&temp_dir.path().join("db")).await;
+    db.create_cast_view("typed", typed(CastMode::Lenient))
+        .await
+        .unwrap();
+    assert_eq!(db.cast_view_names().unwrap(), vec!["typed".to_string()]);
+
+    let rows = db
+        .query_rows("SELECT id, ts, amount, qty FROM typed ORDER BY id")
+        .await
+        .unwrap();
+    assert_eq!(ids(&rows), vec![1, 2, 10, 11]);
+    assert!(matches!(
+        rows.get(0, "ts").unwrap(),
+        Value::Timestamp {
+            unit: TimeUnit::Microsecond,
+            timezone: None,
+            ..
+        }
+    ));
+    assert_eq!(rows.get(0, "amount").unwrap(), &Value::Float(12.5));
+    assert_eq!(rows.get(0, "qty").unwrap(), &Value::Float(3.0));
+    assert_eq!(rows.get(1, "ts").unwrap(), &Value::Null);
+    assert_eq!(rows.get(1, "amount").unwrap(), &Value::Null);
+
+    // Comparisons use the cast types
+    let rows = db
+        .query_rows("SELECT id FROM typed WHERE ts > '2024-06-01' ORDER BY id")
+        .await
+        .unwrap();
+    assert_eq!(ids(&rows), vec![10, 11]);
+    let rows = db
+        .query_rows("SELECT SUM(amount) AS total FROM typed")
+        .await
+        .unwrap();
+    assert_eq!(rows.get(0, "total").unwrap(), &Value::Float(119.75));
+
+    // The stored columns are untouched
+    let rows = db
+        .query_rows("SELECT amount FROM data WHERE id = 2")
+        .await
+        .unwrap();
+    assert_eq!(rows.get(0, "amount").unwrap(), &Value::String("n/a".into()));
+}
+
+#[tokio::test]
+async fn test_strict_view_fails_on_bad_values() {
+    let temp_dir = TempDir::new().unwrap();
+    let db = create_db(&temp_dir.path().join("db")).await;
+    db.create_cast_view("strict_typed", typed(CastMode::Strict))
+        .await
+        .unwrap();
+
+    assert!(db.query_rows("SELECT * FROM strict_typed").await.is_err());
+
+    // Rows that all convert read fine
+    let rows = db
+        .query_rows("SELECT id, amount FROM strict_typed WHERE id >= 10 ORDER BY id")
+        .await
+        .unwrap();
+    assert_eq!(ids(&rows), vec![10, 11]);
+    assert_eq!(rows.get(1, "amount").unwrap(), &Value::Float(100.25));
+}
+
+#[tokio::test]
+async fn test_filters_on_cast_columns_skip_no_files() {
+    let temp_dir = TempDir::new().unwrap();
+    let db = create_db(&temp_dir.path().join("db")).await;
+    db.create_cast_view("typed", typed(CastMode::Lenient))
+        .await
+        .unwrap();
+
+    // As strings, "100.25" sorts below "12.5": stored statistics can't be used
+    let rows = db
+        .query_rows("SELECT id FROM typed WHERE amount > 50")
+        .await
+        .unwrap();
+    assert_eq!(ids(&rows), vec![11]);
+    let stats = db.get_data_skipping_stats().await.unwrap();
+    assert_eq!((stats.files_read, stats.files_skipped), (2, 0));
+
+    // Columns the view doesn't cast still skip files
+    let rows = db
+        .query_rows("SELECT id FROM typed WHERE id >= 10")
+        .await
+        .unwrap();
+    let mut read = ids(&rows);
+    read.sort();
+    assert_eq!(read, vec![10, 11]);
+    let stats = db.get_data_skipping_stats().await.unwrap();
+    assert_eq!((stats.files_read, stats.files_skipped), (1, 1));
+}
+
+#[tokio::test]
+async fn test_csv_view_of_cast_view() {
+    let temp_dir = TempDir::new().unwrap();
+    let db = Arc::new(create_db(&temp_dir.path().join("db")).await);
+    db.create_cast_view("typed", typed(CastMode::Lenient))
+        .await
+        .unwrap();
+
+    let view = CsvFileView::new(db.clone()).with_cast_view("typed");
+    let csv = String::from_utf8(view.generate_csv().await.unwrap()).unwrap();
+    assert!(csv.starts_with("id,ts,amount,qty\n"));
+    assert!(csv.contains("12.5"));
+    assert!(!csv.contains("n/a"));
+    assert!(!csv.contains("not a date"));
+
+    // Read-only, unlike the table's own view
+    assert!(view.apply_write(b"id,ts,amount,qty\n", None).await.is_err());
+}
+
+#[tokio::test]
+async fn test_invalid_views_are_rejected() {
+    let temp_dir = TempDir::new().unwrap();
+    let db = create_db(&temp_dir.path().join("db")).await;
+
+    let unknown = CastView::new(CastMode::Lenient).with_cast("missing", DataType::Float64);
+    assert!(db.create_cast_view("typed", unknown).await.is_err());
+    let impossible = CastView::new(CastMode::Lenient).with_cast(
+        "qty",
+        DataType::Struct(vec![Field::new("x", DataType::Int32, true)].into()),
+    );
+    assert!(db.create_cast_view("typed", impossible).await.is_err());
+    assert!(
+        db.create_cast_view("data", typed(CastMode::Lenient))
+            .await
+            .is_err()
+    );
+    assert!(db.cast_view_names().unwrap().is_empty());
+
+    // A dropped view can't be queried
+    db.create_cast_view("typed", typed(CastMode::Lenient))
+        .await
+        .unwrap();
+    assert!(db.drop_cast_view("typed").await.unwrap());
+    assert!(!db.drop_cast_view("typed").await.unwrap());
+    assert!(db.query_rows("SELECT * FROM typed").await.is_err());
+}
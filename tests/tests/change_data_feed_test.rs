@@ -0,0 +1,131 @@
+// Change Data Feed Integration Tests
+// Tests enabling the change data feed, reading inserted, updated and deleted
+// rows with their change type and commit version, and the version ranges and
+// tables read_changes refuses
+
+use arrow::array::{ArrayRef, Int32Array, Int64Array, RecordBatch, StringArray};
+use arrow::datatypes::{DataType, Field, Schema, SchemaRef};
+use fsdb::{DatabaseOps, Error};
+use std::sync::Arc;
+use tempfile::TempDir;
+
+fn create_schema() -> SchemaRef {
+    Arc::new(Schema::new(vec![
+        Field::new("id", DataType::Int32, false),
+        Field::new("value", DataType::Utf8, true),
+    ]))
+}
+
+fn batch(ids: Vec<i32>, values: Vec<&str>) -> RecordBatch {
+    RecordBatch::try_new(
+        create_schema(),
+        vec![
+            Arc::new(Int32Array::from(ids)) as ArrayRef,
+            Arc::new(StringArray::from(values)) as ArrayRef,
+        ],
+    )
+    .unwrap()
+}
+
+/// (version, change type, id, value) of each change row, sorted
+fn changes(batches: &[RecordBatch]) -> Vec<(i64, String, i32, String)> {
+    let mut rows = Vec::new();
+    for batch in batches {
+        let column = |name: &str| batch.column_by_name(name).unwrap().clone();
+        let ids = column("id");
+        let ids = ids.as_any().downcast_ref::<Int32Array>().unwrap();
+        let values = column("value");
+        let values = values.as_any().downcast_ref::<StringArray>().unwrap();
+        let types = column("_change_type");
+        let types = types.as_any().downcast_ref::<StringArray>().unwrap();
+        let versions = column("_commit_version");
+        let versions = versions.as_any().downcast_ref::<Int64Array>().unwrap();
+        for i in 0..batch.num_rows() {
+            rows.push((
+                versions.value(i),
+                types.value(i).to_string(),
+                ids.value(i),
+                values.value(i).to_string(),
+            ));
+        }
+    }
+    rows.sort();
+    rows
+}
+
+fn change(version: i64, change_type: &str, id: i32, value: &str) -> (i64, String, i32, String) {
+    (version, change_type.to_string(), id, value.to_string())
+}
+
+#[tokio::test]
+async fn test_inserts_updates_and_deletes() {
+    let temp_dir = TempDir::new().unwrap();
+    let db = DatabaseOps::create(temp_dir.path().join("db"), create_schema())
+        .await
+        .unwrap();
+    assert!(!db.change_data_feed_enabled());
+    db.set_change_data_feed(true).await.unwrap();
+    assert!(db.change_data_feed_enabled());
+    let enabled_at = db.get_delta_table().await.unwrap().version().unwrap();
+
+    db.insert(batch(vec![1, 2, 3], vec!["a", "b", "c"]))
+        .await
+        .unwrap();
+    db.update_rows(&[("value", "'x'")], "id = 1").await.unwrap();
+    db.delete_rows_where("id = 2").await.unwrap();
+    let latest = db.get_delta_table().await.unwrap().version().unwrap();
+    assert_eq!(latest, enabled_at + 3);
+
+    let from = (enabled_at + 1) as u64;
+    let rows = db.read_changes(from, latest as u64).await.unwrap();
+    let (insert, update, delete) = (enabled_at + 1, enabled_at + 2, enabled_at + 3);
+    assert_eq!(
+        changes(&rows),
+        vec![
+            change(insert, "insert", 1, "a"),
+            change(insert, "insert", 2, "b"),
+            change(insert, "insert", 3, "c"),
+            change(update, "update_postimage", 1, "x"),
+            change(update, "update_preimage", 1, "a"),
+            change(delete, "delete", 2, "b"),
+        ]
+    );
+    let schema = rows[0].schema();
+    assert_eq!(schema.fields().len(), 5);
+    assert!(schema.field_with_name("_commit_timestamp").is_ok());
+
+    // A single version reads only its own changes
+    let rows = db.read_changes(delete as u64, delete as u64).await.unwrap();
+    assert_eq!(changes(&rows), vec![change(delete, "delete", 2, "b")]);
+}
+
+#[tokio::test]
+async fn test_invalid_reads() {
+    let temp_dir = TempDir::new().unwrap();
+    let db = DatabaseOps::create(temp_dir.path().join("db"), create_schema())
+        .await
+        .unwrap();
+    db.insert(batch(vec![1], vec!["a"])).await.unwrap();
+
+    // Not enabled
+    let err = db.read_changes(0, 1).await.unwrap_err();
+    assert!(matches!(err, Error::InvalidOperation(_)), "{}", err);
+
+    db.set_change_data_feed(true).await.unwrap();
+    let latest = db.get_delta_table().await.unwrap().version().unwrap() as u64;
+    for (from, to) in [(2, 1), (0, latest + 1)] {
+        let err = db.read_changes(from, to).await.unwrap_err();
+        assert!(
+            matches!(err, Error::InvalidOperation(_)),
+            "{}..={}: {}",
+            from,
+            to,
+            err
+        );
+    }
+
+    // Turned off again, the table stops recording changes
+    db.set_change_data_feed(false).await.unwrap();
+    assert!(!db.change_data_feed_enabled());
+    assert!(db.read_changes(0, latest).await.is_err());
+}
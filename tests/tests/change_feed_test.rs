@@ -0,0 +1,109 @@
+use arrow::array::{Int32Array, RecordBatch, StringArray};
+use arrow::datatypes::{DataType, Field, Schema};
+use fsdb::Error;
+use fsdb::database_ops::DatabaseOps;
+use fsdb::delta_lake::changes::CancellationToken;
+use std::sync::Arc;
+use std::time::Duration;
+use tempfile::TempDir;
+
+async fn create_db(temp_dir: &TempDir) -> DatabaseOps {
+    let schema = Arc::new(Schema::new(vec![
+        Field::new("id", DataType::Int32, false),
+        Field::new("name", DataType::Utf8, false),
+    ]));
+    DatabaseOps::create(temp_dir.path().join("db"), schema)
+        .await
+        .unwrap()
+}
+
+async fn insert(db: &DatabaseOps, ids: Vec<i32>) {
+    let names: Vec<String> = ids.iter().map(|id| format!("user_{}", id)).collect();
+    let batch = RecordBatch::try_new(
+        db.schema(),
+        vec![
+            Arc::new(Int32Array::from(ids)),
+            Arc::new(StringArray::from(names)),
+        ],
+    )
+    .unwrap();
+    db.insert(batch).await.unwrap();
+}
+
+#[tokio::test]
+async fn test_follower_observes_commits_in_order() {
+    let temp_dir = TempDir::new().unwrap();
+    let db = Arc::new(create_db(&temp_dir).await);
+    let cancel = CancellationToken::new();
+
+    // Version 0 created the table; follow everything after it
+    let mut follower = db
+        .follow_changes(1, cancel.clone())
+        .unwrap()
+        .with_poll_interval(Duration::from_millis(10));
+
+    let writer = {
+        let db = db.clone();
+        tokio::spawn(async move {
+            insert(&db, vec![1, 2]).await;
+            insert(&db, vec![3]).await;
+            db.delete_rows_where("id = 1").await.unwrap();
+        })
+    };
+
+    let mut changes = Vec::new();
+    for _ in 0..3 {
+        let change = tokio::time::timeout(Duration::from_secs(30), follower.next_change())
+            .await
+            .expect("Follower should observe the commit")
+            .expect("Follower should not end before cancellation")
+            .unwrap();
+        changes.push(change);
+    }
+    writer.await.unwrap();
+
+    let versions: Vec<u64> = changes.iter().map(|c| c.version).collect();
+    assert_eq!(versions, vec![1, 2, 3]);
+    assert_eq!(changes[0].num_added(), 2);
+    assert_eq!(changes[0].num_removed(), 0);
+    assert_eq!(changes[1].num_added(), 1);
+
+    // The DELETE removes the file holding ids 1 and 2 and rewrites id 2
+    assert_eq!(changes[2].num_removed(), 2);
+    assert_eq!(changes[2].num_added(), 1);
+    assert_eq!(follower.next_version(), Some(4));
+
+    // Cancelling ends a follower that is waiting for the next commit
+    let waiting = tokio::spawn(async move { follower.next_change().await.is_none() });
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    cancel.cancel();
+    assert!(
+        tokio::time::timeout(Duration::from_secs(5), waiting)
+            .await
+            .unwrap()
+            .unwrap()
+    );
+}
+
+#[tokio::test]
+async fn test_follower_reports_cleaned_up_start_version() {
+    let temp_dir = TempDir::new().unwrap();
+    let db = create_db(&temp_dir).await;
+    insert(&db, vec![1]).await;
+    insert(&db, vec![2]).await;
+
+    // Simulate log cleanup removing version 1 after a checkpoint
+    let log_dir = temp_dir.path().join("db").join("_delta_log");
+    std::fs::remove_file(log_dir.join(format!("{:020}.json", 1))).unwrap();
+
+    let mut follower = db.follow_changes(1, CancellationToken::new()).unwrap();
+    let err = follower.next_change().await.unwrap().unwrap_err();
+    assert!(matches!(err, Error::VersionGone(1)), "{}", err);
+
+    // The consumer re-bootstraps from a version still in the log
+    assert!(follower.next_change().await.is_none());
+    let mut follower = db.follow_changes(2, CancellationToken::new()).unwrap();
+    let change = follower.next_change().await.unwrap().unwrap();
+    assert_eq!(change.version, 2);
+    assert_eq!(change.num_added(), 1);
+}
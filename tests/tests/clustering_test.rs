@@ -0,0 +1,146 @@
+// Clustered Insert Integration Tests
+// Tests that a clustering key keeps data files in narrow key ranges, so range
+// queries skip more files than on the same rows inserted unclustered
+
+use arrow::array::{ArrayRef, Int64Array, RecordBatch, StringArray};
+use arrow::datatypes::{DataType, Field, Schema, SchemaRef};
+use fsdb::delta_lake::get_file_statistics;
+use fsdb::query::Value;
+use fsdb::{DatabaseOps, Error};
+use std::path::Path;
+use std::sync::Arc;
+use tempfile::TempDir;
+
+fn create_schema() -> SchemaRef {
+    Arc::new(Schema::new(vec![
+        Field::new("ts", DataType::Int64, false),
+        Field::new("sensor", DataType::Utf8, false),
+    ]))
+}
+
+/// Six batches of readings, batch k covering ts 100k..100k+99 in reverse
+/// order plus one reading that arrived late from the first range
+fn readings() -> Vec<RecordBatch> {
+    (0..6)
+        .map(|k| {
+            let mut ts: Vec<i64> = (100 * k..100 * k + 100).rev().collect();
+            if k > 0 {
+                ts.push(50 + k);
+            }
+            let sensors: Vec<String> = ts.iter().map(|t| format!("s{}", t % 3)).collect();
+            RecordBatch::try_new(
+                create_schema(),
+                vec![
+                    Arc::new(Int64Array::from(ts)) as ArrayRef,
+                    Arc::new(StringArray::from(sensors)) as ArrayRef,
+                ],
+            )
+            .unwrap()
+        })
+        .collect()
+}
+
+async fn load(path: &Path, clustering_key: Option<&str>) -> DatabaseOps {
+    let db = DatabaseOps::create(path, create_schema()).await.unwrap();
+    if clustering_key.is_some() {
+        db.set_clustering_key(clustering_key).await.unwrap();
+    }
+    for batch in readings() {
+        db.insert(batch).await.unwrap();
+    }
+    db
+}
+
+/// Rows matching `predicate` and how many files the query read
+async fn range_query(db: &DatabaseOps, predicate: &str) -> (Vec<i64>, usize) {
+    let sql = format!("SELECT ts FROM data WHERE {} ORDER BY ts", predicate);
+    let rows = db.query_rows(&sql).await.unwrap();
+    let ts = (0..rows.len())
+        .map(|row| match rows.get(row, "ts") {
+            Some(Value::Int(ts)) => *ts,
+            other => panic!("unexpected ts {:?}", other),
+        })
+        .collect();
+    let stats = db.get_data_skipping_stats().await.unwrap();
+    (ts, stats.files_read)
+}
+
+#[tokio::test]
+async fn test_clustered_inserts_prune_range_queries() {
+    let temp_dir = TempDir::new().unwrap();
+    let plain = load(&temp_dir.path().join("plain"), None).await;
+    let clustered = load(&temp_dir.path().join("clustered"), Some("ts")).await;
+
+    let expected: Vec<i64> = (250..=260).collect();
+    let (rows, plain_files) = range_query(&plain, "ts >= 250 AND ts <= 260").await;
+    assert_eq!(rows, expected);
+    let (rows, clustered_files) = range_query(&clustered, "ts >= 250 AND ts <= 260").await;
+    assert_eq!(rows, expected);
+
+    // Late readings stretch batches 2-5 back over ts 52-55, so the plain
+    // table reads all four; the clustered one only the file for 200-299
+    assert_eq!(plain_files, 4);
+    assert_eq!(clustered_files, 1);
+
+    // The late readings still read back with their range
+    let (rows, clustered_files) = range_query(&clustered, "ts >= 50 AND ts <= 55").await;
+    assert_eq!(rows, vec![50, 51, 51, 52, 52, 53, 53, 54, 54, 55, 55]);
+    assert_eq!(clustered_files, 6);
+    let (_, plain_files) = range_query(&plain, "ts >= 50 AND ts <= 55").await;
+    assert_eq!(plain_files, 6);
+}
+
+#[tokio::test]
+async fn test_clustered_files_keep_tight_ranges() {
+    let temp_dir = TempDir::new().unwrap();
+    let path = temp_dir.path().join("clustered");
+    let db = load(&path, Some("ts")).await;
+
+    // No new file's range reaches across another's
+    let mut ranges: Vec<(i64, i64)> = get_file_statistics(&path)
+        .unwrap()
+        .iter()
+        .map(|f| {
+            (
+                f.min_values["ts"].as_i64().unwrap(),
+                f.max_values["ts"].as_i64().unwrap(),
+            )
+        })
+        .collect();
+    ranges.sort();
+    assert_eq!(ranges.len(), 11);
+    assert_eq!(ranges[0], (0, 99));
+    for (start, end) in [(100, 199), (200, 299), (300, 399), (400, 499), (500, 599)] {
+        assert!(ranges.contains(&(start, end)), "{:?}", ranges);
+    }
+    for late in 51..=55 {
+        assert!(ranges.contains(&(late, late)), "{:?}", ranges);
+    }
+
+    let rows = db
+        .query_rows("SELECT COUNT(*) AS n FROM data")
+        .await
+        .unwrap();
+    assert_eq!(rows.get(0, "n"), Some(&Value::Int(605)));
+}
+
+#[tokio::test]
+async fn test_clustering_key_setting() {
+    let temp_dir = TempDir::new().unwrap();
+    let path = temp_dir.path().join("db");
+    let db = DatabaseOps::create(&path, create_schema()).await.unwrap();
+    assert_eq!(db.clustering_key(), None);
+
+    assert!(matches!(
+        db.set_clustering_key(Some("missing")).await,
+        Err(Error::InvalidOperation(_))
+    ));
+
+    db.set_clustering_key(Some("ts")).await.unwrap();
+    assert_eq!(db.clustering_key().as_deref(), Some("ts"));
+    let reopened = DatabaseOps::open(&path).await.unwrap();
+    assert_eq!(reopened.clustering_key().as_deref(), Some("ts"));
+
+    db.set_clustering_key(None).await.unwrap();
+    assert_eq!(db.clustering_key(), None);
+}
@@ -0,0 +1,133 @@
+// Collation Integration Tests
+// Tests case-insensitive string comparison in WHERE, ORDER BY and GROUP BY,
+// and that data skipping never rules out a file the collation can match
+
+use arrow::array::{ArrayRef, Int32Array, RecordBatch, StringArray};
+use arrow::datatypes::{DataType, Field, Schema, SchemaRef};
+use fsdb::DatabaseOps;
+use fsdb::query::{Collation, ResultSet, Value};
+use std::path::Path;
+use std::sync::Arc;
+use tempfile::TempDir;
+
+fn create_schema() -> SchemaRef {
+    Arc::new(Schema::new(vec![
+        Field::new("id", DataType::Int32, false),
+        Field::new("name", DataType::Utf8, false),
+    ]))
+}
+
+/// Two files with binary ranges ALICE..BOB and alice..carol
+async fn create_db(path: &Path) -> DatabaseOps {
+    let schema = create_schema();
+    let db = DatabaseOps::create(path, schema.clone())
+        .await
+        .unwrap()
+        .with_collation(Collation::CaseInsensitive);
+    for (ids, names) in [([1, 2], ["ALICE", "BOB"]), ([3, 4], ["alice", "carol"])] {
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![
+                Arc::new(Int32Array::from(ids.to_vec())) as ArrayRef,
+                Arc::new(StringArray::from(names.to_vec())) as ArrayRef,
+            ],
+        )
+        .unwrap();
+        db.insert(batch).await.unwrap();
+    }
+    db
+}
+
+fn column(rows: &ResultSet, name: &str) -> Vec<Value> {
+    (0..rows.len())
+        .map(|row| rows.get(row, name).cloned().unwrap())
+        .collect()
+}
+
+fn ints(values: &[i64]) -> Vec<Value> {
+    values.iter().map(|v| Value::Int(*v)).collect()
+}
+
+fn strings(values: &[&str]) -> Vec<Value> {
+    values
+        .iter()
+        .map(|v| Value::String(v.to_string()))
+        .collect()
+}
+
+#[tokio::test]
+async fn test_case_insensitive_filter_reads_every_candidate_file() {
+    let temp_dir = TempDir::new().unwrap();
+    let db = create_db(&temp_dir.path().join("db")).await;
+
+    // Binary statistics would skip the ALICE..BOB file for 'alice'
+    let rows = db
+        .query_rows("SELECT id FROM data WHERE name = 'alice' ORDER BY id")
+        .await
+        .unwrap();
+    assert_eq!(column(&rows, "id"), ints(&[1, 3]));
+    let stats = db.get_data_skipping_stats().await.unwrap();
+    assert_eq!((stats.files_read, stats.files_skipped), (2, 0));
+
+    // Only a file sorting below every case variant is skipped
+    let rows = db
+        .query_rows("SELECT id FROM data WHERE name = 'zed'")
+        .await
+        .unwrap();
+    assert!(rows.is_empty());
+    let stats = db.get_data_skipping_stats().await.unwrap();
+    assert_eq!((stats.files_read, stats.files_skipped), (1, 1));
+
+    let sql = "SELECT id FROM data WHERE name IN ('Bob', 'CAROL') OR name LIKE 'A%' ORDER BY id";
+    let rows = db.query_rows(sql).await.unwrap();
+    assert_eq!(column(&rows, "id"), ints(&[1, 2, 3, 4]));
+}
+
+#[tokio::test]
+async fn test_binary_override_per_query() {
+    let temp_dir = TempDir::new().unwrap();
+    let db = create_db(&temp_dir.path().join("db")).await;
+
+    let rows = db
+        .query_rows_with_collation(
+            "SELECT id FROM data WHERE name = 'alice'",
+            Collation::Binary,
+        )
+        .await
+        .unwrap();
+    assert_eq!(column(&rows, "id"), ints(&[3]));
+    let stats = db.get_data_skipping_stats().await.unwrap();
+    assert_eq!((stats.files_read, stats.files_skipped), (1, 1));
+
+    let rows = db
+        .query_rows_with_collation("SELECT name FROM data ORDER BY name", Collation::Binary)
+        .await
+        .unwrap();
+    assert_eq!(
+        column(&rows, "name"),
+        strings(&["ALICE", "BOB", "alice", "carol"])
+    );
+}
+
+#[tokio::test]
+async fn test_case_insensitive_order_and_group() {
+    let temp_dir = TempDir::new().unwrap();
+    let db = create_db(&temp_dir.path().join("db")).await;
+
+    // Values equal ignoring case stay in binary order among themselves
+    let rows = db
+        .query_rows("SELECT name FROM data ORDER BY name")
+        .await
+        .unwrap();
+    assert_eq!(
+        column(&rows, "name"),
+        strings(&["ALICE", "alice", "BOB", "carol"])
+    );
+
+    let rows = db
+        .query_rows("SELECT name, count(*) AS n FROM data GROUP BY name ORDER BY name")
+        .await
+        .unwrap();
+    assert_eq!(column(&rows, "name"), strings(&["alice", "bob", "carol"]));
+    assert_eq!(column(&rows, "n"), ints(&[2, 1, 1]));
+}
@@ -0,0 +1,156 @@
+// Column Default Integration Tests
+// Tests per-column defaults stored in the Delta schema and applied on insert
+
+use arrow::array::{
+    Array, ArrayRef, Int32Array, RecordBatch, StringArray, TimestampMicrosecondArray,
+};
+use arrow::datatypes::{DataType, Field, Schema, SchemaRef, TimeUnit};
+use fsdb::DatabaseOps;
+use fsdb::metadata::with_default;
+use std::sync::Arc;
+use tempfile::TempDir;
+
+fn create_schema() -> SchemaRef {
+    Arc::new(Schema::new(vec![
+        Field::new("id", DataType::Int32, false),
+        with_default(Field::new("status", DataType::Utf8, true), "'pending'"),
+        with_default(Field::new("priority", DataType::Int32, false), "5"),
+        with_default(
+            Field::new(
+                "created_at",
+                DataType::Timestamp(TimeUnit::Microsecond, None),
+                true,
+            ),
+            "now()",
+        ),
+    ]))
+}
+
+fn ids_only(ids: Vec<i32>) -> RecordBatch {
+    let schema = Arc::new(Schema::new(vec![Field::new("id", DataType::Int32, false)]));
+    RecordBatch::try_new(schema, vec![Arc::new(Int32Array::from(ids)) as ArrayRef]).unwrap()
+}
+
+async fn query_one(db: &DatabaseOps, sql: &str) -> RecordBatch {
+    let batches = db.query(sql).await.unwrap();
+    arrow::compute::concat_batches(&batches[0].schema(), &batches).unwrap()
+}
+
+fn now_micros() -> i64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_micros() as i64
+}
+
+#[tokio::test]
+async fn test_literal_defaults_fill_omitted_columns() {
+    let temp_dir = TempDir::new().unwrap();
+    let db = DatabaseOps::create(temp_dir.path().join("db"), create_schema())
+        .await
+        .unwrap();
+
+    db.insert(ids_only(vec![1, 2])).await.unwrap();
+
+    let batch = query_one(&db, "SELECT status, priority FROM data ORDER BY id").await;
+    let status = batch
+        .column(0)
+        .as_any()
+        .downcast_ref::<StringArray>()
+        .unwrap();
+    let priority = batch
+        .column(1)
+        .as_any()
+        .downcast_ref::<Int32Array>()
+        .unwrap();
+    assert_eq!(status.value(0), "pending");
+    assert_eq!(status.value(1), "pending");
+    assert_eq!(priority.values(), &[5, 5]);
+}
+
+#[tokio::test]
+async fn test_now_default_is_evaluated_at_insert() {
+    let temp_dir = TempDir::new().unwrap();
+    let db = DatabaseOps::create(temp_dir.path().join("db"), create_schema())
+        .await
+        .unwrap();
+
+    let before = now_micros();
+    db.insert(ids_only(vec![1])).await.unwrap();
+    let after = now_micros();
+    tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+    db.insert(ids_only(vec![2])).await.unwrap();
+
+    let batch = query_one(&db, "SELECT created_at FROM data ORDER BY id").await;
+    let created = batch
+        .column(0)
+        .as_any()
+        .downcast_ref::<TimestampMicrosecondArray>()
+        .unwrap();
+    assert!(
+        created.value(0) >= before && created.value(0) <= after,
+        "{} not in [{}, {}]",
+        created.value(0),
+        before,
+        after
+    );
+    assert!(created.value(1) > created.value(0));
+}
+
+#[tokio::test]
+async fn test_explicit_null_wins_unless_not_null() {
+    let temp_dir = TempDir::new().unwrap();
+    let db = DatabaseOps::create(temp_dir.path().join("db"), create_schema())
+        .await
+        .unwrap();
+
+    let partial = Arc::new(Schema::new(vec![
+        Field::new("id", DataType::Int32, false),
+        Field::new("status", DataType::Utf8, true),
+        Field::new("priority", DataType::Int32, true),
+    ]));
+    let batch = RecordBatch::try_new(
+        partial,
+        vec![
+            Arc::new(Int32Array::from(vec![1])) as ArrayRef,
+            Arc::new(StringArray::from(vec![None::<&str>])) as ArrayRef,
+            Arc::new(Int32Array::from(vec![None])) as ArrayRef,
+        ],
+    )
+    .unwrap();
+    db.insert(batch).await.unwrap();
+
+    let batch = query_one(&db, "SELECT status, priority FROM data").await;
+    // status is nullable: the caller's null is kept
+    assert!(batch.column(0).is_null(0));
+    // priority is NOT NULL: the default replaces the null
+    let priority = batch
+        .column(1)
+        .as_any()
+        .downcast_ref::<Int32Array>()
+        .unwrap();
+    assert_eq!(priority.value(0), 5);
+}
+
+#[tokio::test]
+async fn test_defaults_survive_reopen_and_buffered_inserts() {
+    let temp_dir = TempDir::new().unwrap();
+    let path = temp_dir.path().join("db");
+    DatabaseOps::create(&path, create_schema()).await.unwrap();
+
+    let db = DatabaseOps::open(&path).await.unwrap();
+    let status = db.schema().field_with_name("status").unwrap().clone();
+    assert_eq!(fsdb::metadata::default_expr(&status), Some("'pending'"));
+
+    db.insert_buffered(ids_only(vec![1])).await.unwrap();
+    db.insert_buffered(ids_only(vec![2])).await.unwrap();
+    db.flush_write_buffer().await.unwrap();
+
+    let batch = query_one(&db, "SELECT COUNT(*) FROM data WHERE status = 'pending'").await;
+    let count = batch
+        .column(0)
+        .as_any()
+        .downcast_ref::<arrow::array::Int64Array>()
+        .unwrap();
+    assert_eq!(count.value(0), 2);
+}
@@ -0,0 +1,439 @@
+// Column Encryption Integration Tests
+// Tests encrypted PII columns: values round-trip for users allowed to decrypt,
+// are stored and shown to others as ciphertext or a mask, equality queries on
+// deterministic columns, the comparisons encrypted columns refuse and the
+// columns that can't be encrypted
+
+use arrow::array::{ArrayRef, AsArray, Int32Array, RecordBatch, StringArray};
+use arrow::datatypes::{DataType, Field, Schema, SchemaRef};
+use fsdb::delta_lake::changes::CancellationToken;
+use fsdb::metadata::with_generated;
+use fsdb::query::{ResultSet, Value};
+use fsdb::security::column_encryption::CIPHERTEXT_PREFIX;
+use fsdb::security::{ColumnKeys, EncryptedColumn, Permission, Role, RoleManager};
+use fsdb::{DatabaseOps, Error};
+use std::path::Path;
+use std::sync::Arc;
+use tempfile::TempDir;
+
+fn create_schema() -> SchemaRef {
+    Arc::new(Schema::new(vec![
+        Field::new("id", DataType::Int32, false),
+        Field::new("name", DataType::Utf8, false),
+        Field::new("ssn", DataType::Utf8, true),
+        Field::new("email", DataType::Utf8, true),
+    ]))
+}
+
+fn batch() -> RecordBatch {
+    RecordBatch::try_new(
+        create_schema(),
+        vec![
+            Arc::new(Int32Array::from(vec![1, 2, 3])) as ArrayRef,
+            Arc::new(StringArray::from(vec!["ada", "bob", "cy"])) as ArrayRef,
+            Arc::new(StringArray::from(vec![
+                Some("123-45-6789"),
+                Some("987-65-4321"),
+                None,
+            ])) as ArrayRef,
+            Arc::new(StringArray::from(vec![
+                Some("ada@example.com"),
+                Some("bob@example.com"),
+                Some("cy@example.com"),
+            ])) as ArrayRef,
+        ],
+    )
+    .unwrap()
+}
+
+fn keys() -> ColumnKeys {
+    ColumnKeys::new().with_key("pii", [42u8; 32])
+}
+
+/// SSNs deterministically encrypted, emails randomized and masked as ***
+async fn encrypt_columns(db: &DatabaseOps) {
+    db.encrypt_column("ssn", EncryptedColumn::new("pii").with_deterministic())
+        .await
+        .unwrap();
+    db.encrypt_column("email", EncryptedColumn::new("pii").with_mask("***"))
+        .await
+        .unwrap();
+}
+
+async fn create_db(path: &Path) -> DatabaseOps {
+    let db = DatabaseOps::create(path, create_schema())
+        .await
+        .unwrap()
+        .with_column_keys(keys());
+    encrypt_columns(&db).await;
+    db.insert(batch()).await.unwrap();
+    db
+}
+
+fn string(rows: &ResultSet, row: usize, column: &str) -> String {
+    match rows.get(row, column).unwrap() {
+        Value::String(s) => s.clone(),
+        other => panic!("expected a string, got {:?}", other),
+    }
+}
+
+#[tokio::test]
+async fn test_encrypted_round_trip() {
+    let temp_dir = TempDir::new().unwrap();
+    let path = temp_dir.path().join("db");
+    let db = create_db(&path).await;
+
+    let rows = db
+        .query_rows("SELECT id, name, ssn, email FROM data ORDER BY id")
+        .await
+        .unwrap();
+    assert_eq!(string(&rows, 0, "ssn"), "123-45-6789");
+    assert_eq!(string(&rows, 1, "email"), "bob@example.com");
+    assert_eq!(rows.get(2, "ssn").unwrap(), &Value::Null);
+
+    // Stored as ciphertext: a handle without the key reads it as such
+    let keyless = DatabaseOps::open(&path).await.unwrap();
+    let rows = keyless
+        .query_rows("SELECT ssn, email, name FROM data ORDER BY id")
+        .await
+        .unwrap();
+    assert!(string(&rows, 0, "ssn").starts_with(CIPHERTEXT_PREFIX));
+    assert_eq!(string(&rows, 0, "email"), "***");
+    assert_eq!(string(&rows, 0, "name"), "ada");
+    assert!(keyless.insert(batch()).await.is_err());
+
+    // Equal SSNs encrypt alike; emails don't
+    db.insert(batch()).await.unwrap();
+    let rows = keyless
+        .query_rows("SELECT COUNT(DISTINCT ssn) AS ssns, COUNT(DISTINCT email) AS emails FROM data")
+        .await
+        .unwrap();
+    assert_eq!(rows.get(0, "ssns").unwrap(), &Value::Int(2));
+    assert_eq!(rows.get(0, "emails").unwrap(), &Value::Int(6));
+}
+
+#[tokio::test]
+async fn test_followed_changes_decrypted() {
+    let temp_dir = TempDir::new().unwrap();
+    let path = temp_dir.path().join("db");
+    let db = create_db(&path).await;
+    let version = db.get_delta_table().await.unwrap().version().unwrap() as u64;
+
+    // Followers read changes as queries do: decrypted with the key, masked without
+    let mut follower = db
+        .follow_changes(version, CancellationToken::new())
+        .unwrap();
+    let change = follower.next_change().await.unwrap().unwrap();
+    let added = &change.added[0];
+    let ssn = added.column_by_name("ssn").unwrap().as_string::<i32>();
+    assert_eq!(ssn.value(0), "123-45-6789");
+
+    let keyless = DatabaseOps::open(&path).await.unwrap();
+    let mut follower = keyless
+        .follow_changes(version, CancellationToken::new())
+        .unwrap();
+    let change = follower.next_change().await.unwrap().unwrap();
+    let added = &change.added[0];
+    let ssn = added.column_by_name("ssn").unwrap().as_string::<i32>();
+    assert!(ssn.value(0).starts_with(CIPHERTEXT_PREFIX));
+    let email = added.column_by_name("email").unwrap().as_string::<i32>();
+    assert_eq!(email.value(0), "***");
+}
+
+#[tokio::test]
+async fn test_equality_on_deterministic_columns() {
+    let temp_dir = TempDir::new().unwrap();
+    let db = create_db(&temp_dir.path().join("db")).await;
+
+    let rows = db
+        .query_rows("SELECT name FROM data WHERE ssn = '987-65-4321'")
+        .await
+        .unwrap();
+    assert_eq!(rows.len(), 1);
+    assert_eq!(string(&rows, 0, "name"), "bob");
+    let rows = db
+        .query_rows("SELECT name FROM data WHERE ssn IN ('123-45-6789', '000-00-0000') OR ssn IS NULL ORDER BY id")
+        .await
+        .unwrap();
+    assert_eq!(rows.len(), 2);
+
+    // Updates and deletes compare and write ciphertext too
+    let commit = db
+        .update_rows(&[("ssn", "'111-11-1111'")], "ssn = '123-45-6789'")
+        .await
+        .unwrap();
+    assert_eq!(commit.rows_affected, 1);
+    let rows = db
+        .query_rows("SELECT ssn FROM data WHERE id = 1")
+        .await
+        .unwrap();
+    assert_eq!(string(&rows, 0, "ssn"), "111-11-1111");
+    let commit = db.delete_rows_where("ssn = '111-11-1111'").await.unwrap();
+    assert_eq!(commit.rows_affected, 1);
+}
+
+#[tokio::test]
+async fn test_unsupported_comparisons_fail() {
+    let temp_dir = TempDir::new().unwrap();
+    let db = create_db(&temp_dir.path().join("db")).await;
+
+    for sql in [
+        "SELECT * FROM data WHERE ssn > '5'",
+        "SELECT * FROM data WHERE ssn BETWEEN '1' AND '5'",
+        "SELECT * FROM data WHERE ssn LIKE '123%'",
+        "SELECT * FROM data WHERE email = 'ada@example.com'",
+    ] {
+        let err = db.query_rows(sql).await.unwrap_err();
+        assert!(
+            matches!(err, Error::InvalidOperation(_)),
+            "{}: {}",
+            sql,
+            err
+        );
+    }
+    assert!(
+        db.update_rows(&[("email", "upper(email)")], "id = 1")
+            .await
+            .is_err()
+    );
+
+    // Columns that can't take encryption
+    assert!(
+        db.encrypt_column("id", EncryptedColumn::new("pii"))
+            .await
+            .is_err()
+    );
+    assert!(
+        db.encrypt_column("name", EncryptedColumn::new("pii"))
+            .await
+            .is_err()
+    );
+}
+
+#[tokio::test]
+async fn test_decryption_requires_permission() {
+    let temp_dir = TempDir::new().unwrap();
+    let path = temp_dir.path().join("db");
+    let admin = DatabaseOps::create_with_auth(&path, create_schema(), true)
+        .await
+        .unwrap()
+        .with_column_keys(keys());
+    encrypt_columns(&admin).await;
+    admin.insert(batch()).await.unwrap();
+    admin
+        .create_user("alice", "secret", &["read"])
+        .await
+        .unwrap();
+    admin
+        .create_user("carol", "secret", &["analyst"])
+        .await
+        .unwrap();
+    let roles = || {
+        let mut roles = RoleManager::new();
+        roles.add_role(Role::new(
+            "analyst".to_string(),
+            vec![Permission::Read, Permission::Decrypt],
+        ));
+        roles
+    };
+    let sql = "SELECT ssn, email FROM data ORDER BY id";
+
+    // Admins may decrypt
+    let rows = admin.query_rows(sql).await.unwrap();
+    assert_eq!(string(&rows, 0, "ssn"), "123-45-6789");
+
+    // Readers without the permission see ciphertext or the mask, key or not
+    let alice = DatabaseOps::open_with_credentials(&path, Some(("alice", "secret")))
+        .await
+        .unwrap()
+        .with_column_keys(keys());
+    let rows = alice.query_rows(sql).await.unwrap();
+    assert!(string(&rows, 0, "ssn").starts_with(CIPHERTEXT_PREFIX));
+    assert_eq!(string(&rows, 0, "email"), "***");
+    assert_eq!(rows.get(2, "ssn").unwrap(), &Value::Null);
+
+    // Equality still works for them, without revealing the value
+    let rows = alice
+        .query_rows("SELECT id FROM data WHERE ssn = '123-45-6789'")
+        .await
+        .unwrap();
+    assert_eq!(rows.get(0, "id").unwrap(), &Value::Int(1));
+
+    let carol = DatabaseOps::open_with_credentials(&path, Some(("carol", "secret")))
+        .await
+        .unwrap()
+        .with_role_manager(roles())
+        .with_column_keys(keys());
+    let rows = carol.query_rows(sql).await.unwrap();
+    assert_eq!(string(&rows, 0, "ssn"), "123-45-6789");
+    assert_eq!(string(&rows, 0, "email"), "ada@example.com");
+
+    // Only admins declare encrypted columns
+    assert!(
+        carol
+            .encrypt_column("name", EncryptedColumn::new("pii"))
+            .await
+            .is_err()
+    );
+}
+
+#[tokio::test]
+async fn test_merge_writes_ciphertext() {
+    let temp_dir = TempDir::new().unwrap();
+    let path = temp_dir.path().join("db");
+    let admin = DatabaseOps::create_with_auth(&path, create_schema(), true)
+        .await
+        .unwrap()
+        .with_column_keys(keys());
+    encrypt_columns(&admin).await;
+    admin.insert(batch()).await.unwrap();
+    admin
+        .create_user("alice", "secret", &["read"])
+        .await
+        .unwrap();
+
+    let source = RecordBatch::try_new(
+        create_schema(),
+        vec![
+            Arc::new(Int32Array::from(vec![1, 2, 4])) as ArrayRef,
+            Arc::new(StringArray::from(vec!["ada", "bob", "di"])) as ArrayRef,
+            Arc::new(StringArray::from(vec![
+                Some("111-11-1111"),
+                Some("222-22-2222"),
+                Some("444-44-4444"),
+            ])) as ArrayRef,
+            Arc::new(StringArray::from(vec![
+                Some("ada@new.example.com"),
+                Some("bob@new.example.com"),
+                Some("di@example.com"),
+            ])) as ArrayRef,
+        ],
+    )
+    .unwrap();
+    let metrics = admin
+        .merge()
+        .await
+        .unwrap()
+        .with_source(source, "source")
+        .on("target.id = source.id")
+        .when_matched_update()
+        .condition("target.ssn = '123-45-6789'")
+        .set("ssn", "source.ssn")
+        .set("email", "'ada@example.org'")
+        .then()
+        .when_not_matched_insert()
+        .values_all()
+        .execute()
+        .await
+        .unwrap();
+    assert_eq!((metrics.rows_updated, metrics.rows_inserted), (1, 1));
+
+    let sql = "SELECT id, ssn, email FROM data ORDER BY id";
+    let rows = admin.query_rows(sql).await.unwrap();
+    assert_eq!(string(&rows, 0, "ssn"), "111-11-1111");
+    assert_eq!(string(&rows, 0, "email"), "ada@example.org");
+    assert_eq!(string(&rows, 1, "ssn"), "987-65-4321");
+    assert_eq!(string(&rows, 3, "ssn"), "444-44-4444");
+    assert_eq!(string(&rows, 3, "email"), "di@example.com");
+
+    // Everything MERGE wrote is stored encrypted
+    let alice = DatabaseOps::open_with_credentials(&path, Some(("alice", "secret")))
+        .await
+        .unwrap()
+        .with_column_keys(keys());
+    let rows = alice.query_rows(sql).await.unwrap();
+    for row in 0..rows.len() {
+        assert_eq!(string(&rows, row, "email"), "***");
+        if row != 2 {
+            assert!(string(&rows, row, "ssn").starts_with(CIPHERTEXT_PREFIX));
+        }
+    }
+    let rows = alice
+        .query_rows("SELECT id FROM data WHERE ssn = '444-44-4444'")
+        .await
+        .unwrap();
+    assert_eq!(rows.get(0, "id").unwrap(), &Value::Int(4));
+
+    // Values that can't be encrypted are refused before anything is written
+    let err = admin
+        .merge()
+        .await
+        .unwrap()
+        .with_source(batch(), "source")
+        .on("target.id = source.id")
+        .when_matched_update()
+        .set("ssn", "source.name")
+        .then()
+        .execute()
+        .await
+        .unwrap_err();
+    assert!(matches!(err, Error::InvalidOperation(_)), "{}", err);
+}
+
+#[tokio::test]
+async fn test_update_returning_encrypts_and_decrypts() {
+    let temp_dir = TempDir::new().unwrap();
+    let path = temp_dir.path().join("db");
+    let db = create_db(&path).await;
+
+    let returning = db
+        .update_rows_returning(&[("ssn", "'555-55-5555'")], "ssn = '987-65-4321'")
+        .await
+        .unwrap();
+    assert_eq!(returning.count, 1);
+    let (batches, _) = db.fetch(returning.cursor, 10).await.unwrap();
+    let ssns = batches[0]
+        .column_by_name("ssn")
+        .unwrap()
+        .as_any()
+        .downcast_ref::<StringArray>()
+        .unwrap();
+    assert_eq!(ssns.value(0), "555-55-5555");
+
+    // Stored encrypted, and found again by the new value
+    let keyless = DatabaseOps::open(&path).await.unwrap();
+    let rows = keyless
+        .query_rows("SELECT ssn FROM data WHERE id = 2")
+        .await
+        .unwrap();
+    assert!(string(&rows, 0, "ssn").starts_with(CIPHERTEXT_PREFIX));
+    let rows = db
+        .query_rows("SELECT id FROM data WHERE ssn = '555-55-5555'")
+        .await
+        .unwrap();
+    assert_eq!(rows.get(0, "id").unwrap(), &Value::Int(2));
+}
+
+#[tokio::test]
+async fn test_columns_with_generated_dependents_not_encrypted() {
+    let temp_dir = TempDir::new().unwrap();
+    let schema = Arc::new(Schema::new(vec![
+        Field::new("id", DataType::Int32, false),
+        Field::new("ssn", DataType::Utf8, true),
+        with_generated(
+            Field::new("ssn_last4", DataType::Utf8, true),
+            "right(ssn, 4)",
+        ),
+    ]));
+    let db = DatabaseOps::create(temp_dir.path(), schema)
+        .await
+        .unwrap()
+        .with_column_keys(keys());
+
+    // ssn_last4 would be computed from the plaintext and stored as is
+    let err = db
+        .encrypt_column("ssn", EncryptedColumn::new("pii"))
+        .await
+        .unwrap_err();
+    assert!(
+        matches!(&err, Error::InvalidOperation(message) if message.contains("ssn_last4")),
+        "{:?}",
+        err
+    );
+    assert!(db.encrypted_columns().unwrap().columns.is_empty());
+
+    // The generated column itself is encrypted after it's computed
+    db.encrypt_column("ssn_last4", EncryptedColumn::new("pii"))
+        .await
+        .unwrap();
+}
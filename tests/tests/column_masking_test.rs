@@ -0,0 +1,295 @@
+// Column Masking Integration Tests
+// Tests per-role column masks: masked users read NULLs, hashes or partial
+// values however they select a column or follow changes, statistics leave
+// masked columns out, Unmask reveals them, and masks scoped to one named
+// table leave the others alone
+
+use arrow::array::{ArrayRef, AsArray, Int32Array, RecordBatch, StringArray};
+use arrow::datatypes::{DataType, Field, Schema, SchemaRef};
+use fsdb::DatabaseOps;
+use fsdb::delta_lake::changes::CancellationToken;
+use fsdb::query::{ResultSet, Value};
+use fsdb::security::{ColumnMask, Permission, Role, RoleManager};
+use std::path::Path;
+use std::sync::Arc;
+use tempfile::TempDir;
+
+fn create_schema() -> SchemaRef {
+    Arc::new(Schema::new(vec![
+        Field::new("id", DataType::Int32, false),
+        Field::new("name", DataType::Utf8, false),
+        Field::new("ssn", DataType::Utf8, true),
+        Field::new("email", DataType::Utf8, true),
+    ]))
+}
+
+fn batch() -> RecordBatch {
+    RecordBatch::try_new(
+        create_schema(),
+        vec![
+            Arc::new(Int32Array::from(vec![1, 2])) as ArrayRef,
+            Arc::new(StringArray::from(vec!["ada", "bob"])) as ArrayRef,
+            Arc::new(StringArray::from(vec![Some("123-45-6789"), None])) as ArrayRef,
+            Arc::new(StringArray::from(vec![
+                Some("ada@example.com"),
+                Some("bob@example.com"),
+            ])) as ArrayRef,
+        ],
+    )
+    .unwrap()
+}
+
+/// Support sees the last four digits of SSNs and hashed emails; auditors
+/// see everything; editors write, seeing the last four digits of SSNs;
+/// analysts see no ids
+fn roles() -> RoleManager {
+    let mut roles = RoleManager::new();
+    roles.add_role(
+        Role::new("support".to_string(), vec![Permission::Read])
+            .with_masked_column("ssn", ColumnMask::Partial { visible: 4 })
+            .with_masked_column("email", ColumnMask::Hash)
+            .with_masked_column("people.name", ColumnMask::Null),
+    );
+    roles.add_role(Role::new(
+        "auditor".to_string(),
+        vec![Permission::Read, Permission::Unmask],
+    ));
+    roles.add_role(
+        Role::new(
+            "editor".to_string(),
+            vec![Permission::Read, Permission::Write],
+        )
+        .with_masked_column("ssn", ColumnMask::Partial { visible: 4 }),
+    );
+    roles.add_role(
+        Role::new("analyst".to_string(), vec![Permission::Read])
+            .with_masked_column("id", ColumnMask::Null),
+    );
+    roles
+}
+
+/// A database with Ada and Bob, and users alice (support) and carol
+/// (support and auditor)
+async fn create_db(path: &Path) -> DatabaseOps {
+    let admin = DatabaseOps::create_with_auth(path, create_schema(), true)
+        .await
+        .unwrap()
+        .with_role_manager(roles());
+    admin.insert(batch()).await.unwrap();
+    admin
+        .create_user("alice", "secret", &["support"])
+        .await
+        .unwrap();
+    admin
+        .create_user("carol", "secret", &["support", "auditor"])
+        .await
+        .unwrap();
+    admin
+}
+
+async fn login(path: &Path, user: &str) -> DatabaseOps {
+    DatabaseOps::open_with_credentials(path, Some((user, "secret")))
+        .await
+        .unwrap()
+        .with_role_manager(roles())
+}
+
+fn string(rows: &ResultSet, row: usize, column: &str) -> String {
+    match rows.get(row, column).unwrap() {
+        Value::String(s) => s.clone(),
+        other => panic!("expected a string, got {:?}", other),
+    }
+}
+
+#[tokio::test]
+async fn test_masked_columns_in_queries() {
+    let temp_dir = TempDir::new().unwrap();
+    let path = temp_dir.path().join("db");
+    let admin = create_db(&path).await;
+    let sql = "SELECT name, ssn, email FROM data ORDER BY id";
+
+    let rows = admin.query_rows(sql).await.unwrap();
+    assert_eq!(string(&rows, 0, "ssn"), "123-45-6789");
+
+    let alice = login(&path, "alice").await;
+    let rows = alice.query_rows(sql).await.unwrap();
+    assert_eq!(string(&rows, 0, "name"), "ada");
+    assert_eq!(string(&rows, 0, "ssn"), "*******6789");
+    assert_eq!(rows.get(1, "ssn").unwrap(), &Value::Null);
+    let hash = ColumnMask::Hash.mask_str("ada@example.com").unwrap();
+    assert_eq!(string(&rows, 0, "email"), hash);
+
+    // Aliases, functions and filters see the masked value too
+    let rows = alice
+        .query_rows("SELECT ssn AS s, upper(ssn) AS u FROM data WHERE id = 1")
+        .await
+        .unwrap();
+    assert_eq!(string(&rows, 0, "s"), "*******6789");
+    assert_eq!(string(&rows, 0, "u"), "*******6789");
+    let rows = alice
+        .query_rows("SELECT id FROM data WHERE ssn = '123-45-6789'")
+        .await
+        .unwrap();
+    assert_eq!(rows.len(), 0);
+    let rows = alice
+        .query_rows(&format!("SELECT id FROM data WHERE email = '{}'", hash))
+        .await
+        .unwrap();
+    assert_eq!(rows.get(0, "id").unwrap(), &Value::Int(1));
+
+    // Past versions and cursors are masked alike
+    let version = admin.get_delta_table().await.unwrap().version().unwrap();
+    let batches = alice
+        .query_version("SELECT ssn FROM data WHERE id = 1", version)
+        .await
+        .unwrap();
+    assert_eq!(
+        batches[0].column(0).as_string::<i32>().value(0),
+        "*******6789"
+    );
+    let cursor = alice
+        .open_cursor("SELECT ssn FROM data WHERE id = 1")
+        .await
+        .unwrap();
+    let (batches, _) = alice.fetch(cursor, 10).await.unwrap();
+    assert_eq!(
+        batches[0].column(0).as_string::<i32>().value(0),
+        "*******6789"
+    );
+
+    // Unmask on any role reveals every column
+    let carol = login(&path, "carol").await;
+    let rows = carol.query_rows(sql).await.unwrap();
+    assert_eq!(string(&rows, 0, "ssn"), "123-45-6789");
+    assert_eq!(string(&rows, 0, "email"), "ada@example.com");
+}
+
+#[tokio::test]
+async fn test_followed_changes_masked() {
+    let temp_dir = TempDir::new().unwrap();
+    let path = temp_dir.path().join("db");
+    let admin = create_db(&path).await;
+    let version = admin.get_delta_table().await.unwrap().version().unwrap() as u64;
+
+    let alice = login(&path, "alice").await;
+    let mut follower = alice
+        .follow_changes(version, CancellationToken::new())
+        .unwrap();
+    let change = follower.next_change().await.unwrap().unwrap();
+    let added = &change.added[0];
+    let ssn = added.column_by_name("ssn").unwrap().as_string::<i32>();
+    assert_eq!(ssn.value(0), "*******6789");
+    let email = added.column_by_name("email").unwrap().as_string::<i32>();
+    assert_eq!(
+        email.value(0),
+        ColumnMask::Hash.mask_str("ada@example.com").unwrap()
+    );
+}
+
+#[tokio::test]
+async fn test_masks_scoped_to_named_tables() {
+    let temp_dir = TempDir::new().unwrap();
+    let path = temp_dir.path().join("db");
+    let admin = create_db(&path).await;
+    let people = admin.create_table("people", create_schema()).await.unwrap();
+    people.insert(batch()).await.unwrap();
+
+    let alice = login(&path, "alice").await;
+    let rows = alice
+        .query_rows("SELECT p.name, p.ssn, d.name AS data_name FROM people p JOIN data d ON p.id = d.id ORDER BY p.id")
+        .await
+        .unwrap();
+    assert_eq!(rows.get(0, "name").unwrap(), &Value::Null);
+    assert_eq!(string(&rows, 0, "ssn"), "*******6789");
+    assert_eq!(string(&rows, 0, "data_name"), "ada");
+}
+
+#[tokio::test]
+async fn test_update_returning_masked() {
+    let temp_dir = TempDir::new().unwrap();
+    let path = temp_dir.path().join("db");
+    let admin = create_db(&path).await;
+    admin
+        .create_user("dave", "secret", &["editor"])
+        .await
+        .unwrap();
+
+    // The returned rows hide what the editor can't read, the stored ones don't
+    let dave = login(&path, "dave").await;
+    let returning = dave
+        .update_rows_returning(&[("name", "'ADA'")], "id = 1")
+        .await
+        .unwrap();
+    assert_eq!(returning.count, 1);
+    let (batches, _) = dave.fetch(returning.cursor, 10).await.unwrap();
+    let ssn = batches[0].column_by_name("ssn").unwrap().as_string::<i32>();
+    assert_eq!(ssn.value(0), "*******6789");
+    let name = batches[0]
+        .column_by_name("name")
+        .unwrap()
+        .as_string::<i32>();
+    assert_eq!(name.value(0), "ADA");
+
+    let rows = admin
+        .query_rows("SELECT name, ssn FROM data WHERE id = 1")
+        .await
+        .unwrap();
+    assert_eq!(string(&rows, 0, "name"), "ADA");
+    assert_eq!(string(&rows, 0, "ssn"), "123-45-6789");
+}
+
+#[tokio::test]
+async fn test_percentile_of_masked_column() {
+    let temp_dir = TempDir::new().unwrap();
+    let path = temp_dir.path().join("db");
+    let admin = create_db(&path).await;
+    admin
+        .create_user("erin", "secret", &["analyst"])
+        .await
+        .unwrap();
+    assert_eq!(admin.approx_percentile("id", 1.0).await.unwrap(), Some(2.0));
+
+    // Masked numbers read as NULL, so there's nothing to estimate from
+    let erin = login(&path, "erin").await;
+    assert_eq!(erin.approx_percentile("id", 1.0).await.unwrap(), None);
+    let alice = login(&path, "alice").await;
+    assert_eq!(alice.approx_percentile("id", 1.0).await.unwrap(), Some(2.0));
+}
+
+#[tokio::test]
+async fn test_statistics_of_masked_columns_hidden() {
+    let temp_dir = TempDir::new().unwrap();
+    let path = temp_dir.path().join("db");
+    let admin = create_db(&path).await;
+    admin
+        .create_user("erin", "secret", &["analyst"])
+        .await
+        .unwrap();
+    admin.create_user("nobody", "secret", &[]).await.unwrap();
+    admin.enable_histograms(&["id"]).await.unwrap();
+
+    let stats = admin.get_column_statistics().await.unwrap();
+    assert!(stats.contains_key("ssn") && stats.contains_key("email"));
+
+    // Bounds of masked columns would give their values away
+    let alice = login(&path, "alice").await;
+    let stats = alice.get_column_statistics().await.unwrap();
+    assert!(stats.contains_key("id") && stats.contains_key("name"));
+    assert!(!stats.contains_key("ssn") && !stats.contains_key("email"));
+
+    let erin = login(&path, "erin").await;
+    assert!(
+        !erin
+            .get_column_statistics()
+            .await
+            .unwrap()
+            .contains_key("id")
+    );
+    let histograms = erin.get_histograms().await.unwrap().unwrap();
+    assert!(histograms.column("id").is_none());
+    assert_eq!(erin.estimate_selectivity("id < 2").await.unwrap(), 1.0);
+    assert!(admin.estimate_selectivity("id < 2").await.unwrap() < 1.0);
+
+    let nobody = login(&path, "nobody").await;
+    assert!(nobody.get_column_statistics().await.is_err());
+}
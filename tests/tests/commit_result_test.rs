@@ -0,0 +1,160 @@
+// Commit Result Integration Tests
+// Tests the version and metadata returned by write operations
+
+use arrow::array::{Int32Array, StringArray};
+use arrow::datatypes::{DataType, Field, Schema, SchemaRef};
+use arrow::record_batch::RecordBatch;
+use fsdb::DatabaseOps;
+use std::sync::Arc;
+use tempfile::TempDir;
+
+fn create_schema() -> SchemaRef {
+    Arc::new(Schema::new(vec![
+        Field::new("id", DataType::Int32, false),
+        Field::new("name", DataType::Utf8, false),
+    ]))
+}
+
+fn create_batch(schema: SchemaRef, ids: Vec<i32>) -> RecordBatch {
+    let names: Vec<String> = ids.iter().map(|id| format!("user_{}", id)).collect();
+    RecordBatch::try_new(
+        schema,
+        vec![
+            Arc::new(Int32Array::from(ids)),
+            Arc::new(StringArray::from(names)),
+        ],
+    )
+    .unwrap()
+}
+
+#[tokio::test]
+async fn test_each_write_reports_its_version() {
+    let temp_dir = TempDir::new().unwrap();
+    let schema = create_schema();
+    let db = DatabaseOps::create(temp_dir.path().join("db"), schema.clone())
+        .await
+        .unwrap();
+
+    let first = db
+        .insert(create_batch(schema.clone(), vec![1, 2, 3]))
+        .await
+        .unwrap();
+    assert_eq!(first.operation, "INSERT");
+    assert_eq!(first.rows_affected, 3);
+    assert!(first.timestamp.is_some());
+
+    let second = db
+        .insert(create_batch(schema.clone(), vec![4, 5]))
+        .await
+        .unwrap();
+    assert_eq!(second.version, first.version + 1);
+    assert_eq!(second.rows_affected, 2);
+
+    let updated = db
+        .update_rows(&[("name", "'renamed'")], "id <= 2")
+        .await
+        .unwrap();
+    assert_eq!(updated.operation, "UPDATE");
+    assert_eq!(updated.version, second.version + 1);
+    assert_eq!(updated.rows_affected, 2);
+
+    let deleted = db.delete_rows_where("id = 5").await.unwrap();
+    assert_eq!(deleted.operation, "DELETE");
+    assert_eq!(deleted.version, updated.version + 1);
+    assert_eq!(deleted.rows_affected, 1);
+
+    // The reported version is the one time travel reads back
+    let latest = db.get_delta_table().await.unwrap();
+    assert_eq!(latest.version(), Some(deleted.version));
+}
+
+#[tokio::test]
+async fn test_no_op_writes_keep_the_current_version() {
+    let temp_dir = TempDir::new().unwrap();
+    let schema = create_schema();
+    let db = DatabaseOps::create(temp_dir.path().join("db"), schema.clone())
+        .await
+        .unwrap();
+    let inserted = db
+        .insert(create_batch(schema.clone(), vec![1, 2]))
+        .await
+        .unwrap();
+
+    let empty = db
+        .insert(RecordBatch::new_empty(schema.clone()))
+        .await
+        .unwrap();
+    assert_eq!(empty.version, inserted.version);
+    assert_eq!(empty.rows_affected, 0);
+
+    let updated = db
+        .update_rows(&[("name", "'nobody'")], "id > 100")
+        .await
+        .unwrap();
+    assert_eq!(updated.version, inserted.version);
+    assert_eq!(updated.rows_affected, 0);
+
+    let deleted = db.delete_rows_where("id > 100").await.unwrap();
+    assert_eq!(deleted.version, inserted.version);
+    assert_eq!(deleted.rows_affected, 0);
+}
+
+#[tokio::test]
+async fn test_transaction_commit_reports_version() {
+    let temp_dir = TempDir::new().unwrap();
+    let schema = create_schema();
+    let db = Arc::new(
+        DatabaseOps::create(temp_dir.path().join("db"), schema.clone())
+            .await
+            .unwrap(),
+    );
+    let before = db.get_delta_table().await.unwrap().version().unwrap_or(0);
+
+    let txn = db.begin_transaction().await.unwrap();
+    txn.insert(create_batch(schema.clone(), vec![1, 2]))
+        .await
+        .unwrap();
+    txn.insert(create_batch(schema.clone(), vec![3]))
+        .await
+        .unwrap();
+    let commit = txn.commit().await.unwrap();
+    assert_eq!(commit.version, before + 1);
+    assert_eq!(commit.rows_affected, 3);
+
+    // Committing a transaction that wrote nothing adds no version
+    let txn = db.begin_transaction().await.unwrap();
+    let empty = txn.commit().await.unwrap();
+    assert_eq!(empty.version, commit.version);
+    assert_eq!(empty.rows_affected, 0);
+}
+
+#[tokio::test]
+async fn test_merge_reports_version() {
+    let temp_dir = TempDir::new().unwrap();
+    let schema = create_schema();
+    let db = DatabaseOps::create(temp_dir.path().join("db"), schema.clone())
+        .await
+        .unwrap();
+    let inserted = db
+        .insert(create_batch(schema.clone(), vec![1, 2]))
+        .await
+        .unwrap();
+
+    let metrics = db
+        .merge()
+        .await
+        .unwrap()
+        .with_source(create_batch(schema.clone(), vec![2, 3]), "source")
+        .on("target.id = source.id")
+        .when_matched_update()
+        .set_all()
+        .when_not_matched_insert()
+        .values_all()
+        .execute()
+        .await
+        .unwrap();
+
+    assert_eq!(metrics.commit.operation, "MERGE");
+    assert_eq!(metrics.commit.version, inserted.version + 1);
+    assert_eq!(metrics.commit.rows_affected, 2);
+}
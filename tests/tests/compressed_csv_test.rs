@@ -0,0 +1,88 @@
+// Compressed CSV Integration Tests
+// Tests that gzip and zstd bulk loads match the equivalent plain CSV load
+
+use arrow::datatypes::{DataType, Field, Schema, SchemaRef};
+use arrow::util::pretty::pretty_format_batches;
+use fsdb::bulk_load::LoadOptions;
+use fsdb::{DatabaseOps, Error};
+use std::fmt::Write as _;
+use std::io::Write;
+use std::path::Path;
+use std::sync::Arc;
+use tempfile::TempDir;
+
+const ROWS: i32 = 3000;
+
+fn create_schema() -> SchemaRef {
+    Arc::new(Schema::new(vec![
+        Field::new("id", DataType::Int32, false),
+        Field::new("name", DataType::Utf8, false),
+        Field::new("score", DataType::Int32, true),
+    ]))
+}
+
+fn csv_content() -> Vec<u8> {
+    let mut csv = String::from("id,name,score\n");
+    for i in 1..=ROWS {
+        writeln!(csv, "{},user_{},{}", i, i, i % 100).unwrap();
+    }
+    csv.into_bytes()
+}
+
+fn gzip(data: &[u8]) -> Vec<u8> {
+    let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+    encoder.write_all(data).unwrap();
+    encoder.finish().unwrap()
+}
+
+/// Load `content` from a file called `name` and return the table contents
+async fn load(dir: &Path, name: &str, content: &[u8]) -> fsdb::Result<String> {
+    let csv_path = dir.join(name);
+    std::fs::write(&csv_path, content).unwrap();
+    let db = DatabaseOps::create(dir.join(format!("{}_db", name)), create_schema())
+        .await
+        .unwrap();
+    let options = LoadOptions::default().with_commit_rows(1000);
+    db.load_from_csv(&csv_path, options).await?;
+    let results = db.query("SELECT * FROM data ORDER BY id").await?;
+    Ok(pretty_format_batches(&results).unwrap().to_string())
+}
+
+#[tokio::test]
+async fn test_compressed_loads_match_plain_load() {
+    let temp_dir = TempDir::new().unwrap();
+    let csv = csv_content();
+    let plain = load(temp_dir.path(), "plain.csv", &csv).await.unwrap();
+
+    let gzipped = load(temp_dir.path(), "data.csv.gz", &gzip(&csv))
+        .await
+        .unwrap();
+    assert_eq!(gzipped, plain);
+
+    let zstd = zstd::encode_all(&csv[..], 3).unwrap();
+    let zstded = load(temp_dir.path(), "data.csv.zst", &zstd).await.unwrap();
+    assert_eq!(zstded, plain);
+
+    // Detected by content even without a telling name
+    let unnamed = load(temp_dir.path(), "upstream.csv", &gzip(&csv))
+        .await
+        .unwrap();
+    assert_eq!(unnamed, plain);
+}
+
+#[tokio::test]
+async fn test_truncated_compressed_load_fails() {
+    let temp_dir = TempDir::new().unwrap();
+    let csv = csv_content();
+
+    for (name, compressed) in [
+        ("cut.csv.gz", gzip(&csv)),
+        ("cut.csv.zst", zstd::encode_all(&csv[..], 3).unwrap()),
+    ] {
+        let truncated = &compressed[..compressed.len() / 2];
+        match load(temp_dir.path(), name, truncated).await {
+            Err(Error::Decompression(_)) => {}
+            other => panic!("expected Decompression for {}, got {:?}", name, other),
+        }
+    }
+}
@@ -0,0 +1,164 @@
+// Conditional Update Integration Tests
+// Tests compare-and-set updates with update_if: applied when the expected
+// condition holds, not applied when it doesn't, the policy for predicates
+// matching several rows, and exactly one winner among concurrent updates
+
+use arrow::array::{ArrayRef, Int32Array, StringArray};
+use arrow::datatypes::{DataType, Field, Schema, SchemaRef};
+use arrow::record_batch::RecordBatch;
+use fsdb::database_ops::MultipleMatches;
+use fsdb::query::Value;
+use fsdb::{DatabaseOps, Error};
+use std::path::Path;
+use std::sync::Arc;
+use tempfile::TempDir;
+
+fn create_schema() -> SchemaRef {
+    Arc::new(Schema::new(vec![
+        Field::new("id", DataType::Int32, false),
+        Field::new("owner", DataType::Utf8, false),
+        Field::new("balance", DataType::Int32, true),
+    ]))
+}
+
+/// Accounts 1-3 of alice, bob and bob, with balances 100, 50 and NULL
+async fn create_db(path: &Path) -> DatabaseOps {
+    let schema = create_schema();
+    let db = DatabaseOps::create(path, schema.clone()).await.unwrap();
+    let batch = RecordBatch::try_new(
+        schema,
+        vec![
+            Arc::new(Int32Array::from(vec![1, 2, 3])) as ArrayRef,
+            Arc::new(StringArray::from(vec!["alice", "bob", "bob"])) as ArrayRef,
+            Arc::new(Int32Array::from(vec![Some(100), Some(50), None])) as ArrayRef,
+        ],
+    )
+    .unwrap();
+    db.insert(batch).await.unwrap();
+    db
+}
+
+async fn balance(db: &DatabaseOps, id: i32) -> Value {
+    let sql = format!("SELECT balance FROM data WHERE id = {}", id);
+    let rows = db.query_rows(&sql).await.unwrap();
+    rows.get(0, "balance").unwrap().clone()
+}
+
+#[tokio::test]
+async fn test_applies_when_condition_holds() {
+    let temp_dir = TempDir::new().unwrap();
+    let db = create_db(&temp_dir.path().join("db")).await;
+    let version = db.get_delta_table().await.unwrap().version();
+
+    let applied = db
+        .update_if(&[("balance", "balance - 30")], "id = 1", "balance = 100")
+        .await
+        .unwrap();
+    assert!(applied);
+    assert_eq!(balance(&db, 1).await, Value::Int(70));
+    assert_eq!(balance(&db, 2).await, Value::Int(50));
+    assert_eq!(
+        db.get_delta_table().await.unwrap().version(),
+        version.map(|v| v + 1)
+    );
+}
+
+#[tokio::test]
+async fn test_not_applied_when_condition_fails() {
+    let temp_dir = TempDir::new().unwrap();
+    let db = create_db(&temp_dir.path().join("db")).await;
+    let version = db.get_delta_table().await.unwrap().version();
+
+    // A stale expectation, no matching row, and a NULL that equals nothing
+    for (where_clause, expected) in [
+        ("id = 1", "balance = 90"),
+        ("id = 9", "balance = 100"),
+        ("id = 3", "balance = 0"),
+    ] {
+        let applied = db
+            .update_if(&[("balance", "0")], where_clause, expected)
+            .await
+            .unwrap();
+        assert!(!applied, "{} IF {}", where_clause, expected);
+    }
+    assert_eq!(balance(&db, 1).await, Value::Int(100));
+    assert_eq!(balance(&db, 3).await, Value::Null);
+    assert_eq!(db.get_delta_table().await.unwrap().version(), version);
+}
+
+#[tokio::test]
+async fn test_multiple_matches() {
+    let temp_dir = TempDir::new().unwrap();
+    let db = create_db(&temp_dir.path().join("db")).await;
+
+    // All or none: account 3's NULL balance fails the comparison
+    let applied = db
+        .update_if(&[("balance", "0")], "owner = 'bob'", "balance IS NOT NULL")
+        .await
+        .unwrap();
+    assert!(!applied);
+    assert_eq!(balance(&db, 2).await, Value::Int(50));
+
+    let applied = db
+        .update_if(
+            &[("balance", "0")],
+            "owner = 'bob'",
+            "balance IS NULL OR balance < 60",
+        )
+        .await
+        .unwrap();
+    assert!(applied);
+    assert_eq!(balance(&db, 2).await, Value::Int(0));
+    assert_eq!(balance(&db, 3).await, Value::Int(0));
+
+    // Refused outright when a single row is expected
+    let err = db
+        .update_if_with(
+            &[("balance", "1")],
+            "owner = 'bob'",
+            "balance = 0",
+            MultipleMatches::Error,
+        )
+        .await
+        .unwrap_err();
+    assert!(matches!(err, Error::InvalidOperation(_)), "{}", err);
+    assert_eq!(balance(&db, 2).await, Value::Int(0));
+    assert!(
+        db.update_if_with(
+            &[("balance", "1")],
+            "id = 2",
+            "balance = 0",
+            MultipleMatches::Error,
+        )
+        .await
+        .unwrap()
+    );
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn test_concurrent_updates_apply_once() {
+    let temp_dir = TempDir::new().unwrap();
+    let path = temp_dir.path().join("db");
+    let first = Arc::new(create_db(&path).await);
+    let second = Arc::new(DatabaseOps::open(&path).await.unwrap());
+
+    // Every writer saw balance 100 and tries to take 30 from it
+    let mut tasks = Vec::new();
+    for i in 0..6 {
+        let db = Arc::clone(if i % 2 == 0 { &first } else { &second });
+        tasks.push(tokio::spawn(async move {
+            db.update_if(&[("balance", "balance - 30")], "id = 1", "balance = 100")
+                .await
+                .unwrap()
+        }));
+    }
+    let mut applied = 0;
+    for task in tasks {
+        if task.await.unwrap() {
+            applied += 1;
+        }
+    }
+    assert_eq!(applied, 1);
+    assert_eq!(balance(&first, 1).await, Value::Int(70));
+    assert_eq!(balance(&second, 1).await, Value::Int(70));
+}
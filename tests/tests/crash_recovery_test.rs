@@ -0,0 +1,179 @@
+// Crash Recovery Integration Tests
+// Tests that opening a table cleans up after writes a crash interrupted: a
+// torn commit is set aside so the table opens at its last consistent
+// version, and orphaned data files are removed only when asked
+
+use arrow::array::{ArrayRef, Int32Array, Int64Array, RecordBatch, StringArray};
+use arrow::datatypes::{DataType, Field, Schema, SchemaRef};
+use fsdb::DatabaseOps;
+use fsdb::delta_lake::RecoveryConfig;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+use tempfile::TempDir;
+
+fn create_schema() -> SchemaRef {
+    Arc::new(Schema::new(vec![
+        Field::new("id", DataType::Int32, false),
+        Field::new("name", DataType::Utf8, false),
+    ]))
+}
+
+/// Versions 1 and 2 insert 3 rows each
+async fn create_db(path: &Path) -> DatabaseOps {
+    let schema = create_schema();
+    let db = DatabaseOps::create(path, schema.clone()).await.unwrap();
+    for start in [0, 3] {
+        let ids: Vec<i32> = (start..start + 3).collect();
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![
+                Arc::new(Int32Array::from(ids.clone())) as ArrayRef,
+                Arc::new(StringArray::from_iter_values(
+                    ids.iter().map(|id| format!("user{}", id)),
+                )) as ArrayRef,
+            ],
+        )
+        .unwrap();
+        db.insert(batch).await.unwrap();
+    }
+    db
+}
+
+async fn count_rows(db: &DatabaseOps) -> i64 {
+    let batches = db.query("SELECT COUNT(*) FROM data").await.unwrap();
+    batches[0]
+        .column(0)
+        .as_any()
+        .downcast_ref::<Int64Array>()
+        .unwrap()
+        .value(0)
+}
+
+/// Backdate a file's modification time, as if its writer died long ago
+fn age(path: &Path) {
+    std::fs::File::options()
+        .write(true)
+        .open(path)
+        .unwrap()
+        .set_modified(SystemTime::now() - Duration::from_secs(2 * 60 * 60))
+        .unwrap();
+}
+
+fn commit_path(path: &Path, version: u64) -> PathBuf {
+    path.join("_delta_log")
+        .join(format!("{:020}.json", version))
+}
+
+fn parquet_files(path: &Path) -> Vec<PathBuf> {
+    let mut files: Vec<PathBuf> = std::fs::read_dir(path)
+        .unwrap()
+        .map(|e| e.unwrap().path())
+        .filter(|p| p.extension().and_then(|e| e.to_str()) == Some("parquet"))
+        .collect();
+    files.sort();
+    files
+}
+
+/// Leave a torn version 3 and the data file it was adding, as a writer
+/// killed mid-commit would
+fn simulate_partial_commit(path: &Path) -> PathBuf {
+    let orphan = path.join("part-00000-interrupted-c000.snappy.parquet");
+    std::fs::copy(&parquet_files(path)[0], &orphan).unwrap();
+    let commit = commit_path(path, 3);
+    std::fs::write(
+        &commit,
+        r#"{"commitInfo":{"timestamp":1700000000000,"operation":"WRITE"}}
+{"add":{"path":"part-00000-interrupted-c000.snappy.parquet","size":5"#,
+    )
+    .unwrap();
+    age(&commit);
+    age(&orphan);
+    orphan
+}
+
+#[tokio::test]
+async fn test_open_discards_partial_commit() {
+    let temp_dir = TempDir::new().unwrap();
+    let path = temp_dir.path().join("db");
+    drop(create_db(&path).await);
+    let orphan = simulate_partial_commit(&path);
+
+    let db = DatabaseOps::open(&path).await.unwrap();
+    assert_eq!(db.recovery_report().discarded_versions, vec![3]);
+    assert_eq!(fsdb::delta_lake::latest_version(&path).unwrap(), Some(2));
+    assert_eq!(count_rows(&db).await, 6);
+    assert!(!commit_path(&path, 3).exists());
+    assert!(
+        path.join("_metadata/recovered")
+            .join(format!("{:020}.json", 3))
+            .exists()
+    );
+    // Orphan removal is opt-in
+    assert!(orphan.exists());
+
+    // Writes carry on from the last consistent version
+    let batch = RecordBatch::try_new(
+        create_schema(),
+        vec![
+            Arc::new(Int32Array::from(vec![6])) as ArrayRef,
+            Arc::new(StringArray::from(vec!["user6"])) as ArrayRef,
+        ],
+    )
+    .unwrap();
+    db.insert(batch).await.unwrap();
+    assert_eq!(fsdb::delta_lake::latest_version(&path).unwrap(), Some(3));
+    assert_eq!(count_rows(&db).await, 7);
+
+    let reopened = DatabaseOps::open(&path).await.unwrap();
+    assert!(reopened.recovery_report().is_clean());
+}
+
+#[tokio::test]
+async fn test_recent_partial_commit_is_left_alone() {
+    let temp_dir = TempDir::new().unwrap();
+    let path = temp_dir.path().join("db");
+    drop(create_db(&path).await);
+    simulate_partial_commit(&path);
+    // Written just now, so possibly by a writer that's still going
+    std::fs::File::options()
+        .write(true)
+        .open(commit_path(&path, 3))
+        .unwrap()
+        .set_modified(SystemTime::now())
+        .unwrap();
+
+    let report = fsdb::delta_lake::recover(&path, &RecoveryConfig::default()).unwrap();
+    assert!(report.discarded_versions.is_empty());
+    assert!(commit_path(&path, 3).exists());
+}
+
+#[tokio::test]
+async fn test_orphaned_files_removed_when_enabled() {
+    let temp_dir = TempDir::new().unwrap();
+    let path = temp_dir.path().join("db");
+    drop(create_db(&path).await);
+    let orphan = simulate_partial_commit(&path);
+    let referenced: Vec<PathBuf> = parquet_files(&path)
+        .into_iter()
+        .filter(|p| *p != orphan)
+        .collect();
+    assert_eq!(referenced.len(), 2);
+
+    let recovery = RecoveryConfig {
+        remove_orphans: true,
+        ..Default::default()
+    };
+    let db = DatabaseOps::open_with_recovery(&path, recovery)
+        .await
+        .unwrap();
+    let report = db.recovery_report();
+    assert_eq!(report.discarded_versions, vec![3]);
+    assert_eq!(
+        report.orphans_removed,
+        vec!["part-00000-interrupted-c000.snappy.parquet"]
+    );
+    assert!(!orphan.exists());
+    assert_eq!(parquet_files(&path), referenced);
+    assert_eq!(count_rows(&db).await, 6);
+}
@@ -0,0 +1,186 @@
+// Credential Store Integration Tests
+// Tests authentication, role definitions and user changes against a custom
+// credential store, and that looked-up users are cached until invalidated
+
+use arrow::array::{ArrayRef, Int32Array, RecordBatch};
+use arrow::datatypes::{DataType, Field, Schema, SchemaRef};
+use fsdb::security::{CredentialStore, Permission, Quota, Role, User};
+use fsdb::{DatabaseOps, Error};
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use tempfile::TempDir;
+
+/// Users and roles in memory, counting lookups
+#[derive(Default)]
+struct MemoryStore {
+    users: Mutex<HashMap<String, User>>,
+    roles: Vec<Role>,
+    loads: AtomicUsize,
+}
+
+impl MemoryStore {
+    fn with_user(self, username: &str, password: &str, roles: &[&str]) -> Self {
+        let user = User::new(
+            username.to_string(),
+            password,
+            roles.iter().map(|r| r.to_string()).collect(),
+        )
+        .unwrap();
+        self.users
+            .lock()
+            .unwrap()
+            .insert(username.to_string(), user);
+        self
+    }
+
+    fn roles_of(&self, username: &str) -> Vec<String> {
+        self.users.lock().unwrap()[username].roles.clone()
+    }
+}
+
+impl CredentialStore for MemoryStore {
+    fn load_user(&self, username: &str) -> fsdb::Result<Option<User>> {
+        self.loads.fetch_add(1, Ordering::SeqCst);
+        Ok(self.users.lock().unwrap().get(username).cloned())
+    }
+
+    fn save_user(&self, user: &User) -> fsdb::Result<()> {
+        self.users
+            .lock()
+            .unwrap()
+            .insert(user.username.clone(), user.clone());
+        Ok(())
+    }
+
+    fn list_roles(&self) -> fsdb::Result<Vec<Role>> {
+        Ok(self.roles.clone())
+    }
+}
+
+fn create_schema() -> SchemaRef {
+    Arc::new(Schema::new(vec![Field::new("id", DataType::Int32, false)]))
+}
+
+fn batch(ids: &[i32]) -> RecordBatch {
+    RecordBatch::try_new(
+        create_schema(),
+        vec![Arc::new(Int32Array::from(ids.to_vec())) as ArrayRef],
+    )
+    .unwrap()
+}
+
+/// alice can read and write, bob only read; tenants like dan may store two rows
+fn create_store() -> Arc<MemoryStore> {
+    let store = MemoryStore {
+        roles: vec![
+            Role::new(
+                "tenant".to_string(),
+                vec![Permission::Read, Permission::Write],
+            )
+            .with_quota(Quota::unlimited().with_max_rows(2)),
+        ],
+        ..Default::default()
+    };
+    Arc::new(
+        store
+            .with_user("alice", "secret", &["read", "write"])
+            .with_user("bob", "hunter2", &["read"])
+            .with_user("dan", "pw", &["tenant"]),
+    )
+}
+
+async fn open(
+    path: &Path,
+    store: &Arc<MemoryStore>,
+    credentials: fsdb::security::Credentials,
+) -> fsdb::Result<DatabaseOps> {
+    DatabaseOps::open_with_credential_store(path, store.clone(), credentials).await
+}
+
+#[tokio::test]
+async fn test_authenticates_against_store() {
+    let temp_dir = TempDir::new().unwrap();
+    let path = temp_dir.path().join("db");
+    drop(DatabaseOps::create(&path, create_schema()).await.unwrap());
+    let store = create_store();
+
+    let alice = open(&path, &store, Some(("alice", "secret")))
+        .await
+        .unwrap();
+    alice.insert(batch(&[1, 2, 3])).await.unwrap();
+
+    let bob = open(&path, &store, Some(("bob", "hunter2"))).await.unwrap();
+    assert_eq!(
+        bob.query_rows("SELECT id FROM data").await.unwrap().len(),
+        3
+    );
+    assert!(bob.insert(batch(&[4])).await.is_err());
+
+    assert!(open(&path, &store, Some(("alice", "wrong"))).await.is_err());
+    assert!(
+        open(&path, &store, Some(("carol", "secret")))
+            .await
+            .is_err()
+    );
+}
+
+#[tokio::test]
+async fn test_store_roles_and_new_users() {
+    let temp_dir = TempDir::new().unwrap();
+    let path = temp_dir.path().join("db");
+    drop(DatabaseOps::create(&path, create_schema()).await.unwrap());
+    let store = create_store();
+
+    // Users created through the table are saved to the store
+    let admin = open(&path, &store, None).await.unwrap();
+    admin.create_user("carol", "pw", &["tenant"]).await.unwrap();
+    assert_eq!(store.roles_of("carol"), vec!["tenant"]);
+    assert!(
+        admin
+            .create_user("carol", "other", &["read"])
+            .await
+            .is_err()
+    );
+
+    // The store's tenant role grants writes, up to its quota
+    let carol = open(&path, &store, Some(("carol", "pw"))).await.unwrap();
+    carol.insert(batch(&[1, 2])).await.unwrap();
+    assert!(matches!(
+        carol.insert(batch(&[3])).await,
+        Err(Error::QuotaExceeded { .. })
+    ));
+}
+
+#[tokio::test]
+async fn test_users_cached_until_role_change() {
+    let temp_dir = TempDir::new().unwrap();
+    let path = temp_dir.path().join("db");
+    drop(DatabaseOps::create(&path, create_schema()).await.unwrap());
+    let store = create_store();
+    let admin = open(&path, &store, None).await.unwrap();
+    let max_rows = || {
+        let admin = &admin;
+        async move { admin.quota_status("dan").await.unwrap().limit.max_rows }
+    };
+
+    // Looked up once, then served from the cache
+    let loads = store.loads.load(Ordering::SeqCst);
+    assert_eq!(max_rows().await, Some(2));
+    assert_eq!(max_rows().await, Some(2));
+    assert_eq!(store.loads.load(Ordering::SeqCst), loads + 1);
+
+    // Revoking through the table updates the cached user
+    admin.revoke_role_from_user("dan", "tenant").await.unwrap();
+    assert!(store.roles_of("dan").is_empty());
+    assert_eq!(max_rows().await, None);
+
+    // A change made in the store directly shows once invalidated
+    store
+        .save_user(&User::new("dan".to_string(), "pw", vec!["tenant".to_string()]).unwrap())
+        .unwrap();
+    assert_eq!(max_rows().await, None);
+    admin.invalidate_cached_user("dan");
+    assert_eq!(max_rows().await, Some(2));
+}
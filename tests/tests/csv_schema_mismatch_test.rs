@@ -0,0 +1,146 @@
+// CSV Schema Mismatch Integration Tests
+// Tests writes to data.csv whose columns don't match the table
+
+use arrow::array::{Int32Array, RecordBatch, StringArray};
+use arrow::datatypes::{DataType, Field, Schema};
+use fsdb::Error;
+use fsdb::database_ops::DatabaseOps;
+use fsdb::nfs::coercion::CoercionPolicy;
+use fsdb::nfs::file_views::CsvFileView;
+use fsdb::query::Value;
+use std::sync::Arc;
+use tempfile::TempDir;
+
+async fn create_db(temp_dir: &TempDir) -> Arc<DatabaseOps> {
+    let schema = Arc::new(Schema::new(vec![
+        Field::new("id", DataType::Int32, false),
+        Field::new("name", DataType::Utf8, false),
+        Field::new("age", DataType::Int32, true),
+    ]));
+    let db = DatabaseOps::create(temp_dir.path().join("db"), schema.clone())
+        .await
+        .unwrap();
+
+    let batch = RecordBatch::try_new(
+        schema,
+        vec![
+            Arc::new(Int32Array::from(vec![1, 2])),
+            Arc::new(StringArray::from(vec!["alice", "bob"])),
+            Arc::new(Int32Array::from(vec![30, 40])),
+        ],
+    )
+    .unwrap();
+    db.insert(batch).await.unwrap();
+    Arc::new(db)
+}
+
+async fn names(db: &DatabaseOps) -> Vec<Value> {
+    let result = db
+        .query_rows("SELECT name FROM data ORDER BY id")
+        .await
+        .unwrap();
+    (0..result.len())
+        .map(|i| result.get(i, "name").unwrap().clone())
+        .collect()
+}
+
+#[tokio::test]
+async fn test_mismatched_columns_are_rejected() {
+    let temp_dir = TempDir::new().unwrap();
+    let db = create_db(&temp_dir).await;
+    let view = CsvFileView::new(db.clone());
+    let before = names(&db).await;
+
+    // Leaving a column out would overwrite it with NULL
+    let err = view
+        .apply_write(b"id,name\n1,alice\n2,bob\n", None)
+        .await
+        .unwrap_err();
+    assert!(
+        matches!(&err, Error::SchemaMismatch(msg) if msg.contains("age")),
+        "{}",
+        err
+    );
+
+    // A column the table doesn't have
+    let err = view
+        .apply_write(b"id,name,age,email\n1,alice,30,a@example.com\n", None)
+        .await
+        .unwrap_err();
+    assert!(
+        matches!(&err, Error::SchemaMismatch(msg) if msg.contains("email")),
+        "{}",
+        err
+    );
+
+    // Headerless rows with more fields than columns
+    let err = view
+        .apply_write(b"3,carol,50,extra\n", None)
+        .await
+        .unwrap_err();
+    assert!(matches!(err, Error::SchemaMismatch(_)), "{}", err);
+
+    // Nothing was written
+    assert_eq!(names(&db).await, before);
+}
+
+#[tokio::test]
+async fn test_reordered_header_matches_by_name() {
+    let temp_dir = TempDir::new().unwrap();
+    let db = create_db(&temp_dir).await;
+    let view = CsvFileView::new(db.clone());
+
+    view.apply_write(b"age,name,id\n31,alice,1\n40,bob,2\n", None)
+        .await
+        .unwrap();
+
+    let result = db
+        .query_rows("SELECT id, name, age FROM data ORDER BY id")
+        .await
+        .unwrap();
+    assert_eq!(result.len(), 2);
+    assert_eq!(result.get(0, "name"), Some(&Value::String("alice".into())));
+    assert_eq!(result.get(0, "age"), Some(&Value::Int(31)));
+    assert_eq!(result.get(1, "age"), Some(&Value::Int(40)));
+}
+
+#[tokio::test]
+async fn test_new_column_added_with_schema_evolution() {
+    let temp_dir = TempDir::new().unwrap();
+    let db = create_db(&temp_dir).await;
+    let view = CsvFileView::new(db.clone())
+        .with_coercion(CoercionPolicy::default().with_schema_evolution(true));
+
+    view.apply_write(
+        b"id,name,age,email\n1,alice,30,a@example.com\n2,bob,40,\n",
+        None,
+    )
+    .await
+    .unwrap();
+
+    let schema = db.table_schema().await.unwrap();
+    let email = schema.field_with_name("email").unwrap();
+    assert_eq!(email.data_type(), &DataType::Utf8);
+    assert!(email.is_nullable());
+
+    // The file now shows the column, and later writes can fill it
+    let csv = String::from_utf8(view.generate_csv().await.unwrap()).unwrap();
+    assert!(csv.starts_with("id,name,age,email\n"), "{}", csv);
+    view.apply_write(b"3,carol,50,c@example.com\n", None)
+        .await
+        .unwrap();
+
+    let result = db
+        .query_rows("SELECT email FROM data ORDER BY id")
+        .await
+        .unwrap();
+    assert_eq!(result.len(), 3);
+    assert_eq!(
+        result.get(0, "email"),
+        Some(&Value::String("a@example.com".into()))
+    );
+    assert_eq!(
+        result.get(2, "email"),
+        Some(&Value::String("c@example.com".into()))
+    );
+}
@@ -0,0 +1,109 @@
+// CSV Streaming Integration Tests
+// Tests that data.csv content is generated in bounded chunks, and that a
+// stream reader serves sequential NFS-style reads from a single pass
+
+use arrow::array::{ArrayRef, Int32Array, RecordBatch, StringArray};
+use arrow::datatypes::{DataType, Field, Schema};
+use fsdb::DatabaseOps;
+use fsdb::nfs::file_views::{CsvFileView, CsvStreamReader};
+use futures::TryStreamExt;
+use std::path::Path;
+use std::sync::Arc;
+use tempfile::TempDir;
+
+/// Four files of 5,000 rows each
+async fn create_db(path: &Path) -> Arc<DatabaseOps> {
+    let schema = Arc::new(Schema::new(vec![
+        Field::new("id", DataType::Int32, false),
+        Field::new("name", DataType::Utf8, false),
+    ]));
+    let db = DatabaseOps::create(path, schema.clone()).await.unwrap();
+    for file in 0..4 {
+        let ids: Vec<i32> = (file * 5000..(file + 1) * 5000).collect();
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![
+                Arc::new(Int32Array::from(ids.clone())) as ArrayRef,
+                Arc::new(StringArray::from_iter_values(
+                    ids.iter().map(|id| format!("user-{}", id)),
+                )) as ArrayRef,
+            ],
+        )
+        .unwrap();
+        db.insert(batch).await.unwrap();
+    }
+    Arc::new(db)
+}
+
+#[tokio::test]
+async fn test_stream_chunks_are_bounded() {
+    let temp_dir = TempDir::new().unwrap();
+    let db = create_db(&temp_dir.path().join("db")).await;
+    let view = CsvFileView::new(db);
+
+    let chunks: Vec<_> = view.stream().await.unwrap().try_collect().await.unwrap();
+    let content = view.generate_csv().await.unwrap();
+    assert_eq!(chunks.concat(), content);
+    assert_eq!(chunks[0].as_ref(), b"id,name\n");
+
+    // No chunk holds more than a batch of rows
+    let largest = chunks.iter().map(|c| c.len()).max().unwrap();
+    assert!(chunks.len() > 4, "{} chunks", chunks.len());
+    assert!(
+        largest * 2 < content.len(),
+        "{} of {}",
+        largest,
+        content.len()
+    );
+
+    assert_eq!(view.size().await.unwrap(), content.len() as u64);
+    let lines = content.iter().filter(|b| **b == b'\n').count();
+    assert_eq!(lines, 20_001);
+}
+
+#[tokio::test]
+async fn test_sequential_reads_reassemble_content() {
+    let temp_dir = TempDir::new().unwrap();
+    let db = create_db(&temp_dir.path().join("db")).await;
+    let view = CsvFileView::new(db);
+    let content = view.generate_csv().await.unwrap();
+
+    // Reads the size NFS clients use, straddling chunk boundaries
+    let mut reader = CsvStreamReader::new(view.stream().await.unwrap());
+    let mut read = Vec::new();
+    loop {
+        let (data, eof) = reader.read_at(read.len() as u64, 32 * 1024).await.unwrap();
+        read.extend_from_slice(&data);
+        if eof {
+            break;
+        }
+        assert!(!data.is_empty());
+    }
+    assert_eq!(read, content);
+
+    // The stream only moves forward
+    assert!(reader.read_at(0, 10).await.is_err());
+}
+
+#[tokio::test]
+async fn test_reads_skip_ahead_and_past_end() {
+    let temp_dir = TempDir::new().unwrap();
+    let db = create_db(&temp_dir.path().join("db")).await;
+    let view = CsvFileView::new(db);
+    let content = view.generate_csv().await.unwrap();
+    let len = content.len() as u64;
+
+    let mut reader = CsvStreamReader::new(view.stream().await.unwrap());
+    let (data, eof) = reader.read_at(len / 2, 100).await.unwrap();
+    assert_eq!(data, &content[content.len() / 2..content.len() / 2 + 100]);
+    assert!(!eof);
+
+    // The last bytes report the end without another read
+    let (data, eof) = reader.read_at(len - 10, 10).await.unwrap();
+    assert_eq!(data, &content[content.len() - 10..]);
+    assert!(eof);
+
+    let (data, eof) = reader.read_at(len + 5, 10).await.unwrap();
+    assert!(data.is_empty());
+    assert!(eof);
+}
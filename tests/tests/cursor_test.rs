@@ -0,0 +1,129 @@
+// Cursor Integration Tests
+// Tests server-side paginated cursors pinned to a Delta Lake version
+
+use arrow::array::{ArrayRef, Int32Array, RecordBatch, StringArray};
+use arrow::datatypes::{DataType, Field, Schema, SchemaRef};
+use fsdb::query::CursorConfig;
+use fsdb::{DatabaseOps, Error};
+use std::sync::Arc;
+use std::time::Duration;
+use tempfile::TempDir;
+
+fn create_schema() -> SchemaRef {
+    Arc::new(Schema::new(vec![
+        Field::new("id", DataType::Int32, false),
+        Field::new("name", DataType::Utf8, false),
+    ]))
+}
+
+fn create_batch(schema: SchemaRef, ids: Vec<i32>) -> RecordBatch {
+    let names: Vec<String> = ids.iter().map(|id| format!("user_{}", id)).collect();
+    RecordBatch::try_new(
+        schema,
+        vec![
+            Arc::new(Int32Array::from(ids)) as ArrayRef,
+            Arc::new(StringArray::from(names)) as ArrayRef,
+        ],
+    )
+    .unwrap()
+}
+
+fn collect_ids(batches: &[RecordBatch]) -> Vec<i32> {
+    batches
+        .iter()
+        .flat_map(|b| {
+            b.column(0)
+                .as_any()
+                .downcast_ref::<Int32Array>()
+                .unwrap()
+                .values()
+                .to_vec()
+        })
+        .collect()
+}
+
+#[tokio::test]
+async fn test_cursor_pages_through_results() {
+    let temp_dir = TempDir::new().unwrap();
+    let schema = create_schema();
+    let db = DatabaseOps::create(temp_dir.path().join("cursor_db"), schema.clone())
+        .await
+        .unwrap();
+    db.insert(create_batch(schema.clone(), (1..=10).collect()))
+        .await
+        .unwrap();
+
+    let cursor = db
+        .open_cursor("SELECT id FROM data ORDER BY id")
+        .await
+        .unwrap();
+
+    let mut ids = Vec::new();
+    let mut pages = 0;
+    loop {
+        let (rows, has_more) = db.fetch(cursor, 4).await.unwrap();
+        ids.extend(collect_ids(&rows));
+        pages += 1;
+        if !has_more {
+            break;
+        }
+    }
+
+    assert_eq!(pages, 3, "10 rows in pages of 4 should take 3 fetches");
+    assert_eq!(ids, (1..=10).collect::<Vec<_>>());
+}
+
+#[tokio::test]
+async fn test_cursor_snapshot_is_stable() {
+    let temp_dir = TempDir::new().unwrap();
+    let schema = create_schema();
+    let db = DatabaseOps::create(temp_dir.path().join("cursor_db"), schema.clone())
+        .await
+        .unwrap();
+    db.insert(create_batch(schema.clone(), vec![1, 2, 3]))
+        .await
+        .unwrap();
+
+    let cursor = db
+        .open_cursor("SELECT id FROM data ORDER BY id")
+        .await
+        .unwrap();
+
+    // Rows written after the cursor was opened are not visible to it
+    db.insert(create_batch(schema.clone(), vec![4, 5, 6]))
+        .await
+        .unwrap();
+
+    let (rows, has_more) = db.fetch(cursor, 100).await.unwrap();
+    assert_eq!(collect_ids(&rows), vec![1, 2, 3]);
+    assert!(!has_more);
+}
+
+#[tokio::test]
+async fn test_fetch_from_expired_cursor() {
+    let temp_dir = TempDir::new().unwrap();
+    let schema = create_schema();
+    let db = DatabaseOps::create(temp_dir.path().join("cursor_db"), schema.clone())
+        .await
+        .unwrap()
+        .with_cursor_config(CursorConfig {
+            idle_timeout: Duration::from_millis(100),
+            ..Default::default()
+        });
+    db.insert(create_batch(schema.clone(), vec![1, 2, 3]))
+        .await
+        .unwrap();
+
+    let cursor = db.open_cursor("SELECT id FROM data").await.unwrap();
+    db.fetch(cursor, 1).await.unwrap();
+
+    tokio::time::sleep(Duration::from_millis(200)).await;
+
+    match db.fetch(cursor, 1).await {
+        Err(Error::CursorExpired(id)) => assert_eq!(id, cursor),
+        other => panic!(
+            "expected CursorExpired, got {:?}",
+            other.map(|(_, more)| more)
+        ),
+    }
+}
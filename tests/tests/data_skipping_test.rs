@@ -311,3 +311,137 @@ async fn test_no_data_skipping_when_all_needed() {
     assert_eq!(skipping_stats.files_skipped, 0, "Should not skip any files");
     assert_eq!(skipping_stats.files_read, 3, "Should read all 3 files");
 }
+
+/// Insert three files for a nullable `score` column:
+/// - File 1: no nulls
+/// - File 2: every other row null
+/// - File 3: all nulls
+async fn create_db_with_nulls(db_path: &std::path::Path) -> DatabaseOps {
+    let schema = Arc::new(Schema::new(vec![
+        Field::new("id", DataType::Int32, false),
+        Field::new("score", DataType::Int32, true),
+    ]));
+
+    let db = DatabaseOps::create(db_path, schema.clone()).await.unwrap();
+
+    let files: [Vec<Option<i32>>; 3] = [
+        (0..100).map(Some).collect(),
+        (0..100).map(|i| (i % 2 == 0).then_some(i)).collect(),
+        vec![None; 100],
+    ];
+    for (i, scores) in files.into_iter().enumerate() {
+        let start_id = i as i32 * 100;
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![
+                Arc::new(Int32Array::from(
+                    (start_id..start_id + 100).collect::<Vec<i32>>(),
+                )),
+                Arc::new(Int32Array::from(scores)),
+            ],
+        )
+        .unwrap();
+        db.insert(batch).await.unwrap();
+    }
+    db
+}
+
+fn single_count(results: &[RecordBatch]) -> i64 {
+    results
+        .iter()
+        .find(|b| b.num_rows() > 0)
+        .map(|b| {
+            b.column(0)
+                .as_any()
+                .downcast_ref::<Int64Array>()
+                .unwrap()
+                .value(0)
+        })
+        .unwrap_or(0)
+}
+
+/// Test IS NULL / IS NOT NULL pruning using per-file null counts
+#[tokio::test]
+async fn test_data_skipping_with_null_predicates() {
+    let temp_dir = TempDir::new().unwrap();
+    let db = create_db_with_nulls(&temp_dir.path().join("test_db")).await;
+
+    // Only files 2 and 3 contain nulls
+    let results = db
+        .query("SELECT COUNT(*) as count FROM data WHERE score IS NULL")
+        .await
+        .unwrap();
+    assert_eq!(single_count(&results), 150);
+    let skipping_stats = db.get_data_skipping_stats().await.unwrap();
+    assert_eq!(
+        skipping_stats.files_skipped, 1,
+        "File without nulls skipped"
+    );
+    assert_eq!(skipping_stats.files_read, 2);
+
+    // Only files 1 and 2 contain values
+    let results = db
+        .query("SELECT COUNT(*) as count FROM data WHERE score IS NOT NULL")
+        .await
+        .unwrap();
+    assert_eq!(single_count(&results), 150);
+    let skipping_stats = db.get_data_skipping_stats().await.unwrap();
+    assert_eq!(skipping_stats.files_skipped, 1, "All-null file skipped");
+    assert_eq!(skipping_stats.files_read, 2);
+
+    // A comparison can never match the all-null file
+    let results = db
+        .query("SELECT COUNT(*) as count FROM data WHERE score >= 0")
+        .await
+        .unwrap();
+    assert_eq!(single_count(&results), 150);
+    let skipping_stats = db.get_data_skipping_stats().await.unwrap();
+    assert_eq!(skipping_stats.files_skipped, 1);
+    assert_eq!(skipping_stats.files_read, 2);
+
+    // A nullness test in a disjunction can't skip the files the other side
+    // matches in
+    let results = db
+        .query("SELECT COUNT(*) as count FROM data WHERE score IS NULL OR id = 1")
+        .await
+        .unwrap();
+    assert_eq!(single_count(&results), 151);
+    assert_eq!(db.get_data_skipping_stats().await.unwrap().files_skipped, 0);
+
+    let results = db
+        .query("SELECT COUNT(*) as count FROM data WHERE score IS NOT NULL OR id = 250")
+        .await
+        .unwrap();
+    assert_eq!(single_count(&results), 151);
+    assert_eq!(db.get_data_skipping_stats().await.unwrap().files_skipped, 0);
+}
+
+/// Test NULL comparisons are unknown and NULLs group together
+#[tokio::test]
+async fn test_null_comparison_policy() {
+    let temp_dir = TempDir::new().unwrap();
+    let db = create_db_with_nulls(&temp_dir.path().join("test_db")).await;
+
+    // col = NULL is unknown for every row, so nothing matches
+    let results = db
+        .query("SELECT COUNT(*) as count FROM data WHERE score = NULL")
+        .await
+        .unwrap();
+    assert_eq!(single_count(&results), 0);
+
+    // Null rows are excluded from both sides of a comparison
+    let results = db
+        .query("SELECT COUNT(*) as count FROM data WHERE score < 50 OR score >= 50")
+        .await
+        .unwrap();
+    assert_eq!(single_count(&results), 150);
+
+    // All 150 null rows form a single group
+    let results = db
+        .query("SELECT COUNT(*) as count FROM data GROUP BY score HAVING score IS NULL")
+        .await
+        .unwrap();
+    let group_rows: usize = results.iter().map(|b| b.num_rows()).sum();
+    assert_eq!(group_rows, 1, "NULLs should form one group");
+    assert_eq!(single_count(&results), 150);
+}
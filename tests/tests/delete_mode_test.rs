@@ -0,0 +1,181 @@
+// Delete Mode Integration Tests
+// Tests that deletes and updates rewrite files, and that tables set to use
+// deletion vectors, which FSDB doesn't write, are refused until they're
+// disabled
+
+use arrow::array::{ArrayRef, Int32Array, RecordBatch, StringArray};
+use arrow::datatypes::{DataType, Field, Schema};
+use fsdb::delta_lake::read_table_config;
+use fsdb::query::Value;
+use fsdb::{DatabaseOps, Error};
+use std::path::Path;
+use std::sync::Arc;
+use tempfile::TempDir;
+
+async fn create_db(path: &Path) -> DatabaseOps {
+    let schema = Arc::new(Schema::new(vec![
+        Field::new("id", DataType::Int32, false),
+        Field::new("name", DataType::Utf8, false),
+    ]));
+    let db = DatabaseOps::create(path, schema.clone()).await.unwrap();
+    let batch = RecordBatch::try_new(
+        schema,
+        vec![
+            Arc::new(Int32Array::from(vec![1, 2, 3, 4])) as ArrayRef,
+            Arc::new(StringArray::from(vec!["a", "b", "c", "d"])) as ArrayRef,
+        ],
+    )
+    .unwrap();
+    db.insert(batch).await.unwrap();
+    db
+}
+
+async fn rows(db: &DatabaseOps) -> Vec<(Value, Value)> {
+    let result = db
+        .query_rows("SELECT id, name FROM data ORDER BY id")
+        .await
+        .unwrap();
+    (0..result.len())
+        .map(|i| {
+            (
+                result.get(i, "id").unwrap().clone(),
+                result.get(i, "name").unwrap().clone(),
+            )
+        })
+        .collect()
+}
+
+/// Every action in the table's commits, in version order
+fn log_actions(path: &Path) -> Vec<serde_json::Value> {
+    let mut commits: Vec<_> = std::fs::read_dir(path.join("_delta_log"))
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "json"))
+        .collect();
+    commits.sort();
+    commits
+        .iter()
+        .flat_map(|commit| {
+            std::fs::read_to_string(commit)
+                .unwrap()
+                .lines()
+                .map(|line| serde_json::from_str(line).unwrap())
+                .collect::<Vec<serde_json::Value>>()
+        })
+        .collect()
+}
+
+fn deletion_vectors(path: &Path) -> usize {
+    log_actions(path)
+        .iter()
+        .filter(|action| !action["add"]["deletionVector"].is_null())
+        .count()
+}
+
+fn removes(path: &Path) -> usize {
+    log_actions(path)
+        .iter()
+        .filter(|action| action.get("remove").is_some())
+        .count()
+}
+
+/// Commit a copy of the table's metadata with `key` set, as another engine would
+fn set_table_property(path: &Path, key: &str, value: &str) {
+    let actions = log_actions(path);
+    let mut metadata = actions
+        .iter()
+        .rev()
+        .find(|action| action.get("metaData").is_some())
+        .unwrap()
+        .clone();
+    metadata["metaData"]["configuration"][key] = value.into();
+    let version = fsdb::delta_lake::latest_version(path).unwrap().unwrap() + 1;
+    std::fs::write(
+        path.join("_delta_log")
+            .join(format!("{:020}.json", version)),
+        metadata.to_string(),
+    )
+    .unwrap();
+}
+
+fn int_and_string(id: i32, name: &str) -> (Value, Value) {
+    (Value::Int(id as i64), Value::String(name.into()))
+}
+
+#[tokio::test]
+async fn test_copy_on_write_rewrites_files() {
+    let temp_dir = TempDir::new().unwrap();
+    let db_path = temp_dir.path().join("db");
+    let db = create_db(&db_path).await;
+    assert!(!db.deletion_vectors_enabled());
+
+    db.disable_deletion_vectors().await.unwrap();
+    let config = read_table_config(&db_path).unwrap();
+    assert_eq!(
+        config
+            .configuration
+            .get("delta.enableDeletionVectors")
+            .map(String::as_str),
+        Some("false")
+    );
+
+    let commit = db.delete_rows_where("id = 2").await.unwrap();
+    assert_eq!(commit.rows_affected, 1);
+    db.update_rows(&[("name", "'z'")], "id = 3").await.unwrap();
+
+    // The original file was replaced twice, without deletion vectors
+    assert_eq!(removes(&db_path), 2);
+    assert_eq!(deletion_vectors(&db_path), 0);
+    assert_eq!(
+        fsdb::delta_lake::read_delta_log(&db_path)
+            .unwrap()
+            .files
+            .len(),
+        1
+    );
+    assert_eq!(
+        rows(&db).await,
+        vec![
+            int_and_string(1, "a"),
+            int_and_string(3, "z"),
+            int_and_string(4, "d")
+        ]
+    );
+
+    // The setting is part of the table
+    let reopened = DatabaseOps::open(&db_path).await.unwrap();
+    assert!(!reopened.deletion_vectors_enabled());
+}
+
+#[tokio::test]
+async fn test_deletion_vector_tables_are_refused() {
+    let temp_dir = TempDir::new().unwrap();
+    let db_path = temp_dir.path().join("db");
+    let db = create_db(&db_path).await;
+
+    // A table another engine set to merge-on-read isn't rewritten behind its back
+    set_table_property(&db_path, "delta.enableDeletionVectors", "true");
+    assert!(db.deletion_vectors_enabled());
+    let err = db.delete_rows_where("id = 2").await.unwrap_err();
+    assert!(
+        matches!(&err, Error::UnsupportedDeltaFeature(f) if f == "deletionVectors"),
+        "{}",
+        err
+    );
+    let err = db
+        .update_rows(&[("name", "'z'")], "id = 3")
+        .await
+        .unwrap_err();
+    assert!(matches!(err, Error::UnsupportedDeltaFeature(_)), "{}", err);
+
+    // Nothing changed, and reads are unaffected
+    assert_eq!(removes(&db_path), 0);
+    assert_eq!(rows(&db).await.len(), 4);
+
+    // Switching back re-enables deletes
+    db.disable_deletion_vectors().await.unwrap();
+    assert!(!db.deletion_vectors_enabled());
+    db.delete_rows_where("id = 2").await.unwrap();
+    assert_eq!(rows(&db).await.len(), 3);
+    assert_eq!(deletion_vectors(&db_path), 0);
+}
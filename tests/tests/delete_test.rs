@@ -0,0 +1,299 @@
+// Row Deletion Integration Tests
+// Tests the row count returned by delete_rows_where, its audit entry and
+// which data files it rewrites
+
+use arrow::array::{ArrayRef, Int32Array, StringArray};
+use arrow::datatypes::{DataType, Field, Schema, SchemaRef};
+use arrow::record_batch::RecordBatch;
+use fsdb::DatabaseOps;
+use std::collections::HashSet;
+use std::path::Path;
+use std::sync::Arc;
+use tempfile::TempDir;
+
+fn create_schema() -> SchemaRef {
+    Arc::new(Schema::new(vec![
+        Field::new("id", DataType::Int32, false),
+        Field::new("name", DataType::Utf8, false),
+    ]))
+}
+
+fn create_batch(schema: SchemaRef) -> RecordBatch {
+    RecordBatch::try_new(
+        schema,
+        vec![
+            Arc::new(Int32Array::from(vec![1, 2, 3, 4, 5])) as ArrayRef,
+            Arc::new(StringArray::from(vec!["a", "b", "c", "d", "e"])) as ArrayRef,
+        ],
+    )
+    .unwrap()
+}
+
+/// Number of commits in the Delta log
+fn commit_count(db_path: &Path) -> usize {
+    std::fs::read_dir(db_path.join("_delta_log"))
+        .unwrap()
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.file_name().to_string_lossy().ends_with(".json"))
+        .count()
+}
+
+async fn row_count(db: &DatabaseOps) -> i64 {
+    let results = db.query("SELECT COUNT(*) FROM data").await.unwrap();
+    results[0]
+        .column(0)
+        .as_any()
+        .downcast_ref::<arrow::array::Int64Array>()
+        .unwrap()
+        .value(0)
+}
+
+#[tokio::test]
+async fn test_delete_returns_matching_row_count() {
+    let temp_dir = TempDir::new().unwrap();
+    let db_path = temp_dir.path().join("delete_db");
+    let schema = create_schema();
+    let db = DatabaseOps::create(&db_path, schema.clone()).await.unwrap();
+    db.insert(create_batch(schema)).await.unwrap();
+
+    let deleted = db.delete_rows_where("id > 3").await.unwrap();
+
+    assert_eq!(deleted.rows_affected, 2);
+    assert_eq!(row_count(&db).await, 3);
+}
+
+#[tokio::test]
+async fn test_delete_no_match_does_not_commit() {
+    let temp_dir = TempDir::new().unwrap();
+    let db_path = temp_dir.path().join("delete_db");
+    let schema = create_schema();
+    let db = DatabaseOps::create(&db_path, schema.clone()).await.unwrap();
+    db.insert(create_batch(schema)).await.unwrap();
+    let commits_before = commit_count(&db_path);
+
+    let version_before = db.get_delta_table().await.unwrap().version().unwrap_or(0);
+    let deleted = db.delete_rows_where("id > 100").await.unwrap();
+
+    assert_eq!(deleted.rows_affected, 0);
+    assert_eq!(
+        deleted.version, version_before,
+        "A no-op delete reports the version the table was already at"
+    );
+    assert_eq!(row_count(&db).await, 5);
+    assert_eq!(
+        commit_count(&db_path),
+        commits_before,
+        "A delete matching nothing should not add a Delta version"
+    );
+}
+
+#[tokio::test]
+async fn test_delete_all_rows() {
+    let temp_dir = TempDir::new().unwrap();
+    let db_path = temp_dir.path().join("delete_db");
+    let schema = create_schema();
+    let db = DatabaseOps::create(&db_path, schema.clone()).await.unwrap();
+    db.insert(create_batch(schema)).await.unwrap();
+
+    let deleted = db.delete_rows_where("1=1").await.unwrap();
+    assert_eq!(deleted.rows_affected, 5);
+    assert_eq!(row_count(&db).await, 0);
+
+    // Truncating an empty table is a no-op
+    let commits_before = commit_count(&db_path);
+    assert_eq!(db.delete_rows_where("1=1").await.unwrap().rows_affected, 0);
+    assert_eq!(commit_count(&db_path), commits_before);
+}
+
+#[tokio::test]
+async fn test_delete_count_is_audited() {
+    let temp_dir = TempDir::new().unwrap();
+    let db_path = temp_dir.path().join("delete_db");
+    let schema = create_schema();
+    let db = DatabaseOps::create_with_auth(&db_path, schema.clone(), true)
+        .await
+        .unwrap();
+    db.create_user("admin", "secret", &["admin"]).await.unwrap();
+    let db = DatabaseOps::open_with_credentials(&db_path, Some(("admin", "secret")))
+        .await
+        .unwrap();
+    db.insert(create_batch(schema)).await.unwrap();
+
+    let first = db.delete_rows_where("id <= 2").await.unwrap();
+    let second = db.delete_rows_where("id > 100").await.unwrap();
+
+    let deletes: Vec<_> = db
+        .get_audit_log()
+        .await
+        .unwrap()
+        .into_iter()
+        .filter(|e| e.operation == "DELETE")
+        .collect();
+    assert_eq!(deletes.len(), 2);
+    assert!(deletes[0].success);
+    assert_eq!(
+        deletes[0].details,
+        format!("WHERE id <= 2 (2 rows, version {})", first.version)
+    );
+    assert_eq!(
+        deletes[1].details,
+        format!("WHERE id > 100 (0 rows, version {})", second.version)
+    );
+    assert_eq!(first.version, second.version);
+}
+
+/// Drain a cursor into (id, name) pairs sorted by id
+async fn fetch_all(db: &DatabaseOps, cursor: fsdb::query::CursorId) -> Vec<(i32, String)> {
+    let mut rows = Vec::new();
+    loop {
+        let (batches, more) = db.fetch(cursor, 2).await.unwrap();
+        for batch in &batches {
+            let ids = batch
+                .column_by_name("id")
+                .unwrap()
+                .as_any()
+                .downcast_ref::<Int32Array>()
+                .unwrap();
+            let names = batch
+                .column_by_name("name")
+                .unwrap()
+                .as_any()
+                .downcast_ref::<StringArray>()
+                .unwrap();
+            for i in 0..batch.num_rows() {
+                rows.push((ids.value(i), names.value(i).to_string()));
+            }
+        }
+        if !more {
+            break;
+        }
+    }
+    rows.sort();
+    rows
+}
+
+#[tokio::test]
+async fn test_delete_returning_yields_deleted_rows() {
+    let temp_dir = TempDir::new().unwrap();
+    let db_path = temp_dir.path().join("delete_db");
+    let schema = create_schema();
+    let db = DatabaseOps::create(&db_path, schema.clone()).await.unwrap();
+    db.insert(create_batch(schema)).await.unwrap();
+
+    let returning = db.delete_rows_where_returning("id >= 3").await.unwrap();
+    assert_eq!(returning.count, 3);
+
+    // Rows are gone from the table but still readable through the cursor
+    assert_eq!(row_count(&db).await, 2);
+    let deleted = fetch_all(&db, returning.cursor).await;
+    assert_eq!(
+        deleted,
+        vec![
+            (3, "c".to_string()),
+            (4, "d".to_string()),
+            (5, "e".to_string())
+        ]
+    );
+}
+
+#[tokio::test]
+async fn test_delete_returning_no_match() {
+    let temp_dir = TempDir::new().unwrap();
+    let db_path = temp_dir.path().join("delete_db");
+    let schema = create_schema();
+    let db = DatabaseOps::create(&db_path, schema.clone()).await.unwrap();
+    db.insert(create_batch(schema)).await.unwrap();
+    let commits_before = commit_count(&db_path);
+
+    let returning = db.delete_rows_where_returning("id > 100").await.unwrap();
+
+    assert_eq!(returning.count, 0);
+    assert!(fetch_all(&db, returning.cursor).await.is_empty());
+    assert_eq!(commit_count(&db_path), commits_before);
+}
+
+/// Insert ids `start..start + 10` as one data file
+async fn insert_range(db: &DatabaseOps, schema: SchemaRef, start: i32) {
+    let ids: Vec<i32> = (start..start + 10).collect();
+    let names: Vec<String> = ids.iter().map(|id| format!("user_{}", id)).collect();
+    let batch = RecordBatch::try_new(
+        schema,
+        vec![
+            Arc::new(Int32Array::from(ids)) as ArrayRef,
+            Arc::new(StringArray::from(names)) as ArrayRef,
+        ],
+    )
+    .unwrap();
+    db.insert(batch).await.unwrap();
+}
+
+/// Live data files in the table
+fn live_files(db_path: &Path) -> HashSet<String> {
+    fsdb::delta_lake::get_file_statistics(db_path)
+        .unwrap()
+        .into_iter()
+        .map(|file| file.path)
+        .collect()
+}
+
+#[tokio::test]
+async fn test_delete_drops_fully_matched_files() {
+    let temp_dir = TempDir::new().unwrap();
+    let db_path = temp_dir.path().join("delete_db");
+    let schema = create_schema();
+    let db = DatabaseOps::create(&db_path, schema.clone()).await.unwrap();
+    for start in [0, 10, 20] {
+        insert_range(&db, schema.clone(), start).await;
+    }
+    let before = live_files(&db_path);
+    assert_eq!(before.len(), 3);
+
+    // Covers the first two files exactly
+    let deleted = db.delete_rows_where("id >= 0 AND id < 20").await.unwrap();
+    assert_eq!(deleted.rows_affected, 20);
+    assert_eq!(row_count(&db).await, 10);
+
+    // Only the untouched file is left: nothing was rewritten
+    let after = live_files(&db_path);
+    assert_eq!(after.len(), 1);
+    assert!(after.is_subset(&before));
+}
+
+#[tokio::test]
+async fn test_delete_rewrites_only_partially_matched_files() {
+    let temp_dir = TempDir::new().unwrap();
+    let db_path = temp_dir.path().join("delete_db");
+    let schema = create_schema();
+    let db = DatabaseOps::create(&db_path, schema.clone()).await.unwrap();
+    for start in [0, 10, 20] {
+        insert_range(&db, schema.clone(), start).await;
+    }
+    let before = live_files(&db_path);
+
+    // Drops the middle file and half of the last one
+    let deleted = db.delete_rows_where("id >= 10 AND id < 25").await.unwrap();
+    assert_eq!(deleted.rows_affected, 15);
+
+    let after = live_files(&db_path);
+    assert_eq!(after.len(), 2);
+    assert_eq!(
+        after.intersection(&before).count(),
+        1,
+        "The first file is kept"
+    );
+
+    let results = db
+        .query("SELECT MIN(id), MAX(id), COUNT(*) FROM data WHERE id >= 10")
+        .await
+        .unwrap();
+    let value = |i: usize| -> i64 {
+        arrow::compute::cast(results[0].column(i), &DataType::Int64)
+            .unwrap()
+            .as_any()
+            .downcast_ref::<arrow::array::Int64Array>()
+            .unwrap()
+            .value(0)
+    };
+    assert_eq!((value(0), value(1), value(2)), (25, 29, 5));
+    assert_eq!(row_count(&db).await, 15);
+}
@@ -0,0 +1,166 @@
+// External Delta Table Compatibility Tests
+// Reads tests/fixtures/spark_column_mapping, a table laid out the way Spark writes it:
+// column mapping in name mode, a two-part checkpoint at version 2 with the
+// commits before it cleaned up, and statistics keyed by physical column names
+
+use arrow::array::Int32Array;
+use fsdb::{DatabaseOps, Error};
+use std::path::{Path, PathBuf};
+use tempfile::TempDir;
+
+/// Copy the fixture so tests that attempt writes can't touch the committed files
+fn copy_fixture(temp_dir: &TempDir) -> PathBuf {
+    fn copy_dir(src: &Path, dst: &Path) {
+        std::fs::create_dir_all(dst).unwrap();
+        for entry in std::fs::read_dir(src).unwrap() {
+            let entry = entry.unwrap();
+            let target = dst.join(entry.file_name());
+            if entry.file_type().unwrap().is_dir() {
+                copy_dir(&entry.path(), &target);
+            } else {
+                std::fs::copy(entry.path(), &target).unwrap();
+            }
+        }
+    }
+
+    let src = Path::new(env!("CARGO_MANIFEST_DIR")).join("fixtures/spark_column_mapping");
+    let dst = temp_dir.path().join("spark_table");
+    copy_dir(&src, &dst);
+    dst
+}
+
+fn ids(batches: &[arrow::array::RecordBatch]) -> Vec<i32> {
+    batches
+        .iter()
+        .flat_map(|batch| {
+            batch
+                .column(0)
+                .as_any()
+                .downcast_ref::<Int32Array>()
+                .unwrap()
+                .values()
+                .to_vec()
+        })
+        .collect()
+}
+
+#[tokio::test]
+async fn test_read_spark_table_with_column_mapping_and_checkpoint() {
+    let temp_dir = TempDir::new().unwrap();
+    let db = DatabaseOps::open(copy_fixture(&temp_dir)).await.unwrap();
+
+    let names: Vec<String> = db
+        .schema()
+        .fields()
+        .iter()
+        .map(|f| f.name().clone())
+        .collect();
+    assert_eq!(names, vec!["id", "name", "score"]);
+
+    // id 5 was deleted after the checkpoint; ids 1-3 only appear in the checkpoint
+    let batches = db.query("SELECT id FROM data ORDER BY id").await.unwrap();
+    assert_eq!(ids(&batches), vec![1, 2, 3, 4, 6]);
+}
+
+#[tokio::test]
+async fn test_spark_statistics_use_logical_names() {
+    let temp_dir = TempDir::new().unwrap();
+    let path = copy_fixture(&temp_dir);
+
+    let files = fsdb::delta_lake::get_file_statistics(&path).unwrap();
+    assert_eq!(files.len(), 2);
+    assert_eq!(files.iter().map(|f| f.num_records).sum::<u64>(), 5);
+    for file in &files {
+        assert!(file.min_values.contains_key("id"), "{:?}", file);
+        assert!(!file.min_values.keys().any(|k| k.starts_with("col-")));
+    }
+
+    let db = DatabaseOps::open(&path).await.unwrap();
+    let stats = db.get_column_statistics().await.unwrap();
+    assert_eq!(stats["id"].min_value, serde_json::json!(1));
+    assert_eq!(stats["id"].max_value, serde_json::json!(6));
+    assert_eq!(stats["score"].null_count, 1);
+}
+
+#[tokio::test]
+async fn test_data_skipping_on_spark_table() {
+    let temp_dir = TempDir::new().unwrap();
+    let db = DatabaseOps::open(copy_fixture(&temp_dir)).await.unwrap();
+
+    let batches = db.query("SELECT id FROM data WHERE id > 3").await.unwrap();
+    let mut found = ids(&batches);
+    found.sort();
+    assert_eq!(found, vec![4, 6]);
+
+    let skipping = db.get_data_skipping_stats().await.unwrap();
+    assert_eq!(skipping.total_files, 2);
+    assert_eq!(skipping.files_skipped, 1);
+}
+
+#[tokio::test]
+async fn test_writes_to_column_mapped_table_are_rejected() {
+    let temp_dir = TempDir::new().unwrap();
+    let path = copy_fixture(&temp_dir);
+    let db = DatabaseOps::open(&path).await.unwrap();
+    let log_entries = std::fs::read_dir(path.join("_delta_log")).unwrap().count();
+
+    let result = db.delete_rows_where("id = 1").await;
+    assert!(
+        matches!(&result, Err(Error::UnsupportedDeltaFeature(f)) if f == "columnMapping"),
+        "{:?}",
+        result
+    );
+
+    // Nothing was committed and the table still reads
+    assert_eq!(
+        std::fs::read_dir(path.join("_delta_log")).unwrap().count(),
+        log_entries
+    );
+    let batches = db.query("SELECT id FROM data ORDER BY id").await.unwrap();
+    assert_eq!(ids(&batches).len(), 5);
+}
+
+#[tokio::test]
+async fn test_unknown_reader_feature_fails_clearly() {
+    let temp_dir = TempDir::new().unwrap();
+    let path = copy_fixture(&temp_dir);
+    std::fs::write(
+        path.join("_delta_log").join("00000000000000000004.json"),
+        concat!(
+            r#"{"commitInfo":{"timestamp":1700000240000,"operation":"SET TBLPROPERTIES"}}"#,
+            "\n",
+            r#"{"protocol":{"minReaderVersion":3,"minWriterVersion":7,"readerFeatures":["columnMapping","futureFeature"],"writerFeatures":["columnMapping","futureFeature"]}}"#,
+            "\n"
+        ),
+    )
+    .unwrap();
+
+    let result = DatabaseOps::open(&path).await;
+    assert!(
+        matches!(&result, Err(Error::UnsupportedDeltaFeature(f)) if f == "futureFeature"),
+        "{:?}",
+        result.err()
+    );
+}
+
+#[tokio::test]
+async fn test_history_lists_versions_folded_into_checkpoint() {
+    let temp_dir = TempDir::new().unwrap();
+    let db = DatabaseOps::open(copy_fixture(&temp_dir)).await.unwrap();
+
+    let history = db.history(10).await.unwrap();
+    let versions: Vec<i64> = history.iter().map(|c| c.version).collect();
+    assert_eq!(versions, vec![3, 2, 1, 0]);
+
+    assert_eq!(history[0].operation.as_deref(), Some("DELETE"));
+    assert_eq!(
+        history[0].operation_parameters["predicate"],
+        r#"["(id#412 = 5)"]"#
+    );
+    assert_eq!(history[1].operation.as_deref(), Some("WRITE"));
+    assert_eq!(history[1].operation_parameters["mode"], "Append");
+
+    // Commits 0 and 1 were cleaned up; only the checkpoint vouches for them
+    assert!(history[2].checkpointed && history[3].checkpointed);
+    assert_eq!(history[2].operation, None);
+}
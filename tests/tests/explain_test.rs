@@ -0,0 +1,122 @@
+// Explain Integration Tests
+// Tests EXPLAIN plans and EXPLAIN ANALYZE runtime statistics
+
+use arrow::array::{ArrayRef, Int32Array, StringArray};
+use arrow::datatypes::{DataType, Field, Schema};
+use arrow::record_batch::RecordBatch;
+use fsdb::DatabaseOps;
+use std::sync::Arc;
+use tempfile::TempDir;
+
+/// Two files, with ids 1-5 and 101-105
+async fn create_db(temp_dir: &TempDir) -> DatabaseOps {
+    let schema = Arc::new(Schema::new(vec![
+        Field::new("id", DataType::Int32, false),
+        Field::new("name", DataType::Utf8, false),
+    ]));
+    let db = DatabaseOps::create(temp_dir.path().join("db"), schema.clone())
+        .await
+        .unwrap();
+    for start in [1, 101] {
+        let ids: Vec<i32> = (start..start + 5).collect();
+        let names: Vec<String> = ids.iter().map(|id| format!("user_{}", id)).collect();
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![
+                Arc::new(Int32Array::from(ids)) as ArrayRef,
+                Arc::new(StringArray::from(names)) as ArrayRef,
+            ],
+        )
+        .unwrap();
+        db.insert(batch).await.unwrap();
+    }
+    db
+}
+
+async fn result_rows(db: &DatabaseOps, sql: &str) -> usize {
+    let batches = db.query(sql).await.unwrap();
+    batches.iter().map(|b| b.num_rows()).sum()
+}
+
+#[tokio::test]
+async fn test_explain_does_not_execute() {
+    let temp_dir = TempDir::new().unwrap();
+    let db = create_db(&temp_dir).await;
+
+    let plan = db
+        .explain("SELECT * FROM data WHERE id > 100")
+        .await
+        .unwrap();
+    assert!(plan.runtime.is_none());
+    assert!(!plan.plan.is_empty());
+    assert_eq!(plan.scan.total_files, 2);
+    assert_eq!(plan.scan.files_to_read, 1);
+    assert_eq!(plan.scan.files_skipped, 1);
+}
+
+#[tokio::test]
+async fn test_explain_analyze_reports_scanned_rows() {
+    let temp_dir = TempDir::new().unwrap();
+    let db = create_db(&temp_dir).await;
+
+    let sql = "SELECT * FROM data";
+    let plan = db.explain_analyze(sql).await.unwrap();
+    let runtime = plan.runtime.as_ref().unwrap();
+    assert!(runtime.error.is_none());
+
+    // A full scan reads every row it returns, and nothing else
+    let rows = result_rows(&db, sql).await;
+    assert_eq!(rows, 10);
+    assert_eq!(runtime.rows_scanned, rows);
+    assert_eq!(runtime.output_rows, rows);
+    assert_eq!(runtime.files_opened, 2);
+
+    assert_eq!(runtime.operators[0].depth, 0);
+    assert!(
+        runtime
+            .operators
+            .iter()
+            .any(|op| op.output_rows == Some(rows))
+    );
+    assert!(plan.to_string().contains("rows scanned"));
+}
+
+#[tokio::test]
+async fn test_explain_analyze_with_data_skipping() {
+    let temp_dir = TempDir::new().unwrap();
+    let db = create_db(&temp_dir).await;
+
+    let sql = "SELECT name FROM data WHERE id > 102";
+    let plan = db.explain_analyze(sql).await.unwrap();
+    let runtime = plan.runtime.unwrap();
+
+    assert_eq!(plan.scan.files_skipped, 1);
+    assert_eq!(runtime.output_rows, result_rows(&db, sql).await);
+    assert_eq!(runtime.output_rows, 3);
+    assert!(runtime.rows_scanned >= runtime.output_rows);
+    assert!(runtime.files_opened <= 1);
+}
+
+#[tokio::test]
+async fn test_failed_execution_keeps_partial_analysis() {
+    let temp_dir = TempDir::new().unwrap();
+    let db = create_db(&temp_dir).await;
+
+    // Plans fine, but no name is a number
+    let plan = db
+        .explain_analyze("SELECT CAST(name AS INT) FROM data")
+        .await
+        .unwrap();
+    let runtime = plan.runtime.as_ref().unwrap();
+    assert!(runtime.error.is_some());
+    assert_eq!(runtime.output_rows, 0);
+    assert!(!runtime.operators.is_empty());
+    assert!(plan.to_string().contains("Failed"));
+
+    // A query that can't be planned is still an error
+    assert!(
+        db.explain_analyze("SELECT missing FROM data")
+            .await
+            .is_err()
+    );
+}
@@ -1,8 +1,11 @@
 use arrow::array::{Int32Array, Int64Array, StringArray};
 use arrow::datatypes::{DataType, Field, Schema as ArrowSchema};
 use arrow::record_batch::RecordBatch;
+use fsdb::Error;
 use fsdb::database_ops::DatabaseOps;
+use fsdb::lock_manager::{LockManagerConfig, LockMode, VictimPolicy};
 use std::sync::Arc;
+use std::time::Duration;
 use tempfile::TempDir;
 
 /// Test basic explicit transaction: begin -> insert -> commit
@@ -366,3 +369,77 @@ async fn test_transaction_with_delete() {
         .unwrap();
     assert_eq!(count_array.value(0), 2, "Should have 2 active rows");
 }
+
+/// Test two transactions locking in opposite order: one is aborted, the other commits
+#[tokio::test]
+async fn test_transaction_deadlock_aborts_youngest() {
+    let temp_dir = TempDir::new().unwrap();
+    let db_path = temp_dir.path().join("test_txn_deadlock");
+
+    let schema = Arc::new(ArrowSchema::new(vec![
+        Field::new("id", DataType::Int32, false),
+        Field::new("name", DataType::Utf8, false),
+    ]));
+
+    let db = Arc::new(
+        DatabaseOps::create(db_path.clone(), schema.clone())
+            .await
+            .unwrap()
+            .with_lock_manager_config(LockManagerConfig {
+                detection_threshold: Duration::from_millis(20),
+                victim_policy: VictimPolicy::Youngest,
+            }),
+    );
+
+    let older = db.begin_transaction().await.unwrap();
+    let younger = db.begin_transaction().await.unwrap();
+    assert!(younger.id() > older.id());
+
+    older.lock("row:1", LockMode::Exclusive).await.unwrap();
+    younger.lock("row:2", LockMode::Exclusive).await.unwrap();
+
+    let batch = |id: i32, name: &str| {
+        RecordBatch::try_new(
+            schema.clone(),
+            vec![
+                Arc::new(Int32Array::from(vec![id])),
+                Arc::new(StringArray::from(vec![name])),
+            ],
+        )
+        .unwrap()
+    };
+    older.insert(batch(1, "older")).await.unwrap();
+    younger.insert(batch(2, "younger")).await.unwrap();
+
+    // Each waits for the other's lock; without detection this would hang
+    let (older_result, younger_result) = tokio::time::timeout(Duration::from_secs(5), async {
+        tokio::join!(
+            older.lock("row:2", LockMode::Exclusive),
+            younger.lock("row:1", LockMode::Exclusive)
+        )
+    })
+    .await
+    .expect("deadlock was not broken");
+
+    older_result.expect("older transaction should get the lock");
+    match younger_result {
+        Err(Error::DeadlockDetected { victim, cycle }) => {
+            assert_eq!(victim, younger.id());
+            assert!(cycle.contains(&older.id()));
+        }
+        other => panic!("expected DeadlockDetected, got {:?}", other),
+    }
+
+    // The victim is aborted and its writes are discarded
+    assert!(younger.commit().await.is_err());
+    older.commit().await.unwrap();
+
+    let results = db.query("SELECT name FROM data").await.unwrap();
+    let names = results[0]
+        .column(0)
+        .as_any()
+        .downcast_ref::<StringArray>()
+        .unwrap();
+    assert_eq!(results[0].num_rows(), 1);
+    assert_eq!(names.value(0), "older");
+}
@@ -0,0 +1,212 @@
+// Export Integration Tests
+// Tests streaming a table out as CSV, NDJSON and Parquet and loading each back
+// into a fresh table, exporting chosen columns and rows, and that a slow
+// reader holds the export back rather than the table being buffered
+
+use arrow::array::{ArrayRef, Float64Array, Int64Array, RecordBatch, StringArray};
+use arrow::datatypes::{DataType, Field, Schema, SchemaRef};
+use arrow::util::pretty::pretty_format_batches;
+use fsdb::DatabaseOps;
+use fsdb::bulk_load::LoadOptions;
+use fsdb::export::{ExportFormat, ExportOptions};
+use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+use tempfile::TempDir;
+use tokio::io::AsyncReadExt;
+
+fn create_schema() -> SchemaRef {
+    Arc::new(Schema::new(vec![
+        Field::new("id", DataType::Int64, false),
+        Field::new("name", DataType::Utf8, true),
+        Field::new("amount", DataType::Float64, true),
+    ]))
+}
+
+fn batch(ids: &[i64], names: &[Option<&str>], amounts: &[Option<f64>]) -> RecordBatch {
+    RecordBatch::try_new(
+        create_schema(),
+        vec![
+            Arc::new(Int64Array::from(ids.to_vec())) as ArrayRef,
+            Arc::new(StringArray::from(names.to_vec())) as ArrayRef,
+            Arc::new(Float64Array::from(amounts.to_vec())) as ArrayRef,
+        ],
+    )
+    .unwrap()
+}
+
+/// Rows 1-5 over two files, with NULLs and names needing CSV quoting
+async fn create_db(path: &Path) -> DatabaseOps {
+    let db = DatabaseOps::create(path, create_schema()).await.unwrap();
+    db.insert(batch(
+        &[1, 2, 3],
+        &[Some("alice"), Some("smith, bob"), None],
+        &[Some(10.5), None, Some(-3.0)],
+    ))
+    .await
+    .unwrap();
+    db.insert(batch(
+        &[4, 5],
+        &[Some("say \"hi\""), Some("dave")],
+        &[Some(0.25), Some(1e6)],
+    ))
+    .await
+    .unwrap();
+    db
+}
+
+async fn contents(db: &DatabaseOps) -> String {
+    let batches = db.query("SELECT * FROM data ORDER BY id").await.unwrap();
+    pretty_format_batches(&batches).unwrap().to_string()
+}
+
+#[tokio::test]
+async fn test_csv_round_trip() {
+    let temp_dir = TempDir::new().unwrap();
+    let db = create_db(&temp_dir.path().join("db")).await;
+
+    let path = temp_dir.path().join("export.csv");
+    let mut file = tokio::fs::File::create(&path).await.unwrap();
+    let report = db
+        .export(&mut file, ExportFormat::Csv, ExportOptions::default())
+        .await
+        .unwrap();
+    assert_eq!(report.rows, 5);
+    assert_eq!(report.bytes, std::fs::metadata(&path).unwrap().len());
+    let csv = std::fs::read_to_string(&path).unwrap();
+    assert!(csv.starts_with("id,name,amount\n"));
+
+    let copy = DatabaseOps::create(temp_dir.path().join("copy"), create_schema())
+        .await
+        .unwrap();
+    copy.load_from_csv(&path, LoadOptions::default())
+        .await
+        .unwrap();
+    assert_eq!(contents(&copy).await, contents(&db).await);
+}
+
+#[tokio::test]
+async fn test_ndjson_round_trip() {
+    let temp_dir = TempDir::new().unwrap();
+    let db = create_db(&temp_dir.path().join("db")).await;
+
+    let mut out = Vec::new();
+    let report = db
+        .export(&mut out, ExportFormat::NdJson, ExportOptions::default())
+        .await
+        .unwrap();
+    assert_eq!(report.rows, 5);
+    assert_eq!(out.iter().filter(|b| **b == b'\n').count(), 5);
+
+    let copy = DatabaseOps::create(temp_dir.path().join("copy"), create_schema())
+        .await
+        .unwrap();
+    let reader = arrow::json::ReaderBuilder::new(create_schema())
+        .build(std::io::Cursor::new(out))
+        .unwrap();
+    for batch in reader {
+        copy.insert(batch.unwrap()).await.unwrap();
+    }
+    assert_eq!(contents(&copy).await, contents(&db).await);
+}
+
+#[tokio::test]
+async fn test_parquet_round_trip() {
+    let temp_dir = TempDir::new().unwrap();
+    let db = create_db(&temp_dir.path().join("db")).await;
+
+    let path = temp_dir.path().join("export.parquet");
+    let mut file = tokio::fs::File::create(&path).await.unwrap();
+    let options = ExportOptions::default().with_row_group_rows(2);
+    let report = db
+        .export(&mut file, ExportFormat::Parquet, options)
+        .await
+        .unwrap();
+    assert_eq!(report.rows, 5);
+
+    // Row groups were written as they filled
+    let reader =
+        ParquetRecordBatchReaderBuilder::try_new(std::fs::File::open(&path).unwrap()).unwrap();
+    assert_eq!(reader.metadata().file_metadata().num_rows(), 5);
+    assert!(reader.metadata().num_row_groups() >= 3);
+
+    let copy = DatabaseOps::create(temp_dir.path().join("copy"), create_schema())
+        .await
+        .unwrap();
+    copy.load_from_parquet(&path).await.unwrap();
+    assert_eq!(contents(&copy).await, contents(&db).await);
+}
+
+#[tokio::test]
+async fn test_columns_and_filter() {
+    let temp_dir = TempDir::new().unwrap();
+    let db = create_db(&temp_dir.path().join("db")).await;
+
+    let mut out = Vec::new();
+    let options = ExportOptions::default()
+        .with_columns(["name", "id"])
+        .with_filter("amount > 0");
+    let report = db
+        .export(&mut out, ExportFormat::Csv, options)
+        .await
+        .unwrap();
+    assert_eq!(report.rows, 3);
+    let csv = String::from_utf8(out).unwrap();
+    let mut lines: Vec<&str> = csv.lines().collect();
+    assert_eq!(lines.remove(0), "name,id");
+    lines.sort();
+    assert_eq!(lines, vec!["\"say \"\"hi\"\"\",4", "alice,1", "dave,5"]);
+
+    // Unknown columns and bad predicates fail before anything is written
+    let mut out = Vec::new();
+    let options = ExportOptions::default().with_columns(["missing"]);
+    assert!(
+        db.export(&mut out, ExportFormat::Csv, options)
+            .await
+            .is_err()
+    );
+    let options = ExportOptions::default().with_filter("amount >");
+    assert!(
+        db.export(&mut out, ExportFormat::Csv, options)
+            .await
+            .is_err()
+    );
+    assert!(out.is_empty());
+}
+
+#[tokio::test]
+async fn test_slow_reader_holds_export_back() {
+    let temp_dir = TempDir::new().unwrap();
+    let db = Arc::new(
+        DatabaseOps::create(temp_dir.path().join("db"), create_schema())
+            .await
+            .unwrap(),
+    );
+    let ids: Vec<i64> = (0..20_000).collect();
+    let names: Vec<Option<&str>> = ids.iter().map(|_| Some("row")).collect();
+    let amounts: Vec<Option<f64>> = ids.iter().map(|id| Some(*id as f64)).collect();
+    db.insert(batch(&ids, &names, &amounts)).await.unwrap();
+
+    // A pipe holding 4 KiB, far less than the export
+    let (mut writer, mut reader) = tokio::io::duplex(4096);
+    let exporter = {
+        let db = db.clone();
+        tokio::spawn(async move {
+            db.export(&mut writer, ExportFormat::NdJson, ExportOptions::default())
+                .await
+                .unwrap()
+        })
+    };
+
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    assert!(!exporter.is_finished());
+
+    // The export ends, closing the pipe, only once everything is read
+    let mut out = Vec::new();
+    reader.read_to_end(&mut out).await.unwrap();
+    let report = exporter.await.unwrap();
+    assert_eq!(report.rows, 20_000);
+    assert_eq!(report.bytes, out.len() as u64);
+    assert_eq!(out.iter().filter(|b| **b == b'\n').count(), 20_000);
+}
@@ -0,0 +1,110 @@
+// File Naming Integration Tests
+// Tests that inserts name their Parquet files after the configured template
+
+use arrow::array::{ArrayRef, Int64Array, StringArray};
+use arrow::datatypes::{DataType, Field, Schema};
+use arrow::record_batch::RecordBatch;
+use fsdb::DatabaseOps;
+use fsdb::delta_lake::{FileNaming, get_file_statistics};
+use std::collections::HashSet;
+use std::sync::Arc;
+use tempfile::TempDir;
+
+fn batch(rows: &[(&str, i64)]) -> RecordBatch {
+    let schema = Arc::new(Schema::new(vec![
+        Field::new("region", DataType::Utf8, false),
+        Field::new("amount", DataType::Int64, false),
+    ]));
+    RecordBatch::try_new(
+        schema,
+        vec![
+            Arc::new(StringArray::from_iter_values(rows.iter().map(|r| r.0))) as ArrayRef,
+            Arc::new(Int64Array::from_iter_values(rows.iter().map(|r| r.1))),
+        ],
+    )
+    .unwrap()
+}
+
+fn file_names(db: &DatabaseOps) -> Vec<String> {
+    let mut names: Vec<String> = get_file_statistics(db.base_path())
+        .unwrap()
+        .into_iter()
+        .map(|file| file.path)
+        .collect();
+    names.sort();
+    names
+}
+
+#[tokio::test]
+async fn test_inserted_files_follow_template() {
+    let temp_dir = TempDir::new().unwrap();
+    let schema = batch(&[]).schema();
+    let db = DatabaseOps::create_partitioned(temp_dir.path().join("orders"), schema, &["region"])
+        .await
+        .unwrap()
+        .with_file_naming(FileNaming::new("orders-{version}-{partition}").unwrap());
+
+    let commit = db
+        .insert(batch(&[("east", 1), ("west", 2), ("east", 3)]))
+        .await
+        .unwrap();
+    let version = format!("{:08}", commit.version);
+    assert_eq!(
+        file_names(&db),
+        vec![
+            format!("region=east/orders-{}-region-east.parquet", version),
+            format!("region=west/orders-{}-region-west.parquet", version),
+        ]
+    );
+    for name in file_names(&db) {
+        assert!(db.base_path().join(name).exists());
+    }
+
+    let results = db
+        .query("SELECT SUM(amount) AS total FROM data")
+        .await
+        .unwrap();
+    let total = results[0]
+        .column(0)
+        .as_any()
+        .downcast_ref::<Int64Array>()
+        .unwrap()
+        .value(0);
+    assert_eq!(total, 6);
+}
+
+#[tokio::test]
+async fn test_colliding_template_keeps_names_unique() {
+    let temp_dir = TempDir::new().unwrap();
+    let schema = batch(&[]).schema();
+    let db = DatabaseOps::create(temp_dir.path().join("orders"), schema)
+        .await
+        .unwrap()
+        .with_file_naming(FileNaming::new("orders").unwrap());
+
+    for amount in 1..=3 {
+        db.insert(batch(&[("east", amount)])).await.unwrap();
+    }
+    let names = file_names(&db);
+    assert_eq!(
+        names,
+        vec!["orders-1.parquet", "orders-2.parquet", "orders.parquet"]
+    );
+    assert_eq!(names.iter().collect::<HashSet<_>>().len(), 3);
+
+    let results = db.query("SELECT COUNT(*) FROM data").await.unwrap();
+    let count = results[0]
+        .column(0)
+        .as_any()
+        .downcast_ref::<Int64Array>()
+        .unwrap()
+        .value(0);
+    assert_eq!(count, 3);
+}
+
+#[tokio::test]
+async fn test_invalid_template_is_rejected() {
+    let err = FileNaming::new("orders/{version}").unwrap_err();
+    assert!(err.to_string().contains("isn't allowed"), "{}", err);
+    assert!(FileNaming::new("{sequence}").is_err());
+}
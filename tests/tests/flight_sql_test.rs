@@ -0,0 +1,196 @@
+// Flight SQL Integration Tests
+// Tests querying through the Flight SQL client: statements, prepared
+// statements and table listings, and logging in with the handshake, with
+// permissions from the user's roles
+
+use arrow::array::{Array, ArrayRef, Int32Array, Int64Array, RecordBatch, StringArray};
+use arrow::datatypes::{DataType, Field, Schema, SchemaRef};
+use arrow_flight::FlightInfo;
+use arrow_flight::sql::CommandGetTables;
+use arrow_flight::sql::client::FlightSqlServiceClient;
+use fsdb::DatabaseOps;
+use fsdb::flight::{FlightSqlConfig, FlightSqlServer};
+use futures::TryStreamExt;
+use std::net::SocketAddr;
+use std::path::Path;
+use std::sync::Arc;
+use tempfile::TempDir;
+use tonic::transport::Channel;
+
+fn create_schema() -> SchemaRef {
+    Arc::new(Schema::new(vec![
+        Field::new("id", DataType::Int32, false),
+        Field::new("name", DataType::Utf8, true),
+    ]))
+}
+
+fn batch(ids: Vec<i32>, names: Vec<&str>) -> RecordBatch {
+    RecordBatch::try_new(
+        create_schema(),
+        vec![
+            Arc::new(Int32Array::from(ids)) as ArrayRef,
+            Arc::new(StringArray::from(names)) as ArrayRef,
+        ],
+    )
+    .unwrap()
+}
+
+async fn start(db: DatabaseOps) -> FlightSqlServer {
+    let config = FlightSqlConfig {
+        addr: SocketAddr::from(([127, 0, 0, 1], 0)),
+        ..FlightSqlConfig::default()
+    };
+    FlightSqlServer::with_config(Arc::new(db), config)
+        .await
+        .unwrap()
+}
+
+async fn connect(server: &FlightSqlServer) -> FlightSqlServiceClient<Channel> {
+    let channel = Channel::from_shared(format!("http://{}", server.local_addr()))
+        .unwrap()
+        .connect()
+        .await
+        .unwrap();
+    FlightSqlServiceClient::new(channel)
+}
+
+/// Batches of every endpoint of `info`
+async fn fetch(client: &mut FlightSqlServiceClient<Channel>, info: FlightInfo) -> Vec<RecordBatch> {
+    let mut batches = Vec::new();
+    for endpoint in info.endpoint {
+        let ticket = endpoint.ticket.unwrap();
+        let stream = client.do_get(ticket).await.unwrap();
+        batches.extend(stream.try_collect::<Vec<_>>().await.unwrap());
+    }
+    batches
+}
+
+fn ids(batches: &[RecordBatch]) -> Vec<i32> {
+    batches
+        .iter()
+        .flat_map(|batch| {
+            let ids = batch
+                .column(0)
+                .as_any()
+                .downcast_ref::<Int32Array>()
+                .unwrap();
+            ids.values().to_vec()
+        })
+        .collect()
+}
+
+#[tokio::test]
+async fn test_statements_and_listings() {
+    let temp_dir = TempDir::new().unwrap();
+    let db = DatabaseOps::create(temp_dir.path().join("db"), create_schema())
+        .await
+        .unwrap();
+    db.insert(batch(vec![1, 2, 3], vec!["ada", "grace", "barbara"]))
+        .await
+        .unwrap();
+    db.create_table("people", create_schema()).await.unwrap();
+    let server = start(db).await;
+    let mut client = connect(&server).await;
+
+    let info = client
+        .execute(
+            "SELECT id, name FROM data WHERE id > 1 ORDER BY id".to_string(),
+            None,
+        )
+        .await
+        .unwrap();
+    let batches = fetch(&mut client, info).await;
+    assert_eq!(ids(&batches), vec![2, 3]);
+    assert_eq!(batches[0].schema().field(1).name(), "name");
+
+    // Prepared statements report their schema before running
+    let mut prepared = client
+        .prepare("SELECT id FROM data ORDER BY id".to_string(), None)
+        .await
+        .unwrap();
+    assert_eq!(prepared.dataset_schema().unwrap().fields().len(), 1);
+    let info = prepared.execute().await.unwrap();
+    assert_eq!(ids(&fetch(&mut client, info).await), vec![1, 2, 3]);
+    prepared.close().await.unwrap();
+
+    // An empty result still has its columns
+    let info = client
+        .execute("SELECT id FROM data WHERE id > 10".to_string(), None)
+        .await
+        .unwrap();
+    let batches = fetch(&mut client, info).await;
+    assert!(ids(&batches).is_empty());
+
+    let info = client
+        .get_tables(CommandGetTables {
+            include_schema: false,
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+    let mut tables = Vec::new();
+    for batch in fetch(&mut client, info).await {
+        let names = batch.column_by_name("table_name").unwrap();
+        let names = names.as_any().downcast_ref::<StringArray>().unwrap();
+        tables.extend(names.iter().flatten().map(str::to_string));
+    }
+    tables.sort();
+    assert_eq!(tables, vec!["data", "people"]);
+
+    // Invalid SQL fails when planned, before any ticket is issued
+    let err = client
+        .execute("SELEC * FROM data".to_string(), None)
+        .await
+        .unwrap_err();
+    assert!(err.to_string().contains("InvalidArgument"), "{}", err);
+
+    server.shutdown().await.unwrap();
+}
+
+async fn create_auth_db(path: &Path) -> DatabaseOps {
+    let db = DatabaseOps::create_with_auth(path, create_schema(), true)
+        .await
+        .unwrap();
+    db.insert(batch(vec![1, 2], vec!["ada", "grace"]))
+        .await
+        .unwrap();
+    db.create_user("alice", "secret", &["read"]).await.unwrap();
+    db.create_user("mallory", "secret", &[]).await.unwrap();
+    db
+}
+
+#[tokio::test]
+async fn test_handshake_authentication() {
+    let temp_dir = TempDir::new().unwrap();
+    let db = create_auth_db(&temp_dir.path().join("db")).await;
+    let server = start(db).await;
+    let sql = || "SELECT COUNT(*) AS n FROM data".to_string();
+
+    // No or wrong credentials
+    let mut client = connect(&server).await;
+    let err = client.execute(sql(), None).await.unwrap_err();
+    assert!(err.to_string().contains("Unauthenticated"), "{}", err);
+    assert!(client.handshake("alice", "wrong").await.is_err());
+
+    // An unknown token is refused rather than treated as anonymous
+    client.set_token("not-a-token".to_string());
+    let err = client.execute(sql(), None).await.unwrap_err();
+    assert!(err.to_string().contains("Unauthenticated"), "{}", err);
+
+    // The handshake's token is sent with later calls
+    let mut client = connect(&server).await;
+    client.handshake("alice", "secret").await.unwrap();
+    let info = client.execute(sql(), None).await.unwrap();
+    let batches = fetch(&mut client, info).await;
+    let counts = batches[0].column(0);
+    let counts = counts.as_any().downcast_ref::<Int64Array>().unwrap();
+    assert_eq!(counts.value(0), 2);
+
+    // Users without read permission can log in but not query
+    let mut client = connect(&server).await;
+    client.handshake("mallory", "secret").await.unwrap();
+    let err = client.execute(sql(), None).await.unwrap_err();
+    assert!(err.to_string().contains("PermissionDenied"), "{}", err);
+
+    server.shutdown().await.unwrap();
+}
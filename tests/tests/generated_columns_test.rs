@@ -0,0 +1,241 @@
+// Generated Column Integration Tests
+// Tests columns computed from other columns on insert and update
+
+use arrow::array::{ArrayRef, Int32Array, RecordBatch, StringArray};
+use arrow::datatypes::{DataType, Field, Schema, SchemaRef};
+use fsdb::DatabaseOps;
+use fsdb::metadata::with_generated;
+use fsdb::query::Value;
+use std::sync::Arc;
+use tempfile::TempDir;
+
+fn create_schema() -> SchemaRef {
+    Arc::new(Schema::new(vec![
+        Field::new("id", DataType::Int32, false),
+        Field::new("first", DataType::Utf8, false),
+        Field::new("last", DataType::Utf8, false),
+        with_generated(
+            Field::new("full_name", DataType::Utf8, true),
+            "first || ' ' || last",
+        ),
+        // References another generated column
+        with_generated(
+            Field::new("name_length", DataType::Int32, true),
+            "character_length(full_name)",
+        ),
+    ]))
+}
+
+fn people(rows: &[(i32, &str, &str)]) -> RecordBatch {
+    let schema = Arc::new(Schema::new(vec![
+        Field::new("id", DataType::Int32, false),
+        Field::new("first", DataType::Utf8, false),
+        Field::new("last", DataType::Utf8, false),
+    ]));
+    RecordBatch::try_new(
+        schema,
+        vec![
+            Arc::new(Int32Array::from(
+                rows.iter().map(|r| r.0).collect::<Vec<_>>(),
+            )) as ArrayRef,
+            Arc::new(StringArray::from(
+                rows.iter().map(|r| r.1).collect::<Vec<_>>(),
+            )) as ArrayRef,
+            Arc::new(StringArray::from(
+                rows.iter().map(|r| r.2).collect::<Vec<_>>(),
+            )) as ArrayRef,
+        ],
+    )
+    .unwrap()
+}
+
+async fn create_db(temp_dir: &TempDir) -> DatabaseOps {
+    let db = DatabaseOps::create(temp_dir.path().join("db"), create_schema())
+        .await
+        .unwrap();
+    db.insert(people(&[(1, "Ada", "Lovelace"), (2, "Alan", "Turing")]))
+        .await
+        .unwrap();
+    db
+}
+
+async fn generated(db: &DatabaseOps, id: i32) -> (Value, Value) {
+    let result = db
+        .query_rows(&format!(
+            "SELECT full_name, name_length FROM data WHERE id = {}",
+            id
+        ))
+        .await
+        .unwrap();
+    (
+        result.get(0, "full_name").unwrap().clone(),
+        result.get(0, "name_length").unwrap().clone(),
+    )
+}
+
+#[tokio::test]
+async fn test_insert_computes_generated_columns() {
+    let temp_dir = TempDir::new().unwrap();
+    let db = create_db(&temp_dir).await;
+
+    assert_eq!(
+        generated(&db, 1).await,
+        (Value::String("Ada Lovelace".into()), Value::Int(12))
+    );
+    assert_eq!(
+        generated(&db, 2).await,
+        (Value::String("Alan Turing".into()), Value::Int(11))
+    );
+
+    // Usable in predicates like a stored column
+    let result = db
+        .query_rows("SELECT id FROM data WHERE full_name = 'Alan Turing'")
+        .await
+        .unwrap();
+    assert_eq!(result.len(), 1);
+    assert_eq!(result.get(0, "id"), Some(&Value::Int(2)));
+
+    // The generating expressions survive a reopen
+    let reopened = DatabaseOps::open(temp_dir.path().join("db")).await.unwrap();
+    reopened
+        .insert(people(&[(3, "Grace", "Hopper")]))
+        .await
+        .unwrap();
+    assert_eq!(
+        generated(&reopened, 3).await,
+        (Value::String("Grace Hopper".into()), Value::Int(12))
+    );
+}
+
+#[tokio::test]
+async fn test_generated_columns_have_file_statistics() {
+    let temp_dir = TempDir::new().unwrap();
+    let _db = create_db(&temp_dir).await;
+
+    let files = fsdb::delta_lake::get_file_statistics(&temp_dir.path().join("db")).unwrap();
+    assert_eq!(files.len(), 1);
+    assert_eq!(
+        files[0].min_values.get("full_name"),
+        Some(&serde_json::json!("Ada Lovelace"))
+    );
+    assert_eq!(
+        files[0].max_values.get("name_length"),
+        Some(&serde_json::json!(12))
+    );
+}
+
+#[tokio::test]
+async fn test_explicit_values_must_match() {
+    let temp_dir = TempDir::new().unwrap();
+    let db = create_db(&temp_dir).await;
+
+    let with_full_name = |full_name: &str| {
+        let batch = people(&[(3, "Grace", "Hopper")]);
+        let mut fields = batch.schema().fields().to_vec();
+        fields.push(Arc::new(Field::new("full_name", DataType::Utf8, true)));
+        let mut columns = batch.columns().to_vec();
+        columns.push(Arc::new(StringArray::from(vec![full_name])));
+        RecordBatch::try_new(Arc::new(Schema::new(fields)), columns).unwrap()
+    };
+
+    let err = db
+        .insert(with_full_name("Rear Admiral Hopper"))
+        .await
+        .unwrap_err();
+    assert!(err.to_string().contains("full_name"), "{}", err);
+
+    // The value it would have been anyway is accepted
+    db.insert(with_full_name("Grace Hopper")).await.unwrap();
+    assert_eq!(
+        generated(&db, 3).await,
+        (Value::String("Grace Hopper".into()), Value::Int(12))
+    );
+}
+
+#[tokio::test]
+async fn test_update_propagates_to_generated_columns() {
+    let temp_dir = TempDir::new().unwrap();
+    let db = create_db(&temp_dir).await;
+
+    let commit = db
+        .update_rows(&[("last", "'Byron'")], "id = 1")
+        .await
+        .unwrap();
+    assert_eq!(commit.rows_affected, 1);
+    assert_eq!(
+        generated(&db, 1).await,
+        (Value::String("Ada Byron".into()), Value::Int(9))
+    );
+    assert_eq!(
+        generated(&db, 2).await,
+        (Value::String("Alan Turing".into()), Value::Int(11))
+    );
+
+    // Assigned expressions see the old row, as in any UPDATE
+    db.update_rows(&[("first", "last"), ("last", "first")], "id = 2")
+        .await
+        .unwrap();
+    assert_eq!(
+        generated(&db, 2).await,
+        (Value::String("Turing Alan".into()), Value::Int(11))
+    );
+
+    // A generated column can't be assigned
+    let err = db
+        .update_rows(&[("full_name", "'Someone Else'")], "id = 1")
+        .await
+        .unwrap_err();
+    assert!(err.to_string().contains("generated"), "{}", err);
+}
+
+#[tokio::test]
+async fn test_merge_computes_generated_columns() {
+    let temp_dir = TempDir::new().unwrap();
+    let db = create_db(&temp_dir).await;
+
+    let metrics = db
+        .merge()
+        .await
+        .unwrap()
+        .with_source(
+            people(&[(1, "Ada", "Byron"), (3, "Grace", "Hopper")]),
+            "source",
+        )
+        .on("target.id = source.id")
+        .when_matched_update()
+        .set("last", "source.last")
+        .then()
+        .when_not_matched_insert()
+        .values_all()
+        .execute()
+        .await
+        .unwrap();
+    assert_eq!((metrics.rows_updated, metrics.rows_inserted), (1, 1));
+    assert_eq!(
+        generated(&db, 1).await,
+        (Value::String("Ada Byron".into()), Value::Int(9))
+    );
+    assert_eq!(
+        generated(&db, 2).await,
+        (Value::String("Alan Turing".into()), Value::Int(11))
+    );
+    assert_eq!(
+        generated(&db, 3).await,
+        (Value::String("Grace Hopper".into()), Value::Int(12))
+    );
+
+    // A generated column can't be set by a clause either
+    let err = db
+        .merge()
+        .await
+        .unwrap()
+        .with_source(people(&[(2, "Alan", "Kay")]), "source")
+        .on("target.id = source.id")
+        .when_matched_update()
+        .set("full_name", "'Someone Else'")
+        .then()
+        .execute()
+        .await
+        .unwrap_err();
+    assert!(err.to_string().contains("generated"), "{}", err);
+}
@@ -0,0 +1,111 @@
+// Health Probe Integration Tests
+// Tests DatabaseOps::health component states for load balancer readiness checks
+
+use arrow::array::{ArrayRef, Int32Array, RecordBatch};
+use arrow::datatypes::{DataType, Field, Schema, SchemaRef};
+use fsdb::DatabaseOps;
+use fsdb::health::HealthState;
+use std::os::unix::fs::PermissionsExt;
+use std::sync::Arc;
+use tempfile::TempDir;
+
+fn create_schema() -> SchemaRef {
+    Arc::new(Schema::new(vec![Field::new("id", DataType::Int32, false)]))
+}
+
+async fn create_db(temp_dir: &TempDir) -> DatabaseOps {
+    let schema = create_schema();
+    let db = DatabaseOps::create(temp_dir.path().join("health_db"), schema.clone())
+        .await
+        .unwrap();
+    let batch = RecordBatch::try_new(
+        schema,
+        vec![Arc::new(Int32Array::from(vec![1, 2, 3])) as ArrayRef],
+    )
+    .unwrap();
+    db.insert(batch).await.unwrap();
+    db
+}
+
+#[tokio::test]
+async fn test_health_all_components_healthy() {
+    let temp_dir = TempDir::new().unwrap();
+    let db = create_db(&temp_dir).await;
+
+    let health = db.health().await;
+
+    assert_eq!(health.status, HealthState::Healthy, "{:?}", health);
+    assert!(health.is_ready());
+    for name in ["storage", "delta_log", "local_disk", "write_buffer"] {
+        let component = health.component(name).expect(name);
+        assert_eq!(component.state, HealthState::Healthy, "{:?}", component);
+        assert!(component.detail.is_none());
+    }
+}
+
+#[tokio::test]
+async fn test_health_missing_storage_is_unhealthy() {
+    let temp_dir = TempDir::new().unwrap();
+    let db = create_db(&temp_dir).await;
+
+    // Storage path disappears underneath a running database
+    std::fs::remove_dir_all(temp_dir.path().join("health_db")).unwrap();
+
+    let health = db.health().await;
+
+    assert_eq!(health.status, HealthState::Unhealthy);
+    assert!(!health.is_ready());
+    assert_eq!(
+        health.component("storage").unwrap().state,
+        HealthState::Unhealthy
+    );
+    assert_eq!(
+        health.component("delta_log").unwrap().state,
+        HealthState::Unhealthy
+    );
+}
+
+#[tokio::test]
+async fn test_health_unreadable_delta_log_is_unhealthy() {
+    let temp_dir = TempDir::new().unwrap();
+    let db = create_db(&temp_dir).await;
+
+    std::fs::remove_dir_all(temp_dir.path().join("health_db").join("_delta_log")).unwrap();
+
+    let health = db.health().await;
+
+    assert_eq!(health.status, HealthState::Unhealthy);
+    assert_eq!(
+        health.component("storage").unwrap().state,
+        HealthState::Healthy
+    );
+    let delta_log = health.component("delta_log").unwrap();
+    assert_eq!(delta_log.state, HealthState::Unhealthy);
+    assert!(delta_log.detail.is_some());
+}
+
+#[tokio::test]
+async fn test_health_read_only_disk_is_degraded() {
+    // Root ignores directory permissions, so the write probe would still succeed
+    if unsafe { libc::geteuid() } == 0 {
+        eprintln!("Skipping read-only disk test when running as root");
+        return;
+    }
+
+    let temp_dir = TempDir::new().unwrap();
+    let db = create_db(&temp_dir).await;
+    let db_path = temp_dir.path().join("health_db");
+    std::fs::set_permissions(&db_path, std::fs::Permissions::from_mode(0o555)).unwrap();
+
+    let health = db.health().await;
+
+    std::fs::set_permissions(&db_path, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+    // Reads still work, so the database stays in rotation
+    assert_eq!(health.status, HealthState::Degraded, "{:?}", health);
+    assert!(health.is_ready());
+    assert_eq!(
+        health.component("local_disk").unwrap().state,
+        HealthState::Degraded
+    );
+}
@@ -0,0 +1,136 @@
+// Histogram Statistics Tests
+// Tests equi-depth histograms against actual selectivity on skewed data
+
+use arrow::array::{ArrayRef, Int32Array, Int64Array, RecordBatch, StringArray};
+use arrow::datatypes::{DataType, Field, Schema, SchemaRef};
+use fsdb::DatabaseOps;
+use std::sync::Arc;
+use tempfile::TempDir;
+
+fn create_schema() -> SchemaRef {
+    Arc::new(Schema::new(vec![
+        Field::new("id", DataType::Int32, false),
+        Field::new("value", DataType::Int64, true),
+        Field::new("label", DataType::Utf8, true),
+    ]))
+}
+
+/// 2,500 rows from `start`: 90% of values in 0..10, the rest spread up to 100,000
+fn skewed_batch(start: i32) -> RecordBatch {
+    let ids: Vec<i32> = (start..start + 2500).collect();
+    let values: Vec<i64> = (0..2500)
+        .map(|i| if i % 10 == 0 { 10 + i * 40 } else { i % 10 })
+        .collect();
+    let labels: Vec<String> = ids.iter().map(|id| format!("row_{}", id)).collect();
+    RecordBatch::try_new(
+        create_schema(),
+        vec![
+            Arc::new(Int32Array::from(ids)) as ArrayRef,
+            Arc::new(Int64Array::from(values)) as ArrayRef,
+            Arc::new(StringArray::from(labels)) as ArrayRef,
+        ],
+    )
+    .unwrap()
+}
+
+async fn actual_selectivity(db: &DatabaseOps, predicate: &str) -> f64 {
+    let count = |sql: String| async move {
+        let batches = db.query(&sql).await.unwrap();
+        batches[0]
+            .column(0)
+            .as_any()
+            .downcast_ref::<Int64Array>()
+            .unwrap()
+            .value(0) as f64
+    };
+    let matching = count(format!("SELECT COUNT(*) FROM data WHERE {}", predicate)).await;
+    let total = count("SELECT COUNT(*) FROM data".to_string()).await;
+    matching / total
+}
+
+async fn assert_estimates_within(db: &DatabaseOps, tolerance: f64) {
+    for predicate in [
+        "value < 100",
+        "value = 3",
+        "value >= 50000",
+        "value > 5 AND value <= 8",
+    ] {
+        let estimate = db.estimate_selectivity(predicate).await.unwrap();
+        let actual = actual_selectivity(db, predicate).await;
+        assert!(
+            (estimate - actual).abs() <= tolerance,
+            "{}: estimated {:.4}, actual {:.4}",
+            predicate,
+            estimate,
+            actual
+        );
+    }
+}
+
+async fn create_table(temp_dir: &TempDir) -> DatabaseOps {
+    let db = DatabaseOps::create(temp_dir.path().join("db"), create_schema())
+        .await
+        .unwrap();
+    for i in 0..4 {
+        db.insert(skewed_batch(i * 2500)).await.unwrap();
+    }
+    db
+}
+
+#[tokio::test]
+async fn test_histogram_estimates_match_skewed_data() {
+    let temp_dir = TempDir::new().unwrap();
+    let db = create_table(&temp_dir).await;
+
+    // Min/max statistics alone spread the values evenly up to 100,000
+    let actual = actual_selectivity(&db, "value < 100").await;
+    let uniform = db.estimate_selectivity("value < 100").await.unwrap();
+    assert!(actual > 0.85, "{}", actual);
+    assert!(uniform < 0.05, "{}", uniform);
+
+    db.enable_histograms(&["value"]).await.unwrap();
+    assert_estimates_within(&db, 0.02).await;
+
+    let histograms = db.get_histograms().await.unwrap().unwrap();
+    assert_eq!(histograms.columns["value"].row_count(), 10_000);
+}
+
+#[tokio::test]
+async fn test_histograms_follow_inserts_and_optimize() {
+    let temp_dir = TempDir::new().unwrap();
+    let db = create_table(&temp_dir).await;
+    db.enable_histograms(&["value"]).await.unwrap();
+
+    // Inserts are merged in approximately
+    for i in 4..8 {
+        db.insert(skewed_batch(i * 2500)).await.unwrap();
+    }
+    let histograms = db.get_histograms().await.unwrap().unwrap();
+    assert_eq!(histograms.columns["value"].row_count(), 20_000);
+    assert_estimates_within(&db, 0.05).await;
+
+    // OPTIMIZE rebuilds them from the table
+    db.optimize().await.unwrap();
+    assert_estimates_within(&db, 0.02).await;
+
+    // Queries record the estimate alongside data skipping statistics
+    db.query("SELECT * FROM data WHERE value >= 50000")
+        .await
+        .unwrap();
+    let stats = db.get_data_skipping_stats().await.unwrap();
+    assert!(stats.estimated_selectivity.unwrap() < 0.1);
+}
+
+#[tokio::test]
+async fn test_histograms_need_numeric_columns() {
+    let temp_dir = TempDir::new().unwrap();
+    let db = create_table(&temp_dir).await;
+
+    assert!(db.enable_histograms(&["label"]).await.is_err());
+    assert!(db.enable_histograms(&["missing"]).await.is_err());
+    assert!(db.get_histograms().await.unwrap().is_none());
+
+    db.enable_histograms(&["VALUE"]).await.unwrap();
+    db.disable_histograms().await.unwrap();
+    assert!(db.get_histograms().await.unwrap().is_none());
+}
@@ -0,0 +1,177 @@
+// Table History Integration Tests
+// Tests that history lists each committed version with its operation, user
+// and commit metadata
+
+use arrow::array::{Int32Array, StringArray};
+use arrow::datatypes::{DataType, Field, Schema, SchemaRef};
+use arrow::record_batch::RecordBatch;
+use fsdb::{DatabaseOps, Error};
+use std::collections::HashMap;
+use std::sync::Arc;
+use tempfile::TempDir;
+
+fn create_schema() -> SchemaRef {
+    Arc::new(Schema::new(vec![
+        Field::new("id", DataType::Int32, false),
+        Field::new("name", DataType::Utf8, false),
+    ]))
+}
+
+fn create_batch(schema: SchemaRef, ids: Vec<i32>) -> RecordBatch {
+    let names: Vec<String> = ids.iter().map(|id| format!("user_{}", id)).collect();
+    RecordBatch::try_new(
+        schema,
+        vec![
+            Arc::new(Int32Array::from(ids)),
+            Arc::new(StringArray::from(names)),
+        ],
+    )
+    .unwrap()
+}
+
+#[tokio::test]
+async fn test_history_lists_operations_newest_first() {
+    let temp_dir = TempDir::new().unwrap();
+    let db_path = temp_dir.path().join("db");
+    let schema = create_schema();
+    let db = DatabaseOps::create_with_auth(&db_path, schema.clone(), true)
+        .await
+        .unwrap();
+    db.create_user("alice", "secret", &["admin"]).await.unwrap();
+    let db = DatabaseOps::open_with_credentials(&db_path, Some(("alice", "secret")))
+        .await
+        .unwrap();
+
+    let inserted = db
+        .insert(create_batch(schema.clone(), vec![1, 2, 3]))
+        .await
+        .unwrap();
+    let updated = db
+        .update_rows(&[("name", "'renamed'")], "id = 1")
+        .await
+        .unwrap();
+    let deleted = db.delete_rows_where("id = 3").await.unwrap();
+    // Changes nothing, so adds no version
+    db.delete_rows_where("id > 100").await.unwrap();
+
+    let history = db.history(10).await.unwrap();
+    let versions: Vec<i64> = history.iter().map(|c| c.version).collect();
+    assert_eq!(
+        versions,
+        vec![deleted.version, updated.version, inserted.version, 0]
+    );
+    let operations: Vec<&str> = history
+        .iter()
+        .map(|c| c.operation.as_deref().unwrap_or(""))
+        .collect();
+    assert_eq!(
+        operations,
+        vec!["DELETE", "UPDATE", "WRITE", "CREATE TABLE"]
+    );
+
+    // Timestamps never go backwards, newest first
+    let timestamps: Vec<i64> = history.iter().map(|c| c.timestamp.unwrap()).collect();
+    assert!(timestamps.windows(2).all(|w| w[0] >= w[1]));
+
+    // Writes are attributed through the audit log
+    for commit in &history[..3] {
+        assert_eq!(commit.user.as_deref(), Some("alice"), "{:?}", commit);
+        assert!(!commit.checkpointed);
+    }
+    assert!(history[0].operation_parameters.contains_key("predicate"));
+
+    // The limit keeps the newest versions
+    let latest = db.history(2).await.unwrap();
+    assert_eq!(latest.len(), 2);
+    assert_eq!(latest[0].version, deleted.version);
+}
+
+#[tokio::test]
+async fn test_commit_metadata_round_trips_through_history() {
+    let temp_dir = TempDir::new().unwrap();
+    let schema = create_schema();
+    let db = Arc::new(
+        DatabaseOps::create(temp_dir.path().join("db"), schema.clone())
+            .await
+            .unwrap(),
+    );
+    let lineage = |pipeline: &str| {
+        HashMap::from([
+            ("pipeline_id".to_string(), pipeline.to_string()),
+            ("source_system".to_string(), "crm".to_string()),
+        ])
+    };
+
+    let inserted = db
+        .insert_with_metadata(
+            create_batch(schema.clone(), vec![1, 2, 3]),
+            lineage("load-7"),
+        )
+        .await
+        .unwrap();
+    let updated = db
+        .update_rows_with_metadata(&[("name", "'renamed'")], "id = 1", lineage("fix-2"))
+        .await
+        .unwrap();
+    // Metadata scoped around any write, here a delete
+    let deleted = db
+        .with_commit_metadata(lineage("purge-1"), db.delete_rows_where("id = 3"))
+        .await
+        .unwrap();
+    let txn = db.begin_transaction().await.unwrap();
+    txn.insert(create_batch(schema.clone(), vec![4]))
+        .await
+        .unwrap();
+    let committed = txn.commit_with_metadata(lineage("txn-9")).await.unwrap();
+    // Writes without metadata record none
+    let plain = db
+        .insert(create_batch(schema.clone(), vec![5]))
+        .await
+        .unwrap();
+
+    let history = db.history(10).await.unwrap();
+    let metadata_of = |version: i64| {
+        history
+            .iter()
+            .find(|c| c.version == version)
+            .unwrap()
+            .metadata
+            .clone()
+    };
+    for (version, pipeline) in [
+        (inserted.version, "load-7"),
+        (updated.version, "fix-2"),
+        (deleted.version, "purge-1"),
+        (committed.version, "txn-9"),
+    ] {
+        let metadata = metadata_of(version);
+        assert_eq!(metadata["pipeline_id"], pipeline);
+        assert_eq!(metadata["source_system"], "crm");
+    }
+    assert!(metadata_of(plain.version).is_empty());
+}
+
+#[tokio::test]
+async fn test_oversized_commit_metadata_is_rejected() {
+    let temp_dir = TempDir::new().unwrap();
+    let schema = create_schema();
+    let db = DatabaseOps::create(temp_dir.path().join("db"), schema.clone())
+        .await
+        .unwrap();
+
+    let metadata = HashMap::from([(
+        "payload".to_string(),
+        "x".repeat(fsdb::delta_lake::commit::MAX_COMMIT_METADATA_BYTES),
+    )]);
+    let result = db
+        .insert_with_metadata(create_batch(schema, vec![1]), metadata)
+        .await;
+    assert!(matches!(
+        result,
+        Err(Error::CommitMetadataTooLarge { limit, .. })
+            if limit == fsdb::delta_lake::commit::MAX_COMMIT_METADATA_BYTES
+    ));
+
+    // Nothing was written
+    assert_eq!(db.history(10).await.unwrap().len(), 1);
+}
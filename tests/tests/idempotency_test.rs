@@ -0,0 +1,133 @@
+// Idempotency Key Integration Tests
+// Tests that retried writes with the same key are applied once, also when
+// several handles on one table race with the same keys
+
+use arrow::array::{Int32Array, Int64Array, StringArray};
+use arrow::datatypes::{DataType, Field, Schema, SchemaRef};
+use arrow::record_batch::RecordBatch;
+use fsdb::DatabaseOps;
+use std::sync::Arc;
+use std::time::Duration;
+use tempfile::TempDir;
+
+fn create_schema() -> SchemaRef {
+    Arc::new(Schema::new(vec![
+        Field::new("id", DataType::Int32, false),
+        Field::new("name", DataType::Utf8, false),
+    ]))
+}
+
+fn create_batch(schema: SchemaRef, id: i32) -> RecordBatch {
+    RecordBatch::try_new(
+        schema,
+        vec![
+            Arc::new(Int32Array::from(vec![id])),
+            Arc::new(StringArray::from(vec![format!("user_{}", id)])),
+        ],
+    )
+    .unwrap()
+}
+
+async fn row_count(db: &DatabaseOps) -> i64 {
+    let results = db.query("SELECT COUNT(*) FROM data").await.unwrap();
+    results[0]
+        .column(0)
+        .as_any()
+        .downcast_ref::<Int64Array>()
+        .unwrap()
+        .value(0)
+}
+
+#[tokio::test]
+async fn test_retried_insert_is_applied_once() {
+    let temp_dir = TempDir::new().unwrap();
+    let schema = create_schema();
+    let db = DatabaseOps::create(temp_dir.path().join("db"), schema.clone())
+        .await
+        .unwrap();
+
+    let first = db
+        .insert_idempotent(create_batch(schema.clone(), 1), "load-1")
+        .await
+        .unwrap();
+    let retry = db
+        .insert_idempotent(create_batch(schema.clone(), 1), "load-1")
+        .await
+        .unwrap();
+
+    assert_eq!(retry, first, "A retry returns the original commit");
+    assert_eq!(row_count(&db).await, 1);
+
+    // A different key is a different write
+    db.insert_idempotent(create_batch(schema.clone(), 2), "load-2")
+        .await
+        .unwrap();
+    assert_eq!(row_count(&db).await, 2);
+
+    // Reusing a key for another kind of write is refused
+    assert!(
+        db.delete_rows_where_idempotent("id = 1", "load-1")
+            .await
+            .is_err()
+    );
+    assert_eq!(row_count(&db).await, 2);
+}
+
+#[tokio::test]
+async fn test_keys_survive_reopen_and_expire() {
+    let temp_dir = TempDir::new().unwrap();
+    let db_path = temp_dir.path().join("db");
+    let schema = create_schema();
+    let db = DatabaseOps::create(&db_path, schema.clone()).await.unwrap();
+    db.insert_idempotent(create_batch(schema.clone(), 1), "load-1")
+        .await
+        .unwrap();
+    drop(db);
+
+    // The ledger is on disk, so a restarted writer still dedups
+    let db = DatabaseOps::open(&db_path).await.unwrap();
+    db.insert_idempotent(create_batch(schema.clone(), 1), "load-1")
+        .await
+        .unwrap();
+    assert_eq!(row_count(&db).await, 1);
+
+    // Once the window has passed the key is forgotten
+    let db = db.with_idempotency_window(Duration::ZERO);
+    tokio::time::sleep(Duration::from_millis(5)).await;
+    db.insert_idempotent(create_batch(schema.clone(), 1), "load-1")
+        .await
+        .unwrap();
+    assert_eq!(row_count(&db).await, 2);
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn test_handles_on_one_table_share_keys() {
+    let temp_dir = TempDir::new().unwrap();
+    let db_path = temp_dir.path().join("db");
+    let schema = create_schema();
+    let a = Arc::new(DatabaseOps::create(&db_path, schema.clone()).await.unwrap());
+    let b = Arc::new(DatabaseOps::open(&db_path).await.unwrap());
+
+    // Both handles race to apply every key; each is applied once
+    let mut tasks = Vec::new();
+    for id in 0..8 {
+        for db in [a.clone(), b.clone()] {
+            let batch = create_batch(schema.clone(), id);
+            tasks.push(tokio::spawn(async move {
+                db.insert_idempotent(batch, &format!("load-{}", id)).await
+            }));
+        }
+    }
+    for task in tasks {
+        task.await.unwrap().unwrap();
+    }
+    assert_eq!(row_count(&a).await, 8);
+
+    // No handle lost another's entries
+    for id in 0..8 {
+        b.insert_idempotent(create_batch(schema.clone(), id), &format!("load-{}", id))
+            .await
+            .unwrap();
+    }
+    assert_eq!(row_count(&a).await, 8);
+}
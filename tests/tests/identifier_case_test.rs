@@ -0,0 +1,147 @@
+// Identifier Case Integration Tests
+// Tests case-insensitive resolution of unquoted table and column names
+
+use arrow::array::{ArrayRef, Int32Array, RecordBatch, StringArray};
+use arrow::datatypes::{DataType, Field, Schema, SchemaRef};
+use fsdb::query::IdentifierCase;
+use fsdb::{DatabaseOps, Error};
+use std::sync::Arc;
+use tempfile::TempDir;
+
+fn create_schema() -> SchemaRef {
+    Arc::new(Schema::new(vec![
+        Field::new("CustomerId", DataType::Int32, false),
+        Field::new("Region", DataType::Utf8, false),
+    ]))
+}
+
+fn create_batch(schema: SchemaRef) -> RecordBatch {
+    RecordBatch::try_new(
+        schema,
+        vec![
+            Arc::new(Int32Array::from(vec![1, 2, 3])) as ArrayRef,
+            Arc::new(StringArray::from(vec!["east", "west", "east"])) as ArrayRef,
+        ],
+    )
+    .unwrap()
+}
+
+fn row_count(batches: &[RecordBatch]) -> usize {
+    batches.iter().map(|b| b.num_rows()).sum()
+}
+
+#[tokio::test]
+async fn test_unquoted_identifiers_are_case_insensitive() {
+    let temp_dir = TempDir::new().unwrap();
+    let schema = create_schema();
+    let db = DatabaseOps::create(temp_dir.path().join("case_db"), schema.clone())
+        .await
+        .unwrap();
+    db.insert(create_batch(schema)).await.unwrap();
+
+    for sql in [
+        "SELECT customerid, region FROM data",
+        "SELECT CUSTOMERID, REGION FROM DATA",
+        "SELECT CustomerId, Region FROM Data WHERE customerID > 1",
+    ] {
+        let results = db.query(sql).await.unwrap();
+        assert_eq!(
+            row_count(&results),
+            if sql.contains("WHERE") { 2 } else { 3 }
+        );
+        assert_eq!(results[0].schema().field(0).name(), "CustomerId");
+    }
+
+    // GROUP BY / ORDER BY through both columns and aliases
+    let results = db
+        .query(
+            "SELECT REGION, COUNT(*) AS Total FROM data GROUP BY region ORDER BY total DESC, Region",
+        )
+        .await
+        .unwrap();
+    assert_eq!(row_count(&results), 2);
+    let regions = results[0]
+        .column(0)
+        .as_any()
+        .downcast_ref::<StringArray>()
+        .unwrap();
+    assert_eq!(regions.value(0), "east");
+}
+
+#[tokio::test]
+async fn test_quoted_identifiers_preserve_case() {
+    let temp_dir = TempDir::new().unwrap();
+    let schema = create_schema();
+    let db = DatabaseOps::create(temp_dir.path().join("case_db"), schema.clone())
+        .await
+        .unwrap();
+    db.insert(create_batch(schema)).await.unwrap();
+
+    let results = db.query("SELECT \"Region\" FROM data").await.unwrap();
+    assert_eq!(row_count(&results), 3);
+
+    assert!(
+        db.query("SELECT \"region\" FROM data").await.is_err(),
+        "Quoted identifiers must match exactly"
+    );
+}
+
+#[tokio::test]
+async fn test_columns_differing_by_case_are_ambiguous() {
+    let temp_dir = TempDir::new().unwrap();
+    let schema = Arc::new(Schema::new(vec![
+        Field::new("name", DataType::Utf8, false),
+        Field::new("Name", DataType::Utf8, false),
+    ]));
+    let db = DatabaseOps::create(temp_dir.path().join("case_db"), schema.clone())
+        .await
+        .unwrap();
+    db.insert(
+        RecordBatch::try_new(
+            schema,
+            vec![
+                Arc::new(StringArray::from(vec!["lower"])) as ArrayRef,
+                Arc::new(StringArray::from(vec!["upper"])) as ArrayRef,
+            ],
+        )
+        .unwrap(),
+    )
+    .await
+    .unwrap();
+
+    match db.query("SELECT name FROM data").await {
+        Err(Error::AmbiguousIdentifier {
+            identifier,
+            candidates,
+        }) => {
+            assert_eq!(identifier, "name");
+            assert_eq!(candidates.len(), 2);
+        }
+        other => panic!("expected AmbiguousIdentifier, got {:?}", other.map(|_| ())),
+    }
+
+    // Quoting selects one column explicitly
+    let results = db.query("SELECT \"Name\" FROM data").await.unwrap();
+    let values = results[0]
+        .column(0)
+        .as_any()
+        .downcast_ref::<StringArray>()
+        .unwrap();
+    assert_eq!(values.value(0), "upper");
+}
+
+#[tokio::test]
+async fn test_case_sensitive_mode() {
+    let temp_dir = TempDir::new().unwrap();
+    let schema = create_schema();
+    let db = DatabaseOps::create(temp_dir.path().join("case_db"), schema.clone())
+        .await
+        .unwrap()
+        .with_identifier_case(IdentifierCase::Sensitive);
+    db.insert(create_batch(schema)).await.unwrap();
+
+    let results = db.query("SELECT Region FROM data").await.unwrap();
+    assert_eq!(row_count(&results), 3);
+
+    assert!(db.query("SELECT region FROM data").await.is_err());
+}
@@ -0,0 +1,115 @@
+// Insert Compatibility Integration Tests
+// Tests dry-run schema checks against a table before inserting
+
+use arrow::array::{ArrayRef, Int32Array, RecordBatch, StringArray};
+use arrow::datatypes::{DataType, Field, Schema, SchemaRef};
+use fsdb::DatabaseOps;
+use fsdb::metadata::ColumnIssue;
+use std::sync::Arc;
+use tempfile::TempDir;
+
+fn create_schema() -> SchemaRef {
+    Arc::new(Schema::new(vec![
+        Field::new("id", DataType::Int32, false),
+        Field::new("name", DataType::Utf8, false),
+        Field::new("score", DataType::Int32, true),
+    ]))
+}
+
+async fn create_db(temp_dir: &TempDir) -> DatabaseOps {
+    DatabaseOps::create(temp_dir.path().join("compat_db"), create_schema())
+        .await
+        .unwrap()
+}
+
+async fn version(db: &DatabaseOps) -> i64 {
+    db.get_delta_table().await.unwrap().version().unwrap_or(0)
+}
+
+#[tokio::test]
+async fn test_compatible_batch() {
+    let temp_dir = TempDir::new().unwrap();
+    let db = create_db(&temp_dir).await;
+    let batch = RecordBatch::try_new(
+        create_schema(),
+        vec![
+            Arc::new(Int32Array::from(vec![1])) as ArrayRef,
+            Arc::new(StringArray::from(vec!["alice"])) as ArrayRef,
+            Arc::new(Int32Array::from(vec![Some(90)])) as ArrayRef,
+        ],
+    )
+    .unwrap();
+
+    let before = version(&db).await;
+    let report = db
+        .check_insert_compatibility(&batch.schema())
+        .await
+        .unwrap();
+    assert!(report.issues.is_empty(), "{:?}", report.issues);
+    assert!(report.is_compatible());
+    // Nothing was written
+    assert_eq!(version(&db).await, before);
+}
+
+#[tokio::test]
+async fn test_coercible_input_warns() {
+    let temp_dir = TempDir::new().unwrap();
+    let db = create_db(&temp_dir).await;
+    let input = Schema::new(vec![
+        Field::new("id", DataType::Int32, false),
+        Field::new("name", DataType::Utf8, false),
+        Field::new("score", DataType::Float64, true),
+    ]);
+
+    let report = db.check_insert_compatibility(&input).await.unwrap();
+    assert!(report.is_compatible());
+    let warnings: Vec<_> = report.warnings().collect();
+    assert_eq!(
+        warnings,
+        vec![&ColumnIssue::Lossy {
+            column: "score".to_string(),
+            input: DataType::Float64,
+            table: DataType::Int32,
+        }]
+    );
+}
+
+#[tokio::test]
+async fn test_incompatible_input_is_diagnosed() {
+    let temp_dir = TempDir::new().unwrap();
+    let db = create_db(&temp_dir).await;
+    let input = Schema::new(vec![
+        Field::new("id", DataType::Utf8, false),
+        Field::new("score", DataType::Int32, true),
+        Field::new("email", DataType::Utf8, true),
+    ]);
+
+    let report = db.check_insert_compatibility(&input).await.unwrap();
+    assert!(!report.is_compatible());
+    assert!(!report.is_compatible_with_evolution());
+    assert_eq!(
+        report.errors().map(ColumnIssue::column).collect::<Vec<_>>(),
+        vec!["id", "name", "email"]
+    );
+    let email = report
+        .issues
+        .iter()
+        .find(|i| i.column() == "email")
+        .unwrap();
+    assert!(email.resolved_by_evolution());
+}
+
+#[tokio::test]
+async fn test_extra_column_needs_only_evolution() {
+    let temp_dir = TempDir::new().unwrap();
+    let db = create_db(&temp_dir).await;
+    let mut fields = create_schema().fields().to_vec();
+    fields.push(Arc::new(Field::new("email", DataType::Utf8, true)));
+
+    let report = db
+        .check_insert_compatibility(&Schema::new(fields))
+        .await
+        .unwrap();
+    assert!(!report.is_compatible());
+    assert!(report.is_compatible_with_evolution());
+}
@@ -0,0 +1,142 @@
+// Integrity Check Integration Tests
+// Tests that check_integrity reports missing files, size mismatches and
+// stale statistics without changing the table, that repair_integrity fixes
+// the statistics alone, and that uncommitted files aren't reported
+
+use arrow::array::{ArrayRef, Int64Array, RecordBatch, StringArray};
+use arrow::datatypes::{DataType, Field, Schema, SchemaRef};
+use fsdb::DatabaseOps;
+use fsdb::delta_lake::{IntegrityIssue, get_file_statistics, latest_version};
+use std::path::Path;
+use std::sync::Arc;
+use tempfile::TempDir;
+
+fn create_schema() -> SchemaRef {
+    Arc::new(Schema::new(vec![
+        Field::new("id", DataType::Int64, false),
+        Field::new("name", DataType::Utf8, true),
+    ]))
+}
+
+fn batch(ids: &[i64]) -> RecordBatch {
+    let names: Vec<String> = ids.iter().map(|id| format!("row{}", id)).collect();
+    RecordBatch::try_new(
+        create_schema(),
+        vec![
+            Arc::new(Int64Array::from(ids.to_vec())) as ArrayRef,
+            Arc::new(StringArray::from(names)) as ArrayRef,
+        ],
+    )
+    .unwrap()
+}
+
+/// Two files, of rows 1-3 and 10-11
+async fn create_db(path: &Path) -> DatabaseOps {
+    let db = DatabaseOps::create(path, create_schema()).await.unwrap();
+    db.insert(batch(&[1, 2, 3])).await.unwrap();
+    db.insert(batch(&[10, 11])).await.unwrap();
+    db
+}
+
+/// Path, as in the log, and size of the file holding `rows` rows
+fn file_of(path: &Path, rows: u64) -> (String, u64) {
+    let file = get_file_statistics(path)
+        .unwrap()
+        .into_iter()
+        .find(|f| f.num_records == rows)
+        .unwrap();
+    (file.path, file.size_bytes)
+}
+
+#[tokio::test]
+async fn test_consistent_table() {
+    let temp_dir = TempDir::new().unwrap();
+    let path = temp_dir.path().join("db");
+    let db = create_db(&path).await;
+
+    let report = db.check_integrity().await.unwrap();
+    assert!(report.is_consistent(), "{:?}", report.issues);
+    assert_eq!(report.files_checked, 2);
+    assert_eq!(report.version, latest_version(&path).unwrap());
+
+    // A file written for a commit that hasn't happened yet isn't a problem
+    let (file, _) = file_of(&path, 3);
+    std::fs::copy(path.join(&file), path.join("part-uncommitted.parquet")).unwrap();
+    let report = db.check_integrity().await.unwrap();
+    assert!(report.is_consistent());
+    assert!(report.unreferenced_files.is_empty());
+}
+
+#[tokio::test]
+async fn test_missing_file_and_size_mismatch_are_reported() {
+    let temp_dir = TempDir::new().unwrap();
+    let path = temp_dir.path().join("db");
+    let db = create_db(&path).await;
+    let version = latest_version(&path).unwrap();
+
+    let (missing, _) = file_of(&path, 3);
+    std::fs::remove_file(path.join(&missing)).unwrap();
+    let (truncated, size) = file_of(&path, 2);
+    let bytes = std::fs::read(path.join(&truncated)).unwrap();
+    std::fs::write(path.join(&truncated), &bytes[..bytes.len() - 10]).unwrap();
+
+    let report = db.check_integrity().await.unwrap();
+    assert_eq!(report.issues.len(), 2, "{:?}", report.issues);
+    assert!(
+        report
+            .issues
+            .contains(&IntegrityIssue::MissingFile { path: missing })
+    );
+    assert!(report.issues.contains(&IntegrityIssue::SizeMismatch {
+        path: truncated,
+        recorded: size,
+        actual: size - 10,
+    }));
+
+    // Neither is repaired, and nothing is committed
+    let report = db.repair_integrity().await.unwrap();
+    assert_eq!(report.issues.len(), 2);
+    assert!(report.stats_repaired.is_empty());
+    assert_eq!(latest_version(&path).unwrap(), version);
+}
+
+#[tokio::test]
+async fn test_stale_stats_are_repaired() {
+    let temp_dir = TempDir::new().unwrap();
+    let path = temp_dir.path().join("db");
+    let db = create_db(&path).await;
+
+    // Have the log claim five rows for the three-row file
+    let (stale, _) = file_of(&path, 3);
+    for entry in std::fs::read_dir(path.join("_delta_log")).unwrap() {
+        let commit = entry.unwrap().path();
+        let content = std::fs::read_to_string(&commit).unwrap_or_default();
+        if content.contains(&stale) {
+            let edited = content.replace("\\\"numRecords\\\":3", "\\\"numRecords\\\":5");
+            assert_ne!(edited, content);
+            std::fs::write(&commit, edited).unwrap();
+        }
+    }
+
+    let version = latest_version(&path).unwrap();
+    let report = db.check_integrity().await.unwrap();
+    assert!(matches!(
+        report.issues.as_slice(),
+        [IntegrityIssue::InconsistentStats { path, .. }] if *path == stale
+    ));
+    assert_eq!(latest_version(&path).unwrap(), version);
+
+    let report = db.repair_integrity().await.unwrap();
+    assert!(report.is_consistent(), "{:?}", report.issues);
+    assert_eq!(report.stats_repaired, vec![stale.clone()]);
+    assert_eq!(latest_version(&path).unwrap(), version.map(|v| v + 1));
+
+    // The file is live with its real row count, and its rows are unchanged
+    assert_eq!(file_of(&path, 3).0, stale);
+    assert!(db.check_integrity().await.unwrap().is_consistent());
+    let rows = db
+        .query_rows("SELECT COUNT(*) AS n FROM data")
+        .await
+        .unwrap();
+    assert_eq!(rows.get(0, "n").unwrap(), &fsdb::query::Value::Int(5));
+}
@@ -0,0 +1,194 @@
+// JSON Column Integration Tests
+// Tests path extraction with the JSON operators and functions, that invalid
+// documents read as NULL, and that the column type survives reopening
+
+use arrow::array::{ArrayRef, Int32Array, RecordBatch, StringArray};
+use arrow::datatypes::{DataType, Field, Schema, SchemaRef};
+use fsdb::DatabaseOps;
+use fsdb::metadata::{is_json, json_field};
+use fsdb::query::{ResultSet, Value};
+use std::path::Path;
+use std::sync::Arc;
+use tempfile::TempDir;
+
+fn create_schema() -> SchemaRef {
+    Arc::new(Schema::new(vec![
+        Field::new("id", DataType::Int32, false),
+        json_field("payload", true),
+    ]))
+}
+
+/// Two files: well-formed documents, then a broken one, a null and an array
+async fn create_db(path: &Path) -> DatabaseOps {
+    let db = DatabaseOps::create(path, create_schema()).await.unwrap();
+    let files: [(&[i32], &[Option<&str>]); 2] = [
+        (
+            &[1, 2, 3],
+            &[
+                Some(r#"{"status": "active", "user": {"name": "ann", "tags": ["a", "b"]}}"#),
+                Some(r#"{"status": "closed", "user": {"name": "bo"}, "count": 7}"#),
+                Some(r#"{"status": "active", "user": null}"#),
+            ],
+        ),
+        (
+            &[4, 5, 6],
+            &[
+                Some(r#"{"status": "active""#),
+                None,
+                Some(r#"[{"status": "nested"}]"#),
+            ],
+        ),
+    ];
+    for (ids, payloads) in files {
+        let batch = RecordBatch::try_new(
+            create_schema(),
+            vec![
+                Arc::new(Int32Array::from(ids.to_vec())) as ArrayRef,
+                Arc::new(StringArray::from(payloads.to_vec())) as ArrayRef,
+            ],
+        )
+        .unwrap();
+        db.insert(batch).await.unwrap();
+    }
+    db
+}
+
+fn column(rows: &ResultSet, name: &str) -> Vec<Value> {
+    (0..rows.len())
+        .map(|row| rows.get(row, name).cloned().unwrap())
+        .collect()
+}
+
+fn ints(values: &[i64]) -> Vec<Value> {
+    values.iter().map(|v| Value::Int(*v)).collect()
+}
+
+#[tokio::test]
+async fn test_path_operators_filter_rows() {
+    let temp_dir = TempDir::new().unwrap();
+    let db = create_db(&temp_dir.path().join("db")).await;
+
+    let rows = db
+        .query_rows("SELECT id FROM data WHERE payload->>'status' = 'active' ORDER BY id")
+        .await
+        .unwrap();
+    assert_eq!(column(&rows, "id"), ints(&[1, 3]));
+
+    // Neither file is skipped: statistics can't see into documents
+    let stats = db.get_data_skipping_stats().await.unwrap();
+    assert_eq!((stats.files_read, stats.files_skipped), (2, 0));
+
+    // Chained operators, an array index, and an extracted number cast for comparison
+    let rows = db
+        .query_rows(
+            "SELECT payload->'user'->>'name' AS name, payload->'user'->'tags'->>1 AS tag \
+             FROM data WHERE id = 1",
+        )
+        .await
+        .unwrap();
+    assert_eq!(rows.get(0, "name"), Some(&Value::String("ann".to_string())));
+    assert_eq!(rows.get(0, "tag"), Some(&Value::String("b".to_string())));
+    let rows = db
+        .query_rows("SELECT id FROM data WHERE CAST(payload->>'count' AS INT) > 5")
+        .await
+        .unwrap();
+    assert_eq!(column(&rows, "id"), ints(&[2]));
+    let rows = db
+        .query_rows("SELECT payload->0->>'status' AS status FROM data WHERE id = 6")
+        .await
+        .unwrap();
+    assert_eq!(
+        rows.get(0, "status"),
+        Some(&Value::String("nested".to_string()))
+    );
+}
+
+#[tokio::test]
+async fn test_extract_functions() {
+    let temp_dir = TempDir::new().unwrap();
+    let db = create_db(&temp_dir.path().join("db")).await;
+
+    let rows = db
+        .query_rows(
+            "SELECT json_extract(payload, '$.user.name') AS quoted, \
+             json_extract_text(payload, '$.user.tags[0]') AS tag, \
+             json_extract(payload, '$.user') AS owner \
+             FROM data WHERE id IN (1, 3) ORDER BY id",
+        )
+        .await
+        .unwrap();
+    assert_eq!(
+        column(&rows, "quoted"),
+        vec![Value::String("\"ann\"".to_string()), Value::Null]
+    );
+    assert_eq!(
+        column(&rows, "tag"),
+        vec![Value::String("a".to_string()), Value::Null]
+    );
+
+    // A JSON null is JSON text to json_extract, and SQL NULL as text
+    assert_eq!(
+        rows.get(1, "owner"),
+        Some(&Value::String("null".to_string()))
+    );
+    let rows = db
+        .query_rows("SELECT id FROM data WHERE payload->'user' IS NOT NULL ORDER BY id")
+        .await
+        .unwrap();
+    assert_eq!(column(&rows, "id"), ints(&[1, 2, 3]));
+    let rows = db
+        .query_rows("SELECT id FROM data WHERE payload->>'user' IS NOT NULL ORDER BY id")
+        .await
+        .unwrap();
+    assert_eq!(column(&rows, "id"), ints(&[1, 2]));
+}
+
+#[tokio::test]
+async fn test_invalid_json_reads_as_null() {
+    let temp_dir = TempDir::new().unwrap();
+    let db = create_db(&temp_dir.path().join("db")).await;
+
+    // The truncated document, the NULL cell and the array have no status
+    let rows = db
+        .query_rows("SELECT id FROM data WHERE payload->>'status' IS NULL ORDER BY id")
+        .await
+        .unwrap();
+    assert_eq!(column(&rows, "id"), ints(&[4, 5, 6]));
+
+    // Missing keys and malformed paths are NULL too, not errors
+    let rows = db
+        .query_rows(
+            "SELECT count(*) AS n FROM data \
+             WHERE payload->>'missing' IS NULL AND json_extract(payload, '$..bad') IS NULL",
+        )
+        .await
+        .unwrap();
+    assert_eq!(column(&rows, "n"), ints(&[6]));
+
+    // The raw text is still there as written
+    let rows = db
+        .query_rows("SELECT payload FROM data WHERE id = 4")
+        .await
+        .unwrap();
+    assert_eq!(
+        column(&rows, "payload"),
+        vec![Value::String(r#"{"status": "active""#.to_string())]
+    );
+}
+
+#[tokio::test]
+async fn test_json_type_survives_reopen() {
+    let temp_dir = TempDir::new().unwrap();
+    let path = temp_dir.path().join("db");
+    drop(create_db(&path).await);
+
+    let db = DatabaseOps::open(&path).await.unwrap();
+    let schema = db.schema();
+    assert!(is_json(schema.field_with_name("payload").unwrap()));
+    assert!(!is_json(schema.field_with_name("id").unwrap()));
+    let rows = db
+        .query_rows("SELECT count(*) AS n FROM data WHERE payload->>'status' = 'active'")
+        .await
+        .unwrap();
+    assert_eq!(column(&rows, "n"), ints(&[2]));
+}
@@ -0,0 +1,184 @@
+// Log Redaction Tests
+// Tests that a LogRedaction policy keeps SQL literals out of emitted log records
+// and that audit entries keep the unredacted text for admins only
+
+use arrow::array::{ArrayRef, Int32Array, RecordBatch, StringArray};
+use arrow::datatypes::{DataType, Field, Schema, SchemaRef};
+use fsdb::DatabaseOps;
+use fsdb::security::LogRedaction;
+use std::io::Write;
+use std::sync::{Arc, Mutex};
+use tempfile::TempDir;
+
+/// Collects everything the fmt subscriber writes
+#[derive(Clone, Default)]
+struct CapturedLogs(Arc<Mutex<Vec<u8>>>);
+
+impl CapturedLogs {
+    fn contents(&self) -> String {
+        String::from_utf8(self.0.lock().unwrap().clone()).unwrap()
+    }
+}
+
+impl Write for CapturedLogs {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.lock().unwrap().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Capture fsdb's log records on this thread until the guard is dropped
+fn capture_logs() -> (CapturedLogs, tracing::subscriber::DefaultGuard) {
+    let logs = CapturedLogs::default();
+    let writer = logs.clone();
+    let subscriber = tracing_subscriber::fmt()
+        .with_env_filter("fsdb=debug")
+        .with_ansi(false)
+        .with_writer(move || writer.clone())
+        .finish();
+    (logs, tracing::subscriber::set_default(subscriber))
+}
+
+fn create_schema() -> SchemaRef {
+    Arc::new(Schema::new(vec![
+        Field::new("id", DataType::Int32, false),
+        Field::new("email", DataType::Utf8, false),
+    ]))
+}
+
+fn create_batch() -> RecordBatch {
+    RecordBatch::try_new(
+        create_schema(),
+        vec![
+            Arc::new(Int32Array::from(vec![1, 2])) as ArrayRef,
+            Arc::new(StringArray::from(vec![
+                "alice@example.com",
+                "bob@example.com",
+            ])) as ArrayRef,
+        ],
+    )
+    .unwrap()
+}
+
+#[tokio::test]
+async fn test_literals_absent_from_log_records() {
+    let temp_dir = TempDir::new().unwrap();
+    let db = DatabaseOps::create(temp_dir.path().join("db"), create_schema())
+        .await
+        .unwrap()
+        .with_log_redaction(LogRedaction::mask_literals());
+    db.insert(create_batch()).await.unwrap();
+
+    let (logs, _guard) = capture_logs();
+    db.query("SELECT * FROM data WHERE email = 'alice@example.com' AND id < 424242")
+        .await
+        .unwrap();
+    db.delete_rows_where("email = 'bob@example.com'")
+        .await
+        .unwrap();
+
+    let logs = logs.contents();
+    assert!(!logs.is_empty());
+    for literal in ["alice@example.com", "bob@example.com", "424242"] {
+        assert!(
+            !logs.contains(literal),
+            "{} leaked into:\n{}",
+            literal,
+            logs
+        );
+    }
+    // The statement's structure is still there for debugging
+    assert!(
+        logs.contains("WHERE email = '?' AND id < ?"),
+        "structure missing from:\n{}",
+        logs
+    );
+}
+
+#[tokio::test]
+async fn test_hashed_identifiers_absent_from_log_records() {
+    let temp_dir = TempDir::new().unwrap();
+    let db = DatabaseOps::create(temp_dir.path().join("db"), create_schema())
+        .await
+        .unwrap()
+        .with_log_redaction(LogRedaction::mask_literals().with_hashed_identifiers(true));
+    db.insert(create_batch()).await.unwrap();
+
+    let (logs, _guard) = capture_logs();
+    db.query("SELECT email FROM data WHERE id = 1")
+        .await
+        .unwrap();
+
+    let logs = logs.contents();
+    assert!(
+        !logs.contains("email"),
+        "column name leaked into:\n{}",
+        logs
+    );
+    assert!(logs.contains("SELECT id_"), "{}", logs);
+}
+
+#[tokio::test]
+async fn test_without_policy_sql_is_logged_verbatim() {
+    let temp_dir = TempDir::new().unwrap();
+    let db = DatabaseOps::create(temp_dir.path().join("db"), create_schema())
+        .await
+        .unwrap();
+    db.insert(create_batch()).await.unwrap();
+
+    let (logs, _guard) = capture_logs();
+    db.query("SELECT * FROM data WHERE email = 'alice@example.com'")
+        .await
+        .unwrap();
+
+    assert!(logs.contents().contains("'alice@example.com'"));
+}
+
+#[tokio::test]
+async fn test_audit_keeps_full_details_for_admins_only() {
+    let temp_dir = TempDir::new().unwrap();
+    let path = temp_dir.path().join("db");
+    let db = DatabaseOps::create_with_auth(&path, create_schema(), true)
+        .await
+        .unwrap();
+    db.create_user("root", "root-pass", &["admin"])
+        .await
+        .unwrap();
+    db.create_user("analyst", "analyst-pass", &["read"])
+        .await
+        .unwrap();
+    drop(db);
+
+    let analyst = DatabaseOps::open_with_credentials(&path, Some(("analyst", "analyst-pass")))
+        .await
+        .unwrap()
+        .with_log_redaction(LogRedaction::mask_literals());
+    analyst
+        .query("SELECT * FROM data WHERE email = 'carol@example.com'")
+        .await
+        .unwrap();
+
+    // The analyst sees only the redacted entry
+    let entries = analyst.get_audit_log().await.unwrap();
+    let entry = entries.iter().find(|e| e.operation == "SELECT").unwrap();
+    assert_eq!(entry.details, "SELECT * FROM data WHERE email = '?'");
+    assert!(entry.full_details.is_none());
+
+    // An admin reading the same log gets the original text
+    let admin = DatabaseOps::open_with_credentials(&path, Some(("root", "root-pass")))
+        .await
+        .unwrap();
+    let entries = admin.get_audit_log().await.unwrap();
+    let entry = entries.iter().find(|e| e.operation == "SELECT").unwrap();
+    assert_eq!(
+        entry.full_details.as_deref(),
+        Some("SELECT * FROM data WHERE email = 'carol@example.com'")
+    );
+
+    // The persisted redacted text never held the literal
+    assert!(!entry.details.contains("carol"));
+}
@@ -0,0 +1,140 @@
+// Log Retention Integration Tests
+// Tests checkpointing and cleaning up _delta_log without breaking reads
+
+use arrow::array::{ArrayRef, Int32Array, Int64Array, RecordBatch};
+use arrow::datatypes::{DataType, Field, Schema, SchemaRef};
+use fsdb::DatabaseOps;
+use fsdb::delta_lake::LogRetentionConfig;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+use tempfile::TempDir;
+
+fn create_schema() -> SchemaRef {
+    Arc::new(Schema::new(vec![Field::new("id", DataType::Int32, false)]))
+}
+
+async fn insert(db: &DatabaseOps, id: i32) {
+    let batch = RecordBatch::try_new(
+        create_schema(),
+        vec![Arc::new(Int32Array::from(vec![id])) as ArrayRef],
+    )
+    .unwrap();
+    db.insert(batch).await.unwrap();
+}
+
+fn count(batches: &[RecordBatch]) -> i64 {
+    batches[0]
+        .column(0)
+        .as_any()
+        .downcast_ref::<Int64Array>()
+        .unwrap()
+        .value(0)
+}
+
+fn commit_files(path: &Path) -> usize {
+    std::fs::read_dir(path.join("_delta_log"))
+        .unwrap()
+        .filter(|entry| {
+            let name = entry.as_ref().unwrap().file_name();
+            let name = name.to_str().unwrap();
+            name.ends_with(".json") && !name.contains("checkpoint")
+        })
+        .count()
+}
+
+fn no_retention() -> LogRetentionConfig {
+    LogRetentionConfig {
+        retention: Duration::ZERO,
+        checkpoint_interval: None,
+    }
+}
+
+#[tokio::test]
+async fn test_cleanup_after_checkpoint_keeps_reads_working() {
+    let temp_dir = TempDir::new().unwrap();
+    let db_path = temp_dir.path().join("db");
+    let db = DatabaseOps::create(&db_path, create_schema())
+        .await
+        .unwrap()
+        .with_log_retention(no_retention());
+    for id in 0..10 {
+        insert(&db, id).await;
+    }
+    assert_eq!(commit_files(&db_path), 11);
+
+    // An open cursor pins version 10, which needs the whole log until a
+    // checkpoint covers it
+    let cursor = db.open_cursor("SELECT COUNT(*) FROM data").await.unwrap();
+    insert(&db, 10).await;
+    insert(&db, 11).await;
+    let report = db.checkpoint().await.unwrap();
+    assert_eq!(report.files_deleted, 0);
+    assert_eq!(commit_files(&db_path), 13);
+    let (batches, _) = db.fetch(cursor, 10).await.unwrap();
+    assert_eq!(count(&batches), 10);
+    assert!(db.close_cursor(cursor).await);
+
+    // Now the newest checkpoint stands in for everything before it
+    let report = db.checkpoint().await.unwrap();
+    assert_eq!(report.checkpoint_version, Some(12));
+    assert!(report.files_deleted >= 12);
+    assert_eq!(commit_files(&db_path), 1);
+
+    let batches = db.query("SELECT COUNT(*) FROM data").await.unwrap();
+    assert_eq!(count(&batches), 12);
+    insert(&db, 12).await;
+    let reopened = DatabaseOps::open(&db_path).await.unwrap();
+    let batches = reopened.query("SELECT COUNT(*) FROM data").await.unwrap();
+    assert_eq!(count(&batches), 13);
+    assert_eq!(
+        fsdb::delta_lake::read_delta_log(&db_path).unwrap().version,
+        Some(13)
+    );
+
+    // Versions before the checkpoint are gone for good
+    assert!(db.query_version("SELECT * FROM data", 5).await.is_err());
+}
+
+#[tokio::test]
+async fn test_recent_commits_outlive_checkpoint() {
+    let temp_dir = TempDir::new().unwrap();
+    let db_path = temp_dir.path().join("db");
+    let db = DatabaseOps::create(&db_path, create_schema())
+        .await
+        .unwrap();
+    for id in 0..5 {
+        insert(&db, id).await;
+    }
+
+    // The default retention keeps a month of commits for time travel
+    let report = db.checkpoint().await.unwrap();
+    assert_eq!(report.files_deleted, 0);
+    assert_eq!(commit_files(&db_path), 6);
+    let batches = db
+        .query_version("SELECT COUNT(*) FROM data", 2)
+        .await
+        .unwrap();
+    assert_eq!(count(&batches), 2);
+}
+
+#[tokio::test]
+async fn test_checkpoint_interval_bounds_the_log() {
+    let temp_dir = TempDir::new().unwrap();
+    let db_path = temp_dir.path().join("db");
+    let db = DatabaseOps::create(&db_path, create_schema())
+        .await
+        .unwrap()
+        .with_log_retention(LogRetentionConfig {
+            checkpoint_interval: Some(4),
+            ..no_retention()
+        });
+    for id in 0..20 {
+        insert(&db, id).await;
+        assert!(commit_files(&db_path) <= 5);
+    }
+
+    assert!(db_path.join("_delta_log").join("_last_checkpoint").exists());
+    let batches = db.query("SELECT COUNT(*) FROM data").await.unwrap();
+    assert_eq!(count(&batches), 20);
+}
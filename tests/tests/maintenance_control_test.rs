@@ -0,0 +1,260 @@
+// Maintenance Control Integration Tests
+// Tests cancelling OPTIMIZE and VACUUM part way, with progress reporting, and
+// the lock that keeps maintenance runs on a table from overlapping
+
+use arrow::array::{ArrayRef, Int32Array, Int64Array, StringArray};
+use arrow::datatypes::{DataType, Field, Schema};
+use arrow::record_batch::RecordBatch;
+use fsdb::delta_lake::changes::CancellationToken;
+use fsdb::delta_lake::{
+    MaintenanceConflict, MaintenanceControl, MaintenanceLockConfig, MaintenanceProgress,
+    read_delta_log,
+};
+use fsdb::{DatabaseOps, Error};
+use std::path::Path;
+use std::sync::{Arc, Mutex, mpsc};
+use std::time::Duration;
+use tempfile::TempDir;
+
+const REGIONS: [&str; 3] = ["eu", "us", "ap"];
+
+/// A partitioned table with `files` single-row files per region
+async fn create_db(path: &Path, files: i32) -> DatabaseOps {
+    let schema = Arc::new(Schema::new(vec![
+        Field::new("id", DataType::Int32, false),
+        Field::new("region", DataType::Utf8, true),
+    ]));
+    let db = DatabaseOps::create_partitioned(path, schema, &["region"])
+        .await
+        .unwrap();
+    let mut id = 0;
+    for region in REGIONS {
+        for _ in 0..files {
+            let batch = RecordBatch::try_new(
+                db.schema(),
+                vec![
+                    Arc::new(Int32Array::from(vec![id])) as ArrayRef,
+                    Arc::new(StringArray::from(vec![region])) as ArrayRef,
+                ],
+            )
+            .unwrap();
+            db.insert(batch).await.unwrap();
+            id += 1;
+        }
+    }
+    db
+}
+
+async fn row_count(db: &DatabaseOps) -> i64 {
+    let results = db.query("SELECT COUNT(*) FROM data").await.unwrap();
+    results[0]
+        .column(0)
+        .as_any()
+        .downcast_ref::<Int64Array>()
+        .unwrap()
+        .value(0)
+}
+
+/// Control that cancels itself once `after` progress reports have arrived
+fn cancel_after(after: usize) -> (MaintenanceControl, Arc<Mutex<Vec<MaintenanceProgress>>>) {
+    let cancel = CancellationToken::new();
+    let reports = Arc::new(Mutex::new(Vec::new()));
+    let control = MaintenanceControl::default()
+        .with_cancellation(cancel.clone())
+        .with_progress({
+            let reports = reports.clone();
+            move |progress| {
+                let mut reports = reports.lock().unwrap();
+                reports.push(progress.clone());
+                if reports.len() >= after {
+                    cancel.cancel();
+                }
+            }
+        });
+    (control, reports)
+}
+
+/// Control that holds its run at the first progress report until released
+///
+/// Returns the control, a receiver told when the run is paused, and the
+/// sender that releases it.
+fn pause_at_first_report() -> (MaintenanceControl, mpsc::Receiver<()>, mpsc::Sender<()>) {
+    let (paused_tx, paused_rx) = mpsc::channel();
+    let (release_tx, release_rx) = mpsc::channel::<()>();
+    let release_rx = Mutex::new(release_rx);
+    let control = MaintenanceControl::default().with_progress(move |_| {
+        if paused_tx.send(()).is_ok() {
+            let _ = release_rx.lock().unwrap().recv();
+        }
+    });
+    (control, paused_rx, release_tx)
+}
+
+/// Wait without blocking the runtime until `paused` is signalled
+async fn wait_paused(paused: mpsc::Receiver<()>) -> mpsc::Receiver<()> {
+    tokio::task::spawn_blocking(move || {
+        paused.recv_timeout(Duration::from_secs(30)).unwrap();
+        paused
+    })
+    .await
+    .unwrap()
+}
+
+/// Parquet files on disk under the table, in any partition directory
+fn parquet_files_on_disk(path: &Path) -> usize {
+    REGIONS
+        .iter()
+        .map(|region| {
+            std::fs::read_dir(path.join(format!("region={}", region)))
+                .unwrap()
+                .filter_map(|entry| entry.ok())
+                .filter(|entry| entry.path().extension().is_some_and(|ext| ext == "parquet"))
+                .count()
+        })
+        .sum()
+}
+
+#[tokio::test]
+async fn test_cancelled_optimize_keeps_committed_partitions() {
+    let temp_dir = TempDir::new().unwrap();
+    let db_path = temp_dir.path().join("db");
+    let db = create_db(&db_path, 4).await;
+    assert_eq!(read_delta_log(&db_path).unwrap().files.len(), 12);
+
+    // Cancel as soon as the first partition has been compacted
+    let (control, reports) = cancel_after(1);
+    let metrics = db.optimize_with_control(&control).await.unwrap();
+
+    assert!(metrics.cancelled);
+    assert_eq!(metrics.num_files_removed, 4);
+    assert_eq!(metrics.num_files_added, 1);
+    let reports = reports.lock().unwrap().clone();
+    assert_eq!(reports.len(), 1);
+    assert_eq!(reports[0].files_processed, 4);
+    assert_eq!(reports[0].files_total, Some(12));
+
+    // The log is consistent: one partition compacted, the others untouched
+    assert_eq!(read_delta_log(&db_path).unwrap().files.len(), 9);
+    assert_eq!(row_count(&db).await, 12);
+    let reopened = DatabaseOps::open(&db_path).await.unwrap();
+    assert_eq!(row_count(&reopened).await, 12);
+
+    // Running again finishes the job
+    let metrics = db.optimize().await.unwrap();
+    assert!(!metrics.cancelled);
+    assert_eq!(metrics.num_files_removed, 8);
+    assert_eq!(read_delta_log(&db_path).unwrap().files.len(), 3);
+    assert_eq!(row_count(&db).await, 12);
+}
+
+#[tokio::test]
+async fn test_cancelled_vacuum_reports_partial_deletion() {
+    let temp_dir = TempDir::new().unwrap();
+    let db_path = temp_dir.path().join("db");
+    let db = create_db(&db_path, 2).await;
+    db.optimize().await.unwrap();
+    assert_eq!(parquet_files_on_disk(&db_path), 9);
+
+    let (control, reports) = cancel_after(2);
+    let report = db.vacuum_with_control(0, &control).await.unwrap();
+
+    assert!(report.cancelled);
+    assert_eq!(report.files_deleted, 2);
+    assert!(report.bytes_reclaimed > 0);
+    let reports = reports.lock().unwrap().clone();
+    assert_eq!(reports.len(), 2);
+    assert_eq!(reports[1].files_processed, 2);
+    assert_eq!(reports[1].files_total, Some(6));
+    assert_eq!(reports[1].bytes_reclaimed, report.bytes_reclaimed);
+
+    // Only unreferenced files were deleted, so the table reads as before
+    assert_eq!(parquet_files_on_disk(&db_path), 7);
+    assert_eq!(row_count(&db).await, 6);
+
+    // The next VACUUM deletes the rest
+    let report = db.vacuum(0).await.unwrap();
+    assert!(!report.cancelled);
+    assert_eq!(report.files_deleted, 4);
+    assert_eq!(parquet_files_on_disk(&db_path), 3);
+    assert_eq!(row_count(&db).await, 6);
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn test_concurrent_maintenance_fails_while_locked() {
+    let temp_dir = TempDir::new().unwrap();
+    let db_path = temp_dir.path().join("db");
+    let db = Arc::new(
+        create_db(&db_path, 2)
+            .await
+            .with_maintenance_lock(MaintenanceLockConfig {
+                on_conflict: MaintenanceConflict::Fail,
+                lock_file: true,
+                ..Default::default()
+            }),
+    );
+
+    let (control, paused, release) = pause_at_first_report();
+    let first = tokio::spawn({
+        let db = db.clone();
+        async move { db.optimize_with_control(&control).await }
+    });
+    let paused = wait_paused(paused).await;
+    assert!(db_path.join("_metadata/maintenance.lock").exists());
+
+    // Only one run at a time, whatever the operation
+    assert!(matches!(
+        db.optimize().await,
+        Err(Error::MaintenanceInProgress(_))
+    ));
+    assert!(matches!(
+        db.vacuum(0).await,
+        Err(Error::MaintenanceInProgress(_))
+    ));
+    assert!(matches!(
+        db.zorder(&["id"]).await,
+        Err(Error::MaintenanceInProgress(_))
+    ));
+
+    drop(paused);
+    release.send(()).unwrap();
+    let metrics = first.await.unwrap().unwrap();
+    assert_eq!(metrics.num_files_removed, 6);
+    assert!(!db_path.join("_metadata/maintenance.lock").exists());
+
+    // Released once the first run is done
+    db.optimize().await.unwrap();
+    assert_eq!(row_count(&db).await, 6);
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn test_concurrent_optimize_waits_for_lock() {
+    let temp_dir = TempDir::new().unwrap();
+    let db_path = temp_dir.path().join("db");
+    let db = Arc::new(create_db(&db_path, 2).await);
+
+    let (control, paused, release) = pause_at_first_report();
+    let first = tokio::spawn({
+        let db = db.clone();
+        async move { db.optimize_with_control(&control).await }
+    });
+    let paused = wait_paused(paused).await;
+
+    // The second run waits for the first instead of overlapping it
+    let second = tokio::spawn({
+        let db = db.clone();
+        async move { db.optimize().await }
+    });
+    tokio::time::sleep(Duration::from_millis(300)).await;
+    assert!(!second.is_finished());
+
+    drop(paused);
+    release.send(()).unwrap();
+    let first = first.await.unwrap().unwrap();
+    let second = second.await.unwrap().unwrap();
+
+    // The second run found the first one's work done
+    assert_eq!(first.num_files_removed, 6);
+    assert_eq!(second.num_files_removed, 0);
+    assert_eq!(read_delta_log(&db_path).unwrap().files.len(), 3);
+    assert_eq!(row_count(&db).await, 6);
+}
@@ -0,0 +1,151 @@
+// Maintenance Scheduler Integration Tests
+// Tests the background maintenance task: compaction once small files pile
+// up or grow old, periodic VACUUM, waiting out active writes, and start/stop
+// with the status it reports
+
+use arrow::array::{ArrayRef, Int32Array, RecordBatch};
+use arrow::datatypes::{DataType, Field, Schema, SchemaRef};
+use fsdb::DatabaseOps;
+use fsdb::delta_lake::{MaintenancePolicy, MaintenanceStatus};
+use std::sync::Arc;
+use std::time::Duration;
+use tempfile::TempDir;
+
+fn create_schema() -> SchemaRef {
+    Arc::new(Schema::new(vec![Field::new("id", DataType::Int32, false)]))
+}
+
+fn batch(ids: std::ops::Range<i32>) -> RecordBatch {
+    RecordBatch::try_new(
+        create_schema(),
+        vec![Arc::new(Int32Array::from_iter_values(ids)) as ArrayRef],
+    )
+    .unwrap()
+}
+
+/// A database with one file per insert of ten rows
+async fn create_db(path: &std::path::Path, files: i32) -> Arc<DatabaseOps> {
+    let db = DatabaseOps::create(path, create_schema()).await.unwrap();
+    for i in 0..files {
+        db.insert(batch(i * 10..i * 10 + 10)).await.unwrap();
+    }
+    Arc::new(db)
+}
+
+/// Checks often, runs nothing unless a test asks for it
+fn policy() -> MaintenancePolicy {
+    MaintenancePolicy {
+        poll_interval: Duration::from_millis(20),
+        quiet_period: Duration::ZERO,
+        max_small_files: 100,
+        max_small_file_age: None,
+        vacuum_interval: None,
+        vacuum_retention_hours: 0,
+        ..Default::default()
+    }
+}
+
+/// Poll the status until `done` holds, for up to ten seconds
+async fn wait_for(
+    db: &DatabaseOps,
+    done: impl Fn(&MaintenanceStatus) -> bool,
+) -> MaintenanceStatus {
+    for _ in 0..500 {
+        let status = db.maintenance_status().await;
+        if done(&status) {
+            return status;
+        }
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+    panic!("timed out: {:?}", db.maintenance_status().await);
+}
+
+async fn count(db: &DatabaseOps) -> String {
+    db.query_rows("SELECT COUNT(*) FROM data")
+        .await
+        .unwrap()
+        .rows()[0][0]
+        .to_string()
+}
+
+#[tokio::test]
+async fn test_compacts_once_small_files_pile_up() {
+    let temp_dir = TempDir::new().unwrap();
+    let db = create_db(&temp_dir.path().join("db"), 4).await;
+
+    db.start_maintenance(MaintenancePolicy {
+        max_small_files: 3,
+        ..policy()
+    })
+    .await
+    .unwrap();
+    assert!(db.start_maintenance(policy()).await.is_err());
+
+    let status = wait_for(&db, |status| status.optimize_runs > 0).await;
+    assert!(status.running);
+    let metrics = status.last_optimize.unwrap();
+    assert_eq!((metrics.files_before, metrics.files_after), (4, 1));
+    assert_eq!(count(&db).await, "40");
+
+    db.stop_maintenance().await;
+    let status = db.maintenance_status().await;
+    assert!(!status.running);
+    assert_eq!(status.optimize_runs, 1);
+}
+
+#[tokio::test]
+async fn test_compacts_old_small_files_and_vacuums() {
+    let temp_dir = TempDir::new().unwrap();
+    let db = create_db(&temp_dir.path().join("db"), 2).await;
+
+    // Two files are under the count, but already older than the age limit
+    db.start_maintenance(MaintenancePolicy {
+        max_small_file_age: Some(Duration::ZERO),
+        vacuum_interval: Some(Duration::ZERO),
+        ..policy()
+    })
+    .await
+    .unwrap();
+
+    let status = wait_for(&db, |status| {
+        status.optimize_runs > 0 && status.vacuum_runs > 0
+    })
+    .await;
+    assert_eq!(status.last_optimize.unwrap().files_after, 1);
+    assert!(status.last_vacuum.is_some() && status.last_vacuum_at.is_some());
+    assert!(status.last_error.is_none());
+    assert_eq!(count(&db).await, "20");
+    db.stop_maintenance().await;
+}
+
+#[tokio::test]
+async fn test_waits_for_writes_to_settle() {
+    let temp_dir = TempDir::new().unwrap();
+    let db = create_db(&temp_dir.path().join("db"), 1).await;
+
+    db.start_maintenance(MaintenancePolicy {
+        quiet_period: Duration::from_secs(3600),
+        max_small_files: 1,
+        ..policy()
+    })
+    .await
+    .unwrap();
+    for i in 1..4 {
+        db.insert(batch(i * 10..i * 10 + 10)).await.unwrap();
+    }
+
+    let status = wait_for(&db, |status| status.checks >= 5).await;
+    assert_eq!(status.optimize_runs, 0);
+    db.stop_maintenance().await;
+
+    // Stopping twice is harmless, and the scheduler can be started again
+    db.stop_maintenance().await;
+    db.start_maintenance(MaintenancePolicy {
+        max_small_files: 1,
+        ..policy()
+    })
+    .await
+    .unwrap();
+    wait_for(&db, |status| status.optimize_runs > 0).await;
+    db.stop_maintenance().await;
+}
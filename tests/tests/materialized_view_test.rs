@@ -0,0 +1,239 @@
+// Materialized View Integration Tests
+// Tests incremental SUM/COUNT maintenance and full refresh of views that can't be maintained
+
+use arrow::array::{Array, ArrayRef, Int64Array, StringArray};
+use arrow::datatypes::{DataType, Field, Schema};
+use arrow::record_batch::RecordBatch;
+use arrow::util::display::array_value_to_string;
+use fsdb::DatabaseOps;
+use fsdb::query::ViewDefinition;
+use std::path::Path;
+use std::sync::Arc;
+use tempfile::TempDir;
+
+const TOTALS: &str = "SELECT region, SUM(amount) AS total, COUNT(*) AS orders, COUNT(amount) AS priced \
+                      FROM data GROUP BY region";
+
+fn orders(rows: &[(&str, &str, Option<i64>)]) -> RecordBatch {
+    let schema = Arc::new(Schema::new(vec![
+        Field::new("region", DataType::Utf8, false),
+        Field::new("customer", DataType::Utf8, false),
+        Field::new("amount", DataType::Int64, true),
+    ]));
+    RecordBatch::try_new(
+        schema,
+        vec![
+            Arc::new(StringArray::from_iter_values(rows.iter().map(|r| r.0))) as ArrayRef,
+            Arc::new(StringArray::from_iter_values(rows.iter().map(|r| r.1))),
+            Arc::new(Int64Array::from_iter(rows.iter().map(|r| r.2))),
+        ],
+    )
+    .unwrap()
+}
+
+async fn create_db(path: &Path) -> DatabaseOps {
+    let batch = orders(&[
+        ("east", "ann", Some(10)),
+        ("east", "bob", Some(5)),
+        ("west", "ann", Some(7)),
+        ("west", "cat", None),
+    ]);
+    let db = DatabaseOps::create(path, batch.schema()).await.unwrap();
+    db.insert(batch).await.unwrap();
+    db
+}
+
+fn rows(batches: &[RecordBatch]) -> Vec<String> {
+    let mut rows = Vec::new();
+    for batch in batches {
+        for row in 0..batch.num_rows() {
+            let cells: Vec<String> = batch
+                .columns()
+                .iter()
+                .map(|column| {
+                    if column.is_null(row) {
+                        "NULL".to_string()
+                    } else {
+                        array_value_to_string(column, row).unwrap()
+                    }
+                })
+                .collect();
+            rows.push(cells.join(","));
+        }
+    }
+    rows
+}
+
+/// The view's rows, next to what its query returns on the table now
+async fn view_and_query(
+    db: &DatabaseOps,
+    view_path: &Path,
+    sql: &str,
+) -> (Vec<String>, Vec<String>) {
+    let columns = "SELECT * FROM data ORDER BY region";
+    let view = DatabaseOps::open(view_path).await.unwrap();
+    let visible: Vec<String> = view
+        .schema()
+        .fields()
+        .iter()
+        .map(|f| f.name().clone())
+        .filter(|name| !name.starts_with("__fsdb_"))
+        .collect();
+    let view_rows = view
+        .query(&columns.replace('*', &visible.join(", ")))
+        .await
+        .unwrap();
+    let query_rows = db
+        .query(&format!("SELECT * FROM ({}) ORDER BY region", sql))
+        .await
+        .unwrap();
+    (rows(&view_rows), rows(&query_rows))
+}
+
+#[tokio::test]
+async fn test_sum_and_count_maintained_incrementally() {
+    let temp_dir = TempDir::new().unwrap();
+    let db = create_db(&temp_dir.path().join("orders")).await;
+    let view_path = temp_dir.path().join("totals");
+
+    let definition = db
+        .create_materialized_view(&view_path, TOTALS)
+        .await
+        .unwrap();
+    assert!(
+        definition.is_incremental(),
+        "{:?}",
+        definition.full_refresh_reason
+    );
+    let (view, query) = view_and_query(&db, &view_path, TOTALS).await;
+    assert_eq!(view, vec!["east,15,2,2", "west,7,2,1"]);
+    assert_eq!(view, query);
+
+    // Inserts, deletes and updates refresh the view as they commit
+    db.insert(orders(&[("north", "dan", Some(3)), ("east", "eve", None)]))
+        .await
+        .unwrap();
+    db.delete_rows_where("customer = 'ann' AND region = 'west'")
+        .await
+        .unwrap();
+    db.update_rows(&[("amount", "amount * 2")], "region = 'east'")
+        .await
+        .unwrap();
+    let (view, query) = view_and_query(&db, &view_path, TOTALS).await;
+    assert_eq!(view, vec!["east,30,3,2", "north,3,1,1", "west,NULL,1,0"]);
+    assert_eq!(view, query);
+
+    // A group whose last row goes disappears
+    db.delete_rows_where("region = 'north'").await.unwrap();
+    let (view, query) = view_and_query(&db, &view_path, TOTALS).await;
+    assert_eq!(view, vec!["east,30,3,2", "west,NULL,1,0"]);
+    assert_eq!(view, query);
+
+    // MERGE doesn't refresh views as it commits; refresh_view applies just
+    // the rows it changed
+    db.merge()
+        .await
+        .unwrap()
+        .with_source(orders(&[("west", "fay", Some(4))]), "source")
+        .on("target.customer = source.customer")
+        .when_not_matched_insert()
+        .values_all()
+        .execute()
+        .await
+        .unwrap();
+    let refresh = db.refresh_view(&view_path).await.unwrap();
+    assert_eq!(refresh.full_refresh_reason, None);
+    assert!(refresh.rows_applied > 0);
+    let (view, query) = view_and_query(&db, &view_path, TOTALS).await;
+    assert_eq!(view, vec!["east,30,3,2", "west,4,2,1"]);
+    assert_eq!(view, query);
+
+    // Nothing new to apply
+    let refresh = db.refresh_view(&view_path).await.unwrap();
+    assert_eq!(refresh.rows_applied, 0);
+    assert_eq!(
+        ViewDefinition::load(&view_path).unwrap().base_version,
+        refresh.base_version
+    );
+
+    // A full refresh arrives at the same rows
+    let refresh = db.refresh_view_full(&view_path).await.unwrap();
+    assert!(refresh.full_refresh_reason.is_some());
+    let (view, query) = view_and_query(&db, &view_path, TOTALS).await;
+    assert_eq!(view, query);
+}
+
+#[tokio::test]
+async fn test_filtered_view_ignores_other_rows() {
+    let temp_dir = TempDir::new().unwrap();
+    let db = create_db(&temp_dir.path().join("orders")).await;
+    let view_path = temp_dir.path().join("big_orders");
+    let sql = "SELECT region, COUNT(*) AS orders FROM data WHERE amount >= 7 GROUP BY region";
+
+    db.create_materialized_view(&view_path, sql).await.unwrap();
+    db.insert(orders(&[
+        ("east", "gus", Some(8)),
+        ("east", "hal", Some(1)),
+    ]))
+    .await
+    .unwrap();
+    db.update_rows(&[("amount", "20")], "customer = 'bob'")
+        .await
+        .unwrap();
+    let (view, query) = view_and_query(&db, &view_path, sql).await;
+    assert_eq!(view, vec!["east,3", "west,1"]);
+    assert_eq!(view, query);
+}
+
+#[tokio::test]
+async fn test_non_maintainable_aggregate_falls_back_to_full_refresh() {
+    let temp_dir = TempDir::new().unwrap();
+    let db = create_db(&temp_dir.path().join("orders")).await;
+    let view_path = temp_dir.path().join("customers");
+    let sql = "SELECT region, COUNT(DISTINCT customer) AS customers FROM data GROUP BY region";
+
+    let definition = db.create_materialized_view(&view_path, sql).await.unwrap();
+    assert!(!definition.is_incremental());
+    assert!(
+        definition
+            .full_refresh_reason
+            .as_deref()
+            .unwrap()
+            .contains("can't be maintained incrementally"),
+        "{:?}",
+        definition.full_refresh_reason
+    );
+
+    // Recomputed on commit: a repeat customer doesn't count twice
+    db.insert(orders(&[
+        ("east", "ann", Some(1)),
+        ("west", "ann", Some(2)),
+    ]))
+    .await
+    .unwrap();
+    let (view, query) = view_and_query(&db, &view_path, sql).await;
+    assert_eq!(view, vec!["east,2", "west,2"]);
+    assert_eq!(view, query);
+
+    db.delete_rows_where("customer = 'cat'").await.unwrap();
+    let refresh = db.refresh_view(&view_path).await.unwrap();
+    assert_eq!(refresh.full_refresh_reason, definition.full_refresh_reason);
+    let (view, query) = view_and_query(&db, &view_path, sql).await;
+    assert_eq!(view, vec!["east,2", "west,1"]);
+    assert_eq!(view, query);
+}
+
+#[tokio::test]
+async fn test_view_columns_must_be_named() {
+    let temp_dir = TempDir::new().unwrap();
+    let db = create_db(&temp_dir.path().join("orders")).await;
+
+    let err = db
+        .create_materialized_view(
+            temp_dir.path().join("unnamed"),
+            "SELECT region, SUM(amount) FROM data GROUP BY region",
+        )
+        .await
+        .unwrap_err();
+    assert!(err.to_string().contains("name it with AS"), "{}", err);
+}
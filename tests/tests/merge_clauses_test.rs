@@ -0,0 +1,155 @@
+// MERGE Clause Integration Tests
+// Tests that WHEN MATCHED clauses apply in order, first match wins, that
+// updates keep the columns they don't set, and WHEN NOT MATCHED BY SOURCE
+// clauses for tombstoning or deleting target rows missing from the source
+
+use arrow::array::{ArrayRef, BooleanArray, Int32Array, RecordBatch, StringArray};
+use arrow::datatypes::{DataType, Field, Schema, SchemaRef};
+use fsdb::DatabaseOps;
+use std::path::Path;
+use std::sync::Arc;
+use tempfile::TempDir;
+
+fn create_schema() -> SchemaRef {
+    Arc::new(Schema::new(vec![
+        Field::new("id", DataType::Int32, false),
+        Field::new("name", DataType::Utf8, false),
+        Field::new("deleted", DataType::Boolean, false),
+    ]))
+}
+
+/// Changes as (id, name, op)
+fn source(rows: &[(i32, &str, &str)]) -> RecordBatch {
+    let schema = Arc::new(Schema::new(vec![
+        Field::new("id", DataType::Int32, false),
+        Field::new("name", DataType::Utf8, false),
+        Field::new("_op", DataType::Utf8, false),
+    ]));
+    RecordBatch::try_new(
+        schema,
+        vec![
+            Arc::new(Int32Array::from_iter_values(rows.iter().map(|r| r.0))) as ArrayRef,
+            Arc::new(StringArray::from_iter_values(rows.iter().map(|r| r.1))) as ArrayRef,
+            Arc::new(StringArray::from_iter_values(rows.iter().map(|r| r.2))) as ArrayRef,
+        ],
+    )
+    .unwrap()
+}
+
+/// Rows 1 to 4, none deleted
+async fn create_db(path: &Path) -> DatabaseOps {
+    let db = DatabaseOps::create(path, create_schema()).await.unwrap();
+    let batch = RecordBatch::try_new(
+        create_schema(),
+        vec![
+            Arc::new(Int32Array::from(vec![1, 2, 3, 4])) as ArrayRef,
+            Arc::new(StringArray::from(vec!["ann", "bob", "cal", "dee"])) as ArrayRef,
+            Arc::new(BooleanArray::from(vec![false; 4])) as ArrayRef,
+        ],
+    )
+    .unwrap();
+    db.insert(batch).await.unwrap();
+    db
+}
+
+/// Every row as "id,name,deleted"
+async fn rows(db: &DatabaseOps) -> Vec<String> {
+    let rows = db
+        .query_rows("SELECT id, name, deleted FROM data ORDER BY id")
+        .await
+        .unwrap();
+    rows.rows()
+        .iter()
+        .map(|row| {
+            row.iter()
+                .map(|value| value.to_string())
+                .collect::<Vec<_>>()
+                .join(",")
+        })
+        .collect()
+}
+
+#[tokio::test]
+async fn test_first_matching_clause_wins() {
+    let temp_dir = TempDir::new().unwrap();
+    let db = create_db(&temp_dir.path().join("db")).await;
+
+    // Row 1 meets both the DELETE and the first UPDATE condition, and is
+    // deleted; row 2 meets neither and takes the unconditional UPDATE
+    let metrics = db
+        .merge()
+        .await
+        .unwrap()
+        .with_source(
+            source(&[(1, "ANN", "D"), (2, "BOB", "U"), (3, "CAL", "R")]),
+            "source",
+        )
+        .on("target.id = source.id")
+        .when_matched_delete()
+        .condition("source._op = 'D'")
+        .then()
+        .when_matched_update()
+        .condition("source._op IN ('D', 'R')")
+        .set("name", "lower(source.name)")
+        .then()
+        .when_matched_update()
+        .set("name", "source.name")
+        .then()
+        .when_matched_delete()
+        .then()
+        .execute()
+        .await
+        .unwrap();
+
+    assert_eq!((metrics.rows_deleted, metrics.rows_updated), (1, 2));
+    assert_eq!(
+        rows(&db).await,
+        vec!["2,BOB,false", "3,cal,false", "4,dee,false"]
+    );
+}
+
+#[tokio::test]
+async fn test_not_matched_by_source() {
+    let temp_dir = TempDir::new().unwrap();
+    let db = create_db(&temp_dir.path().join("db")).await;
+
+    // A full snapshot of rows 1, 2 and 5: row 3 is tombstoned, row 4 is
+    // removed outright, and the clause after the unconditional one is
+    // never reached
+    let metrics = db
+        .merge()
+        .await
+        .unwrap()
+        .with_source(
+            source(&[(1, "ann", "U"), (2, "BOB", "U"), (5, "eve", "I")]),
+            "source",
+        )
+        .on("target.id = source.id")
+        .when_matched_update()
+        .set("name", "source.name")
+        .then()
+        .when_not_matched_insert()
+        .value("id", "source.id")
+        .value("name", "source.name")
+        .value("deleted", "false")
+        .then()
+        .when_not_matched_by_source_delete()
+        .condition("target.name = 'dee'")
+        .then()
+        .when_not_matched_by_source_update()
+        .set("deleted", "true")
+        .then()
+        .when_not_matched_by_source_delete()
+        .then()
+        .execute()
+        .await
+        .unwrap();
+
+    assert_eq!(metrics.rows_inserted, 1);
+    assert_eq!(metrics.rows_updated, 3);
+    assert_eq!(metrics.rows_deleted, 1);
+    assert_eq!(
+        rows(&db).await,
+        vec!["1,ann,false", "2,BOB,false", "3,cal,true", "5,eve,false"]
+    );
+}
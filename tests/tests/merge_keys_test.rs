@@ -0,0 +1,201 @@
+// MERGE Key Integration Tests
+// Tests composite merge keys, that null keys never match, and that several
+// source rows matching one target row are rejected before anything is written
+
+use arrow::array::{ArrayRef, Int32Array, RecordBatch, StringArray};
+use arrow::datatypes::{DataType, Field, Schema, SchemaRef};
+use fsdb::DatabaseOps;
+use fsdb::Error;
+use fsdb::delta_lake::MergeMetrics;
+use std::path::Path;
+use std::sync::Arc;
+use tempfile::TempDir;
+
+fn create_schema() -> SchemaRef {
+    Arc::new(Schema::new(vec![
+        Field::new("region", DataType::Utf8, true),
+        Field::new("id", DataType::Int32, false),
+        Field::new("name", DataType::Utf8, false),
+    ]))
+}
+
+fn batch(rows: &[(Option<&str>, i32, &str)]) -> RecordBatch {
+    RecordBatch::try_new(
+        create_schema(),
+        vec![
+            Arc::new(StringArray::from_iter(rows.iter().map(|r| r.0))) as ArrayRef,
+            Arc::new(Int32Array::from_iter_values(rows.iter().map(|r| r.1))) as ArrayRef,
+            Arc::new(StringArray::from_iter_values(rows.iter().map(|r| r.2))) as ArrayRef,
+        ],
+    )
+    .unwrap()
+}
+
+/// Ids repeat across regions, so only (region, id) identifies a row
+async fn create_db(path: &Path) -> DatabaseOps {
+    let db = DatabaseOps::create(path, create_schema()).await.unwrap();
+    db.insert(batch(&[
+        (Some("east"), 1, "ann"),
+        (Some("west"), 1, "bea"),
+        (Some("east"), 2, "cal"),
+        (None, 3, "dee"),
+    ]))
+    .await
+    .unwrap();
+    db
+}
+
+/// Every row as "region,id,name", nulls empty
+async fn rows(db: &DatabaseOps) -> Vec<String> {
+    let rows = db
+        .query_rows("SELECT region, id, name FROM data ORDER BY region NULLS FIRST, id, name")
+        .await
+        .unwrap();
+    rows.rows()
+        .iter()
+        .map(|row| {
+            row.iter()
+                .map(|value| value.to_string())
+                .collect::<Vec<_>>()
+                .join(",")
+        })
+        .collect()
+}
+
+async fn upsert(db: &DatabaseOps, source: RecordBatch) -> fsdb::Result<MergeMetrics> {
+    db.merge()
+        .await?
+        .with_source(source, "source")
+        .on("target.region = source.region AND target.id = source.id")
+        .when_matched_update()
+        .set_all()
+        .when_not_matched_insert()
+        .values_all()
+        .execute()
+        .await
+}
+
+#[tokio::test]
+async fn test_composite_key_matches_on_every_column() {
+    let temp_dir = TempDir::new().unwrap();
+    let db = create_db(&temp_dir.path().join("db")).await;
+
+    // Only west/1 is replaced, not every row with id 1
+    let metrics = upsert(
+        &db,
+        batch(&[(Some("west"), 1, "bea2"), (Some("north"), 1, "eve")]),
+    )
+    .await
+    .unwrap();
+    assert_eq!((metrics.rows_updated, metrics.rows_inserted), (1, 1));
+    assert_eq!(
+        rows(&db).await,
+        vec![
+            ",3,dee",
+            "east,1,ann",
+            "east,2,cal",
+            "north,1,eve",
+            "west,1,bea2"
+        ]
+    );
+
+    // Conditions given a column at a time make the same key
+    let metrics = db
+        .merge()
+        .await
+        .unwrap()
+        .with_source(batch(&[(Some("east"), 2, "")]), "source")
+        .on("target.region = source.region")
+        .on("target.id = source.id")
+        .when_matched_delete()
+        .then()
+        .execute()
+        .await
+        .unwrap();
+    assert_eq!(metrics.rows_deleted, 1);
+    assert_eq!(
+        rows(&db).await,
+        vec![",3,dee", "east,1,ann", "north,1,eve", "west,1,bea2"]
+    );
+}
+
+#[tokio::test]
+async fn test_null_keys_never_match() {
+    let temp_dir = TempDir::new().unwrap();
+    let db = create_db(&temp_dir.path().join("db")).await;
+
+    // The source row with a null region is inserted like any new row, and
+    // the target row with one is left alone
+    let metrics = upsert(&db, batch(&[(None, 3, "fay"), (None, 3, "gus")]))
+        .await
+        .unwrap();
+    assert_eq!((metrics.rows_updated, metrics.rows_inserted), (0, 2));
+    assert_eq!(
+        rows(&db).await,
+        vec![
+            ",3,dee",
+            ",3,fay",
+            ",3,gus",
+            "east,1,ann",
+            "east,2,cal",
+            "west,1,bea"
+        ]
+    );
+}
+
+#[tokio::test]
+async fn test_ambiguous_source_rejected() {
+    let temp_dir = TempDir::new().unwrap();
+    let db = create_db(&temp_dir.path().join("db")).await;
+    let before = rows(&db).await;
+
+    match upsert(
+        &db,
+        batch(&[
+            (Some("east"), 1, "x"),
+            (Some("east"), 1, "y"),
+            (Some("south"), 9, "z"),
+        ]),
+    )
+    .await
+    {
+        Err(Error::AmbiguousMerge { key, matches }) => {
+            assert_eq!(key, "region = 'east', id = 1");
+            assert_eq!(matches, 2);
+        }
+        other => panic!("expected AmbiguousMerge, got {:?}", other),
+    }
+    assert_eq!(rows(&db).await, before);
+
+    // Duplicate source keys matching no target row are only inserted
+    let metrics = upsert(
+        &db,
+        batch(&[(Some("south"), 9, "x"), (Some("south"), 9, "y")]),
+    )
+    .await
+    .unwrap();
+    assert_eq!(metrics.rows_inserted, 2);
+}
+
+#[tokio::test]
+async fn test_key_columns_must_exist() {
+    let temp_dir = TempDir::new().unwrap();
+    let db = create_db(&temp_dir.path().join("db")).await;
+
+    for condition in ["target.area = source.region", "target.id = source.user_id"] {
+        let result = db
+            .merge()
+            .await
+            .unwrap()
+            .with_source(batch(&[(Some("east"), 1, "x")]), "source")
+            .on(condition)
+            .when_not_matched_insert()
+            .values_all()
+            .execute()
+            .await;
+        match result {
+            Err(Error::InvalidOperation(message)) => assert!(message.contains("MERGE key column")),
+            other => panic!("expected InvalidOperation, got {:?}", other),
+        }
+    }
+}
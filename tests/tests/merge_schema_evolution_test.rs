@@ -0,0 +1,142 @@
+// MERGE Schema Evolution Integration Tests
+// Tests that a source with new columns fails a merge unless schema evolution
+// is enabled, in which case the columns are added to the target and rows
+// written before the merge read them as NULL
+
+use arrow::array::{ArrayRef, Int32Array, RecordBatch, StringArray};
+use arrow::datatypes::{DataType, Field, Schema, SchemaRef};
+use fsdb::DatabaseOps;
+use fsdb::Error;
+use std::sync::Arc;
+use tempfile::TempDir;
+
+fn create_schema() -> SchemaRef {
+    Arc::new(Schema::new(vec![
+        Field::new("id", DataType::Int32, false),
+        Field::new("name", DataType::Utf8, false),
+    ]))
+}
+
+/// Rows with an `email` column the table doesn't have
+fn source(rows: &[(i32, &str, &str)]) -> RecordBatch {
+    let schema = Arc::new(Schema::new(vec![
+        Field::new("id", DataType::Int32, false),
+        Field::new("name", DataType::Utf8, false),
+        Field::new("email", DataType::Utf8, true),
+    ]));
+    RecordBatch::try_new(
+        schema,
+        vec![
+            Arc::new(Int32Array::from_iter_values(rows.iter().map(|r| r.0))) as ArrayRef,
+            Arc::new(StringArray::from_iter_values(rows.iter().map(|r| r.1))) as ArrayRef,
+            Arc::new(StringArray::from_iter_values(rows.iter().map(|r| r.2))) as ArrayRef,
+        ],
+    )
+    .unwrap()
+}
+
+async fn create_db(path: &std::path::Path) -> DatabaseOps {
+    let db = DatabaseOps::create(path, create_schema()).await.unwrap();
+    let batch = RecordBatch::try_new(
+        create_schema(),
+        vec![
+            Arc::new(Int32Array::from(vec![1, 2])) as ArrayRef,
+            Arc::new(StringArray::from(vec!["ann", "bob"])) as ArrayRef,
+        ],
+    )
+    .unwrap();
+    db.insert(batch).await.unwrap();
+    db
+}
+
+/// Every row as "id,name,email", nulls empty
+async fn rows(db: &DatabaseOps) -> Vec<String> {
+    let rows = db
+        .query_rows("SELECT id, name, email FROM data ORDER BY id")
+        .await
+        .unwrap();
+    rows.rows()
+        .iter()
+        .map(|row| {
+            row.iter()
+                .map(|value| value.to_string())
+                .collect::<Vec<_>>()
+                .join(",")
+        })
+        .collect()
+}
+
+#[tokio::test]
+async fn test_new_columns_rejected_by_default() {
+    let temp_dir = TempDir::new().unwrap();
+    let db = create_db(&temp_dir.path().join("db")).await;
+    let version = db.get_delta_table().await.unwrap().version();
+
+    let result = db
+        .merge()
+        .await
+        .unwrap()
+        .with_source(source(&[(3, "cal", "cal@example.com")]), "source")
+        .on("target.id = source.id")
+        .when_not_matched_insert()
+        .values_all()
+        .execute()
+        .await;
+    match result {
+        Err(Error::InvalidOperation(message)) => assert!(message.contains("email")),
+        other => panic!("expected the new column to be rejected, got {:?}", other),
+    }
+    assert_eq!(db.get_delta_table().await.unwrap().version(), version);
+
+    // Source columns only used in conditions don't count
+    db.merge()
+        .await
+        .unwrap()
+        .with_source(source(&[(3, "cal", "cal@example.com")]), "source")
+        .on("target.id = source.id")
+        .when_not_matched_insert()
+        .condition("source.email IS NOT NULL")
+        .value("id", "source.id")
+        .value("name", "source.name")
+        .then()
+        .execute()
+        .await
+        .unwrap();
+    assert_eq!(db.table_schema().await.unwrap().fields().len(), 2);
+}
+
+#[tokio::test]
+async fn test_schema_evolution_adds_columns() {
+    let temp_dir = TempDir::new().unwrap();
+    let db = create_db(&temp_dir.path().join("db")).await;
+
+    let metrics = db
+        .merge()
+        .await
+        .unwrap()
+        .with_schema_evolution(true)
+        .with_source(
+            source(&[(2, "bob", "bob@example.com"), (3, "cal", "cal@example.com")]),
+            "source",
+        )
+        .on("target.id = source.id")
+        .when_matched_update()
+        .set_all()
+        .when_not_matched_insert()
+        .values_all()
+        .execute()
+        .await
+        .unwrap();
+    assert_eq!((metrics.rows_updated, metrics.rows_inserted), (1, 1));
+
+    let schema = db.table_schema().await.unwrap();
+    assert!(schema.field_with_name("email").unwrap().is_nullable());
+    assert_eq!(
+        rows(&db).await,
+        vec![
+            "1,ann,".to_string(),
+            "2,bob,bob@example.com".to_string(),
+            "3,cal,cal@example.com".to_string(),
+        ]
+    );
+}
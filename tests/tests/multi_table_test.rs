@@ -0,0 +1,211 @@
+// Multi-Table Integration Tests
+// Tests named tables next to data: creating, listing and dropping them,
+// writing through their handles, joining them with data in SQL, hiding
+// tables from users whose roles can't read them and rejecting names that
+// would lead outside the database
+
+use arrow::array::{ArrayRef, Int32Array, StringArray};
+use arrow::datatypes::{DataType, Field, Schema, SchemaRef};
+use arrow::record_batch::RecordBatch;
+use fsdb::DatabaseOps;
+use fsdb::Error;
+use fsdb::query::{CastMode, CastView, Value};
+use fsdb::security::{Permission, Role, RoleManager};
+use std::sync::Arc;
+use tempfile::TempDir;
+
+fn customer_schema() -> SchemaRef {
+    Arc::new(Schema::new(vec![
+        Field::new("id", DataType::Int32, false),
+        Field::new("name", DataType::Utf8, false),
+    ]))
+}
+
+fn order_schema() -> SchemaRef {
+    Arc::new(Schema::new(vec![
+        Field::new("order_id", DataType::Int32, false),
+        Field::new("customer_id", DataType::Int32, false),
+        Field::new("amount", DataType::Int32, false),
+    ]))
+}
+
+fn customers() -> RecordBatch {
+    RecordBatch::try_new(
+        customer_schema(),
+        vec![
+            Arc::new(Int32Array::from(vec![1, 2])) as ArrayRef,
+            Arc::new(StringArray::from(vec!["ada", "bob"])) as ArrayRef,
+        ],
+    )
+    .unwrap()
+}
+
+fn orders() -> RecordBatch {
+    RecordBatch::try_new(
+        order_schema(),
+        vec![
+            Arc::new(Int32Array::from(vec![10, 11, 12])) as ArrayRef,
+            Arc::new(Int32Array::from(vec![1, 1, 2])) as ArrayRef,
+            Arc::new(Int32Array::from(vec![5, 7, 3])) as ArrayRef,
+        ],
+    )
+    .unwrap()
+}
+
+#[tokio::test]
+async fn test_create_list_and_join() {
+    let temp_dir = TempDir::new().unwrap();
+    let path = temp_dir.path().join("db");
+    let db = DatabaseOps::create(&path, customer_schema()).await.unwrap();
+    db.insert(customers()).await.unwrap();
+    assert!(db.list_tables().unwrap().is_empty());
+
+    let order_table = db.create_table("orders", order_schema()).await.unwrap();
+    order_table.insert(orders()).await.unwrap();
+    db.create_table("refunds", order_schema()).await.unwrap();
+    assert_eq!(db.list_tables().unwrap(), vec!["orders", "refunds"]);
+
+    // Each table reads its own rows
+    let rows = order_table
+        .query_rows("SELECT COUNT(*) AS n FROM data")
+        .await
+        .unwrap();
+    assert_eq!(rows.get(0, "n").unwrap(), &Value::Int(3));
+    let rows = db
+        .query_rows("SELECT COUNT(*) AS n FROM data")
+        .await
+        .unwrap();
+    assert_eq!(rows.get(0, "n").unwrap(), &Value::Int(2));
+
+    // Any handle can join them by name
+    let sql = "SELECT d.name, SUM(o.amount) AS total FROM data d \
+               JOIN orders o ON o.customer_id = d.id GROUP BY d.name ORDER BY d.name";
+    let rows = db.query_rows(sql).await.unwrap();
+    assert_eq!(rows.len(), 2);
+    assert_eq!(rows.get(0, "total").unwrap(), &Value::Int(12));
+    assert_eq!(rows.get(1, "total").unwrap(), &Value::Int(3));
+
+    // Tables persist, found again from the directory on reopen
+    let reopened = DatabaseOps::open(&path).await.unwrap();
+    assert_eq!(reopened.list_tables().unwrap(), vec!["orders", "refunds"]);
+    let handle = reopened.table("orders").await.unwrap();
+    assert_eq!(handle.schema.fields().len(), 3);
+    assert!(reopened.table("missing").await.is_err());
+}
+
+#[tokio::test]
+async fn test_drop_and_invalid_tables() {
+    let temp_dir = TempDir::new().unwrap();
+    let path = temp_dir.path().join("db");
+    let db = DatabaseOps::create(&path, customer_schema()).await.unwrap();
+    db.insert(customers()).await.unwrap();
+    db.create_table("orders", order_schema())
+        .await
+        .unwrap()
+        .insert(orders())
+        .await
+        .unwrap();
+
+    // Names must be plain identifiers, unused by tables and cast views
+    for name in ["data", "Orders", "a-b", "../escape", "orders"] {
+        assert!(
+            db.create_table(name, order_schema()).await.is_err(),
+            "{}",
+            name
+        );
+    }
+    db.create_cast_view("typed", CastView::new(CastMode::Lenient))
+        .await
+        .unwrap();
+    assert!(db.create_table("typed", order_schema()).await.is_err());
+    assert!(
+        db.create_cast_view("orders", CastView::new(CastMode::Lenient))
+            .await
+            .is_err()
+    );
+
+    assert!(db.drop_table("orders").await.unwrap());
+    assert!(!db.drop_table("orders").await.unwrap());
+    assert!(db.list_tables().unwrap().is_empty());
+    assert!(!path.join("_tables").join("orders").exists());
+    assert!(db.query_rows("SELECT * FROM orders").await.is_err());
+
+    // The main table is untouched
+    let rows = db
+        .query_rows("SELECT COUNT(*) AS n FROM data")
+        .await
+        .unwrap();
+    assert_eq!(rows.get(0, "n").unwrap(), &Value::Int(2));
+}
+
+#[tokio::test]
+async fn test_table_names_cant_leave_the_database() {
+    let temp_dir = TempDir::new().unwrap();
+    let path = temp_dir.path().join("db");
+    let db = DatabaseOps::create(&path, customer_schema()).await.unwrap();
+    DatabaseOps::create(temp_dir.path().join("other"), order_schema())
+        .await
+        .unwrap();
+
+    // ".." would be the main table, "../../other" a table outside the database
+    for name in ["..", "../../other", "data", ""] {
+        match db.table(name).await {
+            Err(Error::InvalidOperation(message)) => {
+                assert!(message.contains("Invalid table name"), "{}", message)
+            }
+            Err(e) => panic!("expected {:?} to be rejected as a name, got {}", name, e),
+            Ok(_) => panic!("{:?} opened a table", name),
+        }
+    }
+}
+
+#[tokio::test]
+async fn test_tables_hidden_by_role() {
+    let temp_dir = TempDir::new().unwrap();
+    let path = temp_dir.path().join("db");
+    let admin = DatabaseOps::create_with_auth(&path, customer_schema(), true)
+        .await
+        .unwrap();
+    admin.insert(customers()).await.unwrap();
+    admin
+        .create_table("orders", order_schema())
+        .await
+        .unwrap()
+        .insert(orders())
+        .await
+        .unwrap();
+    admin
+        .create_table("payroll", customer_schema())
+        .await
+        .unwrap();
+    admin
+        .create_user("alice", "secret", &["sales"])
+        .await
+        .unwrap();
+
+    let mut roles = RoleManager::new();
+    roles.add_role(
+        Role::new("sales".to_string(), vec![Permission::Read])
+            .with_tables(vec!["orders".to_string()]),
+    );
+    let alice = DatabaseOps::open_with_credentials(&path, Some(("alice", "secret")))
+        .await
+        .unwrap()
+        .with_role_manager(roles);
+
+    assert_eq!(alice.list_tables().unwrap(), vec!["orders"]);
+    let rows = alice
+        .query_rows("SELECT COUNT(*) AS n FROM orders")
+        .await
+        .unwrap();
+    assert_eq!(rows.get(0, "n").unwrap(), &Value::Int(3));
+    assert!(alice.query_rows("SELECT * FROM payroll").await.is_err());
+    assert!(alice.table("payroll").await.is_err());
+
+    // Handles act as the same user: reading is allowed, writing and DDL aren't
+    let handle = alice.table("orders").await.unwrap();
+    assert!(handle.query_rows("SELECT * FROM data").await.is_ok());
+    assert!(handle.insert(orders()).await.is_err());
+    assert!(alice.create_table("notes", order_schema()).await.is_err());
+    assert!(alice.drop_table("orders").await.is_err());
+}
@@ -3,8 +3,10 @@
 
 use arrow::array::{Int32Array, Int64Array, RecordBatch, StringArray};
 use arrow::datatypes::{DataType, Field, Schema};
-use fsdb::DatabaseOps;
 use fsdb::nfs::NfsServer;
+use fsdb::nfs::cache_warmer::WarmerConfig;
+use fsdb::nfs::file_ids::FileIdLayout;
+use fsdb::{DatabaseOps, Error};
 use serial_test::serial;
 use std::path::Path;
 use std::sync::Arc;
@@ -120,6 +122,116 @@ async fn test_nfs_server_starts_and_stops() {
     server.shutdown().await.unwrap();
 }
 
+#[tokio::test]
+#[serial]
+async fn test_nfs_server_rejects_overlapping_file_ids() {
+    init_logging();
+
+    let temp_dir = TempDir::new().unwrap();
+    let schema = Arc::new(Schema::new(vec![Field::new("id", DataType::Int32, false)]));
+    let db = DatabaseOps::create(temp_dir.path().join("test_db"), schema)
+        .await
+        .unwrap();
+    let db = Arc::new(db);
+
+    // Created directories would reuse IDs handed to Parquet files
+    let layout = FileIdLayout {
+        parquet_files: 100..1000,
+        created_dirs: 900..2000,
+        ..Default::default()
+    };
+    let port = create_unique_port(12049);
+
+    match NfsServer::with_file_id_layout(db, port, layout).await {
+        Err(Error::InvalidOperation(msg)) => {
+            assert!(msg.contains("overlap"), "unexpected error: {}", msg);
+        }
+        Err(e) => panic!("expected InvalidOperation, got {}", e),
+        Ok(_) => panic!("server started with overlapping file ID ranges"),
+    }
+}
+
+#[tokio::test]
+#[serial]
+async fn test_nfs_server_with_shifted_file_ids() {
+    init_logging();
+
+    let temp_dir = TempDir::new().unwrap();
+    let schema = Arc::new(Schema::new(vec![Field::new("id", DataType::Int32, false)]));
+    let db = DatabaseOps::create(temp_dir.path().join("test_db"), schema.clone())
+        .await
+        .unwrap();
+    let batch = RecordBatch::try_new(schema, vec![Arc::new(Int32Array::from(vec![1, 2]))]).unwrap();
+    db.insert(batch).await.unwrap();
+    let db = Arc::new(db);
+
+    let layout = FileIdLayout::with_base(1 << 40).unwrap();
+    let port = create_unique_port(12049);
+    let server = NfsServer::with_file_id_layout(db, port, layout)
+        .await
+        .unwrap();
+
+    // Paths resolve the same way regardless of the IDs behind them
+    let entries = server.readdir("/data").await.unwrap();
+    assert!(entries.contains(&"data.csv".to_string()));
+    assert!(server.getattr("/data").await.unwrap().is_dir);
+    let content = server.read_file("/data/data.csv", 0, 1024).await.unwrap();
+    assert!(String::from_utf8_lossy(&content).starts_with("id"));
+
+    server.shutdown().await.unwrap();
+}
+
+#[tokio::test]
+#[serial]
+async fn test_nfs_cache_warmed_after_commit() {
+    use std::time::Duration;
+
+    init_logging();
+
+    let temp_dir = TempDir::new().unwrap();
+    let schema = Arc::new(Schema::new(vec![Field::new("id", DataType::Int32, false)]));
+    let db = DatabaseOps::create(temp_dir.path().join("test_db"), schema.clone())
+        .await
+        .unwrap();
+    let db = Arc::new(db);
+
+    let port = create_unique_port(12049);
+    let config = WarmerConfig {
+        poll_interval: Duration::from_millis(20),
+        min_interval: Duration::ZERO,
+        quiet_period: Duration::from_millis(50),
+        max_entry_bytes: None,
+    };
+    let server = NfsServer::new(db.clone(), port)
+        .await
+        .unwrap()
+        .with_cache_warming(config);
+
+    // Commit directly to the database; no client has read data.csv
+    let batch =
+        RecordBatch::try_new(schema, vec![Arc::new(Int32Array::from(vec![7, 8, 9]))]).unwrap();
+    db.insert(batch).await.unwrap();
+
+    let mut warmed = None;
+    for _ in 0..100 {
+        if let Some(content) = server.cache().get("csv:data").await.unwrap() {
+            if String::from_utf8_lossy(&content).contains('9') {
+                warmed = Some(content);
+                break;
+            }
+        }
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+    let warmed = warmed.expect("data.csv should be cached after the commit settles");
+    assert_eq!(String::from_utf8_lossy(&warmed), "id\n7\n8\n9\n");
+
+    // The first client read is served from the warmed entry
+    let content = server.read_file("/data/data.csv", 0, 1024).await.unwrap();
+    assert_eq!(content, warmed);
+
+    server.shutdown().await.unwrap();
+}
+
 #[tokio::test]
 #[serial]
 async fn test_nfs_server_exports_database() {
@@ -330,6 +442,142 @@ async fn test_nfs_file_attributes() {
     server.shutdown().await.unwrap();
 }
 
+#[tokio::test]
+#[serial]
+async fn test_nfs_chunked_write_then_commit() {
+    let temp_dir = TempDir::new().unwrap();
+    let schema = Arc::new(Schema::new(vec![
+        Field::new("id", DataType::Int32, false),
+        Field::new("name", DataType::Utf8, false),
+    ]));
+    let db = DatabaseOps::create(temp_dir.path().join("test_db"), schema)
+        .await
+        .unwrap();
+    let batch = RecordBatch::try_new(
+        db.schema(),
+        vec![
+            Arc::new(Int32Array::from(vec![1])),
+            Arc::new(StringArray::from(vec!["Alice"])),
+        ],
+    )
+    .unwrap();
+    db.insert(batch).await.unwrap();
+
+    let port = create_unique_port(12049);
+    let server = fsdb::nfs::NfsServer::new(Arc::new(db), port).await.unwrap();
+    let size = server
+        .read_file("/data/data.csv", 0, 1024)
+        .await
+        .unwrap()
+        .len() as u64;
+
+    // An append split mid-row across two WRITE calls, the last row unterminated
+    server
+        .write_file("/data/data.csv", size, b"2,Bo")
+        .await
+        .unwrap();
+    server
+        .write_file("/data/data.csv", size + 4, b"b\n3,Carol")
+        .await
+        .unwrap();
+
+    // Nothing is applied while the write is incomplete
+    let content = server.read_file("/data/data.csv", 0, 1024).await.unwrap();
+    assert_eq!(String::from_utf8_lossy(&content), "id,name\n1,Alice\n");
+
+    server.commit_file("/data/data.csv").await.unwrap();
+    let content = server.read_file("/data/data.csv", 0, 1024).await.unwrap();
+    assert_eq!(
+        String::from_utf8_lossy(&content),
+        "id,name\n1,Alice\n2,Bob\n3,Carol\n"
+    );
+
+    // COMMIT with nothing pending succeeds and changes nothing
+    server.commit_file("/data/data.csv").await.unwrap();
+    let content = server.read_file("/data/data.csv", 0, 1024).await.unwrap();
+    assert_eq!(content.len(), 30);
+
+    server.shutdown().await.unwrap();
+}
+
+#[tokio::test]
+#[serial]
+async fn test_nfs_write_into_partition_directory() {
+    let temp_dir = TempDir::new().unwrap();
+    let schema = Arc::new(Schema::new(vec![
+        Field::new("id", DataType::Int32, false),
+        Field::new("name", DataType::Utf8, false),
+        Field::new("region", DataType::Utf8, true),
+    ]));
+    let db = DatabaseOps::create_partitioned(temp_dir.path().join("test_db"), schema, &["region"])
+        .await
+        .unwrap();
+    let batch = RecordBatch::try_new(
+        db.schema(),
+        vec![
+            Arc::new(Int32Array::from(vec![1, 2])),
+            Arc::new(StringArray::from(vec!["Alice", "Bob"])),
+            Arc::new(StringArray::from(vec!["eu", "us"])),
+        ],
+    )
+    .unwrap();
+    db.insert(batch).await.unwrap();
+
+    let port = create_unique_port(12049);
+    let server = fsdb::nfs::NfsServer::new(Arc::new(db), port).await.unwrap();
+    let entries = server.readdir("/data").await.unwrap();
+    assert!(entries.contains(&"region=eu".to_string()), "{:?}", entries);
+    assert!(entries.contains(&"region=us".to_string()), "{:?}", entries);
+    assert_eq!(
+        server.readdir("/data/region=eu").await.unwrap(),
+        vec!["data.csv".to_string()]
+    );
+
+    // Rows written to a partition's data.csv take the partition from the path
+    server
+        .write_file("/data/region=eu/data.csv", 0, b"id,name\n3,Carol\n4,Dave\n")
+        .await
+        .unwrap();
+    let content = server
+        .read_file("/data/region=eu/data.csv", 0, 1024)
+        .await
+        .unwrap();
+    let mut lines: Vec<String> = String::from_utf8_lossy(&content)
+        .lines()
+        .map(str::to_string)
+        .collect();
+    lines.sort();
+    assert_eq!(
+        lines,
+        vec!["1,Alice,eu", "3,Carol,eu", "4,Dave,eu", "id,name,region"]
+    );
+
+    // A row for another partition is rejected and nothing is written
+    let result = server
+        .write_file("/data/region=eu/data.csv", 0, b"5,Erin,eu\n6,Frank,us\n")
+        .await;
+    assert!(result.is_err());
+    let content = server.read_file("/data/data.csv", 0, 1024).await.unwrap();
+    assert_eq!(String::from_utf8_lossy(&content).lines().count(), 5);
+
+    // New partitions start with a directory
+    server.mkdir("/data/region=apac").await.unwrap();
+    server
+        .write_file("/data/region=apac/data.csv", 0, b"7,Grace\n")
+        .await
+        .unwrap();
+    let content = server
+        .read_file("/data/region=apac/data.csv", 0, 1024)
+        .await
+        .unwrap();
+    assert_eq!(
+        String::from_utf8_lossy(&content),
+        "id,name,region\n7,Grace,apac\n"
+    );
+
+    server.shutdown().await.unwrap();
+}
+
 /// Helper to mount NFS using OS command
 /// Requires passwordless sudo (configured via scripts/setup_nfs_sudo.py)
 async fn mount_nfs_os(host: &str, port: u16, mount_point: &Path) -> Result<(), String> {
@@ -1773,6 +2021,12 @@ async fn test_nfs_file_deletion_truncates_table() {
         remove_result.err()
     );
 
+    assert_eq!(
+        remove_result.unwrap(),
+        3,
+        "Truncate should report 3 rows deleted"
+    );
+
     // Verify table is now empty (truncated)
     println!("[VERIFY] Checking table is truncated");
     let results_after = db_arc.query("SELECT * FROM data").await.unwrap();
@@ -2616,7 +2870,8 @@ async fn test_delete_performance_isolated() {
     let deleted_count = db
         .delete_rows_where("id IN (0, 1, 2, 3, 4, 5, 6, 7, 8, 9)")
         .await
-        .unwrap();
+        .unwrap()
+        .rows_affected;
     let direct_delete_duration = start.elapsed();
     println!("[TEST 1] Direct DELETE took: {:?}", direct_delete_duration);
     println!("[TEST 1] Deleted {} rows", deleted_count);
@@ -4337,11 +4592,15 @@ async fn test_nfs_cp_creates_file() {
     // macOS cp tries to copy extended attributes which NFS doesn't support
     // Use -X flag to not copy extended attributes
     #[cfg(target_os = "macos")]
-    let cp_args = vec!["-X", source_file.to_str().unwrap(), dest_file.to_str().unwrap()];
-    
+    let cp_args = vec![
+        "-X",
+        source_file.to_str().unwrap(),
+        dest_file.to_str().unwrap(),
+    ];
+
     #[cfg(not(target_os = "macos"))]
     let cp_args = vec![source_file.to_str().unwrap(), dest_file.to_str().unwrap()];
-    
+
     let output = tokio::process::Command::new("cp")
         .args(&cp_args)
         .output()
@@ -4389,3 +4648,195 @@ async fn test_nfs_cp_creates_file() {
         .expect("Unmount must succeed");
     server.shutdown().await.unwrap();
 }
+
+async fn read_json(server: &NfsServer, path: &str) -> serde_json::Value {
+    let content = server.read_file(path, 0, 1 << 20).await.unwrap();
+    serde_json::from_slice(&content).unwrap()
+}
+
+#[tokio::test]
+#[serial]
+async fn test_stats_json_lists_file_statistics() {
+    init_logging();
+
+    let temp_dir = TempDir::new().unwrap();
+    let schema = Arc::new(Schema::new(vec![
+        Field::new("id", DataType::Int32, false),
+        Field::new("name", DataType::Utf8, true),
+    ]));
+    let db = DatabaseOps::create(temp_dir.path().join("test_db"), schema.clone())
+        .await
+        .unwrap();
+    let batch = |ids: Vec<i32>, names: Vec<Option<&str>>| {
+        RecordBatch::try_new(
+            schema.clone(),
+            vec![
+                Arc::new(Int32Array::from(ids)),
+                Arc::new(StringArray::from(names)),
+            ],
+        )
+        .unwrap()
+    };
+    db.insert(batch(vec![1, 2], vec![Some("Alice"), None]))
+        .await
+        .unwrap();
+    db.insert(batch(vec![3, 4], vec![Some("Carol"), Some("Dave")]))
+        .await
+        .unwrap();
+    let db = Arc::new(db);
+
+    let port = create_unique_port(12049);
+    let server = NfsServer::new(db.clone(), port).await.unwrap();
+
+    let entries = server.readdir("/data").await.unwrap();
+    assert!(
+        entries.contains(&".stats.json".to_string()),
+        "{:?}",
+        entries
+    );
+
+    let stats = read_json(&server, "/data/.stats.json").await;
+    let files = stats["files"].as_array().unwrap();
+    assert_eq!(files.len(), 2, "{}", stats);
+
+    let mut ranges: Vec<(i64, i64, u64)> = files
+        .iter()
+        .map(|file| {
+            assert!(file["path"].as_str().unwrap().ends_with(".parquet"));
+            assert_eq!(file["num_records"], 2);
+            let id = &file["columns"]["id"];
+            let nulls = file["columns"]["name"]["null_count"].as_u64().unwrap();
+            (
+                id["min"].as_i64().unwrap(),
+                id["max"].as_i64().unwrap(),
+                nulls,
+            )
+        })
+        .collect();
+    ranges.sort();
+    assert_eq!(ranges, vec![(1, 2, 1), (3, 4, 0)]);
+
+    // A new version shows up in the next read
+    db.insert(batch(vec![5], vec![Some("Eve")])).await.unwrap();
+    let stats = read_json(&server, "/data/.stats.json").await;
+    assert_eq!(stats["files"].as_array().unwrap().len(), 3);
+
+    server.shutdown().await.unwrap();
+}
+
+#[tokio::test]
+#[serial]
+async fn test_named_tables_under_tables_dir() {
+    init_logging();
+
+    let temp_dir = TempDir::new().unwrap();
+    let schema = Arc::new(Schema::new(vec![
+        Field::new("id", DataType::Int32, false),
+        Field::new("name", DataType::Utf8, false),
+    ]));
+    let db = DatabaseOps::create(temp_dir.path().join("test_db"), schema.clone())
+        .await
+        .unwrap();
+    db.create_table("customers", schema.clone()).await.unwrap();
+    db.create_table("suppliers", schema).await.unwrap();
+    let db = Arc::new(db);
+
+    let port = create_unique_port(12049);
+    let server = NfsServer::new(db.clone(), port).await.unwrap();
+
+    assert!(
+        server
+            .readdir("/")
+            .await
+            .unwrap()
+            .contains(&"tables".to_string())
+    );
+    assert_eq!(
+        server.readdir("/tables").await.unwrap(),
+        vec!["customers", "suppliers"]
+    );
+
+    // Each table's data.csv writes to that table only
+    server
+        .write_file("/tables/customers/data.csv", 0, b"id,name\n1,Alice\n")
+        .await
+        .unwrap();
+    let content = server
+        .read_file("/tables/customers/data.csv", 0, 4096)
+        .await
+        .unwrap();
+    assert_eq!(String::from_utf8(content).unwrap(), "id,name\n1,Alice\n");
+    let content = server
+        .read_file("/tables/suppliers/data.csv", 0, 4096)
+        .await
+        .unwrap();
+    assert_eq!(String::from_utf8(content).unwrap(), "id,name\n");
+
+    let customers = db.table("customers").await.unwrap();
+    let batches = customers.query("SELECT * FROM data").await.unwrap();
+    assert_eq!(batches.iter().map(|b| b.num_rows()).sum::<usize>(), 1);
+    let entries = server.readdir("/tables/customers").await.unwrap();
+    assert!(
+        entries.iter().any(|name| name.ends_with(".parquet")),
+        "{:?}",
+        entries
+    );
+
+    server.shutdown().await.unwrap();
+}
+
+#[tokio::test]
+#[serial]
+async fn test_copying_csv_into_tables_creates_a_table() {
+    init_logging();
+
+    let temp_dir = TempDir::new().unwrap();
+    let schema = Arc::new(Schema::new(vec![
+        Field::new("id", DataType::Int32, false),
+        Field::new("name", DataType::Utf8, false),
+    ]));
+    let db = DatabaseOps::create(temp_dir.path().join("test_db"), schema)
+        .await
+        .unwrap();
+    let db = Arc::new(db);
+
+    let port = create_unique_port(12049);
+    let server = NfsServer::new(db.clone(), port).await.unwrap();
+
+    // cp mydata.csv /mnt/fsdb/tables/new_table/
+    server.mkdir("/tables/new_table").await.unwrap();
+    server
+        .create_file("/tables/new_table/mydata.csv")
+        .await
+        .unwrap();
+    server
+        .write_file(
+            "/tables/new_table/mydata.csv",
+            0,
+            b"sku,qty,shipped\nA-1,3,true\nB-2,5,false\n",
+        )
+        .await
+        .unwrap();
+    server
+        .commit_file("/tables/new_table/mydata.csv")
+        .await
+        .unwrap();
+
+    assert_eq!(server.readdir("/tables").await.unwrap(), vec!["new_table"]);
+    let table = db.table("new_table").await.unwrap();
+    let batches = table
+        .query("SELECT sku FROM data WHERE shipped AND qty > 2")
+        .await
+        .unwrap();
+    assert_eq!(batches.iter().map(|b| b.num_rows()).sum::<usize>(), 1);
+    let content = server
+        .read_file("/tables/new_table/data.csv", 0, 4096)
+        .await
+        .unwrap();
+    assert_eq!(
+        String::from_utf8(content).unwrap(),
+        "sku,qty,shipped\nA-1,3,true\nB-2,5,false\n"
+    );
+
+    server.shutdown().await.unwrap();
+}
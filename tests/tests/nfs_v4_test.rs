@@ -0,0 +1,444 @@
+// NFSv4 Integration Tests
+// Tests the NFSv4 server over TCP with raw RPC calls: NULL, reading
+// data.csv, stateful 4.0 opens whose writes are applied on CLOSE, and
+// byte-range lock conflicts between owners in a 4.1 session
+
+use arrow::array::{ArrayRef, Int32Array, RecordBatch, StringArray};
+use arrow::datatypes::{DataType, Field, Schema};
+use fsdb::DatabaseOps;
+use fsdb::nfs::NfsServer;
+use fsdb::nfs::v4::rpc;
+use fsdb::nfs::v4::status::nfsstat4;
+use fsdb::nfs::v4::xdr::{XdrReader, XdrWriter};
+use serial_test::serial;
+use std::sync::Arc;
+use tempfile::TempDir;
+use tokio::net::TcpStream;
+
+const OP_CLOSE: u32 = 4;
+const OP_LOCK: u32 = 12;
+const OP_LOOKUP: u32 = 15;
+const OP_OPEN: u32 = 18;
+const OP_OPEN_CONFIRM: u32 = 20;
+const OP_PUTROOTFH: u32 = 24;
+const OP_READ: u32 = 25;
+const OP_SETCLIENTID: u32 = 35;
+const OP_SETCLIENTID_CONFIRM: u32 = 36;
+const OP_WRITE: u32 = 38;
+const OP_EXCHANGE_ID: u32 = 42;
+const OP_CREATE_SESSION: u32 = 43;
+const OP_SEQUENCE: u32 = 53;
+
+const SHARE_ACCESS_BOTH: u32 = 3;
+const WRITE_LT: u32 = 2;
+
+fn create_unique_port(base_port: u16) -> u16 {
+    let thread_id = format!("{:?}", std::thread::current().id());
+    let hash: u16 = thread_id.bytes().map(|b| b as u16).sum::<u16>() % 10000;
+    base_port + hash
+}
+
+/// An NFS server of a table with Alice and Bob, also serving NFSv4
+async fn start(temp_dir: &TempDir) -> NfsServer {
+    let schema = Arc::new(Schema::new(vec![
+        Field::new("id", DataType::Int32, false),
+        Field::new("name", DataType::Utf8, false),
+    ]));
+    let db = DatabaseOps::create(temp_dir.path().join("db"), schema.clone())
+        .await
+        .unwrap();
+    let batch = RecordBatch::try_new(
+        schema,
+        vec![
+            Arc::new(Int32Array::from(vec![1, 2])) as ArrayRef,
+            Arc::new(StringArray::from(vec!["Alice", "Bob"])) as ArrayRef,
+        ],
+    )
+    .unwrap();
+    db.insert(batch).await.unwrap();
+
+    NfsServer::new(Arc::new(db), create_unique_port(16000))
+        .await
+        .unwrap()
+        .with_nfs4(0)
+        .await
+        .unwrap()
+}
+
+/// A connection making NFSv4 calls
+struct Client {
+    stream: TcpStream,
+    xid: u32,
+}
+
+impl Client {
+    async fn connect(server: &NfsServer) -> Self {
+        let stream = TcpStream::connect(server.nfs4_addr().unwrap())
+            .await
+            .unwrap();
+        Self { stream, xid: 0 }
+    }
+
+    /// Body of an accepted, successful reply to `procedure`
+    async fn call(&mut self, procedure: u32, args: &[u8]) -> Vec<u8> {
+        self.xid += 1;
+        let mut message = XdrWriter::new();
+        message
+            .u32(self.xid)
+            .u32(0)
+            .u32(2)
+            .u32(rpc::NFS_PROGRAM)
+            .u32(rpc::NFS_V4)
+            .u32(procedure)
+            // AUTH_NONE credential and verifier
+            .u32(0)
+            .opaque(&[])
+            .u32(0)
+            .opaque(&[])
+            .raw(args);
+        rpc::write_record(&mut self.stream, &message.into_inner())
+            .await
+            .unwrap();
+
+        let reply = rpc::read_record(&mut self.stream).await.unwrap().unwrap();
+        let mut reader = XdrReader::new(&reply);
+        assert_eq!(reader.u32(), Ok(self.xid));
+        // REPLY, MSG_ACCEPTED, verifier, SUCCESS
+        assert_eq!(reader.u32(), Ok(1));
+        assert_eq!(reader.u32(), Ok(0));
+        reader.u32().unwrap();
+        reader.opaque().unwrap();
+        assert_eq!(reader.u32(), Ok(0));
+        reader.remaining().to_vec()
+    }
+
+    /// Result of a COMPOUND of `num_ops` operations written by `ops`
+    async fn compound(
+        &mut self,
+        minor_version: u32,
+        num_ops: u32,
+        ops: impl FnOnce(&mut XdrWriter),
+    ) -> Vec<u8> {
+        let mut args = XdrWriter::new();
+        args.opaque(b"").u32(minor_version).u32(num_ops);
+        ops(&mut args);
+        self.call(rpc::PROC_COMPOUND, &args.into_inner()).await
+    }
+}
+
+/// Status of a COMPOUND result, and a reader at its operation results
+fn results(reply: &[u8]) -> (u32, XdrReader<'_>) {
+    let mut reader = XdrReader::new(reply);
+    let status = reader.u32().unwrap();
+    reader.opaque().unwrap();
+    reader.u32().unwrap();
+    (status, reader)
+}
+
+/// Skip the next result, which must be `op` succeeding with no body
+fn expect_ok(reader: &mut XdrReader, op: u32) {
+    assert_eq!(reader.u32(), Ok(op));
+    assert_eq!(reader.u32(), Ok(0), "operation {} failed", op);
+}
+
+/// stateid4: seqid and "other"
+fn stateid(reader: &mut XdrReader) -> (u32, [u8; 12]) {
+    (reader.u32().unwrap(), reader.fixed().unwrap())
+}
+
+/// Skip an OPEN result after its stateid, returning its flags
+fn open_flags(reader: &mut XdrReader) -> u32 {
+    // change_info4
+    reader.bool().unwrap();
+    reader.u64().unwrap();
+    reader.u64().unwrap();
+    let flags = reader.u32().unwrap();
+    reader.u32_array().unwrap();
+    assert_eq!(reader.u32(), Ok(0), "no delegation");
+    flags
+}
+
+/// OPEN of data.csv, without creating it
+fn open_data_csv(writer: &mut XdrWriter, client: u64, owner: &[u8]) {
+    writer.u32(OP_PUTROOTFH);
+    writer.u32(OP_LOOKUP).opaque(b"data");
+    writer
+        .u32(OP_OPEN)
+        .u32(0)
+        .u32(SHARE_ACCESS_BOTH)
+        .u32(0)
+        .u64(client)
+        .opaque(owner)
+        // OPEN4_NOCREATE, CLAIM_NULL
+        .u32(0)
+        .u32(0)
+        .opaque(b"data.csv");
+}
+
+/// SEQUENCE on slot 0 with the slot's next sequence ID
+fn sequence(writer: &mut XdrWriter, session: &[u8; 16], seqid: &mut u32) {
+    *seqid += 1;
+    writer
+        .u32(OP_SEQUENCE)
+        .fixed(session)
+        .u32(*seqid)
+        .u32(0)
+        .u32(0)
+        .bool(false);
+}
+
+/// Skip a successful SEQUENCE result
+fn skip_sequence(reader: &mut XdrReader) {
+    expect_ok(reader, OP_SEQUENCE);
+    reader.fixed::<16>().unwrap();
+    for _ in 0..5 {
+        reader.u32().unwrap();
+    }
+}
+
+/// Open data.csv as `owner` in a 4.1 session, then ask for a write lock
+/// on 100 bytes from `offset`; returns the LOCK's COMPOUND result
+async fn open_and_lock(
+    client: &mut Client,
+    session: &[u8; 16],
+    seqid: &mut u32,
+    client_id: u64,
+    owner: &[u8],
+    offset: u64,
+) -> Vec<u8> {
+    let reply = client
+        .compound(1, 4, |w| {
+            sequence(w, session, seqid);
+            open_data_csv(w, client_id, owner);
+        })
+        .await;
+    let (status, mut reader) = results(&reply);
+    assert_eq!(status, 0);
+    skip_sequence(&mut reader);
+    expect_ok(&mut reader, OP_PUTROOTFH);
+    expect_ok(&mut reader, OP_LOOKUP);
+    expect_ok(&mut reader, OP_OPEN);
+    let (open_seqid, open_other) = stateid(&mut reader);
+    assert_eq!(open_flags(&mut reader) & 2, 0, "4.1 opens need no confirm");
+
+    client
+        .compound(1, 5, |w| {
+            sequence(w, session, seqid);
+            w.u32(OP_PUTROOTFH);
+            w.u32(OP_LOOKUP).opaque(b"data");
+            w.u32(OP_LOOKUP).opaque(b"data.csv");
+            w.u32(OP_LOCK)
+                .u32(WRITE_LT)
+                .bool(false)
+                .u64(offset)
+                .u64(100)
+                // A new lock owner, from the open
+                .bool(true)
+                .u32(0)
+                .u32(open_seqid)
+                .fixed(&open_other)
+                .u32(0)
+                .u64(client_id)
+                .opaque(owner);
+        })
+        .await
+}
+
+#[tokio::test]
+#[serial]
+async fn test_null_and_read() {
+    let temp_dir = TempDir::new().unwrap();
+    let server = start(&temp_dir).await;
+    let mut client = Client::connect(&server).await;
+
+    assert!(client.call(rpc::PROC_NULL, &[]).await.is_empty());
+
+    let reply = client
+        .compound(0, 4, |w| {
+            w.u32(OP_PUTROOTFH);
+            w.u32(OP_LOOKUP).opaque(b"data");
+            w.u32(OP_LOOKUP).opaque(b"data.csv");
+            // The anonymous stateid reads without an open
+            w.u32(OP_READ).u32(0).fixed(&[0; 12]).u64(0).u32(4096);
+        })
+        .await;
+    let (status, mut reader) = results(&reply);
+    assert_eq!(status, 0);
+    expect_ok(&mut reader, OP_PUTROOTFH);
+    expect_ok(&mut reader, OP_LOOKUP);
+    expect_ok(&mut reader, OP_LOOKUP);
+    expect_ok(&mut reader, OP_READ);
+    assert_eq!(reader.bool(), Ok(true), "end of file");
+    let csv = String::from_utf8(reader.opaque().unwrap().to_vec()).unwrap();
+    assert!(csv.contains("1,Alice") && csv.contains("2,Bob"), "{}", csv);
+
+    // Looking up a missing file stops the COMPOUND there
+    let reply = client
+        .compound(0, 2, |w| {
+            w.u32(OP_PUTROOTFH);
+            w.u32(OP_LOOKUP).opaque(b"missing.csv");
+        })
+        .await;
+    assert_eq!(results(&reply).0, nfsstat4::NFS4ERR_NOENT.code());
+
+    server.shutdown().await.unwrap();
+}
+
+#[tokio::test]
+#[serial]
+async fn test_open_write_close() {
+    let temp_dir = TempDir::new().unwrap();
+    let server = start(&temp_dir).await;
+    let mut client = Client::connect(&server).await;
+
+    let reply = client
+        .compound(0, 1, |w| {
+            w.u32(OP_SETCLIENTID)
+                .fixed(&[1; 8])
+                .opaque(b"linux-client")
+                .u32(0x4000_0000)
+                .opaque(b"tcp")
+                .opaque(b"127.0.0.1.0.0")
+                .u32(1);
+        })
+        .await;
+    let (_, mut reader) = results(&reply);
+    expect_ok(&mut reader, OP_SETCLIENTID);
+    let client_id = reader.u64().unwrap();
+    let confirm: [u8; 8] = reader.fixed().unwrap();
+    let reply = client
+        .compound(0, 1, |w| {
+            w.u32(OP_SETCLIENTID_CONFIRM).u64(client_id).fixed(&confirm);
+        })
+        .await;
+    assert_eq!(results(&reply).0, 0);
+
+    // A 4.0 open owner's first open must be confirmed
+    let reply = client
+        .compound(0, 3, |w| open_data_csv(w, client_id, b"owner"))
+        .await;
+    let (status, mut reader) = results(&reply);
+    assert_eq!(status, 0);
+    expect_ok(&mut reader, OP_PUTROOTFH);
+    expect_ok(&mut reader, OP_LOOKUP);
+    expect_ok(&mut reader, OP_OPEN);
+    let (seqid, other) = stateid(&mut reader);
+    assert_ne!(open_flags(&mut reader) & 2, 0, "OPEN4_RESULT_CONFIRM");
+
+    let csv = server.read_file("/data/data.csv", 0, 4096).await.unwrap();
+    let row = b"3,Carol\n";
+    let reply = client
+        .compound(0, 6, |w| {
+            w.u32(OP_PUTROOTFH);
+            w.u32(OP_LOOKUP).opaque(b"data");
+            w.u32(OP_LOOKUP).opaque(b"data.csv");
+            w.u32(OP_OPEN_CONFIRM).u32(seqid).fixed(&other).u32(1);
+            // Unstable, so it's applied on CLOSE
+            w.u32(OP_WRITE)
+                .u32(seqid + 1)
+                .fixed(&other)
+                .u64(csv.len() as u64)
+                .u32(0)
+                .opaque(row);
+            w.u32(OP_CLOSE).u32(2).u32(seqid + 1).fixed(&other);
+        })
+        .await;
+    let (status, mut reader) = results(&reply);
+    assert_eq!(status, 0);
+    expect_ok(&mut reader, OP_PUTROOTFH);
+    expect_ok(&mut reader, OP_LOOKUP);
+    expect_ok(&mut reader, OP_LOOKUP);
+    expect_ok(&mut reader, OP_OPEN_CONFIRM);
+    stateid(&mut reader);
+    expect_ok(&mut reader, OP_WRITE);
+    assert_eq!(reader.u32(), Ok(row.len() as u32));
+
+    let csv = server.read_file("/data/data.csv", 0, 4096).await.unwrap();
+    let csv = String::from_utf8(csv).unwrap();
+    assert!(csv.contains("3,Carol"), "{}", csv);
+
+    // The closed stateid can't be written with any more
+    let reply = client
+        .compound(0, 4, |w| {
+            w.u32(OP_PUTROOTFH);
+            w.u32(OP_LOOKUP).opaque(b"data");
+            w.u32(OP_LOOKUP).opaque(b"data.csv");
+            w.u32(OP_WRITE)
+                .u32(seqid + 1)
+                .fixed(&other)
+                .u64(0)
+                .u32(2)
+                .opaque(b"id,name\n");
+        })
+        .await;
+    assert_eq!(results(&reply).0, nfsstat4::NFS4ERR_BAD_STATEID.code());
+
+    server.shutdown().await.unwrap();
+}
+
+#[tokio::test]
+#[serial]
+async fn test_lock_conflict_in_session() {
+    let temp_dir = TempDir::new().unwrap();
+    let server = start(&temp_dir).await;
+    let mut client = Client::connect(&server).await;
+
+    let reply = client
+        .compound(1, 1, |w| {
+            w.u32(OP_EXCHANGE_ID)
+                .fixed(&[2; 8])
+                .opaque(b"macos-client")
+                .u32(0)
+                // SP4_NONE, no implementation ID
+                .u32(0)
+                .u32(0);
+        })
+        .await;
+    let (_, mut reader) = results(&reply);
+    expect_ok(&mut reader, OP_EXCHANGE_ID);
+    let client_id = reader.u64().unwrap();
+    let sequence = reader.u32().unwrap();
+
+    let reply = client
+        .compound(1, 1, |w| {
+            w.u32(OP_CREATE_SESSION).u64(client_id).u32(sequence).u32(0);
+            for _ in 0..2 {
+                w.u32(0)
+                    .u32(1 << 20)
+                    .u32(1 << 20)
+                    .u32(64 * 1024)
+                    .u32(16)
+                    .u32(4)
+                    .u32_array(&[]);
+            }
+            // Callback program; AUTH_NONE
+            w.u32(0x4000_0000).u32(1).u32(0);
+        })
+        .await;
+    let (_, mut reader) = results(&reply);
+    expect_ok(&mut reader, OP_CREATE_SESSION);
+    let session: [u8; 16] = reader.fixed().unwrap();
+
+    // Each owner opens data.csv and asks for a write lock on overlapping bytes
+    let mut seqid = 0;
+    let reply = open_and_lock(&mut client, &session, &mut seqid, client_id, b"first", 0).await;
+    assert_eq!(results(&reply).0, 0);
+
+    let reply = open_and_lock(&mut client, &session, &mut seqid, client_id, b"second", 50).await;
+    let (status, mut reader) = results(&reply);
+    assert_eq!(status, nfsstat4::NFS4ERR_DENIED.code());
+    skip_sequence(&mut reader);
+    expect_ok(&mut reader, OP_PUTROOTFH);
+    expect_ok(&mut reader, OP_LOOKUP);
+    expect_ok(&mut reader, OP_LOOKUP);
+    assert_eq!(reader.u32(), Ok(OP_LOCK));
+    assert_eq!(reader.u32(), Ok(status));
+    // The failed LOCK says who holds the conflicting range
+    assert_eq!(reader.u64(), Ok(0));
+    assert_eq!(reader.u64(), Ok(100));
+    assert_eq!(reader.u32(), Ok(WRITE_LT));
+    assert_eq!(reader.u64(), Ok(client_id));
+    assert_eq!(reader.opaque(), Ok(&b"first"[..]));
+
+    server.shutdown().await.unwrap();
+}
@@ -3,6 +3,7 @@ use arrow::datatypes::{DataType, Field, Schema};
 use arrow::record_batch::RecordBatch;
 use fsdb::Result;
 use fsdb::database_ops::DatabaseOps;
+use fsdb::delta_lake::AutoCompaction;
 use std::sync::Arc;
 use tracing::info;
 
@@ -365,6 +366,121 @@ async fn test_optimize_with_target_size() -> Result<()> {
     Ok(())
 }
 
+/// Insert `n` single-batch commits of 5 rows each, starting at id `start`
+async fn insert_small_batches(db: &DatabaseOps, start: i32, n: i32) -> Result<()> {
+    for i in start..start + n {
+        let ids: Vec<i32> = (i * 5..i * 5 + 5).collect();
+        let values: Vec<String> = ids.iter().map(|id| format!("value_{}", id)).collect();
+        let batch = RecordBatch::try_new(
+            db.schema(),
+            vec![
+                Arc::new(Int32Array::from(ids)) as ArrayRef,
+                Arc::new(StringArray::from(values)) as ArrayRef,
+            ],
+        )?;
+        db.insert(batch).await?;
+    }
+    Ok(())
+}
+
+fn live_files(db_path: &std::path::Path) -> Result<usize> {
+    Ok(fsdb::delta_lake::read_delta_log(db_path)?.files.len())
+}
+
+fn auto_compaction() -> AutoCompaction {
+    AutoCompaction {
+        small_file_bytes: 1024 * 1024,
+        max_small_files: 5,
+        target_size: None,
+    }
+}
+
+/// Test that many small inserts trigger compaction once past the file-count limit
+#[tokio::test]
+async fn test_auto_compaction_on_small_file_count() -> Result<()> {
+    setup_tracing();
+
+    let temp_dir = tempfile::TempDir::new()?;
+    let db_path = temp_dir.path().join("db");
+    let schema = Arc::new(Schema::new(vec![
+        Field::new("id", DataType::Int32, false),
+        Field::new("value", DataType::Utf8, false),
+    ]));
+    let db = DatabaseOps::create(&db_path, schema)
+        .await?
+        .with_auto_compaction(auto_compaction());
+
+    // Five small files are within the limit
+    insert_small_batches(&db, 0, 5).await?;
+    assert_eq!(live_files(&db_path)?, 5);
+    assert!(db.last_auto_compaction().await.is_none());
+
+    // The sixth crosses it and everything is coalesced into one file
+    insert_small_batches(&db, 5, 1).await?;
+    let metrics = db
+        .last_auto_compaction()
+        .await
+        .expect("Auto-compaction should have run");
+    assert_eq!(metrics.files_before, 6);
+    assert_eq!(metrics.files_after, 1);
+    assert_eq!(live_files(&db_path)?, 1);
+
+    // Later inserts accumulate again until the next trigger
+    insert_small_batches(&db, 6, 4).await?;
+    assert_eq!(live_files(&db_path)?, 5);
+
+    let batches = db.query("SELECT COUNT(*) FROM data").await?;
+    let count = batches[0]
+        .column(0)
+        .as_any()
+        .downcast_ref::<arrow::array::Int64Array>()
+        .unwrap()
+        .value(0);
+    assert_eq!(count, 50, "Compaction must not lose rows");
+
+    Ok(())
+}
+
+/// Test that auto-compaction waits while cursors or transactions are open
+#[tokio::test]
+async fn test_auto_compaction_deferred_while_files_in_use() -> Result<()> {
+    setup_tracing();
+
+    let temp_dir = tempfile::TempDir::new()?;
+    let db_path = temp_dir.path().join("db");
+    let schema = Arc::new(Schema::new(vec![
+        Field::new("id", DataType::Int32, false),
+        Field::new("value", DataType::Utf8, false),
+    ]));
+    let db = Arc::new(
+        DatabaseOps::create(&db_path, schema)
+            .await?
+            .with_auto_compaction(auto_compaction()),
+    );
+
+    let cursor = db.open_cursor("SELECT * FROM data").await?;
+    insert_small_batches(&db, 0, 8).await?;
+    assert!(db.last_auto_compaction().await.is_none());
+    assert_eq!(live_files(&db_path)?, 8);
+    db.close_cursor(cursor).await;
+
+    let txn = db.begin_transaction().await?;
+    insert_small_batches(&db, 8, 1).await?;
+    assert_eq!(live_files(&db_path)?, 9);
+    txn.rollback().await?;
+
+    // The first insert with nothing open compacts
+    insert_small_batches(&db, 9, 1).await?;
+    let metrics = db
+        .last_auto_compaction()
+        .await
+        .expect("Auto-compaction should have run");
+    assert_eq!(metrics.files_before, 10);
+    assert_eq!(live_files(&db_path)?, 1);
+
+    Ok(())
+}
+
 // Helper functions
 
 fn count_parquet_files(db_path: &str) -> Result<usize> {
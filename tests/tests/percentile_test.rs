@@ -0,0 +1,220 @@
+// Percentile Integration Tests
+// Tests exact and approximate percentiles and median in SQL, that nulls are
+// ignored and empty groups give NULL, and that approximate percentiles merge
+// per-file sketches that follow inserts and OPTIMIZE
+
+use arrow::array::{ArrayRef, Float64Array, Int64Array, RecordBatch, StringArray};
+use arrow::datatypes::{DataType, Field, Schema, SchemaRef};
+use fsdb::DatabaseOps;
+use fsdb::delta_lake::{FileSketches, get_file_statistics};
+use fsdb::query::{ResultSet, Value};
+use std::path::Path;
+use std::sync::Arc;
+use tempfile::TempDir;
+
+fn create_schema() -> SchemaRef {
+    Arc::new(Schema::new(vec![
+        Field::new("id", DataType::Int64, false),
+        Field::new("grp", DataType::Utf8, false),
+        Field::new("amount", DataType::Float64, true),
+    ]))
+}
+
+fn batch(ids: &[i64], grp: &str, amounts: &[Option<f64>]) -> RecordBatch {
+    RecordBatch::try_new(
+        create_schema(),
+        vec![
+            Arc::new(Int64Array::from(ids.to_vec())) as ArrayRef,
+            Arc::new(StringArray::from(vec![grp; ids.len()])) as ArrayRef,
+            Arc::new(Float64Array::from(amounts.to_vec())) as ArrayRef,
+        ],
+    )
+    .unwrap()
+}
+
+/// Rows `ids` with skewed amounts in a scrambled order; every tenth is null
+fn skewed(ids: std::ops::Range<i64>) -> RecordBatch {
+    let amounts: Vec<Option<f64>> = ids
+        .clone()
+        .map(|id| {
+            let v = ((id * 7919) % 100_000) as f64;
+            (id % 10 != 0).then_some(v * v / 100_000.0)
+        })
+        .collect();
+    batch(&ids.collect::<Vec<_>>(), "a", &amounts)
+}
+
+/// Five files of 20,000 rows
+async fn create_skewed_db(path: &Path) -> DatabaseOps {
+    let db = DatabaseOps::create(path, create_schema()).await.unwrap();
+    for start in (0..100_000).step_by(20_000) {
+        db.insert(skewed(start..start + 20_000)).await.unwrap();
+    }
+    db
+}
+
+fn float(rows: &ResultSet, row: usize, name: &str) -> Option<f64> {
+    match rows.get(row, name).unwrap() {
+        Value::Float(v) => Some(*v),
+        Value::Null => None,
+        other => panic!("expected a float, got {:?}", other),
+    }
+}
+
+async fn exact(db: &DatabaseOps, p: f64) -> f64 {
+    let sql = format!("SELECT percentile(amount, {}) AS p FROM data", p);
+    float(&db.query_rows(&sql).await.unwrap(), 0, "p").unwrap()
+}
+
+/// `estimate` is between the exact percentiles a hundredth either side of `p`
+async fn assert_within_bound(db: &DatabaseOps, p: f64, estimate: f64) {
+    let low = exact(db, (p - 0.01_f64).max(0.0)).await;
+    let high = exact(db, (p + 0.01_f64).min(1.0)).await;
+    assert!(
+        (low..=high).contains(&estimate),
+        "p{}: estimate {} not in [{}, {}]",
+        p,
+        estimate,
+        low,
+        high
+    );
+}
+
+#[tokio::test]
+async fn test_exact_percentile_and_median_ignore_nulls() {
+    let temp_dir = TempDir::new().unwrap();
+    let db = DatabaseOps::create(&temp_dir.path().join("db"), create_schema())
+        .await
+        .unwrap();
+    let amounts: Vec<Option<f64>> = (1..=100)
+        .map(|v| Some(v as f64))
+        .chain(std::iter::repeat_n(None, 20))
+        .collect();
+    db.insert(batch(&(0..120).collect::<Vec<_>>(), "a", &amounts))
+        .await
+        .unwrap();
+
+    let rows = db
+        .query_rows(
+            "SELECT percentile(amount, 0.95) AS p95, percentile(amount, 0.5) AS p50, \
+             median(amount) AS med, percentile(amount, 0) AS low, \
+             percentile(amount, 1) AS high FROM data",
+        )
+        .await
+        .unwrap();
+    assert!((float(&rows, 0, "p95").unwrap() - 95.05).abs() < 1e-9);
+    assert_eq!(float(&rows, 0, "p50"), Some(50.5));
+    assert_eq!(float(&rows, 0, "med"), Some(50.5));
+    assert_eq!(float(&rows, 0, "low"), Some(1.0));
+    assert_eq!(float(&rows, 0, "high"), Some(100.0));
+
+    // Fractions outside 0..1 are refused
+    assert!(
+        db.query_rows("SELECT percentile(amount, 1.5) FROM data")
+            .await
+            .is_err()
+    );
+    assert!(db.approx_percentile("amount", -0.1).await.is_err());
+    assert!(db.approx_percentile("grp", 0.5).await.is_err());
+}
+
+#[tokio::test]
+async fn test_empty_groups_are_null() {
+    let temp_dir = TempDir::new().unwrap();
+    let db = DatabaseOps::create(&temp_dir.path().join("db"), create_schema())
+        .await
+        .unwrap();
+    db.insert(batch(&[1, 2, 3], "a", &[Some(1.0), Some(2.0), Some(9.0)]))
+        .await
+        .unwrap();
+    db.insert(batch(&[4, 5], "b", &[None, None])).await.unwrap();
+
+    // Per group: b holds only nulls
+    let rows = db
+        .query_rows(
+            "SELECT grp, percentile(amount, 0.5) AS exact, \
+             approx_percentile(amount, 0.5) AS approx, median(amount) AS med \
+             FROM data GROUP BY grp ORDER BY grp",
+        )
+        .await
+        .unwrap();
+    assert_eq!(rows.len(), 2);
+    assert_eq!(float(&rows, 0, "exact"), Some(2.0));
+    assert_eq!(float(&rows, 0, "approx"), Some(2.0));
+    assert_eq!(float(&rows, 0, "med"), Some(2.0));
+    for name in ["exact", "approx", "med"] {
+        assert_eq!(float(&rows, 1, name), None);
+    }
+
+    // No rows at all
+    let rows = db
+        .query_rows(
+            "SELECT percentile(amount, 0.5) AS exact, approx_percentile(amount, 0.5) AS approx \
+             FROM data WHERE id > 100",
+        )
+        .await
+        .unwrap();
+    assert_eq!(float(&rows, 0, "exact"), None);
+    assert_eq!(float(&rows, 0, "approx"), None);
+
+    db.delete_rows_where("grp = 'a'").await.unwrap();
+    assert_eq!(db.approx_percentile("amount", 0.5).await.unwrap(), None);
+}
+
+#[tokio::test]
+async fn test_approx_within_bound_of_exact() {
+    let temp_dir = TempDir::new().unwrap();
+    let db = create_skewed_db(&temp_dir.path().join("db")).await;
+
+    for p in [0.01, 0.25, 0.5, 0.9, 0.99] {
+        let sql = format!("SELECT approx_percentile(amount, {}) AS p FROM data", p);
+        let from_sql = float(&db.query_rows(&sql).await.unwrap(), 0, "p").unwrap();
+        assert_within_bound(&db, p, from_sql).await;
+
+        let from_sketches = db.approx_percentile("amount", p).await.unwrap().unwrap();
+        assert_within_bound(&db, p, from_sketches).await;
+    }
+}
+
+#[tokio::test]
+async fn test_sketches_follow_files() {
+    let temp_dir = TempDir::new().unwrap();
+    let path = temp_dir.path().join("db");
+    let db = create_skewed_db(&path).await;
+    let sketched = |path: &Path| {
+        let sketches = FileSketches::load(path).unwrap();
+        let mut files: Vec<String> = sketches.columns["amount"].keys().cloned().collect();
+        files.sort();
+        let values: u64 = sketches.columns["amount"].values().map(|d| d.count()).sum();
+        (files, values)
+    };
+    let live = |path: &Path| {
+        let mut files: Vec<String> = get_file_statistics(path)
+            .unwrap()
+            .into_iter()
+            .map(|f| f.path)
+            .collect();
+        files.sort();
+        files
+    };
+
+    // A sketch of each file's non-null values
+    db.approx_percentile("amount", 0.5).await.unwrap();
+    assert_eq!(sketched(&path), (live(&path), 90_000));
+
+    // An insert adds a sketch for its file alone
+    let before = FileSketches::load(&path).unwrap();
+    db.insert(skewed(100_000..120_000)).await.unwrap();
+    db.approx_percentile("amount", 0.5).await.unwrap();
+    let after = FileSketches::load(&path).unwrap();
+    assert_eq!(after.columns["amount"].len(), 6);
+    for (file, digest) in &before.columns["amount"] {
+        assert_eq!(&after.columns["amount"][file], digest);
+    }
+
+    // OPTIMIZE replaces the files, and their sketches with them
+    db.optimize().await.unwrap();
+    let median = db.approx_percentile("amount", 0.5).await.unwrap().unwrap();
+    assert_eq!(sketched(&path), (live(&path), 108_000));
+    assert_within_bound(&db, 0.5, median).await;
+}
@@ -0,0 +1,172 @@
+// Query Batch Integration Tests
+// Tests running several queries against one pinned snapshot
+
+use arrow::array::{ArrayRef, Int32Array, RecordBatch, StringArray};
+use arrow::datatypes::{DataType, Field, Schema, SchemaRef};
+use fsdb::DatabaseOps;
+use fsdb::query::Value;
+use std::sync::Arc;
+use tempfile::TempDir;
+
+fn create_schema() -> SchemaRef {
+    Arc::new(Schema::new(vec![
+        Field::new("id", DataType::Int32, false),
+        Field::new("name", DataType::Utf8, false),
+    ]))
+}
+
+fn create_batch(schema: SchemaRef, ids: Vec<i32>) -> RecordBatch {
+    let names: Vec<String> = ids.iter().map(|id| format!("user_{}", id)).collect();
+    RecordBatch::try_new(
+        schema,
+        vec![
+            Arc::new(Int32Array::from(ids)) as ArrayRef,
+            Arc::new(StringArray::from(names)) as ArrayRef,
+        ],
+    )
+    .unwrap()
+}
+
+#[tokio::test]
+async fn test_batch_returns_one_result_per_query() {
+    let temp_dir = TempDir::new().unwrap();
+    let schema = create_schema();
+    let db = DatabaseOps::create(temp_dir.path().join("batch_db"), schema.clone())
+        .await
+        .unwrap();
+    db.insert(create_batch(schema, (1..=10).collect()))
+        .await
+        .unwrap();
+
+    let results = db
+        .query_batch(vec![
+            "SELECT COUNT(*) AS n FROM data".to_string(),
+            "SELECT name FROM data WHERE id = 3".to_string(),
+        ])
+        .await
+        .unwrap();
+
+    assert_eq!(results.len(), 2);
+    let count = results[0].as_ref().unwrap();
+    assert_eq!(count.get(0, "n"), Some(&Value::Int(10)));
+    let name = results[1].as_ref().unwrap();
+    assert_eq!(
+        name.get(0, "name"),
+        Some(&Value::String("user_3".to_string()))
+    );
+}
+
+#[tokio::test]
+async fn test_batch_isolates_failing_queries() {
+    let temp_dir = TempDir::new().unwrap();
+    let schema = create_schema();
+    let db = DatabaseOps::create(temp_dir.path().join("batch_db"), schema.clone())
+        .await
+        .unwrap();
+    db.insert(create_batch(schema, (1..=5).collect()))
+        .await
+        .unwrap();
+
+    let results = db
+        .query_batch(vec![
+            "SELECT COUNT(*) AS n FROM data".to_string(),
+            "SELECT no_such_column FROM data".to_string(),
+            "SELECT MAX(id) AS m FROM data".to_string(),
+        ])
+        .await
+        .unwrap();
+
+    assert_eq!(results.len(), 3);
+    assert_eq!(
+        results[0].as_ref().unwrap().get(0, "n"),
+        Some(&Value::Int(5))
+    );
+    assert!(results[1].is_err());
+    assert_eq!(
+        results[2].as_ref().unwrap().get(0, "m"),
+        Some(&Value::Int(5))
+    );
+}
+
+#[tokio::test]
+async fn test_empty_batch() {
+    let temp_dir = TempDir::new().unwrap();
+    let db = DatabaseOps::create(temp_dir.path().join("batch_db"), create_schema())
+        .await
+        .unwrap();
+
+    assert!(db.query_batch(Vec::new()).await.unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn test_batch_with_params() {
+    let temp_dir = TempDir::new().unwrap();
+    let schema = create_schema();
+    let db = DatabaseOps::create(temp_dir.path().join("batch_db"), schema.clone())
+        .await
+        .unwrap();
+    db.insert(create_batch(schema, (1..=10).collect()))
+        .await
+        .unwrap();
+
+    let results = db
+        .query_batch_with_params(vec![
+            (
+                "SELECT COUNT(*) AS n FROM data WHERE id > $1".to_string(),
+                vec![Value::Int(7)],
+            ),
+            (
+                "SELECT id FROM data WHERE name = $1".to_string(),
+                vec![Value::String("user_4".to_string())],
+            ),
+        ])
+        .await
+        .unwrap();
+
+    assert_eq!(
+        results[0].as_ref().unwrap().get(0, "n"),
+        Some(&Value::Int(3))
+    );
+    assert_eq!(
+        results[1].as_ref().unwrap().get(0, "id"),
+        Some(&Value::Int(4))
+    );
+}
+
+#[tokio::test]
+async fn test_batch_sees_one_snapshot_while_table_changes() {
+    let temp_dir = TempDir::new().unwrap();
+    let schema = create_schema();
+    let db = DatabaseOps::create(temp_dir.path().join("batch_db"), schema.clone())
+        .await
+        .unwrap();
+    db.insert(create_batch(schema.clone(), vec![0]))
+        .await
+        .unwrap();
+
+    let writer = async {
+        for i in 1..=20 {
+            db.insert(create_batch(schema.clone(), vec![i]))
+                .await
+                .unwrap();
+        }
+    };
+    let reader = async {
+        for _ in 0..20 {
+            let results = db
+                .query_batch(vec!["SELECT COUNT(*) AS n FROM data".to_string(); 5])
+                .await
+                .unwrap();
+            let counts: Vec<_> = results
+                .iter()
+                .map(|r| r.as_ref().unwrap().get(0, "n").cloned())
+                .collect();
+            assert!(
+                counts.iter().all(|c| *c == counts[0]),
+                "queries in one batch saw different versions: {:?}",
+                counts
+            );
+        }
+    };
+    tokio::join!(writer, reader);
+}
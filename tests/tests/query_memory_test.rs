@@ -0,0 +1,93 @@
+// Query Memory Limit Integration Tests
+// Tests that a query over its memory budget spills or fails with a typed error
+
+use arrow::array::{Int32Array, Int64Array, StringArray};
+use arrow::datatypes::{DataType, Field, Schema};
+use arrow::record_batch::RecordBatch;
+use fsdb::storage::spill::SpillConfig;
+use fsdb::{DatabaseOps, Error};
+use std::sync::Arc;
+use tempfile::TempDir;
+
+const ROWS: i32 = 50_000;
+
+/// Too small for a hash table over `ROWS` distinct keys
+const TINY_BUDGET: usize = 16 * 1024;
+
+const GROUP_BY: &str = "SELECT name, COUNT(*) AS n FROM data GROUP BY name";
+
+async fn create_db(temp_dir: &TempDir) -> DatabaseOps {
+    let schema = Arc::new(Schema::new(vec![
+        Field::new("id", DataType::Int32, false),
+        Field::new("name", DataType::Utf8, false),
+    ]));
+    let db = DatabaseOps::create(temp_dir.path().join("db"), schema.clone())
+        .await
+        .unwrap()
+        .with_spill_config(SpillConfig {
+            temp_dir: temp_dir.path().join("spill"),
+            max_spill_bytes: None,
+        });
+
+    // Every name is distinct, so the group-by state grows with the table
+    let names: Vec<String> = (0..ROWS).map(|i| format!("user_{:08}", i)).collect();
+    let batch = RecordBatch::try_new(
+        schema,
+        vec![
+            Arc::new(Int32Array::from((0..ROWS).collect::<Vec<_>>())),
+            Arc::new(StringArray::from(names)),
+        ],
+    )
+    .unwrap();
+    db.insert(batch).await.unwrap();
+    db
+}
+
+fn group_count(batches: &[RecordBatch]) -> usize {
+    batches.iter().map(|b| b.num_rows()).sum()
+}
+
+#[tokio::test]
+async fn test_group_by_over_budget_spills_or_fails_typed() {
+    let temp_dir = TempDir::new().unwrap();
+    let db = create_db(&temp_dir).await;
+
+    match db.query_with_memory_limit(GROUP_BY, TINY_BUDGET).await {
+        // Spilled: the answer is still complete
+        Ok(batches) => assert_eq!(group_count(&batches), ROWS as usize),
+        Err(Error::QueryMemoryExceeded { limit, detail }) => {
+            assert_eq!(limit, TINY_BUDGET as u64);
+            assert!(!detail.is_empty());
+        }
+        Err(other) => panic!("expected spill or QueryMemoryExceeded, got {:?}", other),
+    }
+
+    // The failed query doesn't affect the next one
+    let results = db.query("SELECT COUNT(*) FROM data").await.unwrap();
+    let count = results[0]
+        .column(0)
+        .as_any()
+        .downcast_ref::<Int64Array>()
+        .unwrap()
+        .value(0);
+    assert_eq!(count, ROWS as i64);
+}
+
+#[tokio::test]
+async fn test_per_query_limit_overrides_default() {
+    let temp_dir = TempDir::new().unwrap();
+    let db = create_db(&temp_dir)
+        .await
+        .with_query_memory_limit(TINY_BUDGET);
+
+    // Queries that hold little state fit in the default budget
+    let results = db.query("SELECT id FROM data WHERE id < 10").await.unwrap();
+    assert_eq!(group_count(&results), 10);
+
+    // A generous budget for one call lets the group-by run in memory
+    let batches = db
+        .query_with_memory_limit(GROUP_BY, 512 * 1024 * 1024)
+        .await
+        .unwrap();
+    assert_eq!(group_count(&batches), ROWS as usize);
+}
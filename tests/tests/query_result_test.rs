@@ -0,0 +1,124 @@
+use arrow::array::{BooleanArray, Float64Array, Int64Array, RecordBatch, StringArray};
+use arrow::datatypes::{DataType, Field, Schema, TimeUnit};
+use fsdb::database_ops::DatabaseOps;
+use fsdb::query::Value;
+use std::sync::Arc;
+use tempfile::TempDir;
+
+async fn create_db(temp_dir: &TempDir) -> DatabaseOps {
+    let schema = Arc::new(Schema::new(vec![
+        Field::new("id", DataType::Int64, false),
+        Field::new("name", DataType::Utf8, true),
+        Field::new("score", DataType::Float64, true),
+        Field::new("active", DataType::Boolean, true),
+    ]));
+    let db = DatabaseOps::create(&temp_dir.path().join("db"), schema.clone())
+        .await
+        .unwrap();
+
+    let batch = RecordBatch::try_new(
+        schema,
+        vec![
+            Arc::new(Int64Array::from(vec![1, 2])),
+            Arc::new(StringArray::from(vec![Some("Alice"), None])),
+            Arc::new(Float64Array::from(vec![Some(9.5), None])),
+            Arc::new(BooleanArray::from(vec![Some(true), Some(false)])),
+        ],
+    )
+    .unwrap();
+    db.insert(batch).await.unwrap();
+    db
+}
+
+#[tokio::test]
+async fn test_query_rows_keeps_column_types() {
+    let temp_dir = TempDir::new().unwrap();
+    let db = create_db(&temp_dir).await;
+
+    let result = db
+        .query_rows("SELECT id, name, score, active FROM data ORDER BY id")
+        .await
+        .unwrap();
+
+    assert_eq!(result.columns(), vec!["id", "name", "score", "active"]);
+    assert_eq!(result.len(), 2);
+    assert_eq!(result.get(0, "id"), Some(&Value::Int(1)));
+    assert_eq!(
+        result.get(0, "name"),
+        Some(&Value::String("Alice".to_string()))
+    );
+    assert_eq!(result.get(0, "score"), Some(&Value::Float(9.5)));
+    assert_eq!(result.get(0, "active"), Some(&Value::Bool(true)));
+
+    // Nulls stay distinguishable from empty strings
+    assert_eq!(result.get(1, "name"), Some(&Value::Null));
+    assert!(result.get(1, "score").unwrap().is_null());
+    assert_eq!(result.get(1, "missing"), None);
+}
+
+#[tokio::test]
+async fn test_query_rows_keeps_decimal_scale_and_timezone() {
+    let temp_dir = TempDir::new().unwrap();
+    let db = create_db(&temp_dir).await;
+
+    let result = db
+        .query_rows(
+            "SELECT CAST(score AS DECIMAL(20, 4)) AS amount, \
+             arrow_cast(CAST(id AS BIGINT) * 1000000, 'Timestamp(Microsecond, Some(\"+05:30\"))') AS at \
+             FROM data WHERE id = 1",
+        )
+        .await
+        .unwrap();
+
+    let amount = result.get(0, "amount").unwrap();
+    assert_eq!(
+        amount,
+        &Value::Decimal {
+            value: 95000,
+            scale: 4
+        }
+    );
+    assert_eq!(amount.to_string(), "9.5000");
+
+    let at = result.get(0, "at").unwrap();
+    assert_eq!(
+        at,
+        &Value::Timestamp {
+            value: 1_000_000,
+            unit: TimeUnit::Microsecond,
+            timezone: Some("+05:30".to_string()),
+        }
+    );
+    assert_eq!(at.to_string(), "1970-01-01T05:30:01+05:30");
+}
+
+#[tokio::test]
+async fn test_empty_result_keeps_schema() {
+    let temp_dir = TempDir::new().unwrap();
+    let db = create_db(&temp_dir).await;
+
+    let result = db
+        .query_rows("SELECT id, name FROM data WHERE id > 100")
+        .await
+        .unwrap();
+    assert!(result.is_empty());
+    assert_eq!(result.columns(), vec!["id", "name"]);
+
+    let csv = db
+        .query_csv("SELECT id, name FROM data WHERE id > 100")
+        .await
+        .unwrap();
+    assert_eq!(csv.trim_end(), "id,name");
+}
+
+#[tokio::test]
+async fn test_query_csv() {
+    let temp_dir = TempDir::new().unwrap();
+    let db = create_db(&temp_dir).await;
+
+    let csv = db
+        .query_csv("SELECT id, name FROM data ORDER BY id")
+        .await
+        .unwrap();
+    assert_eq!(csv, "id,name\n1,Alice\n2,\n");
+}
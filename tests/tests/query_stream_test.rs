@@ -0,0 +1,107 @@
+// Streaming Query Integration Tests
+// Tests query_stream: results read batch by batch or in pages, buffered rows
+// included, the snapshot pinned at planning, and errors while planning
+
+use arrow::array::{ArrayRef, Int32Array, RecordBatch, StringArray};
+use arrow::datatypes::{DataType, Field, Schema, SchemaRef};
+use fsdb::DatabaseOps;
+use futures::TryStreamExt;
+use std::sync::Arc;
+use tempfile::TempDir;
+
+fn create_schema() -> SchemaRef {
+    Arc::new(Schema::new(vec![
+        Field::new("id", DataType::Int32, false),
+        Field::new("name", DataType::Utf8, false),
+    ]))
+}
+
+fn create_batch(schema: SchemaRef, ids: Vec<i32>) -> RecordBatch {
+    let names: Vec<String> = ids.iter().map(|id| format!("user_{}", id)).collect();
+    RecordBatch::try_new(
+        schema,
+        vec![
+            Arc::new(Int32Array::from(ids)) as ArrayRef,
+            Arc::new(StringArray::from(names)) as ArrayRef,
+        ],
+    )
+    .unwrap()
+}
+
+fn collect_ids(batches: &[RecordBatch]) -> Vec<i32> {
+    batches
+        .iter()
+        .flat_map(|b| {
+            b.column(0)
+                .as_any()
+                .downcast_ref::<Int32Array>()
+                .unwrap()
+                .values()
+                .to_vec()
+        })
+        .collect()
+}
+
+#[tokio::test]
+async fn test_stream_reads_every_row() {
+    let temp_dir = TempDir::new().unwrap();
+    let schema = create_schema();
+    let db = DatabaseOps::create(temp_dir.path().join("stream_db"), schema.clone())
+        .await
+        .unwrap();
+    for start in [1, 101, 201] {
+        db.insert(create_batch(schema.clone(), (start..start + 100).collect()))
+            .await
+            .unwrap();
+    }
+
+    let stream = db
+        .query_stream("SELECT id FROM data ORDER BY id")
+        .await
+        .unwrap();
+    assert_eq!(stream.schema().field(0).name(), "id");
+    let batches: Vec<RecordBatch> = stream.try_collect().await.unwrap();
+    assert_eq!(collect_ids(&batches), (1..=300).collect::<Vec<_>>());
+    assert_eq!(db.get_metrics().await.total_queries, 1);
+}
+
+#[tokio::test]
+async fn test_stream_pages_from_its_snapshot() {
+    let temp_dir = TempDir::new().unwrap();
+    let schema = create_schema();
+    let db = DatabaseOps::create(temp_dir.path().join("stream_db"), schema.clone())
+        .await
+        .unwrap();
+    db.insert(create_batch(schema.clone(), (1..=10).collect()))
+        .await
+        .unwrap();
+
+    let mut stream = db
+        .query_stream("SELECT id FROM data ORDER BY id")
+        .await
+        .unwrap();
+    let (page, more) = stream.next_rows(4).await.unwrap();
+    assert_eq!(collect_ids(&page), vec![1, 2, 3, 4]);
+    assert!(more);
+
+    // Rows written after planning aren't seen
+    db.insert(create_batch(schema.clone(), vec![11, 12]))
+        .await
+        .unwrap();
+    let (page, more) = stream.next_rows(100).await.unwrap();
+    assert_eq!(collect_ids(&page), (5..=10).collect::<Vec<_>>());
+    assert!(!more);
+    assert!(stream.is_exhausted());
+}
+
+#[tokio::test]
+async fn test_stream_planning_errors() {
+    let temp_dir = TempDir::new().unwrap();
+    let db = DatabaseOps::create(temp_dir.path().join("stream_db"), create_schema())
+        .await
+        .unwrap();
+
+    assert!(db.query_stream("SELECT missing FROM data").await.is_err());
+    assert!(db.query_stream("SELECT * FROM nowhere").await.is_err());
+    assert_eq!(db.get_metrics().await.total_errors, 2);
+}
@@ -0,0 +1,232 @@
+// Quota Integration Tests
+// Tests that per-user quotas on rows and bytes are enforced before commit,
+// that deletes give usage back, and that role quotas apply to their users
+
+use arrow::array::{ArrayRef, Int32Array, RecordBatch, StringArray};
+use arrow::datatypes::{DataType, Field, Schema, SchemaRef};
+use fsdb::DatabaseOps;
+use fsdb::Error;
+use fsdb::security::{Permission, Quota, Role, RoleManager};
+use std::path::Path;
+use std::sync::Arc;
+use tempfile::TempDir;
+
+fn create_schema() -> SchemaRef {
+    Arc::new(Schema::new(vec![
+        Field::new("id", DataType::Int32, false),
+        Field::new("name", DataType::Utf8, false),
+    ]))
+}
+
+fn batch(ids: &[i32]) -> RecordBatch {
+    RecordBatch::try_new(
+        create_schema(),
+        vec![
+            Arc::new(Int32Array::from(ids.to_vec())) as ArrayRef,
+            Arc::new(StringArray::from_iter_values(
+                ids.iter().map(|id| format!("user-{}", id)),
+            )) as ArrayRef,
+        ],
+    )
+    .unwrap()
+}
+
+/// A table with authentication, and alice, who can read and write
+async fn create_db(path: &Path) -> DatabaseOps {
+    let db = DatabaseOps::create_with_auth(path, create_schema(), true)
+        .await
+        .unwrap();
+    db.create_user("alice", "secret", &["read", "write"])
+        .await
+        .unwrap();
+    db
+}
+
+async fn open_as_alice(path: &Path) -> DatabaseOps {
+    DatabaseOps::open_with_credentials(path, Some(("alice", "secret")))
+        .await
+        .unwrap()
+}
+
+fn parquet_files(path: &Path) -> usize {
+    std::fs::read_dir(path)
+        .unwrap()
+        .map(|e| e.unwrap().path())
+        .filter(|p| p.extension().and_then(|e| e.to_str()) == Some("parquet"))
+        .count()
+}
+
+#[tokio::test]
+async fn test_row_quota_hit_exceeded_and_freed_by_delete() {
+    let temp_dir = TempDir::new().unwrap();
+    let path = temp_dir.path().join("db");
+    let admin = create_db(&path).await;
+    admin
+        .set_user_quota("alice", Some(Quota::unlimited().with_max_rows(5)))
+        .await
+        .unwrap();
+    let alice = open_as_alice(&path).await;
+
+    // Reaching the limit exactly is allowed
+    alice.insert(batch(&[1, 2, 3])).await.unwrap();
+    alice.insert(batch(&[4, 5])).await.unwrap();
+    let status = alice.quota_status("alice").await.unwrap();
+    assert_eq!(status.usage.rows, 5);
+    assert_eq!(status.rows_remaining(), Some(0));
+
+    match alice.insert(batch(&[6])).await {
+        Err(Error::QuotaExceeded {
+            username,
+            resource,
+            used,
+            requested,
+            limit,
+        }) => {
+            assert_eq!(username, "alice");
+            assert_eq!(resource, "rows");
+            assert_eq!((used, requested, limit), (5, 1, 5));
+        }
+        other => panic!("expected QuotaExceeded, got {:?}", other),
+    }
+    assert_eq!(
+        alice.query_rows("SELECT id FROM data").await.unwrap().len(),
+        5
+    );
+
+    // A delete gives its rows back
+    alice.delete_rows_where("id <= 2").await.unwrap();
+    let status = alice.quota_status("alice").await.unwrap();
+    assert_eq!(status.usage.rows, 3);
+    alice.insert(batch(&[6, 7])).await.unwrap();
+    assert!(alice.insert(batch(&[8])).await.is_err());
+}
+
+#[tokio::test]
+async fn test_byte_quota_checked_before_commit() {
+    let temp_dir = TempDir::new().unwrap();
+    let path = temp_dir.path().join("db");
+    let admin = create_db(&path).await;
+    let alice = open_as_alice(&path).await;
+
+    // Usage is tracked without a quota, and matches the data files
+    alice.insert(batch(&[1, 2, 3])).await.unwrap();
+    let usage = alice.quota_status("alice").await.unwrap().usage;
+    let summary = admin.table_summary().await.unwrap();
+    assert_eq!(usage.bytes, summary.total_bytes);
+    assert_eq!(usage.rows, 3);
+
+    // Room for less than another file: nothing is committed or left behind
+    admin
+        .set_user_quota(
+            "alice",
+            Some(Quota::unlimited().with_max_bytes(usage.bytes + 10)),
+        )
+        .await
+        .unwrap();
+    let alice = open_as_alice(&path).await;
+    let files = parquet_files(&path);
+    match alice.insert(batch(&[4, 5, 6])).await {
+        Err(Error::QuotaExceeded { resource, .. }) => assert_eq!(resource, "bytes"),
+        other => panic!("expected QuotaExceeded, got {:?}", other),
+    }
+    assert_eq!(parquet_files(&path), files);
+    assert_eq!(admin.table_summary().await.unwrap().num_rows, 3);
+
+    // Clearing the quota lifts the limit, keeping the usage
+    admin.set_user_quota("alice", None).await.unwrap();
+    let alice = open_as_alice(&path).await;
+    alice.insert(batch(&[4, 5, 6])).await.unwrap();
+    let status = alice.quota_status("alice").await.unwrap();
+    assert_eq!(status.usage.rows, 6);
+    assert!(status.limit.is_unlimited());
+}
+
+#[tokio::test]
+async fn test_role_quotas_and_status_visibility() {
+    let temp_dir = TempDir::new().unwrap();
+    let path = temp_dir.path().join("db");
+    let admin = create_db(&path).await;
+    admin
+        .create_user("bob", "hunter2", &["tenant"])
+        .await
+        .unwrap();
+    let roles = || {
+        let mut roles = RoleManager::new();
+        roles.add_role(
+            Role::new(
+                "tenant".to_string(),
+                vec![Permission::Read, Permission::Write],
+            )
+            .with_quota(Quota::unlimited().with_max_rows(2)),
+        );
+        roles
+    };
+    let bob = DatabaseOps::open_with_credentials(&path, Some(("bob", "hunter2")))
+        .await
+        .unwrap()
+        .with_role_manager(roles());
+
+    bob.insert(batch(&[1, 2])).await.unwrap();
+    assert!(matches!(
+        bob.insert(batch(&[3])).await,
+        Err(Error::QuotaExceeded { .. })
+    ));
+
+    // A user's own quota takes precedence over their roles'
+    admin
+        .set_user_quota("bob", Some(Quota::unlimited().with_max_rows(3)))
+        .await
+        .unwrap();
+    let bob = DatabaseOps::open_with_credentials(&path, Some(("bob", "hunter2")))
+        .await
+        .unwrap()
+        .with_role_manager(roles());
+    bob.insert(batch(&[3])).await.unwrap();
+    assert_eq!(bob.quota_status("bob").await.unwrap().usage.rows, 3);
+
+    // Other users' usage is for admins only, and usage is per user
+    assert!(bob.quota_status("alice").await.is_err());
+    assert_eq!(admin.quota_status("bob").await.unwrap().usage.rows, 3);
+    assert_eq!(admin.quota_status("alice").await.unwrap().usage.rows, 0);
+}
+
+#[tokio::test]
+async fn test_merge_checks_added_rows() {
+    let temp_dir = TempDir::new().unwrap();
+    let path = temp_dir.path().join("db");
+    let admin = create_db(&path).await;
+    admin
+        .set_user_quota("alice", Some(Quota::unlimited().with_max_rows(3)))
+        .await
+        .unwrap();
+    let alice = open_as_alice(&path).await;
+    alice.insert(batch(&[1, 2])).await.unwrap();
+
+    let merge = |ids: &'static [i32]| {
+        let alice = &alice;
+        async move {
+            alice
+                .merge()
+                .await?
+                .with_source(batch(ids), "source")
+                .on("target.id = source.id")
+                .when_matched_update()
+                .set_all()
+                .when_not_matched_insert()
+                .values_all()
+                .execute()
+                .await
+        }
+    };
+
+    // Updates don't add rows; two new rows would take alice past 3
+    assert!(matches!(
+        merge(&[1, 3, 4]).await,
+        Err(Error::QuotaExceeded { .. })
+    ));
+    assert_eq!(alice.quota_status("alice").await.unwrap().usage.rows, 2);
+
+    let metrics = merge(&[1, 3]).await.unwrap();
+    assert_eq!((metrics.rows_updated, metrics.rows_inserted), (1, 1));
+    assert_eq!(alice.quota_status("alice").await.unwrap().usage.rows, 3);
+}
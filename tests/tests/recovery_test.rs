@@ -1,8 +1,10 @@
 use arrow::array::{ArrayRef, Int32Array, RecordBatch, StringArray};
 use arrow::datatypes::{DataType, Field, Schema, SchemaRef};
 use fsdb::DatabaseOps;
+use fsdb::storage::retry::RetryPolicy;
 use std::fs;
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 
 fn setup_logging() {
     let _ = tracing_subscriber::fmt()
@@ -399,3 +401,38 @@ async fn test_transaction_log_based_recovery() {
 
     cleanup_test_db(db_path);
 }
+
+#[tokio::test]
+async fn test_permanent_storage_error_is_not_retried() {
+    setup_logging();
+    let temp_dir = tempfile::TempDir::new().unwrap();
+    let db_path = temp_dir.path().join("test_db_retry");
+
+    let schema = create_test_schema();
+    // Retrying would take at least 10 seconds
+    let policy = RetryPolicy {
+        max_attempts: 5,
+        base_delay: Duration::from_secs(10),
+        max_delay: Duration::from_secs(10),
+        jitter: 0.0,
+    };
+    let db = DatabaseOps::create(&db_path, schema.clone())
+        .await
+        .expect("Failed to create database")
+        .with_retry_policy(policy);
+
+    let batch = create_test_batch(schema, vec![1], vec!["r1"], vec![1]);
+    db.insert(batch).await.expect("Failed to insert");
+
+    // A missing transaction log is permanent and must fail on the first attempt
+    fs::remove_dir_all(db_path.join("_delta_log")).unwrap();
+    let start = Instant::now();
+    let result = db.query("SELECT * FROM data").await;
+
+    assert!(result.is_err());
+    assert!(
+        start.elapsed() < Duration::from_secs(5),
+        "permanent error was retried: {:?}",
+        start.elapsed()
+    );
+}
@@ -0,0 +1,191 @@
+// REST API Integration Tests
+// Tests the HTTP server: inserting rows and querying them as JSON, listing
+// and describing tables, error statuses, and Basic authentication with
+// permissions from the user's roles
+
+use arrow::datatypes::{DataType, Field, Schema, SchemaRef};
+use fsdb::DatabaseOps;
+use fsdb::rest::{RestConfig, RestServer};
+use serde_json::{Value, json};
+use std::net::SocketAddr;
+use std::path::Path;
+use std::sync::Arc;
+use tempfile::TempDir;
+
+fn create_schema() -> SchemaRef {
+    Arc::new(Schema::new(vec![
+        Field::new("id", DataType::Int32, false),
+        Field::new("name", DataType::Utf8, true),
+    ]))
+}
+
+async fn start(db: DatabaseOps) -> RestServer {
+    let config = RestConfig {
+        addr: SocketAddr::from(([127, 0, 0, 1], 0)),
+        ..RestConfig::default()
+    };
+    RestServer::with_config(Arc::new(db), config).await.unwrap()
+}
+
+/// Send a request, returning the status and JSON body
+async fn send(request: reqwest::RequestBuilder) -> (u16, Value) {
+    let response = request.send().await.unwrap();
+    let status = response.status().as_u16();
+    let body = response.text().await.unwrap();
+    (status, serde_json::from_str(&body).unwrap_or(Value::Null))
+}
+
+fn post(client: &reqwest::Client, url: String, body: Value) -> reqwest::RequestBuilder {
+    client
+        .post(url)
+        .header("content-type", "application/json")
+        .body(body.to_string())
+}
+
+#[tokio::test]
+async fn test_insert_and_query() {
+    let temp_dir = TempDir::new().unwrap();
+    let db = DatabaseOps::create(temp_dir.path().join("db"), create_schema())
+        .await
+        .unwrap();
+    let server = start(db).await;
+    let base = format!("http://{}", server.local_addr());
+    let client = reqwest::Client::new();
+
+    let rows = json!([{"id": 1, "name": "ada"}, {"id": 2, "name": null}, {"id": 3}]);
+    let (status, body) = send(post(
+        &client,
+        format!("{}/api/tables/data/rows", base),
+        rows,
+    ))
+    .await;
+    assert_eq!(status, 200, "{}", body);
+    assert_eq!(body["rows_inserted"], 3);
+
+    let sql = json!({"sql": "SELECT id, name FROM data WHERE id < 3 ORDER BY id"});
+    let (status, body) = send(post(&client, format!("{}/api/query", base), sql)).await;
+    assert_eq!(status, 200, "{}", body);
+    assert_eq!(body["columns"][0]["name"], "id");
+    assert_eq!(body["columns"][1]["type"], "Utf8");
+    assert_eq!(body["rows"], json!([[1, "ada"], [2, null]]));
+
+    let (status, body) = send(client.get(format!("{}/api/tables", base))).await;
+    assert_eq!(status, 200);
+    assert_eq!(body["tables"], json!(["data"]));
+    let (status, body) = send(client.get(format!("{}/api/tables/data", base))).await;
+    assert_eq!(status, 200);
+    assert_eq!(body["num_rows"], 3);
+    assert_eq!(body["columns"].as_array().unwrap().len(), 2);
+
+    let (status, body) = send(client.get(format!("{}/health", base))).await;
+    assert_eq!(status, 200);
+    assert!(body["components"].as_array().is_some_and(|c| !c.is_empty()));
+
+    server.shutdown().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_error_statuses() {
+    let temp_dir = TempDir::new().unwrap();
+    let db = DatabaseOps::create(temp_dir.path().join("db"), create_schema())
+        .await
+        .unwrap();
+    let server = start(db).await;
+    let base = format!("http://{}", server.local_addr());
+    let client = reqwest::Client::new();
+
+    let (status, body) = send(post(
+        &client,
+        format!("{}/api/query", base),
+        json!({"sql": "SELEC * FROM data"}),
+    ))
+    .await;
+    assert_eq!(status, 400);
+    assert!(body["error"].is_string());
+
+    // Rows the schema can't take, and a body that isn't rows at all
+    for rows in [
+        json!([{"id": "one"}]),
+        json!([{"name": "no id"}]),
+        json!([]),
+    ] {
+        let url = format!("{}/api/tables/data/rows", base);
+        let (status, body) = send(post(&client, url, rows.clone())).await;
+        assert_eq!(status, 400, "{}: {}", rows, body);
+    }
+    let url = format!("{}/api/tables/data/rows", base);
+    let (status, _) = send(post(&client, url, json!({"id": 1}))).await;
+    assert_eq!(status, 422);
+
+    let (status, _) = send(client.get(format!("{}/api/tables/missing", base))).await;
+    assert_eq!(status, 404);
+}
+
+async fn create_auth_db(path: &Path) -> DatabaseOps {
+    let db = DatabaseOps::create_with_auth(path, create_schema(), true)
+        .await
+        .unwrap();
+    db.create_user("alice", "secret", &["read"]).await.unwrap();
+    db.create_user("bob", "secret", &["read", "write"])
+        .await
+        .unwrap();
+    db
+}
+
+#[tokio::test]
+async fn test_basic_authentication() {
+    let temp_dir = TempDir::new().unwrap();
+    let db = create_auth_db(&temp_dir.path().join("db")).await;
+    let server = start(db).await;
+    let base = format!("http://{}", server.local_addr());
+    let client = reqwest::Client::new();
+    let rows = || json!([{"id": 1, "name": "ada"}]);
+    let sql = || json!({"sql": "SELECT COUNT(*) AS n FROM data"});
+
+    // No or wrong credentials
+    let response = post(&client, format!("{}/api/query", base), sql())
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 401);
+    assert!(response.headers().contains_key("www-authenticate"));
+    let request =
+        post(&client, format!("{}/api/query", base), sql()).basic_auth("alice", Some("wrong"));
+    assert_eq!(send(request).await.0, 401);
+
+    // Writers can insert, readers only read
+    let url = format!("{}/api/tables/data/rows", base);
+    let request = post(&client, url.clone(), rows()).basic_auth("bob", Some("secret"));
+    assert_eq!(send(request).await.0, 200);
+    let request = post(&client, url, rows()).basic_auth("alice", Some("secret"));
+    assert_eq!(send(request).await.0, 403);
+
+    let request =
+        post(&client, format!("{}/api/query", base), sql()).basic_auth("alice", Some("secret"));
+    let (status, body) = send(request).await;
+    assert_eq!(status, 200, "{}", body);
+    assert_eq!(body["rows"], json!([[1]]));
+
+    // Health checks don't need credentials
+    assert_eq!(send(client.get(format!("{}/health", base))).await.0, 200);
+}
+
+#[tokio::test]
+async fn test_sessions_end_when_roles_change() {
+    let temp_dir = TempDir::new().unwrap();
+    let db = Arc::new(create_auth_db(&temp_dir.path().join("db")).await);
+    let config = RestConfig {
+        addr: SocketAddr::from(([127, 0, 0, 1], 0)),
+        ..RestConfig::default()
+    };
+    let server = RestServer::with_config(db.clone(), config).await.unwrap();
+    let url = format!("http://{}/api/tables/data/rows", server.local_addr());
+    let client = reqwest::Client::new();
+    let insert =
+        || post(&client, url.clone(), json!([{"id": 1}])).basic_auth("bob", Some("secret"));
+
+    assert_eq!(send(insert()).await.0, 200);
+    // Bob's kept session doesn't outlive his write role
+    db.revoke_role_from_user("bob", "write").await.unwrap();
+    assert_eq!(send(insert()).await.0, 403);
+}
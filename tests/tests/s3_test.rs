@@ -15,6 +15,7 @@ use arrow::array::{Int32Array, StringArray};
 use arrow::datatypes::{DataType, Field, Schema};
 use arrow::record_batch::RecordBatch;
 use fsdb::DatabaseOps;
+use fsdb::storage::s3::{MIN_PART_SIZE, minio_config};
 use std::env;
 use std::sync::Arc;
 
@@ -269,3 +270,52 @@ async fn test_s3_multiple_inserts() {
 }
 
 use arrow::array::Int64Array;
+
+#[tokio::test]
+async fn test_s3_multipart_upload() {
+    if !is_minio_available().await {
+        eprintln!("Skipping S3 test: MinIO not available");
+        return;
+    }
+
+    let (endpoint, access_key, secret_key, bucket) = get_minio_config();
+    let s3_path = format!("s3://{}/test_db_{}", bucket, uuid::Uuid::new_v4());
+    let config = minio_config(&endpoint, &access_key, &secret_key)
+        .with_multipart(MIN_PART_SIZE, MIN_PART_SIZE);
+
+    let schema = create_test_schema();
+    let db = DatabaseOps::create_with_s3_config(&s3_path, schema.clone(), config.clone())
+        .await
+        .unwrap();
+
+    // Random strings, so the data file stays well over one part once compressed
+    let rows = 400_000;
+    let names: Vec<String> = (0..rows)
+        .map(|_| uuid::Uuid::new_v4().to_string())
+        .collect();
+    let emails: Vec<String> = names.iter().map(|n| format!("{}@example.com", n)).collect();
+    let batch = RecordBatch::try_new(
+        schema,
+        vec![
+            Arc::new(Int32Array::from_iter_values(0..rows)),
+            Arc::new(StringArray::from(names)),
+            Arc::new(StringArray::from(emails)),
+        ],
+    )
+    .unwrap();
+    db.insert(batch).await.unwrap();
+
+    let db = DatabaseOps::open_with_s3_config(&s3_path, config)
+        .await
+        .unwrap();
+    let batches = db
+        .query("SELECT COUNT(*) as count FROM data")
+        .await
+        .unwrap();
+    let count_col = batches[0]
+        .column(0)
+        .as_any()
+        .downcast_ref::<Int64Array>()
+        .unwrap();
+    assert_eq!(count_col.value(0), rows as i64);
+}
@@ -0,0 +1,190 @@
+// Scan Concurrency Integration Tests
+// Tests bounded parallel reads of many-file tables
+
+use arrow::array::{ArrayRef, Int32Array, Int64Array, RecordBatch, StringArray};
+use arrow::datatypes::{DataType, Field, Schema, SchemaRef};
+use fsdb::DatabaseOps;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tempfile::TempDir;
+
+const FILES: i32 = 32;
+
+fn create_schema() -> SchemaRef {
+    Arc::new(Schema::new(vec![
+        Field::new("id", DataType::Int32, false),
+        Field::new("name", DataType::Utf8, false),
+    ]))
+}
+
+fn create_batch(schema: SchemaRef, start: i32, count: i32) -> RecordBatch {
+    let ids: Vec<i32> = (start..start + count).collect();
+    let names: Vec<String> = ids.iter().map(|id| format!("user_{}", id)).collect();
+    RecordBatch::try_new(
+        schema,
+        vec![
+            Arc::new(Int32Array::from(ids)) as ArrayRef,
+            Arc::new(StringArray::from(names)) as ArrayRef,
+        ],
+    )
+    .unwrap()
+}
+
+/// Create a table with one data file per insert
+async fn create_many_file_table(path: &Path, rows_per_file: i32) {
+    let schema = create_schema();
+    let db = DatabaseOps::create(path, schema.clone()).await.unwrap();
+    for file in 0..FILES {
+        db.insert(create_batch(
+            schema.clone(),
+            file * rows_per_file,
+            rows_per_file,
+        ))
+        .await
+        .unwrap();
+    }
+}
+
+fn collect_ids(batches: &[RecordBatch]) -> Vec<i32> {
+    batches
+        .iter()
+        .flat_map(|b| {
+            b.column(0)
+                .as_any()
+                .downcast_ref::<Int32Array>()
+                .unwrap()
+                .values()
+                .to_vec()
+        })
+        .collect()
+}
+
+#[tokio::test]
+async fn test_concurrent_scan_matches_serial_scan() {
+    let temp_dir = TempDir::new().unwrap();
+    let path = temp_dir.path().join("scan_db");
+    create_many_file_table(&path, 100).await;
+
+    let serial = DatabaseOps::open(&path)
+        .await
+        .unwrap()
+        .with_scan_concurrency(1);
+    let parallel = DatabaseOps::open(&path)
+        .await
+        .unwrap()
+        .with_scan_concurrency(8);
+    assert_eq!(serial.scan_concurrency(), 1);
+    assert_eq!(parallel.scan_concurrency(), 8);
+
+    // Ordered results come back in order however many groups read them
+    let sql = "SELECT id FROM data ORDER BY id";
+    let expected: Vec<i32> = (0..FILES * 100).collect();
+    assert_eq!(collect_ids(&serial.query(sql).await.unwrap()), expected);
+    assert_eq!(collect_ids(&parallel.query(sql).await.unwrap()), expected);
+
+    // Unordered results hold the same rows
+    let mut ids = collect_ids(&parallel.query("SELECT id FROM data").await.unwrap());
+    ids.sort_unstable();
+    assert_eq!(ids, expected);
+
+    let count = parallel.query("SELECT COUNT(*) FROM data").await.unwrap();
+    let count = count[0]
+        .column(0)
+        .as_any()
+        .downcast_ref::<Int64Array>()
+        .unwrap()
+        .value(0);
+    assert_eq!(count, (FILES * 100) as i64);
+}
+
+#[tokio::test]
+async fn test_cursor_prefetches_in_order() {
+    let temp_dir = TempDir::new().unwrap();
+    let path = temp_dir.path().join("scan_db");
+    create_many_file_table(&path, 50).await;
+
+    let db = DatabaseOps::open(&path)
+        .await
+        .unwrap()
+        .with_scan_concurrency(4);
+    let cursor = db
+        .open_cursor("SELECT id FROM data ORDER BY id")
+        .await
+        .unwrap();
+    let mut ids = Vec::new();
+    loop {
+        let (rows, has_more) = db.fetch(cursor, 300).await.unwrap();
+        ids.extend(collect_ids(&rows));
+        if !has_more {
+            break;
+        }
+    }
+    assert_eq!(ids, (0..FILES * 50).collect::<Vec<_>>());
+}
+
+#[tokio::test]
+async fn test_unreadable_file_fails_the_scan_promptly() {
+    let temp_dir = TempDir::new().unwrap();
+    let path = temp_dir.path().join("scan_db");
+    create_many_file_table(&path, 100).await;
+
+    // Corrupt one of the data files
+    let victim = std::fs::read_dir(&path)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .find(|p| p.extension().is_some_and(|e| e == "parquet"))
+        .unwrap();
+    std::fs::write(&victim, b"not parquet").unwrap();
+
+    let db = DatabaseOps::open(&path)
+        .await
+        .unwrap()
+        .with_scan_concurrency(8);
+    let start = Instant::now();
+    let result = tokio::time::timeout(
+        Duration::from_secs(30),
+        db.query("SELECT * FROM data ORDER BY id"),
+    )
+    .await
+    .expect("a failed file should end the scan, not stall it");
+    assert!(result.is_err());
+    assert!(start.elapsed() < Duration::from_secs(30));
+}
+
+/// Benchmark: a many-file scan with several files in flight against one at a time
+/// To run: cargo test test_scan_concurrency_benchmark -- --ignored --nocapture
+#[tokio::test(flavor = "multi_thread")]
+#[ignore = "Benchmark: compares wall time of serial and concurrent scans. Run with: cargo test -- --ignored"]
+async fn test_scan_concurrency_benchmark() {
+    let temp_dir = TempDir::new().unwrap();
+    let path = temp_dir.path().join("scan_db");
+    create_many_file_table(&path, 20_000).await;
+    let sql = "SELECT COUNT(*), MAX(name) FROM data WHERE name LIKE '%9%'";
+
+    let mut timings = Vec::new();
+    for concurrency in [1, 8] {
+        let db = DatabaseOps::open(&path)
+            .await
+            .unwrap()
+            .with_scan_concurrency(concurrency);
+        // Warm the OS page cache so both runs read from memory
+        db.query(sql).await.unwrap();
+        let start = Instant::now();
+        for _ in 0..3 {
+            db.query(sql).await.unwrap();
+        }
+        let elapsed = start.elapsed();
+        println!("scan_concurrency = {}: {:?}", concurrency, elapsed);
+        timings.push(elapsed);
+    }
+
+    if std::thread::available_parallelism().map_or(1, |n| n.get()) > 1 {
+        assert!(
+            timings[1] < timings[0],
+            "concurrent scan ({:?}) should beat serial scan ({:?})",
+            timings[1],
+            timings[0]
+        );
+    }
+}
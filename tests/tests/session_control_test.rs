@@ -0,0 +1,168 @@
+// Session Control Integration Tests
+// Tests listing open cursors and transactions, and cancelling them from
+// outside so their pinned versions and locks are freed
+
+use arrow::array::{ArrayRef, Int32Array, RecordBatch, StringArray};
+use arrow::datatypes::{DataType, Field, Schema, SchemaRef};
+use fsdb::lock_manager::LockMode;
+use fsdb::{DatabaseOps, Error};
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+use tempfile::TempDir;
+
+fn create_schema() -> SchemaRef {
+    Arc::new(Schema::new(vec![
+        Field::new("id", DataType::Int32, false),
+        Field::new("name", DataType::Utf8, false),
+    ]))
+}
+
+fn batch(id: i32) -> RecordBatch {
+    RecordBatch::try_new(
+        create_schema(),
+        vec![
+            Arc::new(Int32Array::from(vec![id])) as ArrayRef,
+            Arc::new(StringArray::from(vec![format!("user{}", id)])) as ArrayRef,
+        ],
+    )
+    .unwrap()
+}
+
+fn count_parquet_files(path: &Path) -> usize {
+    std::fs::read_dir(path)
+        .unwrap()
+        .map(|e| e.unwrap().path())
+        .filter(|p| p.extension().and_then(|e| e.to_str()) == Some("parquet"))
+        .count()
+}
+
+#[tokio::test]
+async fn test_cancelled_cursor_unpins_version() {
+    let temp_dir = TempDir::new().unwrap();
+    let path = temp_dir.path().join("db");
+    let db = DatabaseOps::create(&path, create_schema()).await.unwrap();
+    for id in 0..4 {
+        db.insert(batch(id)).await.unwrap();
+    }
+
+    let cursor = db
+        .open_cursor("SELECT id FROM data ORDER BY id")
+        .await
+        .unwrap();
+    db.fetch(cursor, 1).await.unwrap();
+    let open = db.list_cursors().await.unwrap();
+    assert_eq!(open.len(), 1);
+    assert_eq!(open[0].id, cursor);
+    assert_eq!(open[0].rows_fetched, 1);
+    assert!(!open[0].exhausted);
+
+    // The cursor keeps the pre-OPTIMIZE files from being vacuumed
+    db.optimize().await.unwrap();
+    db.vacuum(0).await.unwrap();
+    assert_eq!(count_parquet_files(&path), 5);
+
+    assert!(db.cancel_cursor(cursor).await.unwrap());
+    assert!(db.list_cursors().await.unwrap().is_empty());
+    db.vacuum(0).await.unwrap();
+    assert_eq!(count_parquet_files(&path), 1);
+
+    // The owner learns of the cancel once, then the cursor is just gone
+    assert!(matches!(
+        db.fetch(cursor, 1).await,
+        Err(Error::Cancelled(_))
+    ));
+    assert!(matches!(
+        db.fetch(cursor, 1).await,
+        Err(Error::CursorExpired(_))
+    ));
+    assert!(!db.cancel_cursor(cursor).await.unwrap());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_aborted_transaction_releases_locks() {
+    let temp_dir = TempDir::new().unwrap();
+    let db = Arc::new(
+        DatabaseOps::create(temp_dir.path().join("db"), create_schema())
+            .await
+            .unwrap(),
+    );
+
+    let stuck = db.begin_transaction().await.unwrap();
+    stuck.lock("table:data", LockMode::Exclusive).await.unwrap();
+    stuck.insert(batch(1)).await.unwrap();
+
+    let open = db.list_transactions().unwrap();
+    assert_eq!(open.len(), 1);
+    assert_eq!((open[0].id, open[0].locks_held), (stuck.id(), 1));
+
+    // Another transaction waits on the lock until the stuck one is aborted
+    let waiter = {
+        let db = db.clone();
+        tokio::spawn(async move {
+            let txn = db.begin_transaction().await.unwrap();
+            txn.lock("table:data", LockMode::Exclusive).await.unwrap();
+            txn.insert(batch(2)).await.unwrap();
+            txn.commit().await.unwrap();
+        })
+    };
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    assert!(!waiter.is_finished());
+
+    assert!(db.abort_transaction(stuck.id()).await.unwrap());
+    tokio::time::timeout(Duration::from_secs(5), waiter)
+        .await
+        .expect("lock was not released")
+        .unwrap();
+
+    // The owner's next operation fails, and its buffered row never lands
+    assert!(matches!(
+        stuck.insert(batch(3)).await,
+        Err(Error::Cancelled(_))
+    ));
+    assert!(matches!(
+        stuck.query("SELECT * FROM data").await,
+        Err(Error::Cancelled(_))
+    ));
+    let stuck_id = stuck.id();
+    assert!(matches!(stuck.commit().await, Err(Error::Cancelled(_))));
+    let rows = db.query_rows("SELECT id FROM data").await.unwrap();
+    assert_eq!(rows.len(), 1);
+
+    assert!(db.list_transactions().unwrap().is_empty());
+    assert!(!db.abort_transaction(stuck_id).await.unwrap());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_abort_fails_pending_lock_wait() {
+    let temp_dir = TempDir::new().unwrap();
+    let db = Arc::new(
+        DatabaseOps::create(temp_dir.path().join("db"), create_schema())
+            .await
+            .unwrap(),
+    );
+
+    let holder = db.begin_transaction().await.unwrap();
+    holder.lock("row:1", LockMode::Exclusive).await.unwrap();
+    let waiter = Arc::new(db.begin_transaction().await.unwrap());
+    let waiter_id = waiter.id();
+
+    let wait = {
+        let waiter = waiter.clone();
+        tokio::spawn(async move { waiter.lock("row:1", LockMode::Exclusive).await })
+    };
+    tokio::time::sleep(Duration::from_millis(200)).await;
+
+    assert!(db.abort_transaction(waiter_id).await.unwrap());
+    let result = tokio::time::timeout(Duration::from_secs(5), wait)
+        .await
+        .expect("wait was not interrupted")
+        .unwrap();
+    assert!(matches!(result, Err(Error::Cancelled(_))));
+
+    // The holder is unaffected
+    let open = db.list_transactions().unwrap();
+    assert_eq!(open.len(), 1);
+    assert_eq!(open[0].id, holder.id());
+    holder.rollback().await.unwrap();
+}
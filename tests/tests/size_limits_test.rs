@@ -0,0 +1,258 @@
+// Size Limit Integration Tests
+// Tests rejecting rows and values over the configured size limits, on the
+// handle they were set on and on the sessions and tables it opens
+
+use arrow::array::{ArrayRef, BinaryArray, Int32Array, Int64Array, RecordBatch, StringArray};
+use arrow::datatypes::{DataType, Field, Schema, SchemaRef};
+use fsdb::nfs::file_views::CsvFileView;
+use fsdb::storage::limits::SizeLimits;
+use fsdb::{DatabaseOps, Error};
+use std::sync::Arc;
+use tempfile::TempDir;
+
+fn create_schema() -> SchemaRef {
+    Arc::new(Schema::new(vec![
+        Field::new("id", DataType::Int32, false),
+        Field::new("name", DataType::Utf8, true),
+        Field::new("photo", DataType::Binary, true),
+    ]))
+}
+
+fn create_batch(id: i32, name: &str, photo: &[u8]) -> RecordBatch {
+    RecordBatch::try_new(
+        create_schema(),
+        vec![
+            Arc::new(Int32Array::from(vec![id])) as ArrayRef,
+            Arc::new(StringArray::from(vec![name])) as ArrayRef,
+            Arc::new(BinaryArray::from(vec![Some(photo)])) as ArrayRef,
+        ],
+    )
+    .unwrap()
+}
+
+async fn create_db(temp_dir: &TempDir, limits: SizeLimits) -> Arc<DatabaseOps> {
+    let db = DatabaseOps::create(temp_dir.path().join("db"), create_schema())
+        .await
+        .unwrap()
+        .with_size_limits(limits);
+    db.insert(create_batch(1, "alice", b"tiny")).await.unwrap();
+    Arc::new(db)
+}
+
+async fn row_count(db: &DatabaseOps) -> i64 {
+    let results = db.query("SELECT COUNT(*) FROM data").await.unwrap();
+    results[0]
+        .column(0)
+        .as_any()
+        .downcast_ref::<Int64Array>()
+        .unwrap()
+        .value(0)
+}
+
+#[tokio::test]
+async fn test_global_limits_reject_inserts() {
+    let temp_dir = TempDir::new().unwrap();
+    let limits = SizeLimits::new()
+        .with_max_field_bytes(16)
+        .with_max_row_bytes(32);
+    let db = create_db(&temp_dir, limits).await;
+
+    let err = db
+        .insert(create_batch(2, &"x".repeat(17), b"tiny"))
+        .await
+        .unwrap_err();
+    assert!(
+        matches!(&err, Error::FieldTooLarge { row: 1, column, bytes: 17, limit: 16 } if column == "name"),
+        "{}",
+        err
+    );
+
+    // Every value fits, but not all of them together: 4 + 16 + 16
+    let err = db
+        .insert(create_batch(2, &"x".repeat(16), &[0; 16]))
+        .await
+        .unwrap_err();
+    assert!(
+        matches!(
+            err,
+            Error::RowTooLarge {
+                row: 1,
+                bytes: 36,
+                limit: 32
+            }
+        ),
+        "{}",
+        err
+    );
+
+    // Buffered rows are checked before they're buffered
+    assert!(matches!(
+        db.insert_buffered(create_batch(3, &"x".repeat(17), b""))
+            .await,
+        Err(Error::FieldTooLarge { .. })
+    ));
+    db.flush_write_buffer().await.unwrap();
+
+    assert_eq!(row_count(&db).await, 1);
+    db.insert(create_batch(2, "bob", b"small")).await.unwrap();
+    assert_eq!(row_count(&db).await, 2);
+}
+
+#[tokio::test]
+async fn test_column_limit_allows_large_blobs() {
+    let temp_dir = TempDir::new().unwrap();
+    let limits = SizeLimits::new()
+        .with_max_field_bytes(16)
+        .with_column_limit("photo", 4096)
+        .with_max_row_bytes(8192);
+    let db = create_db(&temp_dir, limits).await;
+
+    db.insert(create_batch(2, "bob", &[7; 4096])).await.unwrap();
+    let err = db
+        .insert(create_batch(3, "carol", &[7; 4097]))
+        .await
+        .unwrap_err();
+    assert!(
+        matches!(&err, Error::FieldTooLarge { column, limit: 4096, .. } if column == "photo"),
+        "{}",
+        err
+    );
+
+    // Text columns keep the global limit
+    let err = db
+        .insert(create_batch(3, &"x".repeat(17), b""))
+        .await
+        .unwrap_err();
+    assert!(
+        matches!(&err, Error::FieldTooLarge { column, limit: 16, .. } if column == "name"),
+        "{}",
+        err
+    );
+    assert_eq!(row_count(&db).await, 2);
+}
+
+#[tokio::test]
+async fn test_csv_writes_checked_before_parsing() {
+    let temp_dir = TempDir::new().unwrap();
+    let limits = SizeLimits::new()
+        .with_max_field_bytes(16)
+        .with_column_limit("photo", 64);
+    let db = create_db(&temp_dir, limits).await;
+    let view = CsvFileView::new(db.clone());
+
+    // A giant line in an append
+    let giant = format!("2,bob,\n3,{},\n", "y".repeat(1 << 20));
+    let err = view.apply_write(giant.as_bytes(), None).await.unwrap_err();
+    assert!(
+        matches!(&err, Error::FieldTooLarge { row: 2, column, bytes, .. } if column == "name" && *bytes == 1 << 20),
+        "{}",
+        err
+    );
+
+    // Binary cells are measured decoded: 48 bytes as 64 base64 characters
+    let photo = "AAAA".repeat(16);
+    let rewrite = format!("id,name,photo\n1,alice,{}\n2,bob,\n", photo);
+    view.apply_write(rewrite.as_bytes(), None).await.unwrap();
+    assert_eq!(row_count(&db).await, 2);
+
+    let rewrite = format!("id,name,photo\n1,alice,{}\n", "AAAA".repeat(22));
+    let err = view
+        .apply_write(rewrite.as_bytes(), None)
+        .await
+        .unwrap_err();
+    assert!(
+        matches!(&err, Error::FieldTooLarge { column, bytes: 66, .. } if column == "photo"),
+        "{}",
+        err
+    );
+    assert_eq!(row_count(&db).await, 2);
+}
+
+#[tokio::test]
+async fn test_merge_checks_rows_before_writing() {
+    let temp_dir = TempDir::new().unwrap();
+    let limits = SizeLimits::new().with_max_field_bytes(16);
+    let db = create_db(&temp_dir, limits).await;
+
+    // An update over the limit fails before the matched row is deleted
+    let err = db
+        .merge()
+        .await
+        .unwrap()
+        .with_source(create_batch(1, &"x".repeat(17), b"tiny"), "source")
+        .on("target.id = source.id")
+        .when_matched_update()
+        .set("name", "source.name")
+        .then()
+        .execute()
+        .await
+        .unwrap_err();
+    assert!(
+        matches!(&err, Error::FieldTooLarge { column, bytes: 17, .. } if column == "name"),
+        "{}",
+        err
+    );
+
+    let err = db
+        .merge()
+        .await
+        .unwrap()
+        .with_source(create_batch(2, "bob", &[7; 17]), "source")
+        .on("target.id = source.id")
+        .when_not_matched_insert()
+        .values_all()
+        .execute()
+        .await
+        .unwrap_err();
+    assert!(
+        matches!(&err, Error::FieldTooLarge { column, .. } if column == "photo"),
+        "{}",
+        err
+    );
+
+    let results = db.query("SELECT name FROM data").await.unwrap();
+    assert_eq!(results[0].num_rows(), 1);
+    assert_eq!(
+        results[0]
+            .column(0)
+            .as_any()
+            .downcast_ref::<StringArray>()
+            .unwrap()
+            .value(0),
+        "alice"
+    );
+}
+
+#[tokio::test]
+async fn test_derived_handles_keep_limits() {
+    let temp_dir = TempDir::new().unwrap();
+    let db = DatabaseOps::create_with_auth(temp_dir.path().join("db"), create_schema(), true)
+        .await
+        .unwrap()
+        .with_size_limits(SizeLimits::new().with_max_field_bytes(16));
+    db.create_user("bob", "secret", &["read", "write"])
+        .await
+        .unwrap();
+    db.create_table("photos", create_schema()).await.unwrap();
+
+    // Sessions and named table handles check writes like the handle they came from
+    let session = db.login("bob", "secret").await.unwrap();
+    let photos = db.table("photos").await.unwrap();
+    for handle in [&session, &photos] {
+        let err = handle
+            .insert(create_batch(2, &"x".repeat(17), b"tiny"))
+            .await
+            .unwrap_err();
+        assert!(
+            matches!(&err, Error::FieldTooLarge { column, bytes: 17, .. } if column == "name"),
+            "{}",
+            err
+        );
+        handle
+            .insert(create_batch(3, "carol", b"tiny"))
+            .await
+            .unwrap();
+    }
+    assert_eq!(row_count(&session).await, 1);
+    assert_eq!(row_count(&photos).await, 1);
+}
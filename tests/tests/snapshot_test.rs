@@ -0,0 +1,166 @@
+// Snapshot Export/Import Tests
+// Tests exporting one table version to a portable archive and restoring it elsewhere
+
+use arrow::array::{ArrayRef, Int32Array, Int64Array, RecordBatch, StringArray};
+use arrow::datatypes::{DataType, Field, Schema, SchemaRef};
+use fsdb::{DatabaseOps, Error};
+use std::sync::Arc;
+use tempfile::TempDir;
+
+fn create_schema() -> SchemaRef {
+    Arc::new(Schema::new(vec![
+        Field::new("id", DataType::Int32, false),
+        Field::new("name", DataType::Utf8, false),
+    ]))
+}
+
+fn batch(ids: Vec<i32>) -> RecordBatch {
+    let names: Vec<String> = ids.iter().map(|id| format!("user_{}", id)).collect();
+    RecordBatch::try_new(
+        create_schema(),
+        vec![
+            Arc::new(Int32Array::from(ids)) as ArrayRef,
+            Arc::new(StringArray::from(names)) as ArrayRef,
+        ],
+    )
+    .unwrap()
+}
+
+async fn count(db: &DatabaseOps) -> i64 {
+    let batches = db.query("SELECT COUNT(*) FROM data").await.unwrap();
+    batches[0]
+        .column(0)
+        .as_any()
+        .downcast_ref::<Int64Array>()
+        .unwrap()
+        .value(0)
+}
+
+/// Table at version 3: create (0) and three inserts of 2 rows each
+async fn create_table(temp_dir: &TempDir) -> DatabaseOps {
+    let db = DatabaseOps::create(temp_dir.path().join("source"), create_schema())
+        .await
+        .unwrap();
+    for i in 0..3 {
+        db.insert(batch(vec![i * 2, i * 2 + 1])).await.unwrap();
+    }
+    db
+}
+
+#[tokio::test]
+async fn test_export_import_round_trip() {
+    let temp_dir = TempDir::new().unwrap();
+    let db = create_table(&temp_dir).await;
+    let archive = temp_dir.path().join("snapshot.tar");
+
+    let manifest = db.export_snapshot(2, &archive).await.unwrap();
+    assert_eq!(manifest.version, 2);
+
+    // Commits after the export don't affect the archive
+    db.insert(batch(vec![100])).await.unwrap();
+
+    let dest = temp_dir.path().join("restored");
+    let restored = DatabaseOps::import_snapshot(&archive, &dest).await.unwrap();
+    assert_eq!(count(&restored).await, 4);
+    assert_eq!(restored.schema().fields().len(), 2);
+
+    // Every archived file is byte-identical to the source
+    for file in &manifest.files {
+        let original = std::fs::read(temp_dir.path().join("source").join(&file.path)).unwrap();
+        let copy = std::fs::read(dest.join(&file.path)).unwrap();
+        assert_eq!(original, copy, "{} differs", file.path);
+    }
+    // Version 3 was never exported
+    assert!(
+        !dest
+            .join("_delta_log")
+            .join("00000000000000000003.json")
+            .exists()
+    );
+
+    // The restored table is writable
+    restored.insert(batch(vec![200])).await.unwrap();
+    assert_eq!(count(&restored).await, 5);
+}
+
+#[tokio::test]
+async fn test_export_fails_when_file_is_missing() {
+    let temp_dir = TempDir::new().unwrap();
+    let db = create_table(&temp_dir).await;
+    let archive = temp_dir.path().join("snapshot.tar");
+
+    // Simulate vacuum removing a file version 3 still references
+    let manifest = db
+        .export_snapshot(3, temp_dir.path().join("full.tar"))
+        .await
+        .unwrap();
+    let data_file = manifest
+        .files
+        .iter()
+        .find(|f| f.path.ends_with(".parquet") && !f.path.starts_with("_delta_log"))
+        .unwrap();
+    std::fs::remove_file(temp_dir.path().join("source").join(&data_file.path)).unwrap();
+
+    let result = db.export_snapshot(3, &archive).await;
+    assert!(
+        matches!(&result, Err(Error::InvalidOperation(msg)) if msg.contains(&data_file.path)),
+        "{:?}",
+        result
+    );
+    assert!(!archive.exists());
+}
+
+#[tokio::test]
+async fn test_import_rejects_corrupted_archive() {
+    let temp_dir = TempDir::new().unwrap();
+    let db = create_table(&temp_dir).await;
+    let archive = temp_dir.path().join("snapshot.tar");
+    db.export_snapshot(3, &archive).await.unwrap();
+
+    // Flip a byte inside the first file's contents, past its 512-byte header
+    let mut bytes = std::fs::read(&archive).unwrap();
+    bytes[600] ^= 0xff;
+    std::fs::write(&archive, &bytes).unwrap();
+
+    let dest = temp_dir.path().join("restored");
+    let result = DatabaseOps::import_snapshot(&archive, &dest).await;
+    assert!(
+        matches!(&result, Err(Error::InvalidOperation(msg)) if msg.contains("Checksum mismatch")),
+        "{:?}",
+        result.err()
+    );
+    assert!(!dest.exists());
+}
+
+#[tokio::test]
+async fn test_export_unknown_version_fails() {
+    let temp_dir = TempDir::new().unwrap();
+    let db = create_table(&temp_dir).await;
+
+    let result = db
+        .export_snapshot(42, temp_dir.path().join("snapshot.tar"))
+        .await;
+    assert!(result.is_err());
+}
+
+#[tokio::test]
+async fn test_export_requires_admin() {
+    let temp_dir = TempDir::new().unwrap();
+    let path = temp_dir.path().join("source");
+    let db = DatabaseOps::create_with_auth(&path, create_schema(), true)
+        .await
+        .unwrap();
+    db.insert(batch(vec![1, 2])).await.unwrap();
+    db.create_user("reader", "secret", &["read"]).await.unwrap();
+    db.create_user("ops", "secret", &["admin"]).await.unwrap();
+    let archive = temp_dir.path().join("snapshot.tar");
+
+    let reader = db.login("reader", "secret").await.unwrap();
+    let err = reader.export_snapshot(1, &archive).await.unwrap_err();
+    assert!(matches!(err, Error::PermissionDenied(_)), "{:?}", err);
+    assert!(!archive.exists());
+
+    let ops = db.login("ops", "secret").await.unwrap();
+    let manifest = ops.export_snapshot(1, &archive).await.unwrap();
+    assert_eq!(manifest.version, 1);
+}
@@ -0,0 +1,110 @@
+// Spill Configuration Integration Tests
+// Tests that scratch data goes to the configured directory and stays under the cap
+
+use arrow::array::Int64Array;
+use arrow::datatypes::{DataType, Field, Schema};
+use fsdb::bulk_load::LoadOptions;
+use fsdb::storage::spill::SpillConfig;
+use fsdb::{DatabaseOps, Error};
+use std::fmt::Write as _;
+use std::path::Path;
+use std::sync::Arc;
+use tempfile::TempDir;
+
+/// Write a CSV of 100 rows where every `bad_every`th row has a non-numeric id
+fn write_csv(path: &Path, bad_every: i32) {
+    let mut csv = String::from("id,name\n");
+    for i in 1..=100 {
+        if i % bad_every == 0 {
+            writeln!(csv, "oops,user_{}", i).unwrap();
+        } else {
+            writeln!(csv, "{},user_{}", i, i).unwrap();
+        }
+    }
+    std::fs::write(path, csv).unwrap();
+}
+
+async fn create_db(temp_dir: &TempDir, max_spill_bytes: u64) -> DatabaseOps {
+    let schema = Arc::new(Schema::new(vec![
+        Field::new("id", DataType::Int32, false),
+        Field::new("name", DataType::Utf8, false),
+    ]));
+    DatabaseOps::create(temp_dir.path().join("db"), schema)
+        .await
+        .unwrap()
+        .with_spill_config(SpillConfig {
+            temp_dir: temp_dir.path().join("spill"),
+            max_spill_bytes: Some(max_spill_bytes),
+        })
+}
+
+async fn count_rows(db: &DatabaseOps) -> i64 {
+    let results = db.query("SELECT COUNT(*) FROM data").await.unwrap();
+    results[0]
+        .column(0)
+        .as_any()
+        .downcast_ref::<Int64Array>()
+        .unwrap()
+        .value(0)
+}
+
+/// Continue on error without a reject path, so rejects go to scratch space
+fn spilling_options() -> LoadOptions {
+    LoadOptions {
+        continue_on_error: true,
+        ..LoadOptions::default()
+    }
+}
+
+#[tokio::test]
+async fn test_reject_file_lands_in_spill_dir() {
+    let temp_dir = TempDir::new().unwrap();
+    let csv_path = temp_dir.path().join("users.csv");
+    write_csv(&csv_path, 10);
+    let db = create_db(&temp_dir, 1024 * 1024).await;
+
+    let report = db
+        .load_from_csv(&csv_path, spilling_options())
+        .await
+        .unwrap();
+
+    assert_eq!(report.rows_rejected, 10);
+    let reject_file = report.reject_file.expect("Rows should have been rejected");
+    assert_eq!(
+        reject_file,
+        temp_dir.path().join("spill/users.csv.rejects.csv")
+    );
+    assert_eq!(
+        std::fs::read_to_string(&reject_file)
+            .unwrap()
+            .lines()
+            .count(),
+        11,
+        "Header plus one line per rejected row"
+    );
+
+    // Scratch space is given back once the load is done
+    assert_eq!(db.spill().used(), 0);
+    assert_eq!(count_rows(&db).await, 90);
+}
+
+#[tokio::test]
+async fn test_exceeding_spill_cap_fails_the_load() {
+    let temp_dir = TempDir::new().unwrap();
+    let csv_path = temp_dir.path().join("users.csv");
+    write_csv(&csv_path, 2);
+    let db = create_db(&temp_dir, 512).await;
+
+    match db.load_from_csv(&csv_path, spilling_options()).await {
+        Err(Error::SpillLimitExceeded { limit, used, .. }) => {
+            assert_eq!(limit, 512);
+            assert!(used <= limit);
+        }
+        other => panic!("expected SpillLimitExceeded, got {:?}", other),
+    }
+
+    // The reject file was cut off at the cap and the load rolled back
+    assert!(temp_dir.path().join("spill/users.csv.rejects.csv").exists());
+    assert_eq!(db.spill().used(), 0);
+    assert_eq!(count_rows(&db).await, 0);
+}
@@ -0,0 +1,149 @@
+// SQL Validation Integration Tests
+// Tests checking queries against the table without running them
+
+use arrow::array::{ArrayRef, Int32Array, Int64Array, RecordBatch, StringArray};
+use arrow::datatypes::{DataType, Field, Schema};
+use fsdb::{DatabaseOps, Error};
+use std::sync::Arc;
+use tempfile::TempDir;
+
+async fn create_db(temp_dir: &TempDir) -> DatabaseOps {
+    let schema = Arc::new(Schema::new(vec![
+        Field::new("id", DataType::Int32, false),
+        Field::new("name", DataType::Utf8, false),
+    ]));
+    let db = DatabaseOps::create(temp_dir.path().join("db"), schema.clone())
+        .await
+        .unwrap();
+    let batch = RecordBatch::try_new(
+        schema,
+        vec![
+            Arc::new(Int32Array::from(vec![1, 2])) as ArrayRef,
+            Arc::new(StringArray::from(vec!["alice", "bob"])) as ArrayRef,
+        ],
+    )
+    .unwrap();
+    db.insert(batch).await.unwrap();
+    db
+}
+
+async fn row_count(db: &DatabaseOps) -> i64 {
+    let results = db.query("SELECT COUNT(*) FROM data").await.unwrap();
+    results[0]
+        .column(0)
+        .as_any()
+        .downcast_ref::<Int64Array>()
+        .unwrap()
+        .value(0)
+}
+
+fn position(err: &Error) -> Option<(u64, u64)> {
+    match err {
+        Error::InvalidSql { position, .. } => *position,
+        other => panic!("Expected InvalidSql, got {}", other),
+    }
+}
+
+#[tokio::test]
+async fn test_valid_query_returns_output_schema() {
+    let temp_dir = TempDir::new().unwrap();
+    let db = create_db(&temp_dir).await;
+
+    let schema = db
+        .validate("SELECT ID, upper(Name) AS shout FROM data WHERE id > 1")
+        .await
+        .unwrap();
+    let names: Vec<&str> = schema.fields().iter().map(|f| f.name().as_str()).collect();
+    assert_eq!(names, vec!["id", "shout"]);
+    assert_eq!(schema.field(1).data_type(), &DataType::Utf8);
+}
+
+#[tokio::test]
+async fn test_invalid_queries_report_first_error() {
+    let temp_dir = TempDir::new().unwrap();
+    let db = create_db(&temp_dir).await;
+
+    // Syntax error, placed by the parser
+    let err = db
+        .validate("SELECT id\nFROM data\nWHERE id = = 1")
+        .await
+        .unwrap_err();
+    assert_eq!(position(&err), Some((3, 12)), "{}", err);
+    assert!(err.to_string().contains("line 3, column 12"), "{}", err);
+
+    // Unknown column, placed in the SQL as written
+    let err = db
+        .validate("SELECT id,\n       nme\nFROM data")
+        .await
+        .unwrap_err();
+    assert_eq!(position(&err), Some((2, 8)), "{}", err);
+    assert!(err.to_string().contains("nme"), "{}", err);
+
+    // Unknown table
+    let err = db.validate("SELECT id FROM missing").await.unwrap_err();
+    position(&err);
+    assert!(err.to_string().contains("missing"), "{}", err);
+
+    // One statement at a time
+    let err = db
+        .validate("SELECT id FROM data; SELECT name FROM data")
+        .await
+        .unwrap_err();
+    position(&err);
+}
+
+#[tokio::test]
+async fn test_columns_from_schema_evolution_are_valid() {
+    let temp_dir = TempDir::new().unwrap();
+    let db = create_db(&temp_dir).await;
+
+    assert!(db.validate("SELECT email FROM data").await.is_err());
+
+    // Only the new file has the column
+    let schema = Arc::new(Schema::new(vec![
+        Field::new("id", DataType::Int32, false),
+        Field::new("name", DataType::Utf8, false),
+        Field::new("email", DataType::Utf8, true),
+    ]));
+    let batch = RecordBatch::try_new(
+        schema,
+        vec![
+            Arc::new(Int32Array::from(vec![3])) as ArrayRef,
+            Arc::new(StringArray::from(vec!["carol"])) as ArrayRef,
+            Arc::new(StringArray::from(vec!["c@example.com"])) as ArrayRef,
+        ],
+    )
+    .unwrap();
+    db.insert(batch).await.unwrap();
+
+    let schema = db
+        .validate("SELECT id, email FROM data WHERE email IS NOT NULL")
+        .await
+        .unwrap();
+    assert_eq!(schema.field(1).name(), "email");
+}
+
+#[tokio::test]
+async fn test_validation_never_runs_statements() {
+    let temp_dir = TempDir::new().unwrap();
+    let db = create_db(&temp_dir).await;
+    let version = fsdb::delta_lake::read_delta_log(&temp_dir.path().join("db"))
+        .unwrap()
+        .version;
+
+    db.validate("DELETE FROM data WHERE id = 1").await.unwrap();
+    db.validate("INSERT INTO data VALUES (3, 'carol')")
+        .await
+        .unwrap();
+    db.validate("CREATE TABLE data_copy AS SELECT * FROM data")
+        .await
+        .unwrap();
+
+    assert_eq!(row_count(&db).await, 2);
+    assert_eq!(
+        fsdb::delta_lake::read_delta_log(&temp_dir.path().join("db"))
+            .unwrap()
+            .version,
+        version
+    );
+}
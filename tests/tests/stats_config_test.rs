@@ -0,0 +1,145 @@
+// Statistics Configuration Integration Tests
+// Tests that inserts record only the configured per-column statistics in the
+// Delta log, and that queries stay correct on trimmed and truncated columns
+
+use arrow::array::{ArrayRef, Int32Array, RecordBatch, StringArray};
+use arrow::datatypes::{DataType, Field, Schema, SchemaRef};
+use fsdb::DatabaseOps;
+use fsdb::delta_lake::{ColumnStatsConfig, StatsConfig, get_file_statistics};
+use fsdb::query::Value;
+use std::path::Path;
+use std::sync::Arc;
+use tempfile::TempDir;
+
+fn create_schema() -> SchemaRef {
+    Arc::new(Schema::new(vec![
+        Field::new("id", DataType::Int32, false),
+        Field::new("kind", DataType::Utf8, true),
+        Field::new("body", DataType::Utf8, true),
+    ]))
+}
+
+fn body(id: i32) -> String {
+    format!("article {} {}", id, "lorem ipsum ".repeat(20))
+}
+
+/// Two files: ids 1-3 of kind "alert-*", then 4-6 of kind "metric-*"
+async fn create_db(path: &Path, config: Option<StatsConfig>) -> DatabaseOps {
+    let schema = create_schema();
+    let mut db = DatabaseOps::create(path, schema.clone()).await.unwrap();
+    if let Some(config) = config {
+        db = db.with_stats_config(config);
+    }
+    for (ids, kind) in [(1..=3, "alert"), (4..=6, "metric")] {
+        let ids: Vec<i32> = ids.collect();
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![
+                Arc::new(Int32Array::from(ids.clone())) as ArrayRef,
+                Arc::new(StringArray::from_iter_values(
+                    ids.iter().map(|id| format!("{}-{}", kind, id)),
+                )) as ArrayRef,
+                Arc::new(StringArray::from_iter_values(
+                    ids.iter().map(|id| body(*id)),
+                )) as ArrayRef,
+            ],
+        )
+        .unwrap();
+        db.insert(batch).await.unwrap();
+    }
+    db
+}
+
+fn log_size(path: &Path) -> u64 {
+    std::fs::read_dir(path.join("_delta_log"))
+        .unwrap()
+        .map(|e| e.unwrap().path())
+        .filter(|p| p.extension().and_then(|e| e.to_str()) == Some("json"))
+        .map(|p| std::fs::metadata(p).unwrap().len())
+        .sum()
+}
+
+async fn matching_ids(db: &DatabaseOps, predicate: &str) -> Vec<i64> {
+    let sql = format!("SELECT id FROM data WHERE {} ORDER BY id", predicate);
+    let rows = db.query_rows(&sql).await.unwrap();
+    (0..rows.len())
+        .map(|row| match rows.get(row, "id") {
+            Some(Value::Int(id)) => *id,
+            other => panic!("unexpected id {:?}", other),
+        })
+        .collect()
+}
+
+fn trimmed_config() -> StatsConfig {
+    StatsConfig::default()
+        .column("body", ColumnStatsConfig::none())
+        .column("kind", ColumnStatsConfig::all().with_max_string_length(3))
+}
+
+#[tokio::test]
+async fn test_configured_columns_keep_stats() {
+    let temp_dir = TempDir::new().unwrap();
+    let path = temp_dir.path().join("db");
+    create_db(&path, Some(trimmed_config())).await;
+
+    let files = get_file_statistics(&path).unwrap();
+    assert_eq!(files.len(), 2);
+    for file in &files {
+        assert_eq!(file.num_records, 3);
+        // Untouched by the config
+        assert!(file.min_values.contains_key("id"));
+        assert!(file.max_values.contains_key("id"));
+        assert_eq!(file.null_counts.get("id"), Some(&0));
+
+        // Excluded entirely
+        assert!(!file.min_values.contains_key("body"));
+        assert!(!file.max_values.contains_key("body"));
+        assert!(!file.null_counts.contains_key("body"));
+
+        // Truncated, with the null count kept
+        let min = file.min_values["kind"].as_str().unwrap();
+        let max = file.max_values["kind"].as_str().unwrap();
+        assert!(min == "ale" || min == "met", "{}", min);
+        assert_eq!(max, format!("{}\u{10FFFF}", min));
+        assert_eq!(file.null_counts.get("kind"), Some(&0));
+    }
+}
+
+#[tokio::test]
+async fn test_trimmed_stats_shrink_log() {
+    let temp_dir = TempDir::new().unwrap();
+    let full = temp_dir.path().join("full");
+    let trimmed = temp_dir.path().join("trimmed");
+    create_db(&full, None).await;
+    create_db(&trimmed, Some(trimmed_config())).await;
+
+    assert!(log_size(&trimmed) < log_size(&full));
+    let log =
+        std::fs::read_to_string(trimmed.join("_delta_log/00000000000000000001.json")).unwrap();
+    assert!(!log.contains("lorem ipsum"));
+}
+
+#[tokio::test]
+async fn test_queries_on_trimmed_columns() {
+    let temp_dir = TempDir::new().unwrap();
+    let db = create_db(&temp_dir.path().join("db"), Some(trimmed_config())).await;
+
+    // Skipping still works on the columns that kept their stats
+    assert_eq!(matching_ids(&db, "id > 4").await, vec![5, 6]);
+    let stats = db.get_data_skipping_stats().await.unwrap();
+    assert_eq!((stats.files_read, stats.files_skipped), (1, 1));
+
+    // Without stats, every file is read, and the answer is unchanged
+    let predicate = format!("body = '{}'", body(2));
+    assert_eq!(matching_ids(&db, &predicate).await, vec![2]);
+    let stats = db.get_data_skipping_stats().await.unwrap();
+    assert_eq!(stats.files_read, 2);
+
+    // Truncated bounds still cover every value
+    assert_eq!(matching_ids(&db, "kind = 'metric-6'").await, vec![6]);
+    assert_eq!(
+        matching_ids(&db, "kind >= 'alert-3'").await,
+        vec![3, 4, 5, 6]
+    );
+    assert_eq!(matching_ids(&db, "kind < 'ale'").await, Vec::<i64>::new());
+}
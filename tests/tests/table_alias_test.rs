@@ -0,0 +1,136 @@
+// Table Alias Integration Tests
+// Tests self-joins, alias-qualified columns and ambiguous column references
+
+use arrow::array::{ArrayRef, Int32Array, RecordBatch, StringArray};
+use arrow::datatypes::{DataType, Field, Schema};
+use fsdb::query::Value;
+use fsdb::{DatabaseOps, Error};
+use std::sync::Arc;
+use tempfile::TempDir;
+
+/// Employees and the id of their manager
+async fn create_db(temp_dir: &TempDir) -> DatabaseOps {
+    let schema = Arc::new(Schema::new(vec![
+        Field::new("id", DataType::Int32, false),
+        Field::new("Name", DataType::Utf8, false),
+        Field::new("manager_id", DataType::Int32, true),
+    ]));
+    let db = DatabaseOps::create(temp_dir.path().join("db"), schema.clone())
+        .await
+        .unwrap();
+    let batch = RecordBatch::try_new(
+        schema,
+        vec![
+            Arc::new(Int32Array::from(vec![1, 2, 3, 4])) as ArrayRef,
+            Arc::new(StringArray::from(vec!["ada", "grace", "alan", "edsger"])) as ArrayRef,
+            Arc::new(Int32Array::from(vec![None, Some(1), Some(1), Some(2)])) as ArrayRef,
+        ],
+    )
+    .unwrap();
+    db.insert(batch).await.unwrap();
+    db
+}
+
+async fn pairs(db: &DatabaseOps, sql: &str) -> Vec<(String, String)> {
+    let result = db.query_rows(sql).await.unwrap();
+    (0..result.len())
+        .map(|i| {
+            let text = |column: &str| match result.get(i, column).unwrap() {
+                Value::String(s) => s.clone(),
+                other => panic!("Expected a string, got {:?}", other),
+            };
+            (text("employee"), text("manager"))
+        })
+        .collect()
+}
+
+#[tokio::test]
+async fn test_self_join_with_aliases() {
+    let temp_dir = TempDir::new().unwrap();
+    let db = create_db(&temp_dir).await;
+
+    let expected = vec![
+        ("grace".to_string(), "ada".to_string()),
+        ("alan".to_string(), "ada".to_string()),
+        ("edsger".to_string(), "grace".to_string()),
+    ];
+    assert_eq!(
+        pairs(
+            &db,
+            "SELECT e.name AS employee, m.name AS manager \
+             FROM data AS e JOIN data AS m ON e.manager_id = m.id ORDER BY e.id"
+        )
+        .await,
+        expected
+    );
+
+    // Aliases and their columns fold like any other identifier
+    assert_eq!(
+        pairs(
+            &db,
+            "SELECT E.NAME AS employee, m.Name AS manager \
+             FROM data e JOIN data m ON E.Manager_Id = M.ID ORDER BY e.ID"
+        )
+        .await,
+        expected
+    );
+}
+
+#[tokio::test]
+async fn test_unqualified_column_on_both_sides_is_ambiguous() {
+    let temp_dir = TempDir::new().unwrap();
+    let db = create_db(&temp_dir).await;
+
+    let err = db
+        .query("SELECT name FROM data AS e JOIN data AS m ON e.manager_id = m.id")
+        .await
+        .unwrap_err();
+    assert!(
+        matches!(&err, Error::AmbiguousColumn { column } if column == "Name"),
+        "{}",
+        err
+    );
+
+    // A column only one side can have doesn't need qualifying
+    let result = db
+        .query_rows(
+            "SELECT e.id, boss FROM data AS e \
+             JOIN (SELECT id AS boss_id, name AS boss FROM data) AS m ON e.manager_id = m.boss_id \
+             ORDER BY e.id",
+        )
+        .await
+        .unwrap();
+    assert_eq!(result.len(), 3);
+    assert_eq!(result.get(0, "boss").unwrap(), &Value::String("ada".into()));
+}
+
+#[tokio::test]
+async fn test_alias_shadows_table_name() {
+    let temp_dir = TempDir::new().unwrap();
+    let db = create_db(&temp_dir).await;
+
+    // Within the query `data` is the subquery, not the table
+    let result = db
+        .query_rows(
+            "SELECT data.name FROM (SELECT id, upper(name) AS name FROM data) AS data \
+             WHERE data.id = 1",
+        )
+        .await
+        .unwrap();
+    assert_eq!(result.len(), 1);
+    assert_eq!(result.get(0, "name").unwrap(), &Value::String("ADA".into()));
+
+    // And here it's the manager side of a self-join
+    let result = db
+        .query_rows(
+            "SELECT data.name FROM data AS e JOIN data AS data ON e.manager_id = data.id \
+             WHERE e.id = 4",
+        )
+        .await
+        .unwrap();
+    assert_eq!(result.len(), 1);
+    assert_eq!(
+        result.get(0, "Name").unwrap(),
+        &Value::String("grace".into())
+    );
+}
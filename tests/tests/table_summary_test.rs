@@ -0,0 +1,131 @@
+// Table Summary Integration Tests
+// Tests DatabaseOps::table_summary against real counts, with and without
+// per-file statistics in the Delta log
+
+use arrow::array::{ArrayRef, Int32Array, Int64Array, RecordBatch, StringArray};
+use arrow::datatypes::{DataType, Field, Schema, SchemaRef};
+use fsdb::DatabaseOps;
+use std::path::Path;
+use std::sync::Arc;
+use tempfile::TempDir;
+
+fn create_schema() -> SchemaRef {
+    Arc::new(Schema::new(vec![
+        Field::new("id", DataType::Int32, false),
+        Field::new("name", DataType::Utf8, true),
+    ]))
+}
+
+/// Three inserts of 3, 5 and 2 rows
+async fn create_db(path: &Path) -> DatabaseOps {
+    let schema = create_schema();
+    let db = DatabaseOps::create(path, schema.clone()).await.unwrap();
+    let mut next_id = 0;
+    for rows in [3, 5, 2] {
+        let ids: Vec<i32> = (next_id..next_id + rows).collect();
+        next_id += rows;
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![
+                Arc::new(Int32Array::from(ids.clone())) as ArrayRef,
+                Arc::new(StringArray::from_iter_values(
+                    ids.iter().map(|id| format!("user{}", id)),
+                )) as ArrayRef,
+            ],
+        )
+        .unwrap();
+        db.insert(batch).await.unwrap();
+    }
+    db
+}
+
+async fn count_rows(db: &DatabaseOps) -> u64 {
+    let batches = db.query("SELECT COUNT(*) FROM data").await.unwrap();
+    batches[0]
+        .column(0)
+        .as_any()
+        .downcast_ref::<Int64Array>()
+        .unwrap()
+        .value(0) as u64
+}
+
+/// Drop the statistics from every add action in the log's commit files
+fn strip_stats(path: &Path) {
+    for entry in std::fs::read_dir(path.join("_delta_log")).unwrap() {
+        let file = entry.unwrap().path();
+        if file.extension().and_then(|e| e.to_str()) != Some("json") {
+            continue;
+        }
+        let content = std::fs::read_to_string(&file).unwrap();
+        let lines: Vec<String> = content
+            .lines()
+            .map(|line| {
+                let mut action: serde_json::Value = serde_json::from_str(line).unwrap();
+                if let Some(add) = action.get_mut("add").and_then(|a| a.as_object_mut()) {
+                    add.remove("stats");
+                }
+                action.to_string()
+            })
+            .collect();
+        std::fs::write(&file, lines.join("\n") + "\n").unwrap();
+    }
+}
+
+#[tokio::test]
+async fn test_summary_matches_count() {
+    let temp_dir = TempDir::new().unwrap();
+    let path = temp_dir.path().join("summary_db");
+    let db = create_db(&path).await;
+
+    let summary = db.table_summary().await.unwrap();
+    assert_eq!(summary.num_rows, count_rows(&db).await);
+    assert_eq!(summary.num_rows, 10);
+    assert_eq!(summary.num_files, 3);
+    assert_eq!(summary.files_without_stats, 0);
+    assert_eq!(
+        summary.latest_version,
+        fsdb::delta_lake::latest_version(&path).unwrap()
+    );
+
+    let on_disk: u64 = std::fs::read_dir(&path)
+        .unwrap()
+        .map(|e| e.unwrap().path())
+        .filter(|p| p.extension().and_then(|e| e.to_str()) == Some("parquet"))
+        .map(|p| std::fs::metadata(p).unwrap().len())
+        .sum();
+    assert_eq!(summary.total_bytes, on_disk);
+
+    // Deletes are reflected once committed
+    db.delete_rows_where("id < 2").await.unwrap();
+    let summary = db.table_summary().await.unwrap();
+    assert_eq!(summary.num_rows, 8);
+    assert_eq!(summary.num_rows, count_rows(&db).await);
+}
+
+#[tokio::test]
+async fn test_summary_of_empty_table() {
+    let temp_dir = TempDir::new().unwrap();
+    let db = DatabaseOps::create(temp_dir.path().join("empty_db"), create_schema())
+        .await
+        .unwrap();
+
+    let summary = db.table_summary().await.unwrap();
+    assert_eq!(summary.num_rows, 0);
+    assert_eq!(summary.num_files, 0);
+    assert_eq!(summary.total_bytes, 0);
+}
+
+#[tokio::test]
+async fn test_files_without_stats_are_counted_from_footers() {
+    let temp_dir = TempDir::new().unwrap();
+    let path = temp_dir.path().join("no_stats_db");
+    let db = create_db(&path).await;
+    drop(db);
+    strip_stats(&path);
+
+    let db = DatabaseOps::open(&path).await.unwrap();
+    let summary = db.table_summary().await.unwrap();
+    assert_eq!(summary.num_rows, 10);
+    assert_eq!(summary.files_without_stats, 3);
+    assert_eq!(summary.num_rows, count_rows(&db).await);
+}
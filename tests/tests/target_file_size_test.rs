@@ -0,0 +1,172 @@
+// Target File Size Integration Tests
+// Tests that inserts and buffer flushes are split into files near the target
+// size, that an oversized row gets a file of its own, and that OPTIMIZE
+// merges small files up toward the target
+
+use arrow::array::{ArrayRef, Int64Array, RecordBatch, StringArray};
+use arrow::datatypes::{DataType, Field, Schema, SchemaRef};
+use fsdb::DatabaseOps;
+use fsdb::batch_buffer::BatchBufferConfig;
+use fsdb::delta_lake::get_file_statistics;
+use std::path::Path;
+use std::sync::Arc;
+use tempfile::TempDir;
+
+const TARGET: u64 = 64 * 1024;
+
+fn create_schema() -> SchemaRef {
+    Arc::new(Schema::new(vec![
+        Field::new("id", DataType::Int64, false),
+        Field::new("payload", DataType::Utf8, false),
+    ]))
+}
+
+/// `len` pseudo-random hex digits seeded by `seed`, which barely compress
+fn noise(seed: i64, len: usize) -> String {
+    let mut x = seed as u64;
+    let mut digits = String::with_capacity(len + 16);
+    while digits.len() < len {
+        x = x
+            .wrapping_mul(6364136223846793005)
+            .wrapping_add(1442695040888963407);
+        digits.push_str(&format!("{:016x}", x));
+    }
+    digits.truncate(len);
+    digits
+}
+
+/// Rows `ids`, each with a payload of `len` digits of noise
+fn batch(ids: std::ops::Range<i64>, len: usize) -> RecordBatch {
+    let payloads = ids.clone().map(|id| noise(id, len));
+    RecordBatch::try_new(
+        create_schema(),
+        vec![
+            Arc::new(Int64Array::from_iter_values(ids)) as ArrayRef,
+            Arc::new(StringArray::from_iter_values(payloads)) as ArrayRef,
+        ],
+    )
+    .unwrap()
+}
+
+/// (size in bytes, rows) of each live data file, largest first
+fn files(path: &Path) -> Vec<(u64, u64)> {
+    let mut files: Vec<_> = get_file_statistics(path)
+        .unwrap()
+        .iter()
+        .map(|file| (file.size_bytes, file.num_records))
+        .collect();
+    files.sort_unstable_by(|a, b| b.cmp(a));
+    files
+}
+
+/// Every file but the smallest, the remainder, is within half the target
+fn assert_near_target(files: &[(u64, u64)]) {
+    assert!(files.len() > 2, "expected several files, got {:?}", files);
+    for (size, _) in &files[..files.len() - 1] {
+        assert!(
+            (TARGET / 2..=TARGET * 3 / 2).contains(size),
+            "file of {} bytes is far from the {} byte target: {:?}",
+            size,
+            TARGET,
+            files
+        );
+    }
+}
+
+async fn row_count(db: &DatabaseOps) -> u64 {
+    db.table_summary().await.unwrap().num_rows
+}
+
+#[tokio::test]
+async fn test_large_insert_split_near_target() {
+    let temp_dir = TempDir::new().unwrap();
+    let path = temp_dir.path().join("db");
+    let db = DatabaseOps::create(&path, create_schema())
+        .await
+        .unwrap()
+        .with_target_file_size_bytes(TARGET);
+    assert_eq!(db.target_file_size_bytes(), Some(TARGET));
+
+    // One commit, however many files
+    let result = db.insert(batch(0..40_000, 32)).await.unwrap();
+    assert_eq!(result.version, 1);
+    let files = files(&path);
+    assert_near_target(&files);
+    assert_eq!(files.iter().map(|f| f.1).sum::<u64>(), 40_000);
+    assert_eq!(row_count(&db).await, 40_000);
+}
+
+#[tokio::test]
+async fn test_buffer_flush_split_near_target() {
+    let temp_dir = TempDir::new().unwrap();
+    let path = temp_dir.path().join("db");
+    let db = DatabaseOps::create(&path, create_schema())
+        .await
+        .unwrap()
+        .with_target_file_size_bytes(TARGET)
+        .with_write_buffer_config(BatchBufferConfig {
+            max_rows: 100_000,
+            ..Default::default()
+        });
+
+    // Eight small batches, flushed together
+    for start in (0..40_000).step_by(5_000) {
+        db.insert_buffered(batch(start..start + 5_000, 32))
+            .await
+            .unwrap();
+    }
+    db.flush_write_buffer().await.unwrap();
+
+    let files = files(&path);
+    assert_near_target(&files);
+    assert_eq!(row_count(&db).await, 40_000);
+}
+
+#[tokio::test]
+async fn test_oversized_row_gets_one_file() {
+    let temp_dir = TempDir::new().unwrap();
+    let path = temp_dir.path().join("db");
+    let db = DatabaseOps::create(&path, create_schema())
+        .await
+        .unwrap()
+        .with_target_file_size_bytes(1024);
+
+    // Each row alone is several times the target
+    db.insert(batch(0..3, 8192)).await.unwrap();
+
+    let files = files(&path);
+    assert_eq!(files.iter().map(|f| f.1).collect::<Vec<_>>(), vec![1, 1, 1]);
+    assert!(files.iter().all(|(size, _)| *size > 1024));
+}
+
+#[tokio::test]
+async fn test_optimize_merges_toward_target() {
+    let temp_dir = TempDir::new().unwrap();
+    let path = temp_dir.path().join("db");
+    let db = DatabaseOps::create(&path, create_schema())
+        .await
+        .unwrap()
+        .with_target_file_size_bytes(TARGET);
+
+    // Each insert is well under the target, so each is one small file
+    for start in (0..15_000).step_by(500) {
+        db.insert(batch(start..start + 500, 32)).await.unwrap();
+    }
+    let before = files(&path);
+    assert_eq!(before.len(), 30);
+    let total: u64 = before.iter().map(|f| f.0).sum();
+
+    // OPTIMIZE without a size of its own bins files up to the target
+    let metrics = db.optimize().await.unwrap();
+    assert!(metrics.num_files_removed > 0);
+    let after = files(&path);
+    assert!(
+        after.len() as u64 <= total.div_ceil(TARGET) + 1,
+        "{} bytes in {} files after OPTIMIZE: {:?}",
+        total,
+        after.len(),
+        after
+    );
+    assert!(after.iter().all(|(size, _)| *size <= TARGET * 3 / 2));
+    assert_eq!(row_count(&db).await, 15_000);
+}
@@ -1,9 +1,13 @@
 // Time Travel Integration Tests
-// Tests Delta Lake time travel features: query historical versions
+// Tests Delta Lake time travel features: query historical versions, through
+// the API and with VERSION AS OF and TIMESTAMP AS OF in SQL
 
 use arrow::array::{ArrayRef, Int32Array, RecordBatch, StringArray};
-use arrow::datatypes::{DataType, Field, Schema};
+use arrow::datatypes::{DataType, Field, Schema, SchemaRef};
+use arrow::util::pretty::pretty_format_batches;
 use fsdb::DatabaseOps;
+use fsdb::Error;
+use fsdb::query::{ResultSet, Value};
 use std::sync::Arc;
 use tempfile::TempDir;
 
@@ -348,3 +352,175 @@ async fn test_time_travel_with_deletion() {
 
     println!("✓ Time travel with deletion working correctly");
 }
+
+fn id_schema() -> SchemaRef {
+    Arc::new(Schema::new(vec![
+        Field::new("id", DataType::Int32, false),
+        Field::new("value", DataType::Utf8, false),
+    ]))
+}
+
+fn id_batch(ids: Vec<i32>) -> RecordBatch {
+    let values: Vec<String> = ids.iter().map(|id| format!("v{}", id)).collect();
+    RecordBatch::try_new(
+        id_schema(),
+        vec![
+            Arc::new(Int32Array::from(ids)) as ArrayRef,
+            Arc::new(StringArray::from(values)) as ArrayRef,
+        ],
+    )
+    .unwrap()
+}
+
+/// Versions 1, 2 and 3 hold ids 1-2, 1-4 and 1-6
+async fn create_versioned_db(temp_dir: &TempDir) -> DatabaseOps {
+    let db = DatabaseOps::create(temp_dir.path().join("db"), id_schema())
+        .await
+        .unwrap();
+    for ids in [vec![1, 2], vec![3, 4], vec![5, 6]] {
+        db.insert(id_batch(ids)).await.unwrap();
+    }
+    db
+}
+
+async fn count(db: &DatabaseOps, sql: &str) -> Value {
+    db.query_rows(sql)
+        .await
+        .unwrap()
+        .get(0, "n")
+        .unwrap()
+        .clone()
+}
+
+#[tokio::test]
+async fn test_sql_version_as_of() {
+    let temp_dir = TempDir::new().unwrap();
+    let db = create_versioned_db(&temp_dir).await;
+
+    assert_eq!(
+        count(&db, "SELECT COUNT(*) AS n FROM data VERSION AS OF 1").await,
+        Value::Int(2)
+    );
+    assert_eq!(
+        count(
+            &db,
+            "SELECT COUNT(*) AS n FROM data version as of 2 WHERE data.id > 1"
+        )
+        .await,
+        Value::Int(3)
+    );
+    assert_eq!(
+        count(&db, "SELECT COUNT(*) AS n FROM data").await,
+        Value::Int(6)
+    );
+
+    // Two versions of the table in one query: rows added since version 1
+    let rows = db
+        .query_rows(
+            "SELECT cur.id FROM data cur LEFT JOIN data VERSION AS OF 1 AS old \
+             ON cur.id = old.id WHERE old.id IS NULL ORDER BY cur.id",
+        )
+        .await
+        .unwrap();
+    assert_eq!(rows.len(), 4);
+    assert_eq!(rows.get(0, "id").unwrap(), &Value::Int(3));
+
+    // Versions that don't exist, and malformed clauses, fail
+    assert!(
+        db.query_rows("SELECT * FROM data VERSION AS OF 99")
+            .await
+            .is_err()
+    );
+    assert!(
+        db.query_rows("SELECT * FROM data VERSION AS OF 'one'")
+            .await
+            .is_err()
+    );
+}
+
+#[tokio::test]
+async fn test_sql_timestamp_as_of() {
+    let temp_dir = TempDir::new().unwrap();
+    let db = create_versioned_db(&temp_dir).await;
+
+    // A time after the last commit reads the latest version
+    assert_eq!(
+        count(
+            &db,
+            "SELECT COUNT(*) AS n FROM data TIMESTAMP AS OF '2999-01-01 00:00:00'"
+        )
+        .await,
+        Value::Int(6)
+    );
+    // There's no version before the table was created
+    assert!(
+        db.query_rows("SELECT * FROM data TIMESTAMP AS OF '2000-01-01'")
+            .await
+            .is_err()
+    );
+    assert!(
+        db.query_rows("SELECT * FROM data TIMESTAMP AS OF 'yesterday'")
+            .await
+            .is_err()
+    );
+}
+
+#[tokio::test]
+async fn test_query_at_version() {
+    let temp_dir = TempDir::new().unwrap();
+    let db = create_versioned_db(&temp_dir).await;
+
+    // Same results as the equivalent query at that version
+    let batches = db
+        .query_at_version("SELECT id, value FROM data ORDER BY id", 2)
+        .await
+        .unwrap();
+    let expected = db
+        .query("SELECT id, value FROM data VERSION AS OF 2 ORDER BY id")
+        .await
+        .unwrap();
+    assert_eq!(
+        pretty_format_batches(&batches).unwrap().to_string(),
+        pretty_format_batches(&expected).unwrap().to_string()
+    );
+    assert_eq!(batches.iter().map(|b| b.num_rows()).sum::<usize>(), 4);
+
+    // Named tables are read at their own latest version, or as the SQL says
+    let orders = db.create_table("orders", id_schema()).await.unwrap();
+    orders.insert(id_batch(vec![1])).await.unwrap();
+    orders.insert(id_batch(vec![5])).await.unwrap();
+    let sql = "SELECT COUNT(*) AS n FROM data JOIN orders ON data.id = orders.id";
+    let batches = db.query_at_version(sql, 1).await.unwrap();
+    let rows = ResultSet::from_batches(batches[0].schema(), &batches).unwrap();
+    assert_eq!(rows.get(0, "n").unwrap(), &Value::Int(1));
+    assert_eq!(
+        count(
+            &db,
+            "SELECT COUNT(*) AS n FROM data JOIN orders VERSION AS OF 1 o ON data.id = o.id"
+        )
+        .await,
+        Value::Int(1)
+    );
+    assert!(
+        db.query_rows("SELECT * FROM missing VERSION AS OF 1")
+            .await
+            .is_err()
+    );
+
+    // A quoted name can't reach a table outside the database
+    DatabaseOps::create(temp_dir.path().join("other"), id_schema())
+        .await
+        .unwrap();
+    match db
+        .query_rows(r#"SELECT * FROM "../../other" VERSION AS OF 0"#)
+        .await
+    {
+        Err(Error::InvalidOperation(message)) => {
+            assert!(message.contains("Invalid table name"), "{}", message)
+        }
+        other => panic!(
+            "expected the name to be rejected, got {:?}",
+            other.map(|r| r.len())
+        ),
+    }
+}
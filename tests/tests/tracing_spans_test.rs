@@ -0,0 +1,200 @@
+// Tracing Span Integration Tests
+// Tests that queries, commits, OPTIMIZE and NFS reads and writes carry span fields
+
+use arrow::array::{ArrayRef, Int32Array, RecordBatch, StringArray};
+use arrow::datatypes::{DataType, Field, Schema, SchemaRef};
+use fsdb::DatabaseOps;
+use fsdb::nfs::NfsServer;
+use serial_test::serial;
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use tempfile::TempDir;
+use tracing::field::{Field as TracingField, Visit};
+use tracing::span::{Attributes, Id, Record};
+use tracing::{Subscriber, subscriber};
+use tracing_subscriber::layer::{Context, Layer, SubscriberExt};
+use tracing_subscriber::registry::LookupSpan;
+
+fn create_unique_port(base_port: u16) -> u16 {
+    let thread_id = format!("{:?}", thread::current().id());
+    let hash: u16 = thread_id.bytes().map(|b| b as u16).sum::<u16>() % 10000;
+    base_port + hash
+}
+
+/// Fields of a span, as text
+#[derive(Debug, Clone, Default)]
+struct SpanFields(HashMap<String, String>);
+
+impl Visit for SpanFields {
+    fn record_str(&mut self, field: &TracingField, value: &str) {
+        self.0.insert(field.name().to_string(), value.to_string());
+    }
+
+    fn record_debug(&mut self, field: &TracingField, value: &dyn Debug) {
+        self.0
+            .insert(field.name().to_string(), format!("{:?}", value));
+    }
+}
+
+/// Layer keeping the name and final fields of every closed span
+#[derive(Clone, Default)]
+struct SpanRecorder {
+    closed: Arc<Mutex<Vec<(String, SpanFields)>>>,
+}
+
+impl SpanRecorder {
+    /// Fields of each closed span called `name`, in closing order
+    fn spans(&self, name: &str) -> Vec<HashMap<String, String>> {
+        self.closed
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|(n, _)| n == name)
+            .map(|(_, fields)| fields.0.clone())
+            .collect()
+    }
+}
+
+impl<S> Layer<S> for SpanRecorder
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+        let mut fields = SpanFields::default();
+        attrs.record(&mut fields);
+        ctx.span(id).unwrap().extensions_mut().insert(fields);
+    }
+
+    fn on_record(&self, id: &Id, values: &Record<'_>, ctx: Context<'_, S>) {
+        let span = ctx.span(id).unwrap();
+        if let Some(fields) = span.extensions_mut().get_mut::<SpanFields>() {
+            values.record(fields);
+        }
+    }
+
+    fn on_close(&self, id: Id, ctx: Context<'_, S>) {
+        let span = ctx.span(&id).unwrap();
+        let fields = span
+            .extensions()
+            .get::<SpanFields>()
+            .cloned()
+            .unwrap_or_default();
+        self.closed
+            .lock()
+            .unwrap()
+            .push((span.name().to_string(), fields));
+    }
+}
+
+fn create_schema() -> SchemaRef {
+    Arc::new(Schema::new(vec![
+        Field::new("id", DataType::Int32, false),
+        Field::new("name", DataType::Utf8, false),
+    ]))
+}
+
+fn create_batch(ids: Vec<i32>, names: Vec<&str>) -> RecordBatch {
+    RecordBatch::try_new(
+        create_schema(),
+        vec![
+            Arc::new(Int32Array::from(ids)) as ArrayRef,
+            Arc::new(StringArray::from(names)) as ArrayRef,
+        ],
+    )
+    .unwrap()
+}
+
+#[tokio::test]
+async fn test_database_operation_spans() {
+    let recorder = SpanRecorder::default();
+    let _guard = subscriber::set_default(tracing_subscriber::registry().with(recorder.clone()));
+
+    let temp_dir = TempDir::new().unwrap();
+    let db_path = temp_dir.path().join("db");
+    let table = db_path.display().to_string();
+    let db = DatabaseOps::create(&db_path, create_schema())
+        .await
+        .unwrap();
+
+    db.insert(create_batch(vec![1, 2], vec!["a", "b"]))
+        .await
+        .unwrap();
+    db.insert(create_batch(vec![3], vec!["c"])).await.unwrap();
+    db.delete_rows_where("id = 3").await.unwrap();
+    db.query("SELECT * FROM data").await.unwrap();
+    db.optimize().await.unwrap();
+
+    let commits = recorder.spans("commit");
+    assert_eq!(commits.len(), 3);
+    let expected = [
+        ("INSERT", "1", "2"),
+        ("INSERT", "2", "1"),
+        ("DELETE", "3", "1"),
+    ];
+    for (commit, (operation, version, rows)) in commits.iter().zip(expected) {
+        assert_eq!(commit["table"], table);
+        assert_eq!(commit["operation"], operation);
+        assert_eq!(commit["version"], version);
+        assert_eq!(commit["rows"], rows);
+    }
+
+    // The delete counted its rows with a query of its own, first
+    let queries = recorder.spans("query");
+    assert_eq!(queries.len(), 2);
+    assert_eq!(queries[0]["version"], "2");
+    assert_eq!(queries[1]["table"], table);
+    assert_eq!(queries[1]["version"], "3");
+
+    // OPTIMIZE records the version it left the table at
+    let latest = fsdb::delta_lake::latest_version(&db_path).unwrap().unwrap();
+    let optimize = recorder.spans("optimize");
+    assert_eq!(optimize.len(), 1);
+    assert_eq!(optimize[0]["table"], table);
+    assert_eq!(optimize[0]["version"], latest.to_string());
+}
+
+#[tokio::test]
+#[serial]
+async fn test_nfs_read_and_write_spans() {
+    let recorder = SpanRecorder::default();
+    let _guard = subscriber::set_default(tracing_subscriber::registry().with(recorder.clone()));
+
+    let temp_dir = TempDir::new().unwrap();
+    let db = DatabaseOps::create(temp_dir.path().join("db"), create_schema())
+        .await
+        .unwrap();
+    db.insert(create_batch(vec![1], vec!["a"])).await.unwrap();
+    let server = NfsServer::new(Arc::new(db), create_unique_port(20000))
+        .await
+        .unwrap();
+    assert!(server.is_ready().await);
+
+    let content = server.read_file("/data/data.csv", 0, 4096).await.unwrap();
+    let row = b"2,b\n";
+    server
+        .write_file("/data/data.csv", content.len() as u64, row)
+        .await
+        .unwrap();
+
+    let reads = recorder.spans("nfs.read");
+    assert_eq!(reads.len(), 1);
+    assert_eq!(reads[0]["table"], "data");
+    assert!(!reads[0]["fileid"].is_empty());
+    assert_eq!(reads[0]["offset"], "0");
+    assert_eq!(reads[0]["count"], "4096");
+
+    let writes = recorder.spans("nfs.write");
+    assert_eq!(writes.len(), 1);
+    assert_eq!(writes[0]["fileid"], reads[0]["fileid"]);
+    assert_eq!(writes[0]["offset"], content.len().to_string());
+    assert_eq!(writes[0]["count"], row.len().to_string());
+
+    // The append commits inside the write's span
+    let commits = recorder.spans("commit");
+    assert_eq!(commits.last().unwrap()["operation"], "INSERT");
+    assert_eq!(commits.last().unwrap()["version"], "2");
+
+    server.shutdown().await.unwrap();
+}
@@ -0,0 +1,206 @@
+// Union Integration Tests
+// Tests UNION and UNION ALL within a table and across tables with differing column types
+
+use arrow::array::{ArrayRef, Float64Array, Int32Array, Int64Array, StringArray};
+use arrow::datatypes::{DataType, Field, Schema};
+use arrow::record_batch::RecordBatch;
+use fsdb::query::UnionMode;
+use fsdb::storage::spill::SpillConfig;
+use fsdb::{DatabaseOps, Error};
+use std::path::Path;
+use std::sync::Arc;
+use tempfile::TempDir;
+
+async fn create_db(path: &Path, amount_type: DataType, amounts: ArrayRef) -> DatabaseOps {
+    let schema = Arc::new(Schema::new(vec![
+        Field::new("customer", DataType::Utf8, false),
+        Field::new("amount", amount_type, false),
+    ]));
+    let customers: Vec<String> = (0..amounts.len()).map(|i| format!("c{}", i)).collect();
+    let db = DatabaseOps::create(path, schema.clone()).await.unwrap();
+    let batch = RecordBatch::try_new(
+        schema,
+        vec![Arc::new(StringArray::from(customers)), amounts],
+    )
+    .unwrap();
+    db.insert(batch).await.unwrap();
+    db
+}
+
+/// Archived orders with whole amounts, and live orders with fractional ones
+async fn create_archive_and_live(temp_dir: &TempDir) -> (DatabaseOps, DatabaseOps) {
+    let archive = create_db(
+        &temp_dir.path().join("archive"),
+        DataType::Int32,
+        Arc::new(Int32Array::from(vec![10, 20])),
+    )
+    .await;
+    let live = create_db(
+        &temp_dir.path().join("live"),
+        DataType::Float64,
+        Arc::new(Float64Array::from(vec![10.0, 30.5])),
+    )
+    .await;
+    (archive, live)
+}
+
+fn row_count(batches: &[RecordBatch]) -> usize {
+    batches.iter().map(|b| b.num_rows()).sum()
+}
+
+fn amounts(batches: &[RecordBatch]) -> Vec<f64> {
+    let mut amounts: Vec<f64> = batches
+        .iter()
+        .flat_map(|b| {
+            let column = b.column_by_name("amount").unwrap();
+            assert_eq!(column.data_type(), &DataType::Float64);
+            column
+                .as_any()
+                .downcast_ref::<Float64Array>()
+                .unwrap()
+                .values()
+                .to_vec()
+        })
+        .collect();
+    amounts.sort_by(f64::total_cmp);
+    amounts
+}
+
+#[tokio::test]
+async fn test_sql_union_within_table() {
+    let temp_dir = TempDir::new().unwrap();
+    let (archive, _) = create_archive_and_live(&temp_dir).await;
+
+    let results = archive
+        .query("SELECT COUNT(*) FROM (SELECT amount FROM data UNION ALL SELECT amount FROM data)")
+        .await
+        .unwrap();
+    let count = results[0]
+        .column(0)
+        .as_any()
+        .downcast_ref::<Int64Array>()
+        .unwrap()
+        .value(0);
+    assert_eq!(count, 4);
+
+    let results = archive
+        .query("SELECT amount FROM data UNION SELECT amount FROM data")
+        .await
+        .unwrap();
+    assert_eq!(row_count(&results), 2);
+}
+
+#[tokio::test]
+async fn test_union_across_tables_coerces_types() {
+    let temp_dir = TempDir::new().unwrap();
+    let (archive, live) = create_archive_and_live(&temp_dir).await;
+
+    // Int32 and Float64 amounts combine as Float64
+    let all = archive
+        .query_union(
+            "SELECT amount FROM data",
+            &live,
+            "SELECT amount FROM data",
+            UnionMode::All,
+        )
+        .await
+        .unwrap();
+    assert_eq!(amounts(&all), vec![10.0, 10.0, 20.0, 30.5]);
+
+    // 10 and 10.0 are the same value once coerced
+    let distinct = archive
+        .query_union(
+            "SELECT amount FROM data",
+            &live,
+            "SELECT amount FROM data",
+            UnionMode::Distinct,
+        )
+        .await
+        .unwrap();
+    assert_eq!(amounts(&distinct), vec![10.0, 20.0, 30.5]);
+
+    // Columns take the left query's names
+    let renamed = archive
+        .query_union(
+            "SELECT customer AS who, amount FROM data",
+            &live,
+            "SELECT customer, amount FROM data",
+            UnionMode::All,
+        )
+        .await
+        .unwrap();
+    assert_eq!(renamed[0].schema().field(0).name(), "who");
+    assert_eq!(row_count(&renamed), 4);
+}
+
+#[tokio::test]
+async fn test_mismatched_branches_are_rejected() {
+    let temp_dir = TempDir::new().unwrap();
+    let (archive, live) = create_archive_and_live(&temp_dir).await;
+
+    let err = archive
+        .query_union(
+            "SELECT customer, amount FROM data",
+            &live,
+            "SELECT amount FROM data",
+            UnionMode::All,
+        )
+        .await
+        .unwrap_err();
+    assert!(matches!(err, Error::UnionMismatch(_)), "{}", err);
+
+    // Text and numbers have no common type
+    let err = archive
+        .query_union(
+            "SELECT customer FROM data",
+            &live,
+            "SELECT amount FROM data",
+            UnionMode::All,
+        )
+        .await
+        .unwrap_err();
+    assert!(
+        matches!(&err, Error::UnionMismatch(msg) if msg.contains("customer")),
+        "{}",
+        err
+    );
+}
+
+#[tokio::test]
+async fn test_distinct_union_under_memory_limit() {
+    const ROWS: i32 = 20_000;
+    let temp_dir = TempDir::new().unwrap();
+    let archive = create_db(
+        &temp_dir.path().join("archive"),
+        DataType::Int32,
+        Arc::new(Int32Array::from((0..ROWS).collect::<Vec<_>>())),
+    )
+    .await
+    .with_spill_config(SpillConfig {
+        temp_dir: temp_dir.path().join("spill"),
+        max_spill_bytes: None,
+    })
+    .with_query_memory_limit(16 * 1024);
+    let live = create_db(
+        &temp_dir.path().join("live"),
+        DataType::Int32,
+        Arc::new(Int32Array::from((0..ROWS).collect::<Vec<_>>())),
+    )
+    .await;
+
+    // Every row appears in both tables
+    match archive
+        .query_union(
+            "SELECT customer, amount FROM data",
+            &live,
+            "SELECT customer, amount FROM data",
+            UnionMode::Distinct,
+        )
+        .await
+    {
+        // Spilled: duplicates are still removed
+        Ok(batches) => assert_eq!(row_count(&batches), ROWS as usize),
+        Err(Error::QueryMemoryExceeded { limit, .. }) => assert_eq!(limit, 16 * 1024),
+        Err(e) => panic!("Expected spill or QueryMemoryExceeded, got {:?}", e),
+    }
+}
@@ -0,0 +1,146 @@
+// Row Update Integration Tests
+// Tests update_rows and the rows returned by update_rows_returning
+
+use arrow::array::{ArrayRef, Int32Array, StringArray};
+use arrow::datatypes::{DataType, Field, Schema, SchemaRef};
+use arrow::record_batch::RecordBatch;
+use fsdb::DatabaseOps;
+use fsdb::query::CursorId;
+use std::sync::Arc;
+use tempfile::TempDir;
+
+fn create_schema() -> SchemaRef {
+    Arc::new(Schema::new(vec![
+        Field::new("id", DataType::Int32, false),
+        Field::new("status", DataType::Utf8, false),
+        Field::new("score", DataType::Int32, false),
+    ]))
+}
+
+async fn create_db(temp_dir: &TempDir) -> DatabaseOps {
+    let schema = create_schema();
+    let db = DatabaseOps::create(temp_dir.path().join("update_db"), schema.clone())
+        .await
+        .unwrap();
+    let batch = RecordBatch::try_new(
+        schema,
+        vec![
+            Arc::new(Int32Array::from(vec![1, 2, 3, 4])) as ArrayRef,
+            Arc::new(StringArray::from(vec![
+                "pending", "done", "pending", "pending",
+            ])) as ArrayRef,
+            Arc::new(Int32Array::from(vec![10, 20, 30, 40])) as ArrayRef,
+        ],
+    )
+    .unwrap();
+    db.insert(batch).await.unwrap();
+    db
+}
+
+/// Collect (id, status, score) rows sorted by id
+fn rows(batches: &[RecordBatch]) -> Vec<(i32, String, i32)> {
+    let mut rows = Vec::new();
+    for batch in batches {
+        let column = |name| batch.column_by_name(name).unwrap().as_any();
+        let ids = column("id").downcast_ref::<Int32Array>().unwrap();
+        let statuses = column("status").downcast_ref::<StringArray>().unwrap();
+        let scores = column("score").downcast_ref::<Int32Array>().unwrap();
+        for i in 0..batch.num_rows() {
+            rows.push((ids.value(i), statuses.value(i).to_string(), scores.value(i)));
+        }
+    }
+    rows.sort();
+    rows
+}
+
+async fn fetch_all(db: &DatabaseOps, cursor: CursorId) -> Vec<RecordBatch> {
+    let mut batches = Vec::new();
+    loop {
+        let (page, more) = db.fetch(cursor, 2).await.unwrap();
+        batches.extend(page);
+        if !more {
+            return batches;
+        }
+    }
+}
+
+#[tokio::test]
+async fn test_update_rows() {
+    let temp_dir = TempDir::new().unwrap();
+    let db = create_db(&temp_dir).await;
+
+    let updated = db
+        .update_rows(&[("score", "score + 1")], "status = 'pending'")
+        .await
+        .unwrap();
+
+    assert_eq!(updated.rows_affected, 3);
+    let all = db.query("SELECT * FROM data").await.unwrap();
+    assert_eq!(
+        rows(&all),
+        vec![
+            (1, "pending".to_string(), 11),
+            (2, "done".to_string(), 20),
+            (3, "pending".to_string(), 31),
+            (4, "pending".to_string(), 41),
+        ]
+    );
+}
+
+#[tokio::test]
+async fn test_update_rows_rejects_unknown_column() {
+    let temp_dir = TempDir::new().unwrap();
+    let db = create_db(&temp_dir).await;
+
+    assert!(db.update_rows(&[("missing", "1")], "id = 1").await.is_err());
+    assert!(db.update_rows(&[], "id = 1").await.is_err());
+}
+
+#[tokio::test]
+async fn test_update_returning_yields_new_values() {
+    let temp_dir = TempDir::new().unwrap();
+    let db = create_db(&temp_dir).await;
+
+    // The predicate no longer matches once the status changes
+    let returning = db
+        .update_rows_returning(
+            &[("status", "'done'"), ("score", "score * 2")],
+            "status = 'pending' AND id > 1",
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(returning.count, 2);
+    let returned = fetch_all(&db, returning.cursor).await;
+    let expected = vec![(3, "done".to_string(), 60), (4, "done".to_string(), 80)];
+    assert_eq!(rows(&returned), expected);
+
+    // Returned rows keep the table's column types and match what was stored
+    assert_eq!(
+        returned[0]
+            .schema()
+            .field_with_name("score")
+            .unwrap()
+            .data_type(),
+        &DataType::Int32
+    );
+    let stored = db
+        .query("SELECT * FROM data WHERE id IN (3, 4)")
+        .await
+        .unwrap();
+    assert_eq!(rows(&stored), expected);
+}
+
+#[tokio::test]
+async fn test_update_returning_no_match() {
+    let temp_dir = TempDir::new().unwrap();
+    let db = create_db(&temp_dir).await;
+
+    let returning = db
+        .update_rows_returning(&[("score", "0")], "id > 100")
+        .await
+        .unwrap();
+
+    assert_eq!(returning.count, 0);
+    assert!(rows(&fetch_all(&db, returning.cursor).await).is_empty());
+}
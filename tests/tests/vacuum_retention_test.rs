@@ -0,0 +1,96 @@
+// VACUUM Retention Integration Tests
+// Tests the minimum retention VACUUM enforces, also through sessions and named
+// table handles, the force override, and the files, bytes and oldest retained
+// version it reports
+
+use arrow::array::{ArrayRef, Int32Array, RecordBatch};
+use arrow::datatypes::{DataType, Field, Schema, SchemaRef};
+use fsdb::DatabaseOps;
+use fsdb::Error;
+use std::sync::Arc;
+use std::time::Duration;
+use tempfile::TempDir;
+
+fn create_schema() -> SchemaRef {
+    Arc::new(Schema::new(vec![Field::new("id", DataType::Int32, false)]))
+}
+
+/// Three inserts compacted into one file: versions 0 to 4, with the three
+/// insert files removed by the last
+async fn create_db(path: &std::path::Path) -> DatabaseOps {
+    let db = DatabaseOps::create(path, create_schema())
+        .await
+        .unwrap()
+        .with_vacuum_min_retention(Duration::from_secs(24 * 3600));
+    for i in 0..3 {
+        let batch = RecordBatch::try_new(
+            create_schema(),
+            vec![Arc::new(Int32Array::from_iter_values(i * 10..i * 10 + 10)) as ArrayRef],
+        )
+        .unwrap();
+        db.insert(batch).await.unwrap();
+    }
+    db.optimize().await.unwrap();
+    db
+}
+
+#[tokio::test]
+async fn test_minimum_retention_enforced() {
+    let temp_dir = TempDir::new().unwrap();
+    let db = create_db(&temp_dir.path().join("db")).await;
+
+    match db.vacuum(1).await {
+        Err(Error::InvalidOperation(message)) => assert!(message.contains("24h")),
+        other => panic!("expected the retention to be refused, got {:?}", other),
+    }
+    assert!(db.vacuum_dry_run(0).await.is_err());
+    assert!(
+        db.vacuum_with_retention(Duration::ZERO, false)
+            .await
+            .is_err()
+    );
+
+    // Every removal is younger than the retention, so every version stays
+    let report = db.vacuum(24).await.unwrap();
+    assert_eq!(report.files_deleted, 0);
+    assert_eq!(report.oldest_retained_version, Some(0));
+}
+
+#[tokio::test]
+async fn test_force_overrides_minimum() {
+    let temp_dir = TempDir::new().unwrap();
+    let db = create_db(&temp_dir.path().join("db")).await;
+
+    let report = db
+        .vacuum_with_retention(Duration::ZERO, true)
+        .await
+        .unwrap();
+    assert_eq!(report.files_deleted, 3);
+    assert!(report.bytes_reclaimed > 0);
+    assert_eq!(report.oldest_retained_version, Some(4));
+
+    let rows = db.query_rows("SELECT COUNT(*) FROM data").await.unwrap();
+    assert_eq!(rows.rows()[0][0].to_string(), "30");
+}
+
+#[tokio::test]
+async fn test_minimum_applies_to_derived_handles() {
+    let temp_dir = TempDir::new().unwrap();
+    let db = DatabaseOps::create_with_auth(temp_dir.path().join("db"), create_schema(), true)
+        .await
+        .unwrap()
+        .with_vacuum_min_retention(Duration::from_secs(24 * 3600));
+    db.create_user("ops", "secret", &["admin"]).await.unwrap();
+    db.create_table("events", create_schema()).await.unwrap();
+
+    // A session or a named table's handle can't VACUUM below the minimum either
+    let session = db.login("ops", "secret").await.unwrap();
+    let events = db.table("events").await.unwrap();
+    for handle in [&session, &events] {
+        match handle.vacuum(0).await {
+            Err(Error::InvalidOperation(message)) => assert!(message.contains("24h")),
+            other => panic!("expected the retention to be refused, got {:?}", other),
+        }
+        assert!(handle.vacuum_dry_run(0).await.is_err());
+    }
+}
@@ -74,7 +74,10 @@ async fn test_vacuum_basic() -> Result<()> {
 
     // Run OPTIMIZE to compact files
     info!("  Running OPTIMIZE...");
-    db.optimize().await?;
+    let metrics = db.optimize().await?;
+    assert_eq!(metrics.num_files_removed, 10);
+    assert_eq!(metrics.num_files_added, 1);
+    assert!(metrics.bytes_removed > 0 && metrics.bytes_added > 0);
 
     let files_after_optimize = count_parquet_files(db_path)?;
     info!("  Parquet files after OPTIMIZE: {}", files_after_optimize);
@@ -85,7 +88,12 @@ async fn test_vacuum_basic() -> Result<()> {
 
     // Run VACUUM with 0 retention (delete all unreferenced files immediately)
     info!("  Running VACUUM with 0 hour retention...");
-    db.vacuum(0).await?;
+    let report = db.vacuum(0).await?;
+    assert_eq!(report.files_deleted, 10);
+    assert_eq!(
+        report.bytes_reclaimed, metrics.bytes_removed,
+        "Reclaimed bytes should match the compacted files"
+    );
 
     let files_after_vacuum = count_parquet_files(db_path)?;
     info!("  Parquet files after VACUUM: {}", files_after_vacuum);
@@ -284,3 +292,100 @@ async fn test_vacuum_dry_run() -> Result<()> {
 
     Ok(())
 }
+
+/// Test that VACUUM keeps files still read by an open cursor
+///
+/// The cursor is pinned to the version before OPTIMIZE, so the small files it
+/// scans must survive a 0-hour VACUUM until the cursor is closed.
+#[tokio::test]
+async fn test_vacuum_respects_open_cursor() -> Result<()> {
+    setup_tracing();
+
+    let db_path = "/tmp/fsdb_test_vacuum_open_cursor";
+    let _ = std::fs::remove_dir_all(db_path);
+
+    let schema = Arc::new(Schema::new(vec![
+        Field::new("id", DataType::Int32, false),
+        Field::new("value", DataType::Utf8, false),
+    ]));
+    let db = DatabaseOps::create(db_path, schema.clone()).await?;
+
+    for i in 0..10 {
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![
+                Arc::new(Int32Array::from(vec![i])) as ArrayRef,
+                Arc::new(StringArray::from(vec![format!("value_{}", i)])) as ArrayRef,
+            ],
+        )?;
+        db.insert(batch).await?;
+    }
+
+    // Pin the pre-OPTIMIZE version, then tombstone every file it reads
+    let cursor = db.open_cursor("SELECT id FROM data ORDER BY id").await?;
+    db.optimize().await?;
+    assert_eq!(count_parquet_files(db_path)?, 11);
+
+    info!("  Running VACUUM with an open cursor...");
+    db.vacuum(0).await?;
+    assert_eq!(
+        count_parquet_files(db_path)?,
+        11,
+        "Files read by the open cursor must not be vacuumed"
+    );
+
+    let (rows, has_more) = db.fetch(cursor, 100).await?;
+    let fetched: usize = rows.iter().map(|b| b.num_rows()).sum();
+    assert_eq!(fetched, 10, "Cursor should still read all rows");
+    assert!(!has_more);
+
+    // Once the cursor is closed the old files are fair game
+    assert!(db.close_cursor(cursor).await);
+    db.vacuum(0).await?;
+    assert_eq!(count_parquet_files(db_path)?, 1);
+
+    std::fs::remove_dir_all(db_path)?;
+
+    Ok(())
+}
+
+/// Test that VACUUM keeps the configured number of recent versions readable
+#[tokio::test]
+async fn test_vacuum_keep_versions() -> Result<()> {
+    setup_tracing();
+
+    let db_path = "/tmp/fsdb_test_vacuum_keep_versions";
+    let _ = std::fs::remove_dir_all(db_path);
+
+    let schema = Arc::new(Schema::new(vec![
+        Field::new("id", DataType::Int32, false),
+        Field::new("value", DataType::Utf8, false),
+    ]));
+    let db = DatabaseOps::create(db_path, schema.clone())
+        .await?
+        .with_vacuum_keep_versions(2);
+
+    for i in 0..5 {
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![
+                Arc::new(Int32Array::from(vec![i])) as ArrayRef,
+                Arc::new(StringArray::from(vec![format!("value_{}", i)])) as ArrayRef,
+            ],
+        )?;
+        db.insert(batch).await?;
+    }
+
+    // The version before OPTIMIZE is one of the two kept versions
+    db.optimize().await?;
+    db.vacuum(0).await?;
+    assert_eq!(
+        count_parquet_files(db_path)?,
+        6,
+        "Files of the previous version must be kept"
+    );
+
+    std::fs::remove_dir_all(db_path)?;
+
+    Ok(())
+}
@@ -6,6 +6,8 @@
 use arrow::array::{ArrayRef, Int32Array, RecordBatch, StringArray};
 use arrow::datatypes::{DataType, Field, Schema};
 use fsdb::DatabaseOps;
+use fsdb::batch_buffer::BatchBufferConfig;
+use std::collections::BTreeMap;
 use std::sync::Arc;
 use tempfile::TempDir;
 
@@ -152,3 +154,147 @@ async fn test_write_buffer_auto_flush_on_size() {
         "Should batch writes, not create transaction per row"
     );
 }
+
+/// Live data files per partition directory
+fn files_per_region(db_path: &std::path::Path) -> BTreeMap<String, usize> {
+    let mut counts = BTreeMap::new();
+    for file in fsdb::delta_lake::read_delta_log(db_path).unwrap().files {
+        let region = file.partition_values["region"].clone().unwrap_or_default();
+        *counts.entry(region).or_default() += 1;
+    }
+    counts
+}
+
+fn regional_row(schema: &Arc<Schema>, id: i32, region: &str) -> RecordBatch {
+    RecordBatch::try_new(
+        schema.clone(),
+        vec![
+            Arc::new(Int32Array::from(vec![id])) as ArrayRef,
+            Arc::new(StringArray::from(vec![region])) as ArrayRef,
+        ],
+    )
+    .unwrap()
+}
+
+#[tokio::test]
+async fn test_write_buffer_flushes_one_file_per_partition() {
+    let temp_dir = TempDir::new().unwrap();
+    let db_path = temp_dir.path().join("test_db");
+    let schema = Arc::new(Schema::new(vec![
+        Field::new("id", DataType::Int32, false),
+        Field::new("region", DataType::Utf8, false),
+    ]));
+    let db = DatabaseOps::create_partitioned(&db_path, schema.clone(), &["region"])
+        .await
+        .unwrap()
+        .with_write_buffer_config(BatchBufferConfig {
+            max_rows: 30,
+            max_partition_rows: None,
+        });
+
+    // Rows arrive one at a time, interleaved across partitions; the 30th
+    // triggers a flush of the whole buffer
+    let regions = ["eu", "us", "ap"];
+    for id in 0..30 {
+        db.insert_buffered(regional_row(&schema, id, regions[id as usize % 3]))
+            .await
+            .unwrap();
+    }
+    assert_eq!(db.buffered_rows().await, 0);
+
+    let expected: BTreeMap<String, usize> = regions.iter().map(|r| (r.to_string(), 1)).collect();
+    assert_eq!(files_per_region(&db_path), expected);
+}
+
+#[tokio::test]
+async fn test_write_buffer_flushes_full_partition_early() {
+    let temp_dir = TempDir::new().unwrap();
+    let db_path = temp_dir.path().join("test_db");
+    let schema = Arc::new(Schema::new(vec![
+        Field::new("id", DataType::Int32, false),
+        Field::new("region", DataType::Utf8, false),
+    ]));
+    let db = DatabaseOps::create_partitioned(&db_path, schema.clone(), &["region"])
+        .await
+        .unwrap()
+        .with_write_buffer_config(BatchBufferConfig {
+            max_rows: 1000,
+            max_partition_rows: Some(10),
+        });
+
+    db.insert_buffered(regional_row(&schema, 0, "us"))
+        .await
+        .unwrap();
+    for id in 1..=10 {
+        db.insert_buffered(regional_row(&schema, id, "eu"))
+            .await
+            .unwrap();
+    }
+
+    // "eu" filled up and was written on its own; "us" is still buffered
+    assert_eq!(db.buffered_rows().await, 1);
+    assert_eq!(
+        files_per_region(&db_path),
+        BTreeMap::from([("eu".to_string(), 1)])
+    );
+
+    db.flush_write_buffer().await.unwrap();
+    assert_eq!(files_per_region(&db_path).get("us"), Some(&1));
+}
+
+async fn ids(db: &DatabaseOps) -> Vec<i32> {
+    let results = db.query("SELECT id FROM data ORDER BY id").await.unwrap();
+    results
+        .iter()
+        .flat_map(|b| {
+            b.column(0)
+                .as_any()
+                .downcast_ref::<Int32Array>()
+                .unwrap()
+                .values()
+                .to_vec()
+        })
+        .collect()
+}
+
+#[tokio::test]
+async fn test_session_reads_its_buffered_writes() {
+    let temp_dir = TempDir::new().unwrap();
+    let db_path = temp_dir.path().join("test_db");
+    let schema = Arc::new(Schema::new(vec![
+        Field::new("id", DataType::Int32, false),
+        Field::new("region", DataType::Utf8, false),
+    ]));
+    let db = DatabaseOps::create(&db_path, schema.clone()).await.unwrap();
+    db.insert(regional_row(&schema, 1, "us")).await.unwrap();
+
+    // Inserted, then immediately queried within the session
+    db.insert_buffered(regional_row(&schema, 2, "eu"))
+        .await
+        .unwrap();
+    db.insert_buffered(regional_row(&schema, 3, "eu"))
+        .await
+        .unwrap();
+    assert_eq!(db.buffered_rows().await, 2);
+    assert_eq!(ids(&db).await, vec![1, 2, 3]);
+    let results = db
+        .query("SELECT COUNT(*) FROM data WHERE region = 'eu'")
+        .await
+        .unwrap();
+    let count = results[0]
+        .column(0)
+        .as_any()
+        .downcast_ref::<arrow::array::Int64Array>()
+        .unwrap();
+    assert_eq!(count.value(0), 2);
+
+    // Another session only sees what's committed
+    let other = DatabaseOps::open(&db_path).await.unwrap();
+    assert_eq!(ids(&other).await, vec![1]);
+
+    // A delete applies to committed and buffered rows alike
+    db.delete_rows_where("id IN (1, 2)").await.unwrap();
+    assert_eq!(ids(&db).await, vec![3]);
+    assert_eq!(db.buffered_rows().await, 0);
+    assert_eq!(ids(&other).await, vec![3]);
+}
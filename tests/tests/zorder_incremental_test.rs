@@ -0,0 +1,97 @@
+// Incremental Z-ORDER Integration Tests
+// Tests that zorder_incremental rewrites only files whose ranges overlap on
+// the Z-ORDER columns, leaves its own output alone on the next run, and that
+// columns stored with set_zorder_columns are validated and kept by OPTIMIZE
+
+use arrow::array::{ArrayRef, Int32Array, RecordBatch};
+use arrow::datatypes::{DataType, Field, Schema, SchemaRef};
+use fsdb::DatabaseOps;
+use fsdb::Error;
+use std::sync::Arc;
+use tempfile::TempDir;
+
+fn create_schema() -> SchemaRef {
+    Arc::new(Schema::new(vec![
+        Field::new("x", DataType::Int32, false),
+        Field::new("y", DataType::Int32, false),
+    ]))
+}
+
+/// Rows (i, i) for i in `range`
+fn batch(range: std::ops::Range<i32>) -> RecordBatch {
+    RecordBatch::try_new(
+        create_schema(),
+        vec![
+            Arc::new(Int32Array::from_iter_values(range.clone())) as ArrayRef,
+            Arc::new(Int32Array::from_iter_values(range)) as ArrayRef,
+        ],
+    )
+    .unwrap()
+}
+
+/// Every x, in order
+async fn xs(db: &DatabaseOps) -> Vec<String> {
+    let rows = db
+        .query_rows("SELECT x FROM data ORDER BY x")
+        .await
+        .unwrap();
+    rows.rows().iter().map(|row| row[0].to_string()).collect()
+}
+
+#[tokio::test]
+async fn test_only_overlapping_files_rewritten() {
+    let temp_dir = TempDir::new().unwrap();
+    let db = DatabaseOps::create(temp_dir.path().join("db"), create_schema())
+        .await
+        .unwrap();
+    // The first and third files overlap; the second is off on its own
+    for range in [0..10, 100..110, 5..15] {
+        db.insert(batch(range)).await.unwrap();
+    }
+    let before = xs(&db).await;
+
+    let metrics = db.zorder_incremental(&["x", "y"]).await.unwrap();
+    assert_eq!(metrics.num_files_removed, 2);
+    assert_eq!(metrics.total_files_skipped, 1);
+    assert_eq!(metrics.num_files_added, 1);
+    assert_eq!(xs(&db).await, before);
+
+    // The rewritten file no longer overlaps anything
+    let version = db.get_delta_table().await.unwrap().version();
+    let metrics = db.zorder_incremental(&["x", "y"]).await.unwrap();
+    assert_eq!((metrics.num_files_removed, metrics.num_files_added), (0, 0));
+    assert_eq!(db.get_delta_table().await.unwrap().version(), version);
+
+    assert!(db.zorder_incremental(&[]).await.is_err());
+    assert!(db.zorder_incremental(&["missing"]).await.is_err());
+}
+
+#[tokio::test]
+async fn test_stored_zorder_columns() {
+    let temp_dir = TempDir::new().unwrap();
+    let db = DatabaseOps::create(temp_dir.path().join("db"), create_schema())
+        .await
+        .unwrap();
+    assert!(db.zorder_columns().is_empty());
+
+    match db.set_zorder_columns(&["x", "missing"]).await {
+        Err(Error::InvalidOperation(message)) => assert!(message.contains("missing")),
+        other => panic!(
+            "expected the unknown column to be rejected, got {:?}",
+            other
+        ),
+    }
+    db.set_zorder_columns(&["x", "y"]).await.unwrap();
+    assert_eq!(db.zorder_columns(), vec!["x", "y"]);
+
+    for range in [0..10, 5..15, 10..20] {
+        db.insert(batch(range)).await.unwrap();
+    }
+    let before = xs(&db).await;
+    db.optimize().await.unwrap();
+    assert_eq!(xs(&db).await, before);
+    assert_eq!(db.zorder_columns(), vec!["x", "y"]);
+
+    db.set_zorder_columns(&[]).await.unwrap();
+    assert!(db.zorder_columns().is_empty());
+}